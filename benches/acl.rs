@@ -0,0 +1,44 @@
+//! Cost of the ACL every repo is created with on every request
+#[macro_use]
+extern crate criterion;
+extern crate billing_lib;
+extern crate failure;
+extern crate stq_types;
+
+use criterion::Criterion;
+use failure::Error as FailureError;
+use stq_types::{BillingRole, UserId};
+
+use billing_lib::models::authorization::{Action, Resource, Scope};
+use billing_lib::repos::legacy_acl::{Acl, CheckScope};
+use billing_lib::repos::ApplicationAcl;
+
+struct OwnedByNobody;
+
+impl CheckScope<Scope, ()> for OwnedByNobody {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&()>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
+
+fn create_acl(c: &mut Criterion) {
+    c.bench_function("create acl", |b| {
+        b.iter(|| ApplicationAcl::new(vec![BillingRole::User, BillingRole::StoreManager], UserId(2)))
+    });
+}
+
+fn create_acl_and_check(c: &mut Criterion) {
+    c.bench_function("create acl and check permission", |b| {
+        b.iter(|| {
+            let acl = ApplicationAcl::new(vec![BillingRole::User, BillingRole::StoreManager], UserId(2));
+            let acl: &Acl<Resource, Action, Scope, FailureError, ()> = &acl;
+            acl.allows(Resource::Fee, Action::Read, &OwnedByNobody, None).unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, create_acl, create_acl_and_check);
+criterion_main!(benches);
@@ -0,0 +1,30 @@
+//! Embeds versions of the migrations the binary is built with, so that it can compare them
+//! with the migrations applied to the database on startup, see `models::schema_migration`
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+
+    // Versions are derived the way Diesel derives them: the name of the migration directory up to
+    // the first underscore, without dashes
+    let mut versions = fs::read_dir("migrations")
+        .expect("Failed to read migrations directory")
+        .map(|entry| entry.expect("Failed to read migrations directory"))
+        .filter(|entry| entry.file_type().map(|file_type| file_type.is_dir()).unwrap_or(false))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.'))
+        .filter_map(|name| name.split('_').next().map(|version| version.replace('-', "")))
+        .collect::<Vec<_>>();
+    versions.sort();
+
+    let out_path = Path::new(&env::var("OUT_DIR").expect("OUT_DIR is not set")).join("migration_versions.rs");
+    let mut out = File::create(out_path).expect("Failed to create migration versions file");
+    writeln!(out, "&[").expect("Failed to write migration versions file");
+    for version in versions {
+        writeln!(out, "    {:?},", version).expect("Failed to write migration versions file");
+    }
+    writeln!(out, "]").expect("Failed to write migration versions file");
+}
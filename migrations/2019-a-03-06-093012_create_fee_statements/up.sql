@@ -0,0 +1,16 @@
+CREATE TABLE fee_statements (
+    id SERIAL PRIMARY KEY,
+    store_id INTEGER NOT NULL,
+    currency VARCHAR NOT NULL,
+    period_start TIMESTAMP NOT NULL,
+    period_end TIMESTAMP NOT NULL,
+    fees_amount NUMERIC NOT NULL,
+    adjustments_amount NUMERIC NOT NULL,
+    taxes_amount NUMERIC NOT NULL,
+    total_amount NUMERIC NOT NULL,
+    lines JSONB NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    UNIQUE (store_id, currency, period_start)
+);
+
+CREATE INDEX fee_statements_store_id_idx ON fee_statements (store_id);
@@ -0,0 +1,4 @@
+DROP INDEX event_store_status_id_idx;
+
+ALTER TABLE event_store DROP COLUMN lease_expires_at;
+ALTER TABLE event_store DROP COLUMN locked_by;
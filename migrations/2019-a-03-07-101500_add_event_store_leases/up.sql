@@ -0,0 +1,4 @@
+ALTER TABLE event_store ADD COLUMN locked_by TEXT;
+ALTER TABLE event_store ADD COLUMN lease_expires_at TIMESTAMP;
+
+CREATE INDEX event_store_status_id_idx ON event_store (status, id);
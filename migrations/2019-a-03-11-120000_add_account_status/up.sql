@@ -0,0 +1 @@
+ALTER TABLE accounts ADD COLUMN status VARCHAR NOT NULL DEFAULT 'active';
@@ -0,0 +1,16 @@
+CREATE TABLE audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    actor_user_id INTEGER,
+    action VARCHAR NOT NULL,
+    resource_type VARCHAR NOT NULL,
+    resource_id VARCHAR NOT NULL,
+    before JSONB,
+    after JSONB,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX audit_log_actor_user_id_idx ON audit_log (actor_user_id);
+CREATE INDEX audit_log_resource_idx ON audit_log (resource_type, resource_id);
+CREATE INDEX audit_log_created_at_idx ON audit_log (created_at);
+CREATE INDEX audit_log_snapshots_fts_idx ON audit_log
+    USING GIN (to_tsvector('simple', coalesce(before::text, '') || ' ' || coalesce(after::text, '')));
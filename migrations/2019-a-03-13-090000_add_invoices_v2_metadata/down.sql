@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS invoices_v2_metadata_order_source_idx;
+DROP INDEX IF EXISTS invoices_v2_metadata_campaign_id_idx;
+
+ALTER TABLE invoices_v2 DROP COLUMN metadata;
@@ -0,0 +1,4 @@
+ALTER TABLE invoices_v2 ADD COLUMN metadata JSONB;
+
+CREATE INDEX invoices_v2_metadata_campaign_id_idx ON invoices_v2 ((metadata->>'campaign_id'));
+CREATE INDEX invoices_v2_metadata_order_source_idx ON invoices_v2 ((metadata->>'order_source'));
@@ -0,0 +1,13 @@
+CREATE TABLE store_webhooks (
+    id SERIAL PRIMARY KEY,
+    store_id INTEGER NOT NULL,
+    url VARCHAR NOT NULL,
+    secret VARCHAR NOT NULL,
+    event_types JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX store_webhooks_store_id_idx ON store_webhooks (store_id);
+
+SELECT diesel_manage_updated_at('store_webhooks');
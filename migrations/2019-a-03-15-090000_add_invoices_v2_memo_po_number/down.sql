@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS invoices_v2_po_number_idx;
+
+ALTER TABLE invoices_v2 DROP COLUMN po_number;
+ALTER TABLE invoices_v2 DROP COLUMN memo;
@@ -0,0 +1,4 @@
+ALTER TABLE invoices_v2 ADD COLUMN memo TEXT;
+ALTER TABLE invoices_v2 ADD COLUMN po_number VARCHAR;
+
+CREATE INDEX invoices_v2_po_number_idx ON invoices_v2 (po_number);
@@ -0,0 +1,12 @@
+CREATE TABLE payout_instructions (
+    id SERIAL PRIMARY KEY,
+    store_id INTEGER NOT NULL,
+    currency VARCHAR NOT NULL,
+    total_amount NUMERIC NOT NULL,
+    order_ids JSONB NOT NULL,
+    reference_code VARCHAR NOT NULL UNIQUE,
+    document JSONB NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX payout_instructions_store_id_idx ON payout_instructions (store_id);
@@ -0,0 +1 @@
+ALTER TABLE invoices_v2 DROP COLUMN price_reserved;
@@ -0,0 +1,7 @@
+ALTER TABLE invoices_v2 ADD COLUMN price_reserved TIMESTAMP;
+
+-- existing invoices get the deadline of the default payment expiry settings
+UPDATE invoices_v2 SET price_reserved = created_at + INTERVAL '60 minutes' WHERE buyer_currency IN ('eur', 'usd', 'rub');
+UPDATE invoices_v2 SET price_reserved = created_at + INTERVAL '4320 minutes' WHERE price_reserved IS NULL;
+
+ALTER TABLE invoices_v2 ALTER COLUMN price_reserved SET NOT NULL;
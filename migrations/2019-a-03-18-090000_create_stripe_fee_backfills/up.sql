@@ -0,0 +1,14 @@
+CREATE TABLE stripe_fee_backfills (
+    id SERIAL PRIMARY KEY,
+    status VARCHAR NOT NULL,
+    order_ids JSONB NOT NULL,
+    total_orders INTEGER NOT NULL,
+    processed_orders INTEGER NOT NULL DEFAULT 0,
+    updated_orders INTEGER NOT NULL DEFAULT 0,
+    failed_orders INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    finished_at TIMESTAMP
+);
+
+SELECT diesel_manage_updated_at('stripe_fee_backfills');
@@ -0,0 +1 @@
+ALTER TABLE store_subscription DROP COLUMN trial_end_date;
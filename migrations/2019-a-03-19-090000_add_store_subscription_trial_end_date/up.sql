@@ -0,0 +1 @@
+ALTER TABLE store_subscription ADD COLUMN trial_end_date TIMESTAMP;
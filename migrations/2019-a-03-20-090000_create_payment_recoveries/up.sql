@@ -0,0 +1,20 @@
+CREATE TABLE payment_recoveries (
+    id UUID PRIMARY KEY,
+    invoice_id UUID NOT NULL REFERENCES invoices_v2 (id),
+    buyer_user_id INTEGER NOT NULL,
+    status VARCHAR NOT NULL,
+    failed_payment_intent_id VARCHAR NOT NULL,
+    failure_message VARCHAR,
+    failures_count INTEGER NOT NULL DEFAULT 1,
+    retry_payment_intent_id VARCHAR,
+    notified_at TIMESTAMP,
+    retried_at TIMESTAMP,
+    closed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE UNIQUE INDEX payment_recoveries_invoice_id_idx ON payment_recoveries (invoice_id);
+CREATE INDEX payment_recoveries_created_at_idx ON payment_recoveries (created_at);
+
+SELECT diesel_manage_updated_at('payment_recoveries');
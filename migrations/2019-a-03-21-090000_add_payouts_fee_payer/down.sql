@@ -0,0 +1,3 @@
+ALTER TABLE payouts
+    DROP COLUMN fee_payer,
+    DROP COLUMN carried_fee;
@@ -0,0 +1,3 @@
+ALTER TABLE payouts
+    ADD COLUMN fee_payer text NOT NULL DEFAULT 'payout',
+    ADD COLUMN carried_fee numeric NOT NULL DEFAULT 0;
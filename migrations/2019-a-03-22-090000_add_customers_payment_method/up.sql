@@ -0,0 +1 @@
+ALTER TABLE customers ADD COLUMN payment_method_id VARCHAR NULL;
@@ -0,0 +1,14 @@
+CREATE TABLE fee_adjustments (
+    id SERIAL PRIMARY KEY,
+    fee_id INTEGER NOT NULL REFERENCES fees (id),
+    order_id UUID NOT NULL REFERENCES orders (id),
+    currency VARCHAR NOT NULL,
+    refund_amount NUMERIC NOT NULL,
+    seller_amount NUMERIC NOT NULL,
+    fee_amount NUMERIC NOT NULL,
+    fee_charge_id VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX fee_adjustments_fee_id_idx ON fee_adjustments (fee_id);
+CREATE INDEX fee_adjustments_created_at_idx ON fee_adjustments (created_at);
@@ -0,0 +1,7 @@
+CREATE TABLE order_capture_approvals (
+    order_id UUID PRIMARY KEY REFERENCES orders (id),
+    payment_intent_id VARCHAR NOT NULL REFERENCES payment_intent (id),
+    approved_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX order_capture_approvals_payment_intent_id_idx ON order_capture_approvals (payment_intent_id);
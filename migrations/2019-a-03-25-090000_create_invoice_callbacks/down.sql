@@ -0,0 +1,2 @@
+DROP TABLE invoice_callback_deliveries;
+DROP TABLE invoice_callbacks;
@@ -0,0 +1,20 @@
+CREATE TABLE invoice_callbacks (
+    id SERIAL PRIMARY KEY,
+    invoice_id UUID NOT NULL REFERENCES invoices_v2 (id),
+    url VARCHAR NOT NULL,
+    secret VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE UNIQUE INDEX invoice_callbacks_invoice_id_idx ON invoice_callbacks (invoice_id);
+
+CREATE TABLE invoice_callback_deliveries (
+    id SERIAL PRIMARY KEY,
+    invoice_callback_id INTEGER NOT NULL REFERENCES invoice_callbacks (id),
+    notification_id UUID NOT NULL,
+    event_type VARCHAR NOT NULL,
+    error VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX invoice_callback_deliveries_invoice_callback_id_idx ON invoice_callback_deliveries (invoice_callback_id);
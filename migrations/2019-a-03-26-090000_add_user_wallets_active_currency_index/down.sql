@@ -0,0 +1 @@
+DROP INDEX user_wallets_active_currency_idx;
@@ -0,0 +1,10 @@
+-- keep the most recently added wallet active where a user has several active wallets of a currency
+UPDATE user_wallets SET is_active = false
+WHERE is_active AND id NOT IN (
+    SELECT DISTINCT ON (user_id, currency) id
+    FROM user_wallets
+    WHERE is_active
+    ORDER BY user_id, currency, created_at DESC
+);
+
+CREATE UNIQUE INDEX user_wallets_active_currency_idx ON user_wallets (user_id, currency) WHERE is_active;
@@ -0,0 +1,2 @@
+ALTER TABLE subscription_payment DROP COLUMN unit_price;
+ALTER TABLE subscription_payment DROP COLUMN quantity;
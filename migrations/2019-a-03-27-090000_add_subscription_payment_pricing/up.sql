@@ -0,0 +1,2 @@
+ALTER TABLE subscription_payment ADD COLUMN quantity INTEGER;
+ALTER TABLE subscription_payment ADD COLUMN unit_price NUMERIC;
@@ -0,0 +1,15 @@
+CREATE TABLE wallet_verifications (
+    id UUID PRIMARY KEY,
+    user_wallet_id UUID NOT NULL REFERENCES user_wallets (id),
+    user_id INTEGER NOT NULL,
+    amount NUMERIC NOT NULL,
+    status VARCHAR NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    confirmed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX wallet_verifications_user_wallet_id_idx ON wallet_verifications (user_wallet_id);
+
+SELECT diesel_manage_updated_at('wallet_verifications');
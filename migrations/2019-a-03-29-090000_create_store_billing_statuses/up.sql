@@ -0,0 +1,14 @@
+CREATE TABLE store_billing_statuses (
+    store_id INTEGER PRIMARY KEY,
+    state VARCHAR NOT NULL,
+    suspension_reason VARCHAR,
+    overdue_fees_count INTEGER NOT NULL DEFAULT 0,
+    unpaid_since TIMESTAMP,
+    overridden_by INTEGER,
+    override_until TIMESTAMP,
+    evaluated_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+SELECT diesel_manage_updated_at('store_billing_statuses');
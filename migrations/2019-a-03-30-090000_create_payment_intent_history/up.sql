@@ -0,0 +1,9 @@
+CREATE TABLE payment_intent_history (
+    id BIGSERIAL PRIMARY KEY,
+    payment_intent_id VARCHAR NOT NULL REFERENCES payment_intent (id) ON DELETE CASCADE,
+    status VARCHAR NOT NULL,
+    source VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX payment_intent_history_payment_intent_id_idx ON payment_intent_history (payment_intent_id, created_at);
@@ -0,0 +1,3 @@
+DROP INDEX event_store_payload_type_status_id_idx;
+
+ALTER TABLE event_store DROP COLUMN payload_type;
@@ -0,0 +1,12 @@
+ALTER TABLE event_store ADD COLUMN payload_type TEXT;
+
+-- Payloads are externally tagged: unit variants are stored as a string, the others as an object with a single key
+UPDATE event_store
+SET payload_type = CASE
+    WHEN jsonb_typeof(event->'payload') = 'string' THEN event->>'payload'
+    ELSE (SELECT key FROM jsonb_object_keys(event->'payload') AS key LIMIT 1)
+END;
+
+ALTER TABLE event_store ALTER COLUMN payload_type SET NOT NULL;
+
+CREATE INDEX event_store_payload_type_status_id_idx ON event_store (payload_type, status, id);
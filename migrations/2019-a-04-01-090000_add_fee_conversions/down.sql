@@ -0,0 +1,3 @@
+ALTER TABLE fees DROP COLUMN converted_at;
+ALTER TABLE fees DROP COLUMN currency_exchange_id;
+ALTER TABLE fees DROP COLUMN exchange_rate;
@@ -0,0 +1,3 @@
+ALTER TABLE fees ADD COLUMN exchange_rate DOUBLE PRECISION;
+ALTER TABLE fees ADD COLUMN currency_exchange_id UUID;
+ALTER TABLE fees ADD COLUMN converted_at TIMESTAMP;
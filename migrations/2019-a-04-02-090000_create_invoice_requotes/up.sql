@@ -0,0 +1,5 @@
+CREATE TABLE invoice_requotes (
+    invoice_id UUID PRIMARY KEY REFERENCES invoices_v2 (id) ON DELETE CASCADE,
+    original_invoice_id UUID NOT NULL UNIQUE REFERENCES invoices_v2 (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
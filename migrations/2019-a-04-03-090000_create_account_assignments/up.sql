@@ -0,0 +1,12 @@
+CREATE TABLE account_assignments (
+    id BIGSERIAL PRIMARY KEY,
+    account_id UUID NOT NULL REFERENCES accounts (id) ON DELETE CASCADE,
+    invoice_id UUID NOT NULL REFERENCES invoices_v2 (id) ON DELETE CASCADE,
+    assigned_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    released_at TIMESTAMP
+);
+
+CREATE INDEX account_assignments_account_id_assigned_at_idx ON account_assignments (account_id, assigned_at);
+
+INSERT INTO account_assignments (account_id, invoice_id, assigned_at)
+SELECT account_id, id, created_at FROM invoices_v2 WHERE account_id IS NOT NULL;
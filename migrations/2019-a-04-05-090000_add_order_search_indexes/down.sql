@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS invoices_v2_awaiting_payment_idx;
+DROP INDEX IF EXISTS fee_adjustments_order_id_idx;
+DROP INDEX IF EXISTS orders_store_id_created_at_idx;
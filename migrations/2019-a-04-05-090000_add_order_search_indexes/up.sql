@@ -0,0 +1,3 @@
+CREATE INDEX orders_store_id_created_at_idx ON orders (store_id, created_at DESC);
+CREATE INDEX fee_adjustments_order_id_idx ON fee_adjustments (order_id);
+CREATE INDEX invoices_v2_awaiting_payment_idx ON invoices_v2 (id) WHERE paid_at IS NULL;
@@ -0,0 +1 @@
+DROP INDEX IF EXISTS account_assignments_invoice_id_idx;
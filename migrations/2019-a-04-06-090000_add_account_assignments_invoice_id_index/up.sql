@@ -0,0 +1 @@
+CREATE INDEX account_assignments_invoice_id_idx ON account_assignments (invoice_id);
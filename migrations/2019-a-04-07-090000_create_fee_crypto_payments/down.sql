@@ -0,0 +1,2 @@
+DROP TABLE fee_crypto_payment_transactions;
+DROP TABLE fee_crypto_payments;
@@ -0,0 +1,28 @@
+CREATE TABLE fee_crypto_payments (
+    id UUID PRIMARY KEY,
+    fee_id INTEGER NOT NULL REFERENCES fees (id),
+    account_id UUID NOT NULL REFERENCES accounts (id),
+    currency VARCHAR NOT NULL,
+    amount NUMERIC NOT NULL,
+    amount_received NUMERIC NOT NULL DEFAULT 0,
+    status VARCHAR NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    closed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE UNIQUE INDEX fee_crypto_payments_pending_fee_id_idx ON fee_crypto_payments (fee_id) WHERE status = 'pending';
+CREATE UNIQUE INDEX fee_crypto_payments_pending_account_id_idx ON fee_crypto_payments (account_id) WHERE status = 'pending';
+CREATE INDEX fee_crypto_payments_account_id_closed_at_idx ON fee_crypto_payments (account_id, closed_at);
+
+SELECT diesel_manage_updated_at('fee_crypto_payments');
+
+CREATE TABLE fee_crypto_payment_transactions (
+    transaction_id UUID PRIMARY KEY,
+    fee_crypto_payment_id UUID NOT NULL REFERENCES fee_crypto_payments (id),
+    amount NUMERIC NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX fee_crypto_payment_transactions_payment_id_idx ON fee_crypto_payment_transactions (fee_crypto_payment_id);
@@ -0,0 +1,2 @@
+DROP INDEX invoices_v2_cashback_paid_at_idx;
+DROP TABLE cashback_liability_snapshots;
@@ -0,0 +1,11 @@
+CREATE TABLE cashback_liability_snapshots (
+    id SERIAL PRIMARY KEY,
+    currency VARCHAR NOT NULL,
+    as_of TIMESTAMP NOT NULL,
+    amount NUMERIC NOT NULL,
+    invoices_count BIGINT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    UNIQUE (currency, as_of)
+);
+
+CREATE INDEX invoices_v2_cashback_paid_at_idx ON invoices_v2 (paid_at) WHERE final_cashback_amount > 0;
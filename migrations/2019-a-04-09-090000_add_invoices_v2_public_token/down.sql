@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS invoices_v2_public_token_idx;
+
+ALTER TABLE invoices_v2 DROP COLUMN public_token;
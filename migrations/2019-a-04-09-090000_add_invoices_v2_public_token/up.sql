@@ -0,0 +1,5 @@
+ALTER TABLE invoices_v2 ADD COLUMN public_token VARCHAR;
+UPDATE invoices_v2 SET public_token = md5(id::text || random()::text || clock_timestamp()::text);
+ALTER TABLE invoices_v2 ALTER COLUMN public_token SET NOT NULL;
+
+CREATE UNIQUE INDEX invoices_v2_public_token_idx ON invoices_v2 (public_token);
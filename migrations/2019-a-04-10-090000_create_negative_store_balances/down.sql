@@ -0,0 +1 @@
+DROP TABLE negative_store_balances;
@@ -0,0 +1,14 @@
+CREATE TABLE negative_store_balances (
+    id SERIAL PRIMARY KEY,
+    store_id INTEGER NOT NULL,
+    currency VARCHAR NOT NULL,
+    amount NUMERIC NOT NULL,
+    detected_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    notified_at TIMESTAMP,
+    resolved_at TIMESTAMP
+);
+
+CREATE UNIQUE INDEX negative_store_balances_open_idx ON negative_store_balances (store_id, currency) WHERE resolved_at IS NULL;
+
+SELECT diesel_manage_updated_at('negative_store_balances');
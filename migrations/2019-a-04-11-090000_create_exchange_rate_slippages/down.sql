@@ -0,0 +1,2 @@
+DROP TABLE exchange_rate_slippage_metrics;
+DROP TABLE order_rate_slippages;
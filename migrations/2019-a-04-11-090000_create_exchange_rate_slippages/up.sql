@@ -0,0 +1,27 @@
+CREATE TABLE order_rate_slippages (
+    id SERIAL PRIMARY KEY,
+    order_id UUID NOT NULL UNIQUE,
+    invoice_id UUID NOT NULL,
+    seller_currency VARCHAR NOT NULL,
+    buyer_currency VARCHAR NOT NULL,
+    initial_rate NUMERIC NOT NULL,
+    final_rate NUMERIC NOT NULL,
+    rate_refreshes INTEGER NOT NULL,
+    initial_price NUMERIC NOT NULL,
+    final_price NUMERIC NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE TABLE exchange_rate_slippage_metrics (
+    seller_currency VARCHAR NOT NULL,
+    buyer_currency VARCHAR NOT NULL,
+    orders_count BIGINT NOT NULL,
+    refreshed_orders_count BIGINT NOT NULL,
+    initial_price_total NUMERIC NOT NULL,
+    final_price_total NUMERIC NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    PRIMARY KEY (seller_currency, buyer_currency)
+);
+
+SELECT diesel_manage_updated_at('exchange_rate_slippage_metrics');
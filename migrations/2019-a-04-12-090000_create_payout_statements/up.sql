@@ -0,0 +1,10 @@
+CREATE TABLE payout_statements (
+    id SERIAL PRIMARY KEY,
+    payout_id UUID NOT NULL UNIQUE REFERENCES payouts (id),
+    user_id INTEGER NOT NULL,
+    currency VARCHAR NOT NULL,
+    gross_amount NUMERIC NOT NULL,
+    net_amount NUMERIC NOT NULL,
+    lines JSONB NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
@@ -0,0 +1,14 @@
+CREATE TABLE billing_info_changes (
+    id SERIAL PRIMARY KEY,
+    store_id INTEGER NOT NULL,
+    payload VARCHAR NOT NULL,
+    status VARCHAR NOT NULL,
+    requested_by INTEGER NOT NULL,
+    reviewed_by INTEGER,
+    rejection_reason VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    reviewed_at TIMESTAMP
+);
+
+CREATE INDEX billing_info_changes_store_id_idx ON billing_info_changes (store_id);
+CREATE UNIQUE INDEX billing_info_changes_pending_idx ON billing_info_changes (store_id) WHERE status = 'pending';
@@ -0,0 +1,12 @@
+CREATE TABLE payment_attempts (
+    id BIGSERIAL PRIMARY KEY,
+    invoice_id UUID NOT NULL REFERENCES invoices_v2 (id) ON DELETE CASCADE,
+    payment_intent_id VARCHAR NOT NULL REFERENCES payment_intent (id) ON DELETE CASCADE,
+    status VARCHAR NOT NULL,
+    decline_code VARCHAR,
+    error_code VARCHAR,
+    payment_method_fingerprint VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX payment_attempts_invoice_id_idx ON payment_attempts (invoice_id, created_at);
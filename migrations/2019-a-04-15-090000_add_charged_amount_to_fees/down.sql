@@ -0,0 +1,3 @@
+ALTER TABLE fees DROP COLUMN charge_exchange_rate;
+ALTER TABLE fees DROP COLUMN charged_amount;
+ALTER TABLE fees DROP COLUMN charged_currency;
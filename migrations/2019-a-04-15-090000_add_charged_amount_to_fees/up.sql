@@ -0,0 +1,3 @@
+ALTER TABLE fees ADD COLUMN charged_currency VARCHAR;
+ALTER TABLE fees ADD COLUMN charged_amount NUMERIC;
+ALTER TABLE fees ADD COLUMN charge_exchange_rate DOUBLE PRECISION;
@@ -0,0 +1,8 @@
+CREATE TABLE account_pool_claims (
+    id BIGSERIAL PRIMARY KEY,
+    currency VARCHAR NOT NULL,
+    exhausted BOOLEAN NOT NULL,
+    claimed_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX account_pool_claims_claimed_at_idx ON account_pool_claims (claimed_at);
@@ -0,0 +1 @@
+ALTER TABLE payment_intent DROP COLUMN status_event_created_at;
@@ -0,0 +1 @@
+ALTER TABLE payment_intent ADD COLUMN status_event_created_at TIMESTAMP;
@@ -0,0 +1,15 @@
+CREATE TABLE data_retention_subjects (
+    id SERIAL PRIMARY KEY,
+    subject_type VARCHAR NOT NULL,
+    subject_id INTEGER NOT NULL,
+    closed_at TIMESTAMP,
+    legal_hold BOOLEAN NOT NULL DEFAULT FALSE,
+    legal_hold_reason VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE UNIQUE INDEX data_retention_subjects_subject_idx ON data_retention_subjects (subject_type, subject_id);
+CREATE INDEX data_retention_subjects_closed_at_idx ON data_retention_subjects (closed_at) WHERE NOT legal_hold;
+
+SELECT diesel_manage_updated_at('data_retention_subjects');
@@ -0,0 +1 @@
+ALTER TABLE payout_instructions DROP COLUMN stripe_fee;
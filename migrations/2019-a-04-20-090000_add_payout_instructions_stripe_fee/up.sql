@@ -0,0 +1 @@
+ALTER TABLE payout_instructions ADD COLUMN stripe_fee NUMERIC NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+DROP TABLE invoice_snapshots;
+DROP FUNCTION reject_invoice_snapshot_changes();
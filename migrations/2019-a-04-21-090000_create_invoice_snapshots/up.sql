@@ -0,0 +1,17 @@
+CREATE TABLE invoice_snapshots (
+    id SERIAL PRIMARY KEY,
+    invoice_id UUID NOT NULL UNIQUE REFERENCES invoices_v2 (id),
+    dump JSONB NOT NULL,
+    paid_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+-- snapshots are the record of what was paid and are never changed
+CREATE OR REPLACE FUNCTION reject_invoice_snapshot_changes() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'invoice snapshots are immutable';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER invoice_snapshots_immutable BEFORE UPDATE OR DELETE ON invoice_snapshots
+    FOR EACH ROW EXECUTE PROCEDURE reject_invoice_snapshot_changes();
@@ -0,0 +1,14 @@
+CREATE TABLE fee_dunnings (
+    store_id INTEGER PRIMARY KEY,
+    status VARCHAR NOT NULL,
+    steps_taken INTEGER NOT NULL DEFAULT 0,
+    last_action VARCHAR,
+    started_at TIMESTAMP NOT NULL,
+    last_step_at TIMESTAMP,
+    next_step_at TIMESTAMP,
+    resolved_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+SELECT diesel_manage_updated_at('fee_dunnings');
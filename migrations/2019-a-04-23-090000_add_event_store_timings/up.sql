@@ -0,0 +1 @@
+ALTER TABLE event_store ADD COLUMN timings JSONB;
@@ -0,0 +1,3 @@
+DROP INDEX order_exchange_rates_active_order_id_idx;
+
+DROP INDEX order_exchange_rates_order_id_id_idx;
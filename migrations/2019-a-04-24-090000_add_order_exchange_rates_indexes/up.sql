@@ -0,0 +1,3 @@
+CREATE INDEX order_exchange_rates_order_id_id_idx ON order_exchange_rates (order_id, id DESC);
+
+CREATE INDEX order_exchange_rates_active_order_id_idx ON order_exchange_rates (order_id) WHERE status = 'active';
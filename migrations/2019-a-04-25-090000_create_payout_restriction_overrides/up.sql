@@ -0,0 +1,9 @@
+CREATE TABLE payout_restriction_overrides (
+    store_id INTEGER PRIMARY KEY,
+    reason VARCHAR NOT NULL,
+    granted_by INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+SELECT diesel_manage_updated_at('payout_restriction_overrides');
@@ -0,0 +1,5 @@
+DROP INDEX invoices_v2_paid_buyer_user_id_idx;
+DROP INDEX invoices_v2_paid_updated_at_idx;
+DROP INDEX customers_updated_at_idx;
+
+DROP TABLE customer_exports;
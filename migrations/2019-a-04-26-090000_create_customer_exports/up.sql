@@ -0,0 +1,13 @@
+CREATE TABLE customer_exports (
+    id SERIAL PRIMARY KEY,
+    incremental BOOLEAN NOT NULL,
+    updated_after TIMESTAMP,
+    updated_until TIMESTAMP NOT NULL,
+    object_key VARCHAR NOT NULL,
+    records_count INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX customers_updated_at_idx ON customers (updated_at);
+CREATE INDEX invoices_v2_paid_updated_at_idx ON invoices_v2 (updated_at) WHERE paid_at IS NOT NULL;
+CREATE INDEX invoices_v2_paid_buyer_user_id_idx ON invoices_v2 (buyer_user_id) WHERE paid_at IS NOT NULL;
@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS payouts_order_set_key_idx;
+
+DROP INDEX IF EXISTS payouts_user_id_request_id_idx;
+
+ALTER TABLE payouts
+    DROP COLUMN request_id,
+    DROP COLUMN order_set_key;
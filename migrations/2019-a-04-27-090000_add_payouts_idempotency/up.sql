@@ -0,0 +1,7 @@
+ALTER TABLE payouts
+    ADD COLUMN request_id uuid NULL,
+    ADD COLUMN order_set_key text NULL;
+
+CREATE UNIQUE INDEX payouts_user_id_request_id_idx ON payouts (user_id, request_id);
+
+CREATE UNIQUE INDEX payouts_order_set_key_idx ON payouts (order_set_key);
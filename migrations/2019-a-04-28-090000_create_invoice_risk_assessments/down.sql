@@ -0,0 +1,3 @@
+DROP INDEX invoices_v2_buyer_user_id_idx;
+
+DROP TABLE invoice_risk_assessments;
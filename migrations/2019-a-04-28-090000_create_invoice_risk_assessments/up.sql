@@ -0,0 +1,16 @@
+CREATE TABLE invoice_risk_assessments (
+    invoice_id UUID PRIMARY KEY,
+    buyer_user_id INTEGER NOT NULL,
+    buyer_ip VARCHAR,
+    score INTEGER NOT NULL,
+    decision VARCHAR NOT NULL,
+    reasons TEXT[] NOT NULL DEFAULT '{}',
+    review_status VARCHAR,
+    reviewed_by INTEGER,
+    reviewed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX invoice_risk_assessments_buyer_ip_idx ON invoice_risk_assessments (buyer_ip, created_at);
+CREATE INDEX invoice_risk_assessments_pending_review_idx ON invoice_risk_assessments (created_at) WHERE review_status = 'pending';
+CREATE INDEX invoices_v2_buyer_user_id_idx ON invoices_v2 (buyer_user_id);
@@ -0,0 +1,17 @@
+CREATE TABLE store_offboardings (
+    store_id INTEGER PRIMARY KEY,
+    status VARCHAR NOT NULL,
+    requested_by INTEGER NOT NULL,
+    subscription_cancelled_at TIMESTAMP,
+    subscription_account_id UUID,
+    fees_written_off_at TIMESTAMP,
+    written_off_fees JSONB NOT NULL DEFAULT '[]',
+    final_payout_scheduled_at TIMESTAMP,
+    final_payouts JSONB NOT NULL DEFAULT '[]',
+    billing_info_archived_at TIMESTAMP,
+    completed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+SELECT diesel_manage_updated_at('store_offboardings');
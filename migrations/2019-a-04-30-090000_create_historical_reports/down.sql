@@ -0,0 +1,2 @@
+DROP INDEX event_store_payment_expired_invoice_id_idx;
+DROP TABLE historical_reports;
@@ -0,0 +1,10 @@
+CREATE TABLE historical_reports (
+    as_of TIMESTAMP PRIMARY KEY,
+    invoices JSONB NOT NULL DEFAULT '[]',
+    fees JSONB NOT NULL DEFAULT '[]',
+    payouts JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX event_store_payment_expired_invoice_id_idx ON event_store ((event->'payload'->'PaymentExpired'->>'invoice_id'))
+    WHERE payload_type = 'PaymentExpired';
@@ -0,0 +1,2 @@
+DROP TABLE user_credit_entries;
+DROP TABLE user_credit_balances;
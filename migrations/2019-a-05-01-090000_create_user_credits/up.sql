@@ -0,0 +1,25 @@
+CREATE TABLE user_credit_balances (
+    user_id INTEGER NOT NULL,
+    currency VARCHAR NOT NULL,
+    balance NUMERIC NOT NULL DEFAULT 0 CHECK (balance >= 0),
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    PRIMARY KEY (user_id, currency)
+);
+
+SELECT diesel_manage_updated_at('user_credit_balances');
+
+CREATE TABLE user_credit_entries (
+    id UUID PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    currency VARCHAR NOT NULL,
+    kind VARCHAR NOT NULL,
+    amount NUMERIC NOT NULL CHECK (amount > 0),
+    invoice_id UUID,
+    reason VARCHAR,
+    created_by INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX user_credit_entries_user_id_idx ON user_credit_entries (user_id, created_at);
+CREATE UNIQUE INDEX user_credit_entries_invoice_id_kind_idx ON user_credit_entries (invoice_id, kind) WHERE invoice_id IS NOT NULL;
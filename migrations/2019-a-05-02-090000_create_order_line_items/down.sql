@@ -0,0 +1 @@
+DROP TABLE order_line_items;
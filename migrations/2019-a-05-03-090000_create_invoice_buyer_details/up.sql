@@ -0,0 +1,12 @@
+CREATE TABLE invoice_buyer_details (
+    invoice_id UUID PRIMARY KEY REFERENCES invoices_v2 (id),
+    buyer_user_id INTEGER NOT NULL,
+    country VARCHAR NOT NULL,
+    details VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+SELECT diesel_manage_updated_at('invoice_buyer_details');
+
+CREATE INDEX invoice_buyer_details_buyer_user_id_idx ON invoice_buyer_details (buyer_user_id);
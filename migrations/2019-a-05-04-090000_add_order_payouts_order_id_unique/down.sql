@@ -0,0 +1,2 @@
+ALTER TABLE order_payouts
+    DROP CONSTRAINT IF EXISTS order_payouts_order_id_key;
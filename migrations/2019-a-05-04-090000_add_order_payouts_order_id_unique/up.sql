@@ -0,0 +1,2 @@
+ALTER TABLE order_payouts
+    ADD CONSTRAINT order_payouts_order_id_key UNIQUE (order_id);
@@ -0,0 +1,2 @@
+ALTER TABLE fee_adjustments DROP COLUMN charged_fee_amount;
+ALTER TABLE fee_adjustments DROP COLUMN charged_currency;
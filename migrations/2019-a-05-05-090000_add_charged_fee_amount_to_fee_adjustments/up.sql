@@ -0,0 +1,2 @@
+ALTER TABLE fee_adjustments ADD COLUMN charged_currency VARCHAR;
+ALTER TABLE fee_adjustments ADD COLUMN charged_fee_amount NUMERIC;
@@ -0,0 +1,17 @@
+//! Runs the event processing and the scheduled jobs of `billing_lib` without serving HTTP,
+//! so that they are scaled and restarted apart from the API servers
+
+extern crate billing_lib;
+extern crate stq_logging;
+
+fn main() {
+    let config = billing_lib::config::Config::new().expect("Can't load app config!");
+
+    // Prepare sentry integration
+    let _sentry = billing_lib::sentry_integration::init(config.sentry.as_ref());
+
+    // Prepare logger
+    stq_logging::init(config.graylog.as_ref());
+
+    billing_lib::start_worker(config);
+}
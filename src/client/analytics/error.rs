@@ -0,0 +1,26 @@
+use std::fmt;
+
+use failure::{Backtrace, Context, Fail};
+
+#[derive(Debug)]
+pub struct Error {
+    inner: Context<ErrorKind>,
+}
+
+#[derive(Clone, PartialEq, Debug, Fail)]
+pub enum ErrorKind {
+    #[fail(display = "analytics client error - internal error")]
+    Internal,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+pub enum ErrorSource {
+    #[fail(display = "analytics client source - serde_json")]
+    SerdeJson,
+    #[fail(display = "analytics client source - stq_http")]
+    StqHttp,
+    #[fail(display = "analytics client source - io")]
+    Io,
+}
+
+derive_error_impls!();
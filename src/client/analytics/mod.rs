@@ -0,0 +1,162 @@
+//! Publishers of billing events to the message bus of the analytics team
+mod error;
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+
+use failure::Fail;
+use futures::{Future, IntoFuture};
+use futures_cpupool::CpuPool;
+use hyper::{Headers, Method};
+use serde_json;
+use stq_http::client::HttpClient;
+
+use config::AnalyticsSink;
+use models::AnalyticsEvent;
+
+pub use self::error::*;
+
+/// Time to wait for NATS to accept the written data and for each of its replies
+const NATS_TIMEOUT_SEC: u64 = 10;
+
+pub trait AnalyticsPublisher: Send + Sync + 'static {
+    /// Resolves once the sink has accepted the event
+    fn publish(&self, event: AnalyticsEvent) -> Box<Future<Item = (), Error = Error> + Send>;
+}
+
+pub fn create_analytics_publisher<C: HttpClient + Clone>(sink: AnalyticsSink, client: C, cpu_pool: CpuPool) -> Arc<dyn AnalyticsPublisher> {
+    match sink {
+        AnalyticsSink::Kafka { rest_proxy_url, topic } => Arc::new(KafkaRestPublisher {
+            client,
+            rest_proxy_url,
+            topic,
+        }),
+        AnalyticsSink::Nats { address, subject } => Arc::new(NatsPublisher {
+            cpu_pool,
+            address,
+            subject,
+        }),
+    }
+}
+
+/// Produces events to a Kafka topic through the Kafka REST proxy, keyed by the event id
+#[derive(Clone)]
+pub struct KafkaRestPublisher<C: HttpClient + Clone> {
+    client: C,
+    rest_proxy_url: String,
+    topic: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProduceResponse {
+    offsets: Vec<ProduceOffset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProduceOffset {
+    error: Option<String>,
+}
+
+impl<C: HttpClient + Clone> AnalyticsPublisher for KafkaRestPublisher<C> {
+    fn publish(&self, event: AnalyticsEvent) -> Box<Future<Item = (), Error = Error> + Send> {
+        let KafkaRestPublisher {
+            client,
+            rest_proxy_url,
+            topic,
+        } = self.clone();
+
+        let records = json!({ "records": [{ "key": event.id, "value": event }] });
+        let fut = serde_json::to_string(&records)
+            .map_err(ectx!(ErrorSource::SerdeJson, ErrorKind::Internal => records))
+            .into_future()
+            .and_then(move |body| {
+                let url = format!("{}/topics/{}", rest_proxy_url.trim_end_matches('/'), topic);
+                let mut headers = Headers::new();
+                headers.set_raw("Content-Type", "application/vnd.kafka.json.v2+json");
+                client
+                    .request_json::<ProduceResponse>(Method::Post, url.clone(), Some(body.clone()), Some(headers.clone()))
+                    .map_err(ectx!(ErrorSource::StqHttp, ErrorKind::Internal => Method::Post, url, Some(body), Some(headers)))
+            })
+            .and_then(check_produce_response);
+
+        Box::new(fut)
+    }
+}
+
+/// The proxy replies with 200 even when producing fails, the error is reported per record
+fn check_produce_response(response: ProduceResponse) -> Result<(), Error> {
+    match response.offsets.into_iter().filter_map(|offset| offset.error).next() {
+        None => Ok(()),
+        Some(error) => {
+            let e = format_err!("Kafka REST proxy failed to produce the record: {}", error);
+            Err(ectx!(err e, ErrorKind::Internal))
+        }
+    }
+}
+
+/// Publishes events to `<subject>.<event type>` over the NATS text protocol.
+/// NATS doesn't acknowledge messages, so the publisher waits for the reply to a `PING` sent after the message,
+/// which the server sends only once it has processed the message
+#[derive(Clone)]
+pub struct NatsPublisher {
+    cpu_pool: CpuPool,
+    address: String,
+    subject: String,
+}
+
+impl AnalyticsPublisher for NatsPublisher {
+    fn publish(&self, event: AnalyticsEvent) -> Box<Future<Item = (), Error = Error> + Send> {
+        let NatsPublisher {
+            cpu_pool,
+            address,
+            subject,
+        } = self.clone();
+
+        let subject = format!("{}.{}", subject, event.event_type);
+        let fut = cpu_pool.spawn_fn(move || {
+            let payload = serde_json::to_vec(&event).map_err(ectx!(try ErrorSource::SerdeJson, ErrorKind::Internal => event))?;
+
+            nats_publish(&address, &subject, &payload).map_err(ectx!(ErrorSource::Io, ErrorKind::Internal => address, subject))
+        });
+
+        Box::new(fut)
+    }
+}
+
+fn nats_publish(address: &str, subject: &str, payload: &[u8]) -> io::Result<()> {
+    let timeout = Duration::from_secs(NATS_TIMEOUT_SEC);
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    // the server greets with its INFO
+    read_nats_line(&mut reader)?;
+
+    write!(stream, "CONNECT {{\"verbose\":false,\"pedantic\":false,\"name\":\"billing\"}}\r\n")?;
+    write!(stream, "PUB {} {}\r\n", subject, payload.len())?;
+    stream.write_all(payload)?;
+    stream.write_all(b"\r\nPING\r\n")?;
+    stream.flush()?;
+
+    loop {
+        let line = read_nats_line(&mut reader)?;
+        match line.as_str() {
+            "PONG" => return Ok(()),
+            "PING" => stream.write_all(b"PONG\r\n")?,
+            line if line.starts_with("-ERR") => return Err(io::Error::new(io::ErrorKind::Other, line.to_string())),
+            _ => {}
+        }
+    }
+}
+
+fn read_nats_line<R: BufRead>(reader: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "NATS server closed the connection"));
+    }
+
+    Ok(line.trim_end().to_string())
+}
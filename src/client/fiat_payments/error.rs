@@ -0,0 +1,46 @@
+use std::fmt;
+
+use failure::{Backtrace, Context, Fail};
+use serde_json;
+
+use client::stripe::ErrorKind as StripeClientErrorKind;
+
+#[derive(Debug)]
+pub struct Error {
+    inner: Context<ErrorKind>,
+}
+
+#[derive(Clone, PartialEq, Debug, Fail)]
+pub enum ErrorKind {
+    #[fail(display = "fiat payment provider error - unauthorized")]
+    Unauthorized,
+    #[fail(display = "fiat payment provider error - internal error")]
+    Internal,
+    #[fail(display = "fiat payment provider error - bad request")]
+    Validation(serde_json::Value),
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+pub enum ErrorContext {
+    #[fail(display = "fiat payment provider context - currency is not supported")]
+    Currency,
+    #[fail(display = "fiat payment provider context - amount is out of range")]
+    Amount,
+    #[fail(display = "fiat payment provider context - webhook signature verification failed")]
+    WebhookSignature,
+    #[fail(display = "fiat payment provider context - webhook payload is not a Stripe event")]
+    WebhookPayload,
+}
+
+derive_error_impls!();
+
+impl From<StripeClientErrorKind> for ErrorKind {
+    fn from(e: StripeClientErrorKind) -> Self {
+        match e {
+            StripeClientErrorKind::Internal => ErrorKind::Internal,
+            StripeClientErrorKind::MalformedInput => ErrorKind::Internal,
+            StripeClientErrorKind::Unauthorized => ErrorKind::Unauthorized,
+            StripeClientErrorKind::Validation(value) => ErrorKind::Validation(value),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Fiat payment providers. Services take card payments through `FiatPaymentProvider` only,
+//! so a provider other than Stripe needs an implementation of the trait and nothing else.
+mod error;
+mod stripe_provider;
+mod types;
+
+pub use self::error::*;
+pub use self::stripe_provider::StripeFiatPaymentProvider;
+pub use self::types::*;
+
+use futures::Future;
+use stq_types::stripe::PaymentIntentId;
+
+use models::order_v2::OrderId;
+use models::*;
+
+pub trait FiatPaymentProvider: Send + Sync + 'static {
+    /// Creates a payment intent that the buyer confirms on the client side
+    fn create_payment_intent(&self, input: NewFiatPaymentIntent) -> Box<Future<Item = NewPaymentIntent, Error = Error> + Send>;
+
+    /// Captures funds authorized by a payment intent created with `CaptureMethod::Manual`
+    fn capture_payment_intent(
+        &self,
+        payment_intent_id: PaymentIntentId,
+        amount: Amount,
+    ) -> Box<Future<Item = NewPaymentIntent, Error = Error> + Send>;
+
+    fn charge_customer(&self, input: NewFiatCharge) -> Box<Future<Item = FiatCharge, Error = Error> + Send>;
+
+    fn refund(&self, charge_id: ChargeId, amount: Amount, order_id: OrderId) -> Box<Future<Item = (), Error = Error> + Send>;
+
+    /// Verifies the signature of a webhook and converts it to the event handled by the event store,
+    /// `None` is returned for the kinds of events billing does not process
+    fn parse_webhook(&self, signature: String, payload: String) -> Result<Option<EventPayload>, Error>;
+}
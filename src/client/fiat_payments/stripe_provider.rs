@@ -0,0 +1,272 @@
+use std::sync::Arc;
+
+use chrono::NaiveDateTime;
+use failure::Fail;
+use futures::{Future, IntoFuture};
+use serde_json;
+use stq_types::stripe::PaymentIntentId;
+use stripe::{
+    CaptureMethod as StripeCaptureMethod, Event, EventObject, EventType, PaymentIntent as StripePaymentIntent, PaymentIntentSourceType,
+};
+
+use super::{CaptureMethod, Error, ErrorContext, ErrorKind, FiatCharge, FiatPaymentProvider, NewFiatCharge, NewFiatPaymentIntent};
+use client::stripe::{NewCharge, NewPaymentIntent as StripeClientNewPaymentIntent, StripeClient, StripeKeys};
+use models::order_v2::OrderId;
+use models::*;
+use signature::{verify_stripe_signature, SignatureError, SystemClock};
+
+/// Stripe behind `FiatPaymentProvider`, the Stripe specific requests stay in `StripeClient`
+#[derive(Clone)]
+pub struct StripeFiatPaymentProvider {
+    client: Arc<dyn StripeClient>,
+    keys: Arc<StripeKeys>,
+}
+
+impl StripeFiatPaymentProvider {
+    pub fn new(client: Arc<dyn StripeClient>, keys: Arc<StripeKeys>) -> Self {
+        Self { client, keys }
+    }
+}
+
+impl FiatPaymentProvider for StripeFiatPaymentProvider {
+    fn create_payment_intent(&self, input: NewFiatPaymentIntent) -> Box<Future<Item = NewPaymentIntent, Error = Error> + Send> {
+        let client = self.client.clone();
+
+        let fut = payment_intent_create_params(input)
+            .into_future()
+            .and_then(move |params| client.create_payment_intent(params).map_err(ectx!(convert)))
+            .and_then(new_payment_intent);
+
+        Box::new(fut)
+    }
+
+    fn capture_payment_intent(
+        &self,
+        payment_intent_id: PaymentIntentId,
+        amount: Amount,
+    ) -> Box<Future<Item = NewPaymentIntent, Error = Error> + Send> {
+        let fut = self
+            .client
+            .capture_payment_intent(payment_intent_id.clone(), amount)
+            .map_err(ectx!(convert => payment_intent_id, amount))
+            .and_then(new_payment_intent);
+
+        Box::new(fut)
+    }
+
+    fn charge_customer(&self, input: NewFiatCharge) -> Box<Future<Item = FiatCharge, Error = Error> + Send> {
+        let NewFiatCharge {
+            customer_id,
+            amount,
+            currency,
+            metadata,
+        } = input;
+
+        let new_charge = NewCharge {
+            customer_id: customer_id.clone(),
+            amount,
+            currency,
+            capture: true,
+        };
+
+        let fut = self
+            .client
+            .create_charge(new_charge, metadata)
+            .map_err(ectx!(convert => customer_id))
+            .map(|charge| FiatCharge {
+                id: ChargeId::new(charge.id),
+                paid: charge.paid,
+            });
+
+        Box::new(fut)
+    }
+
+    fn refund(&self, charge_id: ChargeId, amount: Amount, order_id: OrderId) -> Box<Future<Item = (), Error = Error> + Send> {
+        let fut = self
+            .client
+            .refund(charge_id.clone(), amount, order_id)
+            .map_err(ectx!(convert => charge_id, amount, order_id))
+            .map(|_| ());
+
+        Box::new(fut)
+    }
+
+    fn parse_webhook(&self, signature: String, payload: String) -> Result<Option<EventPayload>, Error> {
+        let verified = verify_stripe_signature(self.keys.signing_secrets(), &signature, &payload, &SystemClock);
+        match verified {
+            Ok(ref secret_name) => self.keys.record_webhook_signature(secret_name),
+            Err(SignatureError::Mismatch) => self.keys.record_unknown_webhook_signature(),
+            Err(_) => {}
+        }
+        let secret_name = verified.map_err(|e| {
+            warn!("stripe webhook signature error: {}", e);
+            ectx!(try err e, ErrorContext::WebhookSignature, ErrorKind::Unauthorized)
+        })?;
+
+        // stripe-rs can not deserialize setup intent events, they are parsed here
+        if let Some(setup_intent_event) = setup_intent_event(&payload) {
+            info!(
+                "stripe webhook setup intent event verified with the {} signing secret: {:?}",
+                secret_name, setup_intent_event
+            );
+
+            let payload = match setup_intent_event.event_type.as_str() {
+                "setup_intent.succeeded" => Some(EventPayload::SetupIntentSucceeded {
+                    setup_intent: setup_intent_event.data.object,
+                }),
+                event_type => {
+                    warn!("stripe webhook unprocessable setup intent event - type: {}", event_type);
+                    None
+                }
+            };
+
+            return Ok(payload);
+        }
+
+        let event = serde_json::from_str::<Event>(&payload).map_err(|e| {
+            warn!("stripe webhook event parse error: {}", e);
+            ectx!(try err e, ErrorContext::WebhookPayload, ErrorKind::Internal)
+        })?;
+        info!("stripe webhook event verified with the {} signing secret: {:?}", secret_name, event);
+        let event_created_at = event_created_at(&payload);
+
+        let payload = match (event.event_type, event.data.object) {
+            (EventType::PaymentIntentAmountCapturableUpdated, EventObject::PaymentIntent(payment_intent)) => {
+                Some(EventPayload::PaymentIntentAmountCapturableUpdated {
+                    payment_intent,
+                    event_created_at,
+                })
+            }
+            (EventType::PaymentIntentSucceeded, EventObject::PaymentIntent(payment_intent)) => Some(EventPayload::PaymentIntentSucceeded {
+                payment_intent,
+                event_created_at,
+            }),
+            (EventType::PaymentIntentPaymentFailed, EventObject::PaymentIntent(payment_intent)) => {
+                Some(EventPayload::PaymentIntentPaymentFailed {
+                    payment_intent,
+                    event_created_at,
+                })
+            }
+            (event_type, event_object) => {
+                warn!(
+                    "stripe webhook unprocessable event - type: {:?}, object: {:?}",
+                    event_type, event_object
+                );
+                None
+            }
+        };
+
+        Ok(payload)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SetupIntentEvent {
+    #[serde(rename = "type")]
+    event_type: String,
+    data: SetupIntentEventData,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetupIntentEventData {
+    object: SetupIntent,
+}
+
+fn setup_intent_event(payload: &str) -> Option<SetupIntentEvent> {
+    let event: serde_json::Value = serde_json::from_str(payload).ok()?;
+    let is_setup_intent_event = event["type"].as_str().map(|t| t.starts_with("setup_intent.")).unwrap_or(false);
+    if !is_setup_intent_event {
+        return None;
+    }
+
+    serde_json::from_value(event).ok()
+}
+
+/// Creation time of the event, Stripe sends it as a unix timestamp in seconds
+fn event_created_at(payload: &str) -> Option<NaiveDateTime> {
+    let event: serde_json::Value = serde_json::from_str(payload).ok()?;
+    event["created"].as_i64().map(|created| NaiveDateTime::from_timestamp(created, 0))
+}
+
+fn payment_intent_create_params(input: NewFiatPaymentIntent) -> Result<StripeClientNewPaymentIntent, Error> {
+    let NewFiatPaymentIntent {
+        amount,
+        currency,
+        capture_method,
+        receipt_email,
+        description,
+    } = input;
+
+    let currency = currency.try_into_stripe_currency().map_err(|_| {
+        let e = format_err!("Stripe does not support currency: {}", currency);
+        ectx!(try err e, ErrorContext::Currency, ErrorKind::Internal)
+    })?;
+
+    let stripe_amount = amount.to_u64().ok_or_else(|| {
+        let e = format_err!("Amount {} exceeds the largest amount Stripe accepts", amount);
+        ectx!(try err e, ErrorContext::Amount, ErrorKind::Validation(json!({ "amount": amount })))
+    })?;
+
+    let capture_method = match capture_method {
+        CaptureMethod::Automatic => StripeCaptureMethod::Automatic,
+        CaptureMethod::Manual => StripeCaptureMethod::Manual,
+    };
+
+    Ok(StripeClientNewPaymentIntent {
+        allowed_source_types: vec![PaymentIntentSourceType::Card],
+        amount: stripe_amount,
+        currency,
+        capture_method: Some(capture_method),
+        receipt_email,
+        description,
+    })
+}
+
+fn new_payment_intent(payment_intent: StripePaymentIntent) -> Result<NewPaymentIntent, Error> {
+    Ok(NewPaymentIntent {
+        id: PaymentIntentId(payment_intent.id.clone()),
+        amount: payment_intent.amount.into(),
+        amount_received: payment_intent.amount_received.into(),
+        client_secret: payment_intent.client_secret,
+        currency: Currency::try_from_stripe_currency(payment_intent.currency).map_err({
+            let e = format_err!(
+                "Payment intent with ID: {} can not convert currency: {}",
+                payment_intent.id,
+                payment_intent.currency,
+            );
+            move |_| ectx!(try err e, ErrorContext::Currency, ErrorKind::Internal)
+        })?,
+        last_payment_error_message: payment_intent.last_payment_error.map(|err| format!("{:?}", err)),
+        receipt_email: payment_intent.receipt_email,
+        charge_id: payment_intent
+            .charges
+            .data
+            .into_iter()
+            .next()
+            .map(|charge| ChargeId::new(charge.id)),
+        status: payment_intent.status.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payment_intent_create_params_rejects_amounts_stripe_cannot_take() {
+        let input = NewFiatPaymentIntent {
+            amount: Amount::new(u128::from(std::u64::MAX)),
+            currency: Currency::Usd,
+            capture_method: CaptureMethod::Manual,
+            receipt_email: None,
+            description: None,
+        };
+        assert_eq!(payment_intent_create_params(input.clone()).unwrap().amount, std::u64::MAX);
+
+        let input = NewFiatPaymentIntent {
+            amount: Amount::new(u128::from(std::u64::MAX) + 1),
+            ..input
+        };
+        assert!(payment_intent_create_params(input).is_err());
+    }
+}
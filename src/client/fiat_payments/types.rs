@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+
+use models::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaptureMethod {
+    /// Funds are captured as soon as the buyer confirms the payment
+    Automatic,
+    /// Funds are only authorized and captured later with `capture_payment_intent`
+    Manual,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewFiatPaymentIntent {
+    pub amount: Amount,
+    pub currency: Currency,
+    pub capture_method: CaptureMethod,
+    pub receipt_email: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Charge of a saved card of the customer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewFiatCharge {
+    pub customer_id: CustomerId,
+    pub amount: Amount,
+    pub currency: Currency,
+    pub metadata: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FiatCharge {
+    pub id: ChargeId,
+    pub paid: bool,
+}
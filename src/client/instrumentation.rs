@@ -0,0 +1,634 @@
+//! Tells which dependency slows billing down: every call of an instrumented client is given the deadline
+//! of its latency budget, its latency and outcome are kept for a rolling window served at `/debug/dependencies`
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use failure::{Error as FailureError, Fail};
+use futures::future::Either;
+use futures::Future;
+use stripe::{BalanceTransaction, Charge, Currency as StripeCurrency, Customer, Deleted, Metadata, PaymentIntent, Payout, Refund};
+use tokio_timer::Timeout;
+use uuid::Uuid;
+
+use stq_types::stripe::PaymentIntentId;
+use stq_types::{Quantity, StoreId};
+
+use client::notifications::{
+    self, BillingInfoChangeEmail, FeeDunningEmail, InvoiceReceiptEmail, NegativeStoreBalanceEmail, NotificationsClient, PaymentFailedEmail,
+};
+use client::payments::{
+    self, Account, CreateAccount, CreateExternalTransaction, CreateInternalTransaction, FeesResponse, GetFees, GetRate, PaymentsClient,
+    Rate, RateRefresh, TransactionsResponse,
+};
+use client::saga::{self, FeeStatementNotification, OrderStateUpdate, SagaClient, StoreBillingStatusNotification};
+use client::storage::{self, StorageClient};
+use client::stores::{self, CurrencyExchangeInfo, StoresClient};
+use client::stripe::{
+    self as stripe_client, NewCharge, NewCustomer, NewCustomerWithSource, NewPaymentIntent, StripeClient, UpdateCustomer,
+};
+use config;
+use models::order_v2::{ExchangeId, OrderId};
+use models::{Amount, ChargeId, CustomerId, SetupIntent};
+
+const SAGA: &str = "saga";
+const STORES: &str = "stores";
+const STRIPE: &str = "stripe";
+const NOTIFICATIONS: &str = "notifications";
+const PAYMENTS: &str = "payments";
+const STORAGE: &str = "storage";
+
+/// Upper bounds of the latency histogram buckets, the last bucket takes the slower calls
+pub const LATENCY_BUCKETS_MS: &[u64] = &[5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// Calls kept per method, so that a burst doesn't make the window grow unbounded
+const MAX_CALLS_PER_METHOD: usize = 10000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallOutcome {
+    Success,
+    Failure,
+    DeadlineExceeded,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Call {
+    at: Instant,
+    latency: Duration,
+    outcome: CallOutcome,
+}
+
+/// Latencies and outcomes of the calls of a dependency method in the window
+#[derive(Debug, Clone, PartialEq)]
+pub struct DependencyMethodStats {
+    pub dependency: &'static str,
+    pub method: &'static str,
+    pub latency_budget: Option<Duration>,
+    pub calls: usize,
+    pub failures: usize,
+    pub deadlines_exceeded: usize,
+    pub latency_p50: Duration,
+    pub latency_p95: Duration,
+    pub latency_p99: Duration,
+    pub latency_max: Duration,
+    /// Calls by the buckets of `LATENCY_BUCKETS_MS`, with one more for the slower calls
+    pub latency_histogram: Vec<usize>,
+}
+
+impl DependencyMethodStats {
+    /// Share of the calls that failed or missed their deadline
+    pub fn error_rate(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            (self.failures + self.deadlines_exceeded) as f64 / self.calls as f64
+        }
+    }
+}
+
+/// Shared by all instrumented clients, so that the stats cover the event handler and every request
+#[derive(Debug)]
+pub struct DependencyStats {
+    window: Duration,
+    latency_budgets: HashMap<String, Duration>,
+    calls: Mutex<HashMap<(&'static str, &'static str), VecDeque<Call>>>,
+}
+
+impl DependencyStats {
+    pub fn new(window: Duration, latency_budgets: HashMap<String, Duration>) -> Self {
+        DependencyStats {
+            window,
+            latency_budgets,
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_config(config: &config::Dependencies) -> Self {
+        let latency_budgets = config
+            .latency_budgets_ms
+            .iter()
+            .map(|(key, budget_ms)| (key.clone(), Duration::from_millis(*budget_ms)))
+            .collect();
+        DependencyStats::new(Duration::from_secs(config.stats_window_sec), latency_budgets)
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Budget of the method, falling back to the one of the dependency
+    pub fn latency_budget(&self, dependency: &str, method: &str) -> Option<Duration> {
+        self.latency_budgets
+            .get(&format!("{}.{}", dependency, method))
+            .or_else(|| self.latency_budgets.get(dependency))
+            .cloned()
+    }
+
+    pub fn record(&self, dependency: &'static str, method: &'static str, at: Instant, latency: Duration, outcome: CallOutcome) {
+        let mut calls = self.calls.lock().unwrap_or_else(PoisonError::into_inner);
+        let method_calls = calls.entry((dependency, method)).or_insert_with(VecDeque::new);
+        method_calls.push_back(Call { at, latency, outcome });
+        if method_calls.len() > MAX_CALLS_PER_METHOD {
+            method_calls.pop_front();
+        }
+    }
+
+    /// Stats of the methods called in the window before `now`, ordered by dependency and method
+    pub fn snapshot(&self, now: Instant) -> Vec<DependencyMethodStats> {
+        let mut calls = self.calls.lock().unwrap_or_else(PoisonError::into_inner);
+        for method_calls in calls.values_mut() {
+            while method_calls.front().map_or(false, |call| now.duration_since(call.at) > self.window) {
+                method_calls.pop_front();
+            }
+        }
+        calls.retain(|_, method_calls| !method_calls.is_empty());
+
+        let mut stats = calls
+            .iter()
+            .map(|(&(dependency, method), method_calls)| self.method_stats(dependency, method, method_calls))
+            .collect::<Vec<_>>();
+        stats.sort_by(|a, b| (a.dependency, a.method).cmp(&(b.dependency, b.method)));
+        stats
+    }
+
+    fn method_stats(&self, dependency: &'static str, method: &'static str, calls: &VecDeque<Call>) -> DependencyMethodStats {
+        let mut latencies = calls.iter().map(|call| call.latency).collect::<Vec<_>>();
+        latencies.sort();
+
+        let mut latency_histogram = vec![0; LATENCY_BUCKETS_MS.len() + 1];
+        for latency in &latencies {
+            let latency_ms = duration_ms(*latency);
+            let bucket = LATENCY_BUCKETS_MS
+                .iter()
+                .position(|bound_ms| latency_ms <= *bound_ms)
+                .unwrap_or(LATENCY_BUCKETS_MS.len());
+            latency_histogram[bucket] += 1;
+        }
+
+        let outcomes = |outcome| calls.iter().filter(|call| call.outcome == outcome).count();
+
+        DependencyMethodStats {
+            dependency,
+            method,
+            latency_budget: self.latency_budget(dependency, method),
+            calls: calls.len(),
+            failures: outcomes(CallOutcome::Failure),
+            deadlines_exceeded: outcomes(CallOutcome::DeadlineExceeded),
+            latency_p50: percentile(&latencies, 50),
+            latency_p95: percentile(&latencies, 95),
+            latency_p99: percentile(&latencies, 99),
+            latency_max: latencies.last().cloned().unwrap_or_default(),
+            latency_histogram,
+        }
+    }
+}
+
+/// Nearest-rank percentile of the sorted latencies
+fn percentile(sorted_latencies: &[Duration], percent: usize) -> Duration {
+    if sorted_latencies.is_empty() {
+        return Duration::default();
+    }
+    let rank = (sorted_latencies.len() * percent + 99) / 100;
+    sorted_latencies[rank.max(1) - 1]
+}
+
+pub fn duration_ms(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
+
+/// Errors of the instrumented clients, so that a missed deadline is reported as an error of the client
+pub trait InstrumentedError: Sized {
+    fn deadline_exceeded(e: FailureError) -> Self;
+
+    fn internal(e: FailureError) -> Self;
+}
+
+/// Client recording the calls of the wrapped one to the dependency stats
+#[derive(Clone)]
+pub struct Instrumented<C> {
+    inner: C,
+    stats: Arc<DependencyStats>,
+}
+
+impl<C> Instrumented<C> {
+    pub fn new(inner: C, stats: Arc<DependencyStats>) -> Self {
+        Self { inner, stats }
+    }
+
+    fn instrument<T, E, F>(&self, dependency: &'static str, method: &'static str, call: F) -> Box<Future<Item = T, Error = E> + Send>
+    where
+        T: Send + 'static,
+        E: InstrumentedError + Send + 'static,
+        F: FnOnce(&C) -> Box<Future<Item = T, Error = E> + Send>,
+    {
+        let stats = self.stats.clone();
+        let started_at = Instant::now();
+        let call = call(&self.inner).map_err(|e| (e, CallOutcome::Failure));
+
+        let call = match stats.latency_budget(dependency, method) {
+            None => Either::A(call),
+            Some(budget) => Either::B(Timeout::new(call, budget).map_err(move |e| {
+                if e.is_elapsed() {
+                    warn!("{} didn't respond to {} in {:?}", dependency, method, budget);
+                    let e = format_err!("{} didn't respond to {} in {:?}", dependency, method, budget);
+                    (E::deadline_exceeded(e), CallOutcome::DeadlineExceeded)
+                } else if e.is_timer() {
+                    let e = format_err!("Timer of the {} call to {} failed", dependency, method);
+                    (E::internal(e), CallOutcome::Failure)
+                } else {
+                    e.into_inner().expect("Timeout error is either elapsed, timer or inner")
+                }
+            })),
+        };
+
+        let fut = call.then(move |res| {
+            let now = Instant::now();
+            let (res, outcome) = match res {
+                Ok(value) => (Ok(value), CallOutcome::Success),
+                Err((e, outcome)) => (Err(e), outcome),
+            };
+            stats.record(dependency, method, now, now.duration_since(started_at), outcome);
+            res
+        });
+
+        Box::new(fut)
+    }
+}
+
+impl InstrumentedError for saga::Error {
+    fn deadline_exceeded(e: FailureError) -> Self {
+        ectx!(err e, saga::ErrorKind::Internal)
+    }
+
+    fn internal(e: FailureError) -> Self {
+        ectx!(err e, saga::ErrorKind::Internal)
+    }
+}
+
+impl InstrumentedError for stores::Error {
+    fn deadline_exceeded(e: FailureError) -> Self {
+        ectx!(err e, stores::ErrorKind::Internal)
+    }
+
+    fn internal(e: FailureError) -> Self {
+        ectx!(err e, stores::ErrorKind::Internal)
+    }
+}
+
+impl InstrumentedError for stripe_client::Error {
+    fn deadline_exceeded(e: FailureError) -> Self {
+        ectx!(err e, stripe_client::ErrorKind::Internal)
+    }
+
+    fn internal(e: FailureError) -> Self {
+        ectx!(err e, stripe_client::ErrorKind::Internal)
+    }
+}
+
+impl InstrumentedError for notifications::Error {
+    fn deadline_exceeded(e: FailureError) -> Self {
+        ectx!(err e, notifications::ErrorKind::Internal)
+    }
+
+    fn internal(e: FailureError) -> Self {
+        ectx!(err e, notifications::ErrorKind::Internal)
+    }
+}
+
+impl InstrumentedError for storage::Error {
+    fn deadline_exceeded(e: FailureError) -> Self {
+        ectx!(err e, storage::ErrorKind::Internal)
+    }
+
+    fn internal(e: FailureError) -> Self {
+        ectx!(err e, storage::ErrorKind::Internal)
+    }
+}
+
+impl InstrumentedError for payments::Error {
+    fn deadline_exceeded(e: FailureError) -> Self {
+        ectx!(err e, payments::ErrorKind::Unavailable)
+    }
+
+    fn internal(e: FailureError) -> Self {
+        ectx!(err e, payments::ErrorKind::Internal)
+    }
+}
+
+impl<C: SagaClient + Clone> SagaClient for Instrumented<C> {
+    fn update_order_states(&self, order_states: Vec<OrderStateUpdate>) -> Box<Future<Item = (), Error = saga::Error> + Send> {
+        self.instrument(SAGA, "update_order_states", move |inner| inner.update_order_states(order_states))
+    }
+
+    fn notify_fee_statement_generated(&self, notification: FeeStatementNotification) -> Box<Future<Item = (), Error = saga::Error> + Send> {
+        self.instrument(SAGA, "notify_fee_statement_generated", move |inner| {
+            inner.notify_fee_statement_generated(notification)
+        })
+    }
+
+    fn notify_store_billing_status_changed(
+        &self,
+        notification: StoreBillingStatusNotification,
+    ) -> Box<Future<Item = (), Error = saga::Error> + Send> {
+        self.instrument(SAGA, "notify_store_billing_status_changed", move |inner| {
+            inner.notify_store_billing_status_changed(notification)
+        })
+    }
+}
+
+impl<C: StoresClient + Clone> StoresClient for Instrumented<C> {
+    fn get_currency_exchange_info(&self) -> Box<Future<Item = CurrencyExchangeInfo, Error = stores::Error> + Send> {
+        self.instrument(STORES, "get_currency_exchange_info", |inner| inner.get_currency_exchange_info())
+    }
+
+    fn get_published_products_count(&self, store_id: StoreId) -> Box<Future<Item = Quantity, Error = stores::Error> + Send> {
+        self.instrument(STORES, "get_published_products_count", move |inner| {
+            inner.get_published_products_count(store_id)
+        })
+    }
+}
+
+impl<C: NotificationsClient + Clone> NotificationsClient for Instrumented<C> {
+    fn send_payment_failed_email(&self, email: PaymentFailedEmail) -> Box<Future<Item = (), Error = notifications::Error> + Send> {
+        self.instrument(NOTIFICATIONS, "send_payment_failed_email", move |inner| {
+            inner.send_payment_failed_email(email)
+        })
+    }
+
+    fn send_invoice_receipt_email(&self, email: InvoiceReceiptEmail) -> Box<Future<Item = (), Error = notifications::Error> + Send> {
+        self.instrument(NOTIFICATIONS, "send_invoice_receipt_email", move |inner| {
+            inner.send_invoice_receipt_email(email)
+        })
+    }
+
+    fn send_negative_store_balance_email(
+        &self,
+        email: NegativeStoreBalanceEmail,
+    ) -> Box<Future<Item = (), Error = notifications::Error> + Send> {
+        self.instrument(NOTIFICATIONS, "send_negative_store_balance_email", move |inner| {
+            inner.send_negative_store_balance_email(email)
+        })
+    }
+
+    fn send_billing_info_change_email(&self, email: BillingInfoChangeEmail) -> Box<Future<Item = (), Error = notifications::Error> + Send> {
+        self.instrument(NOTIFICATIONS, "send_billing_info_change_email", move |inner| {
+            inner.send_billing_info_change_email(email)
+        })
+    }
+
+    fn send_fee_dunning_email(&self, email: FeeDunningEmail) -> Box<Future<Item = (), Error = notifications::Error> + Send> {
+        self.instrument(NOTIFICATIONS, "send_fee_dunning_email", move |inner| {
+            inner.send_fee_dunning_email(email)
+        })
+    }
+}
+
+impl<C: StorageClient + Clone> StorageClient for Instrumented<C> {
+    fn put_object(&self, key: String, body: String) -> Box<Future<Item = (), Error = storage::Error> + Send> {
+        self.instrument(STORAGE, "put_object", move |inner| inner.put_object(key, body))
+    }
+}
+
+impl<C: StripeClient + Clone> StripeClient for Instrumented<C> {
+    fn create_customer(&self, input: NewCustomer) -> Box<Future<Item = Customer, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "create_customer", move |inner| inner.create_customer(input))
+    }
+
+    fn create_customer_with_source(
+        &self,
+        input: NewCustomerWithSource,
+    ) -> Box<Future<Item = Customer, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "create_customer_with_source", move |inner| {
+            inner.create_customer_with_source(input)
+        })
+    }
+
+    fn get_customer(&self, customer_id: CustomerId) -> Box<Future<Item = Customer, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "get_customer", move |inner| inner.get_customer(customer_id))
+    }
+
+    fn delete_customer(&self, customer_id: CustomerId) -> Box<Future<Item = Deleted, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "delete_customer", move |inner| inner.delete_customer(customer_id))
+    }
+
+    fn update_customer(
+        &self,
+        customer_id: CustomerId,
+        input: UpdateCustomer,
+    ) -> Box<Future<Item = Customer, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "update_customer", move |inner| inner.update_customer(customer_id, input))
+    }
+
+    fn create_charge(
+        &self,
+        input: NewCharge,
+        metadata: Option<Metadata>,
+    ) -> Box<Future<Item = Charge, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "create_charge", move |inner| inner.create_charge(input, metadata))
+    }
+
+    fn get_charge(&self, charge_id: ChargeId) -> Box<Future<Item = Charge, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "get_charge", move |inner| inner.get_charge(charge_id))
+    }
+
+    fn capture_charge(&self, charge_id: ChargeId, amount: Amount) -> Box<Future<Item = Charge, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "capture_charge", move |inner| inner.capture_charge(charge_id, amount))
+    }
+
+    fn get_payment_intent(
+        &self,
+        payment_intent_id: PaymentIntentId,
+    ) -> Box<Future<Item = PaymentIntent, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "get_payment_intent", move |inner| {
+            inner.get_payment_intent(payment_intent_id)
+        })
+    }
+
+    fn capture_payment_intent(
+        &self,
+        payment_intent_id: PaymentIntentId,
+        amount: Amount,
+    ) -> Box<Future<Item = PaymentIntent, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "capture_payment_intent", move |inner| {
+            inner.capture_payment_intent(payment_intent_id, amount)
+        })
+    }
+
+    fn retrieve_balance_transaction(
+        &self,
+        balance_transaction_id: String,
+    ) -> Box<Future<Item = BalanceTransaction, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "retrieve_balance_transaction", move |inner| {
+            inner.retrieve_balance_transaction(balance_transaction_id)
+        })
+    }
+
+    fn refund(
+        &self,
+        charge_id: ChargeId,
+        amount: Amount,
+        order_id: OrderId,
+    ) -> Box<Future<Item = Refund, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "refund", move |inner| inner.refund(charge_id, amount, order_id))
+    }
+
+    fn create_payout(
+        &self,
+        amount: Amount,
+        currency: StripeCurrency,
+        order_id: OrderId,
+    ) -> Box<Future<Item = Payout, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "create_payout", move |inner| {
+            inner.create_payout(amount, currency, order_id)
+        })
+    }
+
+    fn create_payment_intent(&self, input: NewPaymentIntent) -> Box<Future<Item = PaymentIntent, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "create_payment_intent", move |inner| inner.create_payment_intent(input))
+    }
+
+    fn cancel_payment_intent(
+        &self,
+        payment_intent_id: PaymentIntentId,
+    ) -> Box<Future<Item = PaymentIntent, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "cancel_payment_intent", move |inner| {
+            inner.cancel_payment_intent(payment_intent_id)
+        })
+    }
+
+    fn update_payment_intent_receipt_email(
+        &self,
+        payment_intent_id: PaymentIntentId,
+        receipt_email: String,
+    ) -> Box<Future<Item = PaymentIntent, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "update_payment_intent_receipt_email", move |inner| {
+            inner.update_payment_intent_receipt_email(payment_intent_id, receipt_email)
+        })
+    }
+
+    fn update_payment_intent_description(
+        &self,
+        payment_intent_id: PaymentIntentId,
+        description: String,
+    ) -> Box<Future<Item = PaymentIntent, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "update_payment_intent_description", move |inner| {
+            inner.update_payment_intent_description(payment_intent_id, description)
+        })
+    }
+
+    fn ping(&self) -> Box<Future<Item = (), Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "ping", |inner| inner.ping())
+    }
+
+    fn create_setup_intent(&self, customer_id: CustomerId) -> Box<Future<Item = SetupIntent, Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "create_setup_intent", move |inner| inner.create_setup_intent(customer_id))
+    }
+
+    fn attach_payment_method(
+        &self,
+        customer_id: CustomerId,
+        payment_method_id: String,
+    ) -> Box<Future<Item = (), Error = stripe_client::Error> + Send> {
+        self.instrument(STRIPE, "attach_payment_method", move |inner| {
+            inner.attach_payment_method(customer_id, payment_method_id)
+        })
+    }
+}
+
+impl<C: PaymentsClient + Clone> PaymentsClient for Instrumented<C> {
+    fn get_account(&self, account_id: Uuid) -> Box<Future<Item = Account, Error = payments::Error> + Send> {
+        self.instrument(PAYMENTS, "get_account", move |inner| inner.get_account(account_id))
+    }
+
+    fn list_accounts(&self) -> Box<Future<Item = Vec<Account>, Error = payments::Error> + Send> {
+        self.instrument(PAYMENTS, "list_accounts", |inner| inner.list_accounts())
+    }
+
+    fn create_account(&self, input: CreateAccount) -> Box<Future<Item = Account, Error = payments::Error> + Send> {
+        self.instrument(PAYMENTS, "create_account", move |inner| inner.create_account(input))
+    }
+
+    fn delete_account(&self, account_id: Uuid) -> Box<Future<Item = (), Error = payments::Error> + Send> {
+        self.instrument(PAYMENTS, "delete_account", move |inner| inner.delete_account(account_id))
+    }
+
+    fn get_rate(&self, input: GetRate) -> Box<Future<Item = Rate, Error = payments::Error> + Send> {
+        self.instrument(PAYMENTS, "get_rate", move |inner| inner.get_rate(input))
+    }
+
+    fn refresh_rate(&self, exchange_id: ExchangeId) -> Box<Future<Item = RateRefresh, Error = payments::Error> + Send> {
+        self.instrument(PAYMENTS, "refresh_rate", move |inner| inner.refresh_rate(exchange_id))
+    }
+
+    fn get_fees(&self, input: GetFees) -> Box<Future<Item = FeesResponse, Error = payments::Error> + Send> {
+        self.instrument(PAYMENTS, "get_fees", move |inner| inner.get_fees(input))
+    }
+
+    fn get_transaction(&self, tx_id: Uuid) -> Box<Future<Item = Option<TransactionsResponse>, Error = payments::Error> + Send> {
+        self.instrument(PAYMENTS, "get_transaction", move |inner| inner.get_transaction(tx_id))
+    }
+
+    fn create_external_transaction(&self, input: CreateExternalTransaction) -> Box<Future<Item = (), Error = payments::Error> + Send> {
+        self.instrument(PAYMENTS, "create_external_transaction", move |inner| {
+            inner.create_external_transaction(input)
+        })
+    }
+
+    fn create_internal_transaction(&self, input: CreateInternalTransaction) -> Box<Future<Item = (), Error = payments::Error> + Send> {
+        self.instrument(PAYMENTS, "create_internal_transaction", move |inner| {
+            inner.create_internal_transaction(input)
+        })
+    }
+
+    fn ping(&self) -> Box<Future<Item = (), Error = payments::Error> + Send> {
+        self.instrument(PAYMENTS, "ping", |inner| inner.ping())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn method_budget_takes_precedence_over_the_dependency_one() {
+        let budgets = vec![("stripe".to_string(), ms(15000)), ("stripe.create_payout".to_string(), ms(30000))]
+            .into_iter()
+            .collect();
+        let stats = DependencyStats::new(Duration::from_secs(300), budgets);
+
+        assert_eq!(stats.latency_budget("stripe", "create_payout"), Some(ms(30000)));
+        assert_eq!(stats.latency_budget("stripe", "get_charge"), Some(ms(15000)));
+        assert_eq!(stats.latency_budget("saga", "update_order_states"), None);
+    }
+
+    #[test]
+    fn snapshot_summarizes_the_calls_in_the_window() {
+        let stats = DependencyStats::new(Duration::from_secs(300), HashMap::new());
+        let long_ago = Instant::now();
+        let recently = long_ago + Duration::from_secs(200);
+
+        stats.record(SAGA, "update_order_states", long_ago, ms(9000), CallOutcome::Failure);
+        for latency_ms in 1..=100 {
+            stats.record(SAGA, "update_order_states", recently, ms(latency_ms), CallOutcome::Success);
+        }
+        stats.record(SAGA, "update_order_states", recently, ms(20000), CallOutcome::DeadlineExceeded);
+        stats.record(STORES, "get_currency_exchange_info", long_ago, ms(40), CallOutcome::Success);
+
+        let snapshot = stats.snapshot(long_ago + Duration::from_secs(400));
+
+        assert_eq!(snapshot.len(), 1);
+        let method_stats = &snapshot[0];
+        assert_eq!(method_stats.calls, 101);
+        assert_eq!(method_stats.failures, 0);
+        assert_eq!(method_stats.deadlines_exceeded, 1);
+        assert_eq!(method_stats.latency_p50, ms(51));
+        assert_eq!(method_stats.latency_p99, ms(100));
+        assert_eq!(method_stats.latency_max, ms(20000));
+        assert_eq!(method_stats.latency_histogram, vec![5, 5, 15, 25, 50, 0, 0, 0, 0, 0, 0, 1]);
+        assert!((method_stats.error_rate() - 1.0 / 101.0).abs() < 1e-9);
+    }
+}
@@ -1,4 +1,9 @@
+pub mod analytics;
+pub mod fiat_payments;
+pub mod instrumentation;
+pub mod notifications;
 pub mod payments;
 pub mod saga;
+pub mod storage;
 pub mod stores;
 pub mod stripe;
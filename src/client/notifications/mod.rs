@@ -0,0 +1,113 @@
+mod error;
+mod types;
+
+use failure::Fail;
+use futures::{prelude::*, Future};
+use hyper::{Headers, Method};
+use stq_http::client::HttpClient;
+
+pub use self::error::*;
+pub use self::types::{
+    BillingInfoChangeEmail, FeeDunningEmail, InvoiceReceiptEmail, NegativeStoreBalanceEmail, PaymentFailedEmail, UnpaidFeesAmount,
+};
+
+pub trait NotificationsClient: Send + Sync + 'static {
+    fn send_payment_failed_email(&self, email: PaymentFailedEmail) -> Box<Future<Item = (), Error = Error> + Send>;
+    fn send_invoice_receipt_email(&self, email: InvoiceReceiptEmail) -> Box<Future<Item = (), Error = Error> + Send>;
+    fn send_negative_store_balance_email(&self, email: NegativeStoreBalanceEmail) -> Box<Future<Item = (), Error = Error> + Send>;
+    fn send_billing_info_change_email(&self, email: BillingInfoChangeEmail) -> Box<Future<Item = (), Error = Error> + Send>;
+    fn send_fee_dunning_email(&self, email: FeeDunningEmail) -> Box<Future<Item = (), Error = Error> + Send>;
+}
+
+#[derive(Clone)]
+pub struct NotificationsClientImpl<C: HttpClient + Clone> {
+    client: C,
+    url: String,
+}
+
+impl<C: HttpClient + Clone + Send> NotificationsClientImpl<C> {
+    pub fn new(client: C, url: String) -> Self {
+        Self { client, url }
+    }
+}
+
+impl<C: HttpClient + Clone> NotificationsClient for NotificationsClientImpl<C> {
+    fn send_payment_failed_email(&self, email: PaymentFailedEmail) -> Box<Future<Item = (), Error = Error> + Send> {
+        let NotificationsClientImpl { client, url } = self.clone();
+
+        let fut = serde_json::to_string(&email)
+            .map_err(ectx!(ErrorSource::SerdeJson, ErrorKind::Internal => email))
+            .into_future()
+            .and_then(move |body| {
+                let url = format!("{}/users/billing/payment-failed", url);
+                client
+                    .request_json::<()>(Method::Post, url.clone(), Some(body.clone()), None)
+                    .map_err(ectx!(ErrorSource::StqHttp, ErrorKind::Internal => Method::Post, url, Some(body), None as Option<Headers>))
+            });
+
+        Box::new(fut)
+    }
+
+    fn send_invoice_receipt_email(&self, email: InvoiceReceiptEmail) -> Box<Future<Item = (), Error = Error> + Send> {
+        let NotificationsClientImpl { client, url } = self.clone();
+
+        let fut = serde_json::to_string(&email)
+            .map_err(ectx!(ErrorSource::SerdeJson, ErrorKind::Internal => email))
+            .into_future()
+            .and_then(move |body| {
+                let url = format!("{}/users/billing/invoice-receipt", url);
+                client
+                    .request_json::<()>(Method::Post, url.clone(), Some(body.clone()), None)
+                    .map_err(ectx!(ErrorSource::StqHttp, ErrorKind::Internal => Method::Post, url, Some(body), None as Option<Headers>))
+            });
+
+        Box::new(fut)
+    }
+
+    fn send_negative_store_balance_email(&self, email: NegativeStoreBalanceEmail) -> Box<Future<Item = (), Error = Error> + Send> {
+        let NotificationsClientImpl { client, url } = self.clone();
+
+        let fut = serde_json::to_string(&email)
+            .map_err(ectx!(ErrorSource::SerdeJson, ErrorKind::Internal => email))
+            .into_future()
+            .and_then(move |body| {
+                let url = format!("{}/users/billing/negative-store-balance", url);
+                client
+                    .request_json::<()>(Method::Post, url.clone(), Some(body.clone()), None)
+                    .map_err(ectx!(ErrorSource::StqHttp, ErrorKind::Internal => Method::Post, url, Some(body), None as Option<Headers>))
+            });
+
+        Box::new(fut)
+    }
+    fn send_billing_info_change_email(&self, email: BillingInfoChangeEmail) -> Box<Future<Item = (), Error = Error> + Send> {
+        let NotificationsClientImpl { client, url } = self.clone();
+
+        let fut = serde_json::to_string(&email)
+            .map_err(ectx!(ErrorSource::SerdeJson, ErrorKind::Internal => email))
+            .into_future()
+            .and_then(move |body| {
+                let url = format!("{}/users/billing/billing-info-change", url);
+                client
+                    .request_json::<()>(Method::Post, url.clone(), Some(body.clone()), None)
+                    .map_err(ectx!(ErrorSource::StqHttp, ErrorKind::Internal => Method::Post, url, Some(body), None as Option<Headers>))
+            });
+
+        Box::new(fut)
+    }
+
+    fn send_fee_dunning_email(&self, email: FeeDunningEmail) -> Box<Future<Item = (), Error = Error> + Send> {
+        let NotificationsClientImpl { client, url } = self.clone();
+
+        let fut = serde_json::to_string(&email)
+            .map_err(ectx!(ErrorSource::SerdeJson, ErrorKind::Internal => email))
+            .into_future()
+            .and_then(move |body| {
+                let url = format!("{}/users/billing/fee-dunning", url);
+                client
+                    .request_json::<()>(Method::Post, url.clone(), Some(body.clone()), None)
+                    .map_err(ectx!(ErrorSource::StqHttp, ErrorKind::Internal => Method::Post, url, Some(body), None as Option<Headers>))
+            });
+
+        Box::new(fut)
+    }
+}
@@ -0,0 +1,79 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+
+use models::invoice_v2::InvoiceId;
+use models::order_v2::StoreId;
+use models::{
+    BillingInfoChangeId, BillingInfoChangeStatus, Currency, DunningAction, InvoiceReceipt, NegativeStoreBalanceId, PaymentRecoveryId,
+    UserId,
+};
+
+/// Email asking the buyer to retry a payment that failed
+#[derive(Debug, Clone, Serialize)]
+pub struct PaymentFailedEmail {
+    pub payment_recovery_id: PaymentRecoveryId,
+    pub user_id: UserId,
+    pub email: Option<String>,
+    pub invoice_id: InvoiceId,
+    pub currency: Currency,
+    pub amount: BigDecimal,
+    /// Reason reported by the payment provider
+    pub failure_message: Option<String>,
+    pub retry_url: String,
+}
+
+/// Receipt of a paid crypto invoice, rendered in `locale`. `receipt` carries the same data for custom templates
+#[derive(Debug, Clone, Serialize)]
+pub struct InvoiceReceiptEmail {
+    pub user_id: UserId,
+    pub email: Option<String>,
+    pub invoice_id: InvoiceId,
+    pub locale: String,
+    pub subject: String,
+    pub text: String,
+    pub receipt: InvoiceReceipt,
+}
+
+/// Alert to a financial manager that a store owes more in `currency` than its unpaid orders cover
+#[derive(Debug, Clone, Serialize)]
+pub struct NegativeStoreBalanceEmail {
+    pub negative_store_balance_id: NegativeStoreBalanceId,
+    pub user_id: UserId,
+    pub store_id: StoreId,
+    pub currency: Currency,
+    pub amount: BigDecimal,
+    pub detected_at: NaiveDateTime,
+}
+
+/// Notice to the store owner of a change of the store billing info, sent when the change is requested and when it is reviewed
+#[derive(Debug, Clone, Serialize)]
+pub struct BillingInfoChangeEmail {
+    pub billing_info_change_id: BillingInfoChangeId,
+    pub user_id: UserId,
+    pub store_id: StoreId,
+    pub status: BillingInfoChangeStatus,
+    pub requested_by: UserId,
+    pub requested_at: NaiveDateTime,
+    pub rejection_reason: Option<String>,
+}
+
+/// Step of the fee dunning sent to the store owner: a reminder of the unpaid fees, a warning of the suspension at `suspension_at`
+/// or a notice that the store has been suspended
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeDunningEmail {
+    pub user_id: UserId,
+    pub store_id: StoreId,
+    pub action: DunningAction,
+    pub unpaid_fees_count: usize,
+    pub unpaid_amounts: Vec<UnpaidFeesAmount>,
+    /// Time of the failed charge the dunning started with
+    pub unpaid_since: NaiveDateTime,
+    pub suspension_at: Option<NaiveDateTime>,
+}
+
+/// Total of the unpaid fees in `currency`
+#[derive(Debug, Clone, Serialize)]
+pub struct UnpaidFeesAmount {
+    pub currency: Currency,
+    pub amount: BigDecimal,
+}
@@ -17,6 +17,8 @@ pub enum ErrorKind {
     Internal,
     #[fail(display = "payments client error - unprocessable input")]
     Validation(serde_json::Value),
+    #[fail(display = "payments client error - payments gateway unavailable")]
+    Unavailable,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
@@ -0,0 +1,250 @@
+//! Keeps callers from hanging on a slow or failing Payments gateway: every request is given a deadline,
+//! failed reads are retried and a circuit breaker fails requests right away while the gateway is down
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use failure::Fail;
+use futures::future::{self, Loop};
+use futures::Future;
+use tokio_timer::{timeout, Timeout};
+use uuid::Uuid;
+
+use config;
+use models::order_v2::ExchangeId;
+
+use super::error::*;
+use super::{
+    Account, CreateAccount, CreateExternalTransaction, CreateInternalTransaction, FeesResponse, GetFees, GetRate, PaymentsClient, Rate,
+    RateRefresh, TransactionsResponse,
+};
+
+/// Deadline and retries of the requests to the gateway
+#[derive(Debug, Clone, Copy)]
+pub struct GatewayPolicy {
+    pub request_timeout: Duration,
+    pub get_retries: u32,
+}
+
+impl From<config::PaymentsGateway> for GatewayPolicy {
+    fn from(config: config::PaymentsGateway) -> Self {
+        GatewayPolicy {
+            request_timeout: Duration::from_millis(config.request_timeout_ms),
+            get_retries: config.get_retries,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BreakerState {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// A trial request is in flight, another one is let through if it never completes
+    HalfOpen {
+        until: Instant,
+    },
+}
+
+/// Shared by all clients of the gateway, so that every request counts
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, reset_timeout: Duration) -> Self {
+        CircuitBreaker {
+            failure_threshold,
+            reset_timeout,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        }
+    }
+
+    pub fn from_config(config: &config::PaymentsGateway) -> Self {
+        CircuitBreaker::new(
+            config.circuit_breaker_failures,
+            Duration::from_secs(config.circuit_breaker_reset_sec),
+        )
+    }
+
+    /// Returns false while the breaker is open and the request must not be sent
+    fn try_acquire(&self, now: Instant) -> bool {
+        if self.failure_threshold == 0 {
+            return true;
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match *state {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { until } | BreakerState::HalfOpen { until } if now >= until => {
+                *state = BreakerState::HalfOpen {
+                    until: now + self.reset_timeout,
+                };
+                true
+            }
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => false,
+        }
+    }
+
+    fn record_success(&self) {
+        *self.state.lock().unwrap_or_else(PoisonError::into_inner) = BreakerState::Closed { failures: 0 };
+    }
+
+    fn record_failure(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        *state = match *state {
+            BreakerState::Closed { failures } if failures + 1 < self.failure_threshold => BreakerState::Closed { failures: failures + 1 },
+            _ => BreakerState::Open {
+                until: now + self.reset_timeout,
+            },
+        };
+    }
+}
+
+/// Client applying the gateway policy and the circuit breaker to the requests of the wrapped one
+#[derive(Clone)]
+pub struct GuardedPaymentsClient<P: PaymentsClient + Clone> {
+    inner: P,
+    policy: GatewayPolicy,
+    circuit_breaker: Arc<CircuitBreaker>,
+}
+
+impl<P: PaymentsClient + Clone> GuardedPaymentsClient<P> {
+    pub fn new(inner: P, policy: GatewayPolicy, circuit_breaker: Arc<CircuitBreaker>) -> Self {
+        Self {
+            inner,
+            policy,
+            circuit_breaker,
+        }
+    }
+
+    fn guard<T, F>(&self, retries: u32, request: F) -> Box<Future<Item = T, Error = Error> + Send>
+    where
+        T: Send + 'static,
+        F: Fn(&P) -> Box<Future<Item = T, Error = Error> + Send> + Send + 'static,
+    {
+        if !self.circuit_breaker.try_acquire(Instant::now()) {
+            let e = format_err!("Circuit breaker of the payments gateway is open");
+            return Box::new(future::err(ectx!(err e, ErrorKind::Unavailable)));
+        }
+
+        let inner = self.inner.clone();
+        let request_timeout = self.policy.request_timeout;
+        let circuit_breaker = self.circuit_breaker.clone();
+
+        let fut = future::loop_fn(retries, move |retries_left| {
+            Timeout::new(request(&inner), request_timeout)
+                .map_err(move |e| from_timeout_error(e, request_timeout))
+                .then(move |res| match res {
+                    Err(ref e) if is_gateway_failure(e) && retries_left > 0 => {
+                        warn!("Retrying failed payments gateway request, {} retries left: {}", retries_left, e);
+                        Ok(Loop::Continue(retries_left - 1))
+                    }
+                    res => res.map(Loop::Break),
+                })
+        })
+        .then(move |res| {
+            match res {
+                Err(ref e) if is_gateway_failure(e) => circuit_breaker.record_failure(Instant::now()),
+                _ => circuit_breaker.record_success(),
+            };
+            res
+        });
+
+        Box::new(fut)
+    }
+}
+
+/// Errors that tell the gateway is down or misbehaving, rejected input doesn't count
+fn is_gateway_failure(e: &Error) -> bool {
+    match e.kind() {
+        ErrorKind::Internal | ErrorKind::Unavailable => true,
+        ErrorKind::MalformedInput | ErrorKind::Unauthorized | ErrorKind::Validation(_) => false,
+    }
+}
+
+fn from_timeout_error(e: timeout::Error<Error>, request_timeout: Duration) -> Error {
+    if e.is_elapsed() {
+        let e = format_err!("Payments gateway didn't respond in {:?}", request_timeout);
+        ectx!(err e, ErrorKind::Unavailable)
+    } else if e.is_timer() {
+        let e = format_err!("Timer of the payments gateway request failed");
+        ectx!(err e, ErrorKind::Internal)
+    } else {
+        e.into_inner().expect("Timeout error is either elapsed, timer or inner")
+    }
+}
+
+impl<P: PaymentsClient + Clone> PaymentsClient for GuardedPaymentsClient<P> {
+    fn get_account(&self, account_id: Uuid) -> Box<Future<Item = Account, Error = Error> + Send> {
+        self.guard(self.policy.get_retries, move |inner| inner.get_account(account_id))
+    }
+
+    fn list_accounts(&self) -> Box<Future<Item = Vec<Account>, Error = Error> + Send> {
+        self.guard(self.policy.get_retries, |inner| inner.list_accounts())
+    }
+
+    fn create_account(&self, input: CreateAccount) -> Box<Future<Item = Account, Error = Error> + Send> {
+        self.guard(0, move |inner| inner.create_account(input.clone()))
+    }
+
+    fn delete_account(&self, account_id: Uuid) -> Box<Future<Item = (), Error = Error> + Send> {
+        self.guard(0, move |inner| inner.delete_account(account_id))
+    }
+
+    fn get_rate(&self, input: GetRate) -> Box<Future<Item = Rate, Error = Error> + Send> {
+        self.guard(0, move |inner| inner.get_rate(input.clone()))
+    }
+
+    fn refresh_rate(&self, exchange_id: ExchangeId) -> Box<Future<Item = RateRefresh, Error = Error> + Send> {
+        self.guard(0, move |inner| inner.refresh_rate(exchange_id))
+    }
+
+    fn get_fees(&self, input: GetFees) -> Box<Future<Item = FeesResponse, Error = Error> + Send> {
+        self.guard(0, move |inner| inner.get_fees(input.clone()))
+    }
+
+    fn get_transaction(&self, tx_id: Uuid) -> Box<Future<Item = Option<TransactionsResponse>, Error = Error> + Send> {
+        self.guard(self.policy.get_retries, move |inner| inner.get_transaction(tx_id))
+    }
+
+    fn create_external_transaction(&self, input: CreateExternalTransaction) -> Box<Future<Item = (), Error = Error> + Send> {
+        self.guard(0, move |inner| inner.create_external_transaction(input.clone()))
+    }
+
+    fn create_internal_transaction(&self, input: CreateInternalTransaction) -> Box<Future<Item = (), Error = Error> + Send> {
+        self.guard(0, move |inner| inner.create_internal_transaction(input.clone()))
+    }
+
+    fn ping(&self) -> Box<Future<Item = (), Error = Error> + Send> {
+        self.guard(0, |inner| inner.ping())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn circuit_breaker_opens_after_consecutive_failures_and_lets_a_trial_through() {
+        let now = Instant::now();
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+
+        breaker.record_failure(now);
+        assert!(breaker.try_acquire(now));
+        breaker.record_failure(now);
+        assert!(!breaker.try_acquire(now));
+
+        let after_reset = now + Duration::from_secs(30);
+        assert!(breaker.try_acquire(after_reset));
+        assert!(!breaker.try_acquire(after_reset));
+
+        breaker.record_success();
+        assert!(breaker.try_acquire(after_reset));
+    }
+}
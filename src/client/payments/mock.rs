@@ -1,7 +1,7 @@
 use bigdecimal::BigDecimal;
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use futures::{future, Future, IntoFuture};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 use uuid::Uuid;
 
@@ -11,11 +11,21 @@ use super::PaymentsClient;
 use models::order_v2::ExchangeId;
 use models::*;
 
+/// Gateway operation whose failure can be simulated with `MockPaymentsClient::fail`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MockOperation {
+    CreateAccount,
+    GetRate,
+    CreateTransaction,
+}
+
 #[derive(Clone)]
 struct State {
     accounts: HashMap<Uuid, Account>,
     txs: HashMap<Uuid, TransactionsResponse>,
     rates: HashMap<Uuid, Rate>,
+    fixed_rates: HashMap<(TureCurrency, TureCurrency), BigDecimal>,
+    failing_operations: HashSet<MockOperation>,
 }
 
 impl Default for State {
@@ -24,6 +34,18 @@ impl Default for State {
             accounts: HashMap::default(),
             txs: HashMap::default(),
             rates: HashMap::default(),
+            fixed_rates: HashMap::default(),
+            failing_operations: HashSet::default(),
+        }
+    }
+}
+
+impl State {
+    fn check(&self, operation: MockOperation) -> Result<(), Error> {
+        if self.failing_operations.contains(&operation) {
+            Err(ErrorKind::Internal.into())
+        } else {
+            Ok(())
         }
     }
 }
@@ -41,6 +63,39 @@ impl Default for MockPaymentsClient {
     }
 }
 
+impl MockPaymentsClient {
+    /// Makes the gateway quote `rate` for exchanges from `from` to `to` instead of the default one
+    pub fn set_rate(&self, from: TureCurrency, to: TureCurrency, rate: BigDecimal) {
+        let mut state = self.state.lock().unwrap();
+        (*state).fixed_rates.insert((from, to), rate);
+    }
+
+    /// Sets the balance of an existing account, e.g. to simulate an incoming transaction
+    pub fn set_balance(&self, account_id: Uuid, balance: Amount) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(account) = (*state).accounts.get_mut(&account_id) {
+            account.balance = balance;
+        }
+    }
+
+    /// Makes every subsequent call of `operation` fail until `recover` is called
+    pub fn fail(&self, operation: MockOperation) {
+        let mut state = self.state.lock().unwrap();
+        (*state).failing_operations.insert(operation);
+    }
+
+    pub fn recover(&self, operation: MockOperation) {
+        let mut state = self.state.lock().unwrap();
+        (*state).failing_operations.remove(&operation);
+    }
+
+    /// Transactions created through the gateway so far
+    pub fn transactions(&self) -> Vec<TransactionsResponse> {
+        let state = self.state.lock().unwrap();
+        (*state).txs.values().cloned().collect()
+    }
+}
+
 impl PaymentsClient for MockPaymentsClient {
     fn get_account(&self, account_id: Uuid) -> Box<Future<Item = Account, Error = Error> + Send> {
         let state = self.state.clone();
@@ -65,6 +120,10 @@ impl PaymentsClient for MockPaymentsClient {
     }
 
     fn create_account(&self, input: CreateAccount) -> Box<Future<Item = Account, Error = Error> + Send> {
+        if let Err(e) = self.state.lock().unwrap().check(MockOperation::CreateAccount) {
+            return Box::new(future::err(e));
+        }
+
         let CreateAccount {
             id,
             currency,
@@ -114,14 +173,16 @@ impl PaymentsClient for MockPaymentsClient {
             amount,
         } = input;
 
-        let rate = match (from, to) {
-            (TureCurrency::Stq, TureCurrency::Btc) => BigDecimal::from(0.00000007),
-            (TureCurrency::Stq, TureCurrency::Eth) => BigDecimal::from(0.000001),
-            (TureCurrency::Btc, TureCurrency::Stq) => BigDecimal::from(1.0 / 0.00000007),
-            (TureCurrency::Btc, TureCurrency::Eth) => BigDecimal::from(26),
-            (TureCurrency::Eth, TureCurrency::Stq) => BigDecimal::from(1.0 / 0.000001),
-            (TureCurrency::Eth, TureCurrency::Btc) => BigDecimal::from(0.04),
-            _ => BigDecimal::from(1),
+        let state = self.state.clone();
+        let mut state = state.lock().unwrap();
+
+        if let Err(e) = (*state).check(MockOperation::GetRate) {
+            return Box::new(future::err(e));
+        }
+
+        let rate = match (*state).fixed_rates.get(&(from, to)) {
+            Some(rate) => rate.clone(),
+            None => default_rate(from, to),
         };
 
         let rate = Rate {
@@ -135,8 +196,6 @@ impl PaymentsClient for MockPaymentsClient {
             updated_at: Utc::now().naive_utc(),
         };
 
-        let state = self.state.clone();
-        let mut state = state.lock().unwrap();
         (*state).rates.insert(id, rate.clone());
 
         Box::new(future::ok(rate))
@@ -192,6 +251,10 @@ impl PaymentsClient for MockPaymentsClient {
     }
 
     fn create_external_transaction(&self, input: CreateExternalTransaction) -> Box<Future<Item = (), Error = Error> + Send> {
+        if let Err(e) = self.state.lock().unwrap().check(MockOperation::CreateTransaction) {
+            return Box::new(future::err(e));
+        }
+
         let CreateExternalTransaction {
             id,
             from,
@@ -237,16 +300,14 @@ impl PaymentsClient for MockPaymentsClient {
             let state = self.state.clone();
             let mut state = state.lock().unwrap();
 
+            (*state).check(MockOperation::CreateTransaction)?;
+
             let mut from_acct = (*state)
                 .accounts
                 .get(&from)
                 .cloned()
                 .ok_or(validation_err("missing 'from' account"))?;
-            let mut to_acct = (*state)
-                .accounts
-                .get(&from)
-                .cloned()
-                .ok_or(validation_err("missing 'to' account"))?;
+            let mut to_acct = (*state).accounts.get(&to).cloned().ok_or(validation_err("missing 'to' account"))?;
 
             let currency = if from_acct.currency != to_acct.currency {
                 return Err(validation_err("accounts have different currencies"));
@@ -289,4 +350,20 @@ impl PaymentsClient for MockPaymentsClient {
 
         Box::new(result_fn().into_future())
     }
+
+    fn ping(&self) -> Box<Future<Item = (), Error = Error> + Send> {
+        Box::new(future::ok(()))
+    }
+}
+
+fn default_rate(from: TureCurrency, to: TureCurrency) -> BigDecimal {
+    match (from, to) {
+        (TureCurrency::Stq, TureCurrency::Btc) => BigDecimal::from(0.00000007),
+        (TureCurrency::Stq, TureCurrency::Eth) => BigDecimal::from(0.000001),
+        (TureCurrency::Btc, TureCurrency::Stq) => BigDecimal::from(1.0 / 0.00000007),
+        (TureCurrency::Btc, TureCurrency::Eth) => BigDecimal::from(26),
+        (TureCurrency::Eth, TureCurrency::Stq) => BigDecimal::from(1.0 / 0.000001),
+        (TureCurrency::Eth, TureCurrency::Btc) => BigDecimal::from(0.04),
+        _ => BigDecimal::from(1),
+    }
 }
@@ -1,5 +1,8 @@
 mod error;
+pub mod gateway;
 pub mod mock;
+#[cfg(feature = "payments-stub")]
+pub mod stub;
 mod types;
 
 use chrono::Utc;
@@ -45,6 +48,9 @@ pub trait PaymentsClient: Send + Sync + 'static {
     fn create_external_transaction(&self, input: CreateExternalTransaction) -> Box<Future<Item = (), Error = Error> + Send>;
 
     fn create_internal_transaction(&self, input: CreateInternalTransaction) -> Box<Future<Item = (), Error = Error> + Send>;
+
+    /// Performs a cheap authenticated request to check that the gateway is reachable
+    fn ping(&self) -> Box<Future<Item = (), Error = Error> + Send>;
 }
 
 impl<T: ?Sized + PaymentsClient> PaymentsClient for Arc<T> {
@@ -87,6 +93,10 @@ impl<T: ?Sized + PaymentsClient> PaymentsClient for Arc<T> {
     fn create_internal_transaction(&self, input: CreateInternalTransaction) -> Box<Future<Item = (), Error = Error> + Send> {
         (*self.clone()).create_internal_transaction(input)
     }
+
+    fn ping(&self) -> Box<Future<Item = (), Error = Error> + Send> {
+        (*self.clone()).ping()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -351,4 +361,13 @@ impl<C: Clone + HttpClient> PaymentsClient for PaymentsClientImpl<C> {
 
         Box::new(fut)
     }
+
+    fn ping(&self) -> Box<Future<Item = (), Error = Error> + Send> {
+        let query = format!("/v1/users/{}/accounts?offset=0&limit=1", self.user_id);
+        Box::new(
+            self.request_with_auth::<_, serde_json::Value>(Method::Get, query.clone(), json!({}))
+                .map_err(ectx!(ErrorKind::Internal => Method::Get, query, json!({})))
+                .map(|_| ()),
+        )
+    }
 }
@@ -0,0 +1,265 @@
+//! HTTP stub of the Payments gateway backed by `MockPaymentsClient`.
+//! Lets the real `PaymentsClientImpl` be exercised end to end without a live gateway.
+//! Request signatures and tokens are not verified
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+use bigdecimal::ToPrimitive;
+use futures::{future, Future, Stream};
+use hyper::header::ContentType;
+use hyper::server::{Http, Request, Response, Service};
+use hyper::{self, Method, StatusCode};
+use serde::Serialize;
+use serde_json;
+use tokio_core::reactor::Handle;
+use uuid::Uuid;
+
+use super::error::{Error, ErrorKind};
+use super::mock::MockPaymentsClient;
+use super::types::AccountResponse;
+use super::{
+    Account, CreateAccount, CreateExternalTransaction, CreateInternalTransaction, CreateTransactionRequestBody, GetFees, GetRate,
+    GetRateResponse, PaymentsClient, Rate, RefreshRateResponse,
+};
+use models::order_v2::ExchangeId;
+use models::{Amount, WalletAddress};
+
+type StubFuture = Box<Future<Item = Response, Error = hyper::Error>>;
+
+#[derive(Clone)]
+pub struct PaymentsStub {
+    client: MockPaymentsClient,
+    user_id: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RefreshRate {
+    rate_id: ExchangeId,
+}
+
+impl PaymentsStub {
+    /// `user_id` is the owner reported for the accounts, it must match the one in the JWT the client is configured with
+    pub fn new(client: MockPaymentsClient, user_id: u32) -> Self {
+        PaymentsStub { client, user_id }
+    }
+
+    /// Starts serving the stub on `address` and returns the address it is listening on
+    pub fn serve(self, address: &SocketAddr, handle: &Handle) -> Result<SocketAddr, hyper::Error> {
+        let serve = Http::new().serve_addr_handle(address, handle, move || Ok(self.clone()))?;
+        let local_address = serve.incoming_ref().local_addr();
+
+        let handle_clone = handle.clone();
+        handle.spawn(
+            serve
+                .for_each(move |conn| {
+                    handle_clone.spawn(conn.map(|_| ()).map_err(|why| error!("Payments stub error: {:?}", why)));
+                    Ok(())
+                })
+                .map_err(|_| ()),
+        );
+
+        Ok(local_address)
+    }
+
+    fn route(&self, method: Method, path: String, body: Vec<u8>) -> StubFuture {
+        let segments = path.trim_matches('/').split('/').map(String::from).collect::<Vec<_>>();
+        let segments = segments.iter().map(String::as_str).collect::<Vec<_>>();
+
+        match (method, segments.as_slice()) {
+            (Method::Get, ["v1", "accounts", account_id]) => match Uuid::from_str(account_id) {
+                Ok(account_id) => {
+                    let user_id = self.user_id;
+                    respond(
+                        self.client
+                            .get_account(account_id)
+                            .map(move |account| account_response(account, user_id)),
+                    )
+                }
+                Err(_) => not_found(),
+            },
+            (Method::Delete, ["v1", "accounts", account_id]) => match Uuid::from_str(account_id) {
+                Ok(account_id) => respond(self.client.delete_account(account_id)),
+                Err(_) => not_found(),
+            },
+            (Method::Get, ["v1", "users", _, "accounts"]) => {
+                let user_id = self.user_id;
+                respond(self.client.list_accounts().map(move |accounts| {
+                    accounts
+                        .into_iter()
+                        .map(|account| account_response(account, user_id))
+                        .collect::<Vec<_>>()
+                }))
+            }
+            (Method::Post, ["v1", "users", _, "accounts"]) => match serde_json::from_slice::<CreateAccount>(&body) {
+                Ok(input) => {
+                    let user_id = self.user_id;
+                    respond(
+                        self.client
+                            .create_account(input)
+                            .map(move |account| account_response(account, user_id)),
+                    )
+                }
+                Err(_) => bad_request(),
+            },
+            (Method::Post, ["v1", "rate"]) => match serde_json::from_slice::<GetRate>(&body) {
+                Ok(input) => respond(self.client.get_rate(input).map(rate_response)),
+                Err(_) => bad_request(),
+            },
+            (Method::Post, ["v1", "rate", "refresh"]) => match serde_json::from_slice::<RefreshRate>(&body) {
+                Ok(RefreshRate { rate_id }) => respond(self.client.refresh_rate(rate_id).map(|rate_refresh| RefreshRateResponse {
+                    rate: rate_response(rate_refresh.rate),
+                    is_new_rate: rate_refresh.is_new_rate,
+                })),
+                Err(_) => bad_request(),
+            },
+            (Method::Post, ["v1", "fees"]) => match serde_json::from_slice::<GetFees>(&body) {
+                Ok(input) => respond(self.client.get_fees(input)),
+                Err(_) => bad_request(),
+            },
+            (Method::Get, ["v1", "transactions", tx_id]) => match Uuid::from_str(tx_id) {
+                Ok(tx_id) => respond(self.client.get_transaction(tx_id)),
+                Err(_) => not_found(),
+            },
+            (Method::Post, ["v1", "transactions"]) => match serde_json::from_slice::<CreateTransactionRequestBody>(&body) {
+                Ok(input) => self.create_transaction(input),
+                Err(_) => bad_request(),
+            },
+            _ => not_found(),
+        }
+    }
+
+    fn create_transaction(&self, input: CreateTransactionRequestBody) -> StubFuture {
+        let CreateTransactionRequestBody {
+            id,
+            from,
+            to,
+            to_type,
+            to_currency,
+            value,
+            fee,
+            ..
+        } = input;
+
+        let (amount, fee) = match (Amount::from_str(&value), Amount::from_str(&fee)) {
+            (Ok(amount), Ok(fee)) => (amount, fee),
+            _ => return bad_request(),
+        };
+
+        let fut = match to_type.as_str() {
+            "account" => match Uuid::from_str(&to) {
+                Ok(to) => self
+                    .client
+                    .create_internal_transaction(CreateInternalTransaction { id, from, to, amount }),
+                Err(_) => return bad_request(),
+            },
+            "address" => self.client.create_external_transaction(CreateExternalTransaction {
+                id,
+                from,
+                to: WalletAddress::new(to),
+                amount,
+                currency: to_currency,
+                fee,
+            }),
+            _ => return bad_request(),
+        };
+
+        let client = self.client.clone();
+        respond(fut.and_then(move |_| client.get_transaction(id)))
+    }
+}
+
+impl Service for PaymentsStub {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = StubFuture;
+
+    fn call(&self, req: Request) -> Self::Future {
+        let self_ = self.clone();
+        let method = req.method().clone();
+        let path = req.path().to_string();
+
+        Box::new(req.body().concat2().and_then(move |body| self_.route(method, path, body.to_vec())))
+    }
+}
+
+fn account_response(account: Account, user_id: u32) -> AccountResponse {
+    let Account {
+        id,
+        balance,
+        currency,
+        name,
+        account_address,
+    } = account;
+
+    AccountResponse {
+        id,
+        balance: balance.to_string(),
+        currency: currency.to_string(),
+        user_id,
+        account_address: account_address.into_inner(),
+        name,
+        erc_20_approved: true,
+    }
+}
+
+fn rate_response(rate: Rate) -> GetRateResponse {
+    let Rate {
+        id,
+        from,
+        to,
+        amount,
+        rate,
+        expiration,
+        created_at,
+        updated_at,
+    } = rate;
+
+    GetRateResponse {
+        id,
+        from,
+        to,
+        amount,
+        rate: rate.to_f64().unwrap_or_default(),
+        expiration,
+        created_at,
+        updated_at,
+    }
+}
+
+fn respond<T, F>(fut: F) -> StubFuture
+where
+    T: Serialize,
+    F: Future<Item = T, Error = Error> + 'static,
+{
+    Box::new(fut.then(|result| {
+        let response = match result {
+            Ok(value) => json_response(StatusCode::Ok, &value),
+            Err(e) => match e.kind() {
+                ErrorKind::Validation(errors) => json_response(StatusCode::UnprocessableEntity, &errors),
+                ErrorKind::MalformedInput => Response::new().with_status(StatusCode::BadRequest),
+                ErrorKind::Unauthorized => Response::new().with_status(StatusCode::Unauthorized),
+                ErrorKind::Internal => Response::new().with_status(StatusCode::InternalServerError),
+                ErrorKind::Unavailable => Response::new().with_status(StatusCode::ServiceUnavailable),
+            },
+        };
+
+        future::ok(response)
+    }))
+}
+
+fn json_response<T: Serialize>(status: StatusCode, value: &T) -> Response {
+    match serde_json::to_string(value) {
+        Ok(body) => Response::new().with_status(status).with_header(ContentType::json()).with_body(body),
+        Err(_) => Response::new().with_status(StatusCode::InternalServerError),
+    }
+}
+
+fn bad_request() -> StubFuture {
+    Box::new(future::ok(Response::new().with_status(StatusCode::BadRequest)))
+}
+
+fn not_found() -> StubFuture {
+    Box::new(future::ok(Response::new().with_status(StatusCode::NotFound)))
+}
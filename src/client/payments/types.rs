@@ -243,7 +243,7 @@ impl CreateTransactionRequestBody {
     }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct TransactionsResponse {
     pub id: Uuid,
@@ -7,10 +7,16 @@ use hyper::{Headers, Method};
 use stq_http::client::HttpClient;
 
 pub use self::error::*;
-pub use self::types::OrderStateUpdate;
+pub use self::types::{FeeStatementNotification, StoreBillingStatusNotification};
+pub use models::OrderStateUpdate;
 
 pub trait SagaClient: Send + Sync + 'static {
     fn update_order_states(&self, order_states: Vec<OrderStateUpdate>) -> Box<Future<Item = (), Error = Error> + Send>;
+    fn notify_fee_statement_generated(&self, notification: FeeStatementNotification) -> Box<Future<Item = (), Error = Error> + Send>;
+    fn notify_store_billing_status_changed(
+        &self,
+        notification: StoreBillingStatusNotification,
+    ) -> Box<Future<Item = (), Error = Error> + Send>;
 }
 
 #[derive(Clone)]
@@ -41,4 +47,39 @@ impl<C: HttpClient + Clone> SagaClient for SagaClientImpl<C> {
 
         Box::new(fut)
     }
+
+    fn notify_fee_statement_generated(&self, notification: FeeStatementNotification) -> Box<Future<Item = (), Error = Error> + Send> {
+        let SagaClientImpl { client, url } = self.clone();
+
+        let fut = serde_json::to_string(&notification)
+            .map_err(ectx!(ErrorSource::SerdeJson, ErrorKind::Internal => notification))
+            .into_future()
+            .and_then(move |body| {
+                let url = format!("{}/billing/fee_statements/notify", url);
+                client
+                    .request_json::<()>(Method::Post, url.clone(), Some(body.clone()), None)
+                    .map_err(ectx!(ErrorSource::StqHttp, ErrorKind::Internal => Method::Post, url, Some(body), None as Option<Headers>))
+            });
+
+        Box::new(fut)
+    }
+
+    fn notify_store_billing_status_changed(
+        &self,
+        notification: StoreBillingStatusNotification,
+    ) -> Box<Future<Item = (), Error = Error> + Send> {
+        let SagaClientImpl { client, url } = self.clone();
+
+        let fut = serde_json::to_string(&notification)
+            .map_err(ectx!(ErrorSource::SerdeJson, ErrorKind::Internal => notification))
+            .into_future()
+            .and_then(move |body| {
+                let url = format!("{}/billing/store_billing_status/notify", url);
+                client
+                    .request_json::<()>(Method::Post, url.clone(), Some(body.clone()), None)
+                    .map_err(ectx!(ErrorSource::StqHttp, ErrorKind::Internal => Method::Post, url, Some(body), None as Option<Headers>))
+            });
+
+        Box::new(fut)
+    }
 }
@@ -1,14 +1,27 @@
-use stq_static_resources::OrderState;
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
 
-use models::{
-    order_v2::{OrderId, StoreId},
-    UserId,
-};
+use models::{order_v2::StoreId, Currency, FeeStatementId, StoreBillingState, StoreSuspensionReason, UserId};
 
 #[derive(Debug, Clone, Serialize)]
-pub struct OrderStateUpdate {
-    pub order_id: OrderId,
+pub struct FeeStatementNotification {
+    pub fee_statement_id: FeeStatementId,
     pub store_id: StoreId,
-    pub customer_id: UserId,
-    pub status: OrderState,
+    pub store_owner_id: UserId,
+    pub currency: Currency,
+    pub period_start: NaiveDateTime,
+    pub period_end: NaiveDateTime,
+    pub total_amount: BigDecimal,
+}
+
+/// Sent whenever a store is suspended or reinstated, saga propagates it to stores
+#[derive(Debug, Clone, Serialize)]
+pub struct StoreBillingStatusNotification {
+    pub store_id: StoreId,
+    pub store_owner_id: Option<UserId>,
+    pub state: StoreBillingState,
+    pub suspension_reason: Option<StoreSuspensionReason>,
+    pub overdue_fees_count: i32,
+    pub unpaid_since: Option<NaiveDateTime>,
+    pub changed_at: NaiveDateTime,
 }
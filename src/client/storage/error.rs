@@ -0,0 +1,31 @@
+use std::fmt;
+
+use failure::{Backtrace, Context, Fail};
+use serde_json;
+
+#[derive(Debug)]
+pub struct Error {
+    inner: Context<ErrorKind>,
+}
+
+#[derive(Clone, PartialEq, Debug, Fail)]
+pub enum ErrorKind {
+    #[fail(display = "storage client error - malformed input")]
+    MalformedInput,
+    #[fail(display = "storage client error - unauthorized")]
+    Unauthorized,
+    #[fail(display = "storage client error - internal error")]
+    Internal,
+    #[fail(display = "storage client error - bad request")]
+    Validation(serde_json::Value),
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+pub enum ErrorSource {
+    #[fail(display = "storage client source - serde_json")]
+    SerdeJson,
+    #[fail(display = "storage client source - stq_http")]
+    StqHttp,
+}
+
+derive_error_impls!();
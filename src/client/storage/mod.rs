@@ -0,0 +1,39 @@
+//! Client of the storage microservice, objects are written under their key and replaced when written again
+mod error;
+
+use failure::Fail;
+use futures::Future;
+use hyper::{Headers, Method};
+use stq_http::client::HttpClient;
+
+pub use self::error::*;
+
+pub trait StorageClient: Send + Sync + 'static {
+    /// Writes the JSON body to the object at `key`
+    fn put_object(&self, key: String, body: String) -> Box<Future<Item = (), Error = Error> + Send>;
+}
+
+#[derive(Clone)]
+pub struct StorageClientImpl<C: HttpClient + Clone> {
+    client: C,
+    url: String,
+}
+
+impl<C: HttpClient + Clone + Send> StorageClientImpl<C> {
+    pub fn new(client: C, url: String) -> Self {
+        Self { client, url }
+    }
+}
+
+impl<C: HttpClient + Clone> StorageClient for StorageClientImpl<C> {
+    fn put_object(&self, key: String, body: String) -> Box<Future<Item = (), Error = Error> + Send> {
+        let StorageClientImpl { client, url } = self.clone();
+
+        let url = format!("{}/objects/{}", url.trim_end_matches('/'), key);
+        let fut = client
+            .request_json::<()>(Method::Put, url.clone(), Some(body), None)
+            .map_err(ectx!(ErrorSource::StqHttp, ErrorKind::Internal => Method::Put, url, None as Option<Headers>));
+
+        Box::new(fut)
+    }
+}
@@ -0,0 +1,215 @@
+//! Keeps the currency exchange info of the stores microservice in memory. The info is refreshed on a schedule
+//! and fetched on demand once it gets older than the refresh interval, data older than `max_age` is never served:
+//! prices and fees computed at an outdated rate are refused instead. Only validated info is kept, a response
+//! with unknown currencies or invalid rates fails the refresh and leaves the previous info in place
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+use failure::{Error as FailureError, Fail};
+use futures::{future, Future, Stream};
+use sentry::integrations::failure::capture_error;
+use tokio_timer::Interval;
+
+use stq_types::{Quantity, StoreId};
+
+use config;
+
+use super::error::*;
+use super::{CurrencyExchangeInfo, StoresClient};
+
+/// Shared by all stores clients, so that the scheduled refresh serves the requests and the event handler
+#[derive(Debug)]
+pub struct CurrencyExchangeCache {
+    refresh_interval: Duration,
+    max_age: Duration,
+    info: RwLock<Option<(Instant, CurrencyExchangeInfo)>>,
+}
+
+impl CurrencyExchangeCache {
+    pub fn new(refresh_interval: Duration, max_age: Duration) -> Self {
+        CurrencyExchangeCache {
+            refresh_interval,
+            max_age,
+            info: RwLock::new(None),
+        }
+    }
+
+    pub fn from_config(config: &config::CurrencyExchange) -> Self {
+        CurrencyExchangeCache::new(
+            Duration::from_secs(config.refresh_interval_sec),
+            Duration::from_secs(config.max_age_sec),
+        )
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        self.refresh_interval
+    }
+
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    /// Time since the cached info was fetched, `None` if it hasn't been fetched yet
+    pub fn age(&self, now: Instant) -> Option<Duration> {
+        let info = self.info.read().unwrap_or_else(PoisonError::into_inner);
+        info.as_ref().map(|(fetched_at, _)| now.duration_since(*fetched_at))
+    }
+
+    /// Latest info for the services that can do without a request, `None` once it is older than `max_age`
+    pub fn latest(&self, now: Instant) -> Option<CurrencyExchangeInfo> {
+        self.get(now, self.max_age)
+    }
+
+    /// Cached info unless it is older than `max_age`
+    fn get(&self, now: Instant, max_age: Duration) -> Option<CurrencyExchangeInfo> {
+        let info = self.info.read().unwrap_or_else(PoisonError::into_inner);
+        match *info {
+            Some((fetched_at, ref info)) if now.duration_since(fetched_at) <= max_age => Some(info.clone()),
+            _ => None,
+        }
+    }
+
+    fn set(&self, info: CurrencyExchangeInfo, now: Instant) {
+        let mut cached = self.info.write().unwrap_or_else(PoisonError::into_inner);
+        if cached.as_ref().map(|(_, cached)| cached.id.0) != Some(info.id.0) {
+            info!("Currency exchange info updated to {}", info.id.0);
+        }
+        *cached = Some((now, info));
+    }
+
+    /// Error of a request that couldn't get info fresher than `max_age`
+    fn stale_error(&self, now: Instant, cause: Error) -> Error {
+        let e = match self.age(now) {
+            Some(age) => format_err!(
+                "Currency exchange info is {} sec old, older than the allowed {} sec, and can't be refreshed: {}",
+                age.as_secs(),
+                self.max_age.as_secs(),
+                cause
+            ),
+            None => format_err!("Currency exchange info has never been fetched: {}", cause),
+        };
+        ectx!(err e, ErrorKind::Stale)
+    }
+}
+
+/// Client serving the currency exchange info of the wrapped one from the cache
+#[derive(Clone)]
+pub struct CachedStoresClient<S: StoresClient + Clone> {
+    inner: S,
+    cache: Arc<CurrencyExchangeCache>,
+}
+
+impl<S: StoresClient + Clone> CachedStoresClient<S> {
+    pub fn new(inner: S, cache: Arc<CurrencyExchangeCache>) -> Self {
+        Self { inner, cache }
+    }
+
+    /// Fetches the info from the stores microservice and caches it
+    pub fn refresh(&self) -> Box<Future<Item = CurrencyExchangeInfo, Error = Error> + Send> {
+        let cache = self.cache.clone();
+        let fut = self.inner.get_currency_exchange_info().then(move |res| match res {
+            Ok(info) => {
+                cache.set(info.clone(), Instant::now());
+                Ok(info)
+            }
+            Err(e) => Err(cache.stale_error(Instant::now(), e)),
+        });
+
+        Box::new(fut)
+    }
+}
+
+impl<S: StoresClient + Clone> StoresClient for CachedStoresClient<S> {
+    fn get_currency_exchange_info(&self) -> Box<Future<Item = CurrencyExchangeInfo, Error = Error> + Send> {
+        match self.cache.get(Instant::now(), self.cache.refresh_interval) {
+            Some(info) => Box::new(future::ok(info)),
+            None => {
+                // The scheduled refresh is late, data within `max_age` still beats failing the caller
+                let cache = self.cache.clone();
+                Box::new(self.refresh().or_else(move |e| match cache.get(Instant::now(), cache.max_age) {
+                    Some(info) => {
+                        warn!("{}", e);
+                        Ok(info)
+                    }
+                    None => Err(e),
+                }))
+            }
+        }
+    }
+
+    fn get_published_products_count(&self, store_id: StoreId) -> Box<Future<Item = Quantity, Error = Error> + Send> {
+        self.inner.get_published_products_count(store_id)
+    }
+}
+
+/// Refreshes the cached info on every tick of the refresh interval.
+/// A failed refresh is reported and retried on the next tick
+pub fn run_currency_exchange_refresh<S: StoresClient + Clone>(
+    client: CachedStoresClient<S>,
+) -> impl Future<Item = (), Error = FailureError> {
+    Interval::new(Instant::now(), client.cache.refresh_interval)
+        .map_err(FailureError::from)
+        .for_each(move |_| {
+            client.refresh().then(|res| {
+                if let Err(err) = res {
+                    let err = FailureError::from(err.context("An error occurred while refreshing the currency exchange info"));
+                    error!("{:?}", &err);
+                    capture_error(&err);
+                }
+
+                future::ok::<_, FailureError>(())
+            })
+        })
+}
+
+/// Renders the age of the cached info as Prometheus gauges, the age is absent until the info is fetched
+pub fn currency_exchange_cache_gauges(cache: &CurrencyExchangeCache, now: Instant) -> String {
+    let mut text = String::new();
+    text.push_str(
+        "# HELP billing_currency_exchange_age_seconds Time since the currency exchange info was fetched\n\
+         # TYPE billing_currency_exchange_age_seconds gauge\n",
+    );
+    if let Some(age) = cache.age(now) {
+        text.push_str(&format!("billing_currency_exchange_age_seconds {}\n", age.as_secs()));
+    }
+    text.push_str(
+        "# HELP billing_currency_exchange_max_age_seconds Age of the currency exchange info after which it is refused\n\
+         # TYPE billing_currency_exchange_max_age_seconds gauge\n",
+    );
+    text.push_str(&format!("billing_currency_exchange_max_age_seconds {}\n", cache.max_age.as_secs()));
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use stq_types::CurrencyExchangeId;
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn cache_serves_info_within_the_given_age_only() {
+        let now = Instant::now();
+        let cache = CurrencyExchangeCache::new(Duration::from_secs(60), Duration::from_secs(600));
+        assert_eq!(cache.age(now), None);
+        assert!(cache.get(now, cache.max_age()).is_none());
+
+        let info = CurrencyExchangeInfo {
+            id: CurrencyExchangeId(Uuid::new_v4()),
+            data: HashMap::new(),
+        };
+        cache.set(info.clone(), now);
+
+        let later = now + Duration::from_secs(120);
+        assert_eq!(cache.age(later), Some(Duration::from_secs(120)));
+        assert!(cache.get(later, cache.refresh_interval()).is_none());
+        assert_eq!(cache.get(later, cache.max_age()).map(|cached| cached.id.0), Some(info.id.0));
+        assert!(cache.get(now + Duration::from_secs(601), cache.max_age()).is_none());
+
+        let gauges = currency_exchange_cache_gauges(&cache, later);
+        assert!(gauges.contains("billing_currency_exchange_age_seconds 120\n"));
+        assert!(gauges.contains("billing_currency_exchange_max_age_seconds 600\n"));
+    }
+}
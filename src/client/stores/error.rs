@@ -18,6 +18,13 @@ pub enum ErrorKind {
     Internal,
     #[fail(display = "stores client error - bad request")]
     Validation(serde_json::Value),
+    #[fail(display = "stores client error - currency exchange info is stale")]
+    Stale,
+    /// Currencies of the currency exchange info that the billing doesn't support
+    #[fail(display = "stores client error - unknown currencies {:?}", _0)]
+    UnknownCurrencies(Vec<String>),
+    #[fail(display = "stores client error - invalid currency exchange info")]
+    InvalidCurrencyExchange,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
@@ -1,6 +1,8 @@
+pub mod cache;
 mod error;
 mod types;
 
+pub use self::cache::{CachedStoresClient, CurrencyExchangeCache};
 pub use self::error::*;
 pub use self::types::*;
 
@@ -9,9 +11,13 @@ use futures::Future;
 use hyper::{Headers, Method};
 use stq_http::client::HttpClient;
 use stq_http::request_util::{Currency as CurrencyHeader, FiatCurrency as FiatCurrencyHeader};
+use stq_types::{Quantity, StoreId};
 
 pub trait StoresClient: Send + Sync + 'static {
-    fn get_currency_exchange(&self) -> Box<Future<Item = CurrencyExchangeInfoRequest, Error = Error> + Send>;
+    /// Current currency exchange info, validated against the currencies of the billing
+    fn get_currency_exchange_info(&self) -> Box<Future<Item = CurrencyExchangeInfo, Error = Error> + Send>;
+    /// Number of base products of the store that are published at the moment
+    fn get_published_products_count(&self, store_id: StoreId) -> Box<Future<Item = Quantity, Error = Error> + Send>;
 }
 
 #[derive(Clone)]
@@ -34,12 +40,25 @@ fn stores_headers() -> Headers {
 }
 
 impl<C: HttpClient + Clone> StoresClient for StoresClientImpl<C> {
-    fn get_currency_exchange(&self) -> Box<Future<Item = CurrencyExchangeInfoRequest, Error = Error> + Send> {
+    fn get_currency_exchange_info(&self) -> Box<Future<Item = CurrencyExchangeInfo, Error = Error> + Send> {
         let StoresClientImpl { client, url } = self.clone();
         let url = format!("{}/currency_exchange", url);
 
         let fut = client
-            .request_json::<CurrencyExchangeInfoRequest>(Method::Get, url.clone(), None, Some(stores_headers()))
+            .request_json::<CurrencyExchangeInfoResponse>(Method::Get, url.clone(), None, Some(stores_headers()))
+            .map_err(ectx!(ErrorSource::StqHttp, ErrorKind::Internal => Method::Get, url, None as Option<Headers>))
+            .and_then(CurrencyExchangeInfo::try_from_response);
+
+        Box::new(fut)
+    }
+
+    fn get_published_products_count(&self, store_id: StoreId) -> Box<Future<Item = Quantity, Error = Error> + Send> {
+        let StoresClientImpl { client, url } = self.clone();
+        let url = format!("{}/stores/{}/products/published/count", url, store_id);
+
+        let fut = client
+            .request_json::<i32>(Method::Get, url.clone(), None, Some(stores_headers()))
+            .map(Quantity)
             .map_err(ectx!(ErrorSource::StqHttp, ErrorKind::Internal => Method::Get, url, None as Option<Headers>));
 
         Box::new(fut)
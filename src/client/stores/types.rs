@@ -1,20 +1,25 @@
 use std::collections::HashMap;
+use std::iter;
 
+use failure::Fail;
+use serde_json;
 use stq_static_resources::Currency as StqCurrency;
 use stq_types::{CurrencyExchangeId, ExchangeRate};
 
-use models::{currency::ConversionError as CurrencyConversionError, Currency};
+use models::Currency;
+
+use super::error::*;
 
-pub type ExchangeRatesRequest = HashMap<StqCurrency, ExchangeRate>;
 pub type ExchangeRates = HashMap<Currency, ExchangeRate>;
 
-pub type CurrencyExchangeDataRequest = HashMap<StqCurrency, ExchangeRatesRequest>;
 pub type CurrencyExchangeData = HashMap<Currency, ExchangeRates>;
 
+/// Currency exchange info as the stores microservice returns it. Currencies are kept as codes,
+/// so that a currency unknown to the billing is reported instead of failing the whole response
 #[derive(Clone, Debug, Deserialize)]
-pub struct CurrencyExchangeInfoRequest {
+pub struct CurrencyExchangeInfoResponse {
     pub id: CurrencyExchangeId,
-    pub data: CurrencyExchangeDataRequest,
+    pub data: HashMap<String, HashMap<String, ExchangeRate>>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -24,28 +29,109 @@ pub struct CurrencyExchangeInfo {
 }
 
 impl CurrencyExchangeInfo {
-    pub fn try_from_request(other: CurrencyExchangeInfoRequest) -> Result<Self, CurrencyConversionError> {
-        let data = other
+    /// Validates the info returned by the stores microservice: it has rates, all of its currencies are supported
+    /// by the billing and all of its rates are positive. Unknown currencies are reported all at once
+    pub fn try_from_response(response: CurrencyExchangeInfoResponse) -> Result<Self, Error> {
+        let mut unknown_currencies = response
+            .data
+            .iter()
+            .flat_map(|(code, rates)| iter::once(code).chain(rates.keys()))
+            .filter(|code| currency_from_code(code).is_none())
+            .cloned()
+            .collect::<Vec<_>>();
+        unknown_currencies.sort();
+        unknown_currencies.dedup();
+        if !unknown_currencies.is_empty() {
+            let e = format_err!(
+                "Currency exchange {} of the stores microservice has unknown currencies: {}",
+                response.id.0,
+                unknown_currencies.join(", ")
+            );
+            return Err(ectx!(err e, ErrorKind::UnknownCurrencies(unknown_currencies)));
+        }
+
+        let mut invalid_rates = response
+            .data
+            .iter()
+            .flat_map(|(code, rates)| {
+                rates
+                    .iter()
+                    .filter(|(_, rate)| !(rate.0.is_finite() && rate.0 > 0.0))
+                    .map(move |(rate_code, rate)| format!("{} to {} at {}", code, rate_code, rate.0))
+            })
+            .collect::<Vec<_>>();
+        invalid_rates.sort();
+        if response.data.is_empty() || !invalid_rates.is_empty() {
+            let e = format_err!(
+                "Currency exchange {} of the stores microservice has no rates or invalid ones: [{}]",
+                response.id.0,
+                invalid_rates.join(", ")
+            );
+            return Err(ectx!(err e, ErrorKind::InvalidCurrencyExchange));
+        }
+
+        let data = response
             .data
             .into_iter()
-            .map(|(stq_currency, stq_rates)| {
-                Currency::try_from_stq_currency(stq_currency)
-                    .map_err(|_| CurrencyConversionError::UnsupportedCurrency(stq_currency.to_string()))
-                    .and_then(|currency| try_exchange_rates_from_request(stq_rates).map(|rates| (currency, rates)))
+            .filter_map(|(code, rates)| {
+                let rates = rates
+                    .into_iter()
+                    .filter_map(|(rate_code, rate)| currency_from_code(&rate_code).map(|rate_currency| (rate_currency, rate)))
+                    .collect();
+                currency_from_code(&code).map(|currency| (currency, rates))
             })
-            .collect::<Result<CurrencyExchangeData, CurrencyConversionError>>()?;
+            .collect();
 
-        Ok(Self { id: other.id, data })
+        Ok(Self { id: response.id, data })
     }
 }
 
-pub fn try_exchange_rates_from_request(other: ExchangeRatesRequest) -> Result<ExchangeRates, CurrencyConversionError> {
-    other
-        .into_iter()
-        .map(|(stq_rate_currency, stq_rate)| {
-            Currency::try_from_stq_currency(stq_rate_currency)
-                .map_err(|_| CurrencyConversionError::UnsupportedCurrency(stq_rate_currency.to_string()))
-                .map(move |rate_currency| (rate_currency, stq_rate))
-        })
-        .collect::<Result<ExchangeRates, CurrencyConversionError>>()
+/// Billing currency of a currency code of the stores microservice
+fn currency_from_code(code: &str) -> Option<Currency> {
+    serde_json::from_value::<StqCurrency>(serde_json::Value::String(code.to_string()))
+        .ok()
+        .and_then(|stq_currency| Currency::try_from_stq_currency(stq_currency).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn response(data: Vec<(&str, Vec<(&str, f64)>)>) -> CurrencyExchangeInfoResponse {
+        CurrencyExchangeInfoResponse {
+            id: CurrencyExchangeId(Uuid::new_v4()),
+            data: data
+                .into_iter()
+                .map(|(code, rates)| {
+                    let rates = rates
+                        .into_iter()
+                        .map(|(rate_code, rate)| (rate_code.to_string(), ExchangeRate(rate)))
+                        .collect();
+                    (code.to_string(), rates)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn currency_exchange_is_validated() {
+        let stq = serde_json::to_value(StqCurrency::STQ).unwrap().as_str().unwrap().to_string();
+        let eth = serde_json::to_value(StqCurrency::ETH).unwrap().as_str().unwrap().to_string();
+
+        let info = CurrencyExchangeInfo::try_from_response(response(vec![(&stq, vec![(&stq, 1.0), (&eth, 0.0001)])])).unwrap();
+        assert_eq!(info.data[&Currency::Stq][&Currency::Eth].0, 0.0001);
+
+        let e =
+            CurrencyExchangeInfo::try_from_response(response(vec![(&stq, vec![(&stq, 1.0), ("DOGE", 2.0)]), ("XMR", vec![(&stq, 3.0)])]))
+                .unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::UnknownCurrencies(vec!["DOGE".to_string(), "XMR".to_string()]));
+
+        let e = CurrencyExchangeInfo::try_from_response(response(vec![(&stq, vec![(&stq, 1.0), (&eth, -1.0)])])).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidCurrencyExchange);
+
+        let e = CurrencyExchangeInfo::try_from_response(response(vec![])).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidCurrencyExchange);
+    }
 }
@@ -34,6 +34,8 @@ pub enum ErrorSource {
 pub enum ErrorContext {
     #[fail(display = "stripe client context - currency is not fiat")]
     Currency,
+    #[fail(display = "stripe client context - amount is out of range")]
+    Amount,
 }
 
 derive_error_impls!();
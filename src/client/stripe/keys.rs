@@ -0,0 +1,287 @@
+//! Keys of the Stripe account shared by every Stripe client and the webhook verification.
+//! They are replaced at runtime on SIGHUP, so a rotated key is picked up without a restart
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::sync::{Mutex, MutexGuard, RwLock};
+
+use stripe;
+
+use config;
+
+/// Which of the configured keys was used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StripeKeySlot {
+    Primary,
+    Secondary,
+}
+
+impl Display for StripeKeySlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StripeKeySlot::Primary => f.write_str("primary"),
+            StripeKeySlot::Secondary => f.write_str("secondary"),
+        }
+    }
+}
+
+/// Secret API key with the client using it. `fingerprint` identifies the key in logs without revealing it
+#[derive(Clone)]
+pub struct StripeApiKey {
+    pub slot: StripeKeySlot,
+    pub fingerprint: String,
+    pub client: stripe::async::Client,
+}
+
+impl StripeApiKey {
+    fn new(slot: StripeKeySlot, secret_key: &str) -> Self {
+        Self {
+            slot,
+            fingerprint: fingerprint(secret_key),
+            client: stripe::async::Client::new(secret_key.to_string()),
+        }
+    }
+}
+
+struct StripeKeySet {
+    primary: StripeApiKey,
+    secondary: Option<StripeApiKey>,
+    /// Stripe rejected the primary key, calls use the secondary one until the keys are reloaded
+    primary_rejected: bool,
+    /// Webhook signing secrets by the name they are told by in logs and metrics
+    signing_secrets: Vec<(String, String)>,
+}
+
+impl StripeKeySet {
+    fn from_config(config: &config::Stripe) -> Self {
+        let mut signing_secrets = vec![(StripeKeySlot::Primary.to_string(), config.signing_secret.clone())];
+        if let Some(ref secondary_signing_secret) = config.secondary_signing_secret {
+            signing_secrets.push((StripeKeySlot::Secondary.to_string(), secondary_signing_secret.clone()));
+        }
+        for endpoint in &config.webhook_endpoints {
+            signing_secrets.push((endpoint.name.clone(), endpoint.signing_secret.clone()));
+        }
+
+        Self {
+            primary: StripeApiKey::new(StripeKeySlot::Primary, &config.secret_key),
+            secondary: config
+                .secondary_secret_key
+                .as_ref()
+                .map(|secret_key| StripeApiKey::new(StripeKeySlot::Secondary, secret_key)),
+            primary_rejected: false,
+            signing_secrets,
+        }
+    }
+}
+
+/// Webhooks verified since the start, they are kept across reloads
+#[derive(Default)]
+struct WebhookSignatureCounts {
+    matched: BTreeMap<String, u64>,
+    unknown: u64,
+    /// A webhook signed with none of the secrets has been received since the last reload
+    unknown_since_reload: bool,
+}
+
+/// Primary and secondary keys of the Stripe account. API calls use the primary key and switch to the secondary one
+/// once Stripe rejects the primary. Webhooks are accepted when they are signed with any of the signing secrets
+pub struct StripeKeys {
+    keys: RwLock<StripeKeySet>,
+    webhook_signatures: Mutex<WebhookSignatureCounts>,
+}
+
+impl StripeKeys {
+    pub fn from_config(config: &config::Stripe) -> Self {
+        Self {
+            keys: RwLock::new(StripeKeySet::from_config(config)),
+            webhook_signatures: Mutex::new(WebhookSignatureCounts::default()),
+        }
+    }
+
+    /// Replaces the keys with the ones of the config, the next call uses the new primary key
+    pub fn reload(&self, config: &config::Stripe) {
+        let keys = StripeKeySet::from_config(config);
+        info!(
+            "Stripe keys reloaded - primary API key {}, secondary API key {}, {} webhook signing secrets",
+            keys.primary.fingerprint,
+            keys.secondary.as_ref().map(|key| key.fingerprint.as_str()).unwrap_or("none"),
+            keys.signing_secrets.len()
+        );
+
+        match self.keys.write() {
+            Ok(mut current) => *current = keys,
+            Err(poisoned) => *poisoned.into_inner() = keys,
+        }
+        self.lock_webhook_signatures().unknown_since_reload = false;
+    }
+
+    pub fn api_key(&self) -> StripeApiKey {
+        let keys = match self.keys.read() {
+            Ok(keys) => keys,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        match keys.secondary {
+            Some(ref secondary) if keys.primary_rejected => secondary.clone(),
+            _ => keys.primary.clone(),
+        }
+    }
+
+    /// Stripe rejected the key. A rejected primary key is not used until the next reload if there is a secondary one
+    pub fn reject(&self, key: &StripeApiKey) {
+        let mut keys = match self.keys.write() {
+            Ok(keys) => keys,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let is_current_primary = key.slot == StripeKeySlot::Primary && key.fingerprint == keys.primary.fingerprint;
+        if !is_current_primary || keys.primary_rejected {
+            return;
+        }
+
+        match keys.secondary {
+            Some(ref secondary) => warn!(
+                "Stripe rejected the primary API key {}, switching to the secondary API key {}",
+                key.fingerprint, secondary.fingerprint
+            ),
+            None => {
+                error!(
+                    "Stripe rejected the primary API key {} and there is no secondary one",
+                    key.fingerprint
+                );
+                return;
+            }
+        }
+        keys.primary_rejected = true;
+    }
+
+    /// Webhook signing secrets with their names - the primary one first, then the secondary one and the ones of the endpoints
+    pub fn signing_secrets(&self) -> Vec<(String, String)> {
+        match self.keys.read() {
+            Ok(keys) => keys.signing_secrets.clone(),
+            Err(poisoned) => poisoned.into_inner().signing_secrets.clone(),
+        }
+    }
+
+    /// Counts the webhook verified with the signing secret
+    pub fn record_webhook_signature(&self, secret_name: &str) {
+        *self.lock_webhook_signatures().matched.entry(secret_name.to_string()).or_insert(0) += 1;
+    }
+
+    /// Counts the webhook signed with none of the signing secrets. The first one after a reload is logged as an error,
+    /// it usually means that a new webhook endpoint has been registered in Stripe without its secret in the config
+    pub fn record_unknown_webhook_signature(&self) {
+        let mut counts = self.lock_webhook_signatures();
+        counts.unknown += 1;
+        if counts.unknown_since_reload {
+            warn!("Stripe webhook is signed with none of the configured signing secrets");
+        } else {
+            error!(
+                "Stripe webhook is signed with none of the configured signing secrets, an unknown webhook endpoint may be sending events"
+            );
+            counts.unknown_since_reload = true;
+        }
+    }
+
+    /// Webhook signature counters in the Prometheus text format
+    pub fn webhook_signature_metrics(&self) -> String {
+        let counts = self.lock_webhook_signatures();
+        let mut text = String::new();
+
+        text.push_str("# HELP billing_stripe_webhook_signatures Stripe webhooks verified by the signing secret that matched\n");
+        text.push_str("# TYPE billing_stripe_webhook_signatures counter\n");
+        for (secret_name, count) in &counts.matched {
+            text.push_str(&format!(
+                "billing_stripe_webhook_signatures{{secret=\"{}\"}} {}\n",
+                secret_name, count
+            ));
+        }
+
+        text.push_str("# HELP billing_stripe_webhook_unknown_signatures Stripe webhooks signed with none of the signing secrets\n");
+        text.push_str("# TYPE billing_stripe_webhook_unknown_signatures counter\n");
+        text.push_str(&format!("billing_stripe_webhook_unknown_signatures {}\n", counts.unknown));
+        text
+    }
+
+    fn lock_webhook_signatures(&self) -> MutexGuard<WebhookSignatureCounts> {
+        match self.webhook_signatures.lock() {
+            Ok(counts) => counts,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+/// Last four characters of the key, the way the Stripe dashboard shows it
+fn fingerprint(key: &str) -> String {
+    let suffix = key.chars().rev().take(4).collect::<Vec<_>>();
+    format!("...{}", suffix.into_iter().rev().collect::<String>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(secondary_secret_key: Option<&str>) -> config::Stripe {
+        config::Stripe {
+            public_key: "pk_test_public".to_string(),
+            secret_key: "sk_test_primary1".to_string(),
+            signing_secret: "whsec_primary".to_string(),
+            secondary_secret_key: secondary_secret_key.map(String::from),
+            secondary_signing_secret: Some("whsec_secondary".to_string()),
+            webhook_endpoints: vec![config::StripeWebhookEndpoint {
+                name: "cli".to_string(),
+                signing_secret: "whsec_cli".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn rejected_primary_key_is_replaced_with_secondary_until_reload() {
+        let keys = StripeKeys::from_config(&config(Some("sk_test_secondary2")));
+        let primary = keys.api_key();
+        assert_eq!(primary.slot, StripeKeySlot::Primary);
+        assert_eq!(primary.fingerprint, "...ary1");
+
+        keys.reject(&primary);
+        let secondary = keys.api_key();
+        assert_eq!(secondary.slot, StripeKeySlot::Secondary);
+        assert_eq!(secondary.fingerprint, "...ary2");
+
+        keys.reload(&config(Some("sk_test_secondary2")));
+        assert_eq!(keys.api_key().slot, StripeKeySlot::Primary);
+        assert_eq!(
+            keys.signing_secrets(),
+            vec![
+                ("primary".to_string(), "whsec_primary".to_string()),
+                ("secondary".to_string(), "whsec_secondary".to_string()),
+                ("cli".to_string(), "whsec_cli".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn webhook_signatures_are_counted_by_secret_across_reloads() {
+        let keys = StripeKeys::from_config(&config(None));
+        keys.record_webhook_signature("primary");
+        keys.record_webhook_signature("cli");
+        keys.record_unknown_webhook_signature();
+        keys.reload(&config(None));
+        keys.record_webhook_signature("cli");
+        keys.record_unknown_webhook_signature();
+
+        let metrics = keys.webhook_signature_metrics();
+        assert!(metrics.contains("# TYPE billing_stripe_webhook_signatures counter\n"));
+        assert!(metrics.contains("billing_stripe_webhook_signatures{secret=\"cli\"} 2\n"));
+        assert!(metrics.contains("billing_stripe_webhook_signatures{secret=\"primary\"} 1\n"));
+        assert!(metrics.contains("billing_stripe_webhook_unknown_signatures 2\n"));
+    }
+
+    #[test]
+    fn rejected_primary_key_is_kept_without_secondary() {
+        let keys = StripeKeys::from_config(&config(None));
+        let primary = keys.api_key();
+
+        keys.reject(&primary);
+
+        assert_eq!(keys.api_key().slot, StripeKeySlot::Primary);
+    }
+}
@@ -1,21 +1,29 @@
 mod error;
+mod keys;
 mod types;
+pub use self::keys::{StripeApiKey, StripeKeySlot, StripeKeys};
 pub use self::types::{NewPaymentIntent, *};
 
+use std::sync::Arc;
+
 use futures::Future;
 use futures::IntoFuture;
+use serde_json;
 use stripe::{
-    BalanceTransaction, CaptureParams, Charge, ChargeParams, Currency as StripeCurrency, Customer, CustomerParams, Deleted, Metadata,
-    PaymentIntent, PaymentIntentCaptureParams, PaymentIntentCreateParams, PaymentSourceParams, Payout, PayoutParams, Refund, RefundParams,
+    BalanceTransaction, CaptureParams, Charge, ChargeParams, Currency as StripeCurrency, Customer, CustomerParams, Deleted,
+    Error as StripeError, Headers, Metadata, PaymentIntent, PaymentIntentCaptureParams, PaymentIntentCreateParams,
+    PaymentIntentUpdateParams, PaymentSourceParams, Payout, PayoutParams, Refund, RefundParams,
 };
 
-use config;
 use models::order_v2::OrderId;
 use models::*;
 use stq_types::stripe::PaymentIntentId;
 
 pub use self::error::*;
 
+/// Customer that doesn't exist, retrieving it costs Stripe a single lookup
+const STRIPE_PING_CUSTOMER_ID: &str = "cus_billing_ping";
+
 pub trait StripeClient: Send + Sync + 'static {
     fn create_customer(&self, input: NewCustomer) -> Box<Future<Item = Customer, Error = Error> + Send>;
 
@@ -55,111 +63,198 @@ pub trait StripeClient: Send + Sync + 'static {
     fn create_payment_intent(&self, input: NewPaymentIntent) -> Box<Future<Item = PaymentIntent, Error = Error> + Send>;
 
     fn cancel_payment_intent(&self, payment_intent_id: PaymentIntentId) -> Box<Future<Item = PaymentIntent, Error = Error> + Send>;
+
+    fn update_payment_intent_receipt_email(
+        &self,
+        payment_intent_id: PaymentIntentId,
+        receipt_email: String,
+    ) -> Box<Future<Item = PaymentIntent, Error = Error> + Send>;
+
+    /// Empty description clears the one set on the payment intent
+    fn update_payment_intent_description(
+        &self,
+        payment_intent_id: PaymentIntentId,
+        description: String,
+    ) -> Box<Future<Item = PaymentIntent, Error = Error> + Send>;
+
+    /// Checks that the Stripe API is reachable, an error reported by Stripe itself counts as a response
+    fn ping(&self) -> Box<Future<Item = (), Error = Error> + Send>;
+
+    /// Creates a SetupIntent that saves a card of the customer for off-session payments,
+    /// the client confirms it with the returned client secret
+    fn create_setup_intent(&self, customer_id: CustomerId) -> Box<Future<Item = SetupIntent, Error = Error> + Send>;
+
+    /// Attaches the payment method to the customer and makes it the default one for the customer's payments
+    fn attach_payment_method(&self, customer_id: CustomerId, payment_method_id: String) -> Box<Future<Item = (), Error = Error> + Send>;
+
+    /// Removes the card from the customer's sources, the card can't be charged afterwards
+    fn delete_card(&self, customer_id: CustomerId, card_id: String) -> Box<Future<Item = (), Error = Error> + Send>;
+}
+
+#[derive(Serialize)]
+struct SetupIntentCreateParams {
+    customer: String,
+    usage: &'static str,
+}
+
+#[derive(Serialize)]
+struct PaymentMethodAttachParams {
+    customer: String,
+}
+
+#[derive(Serialize)]
+struct CustomerInvoiceSettingsParams {
+    invoice_settings: InvoiceSettingsParams,
+}
+
+#[derive(Serialize)]
+struct InvoiceSettingsParams {
+    default_payment_method: String,
 }
 
 pub struct StripeClientImpl {
-    public_key: String,
-    secret_key: String,
-    client: stripe::async::Client,
+    keys: Arc<StripeKeys>,
 }
 
 impl StripeClientImpl {
-    pub fn create_from_config(config: &config::Config) -> Self {
-        let secret_key = config.stripe.secret_key.clone();
-        let client = stripe::async::Client::new(secret_key.clone());
-        Self {
-            public_key: config.stripe.public_key.clone(),
-            secret_key,
-            client,
+    pub fn new(keys: Arc<StripeKeys>) -> Self {
+        Self { keys }
+    }
+
+    /// Key the call is made with, read on every call so that a reload applies to the next one
+    fn api_key(&self, operation: &str) -> StripeApiKey {
+        let key = self.keys.api_key();
+        info!("stripe {} with the {} API key {}", operation, key.slot, key.fingerprint);
+        key
+    }
+
+    /// Converts the error of a call, an authentication error makes the following calls use the secondary key
+    fn key_error(&self, key: &StripeApiKey) -> impl FnOnce(StripeError) -> Error + Send {
+        let keys = self.keys.clone();
+        let key = key.clone();
+
+        move |e| {
+            if is_authentication_error(&e) {
+                keys.reject(&key);
+            }
+            Error::from(e)
         }
     }
 }
 
 impl StripeClient for StripeClientImpl {
     fn create_customer(&self, input: NewCustomer) -> Box<Future<Item = Customer, Error = Error> + Send> {
+        let key = self.api_key("create_customer");
+        let on_error = self.key_error(&key);
+        let client = idempotent_client(&key.client, input.idempotency_key.clone());
         Box::new(
             Customer::create(
-                &self.client,
+                &client,
                 CustomerParams {
-                    email: Some(&input.email),
+                    email: input.email.as_ref().map(|s| s.as_str()),
                     ..Default::default()
                 },
             )
-            .map_err(From::from),
+            .map_err(on_error),
         )
     }
 
     fn create_customer_with_source(&self, input: NewCustomerWithSource) -> Box<Future<Item = Customer, Error = Error> + Send> {
+        let key = self.api_key("create_customer_with_source");
+        let on_error = self.key_error(&key);
+        let client = idempotent_client(&key.client, input.idempotency_key.clone());
         Box::new(
             Customer::create(
-                &self.client,
+                &client,
                 CustomerParams {
                     email: input.email.as_ref().map(|s| s.as_str()),
                     source: Some(PaymentSourceParams::Token(input.token)),
                     ..Default::default()
                 },
             )
-            .map_err(From::from),
+            .map_err(on_error),
         )
     }
 
     fn get_customer(&self, customer_id: CustomerId) -> Box<Future<Item = Customer, Error = Error> + Send> {
-        Box::new(Customer::retrieve(&self.client, &customer_id.inner()).map_err(From::from))
+        let key = self.api_key("get_customer");
+        let on_error = self.key_error(&key);
+        Box::new(Customer::retrieve(&key.client, &customer_id.inner()).map_err(on_error))
     }
 
     fn delete_customer(&self, customer_id: CustomerId) -> Box<Future<Item = Deleted, Error = Error> + Send> {
-        Box::new(Customer::delete(&self.client, &customer_id.inner()).map_err(From::from))
+        let key = self.api_key("delete_customer");
+        let on_error = self.key_error(&key);
+        Box::new(Customer::delete(&key.client, &customer_id.inner()).map_err(on_error))
     }
 
     fn update_customer(&self, customer_id: CustomerId, input: UpdateCustomer) -> Box<Future<Item = Customer, Error = Error> + Send> {
+        let key = self.api_key("update_customer");
+        let on_error = self.key_error(&key);
         let customer_params = CustomerParams {
             email: input.email.as_ref().map(|e| e.as_ref()),
             source: input.token.map(|token| PaymentSourceParams::Token(token)),
             ..Default::default()
         };
-        Box::new(Customer::update(&self.client, &customer_id.inner(), customer_params).map_err(From::from))
+        Box::new(Customer::update(&key.client, &customer_id.inner(), customer_params).map_err(on_error))
     }
 
     fn create_charge(&self, input: NewCharge, metadata: Option<Metadata>) -> Box<Future<Item = Charge, Error = Error> + Send> {
-        let client = self.client.clone();
-
-        let fut = input.currency.convert().into_future().and_then(move |currency| {
-            Charge::create(
-                &client,
-                ChargeParams {
-                    amount: Some(input.amount.inner() as u64),
-                    currency: Some(currency),
-                    customer: Some(input.customer_id.inner()),
-                    capture: Some(input.capture),
-                    metadata,
-                    ..Default::default()
-                },
-            )
-            .map_err(From::from)
-        });
+        let key = self.api_key("create_charge");
+        let on_error = self.key_error(&key);
+        let client = key.client.clone();
+
+        let fut = input
+            .currency
+            .convert()
+            .into_future()
+            .and_then(move |currency| stripe_amount(input.amount).map(|amount| (currency, amount)))
+            .and_then(move |(currency, amount)| {
+                Charge::create(
+                    &client,
+                    ChargeParams {
+                        amount: Some(amount),
+                        currency: Some(currency),
+                        customer: Some(input.customer_id.inner()),
+                        capture: Some(input.capture),
+                        metadata,
+                        ..Default::default()
+                    },
+                )
+                .map_err(on_error)
+            });
         Box::new(fut)
     }
 
     fn get_charge(&self, charge_id: ChargeId) -> Box<Future<Item = Charge, Error = Error> + Send> {
-        Box::new(Charge::retrieve(&self.client, &charge_id.inner()).map_err(From::from))
+        let key = self.api_key("get_charge");
+        let on_error = self.key_error(&key);
+        Box::new(Charge::retrieve(&key.client, &charge_id.inner()).map_err(on_error))
     }
 
     fn capture_charge(&self, charge_id: ChargeId, amount: Amount) -> Box<Future<Item = Charge, Error = Error> + Send> {
-        Box::new(
+        let key = self.api_key("capture_charge");
+        let on_error = self.key_error(&key);
+        let client = key.client.clone();
+
+        let fut = stripe_amount(amount).into_future().and_then(move |amount| {
             Charge::capture(
-                &self.client,
+                &client,
                 &charge_id.inner(),
                 CaptureParams {
-                    amount: Some(amount.inner() as u64),
+                    amount: Some(amount),
                     ..Default::default()
                 },
             )
-            .map_err(From::from),
-        )
+            .map_err(on_error)
+        });
+        Box::new(fut)
     }
 
     fn get_payment_intent(&self, payment_intent_id: PaymentIntentId) -> Box<Future<Item = PaymentIntent, Error = Error> + Send> {
-        Box::new(PaymentIntent::retrieve(&self.client, &payment_intent_id.0).map_err(From::from))
+        let key = self.api_key("get_payment_intent");
+        let on_error = self.key_error(&key);
+        Box::new(PaymentIntent::retrieve(&key.client, &payment_intent_id.0).map_err(on_error))
     }
 
     fn capture_payment_intent(
@@ -167,39 +262,51 @@ impl StripeClient for StripeClientImpl {
         payment_intent_id: PaymentIntentId,
         amount: Amount,
     ) -> Box<Future<Item = PaymentIntent, Error = Error> + Send> {
-        Box::new(
+        let key = self.api_key("capture_payment_intent");
+        let on_error = self.key_error(&key);
+        let client = key.client.clone();
+
+        let fut = stripe_amount(amount).into_future().and_then(move |amount| {
             PaymentIntent::capture(
-                &self.client,
+                &client,
                 &payment_intent_id.0,
                 PaymentIntentCaptureParams {
-                    amount_to_capture: Some(amount.inner() as u64),
+                    amount_to_capture: Some(amount),
                     ..Default::default()
                 },
             )
-            .map_err(From::from),
-        )
+            .map_err(on_error)
+        });
+        Box::new(fut)
     }
     fn retrieve_balance_transaction(&self, balance_transaction_id: String) -> Box<Future<Item = BalanceTransaction, Error = Error> + Send> {
-        Box::new(BalanceTransaction::retrieve(&self.client, &balance_transaction_id).map_err(From::from))
+        let key = self.api_key("retrieve_balance_transaction");
+        let on_error = self.key_error(&key);
+        Box::new(BalanceTransaction::retrieve(&key.client, &balance_transaction_id).map_err(on_error))
     }
 
     fn refund(&self, charge_id: ChargeId, amount: Amount, order_id: OrderId) -> Box<Future<Item = Refund, Error = Error> + Send> {
+        let key = self.api_key("refund");
+        let on_error = self.key_error(&key);
+        let client = key.client.clone();
         let mut metadata = Metadata::new();
         metadata.insert("order_id".to_string(), format!("{}", order_id));
-        Box::new(
+
+        let fut = stripe_amount(amount).into_future().and_then(move |amount| {
             Refund::create(
-                &self.client,
+                &client,
                 RefundParams {
                     charge: &charge_id.inner(),
-                    amount: Some(amount.inner() as u64),
+                    amount: Some(amount),
                     metadata,
                     reason: None,
                     refund_application_fee: None,
                     reverse_transfer: None,
                 },
             )
-            .map_err(From::from),
-        )
+            .map_err(on_error)
+        });
+        Box::new(fut)
     }
 
     fn create_payout(
@@ -208,46 +315,157 @@ impl StripeClient for StripeClientImpl {
         currency: StripeCurrency,
         order_id: OrderId,
     ) -> Box<Future<Item = Payout, Error = Error> + Send> {
+        let key = self.api_key("create_payout");
+        let on_error = self.key_error(&key);
+        let client = key.client.clone();
         let mut metadata = Metadata::new();
         metadata.insert("order_id".to_string(), format!("{}", order_id));
-        Box::new(
+
+        let fut = stripe_amount(amount).into_future().and_then(move |amount| {
             Payout::create(
-                &self.client,
+                &client,
                 PayoutParams {
-                    amount: amount.inner() as u64,
+                    amount,
                     metadata: Some(metadata),
                     currency,
                     ..Default::default()
                 },
             )
-            .map_err(From::from),
-        )
+            .map_err(on_error)
+        });
+        Box::new(fut)
     }
 
     fn create_payment_intent(&self, input: NewPaymentIntent) -> Box<Future<Item = PaymentIntent, Error = Error> + Send> {
+        let key = self.api_key("create_payment_intent");
+        let on_error = self.key_error(&key);
         let params = PaymentIntentCreateParams {
             allowed_source_types: input.allowed_source_types,
             amount: input.amount,
             currency: input.currency,
             capture_method: input.capture_method,
+            receipt_email: input.receipt_email.as_ref().map(|e| e.as_ref()),
+            description: input.description.as_ref().map(|d| d.as_ref()),
             ..Default::default()
         };
-        Box::new(PaymentIntent::create(&self.client, params).map_err(From::from))
+        Box::new(PaymentIntent::create(&key.client, params).map_err(on_error))
     }
 
     fn cancel_payment_intent(&self, payment_intent_id: PaymentIntentId) -> Box<Future<Item = PaymentIntent, Error = Error> + Send> {
+        let key = self.api_key("cancel_payment_intent");
+        let on_error = self.key_error(&key);
+        Box::new(PaymentIntent::cancel(&key.client, &payment_intent_id.0, stripe::PaymentIntentCancelParams::default()).map_err(on_error))
+    }
+
+    fn update_payment_intent_receipt_email(
+        &self,
+        payment_intent_id: PaymentIntentId,
+        receipt_email: String,
+    ) -> Box<Future<Item = PaymentIntent, Error = Error> + Send> {
+        let key = self.api_key("update_payment_intent_receipt_email");
+        let on_error = self.key_error(&key);
+        let params = PaymentIntentUpdateParams {
+            receipt_email: Some(&receipt_email),
+            ..Default::default()
+        };
+        Box::new(PaymentIntent::update(&key.client, &payment_intent_id.0, params).map_err(on_error))
+    }
+
+    fn update_payment_intent_description(
+        &self,
+        payment_intent_id: PaymentIntentId,
+        description: String,
+    ) -> Box<Future<Item = PaymentIntent, Error = Error> + Send> {
+        let key = self.api_key("update_payment_intent_description");
+        let on_error = self.key_error(&key);
+        let params = PaymentIntentUpdateParams {
+            description: Some(&description),
+            ..Default::default()
+        };
+        Box::new(PaymentIntent::update(&key.client, &payment_intent_id.0, params).map_err(on_error))
+    }
+
+    fn ping(&self) -> Box<Future<Item = (), Error = Error> + Send> {
+        let key = self.api_key("ping");
+        let on_error = self.key_error(&key);
+        Box::new(Customer::retrieve(&key.client, STRIPE_PING_CUSTOMER_ID).then(|res| match res {
+            Ok(_) | Err(StripeError::Stripe(_)) => Ok(()),
+            Err(e) => Err(on_error(e)),
+        }))
+    }
+
+    fn create_setup_intent(&self, customer_id: CustomerId) -> Box<Future<Item = SetupIntent, Error = Error> + Send> {
+        let key = self.api_key("create_setup_intent");
+        let on_error = self.key_error(&key);
+        let params = SetupIntentCreateParams {
+            customer: customer_id.inner(),
+            usage: "off_session",
+        };
+        Box::new(key.client.post_form("/setup_intents", params).map_err(on_error))
+    }
+
+    fn attach_payment_method(&self, customer_id: CustomerId, payment_method_id: String) -> Box<Future<Item = (), Error = Error> + Send> {
+        let key = self.api_key("attach_payment_method");
+        let on_error = self.key_error(&key);
+        let client = key.client.clone();
+
+        let attach_params = PaymentMethodAttachParams {
+            customer: customer_id.inner(),
+        };
+        let default_params = CustomerInvoiceSettingsParams {
+            invoice_settings: InvoiceSettingsParams {
+                default_payment_method: payment_method_id.clone(),
+            },
+        };
+
+        let fut = key
+            .client
+            .post_form::<serde_json::Value, _>(&format!("/payment_methods/{}/attach", payment_method_id), attach_params)
+            .and_then(move |_| client.post_form::<serde_json::Value, _>(&format!("/customers/{}", customer_id.inner()), default_params))
+            .map(|_| ())
+            .map_err(on_error);
+
+        Box::new(fut)
+    }
+
+    fn delete_card(&self, customer_id: CustomerId, card_id: String) -> Box<Future<Item = (), Error = Error> + Send> {
+        let key = self.api_key("delete_card");
+        let on_error = self.key_error(&key);
         Box::new(
-            PaymentIntent::cancel(&self.client, &payment_intent_id.0, stripe::PaymentIntentCancelParams::default()).map_err(From::from),
+            key.client
+                .delete::<serde_json::Value>(&format!("/customers/{}/sources/{}", customer_id.inner(), card_id))
+                .map(|_| ())
+                .map_err(on_error),
         )
     }
 }
 
 impl Clone for StripeClientImpl {
     fn clone(&self) -> Self {
-        StripeClientImpl {
-            public_key: self.public_key.clone(),
-            secret_key: self.secret_key.clone(),
-            client: self.client.clone(),
-        }
+        StripeClientImpl { keys: self.keys.clone() }
+    }
+}
+
+/// Client sending the `Idempotency-Key` header, Stripe replays the response of the first request with the key
+/// for 24 hours
+fn idempotent_client(client: &stripe::async::Client, idempotency_key: String) -> stripe::async::Client {
+    client.with_headers(Headers {
+        idempotency_key: Some(idempotency_key),
+        ..Default::default()
+    })
+}
+
+fn is_authentication_error(e: &StripeError) -> bool {
+    match *e {
+        StripeError::Stripe(ref e) => e.http_status == 401,
+        _ => false,
     }
 }
+
+/// Stripe takes amounts as `u64`, anything larger is rejected instead of being truncated
+fn stripe_amount(amount: Amount) -> Result<u64, Error> {
+    amount.to_u64().ok_or_else(|| {
+        let e = format_err!("Amount {} exceeds the largest amount Stripe accepts", amount);
+        ectx!(err e, ErrorContext::Amount, ErrorKind::Validation(json!({ "amount": amount })))
+    })
+}
@@ -7,7 +7,9 @@ use models::*;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NewCustomer {
-    pub email: String,
+    pub email: Option<String>,
+    /// Stripe returns the customer created by the first request with the same key instead of creating another one
+    pub idempotency_key: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,12 +18,16 @@ pub struct NewPaymentIntent {
     pub amount: u64,
     pub currency: StripeCurrency,
     pub capture_method: Option<CaptureMethod>,
+    pub receipt_email: Option<String>,
+    pub description: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NewCustomerWithSource {
     pub email: Option<String>,
     pub token: TokenId,
+    /// Stripe returns the customer created by the first request with the same key instead of creating another one
+    pub idempotency_key: String,
 }
 
 #[derive(Debug, Clone, Deserialize)]
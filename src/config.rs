@@ -1,10 +1,14 @@
 //! Config module contains the top-level config for the app.
+use std::collections::HashMap;
 use std::env;
 
+use chrono::Duration;
 use config_crate::{Config as RawConfig, ConfigError, Environment, File};
 use sentry_integration::SentryConfig;
 use uuid::Uuid;
 
+use models::{Currency, DunningAction, Feature, ReceiptMessage, RetentionTable, TureCurrency, DEFAULT_RECEIPT_LOCALE};
+
 use stq_http;
 use stq_logging::GrayLogConfig;
 
@@ -15,6 +19,7 @@ pub struct Config {
     pub client: Client,
     pub saga_addr: SagaAddr,
     pub stores_microservice: StoresMicroservice,
+    pub notifications_microservice: NotificationsMicroservice,
     pub callback: Callback,
     pub external_billing: ExternalBilling,
     pub payments: Option<Payments>,
@@ -24,8 +29,63 @@ pub struct Config {
     pub stripe: Stripe,
     pub event_store: EventStore,
     pub fee: FeeValues,
+    pub cashback: Cashback,
+    pub wallet_verification: WalletVerification,
     pub payment_expiry: PaymentExpiry,
+    pub payment_recovery: PaymentRecovery,
+    pub payment_capture: PaymentCapture,
+    pub store_invoices: StoreInvoices,
     pub subscription: Subscription,
+    pub api: Api,
+    pub fee_statements: FeeStatements,
+    pub store_billing_suspension: StoreBillingSuspension,
+    pub warmup: Warmup,
+    #[serde(default)]
+    pub payment_methods: PaymentMethods,
+    #[serde(default)]
+    pub encryption: Encryption,
+    pub analytics: Option<Analytics>,
+    #[serde(default)]
+    pub dependencies: Dependencies,
+    #[serde(default)]
+    pub feature_flags: FeatureFlags,
+    #[serde(default)]
+    pub receipts: Receipts,
+    #[serde(default)]
+    pub fee_crypto_payments: FeeCryptoPayments,
+    #[serde(default)]
+    pub fee_charge: FeeCharge,
+    #[serde(default)]
+    pub cashback_liabilities: CashbackLiabilities,
+    #[serde(default)]
+    pub public_invoices: PublicInvoices,
+    #[serde(default)]
+    pub schema_check: SchemaCheck,
+    #[serde(default)]
+    pub currency_exchange: CurrencyExchange,
+    #[serde(default)]
+    pub worker: Worker,
+    #[serde(default)]
+    pub account_pool: AccountPool,
+    #[serde(default)]
+    pub legacy_invoice_expiration: LegacyInvoiceExpiration,
+    #[serde(default)]
+    pub data_retention: DataRetention,
+    #[serde(default)]
+    pub fee_dunning: FeeDunning,
+    #[serde(default)]
+    pub payments_sandbox: PaymentsSandbox,
+    #[serde(default)]
+    pub exchange_rate_retention: ExchangeRateRetention,
+    #[serde(default)]
+    pub payout_restrictions: PayoutRestrictions,
+    pub storage_microservice: Option<StorageMicroservice>,
+    #[serde(default)]
+    pub customer_export: CustomerExport,
+    #[serde(default)]
+    pub invoice_risk: InvoiceRisk,
+    #[serde(default)]
+    pub work_queue: WorkQueue,
 }
 
 /// Common server settings
@@ -61,6 +121,12 @@ pub struct StoresMicroservice {
     pub url: String,
 }
 
+/// Notifications microservice url
+#[derive(Debug, Deserialize, Clone)]
+pub struct NotificationsMicroservice {
+    pub url: String,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Callback {
     pub url: String,
@@ -86,6 +152,34 @@ pub struct Payments {
     pub min_pooled_accounts: u32,
     pub accounts: Accounts,
     pub sign_public_key: String,
+    #[serde(default)]
+    pub gateway: PaymentsGateway,
+}
+
+/// Protection of billing from a slow or failing Payments gateway
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct PaymentsGateway {
+    /// Time a single request is given. Connections are pooled by the shared HTTP client,
+    /// which doesn't tell connecting from waiting for the response, so this covers both
+    pub request_timeout_ms: u64,
+    /// Number of times failed reads of accounts and transactions are retried, writes are never retried
+    pub get_retries: u32,
+    /// Consecutive failed requests the circuit breaker opens at, failing requests right away. 0 disables it
+    pub circuit_breaker_failures: u32,
+    /// Time the circuit breaker stays open before a trial request is let through
+    pub circuit_breaker_reset_sec: u64,
+}
+
+impl Default for PaymentsGateway {
+    fn default() -> Self {
+        PaymentsGateway {
+            request_timeout_ms: 10000,
+            get_retries: 2,
+            circuit_breaker_failures: 5,
+            circuit_breaker_reset_sec: 30,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -108,6 +202,23 @@ pub struct Stripe {
     pub public_key: String,
     pub secret_key: String,
     pub signing_secret: String,
+    /// Key calls switch to once Stripe rejects `secret_key`, e.g. the old key while a new one is rolled out
+    #[serde(default)]
+    pub secondary_secret_key: Option<String>,
+    /// Webhooks signed with it are accepted too, so that none are rejected while the signing secret is rolled
+    #[serde(default)]
+    pub secondary_signing_secret: Option<String>,
+    /// Further webhook endpoints sending events to the billing, e.g. the Stripe CLI of a test environment.
+    /// Every endpoint has its own signing secret, they are tried after the primary and secondary ones
+    #[serde(default)]
+    pub webhook_endpoints: Vec<StripeWebhookEndpoint>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StripeWebhookEndpoint {
+    /// Tells the endpoint in logs and metrics
+    pub name: String,
+    pub signing_secret: String,
 }
 
 /// Event store processing settings
@@ -116,6 +227,39 @@ pub struct EventStore {
     pub max_processing_attempts: u32,
     pub stuck_threshold_sec: u32,
     pub polling_rate_sec: u32,
+    /// Events taking longer to process are logged with the timings of their phases as warnings
+    pub slow_event_threshold_ms: u64,
+    /// Identifies this instance in event leases, must be unique among instances sharing the database
+    pub instance_id: Option<String>,
+    /// Queues of events processed apart from each other. Events of the payload types no lane lists
+    /// go to the lane without payload types, one event at a time when there is no such lane
+    #[serde(default)]
+    pub lanes: Vec<EventLane>,
+}
+
+/// Events of the listed payload types, polled and processed independently of the other lanes
+#[derive(Debug, Deserialize, Clone)]
+pub struct EventLane {
+    pub name: String,
+    /// Names of the `EventPayload` variants, e.g. "InvoicePaid"
+    #[serde(default)]
+    pub payload_types: Vec<String>,
+    /// Events taken for processing at once
+    pub batch_size: u32,
+    /// Events of a batch processed at the same time
+    pub concurrency: u32,
+    /// Defaults to `polling_rate_sec` of the event store
+    pub polling_rate_ms: Option<u64>,
+}
+
+impl EventStore {
+    /// Configured instance id, or the host name suffixed with a random id
+    pub fn instance_id(&self) -> String {
+        self.instance_id.clone().unwrap_or_else(|| {
+            let host = env::var("HOSTNAME").unwrap_or_else(|_| "billing".to_string());
+            format!("{}-{}", host, Uuid::new_v4().simple())
+        })
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -124,16 +268,676 @@ pub struct FeeValues {
     pub currency_code: String,
 }
 
+/// Limits of the cashback sellers grant on their orders, checked when an invoice is created
+#[derive(Debug, Deserialize, Clone)]
+pub struct Cashback {
+    /// Largest part of the order total granted as cashback, from 0 to 1
+    pub max_fraction: f64,
+    /// Largest cashback of an order by seller currency, in super units.
+    /// Cashback in currencies not listed is limited by `max_fraction` only
+    #[serde(default)]
+    pub max_amounts: HashMap<Currency, f64>,
+    pub policy: CashbackPolicy,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CashbackPolicy {
+    /// Invoice creation fails with the offending orders listed
+    Reject,
+    /// Cashback of the offending orders is lowered to the limits
+    Clamp,
+}
+
+/// Verification of payout wallets with a transfer of a random small amount the user confirms
+#[derive(Debug, Deserialize, Clone)]
+pub struct WalletVerification {
+    /// Smallest payout by currency, in super units, only sent to verified wallets.
+    /// Payouts in currencies not listed are sent to any wallet
+    #[serde(default)]
+    pub large_payout_amounts: HashMap<Currency, f64>,
+    /// Largest verification transfer by currency, in super units, its amount is picked at random below it.
+    /// Wallets of currencies not listed can't be verified
+    #[serde(default)]
+    pub max_transfer_amounts: HashMap<Currency, f64>,
+    /// Time the user has to confirm the transfer
+    pub timeout_min: u32,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct PaymentExpiry {
     pub crypto_timeout_min: u32,
     pub fiat_timeout_min: u32,
+    /// Time a pooled account released by a crypto invoice rests before another invoice gets it,
+    /// late payments of the released invoice are not credited to the next one meanwhile
+    #[serde(default)]
+    pub account_quarantine_min: u32,
+}
+
+impl PaymentExpiry {
+    pub fn account_quarantine(&self) -> Duration {
+        Duration::minutes(self.account_quarantine_min.into())
+    }
+}
+
+/// Recovery of failed fiat payments
+#[derive(Debug, Deserialize, Clone)]
+pub struct PaymentRecovery {
+    /// Page the buyer retries the payment on, the invoice id is appended to it
+    pub retry_url: String,
+}
+
+/// Capture of fiat payments authorized for several orders at once
+#[derive(Debug, Deserialize, Clone)]
+pub struct PaymentCapture {
+    /// Time sellers have to approve or decline their orders, the approved ones are captured afterwards.
+    /// Must be shorter than the 7 days Stripe keeps the funds authorized
+    pub timeout_min: u32,
+}
+
+/// Invoices the stores send to their customers directly
+#[derive(Debug, Deserialize, Clone)]
+pub struct StoreInvoices {
+    /// Page the customer pays the invoice on, the invoice id is appended to it
+    pub payment_url: String,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Subscription {
     pub periodicity_days: i64,
     pub trial_time_duration_days: i64,
+    /// Tiers of the price per published product ordered by `up_to`. The whole quantity of a store
+    /// is charged at the price of the tier it falls into. Without tiers stores are charged the value
+    /// of their subscription for every product published on every day of the period
+    #[serde(default)]
+    pub pricing_tiers: Vec<SubscriptionPricingTier>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SubscriptionPricingTier {
+    /// Largest quantity of published products in the tier, unbounded if not set
+    pub up_to: Option<u32>,
+    /// Price of a published product by currency, in super units
+    pub unit_prices: HashMap<Currency, f64>,
+}
+
+/// API versioning settings
+#[derive(Debug, Deserialize, Clone)]
+pub struct Api {
+    pub v1_enabled: bool,
+    /// HTTP-date sent in the `Sunset` header of v1 responses
+    pub v1_sunset: Option<String>,
+}
+
+/// Monthly store fee statement settings
+#[derive(Debug, Deserialize, Clone)]
+pub struct FeeStatements {
+    /// Tax charged on top of the net fees, in percents
+    pub tax_percent: u64,
+    /// Statements are only generated automatically when enabled
+    pub generation_enabled: bool,
+    /// How often the job checks that the statements of the last month have been generated
+    pub check_interval_sec: u64,
+}
+
+/// Policy suspending stores that leave their fees unpaid
+#[derive(Debug, Deserialize, Clone)]
+pub struct StoreBillingSuspension {
+    /// Stores are only suspended and reinstated automatically when enabled
+    pub enabled: bool,
+    pub evaluation_interval_sec: u64,
+    /// Age at which an unpaid fee becomes overdue
+    pub grace_period_days: i64,
+    /// Number of overdue fees a store is suspended at
+    pub max_overdue_fees: u32,
+    /// Unpaid fees of a store by currency, in super units, it is suspended at regardless of their age.
+    /// Unpaid fees in currencies not listed are only counted when overdue
+    #[serde(default)]
+    pub max_unpaid_amounts: HashMap<Currency, f64>,
+    /// Time a reinstated store is left alone by the policy, so that it can pay the fees
+    pub reinstatement_grace_days: i64,
+}
+
+/// Connections and clients prepared on startup, so that first requests don't pay for initialization
+#[derive(Debug, Deserialize, Clone)]
+pub struct Warmup {
+    pub enabled: bool,
+    /// Number of database connections established in parallel, capped by the pool size
+    pub db_connections: u32,
+    /// User the saga and other services call billing as, its roles are cached on startup
+    pub system_user_id: i32,
+}
+
+/// Rules restricting the currencies buyers may pay in, by buyer country and store.
+/// Every rule that applies to a purchase narrows the allowed currencies down,
+/// with no rules applying every currency is allowed
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct PaymentMethods {
+    #[serde(default)]
+    pub rules: Vec<PaymentMethodRule>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PaymentMethodRule {
+    /// Alpha3 codes of the buyer countries the rule applies to, all countries if empty
+    #[serde(default)]
+    pub countries: Vec<String>,
+    /// Stores the rule applies to, all stores if empty
+    #[serde(default)]
+    pub store_ids: Vec<i32>,
+    /// Currencies allowed by the rule
+    pub currencies: Vec<Currency>,
+}
+
+/// Keys of the application-level encryption of sensitive billing info columns.
+/// New values are stored in plaintext while `current_key_id` is not set
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct Encryption {
+    /// Id of the key new values are encrypted with
+    pub current_key_id: Option<String>,
+    /// Base64 encoded 256 bit keys by id. Rotated out keys must be kept until the re-encryption job has run
+    #[serde(default)]
+    pub keys: HashMap<String, String>,
+}
+
+/// Publishing of billing events to the analytics message bus, disabled when not set
+#[derive(Debug, Deserialize, Clone)]
+pub struct Analytics {
+    pub sink: AnalyticsSink,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AnalyticsSink {
+    /// Kafka topic written through the Kafka REST proxy
+    Kafka { rest_proxy_url: String, topic: String },
+    /// NATS server, messages are published to `<subject>.<event type>`
+    Nats { address: String, subject: String },
+}
+
+/// Instrumentation of the calls to Saga, Stores, Stripe, Notifications, Storage and Payments gateway
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct Dependencies {
+    /// Time the latencies and outcomes served at `/debug/dependencies` are kept for
+    pub stats_window_sec: u64,
+    /// Deadlines of the calls by `<dependency>` or `<dependency>.<method>`, e.g. `stripe.create_payout`,
+    /// the latter taking precedence. Calls without a deadline wait for the HTTP client to give up
+    pub latency_budgets_ms: HashMap<String, u64>,
+}
+
+impl Default for Dependencies {
+    fn default() -> Self {
+        Dependencies {
+            stats_window_sec: 300,
+            latency_budgets_ms: HashMap::new(),
+        }
+    }
+}
+
+/// Rollouts of billing behavior kept in `feature_flags` and changed at runtime through the admin endpoints
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct FeatureFlags {
+    /// Time an instance serves the flags from memory before reading them again
+    pub cache_ttl_sec: u64,
+    /// Whether a feature without a stored flag is on in this environment
+    pub defaults: HashMap<Feature, bool>,
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        FeatureFlags {
+            cache_ttl_sec: 30,
+            defaults: HashMap::new(),
+        }
+    }
+}
+
+impl FeatureFlags {
+    pub fn default_enabled(&self, feature: Feature) -> bool {
+        self.defaults.get(&feature).cloned().unwrap_or_else(|| feature.default_enabled())
+    }
+}
+
+/// Receipts the billing sends to buyers of crypto invoices through the notifications microservice
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct Receipts {
+    pub enabled: bool,
+    /// Locale the receipts are rendered in
+    pub locale: String,
+    /// Messages of the receipts by locale, the missing ones are rendered in English
+    pub translations: HashMap<String, HashMap<ReceiptMessage, String>>,
+}
+
+impl Default for Receipts {
+    fn default() -> Self {
+        Receipts {
+            enabled: true,
+            locale: DEFAULT_RECEIPT_LOCALE.to_string(),
+            translations: HashMap::new(),
+        }
+    }
+}
+
+/// Payment of fees by transfers from the wallets of stores
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct FeeCryptoPayments {
+    /// Time the store has to transfer the fee to the deposit address
+    pub timeout_min: u32,
+}
+
+impl Default for FeeCryptoPayments {
+    fn default() -> Self {
+        FeeCryptoPayments { timeout_min: 60 }
+    }
+}
+
+impl FeeCryptoPayments {
+    pub fn timeout(&self) -> Duration {
+        Duration::minutes(self.timeout_min.into())
+    }
+}
+
+/// Currency fees are charged from the cards of stores in
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct FeeCharge {
+    /// Fees in other currencies are converted into this one before the charge, unset to charge fees in their own currency
+    pub currency: Option<Currency>,
+    pub rate_source: FeeChargeRateSource,
+    /// Amount in the fee currency one unit of the charge currency is worth, used with the `fixed` rate source
+    pub fixed_rates: HashMap<Currency, f64>,
+}
+
+impl Default for FeeCharge {
+    fn default() -> Self {
+        FeeCharge {
+            currency: None,
+            rate_source: FeeChargeRateSource::Stores,
+            fixed_rates: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeChargeRateSource {
+    /// Current exchange rates of the stores microservice
+    Stores,
+    /// Rates of the `fixed_rates` setting
+    Fixed,
+}
+
+/// Sizing of the pools of accounts invoices and fees are paid to
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct AccountPool {
+    /// Smallest pool by currency, currencies not listed keep `min_pooled_accounts` of the payments settings
+    pub min_accounts: HashMap<TureCurrency, u32>,
+    /// Largest pool the replenishment grows by currency, currencies not listed are not capped.
+    /// An exhausted pool still gets an account created at claim time
+    pub max_accounts: HashMap<TureCurrency, u32>,
+    /// Pools are replenished to hold as many free accounts as were claimed within this window
+    pub demand_window_sec: u64,
+    /// Pools are only replenished on a schedule when enabled, they are always replenished on start
+    pub replenishment_enabled: bool,
+    pub replenishment_interval_sec: u64,
+}
+
+impl Default for AccountPool {
+    fn default() -> Self {
+        AccountPool {
+            min_accounts: HashMap::new(),
+            max_accounts: HashMap::new(),
+            demand_window_sec: 3600,
+            replenishment_enabled: true,
+            replenishment_interval_sec: 600,
+        }
+    }
+}
+
+/// Monthly snapshots of the outstanding cashback
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct CashbackLiabilities {
+    /// Snapshots are only taken automatically when enabled
+    pub snapshots_enabled: bool,
+    /// How often the job checks that the snapshot of the last month has been taken
+    pub check_interval_sec: u64,
+}
+
+impl Default for CashbackLiabilities {
+    fn default() -> Self {
+        CashbackLiabilities {
+            snapshots_enabled: true,
+            check_interval_sec: 3600,
+        }
+    }
+}
+
+/// Unauthenticated invoice status endpoint polled by external payment pages
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct PublicInvoices {
+    /// Requests a single client may make per minute before getting `429 Too Many Requests`
+    pub requests_per_minute: u32,
+    /// `max-age` of the `Cache-Control` header of the status
+    pub cache_max_age_sec: u32,
+}
+
+impl Default for PublicInvoices {
+    fn default() -> Self {
+        PublicInvoices {
+            requests_per_minute: 60,
+            cache_max_age_sec: 5,
+        }
+    }
+}
+
+/// Limits of the work queued on the CPU pool, zero leaves a queue unbounded
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct WorkQueue {
+    /// Closures of the HTTP requests queued or running before requests get `503 Service Unavailable`
+    pub controller_max_depth: usize,
+    /// Closures of the events queued or running before the lanes stop taking new events
+    pub event_handler_max_depth: usize,
+    /// `Retry-After` of the responses refused while the queue is full
+    pub retry_after_sec: u64,
+}
+
+impl Default for WorkQueue {
+    fn default() -> Self {
+        WorkQueue {
+            controller_max_depth: 1000,
+            event_handler_max_depth: 200,
+            retry_after_sec: 5,
+        }
+    }
+}
+
+/// Comparison of the migrations the binary is built with against the ones applied to the database on startup
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SchemaCheck {
+    pub enabled: bool,
+    /// Refuse to start if some migrations haven't been applied, otherwise only log them
+    pub refuse_start_on_drift: bool,
+}
+
+impl Default for SchemaCheck {
+    fn default() -> Self {
+        SchemaCheck {
+            enabled: true,
+            refuse_start_on_drift: true,
+        }
+    }
+}
+
+/// Currency exchange info of the stores microservice cached by every instance
+#[derive(Debug, Deserialize, Clone)]
+pub struct CurrencyExchange {
+    /// How often the info is refreshed in the background
+    pub refresh_interval_sec: u64,
+    /// Info older than that is refused, so fees and prices are not computed at an outdated rate
+    pub max_age_sec: u64,
+}
+
+impl Default for CurrencyExchange {
+    fn default() -> Self {
+        CurrencyExchange {
+            refresh_interval_sec: 60,
+            max_age_sec: 600,
+        }
+    }
+}
+
+/// Event processing and scheduled jobs, run by the `billing-worker` binary
+#[derive(Debug, Deserialize, Clone)]
+pub struct Worker {
+    /// The API server runs the worker too, for small deployments running a single process.
+    /// Turn it off when `billing-worker` processes run next to the API servers
+    pub combined: bool,
+}
+
+impl Default for Worker {
+    fn default() -> Self {
+        Worker { combined: true }
+    }
+}
+
+/// Sweep of v1 invoices left awaiting payment after their price reservation ended
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct LegacyInvoiceExpiration {
+    /// Invoices are only swept on a schedule when enabled, the admin endpoint sweeps them on demand
+    pub sweep_enabled: bool,
+    pub sweep_interval_sec: u64,
+    /// Time after the end of the price reservation a payment is still waited for
+    pub grace_period_sec: u64,
+    /// Invoices expired in a single transaction
+    pub batch_size: i64,
+}
+
+impl Default for LegacyInvoiceExpiration {
+    fn default() -> Self {
+        LegacyInvoiceExpiration {
+            sweep_enabled: true,
+            sweep_interval_sec: 3600,
+            grace_period_sec: 3600,
+            batch_size: 100,
+        }
+    }
+}
+
+/// Purge of personal data of closed stores and deleted users once their retention period is over
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct DataRetention {
+    /// Data is only purged on a schedule when enabled, the admin endpoint purges on demand
+    pub purge_enabled: bool,
+    pub purge_interval_sec: u64,
+    /// Scheduled purges only report what they would purge
+    pub dry_run: bool,
+    /// Subjects purged in a single transaction
+    pub batch_size: i64,
+    /// Data of tables without a rule is kept
+    pub rules: Vec<RetentionRule>,
+}
+
+impl Default for DataRetention {
+    fn default() -> Self {
+        DataRetention {
+            purge_enabled: false,
+            purge_interval_sec: 86400,
+            dry_run: true,
+            batch_size: 100,
+            rules: vec![
+                RetentionRule::new(RetentionTable::RussiaBillingInfo, 1825),
+                RetentionRule::new(RetentionTable::InternationalBillingInfo, 1825),
+                RetentionRule::new(RetentionTable::BillingInfoChanges, 1825),
+                RetentionRule::new(RetentionTable::CustomerEmails, 365),
+                RetentionRule::new(RetentionTable::InvoiceBuyerDetails, 1825),
+            ],
+        }
+    }
+}
+
+/// Data of the table is purged once its subject is closed for `retention_days`
+#[derive(Debug, Deserialize, Clone)]
+pub struct RetentionRule {
+    pub table: RetentionTable,
+    pub retention_days: u32,
+}
+
+impl RetentionRule {
+    pub fn new(table: RetentionTable, retention_days: u32) -> Self {
+        RetentionRule { table, retention_days }
+    }
+}
+
+/// Notices and the suspension of stores whose cards could not be charged for their fees
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct FeeDunning {
+    /// Failed charges only start a dunning when enabled
+    pub enabled: bool,
+    /// Steps taken while the fees stay unpaid, each `after_days` past the failed charge
+    pub schedule: Vec<DunningStep>,
+}
+
+impl FeeDunning {
+    /// Steps in the order they are taken
+    pub fn ordered_schedule(&self) -> Vec<DunningStep> {
+        let mut schedule = self.schedule.clone();
+        schedule.sort_by_key(|step| step.after_days);
+        schedule
+    }
+}
+
+impl Default for FeeDunning {
+    fn default() -> Self {
+        FeeDunning {
+            enabled: false,
+            schedule: vec![
+                DunningStep::new(DunningAction::Reminder, 3),
+                DunningStep::new(DunningAction::Warning, 7),
+                DunningStep::new(DunningAction::Suspension, 14),
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct DunningStep {
+    pub action: DunningAction,
+    pub after_days: u32,
+}
+
+impl DunningStep {
+    pub fn new(action: DunningAction, after_days: u32) -> Self {
+        DunningStep { action, after_days }
+    }
+}
+
+/// Simulation of inbound crypto transactions by QA, never to be enabled in production.
+/// `sign_private_key` is the hex secp256k1 key whose public key is `payments.sign_public_key`
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PaymentsSandbox {
+    pub enabled: bool,
+    pub sign_private_key: String,
+}
+
+/// Cleanup of the exchange rates of orders superseded by newer rates, the rate used at payment is always kept
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ExchangeRateRetention {
+    pub cleanup_enabled: bool,
+    pub cleanup_interval_sec: u64,
+    /// Days a superseded rate is kept after it has expired
+    pub retention_days: u32,
+    /// Rates deleted in a single statement
+    pub batch_size: i64,
+}
+
+impl Default for ExchangeRateRetention {
+    fn default() -> Self {
+        ExchangeRateRetention {
+            cleanup_enabled: false,
+            cleanup_interval_sec: 86400,
+            retention_days: 90,
+            batch_size: 1000,
+        }
+    }
+}
+
+/// Countries whose stores can't legally receive payouts, as alpha-3 codes or as the names billing infos spell them with.
+/// Countries are matched ignoring case, stores with Russian billing info are in "RUS"
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PayoutRestrictions {
+    pub restricted_countries: Vec<String>,
+}
+
+/// Storage microservice url, the objects exported by billing are written to it
+#[derive(Debug, Deserialize, Clone)]
+pub struct StorageMicroservice {
+    pub url: String,
+}
+
+/// Export of the customers and their payment summaries for the CRM sync, requires `storage_microservice`
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct CustomerExport {
+    pub export_enabled: bool,
+    pub export_interval_sec: u64,
+    /// Exports only the customers changed since the previous export, all of them otherwise
+    pub incremental: bool,
+    /// Users per exported object
+    pub batch_size: i64,
+    /// Objects of an export are written to `<key_prefix>/<export time>/part-<n>.json`
+    pub key_prefix: String,
+}
+
+impl Default for CustomerExport {
+    fn default() -> Self {
+        CustomerExport {
+            export_enabled: false,
+            export_interval_sec: 86400,
+            incremental: true,
+            batch_size: 1000,
+            key_prefix: "crm/customers".to_string(),
+        }
+    }
+}
+
+/// Risk scoring of the invoices buyers create, every signal found adds its score.
+/// Invoices scoring `review_score` wait for a review before their card payment is captured,
+/// the ones scoring `deny_score` are not created
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct InvoiceRisk {
+    pub enabled: bool,
+    pub review_score: i32,
+    pub deny_score: i32,
+    /// Order totals by seller currency, in super units, from which an invoice is large.
+    /// Invoices in currencies not listed are never large
+    pub large_amounts: HashMap<Currency, f64>,
+    pub large_amount_score: i32,
+    /// Score of the invoices of buyers who have not paid any invoice yet
+    pub new_buyer_score: i32,
+    /// Invoices of the buyer left unpaid from which the buyer is suspicious
+    pub max_unpaid_invoices: i64,
+    pub unpaid_invoices_score: i32,
+    /// Invoices created from a single IP within `ip_window_min` from which the IP is suspicious
+    pub max_ip_invoices: i64,
+    pub ip_window_min: u32,
+    pub ip_velocity_score: i32,
+    /// Score of the invoices created without the IP of the buyer
+    pub missing_ip_score: i32,
+}
+
+impl Default for InvoiceRisk {
+    fn default() -> Self {
+        InvoiceRisk {
+            enabled: false,
+            review_score: 50,
+            deny_score: 100,
+            large_amounts: HashMap::new(),
+            large_amount_score: 40,
+            new_buyer_score: 20,
+            max_unpaid_invoices: 5,
+            unpaid_invoices_score: 30,
+            max_ip_invoices: 10,
+            ip_window_min: 60,
+            ip_velocity_score: 50,
+            missing_ip_score: 10,
+        }
+    }
 }
 
 /// Creates new app config struct
@@ -151,9 +955,26 @@ impl Config {
         s.set_default("event_store.max_processing_attempts", 3i64).unwrap();
         s.set_default("event_store.stuck_threshold_sec", 300i64).unwrap();
         s.set_default("event_store.polling_rate_sec", 10i64).unwrap();
+        s.set_default("event_store.slow_event_threshold_ms", 10000i64).unwrap();
+        s.set_default("cashback.max_fraction", 0.5f64).unwrap();
+        s.set_default("cashback.policy", "reject").unwrap();
+        s.set_default("wallet_verification.timeout_min", 1440i64).unwrap();
         s.set_default("payment_expiry.crypto_timeout_min", 4320i64).unwrap();
         s.set_default("payment_expiry.fiat_timeout_min", 60i64).unwrap();
+        s.set_default("payment_capture.timeout_min", 7200i64).unwrap();
         s.set_default("payments_mock.use_mock", false).unwrap();
+        s.set_default("api.v1_enabled", true).unwrap();
+        s.set_default("fee_statements.tax_percent", 0i64).unwrap();
+        s.set_default("fee_statements.generation_enabled", true).unwrap();
+        s.set_default("fee_statements.check_interval_sec", 3600i64).unwrap();
+        s.set_default("store_billing_suspension.enabled", false).unwrap();
+        s.set_default("store_billing_suspension.evaluation_interval_sec", 3600i64).unwrap();
+        s.set_default("store_billing_suspension.grace_period_days", 30i64).unwrap();
+        s.set_default("store_billing_suspension.max_overdue_fees", 3i64).unwrap();
+        s.set_default("store_billing_suspension.reinstatement_grace_days", 14i64).unwrap();
+        s.set_default("warmup.enabled", false).unwrap();
+        s.set_default("warmup.db_connections", 4i64).unwrap();
+        s.set_default("warmup.system_user_id", 1i64).unwrap();
         s.set_default("payments_mock.min_pooled_accounts", 10).unwrap();
         s.set_default("payments_mock.accounts.main_stq", "cc3f3875-e719-427f-9b83-d4dae8d4263a")
             .unwrap();
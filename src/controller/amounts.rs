@@ -0,0 +1,222 @@
+//! Wraps the application to return monetary fields as integer minor units (cents, satoshis, wei)
+//! when the client opts in with `?amounts=minor` or `Accept-Profile: minor-amounts`
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use futures::{future, Future, Stream};
+use hyper;
+use hyper::header::ContentLength;
+use hyper::server::{Request, Response, Service};
+use hyper::StatusCode;
+use serde_json::{self, Map, Value};
+
+use models::Currency;
+
+const MINOR_AMOUNTS_PROFILE: &str = "minor-amounts";
+
+/// Monetary fields of responses, grouped by the field holding their currency.
+/// An object inherits the currencies of the objects it is nested in,
+/// e.g. fee statement lines are in the currency of the statement
+const MONEY_FIELDS: &[(&str, &[&str])] = &[
+    (
+        "currency",
+        &[
+            "amount",
+            "amount_received",
+            "price",
+            "value",
+            "total_amount",
+            "fees_amount",
+            "adjustments_amount",
+            "taxes_amount",
+            "unpaid_invoices",
+            "paid_not_eligible",
+            "eligible",
+            "paid_out",
+            "pending_amount",
+        ],
+    ),
+    (
+        "seller_currency",
+        &["total_amount", "cashback_amount", "stripe_fee", "seller_price", "seller_cashback"],
+    ),
+    ("buyer_currency", &["amount_captured", "total_price", "total_cashback"]),
+];
+
+pub struct MinorAmountsApplication<S> {
+    inner: S,
+}
+
+impl<S> MinorAmountsApplication<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S> Service for MinorAmountsApplication<S>
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error>,
+    S::Future: 'static,
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Request) -> Self::Future {
+        if !minor_amounts_requested(&req) {
+            return Box::new(self.inner.call(req));
+        }
+
+        Box::new(self.inner.call(req).and_then(|response| {
+            if response.status() != StatusCode::Ok {
+                return future::Either::A(future::ok(response));
+            }
+
+            let mut headers = response.headers().clone();
+            future::Either::B(response.body().concat2().map(move |body| {
+                // CSV exports and other non-JSON bodies are sent as is
+                let mut json = match serde_json::from_slice::<Value>(&body) {
+                    Ok(json) => json,
+                    Err(_) => return Response::new().with_headers(headers).with_body(body),
+                };
+                to_minor_amounts(&mut json, &HashMap::new());
+
+                let body = serde_json::to_vec(&json).expect("JSON value is always serializable");
+                headers.set(ContentLength(body.len() as u64));
+                headers.set_raw("Content-Profile", MINOR_AMOUNTS_PROFILE);
+                headers.set_raw("Vary", "Accept-Profile");
+                Response::new().with_headers(headers).with_body(body)
+            }))
+        }))
+    }
+}
+
+fn minor_amounts_requested(req: &Request) -> bool {
+    let in_query = req.query().unwrap_or_default().split('&').any(|pair| pair == "amounts=minor");
+    let in_header = req
+        .headers()
+        .get_raw("Accept-Profile")
+        .map(|raw| {
+            raw.iter()
+                .filter_map(|line| ::std::str::from_utf8(line).ok())
+                .flat_map(|line| line.split(','))
+                .any(|profile| profile.trim().trim_matches(|c| c == '<' || c == '>' || c == '"') == MINOR_AMOUNTS_PROFILE)
+        })
+        .unwrap_or(false);
+
+    in_query || in_header
+}
+
+/// Replaces monetary fields with strings of minor units, as wei amounts don't fit JSON numbers,
+/// and puts the exponent of the currency next to it, e.g. `currency_exponent`
+fn to_minor_amounts(json: &mut Value, inherited_currencies: &HashMap<&'static str, Currency>) {
+    match json {
+        Value::Object(object) => {
+            let mut currencies = inherited_currencies.clone();
+            for &(currency_field, _) in MONEY_FIELDS {
+                let currency = object
+                    .get(currency_field)
+                    .and_then(Value::as_str)
+                    .and_then(|currency| Currency::from_str(currency).ok());
+                if let Some(currency) = currency {
+                    currencies.insert(currency_field, currency);
+                    object.insert(format!("{}_exponent", currency_field), json!(currency.minor_unit_exponent()));
+                }
+            }
+
+            for &(currency_field, fields) in MONEY_FIELDS {
+                if let Some(&currency) = currencies.get(currency_field) {
+                    convert_fields(object, fields, currency);
+                }
+            }
+
+            for value in object.values_mut() {
+                to_minor_amounts(value, &currencies);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                to_minor_amounts(value, inherited_currencies);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn convert_fields(object: &mut Map<String, Value>, fields: &[&str], currency: Currency) {
+    for field in fields {
+        let minor_units = object.get(*field).and_then(|value| minor_units(value, currency));
+        if let Some(minor_units) = minor_units {
+            object.insert(field.to_string(), Value::String(minor_units));
+        }
+    }
+}
+
+/// Amounts come either as JSON numbers or as decimal strings, the former may carry
+/// float rounding errors so the result is rounded half away from zero
+fn minor_units(value: &Value, currency: Currency) -> Option<String> {
+    let amount = match value {
+        Value::Number(number) => BigDecimal::from_str(&number.to_string()).ok()?,
+        Value::String(string) => BigDecimal::from_str(string).ok()?,
+        _ => return None,
+    };
+
+    let shifted = amount * BigDecimal::from(10i64.pow(currency.minor_unit_exponent()));
+    let half = if shifted < BigDecimal::from(0i64) {
+        BigDecimal::from_str("-0.5").ok()?
+    } else {
+        BigDecimal::from_str("0.5").ok()?
+    };
+
+    Some((shifted + half).with_scale(0).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monetary_fields_are_converted_to_minor_units_of_their_currency() {
+        let mut json = json!({
+            "id": "pi_1",
+            "amount": 10.15,
+            "amount_received": 0.1,
+            "currency": "usd",
+            "orders": [{
+                "seller_currency": "btc",
+                "seller_price": "0.00012345",
+                "seller_cashback": "-0.00000001",
+                "buyer_amounts": { "currency": "eth", "price": "1.5", "exchange_rate": "0.5" }
+            }],
+            "lines": [{ "amount": "2.5" }]
+        });
+
+        to_minor_amounts(&mut json, &HashMap::new());
+
+        assert_eq!(
+            json,
+            json!({
+                "id": "pi_1",
+                "amount": "1015",
+                "amount_received": "10",
+                "currency": "usd",
+                "currency_exponent": 2,
+                "orders": [{
+                    "seller_currency": "btc",
+                    "seller_currency_exponent": 8,
+                    "seller_price": "12345",
+                    "seller_cashback": "-1",
+                    "buyer_amounts": {
+                        "currency": "eth",
+                        "currency_exponent": 18,
+                        "price": "1500000000000000000",
+                        "exchange_rate": "0.5"
+                    }
+                }],
+                "lines": [{ "amount": "250" }]
+            })
+        );
+    }
+}
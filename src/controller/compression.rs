@@ -0,0 +1,121 @@
+//! Wraps the application to compress large responses with gzip or deflate,
+//! depending on what the client accepts
+use std::io::Write;
+
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::Compression;
+use futures::{future, Future, Stream};
+use hyper;
+use hyper::header::{q, AcceptEncoding, ContentEncoding, ContentLength, Encoding};
+use hyper::server::{Request, Response, Service};
+
+/// Smaller responses are sent as is, compressing them doesn't pay off
+const MIN_COMPRESSED_LENGTH: usize = 1024;
+
+pub struct CompressionApplication<S> {
+    inner: S,
+}
+
+impl<S> CompressionApplication<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S> Service for CompressionApplication<S>
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error>,
+    S::Future: 'static,
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Request) -> Self::Future {
+        let encoding = match req.headers().get::<AcceptEncoding>().and_then(negotiate_encoding) {
+            Some(encoding) => encoding,
+            None => return Box::new(self.inner.call(req)),
+        };
+
+        Box::new(self.inner.call(req).and_then(move |response| {
+            if response.headers().has::<ContentEncoding>() {
+                return future::Either::A(future::ok(response));
+            }
+
+            let status = response.status();
+            let mut headers = response.headers().clone();
+            future::Either::B(response.body().concat2().map(move |body| {
+                if body.len() < MIN_COMPRESSED_LENGTH {
+                    return Response::new().with_status(status).with_headers(headers).with_body(body);
+                }
+
+                match compress(&encoding, &body) {
+                    Ok(compressed) => {
+                        headers.set(ContentEncoding(vec![encoding]));
+                        headers.set(ContentLength(compressed.len() as u64));
+                        headers.set_raw("Vary", "Accept-Encoding");
+                        Response::new().with_status(status).with_headers(headers).with_body(compressed)
+                    }
+                    Err(e) => {
+                        error!("Failed to compress the response with {}: {}", encoding, e);
+                        Response::new().with_status(status).with_headers(headers).with_body(body)
+                    }
+                }
+            }))
+        }))
+    }
+}
+
+/// Picks gzip or deflate, whichever the client prefers, gzip wins a tie
+fn negotiate_encoding(accept_encoding: &AcceptEncoding) -> Option<Encoding> {
+    accept_encoding
+        .iter()
+        .filter(|item| (item.item == Encoding::Gzip || item.item == Encoding::Deflate) && item.quality > q(0u16))
+        .max_by_key(|item| (item.quality, item.item == Encoding::Gzip))
+        .map(|item| item.item.clone())
+}
+
+fn compress(encoding: &Encoding, body: &[u8]) -> ::std::io::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body)?;
+            encoder.finish()
+        }
+        _ => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body)?;
+            encoder.finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::header::{qitem, QualityItem};
+
+    use super::*;
+
+    #[test]
+    fn negotiates_preferred_encoding() {
+        assert_eq!(
+            negotiate_encoding(&AcceptEncoding(vec![qitem(Encoding::Deflate), qitem(Encoding::Gzip)])),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(
+            negotiate_encoding(&AcceptEncoding(vec![
+                QualityItem::new(Encoding::Gzip, q(500u16)),
+                qitem(Encoding::Deflate),
+            ])),
+            Some(Encoding::Deflate)
+        );
+        assert_eq!(
+            negotiate_encoding(&AcceptEncoding(vec![
+                QualityItem::new(Encoding::Gzip, q(0u16)),
+                qitem(Encoding::Identity),
+            ])),
+            None
+        );
+    }
+}
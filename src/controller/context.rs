@@ -12,11 +12,16 @@ use stq_router::RouteParser;
 use stq_types::UserId;
 
 use super::routes::*;
+use client::fiat_payments::{FiatPaymentProvider, StripeFiatPaymentProvider};
+use client::instrumentation::{DependencyStats, Instrumented};
+use client::payments::gateway::CircuitBreaker;
 use client::payments::PaymentsClient;
-use client::stripe::{StripeClient, StripeClientImpl};
+use client::stores::CurrencyExchangeCache;
+use client::stripe::{StripeClient, StripeClientImpl, StripeKeys};
 use config::Config;
 use repos::repo_factory::*;
 use services::accounts::AccountService;
+use services::invoice_risk::{RiskEvaluator, RuleRiskEvaluator};
 
 /// Static context for all app
 pub struct StaticContext<T, M, F>
@@ -31,7 +36,18 @@ where
     pub route_parser: Arc<RouteParser<Route>>,
     pub client_handle: ClientHandle,
     pub repo_factory: F,
+    /// Shared by the Stripe clients of the app and the event handler, reloaded on SIGHUP
+    pub stripe_keys: Arc<StripeKeys>,
     pub stripe_client: Arc<dyn StripeClient>,
+    pub fiat_payment_provider: Arc<dyn FiatPaymentProvider>,
+    /// Shared by the clients of the Payments gateway created for every request
+    pub payments_circuit_breaker: Arc<CircuitBreaker>,
+    /// Shared by the instrumented clients of the app and the event handler
+    pub dependency_stats: Arc<DependencyStats>,
+    /// Shared by the stores clients of the app and the event handler, refreshed in the background
+    pub currency_exchange_cache: Arc<CurrencyExchangeCache>,
+    /// Scores the invoices buyers create when risk scoring is enabled
+    pub risk_evaluator: Arc<dyn RiskEvaluator>,
 }
 
 impl<
@@ -43,7 +59,21 @@ impl<
     /// Create a new static context
     pub fn new(db_pool: Pool<M>, cpu_pool: CpuPool, client_handle: ClientHandle, config: Arc<Config>, repo_factory: F) -> Self {
         let route_parser = Arc::new(create_route_parser());
-        let stripe_client = Arc::new(StripeClientImpl::create_from_config(&config));
+        let dependency_stats = Arc::new(DependencyStats::from_config(&config.dependencies));
+        let stripe_keys = Arc::new(StripeKeys::from_config(&config.stripe));
+        let stripe_client: Arc<dyn StripeClient> = Arc::new(Instrumented::new(
+            StripeClientImpl::new(stripe_keys.clone()),
+            dependency_stats.clone(),
+        ));
+        let fiat_payment_provider = Arc::new(StripeFiatPaymentProvider::new(stripe_client.clone(), stripe_keys.clone()));
+        let payments_gateway = config
+            .payments
+            .as_ref()
+            .map(|payments| payments.gateway.clone())
+            .unwrap_or_default();
+        let payments_circuit_breaker = Arc::new(CircuitBreaker::from_config(&payments_gateway));
+        let currency_exchange_cache = Arc::new(CurrencyExchangeCache::from_config(&config.currency_exchange));
+        let risk_evaluator = Arc::new(RuleRiskEvaluator::from_config(&config.invoice_risk));
         Self {
             route_parser,
             db_pool,
@@ -51,7 +81,13 @@ impl<
             client_handle,
             config,
             repo_factory,
+            stripe_keys,
             stripe_client,
+            fiat_payment_provider,
+            payments_circuit_breaker,
+            dependency_stats,
+            currency_exchange_cache,
+            risk_evaluator,
         }
     }
 }
@@ -70,7 +106,13 @@ impl<
             client_handle: self.client_handle.clone(),
             config: self.config.clone(),
             repo_factory: self.repo_factory.clone(),
+            stripe_keys: self.stripe_keys.clone(),
             stripe_client: self.stripe_client.clone(),
+            fiat_payment_provider: self.fiat_payment_provider.clone(),
+            payments_circuit_breaker: self.payments_circuit_breaker.clone(),
+            dependency_stats: self.dependency_stats.clone(),
+            currency_exchange_cache: self.currency_exchange_cache.clone(),
+            risk_evaluator: self.risk_evaluator.clone(),
         }
     }
 }
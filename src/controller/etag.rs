@@ -0,0 +1,106 @@
+//! Wraps the application to answer polled GET endpoints with `ETag` headers,
+//! replying `304 Not Modified` without a body when the client already has the response
+use std::sync::Arc;
+
+use futures::{future, Future, Stream};
+use hex;
+use hyper;
+use hyper::header::{ContentLength, ETag, EntityTag, IfNoneMatch};
+use hyper::server::{Request, Response, Service};
+use hyper::{Get, StatusCode};
+use sha2::{Digest, Sha256};
+
+use stq_router::RouteParser;
+
+use super::routes::{resolve_route, Route};
+
+pub struct ETagApplication<S> {
+    inner: S,
+    route_parser: Arc<RouteParser<Route>>,
+}
+
+impl<S> ETagApplication<S> {
+    pub fn new(inner: S, route_parser: Arc<RouteParser<Route>>) -> Self {
+        Self { inner, route_parser }
+    }
+}
+
+impl<S> Service for ETagApplication<S>
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error>,
+    S::Future: 'static,
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Request) -> Self::Future {
+        let is_polled = *req.method() == Get
+            && resolve_route(&self.route_parser, req.path())
+                .map(|(route, _)| is_polled_route(&route))
+                .unwrap_or(false);
+        if !is_polled {
+            return Box::new(self.inner.call(req));
+        }
+
+        let if_none_match = req.headers().get::<IfNoneMatch>().cloned();
+
+        Box::new(self.inner.call(req).and_then(move |response| {
+            if response.status() != StatusCode::Ok {
+                return future::Either::A(future::ok(response));
+            }
+
+            let mut headers = response.headers().clone();
+            future::Either::B(response.body().concat2().map(move |body| {
+                let etag = entity_tag(&body);
+                if if_none_match.map(|if_none_match| matches(&if_none_match, &etag)).unwrap_or(false) {
+                    headers.remove::<ContentLength>();
+                    Response::new()
+                        .with_status(StatusCode::NotModified)
+                        .with_headers(headers)
+                        .with_header(ETag(etag))
+                } else {
+                    Response::new().with_headers(headers).with_header(ETag(etag)).with_body(body)
+                }
+            }))
+        }))
+    }
+}
+
+/// Endpoints storefronts and external payment pages poll while the buyer is paying
+fn is_polled_route(route: &Route) -> bool {
+    match route {
+        Route::InvoiceByIdV2 { .. }
+        | Route::CheckoutSessionByInvoiceId { .. }
+        | Route::PaymentIntentByInvoice { .. }
+        | Route::PublicInvoiceStatus { .. } => true,
+        _ => false,
+    }
+}
+
+/// Weak tag, so it stays valid when the body is compressed on the way to the client
+fn entity_tag(body: &[u8]) -> EntityTag {
+    EntityTag::weak(hex::encode(Sha256::digest(body)))
+}
+
+fn matches(if_none_match: &IfNoneMatch, etag: &EntityTag) -> bool {
+    match if_none_match {
+        IfNoneMatch::Any => true,
+        IfNoneMatch::Items(tags) => tags.iter().any(|tag| tag.weak_eq(etag)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_none_match_compares_tags_weakly() {
+        let etag = entity_tag(b"{\"status\":\"paid\"}");
+
+        assert!(matches(&IfNoneMatch::Any, &etag));
+        assert!(matches(&IfNoneMatch::Items(vec![EntityTag::strong(etag.tag().to_string())]), &etag));
+        assert!(!matches(&IfNoneMatch::Items(vec![entity_tag(b"{\"status\":\"new\"}")]), &etag));
+    }
+}
@@ -0,0 +1,76 @@
+//! Helpers extracting common inputs of handlers from the request:
+//! the caller, pagination params and required headers
+use std::str::FromStr;
+
+use failure;
+use hyper::header::{Authorization, Header};
+use hyper::server::Request;
+
+use stq_types::UserId;
+
+pub const API_KEY_HEADER: &str = "X-Billing-Api-Key";
+
+/// Pagination params passed in the query string as `skip` and `count`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Pagination {
+    pub skip: i64,
+    pub count: i64,
+}
+
+/// Format of exported data requested with the `format` query param
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+/// Id of the user making the request, taken from the `Authorization` header
+pub fn user_id(req: &Request) -> Option<UserId> {
+    req.headers()
+        .get::<Authorization<String>>()
+        .map(|auth| auth.0.clone())
+        .and_then(|id| i32::from_str(&id).ok())
+        .map(UserId)
+}
+
+/// Store API key the request is made with, taken from the `X-Billing-Api-Key` header
+pub fn api_key(req: &Request) -> Option<String> {
+    req.headers()
+        .get_raw(API_KEY_HEADER)
+        .and_then(|raw| raw.one())
+        .and_then(|value| String::from_utf8(value.to_vec()).ok())
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Pagination params of the request, missing params default to zero
+pub fn pagination(req: &Request) -> Pagination {
+    let (skip_opt, count_opt) = parse_query!(
+        req.query().unwrap_or_default(),
+        "skip" => i64, "count" => i64
+    );
+
+    Pagination {
+        skip: skip_opt.unwrap_or(0),
+        count: count_opt.unwrap_or(0),
+    }
+}
+
+/// Value of a header the handler can't work without
+pub fn header<H: Header + Clone>(req: &Request) -> Result<H, failure::Error> {
+    req.headers()
+        .get::<H>()
+        .cloned()
+        .ok_or(format_err!("{} header not provided", H::header_name()))
+}
+
+/// Export format of the request, JSON unless `format=csv` is passed
+pub fn export_format(req: &Request) -> ExportFormat {
+    let csv_requested = req.query().unwrap_or_default().split('&').any(|pair| pair == "format=csv");
+
+    if csv_requested {
+        ExportFormat::Csv
+    } else {
+        ExportFormat::Json
+    }
+}
@@ -0,0 +1,196 @@
+//! Masking of sensitive response fields by the roles of the caller. Repos and services return bank account details
+//! and contacts in full, the controller masks them before the response is serialized
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::Error as FailureError;
+use futures::Future;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+
+use stq_types::{BillingRole, UserId};
+
+use controller::responses::{BillingInfoChangeResponse, CustomerResponse};
+use errors::Error;
+use models::{InternationalBillingInfo, OrderBillingInfo, OrderBillingInfoSearchResults, RussiaBillingInfo};
+use repos::ReposFactory;
+use services::billing_info::BillingInfoChangeOutcome;
+use services::types::spawn_on_pool;
+
+/// How much of the sensitive fields a role sees
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MaskingLevel {
+    /// Bank account details and the contacts of other users are masked
+    Masked,
+    Full,
+}
+
+impl MaskingLevel {
+    pub fn of_role(role: BillingRole) -> Self {
+        match role {
+            BillingRole::Superuser | BillingRole::FinancialManager => MaskingLevel::Full,
+            _ => MaskingLevel::Masked,
+        }
+    }
+}
+
+/// Masking of the responses to a request
+#[derive(Clone, Copy, Debug)]
+pub struct ResponseMasking {
+    user_id: Option<UserId>,
+    level: MaskingLevel,
+}
+
+impl ResponseMasking {
+    /// The most permissive level of the roles of the caller applies
+    pub fn for_caller(user_id: Option<UserId>, roles: &[BillingRole]) -> Self {
+        let level = roles
+            .iter()
+            .cloned()
+            .map(MaskingLevel::of_role)
+            .max()
+            .unwrap_or(MaskingLevel::Masked);
+
+        Self { user_id, level }
+    }
+
+    /// Whether the data of the owner is shown in full, users always see their own data
+    pub fn reveals(&self, owner: Option<UserId>) -> bool {
+        self.level == MaskingLevel::Full || (owner.is_some() && owner == self.user_id)
+    }
+}
+
+/// Response with sensitive fields
+pub trait MaskResponse {
+    fn mask_for(self, masking: &ResponseMasking) -> Self;
+}
+
+impl<T: MaskResponse> MaskResponse for Option<T> {
+    fn mask_for(self, masking: &ResponseMasking) -> Self {
+        self.map(|value| value.mask_for(masking))
+    }
+}
+
+impl<T: MaskResponse> MaskResponse for Vec<T> {
+    fn mask_for(self, masking: &ResponseMasking) -> Self {
+        self.into_iter().map(|value| value.mask_for(masking)).collect()
+    }
+}
+
+impl MaskResponse for CustomerResponse {
+    fn mask_for(self, masking: &ResponseMasking) -> Self {
+        if masking.reveals(Some(self.user_id)) {
+            self
+        } else {
+            self.masked()
+        }
+    }
+}
+
+impl MaskResponse for InternationalBillingInfo {
+    fn mask_for(self, masking: &ResponseMasking) -> Self {
+        if masking.reveals(None) {
+            self
+        } else {
+            self.masked()
+        }
+    }
+}
+
+impl MaskResponse for RussiaBillingInfo {
+    fn mask_for(self, masking: &ResponseMasking) -> Self {
+        if masking.reveals(None) {
+            self
+        } else {
+            self.masked()
+        }
+    }
+}
+
+impl MaskResponse for BillingInfoChangeResponse {
+    fn mask_for(self, masking: &ResponseMasking) -> Self {
+        if masking.reveals(None) {
+            self
+        } else {
+            Self {
+                payload: self.payload.masked(),
+                ..self
+            }
+        }
+    }
+}
+
+impl<T: MaskResponse> MaskResponse for BillingInfoChangeOutcome<T> {
+    fn mask_for(self, masking: &ResponseMasking) -> Self {
+        match self {
+            BillingInfoChangeOutcome::Applied(billing_info) => BillingInfoChangeOutcome::Applied(billing_info.mask_for(masking)),
+            BillingInfoChangeOutcome::PendingApproval(change) => BillingInfoChangeOutcome::PendingApproval(change.mask_for(masking)),
+        }
+    }
+}
+
+impl MaskResponse for OrderBillingInfo {
+    fn mask_for(self, masking: &ResponseMasking) -> Self {
+        Self {
+            russia_billing_info: self.russia_billing_info.mask_for(masking),
+            international_billing_info: self.international_billing_info.mask_for(masking),
+            ..self
+        }
+    }
+}
+
+impl MaskResponse for OrderBillingInfoSearchResults {
+    fn mask_for(self, masking: &ResponseMasking) -> Self {
+        Self {
+            orders: self.orders.mask_for(masking),
+            ..self
+        }
+    }
+}
+
+/// Masks the response of the request made by the user, the roles of the user are loaded once the response is ready
+pub fn masked<T, M, F, R, E, Fut>(
+    db_pool: Pool<M>,
+    cpu_pool: CpuPool,
+    repo_factory: F,
+    user_id: Option<UserId>,
+    response: Fut,
+) -> impl Future<Item = R, Error = FailureError>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+    R: MaskResponse,
+    E: Into<FailureError>,
+    Fut: Future<Item = R, Error = E>,
+{
+    response.map_err(Into::into).and_then(move |response| {
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let roles = repo_factory.get_caller_roles(&*conn, user_id);
+            Ok(ResponseMasking::for_caller(user_id, &roles))
+        })
+        .map_err(Error::from)
+        .map_err(FailureError::from)
+        .map(move |masking| response.mask_for(&masking))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn most_permissive_role_applies() {
+        let store_manager = ResponseMasking::for_caller(Some(UserId(1)), &[BillingRole::User, BillingRole::StoreManager]);
+        assert!(!store_manager.reveals(None));
+        assert!(!store_manager.reveals(Some(UserId(2))));
+        assert!(store_manager.reveals(Some(UserId(1))));
+
+        let financial_manager = ResponseMasking::for_caller(Some(UserId(3)), &[BillingRole::StoreManager, BillingRole::FinancialManager]);
+        assert!(financial_manager.reveals(None));
+        assert!(financial_manager.reveals(Some(UserId(2))));
+
+        let anonymous = ResponseMasking::for_caller(None, &[]);
+        assert!(!anonymous.reveals(None));
+    }
+}
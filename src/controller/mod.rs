@@ -3,18 +3,27 @@
 //! Basically it provides inputs to `Service` layer and converts outputs
 //! of `Service` layer to http responses
 
+pub mod amounts;
+pub mod compression;
 pub mod context;
+pub mod etag;
+pub mod extractors;
+pub mod masking;
+pub mod openapi;
+pub mod overload;
+pub mod public;
 pub mod requests;
 pub mod responses;
 pub mod routes;
+pub mod versioning;
 
-use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use chrono::NaiveDateTime;
 use diesel::{connection::AnsiTransactionManager, pg::Pg, Connection};
 use futures::{future, Future, IntoFuture};
-use hyper::{header::Authorization, server::Request, Delete, Get, Method, Post, Put};
+use hyper::{server::Request, Delete, Get, Method, Post, Put};
 use r2d2::ManageConnection;
 
 use stq_http::{
@@ -26,36 +35,69 @@ use stq_http::{
         StripeSignature as StripeSignatureHeader,
     },
 };
-use stq_types::UserId;
+use stq_types::{Alpha3, StoreId, UserId};
 
 use self::context::{DynamicContext, StaticContext};
-use self::routes::Route;
+use self::extractors::{ExportFormat, Pagination};
+use self::masking::MaskResponse;
+use self::routes::{ApiVersion, Route};
+use client::instrumentation::Instrumented;
 use client::payments::mock::MockPaymentsClient;
-use client::payments::{PaymentsClient, PaymentsClientImpl};
+use client::payments::{gateway::GuardedPaymentsClient, PaymentsClient, PaymentsClientImpl};
+use client::stores::cache::currency_exchange_cache_gauges;
+use client::stores::{CachedStoresClient, StoresClientImpl};
 use controller::requests::*;
+use controller::responses::{CreateInvoiceV2Response, DependenciesResponse, SystemAccountsTransferResponse};
 use errors::Error;
-use models::order_v2::OrdersSearch;
+use models::order_v2::{InvoicePaymentStatus, OrdersSearch};
 use models::*;
 use repos::repo_factory::*;
 use repos::SearchFee;
 use sentry_integration::log_and_capture_error;
-use services::accounts::{AccountService, AccountServiceImpl};
+use services::accounts::{account_pool_gauges, AccountAdminService, AccountPoolSizing, AccountService, AccountServiceImpl};
+use services::api_key::{ApiKeyService, ApiKeyServiceImpl};
+use services::audit_log::{AuditChange, AuditLogService, AuditLogServiceImpl, AuditTarget};
 use services::billing_info::{BillingInfoService, BillingInfoServiceImpl};
 use services::billing_type::{BillingTypeService, BillingTypeServiceImpl};
+use services::cashback_liability::{CashbackLiabilityService, CashbackLiabilityServiceImpl};
 use services::customer::CustomersService;
 use services::customer::CustomersServiceImpl;
+use services::data_retention::{DataRetentionService, DataRetentionServiceImpl};
+use services::exchange_rate_slippage::{ExchangeRateSlippageService, ExchangeRateSlippageServiceImpl};
+use services::feature_flag::{FeatureFlagService, FeatureFlagServiceImpl};
 use services::fee::{FeesService, FeesServiceImpl};
+use services::fee_preview::{FeePreviewService, FeePreviewServiceImpl};
+use services::fee_statement::{FeeStatementService, FeeStatementServiceImpl};
+use services::historical_report::{HistoricalReportService, HistoricalReportServiceImpl};
 use services::invoice::InvoiceService;
+use services::invoice_callback::{InvoiceCallbackService, InvoiceCallbackServiceImpl};
+use services::invoice_risk::{InvoiceRiskService, InvoiceRiskServiceImpl};
+use services::legacy_invoice_expiration::{LegacyInvoiceExpirationService, LegacyInvoiceExpirationServiceImpl};
 use services::merchant::MerchantService;
-use services::order::OrderService;
+use services::order::{order_line_items_csv, OrderService};
 use services::order_billing::{OrderBillingService, OrderBillingServiceImpl};
+use services::payment_attempt::{PaymentAttemptService, PaymentAttemptServiceImpl};
 use services::payment_intent::{PaymentIntentService, PaymentIntentServiceImpl};
-use services::payout::{CalculatePayoutPayload, GetPayoutsPayload, PayOutToSellerPayload, PayoutService, PayoutServiceImpl};
+use services::payment_method::{PaymentMethodService, PaymentMethodServiceImpl};
+use services::payment_recovery::{PaymentRecoveryService, PaymentRecoveryServiceImpl};
+use services::payout::{CalculatePayoutPayload, GetPayoutsPayload, PayOutToSellerPayload, PayoutOutput, PayoutService, PayoutServiceImpl};
+use services::payout_instruction::{PayoutInstructionsService, PayoutInstructionsServiceImpl};
+use services::payout_restriction::{PayoutRestrictionService, PayoutRestrictionServiceImpl};
+use services::payout_statement::{PayoutStatementService, PayoutStatementServiceImpl};
+use services::schema_migration::{SchemaMigrationService, SchemaMigrationServiceImpl};
+use services::store_balance::{StoreBalanceService, StoreBalanceServiceImpl};
+use services::store_billing_status::{StoreBillingStatusService, StoreBillingStatusServiceImpl};
+use services::store_offboarding::{StoreOffboardingService, StoreOffboardingServiceImpl};
 use services::store_subscription::{StoreSubscriptionService, StoreSubscriptionServiceImpl};
+use services::store_webhook::{StoreWebhookService, StoreWebhookServiceImpl};
 use services::stripe::{StripeService, StripeServiceImpl};
+use services::stripe_fee_backfill::{StripeFeeBackfillService, StripeFeeBackfillServiceImpl};
 use services::subscription::{SubscriptionService, SubscriptionServiceImpl};
-use services::subscription_payment::{SubscriptionPaymentService, SubscriptionPaymentServiceImpl};
+use services::subscription_payment::{subscription_payment_receipts_csv, SubscriptionPaymentService, SubscriptionPaymentServiceImpl};
+use services::user_credit::{UserCreditService, UserCreditServiceImpl};
 use services::user_roles::UserRolesService;
+use services::user_wallet::{UserWalletService, UserWalletServiceImpl};
+use services::work_queue::{WorkQueue, CONTROLLER_WORK_QUEUE, EVENT_HANDLER_WORK_QUEUE};
 use services::Service;
 
 /// Controller handles route parsing and calling `Service` layer
@@ -78,17 +120,74 @@ impl<
     pub fn new(static_context: StaticContext<T, M, F>) -> Self {
         Self { static_context }
     }
-}
 
-impl<
-        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
-        M: ManageConnection<Connection = T>,
-        F: ReposFactory<T>,
-    > Controller for ControllerImpl<T, M, F>
-{
-    /// Handle a request and get future response
-    fn call(&self, req: Request) -> ControllerFuture {
-        let user_id = get_user_id(&req);
+    /// Masks the sensitive fields of the response by the roles of the user making the request
+    fn masked<R, E, Fut>(&self, user_id: Option<UserId>, response: Fut) -> impl Future<Item = R, Error = failure::Error>
+    where
+        R: MaskResponse,
+        E: Into<failure::Error>,
+        Fut: Future<Item = R, Error = E>,
+    {
+        masking::masked(
+            self.static_context.db_pool.clone(),
+            self.static_context.cpu_pool.clone(),
+            self.static_context.repo_factory.clone(),
+            user_id,
+            response,
+        )
+    }
+
+    /// Requests made with a store API key act for the store manager who created the key,
+    /// limited to the routes and permissions of the key scopes
+    fn call_with_api_key(&self, req: Request, key: String) -> ControllerFuture {
+        let access =
+            routes::resolve_route(&self.static_context.route_parser, req.path()).and_then(|(route, _)| route.api_key_access(req.method()));
+        let (scope, store_id) = match access {
+            Some(access) => access,
+            None => {
+                return Box::new(future::err(
+                    format_err!("Request {} {} can't be made with an api key", req.method(), req.path())
+                        .context(Error::Forbidden)
+                        .into(),
+                ));
+            }
+        };
+
+        let api_key_service = ApiKeyServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: None,
+        };
+        let static_context = self.static_context.clone();
+
+        Box::new(
+            api_key_service
+                .authenticate(key)
+                .map_err(Error::from)
+                .map_err(failure::Error::from)
+                .and_then(move |api_key| {
+                    let scopes = api_key.scopes().unwrap_or_default();
+                    if api_key.store_id != store_id || !scopes.contains(&scope) {
+                        return future::Either::A(future::err(
+                            format_err!("Api key {} has no {:?} access to store {}", api_key.id, scope, store_id)
+                                .context(Error::Forbidden)
+                                .into(),
+                        ));
+                    }
+
+                    let repo_factory = static_context.repo_factory.with_api_key_scopes(scopes);
+                    let controller = ControllerImpl::new(StaticContext {
+                        repo_factory,
+                        ..static_context
+                    });
+                    future::Either::B(controller.route_request(req, Some(api_key.created_by)))
+                }),
+        )
+    }
+
+    /// Handles a request made by the user
+    fn route_request(&self, req: Request, user_id: Option<UserId>) -> ControllerFuture {
         let correlation_token = request_util::get_correlation_token(&req);
 
         let request_timeout = req
@@ -110,7 +209,8 @@ impl<
                     self.static_context.db_pool.clone(),
                     self.static_context.cpu_pool.clone(),
                     self.static_context.repo_factory.clone(),
-                    payments_mock_cfg.min_pooled_accounts,
+                    AccountPoolSizing::new(payments_mock_cfg.min_pooled_accounts, &self.static_context.config.account_pool),
+                    self.static_context.config.payment_expiry.account_quarantine(),
                     payments_client.clone(),
                     format!(
                         "{}{}",
@@ -130,11 +230,20 @@ impl<
                 PaymentsClientImpl::create_from_config(time_limited_http_client.clone(), payments_config.clone().into())
                     .ok()
                     .map(|payments_client| {
+                        let payments_client = Instrumented::new(
+                            GuardedPaymentsClient::new(
+                                payments_client,
+                                payments_config.gateway.clone().into(),
+                                self.static_context.payments_circuit_breaker.clone(),
+                            ),
+                            self.static_context.dependency_stats.clone(),
+                        );
                         let account_service = AccountServiceImpl::new(
                             self.static_context.db_pool.clone(),
                             self.static_context.cpu_pool.clone(),
                             self.static_context.repo_factory.clone(),
-                            payments_config.min_pooled_accounts,
+                            AccountPoolSizing::new(payments_config.min_pooled_accounts, &self.static_context.config.account_pool),
+                            self.static_context.config.payment_expiry.account_quarantine(),
                             payments_client.clone(),
                             format!(
                                 "{}{}",
@@ -163,6 +272,17 @@ impl<
 
         let service = Service::new(self.static_context.clone(), dynamic_context.clone());
 
+        let stores_client = CachedStoresClient::new(
+            Instrumented::new(
+                StoresClientImpl::new(
+                    self.static_context.client_handle.clone(),
+                    self.static_context.config.stores_microservice.url.clone(),
+                ),
+                self.static_context.dependency_stats.clone(),
+            ),
+            self.static_context.currency_exchange_cache.clone(),
+        );
+
         let customer_service = Arc::new(CustomersServiceImpl {
             db_pool: self.static_context.db_pool.clone(),
             cpu_pool: self.static_context.cpu_pool.clone(),
@@ -183,14 +303,19 @@ impl<
             cpu_pool: self.static_context.cpu_pool.clone(),
             repo_factory: self.static_context.repo_factory.clone(),
             dynamic_context: dynamic_context.clone(),
+            payout_restrictions: self.static_context.config.payout_restrictions.clone(),
         });
 
         let fees_service = Arc::new(FeesServiceImpl {
             db_pool: self.static_context.db_pool.clone(),
             cpu_pool: self.static_context.cpu_pool.clone(),
             repo_factory: self.static_context.repo_factory.clone(),
-            stripe_client: self.static_context.stripe_client.clone(),
+            fiat_payment_provider: self.static_context.fiat_payment_provider.clone(),
             dynamic_context: dynamic_context.clone(),
+            config: self.static_context.config.fee_crypto_payments.clone(),
+            stores_client: Arc::new(stores_client.clone()),
+            fee_charge: self.static_context.config.fee_charge.clone(),
+            fee_dunning: self.static_context.config.fee_dunning.clone(),
         });
 
         let billing_type_service = Arc::new(BillingTypeServiceImpl {
@@ -205,14 +330,14 @@ impl<
             cpu_pool: self.static_context.cpu_pool.clone(),
             repo_factory: self.static_context.repo_factory.clone(),
             dynamic_context: dynamic_context.clone(),
-            stripe_client: self.static_context.stripe_client.clone(),
+            fiat_payment_provider: self.static_context.fiat_payment_provider.clone(),
         });
 
         let stripe_service = Arc::new(StripeServiceImpl {
             db_pool: self.static_context.db_pool.clone(),
             cpu_pool: self.static_context.cpu_pool.clone(),
             repo_factory: self.static_context.repo_factory.clone(),
-            stripe_client: self.static_context.stripe_client.clone(),
+            fiat_payment_provider: self.static_context.fiat_payment_provider.clone(),
             dynamic_context: dynamic_context.clone(),
             static_context: self.static_context.clone(),
         });
@@ -223,6 +348,19 @@ impl<
             repo_factory: self.static_context.repo_factory.clone(),
             user_id: dynamic_context.user_id.clone(),
             payments_client: payments_client.clone(),
+            stores_client: Arc::new(stores_client.clone()),
+            wallet_verification: self.static_context.config.wallet_verification.clone(),
+            feature_flags: self.static_context.config.feature_flags.clone(),
+            payout_restrictions: self.static_context.config.payout_restrictions.clone(),
+        });
+
+        let fee_preview_service = Arc::new(FeePreviewServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+            stores_client: Arc::new(stores_client.clone()),
+            fee_config: self.static_context.config.fee.clone(),
         });
 
         let subscription_service = Arc::new(SubscriptionServiceImpl {
@@ -239,6 +377,7 @@ impl<
             repo_factory: self.static_context.repo_factory.clone(),
             dynamic_context: dynamic_context.clone(),
             stripe_client: self.static_context.stripe_client.clone(),
+            stores_client: Arc::new(stores_client.clone()),
             config: self.static_context.config.subscription.clone(),
         });
 
@@ -250,14 +389,197 @@ impl<
             config: self.static_context.config.subscription.clone(),
         });
 
+        let payment_method_service = PaymentMethodServiceImpl {
+            config: self.static_context.config.payment_methods.clone(),
+        };
+
+        let fee_statement_service = Arc::new(FeeStatementServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+            config: self.static_context.config.fee_statements.clone(),
+        });
+
+        let payout_statement_service = Arc::new(PayoutStatementServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+        });
+
+        let cashback_liability_service = Arc::new(CashbackLiabilityServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+        });
+
+        let legacy_invoice_expiration_service = Arc::new(LegacyInvoiceExpirationServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+            config: self.static_context.config.legacy_invoice_expiration.clone(),
+        });
+
+        let store_balance_service = Arc::new(StoreBalanceServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+        });
+
+        let exchange_rate_slippage_service = Arc::new(ExchangeRateSlippageServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+        });
+
+        let invoice_risk_service = Arc::new(InvoiceRiskServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+        });
+
+        let store_offboarding_service = Arc::new(StoreOffboardingServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+        });
+
+        let historical_report_service = Arc::new(HistoricalReportServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+        });
+
+        let user_credit_service = Arc::new(UserCreditServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+        });
+
+        let payment_attempt_service = Arc::new(PaymentAttemptServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+        });
+
+        let schema_migration_service = Arc::new(SchemaMigrationServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+        });
+
+        let audit_log_service = Arc::new(AuditLogServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+        });
+
+        let user_wallet_service = Arc::new(UserWalletServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+            wallet_verification: self.static_context.config.wallet_verification.clone(),
+        });
+
+        let invoice_callback_service = Arc::new(InvoiceCallbackServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+        });
+
+        let store_webhook_service = Arc::new(StoreWebhookServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+        });
+
+        let api_key_service = Arc::new(ApiKeyServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+        });
+
+        let store_billing_status_service = Arc::new(StoreBillingStatusServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+            config: self.static_context.config.store_billing_suspension.clone(),
+        });
+
+        let feature_flag_service = Arc::new(FeatureFlagServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+            config: self.static_context.config.feature_flags.clone(),
+        });
+
+        let data_retention_service = Arc::new(DataRetentionServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+            config: self.static_context.config.data_retention.clone(),
+        });
+
+        let payout_instructions_service = Arc::new(PayoutInstructionsServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+            payout_restrictions: self.static_context.config.payout_restrictions.clone(),
+        });
+
+        let payout_restriction_service = Arc::new(PayoutRestrictionServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+            config: self.static_context.config.payout_restrictions.clone(),
+        });
+
+        let stripe_fee_backfill_service = Arc::new(StripeFeeBackfillServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+        });
+
+        let payment_recovery_service = Arc::new(PaymentRecoveryServiceImpl {
+            db_pool: self.static_context.db_pool.clone(),
+            cpu_pool: self.static_context.cpu_pool.clone(),
+            repo_factory: self.static_context.repo_factory.clone(),
+            user_id: dynamic_context.user_id.clone(),
+            fiat_payment_provider: self.static_context.fiat_payment_provider.clone(),
+        });
+
         let path = req.path().to_string();
 
-        let fut = match (&req.method().clone(), self.static_context.route_parser.test(req.path())) {
+        let route = match routes::resolve_route(&self.static_context.route_parser, req.path()) {
+            Some((_, ApiVersion::V1)) if !self.static_context.config.api.v1_enabled => None,
+            resolved => resolved.map(|(route, _)| route),
+        };
+
+        let fut = match (&req.method().clone(), route) {
             (&Post, Some(Route::StripeWebhook)) => serialize_future(
-                req.headers()
-                    .get::<StripeSignatureHeader>()
-                    .cloned()
-                    .ok_or(format_err!("Stripe-Signature header not provided"))
+                extractors::header::<StripeSignatureHeader>(&req)
                     .into_future()
                     .and_then(|signature_header| {
                         info!("stripe controller signature_header: {}", signature_header);
@@ -276,10 +598,7 @@ impl<
                 serialize_future({ parse_body::<ExternalBillingInvoice>(req.body()).and_then(move |data| service.update_invoice(data)) })
             }
             (&Post, Some(Route::PaymentsInboundTx)) => serialize_future(
-                req.headers()
-                    .get::<TureSign>()
-                    .cloned()
-                    .ok_or(format_err!("Sign header not provided"))
+                extractors::header::<TureSign>(&req)
                     .into_future()
                     .and_then(|signature_header| {
                         read_body(req.body()).map_err(failure::Error::from).and_then(|body| {
@@ -298,88 +617,289 @@ impl<
             (&Post, Some(Route::UserMerchants)) => {
                 serialize_future({ parse_body::<CreateUserMerchantPayload>(req.body()).and_then(move |data| service.create_user(data)) })
             }
-            (Delete, Some(Route::UserMerchant { user_id })) => serialize_future({ service.delete_user(user_id) }),
+            // retention of the personal data of a deleted user or a closed store starts once saga deletes its merchant
+            (Delete, Some(Route::UserMerchant { user_id })) => serialize_future(
+                data_retention_service
+                    .mark_closed(DataSubject::User(user_id))
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from)
+                    .and_then(move |_| service.delete_user(user_id)),
+            ),
             (Get, Some(Route::UserMerchantBalance { user_id })) => serialize_future({ service.get_user_balance(user_id) }),
             (&Post, Some(Route::StoreMerchants)) => {
                 serialize_future({ parse_body::<CreateStoreMerchantPayload>(req.body()).and_then(move |data| service.create_store(data)) })
             }
-            (Delete, Some(Route::StoreMerchant { store_id })) => serialize_future({ service.delete_store(store_id) }),
+            // the billing of the store is closed down along with it, saga retries the deletion until the offboarding is completed
+            (Delete, Some(Route::StoreMerchant { store_id })) => serialize_future(
+                data_retention_service
+                    .mark_closed(DataSubject::Store(store_id))
+                    .and_then(move |_| store_offboarding_service.offboard_store(store_id))
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from)
+                    .and_then(move |_| service.delete_store(store_id)),
+            ),
             (Get, Some(Route::StoreMerchantBalance { store_id })) => serialize_future({ service.get_store_balance(store_id) }),
             (&Post, Some(Route::Invoices)) => {
                 serialize_future({ parse_body::<CreateInvoice>(req.body()).and_then(move |data| service.create_invoice(data)) })
             }
-            (&Post, Some(Route::InvoicesV2)) => serialize_future(
-                parse_body::<CreateInvoiceV2>(req.body())
-                    .and_then(move |data| service.create_invoice_v2(data).map_err(Error::from).map_err(failure::Error::from)),
-            ),
+            (&Post, Some(Route::InvoicesV2)) => serialize_future(parse_body::<CreateInvoiceV2>(req.body()).and_then(move |data| {
+                service
+                    .create_invoice_v2(data)
+                    .and_then(move |invoice| {
+                        service
+                            .checkout_session_for_invoice(invoice.clone())
+                            .map(|checkout_session| CreateInvoiceV2Response { invoice, checkout_session })
+                    })
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from)
+            })),
+            (Post, Some(Route::StoreInvoices { store_id })) => {
+                serialize_future(parse_body::<CreateStoreInvoiceRequest>(req.body()).and_then(move |payload| {
+                    service
+                        .create_store_invoice(store_id, payload)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from)
+                }))
+            }
+            (Post, Some(Route::InvoiceRequote { id })) => {
+                serialize_future(parse_body::<InvoiceRequoteRequest>(req.body()).and_then(move |payload| {
+                    service
+                        .requote_invoice(id, payload)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from)
+                }))
+            }
+            (Post, Some(Route::InvoiceCancel { id })) => {
+                serialize_future(service.cancel_invoice(id).map_err(Error::from).map_err(failure::Error::from))
+            }
             (Delete, Some(Route::InvoiceBySagaId { id })) => serialize_future({ service.delete_invoice_by_saga_id(id) }),
             (Get, Some(Route::InvoiceByOrderId { id })) => serialize_future({ service.get_invoice_by_order_id(id) }),
             (Get, Some(Route::InvoiceById { id })) => serialize_future({ service.get_invoice_by_id(id) }),
             (Get, Some(Route::InvoiceByIdV2 { id })) => {
                 serialize_future(service.recalc_invoice_v2(id).map_err(Error::from).map_err(failure::Error::from))
             }
+            (&Method::Patch, Some(Route::InvoiceByIdV2 { id })) => {
+                serialize_future(parse_body::<UpdateInvoiceDetailsRequest>(req.body()).and_then(move |payload| {
+                    service
+                        .update_invoice_details_v2(id, payload)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from)
+                }))
+            }
+            (Get, Some(Route::CheckoutSessionByInvoiceId { invoice_id })) => serialize_future(
+                service
+                    .get_checkout_session(invoice_id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::PublicInvoiceStatus { token })) => serialize_future(
+                service
+                    .get_public_invoice_status(token)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::InvoiceTransactions { id })) => serialize_future(
+                service
+                    .get_invoice_transactions(id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::InvoiceRiskAssessment { id })) => serialize_future(
+                invoice_risk_service
+                    .get_invoice_risk_assessment(id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Post, Some(Route::InvoiceRiskReview { id })) => {
+                serialize_future(parse_body::<ReviewInvoiceRiskRequest>(req.body()).and_then(move |payload| {
+                    audit_log_service.audit(
+                        AuditAction::InvoiceRiskReviewed,
+                        AuditTarget::InvoiceRiskAssessment(id),
+                        move || {
+                            invoice_risk_service
+                                .review_invoice_risk(id, payload)
+                                .map_err(Error::from)
+                                .map_err(failure::Error::from)
+                        },
+                    )
+                }))
+            }
+            (Get, Some(Route::InvoiceRiskReviews)) => serialize_future(
+                invoice_risk_service
+                    .list_pending_invoice_risk_reviews()
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::MyCredits)) => serialize_future(
+                user_credit_service
+                    .get_my_credits()
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::UserCredits { user_id })) => serialize_future(
+                user_credit_service
+                    .get_user_credits(user_id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Post, Some(Route::UserCredits { user_id })) => {
+                serialize_future(parse_body::<GrantUserCreditRequest>(req.body()).and_then(move |payload| {
+                    audit_log_service.audit(AuditAction::UserCreditGranted, AuditTarget::UserCredit(user_id), move || {
+                        user_credit_service
+                            .grant_user_credit(user_id, payload)
+                            .map_err(Error::from)
+                            .map_err(failure::Error::from)
+                    })
+                }))
+            }
             (Post, Some(Route::InvoiceByIdRecalc { id })) => serialize_future({ service.recalc_invoice(id) }),
             (Get, Some(Route::InvoiceOrdersIds { id })) => serialize_future({ service.get_invoice_orders_ids(id) }),
             (Get, Some(Route::RolesByUserId { user_id })) => serialize_future({ service.get_roles(user_id) }),
-            (Post, Some(Route::Roles)) => {
-                serialize_future({ parse_body::<NewUserRole>(req.body()).and_then(move |data| service.create_user_role(data)) })
-            }
-            (Delete, Some(Route::Roles)) => {
-                serialize_future({ parse_body::<RemoveUserRole>(req.body()).and_then(move |data| service.delete_user_role(data)) })
-            }
-            (Delete, Some(Route::RolesByUserId { user_id })) => serialize_future({ service.delete_user_role_by_user_id(user_id) }),
-            (Delete, Some(Route::RoleById { id })) => serialize_future({ service.delete_user_role_by_id(id) }),
+            (Post, Some(Route::Roles)) => serialize_future({
+                parse_body::<NewUserRole>(req.body()).and_then(move |data| {
+                    audit_log_service.audit(AuditAction::UserRoleGranted, AuditTarget::UserRoles(data.user_id), move || {
+                        service.create_user_role(data)
+                    })
+                })
+            }),
+            (Delete, Some(Route::Roles)) => serialize_future({
+                parse_body::<RemoveUserRole>(req.body()).and_then(move |data| {
+                    audit_log_service.audit(AuditAction::UserRoleRevoked, AuditTarget::UserRoles(data.user_id), move || {
+                        service.delete_user_role(data)
+                    })
+                })
+            }),
+            (Delete, Some(Route::RolesByUserId { user_id })) => serialize_future({
+                audit_log_service.audit(AuditAction::UserRoleRevoked, AuditTarget::UserRoles(user_id), move || {
+                    service.delete_user_role_by_user_id(user_id)
+                })
+            }),
+            (Delete, Some(Route::RoleById { id })) => serialize_future({
+                audit_log_service.audit_result(
+                    AuditAction::UserRoleRevoked,
+                    move || service.delete_user_role_by_id(id),
+                    |role: &UserRole| AuditChange::removed(AuditResource::user_roles(role.user_id), role),
+                )
+            }),
+
+            (Get, Some(Route::PaymentMethods)) => {
+                let (country, store_id) = parse_query!(
+                    req.query().unwrap_or_default(),
+                    "country" => String, "store_id" => StoreId
+                );
 
+                serialize_future(
+                    payment_method_service
+                        .get_payment_methods(country.map(Alpha3), store_id)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from),
+                )
+            }
             (Get, Some(Route::PaymentIntentByInvoice { invoice_id })) => {
                 serialize_future({ payment_intent_service.get_by_invoice(invoice_id) })
             }
             (Post, Some(Route::PaymentIntentByFee { fee_id })) => serialize_future({ payment_intent_service.create_by_fee(fee_id) }),
+            (Get, Some(Route::PaymentIntentHistory { id })) => serialize_future({ payment_intent_service.get_history(id) }),
+            (Post, Some(Route::InvoicePaymentRetry { id })) => serialize_future(
+                payment_recovery_service
+                    .retry_payment(id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::InvoiceCallbackByInvoiceId { invoice_id })) => serialize_future(
+                invoice_callback_service
+                    .get_invoice_callback(invoice_id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
             (Post, Some(Route::OrdersByIdCapture { id })) => serialize_future({ service.order_capture(id) }),
             (Post, Some(Route::OrdersByIdDecline { id })) => serialize_future({ service.order_decline(id) }),
 
             (Post, Some(Route::OrdersSetPaymentState { order_id })) => serialize_future({
                 parse_body::<OrderPaymentStateRequest>(req.body())
                     .map_err(failure::Error::from)
-                    .and_then(move |payload| service.update_order_state(order_id, payload.state).map_err(failure::Error::from))
+                    .and_then(move |payload| {
+                        audit_log_service.audit(AuditAction::OrderPaymentStateChanged, AuditTarget::Order(order_id), move || {
+                            service.update_order_state(order_id, payload.state).map_err(failure::Error::from)
+                        })
+                    })
             }),
 
-            (Post, Some(Route::CustomersWithSource)) => serialize_future({
-                parse_body::<NewCustomerWithSourceRequest>(req.body())
-                    .and_then(move |data| customer_service.create_customer_with_source(data).map_err(failure::Error::from))
+            (Get, Some(Route::OrderExchangeRates { order_id })) => serialize_future({
+                service
+                    .get_order_exchange_rates(order_id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from)
+            }),
+            (Post, Some(Route::OrderLineItemsRefund { order_id })) => serialize_future({
+                parse_body::<RefundOrderLineItemsRequest>(req.body())
+                    .map_err(failure::Error::from)
+                    .and_then(move |payload| {
+                        audit_log_service.audit(AuditAction::OrderLineItemsRefunded, AuditTarget::Order(order_id), move || {
+                            service
+                                .refund_order_line_items(order_id, payload)
+                                .map_err(Error::from)
+                                .map_err(failure::Error::from)
+                        })
+                    })
             }),
-            (Get, Some(Route::Customers)) => serialize_future({ customer_service.get_customer() }),
+            (Get, Some(Route::OrderFeePreview { order_id })) => serialize_future({
+                fee_preview_service
+                    .preview_order_fee(order_id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from)
+            }),
+            (Post, Some(Route::OrderFeePreviews)) => serialize_future({
+                parse_body::<OrderFeePreviewsRequest>(req.body()).and_then(move |payload| {
+                    fee_preview_service
+                        .preview_order_fees(payload.order_ids)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from)
+                })
+            }),
+
+            (Post, Some(Route::CustomersWithSource)) => serialize_future(
+                self.masked(
+                    user_id,
+                    parse_body::<NewCustomerWithSourceRequest>(req.body())
+                        .and_then(move |data| customer_service.create_customer_with_source(data).map_err(failure::Error::from)),
+                ),
+            ),
+            (Post, Some(Route::CustomersSetupIntents)) => serialize_future({
+                parse_body::<NewSetupIntentRequest>(req.body())
+                    .and_then(move |data| customer_service.create_setup_intent(data).map_err(failure::Error::from))
+            }),
+            (Post, Some(Route::CustomersDeduplication)) => serialize_future({ customer_service.deduplicate_customers() }),
+            (Get, Some(Route::Customers)) => serialize_future(self.masked(user_id, customer_service.get_customer())),
+            (Get, Some(Route::CustomerByUserId { user_id: customer_user_id })) => {
+                serialize_future(self.masked(user_id, customer_service.get_customer_by_user_id(customer_user_id)))
+            }
             (Delete, Some(Route::Customers)) => serialize_future({
                 parse_body::<DeleteCustomerRequest>(req.body())
                     .and_then(move |payload| customer_service.delete(payload.customer_id).map_err(failure::Error::from))
             }),
-            (Put, Some(Route::Customers)) => serialize_future({
-                parse_body::<UpdateCustomerRequest>(req.body())
-                    .and_then(move |payload| customer_service.update(payload).map_err(failure::Error::from))
-            }),
+            (Put, Some(Route::Customers)) => serialize_future(
+                self.masked(
+                    user_id,
+                    parse_body::<UpdateCustomerRequest>(req.body())
+                        .and_then(move |payload| customer_service.update(payload).map_err(failure::Error::from)),
+                ),
+            ),
             (Post, Some(Route::OrderBillingInfo)) => {
-                let (skip_opt, count_opt) = parse_query!(
-                    req.query().unwrap_or_default(),
-                    "skip" => i64, "count" => i64
-                );
-
-                let skip = skip_opt.unwrap_or(0);
-                let count = count_opt.unwrap_or(0);
+                let Pagination { skip, count } = extractors::pagination(&req);
 
-                serialize_future(parse_body::<OrderBillingSearchTerms>(req.body()).and_then(move |payload| {
-                    order_billing_service
-                        .search(skip, count, payload)
-                        .map_err(Error::from)
-                        .map_err(failure::Error::from)
-                }))
+                serialize_future(self.masked(
+                    user_id,
+                    parse_body::<OrderBillingSearchTerms>(req.body()).and_then(move |payload| {
+                        order_billing_service
+                            .search(skip, count, payload)
+                            .map_err(Error::from)
+                            .map_err(failure::Error::from)
+                    }),
+                ))
             }
             (Post, Some(Route::OrderSearch)) => {
-                let (skip_opt, count_opt) = parse_query!(
-                    req.query().unwrap_or_default(),
-                    "skip" => i64, "count" => i64
-                );
-
-                let skip = skip_opt.unwrap_or(0);
-                let count = count_opt.unwrap_or(0);
+                let Pagination { skip, count } = extractors::pagination(&req);
 
                 serialize_future(parse_body::<OrdersSearch>(req.body()).and_then(move |payload| {
                     service
@@ -388,63 +908,115 @@ impl<
                         .map_err(failure::Error::from)
                 }))
             }
+            (Get, Some(Route::StoreOrders { store_id })) => {
+                let Pagination { skip, count } = extractors::pagination(&req);
+                let payment_status = parse_query!(req.query().unwrap_or_default(), "payment_status" => InvoicePaymentStatus);
+                let payload = OrdersSearch {
+                    store_id: Some(store_id),
+                    payment_status,
+                    ..Default::default()
+                };
 
-            (Post, Some(Route::InternationalBillingInfos)) => serialize_future({
+                let fut = service
+                    .search_orders(skip, count, payload)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from);
+
+                match extractors::export_format(&req) {
+                    ExportFormat::Json => serialize_future(fut),
+                    ExportFormat::Csv => Box::new(fut.map(|results| order_line_items_csv(&results.orders))),
+                }
+            }
+
+            (Post, Some(Route::InternationalBillingInfos)) => serialize_future(self.masked(user_id, {
                 parse_body::<NewInternationalBillingInfo>(req.body()).and_then(move |payload| {
                     billing_info_service
                         .create_international_billing_info(payload)
                         .map_err(failure::Error::from)
                 })
-            }),
+            })),
 
-            (Put, Some(Route::InternationalBillingInfo { id })) => serialize_future({
+            (Put, Some(Route::InternationalBillingInfo { id })) => serialize_future(self.masked(user_id, {
                 parse_body::<UpdateInternationalBillingInfo>(req.body()).and_then(move |payload| {
                     billing_info_service
                         .update_international_billing_info(id, payload)
                         .map_err(failure::Error::from)
                 })
-            }),
-            (Post, Some(Route::RussiaBillingInfos)) => serialize_future({
+            })),
+            (Post, Some(Route::RussiaBillingInfos)) => serialize_future(self.masked(user_id, {
                 parse_body::<NewRussiaBillingInfo>(req.body()).and_then(move |payload| {
                     billing_info_service
                         .create_russia_billing_info(payload)
                         .map_err(failure::Error::from)
                 })
-            }),
-            (Put, Some(Route::RussiaBillingInfo { id })) => serialize_future({
+            })),
+            (Put, Some(Route::RussiaBillingInfo { id })) => serialize_future(self.masked(user_id, {
                 parse_body::<UpdateRussiaBillingInfo>(req.body()).and_then(move |payload| {
                     billing_info_service
                         .update_russia_billing_info(id, payload)
                         .map_err(failure::Error::from)
                 })
-            }),
+            })),
 
             (Get, Some(Route::FeesByOrder { id })) => serialize_future({ fees_service.get_by_order_id(id).map_err(failure::Error::from) }),
             (Post, Some(Route::FeesPay { id })) => serialize_future({ fees_service.create_charge(SearchFee::Id(id)) }),
+            (Post, Some(Route::FeeCryptoPayment { id })) => serialize_future({
+                parse_body::<CreateFeeCryptoPaymentRequest>(req.body())
+                    .and_then(move |payload| fees_service.create_crypto_payment(id, payload).map_err(failure::Error::from))
+            }),
+            (Get, Some(Route::FeeCryptoPayment { id })) => {
+                serialize_future({ fees_service.get_crypto_payment(id).map_err(failure::Error::from) })
+            }
             (Post, Some(Route::FeesPayByOrder { id })) => serialize_future({ fees_service.create_charge(SearchFee::OrderId(id)) }),
             (Post, Some(Route::FeesPayByOrders)) => serialize_future({
                 parse_body::<FeesPayByOrdersRequest>(req.body())
                     .and_then(move |payload| fees_service.create_charge_for_several_fees(payload).map_err(failure::Error::from))
             }),
-            (Get, Some(Route::RussiaBillingInfoByStore { id })) => serialize_future({
+            (Get, Some(Route::RussiaBillingInfoByStore { id })) => serialize_future(self.masked(user_id, {
                 billing_info_service
                     .get_russia_billing_info_by_store(id)
                     .map_err(failure::Error::from)
-            }),
-            (Get, Some(Route::InternationalBillingInfoByStore { id })) => serialize_future({
+            })),
+            (Get, Some(Route::InternationalBillingInfoByStore { id })) => serialize_future(self.masked(user_id, {
                 billing_info_service
                     .get_international_billing_info_by_store(id)
                     .map_err(failure::Error::from)
-            }),
+            })),
+            (Get, Some(Route::BillingInfoChanges)) => serialize_future(self.masked(user_id, {
+                billing_info_service
+                    .get_pending_billing_info_changes()
+                    .map_err(failure::Error::from)
+            })),
+            (Get, Some(Route::BillingInfoChangesByStore { store_id })) => serialize_future(self.masked(user_id, {
+                billing_info_service
+                    .get_billing_info_changes_by_store(store_id)
+                    .map_err(failure::Error::from)
+            })),
+            (Post, Some(Route::BillingInfoChangeApprove { id })) => {
+                serialize_future(self.masked(user_id, billing_info_service.approve_billing_info_change(id)))
+            }
+            (Post, Some(Route::BillingInfoChangeReject { id })) => serialize_future(self.masked(user_id, {
+                parse_body::<RejectBillingInfoChangeRequest>(req.body()).and_then(move |payload| {
+                    billing_info_service
+                        .reject_billing_info_change(id, payload)
+                        .map_err(failure::Error::from)
+                })
+            })),
             (Get, Some(Route::BillingTypeByStore { id })) => {
                 serialize_future({ billing_type_service.get_billing_type_by_store(id).map_err(failure::Error::from) })
             }
             (Post, Some(Route::Payouts)) => serialize_future({
                 parse_body::<PayOutToSellerPayload>(req.body()).and_then(move |payload| {
-                    payout_service
-                        .pay_out_to_seller(payload)
-                        .map_err(Error::from)
-                        .map_err(failure::Error::from)
+                    audit_log_service.audit_result(
+                        AuditAction::PayoutCreated,
+                        move || {
+                            payout_service
+                                .pay_out_to_seller(payload)
+                                .map_err(Error::from)
+                                .map_err(failure::Error::from)
+                        },
+                        |payout: &PayoutOutput| AuditChange::created(AuditResource::payout(payout.id), payout),
+                    )
                 })
             }),
             (Get, Some(Route::PayoutsByStoreId { id })) => serialize_future(
@@ -470,6 +1042,12 @@ impl<
                     .map_err(Error::from)
                     .map_err(failure::Error::from),
             ),
+            (Get, Some(Route::StoreBalanceOverview { store_id })) => serialize_future(
+                payout_service
+                    .get_balance_overview(store_id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
             (Post, Some(Route::PayoutsCalculate)) => serialize_future({
                 parse_body::<CalculatePayoutPayload>(req.body()).and_then(move |payload| {
                     payout_service
@@ -478,6 +1056,32 @@ impl<
                         .map_err(failure::Error::from)
                 })
             }),
+            (Put, Some(Route::UserWalletDeactivate { id })) => serialize_future(
+                user_wallet_service
+                    .deactivate_wallet(id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::UserWalletVerification { id })) => serialize_future(
+                user_wallet_service
+                    .get_wallet_verification(id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Post, Some(Route::UserWalletVerification { id })) => serialize_future(
+                user_wallet_service
+                    .start_wallet_verification(id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Post, Some(Route::UserWalletVerificationConfirm { id })) => serialize_future({
+                parse_body::<ConfirmWalletVerificationRequest>(req.body()).and_then(move |payload| {
+                    user_wallet_service
+                        .confirm_wallet_verification(id, payload)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from)
+                })
+            }),
             (Post, Some(Route::Subscriptions)) => serialize_future({
                 parse_body::<CreateSubscriptionsRequest>(req.body()).and_then(move |payload| {
                     subscription_service
@@ -499,13 +1103,7 @@ impl<
                     .map_err(failure::Error::from),
             ),
             (Post, Some(Route::SubscriptionPaymentSearch)) => {
-                let (skip_opt, count_opt) = parse_query!(
-                    req.query().unwrap_or_default(),
-                    "skip" => i64, "count" => i64
-                );
-
-                let skip = skip_opt.unwrap_or(0);
-                let count = count_opt.unwrap_or(0);
+                let Pagination { skip, count } = extractors::pagination(&req);
 
                 serialize_future(parse_body::<SubscriptionPaymentSearch>(req.body()).and_then(move |payload| {
                     subscription_payment_service
@@ -514,6 +1112,19 @@ impl<
                         .map_err(failure::Error::from)
                 }))
             }
+            (Get, Some(Route::StoreSubscriptionPayments { store_id })) => {
+                let Pagination { skip, count } = extractors::pagination(&req);
+
+                let fut = subscription_payment_service
+                    .get_store_subscription_payments(store_id, skip, count)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from);
+
+                match extractors::export_format(&req) {
+                    ExportFormat::Json => serialize_future(fut),
+                    ExportFormat::Csv => Box::new(fut.map(|receipts| subscription_payment_receipts_csv(&receipts.subscription_payments))),
+                }
+            }
 
             (Post, Some(Route::StoreSubscriptionByStoreId { store_id })) => {
                 serialize_future(parse_body::<CreateStoreSubscriptionRequest>(req.body()).and_then(move |payload| {
@@ -534,6 +1145,401 @@ impl<
                         .map_err(failure::Error::from)
                 }))
             }
+            (Post, Some(Route::StoreSubscriptionTrialStart { store_id })) => serialize_future(
+                store_subscription_service
+                    .start_trial(store_id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Post, Some(Route::StoreSubscriptionTrialExtend { store_id })) => {
+                serialize_future(parse_body::<ExtendTrialRequest>(req.body()).and_then(move |payload| {
+                    audit_log_service.audit(AuditAction::TrialExtended, AuditTarget::StoreSubscription(store_id), move || {
+                        store_subscription_service
+                            .extend_trial(store_id, payload)
+                            .map_err(Error::from)
+                            .map_err(failure::Error::from)
+                    })
+                }))
+            }
+            (Post, Some(Route::StoreSubscriptionTrialEnd { store_id })) => {
+                serialize_future(
+                    audit_log_service.audit(AuditAction::TrialEnded, AuditTarget::StoreSubscription(store_id), move || {
+                        store_subscription_service
+                            .end_trial(store_id)
+                            .map_err(Error::from)
+                            .map_err(failure::Error::from)
+                    }),
+                )
+            }
+            (Post, Some(Route::FeeStatementsGenerate)) => {
+                serialize_future(parse_body::<GenerateFeeStatementsRequest>(req.body()).and_then(move |payload| {
+                    fee_statement_service
+                        .generate_fee_statements(payload)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from)
+                }))
+            }
+            (Get, Some(Route::FeeStatementsByStoreId { store_id })) => serialize_future(
+                fee_statement_service
+                    .get_fee_statements_by_store(store_id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::FeeStatementDownload { id })) => serialize_future(
+                fee_statement_service
+                    .download_fee_statement(id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Post, Some(Route::AccountArchive { account_id })) => {
+                serialize_future(
+                    audit_log_service.audit(AuditAction::AccountArchived, AuditTarget::Account(account_id), move || {
+                        service
+                            .archive_account(account_id)
+                            .map_err(Error::from)
+                            .map_err(failure::Error::from)
+                    }),
+                )
+            }
+            (Post, Some(Route::SystemAccountsTransfer)) => serialize_future({
+                parse_body::<SystemAccountsTransferRequest>(req.body()).and_then(move |payload| {
+                    audit_log_service
+                        .audit_result(
+                            AuditAction::SystemAccountsTransferred,
+                            move || {
+                                service
+                                    .transfer_between_system_accounts(payload)
+                                    .map_err(Error::from)
+                                    .map_err(failure::Error::from)
+                            },
+                            |transfer: &SystemAccountsTransfer| {
+                                AuditChange::created(AuditResource::account(transfer.from_account_id), transfer)
+                            },
+                        )
+                        .map(SystemAccountsTransferResponse::from)
+                })
+            }),
+            (Post, Some(Route::AuditLogSearch)) => {
+                let Pagination { skip, count } = extractors::pagination(&req);
+
+                serialize_future(parse_body::<AuditLogSearch>(req.body()).and_then(move |payload| {
+                    audit_log_service
+                        .search_audit_log(skip, count, payload)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from)
+                }))
+            }
+            (Get, Some(Route::StoreBillingStatus { store_id })) => serialize_future(
+                store_billing_status_service
+                    .get_billing_status(store_id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Post, Some(Route::StoreBillingStatusOverride { store_id })) => serialize_future(
+                parse_body::<OverrideStoreBillingStatusRequest>(req.body()).and_then(move |payload| {
+                    audit_log_service.audit(
+                        AuditAction::StoreBillingStatusOverridden,
+                        AuditTarget::StoreBillingStatus(store_id),
+                        move || {
+                            store_billing_status_service
+                                .override_billing_status(store_id, payload)
+                                .map_err(Error::from)
+                                .map_err(failure::Error::from)
+                        },
+                    )
+                }),
+            ),
+            (Post, Some(Route::StoreBillingStatusReinstate { store_id })) => serialize_future(audit_log_service.audit(
+                AuditAction::StoreReinstated,
+                AuditTarget::StoreBillingStatus(store_id),
+                move || {
+                    store_billing_status_service
+                        .reinstate_store(store_id)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from)
+                },
+            )),
+            (Get, Some(Route::StoreOffboarding { store_id })) => serialize_future(
+                store_offboarding_service
+                    .get_store_offboarding(store_id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::PayoutRestriction { store_id })) => serialize_future(
+                payout_restriction_service
+                    .get_payout_restriction(store_id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Post, Some(Route::PayoutRestrictionOverride { store_id })) => {
+                serialize_future(parse_body::<OverridePayoutRestrictionRequest>(req.body()).and_then(move |payload| {
+                    audit_log_service.audit(
+                        AuditAction::PayoutRestrictionOverridden,
+                        AuditTarget::PayoutRestrictionOverride(store_id),
+                        move || {
+                            payout_restriction_service
+                                .override_payout_restriction(store_id, payload)
+                                .map_err(Error::from)
+                                .map_err(failure::Error::from)
+                        },
+                    )
+                }))
+            }
+            (Delete, Some(Route::PayoutRestrictionOverride { store_id })) => serialize_future(audit_log_service.audit(
+                AuditAction::PayoutRestrictionOverrideRemoved,
+                AuditTarget::PayoutRestrictionOverride(store_id),
+                move || {
+                    payout_restriction_service
+                        .remove_payout_restriction_override(store_id)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from)
+                },
+            )),
+            (Get, Some(Route::StoreWebhooksByStoreId { store_id })) => serialize_future(
+                store_webhook_service
+                    .get_store_webhooks(store_id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Post, Some(Route::StoreWebhooksByStoreId { store_id })) => {
+                serialize_future(parse_body::<CreateStoreWebhookRequest>(req.body()).and_then(move |payload| {
+                    store_webhook_service
+                        .create_store_webhook(store_id, payload)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from)
+                }))
+            }
+            (Put, Some(Route::StoreWebhook { id })) => {
+                serialize_future(parse_body::<UpdateStoreWebhookRequest>(req.body()).and_then(move |payload| {
+                    store_webhook_service
+                        .update_store_webhook(id, payload)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from)
+                }))
+            }
+            (Delete, Some(Route::StoreWebhook { id })) => serialize_future(
+                store_webhook_service
+                    .delete_store_webhook(id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::ApiKeysByStoreId { store_id })) => serialize_future(
+                api_key_service
+                    .get_api_keys(store_id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Post, Some(Route::ApiKeysByStoreId { store_id })) => {
+                serialize_future(parse_body::<CreateApiKeyRequest>(req.body()).and_then(move |payload| {
+                    api_key_service
+                        .create_api_key(store_id, payload)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from)
+                }))
+            }
+            (Delete, Some(Route::ApiKey { id })) => serialize_future(
+                api_key_service
+                    .revoke_api_key(id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::PayoutInstructionsByStoreId { store_id })) => serialize_future(
+                payout_instructions_service
+                    .get_payout_instructions_by_store(store_id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Post, Some(Route::PayoutInstructionsByStoreId { store_id })) => {
+                serialize_future(parse_body::<GeneratePayoutInstructionRequest>(req.body()).and_then(move |payload| {
+                    payout_instructions_service
+                        .generate_payout_instruction(store_id, payload)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from)
+                }))
+            }
+            (Get, Some(Route::PayoutInstruction { id })) => serialize_future(
+                payout_instructions_service
+                    .get_payout_instruction(id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::PayoutStatementDownload { id })) => serialize_future(
+                payout_statement_service
+                    .download_payout_statement(id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Post, Some(Route::StripeFeeBackfills)) => serialize_future(
+                stripe_fee_backfill_service
+                    .start_stripe_fee_backfill()
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::StripeFeeBackfill { id })) => serialize_future(
+                stripe_fee_backfill_service
+                    .get_stripe_fee_backfill(id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Post, Some(Route::BillingInfoReencryption)) => serialize_future(
+                billing_info_service
+                    .reencrypt_billing_infos()
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::PaymentRecoveriesReport)) => {
+                let (created_from, created_to) = parse_query!(
+                    req.query().unwrap_or_default(),
+                    "created_from" => NaiveDateTime, "created_to" => NaiveDateTime
+                );
+
+                serialize_future(
+                    payment_recovery_service
+                        .get_report(created_from, created_to)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from),
+                )
+            }
+            (Get, Some(Route::InvoiceInspection { invoice_id })) => serialize_future(
+                payment_attempt_service
+                    .get_invoice_inspection(invoice_id)
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Post, Some(Route::LegacyInvoicesExpirationSweep)) => {
+                serialize_future(legacy_invoice_expiration_service.sweep_expired_invoices())
+            }
+            (Get, Some(Route::CashbackLiabilities)) => serialize_future(
+                cashback_liability_service
+                    .get_cashback_liabilities()
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::CashbackLiabilitySnapshots)) => {
+                let as_of = parse_query!(req.query().unwrap_or_default(), "as_of" => NaiveDateTime);
+
+                serialize_future(
+                    cashback_liability_service
+                        .get_cashback_liability_snapshots(as_of)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from),
+                )
+            }
+            (Get, Some(Route::HistoricalReport)) => {
+                let as_of = parse_query!(req.query().unwrap_or_default(), "as_of" => NaiveDateTime);
+
+                serialize_future(
+                    historical_report_service
+                        .get_historical_report(as_of)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from),
+                )
+            }
+            (Get, Some(Route::NegativeStoreBalances)) => serialize_future(
+                store_balance_service
+                    .get_negative_store_balances()
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::ExchangeRateSlippages)) => serialize_future(
+                exchange_rate_slippage_service
+                    .get_exchange_rate_slippages()
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::SchemaVersion)) => serialize_future(
+                schema_migration_service
+                    .get_schema_version()
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Get, Some(Route::FeatureFlags)) => serialize_future(
+                feature_flag_service
+                    .list_feature_flags()
+                    .map_err(Error::from)
+                    .map_err(failure::Error::from),
+            ),
+            (Put, Some(Route::FeatureFlag { feature })) => {
+                serialize_future(parse_body::<UpdateFeatureFlagRequest>(req.body()).and_then(move |payload| {
+                    audit_log_service.audit(AuditAction::FeatureFlagUpdated, AuditTarget::FeatureFlag(feature), move || {
+                        feature_flag_service
+                            .update_feature_flag(feature, payload)
+                            .map_err(Error::from)
+                            .map_err(failure::Error::from)
+                    })
+                }))
+            }
+            (Post, Some(Route::DataRetentionPurge)) => {
+                serialize_future(parse_body::<DataRetentionPurgeRequest>(req.body()).and_then(move |payload| {
+                    data_retention_service
+                        .purge(payload.dry_run)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from)
+                }))
+            }
+            (Put, Some(Route::StoreLegalHold { store_id })) => {
+                let subject = DataSubject::Store(store_id);
+                let target = AuditTarget::DataRetentionSubject(subject);
+                serialize_future(parse_body::<LegalHoldRequest>(req.body()).and_then(move |payload| {
+                    audit_log_service.audit(AuditAction::LegalHoldUpdated, target, move || {
+                        data_retention_service
+                            .set_legal_hold(subject, payload)
+                            .map_err(Error::from)
+                            .map_err(failure::Error::from)
+                    })
+                }))
+            }
+            (Put, Some(Route::UserLegalHold { user_id })) => {
+                let subject = DataSubject::User(user_id);
+                let target = AuditTarget::DataRetentionSubject(subject);
+                serialize_future(parse_body::<LegalHoldRequest>(req.body()).and_then(move |payload| {
+                    audit_log_service.audit(AuditAction::LegalHoldUpdated, target, move || {
+                        data_retention_service
+                            .set_legal_hold(subject, payload)
+                            .map_err(Error::from)
+                            .map_err(failure::Error::from)
+                    })
+                }))
+            }
+            (Post, Some(Route::SandboxInboundTx { invoice_id })) => {
+                serialize_future(parse_body::<SimulateInboundTxRequest>(req.body()).and_then(move |payload| {
+                    service
+                        .simulate_inbound_tx(invoice_id, payload)
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from)
+                }))
+            }
+            (Get, Some(Route::DebugDependencies)) => {
+                let dependency_stats = &self.static_context.dependency_stats;
+                let response = DependenciesResponse::new(dependency_stats.window(), dependency_stats.snapshot(Instant::now()));
+                serialize_future(future::ok::<_, failure::Error>(response))
+            }
+            (Get, Some(Route::Metrics)) => {
+                let currency_exchange_gauges = currency_exchange_cache_gauges(&self.static_context.currency_exchange_cache, Instant::now());
+                let stripe_webhook_metrics = self.static_context.stripe_keys.webhook_signature_metrics();
+                let work_queue_metrics = WorkQueue::metrics(&[&*CONTROLLER_WORK_QUEUE, &*EVENT_HANDLER_WORK_QUEUE]);
+                // account pools are only there with the payments integration configured
+                let account_pool_statuses = match dynamic_context.account_service.clone() {
+                    Some(account_service) => future::Either::A(account_service.get_account_pool_statuses()),
+                    None => future::Either::B(future::ok(vec![])),
+                };
+                Box::new(
+                    exchange_rate_slippage_service
+                        .get_exchange_rate_slippage_gauges()
+                        .join3(invoice_risk_service.get_invoice_risk_gauges(), account_pool_statuses)
+                        .map(move |(gauges, invoice_risk_gauges, account_pool_statuses)| {
+                            gauges
+                                + &invoice_risk_gauges
+                                + &currency_exchange_gauges
+                                + &account_pool_gauges(&account_pool_statuses)
+                                + &stripe_webhook_metrics
+                                + &work_queue_metrics
+                        })
+                        .map_err(Error::from)
+                        .map_err(failure::Error::from),
+                )
+            }
+            (Get, Some(Route::OpenApi)) => serialize_future(future::ok::<_, failure::Error>(openapi::spec(&routes::route_specs()))),
 
             // Fallback
             (m, _) => not_found(m, path),
@@ -550,6 +1556,25 @@ impl<
     }
 }
 
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > Controller for ControllerImpl<T, M, F>
+{
+    /// Handle a request and get future response.
+    /// Requests with the `X-Billing-Api-Key` header are authenticated with the store API key instead of the `Authorization` header
+    fn call(&self, req: Request) -> ControllerFuture {
+        match extractors::api_key(&req) {
+            Some(key) => self.call_with_api_key(req, key),
+            None => {
+                let user_id = extractors::user_id(&req);
+                self.route_request(req, user_id)
+            }
+        }
+    }
+}
+
 fn not_found(method: &Method, path: String) -> Box<Future<Item = String, Error = failure::Error>> {
     Box::new(future::err(
         format_err!("Request to non existing endpoint in billing microservice! {:?} {:?}", method, path)
@@ -557,11 +1582,3 @@ fn not_found(method: &Method, path: String) -> Box<Future<Item = String, Error =
             .into(),
     ))
 }
-
-fn get_user_id(req: &Request) -> Option<UserId> {
-    req.headers()
-        .get::<Authorization<String>>()
-        .map(|auth| auth.0.clone())
-        .and_then(|id| i32::from_str(&id).ok())
-        .map(UserId)
-}
@@ -0,0 +1,225 @@
+//! OpenAPI description of the billing API served at `/openapi.json`.
+//!
+//! Every route declares its path params, request body and response in its `RouteSpec`
+//! next to the route definition, request and response types describe their JSON shape
+//! by implementing `ApiSchema`. Routes without a declared body are described as taking
+//! or returning arbitrary JSON.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use super::routes::{PathParamKind, RouteSpec};
+use models::Feature;
+
+const OPENAPI_VERSION: &'static str = "3.0.0";
+const API_TITLE: &'static str = "Billing";
+
+/// JSON schema of a type as it is serialized by serde
+pub trait ApiSchema {
+    fn schema() -> Value;
+
+    /// Whether a struct field of this type may be omitted
+    fn is_optional() -> bool {
+        false
+    }
+}
+
+/// Describes a struct as an object with the listed fields,
+/// fields of `Option` types are not required
+macro_rules! api_object {
+    ($ty:ty { $($field:ident: $field_ty:ty),* $(,)* }) => {
+        impl ApiSchema for $ty {
+            fn schema() -> ::serde_json::Value {
+                let mut properties = ::serde_json::Map::new();
+                let mut required = Vec::new();
+                $(
+                    properties.insert(stringify!($field).to_string(), <$field_ty as ApiSchema>::schema());
+                    if !<$field_ty as ApiSchema>::is_optional() {
+                        required.push(::serde_json::Value::from(stringify!($field)));
+                    }
+                )*
+                json!({ "type": "object", "properties": properties, "required": required })
+            }
+        }
+    };
+}
+
+/// Describes types serialized as a single JSON value, e.g. ids and unit enums
+macro_rules! api_scalar {
+    ($schema:expr => $($ty:ty),* $(,)*) => {
+        $(
+            impl ApiSchema for $ty {
+                fn schema() -> ::serde_json::Value {
+                    $schema
+                }
+            }
+        )*
+    };
+}
+
+mod schemas;
+
+api_scalar!(json!({ "type": "boolean" }) => bool);
+api_scalar!(json!({ "type": "integer", "format": "int32" }) => i32, u32);
+api_scalar!(json!({ "type": "integer", "format": "int64" }) => i64, u64, usize);
+api_scalar!(json!({ "type": "number", "format": "double" }) => f64);
+api_scalar!(json!({ "type": "string" }) => String);
+api_scalar!(json!({ "type": "string", "format": "uuid" }) => Uuid);
+api_scalar!(json!({ "type": "string", "format": "decimal" }) => BigDecimal);
+api_scalar!(json!({ "type": "string", "format": "date-time" }) => NaiveDateTime);
+api_scalar!(json!({}) => Value);
+
+impl<T: ApiSchema> ApiSchema for Option<T> {
+    fn schema() -> Value {
+        let mut schema = T::schema();
+        if let Some(schema) = schema.as_object_mut() {
+            schema.insert("nullable".to_string(), Value::Bool(true));
+        }
+        schema
+    }
+
+    fn is_optional() -> bool {
+        true
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for Vec<T> {
+    fn schema() -> Value {
+        json!({ "type": "array", "items": T::schema() })
+    }
+}
+
+impl<K: Eq + Hash, V: ApiSchema> ApiSchema for HashMap<K, V> {
+    fn schema() -> Value {
+        json!({ "type": "object", "additionalProperties": V::schema() })
+    }
+}
+
+/// Builds OpenAPI document describing the routes
+pub fn spec(route_specs: &[RouteSpec]) -> Value {
+    let mut paths = Map::new();
+
+    for route_spec in route_specs {
+        let operations = paths
+            .entry(route_spec.path.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Some(operations) = operations.as_object_mut() {
+            operations.insert(route_spec.method.to_string().to_lowercase(), operation(route_spec));
+        }
+    }
+
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": API_TITLE,
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+    })
+}
+
+fn operation(route_spec: &RouteSpec) -> Value {
+    let mut parameters = route_spec
+        .params
+        .iter()
+        .map(|(name, kind)| {
+            json!({
+                "name": name,
+                "in": "path",
+                "required": true,
+                "schema": path_param_schema(*kind),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for (name, kind) in &route_spec.query {
+        parameters.push(json!({
+            "name": name,
+            "in": "query",
+            "required": false,
+            "schema": path_param_schema(*kind),
+        }));
+    }
+
+    if route_spec.paginated {
+        for name in &["skip", "count"] {
+            parameters.push(json!({
+                "name": name,
+                "in": "query",
+                "required": false,
+                "schema": i64::schema(),
+            }));
+        }
+    }
+
+    let response_schema = route_spec.response.map(|schema| schema()).unwrap_or(json!({}));
+
+    let mut operation = json!({
+        "parameters": parameters,
+        "responses": {
+            "200": {
+                "description": "Success",
+                "content": { "application/json": { "schema": response_schema } },
+            },
+        },
+    });
+
+    if let Some(request) = route_spec.request {
+        operation["requestBody"] = json!({
+            "required": true,
+            "content": { "application/json": { "schema": request() } },
+        });
+    }
+
+    operation
+}
+
+fn path_param_schema(kind: PathParamKind) -> Value {
+    match kind {
+        PathParamKind::Integer => i32::schema(),
+        PathParamKind::Uuid => Uuid::schema(),
+        PathParamKind::String => String::schema(),
+        PathParamKind::Feature => Feature::schema(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use controller::routes::route_specs;
+
+    use super::*;
+
+    #[test]
+    fn spec_describes_every_route() {
+        let route_specs = route_specs();
+        let spec = spec(&route_specs);
+
+        for route_spec in route_specs {
+            let operation = &spec["paths"][route_spec.path][route_spec.method.to_string().to_lowercase()];
+            assert!(operation.is_object(), "{} {} is missing", route_spec.method, route_spec.path);
+            assert_eq!(
+                operation["parameters"].as_array().map(|params| params.len()),
+                Some(route_spec.params.len() + route_spec.query.len() + if route_spec.paginated { 2 } else { 0 })
+            );
+        }
+    }
+
+    #[test]
+    fn optional_fields_are_not_required() {
+        struct Payload;
+        api_object!(Payload {
+            name: String,
+            email: Option<String>,
+        });
+
+        let schema = Payload::schema();
+
+        assert_eq!(schema["required"], json!(["name"]));
+        assert_eq!(schema["properties"]["email"]["nullable"], json!(true));
+    }
+}
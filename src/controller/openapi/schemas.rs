@@ -0,0 +1,1154 @@
+//! Schemas of request and response bodies
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use serde_json::Value;
+use uuid::Uuid;
+
+use stq_static_resources::{Currency as StqCurrency, OrderState};
+use stq_types::{
+    stripe::PaymentIntentId, Alpha3, CurrencyExchangeId, ProductId, Quantity, StoreId as StqStoreId, SubscriptionPaymentId,
+    UserId as StqUserId,
+};
+
+use controller::requests::*;
+use controller::responses::*;
+use models::invoice_v2::{BuyerAmounts, InvoiceDump, InvoiceId, OrderDump, RateDump};
+use models::order_v2::{OrderId, StoreId};
+use models::{
+    AccountId, ApiKeyId, ApiKeyScope, BillingInfoChangeId, BillingInfoChangePayload, BillingInfoChangeStatus, BuyerBillingDetails,
+    ChargeId, CheckoutPaymentMethod, CheckoutPaymentTarget, CheckoutSession, CheckoutSessionStatus, CreateInvoiceV2, CreateOrderLineItem,
+    CreateOrderV2, Currency, CustomerId, DataSubjectType, ExchangeRateSource, ExchangeRateStatus, Feature, FeeConversion,
+    FeeCryptoPaymentId, FeeCryptoPaymentStatus, FeeId, FeeStatementId, FeeStatementLineKind, FeeStatus, FiatCurrency,
+    InvoiceCallbackEventType, InvoiceCallbackRegistration, InvoiceRiskAssessment, NegativeStoreBalanceId, NewSubscription,
+    OrderExchangeRateId, OrderLineItemId, PaymentAttemptId, PaymentAttemptStatus, PaymentIntentHistorySource, PaymentIntentStatus,
+    PaymentState, PayoutBankDetails, PayoutBeneficiary, PayoutId, PayoutInstructionDocument, PayoutInstructionId, PayoutRemitter,
+    PayoutStatementId, RetentionTable, RiskDecision, RiskReviewStatus, SetupIntentStatus, StoreBillingState, StoreInvoiceLineItem,
+    StoreOffboardingStatus, StoreSubscriptionStatus, StoreSuspensionReason, StoreWebhookEventType, StoreWebhookId, StripeFeeBackfillId,
+    StripeFeeBackfillStatus, SubscriptionPaymentStatus, SystemAccountType, TransactionId, TureCurrency, UserCreditEntryId,
+    UserCreditEntryKind, UserId, UserWalletId, WalletAddress, WalletVerificationId, WalletVerificationStatus,
+};
+
+use super::ApiSchema;
+
+api_scalar!(json!({ "type": "integer", "format": "int32" }) =>
+    ApiKeyId,
+    BillingInfoChangeId,
+    FeeId,
+    FeeStatementId,
+    NegativeStoreBalanceId,
+    PayoutInstructionId,
+    PayoutStatementId,
+    ProductId,
+    Quantity,
+    StoreId,
+    StoreWebhookId,
+    StqStoreId,
+    StqUserId,
+    StripeFeeBackfillId,
+    SubscriptionPaymentId,
+    UserId,
+);
+api_scalar!(json!({ "type": "integer", "format": "int64" }) => OrderExchangeRateId, PaymentAttemptId);
+api_scalar!(json!({ "type": "string", "format": "uuid" }) =>
+    AccountId,
+    CurrencyExchangeId,
+    FeeCryptoPaymentId,
+    InvoiceId,
+    OrderId,
+    OrderLineItemId,
+    PayoutId,
+    TransactionId,
+    UserCreditEntryId,
+    UserWalletId,
+    WalletVerificationId,
+);
+api_scalar!(json!({ "type": "string" }) =>
+    Alpha3,
+    ApiKeyScope,
+    BillingInfoChangeStatus,
+    CardBrand,
+    ChargeId,
+    CheckoutPaymentMethod,
+    CheckoutSessionStatus,
+    Currency,
+    CustomerId,
+    DataSubjectType,
+    ExchangeRateSource,
+    ExchangeRateStatus,
+    Feature,
+    FeeCryptoPaymentStatus,
+    FeeStatementLineKind,
+    FeeStatus,
+    FiatCurrency,
+    InvoiceCallbackEventType,
+    InvoiceRiskReviewDecision,
+    IpAddr,
+    OrderState,
+    PaymentAttemptStatus,
+    PaymentIntentHistorySource,
+    PaymentIntentId,
+    PaymentIntentStatus,
+    PaymentState,
+    RetentionTable,
+    RiskDecision,
+    RiskReviewStatus,
+    SetupIntentStatus,
+    SimulatedPaymentKind,
+    StoreBillingState,
+    StoreOffboardingStatus,
+    StoreSubscriptionStatus,
+    StoreSuspensionReason,
+    StoreWebhookEventType,
+    StqCurrency,
+    StripeFeeBackfillStatus,
+    SubscriptionPaymentStatus,
+    SystemAccountType,
+    TureCurrency,
+    UserCreditEntryKind,
+    WalletAddress,
+    WalletVerificationStatus,
+);
+
+// Requests
+
+api_object!(NewCustomerWithSourceRequest {
+    email: Option<String>,
+    card_token: String,
+});
+
+api_object!(NewSetupIntentRequest { email: Option<String> });
+
+api_object!(DeleteCustomerRequest { customer_id: CustomerId });
+
+api_object!(UpdateCustomerRequest {
+    email: Option<String>,
+    card_token: Option<String>,
+});
+
+api_object!(OrderPaymentStateRequest { state: PaymentState });
+
+api_object!(RefundOrderLineItemsRequest {
+    line_items: Vec<OrderLineItemRefund>,
+});
+
+api_object!(OrderLineItemRefund {
+    line_item_id: OrderLineItemId,
+    quantity: u32,
+});
+
+api_object!(FeesPayByOrdersRequest { order_ids: Vec<OrderId> });
+
+api_object!(CreateFeeCryptoPaymentRequest { currency: TureCurrency });
+
+api_object!(OrderFeePreviewsRequest { order_ids: Vec<OrderId> });
+
+api_object!(NewSubscription {
+    store_id: StqStoreId,
+    published_base_products_quantity: Quantity,
+});
+
+api_object!(CreateSubscriptionsRequest {
+    subscriptions: Vec<NewSubscription>,
+});
+
+api_object!(CreateStoreSubscriptionRequest { currency: StqCurrency });
+
+api_object!(UpdateStoreSubscriptionRequest {
+    currency: Option<StqCurrency>,
+    status: Option<StoreSubscriptionStatus>,
+});
+
+api_object!(ExtendTrialRequest { days: i64 });
+
+api_object!(GenerateFeeStatementsRequest {
+    year: Option<i32>,
+    month: Option<u32>,
+});
+
+api_object!(GeneratePayoutInstructionRequest { currency: FiatCurrency });
+
+api_object!(UpdateInvoiceDetailsRequest {
+    memo: Option<String>,
+    po_number: Option<String>,
+});
+
+impl ApiSchema for CreateStoreWebhookRequest {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": String::schema(),
+                "secret": Option::<String>::schema(),
+                "event_types": Vec::<StoreWebhookEventType>::schema(),
+            },
+            "required": ["url"],
+        })
+    }
+}
+
+api_object!(UpdateStoreWebhookRequest {
+    url: Option<String>,
+    secret: Option<String>,
+    event_types: Option<Vec<StoreWebhookEventType>>,
+});
+
+api_object!(CreateApiKeyRequest {
+    name: String,
+    scopes: Vec<ApiKeyScope>,
+});
+
+api_object!(SystemAccountsTransferRequest {
+    from: SystemAccountType,
+    to: SystemAccountType,
+    currency: TureCurrency,
+    amount: BigDecimal,
+});
+
+api_object!(ConfirmWalletVerificationRequest { amount: BigDecimal });
+
+api_object!(OverrideStoreBillingStatusRequest {
+    state: StoreBillingState,
+    until: Option<NaiveDateTime>,
+});
+
+api_object!(OverridePayoutRestrictionRequest { reason: String });
+
+api_object!(GrantUserCreditRequest {
+    currency: Currency,
+    amount: BigDecimal,
+    reason: String,
+});
+
+api_object!(UpdateFeatureFlagRequest {
+    enabled: bool,
+    rollout_percentage: i32,
+    store_ids: Vec<StqStoreId>,
+});
+
+api_object!(RejectBillingInfoChangeRequest { reason: Option<String> });
+
+api_object!(ReviewInvoiceRiskRequest {
+    decision: InvoiceRiskReviewDecision,
+});
+
+api_object!(CreateOrderV2 {
+    id: OrderId,
+    store: StoreId,
+    currency: Currency,
+    total_amount: f64,
+    product_cashback: Option<f64>,
+    line_items: Vec<CreateOrderLineItem>,
+});
+
+api_object!(CreateOrderLineItem {
+    product_id: ProductId,
+    name: String,
+    quantity: u32,
+    unit_price: BigDecimal,
+});
+
+api_object!(CreateInvoiceV2 {
+    orders: Vec<CreateOrderV2>,
+    customer_id: UserId,
+    currency: Currency,
+    saga_id: InvoiceId,
+    metadata: Option<Value>,
+    memo: Option<String>,
+    po_number: Option<String>,
+    buyer_country: Option<Alpha3>,
+    callback: Option<InvoiceCallbackRegistration>,
+    buyer_ip: Option<IpAddr>,
+    apply_credit: bool,
+    buyer_billing_details: Option<BuyerBillingDetails>,
+});
+
+api_object!(BuyerBillingDetails {
+    name: String,
+    address_line1: String,
+    address_line2: Option<String>,
+    city: String,
+    postal_code: Option<String>,
+    region: Option<String>,
+    country: Alpha3,
+    tax_id: Option<String>,
+});
+
+api_object!(InvoiceCallbackRegistration {
+    url: String,
+    secret: String,
+});
+
+api_object!(StoreInvoiceLineItem {
+    description: String,
+    quantity: u32,
+    unit_price: BigDecimal,
+});
+
+api_object!(CreateStoreInvoiceRequest {
+    customer_id: UserId,
+    currency: Currency,
+    line_items: Vec<StoreInvoiceLineItem>,
+    memo: Option<String>,
+    po_number: Option<String>,
+    buyer_country: Option<Alpha3>,
+    callback: Option<InvoiceCallbackRegistration>,
+});
+
+api_object!(InvoiceRequoteRequest {
+    buyer_country: Option<Alpha3>,
+});
+
+// Responses
+
+api_object!(PaymentIntentResponse {
+    id: PaymentIntentId,
+    amount: f64,
+    amount_received: f64,
+    client_secret: Option<String>,
+    currency: StqCurrency,
+    last_payment_error_message: Option<String>,
+    receipt_email: Option<String>,
+    charge_id: Option<ChargeId>,
+    status: PaymentIntentStatus,
+});
+
+api_object!(PaymentIntentHistoryEntryResponse {
+    status: PaymentIntentStatus,
+    source: PaymentIntentHistorySource,
+    created_at: NaiveDateTime,
+});
+
+api_object!(PaymentIntentHistoryResponse {
+    payment_intent_id: PaymentIntentId,
+    status: PaymentIntentStatus,
+    history: Vec<PaymentIntentHistoryEntryResponse>,
+});
+
+api_object!(BuyerAmounts {
+    exchange_rate: BigDecimal,
+    currency: Currency,
+    price: BigDecimal,
+});
+
+api_object!(RateDump {
+    id: OrderExchangeRateId,
+    exchange_rate: BigDecimal,
+    status: ExchangeRateStatus,
+    reserved_at: NaiveDateTime,
+});
+
+api_object!(OrderDump {
+    id: OrderId,
+    seller_currency: Currency,
+    seller_price: BigDecimal,
+    seller_cashback: BigDecimal,
+    buyer_amounts: Option<BuyerAmounts>,
+    rates: Vec<RateDump>,
+});
+
+api_object!(InvoiceDump {
+    id: InvoiceId,
+    buyer_currency: Currency,
+    amount_captured: BigDecimal,
+    total_price: BigDecimal,
+    total_cashback: Option<BigDecimal>,
+    orders: Vec<OrderDump>,
+    has_missing_rates: bool,
+    created_at: NaiveDateTime,
+    paid_at: Option<NaiveDateTime>,
+    wallet_address: Option<WalletAddress>,
+    status: OrderState,
+    metadata: Option<Value>,
+    memo: Option<String>,
+    po_number: Option<String>,
+    price_reserved: NaiveDateTime,
+    public_token: String,
+});
+
+api_object!(PublicInvoiceStatusResponse {
+    status: OrderState,
+    currency: Currency,
+    amount_remaining: BigDecimal,
+    expires_at: NaiveDateTime,
+});
+
+impl ApiSchema for CheckoutPaymentTarget {
+    fn schema() -> Value {
+        json!({
+            "oneOf": [
+                {
+                    "type": "object",
+                    "properties": { "type": { "type": "string", "enum": ["wallet_address"] }, "wallet_address": WalletAddress::schema() },
+                    "required": ["type", "wallet_address"],
+                },
+                {
+                    "type": "object",
+                    "properties": { "type": { "type": "string", "enum": ["client_secret"] }, "client_secret": String::schema() },
+                    "required": ["type", "client_secret"],
+                },
+            ],
+        })
+    }
+}
+
+api_object!(CheckoutSession {
+    invoice_id: InvoiceId,
+    payment_method: CheckoutPaymentMethod,
+    payment_target: Option<CheckoutPaymentTarget>,
+    currency: Currency,
+    amount: BigDecimal,
+    amount_captured: BigDecimal,
+    status: CheckoutSessionStatus,
+    expires_at: NaiveDateTime,
+    status_url: String,
+});
+
+impl ApiSchema for CreateInvoiceV2Response {
+    fn schema() -> Value {
+        json!({
+            "allOf": [
+                InvoiceDump::schema(),
+                {
+                    "type": "object",
+                    "properties": { "checkout_session": CheckoutSession::schema() },
+                    "required": ["checkout_session"],
+                },
+            ],
+        })
+    }
+}
+
+impl ApiSchema for StoreInvoiceResponse {
+    fn schema() -> Value {
+        json!({
+            "allOf": [
+                InvoiceDump::schema(),
+                {
+                    "type": "object",
+                    "properties": {
+                        "checkout_session": CheckoutSession::schema(),
+                        "payment_url": String::schema(),
+                    },
+                    "required": ["checkout_session", "payment_url"],
+                },
+            ],
+        })
+    }
+}
+
+impl ApiSchema for InvoiceRequoteResponse {
+    fn schema() -> Value {
+        json!({
+            "allOf": [
+                InvoiceDump::schema(),
+                {
+                    "type": "object",
+                    "properties": {
+                        "checkout_session": CheckoutSession::schema(),
+                        "requoted_from": InvoiceId::schema(),
+                    },
+                    "required": ["checkout_session", "requoted_from"],
+                },
+            ],
+        })
+    }
+}
+
+api_object!(OrderResponse {
+    id: OrderId,
+    seller_currency: StqCurrency,
+    total_amount: f64,
+    cashback_amount: f64,
+    invoice_id: InvoiceId,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    store_id: StoreId,
+    state: PaymentState,
+    stripe_fee: Option<f64>,
+    line_items: Vec<OrderLineItemResponse>,
+});
+
+api_object!(OrderLineItemResponse {
+    id: OrderLineItemId,
+    product_id: ProductId,
+    name: String,
+    quantity: i32,
+    unit_price: BigDecimal,
+    refunded_quantity: i32,
+});
+
+api_object!(OrderSearchResultsResponse {
+    total_count: i64,
+    orders: Vec<OrderResponse>,
+});
+
+api_object!(OrderExchangeRateResponse {
+    id: OrderExchangeRateId,
+    exchange_rate: BigDecimal,
+    status: ExchangeRateStatus,
+    source: ExchangeRateSource,
+    used_for_payment: bool,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+});
+
+api_object!(OrderExchangeRatesResponse {
+    order_id: OrderId,
+    invoice_id: InvoiceId,
+    paid_at: Option<NaiveDateTime>,
+    rates: Vec<OrderExchangeRateResponse>,
+});
+
+api_object!(OrderFeePreviewResponse {
+    order_id: OrderId,
+    amount: BigDecimal,
+    currency: Currency,
+    rate: Option<FeeRateSnapshotResponse>,
+});
+
+api_object!(FeeRateSnapshotResponse {
+    currency_exchange_id: CurrencyExchangeId,
+    from: Currency,
+    to: Currency,
+    exchange_rate: f64,
+});
+
+api_object!(Card {
+    id: String,
+    brand: CardBrand,
+    country: String,
+    customer: Option<String>,
+    exp_month: u32,
+    exp_year: u32,
+    last4: String,
+    name: Option<String>,
+});
+
+api_object!(CustomerResponse {
+    id: CustomerId,
+    user_id: StqUserId,
+    email: Option<String>,
+    payment_method_id: Option<String>,
+    cards: Vec<Card>,
+});
+
+api_object!(SetupIntentResponse {
+    id: String,
+    client_secret: Option<String>,
+    status: SetupIntentStatus,
+});
+
+api_object!(FeeResponse {
+    id: FeeId,
+    order_id: OrderId,
+    amount: f64,
+    status: FeeStatus,
+    currency: StqCurrency,
+    charge_id: Option<ChargeId>,
+    metadata: Option<Value>,
+    conversion: Option<FeeConversion>,
+    charged_amount: Option<f64>,
+    charged_currency: Option<StqCurrency>,
+    charge_exchange_rate: Option<f64>,
+});
+
+api_object!(FeeConversion {
+    currency_exchange_id: Uuid,
+    from: Currency,
+    to: Currency,
+    exchange_rate: f64,
+    converted_at: NaiveDateTime,
+});
+
+api_object!(FeeCryptoPaymentResponse {
+    id: FeeCryptoPaymentId,
+    fee_id: FeeId,
+    wallet_address: WalletAddress,
+    currency: StqCurrency,
+    amount: BigDecimal,
+    amount_received: BigDecimal,
+    status: FeeCryptoPaymentStatus,
+    expires_at: NaiveDateTime,
+    closed_at: Option<NaiveDateTime>,
+});
+
+api_object!(SubscriptionPaymentResponse {
+    id: SubscriptionPaymentId,
+    store_id: StqStoreId,
+    amount: BigDecimal,
+    currency: StqCurrency,
+    charge_id: Option<ChargeId>,
+    transaction_id: Option<TransactionId>,
+    status: SubscriptionPaymentStatus,
+    created_at: NaiveDateTime,
+    quantity: Option<Quantity>,
+    unit_price: Option<BigDecimal>,
+});
+
+api_object!(SubscriptionPaymentSearchResponse {
+    total_count: i64,
+    subscription_payments: Vec<SubscriptionPaymentResponse>,
+});
+
+impl ApiSchema for SubscriptionPaymentReceiptResponse {
+    fn schema() -> Value {
+        json!({
+            "allOf": [
+                SubscriptionPaymentResponse::schema(),
+                {
+                    "type": "object",
+                    "properties": {
+                        "period_start": Option::<NaiveDateTime>::schema(),
+                        "period_end": Option::<NaiveDateTime>::schema(),
+                        "published_base_products_quantity": i64::schema(),
+                    },
+                    "required": ["published_base_products_quantity"],
+                },
+            ],
+        })
+    }
+}
+
+api_object!(SubscriptionPaymentReceiptsResponse {
+    total_count: i64,
+    subscription_payments: Vec<SubscriptionPaymentReceiptResponse>,
+});
+
+api_object!(AvailablePaymentMethod {
+    payment_method: CheckoutPaymentMethod,
+    currencies: Vec<Currency>,
+});
+
+api_object!(PaymentMethodsResponse {
+    payment_methods: Vec<AvailablePaymentMethod>,
+});
+
+api_object!(FeeStatementResponse {
+    id: FeeStatementId,
+    store_id: StqStoreId,
+    currency: StqCurrency,
+    period_start: NaiveDateTime,
+    period_end: NaiveDateTime,
+    fees_amount: BigDecimal,
+    adjustments_amount: BigDecimal,
+    taxes_amount: BigDecimal,
+    total_amount: BigDecimal,
+    created_at: NaiveDateTime,
+});
+
+api_object!(FeeStatementLineResponse {
+    kind: FeeStatementLineKind,
+    fee_id: Option<FeeId>,
+    order_id: Option<OrderId>,
+    amount: BigDecimal,
+    description: String,
+    created_at: Option<NaiveDateTime>,
+    conversion: Option<FeeConversion>,
+});
+
+impl ApiSchema for FeeStatementDocumentResponse {
+    fn schema() -> Value {
+        json!({
+            "allOf": [
+                FeeStatementResponse::schema(),
+                {
+                    "type": "object",
+                    "properties": { "lines": Vec::<FeeStatementLineResponse>::schema() },
+                    "required": ["lines"],
+                },
+            ],
+        })
+    }
+}
+
+api_object!(PayoutBankDetails {
+    name: String,
+    address: String,
+    iban: String,
+    swift: String,
+});
+
+api_object!(PayoutBeneficiary {
+    name: String,
+    address: String,
+    city: String,
+    country: String,
+    bank: PayoutBankDetails,
+});
+
+api_object!(PayoutRemitter {
+    name: String,
+    address: String,
+    city: String,
+    country: String,
+    bank: PayoutBankDetails,
+});
+
+api_object!(PayoutInstructionDocument {
+    beneficiary: PayoutBeneficiary,
+    remitter: Option<PayoutRemitter>,
+    account_currency: StqCurrency,
+    reference_code: String,
+    payment_details: String,
+});
+
+api_object!(PayoutInstructionResponse {
+    id: PayoutInstructionId,
+    store_id: StqStoreId,
+    currency: StqCurrency,
+    total_amount: BigDecimal,
+    stripe_fee: BigDecimal,
+    order_ids: Vec<OrderId>,
+    reference_code: String,
+    document: PayoutInstructionDocument,
+    created_at: NaiveDateTime,
+});
+
+api_object!(PayoutStatementLineResponse {
+    order_id: OrderId,
+    store_id: StoreId,
+    gross_amount: BigDecimal,
+    fee_amount: Option<BigDecimal>,
+    stripe_fee: Option<BigDecimal>,
+    net_amount: BigDecimal,
+    buyer_currency: StqCurrency,
+    exchange_rate: Option<BigDecimal>,
+});
+
+api_object!(PayoutStatementDocumentResponse {
+    id: PayoutStatementId,
+    payout_id: PayoutId,
+    user_id: StqUserId,
+    currency: StqCurrency,
+    gross_amount: BigDecimal,
+    net_amount: BigDecimal,
+    lines: Vec<PayoutStatementLineResponse>,
+    created_at: NaiveDateTime,
+});
+
+api_object!(StripeFeeBackfillResponse {
+    id: StripeFeeBackfillId,
+    status: StripeFeeBackfillStatus,
+    total_orders: i32,
+    processed_orders: i32,
+    updated_orders: i32,
+    failed_orders: i32,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    finished_at: Option<NaiveDateTime>,
+});
+
+api_object!(BillingInfoReencryptionResponse {
+    international_billing_infos: usize,
+    russia_billing_infos: usize,
+});
+
+api_object!(LegacyInvoiceExpirationSweepResponse {
+    invoices_expired: usize,
+    orders_expired: usize,
+});
+
+api_object!(DataRetentionPurgeRequest { dry_run: bool });
+
+api_object!(DataRetentionPurgeResponse {
+    dry_run: bool,
+    tables: Vec<RetentionTableReport>,
+});
+
+api_object!(RetentionTableReport {
+    table: RetentionTable,
+    retention_days: u32,
+    subjects: usize,
+    rows: u64,
+});
+
+api_object!(LegalHoldRequest {
+    legal_hold: bool,
+    reason: Option<String>,
+});
+
+api_object!(SimulateInboundTxRequest {
+    kind: SimulatedPaymentKind
+});
+
+api_object!(SimulatedInboundTxResponse {
+    invoice_id: InvoiceId,
+    transaction_id: TransactionId,
+    wallet_address: WalletAddress,
+    currency: Currency,
+    amount: BigDecimal,
+});
+
+api_object!(DataRetentionSubjectResponse {
+    subject_type: DataSubjectType,
+    subject_id: i32,
+    closed_at: Option<NaiveDateTime>,
+    legal_hold: bool,
+    legal_hold_reason: Option<String>,
+});
+
+api_object!(CashbackLiabilitiesResponse {
+    as_of: NaiveDateTime,
+    currencies: Vec<CashbackLiabilityResponse>,
+});
+
+api_object!(CashbackLiabilityResponse {
+    currency: StqCurrency,
+    amount: BigDecimal,
+    invoices_count: i64,
+});
+
+api_object!(CashbackLiabilitySnapshotResponse {
+    currency: StqCurrency,
+    as_of: NaiveDateTime,
+    amount: BigDecimal,
+    invoices_count: i64,
+    created_at: NaiveDateTime,
+});
+
+api_object!(NegativeStoreBalanceResponse {
+    id: NegativeStoreBalanceId,
+    store_id: StoreId,
+    currency: StqCurrency,
+    amount: BigDecimal,
+    detected_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    notified_at: Option<NaiveDateTime>,
+});
+
+api_object!(ExchangeRateSlippageResponse {
+    seller_currency: StqCurrency,
+    buyer_currency: StqCurrency,
+    orders_count: i64,
+    refreshed_orders_count: i64,
+    initial_price_total: BigDecimal,
+    final_price_total: BigDecimal,
+    slippage: BigDecimal,
+    slippage_ratio: BigDecimal,
+    updated_at: NaiveDateTime,
+});
+
+api_object!(SchemaVersionResponse {
+    current_version: Option<String>,
+    expected_version: Option<String>,
+    up_to_date: bool,
+    pending_migrations: Vec<String>,
+    unknown_migrations: Vec<String>,
+});
+
+api_object!(PaymentAttemptResponse {
+    id: PaymentAttemptId,
+    payment_intent_id: PaymentIntentId,
+    status: PaymentAttemptStatus,
+    decline_code: Option<String>,
+    error_code: Option<String>,
+    payment_method_fingerprint: Option<String>,
+    created_at: NaiveDateTime,
+});
+
+api_object!(PaymentAttemptCountResponse {
+    status: PaymentAttemptStatus,
+    count: i64,
+});
+
+api_object!(InvoiceRiskAssessment {
+    invoice_id: InvoiceId,
+    buyer_user_id: UserId,
+    buyer_ip: Option<String>,
+    score: i32,
+    decision: RiskDecision,
+    reasons: Vec<String>,
+    review_status: Option<RiskReviewStatus>,
+    reviewed_by: Option<StqUserId>,
+    reviewed_at: Option<NaiveDateTime>,
+    created_at: NaiveDateTime,
+});
+
+api_object!(InvoiceInspectionResponse {
+    invoice_id: InvoiceId,
+    status: OrderState,
+    paid_at: Option<NaiveDateTime>,
+    payment_intent_id: Option<PaymentIntentId>,
+    payment_attempts_count: i64,
+    payment_attempts_by_status: Vec<PaymentAttemptCountResponse>,
+    payment_methods_count: i64,
+    payment_attempts: Vec<PaymentAttemptResponse>,
+});
+
+impl ApiSchema for BillingInfoChangePayload {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "kind": { "type": "string", "enum": ["create_international", "update_international", "create_russia", "update_russia"] },
+                "id": i32::schema(),
+                "billing_info": Value::schema(),
+            },
+            "required": ["kind", "billing_info"],
+        })
+    }
+}
+
+api_object!(BillingInfoChangeResponse {
+    id: BillingInfoChangeId,
+    store_id: StqStoreId,
+    status: BillingInfoChangeStatus,
+    payload: BillingInfoChangePayload,
+    requested_by: StqUserId,
+    reviewed_by: Option<StqUserId>,
+    rejection_reason: Option<String>,
+    created_at: NaiveDateTime,
+    reviewed_at: Option<NaiveDateTime>,
+});
+
+api_object!(CustomersDeduplicationResponse {
+    users: usize,
+    deleted_customers: usize,
+});
+
+api_object!(DependenciesResponse {
+    window_sec: u64,
+    methods: Vec<DependencyMethodResponse>,
+});
+
+api_object!(DependencyMethodResponse {
+    dependency: String,
+    method: String,
+    latency_budget_ms: Option<u64>,
+    calls: usize,
+    failures: usize,
+    deadlines_exceeded: usize,
+    error_rate: f64,
+    latency_p50_ms: u64,
+    latency_p95_ms: u64,
+    latency_p99_ms: u64,
+    latency_max_ms: u64,
+    latency_histogram: Vec<LatencyBucketResponse>,
+});
+
+api_object!(LatencyBucketResponse {
+    le_ms: Option<u64>,
+    calls: usize,
+});
+
+api_object!(SystemAccountsTransferResponse {
+    transaction_id: Uuid,
+    from_account_id: Uuid,
+    to_account_id: Uuid,
+    currency: TureCurrency,
+    amount: BigDecimal,
+});
+
+api_object!(PaymentRecoveryReportResponse {
+    total: usize,
+    pending: usize,
+    notified: usize,
+    retried: usize,
+    recovered: usize,
+    expired: usize,
+    cancelled: usize,
+    recovery_rate: Option<f64>,
+});
+
+api_object!(StoreSubscriptionResponse {
+    store_id: StqStoreId,
+    currency: StqCurrency,
+    value: BigDecimal,
+    wallet_address: Option<WalletAddress>,
+    trial_start_date: Option<NaiveDateTime>,
+    trial_end_date: Option<NaiveDateTime>,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    status: StoreSubscriptionStatus,
+});
+
+api_object!(BalancesResponse {
+    currencies: HashMap<StqCurrency, BigDecimal>,
+});
+
+api_object!(StoreBalanceOverviewResponse {
+    store_id: StqStoreId,
+    currencies: Vec<CurrencyBalanceOverviewResponse>,
+    stq_fiat_estimates: Vec<StqFiatEstimateResponse>,
+});
+
+api_object!(CurrencyBalanceOverviewResponse {
+    currency: StqCurrency,
+    unpaid_invoices: BigDecimal,
+    paid_not_eligible: BigDecimal,
+    eligible: BigDecimal,
+    paid_out: BigDecimal,
+});
+
+api_object!(StqFiatEstimateResponse {
+    currency: StqCurrency,
+    exchange_rate: f64,
+    pending_amount: BigDecimal,
+});
+
+api_object!(StoreWebhookResponse {
+    id: StoreWebhookId,
+    store_id: StqStoreId,
+    url: String,
+    secret: String,
+    event_types: Vec<StoreWebhookEventType>,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+});
+
+api_object!(ApiKeyResponse {
+    id: ApiKeyId,
+    store_id: StqStoreId,
+    created_by: StqUserId,
+    name: String,
+    key_prefix: String,
+    scopes: Vec<ApiKeyScope>,
+    last_used_at: Option<NaiveDateTime>,
+    revoked_at: Option<NaiveDateTime>,
+    created_at: NaiveDateTime,
+});
+
+api_object!(CreatedApiKeyResponse {
+    api_key: ApiKeyResponse,
+    key: String,
+});
+
+api_object!(StoreBillingStatusResponse {
+    store_id: StqStoreId,
+    state: StoreBillingState,
+    suspension_reason: Option<StoreSuspensionReason>,
+    overdue_fees_count: i32,
+    unpaid_since: Option<NaiveDateTime>,
+    overridden_by: Option<StqUserId>,
+    override_until: Option<NaiveDateTime>,
+    evaluated_at: Option<NaiveDateTime>,
+});
+
+api_object!(PayoutRestrictionResponse {
+    store_id: StqStoreId,
+    country: Option<String>,
+    restricted_country: bool,
+    overridden_by: Option<StqUserId>,
+    override_reason: Option<String>,
+    payouts_allowed: bool,
+});
+
+api_object!(WrittenOffFeeResponse {
+    fee_id: FeeId,
+    order_id: OrderId,
+    currency: StqCurrency,
+    amount: BigDecimal,
+});
+
+api_object!(FinalPayoutResponse {
+    currency: StqCurrency,
+    gross_amount: BigDecimal,
+    settled_fees: BigDecimal,
+    amount: BigDecimal,
+    order_ids: Vec<OrderId>,
+});
+
+api_object!(StoreOffboardingResponse {
+    store_id: StqStoreId,
+    status: StoreOffboardingStatus,
+    requested_by: StqUserId,
+    subscription_cancelled_at: Option<NaiveDateTime>,
+    subscription_account_id: Option<AccountId>,
+    fees_written_off_at: Option<NaiveDateTime>,
+    written_off_fees: Vec<WrittenOffFeeResponse>,
+    final_payout_scheduled_at: Option<NaiveDateTime>,
+    final_payouts: Vec<FinalPayoutResponse>,
+    billing_info_archived_at: Option<NaiveDateTime>,
+    completed_at: Option<NaiveDateTime>,
+    created_at: NaiveDateTime,
+});
+
+api_object!(HistoricalStateTotalResponse {
+    state: String,
+    currency: StqCurrency,
+    count: i64,
+    amount: BigDecimal,
+});
+
+api_object!(HistoricalReportResponse {
+    as_of: NaiveDateTime,
+    invoices: Vec<HistoricalStateTotalResponse>,
+    fees: Vec<HistoricalStateTotalResponse>,
+    payouts: Vec<HistoricalStateTotalResponse>,
+    generated_at: NaiveDateTime,
+});
+
+api_object!(UserCreditBalanceResponse {
+    currency: Currency,
+    balance: BigDecimal,
+    updated_at: NaiveDateTime,
+});
+
+api_object!(UserCreditEntryResponse {
+    id: UserCreditEntryId,
+    currency: Currency,
+    kind: UserCreditEntryKind,
+    amount: BigDecimal,
+    invoice_id: Option<InvoiceId>,
+    reason: Option<String>,
+    created_by: Option<StqUserId>,
+    created_at: NaiveDateTime,
+});
+
+api_object!(UserCreditsResponse {
+    user_id: StqUserId,
+    balances: Vec<UserCreditBalanceResponse>,
+    entries: Vec<UserCreditEntryResponse>,
+});
+
+api_object!(FeatureFlagResponse {
+    feature: Feature,
+    enabled: bool,
+    rollout_percentage: i32,
+    store_ids: Vec<StqStoreId>,
+    is_default: bool,
+    updated_at: Option<NaiveDateTime>,
+});
+
+api_object!(InvoiceCallbackDeliveryResponse {
+    notification_id: Uuid,
+    event_type: InvoiceCallbackEventType,
+    succeeded: bool,
+    error: Option<String>,
+    created_at: NaiveDateTime,
+});
+
+api_object!(InvoiceCallbackResponse {
+    invoice_id: InvoiceId,
+    url: String,
+    created_at: NaiveDateTime,
+    deliveries: Vec<InvoiceCallbackDeliveryResponse>,
+});
+
+api_object!(InboundTransactionResponse {
+    id: TransactionId,
+    currency: Currency,
+    amount: BigDecimal,
+    status: Option<String>,
+    received_at: NaiveDateTime,
+});
+
+api_object!(UserWalletResponse {
+    id: UserWalletId,
+    address: WalletAddress,
+    currency: TureCurrency,
+    is_active: bool,
+    created_at: NaiveDateTime,
+});
+
+api_object!(WalletVerificationResponse {
+    id: WalletVerificationId,
+    user_wallet_id: UserWalletId,
+    status: WalletVerificationStatus,
+    expires_at: NaiveDateTime,
+    confirmed_at: Option<NaiveDateTime>,
+    created_at: NaiveDateTime,
+});
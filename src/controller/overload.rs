@@ -0,0 +1,87 @@
+//! Wraps the application to refuse requests while the work queue of the controller is full. They get
+//! `503 Service Unavailable` with `Retry-After` before any of their work is queued, metrics are served anyway
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{future, Future};
+use hyper;
+use hyper::header::RetryAfter;
+use hyper::server::{Request, Response, Service};
+use hyper::StatusCode;
+
+use stq_router::RouteParser;
+
+use super::routes::{resolve_route, Route};
+use config::WorkQueue as WorkQueueConfig;
+use services::work_queue::WorkQueue;
+
+pub struct OverloadApplication<S> {
+    inner: S,
+    route_parser: Arc<RouteParser<Route>>,
+    work_queue: WorkQueue,
+    retry_after: Duration,
+}
+
+impl<S> OverloadApplication<S> {
+    pub fn new(inner: S, route_parser: Arc<RouteParser<Route>>, work_queue: WorkQueue, config: &WorkQueueConfig) -> Self {
+        Self {
+            inner,
+            route_parser,
+            work_queue,
+            retry_after: Duration::from_secs(config.retry_after_sec),
+        }
+    }
+}
+
+impl<S> Service for OverloadApplication<S>
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error>,
+    S::Future: 'static,
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Request) -> Self::Future {
+        let is_metrics = resolve_route(&self.route_parser, req.path())
+            .map(|(route, _)| route == Route::Metrics)
+            .unwrap_or(false);
+
+        if !is_metrics && self.work_queue.is_full() {
+            self.work_queue.record_rejected();
+            warn!(
+                "Work queue of the controller is full with {} closures, refusing {} {}",
+                self.work_queue.depth(),
+                req.method(),
+                req.path()
+            );
+            return Box::new(future::ok(overloaded_response(self.retry_after)));
+        }
+
+        // admitted requests are never refused afterwards, their closures are queued even if the queue fills up meanwhile
+        Box::new(self.inner.call(req))
+    }
+}
+
+fn overloaded_response(retry_after: Duration) -> Response {
+    Response::new()
+        .with_status(StatusCode::ServiceUnavailable)
+        .with_header(RetryAfter::Delay(retry_after))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overloaded_response_tells_when_to_retry() {
+        let response = overloaded_response(Duration::from_secs(5));
+
+        assert_eq!(response.status(), StatusCode::ServiceUnavailable);
+        assert_eq!(
+            response.headers().get::<RetryAfter>(),
+            Some(&RetryAfter::Delay(Duration::from_secs(5)))
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Wraps the application to serve the endpoints reached without authentication:
+//! every client is limited to a number of requests per minute and caches may keep the responses for a while
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::{future, Future};
+use hyper;
+use hyper::header::{CacheControl, CacheDirective, RetryAfter};
+use hyper::server::{Request, Response, Service};
+use hyper::StatusCode;
+
+use stq_router::RouteParser;
+
+use super::routes::{resolve_route, Route};
+use config::PublicInvoices;
+
+pub struct PublicApplication<S> {
+    inner: S,
+    route_parser: Arc<RouteParser<Route>>,
+    rate_limiter: RateLimiter,
+    cache_max_age_sec: u32,
+}
+
+impl<S> PublicApplication<S> {
+    pub fn new(inner: S, route_parser: Arc<RouteParser<Route>>, rate_limiter: RateLimiter, config: &PublicInvoices) -> Self {
+        Self {
+            inner,
+            route_parser,
+            rate_limiter,
+            cache_max_age_sec: config.cache_max_age_sec,
+        }
+    }
+}
+
+impl<S> Service for PublicApplication<S>
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error>,
+    S::Future: 'static,
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Request) -> Self::Future {
+        let is_public = resolve_route(&self.route_parser, req.path())
+            .map(|(route, _)| is_public_route(&route))
+            .unwrap_or(false);
+        if !is_public {
+            return Box::new(self.inner.call(req));
+        }
+
+        let client = client_address(
+            req.headers().get_raw("X-Forwarded-For").and_then(|raw| raw.one()),
+            req.remote_addr(),
+        );
+        if let Err(retry_after) = self.rate_limiter.check(&client, Instant::now()) {
+            warn!("Client {} exceeded the rate limit of the public endpoints", client);
+            return Box::new(future::ok(
+                Response::new()
+                    .with_status(StatusCode::TooManyRequests)
+                    .with_header(RetryAfter::Delay(retry_after)),
+            ));
+        }
+
+        let cache_max_age_sec = self.cache_max_age_sec;
+        Box::new(self.inner.call(req).map(move |mut response| {
+            if response.status() == StatusCode::Ok || response.status() == StatusCode::NotModified {
+                response.headers_mut().set(CacheControl(vec![
+                    CacheDirective::Public,
+                    CacheDirective::MaxAge(cache_max_age_sec),
+                ]));
+            }
+            response
+        }))
+    }
+}
+
+/// Endpoints served without authentication
+fn is_public_route(route: &Route) -> bool {
+    match route {
+        Route::PublicInvoiceStatus { .. } => true,
+        _ => false,
+    }
+}
+
+/// The service sits behind the gateway, so the client is the address the gateway has appended to `X-Forwarded-For`.
+/// The entries before it are sent by the client and can't be trusted
+fn client_address(forwarded_for: Option<&[u8]>, remote_addr: Option<SocketAddr>) -> String {
+    forwarded_for
+        .and_then(|forwarded_for| ::std::str::from_utf8(forwarded_for).ok())
+        .and_then(|forwarded_for| forwarded_for.rsplit(',').next())
+        .map(|client| client.trim().to_string())
+        .filter(|client| !client.is_empty())
+        .or_else(|| remote_addr.map(|remote_addr| remote_addr.ip().to_string()))
+        .unwrap_or_default()
+}
+
+/// Counts the requests of every client in fixed windows, shared by all connections of the server
+#[derive(Clone)]
+pub struct RateLimiter {
+    requests_per_window: u32,
+    window: Duration,
+    windows: Arc<Mutex<Windows>>,
+}
+
+/// Window of every client and the time the windows of the clients gone quiet were last dropped
+struct Windows {
+    by_client: HashMap<String, (Instant, u32)>,
+    swept_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_window: u32, window: Duration) -> Self {
+        Self {
+            requests_per_window,
+            window,
+            windows: Arc::new(Mutex::new(Windows {
+                by_client: HashMap::new(),
+                swept_at: Instant::now(),
+            })),
+        }
+    }
+
+    pub fn per_minute(config: &PublicInvoices) -> Self {
+        Self::new(config.requests_per_minute, Duration::from_secs(60))
+    }
+
+    /// Counts the request of the client. Returns the time left until the next window
+    /// if the client has used up the requests of the current one
+    pub fn check(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        let mut windows = match self.windows.lock() {
+            Ok(windows) => windows,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        // windows of the clients gone quiet are dropped once per window, so the map only holds the recent clients
+        let window = self.window;
+        if now.duration_since(windows.swept_at) >= window {
+            windows
+                .by_client
+                .retain(|_, &mut (started_at, _)| now.duration_since(started_at) < window);
+            windows.swept_at = now;
+        }
+
+        let (started_at, requests) = windows.by_client.entry(client.to_string()).or_insert((now, 0));
+        if now.duration_since(*started_at) >= window {
+            *started_at = now;
+            *requests = 0;
+        }
+        if *requests >= self.requests_per_window {
+            return Err(window - now.duration_since(*started_at));
+        }
+
+        *requests += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limiter_resets_requests_in_next_window() {
+        let rate_limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert_eq!(rate_limiter.check("10.0.0.1", now), Ok(()));
+        assert_eq!(rate_limiter.check("10.0.0.1", now + Duration::from_secs(10)), Ok(()));
+        assert_eq!(
+            rate_limiter.check("10.0.0.1", now + Duration::from_secs(20)),
+            Err(Duration::from_secs(40))
+        );
+        assert_eq!(rate_limiter.check("10.0.0.2", now + Duration::from_secs(20)), Ok(()));
+        assert_eq!(rate_limiter.check("10.0.0.1", now + Duration::from_secs(60)), Ok(()));
+    }
+
+    #[test]
+    fn rate_limiter_drops_windows_of_quiet_clients() {
+        let rate_limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert_eq!(rate_limiter.check("10.0.0.1", now), Ok(()));
+        assert_eq!(rate_limiter.check("10.0.0.2", now + Duration::from_secs(30)), Ok(()));
+        assert_eq!(rate_limiter.check("10.0.0.2", now + Duration::from_secs(70)), Ok(()));
+
+        let windows = rate_limiter.windows.lock().unwrap();
+        assert_eq!(windows.by_client.keys().collect::<Vec<_>>(), vec!["10.0.0.2"]);
+    }
+
+    #[test]
+    fn client_is_the_address_appended_by_the_gateway() {
+        let remote_addr = "192.168.0.10:41000".parse().ok();
+
+        assert_eq!(client_address(Some(&b"203.0.113.7, 10.0.0.1"[..]), remote_addr), "10.0.0.1");
+        assert_eq!(client_address(Some(&b"198.51.100.1"[..]), remote_addr), "198.51.100.1");
+        assert_eq!(client_address(None, remote_addr), "192.168.0.10");
+    }
+}
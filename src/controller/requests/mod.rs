@@ -1,7 +1,15 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
 use stq_static_resources::Currency as StqCurrency;
+use stq_types::{Alpha3, StoreId as StqStoreId};
 
+use models::invoice_v2::UpdateInvoiceDetails;
 use models::order_v2::OrderId as Orderv2Id;
-use models::{CreateStoreSubscription, CustomerId, NewSubscription, PaymentState, StoreSubscriptionStatus, UpdateStoreSubscription};
+use models::{
+    ApiKeyScope, CreateStoreSubscription, Currency, CustomerId, FiatCurrency, InvoiceCallbackRegistration, NewSubscription,
+    OrderLineItemId, PaymentState, StoreBillingState, StoreInvoiceLineItem, StoreSubscriptionStatus, StoreWebhookEventType,
+    SystemAccountType, TureCurrency, UpdateStoreSubscription, UserId,
+};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct NewCustomerWithSourceRequest {
@@ -9,6 +17,13 @@ pub struct NewCustomerWithSourceRequest {
     pub card_token: String,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NewSetupIntentRequest {
+    /// Email of the Stripe customer, used only when the user doesn't have a customer yet
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct DeleteCustomerRequest {
     pub customer_id: CustomerId,
@@ -25,11 +40,34 @@ pub struct OrderPaymentStateRequest {
     pub state: PaymentState,
 }
 
+/// Units of line items of an order refunded to the buyer
+#[derive(Deserialize, Debug, Clone)]
+pub struct RefundOrderLineItemsRequest {
+    pub line_items: Vec<OrderLineItemRefund>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OrderLineItemRefund {
+    pub line_item_id: OrderLineItemId,
+    pub quantity: u32,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct FeesPayByOrdersRequest {
     pub order_ids: Vec<Orderv2Id>,
 }
 
+/// The fee is paid in its own currency if it is a cryptocurrency, or in the currency it was converted to
+#[derive(Deserialize, Debug, Clone)]
+pub struct CreateFeeCryptoPaymentRequest {
+    pub currency: TureCurrency,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OrderFeePreviewsRequest {
+    pub order_ids: Vec<Orderv2Id>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct CreateSubscriptionsRequest {
     pub subscriptions: Vec<NewSubscription>,
@@ -56,6 +94,12 @@ impl From<UpdateStoreSubscriptionRequest> for UpdateStoreSubscription {
     }
 }
 
+/// Number of days to add to the current end of the store trial
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExtendTrialRequest {
+    pub days: i64,
+}
+
 impl From<CreateStoreSubscriptionRequest> for CreateStoreSubscription {
     fn from(data: CreateStoreSubscriptionRequest) -> Self {
         CreateStoreSubscription {
@@ -63,3 +107,177 @@ impl From<CreateStoreSubscriptionRequest> for CreateStoreSubscription {
         }
     }
 }
+
+/// Month to generate fee statements for, defaults to the previous calendar month
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GenerateFeeStatementsRequest {
+    pub year: Option<i32>,
+    pub month: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GeneratePayoutInstructionRequest {
+    pub currency: FiatCurrency,
+}
+
+/// Omitted fields are left unchanged, empty strings clear them
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpdateInvoiceDetailsRequest {
+    pub memo: Option<String>,
+    pub po_number: Option<String>,
+}
+
+impl From<UpdateInvoiceDetailsRequest> for UpdateInvoiceDetails {
+    fn from(data: UpdateInvoiceDetailsRequest) -> Self {
+        let clear_if_empty = |value: String| if value.is_empty() { None } else { Some(value) };
+
+        UpdateInvoiceDetails {
+            memo: data.memo.map(clear_if_empty),
+            po_number: data.po_number.map(clear_if_empty),
+        }
+    }
+}
+
+/// Invoice a store bills its customer with outside of the marketplace cart
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateStoreInvoiceRequest {
+    pub customer_id: UserId,
+    pub currency: Currency,
+    pub line_items: Vec<StoreInvoiceLineItem>,
+    #[serde(default)]
+    pub memo: Option<String>,
+    #[serde(default)]
+    pub po_number: Option<String>,
+    /// Country of the customer, restricts the currencies the invoice can be paid in
+    #[serde(default)]
+    pub buyer_country: Option<Alpha3>,
+    /// Url notified of the status changes of the invoice
+    #[serde(default)]
+    pub callback: Option<InvoiceCallbackRegistration>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct InvoiceRequoteRequest {
+    /// Country of the buyer, restricts the currencies the new invoice can be paid in
+    #[serde(default)]
+    pub buyer_country: Option<Alpha3>,
+}
+
+/// Omitted secret is generated by the service, empty `event_types` subscribes to all events
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateStoreWebhookRequest {
+    pub url: String,
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub event_types: Vec<StoreWebhookEventType>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpdateStoreWebhookRequest {
+    pub url: Option<String>,
+    pub secret: Option<String>,
+    pub event_types: Option<Vec<StoreWebhookEventType>>,
+}
+
+/// The key acts for the store manager creating it, within its scopes
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+/// `amount` is given in super units, e.g. STQ rather than wei
+#[derive(Debug, Clone, Deserialize)]
+pub struct SystemAccountsTransferRequest {
+    pub from: SystemAccountType,
+    pub to: SystemAccountType,
+    pub currency: TureCurrency,
+    pub amount: BigDecimal,
+}
+
+/// Credit given to a user, e.g. for a refund or a promo. `amount` is given in super units, `reason` is kept with the credit
+#[derive(Debug, Clone, Deserialize)]
+pub struct GrantUserCreditRequest {
+    pub currency: Currency,
+    pub amount: BigDecimal,
+    pub reason: String,
+}
+
+/// `amount` of the verification transfer the user received, in super units
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConfirmWalletVerificationRequest {
+    pub amount: BigDecimal,
+}
+
+/// State a financial manager puts a store in, the unpaid fees policy leaves it alone
+/// until `until` or indefinitely if it is not set
+#[derive(Debug, Clone, Deserialize)]
+pub struct OverrideStoreBillingStatusRequest {
+    pub state: StoreBillingState,
+    pub until: Option<NaiveDateTime>,
+}
+
+/// Allows the payouts of a store whose country is restricted, `reason` is kept for compliance
+#[derive(Debug, Clone, Deserialize)]
+pub struct OverridePayoutRestrictionRequest {
+    pub reason: String,
+}
+
+/// Rollout of a feature, it is on for `store_ids` and for `rollout_percentage` percent of the other stores.
+/// A disabled flag turns the feature off everywhere
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpdateFeatureFlagRequest {
+    pub enabled: bool,
+    pub rollout_percentage: i32,
+    #[serde(default)]
+    pub store_ids: Vec<StqStoreId>,
+}
+
+/// Rejection of a pending billing info change, the reason is sent to the store owner
+#[derive(Debug, Clone, Deserialize)]
+pub struct RejectBillingInfoChangeRequest {
+    pub reason: Option<String>,
+}
+
+/// `dry_run` only reports the data the retention rules would purge
+#[derive(Debug, Clone, Deserialize)]
+pub struct DataRetentionPurgeRequest {
+    pub dry_run: bool,
+}
+
+/// Legal hold keeps the personal data of a store or a user past its retention period, a hold needs a reason
+#[derive(Debug, Clone, Deserialize)]
+pub struct LegalHoldRequest {
+    pub legal_hold: bool,
+    pub reason: Option<String>,
+}
+
+/// Inbound transaction simulated in a sandbox, see `SimulatedPaymentKind` for its amount
+#[derive(Debug, Clone, Deserialize)]
+pub struct SimulateInboundTxRequest {
+    pub kind: SimulatedPaymentKind,
+}
+
+/// Amount of a simulated transaction relative to the amount the invoice still awaits:
+/// all of it, half of it or half as much again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimulatedPaymentKind {
+    Full,
+    Partial,
+    Overpayment,
+}
+
+/// Decision of a superuser on an invoice held for a risk review
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReviewInvoiceRiskRequest {
+    pub decision: InvoiceRiskReviewDecision,
+}
+
+/// Approved invoices are captured as allowed ones, the payment of rejected ones is released
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceRiskReviewDecision {
+    Approve,
+    Reject,
+}
@@ -1,21 +1,36 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
 use bigdecimal::{BigDecimal, ToPrimitive};
 use chrono::NaiveDateTime;
 use failure::Fail;
 use stripe::{Card as StripeCard, CardBrand as StripeCardBrand};
+use uuid::Uuid;
 
-use stq_types::{stripe::PaymentIntentId, StoreId as StqStoreId, SubscriptionPaymentId, UserId};
+use stq_types::{stripe::PaymentIntentId, CurrencyExchangeId, ProductId, Quantity, StoreId as StqStoreId, SubscriptionPaymentId, UserId};
 
 use models::{
     fee::FeeId,
-    invoice_v2::InvoiceId,
+    invoice_v2::{InvoiceDump, InvoiceId, RawAmountReceived, RawInvoice},
     order_v2::{OrderId, RawOrder, StoreId},
-    ChargeId, CustomerId, Fee, FeeStatus, PaymentIntent, PaymentIntentStatus, PaymentState, StoreSubscriptionStatus, SubscriptionPayment,
-    SubscriptionPaymentSearchResults, SubscriptionPaymentStatus, TransactionId, WalletAddress,
+    AccountId, ApiKey, ApiKeyId, ApiKeyScope, BillingInfoChange, BillingInfoChangeId, BillingInfoChangePayload, BillingInfoChangeStatus,
+    CashbackLiability, CashbackLiabilitySnapshot, ChargeId, CheckoutPaymentMethod, CheckoutSession, Currency, CustomerId,
+    DataRetentionSubject, DataSubjectType, ExchangeRateSlippageMetric, ExchangeRateSource, ExchangeRateStatus, Feature, FeatureFlag, Fee,
+    FeeConversion, FeeCryptoPayment, FeeCryptoPaymentId, FeeCryptoPaymentStatus, FeeStatement, FeeStatementId, FeeStatementLineKind,
+    FeeStatus, HistoricalReport, HistoricalStateTotal, InvoiceCallback, InvoiceCallbackDelivery, InvoiceCallbackEventType,
+    NegativeStoreBalance, NegativeStoreBalanceId, OrderExchangeRateId, OrderLineItem, OrderLineItemId, PaymentAttempt, PaymentAttemptId,
+    PaymentAttemptStatus, PaymentIntent, PaymentIntentHistoryEntry, PaymentIntentHistorySource, PaymentIntentStatus, PaymentState,
+    PayoutId, PayoutInstruction, PayoutInstructionDocument, PayoutInstructionId, PayoutRestrictionOverride, PayoutStatement,
+    PayoutStatementId, RetentionTable, SchemaVersion, SetupIntentStatus, StoreBillingState, StoreBillingStatus, StoreOffboarding,
+    StoreOffboardingStatus, StoreSubscriptionStatus, StoreSuspensionReason, StoreWebhook, StoreWebhookEventType, StoreWebhookId,
+    StripeFeeBackfill, StripeFeeBackfillId, StripeFeeBackfillStatus, Subscription, SubscriptionPayment, SubscriptionPaymentSearchResults,
+    SubscriptionPaymentStatus, SystemAccountsTransfer, TransactionId, TureCurrency, UserCreditBalance, UserCreditEntry, UserCreditEntryId,
+    UserCreditEntryKind, UserWallet, UserWalletId, WalletAddress, WalletVerification, WalletVerificationId, WalletVerificationStatus,
 };
-use stq_static_resources::Currency as StqCurrency;
+use stq_static_resources::{Currency as StqCurrency, OrderState};
 
+use client::instrumentation::{duration_ms, DependencyMethodStats, LATENCY_BUCKETS_MS};
+use models::masking::mask;
 use services::error::{Error, ErrorContext, ErrorKind};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -53,6 +68,77 @@ impl PaymentIntentResponse {
     }
 }
 
+/// Status transitions of a payment intent, oldest first
+#[derive(Debug, Clone, Serialize)]
+pub struct PaymentIntentHistoryResponse {
+    pub payment_intent_id: PaymentIntentId,
+    pub status: PaymentIntentStatus,
+    pub history: Vec<PaymentIntentHistoryEntryResponse>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PaymentIntentHistoryEntryResponse {
+    pub status: PaymentIntentStatus,
+    pub source: PaymentIntentHistorySource,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<PaymentIntentHistoryEntry> for PaymentIntentHistoryEntryResponse {
+    fn from(entry: PaymentIntentHistoryEntry) -> Self {
+        Self {
+            status: entry.status,
+            source: entry.source,
+            created_at: entry.created_at,
+        }
+    }
+}
+
+/// Created invoice together with the checkout session the storefront uses to start the payment
+#[derive(Debug, Clone, Serialize)]
+pub struct CreateInvoiceV2Response {
+    #[serde(flatten)]
+    pub invoice: InvoiceDump,
+    pub checkout_session: CheckoutSession,
+}
+
+/// Invoice created by a store together with the link the store shares with its customer to pay it
+#[derive(Debug, Clone, Serialize)]
+pub struct StoreInvoiceResponse {
+    #[serde(flatten)]
+    pub invoice: InvoiceDump,
+    pub checkout_session: CheckoutSession,
+    pub payment_url: String,
+}
+
+/// Invoice that replaced an expired one, priced at the current rates
+#[derive(Debug, Clone, Serialize)]
+pub struct InvoiceRequoteResponse {
+    #[serde(flatten)]
+    pub invoice: InvoiceDump,
+    pub checkout_session: CheckoutSession,
+    pub requoted_from: InvoiceId,
+}
+
+/// Minimal state of the invoice shown by external payment pages, which only know its public token
+#[derive(Debug, Clone, Serialize)]
+pub struct PublicInvoiceStatusResponse {
+    pub status: OrderState,
+    pub currency: Currency,
+    pub amount_remaining: BigDecimal,
+    pub expires_at: NaiveDateTime,
+}
+
+impl From<InvoiceDump> for PublicInvoiceStatusResponse {
+    fn from(invoice: InvoiceDump) -> Self {
+        Self {
+            amount_remaining: invoice.amount_remaining(),
+            status: invoice.status,
+            currency: invoice.buyer_currency,
+            expires_at: invoice.price_reserved,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct OrderResponse {
     pub id: OrderId,
@@ -65,10 +151,12 @@ pub struct OrderResponse {
     pub store_id: StoreId,
     pub state: PaymentState,
     pub stripe_fee: Option<f64>,
+    pub line_items: Vec<OrderLineItemResponse>,
 }
 
 impl OrderResponse {
-    pub fn try_from_raw_order(raw_order: RawOrder) -> Result<Self, Error> {
+    /// `line_items` may hold the line items of other orders as well, only the ones of this order are kept
+    pub fn try_from_raw_order(raw_order: RawOrder, line_items: &[OrderLineItem]) -> Result<Self, Error> {
         let total_amount = raw_order
             .total_amount
             .to_super_unit(raw_order.seller_currency)
@@ -100,24 +188,112 @@ impl OrderResponse {
             store_id: raw_order.store_id,
             state: raw_order.state,
             stripe_fee,
+            line_items: line_items
+                .iter()
+                .filter(|line_item| line_item.order_id == raw_order.id)
+                .cloned()
+                .map(|line_item| OrderLineItemResponse::new(raw_order.seller_currency, line_item))
+                .collect(),
         })
     }
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderLineItemResponse {
+    pub id: OrderLineItemId,
+    pub product_id: ProductId,
+    pub name: String,
+    pub quantity: i32,
+    /// Price of a single unit in the seller currency
+    pub unit_price: BigDecimal,
+    pub refunded_quantity: i32,
+}
+
+impl OrderLineItemResponse {
+    pub fn new(seller_currency: Currency, line_item: OrderLineItem) -> Self {
+        OrderLineItemResponse {
+            id: line_item.id,
+            product_id: line_item.product_id,
+            name: line_item.name,
+            quantity: line_item.quantity,
+            unit_price: line_item.unit_price.to_super_unit(seller_currency),
+            refunded_quantity: line_item.refunded_quantity,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct OrderSearchResultsResponse {
     pub total_count: i64,
     pub orders: Vec<OrderResponse>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderExchangeRateResponse {
+    pub id: OrderExchangeRateId,
+    pub exchange_rate: BigDecimal,
+    pub status: ExchangeRateStatus,
+    pub source: ExchangeRateSource,
+    pub used_for_payment: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderExchangeRatesResponse {
+    pub order_id: OrderId,
+    pub invoice_id: InvoiceId,
+    pub paid_at: Option<NaiveDateTime>,
+    pub rates: Vec<OrderExchangeRateResponse>,
+}
+
+/// Fee the order would be charged if it was paid now, nothing is saved
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderFeePreviewResponse {
+    pub order_id: OrderId,
+    pub amount: BigDecimal,
+    pub currency: Currency,
+    /// Exchange rate the fee of a crypto order is converted with, none for fiat orders
+    pub rate: Option<FeeRateSnapshotResponse>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeRateSnapshotResponse {
+    pub currency_exchange_id: CurrencyExchangeId,
+    pub from: Currency,
+    pub to: Currency,
+    /// Amount in `from` one unit of `to` is worth
+    pub exchange_rate: f64,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct CustomerResponse {
     pub id: CustomerId,
     pub user_id: UserId,
     pub email: Option<String>,
+    /// Default payment method saved through a SetupIntent
+    pub payment_method_id: Option<String>,
     pub cards: Vec<Card>,
 }
 
+impl CustomerResponse {
+    /// Customer as shown to users who may not see its contact and card details in full
+    pub fn masked(self) -> Self {
+        Self {
+            email: self.email.map(|email| mask(&email)),
+            cards: self.cards.into_iter().map(Card::masked).collect(),
+            ..self
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SetupIntentResponse {
+    pub id: String,
+    pub client_secret: Option<String>,
+    pub status: SetupIntentStatus,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Card {
     pub id: String,
@@ -145,6 +321,16 @@ impl From<StripeCard> for Card {
     }
 }
 
+impl Card {
+    /// `last4` is partial already, the cardholder name is masked
+    pub fn masked(self) -> Self {
+        Self {
+            name: self.name.map(|name| mask(&name)),
+            ..self
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug, Clone, Eq)]
 pub enum CardBrand {
     AmericanExpress,
@@ -182,11 +368,28 @@ pub struct FeeResponse {
     pub currency: StqCurrency,
     pub charge_id: Option<ChargeId>,
     pub metadata: Option<serde_json::Value>,
+    /// Exchange rate the fee of a crypto order was converted with, none for fiat orders
+    pub conversion: Option<FeeConversion>,
+    /// Amount charged from the card, differs from `amount` if the fee was converted into the charge currency
+    pub charged_amount: Option<f64>,
+    pub charged_currency: Option<StqCurrency>,
+    /// Amount in the fee currency one unit of the charged currency was worth
+    pub charge_exchange_rate: Option<f64>,
 }
 
 impl FeeResponse {
     pub fn try_from_fee(other: Fee) -> Result<Self, Error> {
         let other_amount = other.amount.to_super_unit(other.currency).to_f64();
+        let conversion = other.conversion();
+        let charged_amount = match (other.charged_amount, other.charged_currency) {
+            (Some(charged_amount), Some(charged_currency)) => Some(
+                charged_amount
+                    .to_super_unit(charged_currency)
+                    .to_f64()
+                    .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?,
+            ),
+            _ => None,
+        };
 
         match other_amount {
             Some(amount) => Ok(Self {
@@ -197,12 +400,46 @@ impl FeeResponse {
                 currency: other.currency.into(),
                 charge_id: other.charge_id,
                 metadata: other.metadata,
+                conversion,
+                charged_amount,
+                charged_currency: other.charged_currency.map(Into::into),
+                charge_exchange_rate: other.charge_exchange_rate,
             }),
             _ => Err(ectx!(err ErrorContext::AmountConversion, ErrorKind::Internal)),
         }
     }
 }
 
+/// `amount` and `amount_received` are given in super units
+#[derive(Clone, Debug, Serialize)]
+pub struct FeeCryptoPaymentResponse {
+    pub id: FeeCryptoPaymentId,
+    pub fee_id: FeeId,
+    pub wallet_address: WalletAddress,
+    pub currency: StqCurrency,
+    pub amount: BigDecimal,
+    pub amount_received: BigDecimal,
+    pub status: FeeCryptoPaymentStatus,
+    pub expires_at: NaiveDateTime,
+    pub closed_at: Option<NaiveDateTime>,
+}
+
+impl FeeCryptoPaymentResponse {
+    pub fn new(payment: FeeCryptoPayment, wallet_address: WalletAddress) -> Self {
+        FeeCryptoPaymentResponse {
+            id: payment.id,
+            fee_id: payment.fee_id,
+            wallet_address,
+            currency: payment.currency.into(),
+            amount: payment.amount.to_super_unit(payment.currency),
+            amount_received: payment.amount_received.to_super_unit(payment.currency),
+            status: payment.status,
+            expires_at: payment.expires_at,
+            closed_at: payment.closed_at,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct SubscriptionPaymentResponse {
     pub id: SubscriptionPaymentId,
@@ -213,10 +450,13 @@ pub struct SubscriptionPaymentResponse {
     pub transaction_id: Option<TransactionId>,
     pub status: SubscriptionPaymentStatus,
     pub created_at: NaiveDateTime,
+    pub quantity: Option<Quantity>,
+    pub unit_price: Option<BigDecimal>,
 }
 
 impl From<SubscriptionPayment> for SubscriptionPaymentResponse {
     fn from(subscription_payment: SubscriptionPayment) -> SubscriptionPaymentResponse {
+        let currency = subscription_payment.currency;
         SubscriptionPaymentResponse {
             id: subscription_payment.id,
             store_id: subscription_payment.store_id,
@@ -226,10 +466,40 @@ impl From<SubscriptionPayment> for SubscriptionPaymentResponse {
             transaction_id: subscription_payment.transaction_id,
             status: subscription_payment.status,
             created_at: subscription_payment.created_at,
+            quantity: subscription_payment.quantity,
+            unit_price: subscription_payment.unit_price.map(|unit_price| unit_price.to_super_unit(currency)),
         }
     }
 }
 
+/// Subscription payment together with the daily subscription records it was charged for
+#[derive(Clone, Debug, Serialize)]
+pub struct SubscriptionPaymentReceiptResponse {
+    #[serde(flatten)]
+    pub payment: SubscriptionPaymentResponse,
+    pub period_start: Option<NaiveDateTime>,
+    pub period_end: Option<NaiveDateTime>,
+    /// Sum of published products over the days of the period
+    pub published_base_products_quantity: i64,
+}
+
+impl SubscriptionPaymentReceiptResponse {
+    pub fn new(subscription_payment: SubscriptionPayment, subscriptions: &[Subscription]) -> Self {
+        SubscriptionPaymentReceiptResponse {
+            payment: SubscriptionPaymentResponse::from(subscription_payment),
+            period_start: subscriptions.iter().map(|s| s.created_at).min(),
+            period_end: subscriptions.iter().map(|s| s.created_at).max(),
+            published_base_products_quantity: subscriptions.iter().map(|s| i64::from(s.published_base_products_quantity.0)).sum(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SubscriptionPaymentReceiptsResponse {
+    pub total_count: i64,
+    pub subscription_payments: Vec<SubscriptionPaymentReceiptResponse>,
+}
+
 #[derive(Serialize, Clone, Debug)]
 pub struct SubscriptionPaymentSearchResponse {
     pub total_count: i64,
@@ -249,6 +519,317 @@ impl From<SubscriptionPaymentSearchResults> for SubscriptionPaymentSearchRespons
     }
 }
 
+#[derive(Clone, Debug, Serialize)]
+pub struct FeeStatementResponse {
+    pub id: FeeStatementId,
+    pub store_id: StqStoreId,
+    pub currency: StqCurrency,
+    pub period_start: NaiveDateTime,
+    pub period_end: NaiveDateTime,
+    pub fees_amount: BigDecimal,
+    pub adjustments_amount: BigDecimal,
+    pub taxes_amount: BigDecimal,
+    pub total_amount: BigDecimal,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<FeeStatement> for FeeStatementResponse {
+    fn from(fee_statement: FeeStatement) -> FeeStatementResponse {
+        let currency = fee_statement.currency;
+        FeeStatementResponse {
+            id: fee_statement.id,
+            store_id: fee_statement.store_id,
+            currency: currency.into(),
+            period_start: fee_statement.period_start,
+            period_end: fee_statement.period_end,
+            fees_amount: fee_statement.fees_amount.to_super_unit(currency),
+            adjustments_amount: fee_statement.adjustments_amount.to_super_unit(currency),
+            taxes_amount: fee_statement.taxes_amount.to_super_unit(currency),
+            total_amount: fee_statement.total_amount.to_super_unit(currency),
+            created_at: fee_statement.created_at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FeeStatementLineResponse {
+    pub kind: FeeStatementLineKind,
+    pub fee_id: Option<FeeId>,
+    pub order_id: Option<OrderId>,
+    pub amount: BigDecimal,
+    pub description: String,
+    pub created_at: Option<NaiveDateTime>,
+    pub conversion: Option<FeeConversion>,
+}
+
+/// Full fee statement document, as returned by the download endpoint
+#[derive(Clone, Debug, Serialize)]
+pub struct FeeStatementDocumentResponse {
+    #[serde(flatten)]
+    pub statement: FeeStatementResponse,
+    pub lines: Vec<FeeStatementLineResponse>,
+}
+
+impl FeeStatementDocumentResponse {
+    pub fn try_from_fee_statement(fee_statement: FeeStatement) -> Result<Self, Error> {
+        let currency = fee_statement.currency;
+        let lines = fee_statement
+            .lines()
+            .map_err(|e| ectx!(err e, ErrorKind::Internal))?
+            .into_iter()
+            .map(|line| FeeStatementLineResponse {
+                kind: line.kind,
+                fee_id: line.fee_id,
+                order_id: line.order_id,
+                amount: line.amount.to_super_unit(currency),
+                description: line.description,
+                created_at: line.created_at,
+                conversion: line.conversion,
+            })
+            .collect();
+
+        Ok(FeeStatementDocumentResponse {
+            statement: fee_statement.into(),
+            lines,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PayoutInstructionResponse {
+    pub id: PayoutInstructionId,
+    pub store_id: StqStoreId,
+    pub currency: StqCurrency,
+    /// Amount transferred, the orders less their Stripe fees
+    pub total_amount: BigDecimal,
+    pub stripe_fee: BigDecimal,
+    pub order_ids: Vec<OrderId>,
+    pub reference_code: String,
+    pub document: PayoutInstructionDocument,
+    pub created_at: NaiveDateTime,
+}
+
+impl PayoutInstructionResponse {
+    pub fn try_from_payout_instruction(payout_instruction: PayoutInstruction) -> Result<Self, Error> {
+        let order_ids = payout_instruction.order_ids().map_err(|e| ectx!(err e, ErrorKind::Internal))?;
+        let document = payout_instruction.document().map_err(|e| ectx!(err e, ErrorKind::Internal))?;
+        let currency = payout_instruction.currency;
+
+        Ok(PayoutInstructionResponse {
+            id: payout_instruction.id,
+            store_id: payout_instruction.store_id,
+            currency: currency.into(),
+            total_amount: payout_instruction.total_amount.to_super_unit(currency),
+            stripe_fee: payout_instruction.stripe_fee.to_super_unit(currency),
+            order_ids,
+            reference_code: payout_instruction.reference_code,
+            document,
+            created_at: payout_instruction.created_at,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct WrittenOffFeeResponse {
+    pub fee_id: FeeId,
+    pub order_id: OrderId,
+    pub currency: StqCurrency,
+    pub amount: BigDecimal,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FinalPayoutResponse {
+    pub currency: StqCurrency,
+    /// Orders of the store awaiting payout
+    pub gross_amount: BigDecimal,
+    /// Fees carried over by earlier payouts, deducted from the orders
+    pub settled_fees: BigDecimal,
+    /// Amount left to be paid out to the store
+    pub amount: BigDecimal,
+    pub order_ids: Vec<OrderId>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StoreOffboardingResponse {
+    pub store_id: StqStoreId,
+    pub status: StoreOffboardingStatus,
+    pub requested_by: UserId,
+    pub subscription_cancelled_at: Option<NaiveDateTime>,
+    pub subscription_account_id: Option<AccountId>,
+    pub fees_written_off_at: Option<NaiveDateTime>,
+    pub written_off_fees: Vec<WrittenOffFeeResponse>,
+    pub final_payout_scheduled_at: Option<NaiveDateTime>,
+    pub final_payouts: Vec<FinalPayoutResponse>,
+    pub billing_info_archived_at: Option<NaiveDateTime>,
+    pub completed_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl StoreOffboardingResponse {
+    pub fn try_from_store_offboarding(offboarding: StoreOffboarding) -> Result<Self, Error> {
+        let written_off_fees = offboarding
+            .written_off_fees()
+            .map_err(|e| ectx!(try err e, ErrorKind::Internal))?
+            .into_iter()
+            .map(|fee| WrittenOffFeeResponse {
+                fee_id: fee.fee_id,
+                order_id: fee.order_id,
+                currency: fee.currency.into(),
+                amount: fee.amount.to_super_unit(fee.currency),
+            })
+            .collect();
+        let final_payouts = offboarding
+            .final_payouts()
+            .map_err(|e| ectx!(try err e, ErrorKind::Internal))?
+            .into_iter()
+            .map(|payout| FinalPayoutResponse {
+                currency: payout.currency.into(),
+                gross_amount: payout.gross_amount.to_super_unit(payout.currency),
+                settled_fees: payout.settled_fees.to_super_unit(payout.currency),
+                amount: payout.amount.to_super_unit(payout.currency),
+                order_ids: payout.order_ids,
+            })
+            .collect();
+
+        Ok(StoreOffboardingResponse {
+            store_id: offboarding.store_id,
+            status: offboarding.status,
+            requested_by: offboarding.requested_by,
+            subscription_cancelled_at: offboarding.subscription_cancelled_at,
+            subscription_account_id: offboarding.subscription_account_id,
+            fees_written_off_at: offboarding.fees_written_off_at,
+            written_off_fees,
+            final_payout_scheduled_at: offboarding.final_payout_scheduled_at,
+            final_payouts,
+            billing_info_archived_at: offboarding.billing_info_archived_at,
+            completed_at: offboarding.completed_at,
+            created_at: offboarding.created_at,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PayoutStatementLineResponse {
+    pub order_id: OrderId,
+    pub store_id: StoreId,
+    pub gross_amount: BigDecimal,
+    pub fee_amount: Option<BigDecimal>,
+    pub stripe_fee: Option<BigDecimal>,
+    pub net_amount: BigDecimal,
+    pub buyer_currency: StqCurrency,
+    pub exchange_rate: Option<BigDecimal>,
+}
+
+/// Full payout statement document, as returned by the download endpoint
+#[derive(Clone, Debug, Serialize)]
+pub struct PayoutStatementDocumentResponse {
+    pub id: PayoutStatementId,
+    pub payout_id: PayoutId,
+    pub user_id: UserId,
+    pub currency: StqCurrency,
+    pub gross_amount: BigDecimal,
+    pub net_amount: BigDecimal,
+    pub lines: Vec<PayoutStatementLineResponse>,
+    pub created_at: NaiveDateTime,
+}
+
+impl PayoutStatementDocumentResponse {
+    pub fn try_from_payout_statement(payout_statement: PayoutStatement) -> Result<Self, Error> {
+        let currency = payout_statement.currency;
+        let lines = payout_statement
+            .lines()
+            .map_err(|e| ectx!(err e, ErrorKind::Internal))?
+            .into_iter()
+            .map(|line| PayoutStatementLineResponse {
+                order_id: line.order_id,
+                store_id: line.store_id,
+                gross_amount: line.gross_amount.to_super_unit(currency),
+                fee_amount: line.fee_amount.map(|fee_amount| fee_amount.to_super_unit(currency)),
+                stripe_fee: line.stripe_fee.map(|stripe_fee| stripe_fee.to_super_unit(currency)),
+                net_amount: line.net_amount.to_super_unit(currency),
+                buyer_currency: line.buyer_currency.into(),
+                exchange_rate: line.exchange_rate,
+            })
+            .collect();
+
+        Ok(PayoutStatementDocumentResponse {
+            id: payout_statement.id,
+            payout_id: payout_statement.payout_id,
+            user_id: UserId(payout_statement.user_id.inner()),
+            currency: currency.into(),
+            gross_amount: payout_statement.gross_amount.to_super_unit(currency),
+            net_amount: payout_statement.net_amount.to_super_unit(currency),
+            lines,
+            created_at: payout_statement.created_at,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StripeFeeBackfillResponse {
+    pub id: StripeFeeBackfillId,
+    pub status: StripeFeeBackfillStatus,
+    pub total_orders: i32,
+    pub processed_orders: i32,
+    pub updated_orders: i32,
+    pub failed_orders: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+impl From<StripeFeeBackfill> for StripeFeeBackfillResponse {
+    fn from(backfill: StripeFeeBackfill) -> Self {
+        StripeFeeBackfillResponse {
+            id: backfill.id,
+            status: backfill.status,
+            total_orders: backfill.total_orders,
+            processed_orders: backfill.processed_orders,
+            updated_orders: backfill.updated_orders,
+            failed_orders: backfill.failed_orders,
+            created_at: backfill.created_at,
+            updated_at: backfill.updated_at,
+            finished_at: backfill.finished_at,
+        }
+    }
+}
+
+/// Outcomes of the recoveries of failed fiat payments started in a period
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct PaymentRecoveryReportResponse {
+    pub total: usize,
+    pub pending: usize,
+    pub notified: usize,
+    pub retried: usize,
+    pub recovered: usize,
+    pub expired: usize,
+    pub cancelled: usize,
+    /// Share of the closed recoveries that ended with a paid invoice, `None` until one is closed
+    pub recovery_rate: Option<f64>,
+}
+
+/// Internal transaction between system accounts, `amount` is in super units
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemAccountsTransferResponse {
+    pub transaction_id: Uuid,
+    pub from_account_id: Uuid,
+    pub to_account_id: Uuid,
+    pub currency: TureCurrency,
+    pub amount: BigDecimal,
+}
+
+impl From<SystemAccountsTransfer> for SystemAccountsTransferResponse {
+    fn from(transfer: SystemAccountsTransfer) -> Self {
+        SystemAccountsTransferResponse {
+            transaction_id: transfer.transaction_id,
+            from_account_id: transfer.from_account_id.into_inner(),
+            to_account_id: transfer.to_account_id.into_inner(),
+            currency: transfer.currency,
+            amount: transfer.amount.to_super_unit(transfer.currency.into()),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct StoreSubscriptionResponse {
     pub store_id: StqStoreId,
@@ -272,3 +853,830 @@ impl BalancesResponse {
         Self { currencies }
     }
 }
+
+/// Amounts of the store orders split by the stage of their payout
+#[derive(Clone, Debug, Serialize)]
+pub struct StoreBalanceOverviewResponse {
+    pub store_id: StqStoreId,
+    pub currencies: Vec<CurrencyBalanceOverviewResponse>,
+    /// Fiat value of the STQ not yet paid out, empty when the exchange rates are unavailable
+    pub stq_fiat_estimates: Vec<StqFiatEstimateResponse>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CurrencyBalanceOverviewResponse {
+    pub currency: StqCurrency,
+    pub unpaid_invoices: BigDecimal,
+    pub paid_not_eligible: BigDecimal,
+    pub eligible: BigDecimal,
+    pub paid_out: BigDecimal,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StqFiatEstimateResponse {
+    pub currency: StqCurrency,
+    pub exchange_rate: f64,
+    pub pending_amount: BigDecimal,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StoreWebhookResponse {
+    pub id: StoreWebhookId,
+    pub store_id: StqStoreId,
+    pub url: String,
+    pub secret: String,
+    pub event_types: Vec<StoreWebhookEventType>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl StoreWebhookResponse {
+    pub fn try_from_store_webhook(store_webhook: StoreWebhook) -> Result<Self, Error> {
+        let event_types = store_webhook
+            .event_types()
+            .map_err(|e| ectx!(err e, ErrorContext::StoreWebhook, ErrorKind::Internal))?;
+
+        Ok(Self {
+            id: store_webhook.id,
+            store_id: store_webhook.store_id,
+            url: store_webhook.url,
+            secret: store_webhook.secret,
+            event_types,
+            created_at: store_webhook.created_at,
+            updated_at: store_webhook.updated_at,
+        })
+    }
+}
+
+/// Key of a store without the key itself, which is only returned when the key is created
+#[derive(Clone, Debug, Serialize)]
+pub struct ApiKeyResponse {
+    pub id: ApiKeyId,
+    pub store_id: StqStoreId,
+    pub created_by: UserId,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl ApiKeyResponse {
+    pub fn try_from_api_key(api_key: ApiKey) -> Result<Self, Error> {
+        let scopes = api_key
+            .scopes()
+            .map_err(|e| ectx!(err e, ErrorContext::ApiKey, ErrorKind::Internal))?;
+
+        Ok(Self {
+            id: api_key.id,
+            store_id: api_key.store_id,
+            created_by: api_key.created_by,
+            name: api_key.name,
+            key_prefix: api_key.key_prefix,
+            scopes,
+            last_used_at: api_key.last_used_at,
+            revoked_at: api_key.revoked_at,
+            created_at: api_key.created_at,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CreatedApiKeyResponse {
+    pub api_key: ApiKeyResponse,
+    /// Shown once, only its hash is stored
+    pub key: String,
+}
+
+/// Callback registered with the invoice together with its delivery log, the secret is not disclosed
+#[derive(Clone, Debug, Serialize)]
+pub struct InvoiceCallbackResponse {
+    pub invoice_id: InvoiceId,
+    pub url: String,
+    pub created_at: NaiveDateTime,
+    pub deliveries: Vec<InvoiceCallbackDeliveryResponse>,
+}
+
+impl InvoiceCallbackResponse {
+    pub fn new(invoice_callback: InvoiceCallback, deliveries: Vec<InvoiceCallbackDelivery>) -> Self {
+        Self {
+            invoice_id: invoice_callback.invoice_id,
+            url: invoice_callback.url,
+            created_at: invoice_callback.created_at,
+            deliveries: deliveries.into_iter().map(InvoiceCallbackDeliveryResponse::from).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct InvoiceCallbackDeliveryResponse {
+    pub notification_id: Uuid,
+    pub event_type: InvoiceCallbackEventType,
+    pub succeeded: bool,
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<InvoiceCallbackDelivery> for InvoiceCallbackDeliveryResponse {
+    fn from(delivery: InvoiceCallbackDelivery) -> Self {
+        Self {
+            notification_id: delivery.notification_id,
+            event_type: delivery.event_type,
+            succeeded: delivery.error.is_none(),
+            error: delivery.error,
+            created_at: delivery.created_at,
+        }
+    }
+}
+
+/// Inbound transaction credited to the account of a crypto invoice.
+/// `status` is reported by Payments gateway, none if the gateway doesn't know the transaction
+#[derive(Clone, Debug, Serialize)]
+pub struct InboundTransactionResponse {
+    pub id: TransactionId,
+    pub currency: Currency,
+    pub amount: BigDecimal,
+    pub status: Option<String>,
+    pub received_at: NaiveDateTime,
+}
+
+impl InboundTransactionResponse {
+    pub fn new(currency: Currency, amount_received: RawAmountReceived, status: Option<String>) -> Self {
+        Self {
+            id: amount_received.id,
+            currency,
+            amount: amount_received.amount_received.to_super_unit(currency),
+            status,
+            received_at: amount_received.created_at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct UserWalletResponse {
+    pub id: UserWalletId,
+    pub address: WalletAddress,
+    pub currency: TureCurrency,
+    pub is_active: bool,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<UserWallet> for UserWalletResponse {
+    fn from(user_wallet: UserWallet) -> Self {
+        Self {
+            id: user_wallet.id,
+            address: user_wallet.address,
+            currency: user_wallet.currency,
+            is_active: user_wallet.is_active,
+            created_at: user_wallet.created_at,
+        }
+    }
+}
+
+/// Verification of a payout wallet, the amount sent is not disclosed as the user has to confirm it
+#[derive(Clone, Debug, Serialize)]
+pub struct WalletVerificationResponse {
+    pub id: WalletVerificationId,
+    pub user_wallet_id: UserWalletId,
+    pub status: WalletVerificationStatus,
+    pub expires_at: NaiveDateTime,
+    pub confirmed_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl WalletVerificationResponse {
+    /// A pending verification past its deadline is reported as expired
+    pub fn new(wallet_verification: WalletVerification, now: NaiveDateTime) -> Self {
+        let status = match wallet_verification.status {
+            WalletVerificationStatus::Pending if !wallet_verification.is_pending(now) => WalletVerificationStatus::Expired,
+            status => status,
+        };
+
+        Self {
+            id: wallet_verification.id,
+            user_wallet_id: wallet_verification.user_wallet_id,
+            status,
+            expires_at: wallet_verification.expires_at,
+            confirmed_at: wallet_verification.confirmed_at,
+            created_at: wallet_verification.created_at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StoreBillingStatusResponse {
+    pub store_id: StqStoreId,
+    pub state: StoreBillingState,
+    pub suspension_reason: Option<StoreSuspensionReason>,
+    pub overdue_fees_count: i32,
+    pub unpaid_since: Option<NaiveDateTime>,
+    pub overridden_by: Option<UserId>,
+    pub override_until: Option<NaiveDateTime>,
+    pub evaluated_at: Option<NaiveDateTime>,
+}
+
+impl StoreBillingStatusResponse {
+    /// Status of a store the policy has never evaluated
+    pub fn active(store_id: StqStoreId) -> Self {
+        Self {
+            store_id,
+            state: StoreBillingState::Active,
+            suspension_reason: None,
+            overdue_fees_count: 0,
+            unpaid_since: None,
+            overridden_by: None,
+            override_until: None,
+            evaluated_at: None,
+        }
+    }
+}
+
+impl From<StoreBillingStatus> for StoreBillingStatusResponse {
+    fn from(store_billing_status: StoreBillingStatus) -> Self {
+        Self {
+            store_id: store_billing_status.store_id,
+            state: store_billing_status.state,
+            suspension_reason: store_billing_status.suspension_reason,
+            overdue_fees_count: store_billing_status.overdue_fees_count,
+            unpaid_since: store_billing_status.unpaid_since,
+            overridden_by: store_billing_status.overridden_by,
+            override_until: store_billing_status.override_until,
+            evaluated_at: store_billing_status.evaluated_at,
+        }
+    }
+}
+
+/// Whether the payouts of a store are blocked by the country of its billing info
+#[derive(Clone, Debug, Serialize)]
+pub struct PayoutRestrictionResponse {
+    pub store_id: StqStoreId,
+    /// Country of the billing info, `None` if the store has no billing info yet
+    pub country: Option<String>,
+    pub restricted_country: bool,
+    pub overridden_by: Option<UserId>,
+    pub override_reason: Option<String>,
+    pub payouts_allowed: bool,
+}
+
+impl PayoutRestrictionResponse {
+    pub fn new(
+        store_id: StqStoreId,
+        country: Option<String>,
+        restricted_country: bool,
+        payout_override: Option<PayoutRestrictionOverride>,
+    ) -> Self {
+        let payouts_allowed = !restricted_country || payout_override.is_some();
+        let (overridden_by, override_reason) = match payout_override {
+            Some(payout_override) => (Some(payout_override.granted_by), Some(payout_override.reason)),
+            None => (None, None),
+        };
+
+        Self {
+            store_id,
+            country,
+            restricted_country,
+            overridden_by,
+            override_reason,
+            payouts_allowed,
+        }
+    }
+}
+
+/// Payment method the buyer may use together with the currencies allowed for it
+#[derive(Clone, Debug, Serialize)]
+pub struct AvailablePaymentMethod {
+    pub payment_method: CheckoutPaymentMethod,
+    pub currencies: Vec<Currency>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PaymentMethodsResponse {
+    pub payment_methods: Vec<AvailablePaymentMethod>,
+}
+
+/// Billing infos sealed with the current key by the first batch of the re-encryption
+#[derive(Clone, Debug, Serialize)]
+pub struct BillingInfoReencryptionResponse {
+    pub international_billing_infos: usize,
+    pub russia_billing_infos: usize,
+}
+
+/// V1 invoices expired by a sweep, with the orders of them saga is notified of
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct LegacyInvoiceExpirationSweepResponse {
+    pub invoices_expired: usize,
+    pub orders_expired: usize,
+}
+
+/// Personal data purged by a run of the retention rules, or found to be purged on a dry run
+#[derive(Clone, Debug, Serialize)]
+pub struct DataRetentionPurgeResponse {
+    pub dry_run: bool,
+    pub tables: Vec<RetentionTableReport>,
+}
+
+/// Data of `subjects` closed stores or deleted users found in `rows` rows of the table
+#[derive(Clone, Debug, Serialize)]
+pub struct RetentionTableReport {
+    pub table: RetentionTable,
+    pub retention_days: u32,
+    pub subjects: usize,
+    pub rows: u64,
+}
+
+/// Transaction the sandbox has sent to the invoice through the Payments gateway callback
+#[derive(Clone, Debug, Serialize)]
+pub struct SimulatedInboundTxResponse {
+    pub invoice_id: InvoiceId,
+    pub transaction_id: TransactionId,
+    pub wallet_address: WalletAddress,
+    pub currency: Currency,
+    pub amount: BigDecimal,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DataRetentionSubjectResponse {
+    pub subject_type: DataSubjectType,
+    pub subject_id: i32,
+    pub closed_at: Option<NaiveDateTime>,
+    pub legal_hold: bool,
+    pub legal_hold_reason: Option<String>,
+}
+
+impl From<DataRetentionSubject> for DataRetentionSubjectResponse {
+    fn from(other: DataRetentionSubject) -> Self {
+        Self {
+            subject_type: other.subject_type,
+            subject_id: other.subject_id,
+            closed_at: other.closed_at,
+            legal_hold: other.legal_hold,
+            legal_hold_reason: other.legal_hold_reason,
+        }
+    }
+}
+
+/// Cashback owed to buyers by currency, for the invoices paid before `as_of`
+#[derive(Clone, Debug, Serialize)]
+pub struct CashbackLiabilitiesResponse {
+    pub as_of: NaiveDateTime,
+    pub currencies: Vec<CashbackLiabilityResponse>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CashbackLiabilityResponse {
+    pub currency: StqCurrency,
+    pub amount: BigDecimal,
+    pub invoices_count: i64,
+}
+
+impl From<CashbackLiability> for CashbackLiabilityResponse {
+    fn from(liability: CashbackLiability) -> CashbackLiabilityResponse {
+        let currency = liability.currency;
+        CashbackLiabilityResponse {
+            currency: currency.into(),
+            amount: liability.amount.to_super_unit(currency),
+            invoices_count: liability.invoices_count,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CashbackLiabilitySnapshotResponse {
+    pub currency: StqCurrency,
+    pub as_of: NaiveDateTime,
+    pub amount: BigDecimal,
+    pub invoices_count: i64,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<CashbackLiabilitySnapshot> for CashbackLiabilitySnapshotResponse {
+    fn from(snapshot: CashbackLiabilitySnapshot) -> CashbackLiabilitySnapshotResponse {
+        let currency = snapshot.currency;
+        CashbackLiabilitySnapshotResponse {
+            currency: currency.into(),
+            as_of: snapshot.as_of,
+            amount: snapshot.amount.to_super_unit(currency),
+            invoices_count: snapshot.invoices_count,
+            created_at: snapshot.created_at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct HistoricalStateTotalResponse {
+    pub state: String,
+    pub currency: StqCurrency,
+    pub count: i64,
+    pub amount: BigDecimal,
+}
+
+impl From<HistoricalStateTotal> for HistoricalStateTotalResponse {
+    fn from(total: HistoricalStateTotal) -> HistoricalStateTotalResponse {
+        let currency = total.currency;
+        HistoricalStateTotalResponse {
+            state: total.state,
+            currency: currency.into(),
+            count: total.count,
+            amount: total.amount.to_super_unit(currency),
+        }
+    }
+}
+
+/// Invoices, fees and payouts by the states they were in at `as_of`. `generated_at` is the time
+/// the report was reconstructed, earlier than the request if the report was kept from a previous one
+#[derive(Clone, Debug, Serialize)]
+pub struct HistoricalReportResponse {
+    pub as_of: NaiveDateTime,
+    pub invoices: Vec<HistoricalStateTotalResponse>,
+    pub fees: Vec<HistoricalStateTotalResponse>,
+    pub payouts: Vec<HistoricalStateTotalResponse>,
+    pub generated_at: NaiveDateTime,
+}
+
+impl HistoricalReportResponse {
+    pub fn try_from_historical_report(report: HistoricalReport) -> Result<Self, Error> {
+        let invoices = report.invoices().map_err(|e| ectx!(try err e, ErrorKind::Internal))?;
+        let fees = report.fees().map_err(|e| ectx!(try err e, ErrorKind::Internal))?;
+        let payouts = report.payouts().map_err(|e| ectx!(try err e, ErrorKind::Internal))?;
+
+        Ok(HistoricalReportResponse {
+            as_of: report.as_of,
+            invoices: invoices.into_iter().map(HistoricalStateTotalResponse::from).collect(),
+            fees: fees.into_iter().map(HistoricalStateTotalResponse::from).collect(),
+            payouts: payouts.into_iter().map(HistoricalStateTotalResponse::from).collect(),
+            generated_at: report.created_at,
+        })
+    }
+}
+
+/// Credit balances of a user and their changes, newest first. Amounts are given in super units
+#[derive(Clone, Debug, Serialize)]
+pub struct UserCreditsResponse {
+    pub user_id: UserId,
+    pub balances: Vec<UserCreditBalanceResponse>,
+    pub entries: Vec<UserCreditEntryResponse>,
+}
+
+impl UserCreditsResponse {
+    pub fn new(user_id: UserId, balances: Vec<UserCreditBalance>, entries: Vec<UserCreditEntry>) -> Self {
+        UserCreditsResponse {
+            user_id,
+            balances: balances.into_iter().map(UserCreditBalanceResponse::from).collect(),
+            entries: entries.into_iter().map(UserCreditEntryResponse::from).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct UserCreditBalanceResponse {
+    pub currency: Currency,
+    pub balance: BigDecimal,
+    pub updated_at: NaiveDateTime,
+}
+
+impl From<UserCreditBalance> for UserCreditBalanceResponse {
+    fn from(balance: UserCreditBalance) -> Self {
+        UserCreditBalanceResponse {
+            currency: balance.currency,
+            balance: balance.balance.to_super_unit(balance.currency),
+            updated_at: balance.updated_at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct UserCreditEntryResponse {
+    pub id: UserCreditEntryId,
+    pub currency: Currency,
+    pub kind: UserCreditEntryKind,
+    pub amount: BigDecimal,
+    /// Invoice the credit was applied to or restored from
+    pub invoice_id: Option<InvoiceId>,
+    pub reason: Option<String>,
+    pub created_by: Option<UserId>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<UserCreditEntry> for UserCreditEntryResponse {
+    fn from(entry: UserCreditEntry) -> Self {
+        UserCreditEntryResponse {
+            id: entry.id,
+            currency: entry.currency,
+            kind: entry.kind,
+            amount: entry.amount.to_super_unit(entry.currency),
+            invoice_id: entry.invoice_id,
+            reason: entry.reason,
+            created_by: entry.created_by,
+            created_at: entry.created_at,
+        }
+    }
+}
+
+/// Amount a store owes above its unpaid orders, as of the last balance check of the store
+#[derive(Clone, Debug, Serialize)]
+pub struct NegativeStoreBalanceResponse {
+    pub id: NegativeStoreBalanceId,
+    pub store_id: StoreId,
+    pub currency: StqCurrency,
+    pub amount: BigDecimal,
+    pub detected_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub notified_at: Option<NaiveDateTime>,
+}
+
+impl From<NegativeStoreBalance> for NegativeStoreBalanceResponse {
+    fn from(negative_store_balance: NegativeStoreBalance) -> NegativeStoreBalanceResponse {
+        let currency = negative_store_balance.currency;
+        NegativeStoreBalanceResponse {
+            id: negative_store_balance.id,
+            store_id: negative_store_balance.store_id,
+            currency: currency.into(),
+            amount: negative_store_balance.amount.to_super_unit(currency),
+            detected_at: negative_store_balance.detected_at,
+            updated_at: negative_store_balance.updated_at,
+            notified_at: negative_store_balance.notified_at,
+        }
+    }
+}
+
+/// Price changes of the paid orders of a currency pair caused by rate refreshes,
+/// the prices are totals in the buyer currency
+#[derive(Clone, Debug, Serialize)]
+pub struct ExchangeRateSlippageResponse {
+    pub seller_currency: StqCurrency,
+    pub buyer_currency: StqCurrency,
+    pub orders_count: i64,
+    pub refreshed_orders_count: i64,
+    pub initial_price_total: BigDecimal,
+    pub final_price_total: BigDecimal,
+    /// Positive if the buyers paid more than they were quoted at invoice creation
+    pub slippage: BigDecimal,
+    pub slippage_ratio: BigDecimal,
+    pub updated_at: NaiveDateTime,
+}
+
+impl From<ExchangeRateSlippageMetric> for ExchangeRateSlippageResponse {
+    fn from(metric: ExchangeRateSlippageMetric) -> ExchangeRateSlippageResponse {
+        let buyer_currency = metric.buyer_currency;
+        ExchangeRateSlippageResponse {
+            seller_currency: metric.seller_currency.into(),
+            buyer_currency: buyer_currency.into(),
+            orders_count: metric.orders_count,
+            refreshed_orders_count: metric.refreshed_orders_count,
+            initial_price_total: metric.initial_price_total.to_super_unit(buyer_currency),
+            final_price_total: metric.final_price_total.to_super_unit(buyer_currency),
+            slippage: metric.slippage(),
+            slippage_ratio: metric.slippage_ratio(),
+            updated_at: metric.updated_at,
+        }
+    }
+}
+
+/// Schema version of the database compared with the migrations the running binary is built with
+#[derive(Clone, Debug, Serialize)]
+pub struct SchemaVersionResponse {
+    pub current_version: Option<String>,
+    pub expected_version: Option<String>,
+    /// False if some migrations of the binary haven't been applied to the database
+    pub up_to_date: bool,
+    pub pending_migrations: Vec<String>,
+    /// Migrations applied to the database that the binary doesn't know of
+    pub unknown_migrations: Vec<String>,
+}
+
+impl From<SchemaVersion> for SchemaVersionResponse {
+    fn from(schema_version: SchemaVersion) -> SchemaVersionResponse {
+        SchemaVersionResponse {
+            up_to_date: !schema_version.is_behind(),
+            current_version: schema_version.current_version,
+            expected_version: schema_version.expected_version,
+            pending_migrations: schema_version.pending_migrations,
+            unknown_migrations: schema_version.unknown_migrations,
+        }
+    }
+}
+
+/// Requested change of the billing info of a store, bank account details are masked
+/// unless the user may see them in full
+#[derive(Clone, Debug, Serialize)]
+pub struct BillingInfoChangeResponse {
+    pub id: BillingInfoChangeId,
+    pub store_id: StqStoreId,
+    pub status: BillingInfoChangeStatus,
+    pub payload: BillingInfoChangePayload,
+    pub requested_by: UserId,
+    pub reviewed_by: Option<UserId>,
+    pub rejection_reason: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub reviewed_at: Option<NaiveDateTime>,
+}
+
+impl From<BillingInfoChange> for BillingInfoChangeResponse {
+    fn from(change: BillingInfoChange) -> BillingInfoChangeResponse {
+        BillingInfoChangeResponse {
+            id: change.id,
+            store_id: change.store_id,
+            status: change.status,
+            payload: change.payload,
+            requested_by: change.requested_by,
+            reviewed_by: change.reviewed_by,
+            rejection_reason: change.rejection_reason,
+            created_at: change.created_at,
+            reviewed_at: change.reviewed_at,
+        }
+    }
+}
+
+/// Duplicate customers merged by a batch of the deduplication, zero `users` means no duplicates are left
+#[derive(Clone, Debug, Serialize)]
+pub struct CustomersDeduplicationResponse {
+    pub users: usize,
+    pub deleted_customers: usize,
+}
+
+/// Calls of the instrumented clients in the last `window_sec`, by dependency and method
+#[derive(Clone, Debug, Serialize)]
+pub struct DependenciesResponse {
+    pub window_sec: u64,
+    pub methods: Vec<DependencyMethodResponse>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DependencyMethodResponse {
+    pub dependency: String,
+    pub method: String,
+    /// Deadline of the calls, none when they wait for the HTTP client to give up
+    pub latency_budget_ms: Option<u64>,
+    pub calls: usize,
+    pub failures: usize,
+    pub deadlines_exceeded: usize,
+    /// Share of the calls that failed or missed their deadline
+    pub error_rate: f64,
+    pub latency_p50_ms: u64,
+    pub latency_p95_ms: u64,
+    pub latency_p99_ms: u64,
+    pub latency_max_ms: u64,
+    pub latency_histogram: Vec<LatencyBucketResponse>,
+}
+
+/// Calls that took at most `le_ms`, and more than the bound of the previous bucket. The last bucket has no bound
+#[derive(Clone, Debug, Serialize)]
+pub struct LatencyBucketResponse {
+    pub le_ms: Option<u64>,
+    pub calls: usize,
+}
+
+impl DependenciesResponse {
+    pub fn new(window: Duration, stats: Vec<DependencyMethodStats>) -> Self {
+        Self {
+            window_sec: window.as_secs(),
+            methods: stats.into_iter().map(DependencyMethodResponse::from).collect(),
+        }
+    }
+}
+
+impl From<DependencyMethodStats> for DependencyMethodResponse {
+    fn from(stats: DependencyMethodStats) -> Self {
+        let latency_histogram = stats
+            .latency_histogram
+            .iter()
+            .enumerate()
+            .map(|(bucket, calls)| LatencyBucketResponse {
+                le_ms: LATENCY_BUCKETS_MS.get(bucket).cloned(),
+                calls: *calls,
+            })
+            .collect();
+
+        Self {
+            dependency: stats.dependency.to_string(),
+            method: stats.method.to_string(),
+            latency_budget_ms: stats.latency_budget.map(duration_ms),
+            calls: stats.calls,
+            failures: stats.failures,
+            deadlines_exceeded: stats.deadlines_exceeded,
+            error_rate: stats.error_rate(),
+            latency_p50_ms: duration_ms(stats.latency_p50),
+            latency_p95_ms: duration_ms(stats.latency_p95),
+            latency_p99_ms: duration_ms(stats.latency_p99),
+            latency_max_ms: duration_ms(stats.latency_max),
+            latency_histogram,
+        }
+    }
+}
+
+/// Rollout of a feature. A feature without a stored flag is reported with the default of the environment
+#[derive(Clone, Debug, Serialize)]
+pub struct FeatureFlagResponse {
+    pub feature: Feature,
+    pub enabled: bool,
+    pub rollout_percentage: i32,
+    pub store_ids: Vec<StqStoreId>,
+    pub is_default: bool,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl FeatureFlagResponse {
+    pub fn default_for(feature: Feature, enabled: bool) -> Self {
+        Self {
+            feature,
+            enabled,
+            rollout_percentage: 100,
+            store_ids: Vec::new(),
+            is_default: true,
+            updated_at: None,
+        }
+    }
+
+    pub fn from_flag(feature: Feature, flag: FeatureFlag) -> Self {
+        Self {
+            feature,
+            enabled: flag.enabled,
+            rollout_percentage: flag.rollout_percentage,
+            store_ids: flag.store_ids().unwrap_or_default(),
+            is_default: false,
+            updated_at: Some(flag.updated_at),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PaymentAttemptResponse {
+    pub id: PaymentAttemptId,
+    pub payment_intent_id: PaymentIntentId,
+    pub status: PaymentAttemptStatus,
+    pub decline_code: Option<String>,
+    pub error_code: Option<String>,
+    pub payment_method_fingerprint: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<PaymentAttempt> for PaymentAttemptResponse {
+    fn from(attempt: PaymentAttempt) -> PaymentAttemptResponse {
+        PaymentAttemptResponse {
+            id: attempt.id,
+            payment_intent_id: attempt.payment_intent_id,
+            status: attempt.status,
+            decline_code: attempt.decline_code,
+            error_code: attempt.error_code,
+            payment_method_fingerprint: attempt.payment_method_fingerprint,
+            created_at: attempt.created_at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PaymentAttemptCountResponse {
+    pub status: PaymentAttemptStatus,
+    pub count: i64,
+}
+
+/// Invoice with the attempts to pay it in the fiat flow, oldest first
+#[derive(Clone, Debug, Serialize)]
+pub struct InvoiceInspectionResponse {
+    pub invoice_id: InvoiceId,
+    pub status: OrderState,
+    pub paid_at: Option<NaiveDateTime>,
+    pub payment_intent_id: Option<PaymentIntentId>,
+    pub payment_attempts_count: i64,
+    /// Number of attempts of every status, statuses without attempts included
+    pub payment_attempts_by_status: Vec<PaymentAttemptCountResponse>,
+    /// Number of distinct cards the buyer tried
+    pub payment_methods_count: i64,
+    pub payment_attempts: Vec<PaymentAttemptResponse>,
+}
+
+impl InvoiceInspectionResponse {
+    pub fn new(invoice: RawInvoice, payment_intent_id: Option<PaymentIntentId>, payment_attempts: Vec<PaymentAttempt>) -> Self {
+        let payment_attempts_by_status = [
+            PaymentAttemptStatus::Succeeded,
+            PaymentAttemptStatus::Authorized,
+            PaymentAttemptStatus::Declined,
+            PaymentAttemptStatus::AuthenticationFailed,
+            PaymentAttemptStatus::Failed,
+        ]
+        .iter()
+        .map(|status| PaymentAttemptCountResponse {
+            status: *status,
+            count: payment_attempts.iter().filter(|attempt| attempt.status == *status).count() as i64,
+        })
+        .collect();
+
+        let payment_methods_count = payment_attempts
+            .iter()
+            .filter_map(|attempt| attempt.payment_method_fingerprint.as_ref())
+            .collect::<HashSet<_>>()
+            .len() as i64;
+
+        InvoiceInspectionResponse {
+            invoice_id: invoice.id,
+            status: invoice.status,
+            paid_at: invoice.paid_at,
+            payment_intent_id,
+            payment_attempts_count: payment_attempts.len() as i64,
+            payment_attempts_by_status,
+            payment_methods_count,
+            payment_attempts: payment_attempts.into_iter().map(PaymentAttemptResponse::from).collect(),
+        }
+    }
+}
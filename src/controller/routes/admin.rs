@@ -0,0 +1,118 @@
+//! Administrative routes: user roles, accounts, audit log, backfills, re-encryption, reports, invoice inspection, feature flags,
+//! schema version, the v1 invoice expiration sweep, data retention, historical reports and the payments sandbox
+use hyper::Method;
+use stq_router::RouteParser;
+
+use super::{param, PathParamKind, Route, RouteSpec};
+use controller::requests::{
+    DataRetentionPurgeRequest, LegalHoldRequest, SimulateInboundTxRequest, SystemAccountsTransferRequest, UpdateFeatureFlagRequest,
+};
+use controller::responses::{
+    BillingInfoReencryptionResponse, CashbackLiabilitiesResponse, CashbackLiabilitySnapshotResponse, DataRetentionPurgeResponse,
+    DataRetentionSubjectResponse, ExchangeRateSlippageResponse, FeatureFlagResponse, HistoricalReportResponse, InvoiceInspectionResponse,
+    LegacyInvoiceExpirationSweepResponse, NegativeStoreBalanceResponse, PaymentRecoveryReportResponse, SchemaVersionResponse,
+    SimulatedInboundTxResponse, StripeFeeBackfillResponse, SystemAccountsTransferResponse,
+};
+
+pub fn add_routes(route_parser: &mut RouteParser<Route>) {
+    route_parser.add_route(r"^/roles$", || Route::Roles);
+    route_parser.add_route_with_params(r"^/roles/by-user-id/(\d+)$", |params| {
+        param(&params, 0).map(|user_id| Route::RolesByUserId { user_id })
+    });
+    route_parser.add_route_with_params(r"^/roles/by-id/([a-zA-Z0-9-]+)$", |params| {
+        param(&params, 0).map(|id| Route::RoleById { id })
+    });
+    route_parser.add_route(r"^/accounts/system/transfer$", || Route::SystemAccountsTransfer);
+    route_parser.add_route_with_params(r"^/accounts/([a-zA-Z0-9-]+)/archive$", |params| {
+        param(&params, 0).map(|account_id| Route::AccountArchive { account_id })
+    });
+    route_parser.add_route(r"^/audit_log/search$", || Route::AuditLogSearch);
+    route_parser.add_route(r"^/stripe_fee_backfills$", || Route::StripeFeeBackfills);
+    route_parser.add_route_with_params(r"^/stripe_fee_backfills/(\d+)$", |params| {
+        param(&params, 0).map(|id| Route::StripeFeeBackfill { id })
+    });
+    route_parser.add_route(r"^/billing_info/reencrypt$", || Route::BillingInfoReencryption);
+    route_parser.add_route(r"^/payment_recoveries/report$", || Route::PaymentRecoveriesReport);
+    route_parser.add_route_with_params(r"^/invoice_inspections/([a-zA-Z0-9-]+)$", |params| {
+        param(&params, 0).map(|invoice_id| Route::InvoiceInspection { invoice_id })
+    });
+    route_parser.add_route(r"^/invoices/expired/sweep$", || Route::LegacyInvoicesExpirationSweep);
+    route_parser.add_route(r"^/cashback_liabilities$", || Route::CashbackLiabilities);
+    route_parser.add_route(r"^/cashback_liabilities/snapshots$", || Route::CashbackLiabilitySnapshots);
+    route_parser.add_route(r"^/historical_reports$", || Route::HistoricalReport);
+    route_parser.add_route(r"^/negative_store_balances$", || Route::NegativeStoreBalances);
+    route_parser.add_route(r"^/exchange_rate_slippages$", || Route::ExchangeRateSlippages);
+    route_parser.add_route(r"^/schema_version$", || Route::SchemaVersion);
+    route_parser.add_route(r"^/feature_flags$", || Route::FeatureFlags);
+    route_parser.add_route_with_params(r"^/feature_flags/([a-z_]+)$", |params| {
+        param(&params, 0).map(|feature| Route::FeatureFlag { feature })
+    });
+    route_parser.add_route(r"^/data_retention/purge$", || Route::DataRetentionPurge);
+    route_parser.add_route_with_params(r"^/data_retention/stores/(\d+)/legal_hold$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreLegalHold { store_id })
+    });
+    route_parser.add_route_with_params(r"^/data_retention/users/(\d+)/legal_hold$", |params| {
+        param(&params, 0).map(|user_id| Route::UserLegalHold { user_id })
+    });
+    route_parser.add_route_with_params(r"^/sandbox/invoices/([a-zA-Z0-9-]+)/inbound_tx$", |params| {
+        param(&params, 0).map(|invoice_id| Route::SandboxInboundTx { invoice_id })
+    });
+}
+
+pub fn route_specs() -> Vec<RouteSpec> {
+    vec![
+        RouteSpec::new(Method::Post, "/roles"),
+        RouteSpec::new(Method::Delete, "/roles"),
+        RouteSpec::new(Method::Get, "/roles/by-user-id/{user_id}").param("user_id", PathParamKind::Integer),
+        RouteSpec::new(Method::Delete, "/roles/by-user-id/{user_id}").param("user_id", PathParamKind::Integer),
+        RouteSpec::new(Method::Delete, "/roles/by-id/{id}").param("id", PathParamKind::Uuid),
+        RouteSpec::new(Method::Post, "/accounts/{account_id}/archive").param("account_id", PathParamKind::Uuid),
+        RouteSpec::new(Method::Post, "/accounts/system/transfer")
+            .request::<SystemAccountsTransferRequest>()
+            .response::<SystemAccountsTransferResponse>(),
+        RouteSpec::new(Method::Post, "/audit_log/search").paginated(),
+        RouteSpec::new(Method::Post, "/stripe_fee_backfills").response::<StripeFeeBackfillResponse>(),
+        RouteSpec::new(Method::Get, "/stripe_fee_backfills/{id}")
+            .param("id", PathParamKind::Integer)
+            .response::<Option<StripeFeeBackfillResponse>>(),
+        RouteSpec::new(Method::Post, "/billing_info/reencrypt").response::<BillingInfoReencryptionResponse>(),
+        RouteSpec::new(Method::Get, "/payment_recoveries/report")
+            .query("created_from", PathParamKind::String)
+            .query("created_to", PathParamKind::String)
+            .response::<PaymentRecoveryReportResponse>(),
+        RouteSpec::new(Method::Get, "/invoice_inspections/{invoice_id}")
+            .param("invoice_id", PathParamKind::Uuid)
+            .response::<InvoiceInspectionResponse>(),
+        RouteSpec::new(Method::Post, "/invoices/expired/sweep").response::<LegacyInvoiceExpirationSweepResponse>(),
+        RouteSpec::new(Method::Get, "/cashback_liabilities").response::<CashbackLiabilitiesResponse>(),
+        RouteSpec::new(Method::Get, "/cashback_liabilities/snapshots")
+            .query("as_of", PathParamKind::String)
+            .response::<Vec<CashbackLiabilitySnapshotResponse>>(),
+        RouteSpec::new(Method::Get, "/historical_reports")
+            .query("as_of", PathParamKind::String)
+            .response::<HistoricalReportResponse>(),
+        RouteSpec::new(Method::Get, "/negative_store_balances").response::<Vec<NegativeStoreBalanceResponse>>(),
+        RouteSpec::new(Method::Get, "/exchange_rate_slippages").response::<Vec<ExchangeRateSlippageResponse>>(),
+        RouteSpec::new(Method::Get, "/schema_version").response::<SchemaVersionResponse>(),
+        RouteSpec::new(Method::Get, "/feature_flags").response::<Vec<FeatureFlagResponse>>(),
+        RouteSpec::new(Method::Put, "/feature_flags/{feature}")
+            .param("feature", PathParamKind::Feature)
+            .request::<UpdateFeatureFlagRequest>()
+            .response::<FeatureFlagResponse>(),
+        RouteSpec::new(Method::Post, "/data_retention/purge")
+            .request::<DataRetentionPurgeRequest>()
+            .response::<DataRetentionPurgeResponse>(),
+        RouteSpec::new(Method::Put, "/data_retention/stores/{store_id}/legal_hold")
+            .param("store_id", PathParamKind::Integer)
+            .request::<LegalHoldRequest>()
+            .response::<DataRetentionSubjectResponse>(),
+        RouteSpec::new(Method::Put, "/data_retention/users/{user_id}/legal_hold")
+            .param("user_id", PathParamKind::Integer)
+            .request::<LegalHoldRequest>()
+            .response::<DataRetentionSubjectResponse>(),
+        RouteSpec::new(Method::Post, "/sandbox/invoices/{invoice_id}/inbound_tx")
+            .param("invoice_id", PathParamKind::Uuid)
+            .request::<SimulateInboundTxRequest>()
+            .response::<SimulatedInboundTxResponse>(),
+    ]
+}
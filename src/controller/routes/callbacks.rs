@@ -0,0 +1,19 @@
+//! Callbacks of Stripe, external billing and Payments gateway
+use hyper::Method;
+use stq_router::RouteParser;
+
+use super::{Route, RouteSpec, PAYMENTS_CALLBACK_ENDPOINT};
+
+pub fn add_routes(route_parser: &mut RouteParser<Route>) {
+    route_parser.add_route(r"^/v2/callback/stripe$", || Route::StripeWebhook);
+    route_parser.add_route(r"^/external_billing_callback$", || Route::ExternalBillingCallback);
+    route_parser.add_route(&format!(r"^{}$", PAYMENTS_CALLBACK_ENDPOINT), || Route::PaymentsInboundTx);
+}
+
+pub fn route_specs() -> Vec<RouteSpec> {
+    vec![
+        RouteSpec::new(Method::Post, "/v2/callback/stripe"),
+        RouteSpec::new(Method::Post, "/external_billing_callback"),
+        RouteSpec::new(Method::Post, PAYMENTS_CALLBACK_ENDPOINT),
+    ]
+}
@@ -0,0 +1,37 @@
+//! Stripe customers of the current user, support looks up customers of other users, superusers merge duplicates
+use hyper::Method;
+use stq_router::RouteParser;
+
+use super::{param, PathParamKind, Route, RouteSpec};
+use controller::requests::{DeleteCustomerRequest, NewCustomerWithSourceRequest, NewSetupIntentRequest, UpdateCustomerRequest};
+use controller::responses::{CustomerResponse, CustomersDeduplicationResponse, SetupIntentResponse};
+
+pub fn add_routes(route_parser: &mut RouteParser<Route>) {
+    route_parser.add_route(r"^/customers$", || Route::Customers);
+    route_parser.add_route(r"^/customers/with_source$", || Route::CustomersWithSource);
+    route_parser.add_route(r"^/customers/setup_intents$", || Route::CustomersSetupIntents);
+    route_parser.add_route(r"^/customers/deduplicate$", || Route::CustomersDeduplication);
+    route_parser.add_route_with_params(r"^/customers/by-user-id/(\d+)$", |params| {
+        param(&params, 0).map(|user_id| Route::CustomerByUserId { user_id })
+    });
+}
+
+pub fn route_specs() -> Vec<RouteSpec> {
+    vec![
+        RouteSpec::new(Method::Get, "/customers").response::<Option<CustomerResponse>>(),
+        RouteSpec::new(Method::Put, "/customers")
+            .request::<UpdateCustomerRequest>()
+            .response::<CustomerResponse>(),
+        RouteSpec::new(Method::Delete, "/customers").request::<DeleteCustomerRequest>(),
+        RouteSpec::new(Method::Post, "/customers/with_source")
+            .request::<NewCustomerWithSourceRequest>()
+            .response::<CustomerResponse>(),
+        RouteSpec::new(Method::Post, "/customers/setup_intents")
+            .request::<NewSetupIntentRequest>()
+            .response::<SetupIntentResponse>(),
+        RouteSpec::new(Method::Post, "/customers/deduplicate").response::<CustomersDeduplicationResponse>(),
+        RouteSpec::new(Method::Get, "/customers/by-user-id/{user_id}")
+            .param("user_id", PathParamKind::Integer)
+            .response::<Option<CustomerResponse>>(),
+    ]
+}
@@ -0,0 +1,18 @@
+//! Diagnostics of the running instance and metrics scraped by Prometheus
+use hyper::Method;
+use stq_router::RouteParser;
+
+use super::{Route, RouteSpec};
+use controller::responses::DependenciesResponse;
+
+pub fn add_routes(route_parser: &mut RouteParser<Route>) {
+    route_parser.add_route(r"^/debug/dependencies$", || Route::DebugDependencies);
+    route_parser.add_route(r"^/metrics$", || Route::Metrics);
+}
+
+pub fn route_specs() -> Vec<RouteSpec> {
+    vec![
+        RouteSpec::new(Method::Get, "/debug/dependencies").response::<DependenciesResponse>(),
+        RouteSpec::new(Method::Get, "/metrics"),
+    ]
+}
@@ -0,0 +1,13 @@
+//! Description of the API itself
+use hyper::Method;
+use stq_router::RouteParser;
+
+use super::{Route, RouteSpec};
+
+pub fn add_routes(route_parser: &mut RouteParser<Route>) {
+    route_parser.add_route(r"^/openapi\.json$", || Route::OpenApi);
+}
+
+pub fn route_specs() -> Vec<RouteSpec> {
+    vec![RouteSpec::new(Method::Get, "/openapi.json")]
+}
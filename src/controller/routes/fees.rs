@@ -0,0 +1,77 @@
+//! Order fees, their payment by card or crypto transfer, payment intents and monthly fee statements
+use hyper::Method;
+use stq_router::RouteParser;
+use stq_types::stripe::PaymentIntentId;
+
+use super::{param, PathParamKind, Route, RouteSpec};
+use controller::requests::{CreateFeeCryptoPaymentRequest, FeesPayByOrdersRequest, GenerateFeeStatementsRequest};
+use controller::responses::{
+    FeeCryptoPaymentResponse, FeeResponse, FeeStatementDocumentResponse, FeeStatementResponse, PaymentIntentHistoryResponse,
+    PaymentIntentResponse,
+};
+
+pub fn add_routes(route_parser: &mut RouteParser<Route>) {
+    route_parser.add_route_with_params(r"^/fees/by-order-id/([a-zA-Z0-9-]+)$", |params| {
+        param(&params, 0).map(|id| Route::FeesByOrder { id })
+    });
+    route_parser.add_route_with_params(r"^/fees/(\d+)/pay$", |params| param(&params, 0).map(|id| Route::FeesPay { id }));
+    route_parser.add_route_with_params(r"^/fees/(\d+)/crypto_payment$", |params| {
+        param(&params, 0).map(|id| Route::FeeCryptoPayment { id })
+    });
+    route_parser.add_route_with_params(r"^/fees/by-order-id/([a-zA-Z0-9-]+)/pay$", |params| {
+        param(&params, 0).map(|id| Route::FeesPayByOrder { id })
+    });
+    route_parser.add_route(r"^/fees/by-order-ids/pay$", || Route::FeesPayByOrders);
+    route_parser.add_route_with_params(r"^/payment_intents/fees/([a-zA-Z0-9-]+)$", |params| {
+        param(&params, 0).map(|fee_id| Route::PaymentIntentByFee { fee_id })
+    });
+    route_parser.add_route_with_params(r"^/payment-intents/([a-zA-Z0-9_]+)/history$", |params| {
+        param(&params, 0).map(|id| Route::PaymentIntentHistory { id: PaymentIntentId(id) })
+    });
+    route_parser.add_route(r"^/fee_statements/generate$", || Route::FeeStatementsGenerate);
+    route_parser.add_route_with_params(r"^/fee_statements/by-store-id/(\d+)$", |params| {
+        param(&params, 0).map(|store_id| Route::FeeStatementsByStoreId { store_id })
+    });
+    route_parser.add_route_with_params(r"^/fee_statements/(\d+)/download$", |params| {
+        param(&params, 0).map(|id| Route::FeeStatementDownload { id })
+    });
+}
+
+pub fn route_specs() -> Vec<RouteSpec> {
+    vec![
+        RouteSpec::new(Method::Get, "/fees/by-order-id/{id}")
+            .param("id", PathParamKind::Uuid)
+            .response::<Option<FeeResponse>>(),
+        RouteSpec::new(Method::Post, "/fees/{id}/pay")
+            .param("id", PathParamKind::Integer)
+            .response::<FeeResponse>(),
+        RouteSpec::new(Method::Post, "/fees/{id}/crypto_payment")
+            .param("id", PathParamKind::Integer)
+            .request::<CreateFeeCryptoPaymentRequest>()
+            .response::<FeeCryptoPaymentResponse>(),
+        RouteSpec::new(Method::Get, "/fees/{id}/crypto_payment")
+            .param("id", PathParamKind::Integer)
+            .response::<Option<FeeCryptoPaymentResponse>>(),
+        RouteSpec::new(Method::Post, "/fees/by-order-id/{id}/pay")
+            .param("id", PathParamKind::Uuid)
+            .response::<FeeResponse>(),
+        RouteSpec::new(Method::Post, "/fees/by-order-ids/pay")
+            .request::<FeesPayByOrdersRequest>()
+            .response::<Vec<FeeResponse>>(),
+        RouteSpec::new(Method::Post, "/payment_intents/fees/{fee_id}")
+            .param("fee_id", PathParamKind::Integer)
+            .response::<PaymentIntentResponse>(),
+        RouteSpec::new(Method::Get, "/payment-intents/{id}/history")
+            .param("id", PathParamKind::String)
+            .response::<PaymentIntentHistoryResponse>(),
+        RouteSpec::new(Method::Post, "/fee_statements/generate")
+            .request::<GenerateFeeStatementsRequest>()
+            .response::<Vec<FeeStatementResponse>>(),
+        RouteSpec::new(Method::Get, "/fee_statements/by-store-id/{store_id}")
+            .param("store_id", PathParamKind::Integer)
+            .response::<Vec<FeeStatementResponse>>(),
+        RouteSpec::new(Method::Get, "/fee_statements/{id}/download")
+            .param("id", PathParamKind::Integer)
+            .response::<FeeStatementDocumentResponse>(),
+    ]
+}
@@ -0,0 +1,146 @@
+//! Invoices, their payment intents, checkout sessions, the payment methods offered at checkout and the credit buyers spend there
+use hyper::Method;
+use stq_router::RouteParser;
+
+use super::{param, PathParamKind, Route, RouteSpec, CHECKOUT_SESSIONS_ENDPOINT};
+use controller::requests::{
+    CreateStoreInvoiceRequest, GrantUserCreditRequest, InvoiceRequoteRequest, ReviewInvoiceRiskRequest, UpdateInvoiceDetailsRequest,
+};
+use controller::responses::{
+    CreateInvoiceV2Response, InboundTransactionResponse, InvoiceCallbackResponse, InvoiceRequoteResponse, PaymentIntentResponse,
+    PaymentMethodsResponse, PublicInvoiceStatusResponse, StoreInvoiceResponse, UserCreditsResponse,
+};
+use models::invoice_v2::InvoiceDump;
+use models::{CheckoutSession, CreateInvoiceV2, InvoiceRiskAssessment};
+
+pub fn add_routes(route_parser: &mut RouteParser<Route>) {
+    route_parser.add_route(r"^/invoices$", || Route::Invoices);
+    route_parser.add_route(r"^/v2/invoices$", || Route::InvoicesV2);
+    route_parser.add_route_with_params(r"^/invoices/by-saga-id/([a-zA-Z0-9-]+)$", |params| {
+        param(&params, 0).map(|id| Route::InvoiceBySagaId { id })
+    });
+    route_parser.add_route_with_params(r"^/invoices/by-id/([a-zA-Z0-9-]+)/recalc$", |params| {
+        param(&params, 0).map(|id| Route::InvoiceByIdRecalc { id })
+    });
+    route_parser.add_route_with_params(r"^/invoices/by-id/([a-zA-Z0-9-]+)/order_ids$", |params| {
+        param(&params, 0).map(|id| Route::InvoiceOrdersIds { id })
+    });
+    route_parser.add_route_with_params(r"^/invoices/by-id/([a-zA-Z0-9-]+)$", |params| {
+        param(&params, 0).map(|id| Route::InvoiceById { id })
+    });
+    route_parser.add_route_with_params(r"^/v2/invoices/by-id/([a-zA-Z0-9-]+)$", |params| {
+        param(&params, 0).map(|id| Route::InvoiceByIdV2 { id })
+    });
+    route_parser.add_route_with_params(r"^/v2/invoices/by-id/([a-zA-Z0-9-]+)/payment_retry$", |params| {
+        param(&params, 0).map(|id| Route::InvoicePaymentRetry { id })
+    });
+    route_parser.add_route_with_params(r"^/v2/invoices/by-id/([a-zA-Z0-9-]+)/requote$", |params| {
+        param(&params, 0).map(|id| Route::InvoiceRequote { id })
+    });
+    route_parser.add_route_with_params(r"^/v2/invoices/by-id/([a-zA-Z0-9-]+)/cancel$", |params| {
+        param(&params, 0).map(|id| Route::InvoiceCancel { id })
+    });
+    route_parser.add_route_with_params(r"^/v2/invoices/by-id/([a-zA-Z0-9-]+)/callback$", |params| {
+        param(&params, 0).map(|invoice_id| Route::InvoiceCallbackByInvoiceId { invoice_id })
+    });
+    route_parser.add_route_with_params(r"^/v2/invoices/by-id/([a-zA-Z0-9-]+)/transactions$", |params| {
+        param(&params, 0).map(|id| Route::InvoiceTransactions { id })
+    });
+    route_parser.add_route_with_params(r"^/v2/invoices/by-id/([a-zA-Z0-9-]+)/risk_assessment$", |params| {
+        param(&params, 0).map(|id| Route::InvoiceRiskAssessment { id })
+    });
+    route_parser.add_route_with_params(r"^/v2/invoices/by-id/([a-zA-Z0-9-]+)/risk_assessment/review$", |params| {
+        param(&params, 0).map(|id| Route::InvoiceRiskReview { id })
+    });
+    route_parser.add_route(r"^/v2/invoices/risk_reviews$", || Route::InvoiceRiskReviews);
+    route_parser.add_route_with_params(&format!(r"^{}/([a-zA-Z0-9-]+)$", CHECKOUT_SESSIONS_ENDPOINT), |params| {
+        param(&params, 0).map(|invoice_id| Route::CheckoutSessionByInvoiceId { invoice_id })
+    });
+    route_parser.add_route_with_params(r"^/public/invoices/([a-zA-Z0-9]+)/status$", |params| {
+        param(&params, 0).map(|token| Route::PublicInvoiceStatus { token })
+    });
+    route_parser.add_route_with_params(r"^/stores/(\d+)/invoices$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreInvoices { store_id })
+    });
+    route_parser.add_route_with_params(r"^/invoices/by-order-id/([a-zA-Z0-9-]+)$", |params| {
+        param(&params, 0).map(|id| Route::InvoiceByOrderId { id })
+    });
+    route_parser.add_route_with_params(r"^/payment_intents/invoices/([a-zA-Z0-9-]+)$", |params| {
+        param(&params, 0).map(|invoice_id| Route::PaymentIntentByInvoice { invoice_id })
+    });
+    route_parser.add_route(r"^/payment-methods$", || Route::PaymentMethods);
+    route_parser.add_route(r"^/users/me/credits$", || Route::MyCredits);
+    route_parser.add_route_with_params(r"^/users/(\d+)/credits$", |params| {
+        param(&params, 0).map(|user_id| Route::UserCredits { user_id })
+    });
+}
+
+pub fn route_specs() -> Vec<RouteSpec> {
+    vec![
+        RouteSpec::new(Method::Post, "/invoices"),
+        RouteSpec::new(Method::Post, "/v2/invoices")
+            .request::<CreateInvoiceV2>()
+            .response::<CreateInvoiceV2Response>(),
+        RouteSpec::new(Method::Delete, "/invoices/by-saga-id/{id}").param("id", PathParamKind::Uuid),
+        RouteSpec::new(Method::Post, "/invoices/by-id/{id}/recalc").param("id", PathParamKind::Uuid),
+        RouteSpec::new(Method::Get, "/invoices/by-id/{id}/order_ids").param("id", PathParamKind::Uuid),
+        RouteSpec::new(Method::Get, "/invoices/by-id/{id}").param("id", PathParamKind::Uuid),
+        RouteSpec::new(Method::Get, "/v2/invoices/by-id/{id}")
+            .param("id", PathParamKind::Uuid)
+            .response::<Option<InvoiceDump>>(),
+        RouteSpec::new(Method::Patch, "/v2/invoices/by-id/{id}")
+            .param("id", PathParamKind::Uuid)
+            .request::<UpdateInvoiceDetailsRequest>()
+            .response::<Option<InvoiceDump>>(),
+        RouteSpec::new(Method::Post, "/v2/invoices/by-id/{id}/payment_retry")
+            .param("id", PathParamKind::Uuid)
+            .response::<PaymentIntentResponse>(),
+        RouteSpec::new(Method::Post, "/v2/invoices/by-id/{id}/requote")
+            .param("id", PathParamKind::Uuid)
+            .request::<InvoiceRequoteRequest>()
+            .response::<InvoiceRequoteResponse>(),
+        RouteSpec::new(Method::Post, "/v2/invoices/by-id/{id}/cancel")
+            .param("id", PathParamKind::Uuid)
+            .response::<InvoiceDump>(),
+        RouteSpec::new(Method::Get, "/v2/invoices/by-id/{id}/callback")
+            .param("id", PathParamKind::Uuid)
+            .response::<Option<InvoiceCallbackResponse>>(),
+        RouteSpec::new(Method::Get, "/v2/invoices/by-id/{id}/transactions")
+            .param("id", PathParamKind::Uuid)
+            .response::<Option<Vec<InboundTransactionResponse>>>(),
+        RouteSpec::new(Method::Get, "/v2/invoices/by-id/{id}/risk_assessment")
+            .param("id", PathParamKind::Uuid)
+            .response::<Option<InvoiceRiskAssessment>>(),
+        RouteSpec::new(Method::Post, "/v2/invoices/by-id/{id}/risk_assessment/review")
+            .param("id", PathParamKind::Uuid)
+            .request::<ReviewInvoiceRiskRequest>()
+            .response::<InvoiceRiskAssessment>(),
+        RouteSpec::new(Method::Get, "/v2/invoices/risk_reviews").response::<Vec<InvoiceRiskAssessment>>(),
+        RouteSpec::new(Method::Get, "/v2/checkout-sessions/{invoice_id}")
+            .param("invoice_id", PathParamKind::Uuid)
+            .response::<Option<CheckoutSession>>(),
+        RouteSpec::new(Method::Get, "/public/invoices/{token}/status")
+            .param("token", PathParamKind::String)
+            .response::<Option<PublicInvoiceStatusResponse>>(),
+        RouteSpec::new(Method::Post, "/stores/{store_id}/invoices")
+            .param("store_id", PathParamKind::Integer)
+            .request::<CreateStoreInvoiceRequest>()
+            .response::<StoreInvoiceResponse>(),
+        RouteSpec::new(Method::Get, "/invoices/by-order-id/{id}").param("id", PathParamKind::Uuid),
+        RouteSpec::new(Method::Get, "/payment_intents/invoices/{invoice_id}")
+            .param("invoice_id", PathParamKind::Uuid)
+            .response::<Option<PaymentIntentResponse>>(),
+        RouteSpec::new(Method::Get, "/payment-methods")
+            .query("country", PathParamKind::String)
+            .query("store_id", PathParamKind::Integer)
+            .response::<PaymentMethodsResponse>(),
+        RouteSpec::new(Method::Get, "/users/me/credits").response::<UserCreditsResponse>(),
+        RouteSpec::new(Method::Get, "/users/{user_id}/credits")
+            .param("user_id", PathParamKind::Integer)
+            .response::<UserCreditsResponse>(),
+        RouteSpec::new(Method::Post, "/users/{user_id}/credits")
+            .param("user_id", PathParamKind::Integer)
+            .request::<GrantUserCreditRequest>()
+            .response::<UserCreditsResponse>(),
+    ]
+}
@@ -0,0 +1,457 @@
+mod admin;
+mod callbacks;
+mod customers;
+mod debug;
+mod docs;
+mod fees;
+mod invoices;
+mod orders;
+mod payouts;
+mod stores;
+mod subscriptions;
+
+use std::str::FromStr;
+
+use hyper::Method;
+use serde_json::Value;
+use stq_router::RouteParser;
+use stq_types::stripe::PaymentIntentId;
+use stq_types::{InternationalBillingId, InvoiceId, OrderId, RoleId, RussiaBillingId, SagaId, StoreId, SubscriptionPaymentId, UserId};
+
+use controller::openapi::ApiSchema;
+use models::invoice_v2;
+use models::order_v2::{OrderId as Orderv2Id, StoreId as BillingStoreId};
+use models::{
+    AccountId, ApiKeyId, ApiKeyScope, BillingInfoChangeId, Feature, FeeId, FeeStatementId, PayoutId, PayoutInstructionId,
+    PayoutStatementId, StoreWebhookId, StripeFeeBackfillId, UserWalletId,
+};
+
+pub const PAYMENTS_CALLBACK_ENDPOINT: &'static str = "/v2/callback/payments/inbound_tx";
+pub const CHECKOUT_SESSIONS_ENDPOINT: &'static str = "/v2/checkout-sessions";
+
+const V1_PREFIX: &'static str = "/v1";
+const V2_PREFIX: &'static str = "/v2";
+
+/// Version of the API a request is addressed to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+/// List of all routes with params for the app
+#[derive(Clone, Debug, PartialEq)]
+pub enum Route {
+    StripeWebhook,
+    ExternalBillingCallback,
+    PaymentsInboundTx,
+    Invoices,
+    InvoicesV2,
+    InvoiceBySagaId { id: SagaId },
+    InvoiceById { id: InvoiceId },
+    InvoiceByIdV2 { id: invoice_v2::InvoiceId },
+    InvoicePaymentRetry { id: invoice_v2::InvoiceId },
+    InvoiceRequote { id: invoice_v2::InvoiceId },
+    InvoiceCancel { id: invoice_v2::InvoiceId },
+    InvoiceCallbackByInvoiceId { invoice_id: invoice_v2::InvoiceId },
+    InvoiceTransactions { id: invoice_v2::InvoiceId },
+    InvoiceRiskAssessment { id: invoice_v2::InvoiceId },
+    InvoiceRiskReview { id: invoice_v2::InvoiceId },
+    InvoiceRiskReviews,
+    MyCredits,
+    UserCredits { user_id: UserId },
+    CheckoutSessionByInvoiceId { invoice_id: invoice_v2::InvoiceId },
+    PublicInvoiceStatus { token: String },
+    StoreInvoices { store_id: BillingStoreId },
+    InvoiceByOrderId { id: OrderId },
+    InvoiceOrdersIds { id: InvoiceId },
+    InvoiceByIdRecalc { id: InvoiceId },
+    OrdersByIdCapture { id: Orderv2Id },
+    OrdersByIdDecline { id: Orderv2Id },
+    UserMerchants,
+    StoreMerchants,
+    UserMerchant { user_id: UserId },
+    UserMerchantBalance { user_id: UserId },
+    StoreMerchant { store_id: StoreId },
+    StoreMerchantBalance { store_id: StoreId },
+    Roles,
+    RoleById { id: RoleId },
+    RolesByUserId { user_id: UserId },
+    PaymentIntentByInvoice { invoice_id: invoice_v2::InvoiceId },
+    PaymentIntentByFee { fee_id: FeeId },
+    PaymentIntentHistory { id: PaymentIntentId },
+    PaymentMethods,
+    Customers,
+    CustomersWithSource,
+    CustomersSetupIntents,
+    CustomersDeduplication,
+    CustomerByUserId { user_id: UserId },
+    OrdersSetPaymentState { order_id: Orderv2Id },
+    OrderExchangeRates { order_id: Orderv2Id },
+    OrderLineItemsRefund { order_id: Orderv2Id },
+    OrderFeePreview { order_id: Orderv2Id },
+    OrderFeePreviews,
+    OrderSearch,
+    StoreOrders { store_id: BillingStoreId },
+    OrderBillingInfo,
+    InternationalBillingInfos,
+    RussiaBillingInfos,
+    InternationalBillingInfo { id: InternationalBillingId },
+    RussiaBillingInfo { id: RussiaBillingId },
+    InternationalBillingInfoByStore { id: StoreId },
+    RussiaBillingInfoByStore { id: StoreId },
+    BillingTypeByStore { id: StoreId },
+    BillingInfoChanges,
+    BillingInfoChangesByStore { store_id: StoreId },
+    BillingInfoChangeApprove { id: BillingInfoChangeId },
+    BillingInfoChangeReject { id: BillingInfoChangeId },
+    FeesByOrder { id: Orderv2Id },
+    FeesPay { id: FeeId },
+    FeeCryptoPayment { id: FeeId },
+    FeesPayByOrder { id: Orderv2Id },
+    FeesPayByOrders,
+    Payouts,
+    PayoutById { id: PayoutId },
+    PayoutsByOrderIds,
+    PayoutsByStoreId { id: BillingStoreId },
+    StoreBalance { store_id: BillingStoreId },
+    StoreBalanceOverview { store_id: BillingStoreId },
+    PayoutsCalculate,
+    UserWalletDeactivate { id: UserWalletId },
+    UserWalletVerification { id: UserWalletId },
+    UserWalletVerificationConfirm { id: UserWalletId },
+    Subscriptions,
+    SubscriptionBySubscriptionPaymentId { id: SubscriptionPaymentId },
+    SubscriptionPayment,
+    SubscriptionPaymentSearch,
+    StoreSubscription,
+    StoreSubscriptionByStoreId { store_id: StoreId },
+    StoreSubscriptionTrialStart { store_id: StoreId },
+    StoreSubscriptionTrialExtend { store_id: StoreId },
+    StoreSubscriptionTrialEnd { store_id: StoreId },
+    StoreSubscriptionPayments { store_id: StoreId },
+    FeeStatementsGenerate,
+    FeeStatementsByStoreId { store_id: StoreId },
+    FeeStatementDownload { id: FeeStatementId },
+    AccountArchive { account_id: AccountId },
+    SystemAccountsTransfer,
+    AuditLogSearch,
+    StoreBillingStatus { store_id: StoreId },
+    StoreBillingStatusOverride { store_id: StoreId },
+    StoreBillingStatusReinstate { store_id: StoreId },
+    StoreOffboarding { store_id: StoreId },
+    PayoutRestriction { store_id: StoreId },
+    PayoutRestrictionOverride { store_id: StoreId },
+    StoreWebhooksByStoreId { store_id: StoreId },
+    StoreWebhook { id: StoreWebhookId },
+    ApiKeysByStoreId { store_id: StoreId },
+    ApiKey { id: ApiKeyId },
+    PayoutInstructionsByStoreId { store_id: StoreId },
+    PayoutInstruction { id: PayoutInstructionId },
+    PayoutStatementDownload { id: PayoutStatementId },
+    StripeFeeBackfills,
+    StripeFeeBackfill { id: StripeFeeBackfillId },
+    PaymentRecoveriesReport,
+    InvoiceInspection { invoice_id: invoice_v2::InvoiceId },
+    LegacyInvoicesExpirationSweep,
+    CashbackLiabilities,
+    CashbackLiabilitySnapshots,
+    HistoricalReport,
+    NegativeStoreBalances,
+    ExchangeRateSlippages,
+    SchemaVersion,
+    BillingInfoReencryption,
+    FeatureFlags,
+    FeatureFlag { feature: Feature },
+    DataRetentionPurge,
+    StoreLegalHold { store_id: StoreId },
+    UserLegalHold { user_id: UserId },
+    SandboxInboundTx { invoice_id: invoice_v2::InvoiceId },
+    DebugDependencies,
+    Metrics,
+    OpenApi,
+}
+
+impl Route {
+    /// Returns the API version the route semantics belong to.
+    /// `None` means that the handler is shared between both versions
+    pub fn api_version(&self) -> Option<ApiVersion> {
+        match self {
+            Route::ExternalBillingCallback
+            | Route::Invoices
+            | Route::InvoiceBySagaId { .. }
+            | Route::InvoiceById { .. }
+            | Route::InvoiceByOrderId { .. }
+            | Route::InvoiceOrdersIds { .. }
+            | Route::InvoiceByIdRecalc { .. }
+            | Route::UserMerchants
+            | Route::StoreMerchants
+            | Route::UserMerchant { .. }
+            | Route::UserMerchantBalance { .. }
+            | Route::StoreMerchant { .. }
+            | Route::StoreMerchantBalance { .. } => Some(ApiVersion::V1),
+            Route::StripeWebhook
+            | Route::PaymentsInboundTx
+            | Route::InvoicesV2
+            | Route::InvoiceByIdV2 { .. }
+            | Route::InvoicePaymentRetry { .. }
+            | Route::InvoiceRequote { .. }
+            | Route::InvoiceCancel { .. }
+            | Route::InvoiceCallbackByInvoiceId { .. }
+            | Route::InvoiceTransactions { .. }
+            | Route::CheckoutSessionByInvoiceId { .. } => Some(ApiVersion::V2),
+            _ => None,
+        }
+    }
+
+    /// Scope a store API key needs for the request and the store the request is about.
+    /// `None` means that the request can't be made with an API key
+    pub fn api_key_access(&self, method: &Method) -> Option<(ApiKeyScope, StoreId)> {
+        match (method, self) {
+            (&Method::Get, Route::StoreOrders { store_id }) => Some((ApiKeyScope::Orders, StoreId(store_id.inner()))),
+            (&Method::Post, Route::StoreInvoices { store_id }) => Some((ApiKeyScope::Invoices, StoreId(store_id.inner()))),
+            (&Method::Get, Route::StoreBalance { store_id }) | (&Method::Get, Route::StoreBalanceOverview { store_id }) => {
+                Some((ApiKeyScope::Balance, StoreId(store_id.inner())))
+            }
+            (&Method::Get, Route::PayoutsByStoreId { id }) => Some((ApiKeyScope::Payouts, StoreId(id.inner()))),
+            (&Method::Get, Route::FeeStatementsByStoreId { store_id }) => Some((ApiKeyScope::FeeStatements, *store_id)),
+            _ => None,
+        }
+    }
+}
+
+/// Resolves the route for the path taking the version prefix into account.
+///
+/// `/v1/...` paths are resolved without the prefix and can't reach v2-only routes.
+/// `/v2/...` paths are first matched against explicit v2 routes, then against shared routes without the prefix.
+/// Unprefixed paths are legacy: they are treated as v1 only if they reach v1-only routes, shared routes behave the same
+/// in both versions and are treated as v2, so that they stay served and undeprecated with v1 disabled.
+pub fn resolve_route(route_parser: &RouteParser<Route>, path: &str) -> Option<(Route, ApiVersion)> {
+    if path.starts_with(&format!("{}/", V1_PREFIX)) {
+        route_parser
+            .test(&path[V1_PREFIX.len()..])
+            .filter(|route| route.api_version() != Some(ApiVersion::V2))
+            .map(|route| (route, ApiVersion::V1))
+    } else if path.starts_with(&format!("{}/", V2_PREFIX)) {
+        route_parser
+            .test(path)
+            .or_else(|| {
+                route_parser
+                    .test(&path[V2_PREFIX.len()..])
+                    .filter(|route| route.api_version() != Some(ApiVersion::V1))
+            })
+            .map(|route| (route, ApiVersion::V2))
+    } else {
+        route_parser.test(path).map(|route| {
+            let version = route.api_version().unwrap_or(ApiVersion::V2);
+            (route, version)
+        })
+    }
+}
+
+pub fn create_route_parser() -> RouteParser<Route> {
+    let mut route_parser = RouteParser::default();
+    callbacks::add_routes(&mut route_parser);
+    invoices::add_routes(&mut route_parser);
+    orders::add_routes(&mut route_parser);
+    fees::add_routes(&mut route_parser);
+    payouts::add_routes(&mut route_parser);
+    subscriptions::add_routes(&mut route_parser);
+    customers::add_routes(&mut route_parser);
+    stores::add_routes(&mut route_parser);
+    admin::add_routes(&mut route_parser);
+    debug::add_routes(&mut route_parser);
+    docs::add_routes(&mut route_parser);
+    route_parser
+}
+
+/// Listing of every method and path served by the controller, used to describe the API
+pub fn route_specs() -> Vec<RouteSpec> {
+    let mut specs = Vec::new();
+    specs.extend(callbacks::route_specs());
+    specs.extend(invoices::route_specs());
+    specs.extend(orders::route_specs());
+    specs.extend(fees::route_specs());
+    specs.extend(payouts::route_specs());
+    specs.extend(subscriptions::route_specs());
+    specs.extend(customers::route_specs());
+    specs.extend(stores::route_specs());
+    specs.extend(admin::route_specs());
+    specs.extend(debug::route_specs());
+    specs.extend(docs::route_specs());
+    specs
+}
+
+/// Parses the path param at `index` into the type of the route field
+fn param<S: AsRef<str>, T: FromStr>(params: &[S], index: usize) -> Option<T> {
+    params.get(index).and_then(|param| param.as_ref().parse().ok())
+}
+
+/// Format of a path or query param
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathParamKind {
+    Integer,
+    Uuid,
+    String,
+    Feature,
+}
+
+impl PathParamKind {
+    /// Value of the param that the route parser accepts
+    pub fn example(&self) -> &'static str {
+        match self {
+            PathParamKind::Integer => "1",
+            PathParamKind::Uuid => "00000000-0000-0000-0000-000000000000",
+            PathParamKind::String => "example",
+            PathParamKind::Feature => "payout_wallet_verification",
+        }
+    }
+}
+
+/// Method and path template of a handled request, e.g. `GET /payouts/{id}`
+#[derive(Clone, Debug)]
+pub struct RouteSpec {
+    pub method: Method,
+    pub path: &'static str,
+    pub params: Vec<(&'static str, PathParamKind)>,
+    pub query: Vec<(&'static str, PathParamKind)>,
+    pub paginated: bool,
+    pub request: Option<fn() -> Value>,
+    pub response: Option<fn() -> Value>,
+}
+
+impl RouteSpec {
+    pub fn new(method: Method, path: &'static str) -> Self {
+        Self {
+            method,
+            path,
+            params: Vec::new(),
+            query: Vec::new(),
+            paginated: false,
+            request: None,
+            response: None,
+        }
+    }
+
+    /// Declares a path param, its name must match the placeholder in the path template
+    pub fn param(mut self, name: &'static str, kind: PathParamKind) -> Self {
+        self.params.push((name, kind));
+        self
+    }
+
+    /// Declares an optional query param
+    pub fn query(mut self, name: &'static str, kind: PathParamKind) -> Self {
+        self.query.push((name, kind));
+        self
+    }
+
+    /// Declares `skip` and `count` query params
+    pub fn paginated(mut self) -> Self {
+        self.paginated = true;
+        self
+    }
+
+    /// Declares the JSON body of the request
+    pub fn request<T: ApiSchema>(mut self) -> Self {
+        self.request = Some(T::schema as fn() -> Value);
+        self
+    }
+
+    /// Declares the JSON body of the successful response
+    pub fn response<T: ApiSchema>(mut self) -> Self {
+        self.response = Some(T::schema as fn() -> Value);
+        self
+    }
+
+    /// Path with every param replaced by a valid value
+    pub fn example_path(&self) -> String {
+        self.params.iter().fold(self.path.to_string(), |path, (name, kind)| {
+            path.replace(&format!("{{{}}}", name), kind.example())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_versioned_paths() {
+        let route_parser = create_route_parser();
+
+        assert_eq!(
+            resolve_route(&route_parser, "/orders/search"),
+            Some((Route::OrderSearch, ApiVersion::V2))
+        );
+        assert_eq!(resolve_route(&route_parser, "/invoices"), Some((Route::Invoices, ApiVersion::V1)));
+        assert_eq!(
+            resolve_route(&route_parser, "/v1/orders/search"),
+            Some((Route::OrderSearch, ApiVersion::V1))
+        );
+        assert_eq!(
+            resolve_route(&route_parser, "/v2/orders/search"),
+            Some((Route::OrderSearch, ApiVersion::V2))
+        );
+        assert_eq!(
+            resolve_route(&route_parser, "/v2/invoices"),
+            Some((Route::InvoicesV2, ApiVersion::V2))
+        );
+        assert_eq!(
+            resolve_route(&route_parser, "/v1/invoices"),
+            Some((Route::Invoices, ApiVersion::V1))
+        );
+        assert_eq!(resolve_route(&route_parser, "/v1/v2/invoices"), None);
+        assert_eq!(resolve_route(&route_parser, "/v2/merchants/user"), None);
+    }
+    #[test]
+    fn resolves_every_route_spec() {
+        let route_parser = create_route_parser();
+
+        for spec in route_specs() {
+            let path = spec.example_path();
+            assert!(!path.contains('{'), "unknown param in {:?}", spec);
+            assert!(
+                resolve_route(&route_parser, &path).is_some(),
+                "{} {} is not routed",
+                spec.method,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn route_specs_are_unique() {
+        let specs = route_specs();
+
+        for (i, spec) in specs.iter().enumerate() {
+            assert!(
+                specs[i + 1..]
+                    .iter()
+                    .all(|other| other.method != spec.method || other.path != spec.path),
+                "{} {} is listed twice",
+                spec.method,
+                spec.path
+            );
+        }
+    }
+
+    #[test]
+    fn api_keys_reach_only_store_scoped_routes() {
+        let route_parser = create_route_parser();
+        let access = |method: Method, path: &str| resolve_route(&route_parser, path).and_then(|(route, _)| route.api_key_access(&method));
+
+        assert_eq!(access(Method::Get, "/v2/stores/7/orders"), Some((ApiKeyScope::Orders, StoreId(7))));
+        assert_eq!(
+            access(Method::Get, "/balance/by-store-id/7"),
+            Some((ApiKeyScope::Balance, StoreId(7)))
+        );
+        assert_eq!(
+            access(Method::Get, "/fee_statements/by-store-id/7"),
+            Some((ApiKeyScope::FeeStatements, StoreId(7)))
+        );
+        assert_eq!(access(Method::Post, "/v2/stores/7/orders"), None);
+        assert_eq!(access(Method::Get, "/api_keys/by-store-id/7"), None);
+        assert_eq!(access(Method::Delete, "/api_keys/1"), None);
+        assert_eq!(access(Method::Get, "/store_webhooks/by-store-id/7"), None);
+    }
+}
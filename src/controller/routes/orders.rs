@@ -0,0 +1,66 @@
+//! Orders, their payment states and exchange rates
+use hyper::Method;
+use stq_router::RouteParser;
+
+use super::{param, PathParamKind, Route, RouteSpec};
+use controller::requests::{OrderFeePreviewsRequest, OrderPaymentStateRequest, RefundOrderLineItemsRequest};
+use controller::responses::{OrderExchangeRatesResponse, OrderFeePreviewResponse, OrderResponse, OrderSearchResultsResponse};
+
+pub fn add_routes(route_parser: &mut RouteParser<Route>) {
+    route_parser.add_route_with_params(r"^/orders/([a-zA-Z0-9-]+)/capture$", |params| {
+        param(&params, 0).map(|id| Route::OrdersByIdCapture { id })
+    });
+    route_parser.add_route_with_params(r"^/orders/([a-zA-Z0-9-]+)/decline$", |params| {
+        param(&params, 0).map(|id| Route::OrdersByIdDecline { id })
+    });
+    route_parser.add_route_with_params(r"^/orders/([a-zA-Z0-9-]+)/set_payment_state$", |params| {
+        param(&params, 0).map(|order_id| Route::OrdersSetPaymentState { order_id })
+    });
+    route_parser.add_route_with_params(r"^/orders/([a-zA-Z0-9-]+)/exchange-rates$", |params| {
+        param(&params, 0).map(|order_id| Route::OrderExchangeRates { order_id })
+    });
+    route_parser.add_route_with_params(r"^/orders/([a-zA-Z0-9-]+)/line-items/refund$", |params| {
+        param(&params, 0).map(|order_id| Route::OrderLineItemsRefund { order_id })
+    });
+    route_parser.add_route_with_params(r"^/orders/([a-zA-Z0-9-]+)/fee-preview$", |params| {
+        param(&params, 0).map(|order_id| Route::OrderFeePreview { order_id })
+    });
+    route_parser.add_route(r"^/orders/fee-preview$", || Route::OrderFeePreviews);
+    route_parser.add_route(r"^/orders/search$", || Route::OrderSearch);
+    route_parser.add_route_with_params(r"^/stores/(\d+)/orders$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreOrders { store_id })
+    });
+    route_parser.add_route(r"^/order_billing_info$", || Route::OrderBillingInfo);
+}
+
+pub fn route_specs() -> Vec<RouteSpec> {
+    vec![
+        RouteSpec::new(Method::Post, "/orders/{id}/capture").param("id", PathParamKind::Uuid),
+        RouteSpec::new(Method::Post, "/orders/{id}/decline").param("id", PathParamKind::Uuid),
+        RouteSpec::new(Method::Post, "/orders/{order_id}/set_payment_state")
+            .param("order_id", PathParamKind::Uuid)
+            .request::<OrderPaymentStateRequest>(),
+        RouteSpec::new(Method::Get, "/orders/{order_id}/exchange-rates")
+            .param("order_id", PathParamKind::Uuid)
+            .response::<OrderExchangeRatesResponse>(),
+        RouteSpec::new(Method::Post, "/orders/{order_id}/line-items/refund")
+            .param("order_id", PathParamKind::Uuid)
+            .request::<RefundOrderLineItemsRequest>()
+            .response::<OrderResponse>(),
+        RouteSpec::new(Method::Get, "/orders/{order_id}/fee-preview")
+            .param("order_id", PathParamKind::Uuid)
+            .response::<OrderFeePreviewResponse>(),
+        RouteSpec::new(Method::Post, "/orders/fee-preview")
+            .request::<OrderFeePreviewsRequest>()
+            .response::<Vec<OrderFeePreviewResponse>>(),
+        RouteSpec::new(Method::Post, "/orders/search")
+            .paginated()
+            .response::<OrderSearchResultsResponse>(),
+        RouteSpec::new(Method::Get, "/stores/{store_id}/orders")
+            .param("store_id", PathParamKind::Integer)
+            .query("payment_status", PathParamKind::String)
+            .paginated()
+            .response::<OrderSearchResultsResponse>(),
+        RouteSpec::new(Method::Post, "/order_billing_info").paginated(),
+    ]
+}
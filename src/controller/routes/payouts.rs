@@ -0,0 +1,88 @@
+//! Payouts to sellers and their statements, the wallets they are sent to, store balances and bank transfer payout instructions
+use hyper::Method;
+use stq_router::RouteParser;
+
+use super::{param, PathParamKind, Route, RouteSpec};
+use controller::requests::{ConfirmWalletVerificationRequest, GeneratePayoutInstructionRequest};
+use controller::responses::{
+    BalancesResponse, PayoutInstructionResponse, PayoutStatementDocumentResponse, StoreBalanceOverviewResponse, UserWalletResponse,
+    WalletVerificationResponse,
+};
+
+pub fn add_routes(route_parser: &mut RouteParser<Route>) {
+    route_parser.add_route(r"^/payouts$", || Route::Payouts);
+    route_parser.add_route(r"^/payouts/by-order-ids$", || Route::PayoutsByOrderIds);
+    route_parser.add_route(r"^/payouts/calculate$", || Route::PayoutsCalculate);
+    route_parser.add_route_with_params(r"^/payouts/by-store-id/(\d+)$", |params| {
+        param(&params, 0).map(|id| Route::PayoutsByStoreId { id })
+    });
+    route_parser.add_route_with_params(r"^/users/me/wallets/([a-zA-Z0-9-]+)/deactivate$", |params| {
+        param(&params, 0).map(|id| Route::UserWalletDeactivate { id })
+    });
+    route_parser.add_route_with_params(r"^/users/me/wallets/([a-zA-Z0-9-]+)/verification$", |params| {
+        param(&params, 0).map(|id| Route::UserWalletVerification { id })
+    });
+    route_parser.add_route_with_params(r"^/users/me/wallets/([a-zA-Z0-9-]+)/verification/confirm$", |params| {
+        param(&params, 0).map(|id| Route::UserWalletVerificationConfirm { id })
+    });
+    route_parser.add_route_with_params(r"^/balance/by-store-id/(\d+)$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreBalance { store_id })
+    });
+    route_parser.add_route_with_params(r"^/stores/(\d+)/balance-overview$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreBalanceOverview { store_id })
+    });
+    route_parser.add_route_with_params(r"^/payouts/([a-zA-Z0-9-]+)$", |params| {
+        param(&params, 0).map(|id| Route::PayoutById { id })
+    });
+    route_parser.add_route_with_params(r"^/payout_instructions/by-store-id/(\d+)$", |params| {
+        param(&params, 0).map(|store_id| Route::PayoutInstructionsByStoreId { store_id })
+    });
+    route_parser.add_route_with_params(r"^/payout_instructions/(\d+)$", |params| {
+        param(&params, 0).map(|id| Route::PayoutInstruction { id })
+    });
+    route_parser.add_route_with_params(r"^/payout_statements/(\d+)/download$", |params| {
+        param(&params, 0).map(|id| Route::PayoutStatementDownload { id })
+    });
+}
+
+pub fn route_specs() -> Vec<RouteSpec> {
+    vec![
+        RouteSpec::new(Method::Post, "/payouts"),
+        RouteSpec::new(Method::Post, "/payouts/by-order-ids"),
+        RouteSpec::new(Method::Post, "/payouts/calculate"),
+        RouteSpec::new(Method::Get, "/payouts/by-store-id/{id}").param("id", PathParamKind::Integer),
+        RouteSpec::new(Method::Put, "/users/me/wallets/{id}/deactivate")
+            .param("id", PathParamKind::Uuid)
+            .response::<UserWalletResponse>(),
+        RouteSpec::new(Method::Get, "/users/me/wallets/{id}/verification")
+            .param("id", PathParamKind::Uuid)
+            .response::<Option<WalletVerificationResponse>>(),
+        RouteSpec::new(Method::Post, "/users/me/wallets/{id}/verification")
+            .param("id", PathParamKind::Uuid)
+            .response::<WalletVerificationResponse>(),
+        RouteSpec::new(Method::Post, "/users/me/wallets/{id}/verification/confirm")
+            .param("id", PathParamKind::Uuid)
+            .request::<ConfirmWalletVerificationRequest>()
+            .response::<WalletVerificationResponse>(),
+        RouteSpec::new(Method::Get, "/balance/by-store-id/{store_id}")
+            .param("store_id", PathParamKind::Integer)
+            .response::<BalancesResponse>(),
+        RouteSpec::new(Method::Get, "/stores/{store_id}/balance-overview")
+            .param("store_id", PathParamKind::Integer)
+            .response::<StoreBalanceOverviewResponse>(),
+        RouteSpec::new(Method::Get, "/payouts/{id}").param("id", PathParamKind::Uuid),
+        RouteSpec::new(Method::Get, "/payout_instructions/by-store-id/{store_id}")
+            .param("store_id", PathParamKind::Integer)
+            .response::<Vec<PayoutInstructionResponse>>(),
+        RouteSpec::new(Method::Post, "/payout_instructions/by-store-id/{store_id}")
+            .param("store_id", PathParamKind::Integer)
+            .request::<GeneratePayoutInstructionRequest>()
+            .response::<PayoutInstructionResponse>(),
+        RouteSpec::new(Method::Get, "/payout_instructions/{id}")
+            .param("id", PathParamKind::Integer)
+            .response::<Option<PayoutInstructionResponse>>(),
+        RouteSpec::new(Method::Get, "/payout_statements/{id}/download")
+            .param("id", PathParamKind::Integer)
+            .response::<PayoutStatementDocumentResponse>(),
+    ]
+}
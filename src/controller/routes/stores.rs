@@ -0,0 +1,161 @@
+//! Merchants, billing info, billing types, billing statuses, offboardings, payout restrictions and webhooks of stores
+use hyper::Method;
+use stq_router::RouteParser;
+
+use super::{param, PathParamKind, Route, RouteSpec};
+use controller::requests::{
+    CreateApiKeyRequest, CreateStoreWebhookRequest, OverridePayoutRestrictionRequest, OverrideStoreBillingStatusRequest,
+    RejectBillingInfoChangeRequest, UpdateStoreWebhookRequest,
+};
+use controller::responses::{
+    ApiKeyResponse, BillingInfoChangeResponse, CreatedApiKeyResponse, PayoutRestrictionResponse, StoreBillingStatusResponse,
+    StoreOffboardingResponse, StoreWebhookResponse,
+};
+
+pub fn add_routes(route_parser: &mut RouteParser<Route>) {
+    route_parser.add_route(r"^/merchants/user$", || Route::UserMerchants);
+    route_parser.add_route_with_params(r"^/merchants/user/(\d+)$", |params| {
+        param(&params, 0).map(|user_id| Route::UserMerchant { user_id })
+    });
+    route_parser.add_route_with_params(r"^/merchants/user/(\d+)/balance$", |params| {
+        param(&params, 0).map(|user_id| Route::UserMerchantBalance { user_id })
+    });
+    route_parser.add_route(r"^/merchants/store$", || Route::StoreMerchants);
+    route_parser.add_route_with_params(r"^/merchants/store/(\d+)$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreMerchant { store_id })
+    });
+    route_parser.add_route_with_params(r"^/merchants/store/(\d+)/balance$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreMerchantBalance { store_id })
+    });
+    route_parser.add_route(r"^/billing_info/international$", || Route::InternationalBillingInfos);
+    route_parser.add_route(r"^/billing_info/russia$", || Route::RussiaBillingInfos);
+    route_parser.add_route_with_params(r"^/billing_type/by-store-id/(\d+)$", |params| {
+        param(&params, 0).map(|id| Route::BillingTypeByStore { id })
+    });
+    route_parser.add_route_with_params(r"^/billing_info/international/by-store-id/(\d+)$", |params| {
+        param(&params, 0).map(|id| Route::InternationalBillingInfoByStore { id })
+    });
+    route_parser.add_route_with_params(r"^/billing_info/russia/by-store-id/(\d+)$", |params| {
+        param(&params, 0).map(|id| Route::RussiaBillingInfoByStore { id })
+    });
+    route_parser.add_route_with_params(r"^/billing_info/international/(\d+)$", |params| {
+        param(&params, 0).map(|id| Route::InternationalBillingInfo { id })
+    });
+    route_parser.add_route_with_params(r"^/billing_info/russia/(\d+)$", |params| {
+        param(&params, 0).map(|id| Route::RussiaBillingInfo { id })
+    });
+    route_parser.add_route(r"^/billing_info_changes$", || Route::BillingInfoChanges);
+    route_parser.add_route_with_params(r"^/billing_info_changes/by-store-id/(\d+)$", |params| {
+        param(&params, 0).map(|store_id| Route::BillingInfoChangesByStore { store_id })
+    });
+    route_parser.add_route_with_params(r"^/billing_info_changes/(\d+)/approve$", |params| {
+        param(&params, 0).map(|id| Route::BillingInfoChangeApprove { id })
+    });
+    route_parser.add_route_with_params(r"^/billing_info_changes/(\d+)/reject$", |params| {
+        param(&params, 0).map(|id| Route::BillingInfoChangeReject { id })
+    });
+    route_parser.add_route_with_params(r"^/store_billing_status/by-store-id/(\d+)$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreBillingStatus { store_id })
+    });
+    route_parser.add_route_with_params(r"^/store_billing_status/by-store-id/(\d+)/override$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreBillingStatusOverride { store_id })
+    });
+    route_parser.add_route_with_params(r"^/store_billing_status/by-store-id/(\d+)/reinstate$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreBillingStatusReinstate { store_id })
+    });
+    route_parser.add_route_with_params(r"^/store_offboardings/by-store-id/(\d+)$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreOffboarding { store_id })
+    });
+    route_parser.add_route_with_params(r"^/payout_restrictions/by-store-id/(\d+)$", |params| {
+        param(&params, 0).map(|store_id| Route::PayoutRestriction { store_id })
+    });
+    route_parser.add_route_with_params(r"^/payout_restrictions/by-store-id/(\d+)/override$", |params| {
+        param(&params, 0).map(|store_id| Route::PayoutRestrictionOverride { store_id })
+    });
+    route_parser.add_route_with_params(r"^/store_webhooks/by-store-id/(\d+)$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreWebhooksByStoreId { store_id })
+    });
+    route_parser.add_route_with_params(r"^/store_webhooks/(\d+)$", |params| {
+        param(&params, 0).map(|id| Route::StoreWebhook { id })
+    });
+    route_parser.add_route_with_params(r"^/api_keys/by-store-id/(\d+)$", |params| {
+        param(&params, 0).map(|store_id| Route::ApiKeysByStoreId { store_id })
+    });
+    route_parser.add_route_with_params(r"^/api_keys/(\d+)$", |params| param(&params, 0).map(|id| Route::ApiKey { id }));
+}
+
+pub fn route_specs() -> Vec<RouteSpec> {
+    vec![
+        RouteSpec::new(Method::Post, "/merchants/user"),
+        RouteSpec::new(Method::Delete, "/merchants/user/{user_id}").param("user_id", PathParamKind::Integer),
+        RouteSpec::new(Method::Get, "/merchants/user/{user_id}/balance").param("user_id", PathParamKind::Integer),
+        RouteSpec::new(Method::Post, "/merchants/store"),
+        RouteSpec::new(Method::Delete, "/merchants/store/{store_id}").param("store_id", PathParamKind::Integer),
+        RouteSpec::new(Method::Get, "/merchants/store/{store_id}/balance").param("store_id", PathParamKind::Integer),
+        RouteSpec::new(Method::Post, "/billing_info/international"),
+        RouteSpec::new(Method::Post, "/billing_info/russia"),
+        RouteSpec::new(Method::Get, "/billing_type/by-store-id/{id}").param("id", PathParamKind::Integer),
+        RouteSpec::new(Method::Get, "/billing_info/international/by-store-id/{id}").param("id", PathParamKind::Integer),
+        RouteSpec::new(Method::Get, "/billing_info/russia/by-store-id/{id}").param("id", PathParamKind::Integer),
+        RouteSpec::new(Method::Put, "/billing_info/international/{id}").param("id", PathParamKind::Integer),
+        RouteSpec::new(Method::Put, "/billing_info/russia/{id}").param("id", PathParamKind::Integer),
+        RouteSpec::new(Method::Get, "/billing_info_changes").response::<Vec<BillingInfoChangeResponse>>(),
+        RouteSpec::new(Method::Get, "/billing_info_changes/by-store-id/{store_id}")
+            .param("store_id", PathParamKind::Integer)
+            .response::<Vec<BillingInfoChangeResponse>>(),
+        RouteSpec::new(Method::Post, "/billing_info_changes/{id}/approve")
+            .param("id", PathParamKind::Integer)
+            .response::<BillingInfoChangeResponse>(),
+        RouteSpec::new(Method::Post, "/billing_info_changes/{id}/reject")
+            .param("id", PathParamKind::Integer)
+            .request::<RejectBillingInfoChangeRequest>()
+            .response::<BillingInfoChangeResponse>(),
+        RouteSpec::new(Method::Get, "/store_billing_status/by-store-id/{store_id}")
+            .param("store_id", PathParamKind::Integer)
+            .response::<StoreBillingStatusResponse>(),
+        RouteSpec::new(Method::Post, "/store_billing_status/by-store-id/{store_id}/override")
+            .param("store_id", PathParamKind::Integer)
+            .request::<OverrideStoreBillingStatusRequest>()
+            .response::<StoreBillingStatusResponse>(),
+        RouteSpec::new(Method::Post, "/store_billing_status/by-store-id/{store_id}/reinstate")
+            .param("store_id", PathParamKind::Integer)
+            .response::<StoreBillingStatusResponse>(),
+        RouteSpec::new(Method::Get, "/store_offboardings/by-store-id/{store_id}")
+            .param("store_id", PathParamKind::Integer)
+            .response::<Option<StoreOffboardingResponse>>(),
+        RouteSpec::new(Method::Get, "/payout_restrictions/by-store-id/{store_id}")
+            .param("store_id", PathParamKind::Integer)
+            .response::<PayoutRestrictionResponse>(),
+        RouteSpec::new(Method::Post, "/payout_restrictions/by-store-id/{store_id}/override")
+            .param("store_id", PathParamKind::Integer)
+            .request::<OverridePayoutRestrictionRequest>()
+            .response::<PayoutRestrictionResponse>(),
+        RouteSpec::new(Method::Delete, "/payout_restrictions/by-store-id/{store_id}/override")
+            .param("store_id", PathParamKind::Integer)
+            .response::<PayoutRestrictionResponse>(),
+        RouteSpec::new(Method::Get, "/store_webhooks/by-store-id/{store_id}")
+            .param("store_id", PathParamKind::Integer)
+            .response::<Vec<StoreWebhookResponse>>(),
+        RouteSpec::new(Method::Post, "/store_webhooks/by-store-id/{store_id}")
+            .param("store_id", PathParamKind::Integer)
+            .request::<CreateStoreWebhookRequest>()
+            .response::<StoreWebhookResponse>(),
+        RouteSpec::new(Method::Put, "/store_webhooks/{id}")
+            .param("id", PathParamKind::Integer)
+            .request::<UpdateStoreWebhookRequest>()
+            .response::<StoreWebhookResponse>(),
+        RouteSpec::new(Method::Delete, "/store_webhooks/{id}")
+            .param("id", PathParamKind::Integer)
+            .response::<StoreWebhookResponse>(),
+        RouteSpec::new(Method::Get, "/api_keys/by-store-id/{store_id}")
+            .param("store_id", PathParamKind::Integer)
+            .response::<Vec<ApiKeyResponse>>(),
+        RouteSpec::new(Method::Post, "/api_keys/by-store-id/{store_id}")
+            .param("store_id", PathParamKind::Integer)
+            .request::<CreateApiKeyRequest>()
+            .response::<CreatedApiKeyResponse>(),
+        RouteSpec::new(Method::Delete, "/api_keys/{id}")
+            .param("id", PathParamKind::Integer)
+            .response::<ApiKeyResponse>(),
+    ]
+}
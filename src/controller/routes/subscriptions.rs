@@ -0,0 +1,70 @@
+//! Store subscriptions and their payments
+use hyper::Method;
+use stq_router::RouteParser;
+
+use super::{param, PathParamKind, Route, RouteSpec};
+use controller::requests::{
+    CreateStoreSubscriptionRequest, CreateSubscriptionsRequest, ExtendTrialRequest, UpdateStoreSubscriptionRequest,
+};
+use controller::responses::{StoreSubscriptionResponse, SubscriptionPaymentReceiptsResponse, SubscriptionPaymentSearchResponse};
+
+pub fn add_routes(route_parser: &mut RouteParser<Route>) {
+    route_parser.add_route(r"^/subscriptions$", || Route::Subscriptions);
+    route_parser.add_route_with_params(r"^/subscriptions/by-subscription-payment-id/(\d+)$", |params| {
+        param(&params, 0).map(|id| Route::SubscriptionBySubscriptionPaymentId { id })
+    });
+    route_parser.add_route(r"^/subscription/payment$", || Route::SubscriptionPayment);
+    route_parser.add_route(r"^/subscription/payment/search$", || Route::SubscriptionPaymentSearch);
+    route_parser.add_route(r"^/store_subscription$", || Route::StoreSubscription);
+    route_parser.add_route_with_params(r"^/stores/(\d+)/subscription-payments$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreSubscriptionPayments { store_id })
+    });
+    route_parser.add_route_with_params(r"^/store_subscription/by-store-id/(\d+)$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreSubscriptionByStoreId { store_id })
+    });
+    route_parser.add_route_with_params(r"^/store_subscription/by-store-id/(\d+)/trial/start$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreSubscriptionTrialStart { store_id })
+    });
+    route_parser.add_route_with_params(r"^/store_subscription/by-store-id/(\d+)/trial/extend$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreSubscriptionTrialExtend { store_id })
+    });
+    route_parser.add_route_with_params(r"^/store_subscription/by-store-id/(\d+)/trial/end$", |params| {
+        param(&params, 0).map(|store_id| Route::StoreSubscriptionTrialEnd { store_id })
+    });
+}
+
+pub fn route_specs() -> Vec<RouteSpec> {
+    vec![
+        RouteSpec::new(Method::Post, "/subscriptions").request::<CreateSubscriptionsRequest>(),
+        RouteSpec::new(Method::Post, "/subscriptions/by-subscription-payment-id/{id}").param("id", PathParamKind::Integer),
+        RouteSpec::new(Method::Post, "/subscription/payment"),
+        RouteSpec::new(Method::Post, "/subscription/payment/search")
+            .paginated()
+            .response::<SubscriptionPaymentSearchResponse>(),
+        RouteSpec::new(Method::Get, "/stores/{store_id}/subscription-payments")
+            .param("store_id", PathParamKind::Integer)
+            .paginated()
+            .response::<SubscriptionPaymentReceiptsResponse>(),
+        RouteSpec::new(Method::Get, "/store_subscription/by-store-id/{store_id}")
+            .param("store_id", PathParamKind::Integer)
+            .response::<Option<StoreSubscriptionResponse>>(),
+        RouteSpec::new(Method::Post, "/store_subscription/by-store-id/{store_id}")
+            .param("store_id", PathParamKind::Integer)
+            .request::<CreateStoreSubscriptionRequest>()
+            .response::<StoreSubscriptionResponse>(),
+        RouteSpec::new(Method::Put, "/store_subscription/by-store-id/{store_id}")
+            .param("store_id", PathParamKind::Integer)
+            .request::<UpdateStoreSubscriptionRequest>()
+            .response::<StoreSubscriptionResponse>(),
+        RouteSpec::new(Method::Post, "/store_subscription/by-store-id/{store_id}/trial/start")
+            .param("store_id", PathParamKind::Integer)
+            .response::<StoreSubscriptionResponse>(),
+        RouteSpec::new(Method::Post, "/store_subscription/by-store-id/{store_id}/trial/extend")
+            .param("store_id", PathParamKind::Integer)
+            .request::<ExtendTrialRequest>()
+            .response::<StoreSubscriptionResponse>(),
+        RouteSpec::new(Method::Post, "/store_subscription/by-store-id/{store_id}/trial/end")
+            .param("store_id", PathParamKind::Integer)
+            .response::<StoreSubscriptionResponse>(),
+    ]
+}
@@ -0,0 +1,53 @@
+//! Wraps the application to mark responses of the deprecated v1 API with
+//! `Deprecation` and `Sunset` headers
+use std::sync::Arc;
+
+use futures::Future;
+use hyper;
+use hyper::server::{Request, Response, Service};
+
+use stq_router::RouteParser;
+
+use super::routes::{resolve_route, ApiVersion, Route};
+
+pub struct VersionedApplication<S> {
+    inner: S,
+    route_parser: Arc<RouteParser<Route>>,
+    v1_sunset: Option<String>,
+}
+
+impl<S> VersionedApplication<S> {
+    pub fn new(inner: S, route_parser: Arc<RouteParser<Route>>, v1_sunset: Option<String>) -> Self {
+        Self {
+            inner,
+            route_parser,
+            v1_sunset,
+        }
+    }
+}
+
+impl<S> Service for VersionedApplication<S>
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error>,
+    S::Future: 'static,
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Request) -> Self::Future {
+        let is_v1 = resolve_route(&self.route_parser, req.path()).map(|(_, version)| version) == Some(ApiVersion::V1);
+        let v1_sunset = self.v1_sunset.clone();
+
+        Box::new(self.inner.call(req).map(move |mut response| {
+            if is_v1 {
+                response.headers_mut().set_raw("Deprecation", "true");
+                if let Some(sunset) = v1_sunset {
+                    response.headers_mut().set_raw("Sunset", sunset);
+                }
+            }
+            response
+        }))
+    }
+}
@@ -26,6 +26,10 @@ pub enum Error {
     InternalV2,
     #[fail(display = "Validation error (error handling v2)")]
     ValidateV2(serde_json::Value),
+    #[fail(display = "Payments gateway is unavailable")]
+    PaymentsGatewayUnavailable,
+    #[fail(display = "Exchange rates are unavailable")]
+    ExchangeRatesUnavailable,
 }
 
 impl From<services::Error> for Error {
@@ -35,6 +39,8 @@ impl From<services::Error> for Error {
             services::ErrorKind::Forbidden => Error::Forbidden,
             services::ErrorKind::NotFound => Error::NotFound,
             services::ErrorKind::Validation(value) => Error::ValidateV2(value),
+            services::ErrorKind::PaymentsGatewayUnavailable => Error::PaymentsGatewayUnavailable,
+            services::ErrorKind::ExchangeRatesUnavailable => Error::ExchangeRatesUnavailable,
         }
     }
 }
@@ -48,6 +54,7 @@ impl Codeable for Error {
             Error::Parse => StatusCode::BadRequest,
             Error::Connection | Error::HttpClient | Error::InternalV2 => StatusCode::InternalServerError,
             Error::Forbidden | Error::InvalidToken => StatusCode::Forbidden,
+            Error::PaymentsGatewayUnavailable | Error::ExchangeRatesUnavailable => StatusCode::ServiceUnavailable,
         }
     }
 }
@@ -33,6 +33,14 @@ pub enum ErrorSource {
 
 derive_error_impls!();
 
+impl From<DieselError> for Error {
+    fn from(e: DieselError) -> Self {
+        Error {
+            inner: ErrorKind::from(&e).into(),
+        }
+    }
+}
+
 impl<'a> From<&'a DieselError> for ErrorKind {
     fn from(_e: &DieselError) -> Self {
         ErrorKind::Internal
@@ -1,40 +1,68 @@
 use std::str::FromStr;
 
-use bigdecimal::BigDecimal;
-use chrono::Utc;
+use chrono::{Duration, NaiveDateTime, Utc};
 use diesel::{connection::AnsiTransactionManager, pg::Pg, Connection};
 use failure::Fail;
-use futures::{future, Future, IntoFuture};
+use futures::{future, stream, Future, IntoFuture, Stream};
+use hyper::header::ContentType;
+use hyper::{Headers, Method};
 use r2d2::ManageConnection;
+use serde_json;
 use stq_http::client::HttpClient;
 use stq_static_resources::OrderState;
 use stq_types::stripe::PaymentIntentId;
-use stripe::CaptureMethod;
+use stq_types::{BillingRole, InternationalBillingId, RussiaBillingId, StoreId as StqStoreId, UserId as StqUserId};
 use stripe::PaymentIntent as StripePaymentIntent;
+use stripe::{BalanceTransaction, CaptureMethod};
 use uuid::Uuid;
 
 use client::{
-    payments::{CreateExternalTransaction, CreateInternalTransaction, PaymentsClient},
-    saga::{OrderStateUpdate, SagaClient},
-    stores::{CurrencyExchangeInfo, StoresClient},
+    notifications::{
+        BillingInfoChangeEmail, FeeDunningEmail, NegativeStoreBalanceEmail, NotificationsClient, PaymentFailedEmail, UnpaidFeesAmount,
+    },
+    payments::{CreateExternalTransaction, CreateInternalTransaction, GetFees, PaymentsClient},
+    saga::{FeeStatementNotification, SagaClient, StoreBillingStatusNotification},
+    stores::StoresClient,
     stripe::StripeClient,
 };
 use models::{
     invoice_v2::{InvoiceId, InvoiceSetAmountPaid, PaymentFlow, RawInvoice},
-    order_v2::OrderId,
-    Account, AccountId, AccountWithBalance, Amount, CryptoWalletPayoutTarget, Currency, Event, EventPayload, PaymentState, Payout,
-    PayoutId, PayoutStatus, PayoutTarget,
+    order_v2::{OrderId, RawOrder, StoreId},
+    Account, AccountId, AccountWithBalance, Amount, AnalyticsEvent, AnalyticsEventType, BillingInfoChangeId, ChargeId,
+    CryptoWalletPayoutTarget, Currency, CustomerId, DunningAction, Event, EventPayload, EventPhase, FeeCryptoPaymentId, FeeDunningStatus,
+    FeeStatementId, FeeStatementSearch, InvoiceCallback, InvoiceCallbackEventType, InvoiceCallbackId, InvoiceCallbackNotification,
+    InvoiceReceipt, InvoiceRiskAssessment, NegativeStoreBalance, NegativeStoreBalanceId, NewInvoiceCallbackDelivery, OrderStateUpdate,
+    PaymentIntent, PaymentIntentCaptureDecision, PaymentIntentHistorySource, PaymentIntentStatus, PaymentRecoveryId, PaymentRecoveryStatus,
+    PaymentState, Payout, PayoutId, PayoutStatus, PayoutTarget, PayoutsByOrderIds, RiskReviewStatus, SetupIntent, StoreWebhook,
+    StoreWebhookId, StoreWebhookNotification, StripeFeeBackfillId, StripeFeeBackfillStatus, UpdateDbCustomer, UpdatePaymentIntent,
+    UpdatePaymentRecovery, UserId, UserWallet, WalletVerification, WalletVerificationId,
 };
-use repos::{ReposFactory, SearchPaymentIntent, SearchPaymentIntentInvoice};
+use repos::{ReposFactory, SearchCustomer, SearchPaymentIntent, SearchPaymentIntentInvoice};
 
 use services::accounts::AccountService;
-use services::payment_intent::cancel_payment_intent;
-use services::stripe::PaymentType;
+use services::analytics::{enqueue_analytics_event, fee_analytics_data, invoice_analytics_data, payout_analytics_data};
+use services::billing_info::BILLING_INFO_REENCRYPTION_BATCH_SIZE;
+use services::fee_crypto_payment::expire_fee_crypto_payment;
+use services::fee_dunning::{fee_dunning_suspension_at, store_unpaid_fees, take_fee_dunning_step};
+use services::invoice::{get_invoice_price, record_invoice_snapshot};
+use services::invoice_callback::{enqueue_invoice_callback_delivery, invoice_paid_callback_data};
+use services::order::decline_released_order;
+use services::payment_attempt::{record_payment_attempt, PaymentAttemptOutcome};
+use services::payment_intent::{cancel_payment_intent, record_payment_intent_status};
+use services::payment_recovery::{close_payment_recovery, record_payment_failure};
+use services::receipt::invoice_receipt_email;
+use services::saga::enqueue_order_state_updates;
+use services::store_webhook::{enqueue_fee_charged_webhooks, enqueue_order_paid_webhooks, enqueue_payout_completed_webhooks};
+use services::stripe::{update_payment_intent, PaymentType};
+use services::stripe_fee_backfill::{
+    allocate_stripe_fee, next_stripe_fee_backfill_batch, stripe_fee_backfill_progress, stripe_fee_for_order,
+};
+use services::user_credit::restore_invoice_credit;
 
 use super::error::*;
-use super::{spawn_on_pool, EventHandler, EventHandlerFuture};
+use super::{spawn_phase_on_pool, EventHandler, EventHandlerFuture};
 
-impl<T, M, F, HC, PC, SC, STC, STRC, AS> EventHandler<T, M, F, HC, PC, SC, STC, STRC, AS>
+impl<T, M, F, HC, PC, SC, STC, STRC, NC, AS> EventHandler<T, M, F, HC, PC, SC, STC, STRC, NC, AS>
 where
     T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
     M: ManageConnection<Connection = T>,
@@ -44,6 +72,7 @@ where
     SC: SagaClient + Clone,
     STC: StoresClient + Clone,
     STRC: StripeClient + Clone,
+    NC: NotificationsClient + Clone,
     AS: AccountService + Clone + 'static,
 {
     pub fn handle_event(self, event: Event) -> EventHandlerFuture<()> {
@@ -52,27 +81,640 @@ where
         match payload {
             EventPayload::NoOp => Box::new(future::ok(())),
             EventPayload::InvoicePaid { invoice_id } => self.handle_invoice_paid(invoice_id),
-            EventPayload::PaymentIntentPaymentFailed { payment_intent } => self.handle_payment_intent_payment_failed(payment_intent),
-            EventPayload::PaymentIntentAmountCapturableUpdated { payment_intent } => {
-                self.handle_payment_intent_succeeded_or_amount_capturable_updated(payment_intent)
-            }
-            EventPayload::PaymentIntentSucceeded { payment_intent } => {
-                self.handle_payment_intent_succeeded_or_amount_capturable_updated(payment_intent)
-            }
+            EventPayload::PaymentIntentPaymentFailed {
+                payment_intent,
+                event_created_at,
+            } => self.handle_payment_intent_payment_failed(payment_intent, event_created_at),
+            EventPayload::PaymentIntentAmountCapturableUpdated {
+                payment_intent,
+                event_created_at,
+            } => self.handle_payment_intent_succeeded_or_amount_capturable_updated(payment_intent, event_created_at),
+            EventPayload::PaymentIntentSucceeded {
+                payment_intent,
+                event_created_at,
+            } => self.handle_payment_intent_succeeded_or_amount_capturable_updated(payment_intent, event_created_at),
             EventPayload::PaymentIntentCapture { order_id } => self.handle_payment_intent_capture(order_id),
+            EventPayload::PaymentIntentCaptureTimeout { payment_intent_id } => {
+                self.handle_payment_intent_capture_timeout(payment_intent_id)
+            }
+            EventPayload::SetupIntentSucceeded { setup_intent } => self.handle_setup_intent_succeeded(setup_intent),
             EventPayload::PaymentExpired { invoice_id } => self.handle_payment_expired(invoice_id),
             EventPayload::PayoutInitiated { payout_id } => self.handle_payout_initiated(payout_id),
+            EventPayload::WalletVerificationInitiated { wallet_verification_id } => {
+                self.handle_wallet_verification_initiated(wallet_verification_id)
+            }
+            EventPayload::FeeStatementGenerated { fee_statement_id } => self.handle_fee_statement_generated(fee_statement_id),
+            EventPayload::StoreBillingStatusChanged { store_id } => self.handle_store_billing_status_changed(store_id),
+            EventPayload::StoreWebhookDelivery {
+                store_webhook_id,
+                notification,
+            } => self.handle_store_webhook_delivery(store_webhook_id, notification),
+            EventPayload::StripeFeeBackfillBatch { stripe_fee_backfill_id } => {
+                self.handle_stripe_fee_backfill_batch(stripe_fee_backfill_id)
+            }
+            EventPayload::InternationalBillingInfoReencryptionBatch { after_id } => {
+                self.handle_international_billing_info_reencryption_batch(after_id)
+            }
+            EventPayload::RussiaBillingInfoReencryptionBatch { after_id } => self.handle_russia_billing_info_reencryption_batch(after_id),
+            EventPayload::SagaOrderStatesUpdate { order_states } => self.handle_saga_order_states_update(order_states),
+            EventPayload::PaymentRecoveryNotification { payment_recovery_id } => {
+                self.handle_payment_recovery_notification(payment_recovery_id)
+            }
+            EventPayload::AnalyticsEventPublish { analytics_event } => self.handle_analytics_event_publish(analytics_event),
+            EventPayload::InvoiceCallbackDelivery {
+                invoice_callback_id,
+                notification,
+            } => self.handle_invoice_callback_delivery(invoice_callback_id, notification),
+            EventPayload::InvoiceReceiptNotification { invoice_id } => self.handle_invoice_receipt_notification(invoice_id),
+            EventPayload::FeeCryptoPaymentPaid { fee_crypto_payment_id } => self.handle_fee_crypto_payment_paid(fee_crypto_payment_id),
+            EventPayload::FeeCryptoPaymentExpired { fee_crypto_payment_id } => {
+                self.handle_fee_crypto_payment_expired(fee_crypto_payment_id)
+            }
+            EventPayload::NegativeStoreBalanceDetected { negative_store_balance_id } => {
+                self.handle_negative_store_balance_detected(negative_store_balance_id)
+            }
+            EventPayload::BillingInfoChangeNotification { billing_info_change_id } => {
+                self.handle_billing_info_change_notification(billing_info_change_id)
+            }
+            EventPayload::FeeDunningStep { store_id } => self.handle_fee_dunning_step(store_id),
+            EventPayload::FeeDunningNotification { store_id, action } => self.handle_fee_dunning_notification(store_id, action),
+        }
+    }
+
+    pub fn handle_analytics_event_publish(self, analytics_event: AnalyticsEvent) -> EventHandlerFuture<()> {
+        match self.analytics_publisher {
+            None => {
+                info!(
+                    "Analytics event publish handler: analytics sink is not configured, skipping {} event {}",
+                    analytics_event.event_type, analytics_event.id
+                );
+                Box::new(future::ok(()))
+            }
+            Some(analytics_publisher) => {
+                let analytics_event_id = analytics_event.id;
+                let fut = analytics_publisher
+                    .publish(analytics_event)
+                    .map_err(ectx!(ErrorKind::Internal => analytics_event_id));
+
+                Box::new(self.spans.time(EventPhase::ExternalCall, fut))
+            }
         }
     }
 
-    // TODO: handle this event properly
-    pub fn handle_payment_intent_payment_failed(self, _payment_intent: StripePaymentIntent) -> EventHandlerFuture<()> {
-        Box::new(future::ok(()))
+    pub fn handle_saga_order_states_update(self, order_states: Vec<OrderStateUpdate>) -> EventHandlerFuture<()> {
+        let fut = self
+            .saga_client
+            .update_order_states(order_states.clone())
+            .map_err(ectx!(ErrorKind::Internal => order_states));
+
+        Box::new(self.spans.time(EventPhase::ExternalCall, fut))
+    }
+
+    pub fn handle_fee_statement_generated(self, fee_statement_id: FeeStatementId) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            saga_client,
+            spans,
+            ..
+        } = self;
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool, cpu_pool, move |conn| {
+            let fee_statements_repo = repo_factory.create_fee_statements_repo_with_sys_acl(&conn);
+            let user_roles_repo = repo_factory.create_user_roles_repo_with_sys_acl(&conn);
+
+            let fee_statement = fee_statements_repo
+                .get(FeeStatementSearch::by_id(fee_statement_id))
+                .map_err(ectx!(try convert => fee_statement_id))?;
+
+            let fee_statement = match fee_statement {
+                None => {
+                    info!(
+                        "Fee statement generated handler: fee statement with ID {} not found",
+                        fee_statement_id
+                    );
+                    return Ok(None);
+                }
+                Some(fee_statement) => fee_statement,
+            };
+
+            let store_id = fee_statement.store_id;
+            let store_owner = user_roles_repo.get_by_store_id(store_id).map_err(ectx!(try convert => store_id))?;
+
+            let store_owner = match store_owner {
+                None => {
+                    info!(
+                        "Fee statement generated handler: owner of store {} not found, skipping notification for fee statement {}",
+                        store_id, fee_statement_id
+                    );
+                    return Ok(None);
+                }
+                Some(store_owner) => store_owner,
+            };
+
+            Ok(Some(FeeStatementNotification {
+                fee_statement_id,
+                store_id: StoreId::new(store_id.0),
+                store_owner_id: UserId::new(store_owner.user_id.0),
+                currency: fee_statement.currency,
+                period_start: fee_statement.period_start,
+                period_end: fee_statement.period_end,
+                total_amount: fee_statement.total_amount.to_super_unit(fee_statement.currency),
+            }))
+        })
+        .and_then(move |notification| match notification {
+            None => future::Either::A(future::ok(())),
+            Some(notification) => future::Either::B(
+                spans.time(
+                    EventPhase::ExternalCall,
+                    saga_client
+                        .notify_fee_statement_generated(notification.clone())
+                        .map_err(ectx!(ErrorKind::Internal => notification)),
+                ),
+            ),
+        });
+
+        Box::new(fut)
+    }
+
+    /// Notifies saga of the current status, so a notification that is retried late never brings back an outdated state
+    pub fn handle_store_billing_status_changed(self, store_id: StqStoreId) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            saga_client,
+            spans,
+            ..
+        } = self;
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool, cpu_pool, move |conn| {
+            let store_billing_statuses_repo = repo_factory.create_store_billing_statuses_repo_with_sys_acl(&conn);
+            let user_roles_repo = repo_factory.create_user_roles_repo_with_sys_acl(&conn);
+
+            let store_billing_status = store_billing_statuses_repo.get(store_id).map_err(ectx!(try convert => store_id))?;
+
+            let store_billing_status = match store_billing_status {
+                None => {
+                    info!(
+                        "Store billing status changed handler: billing status of store {} not found",
+                        store_id
+                    );
+                    return Ok(None);
+                }
+                Some(store_billing_status) => store_billing_status,
+            };
+
+            let store_owner = user_roles_repo.get_by_store_id(store_id).map_err(ectx!(try convert => store_id))?;
+
+            Ok(Some(StoreBillingStatusNotification {
+                store_id: StoreId::new(store_id.0),
+                store_owner_id: store_owner.map(|store_owner| UserId::new(store_owner.user_id.0)),
+                state: store_billing_status.state,
+                suspension_reason: store_billing_status.suspension_reason,
+                overdue_fees_count: store_billing_status.overdue_fees_count,
+                unpaid_since: store_billing_status.unpaid_since,
+                changed_at: store_billing_status.updated_at,
+            }))
+        })
+        .and_then(move |notification| match notification {
+            None => future::Either::A(future::ok(())),
+            Some(notification) => future::Either::B(
+                spans.time(
+                    EventPhase::ExternalCall,
+                    saga_client
+                        .notify_store_billing_status_changed(notification.clone())
+                        .map_err(ectx!(ErrorKind::Internal => notification)),
+                ),
+            ),
+        });
+
+        Box::new(fut)
+    }
+
+    /// Delivery failures fail the event, so the event store retries them
+    pub fn handle_store_webhook_delivery(
+        self,
+        store_webhook_id: StoreWebhookId,
+        notification: StoreWebhookNotification,
+    ) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            http_client,
+            spans,
+            ..
+        } = self;
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool, cpu_pool, move |conn| {
+            let store_webhooks_repo = repo_factory.create_store_webhooks_repo_with_sys_acl(&conn);
+
+            store_webhooks_repo
+                .get(store_webhook_id)
+                .map_err(ectx!(convert => store_webhook_id))
+        })
+        .and_then(move |store_webhook| match store_webhook {
+            None => {
+                info!(
+                    "Store webhook delivery handler: store webhook with ID {} not found, skipping {} notification",
+                    store_webhook_id, notification.event_type
+                );
+                future::Either::A(future::ok(()))
+            }
+            Some(store_webhook) => future::Either::B(spans.time(
+                EventPhase::ExternalCall,
+                deliver_store_webhook_notification(http_client, store_webhook, notification),
+            )),
+        });
+
+        Box::new(fut)
+    }
+
+    /// Every attempt is recorded in the delivery log of the callback, a failed one is retried by the event store
+    pub fn handle_invoice_callback_delivery(
+        self,
+        invoice_callback_id: InvoiceCallbackId,
+        notification: InvoiceCallbackNotification,
+    ) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            http_client,
+            spans,
+            ..
+        } = self;
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool.clone(), cpu_pool.clone(), {
+            let repo_factory = repo_factory.clone();
+            move |conn| {
+                let invoice_callbacks_repo = repo_factory.create_invoice_callbacks_repo_with_sys_acl(&conn);
+
+                invoice_callbacks_repo
+                    .get(invoice_callback_id)
+                    .map_err(ectx!(convert => invoice_callback_id))
+            }
+        })
+        .and_then(move |invoice_callback| match invoice_callback {
+            None => {
+                info!(
+                    "Invoice callback delivery handler: invoice callback with ID {} not found, skipping {} notification",
+                    invoice_callback_id, notification.event_type
+                );
+                future::Either::A(future::ok(()))
+            }
+            Some(invoice_callback) => {
+                let notification_id = notification.id;
+                let event_type = notification.event_type;
+
+                let delivery = deliver_invoice_callback_notification(http_client, invoice_callback, notification);
+                future::Either::B(spans.time(EventPhase::ExternalCall, delivery).then(move |result| {
+                    let new_delivery = NewInvoiceCallbackDelivery {
+                        invoice_callback_id,
+                        notification_id,
+                        event_type,
+                        error: result.as_ref().err().map(|e| e.to_string()),
+                    };
+
+                    spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+                        let invoice_callbacks_repo = repo_factory.create_invoice_callbacks_repo_with_sys_acl(&conn);
+
+                        invoice_callbacks_repo
+                            .add_delivery(new_delivery.clone())
+                            .map_err(ectx!(convert => new_delivery))
+                    })
+                    .then(move |logged| {
+                        if let Err(e) = logged {
+                            error!("Failed to record the delivery of invoice callback {}: {}", invoice_callback_id, e);
+                        }
+                        result
+                    })
+                }))
+            }
+        });
+
+        Box::new(fut)
+    }
+
+    /// Starts the recovery of a failed invoice payment, failed fee payments are only recorded
+    pub fn handle_payment_intent_payment_failed(
+        self,
+        payment_intent: StripePaymentIntent,
+        event_created_at: Option<NaiveDateTime>,
+    ) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            spans,
+            ..
+        } = self;
+
+        let payment_intent_id = PaymentIntentId(payment_intent.id.clone());
+        let payment_attempt_outcome = PaymentAttemptOutcome::from_payment_intent(&payment_intent);
+        let failure_message = payment_intent.last_payment_error.map(|err| format!("{:?}", err));
+        let status: PaymentIntentStatus = payment_intent.status.into();
+        let update_payment_intent = UpdatePaymentIntent {
+            status: Some(status.clone()),
+            last_payment_error_message: failure_message.clone(),
+            status_event_created_at: event_created_at,
+            ..Default::default()
+        };
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+            let payment_intent_repo = repo_factory.create_payment_intent_repo_with_sys_acl(&conn);
+            let payment_intent_history_repo = repo_factory.create_payment_intent_history_repo_with_sys_acl(&conn);
+            let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo_with_sys_acl(&conn);
+            let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
+            let payment_recoveries_repo = repo_factory.create_payment_recoveries_repo_with_sys_acl(&conn);
+            let payment_attempts_repo = repo_factory.create_payment_attempts_repo_with_sys_acl(&conn);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+            conn.transaction(|| {
+                let payment_intent = payment_intent_repo
+                    .get(SearchPaymentIntent::Id(payment_intent_id.clone()))
+                    .map_err(ectx!(try convert => payment_intent_id))?;
+
+                let payment_intent = match payment_intent {
+                    None => {
+                        info!(
+                            "Payment intent payment failed handler: payment intent with ID {} not found",
+                            payment_intent_id
+                        );
+                        return Ok(());
+                    }
+                    Some(payment_intent) => payment_intent,
+                };
+
+                // a failure delivered after a newer status is still an attempt to pay, but it must not reopen the payment
+                let is_stale = !payment_intent.accepts_webhook_status(&status, event_created_at);
+                if is_stale {
+                    info!(
+                        "Payment intent payment failed handler: payment intent {} is in status {:?}, ignoring stale status {:?}",
+                        payment_intent_id, payment_intent.status, status
+                    );
+                } else {
+                    let payment_intent = payment_intent_repo
+                        .update(payment_intent_id.clone(), update_payment_intent.clone())
+                        .map_err(ectx!(try convert => payment_intent_id, update_payment_intent))?;
+                    record_payment_intent_status(&*payment_intent_history_repo, &payment_intent, PaymentIntentHistorySource::Webhook)?;
+                }
+
+                let payment_intent_invoice = payment_intent_invoices_repo
+                    .get(SearchPaymentIntentInvoice::PaymentIntentId(payment_intent_id.clone()))
+                    .map_err(ectx!(try convert => payment_intent_id))?;
+
+                let invoice_id = match payment_intent_invoice {
+                    None => return Ok(()),
+                    Some(payment_intent_invoice) => payment_intent_invoice.invoice_id,
+                };
+
+                record_payment_attempt(
+                    &*payment_attempts_repo,
+                    invoice_id,
+                    payment_intent_id.clone(),
+                    payment_attempt_outcome.clone(),
+                )
+                .map_err(ectx!(try ErrorKind::Internal => invoice_id, payment_intent_id))?;
+
+                if is_stale {
+                    return Ok(());
+                }
+
+                let invoice = invoices_repo.get(invoice_id).map_err(ectx!(try convert => invoice_id))?.ok_or({
+                    let e = format_err!("Invoice {} not found", invoice_id);
+                    ectx!(try err e, ErrorKind::Internal)
+                })?;
+
+                if invoice.paid_at.is_some() || invoice.status != OrderState::PaymentAwaited {
+                    info!(
+                        "Payment intent payment failed handler: invoice {} is in status {:?}, skipping recovery",
+                        invoice_id, invoice.status
+                    );
+                    return Ok(());
+                }
+
+                record_payment_failure(
+                    &*payment_recoveries_repo,
+                    &*event_store_repo,
+                    &invoice,
+                    payment_intent_id.clone(),
+                    failure_message.clone(),
+                )
+                .map(|_| ())
+                .map_err(ectx!(ErrorKind::Internal => invoice_id, payment_intent_id))
+            })
+        });
+
+        Box::new(fut)
+    }
+
+    /// Notification failures fail the event, so the event store retries them
+    pub fn handle_payment_recovery_notification(self, payment_recovery_id: PaymentRecoveryId) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            notifications_client,
+            payment_recovery,
+            spans,
+            ..
+        } = self;
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool.clone(), cpu_pool.clone(), {
+            let repo_factory = repo_factory.clone();
+            move |conn| {
+                let payment_recoveries_repo = repo_factory.create_payment_recoveries_repo_with_sys_acl(&conn);
+                let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
+                let payment_intent_repo = repo_factory.create_payment_intent_repo_with_sys_acl(&conn);
+                let customers_repo = repo_factory.create_customers_repo_with_sys_acl(&conn);
+
+                let recovery = payment_recoveries_repo
+                    .get(payment_recovery_id)
+                    .map_err(ectx!(try convert => payment_recovery_id))?;
+
+                let recovery = match recovery {
+                    Some(ref recovery) if recovery.status == PaymentRecoveryStatus::Pending => recovery.clone(),
+                    _ => {
+                        info!(
+                            "Payment recovery notification handler: payment recovery {} is not pending, skipping notification",
+                            payment_recovery_id
+                        );
+                        return Ok(None);
+                    }
+                };
+
+                let invoice_id = recovery.invoice_id;
+                let invoice = invoices_repo.get(invoice_id).map_err(ectx!(try convert => invoice_id))?.ok_or({
+                    let e = format_err!("Invoice {} not found", invoice_id);
+                    ectx!(try err e, ErrorKind::Internal)
+                })?;
+
+                let payment_intent_id = recovery.failed_payment_intent_id.clone();
+                let payment_intent = payment_intent_repo
+                    .get(SearchPaymentIntent::Id(payment_intent_id.clone()))
+                    .map_err(ectx!(try convert => payment_intent_id))?
+                    .ok_or({
+                        let e = format_err!("Payment intent {} not found", payment_intent_id);
+                        ectx!(try err e, ErrorKind::Internal)
+                    })?;
+
+                let buyer_user_id = StqUserId(recovery.buyer_user_id.inner());
+                let email = customers_repo
+                    .get(SearchCustomer::UserId(buyer_user_id))
+                    .map_err(ectx!(try convert => buyer_user_id))?
+                    .and_then(|customer| customer.email)
+                    .or(payment_intent.receipt_email);
+
+                Ok(Some(PaymentFailedEmail {
+                    payment_recovery_id,
+                    user_id: recovery.buyer_user_id,
+                    email,
+                    invoice_id,
+                    currency: invoice.buyer_currency,
+                    amount: payment_intent.amount.to_super_unit(invoice.buyer_currency),
+                    failure_message: recovery.failure_message,
+                    retry_url: format!("{}/{}", payment_recovery.retry_url.trim_end_matches('/'), invoice_id),
+                }))
+            }
+        })
+        .and_then(move |email| match email {
+            None => future::Either::A(future::ok(())),
+            Some(email) => future::Either::B(
+                spans
+                    .time(
+                        EventPhase::ExternalCall,
+                        notifications_client
+                            .send_payment_failed_email(email.clone())
+                            .map_err(ectx!(ErrorKind::Internal => email)),
+                    )
+                    .and_then(move |_| {
+                        spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+                            let payment_recoveries_repo = repo_factory.create_payment_recoveries_repo_with_sys_acl(&conn);
+
+                            let update = UpdatePaymentRecovery {
+                                status: Some(PaymentRecoveryStatus::Notified),
+                                notified_at: Some(Some(Utc::now().naive_utc())),
+                                ..Default::default()
+                            };
+                            payment_recoveries_repo
+                                .update(payment_recovery_id, update.clone())
+                                .map(|_| ())
+                                .map_err(ectx!(convert => payment_recovery_id, update))
+                        })
+                    }),
+            ),
+        });
+
+        Box::new(fut)
+    }
+
+    /// Sends the receipt of a paid crypto invoice to the buyer. The account may have been unlinked from the invoice by now,
+    /// so the wallet address is taken from its assignment. Notification failures fail the event, so the event store retries them
+    pub fn handle_invoice_receipt_notification(self, invoice_id: InvoiceId) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            notifications_client,
+            receipts,
+            spans,
+            ..
+        } = self;
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool, cpu_pool, move |conn| {
+            let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
+            let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+            let rates_repo = repo_factory.create_order_exchange_rates_repo_with_sys_acl(&conn);
+            let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
+            let account_assignments_repo = repo_factory.create_account_assignments_repo_with_sys_acl(&conn);
+            let customers_repo = repo_factory.create_customers_repo_with_sys_acl(&conn);
+            let invoice_snapshots_repo = repo_factory.create_invoice_snapshots_repo_with_sys_acl(&conn);
+            let order_line_items_repo = repo_factory.create_order_line_items_repo_with_sys_acl(&conn);
+            let invoice_buyer_details_repo = repo_factory.create_invoice_buyer_details_repo_with_sys_acl(&conn);
+
+            let invoice = invoices_repo.get(invoice_id).map_err(ectx!(try convert => invoice_id))?.ok_or({
+                let e = format_err!("Invoice {} not found", invoice_id);
+                ectx!(try err e, ErrorKind::Internal)
+            })?;
+
+            if invoice.paid_at.is_none() {
+                info!(
+                    "Invoice receipt notification handler: invoice {} is not paid, skipping notification",
+                    invoice_id
+                );
+                return Ok(None);
+            }
+
+            if let PaymentFlow::Fiat = invoice.payment_flow() {
+                info!(
+                    "Invoice receipt notification handler: invoice {} is paid in fiat, skipping notification",
+                    invoice_id
+                );
+                return Ok(None);
+            }
+
+            let buyer_user_id = invoice.buyer_user_id;
+            let amounts_received = invoices_repo
+                .list_amounts_received(invoice_id)
+                .map_err(ectx!(try convert => invoice_id))?;
+
+            let wallet_address = match account_assignments_repo
+                .get_by_invoice_id(invoice_id)
+                .map_err(ectx!(try convert => invoice_id))?
+            {
+                None => None,
+                Some(assignment) => {
+                    let account_id = assignment.account_id;
+                    accounts_repo
+                        .get(account_id)
+                        .map_err(ectx!(try convert => account_id))?
+                        .map(|account| account.wallet_address)
+                }
+            };
+
+            let invoice = get_invoice_price(&*orders_repo, &*rates_repo, &*accounts_repo, &*invoice_snapshots_repo, invoice)
+                .map_err(ectx!(try ErrorKind::Internal => invoice_id))?;
+
+            let email = customers_repo
+                .get(SearchCustomer::UserId(StqUserId(buyer_user_id.inner())))
+                .map_err(ectx!(try convert => buyer_user_id))?
+                .and_then(|customer| customer.email);
+
+            let order_ids: Vec<_> = invoice.orders.iter().map(|order| order.id).collect();
+            let line_items = order_line_items_repo
+                .get_by_order_ids(&order_ids)
+                .map_err(ectx!(try convert => order_ids))?;
+
+            let buyer_billing_details = invoice_buyer_details_repo
+                .get(invoice_id)
+                .map_err(ectx!(try convert => invoice_id))?
+                .map(|buyer_details| buyer_details.details);
+
+            let receipt = InvoiceReceipt::new(invoice, wallet_address, buyer_billing_details, amounts_received, &line_items);
+            Ok(Some(invoice_receipt_email(&receipts, buyer_user_id, email, receipt)))
+        })
+        .and_then(move |email| match email {
+            None => future::Either::A(future::ok(())),
+            Some(email) => future::Either::B(
+                spans.time(
+                    EventPhase::ExternalCall,
+                    notifications_client
+                        .send_invoice_receipt_email(email.clone())
+                        .map_err(ectx!(ErrorKind::Internal => email)),
+                ),
+            ),
+        });
+
+        Box::new(fut)
     }
 
     pub fn handle_payment_intent_succeeded_or_amount_capturable_updated(
         self,
         payment_intent: StripePaymentIntent,
+        event_created_at: Option<NaiveDateTime>,
     ) -> EventHandlerFuture<()> {
         if payment_intent.capture_method == CaptureMethod::Manual && payment_intent.amount != payment_intent.amount_capturable {
             info!(
@@ -82,29 +724,40 @@ where
             return Box::new(future::ok(()));
         }
 
-        let saga_client = self.saga_client.clone();
         let fee_config = self.fee.clone();
 
         let amount_paid = payment_intent.amount.clone();
         let payment_intent_id = PaymentIntentId(payment_intent.id.clone());
-        let payment_intent_id_cloned = payment_intent_id.clone();
+        // approved orders are captured at the latest when the timeout expires, see `handle_payment_intent_capture`
+        let capture_timeout = if payment_intent.capture_method == CaptureMethod::Manual {
+            Some((
+                payment_intent_id.clone(),
+                Duration::minutes(self.payment_capture.timeout_min as i64),
+            ))
+        } else {
+            None
+        };
         let new_status = OrderState::Paid;
+        let analytics_enabled = self.analytics_publisher.is_some();
 
         let EventHandler {
             db_pool,
             cpu_pool,
             repo_factory,
+            spans,
             ..
         } = self;
 
-        let fut = spawn_on_pool(db_pool.clone(), cpu_pool.clone(), {
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool.clone(), cpu_pool.clone(), {
             let repo_factory = repo_factory.clone();
             move |conn| {
                 let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
                 let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
                 let payment_intent_repo = repo_factory.create_payment_intent_repo_with_sys_acl(&conn);
+                let payment_intent_history_repo = repo_factory.create_payment_intent_history_repo_with_sys_acl(&conn);
                 let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo_with_sys_acl(&conn);
                 let payment_intent_fees_repo = repo_factory.create_payment_intent_fees_repo_with_sys_acl(&conn);
+                let payment_attempts_repo = repo_factory.create_payment_attempts_repo_with_sys_acl(&conn);
                 let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
 
                 crate::services::stripe::payment_intent_succeeded_or_amount_capturable_updated(
@@ -112,11 +765,14 @@ where
                     &*orders_repo,
                     &*invoices_repo,
                     &*payment_intent_repo,
+                    &*payment_intent_history_repo,
                     &*payment_intent_invoices_repo,
                     &*payment_intent_fees_repo,
+                    &*payment_attempts_repo,
                     &*fees_repo,
                     fee_config,
                     payment_intent,
+                    event_created_at,
                 )
                 .map_err(ectx!(ErrorKind::Internal => payment_intent_id))
                 .map(Some)
@@ -126,41 +782,125 @@ where
             let db_pool = db_pool.clone();
             let cpu_pool = cpu_pool.clone();
             let repo_factory = repo_factory.clone();
-            move |payment_type| match payment_type {
-                Some(PaymentType::Invoice { invoice, orders, .. }) => {
-                    let order_state_updates = orders
-                        .into_iter()
-                        .map(|order| OrderStateUpdate {
-                            order_id: order.id,
-                            store_id: order.store_id,
-                            customer_id: invoice.buyer_user_id,
-                            status: new_status,
-                        })
-                        .collect();
-
-                    let saga_update_states = saga_client
-                        .update_order_states(order_state_updates)
-                        .map_err(ectx!(ErrorKind::Internal => payment_intent_id_cloned));
-
-                    let set_invoice_paid = spawn_on_pool(db_pool, cpu_pool, move |conn| {
-                        let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
-
-                        let invoice_set_amount_paid = InvoiceSetAmountPaid {
-                            final_amount_paid: Amount::new(amount_paid as u128),
-                            final_cashback_amount: Amount::new(0u128),
-                            paid_at: Utc::now().naive_utc(),
-                        };
-
-                        let invoice_id = invoice.id.clone();
-                        invoices_repo
-                            .set_amount_paid_fiat(invoice_id.clone(), invoice_set_amount_paid.clone())
-                            .map_err(ectx!(convert => invoice_id, invoice_set_amount_paid))
-                    });
-
-                    future::Either::A(saga_update_states.and_then(|_| set_invoice_paid).map(|_| ()))
+            move |payment_type| -> EventHandlerFuture<()> {
+                match payment_type {
+                    Some(PaymentType::Invoice { invoice, orders, .. }) => {
+                        let order_state_updates = orders
+                            .iter()
+                            .map(|order| OrderStateUpdate {
+                                order_id: order.id,
+                                store_id: order.store_id,
+                                customer_id: invoice.buyer_user_id,
+                                status: new_status,
+                                metadata: invoice.metadata.clone(),
+                            })
+                            .collect();
+
+                        Box::new(spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+                            let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
+                            let store_webhooks_repo = repo_factory.create_store_webhooks_repo_with_sys_acl(&conn);
+                            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+                            let payment_recoveries_repo = repo_factory.create_payment_recoveries_repo_with_sys_acl(&conn);
+                            let invoice_callbacks_repo = repo_factory.create_invoice_callbacks_repo_with_sys_acl(&conn);
+                            let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                            let rates_repo = repo_factory.create_order_exchange_rates_repo_with_sys_acl(&conn);
+                            let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
+                            let invoice_snapshots_repo = repo_factory.create_invoice_snapshots_repo_with_sys_acl(&conn);
+
+                            let invoice_set_amount_paid = InvoiceSetAmountPaid {
+                                final_amount_paid: Amount::new(amount_paid as u128),
+                                final_cashback_amount: Amount::new(0u128),
+                                paid_at: Utc::now().naive_utc(),
+                            };
+
+                            let invoice_id = invoice.id.clone();
+                            conn.transaction(|| {
+                                // waits for a recalculation of the invoice that is in progress
+                                invoices_repo
+                                    .lock_for_recalculation(invoice_id)
+                                    .map_err(ectx!(try convert => invoice_id))?;
+                                let paid_at = invoice_set_amount_paid.paid_at;
+                                let paid_invoice = invoices_repo
+                                    .set_amount_paid_fiat(invoice_id.clone(), invoice_set_amount_paid.clone())
+                                    .map_err(ectx!(try convert => invoice_id, invoice_set_amount_paid))?;
+
+                                let invoice_dump =
+                                    get_invoice_price(&*orders_repo, &*rates_repo, &*accounts_repo, &*invoice_snapshots_repo, paid_invoice)
+                                        .map_err(ectx!(try ErrorKind::Internal => invoice_id))?;
+                                record_invoice_snapshot(&*invoice_snapshots_repo, &invoice_dump, paid_at)
+                                    .map_err(ectx!(try ErrorKind::Internal => invoice_id))?;
+
+                                enqueue_order_state_updates(&*event_store_repo, order_state_updates)
+                                    .map_err(ectx!(try ErrorKind::Internal => invoice_id))?;
+
+                                enqueue_order_paid_webhooks(&*store_webhooks_repo, &*event_store_repo, &orders)
+                                    .map_err(ectx!(try ErrorKind::Internal => invoice_id))?;
+
+                                enqueue_invoice_callback_delivery(
+                                    &*invoice_callbacks_repo,
+                                    &*event_store_repo,
+                                    invoice_id,
+                                    InvoiceCallbackEventType::InvoicePaid,
+                                    invoice_paid_callback_data(&orders),
+                                )
+                                .map_err(ectx!(try ErrorKind::Internal => invoice_id))?;
+
+                                if let Some((payment_intent_id, timeout)) = capture_timeout {
+                                    let event = Event::new(EventPayload::PaymentIntentCaptureTimeout { payment_intent_id });
+                                    let scheduled_on = Utc::now().naive_utc() + timeout;
+                                    event_store_repo
+                                        .add_scheduled_event(event.clone(), scheduled_on)
+                                        .map_err(ectx!(try convert => event))?;
+                                }
+
+                                if analytics_enabled {
+                                    enqueue_analytics_event(
+                                        &*event_store_repo,
+                                        AnalyticsEventType::InvoicePaid,
+                                        invoice_analytics_data(invoice_id, &orders),
+                                    )
+                                    .map_err(ectx!(try ErrorKind::Internal => invoice_id))?;
+                                }
+
+                                close_payment_recovery(&*payment_recoveries_repo, invoice_id, PaymentRecoveryStatus::Recovered)
+                                    .map_err(ectx!(ErrorKind::Internal => invoice_id))
+                            })
+                        }))
+                    }
+                    Some(PaymentType::Fee { fee }) => {
+                        Box::new(spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+                            let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                            let store_webhooks_repo = repo_factory.create_store_webhooks_repo_with_sys_acl(&conn);
+                            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+                            let order_id = fee.order_id;
+                            let order = orders_repo.get(order_id).map_err(ectx!(try convert => order_id))?.ok_or({
+                                let e = format_err!("Order {} not found", order_id);
+                                ectx!(try err e, ErrorKind::Internal)
+                            })?;
+
+                            conn.transaction(|| {
+                                if analytics_enabled {
+                                    enqueue_analytics_event(
+                                        &*event_store_repo,
+                                        AnalyticsEventType::FeeCharged,
+                                        fee_analytics_data(&fee, order.store_id),
+                                    )
+                                    .map_err(ectx!(try ErrorKind::Internal => order_id))?;
+                                }
+
+                                enqueue_fee_charged_webhooks(
+                                    &*store_webhooks_repo,
+                                    &*event_store_repo,
+                                    StqStoreId(order.store_id.inner()),
+                                    vec![fee],
+                                )
+                                .map_err(ectx!(ErrorKind::Internal => order_id))
+                            })
+                        }))
+                    }
+                    None => Box::new(future::ok(())),
                 }
-                Some(PaymentType::Fee) => future::Either::B(future::ok(())),
-                None => future::Either::B(future::ok(())),
             }
         });
 
@@ -180,27 +920,400 @@ where
                             let self_ = self.clone();
                             move |_| self_.set_orders_status(invoice_id.clone(), OrderState::Paid)
                         })
-                        .and_then(move |_| self.create_fee_for_orders(invoice_id)),
+                        .and_then({
+                            let self_ = self.clone();
+                            move |_| self_.create_fee_for_orders(invoice_id)
+                        })
+                        .and_then(move |_| self.notify_of_paid_orders(invoice_id)),
                 )
             });
 
         Box::new(fut)
     }
 
-    pub fn handle_payment_expired(self, invoice_id: InvoiceId) -> EventHandlerFuture<()> {
-        let fut = self.clone().get_invoice(invoice_id).and_then(move |invoice| match invoice.paid_at {
-            Some(_) => future::Either::A(future::ok(())), // do nothing if the invoice has already been paid
-            None => future::Either::B(future::lazy(move || self.process_payment_expired(invoice))),
+    /// Moves the fee paid to the deposit address to the main account
+    pub fn handle_fee_crypto_payment_paid(self, fee_crypto_payment_id: FeeCryptoPaymentId) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            spans,
+            ..
+        } = self.clone();
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool, cpu_pool, move |conn| {
+            let fee_crypto_payments_repo = repo_factory.create_fee_crypto_payments_repo_with_sys_acl(&conn);
+            fee_crypto_payments_repo
+                .get(fee_crypto_payment_id)
+                .map_err(ectx!(try convert => fee_crypto_payment_id))?
+                .ok_or({
+                    let e = format_err!("Fee crypto payment {} not found", fee_crypto_payment_id);
+                    ectx!(err e, ErrorKind::Internal)
+                })
+        })
+        .and_then(move |payment| {
+            self.clone()
+                .get_ture_context()
+                .into_future()
+                .and_then(move |(payments_client, account_service)| {
+                    self.drain_account(payments_client, account_service, payment.account_id)
+                })
         });
 
         Box::new(fut)
     }
 
-    fn process_payment_expired(self, invoice: RawInvoice) -> EventHandlerFuture<()> {
-        let db_pool = self.db_pool.clone();
-        let cpu_pool = self.cpu_pool.clone();
-        let stripe_client = self.stripe_client.clone();
-        let repo_factory = self.repo_factory.clone();
+    /// Expires the fee payment if the required amount has not arrived in time, the fee stays unpaid
+    pub fn handle_fee_crypto_payment_expired(self, fee_crypto_payment_id: FeeCryptoPaymentId) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            spans,
+            ..
+        } = self.clone();
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+            let fee_crypto_payments_repo = repo_factory.create_fee_crypto_payments_repo_with_sys_acl(&conn);
+            expire_fee_crypto_payment(&*fee_crypto_payments_repo, fee_crypto_payment_id)
+                .map_err(ectx!(ErrorKind::Internal => fee_crypto_payment_id))
+        })
+        .and_then(move |payment| match payment {
+            // the payment has been paid
+            None => future::Either::A(future::ok(())),
+            Some(ref payment) if payment.amount_received == Amount::zero() => future::Either::A(future::ok(())),
+            // a partial payment is not credited to the fee, it is refunded to the store manually
+            Some(payment) => {
+                warn!(
+                    "Fee crypto payment {} of fee {} expired with {} of {} {} received, the amount received is to be refunded",
+                    payment.id, payment.fee_id, payment.amount_received, payment.amount, payment.currency
+                );
+                future::Either::B(
+                    self.clone()
+                        .get_ture_context()
+                        .into_future()
+                        .and_then(move |(payments_client, account_service)| {
+                            self.drain_account(payments_client, account_service, payment.account_id)
+                        }),
+                )
+            }
+        });
+
+        Box::new(fut)
+    }
+
+    /// Alerts every financial manager of a negative store balance. A balance that has recovered by now
+    /// or whose alert has been sent already is skipped
+    pub fn handle_negative_store_balance_detected(self, negative_store_balance_id: NegativeStoreBalanceId) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            notifications_client,
+            spans,
+            ..
+        } = self;
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool.clone(), cpu_pool.clone(), {
+            let repo_factory = repo_factory.clone();
+            move |conn| {
+                let negative_store_balances_repo = repo_factory.create_negative_store_balances_repo_with_sys_acl(&conn);
+                let user_roles_repo = repo_factory.create_user_roles_repo_with_sys_acl(&conn);
+
+                let negative_store_balance = negative_store_balances_repo
+                    .get(negative_store_balance_id)
+                    .map_err(ectx!(try convert => negative_store_balance_id))?;
+
+                let negative_store_balance = match negative_store_balance {
+                    Some(ref balance) if balance.resolved_at.is_none() && balance.notified_at.is_none() => balance.clone(),
+                    _ => {
+                        info!(
+                            "Negative store balance detected handler: negative balance {} is resolved or notified, skipping notification",
+                            negative_store_balance_id
+                        );
+                        return Ok(None);
+                    }
+                };
+
+                let financial_managers = user_roles_repo
+                    .list_user_ids_by_role(BillingRole::FinancialManager)
+                    .map_err(ectx!(try convert))?;
+
+                if financial_managers.is_empty() {
+                    warn!(
+                        "Negative store balance detected handler: no financial managers to notify of negative balance {}",
+                        negative_store_balance_id
+                    );
+                    return Ok(None);
+                }
+
+                let NegativeStoreBalance {
+                    store_id,
+                    currency,
+                    amount,
+                    detected_at,
+                    ..
+                } = negative_store_balance;
+
+                Ok(Some(
+                    financial_managers
+                        .into_iter()
+                        .map(|user_id| NegativeStoreBalanceEmail {
+                            negative_store_balance_id,
+                            user_id: UserId::new(user_id.0),
+                            store_id,
+                            currency,
+                            amount: amount.to_super_unit(currency),
+                            detected_at,
+                        })
+                        .collect::<Vec<_>>(),
+                ))
+            }
+        })
+        .and_then(move |emails| match emails {
+            None => future::Either::A(future::ok(())),
+            Some(emails) => future::Either::B(
+                spans
+                    .time(
+                        EventPhase::ExternalCall,
+                        future::join_all(emails.into_iter().map(move |email| {
+                            notifications_client
+                                .send_negative_store_balance_email(email.clone())
+                                .map_err(ectx!(ErrorKind::Internal => email))
+                        })),
+                    )
+                    .and_then(move |_| {
+                        spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+                            let negative_store_balances_repo = repo_factory.create_negative_store_balances_repo_with_sys_acl(&conn);
+
+                            negative_store_balances_repo
+                                .set_notified(negative_store_balance_id)
+                                .map(|_| ())
+                                .map_err(ectx!(convert => negative_store_balance_id))
+                        })
+                    }),
+            ),
+        });
+
+        Box::new(fut)
+    }
+
+    /// Notifies the store owner of a requested or reviewed change of the store billing info,
+    /// so that a change made from a hijacked account does not go unnoticed
+    pub fn handle_billing_info_change_notification(self, billing_info_change_id: BillingInfoChangeId) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            notifications_client,
+            spans,
+            ..
+        } = self;
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool, cpu_pool, move |conn| {
+            let billing_info_changes_repo = repo_factory.create_billing_info_changes_repo_with_sys_acl(&conn);
+            let user_roles_repo = repo_factory.create_user_roles_repo_with_sys_acl(&conn);
+
+            let change = billing_info_changes_repo
+                .get(billing_info_change_id)
+                .map_err(ectx!(try convert => billing_info_change_id))?;
+
+            let change = match change {
+                None => {
+                    info!(
+                        "Billing info change notification handler: billing info change with ID {} not found",
+                        billing_info_change_id
+                    );
+                    return Ok(None);
+                }
+                Some(change) => change,
+            };
+
+            let store_id = change.store_id;
+            let store_owner = user_roles_repo.get_by_store_id(store_id).map_err(ectx!(try convert => store_id))?;
+
+            let store_owner = match store_owner {
+                None => {
+                    warn!(
+                        "Billing info change notification handler: owner of store {} not found, skipping notification for billing info change {}",
+                        store_id, billing_info_change_id
+                    );
+                    return Ok(None);
+                }
+                Some(store_owner) => store_owner,
+            };
+
+            Ok(Some(BillingInfoChangeEmail {
+                billing_info_change_id,
+                user_id: UserId::new(store_owner.user_id.0),
+                store_id: StoreId::new(store_id.0),
+                status: change.status,
+                requested_by: UserId::new(change.requested_by.0),
+                requested_at: change.created_at,
+                rejection_reason: change.rejection_reason,
+            }))
+        })
+        .and_then(move |email| match email {
+            None => future::Either::A(future::ok(())),
+            Some(email) => future::Either::B(
+                spans.time(
+                    EventPhase::ExternalCall,
+                    notifications_client
+                        .send_billing_info_change_email(email.clone())
+                        .map_err(ectx!(ErrorKind::Internal => email)),
+                ),
+            ),
+        });
+
+        Box::new(fut)
+    }
+
+    pub fn handle_fee_dunning_step(self, store_id: StqStoreId) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            fee_dunning,
+            spans,
+            ..
+        } = self;
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+            let fee_dunnings_repo = repo_factory.create_fee_dunnings_repo_with_sys_acl(&conn);
+            let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+            let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+            let store_billing_statuses_repo = repo_factory.create_store_billing_statuses_repo_with_sys_acl(&conn);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+            conn.transaction(|| {
+                take_fee_dunning_step(
+                    &*fee_dunnings_repo,
+                    &*fees_repo,
+                    &*orders_repo,
+                    &*store_billing_statuses_repo,
+                    &*event_store_repo,
+                    &fee_dunning,
+                    store_id,
+                    Utc::now().naive_utc(),
+                )
+                .map(|_| ())
+                .map_err(ectx!(ErrorKind::Internal => store_id))
+            })
+        });
+
+        Box::new(fut)
+    }
+
+    /// Emails the store owner of a step of the fee dunning. A dunning resolved in the meantime is not notified of
+    pub fn handle_fee_dunning_notification(self, store_id: StqStoreId, action: DunningAction) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            notifications_client,
+            fee_dunning,
+            spans,
+            ..
+        } = self;
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool, cpu_pool, move |conn| {
+            let fee_dunnings_repo = repo_factory.create_fee_dunnings_repo_with_sys_acl(&conn);
+            let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+            let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+            let user_roles_repo = repo_factory.create_user_roles_repo_with_sys_acl(&conn);
+
+            let dunning = fee_dunnings_repo.get(store_id).map_err(ectx!(try convert => store_id))?;
+            let dunning = match dunning {
+                Some(ref dunning) if dunning.status == FeeDunningStatus::Open => dunning.clone(),
+                _ => {
+                    info!(
+                        "Fee dunning notification handler: fee dunning of store {} is not open, skipping {} notification",
+                        store_id, action
+                    );
+                    return Ok(None);
+                }
+            };
+
+            let store_owner = user_roles_repo.get_by_store_id(store_id).map_err(ectx!(try convert => store_id))?;
+            let store_owner = match store_owner {
+                None => {
+                    warn!(
+                        "Fee dunning notification handler: owner of store {} not found, skipping {} notification",
+                        store_id, action
+                    );
+                    return Ok(None);
+                }
+                Some(store_owner) => store_owner,
+            };
+
+            let unpaid_fees =
+                store_unpaid_fees(&*fees_repo, &*orders_repo, store_id).map_err(ectx!(try ErrorKind::Internal => store_id))?;
+            if unpaid_fees.is_empty() {
+                return Ok(None);
+            }
+
+            let mut unpaid_amounts: Vec<UnpaidFeesAmount> = Vec::new();
+            for fee in &unpaid_fees {
+                let amount = fee.amount.to_super_unit(fee.currency);
+                match unpaid_amounts
+                    .iter_mut()
+                    .find(|unpaid_amount| unpaid_amount.currency == fee.currency)
+                {
+                    Some(unpaid_amount) => unpaid_amount.amount = unpaid_amount.amount.clone() + amount,
+                    None => unpaid_amounts.push(UnpaidFeesAmount {
+                        currency: fee.currency,
+                        amount,
+                    }),
+                }
+            }
+
+            Ok(Some(FeeDunningEmail {
+                user_id: UserId::new(store_owner.user_id.0),
+                store_id: StoreId::new(store_id.0),
+                action,
+                unpaid_fees_count: unpaid_fees.len(),
+                unpaid_amounts,
+                unpaid_since: dunning.started_at,
+                suspension_at: fee_dunning_suspension_at(&fee_dunning, &dunning),
+            }))
+        })
+        .and_then(move |email| match email {
+            None => future::Either::A(future::ok(())),
+            Some(email) => future::Either::B(
+                spans.time(
+                    EventPhase::ExternalCall,
+                    notifications_client
+                        .send_fee_dunning_email(email.clone())
+                        .map_err(ectx!(ErrorKind::Internal => email)),
+                ),
+            ),
+        });
+
+        Box::new(fut)
+    }
+
+    pub fn handle_payment_expired(self, invoice_id: InvoiceId) -> EventHandlerFuture<()> {
+        let fut = self.clone().get_invoice(invoice_id).and_then(move |invoice| {
+            // do nothing if the invoice has already been paid or the buyer has cancelled it
+            if invoice.paid_at.is_some() || invoice.status == OrderState::Cancelled {
+                future::Either::A(future::ok(()))
+            } else {
+                future::Either::B(future::lazy(move || self.process_payment_expired(invoice)))
+            }
+        });
+
+        Box::new(fut)
+    }
+
+    fn process_payment_expired(self, invoice: RawInvoice) -> EventHandlerFuture<()> {
+        let invoice_id = invoice.id;
+        let buyer_currency = invoice.buyer_currency;
+        let self_ = self.clone();
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+        let stripe_client = self.stripe_client.clone();
+        let repo_factory = self.repo_factory.clone();
+        let spans = self.spans.clone();
 
         let fut = match invoice.payment_flow() {
             PaymentFlow::Crypto => future::Either::A(future::lazy(move || {
@@ -217,14 +1330,96 @@ where
                     .and_then(move |_| self.set_orders_status(invoice.id.clone(), OrderState::AmountExpired))
             })),
             PaymentFlow::Fiat => future::Either::B(future::lazy(move || {
-                self.set_orders_status(invoice.id.clone(), OrderState::AmountExpired)
+                let invoice_id = invoice.id;
+                self.set_orders_status(invoice_id, OrderState::AmountExpired)
+                    .and_then({
+                        let db_pool = db_pool.clone();
+                        let cpu_pool = cpu_pool.clone();
+                        let repo_factory = repo_factory.clone();
+                        let spans = spans.clone();
+                        move |_| {
+                            let cancellation = cancel_payment_intent(db_pool, cpu_pool, stripe_client, repo_factory, invoice_id)
+                                .map_err(ectx!(ErrorKind::Internal => invoice_id));
+                            spans.time(EventPhase::ExternalCall, cancellation)
+                        }
+                    })
                     .and_then(move |_| {
-                        cancel_payment_intent(db_pool, cpu_pool, stripe_client, repo_factory, invoice.id.clone())
-                            .map_err(ectx!(ErrorKind::Internal => invoice.id))
+                        spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+                            let payment_recoveries_repo = repo_factory.create_payment_recoveries_repo_with_sys_acl(&conn);
+
+                            close_payment_recovery(&*payment_recoveries_repo, invoice_id, PaymentRecoveryStatus::Expired)
+                                .map_err(ectx!(ErrorKind::Internal => invoice_id))
+                        })
                     })
             })),
         }
-        .map(|_| ());
+        .and_then({
+            let self_ = self_.clone();
+            move |_| self_.restore_credit_of_expired_invoice(invoice_id)
+        })
+        .and_then(move |_| self_.notify_of_expired_invoice(invoice_id, buyer_currency));
+
+        Box::new(fut)
+    }
+
+    /// Gives the credit the buyer applied to the expired invoice back
+    fn restore_credit_of_expired_invoice(self, invoice_id: InvoiceId) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            spans,
+            ..
+        } = self;
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+            let user_credits_repo = repo_factory.create_user_credits_repo_with_sys_acl(&conn);
+
+            conn.transaction(|| {
+                restore_invoice_credit(&*user_credits_repo, invoice_id)
+                    .map(|_| ())
+                    .map_err(ectx!(ErrorKind::Internal => invoice_id))
+            })
+        });
+
+        Box::new(fut)
+    }
+
+    /// Notifies the analytics and the invoice callback of the expired invoice
+    fn notify_of_expired_invoice(self, invoice_id: InvoiceId, buyer_currency: Currency) -> EventHandlerFuture<()> {
+        let analytics_enabled = self.analytics_publisher.is_some();
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            spans,
+            ..
+        } = self;
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+            let invoice_callbacks_repo = repo_factory.create_invoice_callbacks_repo_with_sys_acl(&conn);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+            conn.transaction(|| {
+                if analytics_enabled {
+                    enqueue_analytics_event(
+                        &*event_store_repo,
+                        AnalyticsEventType::InvoiceExpired,
+                        json!({ "invoice_id": invoice_id, "currency": buyer_currency }),
+                    )
+                    .map_err(ectx!(try ErrorKind::Internal => invoice_id))?;
+                }
+
+                enqueue_invoice_callback_delivery(
+                    &*invoice_callbacks_repo,
+                    &*event_store_repo,
+                    invoice_id,
+                    InvoiceCallbackEventType::InvoiceExpired,
+                    json!({ "currency": buyer_currency }),
+                )
+                .map_err(ectx!(ErrorKind::Internal => invoice_id))
+            })
+        });
 
         Box::new(fut)
     }
@@ -245,13 +1440,21 @@ where
                         let db_pool = self_.db_pool.clone();
                         let cpu_pool = self_.cpu_pool.clone();
                         let repo_factory = self_.repo_factory.clone();
+                        let spans = self_.spans.clone();
                         move |_| {
-                            spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                            spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
                                 let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
-                                invoices_repo
-                                    .unlink_account(invoice_id)
-                                    .map(|_| ())
-                                    .map_err(ectx!(convert => invoice_id))
+                                let account_assignments_repo = repo_factory.create_account_assignments_repo_with_sys_acl(&conn);
+                                conn.transaction(|| {
+                                    invoices_repo.unlink_account(invoice_id).map_err(ectx!(try convert => invoice_id))?;
+
+                                    // The account stays in quarantine for a while before it can be assigned again
+                                    let released_at = Utc::now().naive_utc();
+                                    account_assignments_repo
+                                        .release(account_id, released_at)
+                                        .map(|_| ())
+                                        .map_err(ectx!(convert => account_id, released_at))
+                                })
                             })
                         }
                     })
@@ -290,12 +1493,14 @@ where
                     .map_err(ectx!(ErrorKind::Internal => input))
             });
 
-        Box::new(fut)
+        Box::new(self.spans.time(EventPhase::ExternalCall, fut))
     }
 
     fn get_invoice(self, invoice_id: InvoiceId) -> EventHandlerFuture<RawInvoice> {
-        let EventHandler { db_pool, cpu_pool, .. } = self.clone();
-        spawn_on_pool(db_pool, cpu_pool, {
+        let EventHandler {
+            db_pool, cpu_pool, spans, ..
+        } = self.clone();
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool, cpu_pool, {
             let repo_factory = self.repo_factory.clone();
             move |conn| {
                 let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
@@ -308,17 +1513,22 @@ where
                         ectx!(err e, ErrorKind::Internal)
                     })
             }
-        })
+        });
+
+        Box::new(fut)
     }
 
     fn set_orders_status(self, invoice_id: InvoiceId, status: OrderState) -> EventHandlerFuture<()> {
-        let EventHandler { db_pool, cpu_pool, .. } = self.clone();
+        let EventHandler {
+            db_pool, cpu_pool, spans, ..
+        } = self.clone();
 
-        let fut = spawn_on_pool(db_pool, cpu_pool, {
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, {
             let repo_factory = self.repo_factory.clone();
             move |conn| {
                 let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
                 let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
 
                 let invoice_id_clone = invoice_id.clone();
                 let invoice = invoices_repo
@@ -333,191 +1543,784 @@ where
                     .get_many_by_invoice_id(invoice_id)
                     .map_err(ectx!(try convert => invoice_id))?;
 
-                Ok(orders
-                    .into_iter()
-                    .map(|order| OrderStateUpdate {
-                        order_id: order.id,
-                        store_id: order.store_id,
-                        customer_id: invoice.buyer_user_id.clone(),
-                        status: status.clone(),
+                let order_state_updates = orders
+                    .into_iter()
+                    .map(|order| OrderStateUpdate {
+                        order_id: order.id,
+                        store_id: order.store_id,
+                        customer_id: invoice.buyer_user_id.clone(),
+                        status: status.clone(),
+                        metadata: invoice.metadata.clone(),
+                    })
+                    .collect::<Vec<_>>();
+
+                conn.transaction(|| {
+                    invoices_repo
+                        .set_status(invoice_id, status.clone())
+                        .map_err(ectx!(try convert => invoice_id, status))?;
+
+                    enqueue_order_state_updates(&*event_store_repo, order_state_updates).map_err(ectx!(ErrorKind::Internal => invoice_id))
+                })
+            }
+        });
+
+        Box::new(fut)
+    }
+
+    /// Notifies the stores, the analytics and the invoice callback of the paid orders of the invoice
+    fn notify_of_paid_orders(self, invoice_id: InvoiceId) -> EventHandlerFuture<()> {
+        let analytics_enabled = self.analytics_publisher.is_some();
+        let receipts_enabled = self.receipts.enabled;
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            spans,
+            ..
+        } = self;
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+            let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+            let store_webhooks_repo = repo_factory.create_store_webhooks_repo_with_sys_acl(&conn);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+            let invoice_callbacks_repo = repo_factory.create_invoice_callbacks_repo_with_sys_acl(&conn);
+
+            let orders = orders_repo
+                .get_many_by_invoice_id(invoice_id)
+                .map_err(ectx!(try convert => invoice_id))?;
+
+            conn.transaction(|| {
+                enqueue_invoice_callback_delivery(
+                    &*invoice_callbacks_repo,
+                    &*event_store_repo,
+                    invoice_id,
+                    InvoiceCallbackEventType::InvoicePaid,
+                    invoice_paid_callback_data(&orders),
+                )
+                .map_err(ectx!(try ErrorKind::Internal => invoice_id))?;
+
+                if analytics_enabled {
+                    enqueue_analytics_event(
+                        &*event_store_repo,
+                        AnalyticsEventType::InvoicePaid,
+                        invoice_analytics_data(invoice_id, &orders),
+                    )
+                    .map_err(ectx!(try ErrorKind::Internal => invoice_id))?;
+                }
+
+                if receipts_enabled {
+                    let event = Event::new(EventPayload::InvoiceReceiptNotification { invoice_id });
+                    event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+                }
+
+                enqueue_order_paid_webhooks(&*store_webhooks_repo, &*event_store_repo, &orders)
+                    .map_err(ectx!(ErrorKind::Internal => invoice_id))
+            })
+        });
+
+        Box::new(fut)
+    }
+
+    fn create_fee_for_orders(self, invoice_id: InvoiceId) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool, cpu_pool, spans, ..
+        } = self.clone();
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool, cpu_pool, {
+            let repo_factory = self.repo_factory.clone();
+            move |conn| {
+                let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
+                let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+
+                let invoice_id_clone = invoice_id.clone();
+                let _invoice = invoices_repo
+                    .get(invoice_id_clone)
+                    .map_err(ectx!(try convert => invoice_id_clone))?
+                    .ok_or({
+                        let e = format_err!("Invoice {} not found", invoice_id.clone());
+                        ectx!(try err e, ErrorKind::Internal)
+                    })?;
+
+                orders_repo.get_many_by_invoice_id(invoice_id).map_err(ectx!(convert => invoice_id))
+            }
+        })
+        .and_then({
+            let currency_code = self.fee.currency_code.clone();
+            move |orders| {
+                Currency::from_str(&currency_code)
+                    .map_err(ectx!(ErrorKind::CurrencyConversion))
+                    .map(|fee_currency| (fee_currency, orders))
+            }
+        })
+        .and_then({
+            let stores_client = self.stores_client.clone();
+            let spans = spans.clone();
+            move |(fee_currency, orders)| {
+                spans
+                    .time(
+                        EventPhase::ExternalCall,
+                        stores_client.get_currency_exchange_info().map_err(ectx!(convert)),
+                    )
+                    .map(move |currency_exchange_info| (currency_exchange_info, fee_currency, orders))
+            }
+        })
+        .and_then({
+            let EventHandler {
+                db_pool, cpu_pool, spans, ..
+            } = self.clone();
+            let order_percent = self.fee.order_percent.clone();
+
+            move |(currency_exchange_info, fee_currency, orders)| {
+                spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, {
+                    let repo_factory = self.repo_factory.clone();
+                    move |conn| {
+                        let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+
+                        for order in orders.iter() {
+                            let new_fee =
+                                crate::services::invoice::create_crypto_fee(order_percent, &fee_currency, &currency_exchange_info, order)
+                                    .map_err(ectx!(try ErrorKind::Internal => order.id))?;
+
+                            let _ = fees_repo
+                                .create(new_fee)
+                                .map_err(ectx!(try ErrorKind::Internal => order.id.clone()))?;
+                        }
+
+                        Ok(())
+                    }
+                })
+            }
+        });
+
+        Box::new(fut)
+    }
+
+    /// Records the stripe fee of the order if its payment intent has been captured automatically,
+    /// otherwise re-evaluates the capture of the payment intent for all orders of the invoice
+    pub fn handle_payment_intent_capture(self, order_id: OrderId) -> EventHandlerFuture<()> {
+        let db_pool_ = self.db_pool.clone();
+        let cpu_pool_ = self.cpu_pool.clone();
+        let repo_factory_ = self.repo_factory.clone();
+        let spans = self.spans.clone();
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool_, cpu_pool_, move |conn| {
+            let payment_intent_repo = repo_factory_.create_payment_intent_repo_with_sys_acl(&conn);
+            let orders_repo = repo_factory_.create_orders_repo_with_sys_acl(&conn);
+            let payment_intent_invoices_repo = repo_factory_.create_payment_intent_invoices_repo_with_sys_acl(&conn);
+            let order = orders_repo.get(order_id).map_err(ectx!(try convert => order_id))?.ok_or({
+                let e = format_err!("Record order with id {} not found", order_id);
+                ectx!(try err e, ErrorKind::Internal)
+            })?;
+
+            let order_invoice_id_cloned = order.invoice_id.clone();
+            let payment_intent_invoice = payment_intent_invoices_repo
+                .get(SearchPaymentIntentInvoice::InvoiceId(order.invoice_id.clone()))
+                .map_err(ectx!(try convert => order_invoice_id_cloned))?
+                .ok_or({
+                    let e = format_err!("Record payment_intent_invoice by invoice id {} not found", order.invoice_id);
+                    ectx!(try err e, ErrorKind::Internal)
+                })?;
+
+            let search = SearchPaymentIntent::Id(payment_intent_invoice.payment_intent_id);
+            let search_clone = search.clone();
+            payment_intent_repo
+                .get(search.clone())
+                .map_err(ectx!(try convert => search))?
+                .ok_or({
+                    let e = format_err!("payment intent {:?} not found", search_clone);
+                    ectx!(err e, ErrorKind::Internal)
+                })
+                .map(|payment_intent| (order, payment_intent))
+        })
+        .and_then(move |(order, payment_intent)| -> EventHandlerFuture<()> {
+            if payment_intent.status == PaymentIntentStatus::Succeeded {
+                self.capture_order_of_succeeded_payment_intent(order, payment_intent)
+            } else {
+                self.capture_payment_intent(payment_intent.id, false)
+            }
+        })
+        .then(|res| {
+            if let Err(ref res) = res {
+                if res.kind() == ErrorKind::AlreadyDone {
+                    return Ok(());
+                }
+            }
+            res
+        });
+        Box::new(fut)
+    }
+
+    pub fn handle_payment_intent_capture_timeout(self, payment_intent_id: PaymentIntentId) -> EventHandlerFuture<()> {
+        self.capture_payment_intent(payment_intent_id, true)
+    }
+
+    /// Payment intents created before the capture orchestration are captured automatically,
+    /// only the stripe fee of the order is left to record
+    fn capture_order_of_succeeded_payment_intent(self, order: RawOrder, payment_intent: PaymentIntent) -> EventHandlerFuture<()> {
+        if order.state != PaymentState::Initial || order.stripe_fee.is_some() {
+            let e = format_err!("there is no need to perform capture payment intent");
+            return Box::new(future::err(ectx!(err e, ErrorKind::AlreadyDone)));
+        }
+
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            stripe_client,
+            spans,
+            ..
+        } = self;
+
+        let order_id = order.id;
+        let fut = spans
+            .time(
+                EventPhase::ExternalCall,
+                get_balance_transaction(stripe_client, payment_intent.charge_id),
+            )
+            .and_then(move |balance_transaction| {
+                stripe_fee_for_order(order.total_amount, &balance_transaction).ok_or_else(|| {
+                    let e = format_err!("Stripe fee of order {} is out of range", order_id);
+                    ectx!(err e, ErrorKind::Internal => balance_transaction.id)
+                })
+            })
+            .and_then(move |stripe_fee| {
+                spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+                    let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                    info!("Setting order {} state \'Captured\'", order_id);
+                    orders_repo
+                        .update_state(order_id, PaymentState::Captured)
+                        .map_err(ectx!(try convert => order_id))?;
+                    orders_repo
+                        .update_stripe_fee(order_id, stripe_fee)
+                        .map_err(ectx!(convert => order_id, stripe_fee))
+                        .map(|_| ())
+                })
+            });
+
+        Box::new(fut)
+    }
+
+    /// Captures the payment intent once for the approved orders of the invoice,
+    /// waiting for the rest of the orders until the capture timeout
+    fn capture_payment_intent(self, payment_intent_id: PaymentIntentId, timed_out: bool) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            stripe_client,
+            spans,
+            payment_capture,
+            ..
+        } = self;
+        let capture_timeout = Duration::minutes(payment_capture.timeout_min as i64);
+
+        let capture = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool.clone(), cpu_pool.clone(), {
+            let repo_factory = repo_factory.clone();
+            let payment_intent_id = payment_intent_id.clone();
+            move |conn| {
+                let payment_intent_repo = repo_factory.create_payment_intent_repo_with_sys_acl(&conn);
+                let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo_with_sys_acl(&conn);
+                let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                let order_capture_approvals_repo = repo_factory.create_order_capture_approvals_repo_with_sys_acl(&conn);
+                let invoice_risk_assessments_repo = repo_factory.create_invoice_risk_assessments_repo_with_sys_acl(&conn);
+                let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+                let payment_intent_id_cloned = payment_intent_id.clone();
+                let payment_intent = payment_intent_repo
+                    .get(SearchPaymentIntent::Id(payment_intent_id.clone()))
+                    .map_err(ectx!(try convert => payment_intent_id_cloned))?
+                    .ok_or({
+                        let e = format_err!("Payment intent {} not found", payment_intent_id);
+                        ectx!(try err e, ErrorKind::Internal)
+                    })?;
+
+                if payment_intent.status != PaymentIntentStatus::RequiresCapture {
+                    info!(
+                        "Payment intent {} is in status {:?}, there is nothing to capture",
+                        payment_intent_id, payment_intent.status
+                    );
+                    return Ok(None);
+                }
+
+                let payment_intent_id_cloned = payment_intent_id.clone();
+                let payment_intent_invoice = payment_intent_invoices_repo
+                    .get(SearchPaymentIntentInvoice::PaymentIntentId(payment_intent_id.clone()))
+                    .map_err(ectx!(try convert => payment_intent_id_cloned))?
+                    .ok_or({
+                        let e = format_err!("Record payment_intent_invoice by payment intent id {} not found", payment_intent_id);
+                        ectx!(try err e, ErrorKind::Internal)
+                    })?;
+
+                let invoice_id = payment_intent_invoice.invoice_id;
+                let risk_assessment = invoice_risk_assessments_repo
+                    .get(invoice_id)
+                    .map_err(ectx!(try convert => invoice_id))?;
+                if risk_assessment.as_ref().map_or(false, InvoiceRiskAssessment::holds_capture) {
+                    info!(
+                        "Payment intent {} of invoice {} is held until its risk review",
+                        payment_intent_id, invoice_id
+                    );
+                    if timed_out {
+                        // the capture times out again after the review, otherwise the orders approved meanwhile would wait forever
+                        let event = Event::new(EventPayload::PaymentIntentCaptureTimeout {
+                            payment_intent_id: payment_intent_id.clone(),
+                        });
+                        let scheduled_on = Utc::now().naive_utc() + capture_timeout;
+                        event_store_repo
+                            .add_scheduled_event(event.clone(), scheduled_on)
+                            .map_err(ectx!(try convert => event))?;
+                    }
+                    return Ok(None);
+                }
+
+                let orders = orders_repo
+                    .get_many_by_invoice_id(invoice_id)
+                    .map_err(ectx!(try convert => invoice_id))?;
+
+                let payment_intent_id_cloned = payment_intent_id.clone();
+                let approved_order_ids = order_capture_approvals_repo
+                    .get_by_payment_intent_id(payment_intent_id.clone())
+                    .map_err(ectx!(try convert => payment_intent_id_cloned))?
+                    .into_iter()
+                    .map(|approval| approval.order_id)
+                    .collect::<Vec<_>>();
+
+                // a rejected invoice releases all of its orders, as if none was approved until the capture timeout
+                let risk_rejected = risk_assessment.and_then(|assessment| assessment.review_status) == Some(RiskReviewStatus::Rejected);
+                let (approved_order_ids, timed_out) = if risk_rejected {
+                    (Vec::new(), true)
+                } else {
+                    (approved_order_ids, timed_out)
+                };
+
+                let decision = PaymentIntentCaptureDecision::new(&orders, &approved_order_ids, timed_out).ok_or({
+                    let e = format_err!("Amount to capture of payment intent {} overflows", payment_intent_id);
+                    ectx!(try err e, ErrorKind::Internal)
+                })?;
+
+                Ok(Some((invoice_id, orders, decision)))
+            }
+        });
+
+        let fut = capture.and_then(move |capture| -> EventHandlerFuture<()> {
+            let (invoice_id, orders, decision) = match capture {
+                None => return Box::new(future::ok(())),
+                Some(capture) => capture,
+            };
+
+            match decision {
+                PaymentIntentCaptureDecision::Wait => {
+                    info!(
+                        "Payment intent {} is waiting for the rest of the orders to be approved",
+                        payment_intent_id
+                    );
+                    Box::new(future::ok(()))
+                }
+                PaymentIntentCaptureDecision::Cancel { released_order_ids } => {
+                    info!(
+                        "No order paid by payment intent {} has been approved, cancelling it",
+                        payment_intent_id
+                    );
+                    let released_orders = orders_with_ids(&orders, &released_order_ids);
+                    let cancellation =
+                        cancel_payment_intent(db_pool.clone(), cpu_pool.clone(), stripe_client, repo_factory.clone(), invoice_id)
+                            .map_err(ectx!(ErrorKind::Internal => invoice_id));
+                    let cancellation = spans.time(EventPhase::ExternalCall, cancellation);
+                    Box::new(cancellation.and_then(move |_| {
+                        spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+                            let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                            let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+                            let fee_adjustments_repo = repo_factory.create_fee_adjustments_repo_with_sys_acl(&conn);
+
+                            conn.transaction(|| {
+                                for order in released_orders.iter() {
+                                    let order_id = order.id;
+                                    decline_released_order(&*orders_repo, &*fees_repo, &*fee_adjustments_repo, order)
+                                        .map_err(ectx!(try ErrorKind::Internal => order_id))?;
+                                }
+                                Ok(())
+                            })
+                        })
+                    }))
+                }
+                PaymentIntentCaptureDecision::Capture {
+                    amount,
+                    captured_order_ids,
+                    released_order_ids,
+                } => {
+                    info!(
+                        "Capturing {} of payment intent {} for {} orders, releasing {} orders",
+                        amount,
+                        payment_intent_id,
+                        captured_order_ids.len(),
+                        released_order_ids.len()
+                    );
+                    let captured_orders = orders_with_ids(&orders, &captured_order_ids);
+                    let released_orders = orders_with_ids(&orders, &released_order_ids);
+                    let payment_intent_id_cloned = payment_intent_id.clone();
+                    let capture = stripe_client
+                        .capture_payment_intent(payment_intent_id.clone(), amount)
+                        .map_err(ectx!(convert => payment_intent_id_cloned, amount))
+                        .and_then(move |payment_intent| {
+                            let update_payment_intent = update_payment_intent(payment_intent);
+                            get_balance_transaction(stripe_client, update_payment_intent.charge_id.clone())
+                                .map(move |balance_transaction| (update_payment_intent, balance_transaction))
+                        });
+                    let capture = spans.time(EventPhase::ExternalCall, capture);
+                    Box::new(capture.and_then(move |(update_payment_intent, balance_transaction)| {
+                        spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+                            let payment_intent_repo = repo_factory.create_payment_intent_repo_with_sys_acl(&conn);
+                            let payment_intent_history_repo = repo_factory.create_payment_intent_history_repo_with_sys_acl(&conn);
+                            let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                            let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+                            let fee_adjustments_repo = repo_factory.create_fee_adjustments_repo_with_sys_acl(&conn);
+
+                            conn.transaction(|| {
+                                let payment_intent_id_cloned = payment_intent_id.clone();
+                                let payment_intent = payment_intent_repo
+                                    .update(payment_intent_id.clone(), update_payment_intent)
+                                    .map_err(ectx!(try convert => payment_intent_id_cloned))?;
+                                record_payment_intent_status(
+                                    &*payment_intent_history_repo,
+                                    &payment_intent,
+                                    PaymentIntentHistorySource::Api,
+                                )?;
+
+                                let stripe_fees = captured_stripe_fees(&captured_orders, &balance_transaction).ok_or_else(|| {
+                                    let e = format_err!("Stripe fee of payment intent {} is out of range", payment_intent_id);
+                                    ectx!(try err e, ErrorKind::Internal => balance_transaction.id)
+                                })?;
+
+                                for (order, stripe_fee) in captured_orders.iter().zip(stripe_fees) {
+                                    let order_id = order.id;
+                                    info!("Setting order {} state \'Captured\'", order_id);
+                                    orders_repo
+                                        .update_state(order_id, PaymentState::Captured)
+                                        .map_err(ectx!(try convert => order_id))?;
+                                    orders_repo
+                                        .update_stripe_fee(order_id, stripe_fee)
+                                        .map_err(ectx!(try convert => order_id, stripe_fee))?;
+                                }
+
+                                for order in released_orders.iter() {
+                                    let order_id = order.id;
+                                    decline_released_order(&*orders_repo, &*fees_repo, &*fee_adjustments_repo, order)
+                                        .map_err(ectx!(try ErrorKind::Internal => order_id))?;
+                                }
+
+                                Ok(())
+                            })
+                        })
+                    }))
+                }
+            }
+        });
+
+        Box::new(fut)
+    }
+
+    pub fn handle_setup_intent_succeeded(self, setup_intent: SetupIntent) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            stripe_client,
+            spans,
+            ..
+        } = self;
+
+        let (customer_id, payment_method_id) = match (setup_intent.customer.clone(), setup_intent.payment_method.clone()) {
+            (Some(customer), Some(payment_method)) => (CustomerId::new(customer), payment_method),
+            _ => {
+                warn!(
+                    "Setup intent succeeded handler: setup intent {} has no customer or payment method, skipping",
+                    setup_intent.id
+                );
+                return Box::new(future::ok(()));
+            }
+        };
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool.clone(), cpu_pool.clone(), {
+            let repo_factory = repo_factory.clone();
+            let customer_id = customer_id.clone();
+            move |conn| {
+                let customers_repo = repo_factory.create_customers_repo_with_sys_acl(&conn);
+                customers_repo
+                    .get(SearchCustomer::Id(customer_id.clone()))
+                    .map_err(ectx!(convert => customer_id))
+            }
+        })
+        .and_then(move |customer| match customer {
+            None => {
+                warn!(
+                    "Setup intent succeeded handler: customer {} of setup intent {} not found, skipping",
+                    customer_id, setup_intent.id
+                );
+                future::Either::A(future::ok(()))
+            }
+            Some(customer) => {
+                let customer_id = customer.id;
+                let customer_id_clone = customer_id.clone();
+                let payment_method_id_clone = payment_method_id.clone();
+                let attachment = stripe_client
+                    .attach_payment_method(customer_id.clone(), payment_method_id.clone())
+                    .map_err(ectx!(convert => customer_id_clone, payment_method_id_clone));
+                let fut = spans.time(EventPhase::ExternalCall, attachment).and_then(move |_| {
+                    spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+                        let customers_repo = repo_factory.create_customers_repo_with_sys_acl(&conn);
+                        let payload = UpdateDbCustomer {
+                            payment_method_id: Some(payment_method_id),
+                            ..Default::default()
+                        };
+                        customers_repo
+                            .update(customer_id.clone(), payload.clone())
+                            .map_err(ectx!(convert => customer_id, payload))
+                            .map(|_| ())
+                    })
+                });
+
+                future::Either::B(fut)
+            }
+        });
+
+        Box::new(fut)
+    }
+
+    pub fn handle_stripe_fee_backfill_batch(self, stripe_fee_backfill_id: StripeFeeBackfillId) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            stripe_client,
+            spans,
+            ..
+        } = self;
+
+        let stripe_spans = spans.clone();
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool.clone(), cpu_pool.clone(), {
+            let repo_factory = repo_factory.clone();
+            move |conn| {
+                let stripe_fee_backfills_repo = repo_factory.create_stripe_fee_backfills_repo_with_sys_acl(&conn);
+                let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                let payment_intent_repo = repo_factory.create_payment_intent_repo_with_sys_acl(&conn);
+                let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo_with_sys_acl(&conn);
+
+                let backfill = stripe_fee_backfills_repo
+                    .get(stripe_fee_backfill_id)
+                    .map_err(ectx!(try convert => stripe_fee_backfill_id))?;
+
+                let backfill = match backfill {
+                    Some(ref backfill) if backfill.status == StripeFeeBackfillStatus::InProgress => backfill.clone(),
+                    _ => {
+                        info!(
+                            "Stripe fee backfill batch handler: backfill with ID {} not found or already finished",
+                            stripe_fee_backfill_id
+                        );
+                        return Ok(None);
+                    }
+                };
+
+                let order_ids =
+                    next_stripe_fee_backfill_batch(&backfill).map_err(ectx!(try ErrorKind::Internal => stripe_fee_backfill_id))?;
+                let orders = orders_repo.get_many(&order_ids).map_err(ectx!(try convert => order_ids))?;
+
+                let mut charges = Vec::new();
+                for order in orders {
+                    // the fee might have been set by the capture handler since the backfill was started
+                    if order.stripe_fee.is_some() {
+                        continue;
+                    }
+
+                    let invoice_id = order.invoice_id;
+                    let payment_intent_invoice = payment_intent_invoices_repo
+                        .get(SearchPaymentIntentInvoice::InvoiceId(invoice_id))
+                        .map_err(ectx!(try convert => invoice_id))?;
+
+                    let payment_intent = match payment_intent_invoice {
+                        None => None,
+                        Some(payment_intent_invoice) => {
+                            let search = SearchPaymentIntent::Id(payment_intent_invoice.payment_intent_id);
+                            payment_intent_repo.get(search.clone()).map_err(ectx!(try convert => search))?
+                        }
+                    };
+
+                    let charge_id = payment_intent.and_then(|payment_intent| payment_intent.charge_id);
+                    charges.push((order.id, order.total_amount, charge_id));
+                }
+
+                Ok(Some((backfill, order_ids.len(), charges)))
+            }
+        })
+        .and_then(move |batch| match batch {
+            None => future::Either::A(future::ok(None)),
+            Some((backfill, batch_size, charges)) => {
+                // requests are made one by one to stay well below the Stripe rate limit
+                let fut = stream::iter_ok::<_, Error>(charges)
+                    .and_then(move |(order_id, total_amount, charge_id)| {
+                        let stripe_client_clone = stripe_client.clone();
+                        charge_id
+                            .ok_or({
+                                let e = format_err!("payment intent charge not found");
+                                ectx!(err e, ErrorKind::Internal)
+                            })
+                            .into_future()
+                            .and_then({
+                                let stripe_client = stripe_client.clone();
+                                move |charge_id| stripe_client.get_charge(charge_id.clone()).map_err(ectx!(convert => charge_id))
+                            })
+                            .and_then(move |charge| {
+                                charge.balance_transaction.ok_or({
+                                    let e = format_err!("charge balance transaction id not found");
+                                    ectx!(err e, ErrorKind::Internal)
+                                })
+                            })
+                            .and_then(move |balance_transaction| {
+                                stripe_client_clone
+                                    .retrieve_balance_transaction(balance_transaction.clone())
+                                    .map_err(ectx!(convert => balance_transaction))
+                            })
+                            .then(move |res| match res {
+                                Ok(balance_transaction) => match stripe_fee_for_order(total_amount, &balance_transaction) {
+                                    Some(stripe_fee) => Ok((order_id, Some(stripe_fee))),
+                                    None => {
+                                        warn!(
+                                            "Stripe fee backfill {}: stripe fee of order {} is out of range",
+                                            stripe_fee_backfill_id, order_id
+                                        );
+                                        Ok((order_id, None))
+                                    }
+                                },
+                                Err(e) => {
+                                    warn!(
+                                        "Stripe fee backfill {}: failed to get stripe fee of order {}: {}",
+                                        stripe_fee_backfill_id, order_id, e
+                                    );
+                                    Ok((order_id, None))
+                                }
+                            })
                     })
-                    .collect::<Vec<_>>())
+                    .collect()
+                    .map(move |stripe_fees| Some((backfill, batch_size, stripe_fees)));
+
+                future::Either::B(stripe_spans.time(EventPhase::ExternalCall, fut))
             }
         })
-        .and_then({
-            let saga_client = self.saga_client.clone();
-            move |order_state_updates| {
-                saga_client
-                    .update_order_states(order_state_updates.clone())
-                    .map_err(ectx!(ErrorKind::Internal => order_state_updates))
+        .and_then(move |batch| match batch {
+            None => future::Either::A(future::ok(())),
+            Some((backfill, batch_size, stripe_fees)) => {
+                future::Either::B(spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+                    let stripe_fee_backfills_repo = repo_factory.create_stripe_fee_backfills_repo_with_sys_acl(&conn);
+                    let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                    let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+                    conn.transaction(move || {
+                        let mut updated_orders = 0;
+                        let mut failed_orders = 0;
+                        for (order_id, stripe_fee) in stripe_fees {
+                            match stripe_fee {
+                                Some(stripe_fee) => {
+                                    orders_repo
+                                        .update_stripe_fee(order_id, stripe_fee)
+                                        .map_err(ectx!(try convert => order_id, stripe_fee))?;
+                                    updated_orders += 1;
+                                }
+                                None => failed_orders += 1,
+                            }
+                        }
+
+                        let progress = stripe_fee_backfill_progress(&backfill, batch_size, updated_orders, failed_orders);
+                        let backfill = stripe_fee_backfills_repo
+                            .update_progress(backfill.id, progress.clone())
+                            .map_err(ectx!(try convert => progress))?;
+
+                        info!(
+                            "Stripe fee backfill {}: processed {} of {} orders, {} updated, {} failed",
+                            backfill.id, backfill.processed_orders, backfill.total_orders, backfill.updated_orders, backfill.failed_orders
+                        );
+
+                        if backfill.status == StripeFeeBackfillStatus::InProgress {
+                            let event = Event::new(EventPayload::StripeFeeBackfillBatch {
+                                stripe_fee_backfill_id: backfill.id,
+                            });
+                            event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+                        }
+
+                        Ok(())
+                    })
+                }))
             }
         });
 
         Box::new(fut)
     }
 
-    fn create_fee_for_orders(self, invoice_id: InvoiceId) -> EventHandlerFuture<()> {
-        let EventHandler { db_pool, cpu_pool, .. } = self.clone();
-
-        let fut = spawn_on_pool(db_pool, cpu_pool, {
-            let repo_factory = self.repo_factory.clone();
-            move |conn| {
-                let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
-                let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
-
-                let invoice_id_clone = invoice_id.clone();
-                let _invoice = invoices_repo
-                    .get(invoice_id_clone)
-                    .map_err(ectx!(try convert => invoice_id_clone))?
-                    .ok_or({
-                        let e = format_err!("Invoice {} not found", invoice_id.clone());
-                        ectx!(try err e, ErrorKind::Internal)
-                    })?;
+    pub fn handle_international_billing_info_reencryption_batch(self, after_id: InternationalBillingId) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            spans,
+            ..
+        } = self;
 
-                orders_repo.get_many_by_invoice_id(invoice_id).map_err(ectx!(convert => invoice_id))
-            }
-        })
-        .and_then({
-            let currency_code = self.fee.currency_code.clone();
-            move |orders| {
-                Currency::from_str(&currency_code)
-                    .map_err(ectx!(ErrorKind::CurrencyConversion))
-                    .map(|fee_currency| (fee_currency, orders))
-            }
-        })
-        .and_then({
-            let stores_client = self.stores_client.clone();
-            move |(fee_currency, orders)| {
-                stores_client
-                    .get_currency_exchange()
-                    .map_err(ectx!(convert))
-                    .and_then(|response| CurrencyExchangeInfo::try_from_request(response).map_err(ectx!(ErrorKind::CurrencyConversion)))
-                    .map(move |currency_exchange_info| (currency_exchange_info, fee_currency, orders))
-            }
-        })
-        .and_then({
-            let EventHandler { db_pool, cpu_pool, .. } = self.clone();
-            let order_percent = self.fee.order_percent.clone();
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+            let international_billing_info_repo = repo_factory.create_international_billing_repo_info_with_sys_acl(&conn);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
 
-            move |(currency_exchange_info, fee_currency, orders)| {
-                spawn_on_pool(db_pool, cpu_pool, {
-                    let repo_factory = self.repo_factory.clone();
-                    move |conn| {
-                        let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+            conn.transaction(move || {
+                let batch = international_billing_info_repo
+                    .reencrypt(Some(after_id), BILLING_INFO_REENCRYPTION_BATCH_SIZE)
+                    .map_err(ectx!(try convert => after_id))?;
 
-                        for order in orders.iter() {
-                            let new_fee =
-                                crate::services::invoice::create_crypto_fee(order_percent, &fee_currency, &currency_exchange_info, order)
-                                    .map_err(ectx!(try ErrorKind::Internal => order.id))?;
+                info!(
+                    "International billing info re-encryption: {} entries after {:?} sealed with the current key",
+                    batch.reencrypted, after_id
+                );
 
-                            let _ = fees_repo
-                                .create(new_fee)
-                                .map_err(ectx!(try ErrorKind::Internal => order.id.clone()))?;
-                        }
+                if let Some(after_id) = batch.last_id {
+                    let event = Event::new(EventPayload::InternationalBillingInfoReencryptionBatch { after_id });
+                    event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+                }
 
-                        Ok(())
-                    }
-                })
-            }
+                Ok(())
+            })
         });
 
         Box::new(fut)
     }
 
-    pub fn handle_payment_intent_capture(self, order_id: OrderId) -> EventHandlerFuture<()> {
-        let db_pool_ = self.db_pool.clone();
-        let cpu_pool_ = self.cpu_pool.clone();
-        let repo_factory_ = self.repo_factory.clone();
-        let stripe_client = self.stripe_client.clone();
+    pub fn handle_russia_billing_info_reencryption_batch(self, after_id: RussiaBillingId) -> EventHandlerFuture<()> {
+        let EventHandler {
+            db_pool,
+            cpu_pool,
+            repo_factory,
+            spans,
+            ..
+        } = self;
 
-        let fut = spawn_on_pool(db_pool_, cpu_pool_, move |conn| {
-            let payment_intent_repo = repo_factory_.create_payment_intent_repo_with_sys_acl(&conn);
-            let orders_repo = repo_factory_.create_orders_repo_with_sys_acl(&conn);
-            let payment_intent_invoices_repo = repo_factory_.create_payment_intent_invoices_repo_with_sys_acl(&conn);
-            let order = orders_repo.get(order_id).map_err(ectx!(try convert => order_id))?.ok_or({
-                let e = format_err!("Record order with id {} not found", order_id);
-                ectx!(try err e, ErrorKind::Internal)
-            })?;
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
+            let russia_billing_info_repo = repo_factory.create_russia_billing_info_repo_with_sys_acl(&conn);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
 
-            if order.state != PaymentState::Initial || order.stripe_fee.is_some() {
-                let e = format_err!("there is no need to perform capture payment intent");
-                return Err(ectx!(err e, ErrorKind::AlreadyDone));
-            }
+            conn.transaction(move || {
+                let batch = russia_billing_info_repo
+                    .reencrypt(Some(after_id), BILLING_INFO_REENCRYPTION_BATCH_SIZE)
+                    .map_err(ectx!(try convert => after_id))?;
 
-            let order_invoice_id_cloned = order.invoice_id.clone();
-            let payment_intent_invoice = payment_intent_invoices_repo
-                .get(SearchPaymentIntentInvoice::InvoiceId(order.invoice_id.clone()))
-                .map_err(ectx!(try convert => order_invoice_id_cloned))?
-                .ok_or({
-                    let e = format_err!("Record payment_intent_invoice by invoice id {} not found", order.invoice_id);
-                    ectx!(try err e, ErrorKind::Internal)
-                })?;
+                info!(
+                    "Russia billing info re-encryption: {} entries after {:?} sealed with the current key",
+                    batch.reencrypted, after_id
+                );
 
-            let search = SearchPaymentIntent::Id(payment_intent_invoice.payment_intent_id);
-            let search_clone = search.clone();
-            payment_intent_repo
-                .get(search.clone())
-                .map_err(ectx!(try convert => search))?
-                .ok_or({
-                    let e = format_err!("payment intent {:?} not found", search_clone);
-                    ectx!(err e, ErrorKind::Internal)
-                })
-                .map(|payment_intent| (payment_intent, order.total_amount, order.seller_currency))
-        })
-        .and_then(move |(payment_intent, total_amount, currency)| {
-            let stripe_client_clone = stripe_client.clone();
-            payment_intent
-                .charge_id
-                .ok_or({
-                    let e = format_err!("payment intent charge paid not found");
-                    ectx!(err e, ErrorKind::Internal)
-                })
-                .into_future()
-                .and_then(move |charge_id| stripe_client.get_charge(charge_id.clone()).map_err(ectx!(convert => charge_id)))
-                .and_then(move |charge| {
-                    charge.balance_transaction.ok_or({
-                        let e = format_err!("charge balance transaction id not found");
-                        ectx!(err e, ErrorKind::Internal)
-                    })
-                })
-                .and_then(move |balance_transaction| {
-                    stripe_client_clone
-                        .retrieve_balance_transaction(balance_transaction.clone())
-                        .map_err(ectx!(convert => balance_transaction))
-                })
-                .map(move |balance_transaction| {
-                    let total_amount_super_unit = total_amount.to_super_unit(currency);
-                    let fee_procent = balance_transaction.fee as f64 / balance_transaction.amount as f64;
-                    let stripe_fee = Amount::from_super_unit(currency, total_amount_super_unit * BigDecimal::from(fee_procent));
-                    stripe_fee
-                })
-        })
-        .and_then({
-            let db_pool = self.db_pool.clone();
-            let cpu_pool = self.cpu_pool.clone();
-            let repo_factory = self.repo_factory.clone();
-            move |stripe_fee| {
-                spawn_on_pool(db_pool, cpu_pool, move |conn| {
-                    let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
-                    info!("Setting order {} state \'Captured\'", order_id);
-                    orders_repo
-                        .update_state(order_id, PaymentState::Captured)
-                        .map_err(ectx!(try convert => order_id))?;
-                    orders_repo
-                        .update_stripe_fee(order_id, stripe_fee)
-                        .map_err(ectx!(convert => order_id, stripe_fee))
-                        .map(|_| ())
-                })
-            }
-        })
-        .then(|res| {
-            if let Err(ref res) = res {
-                if res.kind() == ErrorKind::AlreadyDone {
-                    return Ok(());
+                if let Some(after_id) = batch.last_id {
+                    let event = Event::new(EventPayload::RussiaBillingInfoReencryptionBatch { after_id });
+                    event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
                 }
-            }
-            res
+
+                Ok(())
+            })
         });
+
         Box::new(fut)
     }
 
@@ -525,13 +2328,14 @@ where
         let db_pool = self.db_pool.clone();
         let cpu_pool = self.cpu_pool.clone();
         let repo_factory = self.repo_factory.clone();
+        let spans = self.spans.clone();
 
         let (payments_client, account_service) = match self.clone().get_ture_context() {
             Ok((payments_client, account_service)) => (payments_client, account_service),
             Err(e) => return Box::new(future::err(e)),
         };
 
-        let fut = spawn_on_pool(db_pool.clone(), cpu_pool.clone(), move |conn| {
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool.clone(), cpu_pool.clone(), move |conn| {
             let payouts_repo = repo_factory.create_payouts_repo_with_sys_acl(&conn);
 
             let payout_id = payout_id.clone();
@@ -561,38 +2365,234 @@ where
         let payout_id = payout.id.clone();
         let tx_id = payout_id.clone().into_inner();
 
-        let fut = payments_client
+        let spans = self.spans.clone();
+        let transaction = payments_client
             .clone()
             .get_transaction(tx_id.clone())
-            .map_err(ectx!(ErrorKind::Internal => tx_id))
-            .and_then(move |tx| match tx {
-                None => future::Either::A(
-                    create_payout_tx(payments_client, account_service, payout).and_then(move |_| self.mark_payout_as_completed(payout_id)),
-                ),
-                Some(_tx) => future::Either::B(self.mark_payout_as_completed(payout_id)),
+            .map_err(ectx!(ErrorKind::Internal => tx_id));
+
+        let fut = spans.time(EventPhase::ExternalCall, transaction).and_then(move |tx| match tx {
+            None => future::Either::A(
+                self.clone()
+                    .check_no_other_payout_txs(payments_client.clone(), payout.clone())
+                    .and_then(move |_| spans.time(EventPhase::ExternalCall, create_payout_tx(payments_client, account_service, payout)))
+                    .and_then(move |_| self.mark_payout_as_completed(payout_id)),
+            ),
+            Some(_tx) => future::Either::B(self.mark_payout_as_completed(payout_id)),
+        });
+
+        Box::new(fut)
+    }
+
+    /// Refuses to submit the payout when another payout of its orders already has a transaction in the gateway
+    fn check_no_other_payout_txs(self, payments_client: PC, payout: Payout) -> EventHandlerFuture<()> {
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+        let repo_factory = self.repo_factory.clone();
+        let spans = self.spans.clone();
+
+        let Payout {
+            id: payout_id, order_ids, ..
+        } = payout;
+
+        let fut = spawn_phase_on_pool(&self.spans, EventPhase::DbLoad, db_pool, cpu_pool, move |conn| {
+            let payouts_repo = repo_factory.create_payouts_repo_with_sys_acl(&conn);
+
+            let order_ids_clone = order_ids.clone();
+            let PayoutsByOrderIds { payouts, .. } = payouts_repo
+                .get_by_order_ids(&order_ids)
+                .map_err(ectx!(try convert => order_ids_clone))?;
+
+            let mut other_payout_ids = payouts
+                .values()
+                .map(|payout| payout.id)
+                .filter(|id| *id != payout_id)
+                .collect::<Vec<_>>();
+            other_payout_ids.sort_by_key(|id| *id.inner());
+            other_payout_ids.dedup();
+
+            Ok(other_payout_ids)
+        })
+        .and_then(move |other_payout_ids| {
+            let txs = other_payout_ids.into_iter().map(move |other_payout_id| {
+                let tx_id = other_payout_id.into_inner();
+                payments_client
+                    .get_transaction(tx_id)
+                    .map_err(ectx!(ErrorKind::Internal => tx_id))
+                    .map(move |tx| tx.map(|_| other_payout_id))
             });
 
+            spans.time(EventPhase::ExternalCall, future::join_all(txs))
+        })
+        .and_then(move |submitted| match submitted.into_iter().flatten().next() {
+            None => Ok(()),
+            Some(other_payout_id) => {
+                let e = format_err!(
+                    "Payout {} is not submitted, its orders are paid out by payout {} that has a transaction in the gateway",
+                    payout_id,
+                    other_payout_id
+                );
+                Err(ectx!(err e, ErrorKind::Internal))
+            }
+        });
+
         Box::new(fut)
     }
 
     fn mark_payout_as_completed(self, payout_id: PayoutId) -> EventHandlerFuture<()> {
+        let analytics_enabled = self.analytics_publisher.is_some();
         let db_pool = self.db_pool.clone();
         let cpu_pool = self.cpu_pool.clone();
         let repo_factory = self.repo_factory.clone();
+        let spans = self.spans.clone();
 
-        let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbWrite, db_pool, cpu_pool, move |conn| {
             let payouts_repo = repo_factory.create_payouts_repo_with_sys_acl(&conn);
+            let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+            let store_webhooks_repo = repo_factory.create_store_webhooks_repo_with_sys_acl(&conn);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+            conn.transaction(|| {
+                let payout = payouts_repo
+                    .mark_as_completed(payout_id.clone())
+                    .map_err(ectx!(try ErrorKind::Internal => payout_id))?;
+
+                if analytics_enabled {
+                    enqueue_analytics_event(&*event_store_repo, AnalyticsEventType::PayoutSent, payout_analytics_data(&payout))
+                        .map_err(ectx!(try ErrorKind::Internal => payout_id))?;
+                }
 
-            payouts_repo
-                .mark_as_completed(payout_id.clone())
-                .map_err(ectx!(ErrorKind::Internal => payout_id))
-                .map(|_| ())
+                enqueue_payout_completed_webhooks(&*store_webhooks_repo, &*event_store_repo, &*orders_repo, &payout)
+                    .map_err(ectx!(ErrorKind::Internal => payout_id))
+            })
+        });
+
+        Box::new(fut)
+    }
+
+    pub fn handle_wallet_verification_initiated(self, wallet_verification_id: WalletVerificationId) -> EventHandlerFuture<()> {
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+        let repo_factory = self.repo_factory.clone();
+        let spans = self.spans.clone();
+
+        let (payments_client, account_service) = match self.clone().get_ture_context() {
+            Ok((payments_client, account_service)) => (payments_client, account_service),
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        let fut = spawn_phase_on_pool(&spans, EventPhase::DbLoad, db_pool.clone(), cpu_pool.clone(), move |conn| {
+            let wallet_verifications_repo = repo_factory.create_wallet_verifications_repo_with_sys_acl(&conn);
+            let user_wallets_repo = repo_factory.create_user_wallets_repo_with_sys_acl(&conn);
+
+            let wallet_verification = match wallet_verifications_repo
+                .get(wallet_verification_id)
+                .map_err(ectx!(try convert => wallet_verification_id))?
+            {
+                None => return Ok(None),
+                Some(wallet_verification) => wallet_verification,
+            };
+
+            // the user has started another verification or ran out of time, the transfer is not needed anymore
+            if !wallet_verification.is_pending(Utc::now().naive_utc()) {
+                return Ok(None);
+            }
+
+            let user_wallet_id = wallet_verification.user_wallet_id;
+            let user_wallet = user_wallets_repo
+                .get(user_wallet_id)
+                .map_err(ectx!(try convert => user_wallet_id))?
+                .ok_or_else(|| {
+                    let e = format_err!("User wallet with ID {} not found", user_wallet_id);
+                    ectx!(err e, ErrorKind::Internal)
+                })?;
+
+            Ok(Some((wallet_verification, user_wallet)))
+        })
+        .and_then(move |wallet_verification| match wallet_verification {
+            None => {
+                info!(
+                    "Wallet verification initiated handler: verification with ID {} is not pending",
+                    wallet_verification_id
+                );
+                future::Either::A(future::ok(()))
+            }
+            Some((wallet_verification, user_wallet)) => future::Either::B(spans.time(
+                EventPhase::ExternalCall,
+                send_wallet_verification_transfer(payments_client, account_service, wallet_verification, user_wallet),
+            )),
         });
 
         Box::new(fut)
     }
 }
 
+fn deliver_store_webhook_notification<HC>(
+    http_client: HC,
+    store_webhook: StoreWebhook,
+    notification: StoreWebhookNotification,
+) -> EventHandlerFuture<()>
+where
+    HC: HttpClient,
+{
+    let body = match serde_json::to_string(&notification) {
+        Ok(body) => body,
+        Err(e) => {
+            let e: Error = ectx!(err e, ErrorSource::SerdeJson, ErrorKind::Internal => notification);
+            return Box::new(future::err(e));
+        }
+    };
+
+    let mut headers = Headers::new();
+    headers.set(ContentType::json());
+    headers.set_raw("X-Billing-Event", notification.event_type.to_string());
+    headers.set_raw("X-Billing-Signature", store_webhook.sign(&body));
+
+    let StoreWebhook {
+        id: store_webhook_id, url, ..
+    } = store_webhook;
+
+    let fut = http_client
+        .request_json::<()>(Method::Post, url.clone(), Some(body), Some(headers))
+        .map_err(ectx!(ErrorKind::Internal => store_webhook_id, url));
+
+    Box::new(fut)
+}
+
+fn deliver_invoice_callback_notification<HC>(
+    http_client: HC,
+    invoice_callback: InvoiceCallback,
+    notification: InvoiceCallbackNotification,
+) -> EventHandlerFuture<()>
+where
+    HC: HttpClient,
+{
+    let body = match serde_json::to_string(&notification) {
+        Ok(body) => body,
+        Err(e) => {
+            let e: Error = ectx!(err e, ErrorSource::SerdeJson, ErrorKind::Internal => notification);
+            return Box::new(future::err(e));
+        }
+    };
+
+    let mut headers = Headers::new();
+    headers.set(ContentType::json());
+    headers.set_raw("X-Billing-Event", notification.event_type.to_string());
+    headers.set_raw("X-Billing-Signature", invoice_callback.sign(&body));
+
+    let InvoiceCallback {
+        id: invoice_callback_id,
+        url,
+        ..
+    } = invoice_callback;
+
+    let fut = http_client
+        .request_json::<()>(Method::Post, url.clone(), Some(body), Some(headers))
+        .map_err(ectx!(ErrorKind::Internal => invoice_callback_id, url));
+
+    Box::new(fut)
+}
+
 fn create_payout_tx<PC, AS>(payments_client: PC, account_service: AS, payout: Payout) -> EventHandlerFuture<()>
 where
     PC: PaymentsClient,
@@ -600,7 +2600,7 @@ where
 {
     let Payout {
         id: payout_id,
-        gross_amount,
+        net_amount,
         target:
             PayoutTarget::CryptoWallet(CryptoWalletPayoutTarget {
                 currency,
@@ -625,7 +2625,8 @@ where
                 id: tx_id,
                 from: account_id.into_inner(),
                 to: wallet_address,
-                amount: gross_amount,
+                // the network fee is charged on top of the amount, so the seller receives exactly the net amount
+                amount: net_amount,
                 currency,
                 fee: blockchain_fee,
             };
@@ -637,3 +2638,111 @@ where
 
     Box::new(fut)
 }
+
+/// Sends the verification amount to the wallet, the id of the verification is the id of the transaction.
+/// The transfer is sent once, a retried event finds the transaction in Payments gateway
+fn send_wallet_verification_transfer<PC, AS>(
+    payments_client: PC,
+    account_service: AS,
+    wallet_verification: WalletVerification,
+    user_wallet: UserWallet,
+) -> EventHandlerFuture<()>
+where
+    PC: PaymentsClient + Clone,
+    AS: AccountService,
+{
+    let tx_id = wallet_verification.id.into_inner();
+    let UserWallet { address, currency, .. } = user_wallet;
+
+    let fut = payments_client
+        .clone()
+        .get_transaction(tx_id)
+        .map_err(ectx!(ErrorKind::Internal => tx_id))
+        .and_then(move |tx| match tx {
+            Some(_) => future::Either::A(future::ok(())),
+            None => {
+                let input = GetFees {
+                    currency,
+                    account_address: address.clone().into_inner(),
+                };
+
+                let fee = payments_client
+                    .get_fees(input.clone())
+                    .map_err(ectx!(ErrorKind::Internal => input))
+                    .and_then(move |fees| match fees.fees.into_iter().map(|fee| fee.value).min() {
+                        None => {
+                            let e = format_err!("Payments gateway estimated no fees for a transfer in {}", currency);
+                            Err(ectx!(err e, ErrorKind::Internal))
+                        }
+                        Some(value) => Amount::checked_from_super_unit(currency.into(), value.clone()).ok_or_else(|| {
+                            let e = format_err!("Estimated blockchain fee is out of range: {}", value);
+                            ectx!(err e, ErrorKind::Internal)
+                        }),
+                    });
+
+                let main_account = account_service
+                    .get_main_account(currency)
+                    .map_err(ectx!(ErrorKind::Internal => currency));
+
+                future::Either::B(fee.join(main_account).and_then(move |(fee, main_account)| {
+                    let tx = CreateExternalTransaction {
+                        id: tx_id,
+                        from: main_account.account.id.into_inner(),
+                        to: address,
+                        amount: wallet_verification.amount,
+                        currency,
+                        fee,
+                    };
+
+                    payments_client
+                        .create_external_transaction(tx.clone())
+                        .map_err(ectx!(ErrorKind::Internal => tx))
+                }))
+            }
+        });
+
+    Box::new(fut)
+}
+
+fn orders_with_ids(orders: &[RawOrder], order_ids: &[OrderId]) -> Vec<RawOrder> {
+    orders.iter().filter(|order| order_ids.contains(&order.id)).cloned().collect()
+}
+
+/// Fee of the captured charge split between the captured orders, in the currency of the orders
+fn captured_stripe_fees(captured_orders: &[RawOrder], balance_transaction: &BalanceTransaction) -> Option<Vec<Amount>> {
+    let order_amounts = captured_orders.iter().map(|order| order.total_amount).collect::<Vec<_>>();
+    let total_amount = order_amounts
+        .iter()
+        .try_fold(Amount::zero(), |acc, amount| acc.checked_add(*amount))?;
+    let stripe_fee = stripe_fee_for_order(total_amount, balance_transaction)?;
+
+    allocate_stripe_fee(stripe_fee, &order_amounts)
+}
+
+/// Balance transaction of the captured charge, its fee is split between the orders paid with the charge
+fn get_balance_transaction<STRC>(stripe_client: STRC, charge_id: Option<ChargeId>) -> EventHandlerFuture<BalanceTransaction>
+where
+    STRC: StripeClient + Clone,
+{
+    let stripe_client_clone = stripe_client.clone();
+    let fut = charge_id
+        .ok_or({
+            let e = format_err!("payment intent charge paid not found");
+            ectx!(err e, ErrorKind::Internal)
+        })
+        .into_future()
+        .and_then(move |charge_id| stripe_client.get_charge(charge_id.clone()).map_err(ectx!(convert => charge_id)))
+        .and_then(move |charge| {
+            charge.balance_transaction.ok_or({
+                let e = format_err!("charge balance transaction id not found");
+                ectx!(err e, ErrorKind::Internal)
+            })
+        })
+        .and_then(move |balance_transaction| {
+            stripe_client_clone
+                .retrieve_balance_transaction(balance_transaction.clone())
+                .map_err(ectx!(convert => balance_transaction))
+        });
+
+    Box::new(fut)
+}
@@ -0,0 +1,139 @@
+//! Lanes split the event store into queues polled independently, so that slow back-office events
+//! like payouts don't hold back payment confirmations
+use std::collections::HashSet;
+use std::time::Duration;
+
+use failure::Error as FailureError;
+
+use config;
+use models::EventPayloadTypes;
+
+const DEFAULT_LANE_NAME: &str = "default";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lane {
+    pub name: String,
+    pub payload_types: EventPayloadTypes,
+    pub batch_size: u32,
+    pub concurrency: usize,
+    pub polling_rate: Duration,
+}
+
+/// Lanes of the config, completed with the lane taking the events of the payload types no other lane lists
+pub fn lanes_from_config(config: &config::EventStore) -> Result<Vec<Lane>, FailureError> {
+    let default_polling_rate = Duration::from_secs(config.polling_rate_sec.into());
+
+    let mut listed_payload_types = HashSet::new();
+    for lane in &config.lanes {
+        if lane.batch_size == 0 || lane.concurrency == 0 {
+            return Err(format_err!(
+                "Event lane \"{}\" must have a positive batch size and concurrency",
+                lane.name
+            ));
+        }
+        for payload_type in &lane.payload_types {
+            if !listed_payload_types.insert(payload_type.clone()) {
+                return Err(format_err!("Payload type \"{}\" is listed in several event lanes", payload_type));
+            }
+        }
+    }
+    let mut listed_payload_types = listed_payload_types.into_iter().collect::<Vec<_>>();
+    listed_payload_types.sort();
+
+    let mut catch_all_lanes = config.lanes.iter().filter(|lane| lane.payload_types.is_empty());
+    let catch_all_lane = catch_all_lanes.next().cloned();
+    if let Some(lane) = catch_all_lanes.next() {
+        return Err(format_err!(
+            "Event lane \"{}\" lists no payload types, as does another lane",
+            lane.name
+        ));
+    }
+    let catch_all_lane = catch_all_lane.unwrap_or_else(|| config::EventLane {
+        name: DEFAULT_LANE_NAME.to_string(),
+        payload_types: vec![],
+        batch_size: 1,
+        concurrency: 1,
+        polling_rate_ms: None,
+    });
+
+    let lanes = config
+        .lanes
+        .iter()
+        .filter(|lane| !lane.payload_types.is_empty())
+        .map(|lane| (lane, EventPayloadTypes::Only(lane.payload_types.clone())))
+        .chain(::std::iter::once((
+            &catch_all_lane,
+            EventPayloadTypes::AllExcept(listed_payload_types),
+        )))
+        .map(|(lane, payload_types)| Lane {
+            name: lane.name.clone(),
+            payload_types,
+            batch_size: lane.batch_size,
+            concurrency: lane.concurrency as usize,
+            polling_rate: lane.polling_rate_ms.map(Duration::from_millis).unwrap_or(default_polling_rate),
+        })
+        .collect();
+
+    Ok(lanes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_store_config(lanes: Vec<config::EventLane>) -> config::EventStore {
+        config::EventStore {
+            max_processing_attempts: 3,
+            stuck_threshold_sec: 300,
+            polling_rate_sec: 10,
+            slow_event_threshold_ms: 10000,
+            instance_id: None,
+            lanes,
+        }
+    }
+
+    fn lane(name: &str, payload_types: &[&str]) -> config::EventLane {
+        config::EventLane {
+            name: name.to_string(),
+            payload_types: payload_types.iter().map(|t| t.to_string()).collect(),
+            batch_size: 10,
+            concurrency: 4,
+            polling_rate_ms: Some(500),
+        }
+    }
+
+    #[test]
+    fn events_not_listed_in_any_lane_go_to_the_default_lane() {
+        let config = event_store_config(vec![
+            lane("payments", &["InvoicePaid", "PaymentIntentSucceeded"]),
+            lane("payouts", &["PayoutInitiated"]),
+        ]);
+
+        let lanes = lanes_from_config(&config).unwrap();
+
+        assert_eq!(lanes.len(), 3);
+        assert_eq!(
+            lanes[0].payload_types,
+            EventPayloadTypes::Only(vec!["InvoicePaid".to_string(), "PaymentIntentSucceeded".to_string()])
+        );
+        assert_eq!(lanes[2].name, "default");
+        assert_eq!(
+            lanes[2].payload_types,
+            EventPayloadTypes::AllExcept(vec![
+                "InvoicePaid".to_string(),
+                "PaymentIntentSucceeded".to_string(),
+                "PayoutInitiated".to_string(),
+            ])
+        );
+        assert_eq!(lanes[2].polling_rate, Duration::from_secs(10));
+        assert!(lanes[2].payload_types.matches("StoreWebhookDelivery"));
+        assert!(!lanes[2].payload_types.matches("InvoicePaid"));
+    }
+
+    #[test]
+    fn payload_types_listed_in_several_lanes_are_rejected() {
+        let config = event_store_config(vec![lane("payments", &["InvoicePaid"]), lane("other", &["InvoicePaid"])]);
+
+        assert!(lanes_from_config(&config).is_err());
+    }
+}
@@ -1,31 +1,41 @@
 pub mod error;
 mod handlers;
+mod lanes;
+mod spans;
 
 use diesel::{
     connection::{AnsiTransactionManager, Connection},
     pg::Pg,
 };
 use failure::{err_msg, Error as FailureError, Fail};
-use futures::{future, Future, Stream};
+use futures::{future, stream, Future, Stream};
 use futures_cpupool::CpuPool;
 use r2d2::{ManageConnection, Pool, PooledConnection};
 use sentry::integrations::failure::capture_error;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use stq_http::client::HttpClient;
 use tokio_timer::Interval;
 
-use client::{payments::PaymentsClient, saga::SagaClient, stores::StoresClient, stripe::StripeClient};
+use client::{
+    analytics::AnalyticsPublisher, notifications::NotificationsClient, payments::PaymentsClient, saga::SagaClient, stores::StoresClient,
+    stripe::StripeClient,
+};
 use config;
-use models::event_store::EventEntry;
+use models::event::Event;
+use models::event_store::{EventEntry, EventEntryId, EventPhase, EventTimings};
 use repos::repo_factory::ReposFactory;
 use services::accounts::AccountService;
+use services::work_queue::EVENT_HANDLER_WORK_QUEUE;
 
 use self::error::*;
+pub use self::lanes::{lanes_from_config, Lane};
+pub use self::spans::EventSpans;
 
 pub type EventHandlerResult<T> = Result<T, Error>;
 pub type EventHandlerFuture<T> = Box<Future<Item = T, Error = Error>>;
 
-pub struct EventHandler<T, M, F, HC, PC, SC, STC, STRC, AS>
+pub struct EventHandler<T, M, F, HC, PC, SC, STC, STRC, NC, AS>
 where
     T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
     M: ManageConnection<Connection = T>,
@@ -35,6 +45,7 @@ where
     SC: SagaClient,
     STC: StoresClient,
     STRC: StripeClient,
+    NC: NotificationsClient,
     AS: AccountService + 'static,
 {
     pub cpu_pool: CpuPool,
@@ -44,12 +55,23 @@ where
     pub saga_client: SC,
     pub stripe_client: STRC,
     pub stores_client: STC,
+    pub notifications_client: NC,
     pub payments_client: Option<PC>,
     pub account_service: Option<AS>,
     pub fee: config::FeeValues,
+    pub payment_recovery: config::PaymentRecovery,
+    pub payment_capture: config::PaymentCapture,
+    pub receipts: config::Receipts,
+    pub fee_dunning: config::FeeDunning,
+    /// Events taking longer are logged as warnings
+    pub slow_event_threshold: Duration,
+    /// Spans of the phases of the event being processed, a new set is taken for every event
+    pub spans: EventSpans,
+    /// Sink of the analytics events, none are recorded when not configured
+    pub analytics_publisher: Option<Arc<dyn AnalyticsPublisher>>,
 }
 
-impl<T, M, F, HC, PC, SC, STC, STRC, AS> Clone for EventHandler<T, M, F, HC, PC, SC, STC, STRC, AS>
+impl<T, M, F, HC, PC, SC, STC, STRC, NC, AS> Clone for EventHandler<T, M, F, HC, PC, SC, STC, STRC, NC, AS>
 where
     T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
     M: ManageConnection<Connection = T>,
@@ -59,6 +81,7 @@ where
     SC: SagaClient + Clone,
     STC: StoresClient + Clone,
     STRC: StripeClient + Clone,
+    NC: NotificationsClient + Clone,
     AS: AccountService + Clone + 'static,
 {
     fn clone(&self) -> Self {
@@ -70,14 +93,22 @@ where
             saga_client: self.saga_client.clone(),
             stores_client: self.stores_client.clone(),
             stripe_client: self.stripe_client.clone(),
+            notifications_client: self.notifications_client.clone(),
             payments_client: self.payments_client.clone(),
             account_service: self.account_service.clone(),
             fee: self.fee.clone(),
+            payment_recovery: self.payment_recovery.clone(),
+            payment_capture: self.payment_capture.clone(),
+            receipts: self.receipts.clone(),
+            fee_dunning: self.fee_dunning.clone(),
+            slow_event_threshold: self.slow_event_threshold,
+            spans: self.spans.clone(),
+            analytics_publisher: self.analytics_publisher.clone(),
         }
     }
 }
 
-impl<T, M, F, HC, PC, SC, STC, STRC, AS> EventHandler<T, M, F, HC, PC, SC, STC, STRC, AS>
+impl<T, M, F, HC, PC, SC, STC, STRC, NC, AS> EventHandler<T, M, F, HC, PC, SC, STC, STRC, NC, AS>
 where
     T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
     M: ManageConnection<Connection = T>,
@@ -87,20 +118,35 @@ where
     SC: SagaClient + Clone,
     STC: StoresClient + Clone,
     STRC: StripeClient + Clone,
+    NC: NotificationsClient + Clone,
     AS: AccountService + Clone + 'static,
 {
-    pub fn run(self, interval: Duration) -> impl Future<Item = (), Error = FailureError> {
-        Interval::new(Instant::now(), interval)
+    /// Polls every lane on its own, a lane waits for its batch to be processed before taking the next one
+    pub fn run(self, lanes: Vec<Lane>) -> impl Future<Item = (), Error = FailureError> {
+        let lane_loops = lanes.into_iter().map(|lane| self.clone().run_lane(lane)).collect::<Vec<_>>();
+        future::join_all(lane_loops).map(|_| ())
+    }
+
+    fn run_lane(self, lane: Lane) -> impl Future<Item = (), Error = FailureError> {
+        info!(
+            "Processing events of lane \"{}\" ({:?}) in batches of {} by {} every {:?}",
+            lane.name, lane.payload_types, lane.batch_size, lane.concurrency, lane.polling_rate
+        );
+
+        Interval::new(Instant::now(), lane.polling_rate)
             .map_err(ectx!(ErrorSource::TokioTimer, ErrorKind::Internal))
-            .fold(self, |event_handler, _| {
-                trace!("Started processing events");
-                event_handler.clone().process_events().then(|res| {
+            .fold(self, move |event_handler, _| {
+                trace!("Started processing events of lane \"{}\"", lane.name);
+                let lane_name = lane.name.clone();
+                event_handler.clone().process_events(lane.clone()).then(move |res| {
                     match res {
                         Ok(_) => {
-                            trace!("Finished processing events");
+                            trace!("Finished processing events of lane \"{}\"", lane_name);
                         }
                         Err(err) => {
-                            let err = FailureError::from(err.context("An error occurred while processing events"));
+                            let err = FailureError::from(
+                                err.context(format!("An error occurred while processing events of lane \"{}\"", lane_name)),
+                            );
                             error!("{:?}", &err);
                             capture_error(&err);
                         }
@@ -122,65 +168,134 @@ where
         }
     }
 
-    fn process_events(self) -> EventHandlerFuture<()> {
+    fn process_events(self, lane: Lane) -> EventHandlerFuture<()> {
         let EventHandler {
             cpu_pool,
             db_pool,
             repo_factory,
             ..
         } = self.clone();
+        let Lane {
+            name: lane_name,
+            payload_types,
+            batch_size,
+            concurrency,
+            ..
+        } = lane;
 
-        let fut = spawn_on_pool(db_pool.clone(), cpu_pool.clone(), {
-            let repo_factory = repo_factory.clone();
-            move |conn| {
-                let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+        // events are not failed for a busy pool, the lane takes none until the pool has caught up with the ones taken
+        if EVENT_HANDLER_WORK_QUEUE.is_full() {
+            EVENT_HANDLER_WORK_QUEUE.record_rejected();
+            warn!(
+                "Work queue of the event handler is full with {} closures, lane \"{}\" skips this run",
+                EVENT_HANDLER_WORK_QUEUE.depth(),
+                lane_name
+            );
+            return Box::new(future::ok(()));
+        }
 
-                trace!("Resetting stuck events...");
-                let reset_events = event_store_repo.reset_stuck_events().map_err(ectx!(try convert))?;
-                trace!("{} events have been reset", reset_events.len());
-
-                trace!("Getting events for processing...");
-                event_store_repo
-                    .get_events_for_processing(1)
-                    .map(|event_entries| {
-                        trace!("Got {} events to process", event_entries.len());
-                        event_entries
-                            .into_iter()
-                            .next()
-                            .map(|EventEntry { id: entry_id, event, .. }| (entry_id, event))
-                    })
-                    .map_err(ectx!(convert))
-            }
+        let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+            trace!("Resetting stuck events...");
+            let reset_events = event_store_repo.reset_stuck_events().map_err(ectx!(try convert))?;
+            trace!("{} events have been reset", reset_events.len());
+
+            trace!("Getting events for processing...");
+            event_store_repo
+                .get_events_for_processing(batch_size, &payload_types)
+                .map(|event_entries| {
+                    trace!("Got {} events to process", event_entries.len());
+                    event_entries
+                        .into_iter()
+                        .map(|EventEntry { id: entry_id, event, .. }| (entry_id, event))
+                        .collect::<Vec<_>>()
+                })
+                .map_err(ectx!(convert))
         })
-        .and_then(move |event| match event {
-            None => future::Either::A(future::ok(())),
-            Some((entry_id, event)) => future::Either::B(future::lazy(move || {
-                trace!("Started processing event #{} - {:?}", entry_id, event);
-                self.handle_event(event.clone()).then(move |result| {
-                    spawn_on_pool(db_pool, cpu_pool, move |conn| {
-                        let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
-
-                        match result {
-                            Ok(()) => {
-                                trace!("Finished processing event #{} - {:?}", entry_id, event);
-                                event_store_repo.complete_event(entry_id).map_err(ectx!(try convert => entry_id))?;
-                                Ok(())
-                            }
-                            Err(e) => {
-                                trace!("Failed to process event #{} - {:?}", entry_id, event);
-                                event_store_repo.fail_event(entry_id).map_err(ectx!(try convert => entry_id))?;
-                                Err(e)
-                            }
+        .and_then(move |events| {
+            // A failed event is recorded and reported, the rest of the batch is processed anyway
+            stream::iter_ok::<_, Error>(events)
+                .map(move |(entry_id, event)| {
+                    let lane_name = lane_name.clone();
+                    self.clone().process_event(entry_id, event).then(move |res| {
+                        if let Err(err) = res {
+                            let err = FailureError::from(err.context(format!(
+                                "An error occurred while processing event #{} of lane \"{}\"",
+                                entry_id, lane_name
+                            )));
+                            error!("{:?}", &err);
+                            capture_error(&err);
                         }
+                        Ok::<_, Error>(())
                     })
                 })
-            })),
+                .buffer_unordered(concurrency)
+                .for_each(|_| Ok(()))
         });
 
         Box::new(fut)
     }
+
+    /// Handles the event with a new set of spans. The timings are recorded on the event entry whatever the result
+    /// and logged, as a warning if the event has taken longer than `slow_event_threshold`
+    fn process_event(self, entry_id: EventEntryId, event: Event) -> EventHandlerFuture<()> {
+        let event_handler = EventHandler {
+            spans: EventSpans::new(),
+            ..self
+        };
+        let EventHandler {
+            cpu_pool,
+            db_pool,
+            repo_factory,
+            slow_event_threshold,
+            spans,
+            ..
+        } = event_handler.clone();
+
+        trace!("Started processing event #{} - {:?}", entry_id, event);
+        let started_at = Instant::now();
+        let fut = event_handler.handle_event(event.clone()).then(move |result| {
+            let elapsed = started_at.elapsed();
+            let timings = spans.timings(elapsed);
+            log_event_timings(entry_id, &event, &timings, elapsed > slow_event_threshold);
+
+            spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+                if let Err(e) = event_store_repo.record_event_timings(entry_id, timings) {
+                    error!("Failed to record the timings of event #{}: {}", entry_id, e);
+                }
+
+                match result {
+                    Ok(()) => {
+                        trace!("Finished processing event #{} - {:?}", entry_id, event);
+                        event_store_repo.complete_event(entry_id).map_err(ectx!(try convert => entry_id))?;
+                        Ok(())
+                    }
+                    Err(e) => {
+                        trace!("Failed to process event #{} - {:?}", entry_id, event);
+                        event_store_repo.fail_event(entry_id).map_err(ectx!(try convert => entry_id))?;
+                        Err(e)
+                    }
+                }
+            })
+        });
+
+        Box::new(fut)
+    }
+}
+
+fn log_event_timings(entry_id: EventEntryId, event: &Event, timings: &EventTimings, is_slow: bool) {
+    if is_slow {
+        warn!("Event #{} ({}) is slow to process: {}", entry_id, event.payload, timings);
+    } else {
+        info!("Event #{} ({}) processed in {}", entry_id, event.payload, timings);
+    }
 }
 
+/// Runs the closure on the CPU pool with a database connection. The closure is counted in the work queue of the event handler,
+/// which is never refused: events already taken are finished, and the lanes take no new ones while the queue is full
 pub fn spawn_on_pool<T, M, Func, R>(db_pool: Pool<M>, cpu_pool: CpuPool, f: Func) -> EventHandlerFuture<R>
 where
     T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
@@ -188,5 +303,26 @@ where
     Func: FnOnce(PooledConnection<M>) -> Result<R, Error> + Send + 'static,
     R: Send + 'static,
 {
-    Box::new(cpu_pool.spawn_fn(move || db_pool.get().map_err(ectx!(ErrorSource::R2d2, ErrorKind::Internal)).and_then(f)))
+    let slot = EVENT_HANDLER_WORK_QUEUE.enqueue();
+    Box::new(cpu_pool.spawn_fn(move || {
+        let _slot = slot;
+        db_pool.get().map_err(ectx!(ErrorSource::R2d2, ErrorKind::Internal)).and_then(f)
+    }))
+}
+
+/// Spawns the database work of a phase on the pool and records its time in the spans of the event
+pub fn spawn_phase_on_pool<T, M, Func, R>(
+    spans: &EventSpans,
+    phase: EventPhase,
+    db_pool: Pool<M>,
+    cpu_pool: CpuPool,
+    f: Func,
+) -> EventHandlerFuture<R>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    Func: FnOnce(PooledConnection<M>) -> Result<R, Error> + Send + 'static,
+    R: Send + 'static,
+{
+    Box::new(spans.time(phase, spawn_on_pool(db_pool, cpu_pool, f)))
 }
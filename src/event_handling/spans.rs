@@ -0,0 +1,75 @@
+//! Timings of the phases of event handlers, so that a slow event shows whether its time went to the database
+//! or to the services it calls
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::Future;
+
+use models::{EventPhase, EventSpan, EventTimings};
+
+/// Spans of the event being processed, shared by the phases of its handler
+#[derive(Debug, Clone, Default)]
+pub struct EventSpans {
+    spans: Arc<Mutex<Vec<EventSpan>>>,
+}
+
+impl EventSpans {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the time from now until the future resolves, whether it succeeds or not
+    pub fn time<Fut: Future>(&self, phase: EventPhase, fut: Fut) -> impl Future<Item = Fut::Item, Error = Fut::Error> {
+        let spans = self.clone();
+        let started_at = Instant::now();
+        fut.then(move |res| {
+            spans.record(phase, started_at.elapsed());
+            res
+        })
+    }
+
+    pub fn record(&self, phase: EventPhase, elapsed: Duration) {
+        let span = EventSpan {
+            phase,
+            elapsed_ms: duration_ms(elapsed),
+        };
+        match self.spans.lock() {
+            Ok(mut spans) => spans.push(span),
+            Err(_) => warn!("Event spans lock is poisoned, {} span is not recorded", phase),
+        }
+    }
+
+    pub fn timings(&self, total: Duration) -> EventTimings {
+        let spans = self.spans.lock().map(|spans| spans.clone()).unwrap_or_default();
+        EventTimings {
+            total_ms: duration_ms(total),
+            spans,
+        }
+    }
+}
+
+fn duration_ms(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+
+    #[test]
+    fn spans_of_failed_phases_are_recorded() {
+        let spans = EventSpans::new();
+
+        let _ = spans.time(EventPhase::DbLoad, future::ok::<_, ()>(())).wait();
+        let _ = spans.time(EventPhase::ExternalCall, future::err::<(), _>(())).wait();
+        spans.record(EventPhase::ExternalCall, Duration::from_millis(1500));
+
+        let timings = spans.timings(Duration::from_millis(2000));
+        assert_eq!(timings.total_ms, 2000);
+        assert_eq!(timings.spans.len(), 3);
+        assert_eq!(timings.spans[1].phase, EventPhase::ExternalCall);
+        assert!(timings.phase_ms(EventPhase::ExternalCall) >= 1500);
+        assert_eq!(timings.phase_ms(EventPhase::DbWrite), 0);
+    }
+}
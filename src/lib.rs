@@ -22,6 +22,7 @@ extern crate env_logger;
 #[macro_use]
 extern crate failure;
 extern crate chrono;
+extern crate flate2;
 extern crate futures;
 extern crate futures_cpupool;
 extern crate hex;
@@ -30,10 +31,13 @@ extern crate hyper_tls;
 extern crate itertools;
 extern crate jsonwebtoken as jwt;
 #[macro_use]
+extern crate lazy_static;
+#[macro_use]
 extern crate log;
 extern crate r2d2;
 extern crate r2d2_diesel;
 extern crate r2d2_redis;
+extern crate ring;
 extern crate secp256k1;
 extern crate serde;
 #[macro_use]
@@ -58,6 +62,9 @@ extern crate validator;
 #[macro_use]
 extern crate sentry;
 extern crate stripe;
+#[cfg(test)]
+#[macro_use]
+extern crate proptest;
 
 #[macro_use]
 pub mod macros;
@@ -73,6 +80,10 @@ pub mod repos;
 pub mod schema;
 pub mod sentry_integration;
 pub mod services;
+pub mod signature;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+pub mod warmup;
 
 use std::process;
 use std::sync::Arc;
@@ -86,36 +97,66 @@ use hyper::server::Http;
 use r2d2_diesel::ConnectionManager;
 use r2d2_redis::RedisConnectionManager;
 use stq_cache::cache::{redis::RedisCache, Cache, NullCache, TypedCache};
+use stq_http::client::ClientHandle;
 use stq_http::controller::Application;
-use tokio_core::reactor::Core;
+use tokio_core::reactor::{Core, Handle};
 
 use client::{
-    payments::{self, mock::MockPaymentsClient, PaymentsClient, PaymentsClientImpl},
+    analytics::create_analytics_publisher,
+    instrumentation::Instrumented,
+    notifications::NotificationsClientImpl,
+    payments::{self, gateway::GuardedPaymentsClient, mock::MockPaymentsClient, PaymentsClient, PaymentsClientImpl},
     saga::SagaClientImpl,
-    stores::StoresClientImpl,
-    stripe::StripeClientImpl,
+    storage::StorageClientImpl,
+    stores::{cache::run_currency_exchange_refresh, CachedStoresClient, StoresClientImpl},
+    stripe::{StripeClientImpl, StripeKeys},
 };
 use config::Config;
+use controller::amounts::MinorAmountsApplication;
+use controller::compression::CompressionApplication;
 use controller::context::StaticContext;
+use controller::etag::ETagApplication;
+use controller::overload::OverloadApplication;
+use controller::public::{PublicApplication, RateLimiter};
+use controller::versioning::VersionedApplication;
 use errors::Error;
-use event_handling::EventHandler;
+use event_handling::{EventHandler, EventSpans};
 use repos::acl::RolesCacheImpl;
+use repos::encryption::FieldCipher;
 use repos::repo_factory::ReposFactoryImpl;
-use services::accounts::{AccountService, AccountServiceImpl};
+use repos::{FeatureFlagsCache, ReposFactory};
+use services::accounts::{run_account_pool_replenishment, AccountPoolSizing, AccountService, AccountServiceImpl};
+use services::cashback_liability::run_cashback_liability_snapshots;
+use services::customer_export::run_customer_export;
+use services::data_retention::run_data_retention_purge;
+use services::exchange_rate_retention::run_exchange_rate_cleanup;
+use services::fee_statement::run_fee_statement_generation;
+use services::legacy_invoice_expiration::run_legacy_invoice_expiration_sweep;
+use services::schema_migration;
+use services::store_billing_status::run_store_billing_policy;
+use services::work_queue::{CONTROLLER_WORK_QUEUE, EVENT_HANDLER_WORK_QUEUE};
 use std::thread;
 
-/// Starts new web service from provided `Config`
-pub fn start_server<F: FnOnce() + 'static>(config: Config, port: &Option<String>, callback: F) {
-    // Prepare reactor
-    let mut core = Core::new().expect("Unexpected error creating event loop core");
-    let handle = Arc::new(core.handle());
+/// Database, clients and context the API server and the worker are built from
+struct Runtime<F: ReposFactory<PgConnection>> {
+    core: Core,
+    handle: Arc<Handle>,
+    context: StaticContext<PgConnection, ConnectionManager<PgConnection>, F>,
+    payments_ctx: Option<(Arc<dyn PaymentsClient>, Arc<dyn AccountService + Send + Sync>)>,
+    stores_client: CachedStoresClient<Instrumented<StoresClientImpl<ClientHandle>>>,
+}
 
-    let client = stq_http::client::Client::new(&config.to_http_config(), &handle);
-    let client_handle = client.handle();
-    let client_stream = client.stream();
-    handle.spawn(client_stream.for_each(|_| Ok(())));
+/// Starts new web service from provided `Config`.
+/// Events are processed in the same process unless `worker.combined` is turned off
+pub fn start_server<F: FnOnce() + 'static>(config: Config, port: &Option<String>, callback: F) {
+    let Runtime {
+        mut core,
+        handle,
+        context,
+        payments_ctx,
+        stores_client,
+    } = prepare_runtime(&config);
 
-    // Prepare server
     let thread_count = config.server.thread_count;
 
     // Prepare server
@@ -124,6 +165,96 @@ pub fn start_server<F: FnOnce() + 'static>(config: Config, port: &Option<String>
         format!("{}:{}", config.server.host, port).parse().expect("Could not parse address")
     };
 
+    if config.worker.combined {
+        spawn_worker(&config, &context, &payments_ctx, stores_client.clone());
+    } else {
+        info!("Events are left to the worker, see `worker.combined`");
+    }
+
+    spawn_currency_exchange_refresh(stores_client);
+
+    handle.spawn(reload_stripe_keys_on_sighup(context.stripe_keys.clone()));
+
+    let public_rate_limiter = RateLimiter::per_minute(&config.public_invoices);
+
+    let serve = Http::new()
+        .serve_addr_handle(&address, &handle, move || {
+            // Prepare application
+            let controller = controller::ControllerImpl::new(context.clone());
+            let app = Application::<Error>::new(controller);
+            let app = VersionedApplication::new(app, context.route_parser.clone(), context.config.api.v1_sunset.clone());
+            let app = MinorAmountsApplication::new(app);
+            let app = ETagApplication::new(app, context.route_parser.clone());
+            let app = PublicApplication::new(
+                app,
+                context.route_parser.clone(),
+                public_rate_limiter.clone(),
+                &context.config.public_invoices,
+            );
+            let app = OverloadApplication::new(
+                app,
+                context.route_parser.clone(),
+                CONTROLLER_WORK_QUEUE.clone(),
+                &context.config.work_queue,
+            );
+            let app = CompressionApplication::new(app);
+
+            Ok(app)
+        })
+        .unwrap_or_else(|why| {
+            error!("Http Server Initialization Error: {}", why);
+            process::exit(1);
+        });
+
+    let handle_arc2 = handle.clone();
+    handle.spawn(
+        serve
+            .for_each(move |conn| {
+                handle_arc2.spawn(conn.map(|_| ()).map_err(|why| error!("Server Error: {:?}", why)));
+                Ok(())
+            })
+            .map_err(|_| ()),
+    );
+
+    info!("Listening on http://{}, threads: {}", address, thread_count);
+    handle.spawn_fn(move || {
+        callback();
+        future::ok(())
+    });
+
+    wait_for_ctrl_c(&mut core);
+}
+
+/// Starts the worker from provided `Config`: processes events and runs the scheduled jobs without serving HTTP.
+/// Any number of workers may run next to each other and next to combined API servers
+pub fn start_worker(config: Config) {
+    let Runtime {
+        mut core,
+        handle,
+        context,
+        payments_ctx,
+        stores_client,
+    } = prepare_runtime(&config);
+
+    spawn_worker(&config, &context, &payments_ctx, stores_client.clone());
+    spawn_currency_exchange_refresh(stores_client);
+
+    handle.spawn(reload_stripe_keys_on_sighup(context.stripe_keys.clone()));
+
+    info!("Worker is running");
+    wait_for_ctrl_c(&mut core);
+}
+
+fn prepare_runtime(config: &Config) -> Runtime<impl ReposFactory<PgConnection>> {
+    // Prepare reactor
+    let mut core = Core::new().expect("Unexpected error creating event loop core");
+    let handle = Arc::new(core.handle());
+
+    let client = stq_http::client::Client::new(&config.to_http_config(), &handle);
+    let client_handle = client.handle();
+    let client_stream = client.stream();
+    handle.spawn(client_stream.for_each(|_| Ok(())));
+
     // Prepare database pool
     let database_url: String = config.server.database.parse().expect("Database URL must be set in configuration");
     let db_manager = ConnectionManager::<PgConnection>::new(database_url);
@@ -132,7 +263,9 @@ pub fn start_server<F: FnOnce() + 'static>(config: Config, port: &Option<String>
         .expect("Failed to create DB connection pool");
 
     // Prepare CPU pool
-    let cpu_pool = CpuPool::new(thread_count);
+    let cpu_pool = CpuPool::new(config.server.thread_count);
+    CONTROLLER_WORK_QUEUE.set_max_depth(config.work_queue.controller_max_depth);
+    EVENT_HANDLER_WORK_QUEUE.set_max_depth(config.work_queue.event_handler_max_depth);
 
     // Prepare cache
     let roles_cache = match &config.server.redis {
@@ -155,13 +288,34 @@ pub fn start_server<F: FnOnce() + 'static>(config: Config, port: &Option<String>
         None => RolesCacheImpl::new(Box::new(NullCache::new()) as Box<_>),
     };
 
+    let event_store_instance_id = config.event_store.instance_id();
+    info!("Leasing events as instance \"{}\"", event_store_instance_id);
+
     let config::EventStore {
         max_processing_attempts,
         stuck_threshold_sec,
-        polling_rate_sec,
+        ..
     } = config.event_store.clone();
 
-    let repo_factory = ReposFactoryImpl::new(roles_cache, max_processing_attempts, stuck_threshold_sec);
+    let cipher = FieldCipher::new(&config.encryption).expect("Invalid encryption config");
+    if !cipher.is_enabled() {
+        warn!("Billing info encryption key is not configured, account details are stored in plaintext");
+    }
+
+    let feature_flags_cache = FeatureFlagsCache::new(Duration::from_secs(config.feature_flags.cache_ttl_sec));
+
+    let repo_factory = ReposFactoryImpl::new(
+        roles_cache,
+        feature_flags_cache,
+        max_processing_attempts,
+        stuck_threshold_sec,
+        event_store_instance_id,
+        cipher,
+    );
+
+    if config.schema_check.enabled {
+        check_schema_version(&config.schema_check, &db_pool, &repo_factory);
+    }
 
     let context = StaticContext::new(
         db_pool.clone(),
@@ -175,12 +329,21 @@ pub fn start_server<F: FnOnce() + 'static>(config: Config, port: &Option<String>
         let payments_client =
             PaymentsClientImpl::create_from_config(client_handle.clone(), payments::Config::from(payments_config.clone()))
                 .expect("Failed to create Payments client");
+        let payments_client = Instrumented::new(
+            GuardedPaymentsClient::new(
+                payments_client,
+                payments_config.gateway.clone().into(),
+                context.payments_circuit_breaker.clone(),
+            ),
+            context.dependency_stats.clone(),
+        );
 
         let account_service = AccountServiceImpl::new(
             db_pool.clone(),
             cpu_pool.clone(),
             repo_factory.clone(),
-            payments_config.min_pooled_accounts,
+            AccountPoolSizing::new(payments_config.min_pooled_accounts, &config.account_pool),
+            config.payment_expiry.account_quarantine(),
             payments_client.clone(),
             format!("{}{}", config.callback.url, controller::routes::PAYMENTS_CALLBACK_ENDPOINT),
             payments_config.accounts.into(),
@@ -200,7 +363,8 @@ pub fn start_server<F: FnOnce() + 'static>(config: Config, port: &Option<String>
             db_pool.clone(),
             cpu_pool.clone(),
             repo_factory.clone(),
-            payments_mock_cfg.min_pooled_accounts,
+            AccountPoolSizing::new(payments_mock_cfg.min_pooled_accounts, &config.account_pool),
+            config.payment_expiry.account_quarantine(),
             payments_client.clone(),
             format!("{}{}", config.callback.url, controller::routes::PAYMENTS_CALLBACK_ENDPOINT),
             payments_mock_cfg.accounts.into(),
@@ -231,59 +395,294 @@ pub fn start_server<F: FnOnce() + 'static>(config: Config, port: &Option<String>
         }
     };
 
+    if config.warmup.enabled {
+        let _ = core.run(warmup::run(
+            &config.warmup,
+            db_pool.clone(),
+            cpu_pool.clone(),
+            repo_factory.clone(),
+            Instrumented::new(StripeClientImpl::new(context.stripe_keys.clone()), context.dependency_stats.clone()),
+            payments_ctx.as_ref().map(|(payments_client, _)| payments_client.clone()),
+        ));
+    }
+
+    let stores_client = CachedStoresClient::new(
+        Instrumented::new(
+            StoresClientImpl::new(client_handle.clone(), config.stores_microservice.url.clone()),
+            context.dependency_stats.clone(),
+        ),
+        context.currency_exchange_cache.clone(),
+    );
+
+    Runtime {
+        core,
+        handle,
+        context,
+        payments_ctx,
+        stores_client,
+    }
+}
+
+/// Spawns the event processor and the scheduled jobs. Events are leased by a single instance at a time,
+/// so any number of workers may run. The process exits when the event processor fails, for the orchestrator to restart it
+fn spawn_worker<F: ReposFactory<PgConnection>>(
+    config: &Config,
+    context: &StaticContext<PgConnection, ConnectionManager<PgConnection>, F>,
+    payments_ctx: &Option<(Arc<dyn PaymentsClient>, Arc<dyn AccountService + Send + Sync>)>,
+    stores_client: CachedStoresClient<Instrumented<StoresClientImpl<ClientHandle>>>,
+) {
+    let event_lanes = event_handling::lanes_from_config(&config.event_store).expect("Invalid event store lanes config");
+    let client_handle = context.client_handle.clone();
+
     let event_handler = EventHandler {
-        db_pool: db_pool.clone(),
-        cpu_pool: cpu_pool.clone(),
-        repo_factory: repo_factory.clone(),
+        db_pool: context.db_pool.clone(),
+        cpu_pool: context.cpu_pool.clone(),
+        repo_factory: context.repo_factory.clone(),
         http_client: client_handle.clone(),
         payments_client: payments_ctx.as_ref().map(|(payments_client, _)| payments_client.clone()),
         account_service: payments_ctx.as_ref().map(|(_, account_service)| account_service.clone()),
-        saga_client: SagaClientImpl::new(client_handle.clone(), config.saga_addr.url.clone()),
-        stores_client: StoresClientImpl::new(client_handle.clone(), config.stores_microservice.url.clone()),
-        stripe_client: StripeClientImpl::create_from_config(&config),
-        fee: config.fee,
+        saga_client: Instrumented::new(
+            SagaClientImpl::new(client_handle.clone(), config.saga_addr.url.clone()),
+            context.dependency_stats.clone(),
+        ),
+        stores_client,
+        stripe_client: Instrumented::new(StripeClientImpl::new(context.stripe_keys.clone()), context.dependency_stats.clone()),
+        notifications_client: Instrumented::new(
+            NotificationsClientImpl::new(client_handle.clone(), config.notifications_microservice.url.clone()),
+            context.dependency_stats.clone(),
+        ),
+        payment_recovery: config.payment_recovery.clone(),
+        payment_capture: config.payment_capture.clone(),
+        receipts: config.receipts.clone(),
+        fee_dunning: config.fee_dunning.clone(),
+        fee: config.fee.clone(),
+        slow_event_threshold: Duration::from_millis(config.event_store.slow_event_threshold_ms),
+        spans: EventSpans::new(),
+        analytics_publisher: config
+            .analytics
+            .clone()
+            .map(|analytics| create_analytics_publisher(analytics.sink, client_handle.clone(), context.cpu_pool.clone())),
     };
 
     thread::spawn(move || {
         info!("Event processor is now running");
         let mut core = Core::new().expect("Failed to create a Tokio core for the event processor");
-        let polling_rate = Duration::new(polling_rate_sec.into(), 0);
-        core.run(EventHandler::run(event_handler, polling_rate))
-            .expect("Fatal error occurred in the event processor");
+        if let Err(e) = core.run(EventHandler::run(event_handler, event_lanes)) {
+            error!("Fatal error occurred in the event processor: {:?}", e);
+            process::exit(1);
+        }
     });
 
-    let serve = Http::new()
-        .serve_addr_handle(&address, &handle, move || {
-            // Prepare application
-            let controller = controller::ControllerImpl::new(context.clone());
-            let app = Application::<Error>::new(controller);
+    if config.store_billing_suspension.enabled {
+        let store_billing_policy = run_store_billing_policy(
+            config.store_billing_suspension.clone(),
+            config.feature_flags.clone(),
+            context.db_pool.clone(),
+            context.cpu_pool.clone(),
+            context.repo_factory.clone(),
+        );
 
-            Ok(app)
-        })
-        .unwrap_or_else(|why| {
-            error!("Http Server Initialization Error: {}", why);
-            process::exit(1);
+        thread::spawn(move || {
+            info!("Store billing policy is now running");
+            let mut core = Core::new().expect("Failed to create a Tokio core for the store billing policy");
+            core.run(store_billing_policy)
+                .expect("Fatal error occurred in the store billing policy");
+        });
+    }
+
+    if let (Some((_, account_service)), true) = (payments_ctx.as_ref(), config.account_pool.replenishment_enabled) {
+        let account_pool_config = config.account_pool.clone();
+        let account_service = account_service.clone();
+
+        // service futures aren't `Send`, the job is built on its own thread
+        thread::spawn(move || {
+            info!("Account pool replenishment is now running");
+            let mut core = Core::new().expect("Failed to create a Tokio core for the account pool replenishment");
+            core.run(run_account_pool_replenishment(account_pool_config, account_service))
+                .expect("Fatal error occurred in the account pool replenishment");
         });
+    }
+
+    if config.cashback_liabilities.snapshots_enabled {
+        let cashback_liability_snapshots = run_cashback_liability_snapshots(
+            config.cashback_liabilities.clone(),
+            context.db_pool.clone(),
+            context.cpu_pool.clone(),
+            context.repo_factory.clone(),
+        );
 
-    let handle_arc2 = handle.clone();
-    handle.spawn(
-        serve
-            .for_each(move |conn| {
-                handle_arc2.spawn(conn.map(|_| ()).map_err(|why| error!("Server Error: {:?}", why)));
-                Ok(())
-            })
-            .map_err(|_| ()),
-    );
+        thread::spawn(move || {
+            info!("Cashback liability snapshots are now running");
+            let mut core = Core::new().expect("Failed to create a Tokio core for the cashback liability snapshots");
+            core.run(cashback_liability_snapshots)
+                .expect("Fatal error occurred in the cashback liability snapshots");
+        });
+    }
+
+    if config.fee_statements.generation_enabled {
+        let fee_statement_generation = run_fee_statement_generation(
+            config.fee_statements.clone(),
+            context.db_pool.clone(),
+            context.cpu_pool.clone(),
+            context.repo_factory.clone(),
+        );
 
-    info!("Listening on http://{}, threads: {}", address, thread_count);
-    handle.spawn_fn(move || {
-        callback();
-        future::ok(())
+        thread::spawn(move || {
+            info!("Fee statement generation is now running");
+            let mut core = Core::new().expect("Failed to create a Tokio core for the fee statement generation");
+            core.run(fee_statement_generation)
+                .expect("Fatal error occurred in the fee statement generation");
+        });
+    }
+
+    if config.legacy_invoice_expiration.sweep_enabled {
+        let legacy_invoice_expiration_sweep = run_legacy_invoice_expiration_sweep(
+            config.legacy_invoice_expiration.clone(),
+            context.db_pool.clone(),
+            context.cpu_pool.clone(),
+            context.repo_factory.clone(),
+        );
+
+        thread::spawn(move || {
+            info!("V1 invoice expiration sweep is now running");
+            let mut core = Core::new().expect("Failed to create a Tokio core for the v1 invoice expiration sweep");
+            core.run(legacy_invoice_expiration_sweep)
+                .expect("Fatal error occurred in the v1 invoice expiration sweep");
+        });
+    }
+
+    if config.data_retention.purge_enabled {
+        let data_retention_purge = run_data_retention_purge(
+            config.data_retention.clone(),
+            context.db_pool.clone(),
+            context.cpu_pool.clone(),
+            context.repo_factory.clone(),
+        );
+
+        thread::spawn(move || {
+            info!("Data retention purge is now running");
+            let mut core = Core::new().expect("Failed to create a Tokio core for the data retention purge");
+            core.run(data_retention_purge)
+                .expect("Fatal error occurred in the data retention purge");
+        });
+    }
+
+    if config.exchange_rate_retention.cleanup_enabled {
+        let exchange_rate_cleanup = run_exchange_rate_cleanup(
+            config.exchange_rate_retention.clone(),
+            context.db_pool.clone(),
+            context.cpu_pool.clone(),
+            context.repo_factory.clone(),
+        );
+
+        thread::spawn(move || {
+            info!("Exchange rate cleanup is now running");
+            let mut core = Core::new().expect("Failed to create a Tokio core for the exchange rate cleanup");
+            core.run(exchange_rate_cleanup)
+                .expect("Fatal error occurred in the exchange rate cleanup");
+        });
+    }
+
+    if config.customer_export.export_enabled {
+        let storage_microservice = config
+            .storage_microservice
+            .clone()
+            .expect("Customer export requires the storage microservice url");
+        let storage_client = Instrumented::new(
+            StorageClientImpl::new(client_handle.clone(), storage_microservice.url),
+            context.dependency_stats.clone(),
+        );
+        let customer_export_config = config.customer_export.clone();
+        let db_pool = context.db_pool.clone();
+        let cpu_pool = context.cpu_pool.clone();
+        let repo_factory = context.repo_factory.clone();
+
+        // service futures aren't `Send`, the job is built on its own thread
+        thread::spawn(move || {
+            info!("Customer export is now running");
+            let mut core = Core::new().expect("Failed to create a Tokio core for the customer export");
+            core.run(run_customer_export(
+                customer_export_config,
+                db_pool,
+                cpu_pool,
+                repo_factory,
+                storage_client,
+            ))
+            .expect("Fatal error occurred in the customer export");
+        });
+    }
+}
+
+/// Every process keeps its own currency exchange info, so the refresh runs in the API server and the worker alike
+fn spawn_currency_exchange_refresh(stores_client: CachedStoresClient<Instrumented<StoresClientImpl<ClientHandle>>>) {
+    let currency_exchange_refresh = run_currency_exchange_refresh(stores_client);
+
+    thread::spawn(move || {
+        info!("Currency exchange refresh is now running");
+        let mut core = Core::new().expect("Failed to create a Tokio core for the currency exchange refresh");
+        core.run(currency_exchange_refresh)
+            .expect("Fatal error occurred in the currency exchange refresh");
     });
+}
 
+fn wait_for_ctrl_c(core: &mut Core) {
     core.run(tokio_signal::ctrl_c().flatten_stream().take(1u64).for_each(|()| {
         info!("Ctrl+C received. Exit");
         Ok(())
     }))
     .unwrap();
 }
+
+/// Compares the migrations of the binary with the ones applied to the database and exits
+/// if the database is behind, unless the config only asks to log it
+fn check_schema_version<F: ReposFactory<PgConnection>>(
+    config: &config::SchemaCheck,
+    db_pool: &r2d2::Pool<ConnectionManager<PgConnection>>,
+    repo_factory: &F,
+) {
+    let conn = db_pool.get().expect("Failed to get a DB connection for the schema check");
+    let schema_migrations_repo = repo_factory.create_schema_migrations_repo_with_sys_acl(&*conn);
+    let schema_version = schema_migration::schema_version(&*schema_migrations_repo).unwrap_or_else(|e| {
+        error!("Failed to read the schema version of the database: {}", e);
+        process::exit(1);
+    });
+
+    if !schema_version.unknown_migrations.is_empty() {
+        warn!(
+            "Database has migrations this binary doesn't know of: {}",
+            schema_version.unknown_migrations.join(", ")
+        );
+    }
+
+    if schema_version.is_behind() {
+        let message = format!(
+            "Database schema is behind the binary, pending migrations: {}",
+            schema_version.pending_migrations.join(", ")
+        );
+        if config.refuse_start_on_drift {
+            error!("{}", message);
+            process::exit(1);
+        }
+        warn!("{}", message);
+    } else {
+        info!(
+            "Database schema is at version {}",
+            schema_version.current_version.unwrap_or_default()
+        );
+    }
+}
+
+/// Reloads the Stripe keys from the config on SIGHUP, so that rotated keys are used without a restart
+fn reload_stripe_keys_on_sighup(stripe_keys: Arc<StripeKeys>) -> impl Future<Item = (), Error = ()> {
+    tokio_signal::unix::Signal::new(tokio_signal::unix::SIGHUP)
+        .flatten_stream()
+        .for_each(move |_| {
+            info!("SIGHUP received, reloading Stripe keys");
+            match Config::new() {
+                Ok(config) => stripe_keys.reload(&config.stripe),
+                Err(e) => error!("Failed to read the config, keeping the current Stripe keys: {}", e),
+            }
+            Ok(())
+        })
+        .map_err(|e| error!("SIGHUP handler error: {}", e))
+}
@@ -1,5 +1,6 @@
 //! Users is a microservice responsible for authentication and managing user profiles.
 //! This create is for running the service from `billing_lib`. See `billing_lib` for details.
+//! It serves the API and processes events unless `worker.combined` leaves them to `billing-worker`.
 
 extern crate billing_lib;
 extern crate stq_logging;
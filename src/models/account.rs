@@ -53,6 +53,26 @@ pub struct AccountCount {
     pub unpooled: HashMap<TureCurrency, u64>,
 }
 
+/// Lifecycle state of an account. Only active accounts are handed out from the pool.
+/// A draining account has its remaining funds swept to the main account and is archived afterwards.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountStatus {
+    Active,
+    Draining,
+    Archived,
+}
+
+impl Display for AccountStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AccountStatus::Active => f.write_str("active"),
+            AccountStatus::Draining => f.write_str("draining"),
+            AccountStatus::Archived => f.write_str("archived"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Account {
     pub id: AccountId,
@@ -60,6 +80,7 @@ pub struct Account {
     pub is_pooled: bool,
     pub created_at: NaiveDateTime,
     pub wallet_address: WalletAddress,
+    pub status: AccountStatus,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -77,6 +98,7 @@ pub struct RawAccount {
     pub is_pooled: bool,
     pub created_at: NaiveDateTime,
     pub wallet_address: WalletAddress,
+    pub status: AccountStatus,
 }
 
 impl From<RawAccount> for Account {
@@ -87,6 +109,7 @@ impl From<RawAccount> for Account {
             is_pooled,
             created_at,
             wallet_address,
+            status,
         } = raw_account;
 
         Account {
@@ -95,6 +118,7 @@ impl From<RawAccount> for Account {
             is_pooled,
             created_at,
             wallet_address,
+            status,
         }
     }
 }
@@ -117,9 +141,12 @@ pub struct PaymentsCallback {
     pub currency: TureCurrency,
     pub address: WalletAddress,
     pub account_id: Option<AccountId>,
+    /// Time of the transaction as reported by the gateway
+    #[serde(default)]
+    pub created_at: Option<NaiveDateTime>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum SystemAccountType {
     Main,
     Cashback,
@@ -159,6 +186,16 @@ impl SystemAccounts {
     }
 }
 
+/// Internal transaction that moved funds from one system account to another
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemAccountsTransfer {
+    pub transaction_id: Uuid,
+    pub from_account_id: AccountId,
+    pub to_account_id: AccountId,
+    pub currency: TureCurrency,
+    pub amount: Amount,
+}
+
 impl From<config::Accounts> for SystemAccounts {
     fn from(config: config::Accounts) -> SystemAccounts {
         let config::Accounts {
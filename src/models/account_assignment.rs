@@ -0,0 +1,28 @@
+use chrono::NaiveDateTime;
+
+use models::invoice_v2::InvoiceId;
+use models::AccountId;
+use schema::account_assignments;
+
+/// Period a pooled account was the payment target of an invoice, `released_at` is empty until the account is unlinked
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+pub struct AccountAssignment {
+    pub id: i64,
+    pub account_id: AccountId,
+    pub invoice_id: InvoiceId,
+    pub assigned_at: NaiveDateTime,
+    pub released_at: Option<NaiveDateTime>,
+}
+
+impl AccountAssignment {
+    pub fn is_active_at(&self, at: NaiveDateTime) -> bool {
+        self.assigned_at <= at && self.released_at.map(|released_at| at < released_at).unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[table_name = "account_assignments"]
+pub struct NewAccountAssignment {
+    pub account_id: AccountId,
+    pub invoice_id: InvoiceId,
+}
@@ -0,0 +1,39 @@
+use chrono::NaiveDateTime;
+
+use models::TureCurrency;
+use schema::account_pool_claims;
+
+/// Account taken from the pool of a currency. Claims are only appended, they tell the demand for pooled accounts
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+pub struct AccountPoolClaim {
+    pub id: i64,
+    pub currency: TureCurrency,
+    /// The pool had no free account and one was created at claim time
+    pub exhausted: bool,
+    pub claimed_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[table_name = "account_pool_claims"]
+pub struct NewAccountPoolClaim {
+    pub currency: TureCurrency,
+    pub exhausted: bool,
+}
+
+/// Claims of the accounts of a currency within a period
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AccountPoolDemand {
+    pub claims: u64,
+    pub exhaustions: u64,
+}
+
+/// Utilization of the pool of a currency, the demand is observed within the demand window
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AccountPoolStatus {
+    pub currency: TureCurrency,
+    /// Active pooled accounts
+    pub pooled: u64,
+    /// Pooled accounts that can be handed out right away
+    pub free: u64,
+    pub demand: AccountPoolDemand,
+}
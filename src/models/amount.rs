@@ -15,9 +15,9 @@ use stq_types::Quantity;
 
 use models::Currency;
 
-const WEI_IN_ETH: u32 = 18;
-const SATOSHIS_IN_BTC: u32 = 8;
-const CENTS_IN_DOLLAR: u32 = 2;
+pub(crate) const WEI_IN_ETH: u32 = 18;
+pub(crate) const SATOSHIS_IN_BTC: u32 = 8;
+pub(crate) const CENTS_IN_DOLLAR: u32 = 2;
 const MAX_WEI_PRECISION: i64 = 8;
 const MAX_SATOSHIS_PRECISION: i64 = 8;
 const MAX_FIAT_PRECISION: i64 = 2;
@@ -71,30 +71,47 @@ impl Amount {
         self.0.clone()
     }
 
+    /// Converts a value in super units, dropping the digits beyond the precision of the currency.
+    /// Panics on negative values and values that don't fit into `u128`,
+    /// use `checked_from_super_unit` for anything that is not a constant
     pub fn from_super_unit(currency: Currency, value: BigDecimal) -> Amount {
-        let exp = match currency {
-            Currency::Btc => 10i64.pow(SATOSHIS_IN_BTC),
-            Currency::Eth => 10i64.pow(WEI_IN_ETH),
-            Currency::Stq => 10i64.pow(WEI_IN_ETH),
-            Currency::Usd => 10i64.pow(CENTS_IN_DOLLAR),
-            Currency::Eur => 10i64.pow(CENTS_IN_DOLLAR),
-            Currency::Rub => 10i64.pow(CENTS_IN_DOLLAR),
-        };
+        Amount::checked_from_super_unit(currency, value).expect("amount is out of range")
+    }
+
+    /// Converts a value in super units, dropping the digits beyond the precision of the currency.
+    /// Returns None on negative values and values that don't fit into `u128`
+    pub fn checked_from_super_unit(currency: Currency, value: BigDecimal) -> Option<Amount> {
+        let exp = 10i64.pow(currency.minor_unit_exponent());
+
+        Amount::checked_from_decimal(value * BigDecimal::from(exp))
+    }
+
+    /// Converts a value in base units, dropping its fractional part.
+    /// Returns None on negative values and values that don't fit into `u128`
+    pub fn checked_from_decimal(value: BigDecimal) -> Option<Amount> {
+        u128::from_str(&value.with_scale(0).to_string()).ok().map(Amount)
+    }
 
-        let decimal = (value * BigDecimal::from(exp)).with_scale(0);
+    /// Returns None for negative quantities
+    pub fn from_quantity(quantity: Quantity) -> Option<Amount> {
+        if quantity.0 < 0 {
+            None
+        } else {
+            Some(Amount(quantity.0 as u128))
+        }
+    }
 
-        Amount(u128::from_str(&decimal.to_string()).unwrap()) // unwrap never panics
+    /// Returns None if the amount doesn't fit into `u64`, e.g. when passing it to Stripe
+    pub fn to_u64(&self) -> Option<u64> {
+        if self.0 > u128::from(std::u64::MAX) {
+            None
+        } else {
+            Some(self.0 as u64)
+        }
     }
 
     pub fn to_super_unit(&self, current_currency: Currency) -> BigDecimal {
-        let exp = match current_currency {
-            Currency::Btc => 10i64.pow(SATOSHIS_IN_BTC),
-            Currency::Eth => 10i64.pow(WEI_IN_ETH),
-            Currency::Stq => 10i64.pow(WEI_IN_ETH),
-            Currency::Usd => 10i64.pow(CENTS_IN_DOLLAR),
-            Currency::Eur => 10i64.pow(CENTS_IN_DOLLAR),
-            Currency::Rub => 10i64.pow(CENTS_IN_DOLLAR),
-        };
+        let exp = 10i64.pow(current_currency.minor_unit_exponent());
 
         let decimal = BigDecimal::from_str(&self.0.to_string()).unwrap() / BigDecimal::from(exp);
 
@@ -115,24 +132,12 @@ impl From<Amount> for BigDecimal {
     }
 }
 
-impl From<Amount> for u64 {
-    fn from(val: Amount) -> Self {
-        val.0 as u64
-    }
-}
-
 impl From<u64> for Amount {
     fn from(val: u64) -> Self {
         Amount(val as u128)
     }
 }
 
-impl From<Quantity> for Amount {
-    fn from(val: Quantity) -> Self {
-        Amount(val.0 as u128)
-    }
-}
-
 impl FromStr for Amount {
     type Err = ParseAmountError;
 
@@ -245,9 +250,11 @@ fn u128_to_pg_decimal(value: u128) -> PgNumeric {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use proptest::prelude::*;
     use serde_json;
 
+    use super::*;
+
     // This thing converts binary postgres representation to PgNumeric
     // All test cases are generated using postgres command
     // psql -U postgres -d <your_db_name> -c 'COPY ( SELECT CAST (34534 AS NUMERIC) ) TO STDOUT WITH ( FORMAT BINARY );' |   od --skip-bytes=25 -h --endian big
@@ -472,4 +479,54 @@ mod tests {
         );
         assert_eq!(Amount::from_super_unit(Currency::Btc, 1.0.into()), Amount(100_000_000u128));
     }
+
+    fn super_unit_currency() -> impl Strategy<Value = Currency> {
+        // precision of these currencies covers their minimal units, so the round trip is exact
+        prop_oneof![Just(Currency::Btc), Just(Currency::Usd), Just(Currency::Eur), Just(Currency::Rub)]
+    }
+
+    proptest! {
+        #[test]
+        fn to_u64_is_exact_or_none(value in any::<u128>()) {
+            match Amount::new(value).to_u64() {
+                Some(converted) => prop_assert_eq!(u128::from(converted), value),
+                None => prop_assert!(value > u128::from(std::u64::MAX)),
+            }
+        }
+
+        #[test]
+        fn super_unit_round_trip_is_exact(value in any::<u128>(), currency in super_unit_currency()) {
+            let amount = Amount::new(value);
+            prop_assert_eq!(Amount::checked_from_super_unit(currency, amount.to_super_unit(currency)), Some(amount));
+        }
+
+        #[test]
+        fn checked_from_decimal_rejects_values_beyond_u128(excess in 1u128..) {
+            let value = BigDecimal::from(Amount::MAX) + BigDecimal::from(Amount::new(excess));
+            prop_assert_eq!(Amount::checked_from_decimal(value), None);
+        }
+
+        #[test]
+        fn checked_from_decimal_rejects_negative_values(value in 1u128..) {
+            prop_assert_eq!(Amount::checked_from_decimal(-BigDecimal::from(Amount::new(value))), None);
+        }
+
+        #[test]
+        fn checked_ops_never_wrap(a in any::<u128>(), b in any::<u128>()) {
+            let (a_decimal, b_decimal) = (BigDecimal::from(Amount::new(a)), BigDecimal::from(Amount::new(b)));
+            let max = BigDecimal::from(Amount::MAX);
+
+            let sum = a_decimal.clone() + b_decimal.clone();
+            match Amount::new(a).checked_add(Amount::new(b)) {
+                Some(amount) => prop_assert_eq!(BigDecimal::from(amount), sum),
+                None => prop_assert!(sum > max),
+            }
+
+            let product = a_decimal * b_decimal;
+            match Amount::new(a).checked_mul(Amount::new(b)) {
+                Some(amount) => prop_assert_eq!(BigDecimal::from(amount), product),
+                None => prop_assert!(product > max),
+            }
+        }
+    }
 }
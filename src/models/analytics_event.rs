@@ -0,0 +1,53 @@
+use std::fmt::{self, Display};
+
+use chrono::{NaiveDateTime, Utc};
+use serde_json;
+use uuid::Uuid;
+
+/// Version of the analytics message format, bumped on incompatible changes of `data`
+pub const ANALYTICS_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyticsEventType {
+    InvoiceCreated,
+    InvoicePaid,
+    InvoiceExpired,
+    FeeCharged,
+    PayoutSent,
+}
+
+impl Display for AnalyticsEventType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AnalyticsEventType::InvoiceCreated => write!(f, "invoice_created"),
+            AnalyticsEventType::InvoicePaid => write!(f, "invoice_paid"),
+            AnalyticsEventType::InvoiceExpired => write!(f, "invoice_expired"),
+            AnalyticsEventType::FeeCharged => write!(f, "fee_charged"),
+            AnalyticsEventType::PayoutSent => write!(f, "payout_sent"),
+        }
+    }
+}
+
+/// Message published to the analytics sink.
+/// Delivery is at least once, consumers deduplicate redelivered messages by `id`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnalyticsEvent {
+    pub id: Uuid,
+    pub schema_version: u32,
+    pub event_type: AnalyticsEventType,
+    pub data: serde_json::Value,
+    pub occurred_at: NaiveDateTime,
+}
+
+impl AnalyticsEvent {
+    pub fn new(event_type: AnalyticsEventType, data: serde_json::Value) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            schema_version: ANALYTICS_SCHEMA_VERSION,
+            event_type,
+            data,
+            occurred_at: Utc::now().naive_utc(),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::fmt::{self, Display};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use diesel::sql_types::Int4 as SqlInt4;
+use hex;
+use serde_json;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use stq_types::{StoreId, UserId};
+
+use schema::api_keys;
+
+/// Prefix of every key, tells billing keys apart from other secrets in logs and configs
+const API_KEY_PREFIX: &str = "bk_";
+/// Length of the start of the key kept in clear to tell keys of a store apart
+const API_KEY_DISPLAY_LENGTH: usize = 11;
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, Default, PartialEq)]
+#[sql_type = "SqlInt4"]
+pub struct ApiKeyId(i32);
+derive_newtype_sql!(api_key_id, SqlInt4, ApiKeyId, ApiKeyId);
+
+impl ApiKeyId {
+    pub fn new(id: i32) -> Self {
+        ApiKeyId(id)
+    }
+
+    pub fn inner(&self) -> &i32 {
+        &self.0
+    }
+}
+
+impl FromStr for ApiKeyId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s.parse()?;
+        Ok(ApiKeyId::new(id))
+    }
+}
+
+impl Display for ApiKeyId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0,))
+    }
+}
+
+/// Billing data of its store a key gives access to
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    Orders,
+    Invoices,
+    Balance,
+    Payouts,
+    FeeStatements,
+}
+
+/// Key of a store for server-to-server requests. Only the SHA-256 of the key is stored,
+/// the key itself is shown once when it is created. `scopes` is a list of `ApiKeyScope`
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct ApiKey {
+    pub id: ApiKeyId,
+    pub store_id: StoreId,
+    /// Store manager the requests made with the key act for
+    pub created_by: UserId,
+    pub name: String,
+    pub key_prefix: String,
+    pub key_hash: String,
+    pub scopes: serde_json::Value,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl ApiKey {
+    pub fn scopes(&self) -> Result<Vec<ApiKeyScope>, serde_json::Error> {
+        serde_json::from_value(self.scopes.clone())
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "api_keys"]
+pub struct NewApiKey {
+    pub store_id: StoreId,
+    pub created_by: UserId,
+    pub name: String,
+    pub key_prefix: String,
+    pub key_hash: String,
+    pub scopes: serde_json::Value,
+}
+
+/// Generates a new random key, returns the key and its prefix kept in clear
+pub fn generate_api_key() -> (String, String) {
+    let key = format!("{}{}{}", API_KEY_PREFIX, Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let key_prefix = key[..API_KEY_DISPLAY_LENGTH].to_string();
+    (key, key_prefix)
+}
+
+/// Hex encoded SHA-256 of the key, keys are random enough for a plain hash to be safe against guessing
+pub fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_keys_are_unique_and_hashed_deterministically() {
+        let (key, key_prefix) = generate_api_key();
+        let (other_key, _) = generate_api_key();
+
+        assert!(key.starts_with(API_KEY_PREFIX));
+        assert!(key.starts_with(&key_prefix));
+        assert_eq!(key_prefix.len(), API_KEY_DISPLAY_LENGTH);
+        assert_ne!(key, other_key);
+        assert_eq!(hash_api_key(&key), hash_api_key(&key));
+        assert_ne!(hash_api_key(&key), hash_api_key(&other_key));
+        assert_eq!(hash_api_key(&key).len(), 64);
+    }
+}
@@ -0,0 +1,241 @@
+use std::fmt::{self, Display};
+
+use chrono::NaiveDateTime;
+use diesel::sql_types::BigInt;
+use serde_json;
+
+use stq_types::{StoreId, UserId};
+
+use models::invoice_v2::InvoiceId;
+use models::order_v2::OrderId;
+use models::{AccountId, DataSubject, Feature, PayoutId};
+use schema::audit_log;
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, PartialEq, Eq, FromStr, Display)]
+#[sql_type = "BigInt"]
+pub struct AuditLogEntryId(i64);
+newtype_from_to_sql!(BigInt, AuditLogEntryId, AuditLogEntryId);
+
+impl AuditLogEntryId {
+    pub fn new(id: i64) -> Self {
+        AuditLogEntryId(id)
+    }
+
+    pub fn inner(&self) -> i64 {
+        self.0
+    }
+}
+
+/// Manual mutation performed through an admin endpoint
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    OrderPaymentStateChanged,
+    UserRoleGranted,
+    UserRoleRevoked,
+    PayoutCreated,
+    AccountArchived,
+    SystemAccountsTransferred,
+    TrialExtended,
+    TrialEnded,
+    StoreBillingStatusOverridden,
+    StoreReinstated,
+    FeatureFlagUpdated,
+    LegalHoldUpdated,
+    PayoutRestrictionOverridden,
+    PayoutRestrictionOverrideRemoved,
+    InvoiceRiskReviewed,
+    UserCreditGranted,
+    OrderLineItemsRefunded,
+}
+
+impl Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuditAction::OrderPaymentStateChanged => f.write_str("order_payment_state_changed"),
+            AuditAction::UserRoleGranted => f.write_str("user_role_granted"),
+            AuditAction::UserRoleRevoked => f.write_str("user_role_revoked"),
+            AuditAction::PayoutCreated => f.write_str("payout_created"),
+            AuditAction::AccountArchived => f.write_str("account_archived"),
+            AuditAction::SystemAccountsTransferred => f.write_str("system_accounts_transferred"),
+            AuditAction::TrialExtended => f.write_str("trial_extended"),
+            AuditAction::TrialEnded => f.write_str("trial_ended"),
+            AuditAction::StoreBillingStatusOverridden => f.write_str("store_billing_status_overridden"),
+            AuditAction::StoreReinstated => f.write_str("store_reinstated"),
+            AuditAction::FeatureFlagUpdated => f.write_str("feature_flag_updated"),
+            AuditAction::LegalHoldUpdated => f.write_str("legal_hold_updated"),
+            AuditAction::PayoutRestrictionOverridden => f.write_str("payout_restriction_overridden"),
+            AuditAction::PayoutRestrictionOverrideRemoved => f.write_str("payout_restriction_override_removed"),
+            AuditAction::InvoiceRiskReviewed => f.write_str("invoice_risk_reviewed"),
+            AuditAction::UserCreditGranted => f.write_str("user_credit_granted"),
+            AuditAction::OrderLineItemsRefunded => f.write_str("order_line_items_refunded"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditResourceType {
+    Order,
+    UserRoles,
+    Payout,
+    Account,
+    StoreSubscription,
+    StoreBillingStatus,
+    FeatureFlag,
+    DataRetentionSubject,
+    PayoutRestrictionOverride,
+    InvoiceRiskAssessment,
+    UserCredit,
+}
+
+impl Display for AuditResourceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuditResourceType::Order => f.write_str("order"),
+            AuditResourceType::UserRoles => f.write_str("user_roles"),
+            AuditResourceType::Payout => f.write_str("payout"),
+            AuditResourceType::Account => f.write_str("account"),
+            AuditResourceType::StoreSubscription => f.write_str("store_subscription"),
+            AuditResourceType::StoreBillingStatus => f.write_str("store_billing_status"),
+            AuditResourceType::FeatureFlag => f.write_str("feature_flag"),
+            AuditResourceType::DataRetentionSubject => f.write_str("data_retention_subject"),
+            AuditResourceType::PayoutRestrictionOverride => f.write_str("payout_restriction_override"),
+            AuditResourceType::InvoiceRiskAssessment => f.write_str("invoice_risk_assessment"),
+            AuditResourceType::UserCredit => f.write_str("user_credit"),
+        }
+    }
+}
+
+/// Resource affected by an audited mutation
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AuditResource {
+    pub resource_type: AuditResourceType,
+    pub resource_id: String,
+}
+
+impl AuditResource {
+    pub fn order(order_id: OrderId) -> Self {
+        AuditResource {
+            resource_type: AuditResourceType::Order,
+            resource_id: order_id.to_string(),
+        }
+    }
+
+    pub fn user_roles(user_id: UserId) -> Self {
+        AuditResource {
+            resource_type: AuditResourceType::UserRoles,
+            resource_id: user_id.to_string(),
+        }
+    }
+
+    pub fn payout(payout_id: PayoutId) -> Self {
+        AuditResource {
+            resource_type: AuditResourceType::Payout,
+            resource_id: payout_id.to_string(),
+        }
+    }
+
+    pub fn account(account_id: AccountId) -> Self {
+        AuditResource {
+            resource_type: AuditResourceType::Account,
+            resource_id: account_id.to_string(),
+        }
+    }
+
+    pub fn store_subscription(store_id: StoreId) -> Self {
+        AuditResource {
+            resource_type: AuditResourceType::StoreSubscription,
+            resource_id: store_id.to_string(),
+        }
+    }
+
+    pub fn store_billing_status(store_id: StoreId) -> Self {
+        AuditResource {
+            resource_type: AuditResourceType::StoreBillingStatus,
+            resource_id: store_id.to_string(),
+        }
+    }
+
+    pub fn feature_flag(feature: Feature) -> Self {
+        AuditResource {
+            resource_type: AuditResourceType::FeatureFlag,
+            resource_id: feature.to_string(),
+        }
+    }
+
+    pub fn data_retention_subject(subject: DataSubject) -> Self {
+        AuditResource {
+            resource_type: AuditResourceType::DataRetentionSubject,
+            resource_id: subject.to_string(),
+        }
+    }
+
+    pub fn payout_restriction_override(store_id: StoreId) -> Self {
+        AuditResource {
+            resource_type: AuditResourceType::PayoutRestrictionOverride,
+            resource_id: store_id.to_string(),
+        }
+    }
+
+    pub fn invoice_risk_assessment(invoice_id: InvoiceId) -> Self {
+        AuditResource {
+            resource_type: AuditResourceType::InvoiceRiskAssessment,
+            resource_id: invoice_id.to_string(),
+        }
+    }
+
+    pub fn user_credit(user_id: UserId) -> Self {
+        AuditResource {
+            resource_type: AuditResourceType::UserCredit,
+            resource_id: user_id.to_string(),
+        }
+    }
+}
+
+impl Display for AuditResource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.resource_type, self.resource_id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+pub struct AuditLogEntry {
+    pub id: AuditLogEntryId,
+    pub actor_user_id: Option<UserId>,
+    pub action: AuditAction,
+    pub resource_type: AuditResourceType,
+    pub resource_id: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[table_name = "audit_log"]
+pub struct NewAuditLogEntry {
+    pub actor_user_id: Option<UserId>,
+    pub action: AuditAction,
+    pub resource_type: AuditResourceType,
+    pub resource_id: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+/// Filters for the audit log. `text` is matched against the before/after snapshots
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditLogSearch {
+    pub actor_user_id: Option<UserId>,
+    pub action: Option<AuditAction>,
+    pub resource_type: Option<AuditResourceType>,
+    pub resource_id: Option<String>,
+    pub created_from: Option<NaiveDateTime>,
+    pub created_to: Option<NaiveDateTime>,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditLogSearchResults {
+    pub total_count: i64,
+    pub entries: Vec<AuditLogEntry>,
+}
@@ -5,23 +5,57 @@ use std::fmt;
 pub enum Resource {
     Account,
     BillingInfo,
+    BillingInfoSecrets,
     OrderInfo,
     UserRoles,
     Invoice,
+    InvoiceCallback,
+    InvoiceRequote,
     OrderExchangeRate,
     PaymentIntent,
+    PaymentIntentHistory,
     ProxyCompanyBillingInfo,
     StoreBillingType,
+    StoreBillingStatus,
     Subscription,
     StoreSubscription,
     StoreSubscriptionStatus,
+    StoreWebhook,
     SubscriptionPayment,
     Customer,
+    FeatureFlag,
     Fee,
+    FeeAdjustment,
+    FeeCryptoPayment,
+    FeeStatement,
     PaymentIntentInvoice,
     PaymentIntentFee,
+    PaymentRecovery,
     UserWallet,
     Payout,
+    PayoutInstruction,
+    StripeFeeBackfill,
+    OrderCaptureApproval,
+    AuditLog,
+    CashbackLiability,
+    NegativeStoreBalance,
+    ExchangeRateSlippage,
+    PayoutStatement,
+    SchemaMigration,
+    BillingInfoChange,
+    PaymentAttempt,
+    ApiKey,
+    DataRetention,
+    InvoiceSnapshot,
+    FeeDunning,
+    PayoutRestriction,
+    CustomerExport,
+    InvoiceRiskAssessment,
+    StoreOffboarding,
+    HistoricalReport,
+    UserCredit,
+    OrderLineItem,
+    InvoiceBuyerDetails,
 }
 
 impl fmt::Display for Resource {
@@ -31,21 +65,55 @@ impl fmt::Display for Resource {
             Resource::OrderInfo => write!(f, "order info"),
             Resource::UserRoles => write!(f, "user roles"),
             Resource::Invoice => write!(f, "invoice"),
+            Resource::InvoiceCallback => write!(f, "invoice callback"),
+            Resource::InvoiceRequote => write!(f, "invoice requote"),
             Resource::BillingInfo => write!(f, "billing info"),
+            Resource::BillingInfoSecrets => write!(f, "billing info secrets"),
             Resource::OrderExchangeRate => write!(f, "order exchange rate"),
             Resource::PaymentIntent => write!(f, "payment intent"),
+            Resource::PaymentIntentHistory => write!(f, "payment intent history"),
             Resource::ProxyCompanyBillingInfo => write!(f, "proxy company billing info"),
             Resource::StoreBillingType => write!(f, "store billing type"),
+            Resource::StoreBillingStatus => write!(f, "store billing status"),
             Resource::Subscription => write!(f, "subscription"),
             Resource::StoreSubscription => write!(f, "store subscription"),
             Resource::StoreSubscriptionStatus => write!(f, "store subscription status"),
+            Resource::StoreWebhook => write!(f, "store webhook"),
             Resource::SubscriptionPayment => write!(f, "subscription payment"),
             Resource::Customer => write!(f, "customer"),
+            Resource::FeatureFlag => write!(f, "feature flag"),
             Resource::Fee => write!(f, "fee"),
+            Resource::FeeAdjustment => write!(f, "fee adjustment"),
+            Resource::FeeCryptoPayment => write!(f, "fee crypto payment"),
+            Resource::FeeStatement => write!(f, "fee statement"),
             Resource::PaymentIntentInvoice => write!(f, "payment_intent_invoice"),
             Resource::PaymentIntentFee => write!(f, "payment_intent_fee"),
+            Resource::PaymentRecovery => write!(f, "payment recovery"),
             Resource::UserWallet => write!(f, "user wallet"),
             Resource::Payout => write!(f, "payout"),
+            Resource::PayoutInstruction => write!(f, "payout instruction"),
+            Resource::StripeFeeBackfill => write!(f, "stripe fee backfill"),
+            Resource::OrderCaptureApproval => write!(f, "order capture approval"),
+            Resource::AuditLog => write!(f, "audit log"),
+            Resource::CashbackLiability => write!(f, "cashback liability"),
+            Resource::NegativeStoreBalance => write!(f, "negative store balance"),
+            Resource::ExchangeRateSlippage => write!(f, "exchange rate slippage"),
+            Resource::PayoutStatement => write!(f, "payout statement"),
+            Resource::SchemaMigration => write!(f, "schema migration"),
+            Resource::BillingInfoChange => write!(f, "billing info change"),
+            Resource::PaymentAttempt => write!(f, "payment attempt"),
+            Resource::ApiKey => write!(f, "api key"),
+            Resource::DataRetention => write!(f, "data retention"),
+            Resource::InvoiceSnapshot => write!(f, "invoice snapshot"),
+            Resource::FeeDunning => write!(f, "fee dunning"),
+            Resource::PayoutRestriction => write!(f, "payout restriction"),
+            Resource::CustomerExport => write!(f, "customer export"),
+            Resource::InvoiceRiskAssessment => write!(f, "invoice risk assessment"),
+            Resource::StoreOffboarding => write!(f, "store offboarding"),
+            Resource::HistoricalReport => write!(f, "historical report"),
+            Resource::UserCredit => write!(f, "user credit"),
+            Resource::OrderLineItem => write!(f, "order line item"),
+            Resource::InvoiceBuyerDetails => write!(f, "invoice buyer details"),
         }
     }
 }
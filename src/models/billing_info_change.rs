@@ -0,0 +1,174 @@
+use std::fmt::{self, Display};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use diesel::sql_types::Int4 as SqlInt4;
+
+use stq_types::{InternationalBillingId, RussiaBillingId, StoreId, SwiftId, UserId};
+
+use models::masking::mask;
+use models::{NewInternationalBillingInfo, NewRussiaBillingInfo, UpdateInternationalBillingInfo, UpdateRussiaBillingInfo};
+use schema::billing_info_changes;
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, Default, PartialEq)]
+#[sql_type = "SqlInt4"]
+pub struct BillingInfoChangeId(i32);
+derive_newtype_sql!(billing_info_change_id, SqlInt4, BillingInfoChangeId, BillingInfoChangeId);
+
+impl BillingInfoChangeId {
+    pub fn new(id: i32) -> Self {
+        BillingInfoChangeId(id)
+    }
+
+    pub fn inner(&self) -> &i32 {
+        &self.0
+    }
+}
+
+impl FromStr for BillingInfoChangeId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s.parse()?;
+        Ok(BillingInfoChangeId::new(id))
+    }
+}
+
+impl Display for BillingInfoChangeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0,))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum BillingInfoChangeStatus {
+    /// Waiting for a financial manager, payouts keep using the billing info as it was before the change
+    Pending,
+    /// Applied to the billing info of the store
+    Approved,
+    /// Discarded by a financial manager
+    Rejected,
+}
+
+/// Billing info of a store as requested to be created or updated
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BillingInfoChangePayload {
+    CreateInternational {
+        billing_info: NewInternationalBillingInfo,
+    },
+    UpdateInternational {
+        id: InternationalBillingId,
+        billing_info: UpdateInternationalBillingInfo,
+    },
+    CreateRussia {
+        billing_info: NewRussiaBillingInfo,
+    },
+    UpdateRussia {
+        id: RussiaBillingId,
+        billing_info: UpdateRussiaBillingInfo,
+    },
+}
+
+impl BillingInfoChangePayload {
+    /// Payload with bank account details hidden
+    pub fn masked(self) -> Self {
+        let mask_swift = |swift: SwiftId| SwiftId(mask(&swift.0));
+
+        match self {
+            BillingInfoChangePayload::CreateInternational { billing_info } => BillingInfoChangePayload::CreateInternational {
+                billing_info: NewInternationalBillingInfo {
+                    account: mask(&billing_info.account),
+                    swift: mask_swift(billing_info.swift.clone()),
+                    ..billing_info
+                },
+            },
+            BillingInfoChangePayload::UpdateInternational { id, billing_info } => BillingInfoChangePayload::UpdateInternational {
+                id,
+                billing_info: UpdateInternationalBillingInfo {
+                    account: billing_info.account.as_ref().map(|account| mask(account)),
+                    swift: billing_info.swift.clone().map(mask_swift),
+                    ..billing_info
+                },
+            },
+            BillingInfoChangePayload::CreateRussia { billing_info } => BillingInfoChangePayload::CreateRussia {
+                billing_info: NewRussiaBillingInfo {
+                    swift_bic: mask_swift(billing_info.swift_bic.clone()),
+                    correspondent_account: mask(&billing_info.correspondent_account),
+                    current_account: mask(&billing_info.current_account),
+                    personal_account: billing_info.personal_account.as_ref().map(|account| mask(account)),
+                    ..billing_info
+                },
+            },
+            BillingInfoChangePayload::UpdateRussia { id, billing_info } => BillingInfoChangePayload::UpdateRussia {
+                id,
+                billing_info: UpdateRussiaBillingInfo {
+                    swift_bic: billing_info.swift_bic.clone().map(mask_swift),
+                    correspondent_account: billing_info.correspondent_account.as_ref().map(|account| mask(account)),
+                    current_account: billing_info.current_account.as_ref().map(|account| mask(account)),
+                    personal_account: billing_info.personal_account.as_ref().map(|account| mask(account)),
+                    ..billing_info
+                },
+            },
+        }
+    }
+}
+
+/// Requested change of the billing info of a store. Changes of store managers wait for a financial manager,
+/// changes of superusers are recorded approved by the requester
+#[derive(Clone, Debug, Serialize)]
+pub struct BillingInfoChange {
+    pub id: BillingInfoChangeId,
+    pub store_id: StoreId,
+    pub payload: BillingInfoChangePayload,
+    pub status: BillingInfoChangeStatus,
+    pub requested_by: UserId,
+    pub reviewed_by: Option<UserId>,
+    pub rejection_reason: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub reviewed_at: Option<NaiveDateTime>,
+}
+
+/// Billing info change as stored, `payload` is the encrypted JSON of `BillingInfoChangePayload`
+#[derive(Clone, Debug, Queryable)]
+pub struct RawBillingInfoChange {
+    pub id: BillingInfoChangeId,
+    pub store_id: StoreId,
+    pub payload: String,
+    pub status: BillingInfoChangeStatus,
+    pub requested_by: UserId,
+    pub reviewed_by: Option<UserId>,
+    pub rejection_reason: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub reviewed_at: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NewBillingInfoChange {
+    pub store_id: StoreId,
+    pub payload: BillingInfoChangePayload,
+    pub status: BillingInfoChangeStatus,
+    pub requested_by: UserId,
+    pub reviewed_by: Option<UserId>,
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[table_name = "billing_info_changes"]
+pub struct NewRawBillingInfoChange {
+    pub store_id: StoreId,
+    pub payload: String,
+    pub status: BillingInfoChangeStatus,
+    pub requested_by: UserId,
+    pub reviewed_by: Option<UserId>,
+    pub reviewed_at: Option<NaiveDateTime>,
+}
+
+/// Decision of a financial manager on a pending change
+#[derive(Clone, Debug)]
+pub struct BillingInfoChangeReview {
+    pub status: BillingInfoChangeStatus,
+    pub reviewed_by: UserId,
+    pub rejection_reason: Option<String>,
+}
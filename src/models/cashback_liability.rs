@@ -0,0 +1,54 @@
+use chrono::NaiveDateTime;
+use diesel::sql_types::{BigInt, Numeric, VarChar};
+
+use models::{Amount, Currency};
+use schema::cashback_liability_snapshots;
+
+/// Cashback accrued on paid invoices in a single currency. Redemptions are not recorded by the billing,
+/// so all of the accrued cashback is outstanding
+#[derive(Clone, Debug, QueryableByName)]
+pub struct CashbackLiability {
+    #[sql_type = "VarChar"]
+    pub currency: Currency,
+    #[sql_type = "Numeric"]
+    pub amount: Amount,
+    #[sql_type = "BigInt"]
+    pub invoices_count: i64,
+}
+
+/// Outstanding cashback in a single currency as of the start of a month, kept for audit
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct CashbackLiabilitySnapshot {
+    pub id: i32,
+    pub currency: Currency,
+    pub as_of: NaiveDateTime,
+    pub amount: Amount,
+    pub invoices_count: i64,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "cashback_liability_snapshots"]
+pub struct NewCashbackLiabilitySnapshot {
+    pub currency: Currency,
+    pub as_of: NaiveDateTime,
+    pub amount: Amount,
+    pub invoices_count: i64,
+}
+
+impl NewCashbackLiabilitySnapshot {
+    pub fn new(as_of: NaiveDateTime, liability: CashbackLiability) -> Self {
+        let CashbackLiability {
+            currency,
+            amount,
+            invoices_count,
+        } = liability;
+
+        Self {
+            currency,
+            as_of,
+            amount,
+            invoices_count,
+        }
+    }
+}
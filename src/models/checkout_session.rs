@@ -0,0 +1,126 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use stq_static_resources::OrderState;
+
+use models::invoice_v2::{InvoiceDump, InvoiceId};
+use models::{Currency, PaymentIntent, PaymentIntentStatus, WalletAddress};
+
+/// The way the buyer pays for the invoice
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckoutPaymentMethod {
+    /// Transfer to a pooled account in the Payments gateway
+    CryptoWallet,
+    /// Card payment confirmed with a Stripe payment intent
+    Card,
+}
+
+impl CheckoutPaymentMethod {
+    /// The way invoices in `currency` are paid
+    pub fn for_currency(currency: Currency) -> Self {
+        if currency.is_fiat() {
+            CheckoutPaymentMethod::Card
+        } else {
+            CheckoutPaymentMethod::CryptoWallet
+        }
+    }
+}
+
+/// What the storefront needs to start the payment
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CheckoutPaymentTarget {
+    WalletAddress { wallet_address: WalletAddress },
+    ClientSecret { client_secret: String },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckoutSessionStatus {
+    /// Waiting for the buyer to pay
+    Open,
+    /// Payment has been received partially or is being confirmed
+    Processing,
+    Paid,
+    Expired,
+    Failed,
+}
+
+/// Payment flow independent view of an invoice
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckoutSession {
+    pub invoice_id: InvoiceId,
+    pub payment_method: CheckoutPaymentMethod,
+    pub payment_target: Option<CheckoutPaymentTarget>,
+    pub currency: Currency,
+    pub amount: BigDecimal,
+    pub amount_captured: BigDecimal,
+    pub status: CheckoutSessionStatus,
+    pub expires_at: NaiveDateTime,
+    pub status_url: String,
+}
+
+impl CheckoutSession {
+    pub fn new(
+        invoice: &InvoiceDump,
+        payment_intent: Option<&PaymentIntent>,
+        expires_at: NaiveDateTime,
+        now: NaiveDateTime,
+        status_url: String,
+    ) -> Self {
+        let payment_method = CheckoutPaymentMethod::for_currency(invoice.buyer_currency);
+
+        let payment_target = match payment_method {
+            CheckoutPaymentMethod::CryptoWallet => invoice
+                .wallet_address
+                .clone()
+                .map(|wallet_address| CheckoutPaymentTarget::WalletAddress { wallet_address }),
+            CheckoutPaymentMethod::Card => payment_intent
+                .and_then(|payment_intent| payment_intent.client_secret.clone())
+                .map(|client_secret| CheckoutPaymentTarget::ClientSecret { client_secret }),
+        };
+
+        let status = checkout_session_status(invoice, payment_intent, expires_at, now);
+
+        CheckoutSession {
+            invoice_id: invoice.id,
+            payment_method,
+            payment_target,
+            currency: invoice.buyer_currency,
+            amount: invoice.total_price.clone(),
+            amount_captured: invoice.amount_captured.clone(),
+            status,
+            expires_at,
+            status_url,
+        }
+    }
+}
+
+fn checkout_session_status(
+    invoice: &InvoiceDump,
+    payment_intent: Option<&PaymentIntent>,
+    expires_at: NaiveDateTime,
+    now: NaiveDateTime,
+) -> CheckoutSessionStatus {
+    if invoice.paid_at.is_some() || invoice.status == OrderState::Paid {
+        return CheckoutSessionStatus::Paid;
+    }
+
+    if invoice.status == OrderState::AmountExpired {
+        return CheckoutSessionStatus::Expired;
+    }
+
+    let processing = match payment_intent.map(|payment_intent| &payment_intent.status) {
+        Some(PaymentIntentStatus::Canceled) => return CheckoutSessionStatus::Failed,
+        Some(PaymentIntentStatus::Processing) | Some(PaymentIntentStatus::RequiresCapture) | Some(PaymentIntentStatus::Succeeded) => true,
+        _ => invoice.status == OrderState::TransactionPending || invoice.amount_captured != BigDecimal::from(0),
+    };
+
+    if processing {
+        CheckoutSessionStatus::Processing
+    } else if now >= expires_at {
+        CheckoutSessionStatus::Expired
+    } else {
+        CheckoutSessionStatus::Open
+    }
+}
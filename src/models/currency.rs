@@ -10,6 +10,8 @@ use enum_iterator::IntoEnumIterator;
 use failure::Fail;
 use stq_static_resources::Currency as StqCurrency;
 
+use models::amount::{CENTS_IN_DOLLAR, SATOSHIS_IN_BTC, WEI_IN_ETH};
+
 #[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, Eq, PartialEq, Hash, IntoEnumIterator)]
 #[sql_type = "VarChar"]
 #[serde(rename_all = "lowercase")]
@@ -104,6 +106,15 @@ impl Currency {
         }
     }
 
+    /// Number of decimal places between the currency and its minor unit, e.g. 8 for satoshis in a bitcoin
+    pub fn minor_unit_exponent(self) -> u32 {
+        match self {
+            Currency::Btc => SATOSHIS_IN_BTC,
+            Currency::Eth | Currency::Stq => WEI_IN_ETH,
+            Currency::Usd | Currency::Eur | Currency::Rub => CENTS_IN_DOLLAR,
+        }
+    }
+
     pub fn is_fiat(self) -> bool {
         use self::CurrencyChoice::*;
 
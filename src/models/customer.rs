@@ -6,13 +6,16 @@ use stq_types::UserId;
 use controller::requests::UpdateCustomerRequest;
 use schema::customers;
 
-#[derive(Clone, Debug, Deserialize, Serialize, Queryable)]
+#[derive(Clone, Debug, Deserialize, Serialize, Queryable, QueryableByName)]
+#[table_name = "customers"]
 pub struct DbCustomer {
     pub id: CustomerId,
     pub user_id: UserId,
     pub email: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    /// Payment method saved with a SetupIntent, used instead of the legacy card source
+    pub payment_method_id: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, Queryable, Insertable)]
@@ -27,6 +30,7 @@ pub struct NewDbCustomer {
 #[table_name = "customers"]
 pub struct UpdateDbCustomer {
     pub email: Option<String>,
+    pub payment_method_id: Option<String>,
 }
 
 pub struct CustomersAccess {
@@ -49,6 +53,9 @@ impl<'r> From<&'r DbCustomer> for CustomersAccess {
 
 impl From<UpdateCustomerRequest> for UpdateDbCustomer {
     fn from(payload: UpdateCustomerRequest) -> UpdateDbCustomer {
-        UpdateDbCustomer { email: payload.email }
+        UpdateDbCustomer {
+            email: payload.email,
+            ..Default::default()
+        }
     }
 }
@@ -0,0 +1,107 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use diesel::sql_types::{BigInt, Integer, Nullable, Numeric, Timestamp, VarChar};
+
+use stq_types::UserId;
+
+use models::{Amount, Currency, CustomerId};
+use schema::customer_exports;
+
+/// Export of the customers to the storage for the CRM sync
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct CustomerExport {
+    pub id: i32,
+    /// Whether only the customers changed after `updated_after` were exported
+    pub incremental: bool,
+    pub updated_after: Option<NaiveDateTime>,
+    /// Watermark of the export, the next incremental export picks the changes made after it
+    pub updated_until: NaiveDateTime,
+    pub object_key: String,
+    pub records_count: i32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "customer_exports"]
+pub struct NewCustomerExport {
+    pub incremental: bool,
+    pub updated_after: Option<NaiveDateTime>,
+    pub updated_until: NaiveDateTime,
+    pub object_key: String,
+    pub records_count: i32,
+}
+
+/// Spend of a user in a single currency, a user without paid invoices has a single row without a currency
+#[derive(Clone, Debug, QueryableByName)]
+pub struct CustomerSpendRow {
+    #[sql_type = "Integer"]
+    pub user_id: UserId,
+    #[sql_type = "Nullable<VarChar>"]
+    pub customer_id: Option<CustomerId>,
+    #[sql_type = "BigInt"]
+    pub cards_count: i64,
+    #[sql_type = "Nullable<VarChar>"]
+    pub currency: Option<Currency>,
+    #[sql_type = "Nullable<Numeric>"]
+    pub amount: Option<Amount>,
+    #[sql_type = "Nullable<Timestamp>"]
+    pub last_paid_at: Option<NaiveDateTime>,
+}
+
+/// Customer and payment summary of a user as exported for the CRM
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CustomerExportRecord {
+    pub user_id: UserId,
+    pub customer_id: Option<CustomerId>,
+    /// Distinct cards the user has tried to pay with
+    pub cards_count: i64,
+    pub lifetime_spend: Vec<CurrencySpend>,
+    pub last_payment_at: Option<NaiveDateTime>,
+}
+
+/// Amount paid by a user in a single currency, in super units
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CurrencySpend {
+    pub currency: Currency,
+    pub amount: BigDecimal,
+}
+
+impl CustomerExportRecord {
+    /// Groups the rows ordered by the user into records, in the same order
+    pub fn from_rows(rows: Vec<CustomerSpendRow>) -> Vec<CustomerExportRecord> {
+        let mut records: Vec<CustomerExportRecord> = Vec::new();
+
+        for row in rows {
+            let CustomerSpendRow {
+                user_id,
+                customer_id,
+                cards_count,
+                currency,
+                amount,
+                last_paid_at,
+            } = row;
+
+            let is_new_user = records.last().map_or(true, |record| record.user_id != user_id);
+            if is_new_user {
+                records.push(CustomerExportRecord {
+                    user_id,
+                    customer_id,
+                    cards_count,
+                    lifetime_spend: Vec::new(),
+                    last_payment_at: None,
+                });
+            }
+
+            let record = records.last_mut().expect("record of the user is pushed above");
+            if let (Some(currency), Some(amount)) = (currency, amount) {
+                record.lifetime_spend.push(CurrencySpend {
+                    currency,
+                    amount: amount.to_super_unit(currency),
+                });
+            }
+            record.last_payment_at = record.last_payment_at.max(last_paid_at);
+        }
+
+        records
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt::{self, Display};
+
+use chrono::NaiveDateTime;
+
+use stq_types::{StoreId, UserId};
+
+use schema::data_retention_subjects;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum DataSubjectType {
+    Store,
+    User,
+}
+
+impl Display for DataSubjectType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataSubjectType::Store => f.write_str("store"),
+            DataSubjectType::User => f.write_str("user"),
+        }
+    }
+}
+
+/// Owner of personal data kept by billing
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash)]
+#[serde(tag = "subject_type", content = "subject_id", rename_all = "snake_case")]
+pub enum DataSubject {
+    Store(StoreId),
+    User(UserId),
+}
+
+impl DataSubject {
+    pub fn subject_type(&self) -> DataSubjectType {
+        match self {
+            DataSubject::Store(_) => DataSubjectType::Store,
+            DataSubject::User(_) => DataSubjectType::User,
+        }
+    }
+
+    pub fn subject_id(&self) -> i32 {
+        match self {
+            DataSubject::Store(store_id) => store_id.0,
+            DataSubject::User(user_id) => user_id.0,
+        }
+    }
+}
+
+impl Display for DataSubject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.subject_type(), self.subject_id())
+    }
+}
+
+/// Personal data purged by a retention rule of its own
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionTable {
+    /// Russian billing info of closed stores, deleted
+    RussiaBillingInfo,
+    /// International billing info of closed stores, deleted
+    InternationalBillingInfo,
+    /// Requested billing info changes of closed stores, deleted
+    BillingInfoChanges,
+    /// Emails of the Stripe customers of deleted users, cleared
+    CustomerEmails,
+    /// Billing details deleted users gave with their invoices, deleted
+    InvoiceBuyerDetails,
+}
+
+impl RetentionTable {
+    /// Owners of the data in the table, the rule applies to them once they are closed
+    pub fn subject_type(&self) -> DataSubjectType {
+        match self {
+            RetentionTable::RussiaBillingInfo | RetentionTable::InternationalBillingInfo | RetentionTable::BillingInfoChanges => {
+                DataSubjectType::Store
+            }
+            RetentionTable::CustomerEmails | RetentionTable::InvoiceBuyerDetails => DataSubjectType::User,
+        }
+    }
+}
+
+impl Display for RetentionTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RetentionTable::RussiaBillingInfo => f.write_str("russia_billing_info"),
+            RetentionTable::InternationalBillingInfo => f.write_str("international_billing_info"),
+            RetentionTable::BillingInfoChanges => f.write_str("billing_info_changes"),
+            RetentionTable::CustomerEmails => f.write_str("customer_emails"),
+            RetentionTable::InvoiceBuyerDetails => f.write_str("invoice_buyer_details"),
+        }
+    }
+}
+
+/// Retention state of a data subject. Its personal data is kept while `closed_at` is not set or `legal_hold` is on
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+pub struct DataRetentionSubject {
+    pub id: i32,
+    pub subject_type: DataSubjectType,
+    pub subject_id: i32,
+    pub closed_at: Option<NaiveDateTime>,
+    pub legal_hold: bool,
+    pub legal_hold_reason: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[table_name = "data_retention_subjects"]
+pub struct NewDataRetentionSubject {
+    pub subject_type: DataSubjectType,
+    pub subject_id: i32,
+    pub closed_at: Option<NaiveDateTime>,
+    pub legal_hold: bool,
+    pub legal_hold_reason: Option<String>,
+}
+
+impl NewDataRetentionSubject {
+    pub fn new(subject: DataSubject) -> Self {
+        NewDataRetentionSubject {
+            subject_type: subject.subject_type(),
+            subject_id: subject.subject_id(),
+            closed_at: None,
+            legal_hold: false,
+            legal_hold_reason: None,
+        }
+    }
+}
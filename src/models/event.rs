@@ -1,11 +1,18 @@
+use chrono::NaiveDateTime;
 use diesel::sql_types::Uuid as SqlUuid;
 use std::fmt;
+use stq_types::stripe::PaymentIntentId;
+use stq_types::{InternationalBillingId, RussiaBillingId, StoreId};
 use stripe::PaymentIntent;
 use uuid::Uuid;
 
 use models::invoice_v2::InvoiceId;
 use models::order_v2::OrderId;
-use models::PayoutId;
+use models::{
+    AnalyticsEvent, BillingInfoChangeId, DunningAction, FeeCryptoPaymentId, FeeStatementId, InvoiceCallbackId, InvoiceCallbackNotification,
+    NegativeStoreBalanceId, OrderStateUpdate, PaymentRecoveryId, PayoutId, SetupIntent, StoreWebhookId, StoreWebhookNotification,
+    StripeFeeBackfillId, WalletVerificationId,
+};
 
 #[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, PartialEq, Eq, FromStr)]
 #[sql_type = "SqlUuid"]
@@ -47,16 +54,38 @@ impl Event {
     }
 }
 
+/// `event_created_at` of the payment intent events is the creation time of the Stripe event, it is absent
+/// in the events stored before it was recorded and is deserialized as `None` then
 #[derive(Clone, Serialize, Deserialize)]
 pub enum EventPayload {
     NoOp,
     InvoicePaid { invoice_id: InvoiceId },
-    PaymentIntentPaymentFailed { payment_intent: PaymentIntent },
-    PaymentIntentAmountCapturableUpdated { payment_intent: PaymentIntent },
-    PaymentIntentSucceeded { payment_intent: PaymentIntent },
+    PaymentIntentPaymentFailed { payment_intent: PaymentIntent, event_created_at: Option<NaiveDateTime> },
+    PaymentIntentAmountCapturableUpdated { payment_intent: PaymentIntent, event_created_at: Option<NaiveDateTime> },
+    PaymentIntentSucceeded { payment_intent: PaymentIntent, event_created_at: Option<NaiveDateTime> },
     PaymentIntentCapture { order_id: OrderId },
+    PaymentIntentCaptureTimeout { payment_intent_id: PaymentIntentId },
     PaymentExpired { invoice_id: InvoiceId },
     PayoutInitiated { payout_id: PayoutId },
+    WalletVerificationInitiated { wallet_verification_id: WalletVerificationId },
+    FeeStatementGenerated { fee_statement_id: FeeStatementId },
+    StoreBillingStatusChanged { store_id: StoreId },
+    StoreWebhookDelivery { store_webhook_id: StoreWebhookId, notification: StoreWebhookNotification },
+    StripeFeeBackfillBatch { stripe_fee_backfill_id: StripeFeeBackfillId },
+    InternationalBillingInfoReencryptionBatch { after_id: InternationalBillingId },
+    RussiaBillingInfoReencryptionBatch { after_id: RussiaBillingId },
+    SagaOrderStatesUpdate { order_states: Vec<OrderStateUpdate> },
+    PaymentRecoveryNotification { payment_recovery_id: PaymentRecoveryId },
+    SetupIntentSucceeded { setup_intent: SetupIntent },
+    AnalyticsEventPublish { analytics_event: AnalyticsEvent },
+    InvoiceCallbackDelivery { invoice_callback_id: InvoiceCallbackId, notification: InvoiceCallbackNotification },
+    InvoiceReceiptNotification { invoice_id: InvoiceId },
+    FeeCryptoPaymentPaid { fee_crypto_payment_id: FeeCryptoPaymentId },
+    FeeCryptoPaymentExpired { fee_crypto_payment_id: FeeCryptoPaymentId },
+    NegativeStoreBalanceDetected { negative_store_balance_id: NegativeStoreBalanceId },
+    BillingInfoChangeNotification { billing_info_change_id: BillingInfoChangeId },
+    FeeDunningStep { store_id: StoreId },
+    FeeDunningNotification { store_id: StoreId, action: DunningAction },
 }
 
 impl fmt::Debug for EventPayload {
@@ -75,8 +104,28 @@ impl fmt::Display for EventPayload {
             EventPayload::PaymentIntentAmountCapturableUpdated { .. } => "PaymentIntentAmountCapturableUpdated",
             EventPayload::PaymentIntentSucceeded { .. } => "PaymentIntentSucceeded",
             EventPayload::PaymentIntentCapture { .. } => "PaymentIntentCapture",
+            EventPayload::PaymentIntentCaptureTimeout { .. } => "PaymentIntentCaptureTimeout",
             EventPayload::PaymentExpired { .. } => "PaymentExpired",
             EventPayload::PayoutInitiated { .. } => "PayoutInitiated",
+            EventPayload::WalletVerificationInitiated { .. } => "WalletVerificationInitiated",
+            EventPayload::FeeStatementGenerated { .. } => "FeeStatementGenerated",
+            EventPayload::StoreBillingStatusChanged { .. } => "StoreBillingStatusChanged",
+            EventPayload::StoreWebhookDelivery { .. } => "StoreWebhookDelivery",
+            EventPayload::StripeFeeBackfillBatch { .. } => "StripeFeeBackfillBatch",
+            EventPayload::InternationalBillingInfoReencryptionBatch { .. } => "InternationalBillingInfoReencryptionBatch",
+            EventPayload::RussiaBillingInfoReencryptionBatch { .. } => "RussiaBillingInfoReencryptionBatch",
+            EventPayload::SagaOrderStatesUpdate { .. } => "SagaOrderStatesUpdate",
+            EventPayload::PaymentRecoveryNotification { .. } => "PaymentRecoveryNotification",
+            EventPayload::SetupIntentSucceeded { .. } => "SetupIntentSucceeded",
+            EventPayload::AnalyticsEventPublish { .. } => "AnalyticsEventPublish",
+            EventPayload::InvoiceCallbackDelivery { .. } => "InvoiceCallbackDelivery",
+            EventPayload::InvoiceReceiptNotification { .. } => "InvoiceReceiptNotification",
+            EventPayload::FeeCryptoPaymentPaid { .. } => "FeeCryptoPaymentPaid",
+            EventPayload::FeeCryptoPaymentExpired { .. } => "FeeCryptoPaymentExpired",
+            EventPayload::NegativeStoreBalanceDetected { .. } => "NegativeStoreBalanceDetected",
+            EventPayload::BillingInfoChangeNotification { .. } => "BillingInfoChangeNotification",
+            EventPayload::FeeDunningStep { .. } => "FeeDunningStep",
+            EventPayload::FeeDunningNotification { .. } => "FeeDunningNotification",
         };
 
         f.write_str(&s)
@@ -34,6 +34,12 @@ pub struct EventEntry {
     pub created_at: NaiveDateTime,
     pub status_updated_at: NaiveDateTime,
     pub scheduled_on: Option<NaiveDateTime>,
+    /// Identifier of the billing instance currently processing the event
+    pub locked_by: Option<String>,
+    /// Moment after which the event may be taken over by another instance
+    pub lease_expires_at: Option<NaiveDateTime>,
+    /// Timings of the last processing attempt
+    pub timings: Option<EventTimings>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -85,12 +91,18 @@ pub struct RawEventEntry {
     pub created_at: NaiveDateTime,
     pub status_updated_at: NaiveDateTime,
     pub scheduled_on: Option<NaiveDateTime>,
+    pub locked_by: Option<String>,
+    pub lease_expires_at: Option<NaiveDateTime>,
+    pub payload_type: String,
+    pub timings: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Fail)]
 pub enum RawEventEntryError {
     #[fail(display = "failed to deserialize event")]
     InvalidEventJson(serde_json::Error),
+    #[fail(display = "failed to deserialize event timings")]
+    InvalidTimingsJson(serde_json::Error),
     #[fail(display = "invalid event status")]
     InvalidStatus,
 }
@@ -105,6 +117,10 @@ impl RawEventEntry {
             created_at,
             status_updated_at,
             scheduled_on,
+            locked_by,
+            lease_expires_at,
+            payload_type: _,
+            timings,
         } = self;
 
         let event = match serde_json::from_value::<Event>(event) {
@@ -121,6 +137,14 @@ impl RawEventEntry {
             }
         };
 
+        let timings = match timings.map(serde_json::from_value::<EventTimings>) {
+            None => None,
+            Some(Ok(timings)) => Some(timings),
+            Some(Err(e)) => {
+                return Err(RawEventEntryError::InvalidTimingsJson(e));
+            }
+        };
+
         Ok(EventEntry {
             id,
             event,
@@ -129,6 +153,9 @@ impl RawEventEntry {
             created_at,
             status_updated_at,
             scheduled_on,
+            locked_by,
+            lease_expires_at,
+            timings,
         })
     }
 }
@@ -140,24 +167,108 @@ pub struct RawNewEventEntry {
     pub status: String,
     pub attempt_count: i32,
     pub scheduled_on: Option<NaiveDateTime>,
+    pub payload_type: String,
 }
 
 impl RawNewEventEntry {
     pub fn try_from_event(event: Event) -> Result<Self, serde_json::Error> {
+        let payload_type = event.payload.to_string();
         serde_json::to_value(&event).map(|event| Self {
             event,
             status: EventStatus::Pending.to_string(),
             attempt_count: 0,
             scheduled_on: None,
+            payload_type,
         })
     }
 
     pub fn try_from_event_scheduled_on(event: Event, scheduled_on: NaiveDateTime) -> Result<Self, serde_json::Error> {
+        let payload_type = event.payload.to_string();
         serde_json::to_value(&event).map(|event| Self {
             event,
             status: EventStatus::Pending.to_string(),
             attempt_count: 0,
             scheduled_on: Some(scheduled_on),
+            payload_type,
         })
     }
 }
+
+/// Phase of the processing of an event, timed by the event handler
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventPhase {
+    DbLoad,
+    ExternalCall,
+    DbWrite,
+}
+
+impl fmt::Display for EventPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            EventPhase::DbLoad => "db_load",
+            EventPhase::ExternalCall => "external_call",
+            EventPhase::DbWrite => "db_write",
+        };
+
+        f.write_str(s)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EventSpan {
+    pub phase: EventPhase,
+    pub elapsed_ms: u64,
+}
+
+/// Time an event has taken to process and the spans of its phases, in the order they have ended.
+/// Spans of concurrent calls overlap, so they may add up to more than the total
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EventTimings {
+    pub total_ms: u64,
+    pub spans: Vec<EventSpan>,
+}
+
+impl EventTimings {
+    /// Time spent in the phase over all of its spans
+    pub fn phase_ms(&self, phase: EventPhase) -> u64 {
+        self.spans
+            .iter()
+            .filter(|span| span.phase == phase)
+            .map(|span| span.elapsed_ms)
+            .sum()
+    }
+}
+
+impl fmt::Display for EventTimings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ms ({}: {} ms, {}: {} ms, {}: {} ms in {} spans)",
+            self.total_ms,
+            EventPhase::DbLoad,
+            self.phase_ms(EventPhase::DbLoad),
+            EventPhase::ExternalCall,
+            self.phase_ms(EventPhase::ExternalCall),
+            EventPhase::DbWrite,
+            self.phase_ms(EventPhase::DbWrite),
+            self.spans.len(),
+        )
+    }
+}
+
+/// Payload types of the events taken for processing, as written by the `Display` of `EventPayload`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventPayloadTypes {
+    Only(Vec<String>),
+    AllExcept(Vec<String>),
+}
+
+impl EventPayloadTypes {
+    pub fn matches(&self, payload_type: &str) -> bool {
+        match self {
+            EventPayloadTypes::Only(payload_types) => payload_types.iter().any(|t| t == payload_type),
+            EventPayloadTypes::AllExcept(payload_types) => payload_types.iter().all(|t| t != payload_type),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+
+use models::invoice_v2::{BuyerAmounts, InvoiceId, OrderDump};
+use models::order_v2::OrderId;
+use models::{Amount, Currency};
+use schema::order_rate_slippages;
+
+/// Change of the price of a paid order caused by refreshes of its exchange rate.
+/// Prices are given in the buyer currency: the initial one by the first rate reserved for the order,
+/// the final one by the rate the order was paid at
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct OrderRateSlippage {
+    pub id: i32,
+    pub order_id: OrderId,
+    pub invoice_id: InvoiceId,
+    pub seller_currency: Currency,
+    pub buyer_currency: Currency,
+    pub initial_rate: BigDecimal,
+    pub final_rate: BigDecimal,
+    pub rate_refreshes: i32,
+    pub initial_price: Amount,
+    pub final_price: Amount,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "order_rate_slippages"]
+pub struct NewOrderRateSlippage {
+    pub order_id: OrderId,
+    pub invoice_id: InvoiceId,
+    pub seller_currency: Currency,
+    pub buyer_currency: Currency,
+    pub initial_rate: BigDecimal,
+    pub final_rate: BigDecimal,
+    pub rate_refreshes: i32,
+    pub initial_price: Amount,
+    pub final_price: Amount,
+}
+
+impl NewOrderRateSlippage {
+    /// Slippage of an order of a paid invoice. Returns None if the order is priced in the buyer currency
+    /// or has no rate to compare with
+    pub fn from_order_dump(invoice_id: InvoiceId, order: &OrderDump) -> Option<Self> {
+        let BuyerAmounts {
+            exchange_rate: final_rate,
+            currency: buyer_currency,
+            price: final_price,
+        } = order.buyer_amounts.clone()?;
+
+        if buyer_currency == order.seller_currency {
+            return None;
+        }
+
+        let initial_rate = order
+            .rates
+            .iter()
+            .min_by_key(|rate| (rate.reserved_at, rate.id.inner()))?
+            .exchange_rate
+            .clone();
+        let initial_price = order.seller_price.clone() / initial_rate.clone();
+
+        Some(Self {
+            order_id: order.id,
+            invoice_id,
+            seller_currency: order.seller_currency,
+            buyer_currency,
+            initial_rate,
+            final_rate,
+            rate_refreshes: order.rates.len() as i32 - 1,
+            initial_price: Amount::checked_from_super_unit(buyer_currency, initial_price)?,
+            final_price: Amount::checked_from_super_unit(buyer_currency, final_price)?,
+        })
+    }
+}
+
+/// Slippage of the paid orders of a currency pair, the prices are totals in the buyer currency
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct ExchangeRateSlippageMetric {
+    pub seller_currency: Currency,
+    pub buyer_currency: Currency,
+    pub orders_count: i64,
+    pub refreshed_orders_count: i64,
+    pub initial_price_total: Amount,
+    pub final_price_total: Amount,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl ExchangeRateSlippageMetric {
+    /// Difference between the final and the initial totals in super units of the buyer currency,
+    /// positive if the buyers paid more than they were quoted first
+    pub fn slippage(&self) -> BigDecimal {
+        self.final_price_total.to_super_unit(self.buyer_currency) - self.initial_price_total.to_super_unit(self.buyer_currency)
+    }
+
+    /// Slippage relative to the initial total to six decimal places, zero if there is nothing to compare with
+    pub fn slippage_ratio(&self) -> BigDecimal {
+        let initial_price_total = self.initial_price_total.to_super_unit(self.buyer_currency);
+        if initial_price_total == BigDecimal::from(0) {
+            BigDecimal::from(0)
+        } else {
+            (self.slippage() / initial_price_total).with_scale(6)
+        }
+    }
+}
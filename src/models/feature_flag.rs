@@ -0,0 +1,159 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use failure::Error as FailureError;
+use serde_json;
+use sha2::{Digest, Sha256};
+
+use stq_types::StoreId;
+
+use schema::feature_flags;
+
+/// Billing behavior that is rolled out gradually. A new feature gets a variant here
+/// and is consulted with `is_feature_enabled` where the behavior branches
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// Large payouts are only sent to verified wallets
+    PayoutWalletVerification,
+    /// Stores whose unpaid fees exceed the limits are suspended
+    StoreBillingSuspension,
+}
+
+impl Feature {
+    pub fn all() -> &'static [Feature] {
+        &[Feature::PayoutWalletVerification, Feature::StoreBillingSuspension]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Feature::PayoutWalletVerification => "payout_wallet_verification",
+            Feature::StoreBillingSuspension => "store_billing_suspension",
+        }
+    }
+
+    /// Whether the feature is on when neither the database nor the config has a say
+    pub fn default_enabled(&self) -> bool {
+        match self {
+            Feature::PayoutWalletVerification | Feature::StoreBillingSuspension => true,
+        }
+    }
+}
+
+impl Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Feature {
+    type Err = FailureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Feature::all()
+            .iter()
+            .find(|feature| feature.as_str() == s)
+            .cloned()
+            .ok_or_else(|| format_err!("Unknown feature \"{}\"", s))
+    }
+}
+
+/// Rollout of a feature. `store_ids` is a list of store ids the feature is on for regardless of the percentage
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct FeatureFlag {
+    pub name: String,
+    pub enabled: bool,
+    pub rollout_percentage: i32,
+    pub store_ids: serde_json::Value,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl FeatureFlag {
+    pub fn store_ids(&self) -> Result<Vec<StoreId>, serde_json::Error> {
+        serde_json::from_value(self.store_ids.clone())
+    }
+
+    /// A disabled flag is off for every store. An enabled one is on for the listed stores
+    /// and for `rollout_percentage` percent of the others
+    pub fn is_enabled_for(&self, store_id: StoreId) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        let is_listed = self.store_ids().map(|store_ids| store_ids.contains(&store_id)).unwrap_or(false);
+        is_listed || i32::from(rollout_bucket(&self.name, store_id)) < self.rollout_percentage
+    }
+}
+
+/// Bucket from 0 to 99 of a store within the rollout of a flag. It does not change between instances and restarts,
+/// so a store stays in the rollout as its percentage grows, and different flags pick different stores first
+pub fn rollout_bucket(name: &str, store_id: StoreId) -> u8 {
+    let hash = Sha256::digest(format!("{}:{}", name, store_id).as_bytes());
+    let value = (u32::from(hash[0]) << 24) | (u32::from(hash[1]) << 16) | (u32::from(hash[2]) << 8) | u32::from(hash[3]);
+    (value % 100) as u8
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "feature_flags"]
+pub struct NewFeatureFlag {
+    pub name: String,
+    pub enabled: bool,
+    pub rollout_percentage: i32,
+    pub store_ids: serde_json::Value,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn flag(enabled: bool, rollout_percentage: i32, store_ids: Vec<StoreId>) -> FeatureFlag {
+        let created_at = NaiveDate::from_ymd(2019, 4, 4).and_hms(9, 0, 0);
+
+        FeatureFlag {
+            name: Feature::PayoutWalletVerification.to_string(),
+            enabled,
+            rollout_percentage,
+            store_ids: serde_json::to_value(store_ids).unwrap(),
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    #[test]
+    fn flag_is_on_for_listed_stores_and_rollout_percentage_of_the_rest() {
+        let store_ids = (1..=1000).map(StoreId).collect::<Vec<_>>();
+
+        assert!(store_ids
+            .iter()
+            .all(|store_id| !flag(false, 100, vec![*store_id]).is_enabled_for(*store_id)));
+        assert!(store_ids.iter().all(|store_id| flag(true, 100, vec![]).is_enabled_for(*store_id)));
+        assert!(store_ids.iter().all(|store_id| !flag(true, 0, vec![]).is_enabled_for(*store_id)));
+        assert!(flag(true, 0, vec![StoreId(7)]).is_enabled_for(StoreId(7)));
+
+        let quarter = flag(true, 25, vec![]);
+        let half = flag(true, 50, vec![]);
+        let in_quarter = store_ids.iter().filter(|store_id| quarter.is_enabled_for(**store_id)).count();
+        assert!(
+            in_quarter > 150 && in_quarter < 350,
+            "{} of 1000 stores in a 25% rollout",
+            in_quarter
+        );
+        assert!(store_ids
+            .iter()
+            .filter(|store_id| quarter.is_enabled_for(**store_id))
+            .all(|store_id| half.is_enabled_for(*store_id)));
+    }
+
+    #[test]
+    fn features_are_parsed_from_their_names() {
+        for feature in Feature::all() {
+            assert_eq!(feature.as_str().parse::<Feature>().unwrap(), *feature);
+            assert_eq!(serde_json::to_value(feature).unwrap(), json!(feature.as_str()));
+        }
+        assert!("unknown_feature".parse::<Feature>().is_err());
+    }
+}
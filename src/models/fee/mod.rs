@@ -4,6 +4,7 @@ pub mod fee_id;
 pub use self::fee_id::FeeId;
 
 use chrono::NaiveDateTime;
+use uuid::Uuid;
 
 use serde_json;
 
@@ -24,6 +25,47 @@ pub struct Fee {
     pub updated_at: NaiveDateTime,
     pub crypto_currency: Option<Currency>,
     pub crypto_amount: Option<Amount>,
+    /// Amount in the crypto currency one unit of the fee currency was worth, set for crypto orders only
+    pub exchange_rate: Option<f64>,
+    /// Snapshot of the exchange rates in Stores the rate comes from
+    pub currency_exchange_id: Option<Uuid>,
+    pub converted_at: Option<NaiveDateTime>,
+    /// Currency the fee was charged from the card in, set once the fee is charged
+    pub charged_currency: Option<Currency>,
+    pub charged_amount: Option<Amount>,
+    /// Amount in the fee currency one unit of the charged currency was worth, set if the fee was converted for the charge
+    pub charge_exchange_rate: Option<f64>,
+}
+
+impl Fee {
+    pub fn conversion(&self) -> Option<FeeConversion> {
+        match (
+            self.crypto_currency,
+            self.exchange_rate,
+            self.currency_exchange_id,
+            self.converted_at,
+        ) {
+            (Some(from), Some(exchange_rate), Some(currency_exchange_id), Some(converted_at)) => Some(FeeConversion {
+                currency_exchange_id,
+                from,
+                to: self.currency,
+                exchange_rate,
+                converted_at,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Exchange rate the fee of a crypto order was converted into the fee currency with
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct FeeConversion {
+    pub currency_exchange_id: Uuid,
+    pub from: Currency,
+    pub to: Currency,
+    /// Amount in `from` one unit of `to` is worth
+    pub exchange_rate: f64,
+    pub converted_at: NaiveDateTime,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, Queryable, Insertable)]
@@ -37,6 +79,11 @@ pub struct NewFee {
     pub metadata: Option<serde_json::Value>,
     pub crypto_currency: Option<Currency>,
     pub crypto_amount: Option<Amount>,
+    /// Amount in the crypto currency one unit of the fee currency was worth, set for crypto orders only
+    pub exchange_rate: Option<f64>,
+    /// Snapshot of the exchange rates in Stores the rate comes from
+    pub currency_exchange_id: Option<Uuid>,
+    pub converted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize, AsChangeset)]
@@ -50,6 +97,9 @@ pub struct UpdateFee {
     pub metadata: Option<serde_json::Value>,
     pub crypto_currency: Option<Currency>,
     pub crypto_amount: Option<Amount>,
+    pub charged_currency: Option<Currency>,
+    pub charged_amount: Option<Amount>,
+    pub charge_exchange_rate: Option<f64>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, DieselTypes, Eq, PartialEq)]
@@ -58,6 +108,8 @@ pub enum FeeStatus {
     NotPaid,
     Paid,
     Fail,
+    /// The fee of a deleted store that is not going to be collected
+    WrittenOff,
 }
 
 impl Display for FeeStatus {
@@ -66,6 +118,7 @@ impl Display for FeeStatus {
             FeeStatus::NotPaid => write!(f, "NotPaid"),
             FeeStatus::Paid => write!(f, "Paid"),
             FeeStatus::Fail => write!(f, "Fail"),
+            FeeStatus::WrittenOff => write!(f, "WrittenOff"),
         }
     }
 }
@@ -0,0 +1,247 @@
+use std::fmt::{self, Display};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use diesel::sql_types::Int4 as SqlInt4;
+
+use models::order_v2::OrderId;
+use models::{Amount, ChargeId, Currency, Fee, FeeId};
+use schema::fee_adjustments;
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, Default, PartialEq)]
+#[sql_type = "SqlInt4"]
+pub struct FeeAdjustmentId(i32);
+derive_newtype_sql!(fee_adjustment_id, SqlInt4, FeeAdjustmentId, FeeAdjustmentId);
+
+impl FeeAdjustmentId {
+    pub fn new(id: i32) -> Self {
+        FeeAdjustmentId(id)
+    }
+
+    pub fn inner(&self) -> &i32 {
+        &self.0
+    }
+}
+
+impl FromStr for FeeAdjustmentId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s.parse()?;
+        Ok(FeeAdjustmentId::new(id))
+    }
+}
+
+impl Display for FeeAdjustmentId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0,))
+    }
+}
+
+/// Reversal of a platform fee caused by a refund of the order.
+/// `fee_charge_id` is set when the fee had already been charged and its share was refunded on that charge,
+/// otherwise the unpaid fee was reduced by `fee_amount`.
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct FeeAdjustment {
+    pub id: FeeAdjustmentId,
+    pub fee_id: FeeId,
+    pub order_id: OrderId,
+    pub currency: Currency,
+    pub refund_amount: Amount,
+    pub seller_amount: Amount,
+    pub fee_amount: Amount,
+    pub fee_charge_id: Option<ChargeId>,
+    pub created_at: NaiveDateTime,
+    /// Currency and amount refunded on the fee charge, which differ from `fee_amount` if the fee was charged with a conversion
+    pub charged_currency: Option<Currency>,
+    pub charged_fee_amount: Option<Amount>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "fee_adjustments"]
+pub struct NewFeeAdjustment {
+    pub fee_id: FeeId,
+    pub order_id: OrderId,
+    pub currency: Currency,
+    pub refund_amount: Amount,
+    pub seller_amount: Amount,
+    pub fee_amount: Amount,
+    pub fee_charge_id: Option<ChargeId>,
+    pub charged_currency: Option<Currency>,
+    pub charged_fee_amount: Option<Amount>,
+}
+
+/// Parts of a refund taken from the seller and from the platform fee of the order
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RefundSplit {
+    pub seller_amount: Amount,
+    pub fee_amount: Amount,
+}
+
+impl RefundSplit {
+    /// Splits the refund proportionally to the share of the fee in the order total,
+    /// `None` is returned when the refund exceeds the order total
+    pub fn new(order_total: Amount, refund_amount: Amount, fee_amount: Amount) -> Option<RefundSplit> {
+        if refund_amount > order_total {
+            return None;
+        }
+
+        let fee_amount = if order_total == Amount::zero() {
+            Amount::zero()
+        } else {
+            fee_amount.checked_mul(refund_amount)?.checked_div(order_total)?
+        };
+        let seller_amount = refund_amount.checked_sub(fee_amount)?;
+
+        Some(RefundSplit { seller_amount, fee_amount })
+    }
+
+    /// Splits a refund of an order refunded in part before by `adjustments` of its fee, which currently amounts to `fee_amount`.
+    /// The share is taken from the fee before the earlier refunds reduced it, and the refund completing the order takes
+    /// whatever is left of the fee. `None` is returned when the refunds exceed the order total
+    pub fn after_refunds(
+        order_total: Amount,
+        refund_amount: Amount,
+        fee_amount: Amount,
+        adjustments: &[FeeAdjustment],
+    ) -> Option<RefundSplit> {
+        let mut refunded_amount = Amount::zero();
+        let mut refunded_fee = Amount::zero();
+        let mut original_fee = fee_amount;
+        for adjustment in adjustments {
+            refunded_amount = refunded_amount.checked_add(adjustment.refund_amount)?;
+            refunded_fee = refunded_fee.checked_add(adjustment.fee_amount)?;
+            // the share of a charged fee is refunded on its charge, only an unpaid fee has been reduced
+            if adjustment.fee_charge_id.is_none() {
+                original_fee = original_fee.checked_add(adjustment.fee_amount)?;
+            }
+        }
+
+        let left_fee = original_fee.checked_sub(refunded_fee)?;
+        let refunded_amount = refunded_amount.checked_add(refund_amount)?;
+        if refunded_amount > order_total {
+            return None;
+        }
+
+        let fee_amount = if refunded_amount == order_total {
+            left_fee
+        } else {
+            let split = RefundSplit::new(order_total, refund_amount, original_fee)?;
+            if split.fee_amount > left_fee {
+                left_fee
+            } else {
+                split.fee_amount
+            }
+        };
+        let seller_amount = refund_amount.checked_sub(fee_amount)?;
+
+        Some(RefundSplit { seller_amount, fee_amount })
+    }
+
+    /// Share of the fee in the refund in the currency the fee was charged in. The share of a fee charged with a conversion
+    /// is taken from its `charged_amount` in proportion to the fee amount
+    pub fn charged_fee_amount(&self, fee: &Fee) -> Option<(Currency, Amount)> {
+        match (fee.charged_currency, fee.charged_amount) {
+            (Some(charged_currency), Some(charged_amount)) => {
+                let charged_fee_amount = if fee.amount == Amount::zero() {
+                    Amount::zero()
+                } else {
+                    charged_amount.checked_mul(self.fee_amount)?.checked_div(fee.amount)?
+                };
+                Some((charged_currency, charged_fee_amount))
+            }
+            _ => Some((fee.currency, self.fee_amount)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_support::FeeBuilder;
+
+    #[test]
+    fn refund_split_is_proportional_to_fee() {
+        let full = RefundSplit::new(Amount::new(10000), Amount::new(10000), Amount::new(500)).unwrap();
+        assert_eq!(full.fee_amount, Amount::new(500));
+        assert_eq!(full.seller_amount, Amount::new(9500));
+
+        let partial = RefundSplit::new(Amount::new(10000), Amount::new(3333), Amount::new(500)).unwrap();
+        assert_eq!(partial.fee_amount, Amount::new(166));
+        assert_eq!(partial.seller_amount, Amount::new(3167));
+
+        assert_eq!(RefundSplit::new(Amount::new(10000), Amount::new(10001), Amount::new(500)), None);
+    }
+
+    fn adjustment(split: RefundSplit, fee_charge_id: Option<ChargeId>) -> FeeAdjustment {
+        FeeAdjustment {
+            id: FeeAdjustmentId::new(1),
+            fee_id: FeeId::new(1),
+            order_id: OrderId::generate(),
+            currency: Currency::Eur,
+            refund_amount: split.seller_amount.checked_add(split.fee_amount).unwrap(),
+            seller_amount: split.seller_amount,
+            fee_amount: split.fee_amount,
+            fee_charge_id,
+            created_at: NaiveDateTime::from_timestamp(0, 0),
+            charged_currency: None,
+            charged_fee_amount: None,
+        }
+    }
+
+    #[test]
+    fn partial_refunds_take_shares_of_the_original_fee_and_the_last_one_takes_the_rest() {
+        let order_total = Amount::new(10000);
+        let mut fee_amount = Amount::new(500);
+        let mut adjustments = vec![];
+
+        // the unpaid fee is reduced by every refund
+        for refund_amount in &[3333, 3333] {
+            let split = RefundSplit::after_refunds(order_total, Amount::new(*refund_amount), fee_amount, &adjustments).unwrap();
+            assert_eq!(split.fee_amount, Amount::new(166));
+            fee_amount = fee_amount.checked_sub(split.fee_amount).unwrap();
+            adjustments.push(adjustment(split, None));
+        }
+
+        assert_eq!(
+            RefundSplit::after_refunds(order_total, Amount::new(3335), fee_amount, &adjustments),
+            None
+        );
+        let last = RefundSplit::after_refunds(order_total, Amount::new(3334), fee_amount, &adjustments).unwrap();
+        assert_eq!(last.fee_amount, Amount::new(168));
+        assert_eq!(last.seller_amount, Amount::new(3166));
+    }
+
+    #[test]
+    fn partial_refunds_of_a_charged_fee_leave_no_fee_once_the_order_is_refunded() {
+        let order_total = Amount::new(100);
+        let fee_amount = Amount::new(10);
+        let charge_id = Some(ChargeId::new("ch_fee".to_string()));
+
+        let first = RefundSplit::after_refunds(order_total, Amount::new(50), fee_amount, &[]).unwrap();
+        assert_eq!(first.fee_amount, Amount::new(5));
+
+        // the charged fee keeps its amount, its share is refunded on the charge
+        let adjustments = vec![adjustment(first, charge_id)];
+        let second = RefundSplit::after_refunds(order_total, Amount::new(50), fee_amount, &adjustments).unwrap();
+        assert_eq!(second.fee_amount, Amount::new(5));
+        assert_eq!(second.seller_amount, Amount::new(45));
+    }
+
+    #[test]
+    fn share_of_a_converted_fee_is_taken_from_its_charged_amount() {
+        let split = RefundSplit::new(Amount::new(10000), Amount::new(3333), Amount::new(500)).unwrap();
+
+        let own_currency = FeeBuilder::new().amount(Amount::new(500)).build();
+        assert_eq!(split.charged_fee_amount(&own_currency), Some((Currency::Eur, Amount::new(166))));
+
+        let converted = FeeBuilder::new()
+            .amount(Amount::new(500))
+            .charged_currency(Some(Currency::Usd))
+            .charged_amount(Some(Amount::new(560)))
+            .charge_exchange_rate(Some(0.8929))
+            .build();
+        assert_eq!(split.charged_fee_amount(&converted), Some((Currency::Usd, Amount::new(185))));
+    }
+}
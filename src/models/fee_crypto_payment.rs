@@ -0,0 +1,110 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use diesel::sql_types::Uuid as SqlUuid;
+use uuid::{self, Uuid};
+
+use models::fee::FeeId;
+use models::{AccountId, Amount, Currency, TransactionId};
+use schema::{fee_crypto_payment_transactions, fee_crypto_payments};
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, PartialEq, Eq, Hash)]
+#[sql_type = "SqlUuid"]
+pub struct FeeCryptoPaymentId(Uuid);
+derive_newtype_sql!(fee_crypto_payment, SqlUuid, FeeCryptoPaymentId, FeeCryptoPaymentId);
+
+impl FeeCryptoPaymentId {
+    pub fn new(id: Uuid) -> Self {
+        FeeCryptoPaymentId(id)
+    }
+
+    pub fn inner(&self) -> &Uuid {
+        &self.0
+    }
+
+    pub fn generate() -> Self {
+        FeeCryptoPaymentId(Uuid::new_v4())
+    }
+}
+
+impl FromStr for FeeCryptoPaymentId {
+    type Err = uuid::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = Uuid::parse_str(s)?;
+        Ok(FeeCryptoPaymentId::new(id))
+    }
+}
+
+impl Display for FeeCryptoPaymentId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0.hyphenated()))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeCryptoPaymentStatus {
+    /// The deposit address waits for the transfer
+    Pending,
+    /// The required amount arrived and the fee is paid
+    Paid,
+    /// The address expired before the required amount arrived
+    Expired,
+    /// The required amount arrived after the fee had been paid by card, the amount received is owed back to the store
+    Refundable,
+}
+
+/// Payment of a fee by a transfer from the wallet of the store. The store transfers `amount` in `currency`
+/// to the pooled account, which is reserved for the fee until the payment is paid or expires
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct FeeCryptoPayment {
+    pub id: FeeCryptoPaymentId,
+    pub fee_id: FeeId,
+    pub account_id: AccountId,
+    pub currency: Currency,
+    pub amount: Amount,
+    pub amount_received: Amount,
+    pub status: FeeCryptoPaymentStatus,
+    pub expires_at: NaiveDateTime,
+    /// The account rests in quarantine from this time on, as late transfers may still arrive to it
+    pub closed_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl FeeCryptoPayment {
+    pub fn is_fully_received(&self) -> bool {
+        self.amount_received >= self.amount
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "fee_crypto_payments"]
+pub struct NewFeeCryptoPayment {
+    pub id: FeeCryptoPaymentId,
+    pub fee_id: FeeId,
+    pub account_id: AccountId,
+    pub currency: Currency,
+    pub amount: Amount,
+    pub status: FeeCryptoPaymentStatus,
+    pub expires_at: NaiveDateTime,
+}
+
+/// Inbound transaction credited to a fee payment, every transaction is credited once
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct FeeCryptoPaymentTransaction {
+    pub transaction_id: TransactionId,
+    pub fee_crypto_payment_id: FeeCryptoPaymentId,
+    pub amount: Amount,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "fee_crypto_payment_transactions"]
+pub struct NewFeeCryptoPaymentTransaction {
+    pub transaction_id: TransactionId,
+    pub fee_crypto_payment_id: FeeCryptoPaymentId,
+    pub amount: Amount,
+}
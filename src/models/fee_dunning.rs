@@ -0,0 +1,93 @@
+use std::fmt::{self, Display};
+
+use chrono::NaiveDateTime;
+
+use stq_types::StoreId;
+
+use schema::fee_dunnings;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeDunningStatus {
+    /// The store has unpaid fees, the next step of the schedule is pending
+    Open,
+    /// The store has paid its fees
+    Resolved,
+}
+
+impl Display for FeeDunningStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FeeDunningStatus::Open => f.write_str("open"),
+            FeeDunningStatus::Resolved => f.write_str("resolved"),
+        }
+    }
+}
+
+/// What a step of the dunning schedule does
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum DunningAction {
+    /// Reminds the store owner of the unpaid fees
+    Reminder,
+    /// Warns the store owner that the store is about to be suspended
+    Warning,
+    /// Suspends the store until the fees are paid
+    Suspension,
+}
+
+impl Display for DunningAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DunningAction::Reminder => f.write_str("reminder"),
+            DunningAction::Warning => f.write_str("warning"),
+            DunningAction::Suspension => f.write_str("suspension"),
+        }
+    }
+}
+
+/// Dunning of a store for the fees its card could not be charged for. There is one dunning per store,
+/// it is started again by the next failed charge once resolved
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct FeeDunning {
+    pub store_id: StoreId,
+    pub status: FeeDunningStatus,
+    /// Steps of the schedule taken since the dunning started
+    pub steps_taken: i32,
+    pub last_action: Option<DunningAction>,
+    /// Time of the failed charge the schedule is counted from
+    pub started_at: NaiveDateTime,
+    pub last_step_at: Option<NaiveDateTime>,
+    pub next_step_at: Option<NaiveDateTime>,
+    pub resolved_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl FeeDunning {
+    /// Whether the store has to be kept suspended by the unpaid fees policy
+    pub fn is_suspending(&self) -> bool {
+        self.status == FeeDunningStatus::Open && self.last_action == Some(DunningAction::Suspension)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "fee_dunnings"]
+pub struct NewFeeDunning {
+    pub store_id: StoreId,
+    pub status: FeeDunningStatus,
+    pub started_at: NaiveDateTime,
+    pub next_step_at: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, AsChangeset)]
+#[table_name = "fee_dunnings"]
+pub struct UpdateFeeDunning {
+    pub status: Option<FeeDunningStatus>,
+    pub steps_taken: Option<i32>,
+    pub last_action: Option<Option<DunningAction>>,
+    pub started_at: Option<NaiveDateTime>,
+    pub last_step_at: Option<Option<NaiveDateTime>>,
+    pub next_step_at: Option<Option<NaiveDateTime>>,
+    pub resolved_at: Option<Option<NaiveDateTime>>,
+}
@@ -0,0 +1,126 @@
+use std::fmt::{self, Display};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use diesel::sql_types::Int4 as SqlInt4;
+use serde_json;
+
+use stq_types::StoreId;
+
+use models::order_v2::OrderId;
+use models::{Amount, Currency, FeeConversion, FeeId};
+use schema::fee_statements;
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, Default, PartialEq)]
+#[sql_type = "SqlInt4"]
+pub struct FeeStatementId(i32);
+derive_newtype_sql!(fee_statement_id, SqlInt4, FeeStatementId, FeeStatementId);
+
+impl FeeStatementId {
+    pub fn new(id: i32) -> Self {
+        FeeStatementId(id)
+    }
+
+    pub fn inner(&self) -> &i32 {
+        &self.0
+    }
+}
+
+impl FromStr for FeeStatementId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s.parse()?;
+        Ok(FeeStatementId::new(id))
+    }
+}
+
+impl Display for FeeStatementId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0,))
+    }
+}
+
+/// Monthly statement of platform fees charged to a store in a single currency.
+/// `total_amount` is `fees_amount - adjustments_amount + taxes_amount`.
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct FeeStatement {
+    pub id: FeeStatementId,
+    pub store_id: StoreId,
+    pub currency: Currency,
+    pub period_start: NaiveDateTime,
+    pub period_end: NaiveDateTime,
+    pub fees_amount: Amount,
+    pub adjustments_amount: Amount,
+    pub taxes_amount: Amount,
+    pub total_amount: Amount,
+    pub lines: serde_json::Value,
+    pub created_at: NaiveDateTime,
+}
+
+impl FeeStatement {
+    pub fn lines(&self) -> Result<Vec<FeeStatementLine>, serde_json::Error> {
+        serde_json::from_value(self.lines.clone())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "fee_statements"]
+pub struct NewFeeStatement {
+    pub store_id: StoreId,
+    pub currency: Currency,
+    pub period_start: NaiveDateTime,
+    pub period_end: NaiveDateTime,
+    pub fees_amount: Amount,
+    pub adjustments_amount: Amount,
+    pub taxes_amount: Amount,
+    pub total_amount: Amount,
+    pub lines: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FeeStatementLine {
+    pub kind: FeeStatementLineKind,
+    pub fee_id: Option<FeeId>,
+    pub order_id: Option<OrderId>,
+    pub amount: Amount,
+    pub description: String,
+    pub created_at: Option<NaiveDateTime>,
+    /// Exchange rate of the fee of a crypto order, lines of statements generated before it was recorded have none
+    #[serde(default)]
+    pub conversion: Option<FeeConversion>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeStatementLineKind {
+    Fee,
+    /// Deducted from the statement total, e.g. a fee whose charge has failed
+    Adjustment,
+    Tax,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FeeStatementSearch {
+    pub id: Option<FeeStatementId>,
+    pub store_id: Option<StoreId>,
+    pub currency: Option<Currency>,
+    pub period_start: Option<NaiveDateTime>,
+}
+
+impl FeeStatementSearch {
+    pub fn by_id(id: FeeStatementId) -> FeeStatementSearch {
+        FeeStatementSearch {
+            id: Some(id),
+            ..Default::default()
+        }
+    }
+
+    pub fn by_store_id(store_id: StoreId) -> FeeStatementSearch {
+        FeeStatementSearch {
+            store_id: Some(store_id),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use chrono::NaiveDateTime;
+use diesel::sql_types::{BigInt, Numeric, VarChar};
+use serde_json;
+
+use models::{Amount, Currency};
+use schema::historical_reports;
+
+/// Records of one kind that were in `state` at the time of the report, totalled in a single currency.
+/// States of invoices are `awaiting_payment`, `paid`, `expired` and `cancelled`, of fees `outstanding`, `paid`
+/// and `written_off`, of payouts `in_progress` and `completed`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, QueryableByName)]
+pub struct HistoricalStateTotal {
+    #[sql_type = "VarChar"]
+    pub state: String,
+    #[sql_type = "VarChar"]
+    pub currency: Currency,
+    #[sql_type = "BigInt"]
+    pub count: i64,
+    #[sql_type = "Numeric"]
+    pub amount: Amount,
+}
+
+/// States of invoices, fees and payouts reconstructed as of a past time, kept so that the same date is
+/// reported the same way every time. Each column holds the `HistoricalStateTotal`s of its kind
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct HistoricalReport {
+    pub as_of: NaiveDateTime,
+    pub invoices: serde_json::Value,
+    pub fees: serde_json::Value,
+    pub payouts: serde_json::Value,
+    pub created_at: NaiveDateTime,
+}
+
+impl HistoricalReport {
+    pub fn invoices(&self) -> Result<Vec<HistoricalStateTotal>, serde_json::Error> {
+        serde_json::from_value(self.invoices.clone())
+    }
+
+    pub fn fees(&self) -> Result<Vec<HistoricalStateTotal>, serde_json::Error> {
+        serde_json::from_value(self.fees.clone())
+    }
+
+    pub fn payouts(&self) -> Result<Vec<HistoricalStateTotal>, serde_json::Error> {
+        serde_json::from_value(self.payouts.clone())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "historical_reports"]
+pub struct NewHistoricalReport {
+    pub as_of: NaiveDateTime,
+    pub invoices: serde_json::Value,
+    pub fees: serde_json::Value,
+    pub payouts: serde_json::Value,
+}
+
+impl NewHistoricalReport {
+    pub fn new(
+        as_of: NaiveDateTime,
+        invoices: &[HistoricalStateTotal],
+        fees: &[HistoricalStateTotal],
+        payouts: &[HistoricalStateTotal],
+    ) -> Result<Self, serde_json::Error> {
+        Ok(NewHistoricalReport {
+            as_of,
+            invoices: serde_json::to_value(invoices)?,
+            fees: serde_json::to_value(fees)?,
+            payouts: serde_json::to_value(payouts)?,
+        })
+    }
+}
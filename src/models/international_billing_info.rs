@@ -1,6 +1,7 @@
 use stq_static_resources::Currency;
 use stq_types::{InternationalBillingId, StoreId, SwiftId};
 
+use models::masking::mask;
 use schema::international_billing_info;
 
 #[derive(Clone, Serialize, Queryable, Insertable, Debug)]
@@ -19,6 +20,17 @@ pub struct InternationalBillingInfo {
     pub recipient_address: String,
 }
 
+impl InternationalBillingInfo {
+    /// Billing info with bank account details hidden
+    pub fn masked(self) -> Self {
+        Self {
+            account: mask(&self.account),
+            swift: SwiftId(mask(&self.swift.0)),
+            ..self
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Insertable, AsChangeset, Debug, Clone)]
 #[table_name = "international_billing_info"]
 pub struct UpdateInternationalBillingInfo {
@@ -52,7 +64,6 @@ pub struct NewInternationalBillingInfo {
 pub struct InternationalBillingInfoSearch {
     pub id: Option<InternationalBillingId>,
     pub store_id: Option<StoreId>,
-    pub swift: Option<SwiftId>,
     pub store_ids: Option<Vec<StoreId>>,
 }
 
@@ -0,0 +1,134 @@
+use chrono::NaiveDateTime;
+
+use stq_types::Alpha3;
+
+use models::invoice_v2::InvoiceId;
+use models::UserId;
+use schema::invoice_buyer_details;
+
+/// Billing name and address of the buyer, given by the saga for the jurisdictions that require them on receipts
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BuyerBillingDetails {
+    pub name: String,
+    pub address_line1: String,
+    #[serde(default)]
+    pub address_line2: Option<String>,
+    pub city: String,
+    #[serde(default)]
+    pub postal_code: Option<String>,
+    #[serde(default)]
+    pub region: Option<String>,
+    pub country: Alpha3,
+    /// VAT or other tax number of the buyer, printed on receipts as given
+    #[serde(default)]
+    pub tax_id: Option<String>,
+}
+
+impl BuyerBillingDetails {
+    /// Address lines as printed on receipts, empty parts are skipped
+    pub fn address(&self) -> String {
+        let parts = vec![
+            Some(&self.address_line1),
+            self.address_line2.as_ref(),
+            Some(&self.city),
+            self.region.as_ref(),
+            self.postal_code.as_ref(),
+            Some(&self.country.0),
+        ];
+
+        parts
+            .into_iter()
+            .flatten()
+            .map(|part| part.trim())
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Trims the details, empty optional fields are dropped
+    pub fn trimmed(self) -> Self {
+        let optional = |value: Option<String>| value.map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+
+        BuyerBillingDetails {
+            name: self.name.trim().to_string(),
+            address_line1: self.address_line1.trim().to_string(),
+            address_line2: optional(self.address_line2),
+            city: self.city.trim().to_string(),
+            postal_code: optional(self.postal_code),
+            region: optional(self.region),
+            country: Alpha3(self.country.0.trim().to_uppercase()),
+            tax_id: optional(self.tax_id),
+        }
+    }
+}
+
+/// Billing details of the buyer of an invoice. `country` is kept as the jurisdiction of the invoice,
+/// the rest of the details are stored encrypted
+#[derive(Clone, Debug, Serialize)]
+pub struct InvoiceBuyerDetails {
+    pub invoice_id: InvoiceId,
+    pub buyer_user_id: UserId,
+    pub country: Alpha3,
+    pub details: BuyerBillingDetails,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// Buyer details as stored, `details` is the encrypted JSON of `BuyerBillingDetails`
+#[derive(Clone, Debug, Queryable)]
+pub struct RawInvoiceBuyerDetails {
+    pub invoice_id: InvoiceId,
+    pub buyer_user_id: UserId,
+    pub country: String,
+    pub details: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug)]
+pub struct NewInvoiceBuyerDetails {
+    pub invoice_id: InvoiceId,
+    pub buyer_user_id: UserId,
+    pub details: BuyerBillingDetails,
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[table_name = "invoice_buyer_details"]
+pub struct NewRawInvoiceBuyerDetails {
+    pub invoice_id: InvoiceId,
+    pub buyer_user_id: UserId,
+    pub country: String,
+    pub details: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn details() -> BuyerBillingDetails {
+        BuyerBillingDetails {
+            name: " Jane Doe ".to_string(),
+            address_line1: "Main st. 1 ".to_string(),
+            address_line2: Some(" ".to_string()),
+            city: "Berlin".to_string(),
+            postal_code: Some("10115".to_string()),
+            region: None,
+            country: Alpha3(" deu".to_string()),
+            tax_id: Some("".to_string()),
+        }
+    }
+
+    #[test]
+    fn trimmed_details_drop_empty_optional_fields() {
+        let details = details().trimmed();
+        assert_eq!(details.name, "Jane Doe");
+        assert_eq!(details.address_line2, None);
+        assert_eq!(details.tax_id, None);
+        assert_eq!(details.country.0, "DEU");
+    }
+
+    #[test]
+    fn address_skips_empty_parts() {
+        assert_eq!(details().trimmed().address(), "Main st. 1, Berlin, 10115, DEU");
+    }
+}
@@ -0,0 +1,126 @@
+use std::fmt::{self, Display};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use diesel::sql_types::Int4 as SqlInt4;
+use hex;
+use serde_json;
+use uuid::Uuid;
+
+use models::invoice_v2::InvoiceId;
+use models::store_webhook::hmac_sha256;
+use schema::{invoice_callback_deliveries, invoice_callbacks};
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, Default, PartialEq)]
+#[sql_type = "SqlInt4"]
+pub struct InvoiceCallbackId(i32);
+derive_newtype_sql!(invoice_callback_id, SqlInt4, InvoiceCallbackId, InvoiceCallbackId);
+
+impl InvoiceCallbackId {
+    pub fn new(id: i32) -> Self {
+        InvoiceCallbackId(id)
+    }
+
+    pub fn inner(&self) -> &i32 {
+        &self.0
+    }
+}
+
+impl FromStr for InvoiceCallbackId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s.parse()?;
+        Ok(InvoiceCallbackId::new(id))
+    }
+}
+
+impl Display for InvoiceCallbackId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0,))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceCallbackEventType {
+    InvoicePaid,
+    InvoiceExpired,
+    InvoiceRefunded,
+}
+
+impl Display for InvoiceCallbackEventType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvoiceCallbackEventType::InvoicePaid => write!(f, "invoice_paid"),
+            InvoiceCallbackEventType::InvoiceExpired => write!(f, "invoice_expired"),
+            InvoiceCallbackEventType::InvoiceRefunded => write!(f, "invoice_refunded"),
+        }
+    }
+}
+
+/// Callback url registered with the invoice when it was created
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct InvoiceCallbackRegistration {
+    pub url: String,
+    /// Key the notifications are signed with
+    pub secret: String,
+}
+
+/// Endpoint of an external storefront that receives status changes of a single invoice,
+/// it doesn't depend on the saga the marketplace orders go through
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct InvoiceCallback {
+    pub id: InvoiceCallbackId,
+    pub invoice_id: InvoiceId,
+    pub url: String,
+    pub secret: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl InvoiceCallback {
+    /// Hex encoded HMAC-SHA256 of the request body keyed with the callback secret
+    pub fn sign(&self, body: &str) -> String {
+        hex::encode(hmac_sha256(self.secret.as_bytes(), body.as_bytes()))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "invoice_callbacks"]
+pub struct NewInvoiceCallback {
+    pub invoice_id: InvoiceId,
+    pub url: String,
+    pub secret: String,
+}
+
+/// Body of the request sent to an invoice callback.
+/// `id` stays the same when a failed delivery is retried
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InvoiceCallbackNotification {
+    pub id: Uuid,
+    pub event_type: InvoiceCallbackEventType,
+    pub invoice_id: InvoiceId,
+    pub data: serde_json::Value,
+    pub created_at: NaiveDateTime,
+}
+
+/// Attempt to deliver a notification, `error` is set when the attempt failed
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct InvoiceCallbackDelivery {
+    pub id: i32,
+    pub invoice_callback_id: InvoiceCallbackId,
+    pub notification_id: Uuid,
+    pub event_type: InvoiceCallbackEventType,
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "invoice_callback_deliveries"]
+pub struct NewInvoiceCallbackDelivery {
+    pub invoice_callback_id: InvoiceCallbackId,
+    pub notification_id: Uuid,
+    pub event_type: InvoiceCallbackEventType,
+    pub error: Option<String>,
+}
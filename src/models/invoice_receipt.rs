@@ -0,0 +1,129 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+
+use stq_types::ProductId;
+
+use models::invoice_v2::{InvoiceDump, InvoiceId, RawAmountReceived};
+use models::order_v2::OrderId;
+use models::{BuyerBillingDetails, Currency, OrderLineItem, TransactionId, WalletAddress};
+
+pub const DEFAULT_RECEIPT_LOCALE: &'static str = "en";
+
+/// Message of a receipt, every locale may give its own text of it. `{name}` placeholders are replaced
+/// with the values of the receipt, see `services::receipt` for the English texts and their placeholders
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiptMessage {
+    Subject,
+    Greeting,
+    AmountPaid,
+    PaidAt,
+    WalletAddress,
+    BilledTo,
+    BuyerAddress,
+    BuyerTaxId,
+    OrdersHeader,
+    Order,
+    OrderWithoutRate,
+    LineItem,
+    TransactionsHeader,
+    Transaction,
+}
+
+/// Receipt of a paid crypto invoice. Stripe sends the receipts of fiat payments, so only crypto invoices get one from the billing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceReceipt {
+    pub invoice_id: InvoiceId,
+    pub currency: Currency,
+    pub amount_paid: BigDecimal,
+    pub paid_at: Option<NaiveDateTime>,
+    /// Address of the account the buyer paid to
+    pub wallet_address: Option<WalletAddress>,
+    /// Billing name and address the buyer gave at checkout, required on receipts in some jurisdictions
+    #[serde(default)]
+    pub buyer_billing_details: Option<BuyerBillingDetails>,
+    pub orders: Vec<InvoiceReceiptOrder>,
+    pub transactions: Vec<InvoiceReceiptTransaction>,
+}
+
+/// Order paid with the invoice, `buyer_price` and `exchange_rate` are missing when the order has no active rate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceReceiptOrder {
+    pub order_id: OrderId,
+    pub seller_currency: Currency,
+    pub seller_price: BigDecimal,
+    pub buyer_price: Option<BigDecimal>,
+    pub exchange_rate: Option<BigDecimal>,
+    pub line_items: Vec<InvoiceReceiptLineItem>,
+}
+
+/// Product paid with the order, `unit_price` is in the seller currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceReceiptLineItem {
+    pub product_id: ProductId,
+    pub name: String,
+    pub quantity: i32,
+    pub unit_price: BigDecimal,
+    pub refunded_quantity: i32,
+}
+
+/// Inbound transaction credited to the account of the invoice
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceReceiptTransaction {
+    pub id: TransactionId,
+    pub amount: BigDecimal,
+    pub received_at: NaiveDateTime,
+}
+
+impl InvoiceReceipt {
+    /// `wallet_address` replaces the one of the dump, which is gone once the account is unlinked from the paid invoice.
+    /// Every order keeps the ones of `line_items` that belong to it
+    pub fn new(
+        invoice: InvoiceDump,
+        wallet_address: Option<WalletAddress>,
+        buyer_billing_details: Option<BuyerBillingDetails>,
+        amounts_received: Vec<RawAmountReceived>,
+        line_items: &[OrderLineItem],
+    ) -> Self {
+        let currency = invoice.buyer_currency;
+
+        InvoiceReceipt {
+            invoice_id: invoice.id,
+            currency,
+            amount_paid: invoice.total_price,
+            paid_at: invoice.paid_at,
+            wallet_address: wallet_address.or(invoice.wallet_address),
+            buyer_billing_details,
+            orders: invoice
+                .orders
+                .into_iter()
+                .map(|order| InvoiceReceiptOrder {
+                    order_id: order.id,
+                    seller_currency: order.seller_currency,
+                    seller_price: order.seller_price,
+                    buyer_price: order.buyer_amounts.as_ref().map(|buyer_amounts| buyer_amounts.price.clone()),
+                    exchange_rate: order.buyer_amounts.map(|buyer_amounts| buyer_amounts.exchange_rate),
+                    line_items: line_items
+                        .iter()
+                        .filter(|line_item| line_item.order_id == order.id)
+                        .map(|line_item| InvoiceReceiptLineItem {
+                            product_id: line_item.product_id,
+                            name: line_item.name.clone(),
+                            quantity: line_item.quantity,
+                            unit_price: line_item.unit_price.to_super_unit(order.seller_currency),
+                            refunded_quantity: line_item.refunded_quantity,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            transactions: amounts_received
+                .into_iter()
+                .map(|amount_received| InvoiceReceiptTransaction {
+                    id: amount_received.id,
+                    amount: amount_received.amount_received.to_super_unit(currency),
+                    received_at: amount_received.created_at,
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use chrono::NaiveDateTime;
+
+use models::invoice_v2::InvoiceId;
+use schema::invoice_requotes;
+
+/// Links an expired invoice to the invoice it was requoted into, an invoice is requoted at most once
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+pub struct InvoiceRequote {
+    pub invoice_id: InvoiceId,
+    pub original_invoice_id: InvoiceId,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[table_name = "invoice_requotes"]
+pub struct NewInvoiceRequote {
+    pub invoice_id: InvoiceId,
+    pub original_invoice_id: InvoiceId,
+}
@@ -0,0 +1,137 @@
+use std::fmt::{self, Display};
+use std::net::IpAddr;
+
+use chrono::NaiveDateTime;
+use diesel::sql_types::{BigInt, Nullable, VarChar};
+
+use stq_types::UserId as StqUserId;
+
+use models::invoice_v2::InvoiceId;
+use models::{Amount, Currency, UserId};
+use schema::invoice_risk_assessments;
+
+/// What happens to an invoice after its risk evaluation
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskDecision {
+    Allow,
+    /// The invoice is created, its card payment is authorized but not captured until a superuser approves it
+    Review,
+    /// The invoice is not created
+    Deny,
+}
+
+impl Display for RiskDecision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RiskDecision::Allow => f.write_str("allow"),
+            RiskDecision::Review => f.write_str("review"),
+            RiskDecision::Deny => f.write_str("deny"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskReviewStatus {
+    Pending,
+    /// The payment is captured as if the invoice was allowed
+    Approved,
+    /// The authorization of the payment is released and the orders are declined
+    Rejected,
+}
+
+impl Display for RiskReviewStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RiskReviewStatus::Pending => f.write_str("pending"),
+            RiskReviewStatus::Approved => f.write_str("approved"),
+            RiskReviewStatus::Rejected => f.write_str("rejected"),
+        }
+    }
+}
+
+/// Risk evaluation of an invoice created by a buyer, kept for the denied invoices too
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct InvoiceRiskAssessment {
+    pub invoice_id: InvoiceId,
+    pub buyer_user_id: UserId,
+    pub buyer_ip: Option<String>,
+    pub score: i32,
+    pub decision: RiskDecision,
+    /// Signals that added to the score
+    pub reasons: Vec<String>,
+    /// Set for the invoices held for a review only
+    pub review_status: Option<RiskReviewStatus>,
+    pub reviewed_by: Option<StqUserId>,
+    pub reviewed_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl InvoiceRiskAssessment {
+    /// Whether the capture of the payment of the invoice waits for a decision
+    pub fn holds_capture(&self) -> bool {
+        self.review_status == Some(RiskReviewStatus::Pending)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "invoice_risk_assessments"]
+pub struct NewInvoiceRiskAssessment {
+    pub invoice_id: InvoiceId,
+    pub buyer_user_id: UserId,
+    pub buyer_ip: Option<String>,
+    pub score: i32,
+    pub decision: RiskDecision,
+    pub reasons: Vec<String>,
+    pub review_status: Option<RiskReviewStatus>,
+}
+
+/// Decision of a superuser on an invoice held for a review
+#[derive(Clone, Debug)]
+pub struct InvoiceRiskReview {
+    pub status: RiskReviewStatus,
+    pub reviewed_by: StqUserId,
+}
+
+/// Invoices of the buyer and from the IP the invoice is created from, as known before the invoice is created
+#[derive(Clone, Debug, Default, PartialEq, QueryableByName)]
+pub struct BuyerRiskHistory {
+    #[sql_type = "BigInt"]
+    pub invoices_count: i64,
+    #[sql_type = "BigInt"]
+    pub paid_invoices_count: i64,
+    /// Invoices assessed for the same IP within the velocity window
+    #[sql_type = "BigInt"]
+    pub ip_invoices_count: i64,
+}
+
+/// Inputs of the risk evaluation of an invoice
+#[derive(Clone, Debug)]
+pub struct RiskFeatures {
+    pub invoice_id: InvoiceId,
+    pub buyer_user_id: UserId,
+    pub buyer_ip: Option<IpAddr>,
+    /// Totals of the orders of the invoice by seller currency
+    pub amounts: Vec<(Currency, Amount)>,
+    pub history: BuyerRiskHistory,
+}
+
+/// Outcome of the risk evaluation of an invoice
+#[derive(Clone, Debug, PartialEq)]
+pub struct RiskEvaluation {
+    pub score: i32,
+    pub decision: RiskDecision,
+    pub reasons: Vec<String>,
+}
+
+/// Assessments by decision and review status, rendered as metrics
+#[derive(Clone, Debug, QueryableByName)]
+pub struct RiskDecisionCount {
+    #[sql_type = "VarChar"]
+    pub decision: RiskDecision,
+    #[sql_type = "Nullable<VarChar>"]
+    pub review_status: Option<RiskReviewStatus>,
+    #[sql_type = "BigInt"]
+    pub count: i64,
+}
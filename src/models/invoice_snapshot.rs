@@ -0,0 +1,87 @@
+use chrono::NaiveDateTime;
+use serde_json;
+
+use models::invoice_v2::{InvoiceDump, InvoiceId, RawInvoice};
+use schema::invoice_snapshots;
+
+/// Invoice as it was priced at the moment it got paid: its orders, rates, totals and fees.
+/// Snapshots are never changed, so paid invoices are not re-derived from the current data
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+pub struct InvoiceSnapshot {
+    pub id: i32,
+    pub invoice_id: InvoiceId,
+    pub dump: serde_json::Value,
+    pub paid_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+impl InvoiceSnapshot {
+    /// Dump of the paid invoice. Only the status and the captured amount, which keep changing after
+    /// the payment, are taken from the current state of the invoice
+    pub fn invoice_dump(&self, invoice: &RawInvoice) -> Result<InvoiceDump, serde_json::Error> {
+        let mut dump: InvoiceDump = serde_json::from_value(self.dump.clone())?;
+        dump.status = invoice.status;
+        dump.amount_captured = invoice.amount_captured.to_super_unit(dump.buyer_currency);
+        Ok(dump)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[table_name = "invoice_snapshots"]
+pub struct NewInvoiceSnapshot {
+    pub invoice_id: InvoiceId,
+    pub dump: serde_json::Value,
+    pub paid_at: NaiveDateTime,
+}
+
+impl NewInvoiceSnapshot {
+    pub fn new(dump: &InvoiceDump, paid_at: NaiveDateTime) -> Result<Self, serde_json::Error> {
+        Ok(NewInvoiceSnapshot {
+            invoice_id: dump.id,
+            dump: serde_json::to_value(dump)?,
+            paid_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::BigDecimal;
+    use stq_static_resources::OrderState;
+
+    use models::invoice_v2::calculate_invoice_price;
+    use models::Amount;
+    use test_support::{RawInvoiceBuilder, RawOrderBuilder};
+
+    use super::*;
+
+    #[test]
+    fn paid_invoice_keeps_the_snapshot_prices() {
+        let invoice = RawInvoiceBuilder::new().paid(Amount::new(10000)).build();
+        let order = RawOrderBuilder::new()
+            .invoice_id(invoice.id)
+            .total_amount(Amount::new(10000))
+            .build();
+        let dump = calculate_invoice_price(invoice.clone(), vec![(order, vec![])], None);
+        let new_snapshot = NewInvoiceSnapshot::new(&dump, invoice.paid_at.unwrap()).unwrap();
+        let snapshot = InvoiceSnapshot {
+            id: 1,
+            invoice_id: new_snapshot.invoice_id,
+            dump: new_snapshot.dump,
+            paid_at: new_snapshot.paid_at,
+            created_at: new_snapshot.paid_at,
+        };
+
+        let invoice = RawInvoice {
+            status: OrderState::Delivered,
+            amount_captured: Amount::new(10000),
+            ..invoice
+        };
+        let dump = snapshot.invoice_dump(&invoice).unwrap();
+
+        assert_eq!(dump.total_price, BigDecimal::from(100));
+        assert_eq!(dump.orders[0].seller_price, BigDecimal::from(100));
+        assert_eq!(dump.status, OrderState::Delivered);
+        assert_eq!(dump.amount_captured, BigDecimal::from(100));
+    }
+}
@@ -1,10 +1,11 @@
 use std::fmt::{self, Display};
 use std::str::FromStr;
-use std::time::{Duration, SystemTime};
+use std::time::SystemTime;
 
 use bigdecimal::{BigDecimal, ToPrimitive};
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use diesel::sql_types::Uuid as SqlUuid;
+use serde_json;
 use stq_static_resources::OrderState;
 use stq_types::{InvoiceId as InvoiceV1Id, ProductPrice, SagaId};
 use uuid::{self, Uuid};
@@ -34,6 +35,11 @@ impl InvoiceId {
     pub fn generate() -> Self {
         InvoiceId(Uuid::new_v4())
     }
+
+    /// Key of the advisory lock of the invoice, the first 8 bytes of the id
+    pub fn lock_key(&self) -> i64 {
+        self.0.as_bytes()[..8].iter().fold(0i64, |key, byte| (key << 8) | i64::from(*byte))
+    }
 }
 
 impl FromStr for InvoiceId {
@@ -51,6 +57,11 @@ impl Display for InvoiceId {
     }
 }
 
+/// Unguessable token of the public invoice status endpoint, minted when the invoice is created
+pub fn generate_public_token() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum PaymentFlow {
     Crypto,
@@ -71,6 +82,16 @@ pub struct RawInvoice {
     pub updated_at: NaiveDateTime,
     pub buyer_user_id: UserId,
     pub status: OrderState,
+    /// Marketplace metadata attached by saga, e.g. campaign id or order source
+    pub metadata: Option<serde_json::Value>,
+    /// Free-form note of a B2B buyer, printed on receipts
+    pub memo: Option<String>,
+    /// Purchase order number of a B2B buyer, printed on receipts
+    pub po_number: Option<String>,
+    /// Deadline for paying the invoice at the reserved exchange rates
+    pub price_reserved: NaiveDateTime,
+    /// Secret of the unauthenticated status endpoint polled by external payment pages
+    pub public_token: String,
 }
 
 impl RawInvoice {
@@ -81,6 +102,20 @@ impl RawInvoice {
             PaymentFlow::Crypto
         }
     }
+
+    pub fn receipt_description(&self) -> Option<String> {
+        receipt_description(self.memo.as_ref().map(String::as_str), self.po_number.as_ref().map(String::as_str))
+    }
+}
+
+/// Description shown on the payment receipt, e.g. "PO 4500012345: Q1 office supplies"
+pub fn receipt_description(memo: Option<&str>, po_number: Option<&str>) -> Option<String> {
+    match (memo, po_number) {
+        (Some(memo), Some(po_number)) => Some(format!("PO {}: {}", po_number, memo)),
+        (None, Some(po_number)) => Some(format!("PO {}", po_number)),
+        (Some(memo), None) => Some(memo.to_string()),
+        (None, None) => None,
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
@@ -107,6 +142,11 @@ pub struct NewInvoice {
     pub buyer_currency: Currency,
     pub amount_captured: Amount,
     pub buyer_user_id: UserId,
+    pub metadata: Option<serde_json::Value>,
+    pub memo: Option<String>,
+    pub po_number: Option<String>,
+    pub price_reserved: NaiveDateTime,
+    pub public_token: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
@@ -118,6 +158,11 @@ pub struct RawNewInvoice {
     pub amount_captured: Amount,
     pub buyer_user_id: UserId,
     pub status: OrderState,
+    pub metadata: Option<serde_json::Value>,
+    pub memo: Option<String>,
+    pub po_number: Option<String>,
+    pub price_reserved: NaiveDateTime,
+    pub public_token: String,
 }
 
 impl From<NewInvoice> for RawNewInvoice {
@@ -128,6 +173,11 @@ impl From<NewInvoice> for RawNewInvoice {
             buyer_currency,
             amount_captured,
             buyer_user_id,
+            metadata,
+            memo,
+            po_number,
+            price_reserved,
+            public_token,
         } = invoice;
 
         Self {
@@ -137,10 +187,24 @@ impl From<NewInvoice> for RawNewInvoice {
             amount_captured,
             buyer_user_id,
             status: OrderState::PaymentAwaited,
+            metadata,
+            memo,
+            po_number,
+            price_reserved,
+            public_token,
         }
     }
 }
 
+/// Buyer references that can be edited until the invoice is paid.
+/// `Some(None)` clears the field, `None` leaves it unchanged
+#[derive(Debug, Clone, Default, Serialize, Deserialize, AsChangeset)]
+#[table_name = "invoices_v2"]
+pub struct UpdateInvoiceDetails {
+    pub memo: Option<Option<String>>,
+    pub po_number: Option<Option<String>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InvoiceSetAmountPaid {
     pub final_amount_paid: Amount,
@@ -259,6 +323,11 @@ pub struct InvoiceDump {
     pub paid_at: Option<NaiveDateTime>,
     pub wallet_address: Option<WalletAddress>,
     pub status: OrderState,
+    pub metadata: Option<serde_json::Value>,
+    pub memo: Option<String>,
+    pub po_number: Option<String>,
+    pub price_reserved: NaiveDateTime,
+    pub public_token: String,
 }
 
 #[derive(Debug, Clone, Fail)]
@@ -270,6 +339,16 @@ pub enum InvoiceConversionError {
 }
 
 impl InvoiceDump {
+    /// Amount the buyer still has to pay, zero once the invoice is paid
+    pub fn amount_remaining(&self) -> BigDecimal {
+        let zero = BigDecimal::from(0);
+        if self.paid_at.is_some() || self.amount_captured >= self.total_price {
+            zero
+        } else {
+            self.total_price.clone() - self.amount_captured.clone()
+        }
+    }
+
     pub fn try_into_v1(self) -> Result<InvoiceV1, InvoiceConversionError> {
         let InvoiceDump {
             id,
@@ -278,6 +357,7 @@ impl InvoiceDump {
             amount_captured,
             total_price,
             status,
+            price_reserved,
             ..
         } = self;
 
@@ -298,7 +378,7 @@ impl InvoiceDump {
             transactions: json!([]),
             amount,
             currency: buyer_currency.into(),
-            price_reserved: SystemTime::now() + Duration::from_secs(300), // assume that the price is reserved for 5 mins
+            price_reserved: DateTime::<Utc>::from_utc(price_reserved, Utc).into(),
             state: status,
             wallet: wallet_address.map(|address| address.into_inner()),
             amount_captured,
@@ -328,6 +408,11 @@ pub fn calculate_invoice_price(
         created_at,
         paid_at,
         status,
+        metadata,
+        memo,
+        po_number,
+        price_reserved,
+        public_token,
         ..
     } = invoice;
 
@@ -406,6 +491,11 @@ pub fn calculate_invoice_price(
             paid_at: Some(paid_at),
             wallet_address,
             status,
+            metadata,
+            memo,
+            po_number,
+            price_reserved,
+            public_token,
         },
         _ => orders.clone().into_iter().fold(
             InvoiceDump {
@@ -420,6 +510,11 @@ pub fn calculate_invoice_price(
                 paid_at: None,
                 wallet_address,
                 status,
+                metadata,
+                memo,
+                po_number,
+                price_reserved,
+                public_token,
             },
             |mut invoice, order_price| {
                 if let Some(BuyerAmounts { price, .. }) = order_price.buyer_amounts {
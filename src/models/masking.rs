@@ -0,0 +1,28 @@
+//! Masking of sensitive values shown to users who may not see them in full
+
+const VISIBLE_CHARS: usize = 4;
+
+/// Replaces all but the last four characters with `*`, short values are masked completely
+pub fn mask(value: &str) -> String {
+    let len = value.chars().count();
+    let visible = if len > VISIBLE_CHARS * 2 { VISIBLE_CHARS } else { 0 };
+
+    value
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if i < len - visible { '*' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_last_characters_of_long_values() {
+        assert_eq!(mask("DE89370400440532013000"), "******************3000");
+        assert_eq!(mask("DEUTDEFF500"), "*******F500");
+        assert_eq!(mask("DEUTDEFF"), "********");
+        assert_eq!(mask(""), "");
+    }
+}
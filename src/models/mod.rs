@@ -2,69 +2,156 @@
 //! modules of the app
 
 pub mod account;
+pub mod account_assignment;
+pub mod account_pool;
 pub mod amount;
+pub mod analytics_event;
+pub mod api_key;
+pub mod audit_log;
 pub mod authorization;
+pub mod billing_info_change;
+pub mod cashback_liability;
 pub mod charge_id;
+pub mod checkout_session;
 pub mod currency;
 pub mod customer;
+pub mod customer_export;
 pub mod customer_id;
 pub mod daily_limit_type;
+pub mod data_retention;
 pub mod event;
 pub mod event_store;
+pub mod exchange_rate_slippage;
+pub mod feature_flag;
 pub mod fee;
+pub mod fee_adjustment;
+pub mod fee_crypto_payment;
+pub mod fee_dunning;
+pub mod fee_statement;
+pub mod historical_report;
 pub mod international_billing_info;
 pub mod invoice;
+pub mod invoice_buyer_details;
+pub mod invoice_callback;
+pub mod invoice_receipt;
+pub mod invoice_requote;
+pub mod invoice_risk_assessment;
+pub mod invoice_snapshot;
 pub mod invoice_v2;
+pub mod masking;
 pub mod merchant;
+pub mod negative_store_balance;
 pub mod order;
 pub mod order_billing;
+pub mod order_capture_approval;
 pub mod order_exchange_rate;
 pub mod order_info;
+pub mod order_line_item;
+pub mod order_state_update;
 pub mod order_v2;
+pub mod payment_attempt;
 pub mod payment_intent;
+pub mod payment_intent_history;
 pub mod payment_intents_fees;
 pub mod payment_intents_invoices;
+pub mod payment_recovery;
 pub mod payment_state;
 pub mod payout;
+pub mod payout_instruction;
+pub mod payout_restriction;
+pub mod payout_statement;
 pub mod proxy_companies_billing_info;
 pub mod role;
 pub mod russia_billing_info;
+pub mod schema_migration;
+pub mod setup_intent;
+pub mod store_balance;
+pub mod store_billing_status;
 pub mod store_billing_type;
+pub mod store_invoice;
+pub mod store_offboarding;
+pub mod store_webhook;
+pub mod stripe_fee_backfill;
 pub mod stripe_payout_id;
 pub mod subscription;
 pub mod transaction_id;
 pub mod user;
+pub mod user_credit;
 pub mod user_wallet;
+pub mod wallet_verification;
 
 pub use self::account::*;
+pub use self::account_assignment::*;
+pub use self::account_pool::*;
 pub use self::amount::*;
+pub use self::analytics_event::*;
+pub use self::api_key::*;
+pub use self::audit_log::*;
 pub use self::authorization::*;
+pub use self::billing_info_change::*;
+pub use self::cashback_liability::*;
 pub use self::charge_id::*;
+pub use self::checkout_session::*;
 pub use self::currency::*;
 pub use self::customer::*;
+pub use self::customer_export::*;
 pub use self::customer_id::*;
 pub use self::daily_limit_type::*;
+pub use self::data_retention::*;
 pub use self::event::*;
 pub use self::event_store::*;
+pub use self::exchange_rate_slippage::*;
+pub use self::feature_flag::*;
 pub use self::fee::*;
+pub use self::fee_adjustment::*;
+pub use self::fee_crypto_payment::*;
+pub use self::fee_dunning::*;
+pub use self::fee_statement::*;
+pub use self::historical_report::*;
 pub use self::international_billing_info::*;
 pub use self::invoice::*;
+pub use self::invoice_buyer_details::*;
+pub use self::invoice_callback::*;
+pub use self::invoice_receipt::*;
+pub use self::invoice_requote::*;
+pub use self::invoice_risk_assessment::*;
+pub use self::invoice_snapshot::*;
 pub use self::merchant::*;
+pub use self::negative_store_balance::*;
 pub use self::order::*;
 pub use self::order_billing::*;
+pub use self::order_capture_approval::*;
 pub use self::order_exchange_rate::*;
 pub use self::order_info::*;
+pub use self::order_line_item::*;
+pub use self::order_state_update::*;
+pub use self::payment_attempt::*;
 pub use self::payment_intent::*;
+pub use self::payment_intent_history::*;
 pub use self::payment_intents_fees::*;
 pub use self::payment_intents_invoices::*;
+pub use self::payment_recovery::*;
 pub use self::payment_state::*;
 pub use self::payout::*;
+pub use self::payout_instruction::*;
+pub use self::payout_restriction::*;
+pub use self::payout_statement::*;
 pub use self::proxy_companies_billing_info::*;
 pub use self::role::*;
 pub use self::russia_billing_info::*;
+pub use self::schema_migration::*;
+pub use self::setup_intent::*;
+pub use self::store_balance::*;
+pub use self::store_billing_status::*;
 pub use self::store_billing_type::*;
+pub use self::store_invoice::*;
+pub use self::store_offboarding::*;
+pub use self::store_webhook::*;
+pub use self::stripe_fee_backfill::*;
 pub use self::stripe_payout_id::*;
 pub use self::subscription::*;
 pub use self::transaction_id::*;
 pub use self::user::*;
+pub use self::user_credit::*;
 pub use self::user_wallet::*;
+pub use self::wallet_verification::*;
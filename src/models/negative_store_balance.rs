@@ -0,0 +1,62 @@
+use std::fmt::{self, Display};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use diesel::sql_types::Int4 as SqlInt4;
+
+use models::order_v2::StoreId;
+use models::{Amount, Currency};
+use schema::negative_store_balances;
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, Default, PartialEq)]
+#[sql_type = "SqlInt4"]
+pub struct NegativeStoreBalanceId(i32);
+derive_newtype_sql!(negative_store_balance_id, SqlInt4, NegativeStoreBalanceId, NegativeStoreBalanceId);
+
+impl NegativeStoreBalanceId {
+    pub fn new(id: i32) -> Self {
+        NegativeStoreBalanceId(id)
+    }
+
+    pub fn inner(&self) -> &i32 {
+        &self.0
+    }
+}
+
+impl FromStr for NegativeStoreBalanceId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s.parse()?;
+        Ok(NegativeStoreBalanceId::new(id))
+    }
+}
+
+impl Display for NegativeStoreBalanceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0,))
+    }
+}
+
+/// Flag raised when a store owes more in a currency than its unpaid orders cover.
+/// `amount` is the deficit as of the last balance check, the flag stays open until the balance recovers
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct NegativeStoreBalance {
+    pub id: NegativeStoreBalanceId,
+    pub store_id: StoreId,
+    pub currency: Currency,
+    pub amount: Amount,
+    pub detected_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub notified_at: Option<NaiveDateTime>,
+    pub resolved_at: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "negative_store_balances"]
+pub struct NewNegativeStoreBalance {
+    pub store_id: StoreId,
+    pub currency: Currency,
+    pub amount: Amount,
+}
@@ -1,11 +1,16 @@
+use serde_json;
 use std::fmt;
+use std::net::IpAddr;
 use stq_static_resources::Currency as StqCurrency;
 use stq_types::*;
 use stq_types::{OrderId as StqOrderId, StoreId as StqStoreId, UserId as StqUserId};
 
 use models::invoice_v2::InvoiceId;
 use models::order_v2::{OrderId, StoreId};
-use models::{currency::ConversionError as CurrencyConversionError, Currency, UserId};
+use models::{
+    currency::ConversionError as CurrencyConversionError, BuyerBillingDetails, CreateOrderLineItem, Currency, InvoiceCallbackRegistration,
+    UserId,
+};
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Order {
@@ -48,7 +53,7 @@ impl fmt::Display for CreateInvoice {
     }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CreateOrderV2 {
     pub id: OrderId,
     #[serde(rename = "store")]
@@ -56,6 +61,9 @@ pub struct CreateOrderV2 {
     pub currency: Currency,
     pub total_amount: f64,
     pub product_cashback: Option<f64>,
+    /// Products of the order, their amounts add up to `total_amount`. They are kept for disputes and refunded one by one
+    #[serde(default)]
+    pub line_items: Vec<CreateOrderLineItem>,
 }
 
 impl fmt::Display for CreateOrderV2 {
@@ -88,6 +96,7 @@ impl CreateOrderV2 {
             currency,
             total_amount: total_amount.0,
             product_cashback: product_cashback.map(|product_cashback| product_cashback.0),
+            line_items: Vec::new(),
         })
     }
 }
@@ -98,6 +107,30 @@ pub struct CreateInvoiceV2 {
     pub customer_id: UserId,
     pub currency: Currency,
     pub saga_id: InvoiceId,
+    /// Marketplace metadata, stored with the invoice and returned in callbacks and reports
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+    /// B2B buyer note, printed on receipts
+    #[serde(default)]
+    pub memo: Option<String>,
+    /// B2B buyer purchase order number, printed on receipts
+    #[serde(default)]
+    pub po_number: Option<String>,
+    /// Country of the buyer, restricts the currencies the invoice can be paid in
+    #[serde(default)]
+    pub buyer_country: Option<Alpha3>,
+    /// Url notified of the status changes of the invoice, independently of the saga
+    #[serde(default)]
+    pub callback: Option<InvoiceCallbackRegistration>,
+    /// IP the buyer checks out from, a signal of the risk scoring of the invoice
+    #[serde(default)]
+    pub buyer_ip: Option<IpAddr>,
+    /// Spend the platform credit of the buyer in the invoice currency, only crypto invoices accept credit
+    #[serde(default)]
+    pub apply_credit: bool,
+    /// Billing name and address of the buyer, printed on receipts. Stored encrypted
+    #[serde(default)]
+    pub buyer_billing_details: Option<BuyerBillingDetails>,
 }
 
 impl CreateInvoiceV2 {
@@ -120,6 +153,14 @@ impl CreateInvoiceV2 {
             customer_id,
             currency,
             saga_id,
+            metadata: None,
+            memo: None,
+            po_number: None,
+            buyer_country: None,
+            callback: None,
+            buyer_ip: None,
+            apply_credit: false,
+            buyer_billing_details: None,
         })
     }
 }
@@ -1,3 +1,4 @@
+use serde_json;
 use stq_types::{BillingType, StoreId};
 
 use controller::responses::OrderResponse;
@@ -18,6 +19,9 @@ pub struct OrderBillingInfo {
     pub proxy_company_billing_info: Option<ProxyCompanyBillingInfo>,
     pub russia_billing_info: Option<RussiaBillingInfo>,
     pub international_billing_info: Option<InternationalBillingInfo>,
+    pub invoice_metadata: Option<serde_json::Value>,
+    pub invoice_memo: Option<String>,
+    pub invoice_po_number: Option<String>,
 }
 
 #[derive(Serialize, Clone, Debug)]
@@ -0,0 +1,114 @@
+use chrono::NaiveDateTime;
+use stq_types::stripe::PaymentIntentId;
+
+use models::order_v2::{OrderId, RawOrder};
+use models::{Amount, PaymentState};
+use schema::order_capture_approvals;
+
+/// Approval of the seller to capture the funds of the order authorized by a payment intent
+#[derive(Clone, Debug, Deserialize, Serialize, Queryable)]
+pub struct OrderCaptureApproval {
+    pub order_id: OrderId,
+    pub payment_intent_id: PaymentIntentId,
+    pub approved_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Insertable)]
+#[table_name = "order_capture_approvals"]
+pub struct NewOrderCaptureApproval {
+    pub order_id: OrderId,
+    pub payment_intent_id: PaymentIntentId,
+}
+
+/// What to do with a payment intent authorizing the funds of several orders
+#[derive(Clone, Debug, PartialEq)]
+pub enum PaymentIntentCaptureDecision {
+    /// Some orders are neither approved nor declined yet
+    Wait,
+    /// Capture the approved orders in one go, the authorization of the rest is released
+    Capture {
+        amount: Amount,
+        captured_order_ids: Vec<OrderId>,
+        released_order_ids: Vec<OrderId>,
+    },
+    /// No order has been approved, the whole authorization is released
+    Cancel { released_order_ids: Vec<OrderId> },
+}
+
+impl PaymentIntentCaptureDecision {
+    /// Orders are waited for until all of them are approved or declined, or until the capture timeout.
+    /// `None` is returned when the amount to capture overflows
+    pub fn new(orders: &[RawOrder], approved_order_ids: &[OrderId], timed_out: bool) -> Option<Self> {
+        let pending_orders = orders
+            .iter()
+            .filter(|order| order.state == PaymentState::Initial)
+            .collect::<Vec<_>>();
+
+        let (approved, not_approved): (Vec<&RawOrder>, Vec<&RawOrder>) =
+            pending_orders.into_iter().partition(|order| approved_order_ids.contains(&order.id));
+
+        if !not_approved.is_empty() && !timed_out {
+            return Some(PaymentIntentCaptureDecision::Wait);
+        }
+
+        let released_order_ids = not_approved.iter().map(|order| order.id).collect::<Vec<_>>();
+        if approved.is_empty() {
+            return Some(PaymentIntentCaptureDecision::Cancel { released_order_ids });
+        }
+
+        let amount = approved
+            .iter()
+            .try_fold(Amount::zero(), |acc, order| acc.checked_add(order.total_amount))?;
+
+        Some(PaymentIntentCaptureDecision::Capture {
+            amount,
+            captured_order_ids: approved.iter().map(|order| order.id).collect(),
+            released_order_ids,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_support::RawOrderBuilder;
+
+    use super::*;
+
+    #[test]
+    fn capture_waits_for_all_orders_until_timeout() {
+        let approved = RawOrderBuilder::new().total_amount(Amount::new(1000)).build();
+        let not_approved = RawOrderBuilder::new().total_amount(Amount::new(2000)).build();
+        let declined = RawOrderBuilder::new()
+            .state(PaymentState::Declined)
+            .total_amount(Amount::new(4000))
+            .build();
+        let orders = vec![approved.clone(), not_approved.clone(), declined];
+
+        assert_eq!(
+            PaymentIntentCaptureDecision::new(&orders, &[approved.id], false),
+            Some(PaymentIntentCaptureDecision::Wait)
+        );
+        assert_eq!(
+            PaymentIntentCaptureDecision::new(&orders, &[approved.id, not_approved.id], false),
+            Some(PaymentIntentCaptureDecision::Capture {
+                amount: Amount::new(3000),
+                captured_order_ids: vec![approved.id, not_approved.id],
+                released_order_ids: vec![],
+            })
+        );
+        assert_eq!(
+            PaymentIntentCaptureDecision::new(&orders, &[approved.id], true),
+            Some(PaymentIntentCaptureDecision::Capture {
+                amount: Amount::new(1000),
+                captured_order_ids: vec![approved.id],
+                released_order_ids: vec![not_approved.id],
+            })
+        );
+        assert_eq!(
+            PaymentIntentCaptureDecision::new(&orders, &[], true),
+            Some(PaymentIntentCaptureDecision::Cancel {
+                released_order_ids: vec![approved.id, not_approved.id],
+            })
+        );
+    }
+}
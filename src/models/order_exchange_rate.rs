@@ -81,6 +81,28 @@ pub struct RawOrderExchangeRate {
     pub updated_at: NaiveDateTime,
 }
 
+impl RawOrderExchangeRate {
+    pub fn source(&self) -> ExchangeRateSource {
+        match self.exchange_id {
+            Some(exchange_id) => ExchangeRateSource::Gateway { exchange_id },
+            None => ExchangeRateSource::Dummy,
+        }
+    }
+
+    /// Checks whether the rate was the active one at the moment the invoice got paid
+    pub fn was_active_at(&self, moment: NaiveDateTime) -> bool {
+        self.created_at <= moment && (self.status == ExchangeRateStatus::Active || self.updated_at >= moment)
+    }
+}
+
+/// Where the rate came from - either reserved in Payments gateway or a dummy rate (1.0) for same-currency orders
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExchangeRateSource {
+    Gateway { exchange_id: ExchangeId },
+    Dummy,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NewOrderExchangeRate {
     pub order_id: OrderId,
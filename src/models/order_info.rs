@@ -5,7 +5,7 @@ use stq_types::{OrderId, OrderInfoId, ProductPrice, SagaId, StoreId, UserId};
 
 use schema::orders_info;
 
-#[derive(Serialize, Queryable, Insertable, Debug)]
+#[derive(Serialize, Queryable, Insertable, Debug, Clone)]
 #[table_name = "orders_info"]
 pub struct OrderInfo {
     pub id: OrderInfoId,
@@ -22,11 +22,11 @@ pub struct OrderInfo {
 #[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
 #[table_name = "orders_info"]
 pub struct NewOrderInfo {
-    order_id: OrderId,
-    customer_id: UserId,
-    store_id: StoreId,
-    saga_id: SagaId,
-    total_amount: ProductPrice,
+    pub order_id: OrderId,
+    pub customer_id: UserId,
+    pub store_id: StoreId,
+    pub saga_id: SagaId,
+    pub total_amount: ProductPrice,
 }
 
 impl NewOrderInfo {
@@ -0,0 +1,194 @@
+use std::fmt::{self, Display};
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use diesel::sql_types::Uuid as SqlUuid;
+use uuid::Uuid;
+
+use stq_types::ProductId;
+
+use models::order_v2::OrderId;
+use models::{Amount, Currency};
+use schema::order_line_items;
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, PartialEq, Eq, Hash)]
+#[sql_type = "SqlUuid"]
+pub struct OrderLineItemId(Uuid);
+derive_newtype_sql!(order_line_item_id, SqlUuid, OrderLineItemId, OrderLineItemId);
+
+impl OrderLineItemId {
+    pub fn new(id: Uuid) -> Self {
+        OrderLineItemId(id)
+    }
+
+    pub fn inner(&self) -> &Uuid {
+        &self.0
+    }
+
+    pub fn generate() -> Self {
+        OrderLineItemId(Uuid::new_v4())
+    }
+}
+
+impl Display for OrderLineItemId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0.hyphenated()))
+    }
+}
+
+/// Product the buyer pays for with an order, as the marketplace gives it when the invoice is created
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CreateOrderLineItem {
+    pub product_id: ProductId,
+    pub name: String,
+    pub quantity: u32,
+    /// Price of a single unit in the seller currency
+    pub unit_price: BigDecimal,
+}
+
+impl CreateOrderLineItem {
+    /// `None` is returned when the price is not positive in the minimal units of the currency or the amount overflows
+    pub fn amount(&self, currency: Currency) -> Option<Amount> {
+        if self.unit_price <= BigDecimal::from(0) {
+            return None;
+        }
+
+        Amount::checked_from_super_unit(currency, self.unit_price.clone())?
+            .checked_mul(Amount::new(u128::from(self.quantity)))
+            .filter(|amount| *amount > Amount::zero())
+    }
+}
+
+/// Total amount of the line items of an order, `None` is returned when the amount of any of them can't be calculated
+pub fn order_line_items_total(currency: Currency, line_items: &[CreateOrderLineItem]) -> Option<Amount> {
+    line_items
+        .iter()
+        .try_fold(Amount::zero(), |total, line_item| total.checked_add(line_item.amount(currency)?))
+}
+
+/// Line item of an order. `refunded_quantity` units of it have been refunded to the buyer
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct OrderLineItem {
+    pub id: OrderLineItemId,
+    pub order_id: OrderId,
+    pub product_id: ProductId,
+    pub name: String,
+    pub quantity: i32,
+    /// Price of a single unit in the seller currency
+    pub unit_price: Amount,
+    pub refunded_quantity: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl OrderLineItem {
+    /// Units that haven't been refunded yet
+    pub fn refundable_quantity(&self) -> i32 {
+        self.quantity - self.refunded_quantity
+    }
+
+    /// Amount of `quantity` units of the line item, `None` is returned when the quantity is negative or the amount overflows
+    pub fn amount_of(&self, quantity: i32) -> Option<Amount> {
+        if quantity < 0 {
+            return None;
+        }
+
+        self.unit_price.checked_mul(Amount::new(quantity as u128))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "order_line_items"]
+pub struct NewOrderLineItem {
+    pub id: OrderLineItemId,
+    pub order_id: OrderId,
+    pub product_id: ProductId,
+    pub name: String,
+    pub quantity: i32,
+    pub unit_price: Amount,
+}
+
+impl NewOrderLineItem {
+    /// `None` is returned when the price is not positive in the minimal units of the currency or the quantity is out of range
+    pub fn new(order_id: OrderId, currency: Currency, line_item: CreateOrderLineItem) -> Option<Self> {
+        if line_item.amount(currency).is_none() || line_item.quantity > i32::max_value() as u32 {
+            return None;
+        }
+
+        let CreateOrderLineItem {
+            product_id,
+            name,
+            quantity,
+            unit_price,
+        } = line_item;
+
+        Some(NewOrderLineItem {
+            id: OrderLineItemId::generate(),
+            order_id,
+            product_id,
+            name: name.trim().to_string(),
+            quantity: quantity as i32,
+            unit_price: Amount::checked_from_super_unit(currency, unit_price)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn line_item(quantity: u32, unit_price: &str) -> CreateOrderLineItem {
+        CreateOrderLineItem {
+            product_id: ProductId(1),
+            name: " Mug ".to_string(),
+            quantity,
+            unit_price: BigDecimal::from_str(unit_price).unwrap(),
+        }
+    }
+
+    #[test]
+    fn order_line_items_total_is_exact_in_minimal_units() {
+        let line_items = vec![line_item(3, "0.1"), line_item(1, "19.99")];
+        assert_eq!(order_line_items_total(Currency::Usd, &line_items), Some(Amount::new(2029)));
+
+        assert_eq!(order_line_items_total(Currency::Usd, &[line_item(0, "10")]), None);
+        assert_eq!(order_line_items_total(Currency::Usd, &[line_item(1, "-10")]), None);
+        assert_eq!(order_line_items_total(Currency::Usd, &[line_item(1, "0.001")]), None);
+    }
+
+    #[test]
+    fn new_line_item_keeps_the_unit_price_in_minimal_units() {
+        let order_id = OrderId::generate();
+        let new_line_item = NewOrderLineItem::new(order_id, Currency::Usd, line_item(3, "19.99")).unwrap();
+        assert_eq!(new_line_item.name, "Mug");
+        assert_eq!(new_line_item.quantity, 3);
+        assert_eq!(new_line_item.unit_price, Amount::new(1999));
+
+        assert!(NewOrderLineItem::new(order_id, Currency::Usd, line_item(u32::max_value(), "1")).is_none());
+        assert!(NewOrderLineItem::new(order_id, Currency::Usd, line_item(1, "0")).is_none());
+    }
+
+    #[test]
+    fn refunds_are_priced_by_the_unit() {
+        let created_at = NaiveDate::from_ymd(2019, 5, 2).and_hms(9, 0, 0);
+        let line_item = OrderLineItem {
+            id: OrderLineItemId::generate(),
+            order_id: OrderId::generate(),
+            product_id: ProductId(1),
+            name: "Mug".to_string(),
+            quantity: 3,
+            unit_price: Amount::new(1999),
+            refunded_quantity: 1,
+            created_at,
+            updated_at: created_at,
+        };
+
+        assert_eq!(line_item.refundable_quantity(), 2);
+        assert_eq!(line_item.amount_of(2), Some(Amount::new(3998)));
+        assert_eq!(line_item.amount_of(-1), None);
+    }
+}
@@ -0,0 +1,88 @@
+//! Order state transitions reported to saga
+use std::collections::HashSet;
+
+use serde_json;
+use stq_static_resources::OrderState;
+
+use models::order_v2::{OrderId, StoreId};
+use models::{OrderInfo, UserId};
+
+/// Number of order state updates delivered to saga with a single request
+pub const ORDER_STATE_UPDATES_BATCH_SIZE: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderStateUpdate {
+    pub order_id: OrderId,
+    pub store_id: StoreId,
+    pub customer_id: UserId,
+    pub status: OrderState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+impl<'a> From<&'a OrderInfo> for OrderStateUpdate {
+    fn from(order_info: &'a OrderInfo) -> Self {
+        Self {
+            order_id: OrderId::new(order_info.order_id.0),
+            store_id: StoreId::new(order_info.store_id.0),
+            customer_id: UserId::new(order_info.customer_id.0),
+            status: order_info.status,
+            metadata: None,
+        }
+    }
+}
+
+/// Splits order state updates into batches that are delivered with a single request each.
+/// An order updated several times keeps only its latest state
+pub fn batch_order_state_updates(order_state_updates: Vec<OrderStateUpdate>) -> Vec<Vec<OrderStateUpdate>> {
+    let mut updated_orders = HashSet::new();
+    let mut latest_updates = order_state_updates
+        .into_iter()
+        .rev()
+        .filter(|update| updated_orders.insert(update.order_id))
+        .collect::<Vec<_>>();
+    latest_updates.reverse();
+
+    latest_updates
+        .chunks(ORDER_STATE_UPDATES_BATCH_SIZE)
+        .map(|batch| batch.to_vec())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn update(order_id: OrderId, status: OrderState) -> OrderStateUpdate {
+        OrderStateUpdate {
+            order_id,
+            store_id: StoreId::new(1),
+            customer_id: UserId::new(1),
+            status,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn batches_keep_latest_state_of_each_order() {
+        let order_ids = (0..ORDER_STATE_UPDATES_BATCH_SIZE + 1)
+            .map(|_| OrderId::new(Uuid::new_v4()))
+            .collect::<Vec<_>>();
+        let mut updates = order_ids
+            .iter()
+            .map(|order_id| update(*order_id, OrderState::TransactionPending))
+            .collect::<Vec<_>>();
+        updates.push(update(order_ids[0], OrderState::Paid));
+
+        let batches = batch_order_state_updates(updates);
+
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].len(), ORDER_STATE_UPDATES_BATCH_SIZE);
+        assert_eq!(batches[0][0].order_id, order_ids[1]);
+        let last_update = &batches[1][0];
+        assert_eq!(last_update.order_id, order_ids[0]);
+        assert_eq!(last_update.status, OrderState::Paid);
+    }
+}
@@ -10,6 +10,7 @@ use diesel::{
     deserialize,
     serialize::{self, Output},
 };
+use failure::Error as FailureError;
 use uuid::{self, Uuid};
 
 use models::invoice_v2::InvoiceId;
@@ -139,12 +140,45 @@ pub struct OrderAccess {
     pub store_id: StoreId,
 }
 
+/// Payment status of an order derived from its invoice and refunds, as shown on the tabs of store dashboards
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoicePaymentStatus {
+    /// The invoice is not paid yet and can still be paid
+    AwaitingPayment,
+    /// The invoice is paid and the order has not been refunded
+    Paid,
+    /// The order has been refunded or is waiting for the refund
+    Refunded,
+}
+
+impl InvoicePaymentStatus {
+    /// Order states of the refunded orders, a declined order also counts once its refund is recorded as a fee adjustment
+    pub fn refunded_states() -> Vec<PaymentState> {
+        vec![PaymentState::RefundNeeded, PaymentState::Refunded]
+    }
+}
+
+impl FromStr for InvoicePaymentStatus {
+    type Err = FailureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "awaiting_payment" => Ok(InvoicePaymentStatus::AwaitingPayment),
+            "paid" => Ok(InvoicePaymentStatus::Paid),
+            "refunded" => Ok(InvoicePaymentStatus::Refunded),
+            _ => Err(format_err!("Unknown invoice payment status \"{}\"", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct OrdersSearch {
     pub store_id: Option<StoreId>,
     pub state: Option<PaymentState>,
     pub order_id: Option<OrderId>,
     pub order_ids: Option<Vec<OrderId>>,
+    pub payment_status: Option<InvoicePaymentStatus>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -0,0 +1,63 @@
+use chrono::NaiveDateTime;
+use diesel::sql_types::BigInt;
+
+use stq_types::stripe::PaymentIntentId;
+
+use models::invoice_v2::InvoiceId;
+use schema::payment_attempts;
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, PartialEq, Eq, FromStr, Display)]
+#[sql_type = "BigInt"]
+pub struct PaymentAttemptId(i64);
+newtype_from_to_sql!(BigInt, PaymentAttemptId, PaymentAttemptId);
+
+impl PaymentAttemptId {
+    pub fn new(id: i64) -> Self {
+        PaymentAttemptId(id)
+    }
+
+    pub fn inner(&self) -> i64 {
+        self.0
+    }
+}
+
+/// Outcome of an attempt to pay an invoice in the fiat flow, as reported by a Stripe webhook
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentAttemptStatus {
+    /// The payment was captured
+    Succeeded,
+    /// The payment was authorized and awaits a manual capture
+    Authorized,
+    /// The card was declined by the issuer, see `decline_code`
+    Declined,
+    /// The buyer didn't complete the authentication (3D Secure) of the payment
+    AuthenticationFailed,
+    /// Any other failure, see `error_code`
+    Failed,
+}
+
+/// Attempt to pay an invoice, attempts are only appended
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+pub struct PaymentAttempt {
+    pub id: PaymentAttemptId,
+    pub invoice_id: InvoiceId,
+    pub payment_intent_id: PaymentIntentId,
+    pub status: PaymentAttemptStatus,
+    pub decline_code: Option<String>,
+    pub error_code: Option<String>,
+    /// Stripe fingerprint of the card, identical for all payment methods of the same card number
+    pub payment_method_fingerprint: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[table_name = "payment_attempts"]
+pub struct NewPaymentAttempt {
+    pub invoice_id: InvoiceId,
+    pub payment_intent_id: PaymentIntentId,
+    pub status: PaymentAttemptStatus,
+    pub decline_code: Option<String>,
+    pub error_code: Option<String>,
+    pub payment_method_fingerprint: Option<String>,
+}
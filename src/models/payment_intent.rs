@@ -18,6 +18,8 @@ pub struct PaymentIntent {
     pub status: PaymentIntentStatus,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    /// Creation time of the webhook event the status comes from, none if the status comes from an API call
+    pub status_event_created_at: Option<NaiveDateTime>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, Queryable, Insertable)]
@@ -45,6 +47,7 @@ pub struct UpdatePaymentIntent {
     pub last_payment_error_message: Option<String>,
     pub receipt_email: Option<String>,
     pub charge_id: Option<ChargeId>,
+    pub status_event_created_at: Option<NaiveDateTime>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, DieselTypes, PartialEq, Eq)]
@@ -71,6 +74,53 @@ impl PaymentIntentStatus {
             _ => false,
         }
     }
+
+    /// Whether the buyer can confirm the payment intent again after its payment failed
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            PaymentIntentStatus::RequiresSource | PaymentIntentStatus::RequiresConfirmation | PaymentIntentStatus::RequiresSourceAction => {
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Stripe never moves a payment intent out of these statuses
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            PaymentIntentStatus::Canceled | PaymentIntentStatus::Succeeded => true,
+            _ => false,
+        }
+    }
+
+    /// How far the payment has gone, breaks the tie between webhooks created in the same second
+    fn precedence(&self) -> u8 {
+        match self {
+            PaymentIntentStatus::Other => 0,
+            PaymentIntentStatus::RequiresSource | PaymentIntentStatus::RequiresConfirmation | PaymentIntentStatus::RequiresSourceAction => {
+                1
+            }
+            PaymentIntentStatus::Processing => 2,
+            PaymentIntentStatus::RequiresCapture => 3,
+            PaymentIntentStatus::Canceled | PaymentIntentStatus::Succeeded => 4,
+        }
+    }
+}
+
+impl PaymentIntent {
+    /// Whether a webhook with `status` created at `event_created_at` may replace the current status.
+    /// Stripe doesn't guarantee the delivery order of webhooks, so a terminal status is kept, an event older
+    /// than the one the status comes from is stale, and events of the same second are ordered by precedence
+    pub fn accepts_webhook_status(&self, status: &PaymentIntentStatus, event_created_at: Option<NaiveDateTime>) -> bool {
+        if self.status.is_terminal() {
+            return self.status == *status;
+        }
+
+        match (self.status_event_created_at, event_created_at) {
+            (Some(current), Some(new)) if current != new => new > current,
+            _ => status.precedence() >= self.status.precedence(),
+        }
+    }
 }
 
 pub struct PaymentIntentAccess {
@@ -100,3 +150,119 @@ impl From<stripe::PaymentIntentStatus> for PaymentIntentStatus {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_support::builders::PaymentIntentBuilder;
+
+    fn at(secs: i64) -> Option<NaiveDateTime> {
+        Some(NaiveDateTime::from_timestamp(1_555_000_000 + secs, 0))
+    }
+
+    /// Applies the webhooks in the given order and returns the resulting status
+    fn resolve(webhooks: &[(PaymentIntentStatus, Option<NaiveDateTime>)]) -> PaymentIntentStatus {
+        let mut payment_intent = PaymentIntentBuilder::default().status(PaymentIntentStatus::RequiresSource).build();
+        for (status, event_created_at) in webhooks {
+            if payment_intent.accepts_webhook_status(status, *event_created_at) {
+                payment_intent.status = status.clone();
+                payment_intent.status_event_created_at = *event_created_at;
+            }
+        }
+        payment_intent.status
+    }
+
+    fn permutations<T: Clone>(items: &[T]) -> Vec<Vec<T>> {
+        if items.len() <= 1 {
+            return vec![items.to_vec()];
+        }
+
+        let mut result = Vec::new();
+        for i in 0..items.len() {
+            let mut rest = items.to_vec();
+            let first = rest.remove(i);
+            for mut permutation in permutations(&rest) {
+                permutation.insert(0, first.clone());
+                result.push(permutation);
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn webhooks_in_any_order_end_in_the_latest_status() {
+        let webhooks = vec![
+            (PaymentIntentStatus::RequiresSource, at(0)),
+            (PaymentIntentStatus::Processing, at(1)),
+            (PaymentIntentStatus::RequiresCapture, at(2)),
+            (PaymentIntentStatus::Succeeded, at(3)),
+        ];
+        for permutation in permutations(&webhooks) {
+            assert_eq!(resolve(&permutation), PaymentIntentStatus::Succeeded, "{:?}", permutation);
+        }
+
+        let webhooks = vec![
+            (PaymentIntentStatus::Processing, at(0)),
+            (PaymentIntentStatus::RequiresSource, at(1)),
+            (PaymentIntentStatus::Processing, at(2)),
+            (PaymentIntentStatus::RequiresCapture, at(3)),
+        ];
+        for permutation in permutations(&webhooks) {
+            assert_eq!(resolve(&permutation), PaymentIntentStatus::RequiresCapture, "{:?}", permutation);
+        }
+    }
+
+    #[test]
+    fn webhooks_of_the_same_second_are_ordered_by_precedence() {
+        let webhooks = vec![
+            (PaymentIntentStatus::RequiresSource, at(0)),
+            (PaymentIntentStatus::Processing, at(0)),
+            (PaymentIntentStatus::RequiresCapture, at(0)),
+        ];
+        for permutation in permutations(&webhooks) {
+            assert_eq!(resolve(&permutation), PaymentIntentStatus::RequiresCapture, "{:?}", permutation);
+        }
+
+        let webhooks = vec![
+            (PaymentIntentStatus::RequiresSource, None),
+            (PaymentIntentStatus::Processing, None),
+            (PaymentIntentStatus::Succeeded, None),
+        ];
+        for permutation in permutations(&webhooks) {
+            assert_eq!(resolve(&permutation), PaymentIntentStatus::Succeeded, "{:?}", permutation);
+        }
+    }
+
+    #[test]
+    fn terminal_statuses_never_regress() {
+        assert_eq!(
+            resolve(&[
+                (PaymentIntentStatus::Succeeded, at(0)),
+                (PaymentIntentStatus::RequiresSource, at(5))
+            ]),
+            PaymentIntentStatus::Succeeded
+        );
+        assert_eq!(
+            resolve(&[(PaymentIntentStatus::Canceled, at(0)), (PaymentIntentStatus::Succeeded, at(5))]),
+            PaymentIntentStatus::Canceled
+        );
+    }
+
+    #[test]
+    fn newer_failures_replace_older_progress() {
+        assert_eq!(
+            resolve(&[
+                (PaymentIntentStatus::Processing, at(0)),
+                (PaymentIntentStatus::RequiresSource, at(1))
+            ]),
+            PaymentIntentStatus::RequiresSource
+        );
+        assert_eq!(
+            resolve(&[
+                (PaymentIntentStatus::RequiresSource, at(1)),
+                (PaymentIntentStatus::Processing, at(0))
+            ]),
+            PaymentIntentStatus::RequiresSource
+        );
+    }
+}
@@ -0,0 +1,50 @@
+use chrono::NaiveDateTime;
+use diesel::sql_types::BigInt;
+
+use stq_types::stripe::PaymentIntentId;
+
+use models::PaymentIntentStatus;
+use schema::payment_intent_history;
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, PartialEq, Eq, FromStr, Display)]
+#[sql_type = "BigInt"]
+pub struct PaymentIntentHistoryEntryId(i64);
+newtype_from_to_sql!(BigInt, PaymentIntentHistoryEntryId, PaymentIntentHistoryEntryId);
+
+impl PaymentIntentHistoryEntryId {
+    pub fn new(id: i64) -> Self {
+        PaymentIntentHistoryEntryId(id)
+    }
+
+    pub fn inner(&self) -> i64 {
+        self.0
+    }
+}
+
+/// Where a status of a payment intent was observed
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentIntentHistorySource {
+    /// Stripe webhook event
+    Webhook,
+    /// Response of a Stripe API call: creation, retrieval, capture or cancellation
+    Api,
+}
+
+/// Status transition of a payment intent, entries are only appended
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+pub struct PaymentIntentHistoryEntry {
+    pub id: PaymentIntentHistoryEntryId,
+    pub payment_intent_id: PaymentIntentId,
+    pub status: PaymentIntentStatus,
+    pub source: PaymentIntentHistorySource,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[table_name = "payment_intent_history"]
+pub struct NewPaymentIntentHistoryEntry {
+    pub payment_intent_id: PaymentIntentId,
+    pub status: PaymentIntentStatus,
+    pub source: PaymentIntentHistorySource,
+}
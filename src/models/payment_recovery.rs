@@ -0,0 +1,115 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use diesel::sql_types::Uuid as SqlUuid;
+use stq_types::stripe::PaymentIntentId;
+use uuid::{self, Uuid};
+
+use models::invoice_v2::InvoiceId;
+use models::UserId;
+use schema::payment_recoveries;
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, PartialEq, Eq, Hash)]
+#[sql_type = "SqlUuid"]
+pub struct PaymentRecoveryId(Uuid);
+derive_newtype_sql!(payment_recovery, SqlUuid, PaymentRecoveryId, PaymentRecoveryId);
+
+impl PaymentRecoveryId {
+    pub fn new(id: Uuid) -> Self {
+        PaymentRecoveryId(id)
+    }
+
+    pub fn inner(&self) -> &Uuid {
+        &self.0
+    }
+
+    pub fn generate() -> Self {
+        PaymentRecoveryId(Uuid::new_v4())
+    }
+}
+
+impl FromStr for PaymentRecoveryId {
+    type Err = uuid::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = Uuid::parse_str(s)?;
+        Ok(PaymentRecoveryId::new(id))
+    }
+}
+
+impl Display for PaymentRecoveryId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0.hyphenated()))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentRecoveryStatus {
+    /// The buyer is about to be notified of the failed payment
+    Pending,
+    /// The buyer got the email with the retry link
+    Notified,
+    /// The buyer followed the retry link
+    Retried,
+    /// The invoice was paid after the failure
+    Recovered,
+    /// The invoice expired without being paid
+    Expired,
+    /// The buyer cancelled the invoice instead of retrying the payment
+    Cancelled,
+}
+
+impl PaymentRecoveryStatus {
+    /// Whether the invoice may still be paid
+    pub fn is_open(&self) -> bool {
+        match self {
+            PaymentRecoveryStatus::Pending | PaymentRecoveryStatus::Notified | PaymentRecoveryStatus::Retried => true,
+            PaymentRecoveryStatus::Recovered | PaymentRecoveryStatus::Expired | PaymentRecoveryStatus::Cancelled => false,
+        }
+    }
+}
+
+/// Recovery of a failed fiat payment of an invoice. There is one recovery per invoice,
+/// a failure of the retried payment is counted in `failures_count` and notifies the buyer again
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct PaymentRecovery {
+    pub id: PaymentRecoveryId,
+    pub invoice_id: InvoiceId,
+    pub buyer_user_id: UserId,
+    pub status: PaymentRecoveryStatus,
+    pub failed_payment_intent_id: PaymentIntentId,
+    pub failure_message: Option<String>,
+    pub failures_count: i32,
+    pub retry_payment_intent_id: Option<PaymentIntentId>,
+    pub notified_at: Option<NaiveDateTime>,
+    pub retried_at: Option<NaiveDateTime>,
+    pub closed_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "payment_recoveries"]
+pub struct NewPaymentRecovery {
+    pub id: PaymentRecoveryId,
+    pub invoice_id: InvoiceId,
+    pub buyer_user_id: UserId,
+    pub status: PaymentRecoveryStatus,
+    pub failed_payment_intent_id: PaymentIntentId,
+    pub failure_message: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, AsChangeset)]
+#[table_name = "payment_recoveries"]
+pub struct UpdatePaymentRecovery {
+    pub status: Option<PaymentRecoveryStatus>,
+    pub failed_payment_intent_id: Option<PaymentIntentId>,
+    pub failure_message: Option<Option<String>>,
+    pub failures_count: Option<i32>,
+    pub retry_payment_intent_id: Option<Option<PaymentIntentId>>,
+    pub notified_at: Option<Option<NaiveDateTime>>,
+    pub retried_at: Option<Option<NaiveDateTime>>,
+    pub closed_at: Option<Option<NaiveDateTime>>,
+}
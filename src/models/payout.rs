@@ -2,6 +2,8 @@ use std::collections::HashMap;
 use std::fmt;
 
 use chrono::NaiveDateTime;
+use hex;
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
 use models::order_v2::OrderId;
@@ -36,6 +38,26 @@ impl fmt::Display for PayoutId {
     }
 }
 
+/// Client-supplied ID of a payout request, a retried request with the same ID returns the payout created by the first one
+#[derive(Debug, Serialize, Deserialize, FromStr, AsExpression, Clone, Copy, PartialEq, Eq, Hash, DieselTypes)]
+pub struct PayoutRequestId(Uuid);
+
+impl PayoutRequestId {
+    pub fn new(id: Uuid) -> Self {
+        PayoutRequestId(id)
+    }
+
+    pub fn inner(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+impl fmt::Display for PayoutRequestId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0.hyphenated()))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, FromStr, Display, AsExpression, Clone, Copy, PartialEq, Eq, Hash, DieselTypes)]
 pub struct OrderPayoutId(i64);
 
@@ -54,6 +76,10 @@ pub struct Payout {
     pub user_id: UserId,
     pub status: PayoutStatus,
     pub order_ids: Vec<OrderId>,
+    pub fee_payer: PayoutFeePayer,
+    /// Fees of earlier payouts paid from the balance, deducted from this payout
+    pub carried_fee: Amount,
+    pub request_id: Option<PayoutRequestId>,
 }
 
 impl Payout {
@@ -62,6 +88,27 @@ impl Payout {
             PayoutTarget::CryptoWallet(ref target) => Currency::from(target.currency),
         }
     }
+
+    pub fn fee(&self) -> Amount {
+        match self.target {
+            PayoutTarget::CryptoWallet(ref target) => target.blockchain_fee,
+        }
+    }
+}
+
+/// Who bears the network fee of a payout: the payout itself or the remaining store balance.
+/// Fees paid from the balance are deducted from the next payout in the same currency
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutFeePayer {
+    Payout,
+    Balance,
+}
+
+impl Default for PayoutFeePayer {
+    fn default() -> Self {
+        PayoutFeePayer::Payout
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -100,6 +147,11 @@ pub struct RawPayout {
     pub payout_target_type: RawPayoutTargetType,
     pub wallet_address: Option<WalletAddress>,
     pub blockchain_fee: Option<Amount>,
+    pub fee_payer: PayoutFeePayer,
+    pub carried_fee: Amount,
+    pub request_id: Option<PayoutRequestId>,
+    /// Key of the sorted order IDs, unique so that the same orders can't be paid out twice. None for older payouts
+    pub order_set_key: Option<String>,
 }
 
 impl PartialEq for RawPayout {
@@ -147,6 +199,10 @@ impl RawPayoutRecords {
                     payout_target_type,
                     wallet_address,
                     blockchain_fee,
+                    fee_payer,
+                    carried_fee,
+                    request_id,
+                    order_set_key: _,
                 },
             raw_order_payouts,
         } = self;
@@ -191,6 +247,9 @@ impl RawPayoutRecords {
             user_id,
             status,
             order_ids,
+            fee_payer,
+            carried_fee,
+            request_id,
         })
     }
 }
@@ -211,6 +270,9 @@ impl From<Payout> for RawNewPayoutRecords {
             user_id,
             status,
             order_ids,
+            fee_payer,
+            carried_fee,
+            request_id,
         } = payout;
 
         let raw_new_payout = match target {
@@ -240,6 +302,10 @@ impl From<Payout> for RawNewPayoutRecords {
                     payout_target_type: RawPayoutTargetType::CryptoWallet,
                     wallet_address: Some(wallet_address),
                     blockchain_fee: Some(blockchain_fee),
+                    fee_payer,
+                    carried_fee,
+                    request_id,
+                    order_set_key: Some(order_set_key(&order_ids)),
                 }
             }
         };
@@ -256,6 +322,13 @@ impl From<Payout> for RawNewPayoutRecords {
     }
 }
 
+/// Same key for the same orders regardless of their order in the payout
+pub fn order_set_key(order_ids: &[OrderId]) -> String {
+    let mut order_ids = order_ids.iter().map(OrderId::to_string).collect::<Vec<_>>();
+    order_ids.sort();
+    hex::encode(Sha256::digest(order_ids.join(",").as_bytes()))
+}
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Hash, DieselTypes)]
 #[serde(rename_all = "snake_case")]
 pub enum RawPayoutTargetType {
@@ -292,3 +365,17 @@ pub struct PayoutsByOrderIds {
     pub payouts: HashMap<OrderId, Payout>,
     pub order_ids_without_payout: Vec<OrderId>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order_set_key_does_not_depend_on_the_order_of_ids() {
+        let first = OrderId::new(Uuid::new_v4());
+        let second = OrderId::new(Uuid::new_v4());
+
+        assert_eq!(order_set_key(&[first, second]), order_set_key(&[second, first]));
+        assert_ne!(order_set_key(&[first, second]), order_set_key(&[first]));
+    }
+}
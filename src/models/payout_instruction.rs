@@ -0,0 +1,142 @@
+use std::fmt::{self, Display};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use diesel::sql_types::Int4 as SqlInt4;
+use serde_json;
+
+use stq_static_resources::Currency as StqCurrency;
+use stq_types::{StoreId, SwiftId};
+
+use models::order_v2::OrderId;
+use models::{Amount, Currency};
+use schema::payout_instructions;
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, Default, PartialEq)]
+#[sql_type = "SqlInt4"]
+pub struct PayoutInstructionId(i32);
+derive_newtype_sql!(payout_instruction_id, SqlInt4, PayoutInstructionId, PayoutInstructionId);
+
+impl PayoutInstructionId {
+    pub fn new(id: i32) -> Self {
+        PayoutInstructionId(id)
+    }
+
+    pub fn inner(&self) -> &i32 {
+        &self.0
+    }
+}
+
+impl FromStr for PayoutInstructionId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s.parse()?;
+        Ok(PayoutInstructionId::new(id))
+    }
+}
+
+impl Display for PayoutInstructionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0,))
+    }
+}
+
+/// Bank transfer instruction for paying out eligible orders of an international store.
+/// `document` holds the `PayoutInstructionDocument` as it was at generation time,
+/// so later changes of the billing info do not alter issued instructions.
+/// `total_amount` is the amount transferred, i.e. the orders less their Stripe fees
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct PayoutInstruction {
+    pub id: PayoutInstructionId,
+    pub store_id: StoreId,
+    pub currency: Currency,
+    pub total_amount: Amount,
+    pub order_ids: serde_json::Value,
+    pub reference_code: String,
+    pub document: serde_json::Value,
+    pub created_at: NaiveDateTime,
+    pub stripe_fee: Amount,
+}
+
+impl PayoutInstruction {
+    pub fn order_ids(&self) -> Result<Vec<OrderId>, serde_json::Error> {
+        serde_json::from_value(self.order_ids.clone())
+    }
+
+    pub fn document(&self) -> Result<PayoutInstructionDocument, serde_json::Error> {
+        serde_json::from_value(self.document.clone())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "payout_instructions"]
+pub struct NewPayoutInstruction {
+    pub store_id: StoreId,
+    pub currency: Currency,
+    pub total_amount: Amount,
+    pub order_ids: serde_json::Value,
+    pub reference_code: String,
+    pub document: serde_json::Value,
+    pub stripe_fee: Amount,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutInstructionDocument {
+    pub beneficiary: PayoutBeneficiary,
+    /// Proxy company sending the transfer on behalf of the platform
+    pub remitter: Option<PayoutRemitter>,
+    /// Currency of the beneficiary bank account, may differ from the currency of the orders
+    pub account_currency: StqCurrency,
+    pub reference_code: String,
+    pub payment_details: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutBeneficiary {
+    pub name: String,
+    pub address: String,
+    pub city: String,
+    pub country: String,
+    pub bank: PayoutBankDetails,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutRemitter {
+    pub name: String,
+    pub address: String,
+    pub city: String,
+    pub country: String,
+    pub bank: PayoutBankDetails,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutBankDetails {
+    pub name: String,
+    pub address: String,
+    pub iban: String,
+    pub swift: SwiftId,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PayoutInstructionSearch {
+    pub id: Option<PayoutInstructionId>,
+    pub store_id: Option<StoreId>,
+}
+
+impl PayoutInstructionSearch {
+    pub fn by_id(id: PayoutInstructionId) -> PayoutInstructionSearch {
+        PayoutInstructionSearch {
+            id: Some(id),
+            ..Default::default()
+        }
+    }
+
+    pub fn by_store_id(store_id: StoreId) -> PayoutInstructionSearch {
+        PayoutInstructionSearch {
+            store_id: Some(store_id),
+            ..Default::default()
+        }
+    }
+}
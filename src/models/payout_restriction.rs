@@ -0,0 +1,23 @@
+use chrono::NaiveDateTime;
+
+use stq_types::{StoreId, UserId};
+
+use schema::payout_restriction_overrides;
+
+/// Permission for a store in a restricted country to receive payouts, there is at most one per store
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct PayoutRestrictionOverride {
+    pub store_id: StoreId,
+    pub reason: String,
+    pub granted_by: UserId,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "payout_restriction_overrides"]
+pub struct NewPayoutRestrictionOverride {
+    pub store_id: StoreId,
+    pub reason: String,
+    pub granted_by: UserId,
+}
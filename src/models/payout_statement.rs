@@ -0,0 +1,104 @@
+use std::fmt::{self, Display};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use diesel::sql_types::Int4 as SqlInt4;
+use serde_json;
+
+use models::order_v2::{OrderId, StoreId};
+use models::{Amount, Currency, PayoutId, UserId};
+use schema::payout_statements;
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, Default, PartialEq)]
+#[sql_type = "SqlInt4"]
+pub struct PayoutStatementId(i32);
+derive_newtype_sql!(payout_statement_id, SqlInt4, PayoutStatementId, PayoutStatementId);
+
+impl PayoutStatementId {
+    pub fn new(id: i32) -> Self {
+        PayoutStatementId(id)
+    }
+
+    pub fn inner(&self) -> &i32 {
+        &self.0
+    }
+}
+
+impl FromStr for PayoutStatementId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s.parse()?;
+        Ok(PayoutStatementId::new(id))
+    }
+}
+
+impl Display for PayoutStatementId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0,))
+    }
+}
+
+/// Statement of a payout to a seller with a line per paid out order.
+/// The net amounts of the lines add up to `net_amount` of the payout
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct PayoutStatement {
+    pub id: PayoutStatementId,
+    pub payout_id: PayoutId,
+    pub user_id: UserId,
+    pub currency: Currency,
+    pub gross_amount: Amount,
+    pub net_amount: Amount,
+    pub lines: serde_json::Value,
+    pub created_at: NaiveDateTime,
+}
+
+impl PayoutStatement {
+    pub fn lines(&self) -> Result<Vec<PayoutStatementLine>, serde_json::Error> {
+        serde_json::from_value(self.lines.clone())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "payout_statements"]
+pub struct NewPayoutStatement {
+    pub payout_id: PayoutId,
+    pub user_id: UserId,
+    pub currency: Currency,
+    pub gross_amount: Amount,
+    pub net_amount: Amount,
+    pub lines: serde_json::Value,
+}
+
+/// Contribution of an order to a payout, amounts are given in the payout currency
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutStatementLine {
+    pub order_id: OrderId,
+    pub store_id: StoreId,
+    pub gross_amount: Amount,
+    /// Platform fee of the order, charged to the store separately from the payout
+    pub fee_amount: Option<Amount>,
+    /// Stripe fee of the order, fiat orders only
+    pub stripe_fee: Option<Amount>,
+    /// Gross amount less the share of the payout deductions, i.e. its blockchain fee
+    /// if the payout bears it and the fees carried from earlier payouts
+    pub net_amount: Amount,
+    pub buyer_currency: Currency,
+    /// Exchange rate the buyer paid the order at, none if the order is priced in the buyer currency
+    pub exchange_rate: Option<BigDecimal>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PayoutStatementAccess {
+    pub user_id: UserId,
+}
+
+impl From<&PayoutStatement> for PayoutStatementAccess {
+    fn from(payout_statement: &PayoutStatement) -> PayoutStatementAccess {
+        PayoutStatementAccess {
+            user_id: payout_statement.user_id,
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use stq_types::{RussiaBillingId, StoreId, SwiftId};
 
+use models::masking::mask;
 use schema::russia_billing_info;
 
 #[derive(Clone, Serialize, Queryable, Insertable, Debug)]
@@ -17,6 +18,19 @@ pub struct RussiaBillingInfo {
     pub beneficiary_full_name: String,
 }
 
+impl RussiaBillingInfo {
+    /// Billing info with bank account details hidden
+    pub fn masked(self) -> Self {
+        Self {
+            swift_bic: SwiftId(mask(&self.swift_bic.0)),
+            correspondent_account: mask(&self.correspondent_account),
+            current_account: mask(&self.current_account),
+            personal_account: self.personal_account.map(|personal_account| mask(&personal_account)),
+            ..self
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Insertable, AsChangeset, Debug, Clone)]
 #[table_name = "russia_billing_info"]
 pub struct UpdateRussiaBillingInfo {
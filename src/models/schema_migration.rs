@@ -0,0 +1,54 @@
+use diesel::sql_types::VarChar;
+
+/// Versions of the migrations the binary is built with in ascending order, generated by `build.rs`
+pub const EMBEDDED_MIGRATIONS: &[&str] = include!(concat!(env!("OUT_DIR"), "/migration_versions.rs"));
+
+/// Migration recorded by Diesel as applied to the database
+#[derive(Clone, Debug, QueryableByName)]
+pub struct AppliedMigration {
+    #[sql_type = "VarChar"]
+    pub version: String,
+}
+
+/// Migrations of the binary compared with the ones applied to the database
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaVersion {
+    /// Latest migration applied to the database
+    pub current_version: Option<String>,
+    /// Latest migration the binary is built with
+    pub expected_version: Option<String>,
+    /// Migrations of the binary missing in the database, in the order they have to be run
+    pub pending_migrations: Vec<String>,
+    /// Migrations applied to the database the binary doesn't know of, e.g. after a rollback of the binary
+    pub unknown_migrations: Vec<String>,
+}
+
+impl SchemaVersion {
+    pub fn new(embedded_versions: &[&str], applied_versions: &[String]) -> Self {
+        let mut applied_versions = applied_versions.to_vec();
+        applied_versions.sort();
+
+        let pending_migrations = embedded_versions
+            .iter()
+            .filter(|version| !applied_versions.iter().any(|applied| applied == *version))
+            .map(|version| version.to_string())
+            .collect();
+        let unknown_migrations = applied_versions
+            .iter()
+            .filter(|applied| !embedded_versions.contains(&applied.as_str()))
+            .cloned()
+            .collect();
+
+        SchemaVersion {
+            current_version: applied_versions.last().cloned(),
+            expected_version: embedded_versions.iter().max().map(|version| version.to_string()),
+            pending_migrations,
+            unknown_migrations,
+        }
+    }
+
+    /// The database lacks some of the migrations, queries of the binary may fail on missing tables or columns
+    pub fn is_behind(&self) -> bool {
+        !self.pending_migrations.is_empty()
+    }
+}
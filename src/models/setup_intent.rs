@@ -0,0 +1,24 @@
+//! Stripe SetupIntent, which saves a card of a customer without charging it.
+//! `stripe-rust` 0.9 has no SetupIntent resource, so only the fields billing uses are kept here
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SetupIntentStatus {
+    RequiresPaymentMethod,
+    RequiresConfirmation,
+    RequiresAction,
+    Processing,
+    Canceled,
+    Succeeded,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetupIntent {
+    pub id: String,
+    pub client_secret: Option<String>,
+    /// ID of the Stripe customer the payment method is saved for
+    pub customer: Option<String>,
+    /// ID of the payment method collected by the client, set once the intent has succeeded
+    pub payment_method: Option<String>,
+    pub status: SetupIntentStatus,
+}
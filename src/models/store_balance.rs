@@ -0,0 +1,90 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use diesel::sql_types::{BigInt, Numeric, VarChar};
+
+use models::{Amount, Currency};
+
+/// Stage of the order funds on their way to the seller
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreBalanceBucket {
+    /// Invoice of the order hasn't been paid by the buyer yet
+    UnpaidInvoices,
+    /// Invoice is paid, but the order can't be paid out yet (e.g. it hasn't been delivered)
+    PaidNotEligible,
+    /// Order is ready to be paid out
+    Eligible,
+    /// Order has been paid out to the seller
+    PaidOut,
+}
+
+impl FromStr for StoreBalanceBucket {
+    type Err = StoreBalanceBucketParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unpaid_invoices" => Ok(StoreBalanceBucket::UnpaidInvoices),
+            "paid_not_eligible" => Ok(StoreBalanceBucket::PaidNotEligible),
+            "eligible" => Ok(StoreBalanceBucket::Eligible),
+            "paid_out" => Ok(StoreBalanceBucket::PaidOut),
+            other => Err(StoreBalanceBucketParseError(other.to_string())),
+        }
+    }
+}
+
+impl Display for StoreBalanceBucket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            StoreBalanceBucket::UnpaidInvoices => "unpaid_invoices",
+            StoreBalanceBucket::PaidNotEligible => "paid_not_eligible",
+            StoreBalanceBucket::Eligible => "eligible",
+            StoreBalanceBucket::PaidOut => "paid_out",
+        };
+
+        f.write_str(s)
+    }
+}
+
+#[derive(Debug, Fail)]
+#[fail(display = "unknown store balance bucket: {}", _0)]
+pub struct StoreBalanceBucketParseError(String);
+
+/// Sum of the store orders in a single currency and bucket
+#[derive(Clone, Debug, QueryableByName)]
+pub struct RawStoreBalanceBucketAmount {
+    #[sql_type = "VarChar"]
+    pub currency: Currency,
+    #[sql_type = "VarChar"]
+    pub bucket: String,
+    #[sql_type = "Numeric"]
+    pub amount: Amount,
+    #[sql_type = "BigInt"]
+    pub orders_count: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct StoreBalanceBucketAmount {
+    pub currency: Currency,
+    pub bucket: StoreBalanceBucket,
+    pub amount: Amount,
+    pub orders_count: i64,
+}
+
+impl RawStoreBalanceBucketAmount {
+    pub fn try_into_bucket_amount(self) -> Result<StoreBalanceBucketAmount, StoreBalanceBucketParseError> {
+        let RawStoreBalanceBucketAmount {
+            currency,
+            bucket,
+            amount,
+            orders_count,
+        } = self;
+
+        Ok(StoreBalanceBucketAmount {
+            currency,
+            bucket: bucket.parse()?,
+            amount,
+            orders_count,
+        })
+    }
+}
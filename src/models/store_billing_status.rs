@@ -0,0 +1,83 @@
+use std::fmt::{self, Display};
+
+use chrono::NaiveDateTime;
+
+use stq_types::{StoreId, UserId};
+
+use schema::store_billing_statuses;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreBillingState {
+    Active,
+    /// The store may not sell until it is reinstated
+    Suspended,
+}
+
+impl Display for StoreBillingState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreBillingState::Active => f.write_str("active"),
+            StoreBillingState::Suspended => f.write_str("suspended"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreSuspensionReason {
+    /// Suspended by the policy, reinstated by it once the fees are paid
+    UnpaidFees,
+    /// Suspended by a financial manager
+    Manual,
+}
+
+/// Billing standing of a store, kept by the unpaid fees policy unless a financial manager overrides it
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct StoreBillingStatus {
+    pub store_id: StoreId,
+    pub state: StoreBillingState,
+    pub suspension_reason: Option<StoreSuspensionReason>,
+    /// Unpaid fees past the grace period at the last evaluation
+    pub overdue_fees_count: i32,
+    /// Creation time of the oldest unpaid fee at the last evaluation
+    pub unpaid_since: Option<NaiveDateTime>,
+    pub overridden_by: Option<UserId>,
+    /// End of the override, an override without it lasts until the next one
+    pub override_until: Option<NaiveDateTime>,
+    pub evaluated_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl StoreBillingStatus {
+    /// Whether the policy has to leave the state alone
+    pub fn is_overridden(&self, now: NaiveDateTime) -> bool {
+        self.overridden_by.is_some() && self.override_until.map_or(true, |override_until| now < override_until)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "store_billing_statuses"]
+pub struct NewStoreBillingStatus {
+    pub store_id: StoreId,
+    pub state: StoreBillingState,
+    pub suspension_reason: Option<StoreSuspensionReason>,
+    pub overdue_fees_count: i32,
+    pub unpaid_since: Option<NaiveDateTime>,
+    pub overridden_by: Option<UserId>,
+    pub override_until: Option<NaiveDateTime>,
+    pub evaluated_at: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, AsChangeset)]
+#[table_name = "store_billing_statuses"]
+pub struct UpdateStoreBillingStatus {
+    pub state: Option<StoreBillingState>,
+    pub suspension_reason: Option<Option<StoreSuspensionReason>>,
+    pub overdue_fees_count: Option<i32>,
+    pub unpaid_since: Option<Option<NaiveDateTime>>,
+    pub overridden_by: Option<Option<UserId>>,
+    pub override_until: Option<Option<NaiveDateTime>>,
+    pub evaluated_at: Option<Option<NaiveDateTime>>,
+}
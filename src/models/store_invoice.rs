@@ -0,0 +1,57 @@
+use bigdecimal::BigDecimal;
+
+use models::{Amount, Currency};
+
+/// Service or good a store bills its customer for with an invoice of its own
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StoreInvoiceLineItem {
+    pub description: String,
+    pub quantity: u32,
+    /// Price of a single unit in the invoice currency
+    pub unit_price: BigDecimal,
+}
+
+impl StoreInvoiceLineItem {
+    /// `None` is returned when the price is not positive in the minimal units of the currency or the amount overflows
+    pub fn amount(&self, currency: Currency) -> Option<Amount> {
+        if self.unit_price <= BigDecimal::from(0) {
+            return None;
+        }
+
+        Amount::checked_from_super_unit(currency, self.unit_price.clone())?
+            .checked_mul(Amount::new(u128::from(self.quantity)))
+            .filter(|amount| *amount > Amount::zero())
+    }
+}
+
+/// Total amount of the line items, `None` is returned when the amount of any of them can't be calculated
+pub fn store_invoice_total(currency: Currency, line_items: &[StoreInvoiceLineItem]) -> Option<Amount> {
+    line_items
+        .iter()
+        .try_fold(Amount::zero(), |total, line_item| total.checked_add(line_item.amount(currency)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn line_item(quantity: u32, unit_price: &str) -> StoreInvoiceLineItem {
+        StoreInvoiceLineItem {
+            description: "Consulting".to_string(),
+            quantity,
+            unit_price: BigDecimal::from_str(unit_price).unwrap(),
+        }
+    }
+
+    #[test]
+    fn store_invoice_total_is_exact_in_minimal_units() {
+        let line_items = vec![line_item(3, "0.1"), line_item(1, "19.99")];
+        assert_eq!(store_invoice_total(Currency::Usd, &line_items), Some(Amount::new(2029)));
+
+        assert_eq!(store_invoice_total(Currency::Usd, &[line_item(0, "10")]), None);
+        assert_eq!(store_invoice_total(Currency::Usd, &[line_item(1, "-10")]), None);
+        assert_eq!(store_invoice_total(Currency::Usd, &[line_item(1, "0.001")]), None);
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt::{self, Display};
+
+use chrono::NaiveDateTime;
+use serde_json;
+
+use stq_types::{StoreId, UserId};
+
+use models::fee::FeeId;
+use models::order_v2::OrderId;
+use models::{AccountId, Amount, Currency};
+use schema::store_offboardings;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreOffboardingStatus {
+    /// Some steps are left, offboarding the store again resumes from the first of them
+    InProgress,
+    Completed,
+}
+
+impl Display for StoreOffboardingStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreOffboardingStatus::InProgress => f.write_str("in_progress"),
+            StoreOffboardingStatus::Completed => f.write_str("completed"),
+        }
+    }
+}
+
+/// Billing of a store deleted upstream being closed down. Every step records the time it was taken at,
+/// a step that has been taken is not taken again when the offboarding is resumed.
+/// `written_off_fees` holds the `WrittenOffFee`s and `final_payouts` the `FinalPayout`s
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct StoreOffboarding {
+    pub store_id: StoreId,
+    pub status: StoreOffboardingStatus,
+    pub requested_by: UserId,
+    pub subscription_cancelled_at: Option<NaiveDateTime>,
+    /// Account the store owner paid the crypto subscription from, archived along with the subscription
+    pub subscription_account_id: Option<AccountId>,
+    pub fees_written_off_at: Option<NaiveDateTime>,
+    pub written_off_fees: serde_json::Value,
+    pub final_payout_scheduled_at: Option<NaiveDateTime>,
+    pub final_payouts: serde_json::Value,
+    pub billing_info_archived_at: Option<NaiveDateTime>,
+    pub completed_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl StoreOffboarding {
+    pub fn written_off_fees(&self) -> Result<Vec<WrittenOffFee>, serde_json::Error> {
+        serde_json::from_value(self.written_off_fees.clone())
+    }
+
+    pub fn final_payouts(&self) -> Result<Vec<FinalPayout>, serde_json::Error> {
+        serde_json::from_value(self.final_payouts.clone())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "store_offboardings"]
+pub struct NewStoreOffboarding {
+    pub store_id: StoreId,
+    pub status: StoreOffboardingStatus,
+    pub requested_by: UserId,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, AsChangeset)]
+#[table_name = "store_offboardings"]
+pub struct UpdateStoreOffboarding {
+    pub status: Option<StoreOffboardingStatus>,
+    pub subscription_cancelled_at: Option<Option<NaiveDateTime>>,
+    pub subscription_account_id: Option<Option<AccountId>>,
+    pub fees_written_off_at: Option<Option<NaiveDateTime>>,
+    pub written_off_fees: Option<serde_json::Value>,
+    pub final_payout_scheduled_at: Option<Option<NaiveDateTime>>,
+    pub final_payouts: Option<serde_json::Value>,
+    pub billing_info_archived_at: Option<Option<NaiveDateTime>>,
+    pub completed_at: Option<Option<NaiveDateTime>>,
+}
+
+/// Fee of the store that was never paid and is not going to be collected
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WrittenOffFee {
+    pub fee_id: FeeId,
+    pub order_id: OrderId,
+    pub currency: Currency,
+    pub amount: Amount,
+}
+
+/// Remaining balance of the store in a currency. The fees carried over by earlier payouts are settled from
+/// the orders awaiting payout, `amount` is left to be paid out. Fees exceeding the orders are not collected
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FinalPayout {
+    pub currency: Currency,
+    pub gross_amount: Amount,
+    pub settled_fees: Amount,
+    pub amount: Amount,
+    pub order_ids: Vec<OrderId>,
+}
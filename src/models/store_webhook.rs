@@ -0,0 +1,153 @@
+use std::fmt::{self, Display};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use diesel::sql_types::Int4 as SqlInt4;
+use hex;
+use serde_json;
+use sha2::{Digest, Sha256};
+
+use stq_types::StoreId;
+
+use schema::store_webhooks;
+
+const HMAC_BLOCK_SIZE: usize = 64;
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, Default, PartialEq)]
+#[sql_type = "SqlInt4"]
+pub struct StoreWebhookId(i32);
+derive_newtype_sql!(store_webhook_id, SqlInt4, StoreWebhookId, StoreWebhookId);
+
+impl StoreWebhookId {
+    pub fn new(id: i32) -> Self {
+        StoreWebhookId(id)
+    }
+
+    pub fn inner(&self) -> &i32 {
+        &self.0
+    }
+}
+
+impl FromStr for StoreWebhookId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s.parse()?;
+        Ok(StoreWebhookId::new(id))
+    }
+}
+
+impl Display for StoreWebhookId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0,))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreWebhookEventType {
+    OrderPaid,
+    FeeCharged,
+    PayoutCompleted,
+}
+
+impl Display for StoreWebhookEventType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreWebhookEventType::OrderPaid => write!(f, "order_paid"),
+            StoreWebhookEventType::FeeCharged => write!(f, "fee_charged"),
+            StoreWebhookEventType::PayoutCompleted => write!(f, "payout_completed"),
+        }
+    }
+}
+
+/// Endpoint of a store that receives billing events.
+/// `event_types` is a list of `StoreWebhookEventType`, an empty list subscribes to all events
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct StoreWebhook {
+    pub id: StoreWebhookId,
+    pub store_id: StoreId,
+    pub url: String,
+    pub secret: String,
+    pub event_types: serde_json::Value,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl StoreWebhook {
+    pub fn event_types(&self) -> Result<Vec<StoreWebhookEventType>, serde_json::Error> {
+        serde_json::from_value(self.event_types.clone())
+    }
+
+    pub fn is_subscribed_to(&self, event_type: StoreWebhookEventType) -> bool {
+        match self.event_types() {
+            Ok(event_types) => event_types.is_empty() || event_types.contains(&event_type),
+            Err(_) => false,
+        }
+    }
+
+    /// Hex encoded HMAC-SHA256 of the request body keyed with the webhook secret
+    pub fn sign(&self, body: &str) -> String {
+        hex::encode(hmac_sha256(self.secret.as_bytes(), body.as_bytes()))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "store_webhooks"]
+pub struct NewStoreWebhook {
+    pub store_id: StoreId,
+    pub url: String,
+    pub secret: String,
+    pub event_types: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, AsChangeset)]
+#[table_name = "store_webhooks"]
+pub struct UpdateStoreWebhook {
+    pub url: Option<String>,
+    pub secret: Option<String>,
+    pub event_types: Option<serde_json::Value>,
+}
+
+/// Body of the request sent to a store webhook
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoreWebhookNotification {
+    pub event_type: StoreWebhookEventType,
+    pub store_id: StoreId,
+    pub data: serde_json::Value,
+    pub created_at: NaiveDateTime,
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut block_key = if key.len() > HMAC_BLOCK_SIZE {
+        Sha256::digest(key).to_vec()
+    } else {
+        key.to_vec()
+    };
+    block_key.resize(HMAC_BLOCK_SIZE, 0);
+
+    let mut inner = Sha256::new();
+    inner.input(&block_key.iter().map(|b| b ^ 0x36).collect::<Vec<u8>>());
+    inner.input(message);
+
+    let mut outer = Sha256::new();
+    outer.input(&block_key.iter().map(|b| b ^ 0x5c).collect::<Vec<u8>>());
+    outer.input(&inner.result());
+
+    outer.result().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hmac_sha256_matches_rfc_4231() {
+        let signature = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(
+            hex::encode(signature),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt::{self, Display};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use diesel::sql_types::Int4 as SqlInt4;
+use serde_json;
+
+use models::order_v2::OrderId;
+use schema::stripe_fee_backfills;
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, Default, PartialEq)]
+#[sql_type = "SqlInt4"]
+pub struct StripeFeeBackfillId(i32);
+derive_newtype_sql!(stripe_fee_backfill_id, SqlInt4, StripeFeeBackfillId, StripeFeeBackfillId);
+
+impl StripeFeeBackfillId {
+    pub fn new(id: i32) -> Self {
+        StripeFeeBackfillId(id)
+    }
+
+    pub fn inner(&self) -> &i32 {
+        &self.0
+    }
+}
+
+impl FromStr for StripeFeeBackfillId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s.parse()?;
+        Ok(StripeFeeBackfillId::new(id))
+    }
+}
+
+impl Display for StripeFeeBackfillId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0,))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum StripeFeeBackfillStatus {
+    InProgress,
+    Finished,
+}
+
+/// Job filling in `stripe_fee` of historical fiat orders from Stripe balance transactions.
+/// `order_ids` is the list of orders that were missing the fee when the job was started,
+/// it is processed in batches with `processed_orders` pointing at the start of the next batch
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct StripeFeeBackfill {
+    pub id: StripeFeeBackfillId,
+    pub status: StripeFeeBackfillStatus,
+    pub order_ids: serde_json::Value,
+    pub total_orders: i32,
+    pub processed_orders: i32,
+    pub updated_orders: i32,
+    pub failed_orders: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+impl StripeFeeBackfill {
+    pub fn order_ids(&self) -> Result<Vec<OrderId>, serde_json::Error> {
+        serde_json::from_value(self.order_ids.clone())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "stripe_fee_backfills"]
+pub struct NewStripeFeeBackfill {
+    pub status: StripeFeeBackfillStatus,
+    pub order_ids: serde_json::Value,
+    pub total_orders: i32,
+}
+
+/// Counters recorded after processing a batch
+#[derive(Clone, Debug, Serialize, Deserialize, AsChangeset)]
+#[table_name = "stripe_fee_backfills"]
+pub struct StripeFeeBackfillProgress {
+    pub status: StripeFeeBackfillStatus,
+    pub processed_orders: i32,
+    pub updated_orders: i32,
+    pub failed_orders: i32,
+    pub finished_at: Option<NaiveDateTime>,
+}
@@ -1,6 +1,7 @@
+use std::fmt;
 use std::io::Write;
 
-use chrono::NaiveDateTime;
+use chrono::{Duration, NaiveDateTime};
 use diesel::deserialize::{self, FromSql};
 use diesel::pg::Pg;
 use diesel::serialize::{self, IsNull, Output, ToSql};
@@ -34,6 +35,16 @@ pub struct StoreSubscription {
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
     pub status: StoreSubscriptionStatus,
+    pub trial_end_date: Option<NaiveDateTime>,
+}
+
+impl StoreSubscription {
+    /// End of the trial period. Unless the trial was extended or ended manually,
+    /// it lasts `default_duration` from its start
+    pub fn trial_end_date(&self, default_duration: Duration) -> Option<NaiveDateTime> {
+        self.trial_end_date
+            .or_else(|| self.trial_start_date.map(|trial_start_date| trial_start_date + default_duration))
+    }
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Eq, PartialEq, Hash, IntoEnumIterator)]
@@ -43,6 +54,8 @@ pub enum StoreSubscriptionStatus {
     Trial,
     Paid,
     Free,
+    /// The store was deleted, its subscriptions are not charged anymore
+    Cancelled,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Queryable, Insertable)]
@@ -56,6 +69,9 @@ pub struct SubscriptionPayment {
     pub transaction_id: Option<TransactionId>,
     pub status: SubscriptionPaymentStatus,
     pub created_at: NaiveDateTime,
+    /// Published products the store was charged for, not set for payments made before usage-based pricing
+    pub quantity: Option<Quantity>,
+    pub unit_price: Option<Amount>,
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Eq, PartialEq, Hash, IntoEnumIterator)]
@@ -101,6 +117,7 @@ pub struct UpdateStoreSubscription {
     pub wallet_address: Option<WalletAddress>,
     pub trial_start_date: Option<NaiveDateTime>,
     pub status: Option<StoreSubscriptionStatus>,
+    pub trial_end_date: Option<NaiveDateTime>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
@@ -112,6 +129,8 @@ pub struct NewSubscriptionPayment {
     pub charge_id: Option<ChargeId>,
     pub transaction_id: Option<TransactionId>,
     pub status: SubscriptionPaymentStatus,
+    pub quantity: Option<Quantity>,
+    pub unit_price: Option<Amount>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -171,6 +190,7 @@ impl FromSql<VarChar, Pg> for StoreSubscriptionStatus {
             Some(b"trial") => Ok(StoreSubscriptionStatus::Trial),
             Some(b"paid") => Ok(StoreSubscriptionStatus::Paid),
             Some(b"free") => Ok(StoreSubscriptionStatus::Free),
+            Some(b"cancelled") => Ok(StoreSubscriptionStatus::Cancelled),
             Some(v) => Err(format!(
                 "Unrecognized enum variant: {:?}",
                 String::from_utf8(v.to_vec()).unwrap_or_else(|_| "Non - UTF8 value".to_string()),
@@ -188,11 +208,21 @@ impl ToSql<VarChar, Pg> for StoreSubscriptionStatus {
             StoreSubscriptionStatus::Trial => out.write_all(b"trial")?,
             StoreSubscriptionStatus::Paid => out.write_all(b"paid")?,
             StoreSubscriptionStatus::Free => out.write_all(b"free")?,
+            StoreSubscriptionStatus::Cancelled => out.write_all(b"cancelled")?,
         };
         Ok(IsNull::No)
     }
 }
 
+impl fmt::Display for SubscriptionPaymentStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SubscriptionPaymentStatus::Paid => f.write_str("paid"),
+            SubscriptionPaymentStatus::Failed => f.write_str("failed"),
+        }
+    }
+}
+
 impl FromSql<VarChar, Pg> for SubscriptionPaymentStatus {
     fn from_sql(data: Option<&[u8]>) -> deserialize::Result<Self> {
         match data {
@@ -218,3 +248,39 @@ impl ToSql<VarChar, Pg> for SubscriptionPaymentStatus {
         Ok(IsNull::No)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store_subscription(trial_start_date: Option<NaiveDateTime>, trial_end_date: Option<NaiveDateTime>) -> StoreSubscription {
+        let now = chrono::offset::Utc::now().naive_utc();
+        StoreSubscription {
+            store_id: StoreId(1),
+            currency: Currency::Eur,
+            value: Amount::new(3),
+            wallet_address: None,
+            trial_start_date,
+            created_at: now,
+            updated_at: now,
+            status: StoreSubscriptionStatus::Trial,
+            trial_end_date,
+        }
+    }
+
+    #[test]
+    fn trial_end_date_defaults_to_trial_duration() {
+        let start = NaiveDateTime::from_timestamp(1_550_000_000, 0);
+        let end = start + Duration::days(90);
+
+        assert_eq!(store_subscription(None, None).trial_end_date(Duration::days(30)), None);
+        assert_eq!(
+            store_subscription(Some(start), None).trial_end_date(Duration::days(30)),
+            Some(start + Duration::days(30))
+        );
+        assert_eq!(
+            store_subscription(Some(start), Some(end)).trial_end_date(Duration::days(30)),
+            Some(end)
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use std::fmt::{self, Display};
+
+use chrono::NaiveDateTime;
+use diesel::sql_types::Uuid as SqlUuid;
+use uuid::Uuid;
+
+use stq_types::UserId as StqUserId;
+
+use models::invoice_v2::InvoiceId;
+use models::{Amount, Currency, UserId};
+use schema::{user_credit_balances, user_credit_entries};
+
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, Clone, Copy, PartialEq, Eq, Hash)]
+#[sql_type = "SqlUuid"]
+pub struct UserCreditEntryId(Uuid);
+derive_newtype_sql!(user_credit_entry, SqlUuid, UserCreditEntryId, UserCreditEntryId);
+
+impl UserCreditEntryId {
+    pub fn new(id: Uuid) -> Self {
+        UserCreditEntryId(id)
+    }
+
+    pub fn inner(&self) -> &Uuid {
+        &self.0
+    }
+
+    pub fn generate() -> Self {
+        UserCreditEntryId(Uuid::new_v4())
+    }
+}
+
+impl Display for UserCreditEntryId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0.hyphenated()))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum UserCreditEntryKind {
+    /// Credit given to the user, e.g. a refund or a promo
+    Granted,
+    /// Credit spent on an invoice at checkout
+    Applied,
+    /// Credit of an invoice that expired or was cancelled unpaid, given back to the user
+    Restored,
+}
+
+impl Display for UserCreditEntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UserCreditEntryKind::Granted => f.write_str("granted"),
+            UserCreditEntryKind::Applied => f.write_str("applied"),
+            UserCreditEntryKind::Restored => f.write_str("restored"),
+        }
+    }
+}
+
+/// Platform credit of a user in a single currency, spent on invoices in that currency only
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct UserCreditBalance {
+    pub user_id: UserId,
+    pub currency: Currency,
+    pub balance: Amount,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "user_credit_balances"]
+pub struct NewUserCreditBalance {
+    pub user_id: UserId,
+    pub currency: Currency,
+    pub balance: Amount,
+}
+
+/// Change of a credit balance, the balance is the sum of the granted and restored entries less the applied ones
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct UserCreditEntry {
+    pub id: UserCreditEntryId,
+    pub user_id: UserId,
+    pub currency: Currency,
+    pub kind: UserCreditEntryKind,
+    pub amount: Amount,
+    /// Invoice the credit was applied to or restored from
+    pub invoice_id: Option<InvoiceId>,
+    pub reason: Option<String>,
+    /// Admin who granted the credit
+    pub created_by: Option<StqUserId>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "user_credit_entries"]
+pub struct NewUserCreditEntry {
+    pub id: UserCreditEntryId,
+    pub user_id: UserId,
+    pub currency: Currency,
+    pub kind: UserCreditEntryKind,
+    pub amount: Amount,
+    pub invoice_id: Option<InvoiceId>,
+    pub reason: Option<String>,
+    pub created_by: Option<StqUserId>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UserCreditAccess {
+    pub user_id: UserId,
+}
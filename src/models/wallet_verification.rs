@@ -0,0 +1,93 @@
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use models::{Amount, UserId, UserWalletAccess, UserWalletId};
+use schema::wallet_verifications;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, From, FromStr, Hash, Serialize, Deserialize, DieselTypes)]
+pub struct WalletVerificationId(Uuid);
+
+impl WalletVerificationId {
+    pub fn new(id: Uuid) -> Self {
+        WalletVerificationId(id)
+    }
+
+    pub fn inner(&self) -> &Uuid {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> Uuid {
+        self.0
+    }
+
+    pub fn generate() -> Self {
+        WalletVerificationId(Uuid::new_v4())
+    }
+}
+
+impl fmt::Display for WalletVerificationId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{}", self.0.hyphenated()))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, DieselTypes)]
+#[serde(rename_all = "snake_case")]
+pub enum WalletVerificationStatus {
+    /// The transfer is on its way to the wallet, the user has to confirm its amount
+    Pending,
+    /// The user proved the control of the wallet
+    Confirmed,
+    /// The user didn't confirm the transfer in time or started another verification
+    Expired,
+}
+
+/// Verification of a payout wallet with a transfer of a random small amount.
+/// The id of the verification is the id of the transfer transaction in Payments gateway
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct WalletVerification {
+    pub id: WalletVerificationId,
+    pub user_wallet_id: UserWalletId,
+    pub user_id: UserId,
+    pub amount: Amount,
+    pub status: WalletVerificationStatus,
+    pub expires_at: NaiveDateTime,
+    pub confirmed_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl WalletVerification {
+    /// Whether the user may still confirm the verification
+    pub fn is_pending(&self, now: NaiveDateTime) -> bool {
+        self.status == WalletVerificationStatus::Pending && now < self.expires_at
+    }
+}
+
+impl From<&WalletVerification> for UserWalletAccess {
+    fn from(wallet_verification: &WalletVerification) -> UserWalletAccess {
+        UserWalletAccess {
+            user_id: wallet_verification.user_id.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Insertable)]
+#[table_name = "wallet_verifications"]
+pub struct NewWalletVerification {
+    pub id: WalletVerificationId,
+    pub user_wallet_id: UserWalletId,
+    pub user_id: UserId,
+    pub amount: Amount,
+    pub status: WalletVerificationStatus,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, AsChangeset)]
+#[table_name = "wallet_verifications"]
+pub struct UpdateWalletVerification {
+    pub status: Option<WalletVerificationStatus>,
+    pub confirmed_at: Option<Option<NaiveDateTime>>,
+}
@@ -0,0 +1,131 @@
+use chrono::NaiveDateTime;
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::invoice_v2::InvoiceId;
+use models::{AccountAssignment, AccountId, NewAccountAssignment};
+use repos::legacy_acl::*;
+
+use schema::account_assignments::dsl as AccountAssignmentsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type AccountAssignmentsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, AccountAssignment>>;
+
+pub struct AccountAssignmentsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: AccountAssignmentsRepoAcl,
+}
+
+pub trait AccountAssignmentsRepo {
+    fn create(&self, payload: NewAccountAssignment) -> RepoResultV2<AccountAssignment>;
+    /// Ends the assignment of the account to its invoice
+    fn release(&self, account_id: AccountId, released_at: NaiveDateTime) -> RepoResultV2<Vec<AccountAssignment>>;
+    /// Assignment of the account at the given time, if the account was the payment target of an invoice then
+    fn get_active_at(&self, account_id: AccountId, at: NaiveDateTime) -> RepoResultV2<Option<AccountAssignment>>;
+    /// Latest assignment of an account to the invoice, it is kept after the account is unlinked
+    fn get_by_invoice_id(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<AccountAssignment>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> AccountAssignmentsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: AccountAssignmentsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> AccountAssignmentsRepo
+    for AccountAssignmentsRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewAccountAssignment) -> RepoResultV2<AccountAssignment> {
+        debug!("create account assignment {:?}.", payload);
+        acl::check(&*self.acl, Resource::Account, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(AccountAssignmentsDsl::account_assignments).values(&payload);
+
+        command.get_result::<AccountAssignment>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind => payload)
+        })
+    }
+
+    fn release(&self, account_id: AccountId, released_at: NaiveDateTime) -> RepoResultV2<Vec<AccountAssignment>> {
+        debug!("release account {} at {}.", account_id, released_at);
+        acl::check(&*self.acl, Resource::Account, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let filter = AccountAssignmentsDsl::account_assignments
+            .filter(AccountAssignmentsDsl::account_id.eq(account_id))
+            .filter(AccountAssignmentsDsl::released_at.is_null());
+
+        diesel::update(filter)
+            .set(AccountAssignmentsDsl::released_at.eq(released_at))
+            .get_results::<AccountAssignment>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind => account_id, released_at)
+            })
+    }
+
+    fn get_active_at(&self, account_id: AccountId, at: NaiveDateTime) -> RepoResultV2<Option<AccountAssignment>> {
+        debug!("get assignment of account {} at {}.", account_id, at);
+
+        let assignment = AccountAssignmentsDsl::account_assignments
+            .filter(AccountAssignmentsDsl::account_id.eq(account_id))
+            .filter(AccountAssignmentsDsl::assigned_at.le(at))
+            .filter(
+                AccountAssignmentsDsl::released_at
+                    .is_null()
+                    .or(AccountAssignmentsDsl::released_at.gt(at)),
+            )
+            .order_by(AccountAssignmentsDsl::assigned_at.desc())
+            .first::<AccountAssignment>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind => account_id, at)
+            })?;
+
+        acl::check(&*self.acl, Resource::Account, Action::Read, self, assignment.as_ref()).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        Ok(assignment)
+    }
+
+    fn get_by_invoice_id(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<AccountAssignment>> {
+        debug!("get account assignment of invoice {}.", invoice_id);
+
+        let assignment = AccountAssignmentsDsl::account_assignments
+            .filter(AccountAssignmentsDsl::invoice_id.eq(invoice_id))
+            .order_by(AccountAssignmentsDsl::assigned_at.desc())
+            .first::<AccountAssignment>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind => invoice_id)
+            })?;
+
+        acl::check(&*self.acl, Resource::Account, Action::Read, self, assignment.as_ref()).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        Ok(assignment)
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, AccountAssignment>
+    for AccountAssignmentsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&AccountAssignment>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
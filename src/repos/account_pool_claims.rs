@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use enum_iterator::IntoEnumIterator;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::{Account, AccountPoolClaim, AccountPoolDemand, NewAccountPoolClaim, TureCurrency};
+use repos::legacy_acl::*;
+
+use schema::account_pool_claims::dsl as AccountPoolClaimsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+/// Claims are a part of the account pools, access to them is the access to accounts
+pub type AccountPoolClaimsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, Account>>;
+
+pub struct AccountPoolClaimsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: AccountPoolClaimsRepoAcl,
+}
+
+pub trait AccountPoolClaimsRepo {
+    fn create(&self, payload: NewAccountPoolClaim) -> RepoResultV2<AccountPoolClaim>;
+    /// Claims made since `since` by currency, every currency is present
+    fn demand_since(&self, since: NaiveDateTime) -> RepoResultV2<HashMap<TureCurrency, AccountPoolDemand>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> AccountPoolClaimsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: AccountPoolClaimsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> AccountPoolClaimsRepo
+    for AccountPoolClaimsRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewAccountPoolClaim) -> RepoResultV2<AccountPoolClaim> {
+        debug!("create account pool claim {:?}.", payload);
+        acl::check(&*self.acl, Resource::Account, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(AccountPoolClaimsDsl::account_pool_claims).values(&payload);
+
+        command.get_result::<AccountPoolClaim>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind => payload)
+        })
+    }
+
+    fn demand_since(&self, since: NaiveDateTime) -> RepoResultV2<HashMap<TureCurrency, AccountPoolDemand>> {
+        debug!("get account pool demand since {}.", since);
+        acl::check(&*self.acl, Resource::Account, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let claims = AccountPoolClaimsDsl::account_pool_claims
+            .filter(AccountPoolClaimsDsl::claimed_at.ge(since))
+            .select((AccountPoolClaimsDsl::currency, AccountPoolClaimsDsl::exhausted))
+            .get_results::<(TureCurrency, bool)>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind => since)
+            })?;
+
+        let empty_demand = TureCurrency::into_enum_iter()
+            .map(|currency| (currency, AccountPoolDemand::default()))
+            .collect::<HashMap<_, _>>();
+
+        Ok(claims.into_iter().fold(empty_demand, |mut demand, (currency, exhausted)| {
+            let currency_demand = demand.entry(currency).or_insert_with(AccountPoolDemand::default);
+            currency_demand.claims += 1;
+            if exhausted {
+                currency_demand.exhaustions += 1;
+            }
+            demand
+        }))
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, Account>
+    for AccountPoolClaimsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, _scope: &Scope, _obj: Option<&Account>) -> bool {
+        true
+    }
+}
@@ -1,3 +1,5 @@
+use chrono::NaiveDateTime;
+use diesel::dsl::{exists, not};
 use diesel::{connection::AnsiTransactionManager, pg::Pg, prelude::*, query_dsl::RunQueryDsl, Connection};
 use enum_iterator::IntoEnumIterator;
 use failure::{Error as FailureError, Fail};
@@ -5,14 +7,19 @@ use std::collections::HashMap;
 use stq_types::UserId;
 
 use models::invoice_v2::RawInvoice;
-use models::{authorization::*, Account, AccountCount, AccountId, NewAccount, RawAccount, TureCurrency, WalletAddress};
+use models::{
+    authorization::*, Account, AccountCount, AccountId, AccountStatus, FeeCryptoPaymentStatus, NewAccount, RawAccount, TureCurrency,
+    WalletAddress,
+};
 use repos::{
     acl,
     error::{ErrorKind, ErrorSource},
     legacy_acl::*,
     types::RepoResultV2,
 };
+use schema::account_assignments::dsl as AccountAssignments;
 use schema::accounts::dsl as Accounts;
+use schema::fee_crypto_payments::dsl as FeeCryptoPayments;
 use schema::invoices_v2::dsl as InvoicesV2;
 
 pub struct AccountsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
@@ -25,8 +32,12 @@ pub trait AccountsRepo {
     fn get(&self, account_id: AccountId) -> RepoResultV2<Option<Account>>;
     fn get_by_wallet_address(&self, wallet_address: WalletAddress) -> RepoResultV2<Option<Account>>;
     fn get_many(&self, account_ids: &[AccountId]) -> RepoResultV2<Vec<Account>>;
-    fn get_free_account(&self, currency: TureCurrency) -> RepoResultV2<Option<Account>>;
+    /// Active pooled account linked to no invoice, which was last released before `released_before`
+    fn get_free_account(&self, currency: TureCurrency, released_before: NaiveDateTime) -> RepoResultV2<Option<Account>>;
+    /// Number of the accounts `get_free_account` would hand out by currency, every currency is present
+    fn count_free(&self, released_before: NaiveDateTime) -> RepoResultV2<HashMap<TureCurrency, u64>>;
     fn create(&self, payload: NewAccount) -> RepoResultV2<Account>;
+    fn set_status(&self, account_id: AccountId, status: AccountStatus) -> RepoResultV2<Account>;
     fn delete(&self, account_id: AccountId) -> RepoResultV2<Option<Account>>;
 }
 
@@ -42,7 +53,10 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
 
         acl::check(&*self.acl, Resource::Account, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
 
-        let query = Accounts::accounts.select((Accounts::currency, Accounts::is_pooled));
+        // retired accounts are not counted so that the pool gets replenished
+        let query = Accounts::accounts
+            .filter(Accounts::status.eq(AccountStatus::Active))
+            .select((Accounts::currency, Accounts::is_pooled));
         let accounts = query.get_results::<(TureCurrency, bool)>(self.db_conn).map_err(|e| {
             let error_kind = ErrorKind::from(&e);
             ectx!(try err e, ErrorSource::Diesel, error_kind)
@@ -121,15 +135,40 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             })
     }
 
-    fn get_free_account(&self, currency: TureCurrency) -> RepoResultV2<Option<Account>> {
-        debug!("Getting a free account for currency: {:?}", currency);
+    fn get_free_account(&self, currency: TureCurrency, released_before: NaiveDateTime) -> RepoResultV2<Option<Account>> {
+        debug!(
+            "Getting a free account for currency: {:?}, released before: {}",
+            currency, released_before
+        );
 
         acl::check(&*self.acl, Resource::Account, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
 
         let query = Accounts::accounts
-            .filter(Accounts::currency.eq(currency).and(Accounts::is_pooled.eq(true)))
+            .filter(
+                Accounts::currency
+                    .eq(currency)
+                    .and(Accounts::is_pooled.eq(true))
+                    .and(Accounts::status.eq(AccountStatus::Active)),
+            )
             .left_join(InvoicesV2::invoices_v2)
-            .filter(InvoicesV2::id.is_null());
+            .filter(InvoicesV2::id.is_null())
+            // accounts rest in quarantine after being released, late payments of the previous invoice still arrive to them
+            .filter(not(exists(
+                AccountAssignments::account_assignments
+                    .filter(AccountAssignments::account_id.eq(Accounts::id))
+                    .filter(AccountAssignments::released_at.ge(released_before)),
+            )))
+            // accounts collecting a fee payment are taken, and rest in quarantine once the payment is closed as well
+            .filter(not(exists(
+                FeeCryptoPayments::fee_crypto_payments
+                    .filter(FeeCryptoPayments::account_id.eq(Accounts::id))
+                    .filter(FeeCryptoPayments::status.eq(FeeCryptoPaymentStatus::Pending)),
+            )))
+            .filter(not(exists(
+                FeeCryptoPayments::fee_crypto_payments
+                    .filter(FeeCryptoPayments::account_id.eq(Accounts::id))
+                    .filter(FeeCryptoPayments::closed_at.ge(released_before)),
+            )));
 
         query
             .get_result::<(RawAccount, Option<RawInvoice>)>(self.db_conn)
@@ -137,10 +176,52 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             .optional()
             .map_err(|e| {
                 let error_kind = ErrorKind::from(&e);
-                ectx!(err e, ErrorSource::Diesel, error_kind => currency)
+                ectx!(err e, ErrorSource::Diesel, error_kind => currency, released_before)
             })
     }
 
+    fn count_free(&self, released_before: NaiveDateTime) -> RepoResultV2<HashMap<TureCurrency, u64>> {
+        debug!("Counting free accounts released before: {}", released_before);
+
+        acl::check(&*self.acl, Resource::Account, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        // same filters as `get_free_account`
+        let query = Accounts::accounts
+            .filter(Accounts::is_pooled.eq(true).and(Accounts::status.eq(AccountStatus::Active)))
+            .left_join(InvoicesV2::invoices_v2)
+            .filter(InvoicesV2::id.is_null())
+            .filter(not(exists(
+                AccountAssignments::account_assignments
+                    .filter(AccountAssignments::account_id.eq(Accounts::id))
+                    .filter(AccountAssignments::released_at.ge(released_before)),
+            )))
+            .filter(not(exists(
+                FeeCryptoPayments::fee_crypto_payments
+                    .filter(FeeCryptoPayments::account_id.eq(Accounts::id))
+                    .filter(FeeCryptoPayments::status.eq(FeeCryptoPaymentStatus::Pending)),
+            )))
+            .filter(not(exists(
+                FeeCryptoPayments::fee_crypto_payments
+                    .filter(FeeCryptoPayments::account_id.eq(Accounts::id))
+                    .filter(FeeCryptoPayments::closed_at.ge(released_before)),
+            )))
+            .select(Accounts::currency);
+
+        let currencies = query.get_results::<TureCurrency>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(try err e, ErrorSource::Diesel, error_kind => released_before)
+        })?;
+
+        let empty_hashmap = TureCurrency::into_enum_iter()
+            .map(|currency| (currency, 0))
+            .collect::<HashMap<_, _>>();
+
+        Ok(currencies.into_iter().fold(empty_hashmap, |mut free, currency| {
+            *free.entry(currency).or_insert(0) += 1;
+            free
+        }))
+    }
+
     fn create(&self, payload: NewAccount) -> RepoResultV2<Account> {
         debug!("Creating an account using payload: {:?}", payload);
 
@@ -154,6 +235,19 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         })
     }
 
+    fn set_status(&self, account_id: AccountId, status: AccountStatus) -> RepoResultV2<Account> {
+        debug!("Setting status of an account with ID: {} to {}", account_id, status);
+
+        acl::check(&*self.acl, Resource::Account, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::update(Accounts::accounts.filter(Accounts::id.eq(account_id))).set(Accounts::status.eq(status));
+
+        command.get_result::<RawAccount>(self.db_conn).map(Account::from).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind => account_id, status)
+        })
+    }
+
     fn delete(&self, account_id: AccountId) -> RepoResultV2<Option<Account>> {
         debug!("Deleting an account with ID: {}", account_id);
 
@@ -8,7 +8,7 @@ pub mod roles_cache;
 pub use self::roles_cache::RolesCacheImpl;
 
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use errors::Error;
 use failure::Error as FailureError;
@@ -19,6 +19,7 @@ use stq_types::{BillingRole, UserId};
 use super::legacy_acl::{Acl, CheckScope};
 
 use models::authorization::*;
+use models::ApiKeyScope;
 
 pub fn check<T>(
     acl: &Acl<Resource, Action, Scope, FailureError, T>,
@@ -40,118 +41,274 @@ pub fn check<T>(
     })
 }
 
+lazy_static! {
+    /// Permissions of every role, built once per process and shared by all ACLs
+    static ref PERMISSIONS: Arc<HashMap<BillingRole, Vec<Permission>>> = Arc::new(permissions());
+}
+
 /// ApplicationAcl contains main logic for manipulation with recources
 #[derive(Clone)]
 pub struct ApplicationAcl {
-    acls: Rc<HashMap<BillingRole, Vec<Permission>>>,
+    acls: Arc<HashMap<BillingRole, Vec<Permission>>>,
     roles: Vec<BillingRole>,
     user_id: UserId,
 }
 
 impl ApplicationAcl {
+    /// Cheap to create on every request, the permission table is shared
     pub fn new(roles: Vec<BillingRole>, user_id: UserId) -> Self {
-        let mut hash = ::std::collections::HashMap::new();
-        hash.insert(
-            BillingRole::Superuser,
-            vec![
-                permission!(Resource::OrderInfo),
-                permission!(Resource::UserRoles),
-                permission!(Resource::Invoice),
-                permission!(Resource::Account),
-                permission!(Resource::OrderExchangeRate),
-                permission!(Resource::PaymentIntent),
-                permission!(Resource::PaymentIntentFee),
-                permission!(Resource::PaymentIntentInvoice),
-                permission!(Resource::Customer),
-                permission!(Resource::Fee),
-                permission!(Resource::StoreBillingType),
-                permission!(Resource::BillingInfo),
-                permission!(Resource::ProxyCompanyBillingInfo),
-                permission!(Resource::UserWallet),
-                permission!(Resource::Payout),
-                permission!(Resource::Subscription),
-                permission!(Resource::StoreSubscription),
-                permission!(Resource::StoreSubscriptionStatus),
-                permission!(Resource::SubscriptionPayment),
-            ],
-        );
-        hash.insert(
-            BillingRole::User,
-            vec![
-                permission!(Resource::UserRoles, Action::Read, Scope::Owned),
-                permission!(Resource::Invoice, Action::Read, Scope::Owned),
-                permission!(Resource::Invoice, Action::Write, Scope::Owned),
-                permission!(Resource::OrderInfo, Action::Write, Scope::Owned),
+        ApplicationAcl {
+            acls: PERMISSIONS.clone(),
+            roles,
+            user_id,
+        }
+    }
+
+    /// ACL of a request made with an API key: the permissions of the key scopes on the stores managed by the user
+    /// the key acts for, whatever other roles the user has
+    pub fn for_api_key(scopes: &[ApiKeyScope], user_id: UserId) -> Self {
+        let mut acls = HashMap::new();
+        acls.insert(BillingRole::StoreManager, api_key_permissions(scopes));
+        ApplicationAcl {
+            acls: Arc::new(acls),
+            roles: vec![BillingRole::StoreManager],
+            user_id,
+        }
+    }
+}
+
+/// Read only subsets of the store manager permissions, the key may only create store invoices,
+/// which are saved with the system ACL
+fn api_key_permissions(scopes: &[ApiKeyScope]) -> Vec<Permission> {
+    scopes
+        .iter()
+        .flat_map(|scope| match scope {
+            ApiKeyScope::Orders => vec![
                 permission!(Resource::OrderInfo, Action::Read, Scope::Owned),
                 permission!(Resource::OrderExchangeRate, Action::Read, Scope::Owned),
-                permission!(Resource::OrderExchangeRate, Action::Write, Scope::Owned),
-                permission!(Resource::PaymentIntent, Action::Read),
-                permission!(Resource::PaymentIntent, Action::Write),
-                permission!(Resource::PaymentIntentFee, Action::Read, Scope::Owned),
-                permission!(Resource::PaymentIntentInvoice, Action::Read, Scope::Owned),
-                permission!(Resource::Customer, Action::Read, Scope::Owned),
-                permission!(Resource::Customer, Action::Write, Scope::Owned),
-                permission!(Resource::UserWallet, Action::Read, Scope::Owned),
-                permission!(Resource::UserWallet, Action::Write, Scope::Owned),
-                permission!(Resource::Payout, Action::Read, Scope::Owned),
-                permission!(Resource::Payout, Action::Write, Scope::Owned),
             ],
-        );
-        hash.insert(
-            BillingRole::StoreManager,
-            vec![
+            ApiKeyScope::Invoices => vec![
                 permission!(Resource::OrderInfo, Action::Read, Scope::Owned),
-                permission!(Resource::UserRoles, Action::Read, Scope::Owned),
-                permission!(Resource::OrderExchangeRate, Action::Read, Scope::Owned),
-                permission!(Resource::OrderExchangeRate, Action::Write, Scope::Owned),
-                permission!(Resource::BillingInfo, Action::Read, Scope::Owned),
-                permission!(Resource::BillingInfo, Action::Write, Scope::Owned),
-                permission!(Resource::StoreBillingType, Action::Read, Scope::Owned),
-                permission!(Resource::StoreBillingType, Action::Write, Scope::Owned),
                 permission!(Resource::PaymentIntent, Action::Read),
-                permission!(Resource::PaymentIntent, Action::Write),
-                permission!(Resource::PaymentIntentFee, Action::Read, Scope::Owned),
                 permission!(Resource::PaymentIntentInvoice, Action::Read, Scope::Owned),
-                permission!(Resource::Fee, Action::Read, Scope::Owned),
-                permission!(Resource::Fee, Action::Write, Scope::Owned),
-                permission!(Resource::UserWallet, Action::Read, Scope::Owned),
-                permission!(Resource::UserWallet, Action::Write, Scope::Owned),
-                permission!(Resource::Payout, Action::Read, Scope::Owned),
-                permission!(Resource::Payout, Action::Write, Scope::Owned),
-                permission!(Resource::StoreSubscription, Action::Read, Scope::Owned),
-                permission!(Resource::StoreSubscription, Action::Write, Scope::Owned),
             ],
-        );
-        hash.insert(
-            BillingRole::FinancialManager,
-            vec![
-                permission!(Resource::OrderInfo, Action::Read),
-                permission!(Resource::StoreBillingType, Action::Read),
-                permission!(Resource::BillingInfo, Action::Read),
-                permission!(Resource::Fee, Action::Read),
-                permission!(Resource::Fee, Action::Write),
-                permission!(Resource::ProxyCompanyBillingInfo, Action::Read),
-                permission!(Resource::PaymentIntentFee, Action::Read),
-                permission!(Resource::PaymentIntentInvoice, Action::Read),
-                permission!(Resource::PaymentIntent, Action::Read),
-                permission!(Resource::Customer, Action::Read),
-                permission!(Resource::UserWallet, Action::Read),
-                permission!(Resource::Payout, Action::Read),
-                permission!(Resource::Payout, Action::Write),
-                permission!(Resource::Subscription, Action::Read),
-                permission!(Resource::StoreSubscription, Action::Read),
-                permission!(Resource::StoreSubscription, Action::Write),
-                permission!(Resource::StoreSubscriptionStatus, Action::Read),
-                permission!(Resource::StoreSubscriptionStatus, Action::Write),
-                permission!(Resource::SubscriptionPayment, Action::Read),
+            ApiKeyScope::Balance | ApiKeyScope::Payouts => vec![
+                permission!(Resource::OrderInfo, Action::Read, Scope::Owned),
+                permission!(Resource::Payout, Action::Read, Scope::Owned),
             ],
-        );
-        ApplicationAcl {
-            acls: Rc::new(hash),
-            roles,
-            user_id,
-        }
-    }
+            ApiKeyScope::FeeStatements => vec![permission!(Resource::FeeStatement, Action::Read, Scope::Owned)],
+        })
+        .collect()
+}
+
+fn permissions() -> HashMap<BillingRole, Vec<Permission>> {
+    let mut hash = HashMap::new();
+    hash.insert(
+        BillingRole::Superuser,
+        vec![
+            permission!(Resource::OrderInfo),
+            permission!(Resource::UserRoles),
+            permission!(Resource::Invoice),
+            permission!(Resource::Account),
+            permission!(Resource::OrderExchangeRate),
+            permission!(Resource::PaymentIntent),
+            permission!(Resource::PaymentIntentHistory),
+            permission!(Resource::PaymentIntentFee),
+            permission!(Resource::PaymentIntentInvoice),
+            permission!(Resource::PaymentRecovery),
+            permission!(Resource::Customer),
+            permission!(Resource::FeatureFlag),
+            permission!(Resource::Fee),
+            permission!(Resource::FeeAdjustment),
+            permission!(Resource::FeeCryptoPayment),
+            permission!(Resource::FeeStatement),
+            permission!(Resource::StoreBillingType),
+            permission!(Resource::StoreBillingStatus),
+            permission!(Resource::BillingInfo),
+            permission!(Resource::BillingInfoSecrets),
+            permission!(Resource::ProxyCompanyBillingInfo),
+            permission!(Resource::UserWallet),
+            permission!(Resource::Payout),
+            permission!(Resource::PayoutInstruction),
+            permission!(Resource::StripeFeeBackfill),
+            permission!(Resource::OrderCaptureApproval),
+            permission!(Resource::Subscription),
+            permission!(Resource::StoreSubscription),
+            permission!(Resource::StoreSubscriptionStatus),
+            permission!(Resource::StoreWebhook),
+            permission!(Resource::InvoiceCallback),
+            permission!(Resource::InvoiceRequote),
+            permission!(Resource::SubscriptionPayment),
+            permission!(Resource::AuditLog),
+            permission!(Resource::CashbackLiability),
+            permission!(Resource::NegativeStoreBalance),
+            permission!(Resource::ExchangeRateSlippage),
+            permission!(Resource::PayoutStatement),
+            permission!(Resource::SchemaMigration),
+            permission!(Resource::BillingInfoChange),
+            permission!(Resource::PaymentAttempt),
+            permission!(Resource::ApiKey),
+            permission!(Resource::DataRetention),
+            permission!(Resource::InvoiceSnapshot),
+            permission!(Resource::FeeDunning),
+            permission!(Resource::PayoutRestriction),
+            permission!(Resource::CustomerExport),
+            permission!(Resource::InvoiceRiskAssessment),
+            permission!(Resource::StoreOffboarding),
+            permission!(Resource::HistoricalReport),
+            permission!(Resource::UserCredit),
+            permission!(Resource::OrderLineItem),
+            permission!(Resource::InvoiceBuyerDetails),
+        ],
+    );
+    hash.insert(
+        BillingRole::User,
+        vec![
+            permission!(Resource::UserRoles, Action::Read, Scope::Owned),
+            permission!(Resource::Invoice, Action::Read, Scope::Owned),
+            permission!(Resource::Invoice, Action::Write, Scope::Owned),
+            permission!(Resource::OrderInfo, Action::Write, Scope::Owned),
+            permission!(Resource::OrderInfo, Action::Read, Scope::Owned),
+            permission!(Resource::OrderExchangeRate, Action::Read, Scope::Owned),
+            permission!(Resource::OrderExchangeRate, Action::Write, Scope::Owned),
+            permission!(Resource::PaymentIntent, Action::Read),
+            permission!(Resource::PaymentIntent, Action::Write),
+            permission!(Resource::PaymentIntentFee, Action::Read, Scope::Owned),
+            permission!(Resource::PaymentIntentInvoice, Action::Read, Scope::Owned),
+            permission!(Resource::PaymentRecovery, Action::Read, Scope::Owned),
+            permission!(Resource::PaymentRecovery, Action::Write, Scope::Owned),
+            permission!(Resource::Customer, Action::Read, Scope::Owned),
+            permission!(Resource::Customer, Action::Write, Scope::Owned),
+            permission!(Resource::UserWallet, Action::Read, Scope::Owned),
+            permission!(Resource::UserWallet, Action::Write, Scope::Owned),
+            permission!(Resource::Payout, Action::Read, Scope::Owned),
+            permission!(Resource::Payout, Action::Write, Scope::Owned),
+            permission!(Resource::PayoutStatement, Action::Read, Scope::Owned),
+            permission!(Resource::UserCredit, Action::Read, Scope::Owned),
+        ],
+    );
+    hash.insert(
+        BillingRole::StoreManager,
+        vec![
+            permission!(Resource::OrderInfo, Action::Read, Scope::Owned),
+            permission!(Resource::UserRoles, Action::Read, Scope::Owned),
+            permission!(Resource::OrderExchangeRate, Action::Read, Scope::Owned),
+            permission!(Resource::OrderExchangeRate, Action::Write, Scope::Owned),
+            // Billing info of the store is changed through a billing info change approved by a financial manager
+            permission!(Resource::BillingInfo, Action::Read, Scope::Owned),
+            permission!(Resource::BillingInfoChange, Action::Read, Scope::Owned),
+            permission!(Resource::BillingInfoChange, Action::Write, Scope::Owned),
+            permission!(Resource::StoreBillingType, Action::Read, Scope::Owned),
+            permission!(Resource::StoreBillingType, Action::Write, Scope::Owned),
+            permission!(Resource::StoreBillingStatus, Action::Read, Scope::Owned),
+            permission!(Resource::PaymentIntent, Action::Read),
+            permission!(Resource::PaymentIntent, Action::Write),
+            permission!(Resource::PaymentIntentFee, Action::Read, Scope::Owned),
+            permission!(Resource::PaymentIntentInvoice, Action::Read, Scope::Owned),
+            permission!(Resource::Fee, Action::Read, Scope::Owned),
+            permission!(Resource::Fee, Action::Write, Scope::Owned),
+            permission!(Resource::FeeCryptoPayment, Action::Read, Scope::Owned),
+            permission!(Resource::FeeCryptoPayment, Action::Write, Scope::Owned),
+            permission!(Resource::FeeStatement, Action::Read, Scope::Owned),
+            permission!(Resource::UserWallet, Action::Read, Scope::Owned),
+            permission!(Resource::UserWallet, Action::Write, Scope::Owned),
+            permission!(Resource::Payout, Action::Read, Scope::Owned),
+            permission!(Resource::Payout, Action::Write, Scope::Owned),
+            permission!(Resource::PayoutStatement, Action::Read, Scope::Owned),
+            permission!(Resource::StoreSubscription, Action::Read, Scope::Owned),
+            permission!(Resource::StoreSubscription, Action::Write, Scope::Owned),
+            permission!(Resource::Subscription, Action::Read, Scope::Owned),
+            permission!(Resource::SubscriptionPayment, Action::Read, Scope::Owned),
+            permission!(Resource::StoreWebhook, Action::Read, Scope::Owned),
+            permission!(Resource::StoreWebhook, Action::Write, Scope::Owned),
+            permission!(Resource::ApiKey, Action::Read, Scope::Owned),
+            permission!(Resource::ApiKey, Action::Write, Scope::Owned),
+        ],
+    );
+    hash.insert(
+        BillingRole::FinancialManager,
+        vec![
+            permission!(Resource::OrderInfo, Action::Read),
+            permission!(Resource::StoreBillingType, Action::Read),
+            permission!(Resource::StoreBillingStatus, Action::Read),
+            permission!(Resource::StoreBillingStatus, Action::Write),
+            permission!(Resource::BillingInfo, Action::Read),
+            permission!(Resource::BillingInfoSecrets, Action::Read),
+            permission!(Resource::BillingInfoChange, Action::Read),
+            permission!(Resource::BillingInfoChange, Action::Write),
+            permission!(Resource::Fee, Action::Read),
+            permission!(Resource::Fee, Action::Write),
+            permission!(Resource::FeeAdjustment, Action::Read),
+            permission!(Resource::FeeCryptoPayment, Action::Read),
+            permission!(Resource::FeeStatement, Action::Read),
+            permission!(Resource::ProxyCompanyBillingInfo, Action::Read),
+            permission!(Resource::PaymentIntentFee, Action::Read),
+            permission!(Resource::PaymentIntentInvoice, Action::Read),
+            permission!(Resource::PaymentIntent, Action::Read),
+            permission!(Resource::PaymentIntentHistory, Action::Read),
+            permission!(Resource::PaymentRecovery, Action::Read),
+            permission!(Resource::InvoiceRequote, Action::Read),
+            permission!(Resource::Customer, Action::Read),
+            permission!(Resource::UserWallet, Action::Read),
+            permission!(Resource::Payout, Action::Read),
+            permission!(Resource::Payout, Action::Write),
+            permission!(Resource::PayoutInstruction, Action::Read),
+            permission!(Resource::PayoutInstruction, Action::Write),
+            permission!(Resource::Subscription, Action::Read),
+            permission!(Resource::StoreSubscription, Action::Read),
+            permission!(Resource::StoreSubscription, Action::Write),
+            permission!(Resource::StoreSubscriptionStatus, Action::Read),
+            permission!(Resource::StoreSubscriptionStatus, Action::Write),
+            permission!(Resource::SubscriptionPayment, Action::Read),
+            permission!(Resource::AuditLog, Action::Read),
+            permission!(Resource::CashbackLiability, Action::Read),
+            permission!(Resource::NegativeStoreBalance, Action::Read),
+            permission!(Resource::ExchangeRateSlippage, Action::Read),
+            permission!(Resource::PayoutStatement, Action::Read),
+            permission!(Resource::InvoiceSnapshot, Action::Read),
+            permission!(Resource::FeeDunning, Action::Read),
+            permission!(Resource::StoreOffboarding, Action::Read),
+            permission!(Resource::HistoricalReport, Action::Read),
+            permission!(Resource::UserCredit, Action::Read),
+            permission!(Resource::InvoiceBuyerDetails, Action::Read),
+            permission!(Resource::PayoutRestriction, Action::Read),
+        ],
+    );
+    // Support looks into customer issues without changing anything,
+    // billing info and cards are masked for it as it may not read billing info secrets
+    hash.insert(
+        BillingRole::Support,
+        vec![
+            permission!(Resource::OrderInfo, Action::Read),
+            permission!(Resource::Invoice, Action::Read),
+            permission!(Resource::InvoiceCallback, Action::Read),
+            permission!(Resource::OrderExchangeRate, Action::Read),
+            permission!(Resource::StoreBillingType, Action::Read),
+            permission!(Resource::StoreBillingStatus, Action::Read),
+            permission!(Resource::BillingInfo, Action::Read),
+            permission!(Resource::BillingInfoChange, Action::Read),
+            permission!(Resource::Fee, Action::Read),
+            permission!(Resource::FeeAdjustment, Action::Read),
+            permission!(Resource::FeeCryptoPayment, Action::Read),
+            permission!(Resource::FeeStatement, Action::Read),
+            permission!(Resource::PaymentIntent, Action::Read),
+            permission!(Resource::PaymentIntentFee, Action::Read),
+            permission!(Resource::PaymentIntentInvoice, Action::Read),
+            permission!(Resource::PaymentRecovery, Action::Read),
+            permission!(Resource::PaymentAttempt, Action::Read),
+            permission!(Resource::Customer, Action::Read),
+            permission!(Resource::UserWallet, Action::Read),
+            permission!(Resource::Payout, Action::Read),
+            permission!(Resource::PayoutStatement, Action::Read),
+            permission!(Resource::Subscription, Action::Read),
+            permission!(Resource::StoreSubscription, Action::Read),
+            permission!(Resource::StoreSubscriptionStatus, Action::Read),
+            permission!(Resource::SubscriptionPayment, Action::Read),
+            permission!(Resource::InvoiceRiskAssessment, Action::Read),
+            permission!(Resource::UserCredit, Action::Read),
+        ],
+    );
+    hash
 }
 
 impl<T> Acl<Resource, Action, Scope, FailureError, T> for ApplicationAcl {
@@ -164,11 +321,10 @@ impl<T> Acl<Resource, Action, Scope, FailureError, T> for ApplicationAcl {
     ) -> Result<bool, FailureError> {
         let empty: Vec<Permission> = Vec::new();
         let user_id = &self.user_id;
-        let hashed_acls = self.acls.clone();
         let acls = self
             .roles
             .iter()
-            .flat_map(|role| hashed_acls.get(role).unwrap_or(&empty))
+            .flat_map(|role| self.acls.get(role).unwrap_or(&empty))
             .filter(|permission| (permission.resource == resource) && ((permission.action == action) || (permission.action == Action::All)))
             .filter(|permission| scope_checker.is_in_scope(*user_id, &permission.scope, obj));
 
@@ -179,37 +335,31 @@ impl<T> Acl<Resource, Action, Scope, FailureError, T> for ApplicationAcl {
 #[cfg(test)]
 mod tests {
 
+    use std::env;
+    use std::fs;
+
     use repos::legacy_acl::{Acl, CheckScope};
-    use std::time::SystemTime;
-    use stq_static_resources::OrderState;
     use stq_types::UserId;
     use stq_types::*;
 
     use models::*;
     use repos::*;
+    use test_support::{render_permission_matrix, OrderInfoBuilder};
+
+    const PERMISSION_MATRIX_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/repos/acl/permission_matrix.txt");
 
     fn create_order() -> OrderInfo {
-        OrderInfo {
-            id: OrderInfoId::new(),
-            order_id: OrderId::new(),
-            customer_id: UserId(1),
-            store_id: StoreId(1),
-            saga_id: SagaId::new(),
-            status: OrderState::New,
-            total_amount: ProductPrice(100.0),
-            created_at: SystemTime::now(),
-            updated_at: SystemTime::now(),
-        }
+        OrderInfoBuilder::new().customer_id(UserId(1)).build()
     }
 
     #[derive(Default)]
     struct ScopeChecker;
 
     impl CheckScope<Scope, OrderInfo> for ScopeChecker {
-        fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&OrderInfo>) -> bool {
+        fn is_in_scope(&self, user_id: UserId, scope: &Scope, obj: Option<&OrderInfo>) -> bool {
             match *scope {
                 Scope::All => true,
-                Scope::Owned => false,
+                Scope::Owned => obj.map_or(false, |order_info| order_info.customer_id == user_id),
             }
         }
     }
@@ -241,16 +391,19 @@ mod tests {
     }
 
     #[test]
-    #[ignore]
     fn test_ordinary_user_for_users() {
         let acl = ApplicationAcl::new(vec![BillingRole::User], UserId(2));
         let s = ScopeChecker::default();
-        let mut resource = create_order();
-        resource.customer_id = UserId(2);
+        let resource = OrderInfoBuilder::new().customer_id(UserId(2)).build();
+        let other_resource = create_order();
 
         assert_eq!(acl.allows(Resource::OrderInfo, Action::All, &s, Some(&resource)).unwrap(), false);
         assert_eq!(acl.allows(Resource::OrderInfo, Action::Read, &s, Some(&resource)).unwrap(), true);
         assert_eq!(acl.allows(Resource::OrderInfo, Action::Write, &s, Some(&resource)).unwrap(), true);
+        assert_eq!(
+            acl.allows(Resource::OrderInfo, Action::Read, &s, Some(&other_resource)).unwrap(),
+            false
+        );
     }
 
     #[test]
@@ -286,4 +439,132 @@ mod tests {
         assert_eq!(acl.allows(Resource::UserRoles, Action::Read, &s, Some(&resource)).unwrap(), false);
         assert_eq!(acl.allows(Resource::UserRoles, Action::Write, &s, Some(&resource)).unwrap(), false);
     }
+
+    #[test]
+    fn test_support_reads_without_secrets() {
+        let acl = ApplicationAcl::new(vec![BillingRole::Support], UserId(2));
+        let s = ScopeChecker::default();
+        let resource = create_order();
+
+        assert_eq!(acl.allows(Resource::OrderInfo, Action::Read, &s, Some(&resource)).unwrap(), true);
+        assert_eq!(acl.allows(Resource::OrderInfo, Action::Write, &s, Some(&resource)).unwrap(), false);
+        assert_eq!(acl.allows(Resource::Customer, Action::Read, &s, None).unwrap(), true);
+        assert_eq!(acl.allows(Resource::Customer, Action::Write, &s, None).unwrap(), false);
+        assert_eq!(acl.allows(Resource::BillingInfo, Action::Read, &s, None).unwrap(), true);
+        assert_eq!(acl.allows(Resource::BillingInfoSecrets, Action::Read, &s, None).unwrap(), false);
+    }
+
+    #[test]
+    fn test_payment_intent_history_is_read_by_financial_managers_only() {
+        let s = ScopeChecker::default();
+        let financial_manager_acl = ApplicationAcl::new(vec![BillingRole::FinancialManager], UserId(2));
+        let support_acl = ApplicationAcl::new(vec![BillingRole::Support], UserId(3));
+        let user_acl = ApplicationAcl::new(vec![BillingRole::User], UserId(4));
+
+        assert_eq!(
+            financial_manager_acl
+                .allows(Resource::PaymentIntentHistory, Action::Read, &s, None)
+                .unwrap(),
+            true
+        );
+        assert_eq!(
+            financial_manager_acl
+                .allows(Resource::PaymentIntentHistory, Action::Write, &s, None)
+                .unwrap(),
+            false
+        );
+        assert_eq!(
+            support_acl.allows(Resource::PaymentIntentHistory, Action::Read, &s, None).unwrap(),
+            false
+        );
+        assert_eq!(
+            user_acl.allows(Resource::PaymentIntentHistory, Action::Read, &s, None).unwrap(),
+            false
+        );
+    }
+
+    #[test]
+    fn test_api_key_acl_is_limited_to_its_scopes() {
+        let s = ScopeChecker::default();
+        let resource = OrderInfoBuilder::new().customer_id(UserId(2)).build();
+        let orders_acl = ApplicationAcl::for_api_key(&[ApiKeyScope::Orders], UserId(2));
+        let fee_statements_acl = ApplicationAcl::for_api_key(&[ApiKeyScope::FeeStatements], UserId(2));
+
+        assert_eq!(
+            orders_acl.allows(Resource::OrderInfo, Action::Read, &s, Some(&resource)).unwrap(),
+            true
+        );
+        assert_eq!(
+            orders_acl.allows(Resource::OrderInfo, Action::Write, &s, Some(&resource)).unwrap(),
+            false
+        );
+        assert_eq!(
+            orders_acl
+                .allows(Resource::OrderInfo, Action::Read, &s, Some(&create_order()))
+                .unwrap(),
+            false
+        );
+        assert_eq!(
+            fee_statements_acl
+                .allows(Resource::OrderInfo, Action::Read, &s, Some(&resource))
+                .unwrap(),
+            false
+        );
+    }
+
+    #[test]
+    fn test_api_key_permissions_are_store_manager_permissions() {
+        let scopes = [
+            ApiKeyScope::Orders,
+            ApiKeyScope::Invoices,
+            ApiKeyScope::Balance,
+            ApiKeyScope::Payouts,
+            ApiKeyScope::FeeStatements,
+        ];
+        let store_manager_permissions = &super::PERMISSIONS[&BillingRole::StoreManager];
+
+        for permission in super::api_key_permissions(&scopes) {
+            assert!(
+                store_manager_permissions
+                    .iter()
+                    .any(|granted| granted.resource == permission.resource
+                        && granted.action == permission.action
+                        && granted.scope == permission.scope),
+                "{:?} {:?} is not granted to store managers",
+                permission.action,
+                permission.resource
+            );
+        }
+    }
+
+    #[test]
+    fn test_acls_share_permissions() {
+        let user_acl = ApplicationAcl::new(vec![BillingRole::User], UserId(2));
+        let superuser_acl = ApplicationAcl::new(vec![BillingRole::Superuser], UserId(1));
+
+        assert!(::std::sync::Arc::ptr_eq(&user_acl.acls, &superuser_acl.acls));
+    }
+
+    #[test]
+    fn test_permission_matrix_matches_golden_table() {
+        let matrix = render_permission_matrix();
+        if env::var("UPDATE_ACL_GOLDEN").is_ok() {
+            fs::write(PERMISSION_MATRIX_PATH, &matrix).unwrap();
+            return;
+        }
+
+        let golden = fs::read_to_string(PERMISSION_MATRIX_PATH).unwrap();
+        let changed_lines = matrix
+            .lines()
+            .zip(golden.lines())
+            .filter(|(actual, expected)| actual != expected)
+            .map(|(actual, expected)| format!("expected: {}\n     got: {}", expected, actual))
+            .collect::<Vec<_>>();
+        assert!(
+            changed_lines.is_empty() && matrix.lines().count() == golden.lines().count(),
+            "permissions differ from {}, rerun with UPDATE_ACL_GOLDEN=1 if the change is intended:\n{}",
+            PERMISSION_MATRIX_PATH,
+            changed_lines.join("\n")
+        );
+    }
 }
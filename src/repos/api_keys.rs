@@ -0,0 +1,198 @@
+use chrono::NaiveDateTime;
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{StoreId, UserId};
+
+use models::authorization::*;
+use models::{ApiKey, ApiKeyId, NewApiKey, UserRole};
+use repos::legacy_acl::*;
+
+use schema::api_keys::dsl as ApiKeysDsl;
+use schema::roles::dsl as UserRolesDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type ApiKeysRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, ApiKeyAccess>>;
+
+pub struct ApiKeyAccess {
+    store_id: StoreId,
+}
+
+pub struct ApiKeysRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: ApiKeysRepoAcl,
+}
+
+pub trait ApiKeysRepo {
+    fn create(&self, payload: NewApiKey) -> RepoResultV2<ApiKey>;
+    fn get(&self, id: ApiKeyId) -> RepoResultV2<Option<ApiKey>>;
+    fn get_by_key_hash(&self, key_hash: String) -> RepoResultV2<Option<ApiKey>>;
+    fn list_by_store_id(&self, store_id: StoreId) -> RepoResultV2<Vec<ApiKey>>;
+    /// Revoking a revoked key keeps the time it was first revoked
+    fn revoke(&self, id: ApiKeyId, revoked_at: NaiveDateTime) -> RepoResultV2<ApiKey>;
+    fn set_last_used_at(&self, id: ApiKeyId, last_used_at: NaiveDateTime) -> RepoResultV2<ApiKey>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> ApiKeysRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: ApiKeysRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+
+    fn check_access(&self, action: Action, store_id: StoreId) -> RepoResultV2<()> {
+        acl::check(&*self.acl, Resource::ApiKey, action, self, Some(&ApiKeyAccess { store_id })).map_err(ectx!(ErrorKind::Forbidden))
+    }
+
+    fn get_by_id(&self, id: ApiKeyId) -> RepoResultV2<ApiKey> {
+        ApiKeysDsl::api_keys
+            .filter(ApiKeysDsl::id.eq(id))
+            .get_result::<ApiKey>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?
+            .ok_or_else(|| {
+                let e = format_err!("api key {} not found", id);
+                ectx!(err e, ErrorKind::NotFound)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> ApiKeysRepo for ApiKeysRepoImpl<'a, T> {
+    fn create(&self, payload: NewApiKey) -> RepoResultV2<ApiKey> {
+        debug!("create api key for store {}.", payload.store_id);
+        self.check_access(Action::Write, payload.store_id)?;
+
+        let command = diesel::insert_into(ApiKeysDsl::api_keys).values(&payload);
+
+        command.get_result::<ApiKey>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get(&self, id: ApiKeyId) -> RepoResultV2<Option<ApiKey>> {
+        debug!("get api key {}.", id);
+
+        let api_key = ApiKeysDsl::api_keys
+            .filter(ApiKeysDsl::id.eq(id))
+            .get_result::<ApiKey>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        if let Some(ref api_key) = api_key {
+            self.check_access(Action::Read, api_key.store_id)?;
+        }
+
+        Ok(api_key)
+    }
+
+    fn get_by_key_hash(&self, key_hash: String) -> RepoResultV2<Option<ApiKey>> {
+        debug!("get api key by hash.");
+
+        let api_key = ApiKeysDsl::api_keys
+            .filter(ApiKeysDsl::key_hash.eq(key_hash))
+            .get_result::<ApiKey>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        if let Some(ref api_key) = api_key {
+            self.check_access(Action::Read, api_key.store_id)?;
+        }
+
+        Ok(api_key)
+    }
+
+    fn list_by_store_id(&self, store_id: StoreId) -> RepoResultV2<Vec<ApiKey>> {
+        debug!("list api keys for store {}.", store_id);
+        self.check_access(Action::Read, store_id)?;
+
+        ApiKeysDsl::api_keys
+            .filter(ApiKeysDsl::store_id.eq(store_id))
+            .order_by(ApiKeysDsl::id.asc())
+            .get_results::<ApiKey>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn revoke(&self, id: ApiKeyId, revoked_at: NaiveDateTime) -> RepoResultV2<ApiKey> {
+        debug!("revoke api key {}.", id);
+
+        let api_key = self.get_by_id(id)?;
+        self.check_access(Action::Write, api_key.store_id)?;
+
+        if api_key.is_revoked() {
+            return Ok(api_key);
+        }
+
+        let filter = ApiKeysDsl::api_keys.filter(ApiKeysDsl::id.eq(id));
+
+        diesel::update(filter)
+            .set(ApiKeysDsl::revoked_at.eq(revoked_at))
+            .get_result::<ApiKey>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn set_last_used_at(&self, id: ApiKeyId, last_used_at: NaiveDateTime) -> RepoResultV2<ApiKey> {
+        debug!("set last used time of api key {}.", id);
+
+        let api_key = self.get_by_id(id)?;
+        self.check_access(Action::Write, api_key.store_id)?;
+
+        let filter = ApiKeysDsl::api_keys.filter(ApiKeysDsl::id.eq(id));
+
+        diesel::update(filter)
+            .set(ApiKeysDsl::last_used_at.eq(last_used_at))
+            .get_result::<ApiKey>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, ApiKeyAccess>
+    for ApiKeysRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, user_id: UserId, scope: &Scope, obj: Option<&ApiKeyAccess>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => {
+                if let Some(ApiKeyAccess { store_id }) = obj {
+                    UserRolesDsl::roles
+                        .filter(UserRolesDsl::user_id.eq(user_id))
+                        .get_results::<UserRole>(self.db_conn)
+                        .map_err(From::from)
+                        .map(|user_roles_arg| {
+                            user_roles_arg
+                                .iter()
+                                .any(|user_role_arg| user_role_arg.data.clone().map(|data| data == store_id.0).unwrap_or_default())
+                        })
+                        .unwrap_or_else(|_: FailureError| false)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
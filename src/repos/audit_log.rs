@@ -0,0 +1,160 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::dsl::sql;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::sql_types::{Bool, Text};
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::{AuditLogEntry, AuditLogSearch, AuditLogSearchResults, NewAuditLogEntry};
+use repos::legacy_acl::*;
+
+use schema::audit_log::dsl as AuditLogDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type AuditLogRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, AuditLogEntry>>;
+
+type BoxedExpr = Box<BoxableExpression<crate::schema::audit_log::table, Pg, SqlType = Bool>>;
+
+pub struct AuditLogRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: AuditLogRepoAcl,
+}
+
+pub trait AuditLogRepo {
+    fn create(&self, payload: NewAuditLogEntry) -> RepoResultV2<AuditLogEntry>;
+    fn search(&self, skip: i64, count: i64, search: AuditLogSearch) -> RepoResultV2<AuditLogSearchResults>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> AuditLogRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: AuditLogRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> AuditLogRepo for AuditLogRepoImpl<'a, T> {
+    fn create(&self, payload: NewAuditLogEntry) -> RepoResultV2<AuditLogEntry> {
+        debug!("create audit log entry {:?}.", payload);
+        acl::check(&*self.acl, Resource::AuditLog, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(AuditLogDsl::audit_log).values(&payload);
+
+        command.get_result::<AuditLogEntry>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn search(&self, skip: i64, count: i64, search: AuditLogSearch) -> RepoResultV2<AuditLogSearchResults> {
+        debug!("Searching audit log, skip={}, count={}, search {:?}", skip, count, search);
+        acl::check(&*self.acl, Resource::AuditLog, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let query: BoxedExpr = into_expr(search).unwrap_or(Box::new(true.into_sql::<Bool>()));
+
+        let entries = crate::schema::audit_log::table
+            .filter(&query)
+            .offset(skip)
+            .limit(count)
+            .order_by(AuditLogDsl::id.desc())
+            .get_results::<AuditLogEntry>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        let total_count = AuditLogDsl::audit_log
+            .filter(&query)
+            .count()
+            .get_result::<i64>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        Ok(AuditLogSearchResults { total_count, entries })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, AuditLogEntry>
+    for AuditLogRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&AuditLogEntry>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
+
+fn into_expr(search: AuditLogSearch) -> Option<BoxedExpr> {
+    let mut query: Option<BoxedExpr> = None;
+
+    let AuditLogSearch {
+        actor_user_id,
+        action,
+        resource_type,
+        resource_id,
+        created_from,
+        created_to,
+        text,
+    } = search;
+
+    if let Some(actor_user_id_filter) = actor_user_id {
+        let new_condition = AuditLogDsl::actor_user_id.eq(actor_user_id_filter);
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
+    if let Some(action_filter) = action {
+        let new_condition = AuditLogDsl::action.eq(action_filter);
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
+    if let Some(resource_type_filter) = resource_type {
+        let new_condition = AuditLogDsl::resource_type.eq(resource_type_filter);
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
+    if let Some(resource_id_filter) = resource_id {
+        let new_condition = AuditLogDsl::resource_id.eq(resource_id_filter);
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
+    if let Some(created_from_filter) = created_from {
+        let new_condition = AuditLogDsl::created_at.ge(created_from_filter);
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
+    if let Some(created_to_filter) = created_to {
+        let new_condition = AuditLogDsl::created_at.le(created_to_filter);
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
+    if let Some(text_filter) = text {
+        // must match the expression of `audit_log_snapshots_fts_idx` for the index to be used
+        let new_condition = sql::<Bool>(
+            "to_tsvector('simple', coalesce(before::text, '') || ' ' || coalesce(after::text, '')) @@ plainto_tsquery('simple', ",
+        )
+        .bind::<Text, _>(text_filter)
+        .sql(")");
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
+    query
+}
+
+fn and(old_condition: Option<BoxedExpr>, new_condition: BoxedExpr) -> BoxedExpr {
+    if let Some(old_condition) = old_condition {
+        Box::new(old_condition.and(new_condition))
+    } else {
+        new_condition
+    }
+}
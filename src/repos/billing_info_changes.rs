@@ -0,0 +1,268 @@
+use chrono::Utc;
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+use serde_json;
+
+use stq_types::{StoreId, UserId};
+
+use models::authorization::*;
+use models::{
+    BillingInfoChange, BillingInfoChangeId, BillingInfoChangePayload, BillingInfoChangeReview, BillingInfoChangeStatus,
+    NewBillingInfoChange, NewRawBillingInfoChange, RawBillingInfoChange, UserRole,
+};
+use repos::legacy_acl::*;
+
+use schema::billing_info_changes::dsl as BillingInfoChangesDsl;
+use schema::roles::dsl as UserRolesDsl;
+
+use super::acl;
+use super::encryption::FieldCipher;
+use super::error::*;
+use super::types::RepoResultV2;
+
+const PAYLOAD_COLUMN: &'static str = "billing_info_changes.payload";
+
+pub type BillingInfoChangesRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, BillingInfoChangeAccess>>;
+
+pub struct BillingInfoChangesRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: BillingInfoChangesRepoAcl,
+    pub cipher: FieldCipher,
+}
+
+pub struct BillingInfoChangeAccess {
+    pub store_id: StoreId,
+}
+
+pub trait BillingInfoChangesRepo {
+    fn create(&self, payload: NewBillingInfoChange) -> RepoResultV2<BillingInfoChange>;
+    fn get(&self, id: BillingInfoChangeId) -> RepoResultV2<Option<BillingInfoChange>>;
+    /// Change of the store waiting for a financial manager, there is one at most
+    fn get_pending_by_store_id(&self, store_id: StoreId) -> RepoResultV2<Option<BillingInfoChange>>;
+    /// Changes of the store, the latest first
+    fn list_by_store_id(&self, store_id: StoreId) -> RepoResultV2<Vec<BillingInfoChange>>;
+    /// Changes of all stores waiting for a financial manager, the oldest first
+    fn list_pending(&self) -> RepoResultV2<Vec<BillingInfoChange>>;
+    /// Approves or rejects a pending change, `None` is returned if the change is not pending anymore
+    fn review(&self, id: BillingInfoChangeId, review: BillingInfoChangeReview) -> RepoResultV2<Option<BillingInfoChange>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> BillingInfoChangesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: BillingInfoChangesRepoAcl, cipher: FieldCipher) -> Self {
+        Self { db_conn, acl, cipher }
+    }
+
+    fn seal(&self, payload: &BillingInfoChangePayload) -> RepoResultV2<String> {
+        let payload = serde_json::to_string(payload).map_err(ectx!(try ErrorSource::SerdeJson, ErrorKind::Internal))?;
+
+        self.cipher
+            .encrypt(PAYLOAD_COLUMN, &payload)
+            .map_err(ectx!(ErrorSource::Encryption, ErrorKind::Internal => PAYLOAD_COLUMN))
+    }
+
+    /// Decrypts the requested billing info, the controller masks the account details for the users that may not see them in full
+    fn reveal(&self, change: RawBillingInfoChange) -> RepoResultV2<BillingInfoChange> {
+        let payload = self
+            .cipher
+            .decrypt(PAYLOAD_COLUMN, &change.payload)
+            .map_err(ectx!(try ErrorSource::Encryption, ErrorKind::Internal => PAYLOAD_COLUMN))?;
+        let payload =
+            serde_json::from_str::<BillingInfoChangePayload>(&payload).map_err(ectx!(try ErrorSource::SerdeJson, ErrorKind::Internal))?;
+
+        Ok(BillingInfoChange {
+            id: change.id,
+            store_id: change.store_id,
+            payload,
+            status: change.status,
+            requested_by: change.requested_by,
+            reviewed_by: change.reviewed_by,
+            rejection_reason: change.rejection_reason,
+            created_at: change.created_at,
+            reviewed_at: change.reviewed_at,
+        })
+    }
+
+    fn check_read(&self, changes: &[RawBillingInfoChange]) -> RepoResultV2<()> {
+        for change in changes {
+            let access = BillingInfoChangeAccess { store_id: change.store_id };
+            acl::check(&*self.acl, Resource::BillingInfoChange, Action::Read, self, Some(&access))
+                .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> BillingInfoChangesRepo
+    for BillingInfoChangesRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewBillingInfoChange) -> RepoResultV2<BillingInfoChange> {
+        debug!(
+            "create billing info change of store {} with status {:?}.",
+            payload.store_id, payload.status
+        );
+        let access = BillingInfoChangeAccess {
+            store_id: payload.store_id,
+        };
+        acl::check(&*self.acl, Resource::BillingInfoChange, Action::Write, self, Some(&access)).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let reviewed_at = match payload.status {
+            BillingInfoChangeStatus::Pending => None,
+            BillingInfoChangeStatus::Approved | BillingInfoChangeStatus::Rejected => Some(Utc::now().naive_utc()),
+        };
+        let new_change = NewRawBillingInfoChange {
+            store_id: payload.store_id,
+            payload: self.seal(&payload.payload)?,
+            status: payload.status,
+            requested_by: payload.requested_by,
+            reviewed_by: payload.reviewed_by,
+            reviewed_at,
+        };
+
+        let command = diesel::insert_into(BillingInfoChangesDsl::billing_info_changes).values(&new_change);
+
+        let created_change = command.get_result::<RawBillingInfoChange>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        self.reveal(created_change)
+    }
+
+    fn get(&self, id: BillingInfoChangeId) -> RepoResultV2<Option<BillingInfoChange>> {
+        debug!("get billing info change {}.", id);
+
+        let change = BillingInfoChangesDsl::billing_info_changes
+            .filter(BillingInfoChangesDsl::id.eq(id))
+            .get_result::<RawBillingInfoChange>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        match change {
+            Some(change) => {
+                self.check_read(::std::slice::from_ref(&change))?;
+                self.reveal(change).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn get_pending_by_store_id(&self, store_id: StoreId) -> RepoResultV2<Option<BillingInfoChange>> {
+        debug!("get pending billing info change of store {}.", store_id);
+        let access = BillingInfoChangeAccess { store_id };
+        acl::check(&*self.acl, Resource::BillingInfoChange, Action::Read, self, Some(&access)).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let change = BillingInfoChangesDsl::billing_info_changes
+            .filter(BillingInfoChangesDsl::store_id.eq(store_id))
+            .filter(BillingInfoChangesDsl::status.eq(BillingInfoChangeStatus::Pending))
+            .get_result::<RawBillingInfoChange>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        match change {
+            Some(change) => self.reveal(change).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn list_by_store_id(&self, store_id: StoreId) -> RepoResultV2<Vec<BillingInfoChange>> {
+        debug!("list billing info changes of store {}.", store_id);
+        let access = BillingInfoChangeAccess { store_id };
+        acl::check(&*self.acl, Resource::BillingInfoChange, Action::Read, self, Some(&access)).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let changes = BillingInfoChangesDsl::billing_info_changes
+            .filter(BillingInfoChangesDsl::store_id.eq(store_id))
+            .order_by(BillingInfoChangesDsl::id.desc())
+            .get_results::<RawBillingInfoChange>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        changes.into_iter().map(|change| self.reveal(change)).collect()
+    }
+
+    fn list_pending(&self) -> RepoResultV2<Vec<BillingInfoChange>> {
+        debug!("list pending billing info changes.");
+
+        let changes = BillingInfoChangesDsl::billing_info_changes
+            .filter(BillingInfoChangesDsl::status.eq(BillingInfoChangeStatus::Pending))
+            .order_by(BillingInfoChangesDsl::id.asc())
+            .get_results::<RawBillingInfoChange>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        self.check_read(&changes)?;
+        changes.into_iter().map(|change| self.reveal(change)).collect()
+    }
+
+    fn review(&self, id: BillingInfoChangeId, review: BillingInfoChangeReview) -> RepoResultV2<Option<BillingInfoChange>> {
+        debug!("review billing info change {}: {:?}.", id, review.status);
+        // Reviewing is allowed to the roles changing billing info of any store,
+        // store managers may only request changes of their own stores
+        acl::check(&*self.acl, Resource::BillingInfoChange, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let filter = BillingInfoChangesDsl::billing_info_changes
+            .filter(BillingInfoChangesDsl::id.eq(id))
+            .filter(BillingInfoChangesDsl::status.eq(BillingInfoChangeStatus::Pending));
+
+        let reviewed_change = diesel::update(filter)
+            .set((
+                BillingInfoChangesDsl::status.eq(review.status),
+                BillingInfoChangesDsl::reviewed_by.eq(Some(review.reviewed_by)),
+                BillingInfoChangesDsl::rejection_reason.eq(review.rejection_reason),
+                BillingInfoChangesDsl::reviewed_at.eq(Some(Utc::now().naive_utc())),
+            ))
+            .get_result::<RawBillingInfoChange>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        match reviewed_change {
+            Some(change) => self.reveal(change).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, BillingInfoChangeAccess>
+    for BillingInfoChangesRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, user_id: UserId, scope: &Scope, obj: Option<&BillingInfoChangeAccess>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => {
+                if let Some(BillingInfoChangeAccess { store_id }) = obj {
+                    UserRolesDsl::roles
+                        .filter(UserRolesDsl::user_id.eq(user_id))
+                        .get_results::<UserRole>(self.db_conn)
+                        .map_err(From::from)
+                        .map(|user_roles_arg| {
+                            user_roles_arg
+                                .iter()
+                                .any(|user_role_arg| user_role_arg.data.clone().map(|data| data == store_id.0).unwrap_or_default())
+                        })
+                        .unwrap_or_else(|_: FailureError| false)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use chrono::NaiveDateTime;
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::sql_query;
+use diesel::sql_types::Timestamp;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::{CashbackLiability, CashbackLiabilitySnapshot, NewCashbackLiabilitySnapshot};
+use repos::legacy_acl::*;
+
+use schema::cashback_liability_snapshots::dsl as CashbackLiabilitySnapshotsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type CashbackLiabilitiesRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, CashbackLiabilitySnapshot>>;
+
+pub struct CashbackLiabilitiesRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: CashbackLiabilitiesRepoAcl,
+}
+
+pub trait CashbackLiabilitiesRepo {
+    /// Cashback of the invoices paid before `as_of` by currency
+    fn get_outstanding(&self, as_of: NaiveDateTime) -> RepoResultV2<Vec<CashbackLiability>>;
+    fn create_snapshot(&self, payload: NewCashbackLiabilitySnapshot) -> RepoResultV2<CashbackLiabilitySnapshot>;
+    /// Snapshots taken as of the given time or all of them, the latest first
+    fn get_snapshots(&self, as_of: Option<NaiveDateTime>) -> RepoResultV2<Vec<CashbackLiabilitySnapshot>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CashbackLiabilitiesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: CashbackLiabilitiesRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CashbackLiabilitiesRepo
+    for CashbackLiabilitiesRepoImpl<'a, T>
+{
+    fn get_outstanding(&self, as_of: NaiveDateTime) -> RepoResultV2<Vec<CashbackLiability>> {
+        debug!("Getting outstanding cashback as of {}", as_of);
+        acl::check(&*self.acl, Resource::CashbackLiability, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        // There is no cashback ledger, the final cashback of the invoice is counted in the buyer currency
+        // like in the invoice itself
+        let command = sql_query(
+            "
+            SELECT
+                buyer_currency AS currency,
+                SUM(final_cashback_amount) AS amount,
+                COUNT(*) AS invoices_count
+            FROM invoices_v2
+            WHERE final_cashback_amount > 0 AND paid_at < $1
+            GROUP BY buyer_currency
+            ORDER BY buyer_currency
+        ",
+        )
+        .bind::<Timestamp, _>(as_of);
+
+        command.get_results::<CashbackLiability>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn create_snapshot(&self, payload: NewCashbackLiabilitySnapshot) -> RepoResultV2<CashbackLiabilitySnapshot> {
+        debug!("create cashback liability snapshot {:?}.", payload);
+        acl::check(&*self.acl, Resource::CashbackLiability, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(CashbackLiabilitySnapshotsDsl::cashback_liability_snapshots).values(&payload);
+
+        command.get_result::<CashbackLiabilitySnapshot>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get_snapshots(&self, as_of: Option<NaiveDateTime>) -> RepoResultV2<Vec<CashbackLiabilitySnapshot>> {
+        debug!("Getting cashback liability snapshots as of {:?}", as_of);
+        acl::check(&*self.acl, Resource::CashbackLiability, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let mut query = CashbackLiabilitySnapshotsDsl::cashback_liability_snapshots.into_boxed();
+        if let Some(as_of) = as_of {
+            query = query.filter(CashbackLiabilitySnapshotsDsl::as_of.eq(as_of));
+        }
+
+        query
+            .order((CashbackLiabilitySnapshotsDsl::as_of.desc(), CashbackLiabilitySnapshotsDsl::currency))
+            .get_results::<CashbackLiabilitySnapshot>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, CashbackLiabilitySnapshot>
+    for CashbackLiabilitiesRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&CashbackLiabilitySnapshot>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
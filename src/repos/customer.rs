@@ -3,10 +3,12 @@ use diesel::connection::AnsiTransactionManager;
 use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::query_dsl::RunQueryDsl;
-use diesel::sql_types::Bool;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Bool};
 use diesel::Connection;
 use failure::Error as FailureError;
 use failure::Fail;
+use validator::{ValidationError, ValidationErrors};
 
 use repos::legacy_acl::*;
 
@@ -36,11 +38,15 @@ pub struct CustomersRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager
 pub trait CustomersRepo {
     fn get(&self, search: SearchCustomer) -> RepoResultV2<Option<DbCustomer>>;
 
+    /// Fails with `ErrorKind::Constraints` if the user already has a customer
     fn create(&self, payload: NewDbCustomer) -> RepoResultV2<DbCustomer>;
 
     fn update(&self, id: CustomerId, payload: UpdateDbCustomer) -> RepoResultV2<DbCustomer>;
 
     fn delete(&self, id: CustomerId) -> RepoResultV2<Option<DbCustomer>>;
+
+    /// Customers of the first `limit` users having more than one customer, ordered by user and creation time
+    fn get_duplicates(&self, limit: i64) -> RepoResultV2<Vec<DbCustomer>>;
 }
 
 impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CustomersRepoImpl<'a, T> {
@@ -58,7 +64,11 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             SearchCustomer::UserId(user_id) => Box::new(CustomersDsl::user_id.eq(user_id)),
         };
 
-        let query = CustomersDsl::customers.filter(search_exp);
+        // a user may still have duplicates created before customers were unique per user
+        let query = CustomersDsl::customers
+            .filter(search_exp)
+            .order((CustomersDsl::created_at, CustomersDsl::id))
+            .limit(1);
 
         query
             .get_result(self.db_conn)
@@ -82,6 +92,20 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
 
         acl::check(&*self.acl, Resource::Customer, Action::Write, self, Some(&access)).map_err(ectx!(try ErrorKind::Forbidden))?;
 
+        let existing = CustomersDsl::customers
+            .filter(CustomersDsl::user_id.eq(payload.user_id))
+            .select(CustomersDsl::id)
+            .first::<CustomerId>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })?;
+        if let Some(existing) = existing {
+            let e = format_err!("User {} already has customer {}", payload.user_id, existing);
+            return Err(ectx!(err e, ErrorKind::Constraints(user_not_unique_errors())));
+        }
+
         let command = diesel::insert_into(CustomersDsl::customers).values(&payload);
 
         command.get_result::<DbCustomer>(self.db_conn).map_err(|e| {
@@ -136,6 +160,42 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
                 })
             })
     }
+
+    fn get_duplicates(&self, limit: i64) -> RepoResultV2<Vec<DbCustomer>> {
+        debug!("Getting duplicate customers of {} users", limit);
+
+        acl::check(&*self.acl, Resource::Customer, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = sql_query(
+            "
+            SELECT customers.*
+            FROM customers
+            WHERE customers.user_id IN (
+                SELECT user_id
+                FROM customers
+                GROUP BY user_id
+                HAVING COUNT(*) > 1
+                ORDER BY user_id
+                LIMIT $1
+            )
+            ORDER BY customers.user_id, customers.created_at, customers.id
+        ",
+        )
+        .bind::<BigInt, _>(limit);
+
+        command.get_results::<DbCustomer>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+}
+
+fn user_not_unique_errors() -> ValidationErrors {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new("not unique");
+    error.message = Some("User already has a customer".into());
+    errors.add("user_id", error);
+    errors
 }
 
 impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, CustomersAccess>
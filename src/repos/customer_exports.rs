@@ -0,0 +1,162 @@
+use chrono::NaiveDateTime;
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Integer, Timestamp};
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::{CustomerExport, CustomerSpendRow, NewCustomerExport};
+use repos::legacy_acl::*;
+
+use schema::customer_exports::dsl as CustomerExportsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type CustomerExportsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, CustomerExport>>;
+
+pub struct CustomerExportsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: CustomerExportsRepoAcl,
+}
+
+pub trait CustomerExportsRepo {
+    /// The latest export, its `updated_until` is the watermark of the next incremental export
+    fn get_last(&self) -> RepoResultV2<Option<CustomerExport>>;
+    fn create(&self, payload: NewCustomerExport) -> RepoResultV2<CustomerExport>;
+    /// Spend rows of the first `limit` users after `after_user_id` whose customer or paid invoices were updated
+    /// in `(updated_after, updated_until]`, ordered by the user
+    fn find_customer_spend(
+        &self,
+        updated_after: NaiveDateTime,
+        updated_until: NaiveDateTime,
+        after_user_id: UserId,
+        limit: i64,
+    ) -> RepoResultV2<Vec<CustomerSpendRow>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CustomerExportsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: CustomerExportsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CustomerExportsRepo
+    for CustomerExportsRepoImpl<'a, T>
+{
+    fn get_last(&self) -> RepoResultV2<Option<CustomerExport>> {
+        debug!("Getting the last customer export");
+        acl::check(&*self.acl, Resource::CustomerExport, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        CustomerExportsDsl::customer_exports
+            .order(CustomerExportsDsl::id.desc())
+            .first::<CustomerExport>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn create(&self, payload: NewCustomerExport) -> RepoResultV2<CustomerExport> {
+        debug!("create customer export {:?}.", payload);
+        acl::check(&*self.acl, Resource::CustomerExport, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(CustomerExportsDsl::customer_exports).values(&payload);
+
+        command.get_result::<CustomerExport>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn find_customer_spend(
+        &self,
+        updated_after: NaiveDateTime,
+        updated_until: NaiveDateTime,
+        after_user_id: UserId,
+        limit: i64,
+    ) -> RepoResultV2<Vec<CustomerSpendRow>> {
+        debug!(
+            "Finding spend of {} customers after user {} updated in ({}, {}]",
+            limit, after_user_id, updated_after, updated_until
+        );
+        acl::check(&*self.acl, Resource::CustomerExport, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        // Cards are kept by Stripe, the ones the user has tried to pay with are counted by their fingerprints
+        let command = sql_query(
+            "
+            WITH batch AS (
+                SELECT user_id
+                FROM (
+                    SELECT user_id FROM customers WHERE updated_at > $1 AND updated_at <= $2
+                    UNION
+                    SELECT buyer_user_id FROM invoices_v2
+                    WHERE paid_at IS NOT NULL AND updated_at > $1 AND updated_at <= $2
+                ) changed
+                WHERE user_id > $3
+                ORDER BY user_id
+                LIMIT $4
+            )
+            SELECT
+                batch.user_id,
+                (
+                    SELECT customers.id FROM customers
+                    WHERE customers.user_id = batch.user_id
+                    ORDER BY customers.created_at, customers.id
+                    LIMIT 1
+                ) AS customer_id,
+                (
+                    SELECT COUNT(DISTINCT payment_attempts.payment_method_fingerprint)
+                    FROM payment_attempts
+                    JOIN invoices_v2 ON invoices_v2.id = payment_attempts.invoice_id
+                    WHERE invoices_v2.buyer_user_id = batch.user_id
+                ) AS cards_count,
+                spend.currency,
+                spend.amount,
+                spend.last_paid_at
+            FROM batch
+            LEFT JOIN (
+                SELECT
+                    buyer_user_id,
+                    buyer_currency AS currency,
+                    SUM(final_amount_paid) AS amount,
+                    MAX(paid_at) AS last_paid_at
+                FROM invoices_v2
+                WHERE paid_at IS NOT NULL AND final_amount_paid IS NOT NULL AND buyer_user_id IN (SELECT user_id FROM batch)
+                GROUP BY buyer_user_id, buyer_currency
+            ) spend ON spend.buyer_user_id = batch.user_id
+            ORDER BY batch.user_id, spend.currency
+        ",
+        )
+        .bind::<Timestamp, _>(updated_after)
+        .bind::<Timestamp, _>(updated_until)
+        .bind::<Integer, _>(after_user_id)
+        .bind::<BigInt, _>(limit);
+
+        command.get_results::<CustomerSpendRow>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, CustomerExport>
+    for CustomerExportsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&CustomerExport>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use chrono::NaiveDateTime;
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::{DataRetentionSubject, DataSubject, NewDataRetentionSubject, RetentionTable};
+use repos::legacy_acl::*;
+
+use schema::billing_info_changes::dsl as BillingInfoChangesDsl;
+use schema::customers::dsl as CustomersDsl;
+use schema::data_retention_subjects::dsl as DataRetentionSubjectsDsl;
+use schema::international_billing_info::dsl as InternationalBillingInfoDsl;
+use schema::invoice_buyer_details::dsl as InvoiceBuyerDetailsDsl;
+use schema::russia_billing_info::dsl as RussiaBillingInfoDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type DataRetentionRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, DataRetentionSubject>>;
+
+pub struct DataRetentionRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: DataRetentionRepoAcl,
+}
+
+pub trait DataRetentionRepo {
+    fn get(&self, subject: DataSubject) -> RepoResultV2<Option<DataRetentionSubject>>;
+    /// Records the subject as closed, a subject closed before keeps the time it was first closed at
+    fn mark_closed(&self, subject: DataSubject, closed_at: NaiveDateTime) -> RepoResultV2<DataRetentionSubject>;
+    /// Puts the subject under legal hold or releases it, the subject doesn't have to be closed
+    fn set_legal_hold(&self, subject: DataSubject, legal_hold: bool, reason: Option<String>) -> RepoResultV2<DataRetentionSubject>;
+    /// Ids of the subjects closed before `closed_before` and not under legal hold that still have data in the table,
+    /// in ascending order starting after `after_subject_id`
+    fn find_purgeable(
+        &self,
+        table: RetentionTable,
+        closed_before: NaiveDateTime,
+        after_subject_id: i32,
+        limit: i64,
+    ) -> RepoResultV2<Vec<i32>>;
+    /// Rows of the table holding data of the subjects
+    fn count_rows(&self, table: RetentionTable, subject_ids: Vec<i32>) -> RepoResultV2<i64>;
+    /// Deletes or clears the data of the subjects in the table, returns the number of rows purged
+    fn purge(&self, table: RetentionTable, subject_ids: Vec<i32>) -> RepoResultV2<usize>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> DataRetentionRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: DataRetentionRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+
+    /// Subject as stored, created if there is none yet
+    fn get_or_create(&self, subject: DataSubject) -> RepoResultV2<DataRetentionSubject> {
+        diesel::insert_into(DataRetentionSubjectsDsl::data_retention_subjects)
+            .values(&NewDataRetentionSubject::new(subject))
+            .on_conflict_do_nothing()
+            .execute(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        DataRetentionSubjectsDsl::data_retention_subjects
+            .filter(DataRetentionSubjectsDsl::subject_type.eq(subject.subject_type()))
+            .filter(DataRetentionSubjectsDsl::subject_id.eq(subject.subject_id()))
+            .get_result::<DataRetentionSubject>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> DataRetentionRepo
+    for DataRetentionRepoImpl<'a, T>
+{
+    fn get(&self, subject: DataSubject) -> RepoResultV2<Option<DataRetentionSubject>> {
+        debug!("get data retention of {}.", subject);
+        acl::check(&*self.acl, Resource::DataRetention, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        DataRetentionSubjectsDsl::data_retention_subjects
+            .filter(DataRetentionSubjectsDsl::subject_type.eq(subject.subject_type()))
+            .filter(DataRetentionSubjectsDsl::subject_id.eq(subject.subject_id()))
+            .get_result::<DataRetentionSubject>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn mark_closed(&self, subject: DataSubject, closed_at: NaiveDateTime) -> RepoResultV2<DataRetentionSubject> {
+        debug!("mark {} closed at {}.", subject, closed_at);
+        acl::check(&*self.acl, Resource::DataRetention, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let retention_subject = self.get_or_create(subject)?;
+        if retention_subject.closed_at.is_some() {
+            return Ok(retention_subject);
+        }
+
+        let filter = DataRetentionSubjectsDsl::data_retention_subjects.filter(DataRetentionSubjectsDsl::id.eq(retention_subject.id));
+
+        diesel::update(filter)
+            .set(DataRetentionSubjectsDsl::closed_at.eq(closed_at))
+            .get_result::<DataRetentionSubject>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn set_legal_hold(&self, subject: DataSubject, legal_hold: bool, reason: Option<String>) -> RepoResultV2<DataRetentionSubject> {
+        debug!("set legal hold of {} to {}.", subject, legal_hold);
+        acl::check(&*self.acl, Resource::DataRetention, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let retention_subject = self.get_or_create(subject)?;
+        let filter = DataRetentionSubjectsDsl::data_retention_subjects.filter(DataRetentionSubjectsDsl::id.eq(retention_subject.id));
+
+        diesel::update(filter)
+            .set((
+                DataRetentionSubjectsDsl::legal_hold.eq(legal_hold),
+                DataRetentionSubjectsDsl::legal_hold_reason.eq(reason),
+            ))
+            .get_result::<DataRetentionSubject>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn find_purgeable(
+        &self,
+        table: RetentionTable,
+        closed_before: NaiveDateTime,
+        after_subject_id: i32,
+        limit: i64,
+    ) -> RepoResultV2<Vec<i32>> {
+        debug!("find subjects closed before {} with data in {}.", closed_before, table);
+        acl::check(&*self.acl, Resource::DataRetention, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let query = DataRetentionSubjectsDsl::data_retention_subjects
+            .select(DataRetentionSubjectsDsl::subject_id)
+            .filter(DataRetentionSubjectsDsl::subject_type.eq(table.subject_type()))
+            .filter(DataRetentionSubjectsDsl::closed_at.lt(closed_before))
+            .filter(DataRetentionSubjectsDsl::legal_hold.eq(false))
+            .filter(DataRetentionSubjectsDsl::subject_id.gt(after_subject_id))
+            .order(DataRetentionSubjectsDsl::subject_id.asc())
+            .limit(limit)
+            .into_boxed();
+
+        let query = match table {
+            RetentionTable::RussiaBillingInfo => query.filter(
+                DataRetentionSubjectsDsl::subject_id
+                    .eq_any(RussiaBillingInfoDsl::russia_billing_info.select(RussiaBillingInfoDsl::store_id)),
+            ),
+            RetentionTable::InternationalBillingInfo => query.filter(
+                DataRetentionSubjectsDsl::subject_id
+                    .eq_any(InternationalBillingInfoDsl::international_billing_info.select(InternationalBillingInfoDsl::store_id)),
+            ),
+            RetentionTable::BillingInfoChanges => query.filter(
+                DataRetentionSubjectsDsl::subject_id
+                    .eq_any(BillingInfoChangesDsl::billing_info_changes.select(BillingInfoChangesDsl::store_id)),
+            ),
+            RetentionTable::CustomerEmails => query.filter(
+                DataRetentionSubjectsDsl::subject_id.eq_any(
+                    CustomersDsl::customers
+                        .select(CustomersDsl::user_id)
+                        .filter(CustomersDsl::email.is_not_null()),
+                ),
+            ),
+            RetentionTable::InvoiceBuyerDetails => query.filter(
+                DataRetentionSubjectsDsl::subject_id
+                    .eq_any(InvoiceBuyerDetailsDsl::invoice_buyer_details.select(InvoiceBuyerDetailsDsl::buyer_user_id)),
+            ),
+        };
+
+        query.get_results::<i32>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn count_rows(&self, table: RetentionTable, subject_ids: Vec<i32>) -> RepoResultV2<i64> {
+        debug!("count rows of {} subjects in {}.", subject_ids.len(), table);
+        acl::check(&*self.acl, Resource::DataRetention, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let count = match table {
+            RetentionTable::RussiaBillingInfo => RussiaBillingInfoDsl::russia_billing_info
+                .filter(RussiaBillingInfoDsl::store_id.eq_any(subject_ids))
+                .count()
+                .get_result::<i64>(self.db_conn),
+            RetentionTable::InternationalBillingInfo => InternationalBillingInfoDsl::international_billing_info
+                .filter(InternationalBillingInfoDsl::store_id.eq_any(subject_ids))
+                .count()
+                .get_result::<i64>(self.db_conn),
+            RetentionTable::BillingInfoChanges => BillingInfoChangesDsl::billing_info_changes
+                .filter(BillingInfoChangesDsl::store_id.eq_any(subject_ids))
+                .count()
+                .get_result::<i64>(self.db_conn),
+            RetentionTable::CustomerEmails => CustomersDsl::customers
+                .filter(CustomersDsl::user_id.eq_any(subject_ids))
+                .filter(CustomersDsl::email.is_not_null())
+                .count()
+                .get_result::<i64>(self.db_conn),
+            RetentionTable::InvoiceBuyerDetails => InvoiceBuyerDetailsDsl::invoice_buyer_details
+                .filter(InvoiceBuyerDetailsDsl::buyer_user_id.eq_any(subject_ids))
+                .count()
+                .get_result::<i64>(self.db_conn),
+        };
+
+        count.map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn purge(&self, table: RetentionTable, subject_ids: Vec<i32>) -> RepoResultV2<usize> {
+        debug!("purge {} of {} subjects.", table, subject_ids.len());
+        acl::check(&*self.acl, Resource::DataRetention, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let purged = match table {
+            RetentionTable::RussiaBillingInfo => {
+                diesel::delete(RussiaBillingInfoDsl::russia_billing_info.filter(RussiaBillingInfoDsl::store_id.eq_any(subject_ids)))
+                    .execute(self.db_conn)
+            }
+            RetentionTable::InternationalBillingInfo => diesel::delete(
+                InternationalBillingInfoDsl::international_billing_info.filter(InternationalBillingInfoDsl::store_id.eq_any(subject_ids)),
+            )
+            .execute(self.db_conn),
+            RetentionTable::BillingInfoChanges => {
+                diesel::delete(BillingInfoChangesDsl::billing_info_changes.filter(BillingInfoChangesDsl::store_id.eq_any(subject_ids)))
+                    .execute(self.db_conn)
+            }
+            RetentionTable::CustomerEmails => diesel::update(
+                CustomersDsl::customers
+                    .filter(CustomersDsl::user_id.eq_any(subject_ids))
+                    .filter(CustomersDsl::email.is_not_null()),
+            )
+            .set(CustomersDsl::email.eq(None::<String>))
+            .execute(self.db_conn),
+            RetentionTable::InvoiceBuyerDetails => diesel::delete(
+                InvoiceBuyerDetailsDsl::invoice_buyer_details.filter(InvoiceBuyerDetailsDsl::buyer_user_id.eq_any(subject_ids)),
+            )
+            .execute(self.db_conn),
+        };
+
+        purged.map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, DataRetentionSubject>
+    for DataRetentionRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&DataRetentionSubject>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
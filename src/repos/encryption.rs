@@ -0,0 +1,212 @@
+//! Application-level encryption of sensitive columns.
+//!
+//! Values are sealed with AES-256-GCM and stored as `enc:v1:<key id>:<base64 of nonce and ciphertext>`.
+//! The column name is bound to the value as associated data, so a value copied into another column
+//! does not decrypt. Values without the prefix were written before encryption was enabled and are
+//! returned as they are until the re-encryption job seals them.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use base64;
+use ring::aead::{self, OpeningKey, SealingKey, AES_256_GCM};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use config::Encryption as EncryptionConfig;
+
+const PREFIX: &'static str = "enc:v1:";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Clone, Fail, PartialEq)]
+pub enum EncryptionError {
+    #[fail(display = "encryption key \"{}\" is not configured", _0)]
+    UnknownKey(String),
+    #[fail(display = "encryption key \"{}\" must be {} base64 encoded bytes", _0, _1)]
+    InvalidKey(String, usize),
+    #[fail(display = "encrypted value is malformed")]
+    Malformed,
+    #[fail(display = "failed to encrypt value")]
+    Seal,
+    #[fail(display = "failed to decrypt value")]
+    Open,
+}
+
+/// Outcome of re-encrypting a batch of rows ordered by id
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ReencryptionBatch<Id> {
+    /// Id of the last row of the batch, `None` if no rows were left
+    pub last_id: Option<Id>,
+    /// Number of rows sealed with the current key
+    pub reencrypted: usize,
+}
+
+/// Encrypts values with the current key and decrypts values sealed with any configured key
+#[derive(Clone)]
+pub struct FieldCipher {
+    current_key_id: Option<String>,
+    keys: Arc<HashMap<String, Vec<u8>>>,
+    rng: Arc<SystemRandom>,
+}
+
+impl FieldCipher {
+    pub fn new(config: &EncryptionConfig) -> Result<Self, EncryptionError> {
+        let mut keys = HashMap::new();
+        for (key_id, encoded_key) in &config.keys {
+            let key = base64::decode(encoded_key).map_err(|_| EncryptionError::InvalidKey(key_id.clone(), KEY_LEN))?;
+            if key.len() != KEY_LEN || key_id.contains(':') {
+                return Err(EncryptionError::InvalidKey(key_id.clone(), KEY_LEN));
+            }
+            keys.insert(key_id.clone(), key);
+        }
+
+        if let Some(ref current_key_id) = config.current_key_id {
+            if !keys.contains_key(current_key_id) {
+                return Err(EncryptionError::UnknownKey(current_key_id.clone()));
+            }
+        }
+
+        Ok(Self {
+            current_key_id: config.current_key_id.clone(),
+            keys: Arc::new(keys),
+            rng: Arc::new(SystemRandom::new()),
+        })
+    }
+
+    /// Cipher that stores new values in plaintext
+    pub fn disabled() -> Self {
+        Self {
+            current_key_id: None,
+            keys: Arc::new(HashMap::new()),
+            rng: Arc::new(SystemRandom::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.current_key_id.is_some()
+    }
+
+    /// Seals the value of `column` with the current key
+    pub fn encrypt(&self, column: &str, plaintext: &str) -> Result<String, EncryptionError> {
+        let key_id = match self.current_key_id {
+            Some(ref key_id) => key_id,
+            None => return Ok(plaintext.to_string()),
+        };
+        let key = self.key(key_id)?;
+        let sealing_key = SealingKey::new(&AES_256_GCM, key).map_err(|_| EncryptionError::Seal)?;
+
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng.fill(&mut nonce).map_err(|_| EncryptionError::Seal)?;
+
+        let tag_len = AES_256_GCM.tag_len();
+        let mut in_out = plaintext.as_bytes().to_vec();
+        in_out.extend(vec![0u8; tag_len]);
+        let sealed_len =
+            aead::seal_in_place(&sealing_key, &nonce, column.as_bytes(), &mut in_out, tag_len).map_err(|_| EncryptionError::Seal)?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&in_out[..sealed_len]);
+
+        Ok(format!("{}{}:{}", PREFIX, key_id, base64::encode(&sealed)))
+    }
+
+    /// Opens the value of `column`, plaintext values are returned unchanged
+    pub fn decrypt(&self, column: &str, value: &str) -> Result<String, EncryptionError> {
+        let (key_id, encoded) = match split_sealed(value) {
+            Some(parts) => parts,
+            None => return Ok(value.to_string()),
+        };
+        let key = self.key(key_id)?;
+        let opening_key = OpeningKey::new(&AES_256_GCM, key).map_err(|_| EncryptionError::Open)?;
+
+        let sealed = base64::decode(encoded).map_err(|_| EncryptionError::Malformed)?;
+        if sealed.len() < NONCE_LEN + AES_256_GCM.tag_len() {
+            return Err(EncryptionError::Malformed);
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+
+        let mut in_out = ciphertext.to_vec();
+        let plaintext = aead::open_in_place(&opening_key, nonce, column.as_bytes(), 0, &mut in_out).map_err(|_| EncryptionError::Open)?;
+
+        String::from_utf8(plaintext.to_vec()).map_err(|_| EncryptionError::Malformed)
+    }
+
+    /// Whether the value is stored the way `encrypt` would store it now,
+    /// values that are not need to be re-encrypted after the key rotation
+    pub fn is_current(&self, value: &str) -> bool {
+        match (split_sealed(value), self.current_key_id.as_ref()) {
+            (Some((key_id, _)), Some(current_key_id)) => key_id == current_key_id,
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    fn key(&self, key_id: &str) -> Result<&[u8], EncryptionError> {
+        self.keys
+            .get(key_id)
+            .map(|key| key.as_slice())
+            .ok_or_else(|| EncryptionError::UnknownKey(key_id.to_string()))
+    }
+}
+
+/// Key id and encoded payload of a sealed value
+fn split_sealed(value: &str) -> Option<(&str, &str)> {
+    if !value.starts_with(PREFIX) {
+        return None;
+    }
+
+    let mut parts = value[PREFIX.len()..].splitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(key_id), Some(encoded)) => Some((key_id, encoded)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher(current_key_id: &str, key_ids: &[&str]) -> FieldCipher {
+        let keys = key_ids
+            .iter()
+            .enumerate()
+            .map(|(i, key_id)| (key_id.to_string(), base64::encode(&[i as u8; KEY_LEN])))
+            .collect();
+
+        FieldCipher::new(&EncryptionConfig {
+            current_key_id: Some(current_key_id.to_string()),
+            keys,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn encrypted_value_round_trips() {
+        let cipher = cipher("2019-03", &["2019-03"]);
+
+        let sealed = cipher.encrypt("account", "DE89370400440532013000").unwrap();
+
+        assert!(sealed.starts_with("enc:v1:2019-03:"));
+        assert!(!sealed.contains("DE89370400440532013000"));
+        assert_eq!(cipher.decrypt("account", &sealed).unwrap(), "DE89370400440532013000");
+        assert_eq!(cipher.decrypt("swift", &sealed), Err(EncryptionError::Open));
+    }
+
+    #[test]
+    fn rotated_key_still_decrypts_old_values() {
+        let old_cipher = cipher("old", &["old"]);
+        let new_cipher = cipher("new", &["old", "new"]);
+        let sealed = old_cipher.encrypt("account", "40702810900000000001").unwrap();
+
+        assert!(!new_cipher.is_current(&sealed));
+        assert!(!new_cipher.is_current("40702810900000000001"));
+        assert_eq!(new_cipher.decrypt("account", &sealed).unwrap(), "40702810900000000001");
+        assert_eq!(
+            new_cipher.decrypt("account", "40702810900000000001").unwrap(),
+            "40702810900000000001"
+        );
+        assert_eq!(
+            FieldCipher::disabled().decrypt("account", &sealed),
+            Err(EncryptionError::UnknownKey("old".to_string()))
+        );
+    }
+}
@@ -29,6 +29,8 @@ pub enum ErrorSource {
     R2d2,
     #[fail(display = "repo source - serde_json")]
     SerdeJson,
+    #[fail(display = "repo source - encryption")]
+    Encryption,
 }
 
 #[allow(dead_code)]
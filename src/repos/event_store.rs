@@ -1,4 +1,4 @@
-use chrono::{NaiveDateTime, Utc};
+use chrono::{Duration, NaiveDateTime, Utc};
 use diesel::connection::AnsiTransactionManager;
 use diesel::pg::Pg;
 use diesel::query_dsl::RunQueryDsl;
@@ -7,7 +7,7 @@ use diesel::{sql_query, Connection, ExpressionMethods, QueryDsl};
 use failure::Fail;
 use std::str::FromStr;
 
-use models::{Event, EventEntry, EventEntryId, EventStatus, RawEventEntry, RawNewEventEntry};
+use models::{Event, EventEntry, EventEntryId, EventPayloadTypes, EventStatus, EventTimings, RawEventEntry, RawNewEventEntry};
 use schema::event_store::dsl as EventStore;
 
 use super::error::*;
@@ -18,27 +18,52 @@ pub trait EventStoreRepo {
 
     fn add_scheduled_event(&self, event: Event, scheduled_on: NaiveDateTime) -> RepoResultV2<EventEntry>;
 
-    fn get_events_for_processing(&self, limit: u32) -> RepoResultV2<Vec<EventEntry>>;
+    fn get_events_for_processing(&self, limit: u32, payload_types: &EventPayloadTypes) -> RepoResultV2<Vec<EventEntry>>;
 
     fn reset_stuck_events(&self) -> RepoResultV2<Vec<EventEntry>>;
 
     fn complete_event(&self, event_entry_id: EventEntryId) -> RepoResultV2<EventEntry>;
 
     fn fail_event(&self, event_entry_id: EventEntryId) -> RepoResultV2<EventEntry>;
+
+    /// Replaces the timings of the event with the ones of its last processing attempt
+    fn record_event_timings(&self, event_entry_id: EventEntryId, timings: EventTimings) -> RepoResultV2<()>;
 }
 
+/// Event store shared by all billing instances.
+///
+/// An instance takes events for processing by leasing them for `stuck_threshold_sec` seconds
+/// under its `instance_id`. Only the lease holder can complete or fail an event, and events with
+/// expired leases are returned to the queue by `reset_stuck_events`.
 pub struct EventStoreRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
     pub db_conn: &'a T,
     pub max_processing_attempts: u32,
     pub stuck_threshold_sec: u32,
+    pub instance_id: String,
 }
 
 impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> EventStoreRepoImpl<'a, T> {
-    pub fn new(db_conn: &'a T, max_processing_attempts: u32, stuck_threshold_sec: u32) -> Self {
+    pub fn new(db_conn: &'a T, max_processing_attempts: u32, stuck_threshold_sec: u32, instance_id: String) -> Self {
         Self {
             db_conn,
             max_processing_attempts,
             stuck_threshold_sec,
+            instance_id,
+        }
+    }
+
+    fn check_lease(&self, event_entry_id: EventEntryId, locked_by: Option<String>) -> RepoResultV2<()> {
+        match locked_by {
+            Some(ref locked_by) if *locked_by != self.instance_id => {
+                let e = format_err!(
+                    "Event entry with ID: {} is leased by instance \"{}\", not by \"{}\"",
+                    event_entry_id,
+                    locked_by,
+                    self.instance_id,
+                );
+                Err(ectx!(err e, ErrorKind::Internal))
+            }
+            _ => Ok(()),
         }
     }
 }
@@ -84,24 +109,36 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             .map_err(ectx!(ErrorSource::SerdeJson, ErrorKind::Internal => raw_event_entry))
     }
 
-    fn get_events_for_processing(&self, limit: u32) -> RepoResultV2<Vec<EventEntry>> {
-        trace!("Getting events for processing (limit: {})", limit);
+    fn get_events_for_processing(&self, limit: u32, payload_types: &EventPayloadTypes) -> RepoResultV2<Vec<EventEntry>> {
+        trace!(
+            "Getting events for processing (limit: {}, payload types: {:?})",
+            limit,
+            payload_types
+        );
 
         let now = Utc::now().naive_utc();
+        let lease_expires_at = now + Duration::seconds(self.stuck_threshold_sec as i64);
+        let (payload_types, included) = match payload_types {
+            EventPayloadTypes::Only(payload_types) => (payload_types.clone(), true),
+            EventPayloadTypes::AllExcept(payload_types) => (payload_types.clone(), false),
+        };
 
+        // Rows locked by a concurrent poller are skipped, so every event is leased by a single instance
         let command = sql_query(
             "
             UPDATE event_store
             SET
                 attempt_count = attempt_count + 1,
                 status = $1,
-                status_updated_at = $2
+                status_updated_at = $2,
+                locked_by = $3,
+                lease_expires_at = $4
             WHERE id IN (
                 SELECT id
                 FROM event_store
-                WHERE status = $3 AND (scheduled_on is null OR scheduled_on <= $4)
+                WHERE status = $5 AND (scheduled_on is null OR scheduled_on <= $6) AND (payload_type = ANY($8)) = $9
                 ORDER BY id
-                LIMIT $5
+                LIMIT $7
                 FOR UPDATE SKIP LOCKED
             )
             RETURNING *
@@ -109,9 +146,13 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         )
         .bind::<sql_types::VarChar, _>(EventStatus::InProgress.to_string())
         .bind::<sql_types::Timestamp, _>(now)
+        .bind::<sql_types::Text, _>(self.instance_id.clone())
+        .bind::<sql_types::Timestamp, _>(lease_expires_at)
         .bind::<sql_types::VarChar, _>(EventStatus::Pending.to_string())
         .bind::<sql_types::Timestamp, _>(now)
-        .bind::<sql_types::BigInt, _>(limit as i64);
+        .bind::<sql_types::BigInt, _>(limit as i64)
+        .bind::<sql_types::Array<sql_types::Text>, _>(payload_types)
+        .bind::<sql_types::Bool, _>(included);
 
         let raw_event_entries = command.get_results::<RawEventEntry>(self.db_conn).map_err(|e| {
             let error_kind = ErrorKind::from(&e);
@@ -141,16 +182,19 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
 
         let now = chrono::Utc::now().naive_utc();
 
+        // Events taken before leases were introduced have no `lease_expires_at` and fall back to `status_updated_at`
         let command = sql_query(
             "
             UPDATE event_store
             SET
                 status = CASE WHEN attempt_count >= $1 THEN $2 ELSE $3 END,
-                status_updated_at = $4
+                status_updated_at = $4,
+                locked_by = NULL,
+                lease_expires_at = NULL
             WHERE id IN (
                 SELECT id
                 FROM event_store
-                WHERE status = $5 AND status_updated_at + $6 < $7
+                WHERE status = $5 AND COALESCE(lease_expires_at, status_updated_at + $6) < $7
                 FOR UPDATE SKIP LOCKED
             )
             RETURNING *
@@ -182,10 +226,11 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         trace!("Completing an event with ID: {}", event_entry_id);
 
         self.db_conn.transaction(|| {
-            let event_status = EventStore::event_store
+            let (event_status, locked_by) = EventStore::event_store
                 .filter(EventStore::id.eq(event_entry_id))
-                .select(EventStore::status)
-                .get_result::<String>(self.db_conn)
+                .select((EventStore::status, EventStore::locked_by))
+                .for_update()
+                .get_result::<(String, Option<String>)>(self.db_conn)
                 .map_err(|e| {
                     let error_kind = ErrorKind::from(&e);
                     ectx!(try err e, ErrorSource::Diesel, error_kind)
@@ -193,6 +238,8 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
 
             let event_status = EventStatus::from_str(event_status.as_str()).map_err(|_| ErrorKind::Internal)?;
 
+            self.check_lease(event_entry_id, locked_by)?;
+
             if event_status != EventStatus::InProgress {
                 let e = format_err!(
                     "Cannot change status from \"{}\" to \"{}\" for event entry with ID: {}",
@@ -208,6 +255,8 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
                 .set((
                     EventStore::status.eq(&EventStatus::Completed.to_string()),
                     EventStore::status_updated_at.eq(chrono::Utc::now().naive_utc()),
+                    EventStore::locked_by.eq(None::<String>),
+                    EventStore::lease_expires_at.eq(None::<NaiveDateTime>),
                 ))
                 .get_result::<RawEventEntry>(self.db_conn)
                 .map_err(|e| {
@@ -224,10 +273,11 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         trace!("Failing an event with ID: {}", event_entry_id);
 
         self.db_conn.transaction(|| {
-            let (event_status, attempt_count) = EventStore::event_store
+            let (event_status, attempt_count, locked_by) = EventStore::event_store
                 .filter(EventStore::id.eq(event_entry_id))
-                .select((EventStore::status, EventStore::attempt_count))
-                .get_result::<(String, i32)>(self.db_conn)
+                .select((EventStore::status, EventStore::attempt_count, EventStore::locked_by))
+                .for_update()
+                .get_result::<(String, i32, Option<String>)>(self.db_conn)
                 .map_err(|e| {
                     let error_kind = ErrorKind::from(&e);
                     ectx!(try err e, ErrorSource::Diesel, error_kind)
@@ -235,6 +285,8 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
 
             let event_status = EventStatus::from_str(event_status.as_str()).map_err(|_| ErrorKind::Internal)?;
 
+            self.check_lease(event_entry_id, locked_by)?;
+
             let new_event_status = if attempt_count >= self.max_processing_attempts as i32 {
                 EventStatus::Failed
             } else {
@@ -256,6 +308,8 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
                 .set((
                     EventStore::status.eq(&new_event_status.to_string()),
                     EventStore::status_updated_at.eq(chrono::Utc::now().naive_utc()),
+                    EventStore::locked_by.eq(None::<String>),
+                    EventStore::lease_expires_at.eq(None::<NaiveDateTime>),
                 ))
                 .get_result::<RawEventEntry>(self.db_conn)
                 .map_err(|e| {
@@ -267,4 +321,20 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
                 .map_err(ectx!(ErrorSource::SerdeJson, ErrorKind::Internal => raw_event_entry))
         })
     }
+
+    fn record_event_timings(&self, event_entry_id: EventEntryId, timings: EventTimings) -> RepoResultV2<()> {
+        trace!("Recording timings of an event with ID: {}: {}", event_entry_id, timings);
+
+        let timings = serde_json::to_value(&timings).map_err(ectx!(try ErrorSource::SerdeJson, ErrorKind::Internal => timings))?;
+
+        diesel::update(EventStore::event_store)
+            .filter(EventStore::id.eq(event_entry_id))
+            .set(EventStore::timings.eq(Some(timings)))
+            .execute(self.db_conn)
+            .map(|_| ())
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
 }
@@ -0,0 +1,121 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Numeric, VarChar};
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::{ExchangeRateSlippageMetric, NewOrderRateSlippage, OrderRateSlippage};
+use repos::legacy_acl::*;
+
+use schema::exchange_rate_slippage_metrics::dsl as ExchangeRateSlippageMetricsDsl;
+use schema::order_rate_slippages::dsl as OrderRateSlippagesDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type ExchangeRateSlippagesRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, OrderRateSlippage>>;
+
+pub struct ExchangeRateSlippagesRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: ExchangeRateSlippagesRepoAcl,
+}
+
+pub trait ExchangeRateSlippagesRepo {
+    /// Records the slippage of an order and adds it to the metrics of its currency pair.
+    /// Returns None if the slippage of the order has already been recorded
+    fn record(&self, payload: NewOrderRateSlippage) -> RepoResultV2<Option<OrderRateSlippage>>;
+    /// Metrics of all currency pairs ordered by the seller and then the buyer currency
+    fn get_metrics(&self) -> RepoResultV2<Vec<ExchangeRateSlippageMetric>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> ExchangeRateSlippagesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: ExchangeRateSlippagesRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> ExchangeRateSlippagesRepo
+    for ExchangeRateSlippagesRepoImpl<'a, T>
+{
+    fn record(&self, payload: NewOrderRateSlippage) -> RepoResultV2<Option<OrderRateSlippage>> {
+        debug!("record exchange rate slippage {:?}.", payload);
+        acl::check(&*self.acl, Resource::ExchangeRateSlippage, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let slippage = diesel::insert_into(OrderRateSlippagesDsl::order_rate_slippages)
+            .values(&payload)
+            .on_conflict(OrderRateSlippagesDsl::order_id)
+            .do_nothing()
+            .get_result::<OrderRateSlippage>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        let slippage = match slippage {
+            None => return Ok(None),
+            Some(slippage) => slippage,
+        };
+
+        let command = sql_query(
+            "
+            INSERT INTO exchange_rate_slippage_metrics AS metrics
+                (seller_currency, buyer_currency, orders_count, refreshed_orders_count, initial_price_total, final_price_total)
+            VALUES ($1, $2, 1, $3, $4, $5)
+            ON CONFLICT (seller_currency, buyer_currency) DO UPDATE SET
+                orders_count = metrics.orders_count + 1,
+                refreshed_orders_count = metrics.refreshed_orders_count + EXCLUDED.refreshed_orders_count,
+                initial_price_total = metrics.initial_price_total + EXCLUDED.initial_price_total,
+                final_price_total = metrics.final_price_total + EXCLUDED.final_price_total
+        ",
+        )
+        .bind::<VarChar, _>(slippage.seller_currency)
+        .bind::<VarChar, _>(slippage.buyer_currency)
+        .bind::<BigInt, _>(if slippage.rate_refreshes > 0 { 1i64 } else { 0i64 })
+        .bind::<Numeric, _>(slippage.initial_price)
+        .bind::<Numeric, _>(slippage.final_price);
+
+        command.execute(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        Ok(Some(slippage))
+    }
+
+    fn get_metrics(&self) -> RepoResultV2<Vec<ExchangeRateSlippageMetric>> {
+        debug!("get exchange rate slippage metrics.");
+        acl::check(&*self.acl, Resource::ExchangeRateSlippage, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        ExchangeRateSlippageMetricsDsl::exchange_rate_slippage_metrics
+            .order_by((
+                ExchangeRateSlippageMetricsDsl::seller_currency.asc(),
+                ExchangeRateSlippageMetricsDsl::buyer_currency.asc(),
+            ))
+            .get_results::<ExchangeRateSlippageMetric>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, OrderRateSlippage>
+    for ExchangeRateSlippagesRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&OrderRateSlippage>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::upsert::excluded;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::{Feature, FeatureFlag, NewFeatureFlag};
+use repos::legacy_acl::*;
+
+use schema::feature_flags::dsl as FeatureFlagsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+/// Flags are read on hot paths, so every instance keeps them in memory for `ttl`.
+/// A change made on another instance is picked up once the copy expires
+pub struct FeatureFlagsCache {
+    ttl: Duration,
+    flags: RwLock<Option<(Instant, Vec<FeatureFlag>)>>,
+}
+
+impl FeatureFlagsCache {
+    pub fn new(ttl: Duration) -> Self {
+        FeatureFlagsCache {
+            ttl,
+            flags: RwLock::new(None),
+        }
+    }
+
+    fn get(&self, now: Instant) -> Option<Vec<FeatureFlag>> {
+        match self.flags.read() {
+            Ok(flags) => match *flags {
+                Some((cached_at, ref flags)) if now.duration_since(cached_at) < self.ttl => Some(flags.clone()),
+                _ => None,
+            },
+            Err(_) => None,
+        }
+    }
+
+    fn set(&self, flags: Vec<FeatureFlag>, now: Instant) {
+        if let Ok(mut cached) = self.flags.write() {
+            *cached = Some((now, flags));
+        }
+    }
+
+    fn invalidate(&self) {
+        if let Ok(mut cached) = self.flags.write() {
+            *cached = None;
+        }
+    }
+}
+
+pub type FeatureFlagsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, FeatureFlag>>;
+
+pub struct FeatureFlagsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: FeatureFlagsRepoAcl,
+    pub cache: Arc<FeatureFlagsCache>,
+}
+
+pub trait FeatureFlagsRepo {
+    /// Every stored flag, served from the cache while it is fresh
+    fn list(&self) -> RepoResultV2<Vec<FeatureFlag>>;
+    fn get(&self, feature: Feature) -> RepoResultV2<Option<FeatureFlag>>;
+    /// Creates the flag or replaces its rollout, dropping the flags cached by this instance
+    fn upsert(&self, payload: NewFeatureFlag) -> RepoResultV2<FeatureFlag>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> FeatureFlagsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: FeatureFlagsRepoAcl, cache: Arc<FeatureFlagsCache>) -> Self {
+        Self { db_conn, acl, cache }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> FeatureFlagsRepo
+    for FeatureFlagsRepoImpl<'a, T>
+{
+    fn list(&self) -> RepoResultV2<Vec<FeatureFlag>> {
+        acl::check(&*self.acl, Resource::FeatureFlag, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let now = Instant::now();
+        if let Some(flags) = self.cache.get(now) {
+            return Ok(flags);
+        }
+
+        debug!("list feature flags.");
+        let flags = FeatureFlagsDsl::feature_flags
+            .order(FeatureFlagsDsl::name)
+            .get_results::<FeatureFlag>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        self.cache.set(flags.clone(), now);
+        Ok(flags)
+    }
+
+    fn get(&self, feature: Feature) -> RepoResultV2<Option<FeatureFlag>> {
+        let flags = self.list()?;
+
+        Ok(flags.into_iter().find(|flag| flag.name == feature.as_str()))
+    }
+
+    fn upsert(&self, payload: NewFeatureFlag) -> RepoResultV2<FeatureFlag> {
+        debug!("upsert feature flag {:?}.", payload);
+        acl::check(&*self.acl, Resource::FeatureFlag, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(FeatureFlagsDsl::feature_flags)
+            .values(&payload)
+            .on_conflict(FeatureFlagsDsl::name)
+            .do_update()
+            .set((
+                FeatureFlagsDsl::enabled.eq(excluded(FeatureFlagsDsl::enabled)),
+                FeatureFlagsDsl::rollout_percentage.eq(excluded(FeatureFlagsDsl::rollout_percentage)),
+                FeatureFlagsDsl::store_ids.eq(excluded(FeatureFlagsDsl::store_ids)),
+            ));
+
+        let flag = command.get_result::<FeatureFlag>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        self.cache.invalidate();
+        Ok(flag)
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, FeatureFlag>
+    for FeatureFlagsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&FeatureFlag>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
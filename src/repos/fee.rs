@@ -1,3 +1,4 @@
+use chrono::NaiveDateTime;
 use diesel;
 use diesel::connection::AnsiTransactionManager;
 use diesel::pg::Pg;
@@ -12,7 +13,7 @@ use repos::legacy_acl::*;
 
 use models::authorization::*;
 use models::order_v2::OrderId;
-use models::{Fee, FeeId, NewFee, UpdateFee, UserRole};
+use models::{Fee, FeeId, FeeStatus, NewFee, UpdateFee, UserRole};
 
 use schema::fees::dsl as FeesDsl;
 use schema::orders::dsl as OrdersDsl;
@@ -36,6 +37,9 @@ pub enum SearchFee {
 pub struct SearchFeeParams {
     pub id: Option<FeeId>,
     pub order_ids: Option<Vec<OrderId>>,
+    pub created_from: Option<NaiveDateTime>,
+    pub created_to: Option<NaiveDateTime>,
+    pub status: Option<FeeStatus>,
 }
 
 pub struct FeeRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
@@ -208,12 +212,34 @@ impl SearchFeeParams {
             ..Default::default()
         }
     }
+
+    /// Fees created in the `[from, to)` interval
+    pub fn by_created_at(from: NaiveDateTime, to: NaiveDateTime) -> SearchFeeParams {
+        SearchFeeParams {
+            created_from: Some(from),
+            created_to: Some(to),
+            ..Default::default()
+        }
+    }
+
+    pub fn by_status(status: FeeStatus) -> SearchFeeParams {
+        SearchFeeParams {
+            status: Some(status),
+            ..Default::default()
+        }
+    }
 }
 
 fn into_expr(search: SearchFeeParams) -> Option<BoxedExpr> {
     let mut query: Option<BoxedExpr> = None;
 
-    let SearchFeeParams { id, order_ids } = search;
+    let SearchFeeParams {
+        id,
+        order_ids,
+        created_from,
+        created_to,
+        status,
+    } = search;
 
     if let Some(id_filter) = id {
         let new_condition = FeesDsl::id.eq(id_filter);
@@ -225,6 +251,21 @@ fn into_expr(search: SearchFeeParams) -> Option<BoxedExpr> {
         query = Some(and(query, Box::new(new_condition)));
     }
 
+    if let Some(created_from_filter) = created_from {
+        let new_condition = FeesDsl::created_at.ge(created_from_filter);
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
+    if let Some(created_to_filter) = created_to {
+        let new_condition = FeesDsl::created_at.lt(created_to_filter);
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
+    if let Some(status_filter) = status {
+        let new_condition = FeesDsl::status.eq(status_filter);
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
     query
 }
 
@@ -0,0 +1,97 @@
+use chrono::NaiveDateTime;
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::order_v2::OrderId;
+use models::{FeeAdjustment, NewFeeAdjustment};
+use repos::legacy_acl::*;
+
+use schema::fee_adjustments::dsl as FeeAdjustmentsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type FeeAdjustmentsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, FeeAdjustment>>;
+
+pub struct FeeAdjustmentsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: FeeAdjustmentsRepoAcl,
+}
+
+pub trait FeeAdjustmentsRepo {
+    fn create(&self, payload: NewFeeAdjustment) -> RepoResultV2<FeeAdjustment>;
+    fn search(&self, created_from: NaiveDateTime, created_to: NaiveDateTime) -> RepoResultV2<Vec<FeeAdjustment>>;
+    fn get_by_order_ids(&self, order_ids: &[OrderId]) -> RepoResultV2<Vec<FeeAdjustment>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> FeeAdjustmentsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: FeeAdjustmentsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> FeeAdjustmentsRepo
+    for FeeAdjustmentsRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewFeeAdjustment) -> RepoResultV2<FeeAdjustment> {
+        debug!("create fee adjustment of fee {} for order {}.", payload.fee_id, payload.order_id);
+        acl::check(&*self.acl, Resource::FeeAdjustment, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(FeeAdjustmentsDsl::fee_adjustments).values(&payload);
+
+        command.get_result::<FeeAdjustment>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn search(&self, created_from: NaiveDateTime, created_to: NaiveDateTime) -> RepoResultV2<Vec<FeeAdjustment>> {
+        debug!("search fee adjustments created from {} to {}.", created_from, created_to);
+        acl::check(&*self.acl, Resource::FeeAdjustment, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        FeeAdjustmentsDsl::fee_adjustments
+            .filter(FeeAdjustmentsDsl::created_at.ge(created_from))
+            .filter(FeeAdjustmentsDsl::created_at.lt(created_to))
+            .order_by(FeeAdjustmentsDsl::created_at.asc())
+            .get_results::<FeeAdjustment>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn get_by_order_ids(&self, order_ids: &[OrderId]) -> RepoResultV2<Vec<FeeAdjustment>> {
+        debug!("get fee adjustments of orders {:?}.", order_ids);
+        acl::check(&*self.acl, Resource::FeeAdjustment, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        FeeAdjustmentsDsl::fee_adjustments
+            .filter(FeeAdjustmentsDsl::order_id.eq_any(order_ids))
+            .order_by(FeeAdjustmentsDsl::created_at.asc())
+            .get_results::<FeeAdjustment>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, FeeAdjustment>
+    for FeeAdjustmentsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&FeeAdjustment>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use chrono::NaiveDateTime;
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+use stq_types::StoreId;
+
+use models::authorization::*;
+use models::fee::FeeId;
+use models::{
+    AccountId, FeeCryptoPayment, FeeCryptoPaymentId, FeeCryptoPaymentStatus, FeeCryptoPaymentTransaction, NewFeeCryptoPayment,
+    NewFeeCryptoPaymentTransaction, UserRole,
+};
+use repos::legacy_acl::*;
+
+use schema::fee_crypto_payment_transactions::dsl as FeeCryptoPaymentTransactionsDsl;
+use schema::fee_crypto_payments::dsl as FeeCryptoPaymentsDsl;
+use schema::fees::dsl as FeesDsl;
+use schema::orders::dsl as OrdersDsl;
+use schema::roles::dsl as UserRolesDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type FeeCryptoPaymentsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, FeeCryptoPayment>>;
+
+pub struct FeeCryptoPaymentsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: FeeCryptoPaymentsRepoAcl,
+}
+
+pub trait FeeCryptoPaymentsRepo {
+    fn create(&self, payload: NewFeeCryptoPayment) -> RepoResultV2<FeeCryptoPayment>;
+    fn get(&self, id: FeeCryptoPaymentId) -> RepoResultV2<Option<FeeCryptoPayment>>;
+    /// The latest payment of the fee, whatever its status
+    fn get_latest_by_fee_id(&self, fee_id: FeeId) -> RepoResultV2<Option<FeeCryptoPayment>>;
+    /// Payment waiting for a transfer to the account, there is one at most
+    fn get_pending_by_account_id(&self, account_id: AccountId) -> RepoResultV2<Option<FeeCryptoPayment>>;
+    /// Records the transaction and adds its amount to the amount received.
+    /// A transaction credited before fails with a constraint violation
+    fn add_transaction(&self, payload: NewFeeCryptoPaymentTransaction) -> RepoResultV2<FeeCryptoPayment>;
+    fn list_transactions(&self, id: FeeCryptoPaymentId) -> RepoResultV2<Vec<FeeCryptoPaymentTransaction>>;
+    /// Sets the final status of a pending payment, the account is released at `closed_at`
+    fn close(&self, id: FeeCryptoPaymentId, status: FeeCryptoPaymentStatus, closed_at: NaiveDateTime) -> RepoResultV2<FeeCryptoPayment>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> FeeCryptoPaymentsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: FeeCryptoPaymentsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+
+    fn get_for_update(&self, id: FeeCryptoPaymentId) -> RepoResultV2<FeeCryptoPayment> {
+        let payment = FeeCryptoPaymentsDsl::fee_crypto_payments
+            .filter(FeeCryptoPaymentsDsl::id.eq(id))
+            .for_update()
+            .get_result::<FeeCryptoPayment>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        acl::check(&*self.acl, Resource::FeeCryptoPayment, Action::Write, self, Some(&payment)).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        Ok(payment)
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> FeeCryptoPaymentsRepo
+    for FeeCryptoPaymentsRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewFeeCryptoPayment) -> RepoResultV2<FeeCryptoPayment> {
+        debug!("create crypto payment of fee {}.", payload.fee_id);
+
+        let command = diesel::insert_into(FeeCryptoPaymentsDsl::fee_crypto_payments).values(&payload);
+
+        let payment = command.get_result::<FeeCryptoPayment>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        acl::check(&*self.acl, Resource::FeeCryptoPayment, Action::Write, self, Some(&payment)).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        Ok(payment)
+    }
+
+    fn get(&self, id: FeeCryptoPaymentId) -> RepoResultV2<Option<FeeCryptoPayment>> {
+        debug!("get fee crypto payment by id {}.", id);
+
+        let payment = FeeCryptoPaymentsDsl::fee_crypto_payments
+            .filter(FeeCryptoPaymentsDsl::id.eq(id))
+            .get_result::<FeeCryptoPayment>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        if let Some(ref payment) = payment {
+            acl::check(&*self.acl, Resource::FeeCryptoPayment, Action::Read, self, Some(payment))
+                .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(payment)
+    }
+
+    fn get_latest_by_fee_id(&self, fee_id: FeeId) -> RepoResultV2<Option<FeeCryptoPayment>> {
+        debug!("get latest crypto payment of fee {}.", fee_id);
+
+        let payment = FeeCryptoPaymentsDsl::fee_crypto_payments
+            .filter(FeeCryptoPaymentsDsl::fee_id.eq(fee_id))
+            .order(FeeCryptoPaymentsDsl::created_at.desc())
+            .first::<FeeCryptoPayment>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        if let Some(ref payment) = payment {
+            acl::check(&*self.acl, Resource::FeeCryptoPayment, Action::Read, self, Some(payment))
+                .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(payment)
+    }
+
+    fn get_pending_by_account_id(&self, account_id: AccountId) -> RepoResultV2<Option<FeeCryptoPayment>> {
+        debug!("get pending fee crypto payment of account {}.", account_id);
+
+        let payment = FeeCryptoPaymentsDsl::fee_crypto_payments
+            .filter(FeeCryptoPaymentsDsl::account_id.eq(account_id))
+            .filter(FeeCryptoPaymentsDsl::status.eq(FeeCryptoPaymentStatus::Pending))
+            .get_result::<FeeCryptoPayment>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        if let Some(ref payment) = payment {
+            acl::check(&*self.acl, Resource::FeeCryptoPayment, Action::Read, self, Some(payment))
+                .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(payment)
+    }
+
+    fn add_transaction(&self, payload: NewFeeCryptoPaymentTransaction) -> RepoResultV2<FeeCryptoPayment> {
+        debug!(
+            "add transaction {} of amount {} to fee crypto payment {}.",
+            payload.transaction_id, payload.amount, payload.fee_crypto_payment_id
+        );
+
+        let id = payload.fee_crypto_payment_id;
+        let payment = self.get_for_update(id)?;
+
+        let amount_received = payment.amount_received.checked_add(payload.amount).ok_or({
+            let e = format_err!(
+                "Overflow occurred when adding amounts. Previous amount received: {}, transaction amount: {}",
+                payment.amount_received,
+                payload.amount,
+            );
+            ectx!(try err e, ErrorKind::Internal)
+        })?;
+
+        self.db_conn
+            .transaction(move || {
+                diesel::insert_into(FeeCryptoPaymentTransactionsDsl::fee_crypto_payment_transactions)
+                    .values(&payload)
+                    .execute(self.db_conn)?;
+
+                diesel::update(FeeCryptoPaymentsDsl::fee_crypto_payments.filter(FeeCryptoPaymentsDsl::id.eq(id)))
+                    .set(FeeCryptoPaymentsDsl::amount_received.eq(amount_received))
+                    .get_result::<FeeCryptoPayment>(self.db_conn)
+            })
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn list_transactions(&self, id: FeeCryptoPaymentId) -> RepoResultV2<Vec<FeeCryptoPaymentTransaction>> {
+        debug!("list transactions of fee crypto payment {}.", id);
+
+        // transactions are visible to whoever may see the payment
+        if self.get(id)?.is_none() {
+            return Ok(vec![]);
+        }
+
+        FeeCryptoPaymentTransactionsDsl::fee_crypto_payment_transactions
+            .filter(FeeCryptoPaymentTransactionsDsl::fee_crypto_payment_id.eq(id))
+            .order(FeeCryptoPaymentTransactionsDsl::created_at.asc())
+            .get_results::<FeeCryptoPaymentTransaction>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn close(&self, id: FeeCryptoPaymentId, status: FeeCryptoPaymentStatus, closed_at: NaiveDateTime) -> RepoResultV2<FeeCryptoPayment> {
+        debug!("close fee crypto payment {} as {:?}.", id, status);
+
+        let payment = self.get_for_update(id)?;
+        if payment.status != FeeCryptoPaymentStatus::Pending {
+            let e = format_err!("Fee crypto payment {} is already closed as {:?}", id, payment.status);
+            return Err(ectx!(err e, ErrorKind::Internal));
+        }
+
+        diesel::update(FeeCryptoPaymentsDsl::fee_crypto_payments.filter(FeeCryptoPaymentsDsl::id.eq(id)))
+            .set((
+                FeeCryptoPaymentsDsl::status.eq(status),
+                FeeCryptoPaymentsDsl::closed_at.eq(Some(closed_at)),
+            ))
+            .get_result::<FeeCryptoPayment>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, FeeCryptoPayment>
+    for FeeCryptoPaymentsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, user_id: stq_types::UserId, scope: &Scope, obj: Option<&FeeCryptoPayment>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => {
+                if let Some(payment) = obj {
+                    let store_id = match FeesDsl::fees
+                        .filter(FeesDsl::id.eq(payment.fee_id))
+                        .inner_join(OrdersDsl::orders)
+                        .select(OrdersDsl::store_id)
+                        .get_result::<StoreId>(self.db_conn)
+                    {
+                        Ok(store_id) => store_id,
+                        Err(_) => return false,
+                    };
+
+                    UserRolesDsl::roles
+                        .filter(UserRolesDsl::user_id.eq(user_id))
+                        .get_results::<UserRole>(self.db_conn)
+                        .map_err(From::from)
+                        .map(|user_roles_arg| {
+                            user_roles_arg
+                                .iter()
+                                .any(|user_role_arg| user_role_arg.data.clone().map(|data| data == store_id.0).unwrap_or_default())
+                        })
+                        .unwrap_or_else(|_: FailureError| false)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
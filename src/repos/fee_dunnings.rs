@@ -0,0 +1,111 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{StoreId, UserId};
+
+use models::authorization::*;
+use models::{FeeDunning, FeeDunningStatus, NewFeeDunning, UpdateFeeDunning};
+use repos::legacy_acl::*;
+
+use schema::fee_dunnings::dsl as FeeDunningsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type FeeDunningsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, FeeDunning>>;
+
+pub struct FeeDunningsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: FeeDunningsRepoAcl,
+}
+
+pub trait FeeDunningsRepo {
+    fn create(&self, payload: NewFeeDunning) -> RepoResultV2<FeeDunning>;
+    fn get(&self, store_id: StoreId) -> RepoResultV2<Option<FeeDunning>>;
+    /// Dunnings of stores that still have unpaid fees, ordered by store
+    fn list_open(&self) -> RepoResultV2<Vec<FeeDunning>>;
+    fn update(&self, store_id: StoreId, payload: UpdateFeeDunning) -> RepoResultV2<FeeDunning>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> FeeDunningsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: FeeDunningsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> FeeDunningsRepo
+    for FeeDunningsRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewFeeDunning) -> RepoResultV2<FeeDunning> {
+        debug!("create fee dunning of store {}.", payload.store_id);
+        acl::check(&*self.acl, Resource::FeeDunning, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(FeeDunningsDsl::fee_dunnings).values(&payload);
+
+        command.get_result::<FeeDunning>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get(&self, store_id: StoreId) -> RepoResultV2<Option<FeeDunning>> {
+        debug!("get fee dunning of store {}.", store_id);
+        acl::check(&*self.acl, Resource::FeeDunning, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        FeeDunningsDsl::fee_dunnings
+            .filter(FeeDunningsDsl::store_id.eq(store_id))
+            .get_result::<FeeDunning>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn list_open(&self) -> RepoResultV2<Vec<FeeDunning>> {
+        debug!("list open fee dunnings.");
+        acl::check(&*self.acl, Resource::FeeDunning, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        FeeDunningsDsl::fee_dunnings
+            .filter(FeeDunningsDsl::status.eq(FeeDunningStatus::Open))
+            .order_by(FeeDunningsDsl::store_id.asc())
+            .get_results::<FeeDunning>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn update(&self, store_id: StoreId, payload: UpdateFeeDunning) -> RepoResultV2<FeeDunning> {
+        debug!("update fee dunning of store {}.", store_id);
+        acl::check(&*self.acl, Resource::FeeDunning, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let filter = FeeDunningsDsl::fee_dunnings.filter(FeeDunningsDsl::store_id.eq(store_id));
+
+        diesel::update(filter)
+            .set(&payload)
+            .get_result::<FeeDunning>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, FeeDunning>
+    for FeeDunningsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&FeeDunning>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
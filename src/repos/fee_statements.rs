@@ -0,0 +1,206 @@
+use std::collections::HashSet;
+
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::sql_types::Bool;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{StoreId, UserId};
+
+use models::authorization::*;
+use models::{FeeStatement, FeeStatementSearch, NewFeeStatement, UserRole};
+use repos::legacy_acl::*;
+
+use schema::fee_statements::dsl as FeeStatementsDsl;
+use schema::roles::dsl as UserRolesDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type FeeStatementsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, FeeStatementAccess>>;
+
+type BoxedExpr = Box<BoxableExpression<crate::schema::fee_statements::table, Pg, SqlType = Bool>>;
+
+pub struct FeeStatementAccess {
+    store_id: StoreId,
+}
+
+pub struct FeeStatementsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: FeeStatementsRepoAcl,
+}
+
+pub trait FeeStatementsRepo {
+    fn create(&self, payload: NewFeeStatement) -> RepoResultV2<FeeStatement>;
+    fn get(&self, search: FeeStatementSearch) -> RepoResultV2<Option<FeeStatement>>;
+    fn search(&self, search: FeeStatementSearch) -> RepoResultV2<Vec<FeeStatement>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> FeeStatementsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: FeeStatementsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> FeeStatementsRepo
+    for FeeStatementsRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewFeeStatement) -> RepoResultV2<FeeStatement> {
+        debug!("create fee statement {:?}.", payload);
+        acl::check(
+            &*self.acl,
+            Resource::FeeStatement,
+            Action::Write,
+            self,
+            Some(&FeeStatementAccess {
+                store_id: payload.store_id,
+            }),
+        )
+        .map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(FeeStatementsDsl::fee_statements).values(&payload);
+
+        command.get_result::<FeeStatement>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get(&self, search: FeeStatementSearch) -> RepoResultV2<Option<FeeStatement>> {
+        debug!("get fee statement {:?}.", search);
+
+        let query: Option<BoxedExpr> = into_expr(search);
+
+        let query = query.ok_or_else(|| {
+            let e = format_err!("fee statement search is empty");
+            ectx!(try err e, ErrorKind::Internal)
+        })?;
+
+        let fee_statement = crate::schema::fee_statements::table
+            .filter(query)
+            .get_result::<FeeStatement>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        if let Some(ref fee_statement) = fee_statement {
+            acl::check(
+                &*self.acl,
+                Resource::FeeStatement,
+                Action::Read,
+                self,
+                Some(&FeeStatementAccess {
+                    store_id: fee_statement.store_id,
+                }),
+            )
+            .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(fee_statement)
+    }
+
+    fn search(&self, search: FeeStatementSearch) -> RepoResultV2<Vec<FeeStatement>> {
+        debug!("search fee statements {:?}.", search);
+
+        let query: BoxedExpr = into_expr(search).unwrap_or(Box::new(true.into_sql::<Bool>()));
+
+        let fee_statements = crate::schema::fee_statements::table
+            .filter(query)
+            .order_by(FeeStatementsDsl::period_start.desc())
+            .get_results::<FeeStatement>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        let store_ids: HashSet<StoreId> = fee_statements.iter().map(|s| s.store_id).collect();
+
+        for store_id in store_ids {
+            acl::check(
+                &*self.acl,
+                Resource::FeeStatement,
+                Action::Read,
+                self,
+                Some(&FeeStatementAccess { store_id }),
+            )
+            .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(fee_statements)
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, FeeStatementAccess>
+    for FeeStatementsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, user_id: UserId, scope: &Scope, obj: Option<&FeeStatementAccess>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => {
+                if let Some(FeeStatementAccess { store_id }) = obj {
+                    UserRolesDsl::roles
+                        .filter(UserRolesDsl::user_id.eq(user_id))
+                        .get_results::<UserRole>(self.db_conn)
+                        .map_err(From::from)
+                        .map(|user_roles_arg| {
+                            user_roles_arg
+                                .iter()
+                                .any(|user_role_arg| user_role_arg.data.clone().map(|data| data == store_id.0).unwrap_or_default())
+                        })
+                        .unwrap_or_else(|_: FailureError| false)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+fn into_expr(search: FeeStatementSearch) -> Option<BoxedExpr> {
+    let mut query: Option<BoxedExpr> = None;
+
+    let FeeStatementSearch {
+        id,
+        store_id,
+        currency,
+        period_start,
+    } = search;
+
+    if let Some(id_filter) = id {
+        let new_condition = FeeStatementsDsl::id.eq(id_filter);
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
+    if let Some(store_id_filter) = store_id {
+        let new_condition = FeeStatementsDsl::store_id.eq(store_id_filter);
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
+    if let Some(currency_filter) = currency {
+        let new_condition = FeeStatementsDsl::currency.eq(currency_filter);
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
+    if let Some(period_start_filter) = period_start {
+        let new_condition = FeeStatementsDsl::period_start.eq(period_start_filter);
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
+    query
+}
+
+fn and(old_condition: Option<BoxedExpr>, new_condition: BoxedExpr) -> BoxedExpr {
+    if let Some(old_condition) = old_condition {
+        Box::new(old_condition.and(new_condition))
+    } else {
+        new_condition
+    }
+}
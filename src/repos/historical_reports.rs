@@ -0,0 +1,245 @@
+use chrono::NaiveDateTime;
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::sql_query;
+use diesel::sql_types::{Text, Timestamp, VarChar};
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_static_resources::OrderState;
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::{
+    EventStatus, FeeCryptoPaymentStatus, FeeStatus, HistoricalReport, HistoricalStateTotal, NewHistoricalReport, PaymentIntentStatus,
+};
+use repos::legacy_acl::*;
+
+use schema::historical_reports::dsl as HistoricalReportsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type HistoricalReportsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, HistoricalReport>>;
+
+pub struct HistoricalReportsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: HistoricalReportsRepoAcl,
+}
+
+pub trait HistoricalReportsRepo {
+    /// Invoices v2 created until `as_of` by their state at that time and the seller currency of their orders
+    fn get_invoice_states(&self, as_of: NaiveDateTime) -> RepoResultV2<Vec<HistoricalStateTotal>>;
+    /// Fees created until `as_of` by their state at that time and currency
+    fn get_fee_states(&self, as_of: NaiveDateTime) -> RepoResultV2<Vec<HistoricalStateTotal>>;
+    /// Payouts initiated until `as_of` by their state at that time and currency, net amounts
+    fn get_payout_states(&self, as_of: NaiveDateTime) -> RepoResultV2<Vec<HistoricalStateTotal>>;
+    /// Stores the report unless one as of the same time exists, the first report is kept
+    fn create(&self, payload: NewHistoricalReport) -> RepoResultV2<HistoricalReport>;
+    fn get(&self, as_of: NaiveDateTime) -> RepoResultV2<Option<HistoricalReport>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> HistoricalReportsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: HistoricalReportsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> HistoricalReportsRepo
+    for HistoricalReportsRepoImpl<'a, T>
+{
+    fn get_invoice_states(&self, as_of: NaiveDateTime) -> RepoResultV2<Vec<HistoricalStateTotal>> {
+        debug!("Getting invoice states as of {}", as_of);
+        acl::check(&*self.acl, Resource::HistoricalReport, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        // An invoice was paid at `paid_at`, the time its snapshot was taken. It expired when its `PaymentExpired` event
+        // was processed, the handler skips paid and cancelled invoices. Cancellation is not recorded,
+        // so a cancelled invoice counts as cancelled since its last update
+        let command = sql_query(
+            "
+            WITH invoice_states AS (
+                SELECT
+                    i.id,
+                    CASE
+                        WHEN i.paid_at <= $1 THEN 'paid'
+                        WHEN i.status = $2 AND i.updated_at <= $1 THEN 'cancelled'
+                        WHEN EXISTS (
+                            SELECT 1
+                            FROM event_store e
+                            WHERE
+                                e.payload_type = 'PaymentExpired'
+                                AND e.event->'payload'->'PaymentExpired'->>'invoice_id' = i.id::text
+                                AND e.status = $3
+                                AND e.status_updated_at <= $1
+                        ) THEN 'expired'
+                        ELSE 'awaiting_payment'
+                    END AS state
+                FROM invoices_v2 i
+                WHERE i.created_at <= $1
+            )
+            SELECT
+                s.state,
+                o.seller_currency AS currency,
+                COUNT(DISTINCT s.id) AS count,
+                SUM(o.total_amount) AS amount
+            FROM invoice_states s
+            JOIN orders o ON o.invoice_id = s.id
+            GROUP BY s.state, o.seller_currency
+            ORDER BY s.state, o.seller_currency
+        ",
+        )
+        .bind::<Timestamp, _>(as_of)
+        .bind::<Text, _>(OrderState::Cancelled)
+        .bind::<Text, _>(EventStatus::Completed.to_string());
+
+        command.get_results::<HistoricalStateTotal>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get_fee_states(&self, as_of: NaiveDateTime) -> RepoResultV2<Vec<HistoricalStateTotal>> {
+        debug!("Getting fee states as of {}", as_of);
+        acl::check(&*self.acl, Resource::HistoricalReport, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        // A fee was paid when the first payment intent charging it succeeded or its crypto payment closed,
+        // and written off when the fees of its store were. Fees without either time fall back to their last update
+        let command = sql_query(
+            "
+            WITH fee_states AS (
+                SELECT
+                    f.currency,
+                    f.amount,
+                    CASE
+                        WHEN f.status = $2 AND COALESCE(
+                            LEAST(
+                                (
+                                    SELECT MIN(h.created_at)
+                                    FROM payment_intents_fees pif
+                                    JOIN payment_intent_history h ON h.payment_intent_id = pif.payment_intent_id
+                                    WHERE pif.fee_id = f.id AND h.status = $3
+                                ),
+                                (
+                                    SELECT MIN(COALESCE(c.closed_at, c.updated_at))
+                                    FROM fee_crypto_payments c
+                                    WHERE c.fee_id = f.id AND c.status = $4
+                                )
+                            ),
+                            f.updated_at
+                        ) <= $1 THEN 'paid'
+                        WHEN f.status = $5 AND COALESCE(
+                            (
+                                SELECT so.fees_written_off_at
+                                FROM orders o
+                                JOIN store_offboardings so ON so.store_id = o.store_id
+                                WHERE o.id = f.order_id
+                            ),
+                            f.updated_at
+                        ) <= $1 THEN 'written_off'
+                        ELSE 'outstanding'
+                    END AS state
+                FROM fees f
+                WHERE f.created_at <= $1
+            )
+            SELECT state, currency, COUNT(*) AS count, SUM(amount) AS amount
+            FROM fee_states
+            GROUP BY state, currency
+            ORDER BY state, currency
+        ",
+        )
+        .bind::<Timestamp, _>(as_of)
+        .bind::<VarChar, _>(FeeStatus::Paid)
+        .bind::<VarChar, _>(PaymentIntentStatus::Succeeded)
+        .bind::<VarChar, _>(FeeCryptoPaymentStatus::Paid)
+        .bind::<VarChar, _>(FeeStatus::WrittenOff);
+
+        command.get_results::<HistoricalStateTotal>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get_payout_states(&self, as_of: NaiveDateTime) -> RepoResultV2<Vec<HistoricalStateTotal>> {
+        debug!("Getting payout states as of {}", as_of);
+        acl::check(&*self.acl, Resource::HistoricalReport, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = sql_query(
+            "
+            SELECT
+                CASE WHEN completed_at <= $1 THEN 'completed' ELSE 'in_progress' END AS state,
+                currency,
+                COUNT(*) AS count,
+                SUM(net_amount) AS amount
+            FROM payouts
+            WHERE initiated_at <= $1
+            GROUP BY 1, currency
+            ORDER BY 1, currency
+        ",
+        )
+        .bind::<Timestamp, _>(as_of);
+
+        command.get_results::<HistoricalStateTotal>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn create(&self, payload: NewHistoricalReport) -> RepoResultV2<HistoricalReport> {
+        debug!("create historical report as of {}.", payload.as_of);
+        acl::check(&*self.acl, Resource::HistoricalReport, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let as_of = payload.as_of;
+        diesel::insert_into(HistoricalReportsDsl::historical_reports)
+            .values(&payload)
+            .on_conflict_do_nothing()
+            .execute(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        HistoricalReportsDsl::historical_reports
+            .filter(HistoricalReportsDsl::as_of.eq(as_of))
+            .get_result::<HistoricalReport>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn get(&self, as_of: NaiveDateTime) -> RepoResultV2<Option<HistoricalReport>> {
+        debug!("get historical report as of {}.", as_of);
+
+        let historical_report = HistoricalReportsDsl::historical_reports
+            .filter(HistoricalReportsDsl::as_of.eq(as_of))
+            .get_result::<HistoricalReport>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        if let Some(ref historical_report) = historical_report {
+            acl::check(&*self.acl, Resource::HistoricalReport, Action::Read, self, Some(historical_report))
+                .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(historical_report)
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, HistoricalReport>
+    for HistoricalReportsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&HistoricalReport>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -10,7 +10,7 @@ use diesel::Connection;
 use failure::Error as FailureError;
 use failure::Fail;
 
-use stq_types::StoreId;
+use stq_types::{InternationalBillingId, StoreId, SwiftId};
 
 use models::authorization::*;
 use models::{
@@ -22,9 +22,13 @@ use schema::international_billing_info::dsl as InternationalBillingInfoDsl;
 use schema::roles::dsl as UserRolesDsl;
 
 use super::acl;
+use super::encryption::{FieldCipher, ReencryptionBatch};
 use super::error::*;
 use super::types::RepoResultV2;
 
+const ACCOUNT_COLUMN: &'static str = "international_billing_info.account";
+const SWIFT_COLUMN: &'static str = "international_billing_info.swift";
+
 type InternationalBillingInfoRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, InternationalBillingInfoAccess>>;
 
 type BoxedExpr = Box<BoxableExpression<crate::schema::international_billing_info::table, Pg, SqlType = Bool>>;
@@ -32,6 +36,7 @@ type BoxedExpr = Box<BoxableExpression<crate::schema::international_billing_info
 pub struct InternationalBillingInfoRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
     pub db_conn: &'a T,
     pub acl: InternationalBillingInfoRepoAcl,
+    pub cipher: FieldCipher,
 }
 
 pub struct InternationalBillingInfoAccess {
@@ -48,11 +53,34 @@ pub trait InternationalBillingInfoRepo {
         payload: UpdateInternationalBillingInfo,
     ) -> RepoResultV2<InternationalBillingInfo>;
     fn delete(&self, search_params: InternationalBillingInfoSearch) -> RepoResultV2<Option<InternationalBillingInfo>>;
+    /// Seals the account details of up to `limit` entries following `after_id` with the current encryption key
+    fn reencrypt(&self, after_id: Option<InternationalBillingId>, limit: i64) -> RepoResultV2<ReencryptionBatch<InternationalBillingId>>;
 }
 
 impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> InternationalBillingInfoRepoImpl<'a, T> {
-    pub fn new(db_conn: &'a T, acl: InternationalBillingInfoRepoAcl) -> Self {
-        Self { db_conn, acl }
+    pub fn new(db_conn: &'a T, acl: InternationalBillingInfoRepoAcl, cipher: FieldCipher) -> Self {
+        Self { db_conn, acl, cipher }
+    }
+
+    fn encrypt(&self, column: &str, value: &str) -> RepoResultV2<String> {
+        self.cipher
+            .encrypt(column, value)
+            .map_err(ectx!(ErrorSource::Encryption, ErrorKind::Internal => column))
+    }
+
+    fn decrypt(&self, column: &str, value: &str) -> RepoResultV2<String> {
+        self.cipher
+            .decrypt(column, value)
+            .map_err(ectx!(ErrorSource::Encryption, ErrorKind::Internal => column))
+    }
+
+    /// Decrypts the account details, the controller masks them for the users that may not see them in full
+    fn reveal(&self, billing_info: InternationalBillingInfo) -> RepoResultV2<InternationalBillingInfo> {
+        Ok(InternationalBillingInfo {
+            account: self.decrypt(ACCOUNT_COLUMN, &billing_info.account)?,
+            swift: SwiftId(self.decrypt(SWIFT_COLUMN, &billing_info.swift.0)?),
+            ..billing_info
+        })
     }
 }
 
@@ -60,7 +88,10 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
     for InternationalBillingInfoRepoImpl<'a, T>
 {
     fn create(&self, new_international_billing_info: NewInternationalBillingInfo) -> RepoResultV2<InternationalBillingInfo> {
-        debug!("create international billing info {:?}.", new_international_billing_info);
+        debug!(
+            "create international billing info for store {}.",
+            new_international_billing_info.store_id
+        );
         acl::check(
             &*self.acl,
             Resource::BillingInfo,
@@ -72,12 +103,20 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         )
         .map_err(ectx!(try ErrorKind::Forbidden))?;
 
+        let new_international_billing_info = NewInternationalBillingInfo {
+            account: self.encrypt(ACCOUNT_COLUMN, &new_international_billing_info.account)?,
+            swift: SwiftId(self.encrypt(SWIFT_COLUMN, &new_international_billing_info.swift.0)?),
+            ..new_international_billing_info
+        };
+
         let command = diesel::insert_into(InternationalBillingInfoDsl::international_billing_info).values(&new_international_billing_info);
 
-        command.get_result::<InternationalBillingInfo>(self.db_conn).map_err(|e| {
+        let created_entry = command.get_result::<InternationalBillingInfo>(self.db_conn).map_err(|e| {
             let error_kind = ErrorKind::from(&e);
-            ectx!(err e, ErrorSource::Diesel, error_kind)
-        })
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        self.reveal(created_entry)
     }
 
     fn get(&self, search_params: InternationalBillingInfoSearch) -> RepoResultV2<Option<InternationalBillingInfo>> {
@@ -109,7 +148,11 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             };
             acl::check(&*self.acl, Resource::BillingInfo, Action::Read, self, Some(&access)).map_err(ectx!(try ErrorKind::Forbidden))?;
         }
-        Ok(billing_info)
+
+        match billing_info {
+            Some(billing_info) => self.reveal(billing_info).map(Some),
+            None => Ok(None),
+        }
     }
 
     fn search(&self, search_params: InternationalBillingInfoSearch) -> RepoResultV2<Vec<InternationalBillingInfo>> {
@@ -136,7 +179,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             acl::check(&*self.acl, Resource::BillingInfo, Action::Read, self, Some(&access)).map_err(ectx!(try ErrorKind::Forbidden))?;
         }
 
-        Ok(billing_info)
+        billing_info.into_iter().map(|billing_info| self.reveal(billing_info)).collect()
     }
 
     fn update(
@@ -157,11 +200,25 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             ectx!(try err e, ErrorKind::Internal)
         })?;
 
+        let payload = UpdateInternationalBillingInfo {
+            account: match payload.account {
+                Some(ref account) => Some(self.encrypt(ACCOUNT_COLUMN, account)?),
+                None => None,
+            },
+            swift: match payload.swift {
+                Some(ref swift) => Some(SwiftId(self.encrypt(SWIFT_COLUMN, &swift.0)?)),
+                None => None,
+            },
+            ..payload
+        };
+
         let query = diesel::update(crate::schema::international_billing_info::table.filter(query)).set(&payload);
-        query.get_result::<InternationalBillingInfo>(self.db_conn).map_err(|e| {
+        let updated_entry = query.get_result::<InternationalBillingInfo>(self.db_conn).map_err(|e| {
             let error_kind = ErrorKind::from(&e);
-            ectx!(err e, ErrorSource::Diesel, error_kind)
-        })
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        self.reveal(updated_entry)
     }
 
     fn delete(&self, search_params: InternationalBillingInfoSearch) -> RepoResultV2<Option<InternationalBillingInfo>> {
@@ -181,10 +238,71 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         })?;
 
         let query = diesel::delete(crate::schema::international_billing_info::table.filter(query));
-        query.get_result::<InternationalBillingInfo>(self.db_conn).optional().map_err(|e| {
+        let deleted_entry = query.get_result::<InternationalBillingInfo>(self.db_conn).optional().map_err(|e| {
             let error_kind = ErrorKind::from(&e);
-            ectx!(err e, ErrorSource::Diesel, error_kind)
-        })
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        match deleted_entry {
+            Some(deleted_entry) => self.reveal(deleted_entry).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn reencrypt(&self, after_id: Option<InternationalBillingId>, limit: i64) -> RepoResultV2<ReencryptionBatch<InternationalBillingId>> {
+        debug!("re-encrypt international billing info after {:?}.", after_id);
+        acl::check(&*self.acl, Resource::BillingInfo, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let mut query = InternationalBillingInfoDsl::international_billing_info
+            .order(InternationalBillingInfoDsl::id)
+            .limit(limit)
+            .into_boxed();
+        if let Some(after_id) = after_id {
+            query = query.filter(InternationalBillingInfoDsl::id.gt(after_id));
+        }
+
+        let billing_infos = query.get_results::<InternationalBillingInfo>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        let mut batch = ReencryptionBatch {
+            last_id: billing_infos.last().map(|billing_info| billing_info.id),
+            reencrypted: 0,
+        };
+
+        for billing_info in billing_infos {
+            if self.cipher.is_current(&billing_info.account) && self.cipher.is_current(&billing_info.swift.0) {
+                continue;
+            }
+
+            let account = self.decrypt(ACCOUNT_COLUMN, &billing_info.account)?;
+            let swift = self.decrypt(SWIFT_COLUMN, &billing_info.swift.0)?;
+            let payload = UpdateInternationalBillingInfo {
+                account: Some(self.encrypt(ACCOUNT_COLUMN, &account)?),
+                currency: None,
+                name: None,
+                bank: None,
+                swift: Some(SwiftId(self.encrypt(SWIFT_COLUMN, &swift)?)),
+                bank_address: None,
+                country: None,
+                city: None,
+                recipient_address: None,
+            };
+
+            diesel::update(
+                InternationalBillingInfoDsl::international_billing_info.filter(InternationalBillingInfoDsl::id.eq(billing_info.id)),
+            )
+            .set(&payload)
+            .execute(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+            batch.reencrypted += 1;
+        }
+
+        Ok(batch)
     }
 }
 
@@ -217,12 +335,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
 fn into_expr(search: InternationalBillingInfoSearch) -> Option<BoxedExpr> {
     let mut query: Option<BoxedExpr> = None;
 
-    let InternationalBillingInfoSearch {
-        id,
-        store_id,
-        swift,
-        store_ids,
-    } = search;
+    let InternationalBillingInfoSearch { id, store_id, store_ids } = search;
 
     if let Some(id_filter) = id {
         let new_condition = InternationalBillingInfoDsl::id.eq(id_filter);
@@ -239,11 +352,6 @@ fn into_expr(search: InternationalBillingInfoSearch) -> Option<BoxedExpr> {
         query = Some(and(query, Box::new(new_condition)));
     }
 
-    if let Some(swift_filter) = swift {
-        let new_condition = InternationalBillingInfoDsl::swift.eq(swift_filter);
-        query = Some(and(query, Box::new(new_condition)));
-    }
-
     query
 }
 
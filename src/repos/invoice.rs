@@ -1,4 +1,6 @@
 //! Invoices repo, presents CRUD operations with db for invoice
+use std::time::SystemTime;
+
 use diesel;
 use diesel::connection::AnsiTransactionManager;
 use diesel::pg::Pg;
@@ -8,6 +10,7 @@ use diesel::Connection;
 use failure::Error as FailureError;
 use failure::Fail;
 
+use stq_static_resources::OrderState;
 use stq_types::{InvoiceId, SagaId, UserId};
 
 use repos::legacy_acl::*;
@@ -40,6 +43,12 @@ pub trait InvoiceRepo {
 
     /// Deletes invoice
     fn delete(&self, id: SagaId) -> RepoResult<Invoice>;
+
+    /// Finds invoices awaiting payment whose price reservation ended before the time, oldest first
+    fn find_expired(&self, reserved_before: SystemTime, limit: i64) -> RepoResult<Vec<Invoice>>;
+
+    /// Sets the state of the invoice
+    fn set_state(&self, id: SagaId, state: OrderState) -> RepoResult<Invoice>;
 }
 
 impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> InvoiceRepoImpl<'a, T> {
@@ -126,6 +135,41 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             })
             .map_err(|e: FailureError| e.context(format!("Delete invoice id {} error occured", id_arg)).into())
     }
+
+    /// Finds invoices awaiting payment whose price reservation ended before the time
+    fn find_expired(&self, reserved_before: SystemTime, limit: i64) -> RepoResult<Vec<Invoice>> {
+        debug!("Find invoices reserved before {:?}.", reserved_before);
+        acl::check(&*self.acl, Resource::Invoice, Action::Read, self, None)
+            .and_then(|_| {
+                invoices
+                    .filter(state.eq(OrderState::PaymentAwaited))
+                    .filter(price_reserved.lt(reserved_before))
+                    .order_by(price_reserved.asc())
+                    .limit(limit)
+                    .get_results::<Invoice>(self.db_conn)
+                    .map_err(From::from)
+            })
+            .map_err(|e: FailureError| e.context("Find expired invoices error occured").into())
+    }
+
+    /// Sets the state of the invoice
+    fn set_state(&self, id_arg: SagaId, state_arg: OrderState) -> RepoResult<Invoice> {
+        debug!("Set invoice {} state {}.", id_arg, state_arg);
+        let filtered = invoices.filter(id.eq(id_arg));
+
+        diesel::update(filtered)
+            .set(state.eq(state_arg))
+            .get_result(self.db_conn)
+            .map_err(From::from)
+            .and_then(|invoice| {
+                acl::check(&*self.acl, Resource::Invoice, Action::Write, self, Some(&invoice))?;
+                Ok(invoice)
+            })
+            .map_err(|e: FailureError| {
+                e.context(format!("Set invoice id {} state {} error occured", id_arg, state_arg))
+                    .into()
+            })
+    }
 }
 
 impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, Invoice>
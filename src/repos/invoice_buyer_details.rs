@@ -0,0 +1,127 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+use serde_json;
+
+use stq_types::{Alpha3, UserId};
+
+use models::authorization::*;
+use models::invoice_v2::InvoiceId;
+use models::{BuyerBillingDetails, InvoiceBuyerDetails, NewInvoiceBuyerDetails, NewRawInvoiceBuyerDetails, RawInvoiceBuyerDetails};
+use repos::legacy_acl::*;
+
+use schema::invoice_buyer_details::dsl as InvoiceBuyerDetailsDsl;
+
+use super::acl;
+use super::encryption::FieldCipher;
+use super::error::*;
+use super::types::RepoResultV2;
+
+const DETAILS_COLUMN: &'static str = "invoice_buyer_details.details";
+
+pub type InvoiceBuyerDetailsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, InvoiceBuyerDetails>>;
+
+pub struct InvoiceBuyerDetailsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: InvoiceBuyerDetailsRepoAcl,
+    pub cipher: FieldCipher,
+}
+
+pub trait InvoiceBuyerDetailsRepo {
+    fn create(&self, payload: NewInvoiceBuyerDetails) -> RepoResultV2<InvoiceBuyerDetails>;
+    fn get(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<InvoiceBuyerDetails>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> InvoiceBuyerDetailsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: InvoiceBuyerDetailsRepoAcl, cipher: FieldCipher) -> Self {
+        Self { db_conn, acl, cipher }
+    }
+
+    fn seal(&self, details: &BuyerBillingDetails) -> RepoResultV2<String> {
+        let details = serde_json::to_string(details).map_err(ectx!(try ErrorSource::SerdeJson, ErrorKind::Internal))?;
+
+        self.cipher
+            .encrypt(DETAILS_COLUMN, &details)
+            .map_err(ectx!(ErrorSource::Encryption, ErrorKind::Internal => DETAILS_COLUMN))
+    }
+
+    fn reveal(&self, buyer_details: RawInvoiceBuyerDetails) -> RepoResultV2<InvoiceBuyerDetails> {
+        let details = self
+            .cipher
+            .decrypt(DETAILS_COLUMN, &buyer_details.details)
+            .map_err(ectx!(try ErrorSource::Encryption, ErrorKind::Internal => DETAILS_COLUMN))?;
+        let details =
+            serde_json::from_str::<BuyerBillingDetails>(&details).map_err(ectx!(try ErrorSource::SerdeJson, ErrorKind::Internal))?;
+
+        Ok(InvoiceBuyerDetails {
+            invoice_id: buyer_details.invoice_id,
+            buyer_user_id: buyer_details.buyer_user_id,
+            country: Alpha3(buyer_details.country),
+            details,
+            created_at: buyer_details.created_at,
+            updated_at: buyer_details.updated_at,
+        })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> InvoiceBuyerDetailsRepo
+    for InvoiceBuyerDetailsRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewInvoiceBuyerDetails) -> RepoResultV2<InvoiceBuyerDetails> {
+        debug!("create buyer details of invoice {}.", payload.invoice_id);
+        acl::check(&*self.acl, Resource::InvoiceBuyerDetails, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        // the country stays readable, it is the jurisdiction of the invoice in reports
+        let new_buyer_details = NewRawInvoiceBuyerDetails {
+            invoice_id: payload.invoice_id,
+            buyer_user_id: payload.buyer_user_id,
+            country: payload.details.country.0.clone(),
+            details: self.seal(&payload.details)?,
+        };
+
+        let created_buyer_details = diesel::insert_into(InvoiceBuyerDetailsDsl::invoice_buyer_details)
+            .values(&new_buyer_details)
+            .get_result::<RawInvoiceBuyerDetails>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        self.reveal(created_buyer_details)
+    }
+
+    fn get(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<InvoiceBuyerDetails>> {
+        debug!("get buyer details of invoice {}.", invoice_id);
+        acl::check(&*self.acl, Resource::InvoiceBuyerDetails, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let buyer_details = InvoiceBuyerDetailsDsl::invoice_buyer_details
+            .filter(InvoiceBuyerDetailsDsl::invoice_id.eq(invoice_id))
+            .get_result::<RawInvoiceBuyerDetails>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        match buyer_details {
+            Some(buyer_details) => self.reveal(buyer_details).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, InvoiceBuyerDetails>
+    for InvoiceBuyerDetailsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&InvoiceBuyerDetails>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
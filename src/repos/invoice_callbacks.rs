@@ -0,0 +1,128 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::invoice_v2::InvoiceId;
+use models::{InvoiceCallback, InvoiceCallbackDelivery, InvoiceCallbackId, NewInvoiceCallback, NewInvoiceCallbackDelivery};
+use repos::legacy_acl::*;
+
+use schema::invoice_callback_deliveries::dsl as InvoiceCallbackDeliveriesDsl;
+use schema::invoice_callbacks::dsl as InvoiceCallbacksDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type InvoiceCallbacksRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, InvoiceCallback>>;
+
+pub struct InvoiceCallbacksRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: InvoiceCallbacksRepoAcl,
+}
+
+pub trait InvoiceCallbacksRepo {
+    fn create(&self, payload: NewInvoiceCallback) -> RepoResultV2<InvoiceCallback>;
+    fn get(&self, id: InvoiceCallbackId) -> RepoResultV2<Option<InvoiceCallback>>;
+    fn get_by_invoice_id(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<InvoiceCallback>>;
+    fn add_delivery(&self, payload: NewInvoiceCallbackDelivery) -> RepoResultV2<InvoiceCallbackDelivery>;
+    /// Delivery attempts of the callback, the oldest first
+    fn list_deliveries(&self, id: InvoiceCallbackId) -> RepoResultV2<Vec<InvoiceCallbackDelivery>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> InvoiceCallbacksRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: InvoiceCallbacksRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> InvoiceCallbacksRepo
+    for InvoiceCallbacksRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewInvoiceCallback) -> RepoResultV2<InvoiceCallback> {
+        debug!("create callback of invoice {}.", payload.invoice_id);
+        acl::check(&*self.acl, Resource::InvoiceCallback, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(InvoiceCallbacksDsl::invoice_callbacks).values(&payload);
+
+        command.get_result::<InvoiceCallback>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get(&self, id: InvoiceCallbackId) -> RepoResultV2<Option<InvoiceCallback>> {
+        debug!("get invoice callback {}.", id);
+        acl::check(&*self.acl, Resource::InvoiceCallback, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        InvoiceCallbacksDsl::invoice_callbacks
+            .filter(InvoiceCallbacksDsl::id.eq(id))
+            .get_result::<InvoiceCallback>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn get_by_invoice_id(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<InvoiceCallback>> {
+        debug!("get callback of invoice {}.", invoice_id);
+        acl::check(&*self.acl, Resource::InvoiceCallback, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        InvoiceCallbacksDsl::invoice_callbacks
+            .filter(InvoiceCallbacksDsl::invoice_id.eq(invoice_id))
+            .get_result::<InvoiceCallback>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn add_delivery(&self, payload: NewInvoiceCallbackDelivery) -> RepoResultV2<InvoiceCallbackDelivery> {
+        debug!(
+            "add delivery of notification {} to invoice callback {}.",
+            payload.notification_id, payload.invoice_callback_id
+        );
+        acl::check(&*self.acl, Resource::InvoiceCallback, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(InvoiceCallbackDeliveriesDsl::invoice_callback_deliveries).values(&payload);
+
+        command.get_result::<InvoiceCallbackDelivery>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn list_deliveries(&self, id: InvoiceCallbackId) -> RepoResultV2<Vec<InvoiceCallbackDelivery>> {
+        debug!("list deliveries of invoice callback {}.", id);
+        acl::check(&*self.acl, Resource::InvoiceCallback, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        InvoiceCallbackDeliveriesDsl::invoice_callback_deliveries
+            .filter(InvoiceCallbackDeliveriesDsl::invoice_callback_id.eq(id))
+            .order_by(InvoiceCallbackDeliveriesDsl::id.asc())
+            .get_results::<InvoiceCallbackDelivery>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, InvoiceCallback>
+    for InvoiceCallbacksRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&InvoiceCallback>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
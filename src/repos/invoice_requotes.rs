@@ -0,0 +1,84 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::invoice_v2::InvoiceId;
+use models::{InvoiceRequote, NewInvoiceRequote};
+use repos::legacy_acl::*;
+
+use schema::invoice_requotes::dsl as InvoiceRequotesDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type InvoiceRequotesRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, InvoiceRequote>>;
+
+pub struct InvoiceRequotesRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: InvoiceRequotesRepoAcl,
+}
+
+pub trait InvoiceRequotesRepo {
+    fn create(&self, payload: NewInvoiceRequote) -> RepoResultV2<InvoiceRequote>;
+    /// Requote the expired invoice was replaced with, if any
+    fn get_by_original_invoice_id(&self, original_invoice_id: InvoiceId) -> RepoResultV2<Option<InvoiceRequote>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> InvoiceRequotesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: InvoiceRequotesRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> InvoiceRequotesRepo
+    for InvoiceRequotesRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewInvoiceRequote) -> RepoResultV2<InvoiceRequote> {
+        debug!("create invoice requote {:?}.", payload);
+        acl::check(&*self.acl, Resource::InvoiceRequote, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(InvoiceRequotesDsl::invoice_requotes).values(&payload);
+
+        command.get_result::<InvoiceRequote>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get_by_original_invoice_id(&self, original_invoice_id: InvoiceId) -> RepoResultV2<Option<InvoiceRequote>> {
+        debug!("get requote of invoice {}.", original_invoice_id);
+
+        let requote = InvoiceRequotesDsl::invoice_requotes
+            .filter(InvoiceRequotesDsl::original_invoice_id.eq(original_invoice_id))
+            .get_result::<InvoiceRequote>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        acl::check(&*self.acl, Resource::InvoiceRequote, Action::Read, self, requote.as_ref()).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        Ok(requote)
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, InvoiceRequote>
+    for InvoiceRequotesRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&InvoiceRequote>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::sql_query;
+use diesel::sql_types::{Integer, Nullable, Timestamp, VarChar};
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId as StqUserId;
+
+use models::authorization::*;
+use models::invoice_v2::InvoiceId;
+use models::{
+    BuyerRiskHistory, InvoiceRiskAssessment, InvoiceRiskReview, NewInvoiceRiskAssessment, RiskDecisionCount, RiskReviewStatus, UserId,
+};
+use repos::legacy_acl::*;
+
+use schema::invoice_risk_assessments::dsl as InvoiceRiskAssessmentsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type InvoiceRiskAssessmentsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, InvoiceRiskAssessment>>;
+
+pub struct InvoiceRiskAssessmentsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: InvoiceRiskAssessmentsRepoAcl,
+}
+
+pub trait InvoiceRiskAssessmentsRepo {
+    fn get(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<InvoiceRiskAssessment>>;
+    fn create(&self, payload: NewInvoiceRiskAssessment) -> RepoResultV2<InvoiceRiskAssessment>;
+    /// Invoices waiting for a review, the oldest first
+    fn list_pending_reviews(&self) -> RepoResultV2<Vec<InvoiceRiskAssessment>>;
+    /// Records the decision on an invoice held for a review, `None` is returned when it is not pending
+    fn review(&self, invoice_id: InvoiceId, review: InvoiceRiskReview) -> RepoResultV2<Option<InvoiceRiskAssessment>>;
+    /// Invoices of the buyer, and the assessments of the IP created after `ip_since`
+    fn get_buyer_history(&self, buyer_user_id: UserId, buyer_ip: Option<String>, ip_since: NaiveDateTime)
+        -> RepoResultV2<BuyerRiskHistory>;
+    fn count_by_decision(&self) -> RepoResultV2<Vec<RiskDecisionCount>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> InvoiceRiskAssessmentsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: InvoiceRiskAssessmentsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> InvoiceRiskAssessmentsRepo
+    for InvoiceRiskAssessmentsRepoImpl<'a, T>
+{
+    fn get(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<InvoiceRiskAssessment>> {
+        debug!("get risk assessment of invoice {}.", invoice_id);
+        acl::check(&*self.acl, Resource::InvoiceRiskAssessment, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        InvoiceRiskAssessmentsDsl::invoice_risk_assessments
+            .filter(InvoiceRiskAssessmentsDsl::invoice_id.eq(invoice_id))
+            .get_result::<InvoiceRiskAssessment>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind => invoice_id)
+            })
+    }
+
+    fn create(&self, payload: NewInvoiceRiskAssessment) -> RepoResultV2<InvoiceRiskAssessment> {
+        debug!("create risk assessment {:?}.", payload);
+        acl::check(&*self.acl, Resource::InvoiceRiskAssessment, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(InvoiceRiskAssessmentsDsl::invoice_risk_assessments).values(&payload);
+
+        command.get_result::<InvoiceRiskAssessment>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind => payload)
+        })
+    }
+
+    fn list_pending_reviews(&self) -> RepoResultV2<Vec<InvoiceRiskAssessment>> {
+        debug!("list invoices pending risk review.");
+        acl::check(&*self.acl, Resource::InvoiceRiskAssessment, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        InvoiceRiskAssessmentsDsl::invoice_risk_assessments
+            .filter(InvoiceRiskAssessmentsDsl::review_status.eq(RiskReviewStatus::Pending))
+            .order_by(InvoiceRiskAssessmentsDsl::created_at.asc())
+            .get_results::<InvoiceRiskAssessment>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn review(&self, invoice_id: InvoiceId, review: InvoiceRiskReview) -> RepoResultV2<Option<InvoiceRiskAssessment>> {
+        debug!("review risk assessment of invoice {}: {:?}.", invoice_id, review.status);
+        acl::check(&*self.acl, Resource::InvoiceRiskAssessment, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let filter = InvoiceRiskAssessmentsDsl::invoice_risk_assessments
+            .filter(InvoiceRiskAssessmentsDsl::invoice_id.eq(invoice_id))
+            .filter(InvoiceRiskAssessmentsDsl::review_status.eq(RiskReviewStatus::Pending));
+
+        diesel::update(filter)
+            .set((
+                InvoiceRiskAssessmentsDsl::review_status.eq(Some(review.status)),
+                InvoiceRiskAssessmentsDsl::reviewed_by.eq(Some(review.reviewed_by)),
+                InvoiceRiskAssessmentsDsl::reviewed_at.eq(Some(Utc::now().naive_utc())),
+            ))
+            .get_result::<InvoiceRiskAssessment>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind => invoice_id)
+            })
+    }
+
+    fn get_buyer_history(
+        &self,
+        buyer_user_id: UserId,
+        buyer_ip: Option<String>,
+        ip_since: NaiveDateTime,
+    ) -> RepoResultV2<BuyerRiskHistory> {
+        debug!("get risk history of buyer {} from ip {:?}.", buyer_user_id, buyer_ip);
+        acl::check(&*self.acl, Resource::InvoiceRiskAssessment, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = sql_query(
+            "
+            SELECT
+                (SELECT COUNT(*) FROM invoices_v2 WHERE buyer_user_id = $1) AS invoices_count,
+                (SELECT COUNT(*) FROM invoices_v2 WHERE buyer_user_id = $1 AND paid_at IS NOT NULL) AS paid_invoices_count,
+                (
+                    SELECT COUNT(*) FROM invoice_risk_assessments
+                    WHERE buyer_ip = $2 AND created_at > $3
+                ) AS ip_invoices_count
+            ",
+        )
+        .bind::<Integer, _>(buyer_user_id)
+        .bind::<Nullable<VarChar>, _>(buyer_ip)
+        .bind::<Timestamp, _>(ip_since);
+
+        command.get_result::<BuyerRiskHistory>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind => buyer_user_id)
+        })
+    }
+
+    fn count_by_decision(&self) -> RepoResultV2<Vec<RiskDecisionCount>> {
+        debug!("count risk assessments by decision.");
+        acl::check(&*self.acl, Resource::InvoiceRiskAssessment, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = sql_query(
+            "
+            SELECT decision, review_status, COUNT(*) AS count
+            FROM invoice_risk_assessments
+            GROUP BY decision, review_status
+            ORDER BY decision, review_status
+            ",
+        );
+
+        command.get_results::<RiskDecisionCount>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, InvoiceRiskAssessment>
+    for InvoiceRiskAssessmentsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: StqUserId, scope: &Scope, _obj: Option<&InvoiceRiskAssessment>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::invoice_v2::InvoiceId;
+use models::{InvoiceSnapshot, NewInvoiceSnapshot};
+use repos::legacy_acl::*;
+
+use schema::invoice_snapshots::dsl as InvoiceSnapshotsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type InvoiceSnapshotsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, InvoiceSnapshot>>;
+
+pub struct InvoiceSnapshotsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: InvoiceSnapshotsRepoAcl,
+}
+
+pub trait InvoiceSnapshotsRepo {
+    /// Stores the snapshot unless the invoice has one already, the first snapshot is kept
+    fn create(&self, payload: NewInvoiceSnapshot) -> RepoResultV2<InvoiceSnapshot>;
+    /// Snapshot of a paid invoice, invoices paid before snapshots were introduced have none
+    fn get(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<InvoiceSnapshot>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> InvoiceSnapshotsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: InvoiceSnapshotsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> InvoiceSnapshotsRepo
+    for InvoiceSnapshotsRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewInvoiceSnapshot) -> RepoResultV2<InvoiceSnapshot> {
+        debug!("create snapshot of invoice {}.", payload.invoice_id);
+        acl::check(&*self.acl, Resource::InvoiceSnapshot, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let invoice_id = payload.invoice_id;
+        diesel::insert_into(InvoiceSnapshotsDsl::invoice_snapshots)
+            .values(&payload)
+            .on_conflict_do_nothing()
+            .execute(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        InvoiceSnapshotsDsl::invoice_snapshots
+            .filter(InvoiceSnapshotsDsl::invoice_id.eq(invoice_id))
+            .get_result::<InvoiceSnapshot>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn get(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<InvoiceSnapshot>> {
+        debug!("get snapshot of invoice {}.", invoice_id);
+
+        let invoice_snapshot = InvoiceSnapshotsDsl::invoice_snapshots
+            .filter(InvoiceSnapshotsDsl::invoice_id.eq(invoice_id))
+            .get_result::<InvoiceSnapshot>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        if let Some(ref invoice_snapshot) = invoice_snapshot {
+            acl::check(&*self.acl, Resource::InvoiceSnapshot, Action::Read, self, Some(invoice_snapshot))
+                .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(invoice_snapshot)
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, InvoiceSnapshot>
+    for InvoiceSnapshotsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&InvoiceSnapshot>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use diesel;
 use diesel::connection::AnsiTransactionManager;
-use diesel::pg::Pg;
+use diesel::pg::{expression::dsl::any, Pg};
 use diesel::prelude::*;
 use diesel::query_dsl::RunQueryDsl;
+use diesel::sql_query;
+use diesel::sql_types::BigInt;
 use diesel::Connection;
 use failure::Error as FailureError;
 use failure::Fail;
@@ -15,6 +17,7 @@ use models::invoice_v2::*;
 use models::{AccountId, TransactionId, UserId};
 use schema::amounts_received::dsl as AmountsReceived;
 use schema::invoices_v2::dsl as InvoicesV2;
+use stq_static_resources::OrderState;
 
 use super::acl;
 use super::error::*;
@@ -29,7 +32,10 @@ pub struct InvoicesV2RepoImpl<'a, T: Connection<Backend = Pg, TransactionManager
 
 pub trait InvoicesV2Repo {
     fn get(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<RawInvoice>>;
+    fn get_many(&self, invoice_ids: &[InvoiceId]) -> RepoResultV2<Vec<RawInvoice>>;
     fn get_by_account_id(&self, account_id: AccountId) -> RepoResultV2<Option<RawInvoice>>;
+    fn get_by_public_token(&self, public_token: &str) -> RepoResultV2<Option<RawInvoice>>;
+    fn get_unpaid_by_buyer_user_id(&self, buyer_user_id: UserId) -> RepoResultV2<Vec<RawInvoice>>;
     fn create(&self, input: NewInvoice) -> RepoResultV2<RawInvoice>;
     fn increase_amount_captured(
         &self,
@@ -37,10 +43,17 @@ pub trait InvoicesV2Repo {
         transaction_id: TransactionId,
         amount_received: Amount,
     ) -> RepoResultV2<RawInvoice>;
+    /// Inbound transactions credited to the invoice, the oldest first
+    fn list_amounts_received(&self, invoice_id: InvoiceId) -> RepoResultV2<Vec<RawAmountReceived>>;
     fn set_amount_paid(&self, invoice_id: InvoiceId, input: InvoiceSetAmountPaid) -> RepoResultV2<RawInvoice>;
     fn set_amount_paid_fiat(&self, invoice_id: InvoiceId, input: InvoiceSetAmountPaid) -> RepoResultV2<RawInvoice>;
+    fn update_details(&self, invoice_id: InvoiceId, input: UpdateInvoiceDetails) -> RepoResultV2<RawInvoice>;
     fn unlink_account(&self, invoice_id: InvoiceId) -> RepoResultV2<RawInvoice>;
+    fn set_status(&self, invoice_id: InvoiceId, status: OrderState) -> RepoResultV2<RawInvoice>;
     fn delete(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<RawInvoice>>;
+    /// Waits for the other transactions recalculating or paying the invoice to end. The lock is held until the end
+    /// of the transaction it is taken in, outside of a transaction it is released right away
+    fn lock_for_recalculation(&self, invoice_id: InvoiceId) -> RepoResultV2<()>;
 }
 
 impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> InvoicesV2RepoImpl<'a, T> {
@@ -77,6 +90,33 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             })
     }
 
+    fn get_many(&self, invoice_ids: &[InvoiceId]) -> RepoResultV2<Vec<RawInvoice>> {
+        debug!(
+            "Getting invoices with IDs: {}",
+            invoice_ids.iter().map(InvoiceId::to_string).collect::<Vec<_>>().join(", ")
+        );
+
+        let query = InvoicesV2::invoices_v2.filter(InvoicesV2::id.eq(any(invoice_ids)));
+
+        let invoices = query.get_results::<RawInvoice>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        for invoice in &invoices {
+            acl::check(
+                &*self.acl,
+                Resource::Invoice,
+                Action::Read,
+                self,
+                Some(&InvoiceAccess::from(invoice.clone())),
+            )
+            .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(invoices)
+    }
+
     fn get_by_account_id(&self, account_id: AccountId) -> RepoResultV2<Option<RawInvoice>> {
         debug!("Getting an invoice by account ID: {}", account_id);
 
@@ -104,6 +144,57 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             })
     }
 
+    fn get_by_public_token(&self, public_token: &str) -> RepoResultV2<Option<RawInvoice>> {
+        // the token is a secret, so it is not logged
+        debug!("Getting an invoice by public token");
+
+        let query = InvoicesV2::invoices_v2.filter(InvoicesV2::public_token.eq(public_token));
+
+        query
+            .get_result(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+            .and_then(|invoice: Option<RawInvoice>| {
+                if let Some(ref invoice) = invoice {
+                    acl::check(
+                        &*self.acl,
+                        Resource::Invoice,
+                        Action::Read,
+                        self,
+                        Some(&InvoiceAccess::from(invoice.clone())),
+                    )
+                    .map_err(ectx!(try ErrorKind::Forbidden))?;
+                };
+                Ok(invoice)
+            })
+    }
+
+    fn get_unpaid_by_buyer_user_id(&self, buyer_user_id: UserId) -> RepoResultV2<Vec<RawInvoice>> {
+        debug!("Getting unpaid invoices by buyer user ID: {}", buyer_user_id);
+
+        acl::check(
+            &*self.acl,
+            Resource::Invoice,
+            Action::Read,
+            self,
+            Some(&InvoiceAccess { user_id: buyer_user_id }),
+        )
+        .map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let query = InvoicesV2::invoices_v2
+            .filter(InvoicesV2::buyer_user_id.eq(buyer_user_id))
+            .filter(InvoicesV2::paid_at.is_null())
+            .order_by(InvoicesV2::created_at.desc());
+
+        query.get_results(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
     fn create(&self, input: NewInvoice) -> RepoResultV2<RawInvoice> {
         debug!("Creating an invoice using input: {:?}", input);
 
@@ -184,6 +275,24 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             })
     }
 
+    fn list_amounts_received(&self, invoice_id: InvoiceId) -> RepoResultV2<Vec<RawAmountReceived>> {
+        debug!("Listing amounts received for invoice with ID = {}", invoice_id);
+
+        // amounts are visible to whoever may see the invoice
+        if self.get(invoice_id)?.is_none() {
+            return Ok(vec![]);
+        }
+
+        AmountsReceived::amounts_received
+            .filter(AmountsReceived::invoice_id.eq(invoice_id))
+            .order_by(AmountsReceived::created_at.asc())
+            .get_results::<RawAmountReceived>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
     fn set_amount_paid(&self, invoice_id: InvoiceId, input: InvoiceSetAmountPaid) -> RepoResultV2<RawInvoice> {
         debug!(
             "Setting amount paid for invoice with ID = {} using payload: {:?}",
@@ -254,6 +363,36 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         })
     }
 
+    fn update_details(&self, invoice_id: InvoiceId, input: UpdateInvoiceDetails) -> RepoResultV2<RawInvoice> {
+        debug!("Updating details of invoice with ID = {} using payload: {:?}", &invoice_id, &input);
+
+        let query = InvoicesV2::invoices_v2.filter(InvoicesV2::id.eq(invoice_id));
+
+        query
+            .get_result::<RawInvoice>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })
+            .and_then(|invoice| {
+                acl::check(
+                    &*self.acl,
+                    Resource::Invoice,
+                    Action::Write,
+                    self,
+                    Some(&InvoiceAccess::from(invoice.clone())),
+                )
+                .map_err(ectx!(try ErrorKind::Forbidden))
+            })?;
+
+        let command = diesel::update(InvoicesV2::invoices_v2.filter(InvoicesV2::id.eq(invoice_id))).set(&input);
+
+        command.get_result::<RawInvoice>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
     fn unlink_account(&self, invoice_id: InvoiceId) -> RepoResultV2<RawInvoice> {
         debug!("Unlinking account for invoice with ID = {}", invoice_id);
 
@@ -285,6 +424,37 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         })
     }
 
+    fn set_status(&self, invoice_id: InvoiceId, status: OrderState) -> RepoResultV2<RawInvoice> {
+        debug!("Setting status of invoice with ID = {} to {:?}", invoice_id, status);
+
+        let query = InvoicesV2::invoices_v2.filter(InvoicesV2::id.eq(invoice_id));
+
+        query
+            .get_result::<RawInvoice>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })
+            .and_then(|invoice| {
+                acl::check(
+                    &*self.acl,
+                    Resource::Invoice,
+                    Action::Write,
+                    self,
+                    Some(&InvoiceAccess::from(invoice.clone())),
+                )
+                .map_err(ectx!(try ErrorKind::Forbidden))
+            })?;
+
+        let command =
+            diesel::update(InvoicesV2::invoices_v2.filter(InvoicesV2::id.eq(invoice_id))).set(InvoicesV2::status.eq(status.clone()));
+
+        command.get_result::<RawInvoice>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind => invoice_id, status)
+        })
+    }
+
     fn delete(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<RawInvoice>> {
         debug!("Deleting an invoice with ID: {}", invoice_id);
 
@@ -321,6 +491,18 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             ectx!(err e, ErrorSource::Diesel, error_kind)
         })
     }
+
+    fn lock_for_recalculation(&self, invoice_id: InvoiceId) -> RepoResultV2<()> {
+        debug!("Locking invoice with ID = {} for recalculation", invoice_id);
+
+        // The lock doesn't give access to the invoice, so there is no ACL check
+        let command = sql_query("SELECT pg_advisory_xact_lock($1)").bind::<BigInt, _>(invoice_id.lock_key());
+
+        command.execute(self.db_conn).map(|_| ()).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind => invoice_id)
+        })
+    }
 }
 
 impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, InvoiceAccess>
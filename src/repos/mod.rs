@@ -1,56 +1,130 @@
 //! Repos is a module responsible for interacting with postgres db
 
+pub mod account_assignments;
+pub mod account_pool_claims;
 pub mod accounts;
 #[macro_use]
 pub mod acl;
+pub mod api_keys;
+pub mod audit_log;
+pub mod billing_info_changes;
+pub mod cashback_liabilities;
 pub mod customer;
+pub mod customer_exports;
+pub mod data_retention;
+pub mod encryption;
 pub mod error;
 pub mod event_store;
+pub mod exchange_rate_slippages;
+pub mod feature_flags;
 pub mod fee;
+pub mod fee_adjustments;
+pub mod fee_crypto_payments;
+pub mod fee_dunnings;
+pub mod fee_statements;
+pub mod historical_reports;
 pub mod international_billing_info;
 pub mod invoice;
+pub mod invoice_buyer_details;
+pub mod invoice_callbacks;
+pub mod invoice_requotes;
+pub mod invoice_risk_assessments;
+pub mod invoice_snapshots;
 pub mod invoices_v2;
+pub mod negative_store_balances;
+pub mod order_capture_approvals;
 pub mod order_exchange_rates;
 pub mod order_info;
+pub mod order_line_items;
 pub mod orders;
+pub mod payment_attempts;
 pub mod payment_intent;
+pub mod payment_intent_history;
 pub mod payment_intents_fees;
 pub mod payment_intents_invoices;
+pub mod payment_recoveries;
+pub mod payout_instructions;
+pub mod payout_restriction_overrides;
+pub mod payout_statements;
 pub mod payouts;
 pub mod proxy_companies_billing_info;
 pub mod repo_factory;
 pub mod russia_billing_info;
+pub mod schema_migrations;
+pub mod store_billing_statuses;
 pub mod store_billing_type;
+pub mod store_offboardings;
 pub mod store_subscription;
+pub mod store_webhooks;
+pub mod stripe_fee_backfills;
 pub mod subscription;
 pub mod subscription_payment;
 pub mod types;
+pub mod user_credits;
 pub mod user_roles;
 pub mod user_wallets;
+pub mod wallet_verifications;
 
+pub use self::account_assignments::*;
+pub use self::account_pool_claims::*;
 pub use self::accounts::*;
 pub use self::acl::*;
+pub use self::api_keys::*;
+pub use self::audit_log::*;
+pub use self::billing_info_changes::*;
+pub use self::cashback_liabilities::*;
 pub use self::customer::*;
+pub use self::customer_exports::*;
+pub use self::data_retention::*;
+pub use self::encryption::*;
 pub use self::error::*;
 pub use self::event_store::*;
+pub use self::exchange_rate_slippages::*;
+pub use self::feature_flags::*;
 pub use self::fee::*;
+pub use self::fee_adjustments::*;
+pub use self::fee_crypto_payments::*;
+pub use self::fee_dunnings::*;
+pub use self::fee_statements::*;
+pub use self::historical_reports::*;
 pub use self::international_billing_info::*;
 pub use self::invoice::*;
+pub use self::invoice_buyer_details::*;
+pub use self::invoice_callbacks::*;
+pub use self::invoice_requotes::*;
+pub use self::invoice_risk_assessments::*;
+pub use self::invoice_snapshots::*;
 pub use self::invoices_v2::*;
+pub use self::negative_store_balances::*;
+pub use self::order_capture_approvals::*;
 pub use self::order_exchange_rates::*;
 pub use self::order_info::*;
+pub use self::order_line_items::*;
 pub use self::orders::*;
+pub use self::payment_attempts::*;
 pub use self::payment_intent::*;
+pub use self::payment_intent_history::*;
 pub use self::payment_intents_fees::*;
 pub use self::payment_intents_invoices::*;
+pub use self::payment_recoveries::*;
+pub use self::payout_instructions::*;
+pub use self::payout_restriction_overrides::*;
+pub use self::payout_statements::*;
 pub use self::payouts::*;
 pub use self::proxy_companies_billing_info::*;
 pub use self::repo_factory::*;
 pub use self::russia_billing_info::*;
+pub use self::schema_migrations::*;
+pub use self::store_billing_statuses::*;
 pub use self::store_billing_type::*;
+pub use self::store_offboardings::*;
 pub use self::store_subscription::*;
+pub use self::store_webhooks::*;
+pub use self::stripe_fee_backfills::*;
 pub use self::subscription::*;
 pub use self::subscription_payment::*;
 pub use self::types::*;
+pub use self::user_credits::*;
 pub use self::user_roles::*;
 pub use self::user_wallets::*;
+pub use self::wallet_verifications::*;
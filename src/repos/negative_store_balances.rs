@@ -0,0 +1,162 @@
+use chrono::Utc;
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::order_v2::StoreId;
+use models::{Amount, NegativeStoreBalance, NegativeStoreBalanceId, NewNegativeStoreBalance};
+use repos::legacy_acl::*;
+
+use schema::negative_store_balances::dsl as NegativeStoreBalancesDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type NegativeStoreBalancesRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, NegativeStoreBalance>>;
+
+pub struct NegativeStoreBalancesRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: NegativeStoreBalancesRepoAcl,
+}
+
+pub trait NegativeStoreBalancesRepo {
+    fn create(&self, payload: NewNegativeStoreBalance) -> RepoResultV2<NegativeStoreBalance>;
+    fn get(&self, id: NegativeStoreBalanceId) -> RepoResultV2<Option<NegativeStoreBalance>>;
+    /// Flags of the store that haven't been resolved yet, one per currency at most
+    fn get_open_by_store_id(&self, store_id: StoreId) -> RepoResultV2<Vec<NegativeStoreBalance>>;
+    /// Flags of all stores that haven't been resolved yet, the largest deficits first
+    fn list_open(&self) -> RepoResultV2<Vec<NegativeStoreBalance>>;
+    fn update_amount(&self, id: NegativeStoreBalanceId, amount: Amount) -> RepoResultV2<NegativeStoreBalance>;
+    fn set_notified(&self, id: NegativeStoreBalanceId) -> RepoResultV2<NegativeStoreBalance>;
+    fn resolve(&self, id: NegativeStoreBalanceId) -> RepoResultV2<NegativeStoreBalance>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> NegativeStoreBalancesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: NegativeStoreBalancesRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> NegativeStoreBalancesRepo
+    for NegativeStoreBalancesRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewNegativeStoreBalance) -> RepoResultV2<NegativeStoreBalance> {
+        debug!("create negative store balance {:?}.", payload);
+        acl::check(&*self.acl, Resource::NegativeStoreBalance, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(NegativeStoreBalancesDsl::negative_store_balances).values(&payload);
+
+        command.get_result::<NegativeStoreBalance>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get(&self, id: NegativeStoreBalanceId) -> RepoResultV2<Option<NegativeStoreBalance>> {
+        debug!("get negative store balance {}.", id);
+        acl::check(&*self.acl, Resource::NegativeStoreBalance, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        NegativeStoreBalancesDsl::negative_store_balances
+            .filter(NegativeStoreBalancesDsl::id.eq(id))
+            .get_result::<NegativeStoreBalance>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn get_open_by_store_id(&self, store_id: StoreId) -> RepoResultV2<Vec<NegativeStoreBalance>> {
+        debug!("get open negative balances of store {}.", store_id);
+        acl::check(&*self.acl, Resource::NegativeStoreBalance, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        NegativeStoreBalancesDsl::negative_store_balances
+            .filter(NegativeStoreBalancesDsl::store_id.eq(store_id))
+            .filter(NegativeStoreBalancesDsl::resolved_at.is_null())
+            .order_by(NegativeStoreBalancesDsl::currency.asc())
+            .get_results::<NegativeStoreBalance>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn list_open(&self) -> RepoResultV2<Vec<NegativeStoreBalance>> {
+        debug!("list open negative store balances.");
+        acl::check(&*self.acl, Resource::NegativeStoreBalance, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        NegativeStoreBalancesDsl::negative_store_balances
+            .filter(NegativeStoreBalancesDsl::resolved_at.is_null())
+            .order_by((NegativeStoreBalancesDsl::amount.desc(), NegativeStoreBalancesDsl::id.asc()))
+            .get_results::<NegativeStoreBalance>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn update_amount(&self, id: NegativeStoreBalanceId, amount: Amount) -> RepoResultV2<NegativeStoreBalance> {
+        debug!("update amount of negative store balance {} to {}.", id, amount);
+        acl::check(&*self.acl, Resource::NegativeStoreBalance, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let filter = NegativeStoreBalancesDsl::negative_store_balances.filter(NegativeStoreBalancesDsl::id.eq(id));
+
+        diesel::update(filter)
+            .set(NegativeStoreBalancesDsl::amount.eq(amount))
+            .get_result::<NegativeStoreBalance>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn set_notified(&self, id: NegativeStoreBalanceId) -> RepoResultV2<NegativeStoreBalance> {
+        debug!("set negative store balance {} notified.", id);
+        acl::check(&*self.acl, Resource::NegativeStoreBalance, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let filter = NegativeStoreBalancesDsl::negative_store_balances.filter(NegativeStoreBalancesDsl::id.eq(id));
+
+        diesel::update(filter)
+            .set(NegativeStoreBalancesDsl::notified_at.eq(Some(Utc::now().naive_utc())))
+            .get_result::<NegativeStoreBalance>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn resolve(&self, id: NegativeStoreBalanceId) -> RepoResultV2<NegativeStoreBalance> {
+        debug!("resolve negative store balance {}.", id);
+        acl::check(&*self.acl, Resource::NegativeStoreBalance, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let filter = NegativeStoreBalancesDsl::negative_store_balances.filter(NegativeStoreBalancesDsl::id.eq(id));
+
+        diesel::update(filter)
+            .set(NegativeStoreBalancesDsl::resolved_at.eq(Some(Utc::now().naive_utc())))
+            .get_result::<NegativeStoreBalance>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, NegativeStoreBalance>
+    for NegativeStoreBalancesRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&NegativeStoreBalance>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
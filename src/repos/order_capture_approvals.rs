@@ -0,0 +1,83 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::stripe::PaymentIntentId;
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::{NewOrderCaptureApproval, OrderCaptureApproval};
+use repos::legacy_acl::*;
+
+use schema::order_capture_approvals::dsl as OrderCaptureApprovalsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type OrderCaptureApprovalsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, OrderCaptureApproval>>;
+
+pub struct OrderCaptureApprovalsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: OrderCaptureApprovalsRepoAcl,
+}
+
+pub trait OrderCaptureApprovalsRepo {
+    fn create(&self, payload: NewOrderCaptureApproval) -> RepoResultV2<OrderCaptureApproval>;
+    fn get_by_payment_intent_id(&self, payment_intent_id: PaymentIntentId) -> RepoResultV2<Vec<OrderCaptureApproval>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> OrderCaptureApprovalsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: OrderCaptureApprovalsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> OrderCaptureApprovalsRepo
+    for OrderCaptureApprovalsRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewOrderCaptureApproval) -> RepoResultV2<OrderCaptureApproval> {
+        debug!(
+            "create capture approval of order {} paid by payment intent {}.",
+            payload.order_id, payload.payment_intent_id.0
+        );
+        acl::check(&*self.acl, Resource::OrderCaptureApproval, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(OrderCaptureApprovalsDsl::order_capture_approvals).values(&payload);
+
+        command.get_result::<OrderCaptureApproval>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get_by_payment_intent_id(&self, payment_intent_id: PaymentIntentId) -> RepoResultV2<Vec<OrderCaptureApproval>> {
+        debug!("get capture approvals of orders paid by payment intent {}.", payment_intent_id.0);
+        acl::check(&*self.acl, Resource::OrderCaptureApproval, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        OrderCaptureApprovalsDsl::order_capture_approvals
+            .filter(OrderCaptureApprovalsDsl::payment_intent_id.eq(payment_intent_id))
+            .order_by(OrderCaptureApprovalsDsl::approved_at.asc())
+            .get_results::<OrderCaptureApproval>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, OrderCaptureApproval>
+    for OrderCaptureApprovalsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&OrderCaptureApproval>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
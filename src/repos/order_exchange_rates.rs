@@ -1,3 +1,4 @@
+use chrono::NaiveDateTime;
 use diesel;
 use diesel::connection::AnsiTransactionManager;
 use diesel::pg::Pg;
@@ -7,6 +8,8 @@ use diesel::Connection;
 use failure::Error as FailureError;
 use failure::Fail;
 
+use stq_static_resources::OrderState;
+
 use repos::legacy_acl::*;
 
 use models::authorization::*;
@@ -18,6 +21,7 @@ use models::order_v2::OrderId;
 use models::UserId;
 
 use schema::invoices_v2::dsl as InvoicesV2;
+use schema::order_exchange_rates;
 use schema::order_exchange_rates::dsl as OrderExchangeRates;
 use schema::orders::dsl as Orders;
 
@@ -40,6 +44,16 @@ pub trait OrderExchangeRatesRepo {
     fn expire_current_active_rate(&self, order_id: OrderId) -> RepoResultV2<Option<RawOrderExchangeRate>>;
     fn delete(&self, rate_id: OrderExchangeRateId) -> RepoResultV2<Option<RawOrderExchangeRate>>;
     fn delete_by_order_id(&self, order_id: OrderId) -> RepoResultV2<Vec<RawOrderExchangeRate>>;
+    /// Rates expired before `expired_before` of the orders of paid or expired invoices with the time the invoice was paid at,
+    /// in ascending order starting after `after_id`
+    fn find_superseded(
+        &self,
+        expired_before: NaiveDateTime,
+        after_id: OrderExchangeRateId,
+        limit: i64,
+    ) -> RepoResultV2<Vec<(RawOrderExchangeRate, Option<NaiveDateTime>)>>;
+    /// Deletes the rates, returns the number of rates deleted
+    fn delete_many(&self, rate_ids: Vec<OrderExchangeRateId>) -> RepoResultV2<usize>;
 }
 
 impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> OrderExchangeRatesRepoImpl<'a, T> {
@@ -262,6 +276,51 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             ectx!(err e, ErrorSource::Diesel, error_kind)
         })
     }
+
+    fn find_superseded(
+        &self,
+        expired_before: NaiveDateTime,
+        after_id: OrderExchangeRateId,
+        limit: i64,
+    ) -> RepoResultV2<Vec<(RawOrderExchangeRate, Option<NaiveDateTime>)>> {
+        debug!("Finding rates expired before {} after rate with ID: {}", expired_before, after_id);
+
+        acl::check(&*self.acl, Resource::OrderExchangeRate, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let query = OrderExchangeRates::order_exchange_rates
+            .inner_join(Orders::orders.inner_join(InvoicesV2::invoices_v2))
+            .filter(OrderExchangeRates::status.eq(ExchangeRateStatus::Expired))
+            .filter(OrderExchangeRates::updated_at.lt(expired_before))
+            .filter(OrderExchangeRates::id.gt(after_id))
+            .filter(
+                InvoicesV2::paid_at
+                    .is_not_null()
+                    .or(InvoicesV2::status.eq(OrderState::AmountExpired)),
+            )
+            .select((order_exchange_rates::all_columns, InvoicesV2::paid_at))
+            .order(OrderExchangeRates::id.asc())
+            .limit(limit);
+
+        query
+            .get_results::<(RawOrderExchangeRate, Option<NaiveDateTime>)>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn delete_many(&self, rate_ids: Vec<OrderExchangeRateId>) -> RepoResultV2<usize> {
+        debug!("Deleting {} rates", rate_ids.len());
+
+        acl::check(&*self.acl, Resource::OrderExchangeRate, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::delete(OrderExchangeRates::order_exchange_rates.filter(OrderExchangeRates::id.eq_any(rate_ids)));
+
+        command.execute(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
 }
 
 impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, OrderExchangeRateAccess>
@@ -0,0 +1,119 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::order_v2::OrderId;
+use models::{NewOrderLineItem, OrderLineItem, OrderLineItemId};
+use repos::legacy_acl::*;
+
+use schema::order_line_items::dsl as OrderLineItemsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type OrderLineItemsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, OrderLineItem>>;
+
+pub struct OrderLineItemsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: OrderLineItemsRepoAcl,
+}
+
+pub trait OrderLineItemsRepo {
+    fn create(&self, payload: NewOrderLineItem) -> RepoResultV2<OrderLineItem>;
+    fn get_by_order_ids(&self, order_ids: &[OrderId]) -> RepoResultV2<Vec<OrderLineItem>>;
+    /// Marks `quantity` more units of the line item as refunded unless fewer are left, returns `None` in that case
+    fn add_refunded_quantity(&self, id: OrderLineItemId, quantity: i32) -> RepoResultV2<Option<OrderLineItem>>;
+    /// Takes back units marked as refunded by `add_refunded_quantity` when the refund has failed
+    fn subtract_refunded_quantity(&self, id: OrderLineItemId, quantity: i32) -> RepoResultV2<OrderLineItem>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> OrderLineItemsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: OrderLineItemsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> OrderLineItemsRepo
+    for OrderLineItemsRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewOrderLineItem) -> RepoResultV2<OrderLineItem> {
+        debug!("create line item {:?}.", payload);
+        acl::check(&*self.acl, Resource::OrderLineItem, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        diesel::insert_into(OrderLineItemsDsl::order_line_items)
+            .values(&payload)
+            .get_result::<OrderLineItem>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn get_by_order_ids(&self, order_ids: &[OrderId]) -> RepoResultV2<Vec<OrderLineItem>> {
+        debug!("get line items of orders {:?}.", order_ids);
+        acl::check(&*self.acl, Resource::OrderLineItem, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        OrderLineItemsDsl::order_line_items
+            .filter(OrderLineItemsDsl::order_id.eq_any(order_ids))
+            .order((OrderLineItemsDsl::created_at, OrderLineItemsDsl::id))
+            .get_results::<OrderLineItem>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn add_refunded_quantity(&self, id: OrderLineItemId, quantity: i32) -> RepoResultV2<Option<OrderLineItem>> {
+        debug!("add {} refunded units to line item {}.", quantity, id);
+        acl::check(&*self.acl, Resource::OrderLineItem, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        // The units left are checked by the update itself, so concurrent refunds cannot refund the same unit twice
+        let filter = OrderLineItemsDsl::order_line_items
+            .filter(OrderLineItemsDsl::id.eq(id))
+            .filter((OrderLineItemsDsl::refunded_quantity + quantity).le(OrderLineItemsDsl::quantity));
+
+        diesel::update(filter)
+            .set(OrderLineItemsDsl::refunded_quantity.eq(OrderLineItemsDsl::refunded_quantity + quantity))
+            .get_result::<OrderLineItem>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn subtract_refunded_quantity(&self, id: OrderLineItemId, quantity: i32) -> RepoResultV2<OrderLineItem> {
+        debug!("subtract {} refunded units from line item {}.", quantity, id);
+        acl::check(&*self.acl, Resource::OrderLineItem, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let filter = OrderLineItemsDsl::order_line_items.filter(OrderLineItemsDsl::id.eq(id));
+
+        diesel::update(filter)
+            .set(OrderLineItemsDsl::refunded_quantity.eq(OrderLineItemsDsl::refunded_quantity - quantity))
+            .get_result::<OrderLineItem>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, OrderLineItem>
+    for OrderLineItemsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&OrderLineItem>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -1,21 +1,24 @@
 use diesel;
 use diesel::connection::AnsiTransactionManager;
+use diesel::dsl::{exists, not};
 use diesel::pg::{expression::dsl::any, Pg};
 use diesel::prelude::*;
 use diesel::query_dsl::RunQueryDsl;
-use diesel::sql_types::Bool;
+use diesel::sql_query;
+use diesel::sql_types::{self, Bool};
 use diesel::Connection;
 use failure::Error as FailureError;
 use failure::Fail;
+use stq_static_resources::OrderState;
 
 use repos::legacy_acl::*;
 use repos::user_roles::user_is_store_manager;
 
 use models::authorization::*;
 use models::invoice_v2::InvoiceId;
-use models::order_v2::{NewOrder, OrderAccess, OrderId, OrderSearchResults, OrdersSearch, RawOrder, StoreId};
-use models::{Amount, Currency, PaymentState, UserId};
-use schema::{invoices_v2::dsl as InvoicesV2, orders::dsl as Orders};
+use models::order_v2::{InvoicePaymentStatus, NewOrder, OrderAccess, OrderId, OrderSearchResults, OrdersSearch, RawOrder, StoreId};
+use models::{Amount, Currency, PaymentState, RawStoreBalanceBucketAmount, StoreBalanceBucketAmount, UserId};
+use schema::{fee_adjustments::dsl as FeeAdjustments, invoices_v2::dsl as InvoicesV2, orders::dsl as Orders};
 
 use super::acl;
 use super::error::*;
@@ -36,6 +39,8 @@ pub trait OrdersRepo {
     fn get_many_by_invoice_id(&self, invoice_id: InvoiceId) -> RepoResultV2<Vec<RawOrder>>;
     fn get_order_ids_by_store_id(&self, store_id: StoreId) -> RepoResultV2<Vec<OrderId>>;
     fn get_orders_for_payout(&self, store_id: StoreId, currency: Option<Currency>) -> RepoResultV2<Vec<RawOrder>>;
+    fn get_orders_without_stripe_fee(&self, states: Vec<PaymentState>) -> RepoResultV2<Vec<RawOrder>>;
+    fn get_store_balance_overview(&self, store_id: StoreId) -> RepoResultV2<Vec<StoreBalanceBucketAmount>>;
     fn search(&self, skip: i64, count: i64, search: OrdersSearch) -> RepoResultV2<OrderSearchResults>;
     fn create(&self, payload: NewOrder) -> RepoResultV2<RawOrder>;
     fn delete(&self, order_id: OrderId) -> RepoResultV2<Option<RawOrder>>;
@@ -200,6 +205,101 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         Ok(results)
     }
 
+    fn get_orders_without_stripe_fee(&self, states: Vec<PaymentState>) -> RepoResultV2<Vec<RawOrder>> {
+        debug!("Getting orders without stripe fee in states: {:?}", states);
+
+        let query = Orders::orders
+            .filter(Orders::stripe_fee.is_null())
+            .filter(Orders::state.eq_any(states))
+            .order_by(Orders::created_at);
+
+        let results = query.get_results::<RawOrder>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        for result in &results {
+            acl::check(
+                &*self.acl,
+                Resource::OrderInfo,
+                Action::Read,
+                self,
+                Some(&OrderAccess {
+                    invoice_id: result.invoice_id,
+                    store_id: result.store_id,
+                }),
+            )
+            .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(results)
+    }
+
+    fn get_store_balance_overview(&self, store_id: StoreId) -> RepoResultV2<Vec<StoreBalanceBucketAmount>> {
+        debug!("Getting balance overview for store with ID: {}", store_id);
+
+        let invoice_ids = Orders::orders
+            .filter(Orders::store_id.eq(store_id))
+            .select(Orders::invoice_id)
+            .distinct()
+            .get_results::<InvoiceId>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        for invoice_id in invoice_ids {
+            acl::check(
+                &*self.acl,
+                Resource::OrderInfo,
+                Action::Read,
+                self,
+                Some(&OrderAccess { invoice_id, store_id }),
+            )
+            .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        // An order with a payout counts as paid out even while the payout is still being processed
+        let command = sql_query(
+            "
+            SELECT
+                orders.seller_currency AS currency,
+                CASE
+                    WHEN paid_out_orders.order_id IS NOT NULL OR orders.state = $1 THEN 'paid_out'
+                    WHEN orders.state = $2 THEN 'eligible'
+                    WHEN invoices_v2.paid_at IS NULL THEN 'unpaid_invoices'
+                    ELSE 'paid_not_eligible'
+                END AS bucket,
+                SUM(orders.total_amount) AS amount,
+                COUNT(*) AS orders_count
+            FROM orders
+            JOIN invoices_v2 ON invoices_v2.id = orders.invoice_id
+            LEFT JOIN (SELECT DISTINCT order_id FROM order_payouts) paid_out_orders ON paid_out_orders.order_id = orders.id
+            WHERE orders.store_id = $3 AND orders.state NOT IN ($4, $5, $6)
+            GROUP BY 1, 2
+            ORDER BY 1, 2
+        ",
+        )
+        .bind::<sql_types::VarChar, _>(PaymentState::PaidToSeller)
+        .bind::<sql_types::VarChar, _>(PaymentState::PaymentToSellerNeeded)
+        .bind::<sql_types::Integer, _>(store_id.inner())
+        .bind::<sql_types::VarChar, _>(PaymentState::Declined)
+        .bind::<sql_types::VarChar, _>(PaymentState::RefundNeeded)
+        .bind::<sql_types::VarChar, _>(PaymentState::Refunded);
+
+        let raw_amounts = command.get_results::<RawStoreBalanceBucketAmount>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        raw_amounts
+            .into_iter()
+            .map(|raw_amount| {
+                RawStoreBalanceBucketAmount::try_into_bucket_amount(raw_amount.clone()).map_err(ectx!(ErrorKind::Internal => raw_amount))
+            })
+            .collect::<Result<Vec<_>, _>>()
+    }
+
     fn search(&self, skip: i64, count: i64, search_params: OrdersSearch) -> RepoResultV2<OrderSearchResults> {
         debug!("Searching orders, skip={}, count={}, search {:?}", skip, count, search_params);
         let query: BoxedExpr = into_expr(search_params).unwrap_or(Box::new(true.into_sql::<Bool>()));
@@ -392,6 +492,7 @@ fn into_expr(search: OrdersSearch) -> Option<BoxedExpr> {
         state,
         order_id,
         order_ids,
+        payment_status,
     } = search;
 
     if let Some(store_id_filter) = store_id {
@@ -414,9 +515,42 @@ fn into_expr(search: OrdersSearch) -> Option<BoxedExpr> {
         query = Some(and(query, Box::new(new_condition)));
     }
 
+    if let Some(payment_status_filter) = payment_status {
+        let new_condition = payment_status_expr(payment_status_filter);
+        query = Some(and(query, new_condition));
+    }
+
     query
 }
 
+/// Looks up the invoice and the fee adjustments of each order by their indexes,
+/// so the filter stays cheap once the orders are narrowed down to a store
+fn payment_status_expr(payment_status: InvoicePaymentStatus) -> BoxedExpr {
+    let refunded = || -> BoxedExpr {
+        Box::new(Orders::state.eq_any(InvoicePaymentStatus::refunded_states()).or(exists(
+            FeeAdjustments::fee_adjustments.filter(FeeAdjustments::order_id.eq(Orders::id)),
+        )))
+    };
+
+    match payment_status {
+        InvoicePaymentStatus::AwaitingPayment => Box::new(exists(
+            InvoicesV2::invoices_v2
+                .filter(InvoicesV2::id.eq(Orders::invoice_id))
+                .filter(InvoicesV2::paid_at.is_null())
+                .filter(InvoicesV2::status.eq_any(vec![OrderState::PaymentAwaited, OrderState::TransactionPending])),
+        )),
+        InvoicePaymentStatus::Paid => Box::new(
+            exists(
+                InvoicesV2::invoices_v2
+                    .filter(InvoicesV2::id.eq(Orders::invoice_id))
+                    .filter(InvoicesV2::paid_at.is_not_null()),
+            )
+            .and(not(refunded())),
+        ),
+        InvoicePaymentStatus::Refunded => refunded(),
+    }
+}
+
 fn and(old_condition: Option<BoxedExpr>, new_condition: BoxedExpr) -> BoxedExpr {
     if let Some(old_condition) = old_condition {
         Box::new(old_condition.and(new_condition))
@@ -0,0 +1,81 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::invoice_v2::InvoiceId;
+use models::{NewPaymentAttempt, PaymentAttempt};
+use repos::legacy_acl::*;
+
+use schema::payment_attempts::dsl as PaymentAttemptsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type PaymentAttemptsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, PaymentAttempt>>;
+
+pub struct PaymentAttemptsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: PaymentAttemptsRepoAcl,
+}
+
+pub trait PaymentAttemptsRepo {
+    fn create(&self, payload: NewPaymentAttempt) -> RepoResultV2<PaymentAttempt>;
+    /// Attempts to pay the invoice, oldest first
+    fn list_by_invoice_id(&self, invoice_id: InvoiceId) -> RepoResultV2<Vec<PaymentAttempt>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PaymentAttemptsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: PaymentAttemptsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PaymentAttemptsRepo
+    for PaymentAttemptsRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewPaymentAttempt) -> RepoResultV2<PaymentAttempt> {
+        debug!("create payment attempt {:?}.", payload);
+        acl::check(&*self.acl, Resource::PaymentAttempt, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(PaymentAttemptsDsl::payment_attempts).values(&payload);
+
+        command.get_result::<PaymentAttempt>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn list_by_invoice_id(&self, invoice_id: InvoiceId) -> RepoResultV2<Vec<PaymentAttempt>> {
+        debug!("list payment attempts of invoice {}.", invoice_id);
+        acl::check(&*self.acl, Resource::PaymentAttempt, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        PaymentAttemptsDsl::payment_attempts
+            .filter(PaymentAttemptsDsl::invoice_id.eq(invoice_id))
+            .order_by((PaymentAttemptsDsl::created_at.asc(), PaymentAttemptsDsl::id.asc()))
+            .get_results::<PaymentAttempt>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, PaymentAttempt>
+    for PaymentAttemptsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&PaymentAttempt>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
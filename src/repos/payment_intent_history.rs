@@ -0,0 +1,97 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::stripe::PaymentIntentId;
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::{NewPaymentIntentHistoryEntry, PaymentIntentHistoryEntry};
+use repos::legacy_acl::*;
+
+use schema::payment_intent_history::dsl as PaymentIntentHistoryDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type PaymentIntentHistoryRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, PaymentIntentHistoryEntry>>;
+
+pub struct PaymentIntentHistoryRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: PaymentIntentHistoryRepoAcl,
+}
+
+pub trait PaymentIntentHistoryRepo {
+    fn create(&self, payload: NewPaymentIntentHistoryEntry) -> RepoResultV2<PaymentIntentHistoryEntry>;
+    /// Status transitions of the payment intent, oldest first
+    fn list_by_payment_intent_id(&self, payment_intent_id: PaymentIntentId) -> RepoResultV2<Vec<PaymentIntentHistoryEntry>>;
+    fn get_latest_by_payment_intent_id(&self, payment_intent_id: PaymentIntentId) -> RepoResultV2<Option<PaymentIntentHistoryEntry>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PaymentIntentHistoryRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: PaymentIntentHistoryRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PaymentIntentHistoryRepo
+    for PaymentIntentHistoryRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewPaymentIntentHistoryEntry) -> RepoResultV2<PaymentIntentHistoryEntry> {
+        debug!("create payment intent history entry {:?}.", payload);
+        acl::check(&*self.acl, Resource::PaymentIntentHistory, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(PaymentIntentHistoryDsl::payment_intent_history).values(&payload);
+
+        command.get_result::<PaymentIntentHistoryEntry>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn list_by_payment_intent_id(&self, payment_intent_id: PaymentIntentId) -> RepoResultV2<Vec<PaymentIntentHistoryEntry>> {
+        debug!("list history of payment intent {}.", payment_intent_id);
+        acl::check(&*self.acl, Resource::PaymentIntentHistory, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        PaymentIntentHistoryDsl::payment_intent_history
+            .filter(PaymentIntentHistoryDsl::payment_intent_id.eq(payment_intent_id))
+            .order_by((PaymentIntentHistoryDsl::created_at.asc(), PaymentIntentHistoryDsl::id.asc()))
+            .get_results::<PaymentIntentHistoryEntry>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn get_latest_by_payment_intent_id(&self, payment_intent_id: PaymentIntentId) -> RepoResultV2<Option<PaymentIntentHistoryEntry>> {
+        debug!("get latest history entry of payment intent {}.", payment_intent_id);
+        acl::check(&*self.acl, Resource::PaymentIntentHistory, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        PaymentIntentHistoryDsl::payment_intent_history
+            .filter(PaymentIntentHistoryDsl::payment_intent_id.eq(payment_intent_id))
+            .order_by((PaymentIntentHistoryDsl::created_at.desc(), PaymentIntentHistoryDsl::id.desc()))
+            .first::<PaymentIntentHistoryEntry>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, PaymentIntentHistoryEntry>
+    for PaymentIntentHistoryRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&PaymentIntentHistoryEntry>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
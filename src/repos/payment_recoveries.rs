@@ -0,0 +1,159 @@
+use chrono::NaiveDateTime;
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::invoice_v2::InvoiceId;
+use models::{NewPaymentRecovery, PaymentRecovery, PaymentRecoveryId, UpdatePaymentRecovery};
+use repos::legacy_acl::*;
+
+use schema::payment_recoveries::dsl as PaymentRecoveriesDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type PaymentRecoveriesRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, PaymentRecovery>>;
+
+pub struct PaymentRecoveriesRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: PaymentRecoveriesRepoAcl,
+}
+
+pub trait PaymentRecoveriesRepo {
+    fn create(&self, payload: NewPaymentRecovery) -> RepoResultV2<PaymentRecovery>;
+    fn get(&self, id: PaymentRecoveryId) -> RepoResultV2<Option<PaymentRecovery>>;
+    fn get_by_invoice_id(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<PaymentRecovery>>;
+    fn update(&self, id: PaymentRecoveryId, payload: UpdatePaymentRecovery) -> RepoResultV2<PaymentRecovery>;
+    fn search(&self, created_from: Option<NaiveDateTime>, created_to: Option<NaiveDateTime>) -> RepoResultV2<Vec<PaymentRecovery>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PaymentRecoveriesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: PaymentRecoveriesRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PaymentRecoveriesRepo
+    for PaymentRecoveriesRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewPaymentRecovery) -> RepoResultV2<PaymentRecovery> {
+        debug!("create payment recovery of invoice {}.", payload.invoice_id);
+
+        let command = diesel::insert_into(PaymentRecoveriesDsl::payment_recoveries).values(&payload);
+
+        let recovery = command.get_result::<PaymentRecovery>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        acl::check(&*self.acl, Resource::PaymentRecovery, Action::Write, self, Some(&recovery)).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        Ok(recovery)
+    }
+
+    fn get(&self, id: PaymentRecoveryId) -> RepoResultV2<Option<PaymentRecovery>> {
+        debug!("get payment recovery by id {}.", id);
+
+        let recovery = PaymentRecoveriesDsl::payment_recoveries
+            .filter(PaymentRecoveriesDsl::id.eq(id))
+            .get_result::<PaymentRecovery>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        if let Some(ref recovery) = recovery {
+            acl::check(&*self.acl, Resource::PaymentRecovery, Action::Read, self, Some(recovery))
+                .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(recovery)
+    }
+
+    fn get_by_invoice_id(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<PaymentRecovery>> {
+        debug!("get payment recovery by invoice id {}.", invoice_id);
+
+        let recovery = PaymentRecoveriesDsl::payment_recoveries
+            .filter(PaymentRecoveriesDsl::invoice_id.eq(invoice_id))
+            .get_result::<PaymentRecovery>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        if let Some(ref recovery) = recovery {
+            acl::check(&*self.acl, Resource::PaymentRecovery, Action::Read, self, Some(recovery))
+                .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(recovery)
+    }
+
+    fn update(&self, id: PaymentRecoveryId, payload: UpdatePaymentRecovery) -> RepoResultV2<PaymentRecovery> {
+        debug!("update payment recovery {}: {:?}.", id, payload);
+
+        let recovery = PaymentRecoveriesDsl::payment_recoveries
+            .filter(PaymentRecoveriesDsl::id.eq(id))
+            .get_result::<PaymentRecovery>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        acl::check(&*self.acl, Resource::PaymentRecovery, Action::Write, self, Some(&recovery)).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let filter = PaymentRecoveriesDsl::payment_recoveries.filter(PaymentRecoveriesDsl::id.eq(id));
+
+        diesel::update(filter)
+            .set(&payload)
+            .get_result::<PaymentRecovery>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn search(&self, created_from: Option<NaiveDateTime>, created_to: Option<NaiveDateTime>) -> RepoResultV2<Vec<PaymentRecovery>> {
+        debug!("search payment recoveries created from {:?} to {:?}.", created_from, created_to);
+        acl::check(&*self.acl, Resource::PaymentRecovery, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let mut query = PaymentRecoveriesDsl::payment_recoveries
+            .order_by(PaymentRecoveriesDsl::created_at.desc())
+            .into_boxed();
+
+        if let Some(created_from) = created_from {
+            query = query.filter(PaymentRecoveriesDsl::created_at.ge(created_from));
+        }
+
+        if let Some(created_to) = created_to {
+            query = query.filter(PaymentRecoveriesDsl::created_at.lt(created_to));
+        }
+
+        query.get_results::<PaymentRecovery>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, PaymentRecovery>
+    for PaymentRecoveriesRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, user_id: UserId, scope: &Scope, obj: Option<&PaymentRecovery>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => obj.map(|recovery| recovery.buyer_user_id.inner() == user_id.0).unwrap_or(false),
+        }
+    }
+}
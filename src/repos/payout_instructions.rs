@@ -0,0 +1,177 @@
+use std::collections::HashSet;
+
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::sql_types::Bool;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{StoreId, UserId};
+
+use models::authorization::*;
+use models::{NewPayoutInstruction, PayoutInstruction, PayoutInstructionSearch, UserRole};
+use repos::legacy_acl::*;
+
+use schema::payout_instructions::dsl as PayoutInstructionsDsl;
+use schema::roles::dsl as UserRolesDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type PayoutInstructionsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, PayoutInstructionAccess>>;
+
+type BoxedExpr = Box<BoxableExpression<crate::schema::payout_instructions::table, Pg, SqlType = Bool>>;
+
+pub struct PayoutInstructionAccess {
+    store_id: StoreId,
+}
+
+pub struct PayoutInstructionsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: PayoutInstructionsRepoAcl,
+}
+
+pub trait PayoutInstructionsRepo {
+    fn create(&self, payload: NewPayoutInstruction) -> RepoResultV2<PayoutInstruction>;
+    fn get(&self, search: PayoutInstructionSearch) -> RepoResultV2<Option<PayoutInstruction>>;
+    fn search(&self, search: PayoutInstructionSearch) -> RepoResultV2<Vec<PayoutInstruction>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PayoutInstructionsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: PayoutInstructionsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+
+    fn check_access(&self, action: Action, store_id: StoreId) -> RepoResultV2<()> {
+        acl::check(
+            &*self.acl,
+            Resource::PayoutInstruction,
+            action,
+            self,
+            Some(&PayoutInstructionAccess { store_id }),
+        )
+        .map_err(ectx!(ErrorKind::Forbidden))
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PayoutInstructionsRepo
+    for PayoutInstructionsRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewPayoutInstruction) -> RepoResultV2<PayoutInstruction> {
+        debug!("create payout instruction {:?}.", payload);
+        self.check_access(Action::Write, payload.store_id)?;
+
+        let command = diesel::insert_into(PayoutInstructionsDsl::payout_instructions).values(&payload);
+
+        command.get_result::<PayoutInstruction>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get(&self, search: PayoutInstructionSearch) -> RepoResultV2<Option<PayoutInstruction>> {
+        debug!("get payout instruction {:?}.", search);
+
+        let query: Option<BoxedExpr> = into_expr(search);
+
+        let query = query.ok_or_else(|| {
+            let e = format_err!("payout instruction search is empty");
+            ectx!(try err e, ErrorKind::Internal)
+        })?;
+
+        let payout_instruction = crate::schema::payout_instructions::table
+            .filter(query)
+            .get_result::<PayoutInstruction>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        if let Some(ref payout_instruction) = payout_instruction {
+            self.check_access(Action::Read, payout_instruction.store_id)?;
+        }
+
+        Ok(payout_instruction)
+    }
+
+    fn search(&self, search: PayoutInstructionSearch) -> RepoResultV2<Vec<PayoutInstruction>> {
+        debug!("search payout instructions {:?}.", search);
+
+        let query: BoxedExpr = into_expr(search).unwrap_or(Box::new(true.into_sql::<Bool>()));
+
+        let payout_instructions = crate::schema::payout_instructions::table
+            .filter(query)
+            .order_by(PayoutInstructionsDsl::created_at.desc())
+            .get_results::<PayoutInstruction>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        let store_ids: HashSet<StoreId> = payout_instructions.iter().map(|i| i.store_id).collect();
+
+        for store_id in store_ids {
+            self.check_access(Action::Read, store_id)?;
+        }
+
+        Ok(payout_instructions)
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, PayoutInstructionAccess>
+    for PayoutInstructionsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, user_id: UserId, scope: &Scope, obj: Option<&PayoutInstructionAccess>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => {
+                if let Some(PayoutInstructionAccess { store_id }) = obj {
+                    UserRolesDsl::roles
+                        .filter(UserRolesDsl::user_id.eq(user_id))
+                        .get_results::<UserRole>(self.db_conn)
+                        .map_err(From::from)
+                        .map(|user_roles_arg| {
+                            user_roles_arg
+                                .iter()
+                                .any(|user_role_arg| user_role_arg.data.clone().map(|data| data == store_id.0).unwrap_or_default())
+                        })
+                        .unwrap_or_else(|_: FailureError| false)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+fn into_expr(search: PayoutInstructionSearch) -> Option<BoxedExpr> {
+    let mut query: Option<BoxedExpr> = None;
+
+    let PayoutInstructionSearch { id, store_id } = search;
+
+    if let Some(id_filter) = id {
+        let new_condition = PayoutInstructionsDsl::id.eq(id_filter);
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
+    if let Some(store_id_filter) = store_id {
+        let new_condition = PayoutInstructionsDsl::store_id.eq(store_id_filter);
+        query = Some(and(query, Box::new(new_condition)));
+    }
+
+    query
+}
+
+fn and(old_condition: Option<BoxedExpr>, new_condition: BoxedExpr) -> BoxedExpr {
+    if let Some(old_condition) = old_condition {
+        Box::new(old_condition.and(new_condition))
+    } else {
+        new_condition
+    }
+}
@@ -0,0 +1,105 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::upsert::excluded;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{StoreId, UserId};
+
+use models::authorization::*;
+use models::{NewPayoutRestrictionOverride, PayoutRestrictionOverride};
+use repos::legacy_acl::*;
+
+use schema::payout_restriction_overrides::dsl as PayoutRestrictionOverridesDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type PayoutRestrictionOverridesRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, PayoutRestrictionOverride>>;
+
+pub struct PayoutRestrictionOverridesRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: PayoutRestrictionOverridesRepoAcl,
+}
+
+pub trait PayoutRestrictionOverridesRepo {
+    fn get(&self, store_id: StoreId) -> RepoResultV2<Option<PayoutRestrictionOverride>>;
+    /// Grants the override or replaces the reason of the one the store already has
+    fn upsert(&self, payload: NewPayoutRestrictionOverride) -> RepoResultV2<PayoutRestrictionOverride>;
+    fn delete(&self, store_id: StoreId) -> RepoResultV2<Option<PayoutRestrictionOverride>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PayoutRestrictionOverridesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: PayoutRestrictionOverridesRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PayoutRestrictionOverridesRepo
+    for PayoutRestrictionOverridesRepoImpl<'a, T>
+{
+    fn get(&self, store_id: StoreId) -> RepoResultV2<Option<PayoutRestrictionOverride>> {
+        debug!("get payout restriction override of store {}.", store_id);
+        acl::check(&*self.acl, Resource::PayoutRestriction, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        PayoutRestrictionOverridesDsl::payout_restriction_overrides
+            .filter(PayoutRestrictionOverridesDsl::store_id.eq(store_id))
+            .get_result::<PayoutRestrictionOverride>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn upsert(&self, payload: NewPayoutRestrictionOverride) -> RepoResultV2<PayoutRestrictionOverride> {
+        debug!("upsert payout restriction override {:?}.", payload);
+        acl::check(&*self.acl, Resource::PayoutRestriction, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(PayoutRestrictionOverridesDsl::payout_restriction_overrides)
+            .values(&payload)
+            .on_conflict(PayoutRestrictionOverridesDsl::store_id)
+            .do_update()
+            .set((
+                PayoutRestrictionOverridesDsl::reason.eq(excluded(PayoutRestrictionOverridesDsl::reason)),
+                PayoutRestrictionOverridesDsl::granted_by.eq(excluded(PayoutRestrictionOverridesDsl::granted_by)),
+            ));
+
+        command.get_result::<PayoutRestrictionOverride>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn delete(&self, store_id: StoreId) -> RepoResultV2<Option<PayoutRestrictionOverride>> {
+        debug!("delete payout restriction override of store {}.", store_id);
+        acl::check(&*self.acl, Resource::PayoutRestriction, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let filter =
+            PayoutRestrictionOverridesDsl::payout_restriction_overrides.filter(PayoutRestrictionOverridesDsl::store_id.eq(store_id));
+
+        diesel::delete(filter)
+            .get_result::<PayoutRestrictionOverride>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind => store_id)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, PayoutRestrictionOverride>
+    for PayoutRestrictionOverridesRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&PayoutRestrictionOverride>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
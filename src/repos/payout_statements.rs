@@ -0,0 +1,138 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::{expression::dsl::any, Pg};
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::{NewPayoutStatement, PayoutId, PayoutStatement, PayoutStatementAccess, PayoutStatementId};
+use repos::legacy_acl::*;
+
+use schema::payout_statements::dsl as PayoutStatementsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type PayoutStatementsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, PayoutStatementAccess>>;
+
+pub struct PayoutStatementsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: PayoutStatementsRepoAcl,
+}
+
+pub trait PayoutStatementsRepo {
+    fn create(&self, payload: NewPayoutStatement) -> RepoResultV2<PayoutStatement>;
+    fn get(&self, id: PayoutStatementId) -> RepoResultV2<Option<PayoutStatement>>;
+    /// Statements of the payouts, payouts made before statements were introduced have none
+    fn get_by_payout_ids(&self, payout_ids: &[PayoutId]) -> RepoResultV2<Vec<PayoutStatement>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PayoutStatementsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: PayoutStatementsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> PayoutStatementsRepo
+    for PayoutStatementsRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewPayoutStatement) -> RepoResultV2<PayoutStatement> {
+        debug!("create payout statement {:?}.", payload);
+        acl::check(
+            &*self.acl,
+            Resource::PayoutStatement,
+            Action::Write,
+            self,
+            Some(&PayoutStatementAccess { user_id: payload.user_id }),
+        )
+        .map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(PayoutStatementsDsl::payout_statements).values(&payload);
+
+        command.get_result::<PayoutStatement>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get(&self, id: PayoutStatementId) -> RepoResultV2<Option<PayoutStatement>> {
+        debug!("get payout statement {}.", id);
+
+        let payout_statement = PayoutStatementsDsl::payout_statements
+            .filter(PayoutStatementsDsl::id.eq(id))
+            .get_result::<PayoutStatement>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        if let Some(ref payout_statement) = payout_statement {
+            acl::check(
+                &*self.acl,
+                Resource::PayoutStatement,
+                Action::Read,
+                self,
+                Some(&PayoutStatementAccess::from(payout_statement)),
+            )
+            .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(payout_statement)
+    }
+
+    fn get_by_payout_ids(&self, payout_ids: &[PayoutId]) -> RepoResultV2<Vec<PayoutStatement>> {
+        debug!("get payout statements of payouts {:?}.", payout_ids);
+
+        if payout_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let payout_statements = PayoutStatementsDsl::payout_statements
+            .filter(PayoutStatementsDsl::payout_id.eq(any(payout_ids)))
+            .get_results::<PayoutStatement>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        for payout_statement in &payout_statements {
+            acl::check(
+                &*self.acl,
+                Resource::PayoutStatement,
+                Action::Read,
+                self,
+                Some(&PayoutStatementAccess::from(payout_statement)),
+            )
+            .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(payout_statements)
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, PayoutStatementAccess>
+    for PayoutStatementsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, user_id: UserId, scope: &Scope, obj: Option<&PayoutStatementAccess>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => {
+                if let Some(PayoutStatementAccess {
+                    user_id: statement_user_id,
+                }) = obj
+                {
+                    statement_user_id.inner() == user_id.0
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
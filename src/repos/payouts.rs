@@ -26,6 +26,8 @@ pub trait PayoutsRepo {
     fn get(&self, id: PayoutId) -> RepoResultV2<Option<Payout>>;
     fn get_by_order_id(&self, order_id: OrderId) -> RepoResultV2<Option<Payout>>;
     fn get_by_order_ids(&self, order_ids: &[OrderId]) -> RepoResultV2<PayoutsByOrderIds>;
+    /// Payout created by the request of the user with the client-supplied `request_id`
+    fn get_by_request_id(&self, user_id: UserId, request_id: PayoutRequestId) -> RepoResultV2<Option<Payout>>;
     fn mark_as_completed(&self, id: PayoutId) -> RepoResultV2<Payout>;
 }
 
@@ -276,6 +278,33 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
 
         Ok(payouts_by_order_ids)
     }
+
+    fn get_by_request_id(&self, user_id: UserId, request_id: PayoutRequestId) -> RepoResultV2<Option<Payout>> {
+        debug!("Getting a payout of user {} by request ID: {}", user_id, request_id);
+
+        let payout_id = Payouts::payouts
+            .filter(Payouts::user_id.eq(user_id))
+            .filter(Payouts::request_id.eq(request_id))
+            .select(Payouts::id)
+            .get_result::<PayoutId>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        let payout = match payout_id {
+            None => return Ok(None),
+            Some(payout_id) => self.get_payout_by_id(payout_id)?,
+        };
+
+        match payout {
+            None => Ok(None),
+            Some(payout) => acl::check(&*self.acl, Resource::Payout, Action::Read, self, Some(&PayoutAccess::from(&payout)))
+                .map(|_| Some(payout))
+                .map_err(ectx!(ErrorKind::Forbidden)),
+        }
+    }
 }
 
 impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, PayoutAccess>
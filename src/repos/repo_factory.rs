@@ -21,6 +21,7 @@ where
     fn create_user_roles_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<UserRolesRepo + 'a>;
     fn create_user_roles_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<UserRolesRepo + 'a>;
     fn create_accounts_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<AccountsRepo + 'a>;
+    fn create_accounts_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<AccountsRepo + 'a>;
     fn create_invoices_v2_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<InvoicesV2Repo + 'a>;
     fn create_invoices_v2_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<InvoicesV2Repo + 'a>;
     fn create_orders_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<OrdersRepo + 'a>;
@@ -30,6 +31,8 @@ where
     fn create_event_store_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<EventStoreRepo + 'a>;
     fn create_payment_intent_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PaymentIntentRepo + 'a>;
     fn create_payment_intent_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<PaymentIntentRepo + 'a>;
+    fn create_payment_intent_history_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PaymentIntentHistoryRepo + 'a>;
+    fn create_payment_intent_history_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<PaymentIntentHistoryRepo + 'a>;
     fn create_customers_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<CustomersRepo + 'a>;
     fn create_customers_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<CustomersRepo + 'a>;
     fn create_fees_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<FeeRepo + 'a>;
@@ -53,6 +56,8 @@ where
     fn create_proxy_companies_billing_info_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<ProxyCompanyBillingInfoRepo + 'a>;
     fn create_user_wallets_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<UserWalletsRepo + 'a>;
     fn create_user_wallets_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<UserWalletsRepo + 'a>;
+    fn create_wallet_verifications_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<WalletVerificationsRepo + 'a>;
+    fn create_wallet_verifications_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<WalletVerificationsRepo + 'a>;
     fn create_payouts_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PayoutsRepo + 'a>;
     fn create_payouts_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<PayoutsRepo + 'a>;
     fn create_subscription_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<SubscriptionRepo + 'a>;
@@ -61,6 +66,82 @@ where
     fn create_store_subscription_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<StoreSubscriptionRepo + 'a>;
     fn create_subscription_payment_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<SubscriptionPaymentRepo + 'a>;
     fn create_subscription_payment_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<SubscriptionPaymentRepo + 'a>;
+    fn create_fee_statements_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<FeeStatementsRepo + 'a>;
+    fn create_fee_statements_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<FeeStatementsRepo + 'a>;
+    fn create_fee_adjustments_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<FeeAdjustmentsRepo + 'a>;
+    fn create_fee_adjustments_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<FeeAdjustmentsRepo + 'a>;
+    fn create_fee_crypto_payments_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<FeeCryptoPaymentsRepo + 'a>;
+    fn create_fee_crypto_payments_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<FeeCryptoPaymentsRepo + 'a>;
+    fn create_audit_log_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<AuditLogRepo + 'a>;
+    fn create_audit_log_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<AuditLogRepo + 'a>;
+    fn create_cashback_liabilities_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<CashbackLiabilitiesRepo + 'a>;
+    fn create_cashback_liabilities_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<CashbackLiabilitiesRepo + 'a>;
+    fn create_negative_store_balances_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<NegativeStoreBalancesRepo + 'a>;
+    fn create_negative_store_balances_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<NegativeStoreBalancesRepo + 'a>;
+    fn create_exchange_rate_slippages_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<ExchangeRateSlippagesRepo + 'a>;
+    fn create_exchange_rate_slippages_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<ExchangeRateSlippagesRepo + 'a>;
+    fn create_payout_statements_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PayoutStatementsRepo + 'a>;
+    fn create_payout_statements_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<PayoutStatementsRepo + 'a>;
+    fn create_schema_migrations_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<SchemaMigrationsRepo + 'a>;
+    fn create_schema_migrations_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<SchemaMigrationsRepo + 'a>;
+    fn create_billing_info_changes_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<BillingInfoChangesRepo + 'a>;
+    fn create_billing_info_changes_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<BillingInfoChangesRepo + 'a>;
+    fn create_payment_attempts_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PaymentAttemptsRepo + 'a>;
+    fn create_payment_attempts_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<PaymentAttemptsRepo + 'a>;
+    fn create_account_pool_claims_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<AccountPoolClaimsRepo + 'a>;
+    fn create_store_webhooks_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<StoreWebhooksRepo + 'a>;
+    fn create_store_webhooks_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<StoreWebhooksRepo + 'a>;
+    fn create_api_keys_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<ApiKeysRepo + 'a>;
+    fn create_api_keys_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<ApiKeysRepo + 'a>;
+    fn create_data_retention_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<DataRetentionRepo + 'a>;
+    fn create_data_retention_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<DataRetentionRepo + 'a>;
+    fn create_invoice_snapshots_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<InvoiceSnapshotsRepo + 'a>;
+    fn create_invoice_snapshots_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<InvoiceSnapshotsRepo + 'a>;
+    fn create_fee_dunnings_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<FeeDunningsRepo + 'a>;
+    fn create_fee_dunnings_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<FeeDunningsRepo + 'a>;
+    fn create_payout_restriction_overrides_repo<'a>(
+        &self,
+        db_conn: &'a C,
+        user_id: Option<UserId>,
+    ) -> Box<PayoutRestrictionOverridesRepo + 'a>;
+    fn create_payout_restriction_overrides_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<PayoutRestrictionOverridesRepo + 'a>;
+    fn create_customer_exports_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<CustomerExportsRepo + 'a>;
+    fn create_customer_exports_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<CustomerExportsRepo + 'a>;
+    fn create_invoice_risk_assessments_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<InvoiceRiskAssessmentsRepo + 'a>;
+    fn create_invoice_risk_assessments_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<InvoiceRiskAssessmentsRepo + 'a>;
+    fn create_store_offboardings_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<StoreOffboardingsRepo + 'a>;
+    fn create_store_offboardings_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<StoreOffboardingsRepo + 'a>;
+    fn create_historical_reports_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<HistoricalReportsRepo + 'a>;
+    fn create_historical_reports_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<HistoricalReportsRepo + 'a>;
+    fn create_user_credits_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<UserCreditsRepo + 'a>;
+    fn create_user_credits_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<UserCreditsRepo + 'a>;
+    fn create_store_billing_statuses_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<StoreBillingStatusesRepo + 'a>;
+    fn create_store_billing_statuses_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<StoreBillingStatusesRepo + 'a>;
+    fn create_payout_instructions_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PayoutInstructionsRepo + 'a>;
+    fn create_payout_instructions_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<PayoutInstructionsRepo + 'a>;
+    fn create_stripe_fee_backfills_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<StripeFeeBackfillsRepo + 'a>;
+    fn create_stripe_fee_backfills_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<StripeFeeBackfillsRepo + 'a>;
+    fn create_payment_recoveries_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PaymentRecoveriesRepo + 'a>;
+    fn create_payment_recoveries_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<PaymentRecoveriesRepo + 'a>;
+    fn create_order_capture_approvals_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<OrderCaptureApprovalsRepo + 'a>;
+    fn create_order_capture_approvals_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<OrderCaptureApprovalsRepo + 'a>;
+    fn create_order_line_items_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<OrderLineItemsRepo + 'a>;
+    fn create_order_line_items_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<OrderLineItemsRepo + 'a>;
+    fn create_invoice_buyer_details_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<InvoiceBuyerDetailsRepo + 'a>;
+    fn create_invoice_buyer_details_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<InvoiceBuyerDetailsRepo + 'a>;
+    fn create_invoice_callbacks_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<InvoiceCallbacksRepo + 'a>;
+    fn create_invoice_callbacks_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<InvoiceCallbacksRepo + 'a>;
+    fn create_invoice_requotes_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<InvoiceRequotesRepo + 'a>;
+    fn create_invoice_requotes_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<InvoiceRequotesRepo + 'a>;
+    fn create_account_assignments_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<AccountAssignmentsRepo + 'a>;
+    fn create_account_assignments_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<AccountAssignmentsRepo + 'a>;
+    fn create_feature_flags_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<FeatureFlagsRepo + 'a>;
+    fn create_feature_flags_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<FeatureFlagsRepo + 'a>;
+    /// Factory serving a request made with an API key: repos created for a user check the permissions
+    /// of the key scopes instead of the roles of the user
+    fn with_api_key_scopes(&self, scopes: Vec<ApiKeyScope>) -> Self;
+    /// Roles the requests of the user act with, a request made with an API key acts as a store manager
+    fn get_caller_roles<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Vec<BillingRole>;
 }
 
 pub struct ReposFactoryImpl<C1>
@@ -68,8 +149,12 @@ where
     C1: Cache<Vec<BillingRole>>,
 {
     roles_cache: Arc<RolesCacheImpl<C1>>,
+    feature_flags_cache: Arc<FeatureFlagsCache>,
     max_processing_attempts: u32,
     stuck_threshold_sec: u32,
+    instance_id: String,
+    cipher: FieldCipher,
+    api_key_scopes: Option<Arc<Vec<ApiKeyScope>>>,
 }
 
 impl<C1> Clone for ReposFactoryImpl<C1>
@@ -79,8 +164,12 @@ where
     fn clone(&self) -> Self {
         Self {
             roles_cache: self.roles_cache.clone(),
+            feature_flags_cache: self.feature_flags_cache.clone(),
             max_processing_attempts: self.max_processing_attempts.clone(),
             stuck_threshold_sec: self.stuck_threshold_sec.clone(),
+            instance_id: self.instance_id.clone(),
+            cipher: self.cipher.clone(),
+            api_key_scopes: self.api_key_scopes.clone(),
         }
     }
 }
@@ -89,11 +178,22 @@ impl<C1> ReposFactoryImpl<C1>
 where
     C1: Cache<Vec<BillingRole>> + Send + Sync + 'static,
 {
-    pub fn new(roles_cache: RolesCacheImpl<C1>, max_processing_attempts: u32, stuck_threshold_sec: u32) -> Self {
+    pub fn new(
+        roles_cache: RolesCacheImpl<C1>,
+        feature_flags_cache: FeatureFlagsCache,
+        max_processing_attempts: u32,
+        stuck_threshold_sec: u32,
+        instance_id: String,
+        cipher: FieldCipher,
+    ) -> Self {
         Self {
             roles_cache: Arc::new(roles_cache),
+            feature_flags_cache: Arc::new(feature_flags_cache),
             max_processing_attempts,
             stuck_threshold_sec,
+            instance_id,
+            cipher,
+            api_key_scopes: None,
         }
     }
 
@@ -115,9 +215,12 @@ where
     ) -> Box<Acl<Resource, Action, Scope, FailureError, T>> {
         user_id.map_or(
             Box::new(UnauthorizedACL::default()) as Box<Acl<Resource, Action, Scope, FailureError, T>>,
-            |id| {
-                let roles = self.get_roles(id, db_conn);
-                (Box::new(ApplicationAcl::new(roles, id)) as Box<Acl<Resource, Action, Scope, FailureError, T>>)
+            |id| match self.api_key_scopes {
+                Some(ref scopes) => Box::new(ApplicationAcl::for_api_key(scopes, id)) as Box<Acl<Resource, Action, Scope, FailureError, T>>,
+                None => {
+                    let roles = self.get_roles(id, db_conn);
+                    (Box::new(ApplicationAcl::new(roles, id)) as Box<Acl<Resource, Action, Scope, FailureError, T>>)
+                }
             },
         )
     }
@@ -172,6 +275,11 @@ where
         )) as Box<AccountsRepo>
     }
 
+    fn create_accounts_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<AccountsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(AccountsRepoImpl::new(db_conn, acl)) as Box<AccountsRepo>
+    }
+
     fn create_invoices_v2_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<InvoicesV2Repo + 'a> {
         Box::new(InvoicesV2RepoImpl::new(db_conn, Box::new(SystemACL::default()))) as Box<InvoicesV2Repo>
     }
@@ -204,6 +312,7 @@ where
             db_conn,
             self.max_processing_attempts,
             self.stuck_threshold_sec,
+            self.instance_id.clone(),
         )) as Box<EventStoreRepo>
     }
 
@@ -217,6 +326,16 @@ where
         Box::new(PaymentIntentRepoImpl::new(db_conn, acl))
     }
 
+    fn create_payment_intent_history_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PaymentIntentHistoryRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(PaymentIntentHistoryRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_payment_intent_history_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<PaymentIntentHistoryRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(PaymentIntentHistoryRepoImpl::new(db_conn, acl))
+    }
+
     fn create_customers_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<CustomersRepo + 'a> {
         let acl = self.get_acl(db_conn, user_id);
         Box::new(CustomersRepoImpl::new(db_conn, acl))
@@ -253,22 +372,22 @@ where
         user_id: Option<UserId>,
     ) -> Box<InternationalBillingInfoRepo + 'a> {
         let acl = self.get_acl(db_conn, user_id);
-        Box::new(InternationalBillingInfoRepoImpl::new(db_conn, acl))
+        Box::new(InternationalBillingInfoRepoImpl::new(db_conn, acl, self.cipher.clone()))
     }
 
     fn create_international_billing_repo_info_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<InternationalBillingInfoRepo + 'a> {
         let acl = Box::new(SystemACL::default());
-        Box::new(InternationalBillingInfoRepoImpl::new(db_conn, acl))
+        Box::new(InternationalBillingInfoRepoImpl::new(db_conn, acl, self.cipher.clone()))
     }
 
     fn create_russia_billing_info_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<RussiaBillingInfoRepo + 'a> {
         let acl = self.get_acl(db_conn, user_id);
-        Box::new(RussiaBillingInfoRepoImpl::new(db_conn, acl))
+        Box::new(RussiaBillingInfoRepoImpl::new(db_conn, acl, self.cipher.clone()))
     }
 
     fn create_russia_billing_info_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<RussiaBillingInfoRepo + 'a> {
         let acl = Box::new(SystemACL::default());
-        Box::new(RussiaBillingInfoRepoImpl::new(db_conn, acl))
+        Box::new(RussiaBillingInfoRepoImpl::new(db_conn, acl, self.cipher.clone()))
     }
 
     fn create_proxy_companies_billing_info_repo<'a>(
@@ -315,6 +434,16 @@ where
         Box::new(UserWalletsRepoImpl::new(db_conn, acl))
     }
 
+    fn create_wallet_verifications_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<WalletVerificationsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(WalletVerificationsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_wallet_verifications_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<WalletVerificationsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(WalletVerificationsRepoImpl::new(db_conn, acl))
+    }
+
     fn create_payouts_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PayoutsRepo + 'a> {
         let acl = self.get_acl(db_conn, user_id);
         Box::new(PayoutsRepoImpl::new(db_conn, acl))
@@ -354,6 +483,360 @@ where
         let acl = Box::new(SystemACL::default());
         Box::new(SubscriptionPaymentRepoImpl::new(db_conn, acl))
     }
+
+    fn create_fee_statements_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<FeeStatementsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(FeeStatementsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_fee_statements_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<FeeStatementsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(FeeStatementsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_fee_adjustments_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<FeeAdjustmentsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(FeeAdjustmentsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_fee_adjustments_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<FeeAdjustmentsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(FeeAdjustmentsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_fee_crypto_payments_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<FeeCryptoPaymentsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(FeeCryptoPaymentsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_fee_crypto_payments_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<FeeCryptoPaymentsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(FeeCryptoPaymentsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_audit_log_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<AuditLogRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(AuditLogRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_audit_log_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<AuditLogRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(AuditLogRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_cashback_liabilities_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<CashbackLiabilitiesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(CashbackLiabilitiesRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_cashback_liabilities_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<CashbackLiabilitiesRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(CashbackLiabilitiesRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_negative_store_balances_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<NegativeStoreBalancesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(NegativeStoreBalancesRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_negative_store_balances_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<NegativeStoreBalancesRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(NegativeStoreBalancesRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_exchange_rate_slippages_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<ExchangeRateSlippagesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(ExchangeRateSlippagesRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_exchange_rate_slippages_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<ExchangeRateSlippagesRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(ExchangeRateSlippagesRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_payout_statements_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PayoutStatementsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(PayoutStatementsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_payout_statements_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<PayoutStatementsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(PayoutStatementsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_schema_migrations_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<SchemaMigrationsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(SchemaMigrationsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_schema_migrations_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<SchemaMigrationsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(SchemaMigrationsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_billing_info_changes_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<BillingInfoChangesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(BillingInfoChangesRepoImpl::new(db_conn, acl, self.cipher.clone()))
+    }
+
+    fn create_billing_info_changes_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<BillingInfoChangesRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(BillingInfoChangesRepoImpl::new(db_conn, acl, self.cipher.clone()))
+    }
+
+    fn create_payment_attempts_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PaymentAttemptsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(PaymentAttemptsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_payment_attempts_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<PaymentAttemptsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(PaymentAttemptsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_account_pool_claims_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<AccountPoolClaimsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(AccountPoolClaimsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_store_webhooks_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<StoreWebhooksRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(StoreWebhooksRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_store_webhooks_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<StoreWebhooksRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(StoreWebhooksRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_api_keys_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<ApiKeysRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(ApiKeysRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_api_keys_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<ApiKeysRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(ApiKeysRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_data_retention_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<DataRetentionRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(DataRetentionRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_data_retention_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<DataRetentionRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(DataRetentionRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_invoice_snapshots_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<InvoiceSnapshotsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(InvoiceSnapshotsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_invoice_snapshots_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<InvoiceSnapshotsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(InvoiceSnapshotsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_fee_dunnings_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<FeeDunningsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(FeeDunningsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_fee_dunnings_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<FeeDunningsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(FeeDunningsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_payout_restriction_overrides_repo<'a>(
+        &self,
+        db_conn: &'a C,
+        user_id: Option<UserId>,
+    ) -> Box<PayoutRestrictionOverridesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(PayoutRestrictionOverridesRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_payout_restriction_overrides_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<PayoutRestrictionOverridesRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(PayoutRestrictionOverridesRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_customer_exports_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<CustomerExportsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(CustomerExportsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_customer_exports_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<CustomerExportsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(CustomerExportsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_invoice_risk_assessments_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<InvoiceRiskAssessmentsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(InvoiceRiskAssessmentsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_invoice_risk_assessments_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<InvoiceRiskAssessmentsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(InvoiceRiskAssessmentsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_store_offboardings_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<StoreOffboardingsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(StoreOffboardingsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_store_offboardings_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<StoreOffboardingsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(StoreOffboardingsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_historical_reports_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<HistoricalReportsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(HistoricalReportsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_historical_reports_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<HistoricalReportsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(HistoricalReportsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_user_credits_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<UserCreditsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(UserCreditsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_user_credits_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<UserCreditsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(UserCreditsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_store_billing_statuses_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<StoreBillingStatusesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(StoreBillingStatusesRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_store_billing_statuses_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<StoreBillingStatusesRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(StoreBillingStatusesRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_payout_instructions_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PayoutInstructionsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(PayoutInstructionsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_payout_instructions_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<PayoutInstructionsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(PayoutInstructionsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_stripe_fee_backfills_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<StripeFeeBackfillsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(StripeFeeBackfillsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_stripe_fee_backfills_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<StripeFeeBackfillsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(StripeFeeBackfillsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_payment_recoveries_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<PaymentRecoveriesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(PaymentRecoveriesRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_payment_recoveries_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<PaymentRecoveriesRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(PaymentRecoveriesRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_order_capture_approvals_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<OrderCaptureApprovalsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(OrderCaptureApprovalsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_order_capture_approvals_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<OrderCaptureApprovalsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(OrderCaptureApprovalsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_order_line_items_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<OrderLineItemsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(OrderLineItemsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_order_line_items_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<OrderLineItemsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(OrderLineItemsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_invoice_buyer_details_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<InvoiceBuyerDetailsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(InvoiceBuyerDetailsRepoImpl::new(db_conn, acl, self.cipher.clone()))
+    }
+
+    fn create_invoice_buyer_details_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<InvoiceBuyerDetailsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(InvoiceBuyerDetailsRepoImpl::new(db_conn, acl, self.cipher.clone()))
+    }
+
+    fn create_invoice_callbacks_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<InvoiceCallbacksRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(InvoiceCallbacksRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_invoice_callbacks_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<InvoiceCallbacksRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(InvoiceCallbacksRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_invoice_requotes_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<InvoiceRequotesRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(InvoiceRequotesRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_invoice_requotes_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<InvoiceRequotesRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(InvoiceRequotesRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_account_assignments_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<AccountAssignmentsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(AccountAssignmentsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_account_assignments_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<AccountAssignmentsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(AccountAssignmentsRepoImpl::new(db_conn, acl))
+    }
+
+    fn create_feature_flags_repo<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Box<FeatureFlagsRepo + 'a> {
+        let acl = self.get_acl(db_conn, user_id);
+        Box::new(FeatureFlagsRepoImpl::new(db_conn, acl, self.feature_flags_cache.clone()))
+    }
+
+    fn create_feature_flags_repo_with_sys_acl<'a>(&self, db_conn: &'a C) -> Box<FeatureFlagsRepo + 'a> {
+        let acl = Box::new(SystemACL::default());
+        Box::new(FeatureFlagsRepoImpl::new(db_conn, acl, self.feature_flags_cache.clone()))
+    }
+
+    fn with_api_key_scopes(&self, scopes: Vec<ApiKeyScope>) -> Self {
+        Self {
+            api_key_scopes: Some(Arc::new(scopes)),
+            ..self.clone()
+        }
+    }
+
+    fn get_caller_roles<'a>(&self, db_conn: &'a C, user_id: Option<UserId>) -> Vec<BillingRole> {
+        match (user_id, &self.api_key_scopes) {
+            (None, _) => vec![],
+            (Some(_), Some(_)) => vec![BillingRole::StoreManager],
+            (Some(id), None) => self.get_roles(id, db_conn),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -370,10 +853,7 @@ pub mod tests {
 
     use futures::Future;
     use hyper::Headers;
-    use services::accounts::AccountService;
-    use services::types::ServiceFutureV2;
-    use std::error::Error;
-    use std::fmt;
+    use services::mock::MockAccountService;
     use std::sync::Arc;
     use std::time::SystemTime;
     use stq_http::client::HttpClient;
@@ -381,20 +861,10 @@ pub mod tests {
 
     use chrono::NaiveDateTime;
     use diesel::connection::AnsiTransactionManager;
-    use diesel::connection::SimpleConnection;
-    use diesel::deserialize::QueryableByName;
     use diesel::pg::Pg;
-    use diesel::query_builder::AsQuery;
-    use diesel::query_builder::QueryFragment;
-    use diesel::query_builder::QueryId;
-    use diesel::sql_types::HasSqlType;
     use diesel::Connection;
-    use diesel::ConnectionResult;
-    use diesel::QueryResult;
-    use diesel::Queryable;
     use futures::Stream;
     use futures_cpupool::CpuPool;
-    use r2d2::ManageConnection;
     use tokio_core::reactor::Handle;
     use uuid::Uuid;
 
@@ -410,12 +880,16 @@ pub mod tests {
     };
     use config::Config;
     use controller::context::{DynamicContext, StaticContext};
-    use models::invoice_v2::{InvoiceId as InvoiceV2Id, InvoiceSetAmountPaid, NewInvoice as NewInvoiceV2, RawInvoice as RawInvoiceV2};
+    use models::invoice_v2::{
+        InvoiceId as InvoiceV2Id, InvoiceSetAmountPaid, NewInvoice as NewInvoiceV2, RawAmountReceived, RawInvoice as RawInvoiceV2,
+        UpdateInvoiceDetails,
+    };
     use models::order_v2::{ExchangeId, NewOrder, OrderId as OrderV2Id, OrderSearchResults, OrdersSearch, RawOrder, StoreId as StoreV2Id};
     use models::{Currency as BillingCurrency, NewPaymentIntent, PaymentIntent, TransactionId, TureCurrency, UpdatePaymentIntent};
     use models::{PayoutId, *};
     use repos::*;
     use services::*;
+    pub use test_support::{MockConnection, MockConnectionManager};
 
     #[derive(Default, Copy, Clone)]
     pub struct ReposFactoryMock;
@@ -449,6 +923,10 @@ pub mod tests {
             Box::new(AccountsRepoMock::default())
         }
 
+        fn create_accounts_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<AccountsRepo + 'a> {
+            Box::new(AccountsRepoMock::default())
+        }
+
         fn create_invoices_v2_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InvoicesV2Repo + 'a> {
             Box::new(InvoicesV2RepoMock::default())
         }
@@ -473,127 +951,439 @@ pub mod tests {
             Box::new(OrderExchangeRatesRepoMock::default())
         }
 
-        fn create_event_store_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<EventStoreRepo + 'a> {
-            Box::new(EventStoreRepoMock::default())
+        fn create_event_store_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<EventStoreRepo + 'a> {
+            Box::new(EventStoreRepoMock::default())
+        }
+
+        fn create_payment_intent_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PaymentIntentRepo + 'a> {
+            Box::new(PaymentIntentRepoMock::default())
+        }
+
+        fn create_payment_intent_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PaymentIntentRepo + 'a> {
+            Box::new(PaymentIntentRepoMock::default())
+        }
+
+        fn create_payment_intent_history_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PaymentIntentHistoryRepo + 'a> {
+            Box::new(PaymentIntentHistoryRepoMock::default())
+        }
+
+        fn create_payment_intent_history_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PaymentIntentHistoryRepo + 'a> {
+            Box::new(PaymentIntentHistoryRepoMock::default())
+        }
+
+        fn create_customers_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<CustomersRepo + 'a> {
+            Box::new(CustomersRepoMock::default())
+        }
+
+        fn create_customers_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<CustomersRepo + 'a> {
+            Box::new(CustomersRepoMock::default())
+        }
+
+        fn create_fees_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<FeeRepo + 'a> {
+            Box::new(FeesRepoMock::default())
+        }
+
+        fn create_fees_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<FeeRepo + 'a> {
+            Box::new(FeesRepoMock::default())
+        }
+
+        fn create_store_billing_type_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<StoreBillingTypeRepo + 'a> {
+            Box::new(StoreBillingTypeRepoMock::default())
+        }
+
+        fn create_store_billing_type_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<StoreBillingTypeRepo + 'a> {
+            Box::new(StoreBillingTypeRepoMock::default())
+        }
+
+        fn create_international_billing_info_repo<'a>(
+            &self,
+            _db_conn: &'a C,
+            _user_id: Option<UserId>,
+        ) -> Box<InternationalBillingInfoRepo + 'a> {
+            Box::new(InternationalBillingInfoRepoMock::default())
+        }
+
+        fn create_international_billing_repo_info_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InternationalBillingInfoRepo + 'a> {
+            Box::new(InternationalBillingInfoRepoMock::default())
+        }
+
+        fn create_russia_billing_info_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<RussiaBillingInfoRepo + 'a> {
+            Box::new(RussiaBillingInfoRepoMock::default())
+        }
+
+        fn create_russia_billing_info_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<RussiaBillingInfoRepo + 'a> {
+            Box::new(RussiaBillingInfoRepoMock::default())
+        }
+
+        fn create_proxy_companies_billing_info_repo<'a>(
+            &self,
+            _db_conn: &'a C,
+            _user_id: Option<UserId>,
+        ) -> Box<ProxyCompanyBillingInfoRepo + 'a> {
+            Box::new(ProxyCompanyBillingInfoRepoMock::default())
+        }
+
+        fn create_proxy_companies_billing_info_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<ProxyCompanyBillingInfoRepo + 'a> {
+            Box::new(ProxyCompanyBillingInfoRepoMock::default())
+        }
+
+        fn create_payment_intent_invoices_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PaymentIntentInvoiceRepo + 'a> {
+            Box::new(PaymentIntentInvoiceRepoMock::default())
+        }
+
+        fn create_payment_intent_invoices_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PaymentIntentInvoiceRepo + 'a> {
+            Box::new(PaymentIntentInvoiceRepoMock::default())
+        }
+
+        fn create_payment_intent_fees_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PaymentIntentFeeRepo + 'a> {
+            Box::new(PaymentIntentFeeRepoMock::default())
+        }
+
+        fn create_payment_intent_fees_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PaymentIntentFeeRepo + 'a> {
+            Box::new(PaymentIntentFeeRepoMock::default())
+        }
+
+        fn create_user_wallets_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<UserWalletsRepo + 'a> {
+            Box::new(UserWalletsRepoMock::default())
+        }
+
+        fn create_user_wallets_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<UserWalletsRepo + 'a> {
+            Box::new(UserWalletsRepoMock::default())
+        }
+
+        fn create_wallet_verifications_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<WalletVerificationsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_wallet_verifications_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<WalletVerificationsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_payouts_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PayoutsRepo + 'a> {
+            Box::new(PayoutsRepoMock::default())
+        }
+
+        fn create_payouts_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PayoutsRepo + 'a> {
+            Box::new(PayoutsRepoMock::default())
+        }
+
+        fn create_subscription_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<SubscriptionRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_subscription_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<SubscriptionRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_store_subscription_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<StoreSubscriptionRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_store_subscription_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<StoreSubscriptionRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_subscription_payment_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<SubscriptionPaymentRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_subscription_payment_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<SubscriptionPaymentRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_fee_statements_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<FeeStatementsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_fee_statements_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<FeeStatementsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_fee_adjustments_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<FeeAdjustmentsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_fee_adjustments_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<FeeAdjustmentsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_fee_crypto_payments_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<FeeCryptoPaymentsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_fee_crypto_payments_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<FeeCryptoPaymentsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_audit_log_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<AuditLogRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_audit_log_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<AuditLogRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_cashback_liabilities_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<CashbackLiabilitiesRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_cashback_liabilities_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<CashbackLiabilitiesRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_negative_store_balances_repo<'a>(
+            &self,
+            _db_conn: &'a C,
+            _user_id: Option<UserId>,
+        ) -> Box<NegativeStoreBalancesRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_negative_store_balances_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<NegativeStoreBalancesRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_exchange_rate_slippages_repo<'a>(
+            &self,
+            _db_conn: &'a C,
+            _user_id: Option<UserId>,
+        ) -> Box<ExchangeRateSlippagesRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_exchange_rate_slippages_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<ExchangeRateSlippagesRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_payout_statements_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PayoutStatementsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_payout_statements_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PayoutStatementsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_schema_migrations_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<SchemaMigrationsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_schema_migrations_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<SchemaMigrationsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_billing_info_changes_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<BillingInfoChangesRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_billing_info_changes_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<BillingInfoChangesRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_payment_attempts_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PaymentAttemptsRepo + 'a> {
+            Box::new(PaymentAttemptsRepoMock::default())
+        }
+
+        fn create_payment_attempts_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PaymentAttemptsRepo + 'a> {
+            Box::new(PaymentAttemptsRepoMock::default())
+        }
+
+        fn create_account_pool_claims_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<AccountPoolClaimsRepo + 'a> {
+            Box::new(AccountPoolClaimsRepoMock::default())
+        }
+
+        fn create_store_webhooks_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<StoreWebhooksRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_store_webhooks_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<StoreWebhooksRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_api_keys_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<ApiKeysRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_api_keys_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<ApiKeysRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_data_retention_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<DataRetentionRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_data_retention_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<DataRetentionRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_invoice_snapshots_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<InvoiceSnapshotsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_invoice_snapshots_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InvoiceSnapshotsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_fee_dunnings_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<FeeDunningsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_fee_dunnings_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<FeeDunningsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_payout_restriction_overrides_repo<'a>(
+            &self,
+            _db_conn: &'a C,
+            _user_id: Option<UserId>,
+        ) -> Box<PayoutRestrictionOverridesRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_payout_restriction_overrides_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PayoutRestrictionOverridesRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_customer_exports_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<CustomerExportsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_customer_exports_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<CustomerExportsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_invoice_risk_assessments_repo<'a>(
+            &self,
+            _db_conn: &'a C,
+            _user_id: Option<UserId>,
+        ) -> Box<InvoiceRiskAssessmentsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_invoice_risk_assessments_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InvoiceRiskAssessmentsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_store_offboardings_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<StoreOffboardingsRepo + 'a> {
+            unimplemented!()
+        }
+
+        fn create_store_offboardings_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<StoreOffboardingsRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_payment_intent_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PaymentIntentRepo + 'a> {
-            Box::new(PaymentIntentRepoMock::default())
+        fn create_historical_reports_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<HistoricalReportsRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_payment_intent_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PaymentIntentRepo + 'a> {
-            Box::new(PaymentIntentRepoMock::default())
+        fn create_historical_reports_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<HistoricalReportsRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_customers_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<CustomersRepo + 'a> {
-            Box::new(CustomersRepoMock::default())
+        fn create_user_credits_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<UserCreditsRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_customers_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<CustomersRepo + 'a> {
-            Box::new(CustomersRepoMock::default())
+        fn create_user_credits_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<UserCreditsRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_fees_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<FeeRepo + 'a> {
-            Box::new(FeesRepoMock::default())
+        fn create_store_billing_statuses_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<StoreBillingStatusesRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_fees_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<FeeRepo + 'a> {
-            Box::new(FeesRepoMock::default())
+        fn create_store_billing_statuses_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<StoreBillingStatusesRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_store_billing_type_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<StoreBillingTypeRepo + 'a> {
-            Box::new(StoreBillingTypeRepoMock::default())
+        fn create_payout_instructions_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PayoutInstructionsRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_store_billing_type_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<StoreBillingTypeRepo + 'a> {
-            Box::new(StoreBillingTypeRepoMock::default())
+        fn create_payout_instructions_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PayoutInstructionsRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_international_billing_info_repo<'a>(
-            &self,
-            _db_conn: &'a C,
-            _user_id: Option<UserId>,
-        ) -> Box<InternationalBillingInfoRepo + 'a> {
-            Box::new(InternationalBillingInfoRepoMock::default())
+        fn create_stripe_fee_backfills_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<StripeFeeBackfillsRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_international_billing_repo_info_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InternationalBillingInfoRepo + 'a> {
-            Box::new(InternationalBillingInfoRepoMock::default())
+        fn create_stripe_fee_backfills_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<StripeFeeBackfillsRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_russia_billing_info_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<RussiaBillingInfoRepo + 'a> {
-            Box::new(RussiaBillingInfoRepoMock::default())
+        fn create_payment_recoveries_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PaymentRecoveriesRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_russia_billing_info_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<RussiaBillingInfoRepo + 'a> {
-            Box::new(RussiaBillingInfoRepoMock::default())
+        fn create_payment_recoveries_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PaymentRecoveriesRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_proxy_companies_billing_info_repo<'a>(
+        fn create_order_capture_approvals_repo<'a>(
             &self,
             _db_conn: &'a C,
             _user_id: Option<UserId>,
-        ) -> Box<ProxyCompanyBillingInfoRepo + 'a> {
-            Box::new(ProxyCompanyBillingInfoRepoMock::default())
+        ) -> Box<OrderCaptureApprovalsRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_proxy_companies_billing_info_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<ProxyCompanyBillingInfoRepo + 'a> {
-            Box::new(ProxyCompanyBillingInfoRepoMock::default())
+        fn create_order_capture_approvals_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<OrderCaptureApprovalsRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_payment_intent_invoices_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PaymentIntentInvoiceRepo + 'a> {
-            Box::new(PaymentIntentInvoiceRepoMock::default())
+        fn create_order_line_items_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<OrderLineItemsRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_payment_intent_invoices_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PaymentIntentInvoiceRepo + 'a> {
-            Box::new(PaymentIntentInvoiceRepoMock::default())
+        fn create_order_line_items_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<OrderLineItemsRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_payment_intent_fees_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PaymentIntentFeeRepo + 'a> {
-            Box::new(PaymentIntentFeeRepoMock::default())
+        fn create_invoice_buyer_details_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<InvoiceBuyerDetailsRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_payment_intent_fees_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PaymentIntentFeeRepo + 'a> {
-            Box::new(PaymentIntentFeeRepoMock::default())
+        fn create_invoice_buyer_details_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InvoiceBuyerDetailsRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_user_wallets_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<UserWalletsRepo + 'a> {
-            Box::new(UserWalletsRepoMock::default())
+        fn create_invoice_callbacks_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<InvoiceCallbacksRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_user_wallets_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<UserWalletsRepo + 'a> {
-            Box::new(UserWalletsRepoMock::default())
+        fn create_invoice_callbacks_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InvoiceCallbacksRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_payouts_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PayoutsRepo + 'a> {
-            Box::new(PayoutsRepoMock::default())
+        fn create_invoice_requotes_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<InvoiceRequotesRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_payouts_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PayoutsRepo + 'a> {
-            Box::new(PayoutsRepoMock::default())
+        fn create_invoice_requotes_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InvoiceRequotesRepo + 'a> {
+            unimplemented!()
         }
 
-        fn create_subscription_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<SubscriptionRepo + 'a> {
+        fn create_account_assignments_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<AccountAssignmentsRepo + 'a> {
             unimplemented!()
         }
 
-        fn create_subscription_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<SubscriptionRepo + 'a> {
+        fn create_account_assignments_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<AccountAssignmentsRepo + 'a> {
             unimplemented!()
         }
 
-        fn create_store_subscription_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<StoreSubscriptionRepo + 'a> {
+        fn create_feature_flags_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<FeatureFlagsRepo + 'a> {
             unimplemented!()
         }
 
-        fn create_store_subscription_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<StoreSubscriptionRepo + 'a> {
+        fn create_feature_flags_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<FeatureFlagsRepo + 'a> {
             unimplemented!()
         }
 
-        fn create_subscription_payment_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<SubscriptionPaymentRepo + 'a> {
-            unimplemented!()
+        fn with_api_key_scopes(&self, _scopes: Vec<ApiKeyScope>) -> Self {
+            *self
         }
 
-        fn create_subscription_payment_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<SubscriptionPaymentRepo + 'a> {
+        fn get_caller_roles<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Vec<BillingRole> {
             unimplemented!()
         }
     }
@@ -724,6 +1514,17 @@ pub mod tests {
         fn delete(&self, _search_params: InternationalBillingInfoSearch) -> RepoResultV2<Option<InternationalBillingInfo>> {
             Ok(Some(international_billing_info()))
         }
+
+        fn reencrypt(
+            &self,
+            _after_id: Option<InternationalBillingId>,
+            _limit: i64,
+        ) -> RepoResultV2<ReencryptionBatch<InternationalBillingId>> {
+            Ok(ReencryptionBatch {
+                last_id: None,
+                reencrypted: 0,
+            })
+        }
     }
 
     #[derive(Clone, Default)]
@@ -749,6 +1550,13 @@ pub mod tests {
         fn delete(&self, _search_params: RussiaBillingInfoSearch) -> RepoResultV2<Option<RussiaBillingInfo>> {
             Ok(Some(russian_billing_info()))
         }
+
+        fn reencrypt(&self, _after_id: Option<RussiaBillingId>, _limit: i64) -> RepoResultV2<ReencryptionBatch<RussiaBillingId>> {
+            Ok(ReencryptionBatch {
+                last_id: None,
+                reencrypted: 0,
+            })
+        }
     }
 
     #[derive(Clone, Default)]
@@ -792,6 +1600,10 @@ pub mod tests {
 
             Ok(Some(DbCustomer { id, ..customer }))
         }
+
+        fn get_duplicates(&self, _limit: i64) -> RepoResultV2<Vec<DbCustomer>> {
+            Ok(vec![])
+        }
     }
 
     #[derive(Clone, Default)]
@@ -823,6 +1635,9 @@ pub mod tests {
                 currency: payload.currency,
                 crypto_currency: payload.crypto_currency,
                 crypto_amount: payload.crypto_amount,
+                exchange_rate: payload.exchange_rate,
+                currency_exchange_id: payload.currency_exchange_id,
+                converted_at: payload.converted_at,
                 ..fee
             })
         }
@@ -859,6 +1674,69 @@ pub mod tests {
         }
     }
 
+    #[derive(Clone, Default)]
+    pub struct PaymentIntentHistoryRepoMock;
+
+    impl PaymentIntentHistoryRepo for PaymentIntentHistoryRepoMock {
+        fn create(&self, payload: NewPaymentIntentHistoryEntry) -> RepoResultV2<PaymentIntentHistoryEntry> {
+            Ok(PaymentIntentHistoryEntry {
+                id: PaymentIntentHistoryEntryId::new(1),
+                payment_intent_id: payload.payment_intent_id,
+                status: payload.status,
+                source: payload.source,
+                created_at: chrono::offset::Utc::now().naive_utc(),
+            })
+        }
+
+        fn list_by_payment_intent_id(&self, _payment_intent_id: PaymentIntentId) -> RepoResultV2<Vec<PaymentIntentHistoryEntry>> {
+            Ok(vec![])
+        }
+
+        fn get_latest_by_payment_intent_id(&self, _payment_intent_id: PaymentIntentId) -> RepoResultV2<Option<PaymentIntentHistoryEntry>> {
+            Ok(None)
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct PaymentAttemptsRepoMock;
+
+    impl PaymentAttemptsRepo for PaymentAttemptsRepoMock {
+        fn create(&self, payload: NewPaymentAttempt) -> RepoResultV2<PaymentAttempt> {
+            Ok(PaymentAttempt {
+                id: PaymentAttemptId::new(1),
+                invoice_id: payload.invoice_id,
+                payment_intent_id: payload.payment_intent_id,
+                status: payload.status,
+                decline_code: payload.decline_code,
+                error_code: payload.error_code,
+                payment_method_fingerprint: payload.payment_method_fingerprint,
+                created_at: chrono::offset::Utc::now().naive_utc(),
+            })
+        }
+
+        fn list_by_invoice_id(&self, _invoice_id: InvoiceV2Id) -> RepoResultV2<Vec<PaymentAttempt>> {
+            Ok(vec![])
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct AccountPoolClaimsRepoMock;
+
+    impl AccountPoolClaimsRepo for AccountPoolClaimsRepoMock {
+        fn create(&self, payload: NewAccountPoolClaim) -> RepoResultV2<AccountPoolClaim> {
+            Ok(AccountPoolClaim {
+                id: 1,
+                currency: payload.currency,
+                exhausted: payload.exhausted,
+                claimed_at: chrono::offset::Utc::now().naive_utc(),
+            })
+        }
+
+        fn demand_since(&self, _since: NaiveDateTime) -> RepoResultV2<HashMap<TureCurrency, AccountPoolDemand>> {
+            Ok(HashMap::default())
+        }
+    }
+
     #[derive(Clone, Default)]
     pub struct OrderInfoRepoMock;
 
@@ -927,6 +1805,16 @@ pub mod tests {
         fn delete(&self, _id: SagaId) -> RepoResult<Invoice> {
             Ok(create_invoice())
         }
+
+        fn find_expired(&self, _reserved_before: SystemTime, _limit: i64) -> RepoResult<Vec<Invoice>> {
+            Ok(vec![])
+        }
+
+        fn set_state(&self, _id: SagaId, state: OrderState) -> RepoResult<Invoice> {
+            let mut invoice = create_invoice();
+            invoice.state = state;
+            Ok(invoice)
+        }
     }
 
     #[derive(Clone, Default)]
@@ -949,6 +1837,13 @@ pub mod tests {
             })
         }
 
+        fn list_user_ids_by_role(&self, role: BillingRole) -> RepoResultV2<Vec<UserId>> {
+            Ok(match role {
+                BillingRole::Superuser => vec![UserId(1)],
+                _ => vec![],
+            })
+        }
+
         fn create(&self, payload: NewUserRole) -> RepoResult<UserRole> {
             Ok(UserRole {
                 id: RoleId::new(),
@@ -1022,6 +1917,18 @@ pub mod tests {
                 is_pooled,
                 created_at: NaiveDateTime::from_timestamp(0, 0),
                 wallet_address,
+                status: AccountStatus::Active,
+            })
+        }
+
+        fn set_status(&self, account_id: AccountId, status: AccountStatus) -> RepoResultV2<Account> {
+            Ok(Account {
+                id: account_id,
+                currency: TureCurrency::Stq,
+                is_pooled: true,
+                created_at: NaiveDateTime::from_timestamp(0, 0),
+                wallet_address: "0x0".to_string().into(),
+                status,
             })
         }
 
@@ -1032,12 +1939,17 @@ pub mod tests {
                 is_pooled: false,
                 created_at: NaiveDateTime::from_timestamp(0, 0),
                 wallet_address: "0x0".to_string().into(),
+                status: AccountStatus::Active,
             }))
         }
 
-        fn get_free_account(&self, _currency: TureCurrency) -> RepoResultV2<Option<Account>> {
+        fn get_free_account(&self, _currency: TureCurrency, _released_before: NaiveDateTime) -> RepoResultV2<Option<Account>> {
             Ok(None)
         }
+
+        fn count_free(&self, _released_before: NaiveDateTime) -> RepoResultV2<HashMap<TureCurrency, u64>> {
+            Ok(HashMap::default())
+        }
     }
 
     #[derive(Debug, Default)]
@@ -1055,6 +1967,11 @@ pub mod tests {
                 buyer_currency,
                 amount_captured,
                 buyer_user_id,
+                metadata,
+                memo,
+                po_number,
+                price_reserved,
+                public_token,
             } = payload;
 
             Ok(RawInvoiceV2 {
@@ -1069,6 +1986,11 @@ pub mod tests {
                 updated_at: NaiveDateTime::from_timestamp(0, 0),
                 buyer_user_id,
                 status: OrderState::New,
+                metadata,
+                memo,
+                po_number,
+                price_reserved,
+                public_token,
             })
         }
 
@@ -1080,10 +2002,26 @@ pub mod tests {
             unimplemented!()
         }
 
+        fn get_by_public_token(&self, _public_token: &str) -> RepoResultV2<Option<RawInvoiceV2>> {
+            unimplemented!()
+        }
+
+        fn get_many(&self, _invoice_ids: &[InvoiceV2Id]) -> RepoResultV2<Vec<RawInvoiceV2>> {
+            Ok(vec![])
+        }
+
+        fn get_unpaid_by_buyer_user_id(&self, _buyer_user_id: ::models::UserId) -> RepoResultV2<Vec<RawInvoiceV2>> {
+            Ok(vec![])
+        }
+
         fn unlink_account(&self, _invoice_id: InvoiceV2Id) -> RepoResultV2<RawInvoiceV2> {
             unimplemented!()
         }
 
+        fn set_status(&self, _invoice_id: InvoiceV2Id, _status: OrderState) -> RepoResultV2<RawInvoiceV2> {
+            unimplemented!()
+        }
+
         fn increase_amount_captured(
             &self,
             _account_id: AccountId,
@@ -1093,6 +2031,10 @@ pub mod tests {
             unimplemented!()
         }
 
+        fn list_amounts_received(&self, _invoice_id: InvoiceV2Id) -> RepoResultV2<Vec<RawAmountReceived>> {
+            Ok(vec![])
+        }
+
         fn set_amount_paid(&self, _invoice_id: InvoiceV2Id, _input: InvoiceSetAmountPaid) -> RepoResultV2<RawInvoiceV2> {
             unimplemented!()
         }
@@ -1100,6 +2042,14 @@ pub mod tests {
         fn set_amount_paid_fiat(&self, _invoice_id: InvoiceV2Id, _input: InvoiceSetAmountPaid) -> RepoResultV2<RawInvoiceV2> {
             unimplemented!()
         }
+
+        fn update_details(&self, _invoice_id: InvoiceV2Id, _input: UpdateInvoiceDetails) -> RepoResultV2<RawInvoiceV2> {
+            unimplemented!()
+        }
+
+        fn lock_for_recalculation(&self, _invoice_id: InvoiceV2Id) -> RepoResultV2<()> {
+            Ok(())
+        }
     }
 
     #[derive(Debug, Default)]
@@ -1126,6 +2076,14 @@ pub mod tests {
             Ok(vec![])
         }
 
+        fn get_orders_without_stripe_fee(&self, _states: Vec<PaymentState>) -> RepoResultV2<Vec<RawOrder>> {
+            Ok(vec![])
+        }
+
+        fn get_store_balance_overview(&self, _store_id: StoreV2Id) -> RepoResultV2<Vec<StoreBalanceBucketAmount>> {
+            Ok(vec![])
+        }
+
         fn search(&self, _skip: i64, _count: i64, _search: OrdersSearch) -> RepoResultV2<OrderSearchResults> {
             Ok(OrderSearchResults {
                 total_count: 0,
@@ -1242,6 +2200,19 @@ pub mod tests {
         fn delete_by_order_id(&self, _order_id: OrderV2Id) -> RepoResultV2<Vec<RawOrderExchangeRate>> {
             Ok(vec![])
         }
+
+        fn find_superseded(
+            &self,
+            _expired_before: NaiveDateTime,
+            _after_id: OrderExchangeRateId,
+            _limit: i64,
+        ) -> RepoResultV2<Vec<(RawOrderExchangeRate, Option<NaiveDateTime>)>> {
+            Ok(vec![])
+        }
+
+        fn delete_many(&self, _rate_ids: Vec<OrderExchangeRateId>) -> RepoResultV2<usize> {
+            Ok(0)
+        }
     }
 
     #[derive(Debug, Default)]
@@ -1257,6 +2228,9 @@ pub mod tests {
                 created_at: chrono::Utc::now().naive_utc(),
                 status_updated_at: chrono::Utc::now().naive_utc(),
                 scheduled_on: None,
+                locked_by: None,
+                lease_expires_at: None,
+                timings: None,
             })
         }
 
@@ -1269,10 +2243,13 @@ pub mod tests {
                 created_at: chrono::Utc::now().naive_utc(),
                 status_updated_at: chrono::Utc::now().naive_utc(),
                 scheduled_on: Some(scheduled_on),
+                locked_by: None,
+                lease_expires_at: None,
+                timings: None,
             })
         }
 
-        fn get_events_for_processing(&self, limit: u32) -> RepoResultV2<Vec<EventEntry>> {
+        fn get_events_for_processing(&self, limit: u32, _payload_types: &EventPayloadTypes) -> RepoResultV2<Vec<EventEntry>> {
             Ok((0..limit)
                 .map(|i| EventEntry {
                     id: EventEntryId::new(i as i64),
@@ -1285,6 +2262,9 @@ pub mod tests {
                     created_at: chrono::Utc::now().naive_utc(),
                     status_updated_at: chrono::Utc::now().naive_utc(),
                     scheduled_on: None,
+                    locked_by: Some("mock".to_string()),
+                    lease_expires_at: None,
+                    timings: None,
                 })
                 .collect::<Vec<_>>())
         }
@@ -1305,6 +2285,9 @@ pub mod tests {
                 created_at: chrono::Utc::now().naive_utc(),
                 status_updated_at: chrono::Utc::now().naive_utc(),
                 scheduled_on: None,
+                locked_by: None,
+                lease_expires_at: None,
+                timings: None,
             })
         }
 
@@ -1320,8 +2303,15 @@ pub mod tests {
                 created_at: chrono::Utc::now().naive_utc(),
                 status_updated_at: chrono::Utc::now().naive_utc(),
                 scheduled_on: None,
+                locked_by: None,
+                lease_expires_at: None,
+                timings: None,
             })
         }
+
+        fn record_event_timings(&self, _event_entry_id: EventEntryId, _timings: EventTimings) -> RepoResultV2<()> {
+            Ok(())
+        }
     }
 
     #[derive(Debug, Default)]
@@ -1340,6 +2330,15 @@ pub mod tests {
             unimplemented!()
         }
 
+        fn get_by_address(
+            &self,
+            _user_id: ::models::UserId,
+            _currency: TureCurrency,
+            _address: WalletAddress,
+        ) -> RepoResultV2<Vec<UserWallet>> {
+            unimplemented!()
+        }
+
         fn deactivate(&self, _id: UserWalletId) -> RepoResultV2<UserWallet> {
             unimplemented!()
         }
@@ -1369,6 +2368,10 @@ pub mod tests {
             unimplemented!()
         }
 
+        fn get_by_request_id(&self, _user_id: ::models::UserId, _request_id: PayoutRequestId) -> RepoResultV2<Option<Payout>> {
+            unimplemented!()
+        }
+
         fn mark_as_completed(&self, _id: PayoutId) -> RepoResultV2<Payout> {
             unimplemented!()
         }
@@ -1454,6 +2457,14 @@ pub mod tests {
         user_id: Option<UserId>,
         handle: Arc<Handle>,
     ) -> Service<MockConnection, MockConnectionManager, ReposFactoryMock, MockHttpClient, MockPaymentsClient, MockAccountService> {
+        create_service_with_repo_factory(user_id, handle, MOCK_REPO_FACTORY)
+    }
+
+    pub fn create_service_with_repo_factory<F: ReposFactory<MockConnection>>(
+        user_id: Option<UserId>,
+        handle: Arc<Handle>,
+        repo_factory: F,
+    ) -> Service<MockConnection, MockConnectionManager, F, MockHttpClient, MockPaymentsClient, MockAccountService> {
         let manager = MockConnectionManager::default();
         let db_pool = r2d2::Pool::builder().build(manager).expect("Failed to create connection pool");
         let cpu_pool = CpuPool::new(1);
@@ -1464,7 +2475,7 @@ pub mod tests {
         let client_stream = client.stream();
         handle.spawn(client_stream.for_each(|_| Ok(())));
 
-        let static_context = StaticContext::new(db_pool, cpu_pool, client_handle.clone(), Arc::new(config), MOCK_REPO_FACTORY);
+        let static_context = StaticContext::new(db_pool, cpu_pool, client_handle.clone(), Arc::new(config), repo_factory);
 
         let dynamic_context = DynamicContext::new(user_id, String::default(), MockHttpClient::default(), None, None);
 
@@ -1479,6 +2490,7 @@ pub mod tests {
             email: None,
             created_at: now,
             updated_at: now,
+            payment_method_id: None,
         }
     }
 
@@ -1496,6 +2508,12 @@ pub mod tests {
             updated_at: now,
             crypto_currency: None,
             crypto_amount: None,
+            exchange_rate: None,
+            currency_exchange_id: None,
+            converted_at: None,
+            charged_currency: None,
+            charged_amount: None,
+            charge_exchange_rate: None,
         }
     }
 
@@ -1513,6 +2531,7 @@ pub mod tests {
             status: PaymentIntentStatus::Other,
             created_at: now,
             updated_at: now,
+            status_event_created_at: None,
         }
     }
 
@@ -1546,79 +2565,6 @@ pub mod tests {
         }
     }
 
-    #[derive(Default)]
-    pub struct MockConnection {
-        tr: AnsiTransactionManager,
-    }
-
-    impl Connection for MockConnection {
-        type Backend = Pg;
-        type TransactionManager = AnsiTransactionManager;
-
-        fn establish(_database_url: &str) -> ConnectionResult<MockConnection> {
-            Ok(MockConnection::default())
-        }
-
-        fn execute(&self, _query: &str) -> QueryResult<usize> {
-            unimplemented!()
-        }
-
-        fn query_by_index<T, U>(&self, _source: T) -> QueryResult<Vec<U>>
-        where
-            T: AsQuery,
-            T::Query: QueryFragment<Pg> + QueryId,
-            Pg: HasSqlType<T::SqlType>,
-            U: Queryable<T::SqlType, Pg>,
-        {
-            unimplemented!()
-        }
-
-        fn query_by_name<T, U>(&self, _source: &T) -> QueryResult<Vec<U>>
-        where
-            T: QueryFragment<Pg> + QueryId,
-            U: QueryableByName<Pg>,
-        {
-            unimplemented!()
-        }
-
-        fn execute_returning_count<T>(&self, _source: &T) -> QueryResult<usize>
-        where
-            T: QueryFragment<Pg> + QueryId,
-        {
-            unimplemented!()
-        }
-
-        fn transaction_manager(&self) -> &Self::TransactionManager {
-            &self.tr
-        }
-    }
-
-    impl SimpleConnection for MockConnection {
-        fn batch_execute(&self, _query: &str) -> QueryResult<()> {
-            Ok(())
-        }
-    }
-
-    #[derive(Default)]
-    pub struct MockConnectionManager;
-
-    impl ManageConnection for MockConnectionManager {
-        type Connection = MockConnection;
-        type Error = MockError;
-
-        fn connect(&self) -> Result<MockConnection, MockError> {
-            Ok(MockConnection::default())
-        }
-
-        fn is_valid(&self, _conn: &mut MockConnection) -> Result<(), MockError> {
-            Ok(())
-        }
-
-        fn has_broken(&self, _conn: &mut MockConnection) -> bool {
-            false
-        }
-    }
-
     #[derive(Default, Clone)]
     pub struct MockHttpClient;
 
@@ -1677,60 +2623,11 @@ pub mod tests {
         fn create_external_transaction(&self, _input: CreateExternalTransaction) -> Box<Future<Item = (), Error = payments::Error> + Send> {
             unimplemented!()
         }
-    }
-
-    #[derive(Default, Clone)]
-    pub struct MockAccountService;
-
-    impl AccountService for MockAccountService {
-        fn init_system_accounts(&self) -> ServiceFutureV2<()> {
-            unimplemented!()
-        }
-
-        fn init_account_pools(&self) -> ServiceFutureV2<()> {
-            unimplemented!()
-        }
-
-        fn create_account(&self, _account_id: Uuid, _name: String, _currency: TureCurrency, _is_pooled: bool) -> ServiceFutureV2<Account> {
-            unimplemented!()
-        }
-
-        fn get_account(&self, _account_id: Uuid) -> ServiceFutureV2<AccountWithBalance> {
-            unimplemented!()
-        }
 
-        fn get_main_account(&self, _currency: TureCurrency) -> ServiceFutureV2<AccountWithBalance> {
+        fn ping(&self) -> Box<Future<Item = (), Error = payments::Error> + Send> {
             unimplemented!()
         }
-
-        fn get_stq_cashback_account(&self) -> ServiceFutureV2<AccountWithBalance> {
-            unimplemented!()
-        }
-
-        fn get_or_create_free_pooled_account(&self, _currency: TureCurrency) -> ServiceFutureV2<Account> {
-            unimplemented!()
-        }
-    }
-
-    #[derive(Debug)]
-    pub struct MockError {}
-
-    impl fmt::Display for MockError {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            write!(f, "SuperError is here!")
-        }
-    }
-
-    impl Error for MockError {
-        fn description(&self) -> &str {
-            "I'm the superhero of errors"
-        }
-
-        fn cause(&self) -> Option<&Error> {
-            None
-        }
     }
 
     pub const MOCK_REPO_FACTORY: ReposFactoryMock = ReposFactoryMock {};
-
 }
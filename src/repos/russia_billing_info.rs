@@ -10,7 +10,7 @@ use diesel::Connection;
 use failure::Error as FailureError;
 use failure::Fail;
 
-use stq_types::StoreId;
+use stq_types::{RussiaBillingId, StoreId, SwiftId};
 
 use models::authorization::*;
 use models::{NewRussiaBillingInfo, RussiaBillingInfo, RussiaBillingInfoSearch, UpdateRussiaBillingInfo, UserRole};
@@ -20,9 +20,15 @@ use schema::roles::dsl as UserRolesDsl;
 use schema::russia_billing_info::dsl as RussiaBillingInfoDsl;
 
 use super::acl;
+use super::encryption::{FieldCipher, ReencryptionBatch};
 use super::error::*;
 use super::types::RepoResultV2;
 
+const SWIFT_BIC_COLUMN: &'static str = "russia_billing_info.swift_bic";
+const CORRESPONDENT_ACCOUNT_COLUMN: &'static str = "russia_billing_info.correspondent_account";
+const CURRENT_ACCOUNT_COLUMN: &'static str = "russia_billing_info.current_account";
+const PERSONAL_ACCOUNT_COLUMN: &'static str = "russia_billing_info.personal_account";
+
 type RussiaBillingInfoRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, RussiaBillingInfoAccess>>;
 
 type BoxedExpr = Box<BoxableExpression<crate::schema::russia_billing_info::table, Pg, SqlType = Bool>>;
@@ -30,6 +36,7 @@ type BoxedExpr = Box<BoxableExpression<crate::schema::russia_billing_info::table
 pub struct RussiaBillingInfoRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
     pub db_conn: &'a T,
     pub acl: RussiaBillingInfoRepoAcl,
+    pub cipher: FieldCipher,
 }
 
 pub struct RussiaBillingInfoAccess {
@@ -42,11 +49,50 @@ pub trait RussiaBillingInfoRepo {
     fn search(&self, search: RussiaBillingInfoSearch) -> RepoResultV2<Vec<RussiaBillingInfo>>;
     fn update(&self, search_params: RussiaBillingInfoSearch, payload: UpdateRussiaBillingInfo) -> RepoResultV2<RussiaBillingInfo>;
     fn delete(&self, search_params: RussiaBillingInfoSearch) -> RepoResultV2<Option<RussiaBillingInfo>>;
+    /// Seals the account details of up to `limit` entries following `after_id` with the current encryption key
+    fn reencrypt(&self, after_id: Option<RussiaBillingId>, limit: i64) -> RepoResultV2<ReencryptionBatch<RussiaBillingId>>;
 }
 
 impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> RussiaBillingInfoRepoImpl<'a, T> {
-    pub fn new(db_conn: &'a T, acl: RussiaBillingInfoRepoAcl) -> Self {
-        Self { db_conn, acl }
+    pub fn new(db_conn: &'a T, acl: RussiaBillingInfoRepoAcl, cipher: FieldCipher) -> Self {
+        Self { db_conn, acl, cipher }
+    }
+
+    fn encrypt(&self, column: &str, value: &str) -> RepoResultV2<String> {
+        self.cipher
+            .encrypt(column, value)
+            .map_err(ectx!(ErrorSource::Encryption, ErrorKind::Internal => column))
+    }
+
+    fn encrypt_optional(&self, column: &str, value: Option<&String>) -> RepoResultV2<Option<String>> {
+        match value {
+            Some(value) => self.encrypt(column, value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn decrypt(&self, column: &str, value: &str) -> RepoResultV2<String> {
+        self.cipher
+            .decrypt(column, value)
+            .map_err(ectx!(ErrorSource::Encryption, ErrorKind::Internal => column))
+    }
+
+    fn decrypt_optional(&self, column: &str, value: Option<&String>) -> RepoResultV2<Option<String>> {
+        match value {
+            Some(value) => self.decrypt(column, value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Decrypts the account details, the controller masks them for the users that may not see them in full
+    fn reveal(&self, billing_info: RussiaBillingInfo) -> RepoResultV2<RussiaBillingInfo> {
+        Ok(RussiaBillingInfo {
+            swift_bic: SwiftId(self.decrypt(SWIFT_BIC_COLUMN, &billing_info.swift_bic.0)?),
+            correspondent_account: self.decrypt(CORRESPONDENT_ACCOUNT_COLUMN, &billing_info.correspondent_account)?,
+            current_account: self.decrypt(CURRENT_ACCOUNT_COLUMN, &billing_info.current_account)?,
+            personal_account: self.decrypt_optional(PERSONAL_ACCOUNT_COLUMN, billing_info.personal_account.as_ref())?,
+            ..billing_info
+        })
     }
 }
 
@@ -54,7 +100,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
     for RussiaBillingInfoRepoImpl<'a, T>
 {
     fn create(&self, new_russia_billing_info: NewRussiaBillingInfo) -> RepoResultV2<RussiaBillingInfo> {
-        debug!("create russia billing info {:?}.", new_russia_billing_info);
+        debug!("create russia billing info for store {}.", new_russia_billing_info.store_id);
         acl::check(
             &*self.acl,
             Resource::BillingInfo,
@@ -66,12 +112,22 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         )
         .map_err(ectx!(try ErrorKind::Forbidden))?;
 
+        let new_russia_billing_info = NewRussiaBillingInfo {
+            swift_bic: SwiftId(self.encrypt(SWIFT_BIC_COLUMN, &new_russia_billing_info.swift_bic.0)?),
+            correspondent_account: self.encrypt(CORRESPONDENT_ACCOUNT_COLUMN, &new_russia_billing_info.correspondent_account)?,
+            current_account: self.encrypt(CURRENT_ACCOUNT_COLUMN, &new_russia_billing_info.current_account)?,
+            personal_account: self.encrypt_optional(PERSONAL_ACCOUNT_COLUMN, new_russia_billing_info.personal_account.as_ref())?,
+            ..new_russia_billing_info
+        };
+
         let command = diesel::insert_into(RussiaBillingInfoDsl::russia_billing_info).values(&new_russia_billing_info);
 
-        command.get_result::<RussiaBillingInfo>(self.db_conn).map_err(|e| {
+        let created_entry = command.get_result::<RussiaBillingInfo>(self.db_conn).map_err(|e| {
             let error_kind = ErrorKind::from(&e);
-            ectx!(err e, ErrorSource::Diesel, error_kind)
-        })
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        self.reveal(created_entry)
     }
 
     fn get(&self, search_params: RussiaBillingInfoSearch) -> RepoResultV2<Option<RussiaBillingInfo>> {
@@ -103,7 +159,11 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             };
             acl::check(&*self.acl, Resource::BillingInfo, Action::Read, self, Some(&access)).map_err(ectx!(try ErrorKind::Forbidden))?;
         }
-        Ok(billing_info)
+
+        match billing_info {
+            Some(billing_info) => self.reveal(billing_info).map(Some),
+            None => Ok(None),
+        }
     }
 
     fn search(&self, search_params: RussiaBillingInfoSearch) -> RepoResultV2<Vec<RussiaBillingInfo>> {
@@ -130,7 +190,7 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             acl::check(&*self.acl, Resource::BillingInfo, Action::Read, self, Some(&access)).map_err(ectx!(try ErrorKind::Forbidden))?;
         }
 
-        Ok(billing_info)
+        billing_info.into_iter().map(|billing_info| self.reveal(billing_info)).collect()
     }
 
     fn update(&self, search_params: RussiaBillingInfoSearch, payload: UpdateRussiaBillingInfo) -> RepoResultV2<RussiaBillingInfo> {
@@ -147,11 +207,24 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             ectx!(try err e, ErrorKind::Internal)
         })?;
 
+        let payload = UpdateRussiaBillingInfo {
+            swift_bic: match payload.swift_bic {
+                Some(ref swift_bic) => Some(SwiftId(self.encrypt(SWIFT_BIC_COLUMN, &swift_bic.0)?)),
+                None => None,
+            },
+            correspondent_account: self.encrypt_optional(CORRESPONDENT_ACCOUNT_COLUMN, payload.correspondent_account.as_ref())?,
+            current_account: self.encrypt_optional(CURRENT_ACCOUNT_COLUMN, payload.current_account.as_ref())?,
+            personal_account: self.encrypt_optional(PERSONAL_ACCOUNT_COLUMN, payload.personal_account.as_ref())?,
+            ..payload
+        };
+
         let query = diesel::update(crate::schema::russia_billing_info::table.filter(query)).set(&payload);
-        query.get_result::<RussiaBillingInfo>(self.db_conn).map_err(|e| {
+        let updated_entry = query.get_result::<RussiaBillingInfo>(self.db_conn).map_err(|e| {
             let error_kind = ErrorKind::from(&e);
-            ectx!(err e, ErrorSource::Diesel, error_kind)
-        })
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        self.reveal(updated_entry)
     }
 
     fn delete(&self, search_params: RussiaBillingInfoSearch) -> RepoResultV2<Option<RussiaBillingInfo>> {
@@ -171,10 +244,77 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         })?;
 
         let query = diesel::delete(crate::schema::russia_billing_info::table.filter(query));
-        query.get_result::<RussiaBillingInfo>(self.db_conn).optional().map_err(|e| {
+        let deleted_entry = query.get_result::<RussiaBillingInfo>(self.db_conn).optional().map_err(|e| {
             let error_kind = ErrorKind::from(&e);
-            ectx!(err e, ErrorSource::Diesel, error_kind)
-        })
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        match deleted_entry {
+            Some(deleted_entry) => self.reveal(deleted_entry).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn reencrypt(&self, after_id: Option<RussiaBillingId>, limit: i64) -> RepoResultV2<ReencryptionBatch<RussiaBillingId>> {
+        debug!("re-encrypt russia billing info after {:?}.", after_id);
+        acl::check(&*self.acl, Resource::BillingInfo, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let mut query = RussiaBillingInfoDsl::russia_billing_info
+            .order(RussiaBillingInfoDsl::id)
+            .limit(limit)
+            .into_boxed();
+        if let Some(after_id) = after_id {
+            query = query.filter(RussiaBillingInfoDsl::id.gt(after_id));
+        }
+
+        let billing_infos = query.get_results::<RussiaBillingInfo>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(try err e, ErrorSource::Diesel, error_kind)
+        })?;
+
+        let mut batch = ReencryptionBatch {
+            last_id: billing_infos.last().map(|billing_info| billing_info.id),
+            reencrypted: 0,
+        };
+
+        for billing_info in billing_infos {
+            let is_current = self.cipher.is_current(&billing_info.swift_bic.0)
+                && self.cipher.is_current(&billing_info.correspondent_account)
+                && self.cipher.is_current(&billing_info.current_account)
+                && billing_info
+                    .personal_account
+                    .as_ref()
+                    .map_or(true, |personal_account| self.cipher.is_current(personal_account));
+            if is_current {
+                continue;
+            }
+
+            let swift_bic = self.decrypt(SWIFT_BIC_COLUMN, &billing_info.swift_bic.0)?;
+            let correspondent_account = self.decrypt(CORRESPONDENT_ACCOUNT_COLUMN, &billing_info.correspondent_account)?;
+            let current_account = self.decrypt(CURRENT_ACCOUNT_COLUMN, &billing_info.current_account)?;
+            let personal_account = self.decrypt_optional(PERSONAL_ACCOUNT_COLUMN, billing_info.personal_account.as_ref())?;
+            let payload = UpdateRussiaBillingInfo {
+                branch_name: None,
+                personal_account: self.encrypt_optional(PERSONAL_ACCOUNT_COLUMN, personal_account.as_ref())?,
+                bank_name: None,
+                swift_bic: Some(SwiftId(self.encrypt(SWIFT_BIC_COLUMN, &swift_bic)?)),
+                tax_id: None,
+                correspondent_account: Some(self.encrypt(CORRESPONDENT_ACCOUNT_COLUMN, &correspondent_account)?),
+                current_account: Some(self.encrypt(CURRENT_ACCOUNT_COLUMN, &current_account)?),
+                beneficiary_full_name: None,
+            };
+
+            diesel::update(RussiaBillingInfoDsl::russia_billing_info.filter(RussiaBillingInfoDsl::id.eq(billing_info.id)))
+                .set(&payload)
+                .execute(self.db_conn)
+                .map_err(|e| {
+                    let error_kind = ErrorKind::from(&e);
+                    ectx!(try err e, ErrorSource::Diesel, error_kind)
+                })?;
+            batch.reencrypted += 1;
+        }
+
+        Ok(batch)
     }
 }
 
@@ -0,0 +1,64 @@
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::sql_query;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::AppliedMigration;
+use repos::legacy_acl::*;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type SchemaMigrationsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, AppliedMigration>>;
+
+pub struct SchemaMigrationsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: SchemaMigrationsRepoAcl,
+}
+
+pub trait SchemaMigrationsRepo {
+    /// Versions of the migrations Diesel has applied to the database
+    fn get_applied_versions(&self) -> RepoResultV2<Vec<String>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> SchemaMigrationsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: SchemaMigrationsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> SchemaMigrationsRepo
+    for SchemaMigrationsRepoImpl<'a, T>
+{
+    fn get_applied_versions(&self) -> RepoResultV2<Vec<String>> {
+        debug!("get applied schema migrations.");
+        acl::check(&*self.acl, Resource::SchemaMigration, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        // The table is maintained by the Diesel CLI and is not a part of the schema
+        sql_query("SELECT version FROM __diesel_schema_migrations ORDER BY version")
+            .get_results::<AppliedMigration>(self.db_conn)
+            .map(|migrations| migrations.into_iter().map(|migration| migration.version).collect())
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, AppliedMigration>
+    for SchemaMigrationsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&AppliedMigration>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
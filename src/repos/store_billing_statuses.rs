@@ -0,0 +1,141 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{StoreId, UserId};
+
+use models::authorization::*;
+use models::{NewStoreBillingStatus, StoreBillingStatus, UpdateStoreBillingStatus, UserRole};
+use repos::legacy_acl::*;
+
+use schema::roles::dsl as UserRolesDsl;
+use schema::store_billing_statuses::dsl as StoreBillingStatusesDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type StoreBillingStatusesRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, StoreBillingStatusAccess>>;
+
+pub struct StoreBillingStatusAccess {
+    store_id: StoreId,
+}
+
+pub struct StoreBillingStatusesRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: StoreBillingStatusesRepoAcl,
+}
+
+pub trait StoreBillingStatusesRepo {
+    fn create(&self, payload: NewStoreBillingStatus) -> RepoResultV2<StoreBillingStatus>;
+    fn get(&self, store_id: StoreId) -> RepoResultV2<Option<StoreBillingStatus>>;
+    /// Statuses of all stores ever evaluated or overridden, ordered by store
+    fn list(&self) -> RepoResultV2<Vec<StoreBillingStatus>>;
+    fn update(&self, store_id: StoreId, payload: UpdateStoreBillingStatus) -> RepoResultV2<StoreBillingStatus>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> StoreBillingStatusesRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: StoreBillingStatusesRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+
+    fn check_access(&self, action: Action, store_id: StoreId) -> RepoResultV2<()> {
+        acl::check(
+            &*self.acl,
+            Resource::StoreBillingStatus,
+            action,
+            self,
+            Some(&StoreBillingStatusAccess { store_id }),
+        )
+        .map_err(ectx!(ErrorKind::Forbidden))
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> StoreBillingStatusesRepo
+    for StoreBillingStatusesRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewStoreBillingStatus) -> RepoResultV2<StoreBillingStatus> {
+        debug!("create billing status of store {}.", payload.store_id);
+        self.check_access(Action::Write, payload.store_id)?;
+
+        let command = diesel::insert_into(StoreBillingStatusesDsl::store_billing_statuses).values(&payload);
+
+        command.get_result::<StoreBillingStatus>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get(&self, store_id: StoreId) -> RepoResultV2<Option<StoreBillingStatus>> {
+        debug!("get billing status of store {}.", store_id);
+        self.check_access(Action::Read, store_id)?;
+
+        StoreBillingStatusesDsl::store_billing_statuses
+            .filter(StoreBillingStatusesDsl::store_id.eq(store_id))
+            .get_result::<StoreBillingStatus>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn list(&self) -> RepoResultV2<Vec<StoreBillingStatus>> {
+        debug!("list store billing statuses.");
+        acl::check(&*self.acl, Resource::StoreBillingStatus, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        StoreBillingStatusesDsl::store_billing_statuses
+            .order_by(StoreBillingStatusesDsl::store_id.asc())
+            .get_results::<StoreBillingStatus>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn update(&self, store_id: StoreId, payload: UpdateStoreBillingStatus) -> RepoResultV2<StoreBillingStatus> {
+        debug!("update billing status of store {}.", store_id);
+        self.check_access(Action::Write, store_id)?;
+
+        let filter = StoreBillingStatusesDsl::store_billing_statuses.filter(StoreBillingStatusesDsl::store_id.eq(store_id));
+
+        diesel::update(filter)
+            .set(&payload)
+            .get_result::<StoreBillingStatus>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, StoreBillingStatusAccess>
+    for StoreBillingStatusesRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, user_id: UserId, scope: &Scope, obj: Option<&StoreBillingStatusAccess>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => {
+                if let Some(StoreBillingStatusAccess { store_id }) = obj {
+                    UserRolesDsl::roles
+                        .filter(UserRolesDsl::user_id.eq(user_id))
+                        .get_results::<UserRole>(self.db_conn)
+                        .map_err(From::from)
+                        .map(|user_roles_arg| {
+                            user_roles_arg
+                                .iter()
+                                .any(|user_role_arg| user_role_arg.data.clone().map(|data| data == store_id.0).unwrap_or_default())
+                        })
+                        .unwrap_or_else(|_: FailureError| false)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
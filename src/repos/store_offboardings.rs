@@ -0,0 +1,108 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{StoreId, UserId};
+
+use models::authorization::*;
+use models::{NewStoreOffboarding, StoreOffboarding, UpdateStoreOffboarding};
+use repos::legacy_acl::*;
+
+use schema::store_offboardings::dsl as StoreOffboardingsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type StoreOffboardingsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, StoreOffboarding>>;
+
+pub struct StoreOffboardingsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: StoreOffboardingsRepoAcl,
+}
+
+pub trait StoreOffboardingsRepo {
+    /// Starts the offboarding of the store, the offboarding started before is returned if there is one
+    fn get_or_create(&self, payload: NewStoreOffboarding) -> RepoResultV2<StoreOffboarding>;
+    fn get(&self, store_id: StoreId) -> RepoResultV2<Option<StoreOffboarding>>;
+    fn update(&self, store_id: StoreId, payload: UpdateStoreOffboarding) -> RepoResultV2<StoreOffboarding>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> StoreOffboardingsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: StoreOffboardingsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> StoreOffboardingsRepo
+    for StoreOffboardingsRepoImpl<'a, T>
+{
+    fn get_or_create(&self, payload: NewStoreOffboarding) -> RepoResultV2<StoreOffboarding> {
+        debug!("get or create offboarding of store {}.", payload.store_id);
+        acl::check(&*self.acl, Resource::StoreOffboarding, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let store_id = payload.store_id;
+
+        diesel::insert_into(StoreOffboardingsDsl::store_offboardings)
+            .values(&payload)
+            .on_conflict_do_nothing()
+            .execute(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        StoreOffboardingsDsl::store_offboardings
+            .filter(StoreOffboardingsDsl::store_id.eq(store_id))
+            .get_result::<StoreOffboarding>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn get(&self, store_id: StoreId) -> RepoResultV2<Option<StoreOffboarding>> {
+        debug!("get offboarding of store {}.", store_id);
+        acl::check(&*self.acl, Resource::StoreOffboarding, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        StoreOffboardingsDsl::store_offboardings
+            .filter(StoreOffboardingsDsl::store_id.eq(store_id))
+            .get_result::<StoreOffboarding>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn update(&self, store_id: StoreId, payload: UpdateStoreOffboarding) -> RepoResultV2<StoreOffboarding> {
+        debug!("update offboarding of store {}.", store_id);
+        acl::check(&*self.acl, Resource::StoreOffboarding, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let filter = StoreOffboardingsDsl::store_offboardings.filter(StoreOffboardingsDsl::store_id.eq(store_id));
+
+        diesel::update(filter)
+            .set(&payload)
+            .get_result::<StoreOffboarding>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, StoreOffboarding>
+    for StoreOffboardingsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&StoreOffboarding>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -114,7 +114,9 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         acl::check(&*self.acl, Resource::StoreSubscription, Action::Write, self, access.as_ref())
             .map_err(ectx!(try ErrorKind::Forbidden))?;
 
-        if payload.status.is_some() && payload.status != updated_entry.map(|s| s.status) {
+        let status_changed = payload.status.is_some() && payload.status != updated_entry.as_ref().map(|s| s.status);
+        let trial_end_changed = payload.trial_end_date.is_some() && payload.trial_end_date != updated_entry.and_then(|s| s.trial_end_date);
+        if status_changed || trial_end_changed {
             acl::check(&*self.acl, Resource::StoreSubscriptionStatus, Action::Write, self, None)
                 .map_err(ectx!(try ErrorKind::Forbidden))?;
         }
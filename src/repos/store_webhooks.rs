@@ -0,0 +1,173 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::{StoreId, UserId};
+
+use models::authorization::*;
+use models::{NewStoreWebhook, StoreWebhook, StoreWebhookId, UpdateStoreWebhook, UserRole};
+use repos::legacy_acl::*;
+
+use schema::roles::dsl as UserRolesDsl;
+use schema::store_webhooks::dsl as StoreWebhooksDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type StoreWebhooksRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, StoreWebhookAccess>>;
+
+pub struct StoreWebhookAccess {
+    store_id: StoreId,
+}
+
+pub struct StoreWebhooksRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: StoreWebhooksRepoAcl,
+}
+
+pub trait StoreWebhooksRepo {
+    fn create(&self, payload: NewStoreWebhook) -> RepoResultV2<StoreWebhook>;
+    fn get(&self, id: StoreWebhookId) -> RepoResultV2<Option<StoreWebhook>>;
+    fn list_by_store_id(&self, store_id: StoreId) -> RepoResultV2<Vec<StoreWebhook>>;
+    fn update(&self, id: StoreWebhookId, payload: UpdateStoreWebhook) -> RepoResultV2<StoreWebhook>;
+    fn delete(&self, id: StoreWebhookId) -> RepoResultV2<StoreWebhook>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> StoreWebhooksRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: StoreWebhooksRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+
+    fn check_access(&self, action: Action, store_id: StoreId) -> RepoResultV2<()> {
+        acl::check(
+            &*self.acl,
+            Resource::StoreWebhook,
+            action,
+            self,
+            Some(&StoreWebhookAccess { store_id }),
+        )
+        .map_err(ectx!(ErrorKind::Forbidden))
+    }
+
+    fn get_by_id(&self, id: StoreWebhookId) -> RepoResultV2<Option<StoreWebhook>> {
+        StoreWebhooksDsl::store_webhooks
+            .filter(StoreWebhooksDsl::id.eq(id))
+            .get_result::<StoreWebhook>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> StoreWebhooksRepo
+    for StoreWebhooksRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewStoreWebhook) -> RepoResultV2<StoreWebhook> {
+        debug!("create store webhook for store {}.", payload.store_id);
+        self.check_access(Action::Write, payload.store_id)?;
+
+        let command = diesel::insert_into(StoreWebhooksDsl::store_webhooks).values(&payload);
+
+        command.get_result::<StoreWebhook>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get(&self, id: StoreWebhookId) -> RepoResultV2<Option<StoreWebhook>> {
+        debug!("get store webhook {}.", id);
+
+        let store_webhook = self.get_by_id(id)?;
+
+        if let Some(ref store_webhook) = store_webhook {
+            self.check_access(Action::Read, store_webhook.store_id)?;
+        }
+
+        Ok(store_webhook)
+    }
+
+    fn list_by_store_id(&self, store_id: StoreId) -> RepoResultV2<Vec<StoreWebhook>> {
+        debug!("list store webhooks for store {}.", store_id);
+        self.check_access(Action::Read, store_id)?;
+
+        StoreWebhooksDsl::store_webhooks
+            .filter(StoreWebhooksDsl::store_id.eq(store_id))
+            .order_by(StoreWebhooksDsl::id.asc())
+            .get_results::<StoreWebhook>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn update(&self, id: StoreWebhookId, payload: UpdateStoreWebhook) -> RepoResultV2<StoreWebhook> {
+        debug!("update store webhook {}.", id);
+
+        let store_webhook = self.get_by_id(id)?.ok_or_else(|| {
+            let e = format_err!("store webhook {} not found", id);
+            ectx!(try err e, ErrorKind::NotFound)
+        })?;
+        self.check_access(Action::Write, store_webhook.store_id)?;
+
+        let filter = StoreWebhooksDsl::store_webhooks.filter(StoreWebhooksDsl::id.eq(id));
+
+        diesel::update(filter)
+            .set(&payload)
+            .get_result::<StoreWebhook>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn delete(&self, id: StoreWebhookId) -> RepoResultV2<StoreWebhook> {
+        debug!("delete store webhook {}.", id);
+
+        let store_webhook = self.get_by_id(id)?.ok_or_else(|| {
+            let e = format_err!("store webhook {} not found", id);
+            ectx!(try err e, ErrorKind::NotFound)
+        })?;
+        self.check_access(Action::Write, store_webhook.store_id)?;
+
+        let filter = StoreWebhooksDsl::store_webhooks.filter(StoreWebhooksDsl::id.eq(id));
+
+        diesel::delete(filter).get_result::<StoreWebhook>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, StoreWebhookAccess>
+    for StoreWebhooksRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, user_id: UserId, scope: &Scope, obj: Option<&StoreWebhookAccess>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => {
+                if let Some(StoreWebhookAccess { store_id }) = obj {
+                    UserRolesDsl::roles
+                        .filter(UserRolesDsl::user_id.eq(user_id))
+                        .get_results::<UserRole>(self.db_conn)
+                        .map_err(From::from)
+                        .map(|user_roles_arg| {
+                            user_roles_arg
+                                .iter()
+                                .any(|user_role_arg| user_role_arg.data.clone().map(|data| data == store_id.0).unwrap_or_default())
+                        })
+                        .unwrap_or_else(|_: FailureError| false)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::{NewStripeFeeBackfill, StripeFeeBackfill, StripeFeeBackfillId, StripeFeeBackfillProgress};
+use repos::legacy_acl::*;
+
+use schema::stripe_fee_backfills::dsl as StripeFeeBackfillsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type StripeFeeBackfillsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, StripeFeeBackfill>>;
+
+pub struct StripeFeeBackfillsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: StripeFeeBackfillsRepoAcl,
+}
+
+pub trait StripeFeeBackfillsRepo {
+    fn create(&self, payload: NewStripeFeeBackfill) -> RepoResultV2<StripeFeeBackfill>;
+    fn get(&self, id: StripeFeeBackfillId) -> RepoResultV2<Option<StripeFeeBackfill>>;
+    fn update_progress(&self, id: StripeFeeBackfillId, progress: StripeFeeBackfillProgress) -> RepoResultV2<StripeFeeBackfill>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> StripeFeeBackfillsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: StripeFeeBackfillsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> StripeFeeBackfillsRepo
+    for StripeFeeBackfillsRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewStripeFeeBackfill) -> RepoResultV2<StripeFeeBackfill> {
+        debug!("create stripe fee backfill for {} orders.", payload.total_orders);
+        acl::check(&*self.acl, Resource::StripeFeeBackfill, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(StripeFeeBackfillsDsl::stripe_fee_backfills).values(&payload);
+
+        command.get_result::<StripeFeeBackfill>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get(&self, id: StripeFeeBackfillId) -> RepoResultV2<Option<StripeFeeBackfill>> {
+        debug!("get stripe fee backfill by id {}.", id);
+        acl::check(&*self.acl, Resource::StripeFeeBackfill, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        StripeFeeBackfillsDsl::stripe_fee_backfills
+            .filter(StripeFeeBackfillsDsl::id.eq(id))
+            .get_result::<StripeFeeBackfill>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn update_progress(&self, id: StripeFeeBackfillId, progress: StripeFeeBackfillProgress) -> RepoResultV2<StripeFeeBackfill> {
+        debug!("update progress of stripe fee backfill {}: {:?}.", id, progress);
+        acl::check(&*self.acl, Resource::StripeFeeBackfill, Action::Write, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let filter = StripeFeeBackfillsDsl::stripe_fee_backfills.filter(StripeFeeBackfillsDsl::id.eq(id));
+
+        diesel::update(filter)
+            .set(&progress)
+            .get_result::<StripeFeeBackfill>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, StripeFeeBackfill>
+    for StripeFeeBackfillsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&StripeFeeBackfill>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => false,
+        }
+    }
+}
@@ -10,7 +10,7 @@ use diesel::Connection;
 use failure::Error as FailureError;
 use failure::Fail;
 
-use stq_types::{StoreId, UserId};
+use stq_types::{StoreId, SubscriptionPaymentId, UserId};
 
 use models::authorization::*;
 use models::{NewSubscription, Subscription, SubscriptionSearch, UpdateSubscription, UserRole};
@@ -41,6 +41,7 @@ pub trait SubscriptionRepo {
     fn get(&self, search: SubscriptionSearch) -> RepoResultV2<Option<Subscription>>;
     fn get_unpaid(&self) -> RepoResultV2<Vec<Subscription>>;
     fn search(&self, search: SubscriptionSearch) -> RepoResultV2<Vec<Subscription>>;
+    fn get_by_subscription_payment_ids(&self, subscription_payment_ids: Vec<SubscriptionPaymentId>) -> RepoResultV2<Vec<Subscription>>;
     fn update(&self, search: SubscriptionSearch, payload: UpdateSubscription) -> RepoResultV2<Subscription>;
 }
 
@@ -152,6 +153,34 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
         Ok(subscriptions)
     }
 
+    fn get_by_subscription_payment_ids(&self, subscription_payment_ids: Vec<SubscriptionPaymentId>) -> RepoResultV2<Vec<Subscription>> {
+        debug!("get subscriptions by subscription payment ids {:?}.", subscription_payment_ids);
+
+        let subscriptions = SubscriptionDsl::subscription
+            .filter(SubscriptionDsl::subscription_payment_id.eq_any(subscription_payment_ids))
+            .order_by(SubscriptionDsl::created_at)
+            .get_results::<Subscription>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        let store_ids: HashSet<StoreId> = subscriptions.iter().map(|s| s.store_id).collect();
+
+        for store_id in store_ids {
+            acl::check(
+                &*self.acl,
+                Resource::Subscription,
+                Action::Read,
+                self,
+                Some(&SubscriptionAccess { store_id }),
+            )
+            .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(subscriptions)
+    }
+
     fn update(&self, search_params: SubscriptionSearch, payload: UpdateSubscription) -> RepoResultV2<Subscription> {
         debug!("update subscription {:?}.", search_params);
         let updated_entry = self.get(search_params.clone())?;
@@ -0,0 +1,229 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::upsert::excluded;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId as StqUserId;
+
+use models::authorization::*;
+use models::invoice_v2::InvoiceId;
+use models::{Amount, Currency, NewUserCreditBalance, NewUserCreditEntry, UserCreditAccess, UserCreditBalance, UserCreditEntry, UserId};
+use repos::legacy_acl::*;
+
+use schema::user_credit_balances::dsl as UserCreditBalancesDsl;
+use schema::user_credit_entries::dsl as UserCreditEntriesDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+pub type UserCreditsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, UserCreditAccess>>;
+
+pub struct UserCreditsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: UserCreditsRepoAcl,
+}
+
+pub trait UserCreditsRepo {
+    fn get_balances(&self, user_id: UserId) -> RepoResultV2<Vec<UserCreditBalance>>;
+    fn get_balance(&self, user_id: UserId, currency: Currency) -> RepoResultV2<Option<UserCreditBalance>>;
+    /// Adds the amount to the balance, creating the balance if the user has none in the currency
+    fn credit(&self, user_id: UserId, currency: Currency, amount: Amount) -> RepoResultV2<UserCreditBalance>;
+    /// Takes the amount from the balance unless the balance is lower, returns `None` in that case
+    fn debit(&self, user_id: UserId, currency: Currency, amount: Amount) -> RepoResultV2<Option<UserCreditBalance>>;
+    fn create_entry(&self, payload: NewUserCreditEntry) -> RepoResultV2<UserCreditEntry>;
+    fn list_entries(&self, user_id: UserId) -> RepoResultV2<Vec<UserCreditEntry>>;
+    fn get_entries_by_invoice_id(&self, invoice_id: InvoiceId) -> RepoResultV2<Vec<UserCreditEntry>>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> UserCreditsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: UserCreditsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> UserCreditsRepo
+    for UserCreditsRepoImpl<'a, T>
+{
+    fn get_balances(&self, user_id: UserId) -> RepoResultV2<Vec<UserCreditBalance>> {
+        debug!("get credit balances of user {}.", user_id);
+        acl::check(
+            &*self.acl,
+            Resource::UserCredit,
+            Action::Read,
+            self,
+            Some(&UserCreditAccess { user_id }),
+        )
+        .map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        UserCreditBalancesDsl::user_credit_balances
+            .filter(UserCreditBalancesDsl::user_id.eq(user_id))
+            .order(UserCreditBalancesDsl::currency)
+            .get_results::<UserCreditBalance>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn get_balance(&self, user_id: UserId, currency: Currency) -> RepoResultV2<Option<UserCreditBalance>> {
+        debug!("get credit balance of user {} in {}.", user_id, currency);
+        acl::check(
+            &*self.acl,
+            Resource::UserCredit,
+            Action::Read,
+            self,
+            Some(&UserCreditAccess { user_id }),
+        )
+        .map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        UserCreditBalancesDsl::user_credit_balances
+            .filter(UserCreditBalancesDsl::user_id.eq(user_id))
+            .filter(UserCreditBalancesDsl::currency.eq(currency))
+            .get_result::<UserCreditBalance>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn credit(&self, user_id: UserId, currency: Currency, amount: Amount) -> RepoResultV2<UserCreditBalance> {
+        debug!("credit {} {} to user {}.", amount, currency, user_id);
+        acl::check(
+            &*self.acl,
+            Resource::UserCredit,
+            Action::Write,
+            self,
+            Some(&UserCreditAccess { user_id }),
+        )
+        .map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let payload = NewUserCreditBalance {
+            user_id,
+            currency,
+            balance: amount,
+        };
+
+        let command = diesel::insert_into(UserCreditBalancesDsl::user_credit_balances)
+            .values(&payload)
+            .on_conflict((UserCreditBalancesDsl::user_id, UserCreditBalancesDsl::currency))
+            .do_update()
+            .set(UserCreditBalancesDsl::balance.eq(UserCreditBalancesDsl::balance + excluded(UserCreditBalancesDsl::balance)));
+
+        command.get_result::<UserCreditBalance>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn debit(&self, user_id: UserId, currency: Currency, amount: Amount) -> RepoResultV2<Option<UserCreditBalance>> {
+        debug!("debit {} {} from user {}.", amount, currency, user_id);
+        acl::check(
+            &*self.acl,
+            Resource::UserCredit,
+            Action::Write,
+            self,
+            Some(&UserCreditAccess { user_id }),
+        )
+        .map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        // The balance is checked by the update itself, so concurrent checkouts cannot spend the same credit twice
+        let filter = UserCreditBalancesDsl::user_credit_balances
+            .filter(UserCreditBalancesDsl::user_id.eq(user_id))
+            .filter(UserCreditBalancesDsl::currency.eq(currency))
+            .filter(UserCreditBalancesDsl::balance.ge(amount));
+
+        diesel::update(filter)
+            .set(UserCreditBalancesDsl::balance.eq(UserCreditBalancesDsl::balance - amount))
+            .get_result::<UserCreditBalance>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn create_entry(&self, payload: NewUserCreditEntry) -> RepoResultV2<UserCreditEntry> {
+        debug!("create user credit entry {:?}.", payload);
+        acl::check(
+            &*self.acl,
+            Resource::UserCredit,
+            Action::Write,
+            self,
+            Some(&UserCreditAccess { user_id: payload.user_id }),
+        )
+        .map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        diesel::insert_into(UserCreditEntriesDsl::user_credit_entries)
+            .values(&payload)
+            .get_result::<UserCreditEntry>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn list_entries(&self, user_id: UserId) -> RepoResultV2<Vec<UserCreditEntry>> {
+        debug!("list credit entries of user {}.", user_id);
+        acl::check(
+            &*self.acl,
+            Resource::UserCredit,
+            Action::Read,
+            self,
+            Some(&UserCreditAccess { user_id }),
+        )
+        .map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        UserCreditEntriesDsl::user_credit_entries
+            .filter(UserCreditEntriesDsl::user_id.eq(user_id))
+            .order(UserCreditEntriesDsl::created_at.desc())
+            .get_results::<UserCreditEntry>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
+    fn get_entries_by_invoice_id(&self, invoice_id: InvoiceId) -> RepoResultV2<Vec<UserCreditEntry>> {
+        debug!("get credit entries of invoice {}.", invoice_id);
+
+        let entries = UserCreditEntriesDsl::user_credit_entries
+            .filter(UserCreditEntriesDsl::invoice_id.eq(invoice_id))
+            .order(UserCreditEntriesDsl::created_at)
+            .get_results::<UserCreditEntry>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        for entry in &entries {
+            acl::check(
+                &*self.acl,
+                Resource::UserCredit,
+                Action::Read,
+                self,
+                Some(&UserCreditAccess { user_id: entry.user_id }),
+            )
+            .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(entries)
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, UserCreditAccess>
+    for UserCreditsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, user_id: StqUserId, scope: &Scope, obj: Option<&UserCreditAccess>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => obj.map(|access| access.user_id.inner() == user_id.0).unwrap_or(false),
+        }
+    }
+}
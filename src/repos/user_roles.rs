@@ -31,6 +31,9 @@ pub trait UserRolesRepo {
     /// Returns list of user_roles for a specific user
     fn list_for_user(&self, user_id: UserId) -> RepoResult<Vec<BillingRole>>;
 
+    /// Returns IDs of the users having the role
+    fn list_user_ids_by_role(&self, role: BillingRole) -> RepoResultV2<Vec<UserId>>;
+
     /// Create a new user role
     fn create(&self, payload: NewUserRole) -> RepoResult<UserRole>;
 
@@ -118,6 +121,23 @@ where
         }
     }
 
+    /// Returns IDs of the users having the role
+    fn list_user_ids_by_role(&self, role: BillingRole) -> RepoResultV2<Vec<UserId>> {
+        debug!("list users with role {:?}.", role);
+        acl::check(&*self.acl, Resource::UserRoles, Action::Read, self, None).map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        roles
+            .filter(name.eq(role))
+            .select(user_id)
+            .distinct()
+            .order_by(user_id.asc())
+            .get_results::<UserId>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
     /// Create a new user role
     fn create(&self, payload: NewUserRole) -> RepoResult<UserRole> {
         debug!("create new user role {:?}.", payload);
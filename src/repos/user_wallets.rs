@@ -24,6 +24,8 @@ pub trait UserWalletsRepo {
     fn add(&self, payload: NewActiveUserWallet) -> RepoResultV2<UserWallet>;
     fn get(&self, id: UserWalletId) -> RepoResultV2<Option<UserWallet>>;
     fn get_currency_wallets_by_user_id(&self, currency: TureCurrency, user_id: UserId) -> RepoResultV2<Vec<UserWallet>>;
+    /// Active and deactivated wallets of the user with the address
+    fn get_by_address(&self, user_id: UserId, currency: TureCurrency, address: WalletAddress) -> RepoResultV2<Vec<UserWallet>>;
     fn deactivate(&self, id: UserWalletId) -> RepoResultV2<UserWallet>;
     fn deactivate_wallets_by_user_id(&self, user_id: UserId) -> RepoResultV2<Vec<UserWallet>>;
 }
@@ -116,6 +118,35 @@ impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager
             })
     }
 
+    fn get_by_address(&self, user_id: UserId, currency: TureCurrency, address: WalletAddress) -> RepoResultV2<Vec<UserWallet>> {
+        debug!(
+            "Getting user wallets for currency {} with user ID: {} and address: {}",
+            currency, user_id, address
+        );
+
+        acl::check(
+            &*self.acl,
+            Resource::UserWallet,
+            Action::Read,
+            self,
+            Some(&UserWalletAccess { user_id }),
+        )
+        .map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let query = UserWallets::user_wallets
+            .filter(UserWallets::currency.eq(currency))
+            .filter(UserWallets::user_id.eq(user_id))
+            .filter(UserWallets::address.eq(address));
+
+        query
+            .get_results::<RawUserWallet>(self.db_conn)
+            .map(|raw_user_wallets| raw_user_wallets.into_iter().map(UserWallet::from).collect::<Vec<_>>())
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+
     fn deactivate(&self, user_wallet_id: UserWalletId) -> RepoResultV2<UserWallet> {
         debug!("Deactivating a user wallet with ID: {}", user_wallet_id);
 
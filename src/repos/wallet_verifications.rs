@@ -0,0 +1,175 @@
+use diesel;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_dsl::RunQueryDsl;
+use diesel::Connection;
+use failure::Error as FailureError;
+use failure::Fail;
+
+use stq_types::UserId;
+
+use models::authorization::*;
+use models::{
+    NewWalletVerification, UpdateWalletVerification, UserWalletAccess, UserWalletId, WalletVerification, WalletVerificationId,
+    WalletVerificationStatus,
+};
+use repos::legacy_acl::*;
+
+use schema::wallet_verifications::dsl as WalletVerificationsDsl;
+
+use super::acl;
+use super::error::*;
+use super::types::RepoResultV2;
+
+/// Verifications are a part of the user wallet and are accessed with its permissions
+pub type WalletVerificationsRepoAcl = Box<Acl<Resource, Action, Scope, FailureError, UserWalletAccess>>;
+
+pub struct WalletVerificationsRepoImpl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> {
+    pub db_conn: &'a T,
+    pub acl: WalletVerificationsRepoAcl,
+}
+
+pub trait WalletVerificationsRepo {
+    fn create(&self, payload: NewWalletVerification) -> RepoResultV2<WalletVerification>;
+    fn get(&self, id: WalletVerificationId) -> RepoResultV2<Option<WalletVerification>>;
+    /// The most recent verification of the wallet
+    fn get_latest_by_user_wallet_id(&self, user_wallet_id: UserWalletId) -> RepoResultV2<Option<WalletVerification>>;
+    /// A confirmed verification of the wallet, if it has been verified
+    fn get_confirmed_by_user_wallet_id(&self, user_wallet_id: UserWalletId) -> RepoResultV2<Option<WalletVerification>>;
+    fn update(&self, id: WalletVerificationId, payload: UpdateWalletVerification) -> RepoResultV2<WalletVerification>;
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> WalletVerificationsRepoImpl<'a, T> {
+    pub fn new(db_conn: &'a T, acl: WalletVerificationsRepoAcl) -> Self {
+        Self { db_conn, acl }
+    }
+
+    fn check_read(&self, wallet_verification: Option<WalletVerification>) -> RepoResultV2<Option<WalletVerification>> {
+        if let Some(ref wallet_verification) = wallet_verification {
+            acl::check(
+                &*self.acl,
+                Resource::UserWallet,
+                Action::Read,
+                self,
+                Some(&UserWalletAccess::from(wallet_verification)),
+            )
+            .map_err(ectx!(try ErrorKind::Forbidden))?;
+        }
+
+        Ok(wallet_verification)
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> WalletVerificationsRepo
+    for WalletVerificationsRepoImpl<'a, T>
+{
+    fn create(&self, payload: NewWalletVerification) -> RepoResultV2<WalletVerification> {
+        debug!("create verification of user wallet {}.", payload.user_wallet_id);
+
+        acl::check(
+            &*self.acl,
+            Resource::UserWallet,
+            Action::Write,
+            self,
+            Some(&UserWalletAccess { user_id: payload.user_id }),
+        )
+        .map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let command = diesel::insert_into(WalletVerificationsDsl::wallet_verifications).values(&payload);
+
+        command.get_result::<WalletVerification>(self.db_conn).map_err(|e| {
+            let error_kind = ErrorKind::from(&e);
+            ectx!(err e, ErrorSource::Diesel, error_kind)
+        })
+    }
+
+    fn get(&self, id: WalletVerificationId) -> RepoResultV2<Option<WalletVerification>> {
+        debug!("get wallet verification {}.", id);
+
+        let wallet_verification = WalletVerificationsDsl::wallet_verifications
+            .filter(WalletVerificationsDsl::id.eq(id))
+            .get_result::<WalletVerification>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        self.check_read(wallet_verification)
+    }
+
+    fn get_latest_by_user_wallet_id(&self, user_wallet_id: UserWalletId) -> RepoResultV2<Option<WalletVerification>> {
+        debug!("get latest verification of user wallet {}.", user_wallet_id);
+
+        let wallet_verification = WalletVerificationsDsl::wallet_verifications
+            .filter(WalletVerificationsDsl::user_wallet_id.eq(user_wallet_id))
+            .order_by(WalletVerificationsDsl::created_at.desc())
+            .first::<WalletVerification>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        self.check_read(wallet_verification)
+    }
+
+    fn get_confirmed_by_user_wallet_id(&self, user_wallet_id: UserWalletId) -> RepoResultV2<Option<WalletVerification>> {
+        debug!("get confirmed verification of user wallet {}.", user_wallet_id);
+
+        let wallet_verification = WalletVerificationsDsl::wallet_verifications
+            .filter(WalletVerificationsDsl::user_wallet_id.eq(user_wallet_id))
+            .filter(WalletVerificationsDsl::status.eq(WalletVerificationStatus::Confirmed))
+            .first::<WalletVerification>(self.db_conn)
+            .optional()
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        self.check_read(wallet_verification)
+    }
+
+    fn update(&self, id: WalletVerificationId, payload: UpdateWalletVerification) -> RepoResultV2<WalletVerification> {
+        debug!("update wallet verification {}: {:?}.", id, payload);
+
+        let wallet_verification = WalletVerificationsDsl::wallet_verifications
+            .filter(WalletVerificationsDsl::id.eq(id))
+            .get_result::<WalletVerification>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(try err e, ErrorSource::Diesel, error_kind)
+            })?;
+
+        acl::check(
+            &*self.acl,
+            Resource::UserWallet,
+            Action::Write,
+            self,
+            Some(&UserWalletAccess::from(&wallet_verification)),
+        )
+        .map_err(ectx!(try ErrorKind::Forbidden))?;
+
+        let filter = WalletVerificationsDsl::wallet_verifications.filter(WalletVerificationsDsl::id.eq(id));
+
+        diesel::update(filter)
+            .set(&payload)
+            .get_result::<WalletVerification>(self.db_conn)
+            .map_err(|e| {
+                let error_kind = ErrorKind::from(&e);
+                ectx!(err e, ErrorSource::Diesel, error_kind)
+            })
+    }
+}
+
+impl<'a, T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> CheckScope<Scope, UserWalletAccess>
+    for WalletVerificationsRepoImpl<'a, T>
+{
+    fn is_in_scope(&self, user_id: UserId, scope: &Scope, obj: Option<&UserWalletAccess>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => obj.map(|access| access.user_id.inner() == user_id.0).unwrap_or(false),
+        }
+    }
+}
@@ -1,3 +1,22 @@
+table! {
+    account_assignments (id) {
+        id -> Int8,
+        account_id -> Uuid,
+        invoice_id -> Uuid,
+        assigned_at -> Timestamp,
+        released_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    account_pool_claims (id) {
+        id -> Int8,
+        currency -> Varchar,
+        exhausted -> Bool,
+        claimed_at -> Timestamp,
+    }
+}
+
 table! {
     accounts (id) {
         id -> Uuid,
@@ -5,6 +24,7 @@ table! {
         is_pooled -> Bool,
         created_at -> Timestamp,
         wallet_address -> Text,
+        status -> Varchar,
     }
 }
 
@@ -17,6 +37,71 @@ table! {
     }
 }
 
+table! {
+    api_keys (id) {
+        id -> Int4,
+        store_id -> Int4,
+        created_by -> Int4,
+        name -> Varchar,
+        key_prefix -> Varchar,
+        key_hash -> Varchar,
+        scopes -> Jsonb,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    audit_log (id) {
+        id -> Int8,
+        actor_user_id -> Nullable<Int4>,
+        action -> Varchar,
+        resource_type -> Varchar,
+        resource_id -> Varchar,
+        before -> Nullable<Jsonb>,
+        after -> Nullable<Jsonb>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    billing_info_changes (id) {
+        id -> Int4,
+        store_id -> Int4,
+        payload -> Varchar,
+        status -> Varchar,
+        requested_by -> Int4,
+        reviewed_by -> Nullable<Int4>,
+        rejection_reason -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        reviewed_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    cashback_liability_snapshots (id) {
+        id -> Int4,
+        currency -> Varchar,
+        as_of -> Timestamp,
+        amount -> Numeric,
+        invoices_count -> Int8,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    customer_exports (id) {
+        id -> Int4,
+        incremental -> Bool,
+        updated_after -> Nullable<Timestamp>,
+        updated_until -> Timestamp,
+        object_key -> Varchar,
+        records_count -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     customers (id) {
         id -> Varchar,
@@ -24,6 +109,20 @@ table! {
         email -> Nullable<Varchar>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        payment_method_id -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    data_retention_subjects (id) {
+        id -> Int4,
+        subject_type -> Varchar,
+        subject_id -> Int4,
+        closed_at -> Nullable<Timestamp>,
+        legal_hold -> Bool,
+        legal_hold_reason -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
@@ -36,6 +135,106 @@ table! {
         created_at -> Timestamp,
         status_updated_at -> Timestamp,
         scheduled_on -> Nullable<Timestamp>,
+        locked_by -> Nullable<Text>,
+        lease_expires_at -> Nullable<Timestamp>,
+        payload_type -> Text,
+        timings -> Nullable<Jsonb>,
+    }
+}
+
+table! {
+    exchange_rate_slippage_metrics (seller_currency, buyer_currency) {
+        seller_currency -> Varchar,
+        buyer_currency -> Varchar,
+        orders_count -> Int8,
+        refreshed_orders_count -> Int8,
+        initial_price_total -> Numeric,
+        final_price_total -> Numeric,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    fee_adjustments (id) {
+        id -> Int4,
+        fee_id -> Int4,
+        order_id -> Uuid,
+        currency -> Varchar,
+        refund_amount -> Numeric,
+        seller_amount -> Numeric,
+        fee_amount -> Numeric,
+        fee_charge_id -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        charged_currency -> Nullable<Varchar>,
+        charged_fee_amount -> Nullable<Numeric>,
+    }
+}
+
+table! {
+    fee_crypto_payment_transactions (transaction_id) {
+        transaction_id -> Uuid,
+        fee_crypto_payment_id -> Uuid,
+        amount -> Numeric,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    fee_crypto_payments (id) {
+        id -> Uuid,
+        fee_id -> Int4,
+        account_id -> Uuid,
+        currency -> Varchar,
+        amount -> Numeric,
+        amount_received -> Numeric,
+        status -> Varchar,
+        expires_at -> Timestamp,
+        closed_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    fee_dunnings (store_id) {
+        store_id -> Int4,
+        status -> Varchar,
+        steps_taken -> Int4,
+        last_action -> Nullable<Varchar>,
+        started_at -> Timestamp,
+        last_step_at -> Nullable<Timestamp>,
+        next_step_at -> Nullable<Timestamp>,
+        resolved_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    fee_statements (id) {
+        id -> Int4,
+        store_id -> Int4,
+        currency -> Varchar,
+        period_start -> Timestamp,
+        period_end -> Timestamp,
+        fees_amount -> Numeric,
+        adjustments_amount -> Numeric,
+        taxes_amount -> Numeric,
+        total_amount -> Numeric,
+        lines -> Jsonb,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    feature_flags (name) {
+        name -> Varchar,
+        enabled -> Bool,
+        rollout_percentage -> Int4,
+        store_ids -> Jsonb,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
@@ -52,6 +251,22 @@ table! {
         updated_at -> Timestamp,
         crypto_currency -> Nullable<Varchar>,
         crypto_amount -> Nullable<Numeric>,
+        exchange_rate -> Nullable<Float8>,
+        currency_exchange_id -> Nullable<Uuid>,
+        converted_at -> Nullable<Timestamp>,
+        charged_currency -> Nullable<Varchar>,
+        charged_amount -> Nullable<Numeric>,
+        charge_exchange_rate -> Nullable<Float8>,
+    }
+}
+
+table! {
+    historical_reports (as_of) {
+        as_of -> Timestamp,
+        invoices -> Jsonb,
+        fees -> Jsonb,
+        payouts -> Jsonb,
+        created_at -> Timestamp,
     }
 }
 
@@ -71,6 +286,38 @@ table! {
     }
 }
 
+table! {
+    invoice_buyer_details (invoice_id) {
+        invoice_id -> Uuid,
+        buyer_user_id -> Int4,
+        country -> Varchar,
+        details -> Varchar,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    invoice_callback_deliveries (id) {
+        id -> Int4,
+        invoice_callback_id -> Int4,
+        notification_id -> Uuid,
+        event_type -> Varchar,
+        error -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    invoice_callbacks (id) {
+        id -> Int4,
+        invoice_id -> Uuid,
+        url -> Varchar,
+        secret -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     invoices (id) {
         id -> Uuid,
@@ -87,6 +334,39 @@ table! {
     }
 }
 
+table! {
+    invoice_requotes (invoice_id) {
+        invoice_id -> Uuid,
+        original_invoice_id -> Uuid,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    invoice_risk_assessments (invoice_id) {
+        invoice_id -> Uuid,
+        buyer_user_id -> Int4,
+        buyer_ip -> Nullable<Varchar>,
+        score -> Int4,
+        decision -> Varchar,
+        reasons -> Array<Text>,
+        review_status -> Nullable<Varchar>,
+        reviewed_by -> Nullable<Int4>,
+        reviewed_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    invoice_snapshots (id) {
+        id -> Int4,
+        invoice_id -> Uuid,
+        dump -> Jsonb,
+        paid_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     invoices_v2 (id) {
         id -> Uuid,
@@ -100,6 +380,11 @@ table! {
         updated_at -> Timestamp,
         buyer_user_id -> Int4,
         status -> Text,
+        metadata -> Nullable<Jsonb>,
+        memo -> Nullable<Text>,
+        po_number -> Nullable<Varchar>,
+        price_reserved -> Timestamp,
+        public_token -> Varchar,
     }
 }
 
@@ -114,6 +399,27 @@ table! {
     }
 }
 
+table! {
+    negative_store_balances (id) {
+        id -> Int4,
+        store_id -> Int4,
+        currency -> Varchar,
+        amount -> Numeric,
+        detected_at -> Timestamp,
+        updated_at -> Timestamp,
+        notified_at -> Nullable<Timestamp>,
+        resolved_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    order_capture_approvals (order_id) {
+        order_id -> Uuid,
+        payment_intent_id -> Varchar,
+        approved_at -> Timestamp,
+    }
+}
+
 table! {
     order_exchange_rates (id) {
         id -> Int8,
@@ -126,6 +432,20 @@ table! {
     }
 }
 
+table! {
+    order_line_items (id) {
+        id -> Uuid,
+        order_id -> Uuid,
+        product_id -> Int4,
+        name -> Varchar,
+        quantity -> Int4,
+        unit_price -> Numeric,
+        refunded_quantity -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 table! {
     order_payouts (id) {
         id -> Int8,
@@ -134,6 +454,22 @@ table! {
     }
 }
 
+table! {
+    order_rate_slippages (id) {
+        id -> Int4,
+        order_id -> Uuid,
+        invoice_id -> Uuid,
+        seller_currency -> Varchar,
+        buyer_currency -> Varchar,
+        initial_rate -> Numeric,
+        final_rate -> Numeric,
+        rate_refreshes -> Int4,
+        initial_price -> Numeric,
+        final_price -> Numeric,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     orders (id) {
         id -> Uuid,
@@ -163,6 +499,19 @@ table! {
     }
 }
 
+table! {
+    payment_attempts (id) {
+        id -> Int8,
+        invoice_id -> Uuid,
+        payment_intent_id -> Varchar,
+        status -> Varchar,
+        decline_code -> Nullable<Varchar>,
+        error_code -> Nullable<Varchar>,
+        payment_method_fingerprint -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     payment_intent (id) {
         id -> Varchar,
@@ -176,6 +525,17 @@ table! {
         status -> Varchar,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        status_event_created_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    payment_intent_history (id) {
+        id -> Int8,
+        payment_intent_id -> Varchar,
+        status -> Varchar,
+        source -> Varchar,
+        created_at -> Timestamp,
     }
 }
 
@@ -199,6 +559,61 @@ table! {
     }
 }
 
+table! {
+    payment_recoveries (id) {
+        id -> Uuid,
+        invoice_id -> Uuid,
+        buyer_user_id -> Int4,
+        status -> Varchar,
+        failed_payment_intent_id -> Varchar,
+        failure_message -> Nullable<Varchar>,
+        failures_count -> Int4,
+        retry_payment_intent_id -> Nullable<Varchar>,
+        notified_at -> Nullable<Timestamp>,
+        retried_at -> Nullable<Timestamp>,
+        closed_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    payout_instructions (id) {
+        id -> Int4,
+        store_id -> Int4,
+        currency -> Varchar,
+        total_amount -> Numeric,
+        order_ids -> Jsonb,
+        reference_code -> Varchar,
+        document -> Jsonb,
+        created_at -> Timestamp,
+        stripe_fee -> Numeric,
+    }
+}
+
+table! {
+    payout_restriction_overrides (store_id) {
+        store_id -> Int4,
+        reason -> Varchar,
+        granted_by -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    payout_statements (id) {
+        id -> Int4,
+        payout_id -> Uuid,
+        user_id -> Int4,
+        currency -> Varchar,
+        gross_amount -> Numeric,
+        net_amount -> Numeric,
+        lines -> Jsonb,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     payouts (id) {
         id -> Uuid,
@@ -211,6 +626,10 @@ table! {
         payout_target_type -> Text,
         wallet_address -> Nullable<Text>,
         blockchain_fee -> Nullable<Numeric>,
+        fee_payer -> Text,
+        carried_fee -> Numeric,
+        request_id -> Nullable<Uuid>,
+        order_set_key -> Nullable<Text>,
     }
 }
 
@@ -254,6 +673,21 @@ table! {
     }
 }
 
+table! {
+    store_billing_statuses (store_id) {
+        store_id -> Int4,
+        state -> Varchar,
+        suspension_reason -> Nullable<Varchar>,
+        overdue_fees_count -> Int4,
+        unpaid_since -> Nullable<Timestamp>,
+        overridden_by -> Nullable<Int4>,
+        override_until -> Nullable<Timestamp>,
+        evaluated_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 table! {
     store_billing_type (id) {
         id -> Int4,
@@ -262,6 +696,24 @@ table! {
     }
 }
 
+table! {
+    store_offboardings (store_id) {
+        store_id -> Int4,
+        status -> Varchar,
+        requested_by -> Int4,
+        subscription_cancelled_at -> Nullable<Timestamp>,
+        subscription_account_id -> Nullable<Uuid>,
+        fees_written_off_at -> Nullable<Timestamp>,
+        written_off_fees -> Jsonb,
+        final_payout_scheduled_at -> Nullable<Timestamp>,
+        final_payouts -> Jsonb,
+        billing_info_archived_at -> Nullable<Timestamp>,
+        completed_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 table! {
     store_subscription (store_id) {
         store_id -> Int4,
@@ -272,6 +724,34 @@ table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         status -> Varchar,
+        trial_end_date -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    store_webhooks (id) {
+        id -> Int4,
+        store_id -> Int4,
+        url -> Varchar,
+        secret -> Varchar,
+        event_types -> Jsonb,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    stripe_fee_backfills (id) {
+        id -> Int4,
+        status -> Varchar,
+        order_ids -> Jsonb,
+        total_orders -> Int4,
+        processed_orders -> Int4,
+        updated_orders -> Int4,
+        failed_orders -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
     }
 }
 
@@ -295,6 +775,32 @@ table! {
         transaction_id -> Nullable<Uuid>,
         status -> Varchar,
         created_at -> Timestamp,
+        quantity -> Nullable<Int4>,
+        unit_price -> Nullable<Numeric>,
+    }
+}
+
+table! {
+    user_credit_balances (user_id, currency) {
+        user_id -> Int4,
+        currency -> Varchar,
+        balance -> Numeric,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    user_credit_entries (id) {
+        id -> Uuid,
+        user_id -> Int4,
+        currency -> Varchar,
+        kind -> Varchar,
+        amount -> Numeric,
+        invoice_id -> Nullable<Uuid>,
+        reason -> Nullable<Varchar>,
+        created_by -> Nullable<Int4>,
+        created_at -> Timestamp,
     }
 }
 
@@ -309,43 +815,116 @@ table! {
     }
 }
 
+table! {
+    wallet_verifications (id) {
+        id -> Uuid,
+        user_wallet_id -> Uuid,
+        user_id -> Int4,
+        amount -> Numeric,
+        status -> Varchar,
+        expires_at -> Timestamp,
+        confirmed_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+joinable!(account_assignments -> accounts (account_id));
+joinable!(account_assignments -> invoices_v2 (invoice_id));
 joinable!(amounts_received -> invoices_v2 (invoice_id));
+joinable!(fee_adjustments -> fees (fee_id));
+joinable!(fee_crypto_payment_transactions -> fee_crypto_payments (fee_crypto_payment_id));
+joinable!(fee_crypto_payments -> accounts (account_id));
+joinable!(fee_crypto_payments -> fees (fee_id));
+joinable!(fee_adjustments -> orders (order_id));
 joinable!(fees -> orders (order_id));
+joinable!(invoice_buyer_details -> invoices_v2 (invoice_id));
+joinable!(invoice_callback_deliveries -> invoice_callbacks (invoice_callback_id));
+joinable!(invoice_callbacks -> invoices_v2 (invoice_id));
+joinable!(invoice_snapshots -> invoices_v2 (invoice_id));
 joinable!(invoices_v2 -> accounts (account_id));
+joinable!(order_capture_approvals -> orders (order_id));
+joinable!(order_capture_approvals -> payment_intent (payment_intent_id));
 joinable!(order_exchange_rates -> orders (order_id));
+joinable!(order_line_items -> orders (order_id));
 joinable!(order_payouts -> orders (order_id));
 joinable!(order_payouts -> payouts (payout_id));
 joinable!(orders -> invoices_v2 (invoice_id));
+joinable!(payment_attempts -> invoices_v2 (invoice_id));
+joinable!(payment_attempts -> payment_intent (payment_intent_id));
+joinable!(payment_intent_history -> payment_intent (payment_intent_id));
 joinable!(payment_intents_fees -> fees (fee_id));
 joinable!(payment_intents_fees -> payment_intent (payment_intent_id));
 joinable!(payment_intents_invoices -> invoices_v2 (invoice_id));
 joinable!(payment_intents_invoices -> payment_intent (payment_intent_id));
+joinable!(payment_recoveries -> invoices_v2 (invoice_id));
+joinable!(payout_statements -> payouts (payout_id));
 joinable!(subscription -> subscription_payment (subscription_payment_id));
+joinable!(wallet_verifications -> user_wallets (user_wallet_id));
 
 allow_tables_to_appear_in_same_query!(
+    account_assignments,
+    account_pool_claims,
     accounts,
     amounts_received,
+    api_keys,
+    audit_log,
+    billing_info_changes,
+    cashback_liability_snapshots,
+    customer_exports,
     customers,
+    data_retention_subjects,
     event_store,
+    exchange_rate_slippage_metrics,
+    feature_flags,
+    fee_adjustments,
+    fee_crypto_payment_transactions,
+    fee_crypto_payments,
+    fee_dunnings,
+    fee_statements,
     fees,
+    historical_reports,
     international_billing_info,
+    invoice_buyer_details,
+    invoice_callback_deliveries,
+    invoice_callbacks,
+    invoice_requotes,
+    invoice_risk_assessments,
+    invoice_snapshots,
     invoices,
     invoices_v2,
     merchants,
+    negative_store_balances,
+    order_capture_approvals,
     order_exchange_rates,
+    order_line_items,
     order_payouts,
+    order_rate_slippages,
     orders,
     orders_info,
+    payment_attempts,
     payment_intent,
+    payment_intent_history,
     payment_intents_fees,
     payment_intents_invoices,
+    payment_recoveries,
+    payout_instructions,
+    payout_restriction_overrides,
+    payout_statements,
     payouts,
     proxy_companies_billing_info,
     roles,
     russia_billing_info,
+    store_billing_statuses,
     store_billing_type,
+    store_offboardings,
     store_subscription,
+    store_webhooks,
+    stripe_fee_backfills,
     subscription,
     subscription_payment,
+    user_credit_balances,
+    user_credit_entries,
     user_wallets,
+    wallet_verifications,
 );
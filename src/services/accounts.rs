@@ -1,24 +1,46 @@
+use bigdecimal::BigDecimal;
+use chrono::{Duration, Utc};
 use diesel::connection::AnsiTransactionManager;
 use diesel::pg::Pg;
 use diesel::Connection;
-use failure::{err_msg, Fail};
+use failure::{err_msg, Error as FailureError, Fail};
 use futures::{future, Future, IntoFuture, Stream};
 use futures_cpupool::CpuPool;
 use r2d2::{ManageConnection, Pool, PooledConnection};
+use sentry::integrations::failure::capture_error;
+use serde_json;
+use std::cmp;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration as StdDuration, Instant};
+use tokio_timer::Interval;
 use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
 
-use super::error::{Error, ErrorKind};
+use stq_http::client::HttpClient;
+
+use super::error::{Error, ErrorContext, ErrorKind};
 use super::types::ServiceFutureV2;
-use client::payments::{Account as PaymentsAccount, CreateAccount, PaymentsClient};
+use client::payments::{Account as PaymentsAccount, CreateAccount, CreateInternalTransaction, PaymentsClient};
+use config::AccountPool as AccountPoolConfig;
+use controller::context::DynamicContext;
+use controller::requests::SystemAccountsTransferRequest;
 use models::*;
 use repos::repo_factory::ReposFactory;
+use services::types::spawn_on_pool;
+use services::Service;
 
 pub trait AccountService: 'static {
     fn init_system_accounts(&self) -> ServiceFutureV2<()>;
 
     fn init_account_pools(&self) -> ServiceFutureV2<()>;
 
+    /// Utilization of the account pools by currency
+    fn get_account_pool_statuses(&self) -> ServiceFutureV2<Vec<AccountPoolStatus>>;
+
+    /// Creates the pooled accounts the observed demand calls for, returns the statuses the pools were sized by
+    fn replenish_account_pools(&self) -> ServiceFutureV2<Vec<AccountPoolStatus>>;
+
     fn get_account(&self, account_id: Uuid) -> ServiceFutureV2<AccountWithBalance>;
 
     fn get_main_account(&self, currency: TureCurrency) -> ServiceFutureV2<AccountWithBalance>;
@@ -28,6 +50,26 @@ pub trait AccountService: 'static {
     fn create_account(&self, account_id: Uuid, name: String, currency: TureCurrency, is_pooled: bool) -> ServiceFutureV2<Account>;
 
     fn get_or_create_free_pooled_account(&self, currency: TureCurrency) -> ServiceFutureV2<Account>;
+
+    /// Takes a pooled account out of the pool, sweeps its remaining funds to the main account and archives it
+    fn drain_and_archive_account(&self, account_id: AccountId) -> ServiceFutureV2<Account>;
+
+    /// Moves funds between two system accounts of the same currency with an internal transaction
+    fn transfer_between_system_accounts(
+        &self,
+        from_type: SystemAccountType,
+        to_type: SystemAccountType,
+        currency: TureCurrency,
+        amount: Amount,
+    ) -> ServiceFutureV2<SystemAccountsTransfer>;
+}
+
+pub trait AccountAdminService {
+    /// Retires a pooled account, available to superusers only
+    fn archive_account(&self, account_id: AccountId) -> ServiceFutureV2<Account>;
+
+    /// Moves funds between system accounts, available to superusers only
+    fn transfer_between_system_accounts(&self, payload: SystemAccountsTransferRequest) -> ServiceFutureV2<SystemAccountsTransfer>;
 }
 
 impl<T: ?Sized + AccountService> AccountService for Arc<T> {
@@ -39,6 +81,14 @@ impl<T: ?Sized + AccountService> AccountService for Arc<T> {
         (*self.clone()).init_account_pools()
     }
 
+    fn get_account_pool_statuses(&self) -> ServiceFutureV2<Vec<AccountPoolStatus>> {
+        (*self.clone()).get_account_pool_statuses()
+    }
+
+    fn replenish_account_pools(&self) -> ServiceFutureV2<Vec<AccountPoolStatus>> {
+        (*self.clone()).replenish_account_pools()
+    }
+
     fn get_account(&self, account_id: Uuid) -> ServiceFutureV2<AccountWithBalance> {
         (*self.clone()).get_account(account_id)
     }
@@ -58,6 +108,20 @@ impl<T: ?Sized + AccountService> AccountService for Arc<T> {
     fn get_or_create_free_pooled_account(&self, currency: TureCurrency) -> ServiceFutureV2<Account> {
         (*self.clone()).get_or_create_free_pooled_account(currency)
     }
+
+    fn drain_and_archive_account(&self, account_id: AccountId) -> ServiceFutureV2<Account> {
+        (*self.clone()).drain_and_archive_account(account_id)
+    }
+
+    fn transfer_between_system_accounts(
+        &self,
+        from_type: SystemAccountType,
+        to_type: SystemAccountType,
+        currency: TureCurrency,
+        amount: Amount,
+    ) -> ServiceFutureV2<SystemAccountsTransfer> {
+        (*self.clone()).transfer_between_system_accounts(from_type, to_type, currency, amount)
+    }
 }
 
 pub struct AccountServiceImpl<T, M, F, PC>
@@ -70,7 +134,9 @@ where
     db_pool: Pool<M>,
     cpu_pool: CpuPool,
     repo_factory: F,
-    min_accounts_in_pool: u32,
+    pool_sizing: AccountPoolSizing,
+    /// Time a released pooled account rests before it is handed out again
+    account_quarantine: Duration,
     payments_client: PC,
     payments_callback_url: String,
     system_accounts: SystemAccounts,
@@ -88,7 +154,8 @@ impl<
             db_pool: self.db_pool.clone(),
             cpu_pool: self.cpu_pool.clone(),
             repo_factory: self.repo_factory.clone(),
-            min_accounts_in_pool: self.min_accounts_in_pool.clone(),
+            pool_sizing: self.pool_sizing.clone(),
+            account_quarantine: self.account_quarantine,
             payments_client: self.payments_client.clone(),
             payments_callback_url: self.payments_callback_url.clone(),
             system_accounts: self.system_accounts.clone(),
@@ -144,42 +211,70 @@ impl<
     }
 
     fn init_account_pools(&self) -> ServiceFutureV2<()> {
-        let fut = self
-            .clone()
-            .spawn_on_pool({
-                let repo_factory = self.repo_factory.clone();
-                move |conn| {
-                    let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
-                    accounts_repo.count().map_err(ectx!(convert))
-                }
-            })
-            .and_then({
-                let self_clone = self.clone();
-                let min_accounts_in_pool = self.min_accounts_in_pool.clone();
-                move |account_count| {
-                    let accounts_to_create = account_count
-                        .pooled
-                        .into_iter()
-                        .filter_map(move |(currency, num_existing)| {
-                            (min_accounts_in_pool as u64)
-                                .checked_sub(num_existing)
-                                .filter(|num_to_create| *num_to_create > 0)
-                                .map(|num_to_create| (currency, num_to_create))
-                        })
-                        .flat_map(|(currency, num_to_create)| (0..num_to_create).map(move |_| currency));
+        Box::new(self.replenish_account_pools().map(|_| ()))
+    }
 
-                    futures::stream::iter_ok::<_, Error>(accounts_to_create)
-                        .fold(self_clone, |self_, currency| {
-                            let account_id = Uuid::new_v4();
-                            self_
-                                .clone()
-                                .create_account(account_id.clone(), account_id.hyphenated().to_string(), currency, true)
-                                .map(move |_| self_)
-                                .map_err(ectx!(try ErrorKind::Internal => account_id.hyphenated().to_string(), currency, true))
-                        })
-                        .map(|_| ())
-                }
-            });
+    fn get_account_pool_statuses(&self) -> ServiceFutureV2<Vec<AccountPoolStatus>> {
+        let now = Utc::now().naive_utc();
+        let released_before = now - self.account_quarantine;
+        let demand_since = now - self.pool_sizing.demand_window;
+
+        self.spawn_on_pool({
+            let repo_factory = self.repo_factory.clone();
+            move |conn| {
+                let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
+                let account_pool_claims_repo = repo_factory.create_account_pool_claims_repo_with_sys_acl(&conn);
+
+                let account_count = accounts_repo.count().map_err(ectx!(try convert))?;
+                let mut free = accounts_repo
+                    .count_free(released_before)
+                    .map_err(ectx!(try convert => released_before))?;
+                let mut demand = account_pool_claims_repo
+                    .demand_since(demand_since)
+                    .map_err(ectx!(try convert => demand_since))?;
+
+                let mut statuses = account_count
+                    .pooled
+                    .into_iter()
+                    .map(|(currency, pooled)| AccountPoolStatus {
+                        currency,
+                        pooled,
+                        free: free.remove(&currency).unwrap_or(0),
+                        demand: demand.remove(&currency).unwrap_or_default(),
+                    })
+                    .collect::<Vec<_>>();
+                statuses.sort_by_key(|status| status.currency.to_string());
+
+                Ok(statuses)
+            }
+        })
+    }
+
+    fn replenish_account_pools(&self) -> ServiceFutureV2<Vec<AccountPoolStatus>> {
+        let fut = self.get_account_pool_statuses().and_then({
+            let self_clone = self.clone();
+            let pool_sizing = self.pool_sizing.clone();
+            move |statuses| {
+                let accounts_to_create = statuses
+                    .iter()
+                    .map(|status| (status.currency, pool_sizing.accounts_to_create(status)))
+                    .filter(|(_, num_to_create)| *num_to_create > 0)
+                    .inspect(|(currency, num_to_create)| info!("Creating {} pooled {} accounts", num_to_create, currency))
+                    .flat_map(|(currency, num_to_create)| (0..num_to_create).map(move |_| currency))
+                    .collect::<Vec<_>>();
+
+                futures::stream::iter_ok::<_, Error>(accounts_to_create)
+                    .fold(self_clone, |self_, currency| {
+                        let account_id = Uuid::new_v4();
+                        self_
+                            .clone()
+                            .create_account(account_id.clone(), account_id.hyphenated().to_string(), currency, true)
+                            .map(move |_| self_)
+                            .map_err(ectx!(try ErrorKind::Internal => account_id.hyphenated().to_string(), currency, true))
+                    })
+                    .map(move |_| statuses)
+            }
+        });
 
         Box::new(fut)
     }
@@ -255,14 +350,28 @@ impl<
     }
 
     fn get_or_create_free_pooled_account(&self, currency: TureCurrency) -> ServiceFutureV2<Account> {
+        let released_before = Utc::now().naive_utc() - self.account_quarantine;
+
         let fut = self
             .spawn_on_pool({
                 let repo_factory = self.repo_factory.clone();
                 move |conn| {
                     let account_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
-                    account_repo
-                        .get_free_account(currency)
-                        .map_err(ectx!(ErrorKind::Internal => currency))
+                    let account_pool_claims_repo = repo_factory.create_account_pool_claims_repo_with_sys_acl(&conn);
+
+                    let free_account = account_repo
+                        .get_free_account(currency, released_before)
+                        .map_err(ectx!(try ErrorKind::Internal => currency, released_before))?;
+
+                    let new_claim = NewAccountPoolClaim {
+                        currency,
+                        exhausted: free_account.is_none(),
+                    };
+                    account_pool_claims_repo
+                        .create(new_claim.clone())
+                        .map_err(ectx!(try convert => new_claim))?;
+
+                    Ok(free_account)
                 }
             })
             .and_then({
@@ -270,6 +379,10 @@ impl<
                 move |free_account| match free_account {
                     Some(free_account) => future::Either::A(future::ok(free_account)),
                     None => {
+                        let e = format_err!("Pool of {} accounts is exhausted, an account is created at claim time", currency);
+                        error!("{}", e);
+                        capture_error(&e);
+
                         let id = Uuid::new_v4();
                         let name = id.hyphenated().to_string();
                         future::Either::B(
@@ -283,6 +396,233 @@ impl<
 
         Box::new(fut)
     }
+
+    fn drain_and_archive_account(&self, account_id: AccountId) -> ServiceFutureV2<Account> {
+        let fut = self
+            .spawn_on_pool({
+                let repo_factory = self.repo_factory.clone();
+                move |conn| {
+                    let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
+                    let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
+
+                    conn.transaction::<_, Error, _>(move || {
+                        let account = accounts_repo.get(account_id).map_err(ectx!(try convert => account_id))?.ok_or({
+                            let e = format_err!("Account {} not found", account_id);
+                            ectx!(try err e, ErrorKind::NotFound)
+                        })?;
+
+                        if account.status == AccountStatus::Archived {
+                            return Ok(account);
+                        }
+
+                        if !account.is_pooled {
+                            return Err(account_state_error(format!("Account {} is not pooled", account_id)));
+                        }
+
+                        // the account is marked as draining before checking for an invoice,
+                        // so that it can't be picked from the pool in the meantime
+                        let account = accounts_repo
+                            .set_status(account_id, AccountStatus::Draining)
+                            .map_err(ectx!(try convert => account_id))?;
+
+                        let invoice = invoices_repo
+                            .get_by_account_id(account_id)
+                            .map_err(ectx!(try convert => account_id))?;
+
+                        match invoice {
+                            Some(invoice) => Err(account_state_error(format!(
+                                "Account {} is linked to invoice {}",
+                                account_id, invoice.id
+                            ))),
+                            None => Ok(account),
+                        }
+                    })
+                }
+            })
+            .and_then({
+                let self_ = self.clone();
+                move |account| match account.status {
+                    AccountStatus::Archived => future::Either::A(future::ok(account)),
+                    _ => future::Either::B(self_.clone().sweep_to_main_account(account).and_then(move |_| {
+                        self_.spawn_on_pool({
+                            let repo_factory = self_.repo_factory.clone();
+                            move |conn| {
+                                let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
+                                accounts_repo
+                                    .set_status(account_id, AccountStatus::Archived)
+                                    .map_err(ectx!(convert => account_id))
+                            }
+                        })
+                    })),
+                }
+            });
+
+        Box::new(fut)
+    }
+
+    fn transfer_between_system_accounts(
+        &self,
+        from_type: SystemAccountType,
+        to_type: SystemAccountType,
+        currency: TureCurrency,
+        amount: Amount,
+    ) -> ServiceFutureV2<SystemAccountsTransfer> {
+        if from_type == to_type {
+            return Box::new(future::err(system_accounts_transfer_error(
+                "to",
+                format!("Cannot transfer from {} system account to itself", from_type),
+            )));
+        }
+
+        if amount == Amount::zero() {
+            return Box::new(future::err(system_accounts_transfer_error(
+                "amount",
+                "Transfer amount must be positive".to_string(),
+            )));
+        }
+
+        let (from_account_id, to_account_id) = match (
+            self.system_accounts.get(currency, from_type),
+            self.system_accounts.get(currency, to_type),
+        ) {
+            (Some(from_account_id), Some(to_account_id)) => (from_account_id, to_account_id),
+            (None, _) => {
+                return Box::new(future::err(system_accounts_transfer_error(
+                    "from",
+                    format!("{} system account for currency {} is missing", from_type, currency),
+                )));
+            }
+            (_, None) => {
+                return Box::new(future::err(system_accounts_transfer_error(
+                    "to",
+                    format!("{} system account for currency {} is missing", to_type, currency),
+                )));
+            }
+        };
+
+        let fut = self.get_account(from_account_id.into_inner()).and_then({
+            let payments_client = self.payments_client.clone();
+            move |AccountWithBalance { balance, .. }| {
+                if balance < amount {
+                    return future::Either::A(future::err(system_accounts_transfer_error(
+                        "amount",
+                        format!("{} system account balance for currency {} is insufficient", from_type, currency),
+                    )));
+                }
+
+                let input = CreateInternalTransaction {
+                    id: Uuid::new_v4(),
+                    from: from_account_id.into_inner(),
+                    to: to_account_id.into_inner(),
+                    amount,
+                };
+
+                future::Either::B(
+                    payments_client
+                        .create_internal_transaction(input.clone())
+                        .map_err(ectx!(convert => input))
+                        .map(move |_| SystemAccountsTransfer {
+                            transaction_id: input.id,
+                            from_account_id,
+                            to_account_id,
+                            currency,
+                            amount,
+                        }),
+                )
+            }
+        });
+
+        Box::new(fut)
+    }
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+        C: HttpClient + Clone,
+        PC: PaymentsClient + Clone,
+        AS: AccountService + Clone,
+    > AccountAdminService for Service<T, M, F, C, PC, AS>
+{
+    fn archive_account(&self, account_id: AccountId) -> ServiceFutureV2<Account> {
+        let db_pool = self.static_context.db_pool.clone();
+        let cpu_pool = self.static_context.cpu_pool.clone();
+        let repo_factory = self.static_context.repo_factory.clone();
+        let DynamicContext {
+            user_id, account_service, ..
+        } = self.dynamic_context.clone();
+
+        let account_service = match account_service {
+            Some(account_service) => account_service,
+            None => {
+                let e = err_msg("payments integration has not been configured");
+                return Box::new(future::err(ectx!(err e, ErrorKind::Internal)));
+            }
+        };
+
+        // accounts are readable by superusers only, so the lookup doubles as the permission check
+        let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let accounts_repo = repo_factory.create_accounts_repo(&conn, user_id);
+            accounts_repo.get(account_id).map_err(ectx!(try convert => account_id))?.ok_or({
+                let e = format_err!("Account {} not found", account_id);
+                ectx!(err e, ErrorKind::NotFound)
+            })
+        })
+        .and_then(move |_| account_service.drain_and_archive_account(account_id));
+
+        Box::new(fut)
+    }
+
+    fn transfer_between_system_accounts(&self, payload: SystemAccountsTransferRequest) -> ServiceFutureV2<SystemAccountsTransfer> {
+        let db_pool = self.static_context.db_pool.clone();
+        let cpu_pool = self.static_context.cpu_pool.clone();
+        let repo_factory = self.static_context.repo_factory.clone();
+        let DynamicContext {
+            user_id, account_service, ..
+        } = self.dynamic_context.clone();
+
+        let account_service = match account_service {
+            Some(account_service) => account_service,
+            None => {
+                let e = err_msg("payments integration has not been configured");
+                return Box::new(future::err(ectx!(err e, ErrorKind::Internal)));
+            }
+        };
+
+        let SystemAccountsTransferRequest {
+            from,
+            to,
+            currency,
+            amount,
+        } = payload;
+
+        if amount <= BigDecimal::from(0) {
+            return Box::new(future::err(system_accounts_transfer_error(
+                "amount",
+                "Transfer amount must be positive".to_string(),
+            )));
+        }
+
+        let amount = match Amount::checked_from_super_unit(currency.into(), amount) {
+            Some(amount) => amount,
+            None => {
+                return Box::new(future::err(system_accounts_transfer_error(
+                    "amount",
+                    "Transfer amount is too large".to_string(),
+                )));
+            }
+        };
+
+        // accounts are readable by superusers only, so counting them doubles as the permission check
+        let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let accounts_repo = repo_factory.create_accounts_repo(&conn, user_id);
+            accounts_repo.count().map(|_| ()).map_err(ectx!(convert))
+        })
+        .and_then(move |_| account_service.transfer_between_system_accounts(from, to, currency, amount));
+
+        Box::new(fut)
+    }
 }
 
 impl<
@@ -296,7 +636,8 @@ impl<
         db_pool: Pool<M>,
         cpu_pool: CpuPool,
         repo_factory: F,
-        min_accounts_in_pool: u32,
+        pool_sizing: AccountPoolSizing,
+        account_quarantine: Duration,
         payments_client: PC,
         payments_callback_url: String,
         system_accounts: SystemAccounts,
@@ -305,7 +646,8 @@ impl<
             db_pool,
             cpu_pool,
             repo_factory,
-            min_accounts_in_pool,
+            pool_sizing,
+            account_quarantine,
             payments_client,
             payments_callback_url,
             system_accounts,
@@ -378,6 +720,35 @@ impl<
         Box::new(Future::join(fut1, fut2).map(|_| ()))
     }
 
+    fn sweep_to_main_account(self, account: Account) -> ServiceFutureV2<()> {
+        let account_id = account.id.into_inner();
+        let currency = account.currency;
+
+        let fut = Future::join(self.get_account(account_id), self.get_main_account(currency)).and_then({
+            let payments_client = self.payments_client.clone();
+            move |(AccountWithBalance { balance, .. }, AccountWithBalance { account: main_account, .. })| {
+                if balance == Amount::zero() {
+                    return future::Either::A(future::ok(()));
+                }
+
+                let input = CreateInternalTransaction {
+                    id: Uuid::new_v4(),
+                    from: account_id,
+                    to: main_account.id.into_inner(),
+                    amount: balance,
+                };
+
+                future::Either::B(
+                    payments_client
+                        .create_internal_transaction(input.clone())
+                        .map_err(ectx!(convert => input)),
+                )
+            }
+        });
+
+        Box::new(fut)
+    }
+
     fn spawn_on_pool<R, Func>(&self, f: Func) -> ServiceFutureV2<R>
     where
         Func: FnOnce(PooledConnection<M>) -> Result<R, Error> + Send + 'static,
@@ -388,3 +759,163 @@ impl<
         Box::new(cpu_pool.spawn_fn(move || db_pool.get().map_err(ectx!(ErrorKind::Internal)).and_then(f)))
     }
 }
+
+/// Limits of the pool sizes by currency and the window the demand for accounts is observed within
+#[derive(Debug, Clone)]
+pub struct AccountPoolSizing {
+    default_min_accounts: u32,
+    min_accounts: HashMap<TureCurrency, u32>,
+    max_accounts: HashMap<TureCurrency, u32>,
+    demand_window: Duration,
+}
+
+impl AccountPoolSizing {
+    pub fn new(default_min_accounts: u32, config: &AccountPoolConfig) -> Self {
+        AccountPoolSizing {
+            default_min_accounts,
+            min_accounts: config.min_accounts.clone(),
+            max_accounts: config.max_accounts.clone(),
+            demand_window: Duration::seconds(config.demand_window_sec as i64),
+        }
+    }
+
+    /// Accounts to create for the pool to hold as many free accounts as were claimed within the demand window,
+    /// keeping it within its minimum and maximum size. The minimum wins over a lower maximum
+    pub fn accounts_to_create(&self, status: &AccountPoolStatus) -> u64 {
+        let min_accounts = u64::from(*self.min_accounts.get(&status.currency).unwrap_or(&self.default_min_accounts));
+        let wanted = status.pooled + status.demand.claims.saturating_sub(status.free);
+        let wanted = match self.max_accounts.get(&status.currency) {
+            Some(max_accounts) => cmp::min(wanted, u64::from(*max_accounts)),
+            None => wanted,
+        };
+
+        cmp::max(wanted, min_accounts).saturating_sub(status.pooled)
+    }
+}
+
+/// Replenishes the account pools on every tick of the replenishment interval.
+/// A failed replenishment is reported and retried on the next tick
+pub fn run_account_pool_replenishment(
+    config: AccountPoolConfig,
+    account_service: Arc<dyn AccountService + Send + Sync>,
+) -> impl Future<Item = (), Error = FailureError> {
+    let interval = StdDuration::from_secs(config.replenishment_interval_sec);
+
+    Interval::new(Instant::now(), interval)
+        .map_err(FailureError::from)
+        .for_each(move |_| {
+            debug!("Started replenishing account pools");
+            account_service.replenish_account_pools().then(|res| {
+                match res {
+                    Ok(statuses) => {
+                        for status in statuses.iter().filter(|status| status.demand.exhaustions > 0) {
+                            warn!(
+                                "Pool of {} accounts was exhausted {} times out of {} claims within the demand window",
+                                status.currency, status.demand.exhaustions, status.demand.claims
+                            );
+                        }
+                        debug!("Finished replenishing account pools");
+                    }
+                    Err(err) => {
+                        let err = FailureError::from(err.context("An error occurred while replenishing account pools"));
+                        error!("{:?}", &err);
+                        capture_error(&err);
+                    }
+                };
+
+                future::ok::<_, FailureError>(())
+            })
+        })
+}
+
+/// Renders the utilization of the account pools as Prometheus gauges labelled with the currency
+pub fn account_pool_gauges(statuses: &[AccountPoolStatus]) -> String {
+    let gauges: &[(&str, &str, fn(&AccountPoolStatus) -> u64)] = &[
+        ("billing_account_pool_accounts", "Active pooled accounts", |status| status.pooled),
+        (
+            "billing_account_pool_free_accounts",
+            "Pooled accounts that can be handed out right away",
+            |status| status.free,
+        ),
+        (
+            "billing_account_pool_claims",
+            "Accounts claimed from the pool within the demand window",
+            |status| status.demand.claims,
+        ),
+        (
+            "billing_account_pool_exhaustions",
+            "Claims within the demand window that found the pool exhausted",
+            |status| status.demand.exhaustions,
+        ),
+    ];
+
+    let mut text = String::new();
+    for (name, help, value) in gauges {
+        text.push_str(&format!("# HELP {} {}\n# TYPE {} gauge\n", name, help, name));
+        for status in statuses {
+            text.push_str(&format!("{}{{currency=\"{}\"}} {}\n", name, status.currency, value(status)));
+        }
+    }
+    text
+}
+
+fn account_state_error(message: String) -> Error {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new("account_state");
+    error.message = Some(message.into());
+    errors.add("account_id", error);
+    ectx!(err ErrorContext::AccountState, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
+fn system_accounts_transfer_error(field: &'static str, message: String) -> Error {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new("system_accounts_transfer");
+    error.message = Some(message.into());
+    errors.add(field, error);
+    ectx!(err ErrorContext::SystemAccountsTransfer, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool_status(currency: TureCurrency, pooled: u64, free: u64, claims: u64) -> AccountPoolStatus {
+        AccountPoolStatus {
+            currency,
+            pooled,
+            free,
+            demand: AccountPoolDemand { claims, exhaustions: 0 },
+        }
+    }
+
+    #[test]
+    fn pools_are_sized_by_demand_within_limits() {
+        let mut config = AccountPoolConfig::default();
+        config.min_accounts.insert(TureCurrency::Stq, 50);
+        config.max_accounts.insert(TureCurrency::Btc, 20);
+        let sizing = AccountPoolSizing::new(10, &config);
+
+        // below the minimum
+        assert_eq!(sizing.accounts_to_create(&pool_status(TureCurrency::Stq, 30, 30, 0)), 20);
+        assert_eq!(sizing.accounts_to_create(&pool_status(TureCurrency::Eth, 4, 4, 0)), 6);
+        // free accounts cover the demand
+        assert_eq!(sizing.accounts_to_create(&pool_status(TureCurrency::Stq, 80, 40, 40)), 0);
+        // the pool grows by the claims free accounts don't cover
+        assert_eq!(sizing.accounts_to_create(&pool_status(TureCurrency::Stq, 80, 10, 40)), 30);
+        // up to the maximum
+        assert_eq!(sizing.accounts_to_create(&pool_status(TureCurrency::Btc, 15, 0, 30)), 5);
+        assert_eq!(sizing.accounts_to_create(&pool_status(TureCurrency::Btc, 25, 0, 30)), 0);
+    }
+
+    #[test]
+    fn account_pool_gauges_are_labelled_with_currency() {
+        let mut status = pool_status(TureCurrency::Stq, 80, 10, 40);
+        status.demand.exhaustions = 3;
+
+        let gauges = account_pool_gauges(&[status]);
+        assert!(gauges.contains("billing_account_pool_accounts{currency=\"stq\"} 80\n"));
+        assert!(gauges.contains("billing_account_pool_free_accounts{currency=\"stq\"} 10\n"));
+        assert!(gauges.contains("billing_account_pool_claims{currency=\"stq\"} 40\n"));
+        assert!(gauges.contains("billing_account_pool_exhaustions{currency=\"stq\"} 3\n"));
+    }
+}
@@ -0,0 +1,62 @@
+//! Billing events for the analytics team are published by the event store after the transaction
+//! that recorded them commits, a failed publish is retried like any other event
+use failure::Fail;
+use serde_json;
+
+use models::invoice_v2::InvoiceId;
+use models::order_v2::{RawOrder, StoreId};
+use models::{AnalyticsEvent, AnalyticsEventType, Event, EventPayload, Fee, Payout};
+use repos::EventStoreRepo;
+use services::Error;
+
+/// Schedules publishing of the event to the analytics sink
+pub fn enqueue_analytics_event(
+    event_store_repo: &EventStoreRepo,
+    event_type: AnalyticsEventType,
+    data: serde_json::Value,
+) -> Result<(), Error> {
+    let event = Event::new(EventPayload::AnalyticsEventPublish {
+        analytics_event: AnalyticsEvent::new(event_type, data),
+    });
+    event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+
+    Ok(())
+}
+
+/// Amounts of the orders are given in their seller currencies
+pub fn invoice_analytics_data(invoice_id: InvoiceId, orders: &[RawOrder]) -> serde_json::Value {
+    let orders = orders
+        .iter()
+        .map(|order| {
+            json!({
+                "order_id": order.id,
+                "store_id": order.store_id,
+                "currency": order.seller_currency,
+                "total_amount": order.total_amount.to_super_unit(order.seller_currency),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({ "invoice_id": invoice_id, "orders": orders })
+}
+
+pub fn fee_analytics_data(fee: &Fee, store_id: StoreId) -> serde_json::Value {
+    json!({
+        "fee_id": fee.id,
+        "order_id": fee.order_id,
+        "store_id": store_id,
+        "currency": fee.currency,
+        "amount": fee.amount.to_super_unit(fee.currency),
+    })
+}
+
+pub fn payout_analytics_data(payout: &Payout) -> serde_json::Value {
+    let currency = payout.currency();
+    json!({
+        "payout_id": payout.id,
+        "order_ids": payout.order_ids,
+        "currency": currency,
+        "gross_amount": payout.gross_amount.to_super_unit(currency),
+        "net_amount": payout.net_amount.to_super_unit(currency),
+    })
+}
@@ -0,0 +1,186 @@
+//! ApiKeyService manages keys stores use to call billing from their own servers
+use chrono::Utc;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use futures::future;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use serde_json;
+use validator::{ValidationError, ValidationErrors};
+
+use failure::Fail;
+
+use stq_types::{StoreId, UserId};
+
+use super::types::ServiceFutureV2;
+use controller::requests::CreateApiKeyRequest;
+use controller::responses::{ApiKeyResponse, CreatedApiKeyResponse};
+use models::order_v2::StoreId as OrderStoreId;
+use models::{generate_api_key, hash_api_key, ApiKey, ApiKeyId, NewApiKey};
+use repos::{user_is_store_manager, ApiKeysRepo, ReposFactory};
+use services::types::spawn_on_pool;
+use services::{Error, ErrorContext, ErrorKind};
+
+pub trait ApiKeyService {
+    /// Creates a key acting for the current user, who must manage the store. The key is returned only here
+    fn create_api_key(&self, store_id: StoreId, payload: CreateApiKeyRequest) -> ServiceFutureV2<CreatedApiKeyResponse>;
+    /// Lists keys of a store, revoked ones included
+    fn get_api_keys(&self, store_id: StoreId) -> ServiceFutureV2<Vec<ApiKeyResponse>>;
+    /// Revokes a key, requests made with it are rejected from then on
+    fn revoke_api_key(&self, id: ApiKeyId) -> ServiceFutureV2<ApiKeyResponse>;
+    /// Finds the key a request is made with and records its use
+    fn authenticate(&self, key: String) -> ServiceFutureV2<ApiKey>;
+}
+
+pub struct ApiKeyServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > ApiKeyService for ApiKeyServiceImpl<T, M, F>
+{
+    fn create_api_key(&self, store_id: StoreId, payload: CreateApiKeyRequest) -> ServiceFutureV2<CreatedApiKeyResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        if let Err(e) = validate_create_api_key(&payload) {
+            return Box::new(future::err(e));
+        }
+
+        let CreateApiKeyRequest { name, mut scopes } = payload;
+        scopes.sort();
+        scopes.dedup();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            // requests made with the key act for its creator, so only a manager of the store can create one
+            let created_by = match user_id {
+                Some(user_id) if user_is_store_manager(&*conn, user_id, OrderStoreId::new(store_id.0)) => user_id,
+                _ => return Err(ectx!(err ErrorContext::Unauthorized, ErrorKind::Forbidden)),
+            };
+
+            let api_keys_repo = repo_factory.create_api_keys_repo(&conn, user_id);
+
+            let (key, key_prefix) = generate_api_key();
+            let new_api_key = NewApiKey {
+                store_id,
+                created_by,
+                name,
+                key_prefix,
+                key_hash: hash_api_key(&key),
+                scopes: serde_json::to_value(scopes).unwrap_or(json!([])),
+            };
+
+            let api_key = api_keys_repo
+                .create(new_api_key)
+                .map_err(ectx!(try convert => store_id, created_by))?;
+
+            Ok(CreatedApiKeyResponse {
+                api_key: ApiKeyResponse::try_from_api_key(api_key)?,
+                key,
+            })
+        })
+    }
+
+    fn get_api_keys(&self, store_id: StoreId) -> ServiceFutureV2<Vec<ApiKeyResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let api_keys_repo = repo_factory.create_api_keys_repo(&conn, user_id);
+
+            api_keys_repo
+                .list_by_store_id(store_id)
+                .map_err(ectx!(try convert => store_id))?
+                .into_iter()
+                .map(ApiKeyResponse::try_from_api_key)
+                .collect()
+        })
+    }
+
+    fn revoke_api_key(&self, id: ApiKeyId) -> ServiceFutureV2<ApiKeyResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let api_keys_repo = repo_factory.create_api_keys_repo(&conn, user_id);
+
+            api_keys_repo.get(id).map_err(ectx!(try convert => id))?.ok_or_else(|| {
+                let e = format_err!("Api key {} not found", id);
+                ectx!(err e, ErrorContext::ApiKey, ErrorKind::NotFound)
+            })?;
+
+            api_keys_repo
+                .revoke(id, Utc::now().naive_utc())
+                .map_err(ectx!(try convert => id))
+                .and_then(ApiKeyResponse::try_from_api_key)
+        })
+    }
+
+    fn authenticate(&self, key: String) -> ServiceFutureV2<ApiKey> {
+        let repo_factory = self.repo_factory.clone();
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let api_keys_repo = repo_factory.create_api_keys_repo_with_sys_acl(&conn);
+
+            let api_key = api_keys_repo
+                .get_by_key_hash(hash_api_key(&key))
+                .map_err(ectx!(try convert))?
+                .ok_or_else(|| ectx!(err ErrorContext::Unauthorized, ErrorKind::Forbidden))?;
+
+            if api_key.is_revoked() {
+                return Err(ectx!(err ErrorContext::Unauthorized, ErrorKind::Forbidden));
+            }
+
+            // a key stops working once its creator no longer manages the store
+            if !user_is_store_manager(&*conn, api_key.created_by, OrderStoreId::new(api_key.store_id.0)) {
+                return Err(ectx!(err ErrorContext::Unauthorized, ErrorKind::Forbidden));
+            }
+
+            let api_key_id = api_key.id;
+            api_keys_repo
+                .set_last_used_at(api_key_id, Utc::now().naive_utc())
+                .map_err(ectx!(convert => api_key_id))
+        })
+    }
+}
+
+fn validate_create_api_key(payload: &CreateApiKeyRequest) -> Result<(), Error> {
+    let mut errors = ValidationErrors::new();
+
+    if payload.name.trim().is_empty() {
+        let mut error = ValidationError::new("empty");
+        error.message = Some("Api key name must not be empty".into());
+        errors.add("name", error);
+    }
+
+    if payload.scopes.is_empty() {
+        let mut error = ValidationError::new("empty");
+        error.message = Some("Api key must have at least one scope".into());
+        errors.add("scopes", error);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ectx!(err ErrorContext::ApiKey, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default())))
+    }
+}
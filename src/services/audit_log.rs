@@ -0,0 +1,305 @@
+//! AuditLogService records manual mutations performed through admin endpoints and serves the audit trail
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::Fail;
+use futures::Future;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use serde::Serialize;
+use serde_json;
+
+use stq_types::{StoreId, UserId};
+
+use super::types::ServiceFutureV2;
+use models::invoice_v2::InvoiceId;
+use models::order_v2::OrderId;
+use models::{
+    AccountId, AuditAction, AuditLogSearch, AuditLogSearchResults, AuditResource, DataSubject, Feature, NewAuditLogEntry,
+    StoreSubscriptionSearch, UserId as CreditUserId,
+};
+use repos::ReposFactory;
+use services::types::spawn_on_pool;
+use services::{Error, ErrorKind};
+
+pub trait AuditLogService {
+    /// Searches the audit log, newest entries first
+    fn search_audit_log(&self, skip: i64, count: i64, search: AuditLogSearch) -> ServiceFutureV2<AuditLogSearchResults>;
+}
+
+/// Resource whose state is captured right before and after an audited mutation
+#[derive(Debug, Clone, Copy)]
+pub enum AuditTarget {
+    Order(OrderId),
+    UserRoles(UserId),
+    Account(AccountId),
+    StoreSubscription(StoreId),
+    StoreBillingStatus(StoreId),
+    FeatureFlag(Feature),
+    DataRetentionSubject(DataSubject),
+    PayoutRestrictionOverride(StoreId),
+    InvoiceRiskAssessment(InvoiceId),
+    UserCredit(UserId),
+}
+
+impl AuditTarget {
+    pub fn resource(&self) -> AuditResource {
+        match *self {
+            AuditTarget::Order(order_id) => AuditResource::order(order_id),
+            AuditTarget::UserRoles(user_id) => AuditResource::user_roles(user_id),
+            AuditTarget::Account(account_id) => AuditResource::account(account_id),
+            AuditTarget::StoreSubscription(store_id) => AuditResource::store_subscription(store_id),
+            AuditTarget::StoreBillingStatus(store_id) => AuditResource::store_billing_status(store_id),
+            AuditTarget::FeatureFlag(feature) => AuditResource::feature_flag(feature),
+            AuditTarget::DataRetentionSubject(subject) => AuditResource::data_retention_subject(subject),
+            AuditTarget::PayoutRestrictionOverride(store_id) => AuditResource::payout_restriction_override(store_id),
+            AuditTarget::InvoiceRiskAssessment(invoice_id) => AuditResource::invoice_risk_assessment(invoice_id),
+            AuditTarget::UserCredit(user_id) => AuditResource::user_credit(user_id),
+        }
+    }
+}
+
+/// Change made by an audited mutation
+#[derive(Debug, Clone)]
+pub struct AuditChange {
+    pub resource: AuditResource,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+impl AuditChange {
+    /// Change that brings a resource into existence
+    pub fn created<S: Serialize>(resource: AuditResource, after: &S) -> Self {
+        AuditChange {
+            resource,
+            before: None,
+            after: serde_json::to_value(after).ok(),
+        }
+    }
+
+    /// Change that removes a resource
+    pub fn removed<S: Serialize>(resource: AuditResource, before: &S) -> Self {
+        AuditChange {
+            resource,
+            before: serde_json::to_value(before).ok(),
+            after: None,
+        }
+    }
+}
+
+pub struct AuditLogServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > AuditLogService for AuditLogServiceImpl<T, M, F>
+{
+    fn search_audit_log(&self, skip: i64, count: i64, search: AuditLogSearch) -> ServiceFutureV2<AuditLogSearchResults> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+
+        spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let audit_log_repo = repo_factory.create_audit_log_repo(&conn, user_id);
+            audit_log_repo
+                .search(skip, count, search.clone())
+                .map_err(ectx!(convert => skip, count, search))
+        })
+    }
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > AuditLogServiceImpl<T, M, F>
+{
+    /// Runs `mutation` and records it together with snapshots of `target` taken right before and after it.
+    /// Nothing is recorded if the mutation fails
+    pub fn audit<R, E, Fut, Func>(&self, action: AuditAction, target: AuditTarget, mutation: Func) -> Box<Future<Item = R, Error = E>>
+    where
+        R: 'static,
+        E: From<Error> + 'static,
+        Fut: Future<Item = R, Error = E> + 'static,
+        Func: FnOnce() -> Fut + 'static,
+    {
+        let self_ = self.clone();
+
+        let fut = self
+            .snapshot(target)
+            .map_err(E::from)
+            .and_then(move |before| mutation().map(move |result| (before, result)))
+            .and_then(move |(before, result)| {
+                let record_fut = self_.snapshot(target).and_then(move |after| {
+                    self_.record(
+                        action,
+                        AuditChange {
+                            resource: target.resource(),
+                            before,
+                            after,
+                        },
+                    )
+                });
+
+                record_fut.then(move |_| Ok::<_, E>(result))
+            });
+
+        Box::new(fut)
+    }
+
+    /// Runs `mutation` and records the change described by its result.
+    /// Used for mutations whose resource is only known once they complete
+    pub fn audit_result<R, E, Fut, Func, Describe>(
+        &self,
+        action: AuditAction,
+        mutation: Func,
+        describe: Describe,
+    ) -> Box<Future<Item = R, Error = E>>
+    where
+        R: 'static,
+        E: From<Error> + 'static,
+        Fut: Future<Item = R, Error = E> + 'static,
+        Func: FnOnce() -> Fut + 'static,
+        Describe: FnOnce(&R) -> AuditChange + 'static,
+    {
+        let self_ = self.clone();
+
+        let fut = mutation().and_then(move |result| {
+            let change = describe(&result);
+            self_.record(action, change).then(move |_| Ok::<_, E>(result))
+        });
+
+        Box::new(fut)
+    }
+
+    fn snapshot(&self, target: AuditTarget) -> ServiceFutureV2<Option<serde_json::Value>> {
+        let repo_factory = self.repo_factory.clone();
+
+        spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| match target {
+            AuditTarget::Order(order_id) => {
+                let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                let order = orders_repo.get(order_id).map_err(ectx!(try convert => order_id))?;
+                to_snapshot(order)
+            }
+            AuditTarget::UserRoles(user_id) => {
+                let user_roles_repo = repo_factory.create_user_roles_repo_with_sys_acl(&conn);
+                let roles = user_roles_repo
+                    .list_for_user(user_id)
+                    .map_err(ectx!(try ErrorKind::Internal => user_id))?;
+                to_snapshot(Some(roles))
+            }
+            AuditTarget::Account(account_id) => {
+                let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
+                let account = accounts_repo.get(account_id).map_err(ectx!(try convert => account_id))?;
+                to_snapshot(account)
+            }
+            AuditTarget::StoreSubscription(store_id) => {
+                let store_subscription_repo = repo_factory.create_store_subscription_with_sys_acl(&conn);
+                let store_subscription = store_subscription_repo
+                    .get(StoreSubscriptionSearch::by_store_id(store_id))
+                    .map_err(ectx!(try convert => store_id))?;
+                to_snapshot(store_subscription)
+            }
+            AuditTarget::StoreBillingStatus(store_id) => {
+                let store_billing_statuses_repo = repo_factory.create_store_billing_statuses_repo_with_sys_acl(&conn);
+                let store_billing_status = store_billing_statuses_repo.get(store_id).map_err(ectx!(try convert => store_id))?;
+                to_snapshot(store_billing_status)
+            }
+            AuditTarget::FeatureFlag(feature) => {
+                let feature_flags_repo = repo_factory.create_feature_flags_repo_with_sys_acl(&conn);
+                let feature_flag = feature_flags_repo.get(feature).map_err(ectx!(try convert => feature))?;
+                to_snapshot(feature_flag)
+            }
+            AuditTarget::DataRetentionSubject(subject) => {
+                let data_retention_repo = repo_factory.create_data_retention_repo_with_sys_acl(&conn);
+                let retention_subject = data_retention_repo.get(subject).map_err(ectx!(try convert => subject))?;
+                to_snapshot(retention_subject)
+            }
+            AuditTarget::PayoutRestrictionOverride(store_id) => {
+                let payout_restriction_overrides_repo = repo_factory.create_payout_restriction_overrides_repo_with_sys_acl(&conn);
+                let payout_override = payout_restriction_overrides_repo
+                    .get(store_id)
+                    .map_err(ectx!(try convert => store_id))?;
+                to_snapshot(payout_override)
+            }
+            AuditTarget::InvoiceRiskAssessment(invoice_id) => {
+                let invoice_risk_assessments_repo = repo_factory.create_invoice_risk_assessments_repo_with_sys_acl(&conn);
+                let assessment = invoice_risk_assessments_repo
+                    .get(invoice_id)
+                    .map_err(ectx!(try convert => invoice_id))?;
+                to_snapshot(assessment)
+            }
+            AuditTarget::UserCredit(user_id) => {
+                let user_credits_repo = repo_factory.create_user_credits_repo_with_sys_acl(&conn);
+                let credit_user_id = CreditUserId::new(user_id.0);
+                let balances = user_credits_repo
+                    .get_balances(credit_user_id)
+                    .map_err(ectx!(try convert => credit_user_id))?;
+                to_snapshot(Some(balances))
+            }
+        })
+    }
+
+    /// Failing to record an entry does not fail the mutation, which has already been applied by then
+    fn record(&self, action: AuditAction, change: AuditChange) -> ServiceFutureV2<()> {
+        let repo_factory = self.repo_factory.clone();
+        let actor_user_id = self.user_id;
+
+        let fut = spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let audit_log_repo = repo_factory.create_audit_log_repo_with_sys_acl(&conn);
+            let AuditChange { resource, before, after } = change;
+
+            let payload = NewAuditLogEntry {
+                actor_user_id,
+                action,
+                resource_type: resource.resource_type,
+                resource_id: resource.resource_id,
+                before,
+                after,
+            };
+
+            audit_log_repo
+                .create(payload.clone())
+                .map(|_| ())
+                .map_err(ectx!(convert => payload))
+        })
+        .map_err(move |e| {
+            error!("Failed to record {} by user {:?} in the audit log: {}", action, actor_user_id, e);
+            e
+        });
+
+        Box::new(fut)
+    }
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > Clone for AuditLogServiceImpl<T, M, F>
+{
+    fn clone(&self) -> Self {
+        Self {
+            db_pool: self.db_pool.clone(),
+            cpu_pool: self.cpu_pool.clone(),
+            repo_factory: self.repo_factory.clone(),
+            user_id: self.user_id,
+        }
+    }
+}
+
+fn to_snapshot<S: Serialize>(value: Option<S>) -> Result<Option<serde_json::Value>, Error> {
+    match value {
+        None => Ok(None),
+        Some(value) => serde_json::to_value(value).map(Some).map_err(ectx!(ErrorKind::Internal)),
+    }
+}
@@ -1,4 +1,6 @@
-//! BillingInfo Service, presents operations with billing info resource
+//! BillingInfo Service, presents operations with billing info resource.
+//! Billing info is where the payouts of a store are sent, so the changes of everyone but superusers
+//! wait for the approval of a financial manager and the store owner is notified of every change
 use diesel::connection::AnsiTransactionManager;
 use diesel::pg::Pg;
 use diesel::Connection;
@@ -9,31 +11,69 @@ use validator::{ValidationError, ValidationErrors};
 use failure::Fail;
 
 use stq_http::client::HttpClient;
-use stq_types::{BillingType, InternationalBillingId, RussiaBillingId, StoreId};
+use stq_types::{BillingRole, BillingType, InternationalBillingId, RussiaBillingId, StoreId, UserId};
 
 use client::payments::PaymentsClient;
+use config::PayoutRestrictions as PayoutRestrictionsConfig;
 use services::accounts::AccountService;
 
 use models::*;
-use repos::{InternationalBillingInfoRepo, ReposFactory, RussiaBillingInfoRepo, StoreBillingTypeRepo};
+use repos::{
+    BillingInfoChangesRepo, EventStoreRepo, InternationalBillingInfoRepo, ReposFactory, RussiaBillingInfoRepo, StoreBillingTypeRepo,
+    StoreOffboardingsRepo, UserRolesRepo,
+};
 use services::error::{Error as ServiceError, ErrorContext, ErrorKind};
+use services::payout_restriction::{billing_info_change_country, check_payout_restriction};
 
-use super::types::ServiceFutureV2;
+use super::types::{ServiceFutureV2, ServiceResultV2};
 use controller::context::DynamicContext;
+use controller::requests::RejectBillingInfoChangeRequest;
+use controller::responses::{BillingInfoChangeResponse, BillingInfoReencryptionResponse};
+
+/// Number of billing infos of each kind sealed with the current key in one transaction
+pub const BILLING_INFO_REENCRYPTION_BATCH_SIZE: i64 = 100;
 
 use services::types::spawn_on_pool;
 
+/// Billing info as changed right away or the change waiting for a financial manager
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum BillingInfoChangeOutcome<T> {
+    Applied(T),
+    PendingApproval(BillingInfoChangeResponse),
+}
+
 pub trait BillingInfoService {
     fn get_russia_billing_info_by_store(&self, store_id: StoreId) -> ServiceFutureV2<Option<RussiaBillingInfo>>;
     fn get_international_billing_info_by_store(&self, store_id: StoreId) -> ServiceFutureV2<Option<InternationalBillingInfo>>;
-    fn create_international_billing_info(&self, payload: NewInternationalBillingInfo) -> ServiceFutureV2<InternationalBillingInfo>;
+    fn create_international_billing_info(
+        &self,
+        payload: NewInternationalBillingInfo,
+    ) -> ServiceFutureV2<BillingInfoChangeOutcome<InternationalBillingInfo>>;
     fn update_international_billing_info(
         &self,
         id: InternationalBillingId,
         payload: UpdateInternationalBillingInfo,
-    ) -> ServiceFutureV2<InternationalBillingInfo>;
-    fn create_russia_billing_info(&self, payload: NewRussiaBillingInfo) -> ServiceFutureV2<RussiaBillingInfo>;
-    fn update_russia_billing_info(&self, id: RussiaBillingId, payload: UpdateRussiaBillingInfo) -> ServiceFutureV2<RussiaBillingInfo>;
+    ) -> ServiceFutureV2<BillingInfoChangeOutcome<InternationalBillingInfo>>;
+    fn create_russia_billing_info(&self, payload: NewRussiaBillingInfo) -> ServiceFutureV2<BillingInfoChangeOutcome<RussiaBillingInfo>>;
+    fn update_russia_billing_info(
+        &self,
+        id: RussiaBillingId,
+        payload: UpdateRussiaBillingInfo,
+    ) -> ServiceFutureV2<BillingInfoChangeOutcome<RussiaBillingInfo>>;
+    /// Billing info changes of all stores waiting for a financial manager
+    fn get_pending_billing_info_changes(&self) -> ServiceFutureV2<Vec<BillingInfoChangeResponse>>;
+    fn get_billing_info_changes_by_store(&self, store_id: StoreId) -> ServiceFutureV2<Vec<BillingInfoChangeResponse>>;
+    /// Applies a pending change to the billing info of the store, the change can't be approved by its requester
+    fn approve_billing_info_change(&self, id: BillingInfoChangeId) -> ServiceFutureV2<BillingInfoChangeResponse>;
+    fn reject_billing_info_change(
+        &self,
+        id: BillingInfoChangeId,
+        payload: RejectBillingInfoChangeRequest,
+    ) -> ServiceFutureV2<BillingInfoChangeResponse>;
+    /// Seals the first batch of billing infos with the current encryption key,
+    /// the rest are re-encrypted by the event store in batches of the same size
+    fn reencrypt_billing_infos(&self) -> ServiceFutureV2<BillingInfoReencryptionResponse>;
 }
 
 pub struct BillingInfoServiceImpl<
@@ -48,6 +88,14 @@ pub struct BillingInfoServiceImpl<
     pub cpu_pool: CpuPool,
     pub repo_factory: F,
     pub dynamic_context: DynamicContext<C, PC, AS>,
+    pub payout_restrictions: PayoutRestrictionsConfig,
+}
+
+/// Repos changing the billing info of a store
+struct BillingInfoRepos<'a> {
+    store_billing_type_repo: Box<StoreBillingTypeRepo + 'a>,
+    international_billing_info_repo: Box<InternationalBillingInfoRepo + 'a>,
+    russia_billing_info_repo: Box<RussiaBillingInfoRepo + 'a>,
 }
 
 impl<
@@ -91,7 +139,10 @@ impl<
         })
     }
 
-    fn create_international_billing_info(&self, payload: NewInternationalBillingInfo) -> ServiceFutureV2<InternationalBillingInfo> {
+    fn create_international_billing_info(
+        &self,
+        payload: NewInternationalBillingInfo,
+    ) -> ServiceFutureV2<BillingInfoChangeOutcome<InternationalBillingInfo>> {
         let repo_factory = self.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
 
@@ -99,17 +150,36 @@ impl<
         let cpu_pool = self.cpu_pool.clone();
 
         spawn_on_pool(db_pool, cpu_pool, move |conn| {
-            let store_billing_type_repo = repo_factory.create_store_billing_type_repo(&conn, user_id);
-            let international_billing_info_repo = repo_factory.create_international_billing_info_repo(&conn, user_id);
-            let russia_billing_info_repo = repo_factory.create_russia_billing_info_repo(&conn, user_id);
+            let user_roles_repo = repo_factory.create_user_roles_repo_with_sys_acl(&conn);
+            let store_offboardings_repo = repo_factory.create_store_offboardings_repo_with_sys_acl(&conn);
+            let billing_info_changes_repo = repo_factory.create_billing_info_changes_repo(&conn, user_id);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+            let repos = BillingInfoRepos {
+                store_billing_type_repo: repo_factory.create_store_billing_type_repo(&conn, user_id),
+                international_billing_info_repo: repo_factory.create_international_billing_info_repo(&conn, user_id),
+                russia_billing_info_repo: repo_factory.create_russia_billing_info_repo(&conn, user_id),
+            };
             conn.transaction(move || {
-                let store_id = payload.store_id;
-
-                validate_create_international_billing_info(&*international_billing_info_repo, &payload)?;
-                update_store_billing_type_to_international(&*store_billing_type_repo, &*russia_billing_info_repo, store_id)?;
-
-                let created_info = international_billing_info_repo.create(payload).map_err(ectx!(try convert))?;
-                Ok(created_info)
+                validate_create_international_billing_info(&*repos.international_billing_info_repo, &payload)?;
+
+                let change = request_billing_info_change(
+                    &*user_roles_repo,
+                    &*store_offboardings_repo,
+                    &*billing_info_changes_repo,
+                    &*event_store_repo,
+                    user_id,
+                    payload.store_id,
+                    BillingInfoChangePayload::CreateInternational {
+                        billing_info: payload.clone(),
+                    },
+                )?;
+
+                match change.status {
+                    BillingInfoChangeStatus::Approved => {
+                        create_international_billing_info(&repos, payload).map(BillingInfoChangeOutcome::Applied)
+                    }
+                    _ => Ok(BillingInfoChangeOutcome::PendingApproval(change.into())),
+                }
             })
         })
     }
@@ -118,7 +188,7 @@ impl<
         &self,
         id: InternationalBillingId,
         payload: UpdateInternationalBillingInfo,
-    ) -> ServiceFutureV2<InternationalBillingInfo> {
+    ) -> ServiceFutureV2<BillingInfoChangeOutcome<InternationalBillingInfo>> {
         let repo_factory = self.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
 
@@ -126,17 +196,89 @@ impl<
         let cpu_pool = self.cpu_pool.clone();
 
         spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let user_roles_repo = repo_factory.create_user_roles_repo_with_sys_acl(&conn);
+            let store_offboardings_repo = repo_factory.create_store_offboardings_repo_with_sys_acl(&conn);
+            let billing_info_changes_repo = repo_factory.create_billing_info_changes_repo(&conn, user_id);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
             let international_billing_info_repo = repo_factory.create_international_billing_info_repo(&conn, user_id);
+            conn.transaction(move || {
+                let billing_info = international_billing_info_repo
+                    .get(InternationalBillingInfoSearch::by_id(id))
+                    .map_err(ectx!(try convert => id))?
+                    .ok_or_else(|| {
+                        let e = format_err!("International billing info {} not found", id);
+                        ectx!(err e, ErrorKind::NotFound)
+                    })?;
+
+                let change = request_billing_info_change(
+                    &*user_roles_repo,
+                    &*store_offboardings_repo,
+                    &*billing_info_changes_repo,
+                    &*event_store_repo,
+                    user_id,
+                    billing_info.store_id,
+                    BillingInfoChangePayload::UpdateInternational {
+                        id,
+                        billing_info: payload.clone(),
+                    },
+                )?;
+
+                match change.status {
+                    BillingInfoChangeStatus::Approved => international_billing_info_repo
+                        .update(InternationalBillingInfoSearch::by_id(id), payload)
+                        .map(BillingInfoChangeOutcome::Applied)
+                        .map_err(ectx!(convert)),
+                    _ => Ok(BillingInfoChangeOutcome::PendingApproval(change.into())),
+                }
+            })
+        })
+    }
 
-            let updated = international_billing_info_repo
-                .update(InternationalBillingInfoSearch::by_id(id), payload)
-                .map_err(ectx!(try convert))?;
+    fn create_russia_billing_info(&self, payload: NewRussiaBillingInfo) -> ServiceFutureV2<BillingInfoChangeOutcome<RussiaBillingInfo>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
 
-            Ok(updated)
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let user_roles_repo = repo_factory.create_user_roles_repo_with_sys_acl(&conn);
+            let store_offboardings_repo = repo_factory.create_store_offboardings_repo_with_sys_acl(&conn);
+            let billing_info_changes_repo = repo_factory.create_billing_info_changes_repo(&conn, user_id);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+            let repos = BillingInfoRepos {
+                store_billing_type_repo: repo_factory.create_store_billing_type_repo(&conn, user_id),
+                international_billing_info_repo: repo_factory.create_international_billing_info_repo(&conn, user_id),
+                russia_billing_info_repo: repo_factory.create_russia_billing_info_repo(&conn, user_id),
+            };
+            conn.transaction(move || {
+                validate_create_russia_billing_info(&*repos.russia_billing_info_repo, &payload)?;
+
+                let change = request_billing_info_change(
+                    &*user_roles_repo,
+                    &*store_offboardings_repo,
+                    &*billing_info_changes_repo,
+                    &*event_store_repo,
+                    user_id,
+                    payload.store_id,
+                    BillingInfoChangePayload::CreateRussia {
+                        billing_info: payload.clone(),
+                    },
+                )?;
+
+                match change.status {
+                    BillingInfoChangeStatus::Approved => create_russia_billing_info(&repos, payload).map(BillingInfoChangeOutcome::Applied),
+                    _ => Ok(BillingInfoChangeOutcome::PendingApproval(change.into())),
+                }
+            })
         })
     }
 
-    fn create_russia_billing_info(&self, payload: NewRussiaBillingInfo) -> ServiceFutureV2<RussiaBillingInfo> {
+    fn update_russia_billing_info(
+        &self,
+        id: RussiaBillingId,
+        payload: UpdateRussiaBillingInfo,
+    ) -> ServiceFutureV2<BillingInfoChangeOutcome<RussiaBillingInfo>> {
         let repo_factory = self.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
 
@@ -144,22 +286,133 @@ impl<
         let cpu_pool = self.cpu_pool.clone();
 
         spawn_on_pool(db_pool, cpu_pool, move |conn| {
-            let store_billing_type_repo = repo_factory.create_store_billing_type_repo(&conn, user_id);
-            let international_billing_info_repo = repo_factory.create_international_billing_info_repo(&conn, user_id);
+            let user_roles_repo = repo_factory.create_user_roles_repo_with_sys_acl(&conn);
+            let store_offboardings_repo = repo_factory.create_store_offboardings_repo_with_sys_acl(&conn);
+            let billing_info_changes_repo = repo_factory.create_billing_info_changes_repo(&conn, user_id);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
             let russia_billing_info_repo = repo_factory.create_russia_billing_info_repo(&conn, user_id);
             conn.transaction(move || {
-                let store_id = payload.store_id;
+                let billing_info = russia_billing_info_repo
+                    .get(RussiaBillingInfoSearch::by_id(id))
+                    .map_err(ectx!(try convert => id))?
+                    .ok_or_else(|| {
+                        let e = format_err!("Russia billing info {} not found", id);
+                        ectx!(err e, ErrorKind::NotFound)
+                    })?;
+
+                let change = request_billing_info_change(
+                    &*user_roles_repo,
+                    &*store_offboardings_repo,
+                    &*billing_info_changes_repo,
+                    &*event_store_repo,
+                    user_id,
+                    billing_info.store_id,
+                    BillingInfoChangePayload::UpdateRussia {
+                        id,
+                        billing_info: payload.clone(),
+                    },
+                )?;
+
+                match change.status {
+                    BillingInfoChangeStatus::Approved => russia_billing_info_repo
+                        .update(RussiaBillingInfoSearch::by_id(id), payload)
+                        .map(BillingInfoChangeOutcome::Applied)
+                        .map_err(ectx!(convert)),
+                    _ => Ok(BillingInfoChangeOutcome::PendingApproval(change.into())),
+                }
+            })
+        })
+    }
+
+    fn get_pending_billing_info_changes(&self) -> ServiceFutureV2<Vec<BillingInfoChangeResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let billing_info_changes_repo = repo_factory.create_billing_info_changes_repo(&conn, user_id);
+
+            billing_info_changes_repo
+                .list_pending()
+                .map(|changes| changes.into_iter().map(BillingInfoChangeResponse::from).collect())
+                .map_err(ectx!(convert))
+        })
+    }
+
+    fn get_billing_info_changes_by_store(&self, store_id: StoreId) -> ServiceFutureV2<Vec<BillingInfoChangeResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
 
-                validate_create_russia_billing_info(&*russia_billing_info_repo, &payload)?;
-                update_store_billing_type_to_russia(&*store_billing_type_repo, &*international_billing_info_repo, store_id)?;
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let billing_info_changes_repo = repo_factory.create_billing_info_changes_repo(&conn, user_id);
+
+            billing_info_changes_repo
+                .list_by_store_id(store_id)
+                .map(|changes| changes.into_iter().map(BillingInfoChangeResponse::from).collect())
+                .map_err(ectx!(convert => store_id))
+        })
+    }
+
+    fn approve_billing_info_change(&self, id: BillingInfoChangeId) -> ServiceFutureV2<BillingInfoChangeResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let payout_restrictions = self.payout_restrictions.clone();
 
-                let created_info = russia_billing_info_repo.create(payload).map_err(ectx!(try convert))?;
-                Ok(created_info)
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let billing_info_changes_repo = repo_factory.create_billing_info_changes_repo(&conn, user_id);
+            let payout_restriction_overrides_repo = repo_factory.create_payout_restriction_overrides_repo_with_sys_acl(&conn);
+            let sys_billing_info_changes_repo = repo_factory.create_billing_info_changes_repo_with_sys_acl(&conn);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+            let sys_repos = BillingInfoRepos {
+                store_billing_type_repo: repo_factory.create_store_billing_type_repo_with_sys_acl(&conn),
+                international_billing_info_repo: repo_factory.create_international_billing_repo_info_with_sys_acl(&conn),
+                russia_billing_info_repo: repo_factory.create_russia_billing_info_repo_with_sys_acl(&conn),
+            };
+            conn.transaction(move || {
+                let reviewed_change = review_billing_info_change(
+                    &*billing_info_changes_repo,
+                    &*event_store_repo,
+                    user_id,
+                    id,
+                    BillingInfoChangeStatus::Approved,
+                    None,
+                )?;
+
+                // The payload of the reviewed change may be masked for the reviewer
+                let change = sys_billing_info_changes_repo
+                    .get(id)
+                    .map_err(ectx!(try convert => id))?
+                    .ok_or_else(|| {
+                        let e = format_err!("Billing info change {} not found", id);
+                        ectx!(err e, ErrorKind::Internal)
+                    })?;
+                let country = billing_info_change_country(&change.payload);
+                check_payout_restriction(
+                    &payout_restrictions,
+                    &*payout_restriction_overrides_repo,
+                    change.store_id,
+                    country.as_ref().map(String::as_str),
+                )?;
+                apply_billing_info_change(&sys_repos, change.payload)?;
+
+                Ok(reviewed_change.into())
             })
         })
     }
 
-    fn update_russia_billing_info(&self, id: RussiaBillingId, payload: UpdateRussiaBillingInfo) -> ServiceFutureV2<RussiaBillingInfo> {
+    fn reject_billing_info_change(
+        &self,
+        id: BillingInfoChangeId,
+        payload: RejectBillingInfoChangeRequest,
+    ) -> ServiceFutureV2<BillingInfoChangeResponse> {
         let repo_factory = self.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
 
@@ -167,24 +420,235 @@ impl<
         let cpu_pool = self.cpu_pool.clone();
 
         spawn_on_pool(db_pool, cpu_pool, move |conn| {
-            let russia_billing_info_repo = repo_factory.create_russia_billing_info_repo(&conn, user_id);
+            let billing_info_changes_repo = repo_factory.create_billing_info_changes_repo(&conn, user_id);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+            conn.transaction(move || {
+                review_billing_info_change(
+                    &*billing_info_changes_repo,
+                    &*event_store_repo,
+                    user_id,
+                    id,
+                    BillingInfoChangeStatus::Rejected,
+                    payload.reason,
+                )
+                .map(BillingInfoChangeResponse::from)
+            })
+        })
+    }
 
-            let updated = russia_billing_info_repo
-                .update(RussiaBillingInfoSearch::by_id(id), payload)
-                .map_err(ectx!(try convert))?;
+    fn reencrypt_billing_infos(&self) -> ServiceFutureV2<BillingInfoReencryptionResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
 
-            Ok(updated)
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let international_billing_info_repo = repo_factory.create_international_billing_info_repo(&conn, user_id);
+            let russia_billing_info_repo = repo_factory.create_russia_billing_info_repo(&conn, user_id);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+            conn.transaction(move || {
+                let international_batch = international_billing_info_repo
+                    .reencrypt(None, BILLING_INFO_REENCRYPTION_BATCH_SIZE)
+                    .map_err(ectx!(try convert))?;
+                let russia_batch = russia_billing_info_repo
+                    .reencrypt(None, BILLING_INFO_REENCRYPTION_BATCH_SIZE)
+                    .map_err(ectx!(try convert))?;
+
+                if let Some(after_id) = international_batch.last_id {
+                    let event = Event::new(EventPayload::InternationalBillingInfoReencryptionBatch { after_id });
+                    event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+                }
+                if let Some(after_id) = russia_batch.last_id {
+                    let event = Event::new(EventPayload::RussiaBillingInfoReencryptionBatch { after_id });
+                    event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+                }
+
+                Ok(BillingInfoReencryptionResponse {
+                    international_billing_infos: international_batch.reencrypted,
+                    russia_billing_infos: russia_batch.reencrypted,
+                })
+            })
         })
     }
 }
 
+/// Records the change of the billing info requested by the user. Superusers change billing info right away,
+/// the changes of everyone else wait for a financial manager. The store owner is notified either way.
+/// The billing info of an offboarded store is archived and can't be changed by anyone
+fn request_billing_info_change(
+    user_roles_repo: &UserRolesRepo,
+    store_offboardings_repo: &StoreOffboardingsRepo,
+    billing_info_changes_repo: &BillingInfoChangesRepo,
+    event_store_repo: &EventStoreRepo,
+    user_id: Option<UserId>,
+    store_id: StoreId,
+    payload: BillingInfoChangePayload,
+) -> ServiceResultV2<BillingInfoChange> {
+    let user_id = user_id.ok_or_else(|| ectx!(err ErrorContext::Unauthorized, ErrorKind::Forbidden))?;
+
+    let offboarding = store_offboardings_repo.get(store_id).map_err(ectx!(try convert => store_id))?;
+    if offboarding.and_then(|offboarding| offboarding.billing_info_archived_at).is_some() {
+        return Err(billing_info_change_error(
+            "archived",
+            format!("Billing info of store \"{}\" is archived, the store is offboarded", store_id),
+        ));
+    }
+
+    let roles = user_roles_repo
+        .list_for_user(user_id)
+        .map_err(ectx!(try ErrorKind::Internal => user_id))?;
+
+    let new_change = if roles.contains(&BillingRole::Superuser) {
+        NewBillingInfoChange {
+            store_id,
+            payload,
+            status: BillingInfoChangeStatus::Approved,
+            requested_by: user_id,
+            reviewed_by: Some(user_id),
+        }
+    } else {
+        let pending_change = billing_info_changes_repo
+            .get_pending_by_store_id(store_id)
+            .map_err(ectx!(try convert => store_id))?;
+        if let Some(pending_change) = pending_change {
+            return Err(billing_info_change_error(
+                "pending",
+                format!(
+                    "Billing info change {} of store \"{}\" is already awaiting approval",
+                    pending_change.id, store_id
+                ),
+            ));
+        }
+
+        NewBillingInfoChange {
+            store_id,
+            payload,
+            status: BillingInfoChangeStatus::Pending,
+            requested_by: user_id,
+            reviewed_by: None,
+        }
+    };
+
+    let change = billing_info_changes_repo
+        .create(new_change)
+        .map_err(ectx!(try convert => store_id))?;
+
+    enqueue_billing_info_change_notification(event_store_repo, change.id)?;
+
+    Ok(change)
+}
+
+/// Approves or rejects a pending change, the requester of the change may not review it
+fn review_billing_info_change(
+    billing_info_changes_repo: &BillingInfoChangesRepo,
+    event_store_repo: &EventStoreRepo,
+    user_id: Option<UserId>,
+    id: BillingInfoChangeId,
+    status: BillingInfoChangeStatus,
+    rejection_reason: Option<String>,
+) -> ServiceResultV2<BillingInfoChange> {
+    let user_id = user_id.ok_or_else(|| ectx!(err ErrorContext::Unauthorized, ErrorKind::Forbidden))?;
+
+    let change = billing_info_changes_repo
+        .get(id)
+        .map_err(ectx!(try convert => id))?
+        .ok_or_else(|| {
+            let e = format_err!("Billing info change {} not found", id);
+            ectx!(err e, ErrorKind::NotFound)
+        })?;
+
+    if change.requested_by == user_id {
+        return Err(billing_info_change_error(
+            "requester",
+            format!("Billing info change {} can't be reviewed by its requester", id),
+        ));
+    }
+
+    let review = BillingInfoChangeReview {
+        status,
+        reviewed_by: user_id,
+        rejection_reason,
+    };
+    let reviewed_change = billing_info_changes_repo
+        .review(id, review)
+        .map_err(ectx!(try convert => id))?
+        .ok_or_else(|| billing_info_change_error("status", format!("Billing info change {} is not pending", id)))?;
+
+    enqueue_billing_info_change_notification(event_store_repo, id)?;
+
+    Ok(reviewed_change)
+}
+
+/// Applies an approved change to the billing info of the store
+fn apply_billing_info_change(repos: &BillingInfoRepos, payload: BillingInfoChangePayload) -> ServiceResultV2<()> {
+    match payload {
+        BillingInfoChangePayload::CreateInternational { billing_info } => {
+            validate_create_international_billing_info(&*repos.international_billing_info_repo, &billing_info)?;
+            create_international_billing_info(repos, billing_info).map(|_| ())
+        }
+        BillingInfoChangePayload::UpdateInternational { id, billing_info } => repos
+            .international_billing_info_repo
+            .update(InternationalBillingInfoSearch::by_id(id), billing_info)
+            .map(|_| ())
+            .map_err(ectx!(convert => id)),
+        BillingInfoChangePayload::CreateRussia { billing_info } => {
+            validate_create_russia_billing_info(&*repos.russia_billing_info_repo, &billing_info)?;
+            create_russia_billing_info(repos, billing_info).map(|_| ())
+        }
+        BillingInfoChangePayload::UpdateRussia { id, billing_info } => repos
+            .russia_billing_info_repo
+            .update(RussiaBillingInfoSearch::by_id(id), billing_info)
+            .map(|_| ())
+            .map_err(ectx!(convert => id)),
+    }
+}
+
+fn create_international_billing_info(
+    repos: &BillingInfoRepos,
+    payload: NewInternationalBillingInfo,
+) -> ServiceResultV2<InternationalBillingInfo> {
+    update_store_billing_type_to_international(&*repos.store_billing_type_repo, &*repos.russia_billing_info_repo, payload.store_id)?;
+
+    repos.international_billing_info_repo.create(payload).map_err(ectx!(convert))
+}
+
+fn create_russia_billing_info(repos: &BillingInfoRepos, payload: NewRussiaBillingInfo) -> ServiceResultV2<RussiaBillingInfo> {
+    update_store_billing_type_to_russia(
+        &*repos.store_billing_type_repo,
+        &*repos.international_billing_info_repo,
+        payload.store_id,
+    )?;
+
+    repos.russia_billing_info_repo.create(payload).map_err(ectx!(convert))
+}
+
+fn enqueue_billing_info_change_notification(
+    event_store_repo: &EventStoreRepo,
+    billing_info_change_id: BillingInfoChangeId,
+) -> ServiceResultV2<()> {
+    let event = Event::new(EventPayload::BillingInfoChangeNotification { billing_info_change_id });
+    event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+
+    Ok(())
+}
+
+fn billing_info_change_error(code: &'static str, message: String) -> ServiceError {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    errors.add("billing_info_change", error);
+    ectx!(err ErrorContext::BillingInfo, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
 fn validate_create_international_billing_info(
     repo: &InternationalBillingInfoRepo,
     payload: &NewInternationalBillingInfo,
 ) -> Result<(), ServiceError> {
     let existing_info = repo
         .get(InternationalBillingInfoSearch::by_store_id(payload.store_id))
-        .map_err(ectx!(try convert => payload))?;
+        .map_err(ectx!(try convert => payload.store_id))?;
 
     if existing_info.is_some() {
         let mut errors = ValidationErrors::new();
@@ -200,7 +664,7 @@ fn validate_create_international_billing_info(
 fn validate_create_russia_billing_info(repo: &RussiaBillingInfoRepo, payload: &NewRussiaBillingInfo) -> Result<(), ServiceError> {
     let existing_info = repo
         .get(RussiaBillingInfoSearch::by_store_id(payload.store_id))
-        .map_err(ectx!(try convert => payload))?;
+        .map_err(ectx!(try convert => payload.store_id))?;
 
     if existing_info.is_some() {
         let mut errors = ValidationErrors::new();
@@ -0,0 +1,129 @@
+//! Limits of the cashback sellers grant on their orders. Cashback above the limits is most likely
+//! a bug upstream, so it is rejected or clamped before the invoice is created
+use failure::Fail;
+
+use config::{Cashback as CashbackConfig, CashbackPolicy};
+use models::CreateOrderV2;
+use services::error::{Error, ErrorContext, ErrorKind};
+
+/// Applies the cashback limits to the orders of a new invoice.
+/// With the `reject` policy every offending order is listed in the validation error,
+/// with `clamp` their `product_cashback` is lowered to the largest fraction allowed
+pub fn apply_cashback_limits(config: &CashbackConfig, orders: Vec<CreateOrderV2>) -> Result<Vec<CreateOrderV2>, Error> {
+    let mut violations = Vec::new();
+    let mut limited_orders = Vec::with_capacity(orders.len());
+
+    for order in orders {
+        let product_cashback = match order.product_cashback {
+            None => {
+                limited_orders.push(order);
+                continue;
+            }
+            Some(product_cashback) => product_cashback,
+        };
+
+        let max_product_cashback = max_cashback_fraction(config, &order);
+        if product_cashback >= 0.0 && product_cashback <= max_product_cashback {
+            limited_orders.push(order);
+            continue;
+        }
+
+        violations.push(json!({
+            "order_id": order.id,
+            "currency": order.currency,
+            "total_amount": order.total_amount,
+            "product_cashback": product_cashback,
+            "max_product_cashback": max_product_cashback,
+        }));
+        limited_orders.push(CreateOrderV2 {
+            product_cashback: Some(product_cashback.max(0.0).min(max_product_cashback)),
+            ..order
+        });
+    }
+
+    if violations.is_empty() {
+        return Ok(limited_orders);
+    }
+
+    match config.policy {
+        CashbackPolicy::Reject => {
+            let e = format_err!("Cashback of {} orders is out of limits", violations.len());
+            Err(ectx!(err e, ErrorContext::Cashback, ErrorKind::Validation(json!({ "orders": violations }))))
+        }
+        CashbackPolicy::Clamp => {
+            warn!("Cashback of orders was clamped to the limits: {:?}", violations);
+            Ok(limited_orders)
+        }
+    }
+}
+
+/// Largest fraction of the order total allowed as its cashback
+fn max_cashback_fraction(config: &CashbackConfig, order: &CreateOrderV2) -> f64 {
+    match config.max_amounts.get(&order.currency) {
+        Some(max_amount) if order.total_amount > 0.0 => config.max_fraction.min(max_amount / order.total_amount),
+        _ => config.max_fraction,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use uuid::Uuid;
+
+    use super::*;
+    use models::order_v2::{OrderId, StoreId};
+    use models::Currency;
+
+    fn config(policy: CashbackPolicy) -> CashbackConfig {
+        let mut max_amounts = HashMap::new();
+        max_amounts.insert(Currency::Eur, 10.0);
+
+        CashbackConfig {
+            max_fraction: 0.5,
+            max_amounts,
+            policy,
+        }
+    }
+
+    fn order(currency: Currency, total_amount: f64, product_cashback: Option<f64>) -> CreateOrderV2 {
+        CreateOrderV2 {
+            id: OrderId::new(Uuid::new_v4()),
+            store_id: StoreId::new(1),
+            currency,
+            total_amount,
+            product_cashback,
+            line_items: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn orders_within_limits_are_kept() {
+        let orders = vec![
+            order(Currency::Stq, 100.0, Some(0.5)),
+            order(Currency::Eur, 100.0, Some(0.1)),
+            order(Currency::Eur, 100.0, None),
+        ];
+
+        let limited = apply_cashback_limits(&config(CashbackPolicy::Reject), orders.clone()).unwrap();
+
+        let cashbacks = limited.iter().map(|order| order.product_cashback).collect::<Vec<_>>();
+        assert_eq!(cashbacks, vec![Some(0.5), Some(0.1), None]);
+    }
+
+    #[test]
+    fn offending_orders_are_rejected_or_clamped() {
+        let orders = vec![
+            order(Currency::Stq, 100.0, Some(1.0)),
+            order(Currency::Eur, 100.0, Some(0.2)),
+            order(Currency::Usd, 100.0, Some(-0.1)),
+        ];
+
+        assert!(apply_cashback_limits(&config(CashbackPolicy::Reject), orders.clone()).is_err());
+
+        let limited = apply_cashback_limits(&config(CashbackPolicy::Clamp), orders).unwrap();
+
+        let cashbacks = limited.iter().map(|order| order.product_cashback).collect::<Vec<_>>();
+        assert_eq!(cashbacks, vec![Some(0.5), Some(0.1), Some(0.0)]);
+    }
+}
@@ -0,0 +1,183 @@
+//! CashbackLiabilityService reports the cashback owed to buyers. A snapshot of the outstanding cashback
+//! is taken as of the start of every month and kept for audit
+use std::time::{Duration as StdDuration, Instant};
+
+use chrono::{Datelike, NaiveDateTime, Utc};
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::{Error as FailureError, Fail};
+use futures::{future, Future, Stream};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use sentry::integrations::failure::capture_error;
+use tokio_timer::Interval;
+
+use stq_types::UserId;
+
+use super::types::ServiceFutureV2;
+use config::CashbackLiabilities as CashbackLiabilitiesConfig;
+use controller::responses::{CashbackLiabilitiesResponse, CashbackLiabilityResponse, CashbackLiabilitySnapshotResponse};
+use models::{CashbackLiabilitySnapshot, NewCashbackLiabilitySnapshot};
+use repos::ReposFactory;
+use services::types::spawn_on_pool;
+
+pub trait CashbackLiabilityService {
+    /// Cashback outstanding right now by currency
+    fn get_cashback_liabilities(&self) -> ServiceFutureV2<CashbackLiabilitiesResponse>;
+    /// Monthly snapshots taken as of the given time or all of them, the latest first
+    fn get_cashback_liability_snapshots(&self, as_of: Option<NaiveDateTime>) -> ServiceFutureV2<Vec<CashbackLiabilitySnapshotResponse>>;
+}
+
+pub struct CashbackLiabilityServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > CashbackLiabilityService for CashbackLiabilityServiceImpl<T, M, F>
+{
+    fn get_cashback_liabilities(&self) -> ServiceFutureV2<CashbackLiabilitiesResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let cashback_liabilities_repo = repo_factory.create_cashback_liabilities_repo(&conn, user_id);
+
+            let as_of = Utc::now().naive_utc();
+            let liabilities = cashback_liabilities_repo
+                .get_outstanding(as_of)
+                .map_err(ectx!(try convert => as_of))?;
+
+            Ok(CashbackLiabilitiesResponse {
+                as_of,
+                currencies: liabilities.into_iter().map(CashbackLiabilityResponse::from).collect(),
+            })
+        })
+    }
+
+    fn get_cashback_liability_snapshots(&self, as_of: Option<NaiveDateTime>) -> ServiceFutureV2<Vec<CashbackLiabilitySnapshotResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let cashback_liabilities_repo = repo_factory.create_cashback_liabilities_repo(&conn, user_id);
+
+            let snapshots = cashback_liabilities_repo
+                .get_snapshots(as_of)
+                .map_err(ectx!(try convert => as_of))?;
+
+            Ok(snapshots.into_iter().map(CashbackLiabilitySnapshotResponse::from).collect())
+        })
+    }
+}
+
+/// Takes the snapshot of the month that has just ended on every tick of `check_interval_sec`.
+/// A failed snapshot is reported and retried on the next tick
+pub fn run_cashback_liability_snapshots<T, M, F>(
+    config: CashbackLiabilitiesConfig,
+    db_pool: Pool<M>,
+    cpu_pool: CpuPool,
+    repo_factory: F,
+) -> impl Future<Item = (), Error = FailureError>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    let interval = StdDuration::from_secs(config.check_interval_sec);
+
+    Interval::new(Instant::now(), interval)
+        .map_err(FailureError::from)
+        .for_each(move |_| {
+            let as_of = snapshot_time(Utc::now().naive_utc());
+            take_cashback_liability_snapshot(db_pool.clone(), cpu_pool.clone(), repo_factory.clone(), as_of).then(move |res| {
+                match res {
+                    Ok(ref snapshots) if snapshots.is_empty() => {}
+                    Ok(snapshots) => {
+                        info!("Took cashback liability snapshot as of {} in {} currencies", as_of, snapshots.len());
+                    }
+                    Err(err) => {
+                        let err = FailureError::from(err.context("An error occurred while taking the cashback liability snapshot"));
+                        error!("{:?}", &err);
+                        capture_error(&err);
+                    }
+                };
+
+                future::ok::<_, FailureError>(())
+            })
+        })
+}
+
+/// Stores the outstanding cashback as of `as_of` unless a snapshot as of that time exists.
+/// Returns the stored snapshot, which is empty if it had been taken before
+pub fn take_cashback_liability_snapshot<T, M, F>(
+    db_pool: Pool<M>,
+    cpu_pool: CpuPool,
+    repo_factory: F,
+    as_of: NaiveDateTime,
+) -> ServiceFutureV2<Vec<CashbackLiabilitySnapshot>>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    spawn_on_pool(db_pool, cpu_pool, move |conn| {
+        let cashback_liabilities_repo = repo_factory.create_cashback_liabilities_repo_with_sys_acl(&conn);
+
+        conn.transaction(move || {
+            let existing = cashback_liabilities_repo
+                .get_snapshots(Some(as_of))
+                .map_err(ectx!(try convert => as_of))?;
+            if !existing.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let liabilities = cashback_liabilities_repo
+                .get_outstanding(as_of)
+                .map_err(ectx!(try convert => as_of))?;
+
+            liabilities
+                .into_iter()
+                .map(|liability| {
+                    let payload = NewCashbackLiabilitySnapshot::new(as_of, liability);
+                    cashback_liabilities_repo
+                        .create_snapshot(payload.clone())
+                        .map_err(ectx!(convert => payload))
+                })
+                .collect()
+        })
+    })
+}
+
+/// Start of the month of `now`, the snapshot as of it covers everything paid in the previous months
+pub fn snapshot_time(now: NaiveDateTime) -> NaiveDateTime {
+    now.date().with_day(1).unwrap_or(now.date()).and_hms(0, 0, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn snapshot_is_taken_as_of_start_of_month() {
+        let now = NaiveDate::from_ymd(2019, 3, 17).and_hms(13, 45, 0);
+
+        assert_eq!(snapshot_time(now), NaiveDate::from_ymd(2019, 3, 1).and_hms(0, 0, 0));
+    }
+}
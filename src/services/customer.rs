@@ -10,27 +10,37 @@ use futures_cpupool::CpuPool;
 use r2d2::{ManageConnection, Pool};
 
 use failure::Fail;
-use futures::{future, Future, IntoFuture};
-use stripe::{CardTokenId, ParseIdError, PaymentSource, TokenId};
+use futures::{future, stream, Future, IntoFuture, Stream};
+use serde_json;
+use stripe::{CardTokenId, Customer, ParseIdError, PaymentSource, TokenId};
+use validator::{validate_email, ValidationError, ValidationErrors};
 
 use stq_http::client::HttpClient;
+use stq_types::stripe::PaymentIntentId;
+use stq_types::UserId;
 
 use client::payments::PaymentsClient;
 use client::stripe::StripeClient;
 use services::accounts::AccountService;
 
-use models::{CustomerId, DbCustomer, NewDbCustomer, UpdateDbCustomer};
-use repos::{ReposFactory, SearchCustomer};
+use models::{CustomerId, DbCustomer, NewDbCustomer, UpdateDbCustomer, UpdatePaymentIntent, UserId as BillingUserId};
+use repos::error::ErrorKind as RepoErrorKind;
+use repos::{CustomersRepo, ReposFactory, SearchCustomer, SearchPaymentIntent, SearchPaymentIntentInvoice};
 use services::error::{Error, ErrorContext, ErrorKind};
 
 use super::types::ServiceFutureV2;
-use client::stripe::{ErrorKind as StripeErrorKind, NewCustomerWithSource, UpdateCustomer};
+use client::stripe::{ErrorKind as StripeErrorKind, NewCustomer, NewCustomerWithSource, UpdateCustomer};
 use controller::context::DynamicContext;
-use controller::requests::{NewCustomerWithSourceRequest, UpdateCustomerRequest};
-use controller::responses::{Card, CustomerResponse};
+use controller::requests::{NewCustomerWithSourceRequest, NewSetupIntentRequest, UpdateCustomerRequest};
+use controller::responses::{Card, CustomerResponse, CustomersDeduplicationResponse, SetupIntentResponse};
 
 use services::types::spawn_on_pool;
 
+/// Users whose duplicate customers are merged by a single deduplication request
+const CUSTOMERS_DEDUPLICATION_BATCH_SIZE: i64 = 20;
+
+const CARD_TOKEN_PREFIX: &str = "tok_";
+
 pub trait CustomersService {
     /// Creates new customer with default payment source
     fn create_customer_with_source(&self, payload: NewCustomerWithSourceRequest) -> ServiceFutureV2<CustomerResponse>;
@@ -38,11 +48,22 @@ pub trait CustomersService {
     /// Getting customer for current user
     fn get_customer(&self) -> ServiceFutureV2<Option<CustomerResponse>>;
 
+    /// Getting customer of any user
+    fn get_customer_by_user_id(&self, user_id: UserId) -> ServiceFutureV2<Option<CustomerResponse>>;
+
     /// Delete customer for current user
     fn delete(&self, payload: CustomerId) -> ServiceFutureV2<()>;
 
     /// Update customer for current user
     fn update(&self, payload: UpdateCustomerRequest) -> ServiceFutureV2<CustomerResponse>;
+
+    /// Creates a SetupIntent that saves a card for current user without charging it,
+    /// the Stripe customer is created first if the user doesn't have one
+    fn create_setup_intent(&self, payload: NewSetupIntentRequest) -> ServiceFutureV2<SetupIntentResponse>;
+
+    /// Merges the customers of a batch of users having more than one, the customer with a default card
+    /// (or else the oldest one) is kept and the rest are deleted in Stripe together with their cards
+    fn deduplicate_customers(&self) -> ServiceFutureV2<CustomersDeduplicationResponse>;
 }
 
 pub struct CustomersServiceImpl<
@@ -70,6 +91,11 @@ impl<
     > CustomersService for CustomersServiceImpl<T, M, F, C, PC, AS>
 {
     fn create_customer_with_source(&self, payload: NewCustomerWithSourceRequest) -> ServiceFutureV2<CustomerResponse> {
+        let payload = match validate_new_customer_with_source(payload) {
+            Ok(payload) => payload,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
         let repo_factory = self.repo_factory.clone();
         let repo_factory2 = self.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
@@ -107,6 +133,7 @@ impl<
                                     let client_payload = NewCustomerWithSource {
                                         email: payload_cloned.email,
                                         token,
+                                        idempotency_key: customer_idempotency_key(user_id),
                                     };
 
                                     stripe_client
@@ -123,15 +150,13 @@ impl<
                                             email: customer.email.clone(),
                                         };
 
-                                        customers_repo
-                                            .create(new_customer.clone())
-                                            .map_err(ectx!(convert => new_customer))
-                                            .map(move |db_customer| CustomerResponse {
-                                                id: db_customer.id,
-                                                user_id: db_customer.user_id,
-                                                email: db_customer.email,
-                                                cards: get_customer_cards(customer.sources.data),
-                                            })
+                                        create_db_customer(&*customers_repo, new_customer).map(move |db_customer| CustomerResponse {
+                                            id: db_customer.id,
+                                            user_id: db_customer.user_id,
+                                            email: db_customer.email,
+                                            payment_method_id: db_customer.payment_method_id,
+                                            cards: get_customer_cards(customer.sources.data),
+                                        })
                                     })
                                 }),
                         )
@@ -167,12 +192,19 @@ impl<
                             .get_customer(value.id.clone())
                             .map_err(ectx!(convert => db_customer_id))
                             .map(move |customer| {
-                                let DbCustomer { id, user_id, email, .. } = value;
+                                let DbCustomer {
+                                    id,
+                                    user_id,
+                                    email,
+                                    payment_method_id,
+                                    ..
+                                } = value;
 
                                 CustomerResponse {
                                     id,
                                     user_id,
                                     email,
+                                    payment_method_id,
                                     cards: get_customer_cards(customer.sources.data),
                                 }
                             })
@@ -185,6 +217,54 @@ impl<
         Box::new(fut)
     }
 
+    fn get_customer_by_user_id(&self, user_id: UserId) -> ServiceFutureV2<Option<CustomerResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let current_user_id = self.dynamic_context.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+        let stripe_client = self.stripe_client.clone();
+
+        let current_user_id = match current_user_id {
+            Some(current_user_id) => current_user_id,
+            None => return Box::new(future::err(ectx!(err ErrorContext::Unauthorized, ErrorKind::Forbidden))),
+        };
+
+        let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let customers_repo = repo_factory.create_customers_repo(&conn, Some(current_user_id));
+
+            customers_repo
+                .get(SearchCustomer::UserId(user_id))
+                .map_err(ectx!(convert => user_id))
+        })
+        .and_then(move |db_customer| {
+            db_customer.map(|value| {
+                let db_customer_id = value.id.clone();
+                stripe_client
+                    .get_customer(value.id.clone())
+                    .map_err(ectx!(convert => db_customer_id))
+                    .map(move |customer| {
+                        let DbCustomer {
+                            id,
+                            user_id,
+                            email,
+                            payment_method_id,
+                            ..
+                        } = value;
+
+                        CustomerResponse {
+                            id,
+                            user_id,
+                            email,
+                            payment_method_id,
+                            cards: get_customer_cards(customer.sources.data),
+                        }
+                    })
+            })
+        });
+
+        Box::new(fut)
+    }
+
     fn delete(&self, customer_id: CustomerId) -> ServiceFutureV2<()> {
         let repo_factory = self.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
@@ -216,11 +296,21 @@ impl<
     }
 
     fn update(&self, payload: UpdateCustomerRequest) -> ServiceFutureV2<CustomerResponse> {
+        let payload = match validate_update_customer(payload) {
+            Ok(payload) => payload,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
         let repo_factory = self.repo_factory.clone();
+        let repo_factory2 = self.repo_factory.clone();
         let user_id = self.dynamic_context.user_id;
         let db_pool = self.db_pool.clone();
         let cpu_pool = self.cpu_pool.clone();
+        let db_pool2 = self.db_pool.clone();
+        let cpu_pool2 = self.cpu_pool.clone();
         let stripe_client = self.stripe_client.clone();
+        let stripe_client2 = self.stripe_client.clone();
+        let email = payload.email.clone();
 
         let fut = user_id
             .ok_or_else(|| {
@@ -258,23 +348,471 @@ impl<
                     .into_future()
                     .and_then({
                         let customer_id = customer.id.clone();
-                        move |input| stripe_client.update_customer(customer_id, input).map_err(ectx!(convert))
+                        move |input| update_stripe_customer(stripe_client, customer_id, input)
                     })
                     .map(move |stripe_customer| (customer, stripe_customer))
             })
+            .and_then(move |(db_customer, stripe_customer)| match email {
+                Some(email) => future::Either::A(
+                    update_open_payment_intents_receipt_email(
+                        db_pool2,
+                        cpu_pool2,
+                        repo_factory2,
+                        stripe_client2,
+                        db_customer.user_id,
+                        email,
+                    )
+                    .map(move |_| (db_customer, stripe_customer)),
+                ),
+                None => future::Either::B(future::ok((db_customer, stripe_customer))),
+            })
             .and_then(|(db_customer, stripe_customer)| {
-                let DbCustomer { id, user_id, email, .. } = db_customer;
+                let DbCustomer {
+                    id,
+                    user_id,
+                    email,
+                    payment_method_id,
+                    ..
+                } = db_customer;
 
                 Ok(CustomerResponse {
                     id,
                     user_id,
                     email,
+                    payment_method_id,
                     cards: get_customer_cards(stripe_customer.sources.data),
                 })
             });
 
         Box::new(fut)
     }
+
+    fn create_setup_intent(&self, payload: NewSetupIntentRequest) -> ServiceFutureV2<SetupIntentResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+        let stripe_client = self.stripe_client.clone();
+
+        let fut = user_id
+            .ok_or_else(|| ectx!(err ErrorContext::Unauthorized, ErrorKind::Forbidden))
+            .into_future()
+            .and_then({
+                let repo_factory = repo_factory.clone();
+                let db_pool = db_pool.clone();
+                let cpu_pool = cpu_pool.clone();
+                move |user_id| {
+                    spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                        let customers_repo = repo_factory.create_customers_repo(&conn, Some(user_id));
+
+                        customers_repo
+                            .get(SearchCustomer::UserId(user_id))
+                            .map_err(ectx!(convert => user_id))
+                    })
+                    .map(move |db_customer| (user_id, db_customer))
+                }
+            })
+            .and_then({
+                let stripe_client = stripe_client.clone();
+                move |(user_id, db_customer)| match db_customer {
+                    Some(db_customer) => future::Either::A(future::ok(db_customer.id)),
+                    None => future::Either::B(
+                        stripe_client
+                            .create_customer(NewCustomer {
+                                email: payload.email.clone(),
+                                idempotency_key: customer_idempotency_key(user_id),
+                            })
+                            .map_err(ectx!(convert => payload))
+                            .and_then(move |customer| {
+                                spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                                    let customers_repo = repo_factory.create_customers_repo(&conn, Some(user_id));
+
+                                    let new_customer = NewDbCustomer {
+                                        id: CustomerId::new(customer.id.clone()),
+                                        user_id: user_id,
+                                        email: customer.email.clone(),
+                                    };
+
+                                    create_db_customer(&*customers_repo, new_customer).map(|db_customer| db_customer.id)
+                                })
+                            }),
+                    ),
+                }
+            })
+            .and_then(move |customer_id| {
+                let customer_id_cloned = customer_id.clone();
+                stripe_client
+                    .create_setup_intent(customer_id)
+                    .map_err(ectx!(convert => customer_id_cloned))
+            })
+            .map(|setup_intent| SetupIntentResponse {
+                id: setup_intent.id,
+                client_secret: setup_intent.client_secret,
+                status: setup_intent.status,
+            });
+
+        Box::new(fut)
+    }
+
+    fn deduplicate_customers(&self) -> ServiceFutureV2<CustomersDeduplicationResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+        let stripe_client = self.stripe_client.clone();
+
+        let fut = spawn_on_pool(db_pool.clone(), cpu_pool.clone(), {
+            let repo_factory = repo_factory.clone();
+            move |conn| {
+                let customers_repo = repo_factory.create_customers_repo(&conn, user_id);
+
+                customers_repo
+                    .get_duplicates(CUSTOMERS_DEDUPLICATION_BATCH_SIZE)
+                    .map_err(ectx!(convert))
+            }
+        })
+        .and_then(move |customers| {
+            let duplicates = group_by_user(customers);
+            let users = duplicates.len();
+
+            stream::iter_ok::<_, Error>(duplicates)
+                .and_then({
+                    let stripe_client = stripe_client.clone();
+                    move |customers| {
+                        let customers = customers.into_iter().map(|customer| {
+                            let customer_id = customer.id.clone();
+                            stripe_client
+                                .get_customer(customer.id.clone())
+                                .map_err(ectx!(convert => customer_id))
+                                .map(move |stripe_customer| {
+                                    let has_default_card = customer.payment_method_id.is_some() || stripe_customer.default_source.is_some();
+                                    (customer, has_default_card)
+                                })
+                        });
+
+                        future::join_all(customers).map(duplicates_to_delete)
+                    }
+                })
+                .map(stream::iter_ok)
+                .flatten()
+                .and_then(move |customer| {
+                    let db_pool = db_pool.clone();
+                    let cpu_pool = cpu_pool.clone();
+                    let repo_factory = repo_factory.clone();
+                    let customer_id = customer.id.clone();
+
+                    info!("Deleting duplicate customer {} of user {}", customer.id, customer.user_id);
+
+                    stripe_client
+                        .delete_customer(customer.id.clone())
+                        .map_err(ectx!(convert => customer_id))
+                        .and_then(move |deleted_customer| {
+                            spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                                let customers_repo = repo_factory.create_customers_repo(&conn, user_id);
+
+                                if deleted_customer.deleted {
+                                    customers_repo
+                                        .delete(customer.id.clone())
+                                        .map_err(ectx!(convert => customer.id))
+                                        .map(|_| ())
+                                } else {
+                                    let e = format_err!("Cannot delete customer in stripe with id: {:?}", customer.id);
+                                    Err(ectx!(err e, ErrorKind::Internal))
+                                }
+                            })
+                        })
+                })
+                .fold(0, |deleted_customers, _| Ok::<_, Error>(deleted_customers + 1))
+                .map(move |deleted_customers| CustomersDeduplicationResponse { users, deleted_customers })
+        });
+
+        Box::new(fut)
+    }
+}
+
+/// Same key for every attempt to create the customer of the user, so that retries don't create duplicates in Stripe
+fn customer_idempotency_key(user_id: UserId) -> String {
+    format!("customer-{}", user_id)
+}
+
+/// Saves the customer created in Stripe. A retry replayed by Stripe returns the customer the first attempt saved
+fn create_db_customer(customers_repo: &CustomersRepo, new_customer: NewDbCustomer) -> Result<DbCustomer, Error> {
+    let e = match customers_repo.create(new_customer.clone()) {
+        Ok(db_customer) => return Ok(db_customer),
+        Err(e) => e,
+    };
+
+    match e.kind() {
+        RepoErrorKind::Constraints(_) => {
+            let user_id = new_customer.user_id;
+            let existing = customers_repo
+                .get(SearchCustomer::UserId(user_id))
+                .map_err(ectx!(try convert => user_id))?;
+
+            match existing {
+                Some(ref existing) if existing.id == new_customer.id => Ok(existing.clone()),
+                _ => {
+                    let e = format_err!("Stripe Customer already exists for user_id {}", user_id);
+                    Err(ectx!(err e, ErrorKind::Internal))
+                }
+            }
+        }
+        _ => Err(ectx!(convert err e => new_customer)),
+    }
+}
+
+/// Splits customers ordered by user into the customers of each user
+fn group_by_user(customers: Vec<DbCustomer>) -> Vec<Vec<DbCustomer>> {
+    let mut groups: Vec<Vec<DbCustomer>> = Vec::new();
+    for customer in customers {
+        let same_user = groups.last().map(|group| group[0].user_id == customer.user_id).unwrap_or(false);
+        if same_user {
+            groups.last_mut().unwrap().push(customer);
+        } else {
+            groups.push(vec![customer]);
+        }
+    }
+    groups
+}
+
+/// Customers of a user to delete, ordered from the oldest one and flagged with whether they have a default card.
+/// The first customer with a default card is kept, or the oldest one if none has
+fn duplicates_to_delete(customers: Vec<(DbCustomer, bool)>) -> Vec<DbCustomer> {
+    let kept = customers.iter().position(|&(_, has_default_card)| has_default_card).unwrap_or(0);
+
+    customers
+        .into_iter()
+        .enumerate()
+        .filter(|&(index, _)| index != kept)
+        .map(|(_, (customer, _))| customer)
+        .collect()
+}
+
+/// Sets the new receipt email on the open payment intents of the user's unpaid invoices,
+/// so that receipts for them are not sent to the old address
+fn update_open_payment_intents_receipt_email<T, M, F>(
+    db_pool: Pool<M>,
+    cpu_pool: CpuPool,
+    repo_factory: F,
+    stripe_client: Arc<dyn StripeClient>,
+    user_id: UserId,
+    receipt_email: String,
+) -> ServiceFutureV2<()>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    let fut = spawn_on_pool(db_pool.clone(), cpu_pool.clone(), {
+        let repo_factory = repo_factory.clone();
+        let receipt_email = receipt_email.clone();
+        move |conn| {
+            let invoices_repo = repo_factory.create_invoices_v2_repo(&conn, Some(user_id));
+            let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo_with_sys_acl(&conn);
+            let payment_intent_repo = repo_factory.create_payment_intent_repo_with_sys_acl(&conn);
+
+            let invoices = invoices_repo
+                .get_unpaid_by_buyer_user_id(BillingUserId::new(user_id.0))
+                .map_err(ectx!(try convert => user_id))?;
+
+            let mut payment_intent_ids = Vec::new();
+            for invoice in invoices {
+                let invoice_id = invoice.id;
+                let payment_intent_invoice = payment_intent_invoices_repo
+                    .get(SearchPaymentIntentInvoice::InvoiceId(invoice_id))
+                    .map_err(ectx!(try convert => invoice_id))?;
+
+                if let Some(payment_intent_invoice) = payment_intent_invoice {
+                    let payment_intent_id = payment_intent_invoice.payment_intent_id;
+                    let payment_intent = payment_intent_repo
+                        .get(SearchPaymentIntent::Id(payment_intent_id.clone()))
+                        .map_err(ectx!(try convert => payment_intent_id))?;
+
+                    if let Some(payment_intent) = payment_intent {
+                        if payment_intent.status.is_cancellable() && payment_intent.receipt_email.as_ref() != Some(&receipt_email) {
+                            payment_intent_ids.push(payment_intent.id);
+                        }
+                    }
+                }
+            }
+
+            Ok(payment_intent_ids)
+        }
+    })
+    .and_then(move |payment_intent_ids| {
+        stream::iter_ok::<_, Error>(payment_intent_ids).for_each(move |payment_intent_id| {
+            let db_pool = db_pool.clone();
+            let cpu_pool = cpu_pool.clone();
+            let repo_factory = repo_factory.clone();
+
+            stripe_client
+                .update_payment_intent_receipt_email(payment_intent_id.clone(), receipt_email.clone())
+                .map_err(ectx!(convert => payment_intent_id))
+                .and_then(move |stripe_payment_intent| {
+                    spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                        let payment_intent_repo = repo_factory.create_payment_intent_repo_with_sys_acl(&conn);
+
+                        let payment_intent_id = PaymentIntentId(stripe_payment_intent.id);
+                        let update_payment_intent = UpdatePaymentIntent {
+                            receipt_email: stripe_payment_intent.receipt_email,
+                            ..UpdatePaymentIntent::default()
+                        };
+
+                        payment_intent_repo
+                            .update(payment_intent_id.clone(), update_payment_intent.clone())
+                            .map_err(ectx!(convert => payment_intent_id, update_payment_intent))
+                            .map(|_| ())
+                    })
+                })
+        })
+    });
+
+    Box::new(fut)
+}
+
+/// Updates the customer in Stripe. A card added with the fingerprint of a card the customer already has
+/// is deleted right away and rejected, so that the customer isn't left with the same card twice
+fn update_stripe_customer(
+    stripe_client: Arc<dyn StripeClient>,
+    customer_id: CustomerId,
+    input: UpdateCustomer,
+) -> ServiceFutureV2<Customer> {
+    if input.token.is_none() {
+        let customer_id_cloned = customer_id.clone();
+        return Box::new(
+            stripe_client
+                .update_customer(customer_id, input)
+                .map_err(ectx!(convert => customer_id_cloned)),
+        );
+    }
+
+    let customer_id_cloned = customer_id.clone();
+    let fut = stripe_client
+        .get_customer(customer_id.clone())
+        .map_err(ectx!(convert => customer_id_cloned))
+        .and_then({
+            let stripe_client = stripe_client.clone();
+            let customer_id = customer_id.clone();
+            move |existing| {
+                let customer_id_cloned = customer_id.clone();
+                stripe_client
+                    .update_customer(customer_id, input)
+                    .map_err(ectx!(convert => customer_id_cloned))
+                    .map(move |updated| (existing, updated))
+            }
+        })
+        .and_then(move |(existing, updated)| {
+            let duplicates = added_duplicate_cards(
+                &get_card_fingerprints(&existing.sources.data),
+                &get_card_fingerprints(&updated.sources.data),
+            );
+            if duplicates.is_empty() {
+                return future::Either::A(future::ok(updated));
+            }
+
+            let deleted_cards = duplicates.into_iter().map(move |card_id| {
+                info!("Deleting card {} of customer {} duplicating an existing card", card_id, customer_id);
+                let customer_id_cloned = customer_id.clone();
+                stripe_client
+                    .delete_card(customer_id.clone(), card_id)
+                    .map_err(ectx!(convert => customer_id_cloned))
+            });
+
+            future::Either::B(future::join_all(deleted_cards).and_then(|_| {
+                let mut errors = ValidationErrors::new();
+                let mut error = ValidationError::new("duplicate");
+                error.message = Some("Card is already saved for the customer".into());
+                errors.add("card_token", error);
+
+                Err(ectx!(err ErrorContext::Customer, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default())))
+            }))
+        });
+
+    Box::new(fut)
+}
+
+/// Ids and fingerprints of the customer's cards, the same card number always has the same fingerprint
+fn get_card_fingerprints(elements: &[PaymentSource]) -> Vec<(String, String)> {
+    elements
+        .iter()
+        .filter_map(|data_element| match *data_element {
+            PaymentSource::Card(ref card) => Some((card.id.clone(), card.fingerprint.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Cards added by an update whose fingerprint is the fingerprint of a card the customer had before and still has
+fn added_duplicate_cards(existing: &[(String, String)], updated: &[(String, String)]) -> Vec<String> {
+    let kept = updated
+        .iter()
+        .filter(|&&(ref id, _)| existing.iter().any(|&(ref existing_id, _)| existing_id == id))
+        .collect::<Vec<_>>();
+
+    updated
+        .iter()
+        .filter(|&&(ref id, _)| kept.iter().all(|&&(ref kept_id, _)| kept_id != id))
+        .filter(|&&(_, ref fingerprint)| kept.iter().any(|&&(_, ref kept_fingerprint)| kept_fingerprint == fingerprint))
+        .map(|&(ref id, _)| id.clone())
+        .collect()
+}
+
+/// Trims and lowercases the email and checks the card token, so that Stripe only gets input it accepts
+fn validate_new_customer_with_source(payload: NewCustomerWithSourceRequest) -> Result<NewCustomerWithSourceRequest, Error> {
+    let mut errors = ValidationErrors::new();
+
+    let email = normalize_email(payload.email, &mut errors);
+    let card_token = payload.card_token.trim().to_string();
+    check_card_token(&card_token, &mut errors);
+
+    if errors.is_empty() {
+        Ok(NewCustomerWithSourceRequest { email, card_token })
+    } else {
+        Err(ectx!(err ErrorContext::Customer, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default())))
+    }
+}
+
+/// Same checks as for a new customer, for the fields being updated
+fn validate_update_customer(payload: UpdateCustomerRequest) -> Result<UpdateCustomerRequest, Error> {
+    let mut errors = ValidationErrors::new();
+
+    let email = normalize_email(payload.email, &mut errors);
+    let card_token = payload.card_token.map(|card_token| card_token.trim().to_string());
+    if let Some(ref card_token) = card_token {
+        check_card_token(card_token, &mut errors);
+    }
+
+    if errors.is_empty() {
+        Ok(UpdateCustomerRequest { email, card_token })
+    } else {
+        Err(ectx!(err ErrorContext::Customer, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default())))
+    }
+}
+
+/// Blank email is treated as no email
+fn normalize_email(email: Option<String>, errors: &mut ValidationErrors) -> Option<String> {
+    let email = email.map(|email| email.trim().to_lowercase()).filter(|email| !email.is_empty())?;
+
+    if !validate_email(email.as_str()) {
+        let mut error = ValidationError::new("email");
+        error.message = Some("Email is not a valid address".into());
+        errors.add("email", error);
+    }
+
+    Some(email)
+}
+
+/// Card tokens created by Stripe.js look like `tok_1EHzYv2eZvKYlo2C`
+fn check_card_token(card_token: &str, errors: &mut ValidationErrors) {
+    let valid = card_token.starts_with(CARD_TOKEN_PREFIX)
+        && card_token.len() > CARD_TOKEN_PREFIX.len()
+        && card_token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if !valid {
+        let mut error = ValidationError::new("card_token");
+        error.message = Some("Card token is not a Stripe card token".into());
+        errors.add("card_token", error);
+    }
 }
 
 fn get_customer_cards(elements: Vec<PaymentSource>) -> Vec<Card> {
@@ -307,3 +845,109 @@ fn try_from_request(payload: UpdateCustomerRequest) -> Result<UpdateCustomer, Er
         token,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::Utc;
+
+    fn db_customer(id: &str, user_id: i32) -> DbCustomer {
+        let now = Utc::now().naive_utc();
+        DbCustomer {
+            id: CustomerId::new(id.to_string()),
+            user_id: UserId(user_id),
+            email: None,
+            created_at: now,
+            updated_at: now,
+            payment_method_id: None,
+        }
+    }
+
+    #[test]
+    fn group_by_user_splits_customers_of_each_user() {
+        let customers = vec![db_customer("cus_1", 1), db_customer("cus_2", 1), db_customer("cus_3", 2)];
+
+        let groups = group_by_user(customers);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].len(), 2);
+        assert_eq!(groups[1][0].id, CustomerId::new("cus_3".to_string()));
+    }
+
+    #[test]
+    fn duplicates_to_delete_keeps_customer_with_default_card() {
+        let customers = vec![
+            (db_customer("cus_1", 1), false),
+            (db_customer("cus_2", 1), true),
+            (db_customer("cus_3", 1), true),
+        ];
+
+        let deleted = duplicates_to_delete(customers)
+            .into_iter()
+            .map(|customer| customer.id)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            deleted,
+            vec![CustomerId::new("cus_1".to_string()), CustomerId::new("cus_3".to_string())]
+        );
+    }
+
+    #[test]
+    fn duplicates_to_delete_keeps_oldest_customer_without_default_cards() {
+        let customers = vec![(db_customer("cus_1", 1), false), (db_customer("cus_2", 1), false)];
+
+        let deleted = duplicates_to_delete(customers)
+            .into_iter()
+            .map(|customer| customer.id)
+            .collect::<Vec<_>>();
+
+        assert_eq!(deleted, vec![CustomerId::new("cus_2".to_string())]);
+    }
+
+    #[test]
+    fn validate_new_customer_with_source_normalizes_email() {
+        let payload = NewCustomerWithSourceRequest {
+            email: Some("  Buyer@Example.COM ".to_string()),
+            card_token: " tok_1EHzYv2eZvKYlo2C".to_string(),
+        };
+
+        let payload = validate_new_customer_with_source(payload).unwrap();
+
+        assert_eq!(payload.email, Some("buyer@example.com".to_string()));
+        assert_eq!(payload.card_token, "tok_1EHzYv2eZvKYlo2C");
+    }
+
+    #[test]
+    fn validate_new_customer_with_source_rejects_bad_email_and_token() {
+        let payload = NewCustomerWithSourceRequest {
+            email: Some("buyer@".to_string()),
+            card_token: "card_1EHzYv2eZvKYlo2C".to_string(),
+        };
+
+        let e = validate_new_customer_with_source(payload).unwrap_err();
+
+        match e.kind() {
+            ErrorKind::Validation(errors) => {
+                assert!(errors.get("email").is_some());
+                assert!(errors.get("card_token").is_some());
+            }
+            kind => panic!("validation error is expected, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn added_duplicate_cards_finds_new_cards_with_existing_fingerprints() {
+        let card = |id: &str, fingerprint: &str| (id.to_string(), fingerprint.to_string());
+        let existing = vec![card("card_1", "fp_1"), card("card_2", "fp_2")];
+        let updated = vec![
+            card("card_1", "fp_1"),
+            card("card_3", "fp_1"),
+            card("card_4", "fp_2"),
+            card("card_5", "fp_5"),
+        ];
+
+        assert_eq!(added_duplicate_cards(&existing, &updated), vec!["card_3".to_string()]);
+    }
+}
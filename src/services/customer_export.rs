@@ -0,0 +1,274 @@
+//! Nightly export of the customers and their payment summaries to the storage for the CRM sync.
+//! An incremental export picks the users whose customer or paid invoices were updated since the watermark
+//! of the previous export, the users are written in parts of `batch_size` records
+use std::time::{Duration as StdDuration, Instant};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::{Error as FailureError, Fail};
+use futures::future::{self, Either, Loop};
+use futures::{Future, IntoFuture, Stream};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use sentry::integrations::failure::capture_error;
+use serde_json;
+use tokio_timer::Interval;
+
+use stq_types::UserId;
+
+use client::storage::StorageClient;
+use config::CustomerExport as CustomerExportConfig;
+use models::{CustomerExport, CustomerExportRecord, NewCustomerExport};
+use repos::ReposFactory;
+use services::types::{spawn_on_pool, ServiceFutureV2};
+use services::{ErrorContext, ErrorKind};
+
+/// Part of an export that is being written
+#[derive(Debug, Clone)]
+struct ExportProgress {
+    after_user_id: UserId,
+    part: u32,
+    records_count: i32,
+}
+
+/// Exports the customers on every tick of `export_interval_sec`.
+/// A failed export is reported and its changes are exported again on the next tick, as the watermark stays put
+pub fn run_customer_export<T, M, F, S>(
+    config: CustomerExportConfig,
+    db_pool: Pool<M>,
+    cpu_pool: CpuPool,
+    repo_factory: F,
+    storage_client: S,
+) -> impl Future<Item = (), Error = FailureError>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+    S: StorageClient + Clone,
+{
+    let interval = StdDuration::from_secs(config.export_interval_sec);
+
+    Interval::new(Instant::now(), interval)
+        .map_err(FailureError::from)
+        .for_each(move |_| {
+            export_customers(
+                config.clone(),
+                db_pool.clone(),
+                cpu_pool.clone(),
+                repo_factory.clone(),
+                storage_client.clone(),
+            )
+            .then(|res| {
+                match res {
+                    Ok(export) => {
+                        info!(
+                            "Exported {} customers updated in ({:?}, {}] to {}",
+                            export.records_count, export.updated_after, export.updated_until, export.object_key
+                        );
+                    }
+                    Err(err) => {
+                        let err = FailureError::from(err.context("An error occurred while exporting customers"));
+                        error!("{:?}", &err);
+                        capture_error(&err);
+                    }
+                };
+
+                future::ok::<_, FailureError>(())
+            })
+        })
+}
+
+/// Writes the parts of the export and then records it, moving the watermark
+fn export_customers<T, M, F, S>(
+    config: CustomerExportConfig,
+    db_pool: Pool<M>,
+    cpu_pool: CpuPool,
+    repo_factory: F,
+    storage_client: S,
+) -> ServiceFutureV2<CustomerExport>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+    S: StorageClient + Clone,
+{
+    // Changes committed while the export runs are picked by the next one
+    let updated_until = Utc::now().naive_utc();
+    let object_key = export_key(&config.key_prefix, updated_until);
+    let incremental = config.incremental;
+    let batch_size = config.batch_size;
+
+    let fut = spawn_on_pool(db_pool.clone(), cpu_pool.clone(), {
+        let repo_factory = repo_factory.clone();
+        move |conn| {
+            let customer_exports_repo = repo_factory.create_customer_exports_repo_with_sys_acl(&*conn);
+
+            if !incremental {
+                return Ok(None);
+            }
+
+            customer_exports_repo
+                .get_last()
+                .map(|last_export| last_export.map(|last_export| last_export.updated_until))
+                .map_err(ectx!(convert))
+        }
+    })
+    .and_then({
+        let db_pool = db_pool.clone();
+        let cpu_pool = cpu_pool.clone();
+        let repo_factory = repo_factory.clone();
+        let object_key = object_key.clone();
+        move |updated_after| {
+            let start = ExportProgress {
+                after_user_id: UserId(0),
+                part: 0,
+                records_count: 0,
+            };
+
+            future::loop_fn(start, move |progress| {
+                let repo_factory = repo_factory.clone();
+                let storage_client = storage_client.clone();
+                let object_key = object_key.clone();
+                let after_user_id = progress.after_user_id;
+
+                spawn_on_pool(db_pool.clone(), cpu_pool.clone(), move |conn| {
+                    let customer_exports_repo = repo_factory.create_customer_exports_repo_with_sys_acl(&*conn);
+
+                    let rows = customer_exports_repo
+                        .find_customer_spend(
+                            updated_after.unwrap_or_else(watermark_origin),
+                            updated_until,
+                            after_user_id,
+                            batch_size,
+                        )
+                        .map_err(ectx!(try convert => updated_after, updated_until, after_user_id))?;
+
+                    Ok(CustomerExportRecord::from_rows(rows))
+                })
+                .and_then(move |records| {
+                    let last_user_id = match records.last() {
+                        Some(record) => record.user_id,
+                        None => return Either::A(future::ok(Loop::Break((updated_after, progress)))),
+                    };
+
+                    let is_last_part = (records.len() as i64) < batch_size;
+                    let next = ExportProgress {
+                        after_user_id: last_user_id,
+                        part: progress.part + 1,
+                        records_count: progress.records_count + records.len() as i32,
+                    };
+                    let key = part_key(&object_key, progress.part);
+                    let records_key = key.clone();
+
+                    let fut = serde_json::to_string(&records)
+                        .map_err(ectx!(ErrorContext::CustomerExport, ErrorKind::Internal => records_key))
+                        .into_future()
+                        .and_then(move |body| {
+                            storage_client
+                                .put_object(key.clone(), body)
+                                .map_err(ectx!(ErrorContext::CustomerExport, ErrorKind::Internal => key))
+                        })
+                        .map(move |_| {
+                            if is_last_part {
+                                Loop::Break((updated_after, next))
+                            } else {
+                                Loop::Continue(next)
+                            }
+                        });
+
+                    Either::B(fut)
+                })
+            })
+        }
+    })
+    .and_then(move |(updated_after, progress)| {
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let customer_exports_repo = repo_factory.create_customer_exports_repo_with_sys_acl(&*conn);
+
+            let payload = NewCustomerExport {
+                incremental,
+                updated_after,
+                updated_until,
+                object_key,
+                records_count: progress.records_count,
+            };
+            customer_exports_repo.create(payload.clone()).map_err(ectx!(convert => payload))
+        })
+    });
+
+    Box::new(fut)
+}
+
+/// Lower bound of the changes picked by a full export
+fn watermark_origin() -> NaiveDateTime {
+    NaiveDateTime::from_timestamp(0, 0)
+}
+
+fn export_key(key_prefix: &str, updated_until: NaiveDateTime) -> String {
+    format!("{}/{}", key_prefix.trim_end_matches('/'), updated_until.format("%Y%m%dT%H%M%S"))
+}
+
+fn part_key(object_key: &str, part: u32) -> String {
+    format!("{}/part-{:05}.json", object_key, part)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use chrono::NaiveDate;
+
+    use models::{Amount, Currency, CurrencySpend, CustomerId, CustomerSpendRow};
+
+    fn row(user_id: i32, currency: Option<Currency>, amount: u128, paid_day: Option<u32>) -> CustomerSpendRow {
+        CustomerSpendRow {
+            user_id: UserId(user_id),
+            customer_id: Some(CustomerId::new(format!("cus_{}", user_id))),
+            cards_count: 1,
+            currency,
+            amount: currency.map(|_| Amount::new(amount)),
+            last_paid_at: paid_day.map(|day| NaiveDate::from_ymd(2019, 4, day).and_hms(0, 0, 0)),
+        }
+    }
+
+    #[test]
+    fn spend_rows_are_grouped_by_user() {
+        let rows = vec![
+            row(1, Some(Currency::Eur), 1050, Some(3)),
+            row(1, Some(Currency::Usd), 200, Some(5)),
+            row(2, None, 0, None),
+        ];
+
+        let records = CustomerExportRecord::from_rows(rows);
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].lifetime_spend,
+            vec![
+                CurrencySpend {
+                    currency: Currency::Eur,
+                    amount: "10.5".parse::<BigDecimal>().unwrap(),
+                },
+                CurrencySpend {
+                    currency: Currency::Usd,
+                    amount: BigDecimal::from(2),
+                },
+            ]
+        );
+        assert_eq!(records[0].last_payment_at, Some(NaiveDate::from_ymd(2019, 4, 5).and_hms(0, 0, 0)));
+        assert_eq!(records[1].user_id, UserId(2));
+        assert!(records[1].lifetime_spend.is_empty());
+        assert_eq!(records[1].last_payment_at, None);
+    }
+
+    #[test]
+    fn parts_are_written_under_the_export_time() {
+        let updated_until = NaiveDate::from_ymd(2019, 4, 26).and_hms(3, 0, 0);
+        let object_key = export_key("crm/customers/", updated_until);
+
+        assert_eq!(object_key, "crm/customers/20190426T030000");
+        assert_eq!(part_key(&object_key, 2), "crm/customers/20190426T030000/part-00002.json");
+    }
+}
@@ -0,0 +1,369 @@
+//! DataRetentionService purges personal data of closed stores and deleted users once its retention period is over.
+//! Stores and users are closed by saga through the merchant endpoints, data of subjects under legal hold is kept
+use std::time::{Duration as StdDuration, Instant};
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::{Error as FailureError, Fail};
+use futures::{future, Future, Stream};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use sentry::integrations::failure::capture_error;
+use serde_json;
+use tokio_timer::Interval;
+use validator::{ValidationError, ValidationErrors};
+
+use stq_types::UserId;
+
+use super::types::ServiceFutureV2;
+use config::{DataRetention as DataRetentionConfig, RetentionRule};
+use controller::requests::LegalHoldRequest;
+use controller::responses::{DataRetentionPurgeResponse, DataRetentionSubjectResponse, RetentionTableReport};
+use models::DataSubject;
+use repos::{DataRetentionRepo, ReposFactory};
+use services::types::spawn_on_pool;
+use services::{Error, ErrorContext, ErrorKind};
+
+pub trait DataRetentionService {
+    /// Purges the data of every table with a retention rule, a dry run only reports what would be purged
+    fn purge(&self, dry_run: bool) -> ServiceFutureV2<DataRetentionPurgeResponse>;
+    /// Puts a store or a user under legal hold or releases it
+    fn set_legal_hold(&self, subject: DataSubject, payload: LegalHoldRequest) -> ServiceFutureV2<DataRetentionSubjectResponse>;
+    /// Starts the retention period of the data of a closed store or a deleted user
+    fn mark_closed(&self, subject: DataSubject) -> ServiceFutureV2<DataRetentionSubjectResponse>;
+}
+
+pub struct DataRetentionServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+    pub config: DataRetentionConfig,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > DataRetentionService for DataRetentionServiceImpl<T, M, F>
+{
+    fn purge(&self, dry_run: bool) -> ServiceFutureV2<DataRetentionPurgeResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let config = self.config.clone();
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let data_retention_repo = repo_factory.create_data_retention_repo(&*conn, user_id);
+
+            purge_data(&*conn, &*data_retention_repo, &config, Utc::now().naive_utc(), dry_run)
+        })
+    }
+
+    fn set_legal_hold(&self, subject: DataSubject, payload: LegalHoldRequest) -> ServiceFutureV2<DataRetentionSubjectResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        let reason = match validate_legal_hold(payload) {
+            Ok(reason) => reason,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let data_retention_repo = repo_factory.create_data_retention_repo(&*conn, user_id);
+
+            data_retention_repo
+                .set_legal_hold(subject, reason.is_some(), reason)
+                .map_err(ectx!(convert => subject))
+                .map(DataRetentionSubjectResponse::from)
+        })
+    }
+
+    fn mark_closed(&self, subject: DataSubject) -> ServiceFutureV2<DataRetentionSubjectResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let data_retention_repo = repo_factory.create_data_retention_repo(&*conn, user_id);
+
+            data_retention_repo
+                .mark_closed(subject, Utc::now().naive_utc())
+                .map_err(ectx!(convert => subject))
+                .map(DataRetentionSubjectResponse::from)
+        })
+    }
+}
+
+/// Purges data past its retention period on every tick of `purge_interval_sec`.
+/// Only reports the data to purge while `dry_run` is on. A failed purge is reported and resumed on the next tick
+pub fn run_data_retention_purge<T, M, F>(
+    config: DataRetentionConfig,
+    db_pool: Pool<M>,
+    cpu_pool: CpuPool,
+    repo_factory: F,
+) -> impl Future<Item = (), Error = FailureError>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    let interval = StdDuration::from_secs(config.purge_interval_sec);
+
+    Interval::new(Instant::now(), interval)
+        .map_err(FailureError::from)
+        .for_each(move |_| {
+            let repo_factory = repo_factory.clone();
+            let config = config.clone();
+
+            spawn_on_pool(db_pool.clone(), cpu_pool.clone(), move |conn| {
+                let data_retention_repo = repo_factory.create_data_retention_repo_with_sys_acl(&*conn);
+
+                purge_data(&*conn, &*data_retention_repo, &config, Utc::now().naive_utc(), config.dry_run)
+            })
+            .then(|res| {
+                match res {
+                    Ok(report) => {
+                        let verb = if report.dry_run { "Would purge" } else { "Purged" };
+                        for table in report.tables.iter().filter(|table| table.rows > 0) {
+                            info!(
+                                "{} {} rows of {} of {} subjects past {} days of retention",
+                                verb, table.rows, table.table, table.subjects, table.retention_days
+                            );
+                        }
+                    }
+                    Err(err) => {
+                        let err = FailureError::from(err.context("An error occurred while purging data past retention"));
+                        error!("{:?}", &err);
+                        capture_error(&err);
+                    }
+                };
+
+                future::ok::<_, FailureError>(())
+            })
+        })
+}
+
+/// Applies every retention rule of the config as of `now`, `batch_size` subjects per transaction
+fn purge_data<T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static>(
+    conn: &T,
+    data_retention_repo: &DataRetentionRepo,
+    config: &DataRetentionConfig,
+    now: NaiveDateTime,
+    dry_run: bool,
+) -> Result<DataRetentionPurgeResponse, Error> {
+    let tables = config
+        .rules
+        .iter()
+        .map(|rule| purge_table(conn, data_retention_repo, rule, now, config.batch_size, dry_run))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(DataRetentionPurgeResponse { dry_run, tables })
+}
+
+fn purge_table<T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static>(
+    conn: &T,
+    data_retention_repo: &DataRetentionRepo,
+    rule: &RetentionRule,
+    now: NaiveDateTime,
+    batch_size: i64,
+    dry_run: bool,
+) -> Result<RetentionTableReport, Error> {
+    let table = rule.table;
+    let closed_before = now - Duration::days(rule.retention_days as i64);
+    let mut report = RetentionTableReport {
+        table,
+        retention_days: rule.retention_days,
+        subjects: 0,
+        rows: 0,
+    };
+
+    // purged subjects have no data left and are not found again, a dry run pages past the ones it has counted
+    let mut after_subject_id = 0;
+    loop {
+        let (subject_ids, rows) = conn.transaction::<_, Error, _>(|| {
+            let subject_ids = data_retention_repo
+                .find_purgeable(table, closed_before, after_subject_id, batch_size)
+                .map_err(ectx!(try convert => table, closed_before, after_subject_id, batch_size))?;
+            if subject_ids.is_empty() {
+                return Ok((subject_ids, 0));
+            }
+
+            let rows = if dry_run {
+                data_retention_repo
+                    .count_rows(table, subject_ids.clone())
+                    .map_err(ectx!(try convert => table))? as u64
+            } else {
+                data_retention_repo
+                    .purge(table, subject_ids.clone())
+                    .map_err(ectx!(try convert => table))? as u64
+            };
+
+            Ok((subject_ids, rows))
+        })?;
+
+        report.subjects += subject_ids.len();
+        report.rows += rows;
+
+        match subject_ids.last() {
+            Some(&last) if (subject_ids.len() as i64) >= batch_size => after_subject_id = last,
+            _ => return Ok(report),
+        }
+    }
+}
+
+/// Reason of the hold, none when the subject is released
+fn validate_legal_hold(payload: LegalHoldRequest) -> Result<Option<String>, Error> {
+    let reason = payload
+        .reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+
+    match (payload.legal_hold, reason) {
+        (true, None) => {
+            let mut errors = ValidationErrors::new();
+            let mut error = ValidationError::new("empty");
+            error.message = Some("Legal hold must have a reason".into());
+            errors.add("reason", error);
+            Err(ectx!(err ErrorContext::DataRetention, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default())))
+        }
+        (true, reason) => Ok(reason),
+        (false, _) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use futures_cpupool::CpuPool;
+    use tokio_core::reactor::Core;
+
+    use stq_types::{StoreId, UserId};
+
+    use super::*;
+    use config::RetentionRule;
+    use models::{DataRetentionSubject, DataSubjectType, RetentionTable};
+    use test_support::{InMemoryReposFactory, MockConnection, MockConnectionManager};
+
+    fn closed_subject(subject_type: DataSubjectType, subject_id: i32, closed_days_ago: i64, legal_hold: bool) -> DataRetentionSubject {
+        let now = Utc::now().naive_utc();
+        DataRetentionSubject {
+            id: subject_id,
+            subject_type,
+            subject_id,
+            closed_at: Some(now - Duration::days(closed_days_ago)),
+            legal_hold,
+            legal_hold_reason: if legal_hold { Some("litigation".to_string()) } else { None },
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn service(
+        repo_factory: InMemoryReposFactory,
+    ) -> DataRetentionServiceImpl<MockConnection, MockConnectionManager, InMemoryReposFactory> {
+        DataRetentionServiceImpl {
+            db_pool: ::r2d2::Pool::builder().build(MockConnectionManager::default()).unwrap(),
+            cpu_pool: CpuPool::new(1),
+            repo_factory,
+            user_id: None,
+            config: DataRetentionConfig {
+                batch_size: 1,
+                rules: vec![
+                    RetentionRule::new(RetentionTable::RussiaBillingInfo, 30),
+                    RetentionRule::new(RetentionTable::CustomerEmails, 30),
+                ],
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn purges_data_past_retention_except_under_legal_hold() {
+        let mut core = Core::new().unwrap();
+        let repo_factory = InMemoryReposFactory::new()
+            .with_data_retention_subjects(vec![
+                closed_subject(DataSubjectType::Store, 1, 60, false),
+                closed_subject(DataSubjectType::Store, 2, 60, true),
+                closed_subject(DataSubjectType::Store, 3, 10, false),
+                closed_subject(DataSubjectType::User, 4, 60, false),
+            ])
+            .with_retained_data(vec![
+                (RetentionTable::RussiaBillingInfo, 1),
+                (RetentionTable::RussiaBillingInfo, 2),
+                (RetentionTable::RussiaBillingInfo, 3),
+                (RetentionTable::InternationalBillingInfo, 1),
+                (RetentionTable::CustomerEmails, 4),
+                (RetentionTable::CustomerEmails, 4),
+            ]);
+
+        let report = core.run(service(repo_factory.clone()).purge(false)).unwrap();
+
+        assert!(!report.dry_run);
+        assert_eq!(report.tables.len(), 2);
+        assert_eq!((report.tables[0].subjects, report.tables[0].rows), (1, 1));
+        assert_eq!((report.tables[1].subjects, report.tables[1].rows), (1, 2));
+        assert_eq!(
+            repo_factory.state().retained_data,
+            vec![
+                (RetentionTable::RussiaBillingInfo, 2),
+                (RetentionTable::RussiaBillingInfo, 3),
+                (RetentionTable::InternationalBillingInfo, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn dry_run_reports_without_purging() {
+        let mut core = Core::new().unwrap();
+        let retained_data = vec![(RetentionTable::RussiaBillingInfo, 1), (RetentionTable::RussiaBillingInfo, 2)];
+        let repo_factory = InMemoryReposFactory::new()
+            .with_data_retention_subjects(vec![
+                closed_subject(DataSubjectType::Store, 1, 60, false),
+                closed_subject(DataSubjectType::Store, 2, 60, false),
+            ])
+            .with_retained_data(retained_data.clone());
+
+        let report = core.run(service(repo_factory.clone()).purge(true)).unwrap();
+
+        assert!(report.dry_run);
+        assert_eq!((report.tables[0].subjects, report.tables[0].rows), (2, 2));
+        assert_eq!(repo_factory.state().retained_data, retained_data);
+    }
+
+    #[test]
+    fn legal_hold_requires_reason_and_closing_starts_retention() {
+        let mut core = Core::new().unwrap();
+        let repo_factory = InMemoryReposFactory::new();
+        let service = service(repo_factory.clone());
+        let subject = DataSubject::Store(StoreId(1));
+
+        let missing_reason = LegalHoldRequest {
+            legal_hold: true,
+            reason: Some(" ".to_string()),
+        };
+        assert!(core.run(service.set_legal_hold(subject, missing_reason)).is_err());
+
+        let hold = LegalHoldRequest {
+            legal_hold: true,
+            reason: Some("litigation".to_string()),
+        };
+        let held = core.run(service.set_legal_hold(subject, hold)).unwrap();
+        assert!(held.legal_hold);
+        assert_eq!(held.legal_hold_reason, Some("litigation".to_string()));
+
+        let closed = core.run(service.mark_closed(DataSubject::User(UserId(1)))).unwrap();
+        assert!(closed.closed_at.is_some());
+        assert_eq!(repo_factory.state().data_retention_subjects.len(), 2);
+    }
+}
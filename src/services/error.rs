@@ -5,6 +5,7 @@ use std::fmt;
 use stripe::WebhookError;
 use validator::ValidationErrors;
 
+use client::fiat_payments::ErrorKind as FiatPaymentsErrorKind;
 use client::payments::ErrorKind as PaymentsClientErrorKind;
 use client::stores::ErrorKind as StoresErrorKind;
 use client::stripe::ErrorKind as StripeClientErrorKind;
@@ -25,6 +26,10 @@ pub enum ErrorKind {
     NotFound,
     #[fail(display = "service error - validation")]
     Validation(serde_json::Value),
+    #[fail(display = "service error - payments gateway unavailable")]
+    PaymentsGatewayUnavailable,
+    #[fail(display = "service error - exchange rates unavailable")]
+    ExchangeRatesUnavailable,
 }
 
 #[allow(dead_code)]
@@ -34,6 +39,8 @@ pub enum ErrorContext {
     AmountConversion,
     #[fail(display = "service context - error currency conversion")]
     CurrencyConversion,
+    #[fail(display = "service context - cashback out of limits")]
+    Cashback,
     #[fail(display = "service context - error unauthorized")]
     Unauthorized,
     #[fail(display = "service context - wrong order state")]
@@ -52,6 +59,46 @@ pub enum ErrorContext {
     VerifySign,
     #[fail(display = "service error context - stripe error")]
     StripeClient,
+    #[fail(display = "service context - fee statement error")]
+    FeeStatement,
+    #[fail(display = "service context - wrong account state")]
+    AccountState,
+    #[fail(display = "service context - store webhook error")]
+    StoreWebhook,
+    #[fail(display = "service context - api key error")]
+    ApiKey,
+    #[fail(display = "service context - customer error")]
+    Customer,
+    #[fail(display = "service context - invoice callback error")]
+    InvoiceCallback,
+    #[fail(display = "service context - invoice details error")]
+    InvoiceDetails,
+    #[fail(display = "service context - payout instruction error")]
+    PayoutInstruction,
+    #[fail(display = "service context - payment recovery error")]
+    PaymentRecovery,
+    #[fail(display = "service context - invoice requote error")]
+    InvoiceRequote,
+    #[fail(display = "service context - invoice cancel error")]
+    InvoiceCancel,
+    #[fail(display = "service context - feature flag error")]
+    FeatureFlag,
+    #[fail(display = "service context - system accounts transfer error")]
+    SystemAccountsTransfer,
+    #[fail(display = "service context - data retention error")]
+    DataRetention,
+    #[fail(display = "service context - invoice snapshot error")]
+    InvoiceSnapshot,
+    #[fail(display = "service context - payments sandbox error")]
+    PaymentsSandbox,
+    #[fail(display = "service context - customer export error")]
+    CustomerExport,
+    #[fail(display = "service context - invoice risk error")]
+    InvoiceRisk,
+    #[fail(display = "service context - historical report error")]
+    HistoricalReport,
+    #[fail(display = "service context - user credit error")]
+    UserCredit,
 }
 
 derive_error_impls!();
@@ -74,6 +121,7 @@ impl From<PaymentsClientErrorKind> for ErrorKind {
             PaymentsClientErrorKind::MalformedInput => ErrorKind::Internal,
             PaymentsClientErrorKind::Unauthorized => ErrorKind::Internal,
             PaymentsClientErrorKind::Validation(value) => ErrorKind::Validation(value),
+            PaymentsClientErrorKind::Unavailable => ErrorKind::PaymentsGatewayUnavailable,
         }
     }
 }
@@ -117,6 +165,16 @@ impl From<StripeClientErrorKind> for ErrorKind {
     }
 }
 
+impl From<FiatPaymentsErrorKind> for ErrorKind {
+    fn from(e: FiatPaymentsErrorKind) -> Self {
+        match e {
+            FiatPaymentsErrorKind::Internal => ErrorKind::Internal,
+            FiatPaymentsErrorKind::Unauthorized => ErrorKind::Internal,
+            FiatPaymentsErrorKind::Validation(value) => ErrorKind::Validation(value),
+        }
+    }
+}
+
 impl From<StoresErrorKind> for ErrorKind {
     fn from(e: StoresErrorKind) -> Self {
         match e {
@@ -124,6 +182,9 @@ impl From<StoresErrorKind> for ErrorKind {
             StoresErrorKind::MalformedInput => ErrorKind::Internal,
             StoresErrorKind::Unauthorized => ErrorKind::Internal,
             StoresErrorKind::Validation(value) => ErrorKind::Validation(value),
+            StoresErrorKind::Stale => ErrorKind::ExchangeRatesUnavailable,
+            StoresErrorKind::UnknownCurrencies(_) => ErrorKind::ExchangeRatesUnavailable,
+            StoresErrorKind::InvalidCurrencyExchange => ErrorKind::ExchangeRatesUnavailable,
         }
     }
 }
@@ -0,0 +1,160 @@
+//! Cleanup of the exchange rates superseded by newer rates of their orders. Every refresh of the price of an order
+//! adds a rate, so once the invoice is paid or expired only the rate used at payment is of any use
+use std::time::{Duration as StdDuration, Instant};
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::{Error as FailureError, Fail};
+use futures::{future, Future, Stream};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use sentry::integrations::failure::capture_error;
+use tokio_timer::Interval;
+
+use config::ExchangeRateRetention as ExchangeRateRetentionConfig;
+use models::order_exchange_rate::{OrderExchangeRateId, RawOrderExchangeRate};
+use repos::{OrderExchangeRatesRepo, ReposFactory};
+use services::types::spawn_on_pool;
+use services::Error;
+
+/// Rates of a cleanup
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ExchangeRateCleanup {
+    pub rates_deleted: usize,
+    /// Rates used at payment that are past retention but kept
+    pub rates_retained: usize,
+}
+
+/// Deletes the superseded rates on every tick of `cleanup_interval_sec`.
+/// A failed cleanup is reported and the rest of the rates are deleted on the next tick
+pub fn run_exchange_rate_cleanup<T, M, F>(
+    config: ExchangeRateRetentionConfig,
+    db_pool: Pool<M>,
+    cpu_pool: CpuPool,
+    repo_factory: F,
+) -> impl Future<Item = (), Error = FailureError>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    let interval = StdDuration::from_secs(config.cleanup_interval_sec);
+
+    Interval::new(Instant::now(), interval)
+        .map_err(FailureError::from)
+        .for_each(move |_| {
+            let repo_factory = repo_factory.clone();
+            let batch_size = config.batch_size;
+            let expired_before = Utc::now().naive_utc() - Duration::days(config.retention_days as i64);
+
+            spawn_on_pool(db_pool.clone(), cpu_pool.clone(), move |conn| {
+                let rates_repo = repo_factory.create_order_exchange_rates_repo_with_sys_acl(&*conn);
+
+                delete_superseded_rates(&*rates_repo, expired_before, batch_size)
+            })
+            .then(|res| {
+                match res {
+                    Ok(ref cleanup) if cleanup.rates_deleted == 0 => {}
+                    Ok(cleanup) => {
+                        info!(
+                            "Deleted {} superseded exchange rates, kept {} rates used at payment",
+                            cleanup.rates_deleted, cleanup.rates_retained
+                        );
+                    }
+                    Err(err) => {
+                        let err = FailureError::from(err.context("An error occurred while deleting superseded exchange rates"));
+                        error!("{:?}", &err);
+                        capture_error(&err);
+                    }
+                };
+
+                future::ok::<_, FailureError>(())
+            })
+        })
+}
+
+/// Deletes the rates expired before `expired_before` of the orders of paid or expired invoices, `batch_size` rates
+/// at a time. The rate that was active when the invoice was paid is kept
+fn delete_superseded_rates(
+    rates_repo: &OrderExchangeRatesRepo,
+    expired_before: NaiveDateTime,
+    batch_size: i64,
+) -> Result<ExchangeRateCleanup, Error> {
+    let mut cleanup = ExchangeRateCleanup::default();
+    let mut after_id = OrderExchangeRateId::new(0);
+
+    loop {
+        let rates = rates_repo
+            .find_superseded(expired_before, after_id, batch_size)
+            .map_err(ectx!(try convert => expired_before, after_id))?;
+        let last_id = match rates.last() {
+            Some((rate, _)) => rate.id,
+            None => return Ok(cleanup),
+        };
+
+        let rate_ids = superseded_rate_ids(&rates);
+        cleanup.rates_retained += rates.len() - rate_ids.len();
+        if !rate_ids.is_empty() {
+            cleanup.rates_deleted += rates_repo.delete_many(rate_ids).map_err(ectx!(try convert => last_id))?;
+        }
+
+        if (rates.len() as i64) < batch_size {
+            return Ok(cleanup);
+        }
+        after_id = last_id;
+    }
+}
+
+/// Ids of the rates that were not used at payment, given the time the invoice of every rate was paid at
+fn superseded_rate_ids(rates: &[(RawOrderExchangeRate, Option<NaiveDateTime>)]) -> Vec<OrderExchangeRateId> {
+    rates
+        .iter()
+        .filter(|(rate, paid_at)| !paid_at.map(|paid_at| rate.was_active_at(paid_at)).unwrap_or(false))
+        .map(|(rate, _)| rate.id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use chrono::NaiveDate;
+
+    use models::order_exchange_rate::ExchangeRateStatus;
+    use models::order_v2::OrderId;
+
+    fn expired_rate(id: i64, created_hour: u32, expired_hour: u32) -> RawOrderExchangeRate {
+        RawOrderExchangeRate {
+            id: OrderExchangeRateId::new(id),
+            order_id: OrderId::generate(),
+            exchange_id: None,
+            exchange_rate: BigDecimal::from(1),
+            status: ExchangeRateStatus::Expired,
+            created_at: hour(created_hour),
+            updated_at: hour(expired_hour),
+        }
+    }
+
+    fn hour(hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2019, 4, 1).and_hms(hour, 0, 0)
+    }
+
+    #[test]
+    fn rate_used_at_payment_is_kept() {
+        let rates = vec![
+            (expired_rate(1, 10, 11), Some(hour(12))),
+            (expired_rate(2, 11, 13), Some(hour(12))),
+            (expired_rate(3, 13, 14), Some(hour(12))),
+            (expired_rate(4, 10, 11), None),
+        ];
+
+        let rate_ids = superseded_rate_ids(&rates)
+            .iter()
+            .map(|rate_id| rate_id.inner())
+            .collect::<Vec<_>>();
+
+        assert_eq!(rate_ids, vec![1, 3, 4]);
+    }
+}
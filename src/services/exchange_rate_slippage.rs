@@ -0,0 +1,221 @@
+//! Tracks how much the prices of crypto invoices change between their creation and payment because of
+//! exchange rate refreshes. The slippage of every order is recorded when its invoice gets paid
+//! and summed up by currency pair, the sums are served as a report and as Prometheus gauges
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::Fail;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+
+use stq_types::UserId;
+
+use super::types::{ServiceFutureV2, ServiceResultV2};
+use controller::responses::ExchangeRateSlippageResponse;
+use models::invoice_v2::InvoiceDump;
+use models::{ExchangeRateSlippageMetric, NewOrderRateSlippage};
+use repos::{ExchangeRateSlippagesRepo, ReposFactory};
+use services::types::spawn_on_pool;
+
+pub trait ExchangeRateSlippageService {
+    /// Slippage of the paid orders by currency pair
+    fn get_exchange_rate_slippages(&self) -> ServiceFutureV2<Vec<ExchangeRateSlippageResponse>>;
+    /// Slippage of the paid orders by currency pair in the Prometheus text format
+    fn get_exchange_rate_slippage_gauges(&self) -> ServiceFutureV2<String>;
+}
+
+pub struct ExchangeRateSlippageServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > ExchangeRateSlippageService for ExchangeRateSlippageServiceImpl<T, M, F>
+{
+    fn get_exchange_rate_slippages(&self) -> ServiceFutureV2<Vec<ExchangeRateSlippageResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let exchange_rate_slippages_repo = repo_factory.create_exchange_rate_slippages_repo(&conn, user_id);
+
+            let metrics = exchange_rate_slippages_repo.get_metrics().map_err(ectx!(try convert))?;
+
+            Ok(metrics.into_iter().map(ExchangeRateSlippageResponse::from).collect())
+        })
+    }
+
+    fn get_exchange_rate_slippage_gauges(&self) -> ServiceFutureV2<String> {
+        let repo_factory = self.repo_factory.clone();
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        // Scraped without a user like the other diagnostics of the instance
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let exchange_rate_slippages_repo = repo_factory.create_exchange_rate_slippages_repo_with_sys_acl(&conn);
+
+            let metrics = exchange_rate_slippages_repo.get_metrics().map_err(ectx!(try convert))?;
+
+            Ok(exchange_rate_slippage_gauges(&metrics))
+        })
+    }
+}
+
+/// Records the slippage of the orders of an invoice that has just been paid.
+/// Orders priced in the buyer currency are skipped, they have no rate to refresh
+pub fn record_exchange_rate_slippages(
+    exchange_rate_slippages_repo: &ExchangeRateSlippagesRepo,
+    invoice_dump: &InvoiceDump,
+) -> ServiceResultV2<()> {
+    for order in &invoice_dump.orders {
+        if let Some(slippage) = NewOrderRateSlippage::from_order_dump(invoice_dump.id.clone(), order) {
+            exchange_rate_slippages_repo
+                .record(slippage.clone())
+                .map_err(ectx!(try convert => slippage))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Renders the metrics as Prometheus gauges labelled with the currency pair
+pub fn exchange_rate_slippage_gauges(metrics: &[ExchangeRateSlippageMetric]) -> String {
+    let gauges: &[(&str, &str, fn(&ExchangeRateSlippageMetric) -> String)] = &[
+        (
+            "billing_exchange_rate_slippage_orders",
+            "Paid orders priced in another currency than the buyer one",
+            |metric| metric.orders_count.to_string(),
+        ),
+        (
+            "billing_exchange_rate_slippage_refreshed_orders",
+            "Paid orders whose exchange rate was refreshed at least once",
+            |metric| metric.refreshed_orders_count.to_string(),
+        ),
+        (
+            "billing_exchange_rate_slippage_initial_price",
+            "Prices of the paid orders at their first exchange rate in the buyer currency",
+            |metric| metric.initial_price_total.to_super_unit(metric.buyer_currency).to_string(),
+        ),
+        (
+            "billing_exchange_rate_slippage_final_price",
+            "Prices the orders were paid at in the buyer currency",
+            |metric| metric.final_price_total.to_super_unit(metric.buyer_currency).to_string(),
+        ),
+        (
+            "billing_exchange_rate_slippage_ratio",
+            "Change of the prices of the paid orders relative to their initial prices",
+            |metric| metric.slippage_ratio().to_string(),
+        ),
+    ];
+
+    let mut text = String::new();
+    for (name, help, value) in gauges {
+        text.push_str(&format!("# HELP {} {}\n# TYPE {} gauge\n", name, help, name));
+        for metric in metrics {
+            text.push_str(&format!(
+                "{}{{seller_currency=\"{}\",buyer_currency=\"{}\"}} {}\n",
+                name,
+                metric.seller_currency,
+                metric.buyer_currency,
+                value(metric)
+            ));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::BigDecimal;
+    use chrono::NaiveDate;
+    use std::str::FromStr;
+    use uuid::Uuid;
+
+    use models::invoice_v2::{BuyerAmounts, InvoiceId, OrderDump, RateDump};
+    use models::order_v2::OrderId;
+    use models::*;
+
+    use super::exchange_rate_slippage_gauges;
+
+    fn rate(id: i64, exchange_rate: &str, status: ExchangeRateStatus, hour: u32) -> RateDump {
+        RateDump {
+            id: OrderExchangeRateId::new(id),
+            exchange_rate: BigDecimal::from_str(exchange_rate).unwrap(),
+            status,
+            reserved_at: NaiveDate::from_ymd(2019, 4, 11).and_hms(hour, 0, 0),
+        }
+    }
+
+    #[test]
+    fn slippage_compares_the_first_and_the_paid_rate() {
+        let order = OrderDump {
+            id: OrderId::new(Uuid::new_v4()),
+            seller_currency: Currency::Eur,
+            seller_price: BigDecimal::from(100),
+            seller_cashback: BigDecimal::from(0),
+            buyer_amounts: Some(BuyerAmounts {
+                exchange_rate: BigDecimal::from_str("0.0008").unwrap(),
+                currency: Currency::Stq,
+                price: BigDecimal::from(125000),
+            }),
+            rates: vec![
+                rate(3, "0.0008", ExchangeRateStatus::Active, 12),
+                rate(1, "0.001", ExchangeRateStatus::Expired, 10),
+                rate(2, "0.0009", ExchangeRateStatus::Expired, 11),
+            ],
+        };
+
+        let slippage = NewOrderRateSlippage::from_order_dump(InvoiceId::new(Uuid::new_v4()), &order).unwrap();
+
+        assert_eq!(slippage.initial_rate, BigDecimal::from_str("0.001").unwrap());
+        assert_eq!(slippage.final_rate, BigDecimal::from_str("0.0008").unwrap());
+        assert_eq!(slippage.rate_refreshes, 2);
+        assert_eq!(
+            slippage.initial_price,
+            Amount::from_super_unit(Currency::Stq, BigDecimal::from(100000))
+        );
+        assert_eq!(
+            slippage.final_price,
+            Amount::from_super_unit(Currency::Stq, BigDecimal::from(125000))
+        );
+
+        let same_currency = OrderDump {
+            seller_currency: Currency::Stq,
+            ..order
+        };
+        assert!(NewOrderRateSlippage::from_order_dump(InvoiceId::new(Uuid::new_v4()), &same_currency).is_none());
+    }
+
+    #[test]
+    fn gauges_are_labelled_with_the_currency_pair() {
+        let updated_at = NaiveDate::from_ymd(2019, 4, 11).and_hms(12, 0, 0);
+        let metric = ExchangeRateSlippageMetric {
+            seller_currency: Currency::Eur,
+            buyer_currency: Currency::Btc,
+            orders_count: 4,
+            refreshed_orders_count: 1,
+            initial_price_total: Amount::new(200_000_000),
+            final_price_total: Amount::new(210_000_000),
+            created_at: updated_at,
+            updated_at,
+        };
+
+        let text = exchange_rate_slippage_gauges(&[metric]);
+
+        assert!(text.contains("# TYPE billing_exchange_rate_slippage_orders gauge\n"));
+        assert!(text.contains("billing_exchange_rate_slippage_orders{seller_currency=\"eur\",buyer_currency=\"btc\"} 4\n"));
+        assert!(text.contains("billing_exchange_rate_slippage_refreshed_orders{seller_currency=\"eur\",buyer_currency=\"btc\"} 1\n"));
+        assert!(text.contains("billing_exchange_rate_slippage_ratio{seller_currency=\"eur\",buyer_currency=\"btc\"} 0.050000\n"));
+    }
+}
@@ -0,0 +1,119 @@
+//! FeatureFlagService lets billing behavior be rolled out store by store. Services consult
+//! `is_feature_enabled` where the behavior branches, superusers change the rollouts at runtime
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::Fail;
+use futures::future;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use serde_json;
+use validator::{ValidationError, ValidationErrors};
+
+use stq_types::{StoreId, UserId};
+
+use super::types::{ServiceFutureV2, ServiceResultV2};
+use config::FeatureFlags as FeatureFlagsConfig;
+use controller::requests::UpdateFeatureFlagRequest;
+use controller::responses::FeatureFlagResponse;
+use models::{Feature, NewFeatureFlag};
+use repos::{FeatureFlagsRepo, ReposFactory};
+use services::types::spawn_on_pool;
+use services::{ErrorContext, ErrorKind};
+
+pub trait FeatureFlagService {
+    /// Rollouts of every feature, including the ones left at the default of the environment
+    fn list_feature_flags(&self) -> ServiceFutureV2<Vec<FeatureFlagResponse>>;
+    /// Replaces the rollout of the feature, other instances pick it up within `feature_flags.cache_ttl_sec`
+    fn update_feature_flag(&self, feature: Feature, payload: UpdateFeatureFlagRequest) -> ServiceFutureV2<FeatureFlagResponse>;
+}
+
+pub struct FeatureFlagServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+    pub config: FeatureFlagsConfig,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > FeatureFlagService for FeatureFlagServiceImpl<T, M, F>
+{
+    fn list_feature_flags(&self) -> ServiceFutureV2<Vec<FeatureFlagResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let config = self.config.clone();
+
+        spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let feature_flags_repo = repo_factory.create_feature_flags_repo(&conn, user_id);
+
+            let flags = feature_flags_repo.list().map_err(ectx!(try convert))?;
+
+            Ok(Feature::all()
+                .iter()
+                .map(|feature| match flags.iter().find(|flag| flag.name == feature.as_str()) {
+                    Some(flag) => FeatureFlagResponse::from_flag(*feature, flag.clone()),
+                    None => FeatureFlagResponse::default_for(*feature, config.default_enabled(*feature)),
+                })
+                .collect())
+        })
+    }
+
+    fn update_feature_flag(&self, feature: Feature, payload: UpdateFeatureFlagRequest) -> ServiceFutureV2<FeatureFlagResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+
+        if payload.rollout_percentage < 0 || payload.rollout_percentage > 100 {
+            let mut errors = ValidationErrors::new();
+            let mut error = ValidationError::new("range");
+            error.message = Some("Rollout percentage has to be between 0 and 100".into());
+            error.add_param("value".into(), &payload.rollout_percentage);
+            errors.add("rollout_percentage", error);
+
+            return Box::new(future::err(ectx!(err ErrorContext::FeatureFlag, ErrorKind::from(errors))));
+        }
+
+        spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let feature_flags_repo = repo_factory.create_feature_flags_repo(&conn, user_id);
+
+            let UpdateFeatureFlagRequest {
+                enabled,
+                rollout_percentage,
+                store_ids,
+            } = payload;
+            let new_feature_flag = NewFeatureFlag {
+                name: feature.to_string(),
+                enabled,
+                rollout_percentage,
+                store_ids: serde_json::to_value(store_ids).map_err(ectx!(try ErrorKind::Internal))?,
+            };
+
+            feature_flags_repo
+                .upsert(new_feature_flag.clone())
+                .map(|flag| FeatureFlagResponse::from_flag(feature, flag))
+                .map_err(ectx!(convert => new_feature_flag))
+        })
+    }
+}
+
+/// Whether the feature is on for the store. A feature without a stored flag follows the default of the environment
+pub fn is_feature_enabled(
+    feature_flags_repo: &FeatureFlagsRepo,
+    config: &FeatureFlagsConfig,
+    feature: Feature,
+    store_id: StoreId,
+) -> ServiceResultV2<bool> {
+    let flag = feature_flags_repo.get(feature).map_err(ectx!(try convert => feature))?;
+
+    Ok(match flag {
+        Some(flag) => flag.is_enabled_for(store_id),
+        None => config.default_enabled(feature),
+    })
+}
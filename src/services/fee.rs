@@ -2,10 +2,12 @@
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+use bigdecimal::BigDecimal;
+use chrono::Utc;
 use diesel::connection::AnsiTransactionManager;
 use diesel::pg::Pg;
 use diesel::Connection;
-use futures::IntoFuture;
+use futures::{future, IntoFuture};
 use futures_cpupool::CpuPool;
 use r2d2::{ManageConnection, Pool};
 use validator::{ValidationError, ValidationErrors};
@@ -16,18 +18,29 @@ use futures::Future;
 use stq_http::client::HttpClient;
 use stq_types::StoreId as StqStoreId;
 
+use client::fiat_payments::{FiatPaymentProvider, NewFiatCharge};
 use client::payments::PaymentsClient;
-use client::stripe::{NewCharge, StripeClient};
+use client::stores::{CurrencyExchangeInfo, StoresClient};
+use config::{
+    FeeCharge as FeeChargeConfig, FeeChargeRateSource, FeeCryptoPayments as FeeCryptoPaymentsConfig, FeeDunning as FeeDunningConfig,
+};
 use services::accounts::AccountService;
+use services::fee_dunning::{resolve_paid_fee_dunning, start_fee_dunning};
+use services::store_webhook::enqueue_fee_charged_webhooks;
 
 use models::{
     order_v2::{OrderId, OrdersSearch, StoreId},
-    Amount, ChargeId, Currency, Fee, FeeStatus, UpdateFee,
+    Amount, Currency, Event, EventPayload, Fee, FeeCryptoPayment, FeeCryptoPaymentId, FeeCryptoPaymentStatus, FeeId, FeeStatus,
+    NewFeeCryptoPayment, UpdateFee,
 };
 use repos::{ReposFactory, SearchCustomer, SearchFee, SearchFeeParams};
 
 use super::types::ServiceFutureV2;
-use controller::{context::DynamicContext, requests::FeesPayByOrdersRequest, responses::FeeResponse};
+use controller::{
+    context::DynamicContext,
+    requests::{CreateFeeCryptoPaymentRequest, FeesPayByOrdersRequest},
+    responses::{FeeCryptoPaymentResponse, FeeResponse},
+};
 use models::order_v2::OrderId as Orderv2Id;
 use services::{Error, ErrorContext, ErrorKind};
 
@@ -40,6 +53,10 @@ pub trait FeesService {
     fn create_charge(&self, search: SearchFee) -> ServiceFutureV2<FeeResponse>;
     /// Create Charge object in Stripe
     fn create_charge_for_several_fees(&self, params: FeesPayByOrdersRequest) -> ServiceFutureV2<Vec<FeeResponse>>;
+    /// Reserves a deposit address the store transfers the fee to from its wallet
+    fn create_crypto_payment(&self, fee_id: FeeId, payload: CreateFeeCryptoPaymentRequest) -> ServiceFutureV2<FeeCryptoPaymentResponse>;
+    /// Getting the latest crypto payment of the fee
+    fn get_crypto_payment(&self, fee_id: FeeId) -> ServiceFutureV2<Option<FeeCryptoPaymentResponse>>;
 }
 
 pub struct FeesServiceImpl<
@@ -53,8 +70,12 @@ pub struct FeesServiceImpl<
     pub db_pool: Pool<M>,
     pub cpu_pool: CpuPool,
     pub repo_factory: F,
-    pub stripe_client: Arc<dyn StripeClient>,
+    pub fiat_payment_provider: Arc<dyn FiatPaymentProvider>,
     pub dynamic_context: DynamicContext<C, PC, AS>,
+    pub config: FeeCryptoPaymentsConfig,
+    pub stores_client: Arc<dyn StoresClient>,
+    pub fee_charge: FeeChargeConfig,
+    pub fee_dunning: FeeDunningConfig,
 }
 
 impl<
@@ -137,6 +158,114 @@ impl<
         debug!("Create charge in stripe by params: {:?}", params);
         self.create_charge_by_order_ids(params.order_ids)
     }
+
+    fn create_crypto_payment(&self, fee_id: FeeId, payload: CreateFeeCryptoPaymentRequest) -> ServiceFutureV2<FeeCryptoPaymentResponse> {
+        debug!("Create crypto payment of fee {} by params: {:?}", fee_id, payload);
+
+        let account_service = match self.dynamic_context.account_service.clone() {
+            Some(account_service) => account_service,
+            None => {
+                let e = format_err!("Accounts service was not found in dynamic context");
+                return Box::new(future::err(ectx!(err e, ErrorKind::Internal)));
+            }
+        };
+
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+        let timeout = self.config.timeout();
+        let ture_currency = payload.currency;
+        let currency = Currency::from(ture_currency);
+
+        let fut = spawn_on_pool(db_pool.clone(), cpu_pool.clone(), {
+            let repo_factory = repo_factory.clone();
+            move |conn| {
+                let fees_repo = repo_factory.create_fees_repo(&conn, user_id);
+                let fee_crypto_payments_repo = repo_factory.create_fee_crypto_payments_repo(&conn, user_id);
+
+                let fee = fees_repo.get(SearchFee::Id(fee_id)).map_err(ectx!(try convert => fee_id))?.ok_or({
+                    let e = format_err!("Fee {} not found", fee_id);
+                    ectx!(try err e, ErrorKind::NotFound)
+                })?;
+                let latest_payment = fee_crypto_payments_repo
+                    .get_latest_by_fee_id(fee_id)
+                    .map_err(ectx!(try convert => fee_id))?;
+
+                validate_crypto_payment(&fee, latest_payment.as_ref())?;
+                crypto_payment_amount(&fee, currency)
+            }
+        })
+        .and_then(move |amount| {
+            account_service
+                .get_or_create_free_pooled_account(ture_currency)
+                .map_err(ectx!(convert => ture_currency))
+                .map(move |account| (amount, account))
+        })
+        .and_then(move |(amount, account)| {
+            spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                let fee_crypto_payments_repo = repo_factory.create_fee_crypto_payments_repo(&conn, user_id);
+                let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+                conn.transaction(|| {
+                    let expires_at = Utc::now().naive_utc() + timeout;
+                    let new_payment = NewFeeCryptoPayment {
+                        id: FeeCryptoPaymentId::generate(),
+                        fee_id,
+                        account_id: account.id,
+                        currency,
+                        amount,
+                        status: FeeCryptoPaymentStatus::Pending,
+                        expires_at,
+                    };
+                    let payment = fee_crypto_payments_repo
+                        .create(new_payment.clone())
+                        .map_err(ectx!(try convert => new_payment))?;
+
+                    let event = Event::new(EventPayload::FeeCryptoPaymentExpired {
+                        fee_crypto_payment_id: payment.id,
+                    });
+                    event_store_repo
+                        .add_scheduled_event(event.clone(), expires_at)
+                        .map_err(ectx!(try convert => event, expires_at))?;
+
+                    Ok(FeeCryptoPaymentResponse::new(payment, account.wallet_address.clone()))
+                })
+            })
+        });
+
+        Box::new(fut)
+    }
+
+    fn get_crypto_payment(&self, fee_id: FeeId) -> ServiceFutureV2<Option<FeeCryptoPaymentResponse>> {
+        debug!("Requesting crypto payment of fee {}", fee_id);
+
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let fee_crypto_payments_repo = repo_factory.create_fee_crypto_payments_repo(&conn, user_id);
+            let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
+
+            let payment = fee_crypto_payments_repo
+                .get_latest_by_fee_id(fee_id)
+                .map_err(ectx!(try convert => fee_id))?;
+
+            match payment {
+                None => Ok(None),
+                Some(payment) => {
+                    let account_id = payment.account_id;
+                    let account = accounts_repo.get(account_id).map_err(ectx!(try convert => account_id))?.ok_or({
+                        let e = format_err!("Account {} not found", account_id);
+                        ectx!(try err e, ErrorKind::Internal)
+                    })?;
+                    Ok(Some(FeeCryptoPaymentResponse::new(payment, account.wallet_address)))
+                }
+            }
+        })
+    }
 }
 
 impl<
@@ -190,7 +319,7 @@ impl<
         let repo_factory = self.repo_factory.clone();
         let db_pool = self.db_pool.clone();
         let cpu_pool = self.cpu_pool.clone();
-        let stripe_client = self.stripe_client.clone();
+        let fiat_payment_provider = self.fiat_payment_provider.clone();
         let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
             let user_roles_repo = repo_factory.create_user_roles_repo(&conn, user_id);
             let customers_repo = repo_factory.create_customers_repo(&conn, user_id);
@@ -219,59 +348,117 @@ impl<
 
             Ok((fees, stripe_customer))
         })
-        .and_then(move |(fees, customer)| {
-            total_amount(fees.clone())
-                .into_future()
-                .and_then({
-                    let fees = fees.clone();
-                    move |amount| extract_currency(fees).map(move |currency| (currency, amount))
-                })
-                .and_then(move |(currency, amount)| {
-                    let new_charge = NewCharge {
-                        customer_id: customer.id.clone(),
-                        amount,
-                        currency,
-                        capture: true,
-                    };
-
-                    let customer_id_cloned = customer.id.clone();
+        .and_then({
+            let self_clone = self.clone();
+            let dunning_service = self.clone();
+            move |(fees, customer)| {
+                extract_currency(fees.clone())
+                    .into_future()
+                    .and_then(move |currency| self_clone.charge_conversion(currency))
+                    .and_then(move |conversion| {
+                        let charged_amounts = fees
+                            .iter()
+                            .map(|fee| charged_amount(fee, &conversion))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        let amount = total_amount(&charged_amounts)?;
+
+                        Ok((fees, conversion, charged_amounts, amount))
+                    })
+                    .and_then(move |(fees, conversion, charged_amounts, amount)| {
+                        let new_charge = NewFiatCharge {
+                            customer_id: customer.id.clone(),
+                            amount,
+                            currency: conversion.currency,
+                            metadata: create_charge_metadata(&fees),
+                        };
 
-                    stripe_client
-                        .create_charge(new_charge, create_charge_metadata(&fees))
-                        .map_err(ectx!(convert => customer_id_cloned))
-                        .map(|charge| (fees, charge))
-                })
+                        let customer_id_cloned = customer.id.clone();
+
+                        fiat_payment_provider
+                            .charge_customer(new_charge)
+                            .map_err(ectx!(convert => customer_id_cloned))
+                            // a declined card is reported as an error, the fees stay unpaid
+                            .or_else(move |e| {
+                                dunning_service.start_fee_dunning(store_id).then(move |res| {
+                                    if let Err(dunning_err) = res {
+                                        error!(
+                                            "Fee dunning of store {} not started after a failed charge: {}",
+                                            store_id, dunning_err
+                                        );
+                                    }
+                                    Err(e)
+                                })
+                            })
+                            .map(move |charge| (fees.into_iter().zip(charged_amounts).collect::<Vec<_>>(), conversion, charge))
+                    })
+            }
         })
         .and_then({
             let repo_factory = self.repo_factory.clone();
             let db_pool = self.db_pool.clone();
             let cpu_pool = self.cpu_pool.clone();
-            move |(fees, charge)| {
+            let fee_dunning = self.fee_dunning.clone();
+            move |(fees, conversion, charge)| {
                 spawn_on_pool(db_pool, cpu_pool, move |conn| {
                     let fees_repo = repo_factory.create_fees_repo(&conn, user_id);
+                    let store_webhooks_repo = repo_factory.create_store_webhooks_repo_with_sys_acl(&conn);
+                    let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+                    let fee_dunnings_repo = repo_factory.create_fee_dunnings_repo_with_sys_acl(&conn);
+                    let store_fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+                    let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                    let store_billing_statuses_repo = repo_factory.create_store_billing_statuses_repo_with_sys_acl(&conn);
                     conn.transaction(|| {
                         let status = if charge.paid {
                             Some(FeeStatus::Paid)
                         } else {
                             Some(FeeStatus::Fail)
                         };
-                        let charge_id = Some(charge.id).map(|v| ChargeId::new(v));
-                        let update_fee = UpdateFee {
-                            charge_id,
-                            status,
-                            ..Default::default()
-                        };
-                        let fee_result: Result<Vec<_>, _> = fees
+                        let charge_id = Some(charge.id);
+                        let fees: Result<Vec<_>, Error> = fees
                             .into_iter()
-                            .map(|fee| {
+                            .map(|(fee, charged_amount)| {
+                                let update_fee = UpdateFee {
+                                    charge_id: charge_id.clone(),
+                                    status: status.clone(),
+                                    charged_currency: Some(conversion.currency),
+                                    charged_amount: Some(charged_amount),
+                                    charge_exchange_rate: conversion.exchange_rate,
+                                    ..Default::default()
+                                };
                                 let fee_id_cloned = fee.id.clone();
-                                fees_repo
-                                    .update(fee.id, update_fee.clone())
-                                    .map_err(ectx!(convert => fee_id_cloned))
-                                    .and_then(|res| FeeResponse::try_from_fee(res))
+                                fees_repo.update(fee.id, update_fee).map_err(ectx!(convert => fee_id_cloned))
                             })
                             .collect();
-                        fee_result
+                        let fees = fees?;
+
+                        let now = Utc::now().naive_utc();
+                        if charge.paid {
+                            enqueue_fee_charged_webhooks(
+                                &*store_webhooks_repo,
+                                &*event_store_repo,
+                                StqStoreId(store_id.inner()),
+                                fees.clone(),
+                            )?;
+                            resolve_paid_fee_dunning(
+                                &*fee_dunnings_repo,
+                                &*store_fees_repo,
+                                &*orders_repo,
+                                &*store_billing_statuses_repo,
+                                &*event_store_repo,
+                                StqStoreId(store_id.inner()),
+                                now,
+                            )?;
+                        } else {
+                            start_fee_dunning(
+                                &*fee_dunnings_repo,
+                                &*event_store_repo,
+                                &fee_dunning,
+                                StqStoreId(store_id.inner()),
+                                now,
+                            )?;
+                        }
+
+                        fees.into_iter().map(|res| FeeResponse::try_from_fee(res)).collect()
                     })
                 })
             }
@@ -279,14 +466,103 @@ impl<
 
         Box::new(fut)
     }
+
+    /// Starts the fee dunning of the store after a charge of its fees has failed
+    fn start_fee_dunning(&self, store_id: StoreId) -> ServiceFutureV2<()> {
+        let repo_factory = self.repo_factory.clone();
+        let fee_dunning = self.fee_dunning.clone();
+
+        spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let fee_dunnings_repo = repo_factory.create_fee_dunnings_repo_with_sys_acl(&conn);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+            conn.transaction(|| {
+                start_fee_dunning(
+                    &*fee_dunnings_repo,
+                    &*event_store_repo,
+                    &fee_dunning,
+                    StqStoreId(store_id.inner()),
+                    Utc::now().naive_utc(),
+                )
+                .map(|_| ())
+            })
+        })
+    }
+
+    /// Currency the fees in `fee_currency` are charged in and the rate they are converted into it with
+    fn charge_conversion(&self, fee_currency: Currency) -> ServiceFutureV2<ChargeConversion> {
+        let charge_currency = match self.fee_charge.currency {
+            Some(currency) if currency != fee_currency => currency,
+            _ => {
+                return Box::new(future::ok(ChargeConversion {
+                    currency: fee_currency,
+                    exchange_rate: None,
+                }));
+            }
+        };
+
+        let exchange_rate: ServiceFutureV2<f64> = match self.fee_charge.rate_source {
+            FeeChargeRateSource::Fixed => Box::new(
+                self.fee_charge
+                    .fixed_rates
+                    .get(&fee_currency)
+                    .cloned()
+                    .ok_or_else(|| missing_charge_rate_error(fee_currency, charge_currency))
+                    .into_future(),
+            ),
+            FeeChargeRateSource::Stores => Box::new(self.stores_client.get_currency_exchange_info().map_err(ectx!(convert)).and_then(
+                move |currency_exchange_info: CurrencyExchangeInfo| {
+                    currency_exchange_info
+                        .data
+                        .get(&fee_currency)
+                        .and_then(|rates| rates.get(&charge_currency).map(|rate| rate.0))
+                        .ok_or_else(|| missing_charge_rate_error(fee_currency, charge_currency))
+                },
+            )),
+        };
+
+        Box::new(exchange_rate.map(move |exchange_rate| ChargeConversion {
+            currency: charge_currency,
+            exchange_rate: Some(exchange_rate),
+        }))
+    }
+}
+
+/// Currency the fees are charged from the card in
+#[derive(Clone, Debug)]
+struct ChargeConversion {
+    currency: Currency,
+    /// Amount in the fee currency one unit of `currency` is worth, none if the fees are charged in their own currency
+    exchange_rate: Option<f64>,
+}
+
+/// The fee amount converted into the charge currency
+fn charged_amount(fee: &Fee, conversion: &ChargeConversion) -> Result<Amount, Error> {
+    match conversion.exchange_rate {
+        None => Ok(fee.amount),
+        Some(exchange_rate) if exchange_rate > 0.0 => {
+            let fee_amount_super_unit = fee.amount.to_super_unit(fee.currency);
+            Amount::checked_from_super_unit(conversion.currency, fee_amount_super_unit / BigDecimal::from(exchange_rate))
+                .ok_or(ectx!(err ErrorContext::AmountConversion, ErrorKind::Internal))
+        }
+        Some(exchange_rate) => {
+            let e = format_err!("Cannot charge fee {} - exchange rate {} is not positive", fee.id, exchange_rate);
+            Err(ectx!(err e, ErrorKind::Internal))
+        }
+    }
+}
+
+fn missing_charge_rate_error(fee_currency: Currency, charge_currency: Currency) -> Error {
+    let e = format_err!("Cannot charge fees - no exchange rate from {} to {}", fee_currency, charge_currency);
+    ectx!(err e, ErrorKind::Internal)
 }
 
 fn validate_charge_fees(fees: &[Fee]) -> Result<(), Error> {
     for fee in fees {
-        if fee.status == FeeStatus::Paid {
+        if fee.status == FeeStatus::Paid || fee.status == FeeStatus::WrittenOff {
             let mut errors = ValidationErrors::new();
             let mut error = ValidationError::new("wrong_fee_status");
-            error.message = Some(format!("Cannot charge fee - fee {} has status \"{}\"", fee.id, FeeStatus::Paid).into());
+            error.message = Some(format!("Cannot charge fee - fee {} has status \"{}\"", fee.id, fee.status).into());
             errors.add("order_id", error);
             return Err(ectx!(err ErrorContext::OrderState ,ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default())));
         }
@@ -294,6 +570,44 @@ fn validate_charge_fees(fees: &[Fee]) -> Result<(), Error> {
     Ok(())
 }
 
+fn validate_crypto_payment(fee: &Fee, latest_payment: Option<&FeeCryptoPayment>) -> Result<(), Error> {
+    if fee.status == FeeStatus::Paid || fee.status == FeeStatus::WrittenOff {
+        let message = format!("Cannot pay fee - fee {} has status \"{}\"", fee.id, fee.status);
+        return Err(crypto_payment_validation_error("wrong_fee_status", message));
+    }
+
+    match latest_payment {
+        Some(payment) if payment.status == FeeCryptoPaymentStatus::Pending => {
+            let message = format!("Cannot pay fee - crypto payment {} of fee {} is pending", payment.id, fee.id);
+            Err(crypto_payment_validation_error("crypto_payment_pending", message))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The fee amount if the fee is charged in `currency`, or the amount the fee of a crypto order was converted to
+fn crypto_payment_amount(fee: &Fee, currency: Currency) -> Result<Amount, Error> {
+    if fee.currency == currency {
+        return Ok(fee.amount);
+    }
+
+    match (fee.crypto_currency, fee.crypto_amount) {
+        (Some(crypto_currency), Some(crypto_amount)) if crypto_currency == currency => Ok(crypto_amount),
+        _ => {
+            let message = format!("Cannot pay fee - fee {} can not be paid in {}", fee.id, currency);
+            Err(crypto_payment_validation_error("wrong_currency", message))
+        }
+    }
+}
+
+fn crypto_payment_validation_error(code: &'static str, message: String) -> Error {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    errors.add("fee_id", error);
+    ectx!(err ErrorContext::FeeState, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
 fn extract_currency(fees: Vec<Fee>) -> Result<Currency, Error> {
     let currencies: HashSet<Currency> = fees.iter().map(|fee| fee.currency).collect();
     if currencies.len() != 1 {
@@ -310,9 +624,10 @@ fn extract_currency(fees: Vec<Fee>) -> Result<Currency, Error> {
     Ok(currency)
 }
 
-fn total_amount(fees: Vec<Fee>) -> Result<Amount, Error> {
-    fees.iter()
-        .map(|fee| fee.amount)
+fn total_amount(amounts: &[Amount]) -> Result<Amount, Error> {
+    amounts
+        .iter()
+        .cloned()
         .try_fold(Amount::zero(), |acc, next| acc.checked_add(next))
         .ok_or_else(|| {
             let e = format_err!("Amount checked add error");
@@ -358,8 +673,66 @@ impl<
             db_pool: self.db_pool.clone(),
             cpu_pool: self.cpu_pool.clone(),
             repo_factory: self.repo_factory.clone(),
-            stripe_client: self.stripe_client.clone(),
+            fiat_payment_provider: self.fiat_payment_provider.clone(),
             dynamic_context: self.dynamic_context.clone(),
+            config: self.config.clone(),
+            stores_client: self.stores_client.clone(),
+            fee_charge: self.fee_charge.clone(),
+            fee_dunning: self.fee_dunning.clone(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_support::FeeBuilder;
+
+    #[test]
+    fn fees_are_paid_by_crypto_in_their_own_or_converted_currency() {
+        let stq_fee = FeeBuilder::new().currency(Currency::Stq).amount(Amount::new(500)).build();
+        assert_eq!(crypto_payment_amount(&stq_fee, Currency::Stq).ok(), Some(Amount::new(500)));
+        assert!(crypto_payment_amount(&stq_fee, Currency::Btc).is_err());
+
+        let converted_fee = FeeBuilder::new()
+            .currency(Currency::Eur)
+            .amount(Amount::new(500))
+            .crypto_currency(Some(Currency::Btc))
+            .crypto_amount(Some(Amount::new(70000)))
+            .build();
+        assert_eq!(crypto_payment_amount(&converted_fee, Currency::Btc).ok(), Some(Amount::new(70000)));
+        assert!(crypto_payment_amount(&converted_fee, Currency::Eth).is_err());
+    }
+
+    #[test]
+    fn fees_are_converted_into_the_charge_currency() {
+        let fee = FeeBuilder::new().currency(Currency::Usd).amount(Amount::new(1250)).build();
+
+        let own_currency = ChargeConversion {
+            currency: Currency::Usd,
+            exchange_rate: None,
+        };
+        assert_eq!(charged_amount(&fee, &own_currency).ok(), Some(Amount::new(1250)));
+
+        let eur = ChargeConversion {
+            currency: Currency::Eur,
+            exchange_rate: Some(1.25),
+        };
+        assert_eq!(charged_amount(&fee, &eur).ok(), Some(Amount::new(1000)));
+
+        let broken_rate = ChargeConversion {
+            currency: Currency::Eur,
+            exchange_rate: Some(0.0),
+        };
+        assert!(charged_amount(&fee, &broken_rate).is_err());
+    }
+
+    #[test]
+    fn paid_fees_are_not_paid_by_crypto() {
+        let paid_fee = FeeBuilder::new().status(FeeStatus::Paid).build();
+        assert!(validate_crypto_payment(&paid_fee, None).is_err());
+
+        let unpaid_fee = FeeBuilder::new().build();
+        assert!(validate_crypto_payment(&unpaid_fee, None).is_ok());
+    }
+}
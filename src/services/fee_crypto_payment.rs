@@ -0,0 +1,140 @@
+//! Payment of fees by transfers from the wallets of stores. A payment reserves a pooled account as the deposit
+//! address of the fee, inbound transactions to the account are credited to the payment and the fee is paid
+//! once the required amount arrives. The event handler drains the account and expires unpaid payments
+use chrono::{NaiveDateTime, Utc};
+use failure::Fail;
+use stq_types::StoreId as StqStoreId;
+
+use models::{
+    AccountId, Amount, Event, EventPayload, FeeCryptoPayment, FeeCryptoPaymentId, FeeCryptoPaymentStatus, FeeStatus,
+    NewFeeCryptoPaymentTransaction, TransactionId, UpdateFee,
+};
+use repos::error::ErrorKind as RepoErrorKind;
+use repos::{EventStoreRepo, FeeCryptoPaymentsRepo, FeeRepo, OrdersRepo, SearchFee, StoreWebhooksRepo};
+use services::store_webhook::enqueue_fee_charged_webhooks;
+use services::{Error, ErrorKind};
+
+/// Credits the transaction to the pending fee payment of the account and pays the fee once the required amount
+/// has arrived. Returns `None` if the account does not collect a fee payment
+pub fn credit_fee_crypto_payment(
+    fee_crypto_payments_repo: &FeeCryptoPaymentsRepo,
+    fees_repo: &FeeRepo,
+    orders_repo: &OrdersRepo,
+    store_webhooks_repo: &StoreWebhooksRepo,
+    event_store_repo: &EventStoreRepo,
+    account_id: AccountId,
+    transaction_id: TransactionId,
+    transaction_at: NaiveDateTime,
+    amount: Amount,
+) -> Result<Option<FeeCryptoPayment>, Error> {
+    let payment = fee_crypto_payments_repo
+        .get_pending_by_account_id(account_id)
+        .map_err(ectx!(try convert => account_id))?;
+    let payment = match payment {
+        Some(payment) => payment,
+        None => return Ok(None),
+    };
+
+    // e.g. a late payment for the previous invoice of the pooled account
+    if transaction_at < payment.created_at {
+        warn!(
+            "Transaction {} to account {} made at {} is not credited to fee crypto payment {} created at {}",
+            transaction_id, account_id, transaction_at, payment.id, payment.created_at
+        );
+        return Ok(None);
+    }
+
+    let new_transaction = NewFeeCryptoPaymentTransaction {
+        transaction_id,
+        fee_crypto_payment_id: payment.id,
+        amount,
+    };
+    let payment = match fee_crypto_payments_repo.add_transaction(new_transaction.clone()) {
+        Ok(payment) => payment,
+        Err(e) => {
+            return match e.kind() {
+                // the transaction has already been credited
+                RepoErrorKind::Constraints(_) => Ok(Some(payment)),
+                _ => Err(ectx!(convert err e => new_transaction)),
+            };
+        }
+    };
+
+    if !payment.is_fully_received() {
+        info!(
+            "Fee crypto payment {} received {} of {} {}",
+            payment.id, payment.amount_received, payment.amount, payment.currency
+        );
+        return Ok(Some(payment));
+    }
+
+    let payment_id = payment.id;
+    let fee_id = payment.fee_id;
+    let fee = fees_repo.get(SearchFee::Id(fee_id)).map_err(ectx!(try convert => fee_id))?.ok_or({
+        let e = format_err!("Fee {} not found", fee_id);
+        ectx!(try err e, ErrorKind::Internal)
+    })?;
+
+    // the store has paid the fee by card meanwhile, the payment is recorded as owed back to the store
+    let is_fee_paid = fee.status == FeeStatus::Paid;
+    let status = if is_fee_paid {
+        FeeCryptoPaymentStatus::Refundable
+    } else {
+        FeeCryptoPaymentStatus::Paid
+    };
+    let closed_at = Utc::now().naive_utc();
+    let payment = fee_crypto_payments_repo
+        .close(payment_id, status, closed_at)
+        .map_err(ectx!(try convert => payment_id))?;
+
+    if is_fee_paid {
+        info!(
+            "Fee {} is already paid, fee crypto payment {} of {} {} is to be refunded",
+            fee_id, payment_id, payment.amount_received, payment.currency
+        );
+    } else {
+        let update_fee = UpdateFee {
+            status: Some(FeeStatus::Paid),
+            ..Default::default()
+        };
+        let fee = fees_repo
+            .update(fee_id, update_fee.clone())
+            .map_err(ectx!(try convert => fee_id, update_fee))?;
+
+        let order_id = fee.order_id;
+        let order = orders_repo.get(order_id).map_err(ectx!(try convert => order_id))?.ok_or({
+            let e = format_err!("Order {} not found", order_id);
+            ectx!(try err e, ErrorKind::Internal)
+        })?;
+
+        enqueue_fee_charged_webhooks(store_webhooks_repo, event_store_repo, StqStoreId(order.store_id.inner()), vec![fee])?;
+    }
+
+    let event = Event::new(EventPayload::FeeCryptoPaymentPaid {
+        fee_crypto_payment_id: payment_id,
+    });
+    event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+
+    Ok(Some(payment))
+}
+
+/// Expires the payment if it is still pending. Returns the expired payment
+pub fn expire_fee_crypto_payment(
+    fee_crypto_payments_repo: &FeeCryptoPaymentsRepo,
+    fee_crypto_payment_id: FeeCryptoPaymentId,
+) -> Result<Option<FeeCryptoPayment>, Error> {
+    let payment = fee_crypto_payments_repo
+        .get(fee_crypto_payment_id)
+        .map_err(ectx!(try convert => fee_crypto_payment_id))?;
+
+    match payment {
+        Some(ref payment) if payment.status == FeeCryptoPaymentStatus::Pending => {
+            let closed_at = Utc::now().naive_utc();
+            fee_crypto_payments_repo
+                .close(fee_crypto_payment_id, FeeCryptoPaymentStatus::Expired, closed_at)
+                .map(Some)
+                .map_err(ectx!(convert => fee_crypto_payment_id))
+        }
+        _ => Ok(None),
+    }
+}
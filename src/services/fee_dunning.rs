@@ -0,0 +1,286 @@
+//! Dunning of stores whose fees could not be charged. A failed charge starts the dunning of the store, its steps
+//! are taken by scheduled events `after_days` past the failed charge: the owner is reminded of the unpaid fees,
+//! warned of the suspension and finally the store is suspended. Paying the fees resolves the dunning and
+//! reinstates the store it has suspended
+use chrono::{Duration, NaiveDateTime};
+use failure::Fail;
+
+use stq_types::StoreId;
+
+use config::{DunningStep, FeeDunning as FeeDunningConfig};
+use models::order_v2::StoreId as StoreV2Id;
+use models::{DunningAction, Event, EventPayload, Fee, FeeDunning, FeeDunningStatus, FeeStatus, NewFeeDunning, UpdateFeeDunning};
+use repos::{EventStoreRepo, FeeDunningsRepo, FeeRepo, OrdersRepo, SearchFeeParams, StoreBillingStatusesRepo};
+use services::store_billing_status::apply_fee_dunning_suspension;
+use services::types::ServiceResultV2;
+
+/// Starts the dunning of the store after a failed charge of its fees. An open dunning keeps its schedule,
+/// a resolved one is started over. Returns `None` if the dunning is disabled
+pub fn start_fee_dunning(
+    fee_dunnings_repo: &FeeDunningsRepo,
+    event_store_repo: &EventStoreRepo,
+    config: &FeeDunningConfig,
+    store_id: StoreId,
+    failed_at: NaiveDateTime,
+) -> ServiceResultV2<Option<FeeDunning>> {
+    let first_step = match config.ordered_schedule().first() {
+        Some(first_step) if config.enabled => *first_step,
+        _ => return Ok(None),
+    };
+
+    let current = fee_dunnings_repo.get(store_id).map_err(ectx!(try convert => store_id))?;
+    let next_step_at = step_at(failed_at, first_step);
+    let dunning = match current {
+        Some(ref current) if current.status == FeeDunningStatus::Open => return Ok(Some(current.clone())),
+        Some(_) => {
+            let update = UpdateFeeDunning {
+                status: Some(FeeDunningStatus::Open),
+                steps_taken: Some(0),
+                last_action: Some(None),
+                started_at: Some(failed_at),
+                last_step_at: Some(None),
+                next_step_at: Some(Some(next_step_at)),
+                resolved_at: Some(None),
+            };
+            fee_dunnings_repo.update(store_id, update).map_err(ectx!(try convert => store_id))?
+        }
+        None => {
+            let new_dunning = NewFeeDunning {
+                store_id,
+                status: FeeDunningStatus::Open,
+                started_at: failed_at,
+                next_step_at: Some(next_step_at),
+            };
+            fee_dunnings_repo
+                .create(new_dunning.clone())
+                .map_err(ectx!(try convert => new_dunning))?
+        }
+    };
+
+    info!(
+        "Fee dunning of store {} started, {} is due at {}",
+        store_id, first_step.action, next_step_at
+    );
+    let event = Event::new(EventPayload::FeeDunningStep { store_id });
+    event_store_repo
+        .add_scheduled_event(event.clone(), next_step_at)
+        .map_err(ectx!(try convert => event))?;
+
+    Ok(Some(dunning))
+}
+
+/// Takes the step of the schedule that is due, or resolves the dunning if the store has paid its fees.
+/// The owner is notified of the step by a `FeeDunningNotification` event. Returns the step taken
+pub fn take_fee_dunning_step(
+    fee_dunnings_repo: &FeeDunningsRepo,
+    fees_repo: &FeeRepo,
+    orders_repo: &OrdersRepo,
+    store_billing_statuses_repo: &StoreBillingStatusesRepo,
+    event_store_repo: &EventStoreRepo,
+    config: &FeeDunningConfig,
+    store_id: StoreId,
+    now: NaiveDateTime,
+) -> ServiceResultV2<Option<DunningStep>> {
+    let dunning = match fee_dunnings_repo.get(store_id).map_err(ectx!(try convert => store_id))? {
+        Some(dunning) => dunning,
+        None => return Ok(None),
+    };
+
+    let is_resolved = resolve_paid_fee_dunning(
+        fee_dunnings_repo,
+        fees_repo,
+        orders_repo,
+        store_billing_statuses_repo,
+        event_store_repo,
+        store_id,
+        now,
+    )?;
+    if is_resolved {
+        return Ok(None);
+    }
+
+    let schedule = config.ordered_schedule();
+    let step = match due_dunning_step(&schedule, &dunning, now) {
+        Some(step) => step,
+        None => return Ok(None),
+    };
+
+    let steps_taken = dunning.steps_taken + 1;
+    let next_step_at = schedule
+        .get(steps_taken as usize)
+        .map(|next_step| step_at(dunning.started_at, *next_step));
+    let update = UpdateFeeDunning {
+        steps_taken: Some(steps_taken),
+        last_action: Some(Some(step.action)),
+        last_step_at: Some(Some(now)),
+        next_step_at: Some(next_step_at),
+        ..Default::default()
+    };
+    fee_dunnings_repo.update(store_id, update).map_err(ectx!(try convert => store_id))?;
+    info!("Fee dunning of store {} took step {}: {}", store_id, steps_taken, step.action);
+
+    if let Some(next_step_at) = next_step_at {
+        let event = Event::new(EventPayload::FeeDunningStep { store_id });
+        event_store_repo
+            .add_scheduled_event(event.clone(), next_step_at.max(now))
+            .map_err(ectx!(try convert => event))?;
+    }
+
+    if step.action == DunningAction::Suspension {
+        apply_fee_dunning_suspension(store_billing_statuses_repo, event_store_repo, store_id, true, now)?;
+    }
+
+    let event = Event::new(EventPayload::FeeDunningNotification {
+        store_id,
+        action: step.action,
+    });
+    event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+
+    Ok(Some(step))
+}
+
+/// Resolves the open dunning of the store once it has no unpaid fees left and reinstates the store
+/// if the dunning has suspended it. Returns whether the dunning has been resolved
+pub fn resolve_paid_fee_dunning(
+    fee_dunnings_repo: &FeeDunningsRepo,
+    fees_repo: &FeeRepo,
+    orders_repo: &OrdersRepo,
+    store_billing_statuses_repo: &StoreBillingStatusesRepo,
+    event_store_repo: &EventStoreRepo,
+    store_id: StoreId,
+    now: NaiveDateTime,
+) -> ServiceResultV2<bool> {
+    let dunning = match fee_dunnings_repo.get(store_id).map_err(ectx!(try convert => store_id))? {
+        Some(ref dunning) if dunning.status == FeeDunningStatus::Open => dunning.clone(),
+        _ => return Ok(false),
+    };
+
+    if !store_unpaid_fees(fees_repo, orders_repo, store_id)?.is_empty() {
+        return Ok(false);
+    }
+
+    let update = UpdateFeeDunning {
+        status: Some(FeeDunningStatus::Resolved),
+        next_step_at: Some(None),
+        resolved_at: Some(Some(now)),
+        ..Default::default()
+    };
+    fee_dunnings_repo.update(store_id, update).map_err(ectx!(try convert => store_id))?;
+    info!("Fee dunning of store {} resolved", store_id);
+
+    if dunning.is_suspending() {
+        apply_fee_dunning_suspension(store_billing_statuses_repo, event_store_repo, store_id, false, now)?;
+    }
+
+    Ok(true)
+}
+
+/// Fees of the store's orders that are not paid yet, including the ones whose charge has failed.
+/// Written off fees are not collected anymore and are left out
+pub fn store_unpaid_fees(fees_repo: &FeeRepo, orders_repo: &OrdersRepo, store_id: StoreId) -> ServiceResultV2<Vec<Fee>> {
+    let order_ids = orders_repo
+        .get_order_ids_by_store_id(StoreV2Id::new(store_id.0))
+        .map_err(ectx!(try convert => store_id))?;
+    if order_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let fees = fees_repo
+        .search(SearchFeeParams::by_order_ids(order_ids))
+        .map_err(ectx!(try convert => store_id))?;
+
+    Ok(fees
+        .into_iter()
+        .filter(|fee| fee.status == FeeStatus::NotPaid || fee.status == FeeStatus::Fail)
+        .collect())
+}
+
+/// The step of the schedule that is due for an open dunning. Steps are taken one at a time,
+/// a step that was skipped, e.g. while the service was down, is taken before the next one
+pub fn due_dunning_step(schedule: &[DunningStep], dunning: &FeeDunning, now: NaiveDateTime) -> Option<DunningStep> {
+    if dunning.status != FeeDunningStatus::Open {
+        return None;
+    }
+
+    schedule
+        .get(dunning.steps_taken as usize)
+        .cloned()
+        .filter(|step| step_at(dunning.started_at, *step) <= now)
+}
+
+/// Time the store is suspended at, or is to be suspended at if the fees stay unpaid
+pub fn fee_dunning_suspension_at(config: &FeeDunningConfig, dunning: &FeeDunning) -> Option<NaiveDateTime> {
+    if dunning.last_action == Some(DunningAction::Suspension) {
+        return dunning.last_step_at;
+    }
+
+    config
+        .schedule
+        .iter()
+        .find(|step| step.action == DunningAction::Suspension)
+        .map(|step| step_at(dunning.started_at, *step))
+}
+
+fn step_at(started_at: NaiveDateTime, step: DunningStep) -> NaiveDateTime {
+    started_at + Duration::days(step.after_days as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn schedule() -> Vec<DunningStep> {
+        vec![
+            DunningStep::new(DunningAction::Reminder, 3),
+            DunningStep::new(DunningAction::Warning, 7),
+            DunningStep::new(DunningAction::Suspension, 14),
+        ]
+    }
+
+    fn dunning(status: FeeDunningStatus, steps_taken: i32) -> FeeDunning {
+        let started_at = NaiveDate::from_ymd(2019, 4, 1).and_hms(12, 0, 0);
+        FeeDunning {
+            store_id: StoreId(1),
+            status,
+            steps_taken,
+            last_action: None,
+            started_at,
+            last_step_at: None,
+            next_step_at: None,
+            resolved_at: None,
+            created_at: started_at,
+            updated_at: started_at,
+        }
+    }
+
+    fn day(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2019, 4, day).and_hms(12, 0, 0)
+    }
+
+    #[test]
+    fn step_is_not_due_before_its_time() {
+        assert_eq!(due_dunning_step(&schedule(), &dunning(FeeDunningStatus::Open, 0), day(3)), None);
+        assert_eq!(
+            due_dunning_step(&schedule(), &dunning(FeeDunningStatus::Open, 0), day(4)),
+            Some(DunningStep::new(DunningAction::Reminder, 3))
+        );
+    }
+
+    #[test]
+    fn steps_are_taken_one_at_a_time() {
+        assert_eq!(
+            due_dunning_step(&schedule(), &dunning(FeeDunningStatus::Open, 1), day(20)),
+            Some(DunningStep::new(DunningAction::Warning, 7))
+        );
+        assert_eq!(due_dunning_step(&schedule(), &dunning(FeeDunningStatus::Open, 3), day(20)), None);
+    }
+
+    #[test]
+    fn resolved_dunning_takes_no_steps() {
+        assert_eq!(
+            due_dunning_step(&schedule(), &dunning(FeeDunningStatus::Resolved, 0), day(20)),
+            None
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! FeePreviewService tells sellers the fee of an order before it is paid.
+//! The fee is calculated by the same code as on payment, with the current exchange rates, and is not saved
+use std::str::FromStr;
+use std::sync::Arc;
+
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::Fail;
+use futures::{future, Future};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+
+use stq_types::UserId;
+
+use super::types::ServiceFutureV2;
+use client::stores::{CurrencyExchangeInfo, StoresClient};
+use config::FeeValues;
+use controller::responses::{FeeRateSnapshotResponse, OrderFeePreviewResponse};
+use models::order_v2::{OrderId, RawOrder};
+use models::Currency;
+use repos::ReposFactory;
+use services::error::Error as ServiceError;
+use services::invoice::create_crypto_fee;
+use services::stripe::create_fee;
+use services::types::spawn_on_pool;
+use services::{ErrorContext, ErrorKind};
+
+pub trait FeePreviewService {
+    /// Fee the order would be charged if it was paid now
+    fn preview_order_fee(&self, order_id: OrderId) -> ServiceFutureV2<OrderFeePreviewResponse>;
+    /// Fees of several orders, the exchange rates are fetched once for all of them
+    fn preview_order_fees(&self, order_ids: Vec<OrderId>) -> ServiceFutureV2<Vec<OrderFeePreviewResponse>>;
+}
+
+pub struct FeePreviewServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+    pub stores_client: Arc<dyn StoresClient>,
+    pub fee_config: FeeValues,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > FeePreviewService for FeePreviewServiceImpl<T, M, F>
+{
+    fn preview_order_fee(&self, order_id: OrderId) -> ServiceFutureV2<OrderFeePreviewResponse> {
+        let fut = self.preview_order_fees(vec![order_id]).and_then(move |previews| {
+            previews.into_iter().next().ok_or({
+                let e = format_err!("Fee preview of order {} is missing", order_id);
+                ectx!(err e, ErrorKind::Internal)
+            })
+        });
+
+        Box::new(fut)
+    }
+
+    fn preview_order_fees(&self, order_ids: Vec<OrderId>) -> ServiceFutureV2<Vec<OrderFeePreviewResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let stores_client = self.stores_client.clone();
+        let fee_config = self.fee_config.clone();
+
+        let fut = spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            // Access to the orders is checked by the orders repo
+            let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
+
+            let mut orders = Vec::with_capacity(order_ids.len());
+            for order_id in order_ids {
+                let order = orders_repo.get(order_id).map_err(ectx!(try convert => order_id))?.ok_or({
+                    let e = format_err!("Order {} not found", order_id);
+                    ectx!(try err e, ErrorKind::NotFound)
+                })?;
+                orders.push(order);
+            }
+
+            Ok(orders)
+        })
+        .and_then(move |orders: Vec<RawOrder>| {
+            // fees of fiat orders are charged in their own currency, no exchange rates needed
+            let currency_exchange_info = if orders.iter().all(|order| order.seller_currency.is_fiat()) {
+                future::Either::A(future::ok(None))
+            } else {
+                future::Either::B(stores_client.get_currency_exchange_info().map_err(ectx!(convert)).map(Some))
+            };
+
+            currency_exchange_info.and_then(move |currency_exchange_info| {
+                orders
+                    .iter()
+                    .map(|order| preview_fee(&fee_config, currency_exchange_info.as_ref(), order))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+        });
+
+        Box::new(fut)
+    }
+}
+
+/// Calculates the fee the way it is done on payment: fiat orders are charged in their own currency
+/// when the Stripe payment succeeds, crypto ones in the fee currency when the invoice is paid
+fn preview_fee(
+    fee_config: &FeeValues,
+    currency_exchange_info: Option<&CurrencyExchangeInfo>,
+    order: &RawOrder,
+) -> Result<OrderFeePreviewResponse, ServiceError> {
+    let (new_fee, rate) = if order.seller_currency.is_fiat() {
+        (create_fee(fee_config.order_percent, order)?, None)
+    } else {
+        let fee_currency = Currency::from_str(&fee_config.currency_code)
+            .map_err(ectx!(try ErrorContext::CurrencyConversion, ErrorKind::Internal => fee_config.currency_code))?;
+        let currency_exchange_info = currency_exchange_info.ok_or({
+            let e = format_err!("Exchange rates are required for the fee of crypto order {}", order.id);
+            ectx!(try err e, ErrorKind::Internal)
+        })?;
+
+        let new_fee = create_crypto_fee(fee_config.order_percent, &fee_currency, currency_exchange_info, order)?;
+        let exchange_rate = new_fee
+            .exchange_rate
+            .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+        let rate = FeeRateSnapshotResponse {
+            currency_exchange_id: currency_exchange_info.id.clone(),
+            from: order.seller_currency,
+            to: fee_currency,
+            exchange_rate,
+        };
+
+        (new_fee, Some(rate))
+    };
+
+    Ok(OrderFeePreviewResponse {
+        order_id: order.id,
+        amount: new_fee.amount.to_super_unit(new_fee.currency),
+        currency: new_fee.currency,
+        rate,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::BigDecimal;
+    use uuid::Uuid;
+
+    use stq_types::{CurrencyExchangeId, ExchangeRate};
+
+    use client::stores::{CurrencyExchangeData, ExchangeRates};
+    use models::Amount;
+    use test_support::RawOrderBuilder;
+
+    use super::*;
+
+    #[test]
+    fn crypto_fees_are_previewed_in_the_fee_currency_with_the_rate_used() {
+        let fee_config = FeeValues {
+            order_percent: 5,
+            currency_code: "eur".to_string(),
+        };
+
+        let mut exchange_rates = ExchangeRates::new();
+        exchange_rates.insert(Currency::Eur, ExchangeRate(5.0));
+        let mut data = CurrencyExchangeData::new();
+        data.insert(Currency::Stq, exchange_rates);
+        let currency_exchange_info = CurrencyExchangeInfo {
+            id: CurrencyExchangeId(Uuid::new_v4()),
+            data,
+        };
+
+        let crypto_order = RawOrderBuilder::new()
+            .seller_currency(Currency::Stq)
+            .total_amount(Amount::from_super_unit(Currency::Stq, BigDecimal::from(100)))
+            .build();
+        let crypto_preview = preview_fee(&fee_config, Some(&currency_exchange_info), &crypto_order).unwrap();
+        assert_eq!(crypto_preview.currency, Currency::Eur);
+        assert_eq!(crypto_preview.amount, BigDecimal::from(1));
+        assert_eq!(crypto_preview.rate.map(|rate| rate.exchange_rate), Some(5.0));
+
+        let fiat_order = RawOrderBuilder::new()
+            .seller_currency(Currency::Usd)
+            .total_amount(Amount::from_super_unit(Currency::Usd, BigDecimal::from(100)))
+            .build();
+        let fiat_preview = preview_fee(&fee_config, None, &fiat_order).unwrap();
+        assert_eq!(fiat_preview.currency, Currency::Usd);
+        assert_eq!(fiat_preview.amount, BigDecimal::from(5));
+        assert!(fiat_preview.rate.is_none());
+    }
+}
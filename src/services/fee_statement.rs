@@ -0,0 +1,548 @@
+//! FeeStatementService generates and serves monthly statements of platform fees charged to stores
+use std::collections::HashMap;
+use std::time::{Duration as StdDuration, Instant};
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Utc};
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use futures::{future, Future, Stream};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use sentry::integrations::failure::capture_error;
+use serde_json;
+use tokio_timer::Interval;
+use validator::{ValidationError, ValidationErrors};
+
+use failure::{Error as FailureError, Fail};
+
+use stq_types::{StoreId, UserId};
+
+use super::types::ServiceFutureV2;
+use config::FeeStatements as FeeStatementsConfig;
+use controller::requests::GenerateFeeStatementsRequest;
+use controller::responses::{FeeStatementDocumentResponse, FeeStatementResponse};
+use models::order_v2::OrderId;
+use models::{
+    Amount, Currency, Event, EventPayload, Fee, FeeAdjustment, FeeStatementId, FeeStatementLine, FeeStatementLineKind, FeeStatementSearch,
+    FeeStatus, NewFeeStatement,
+};
+use repos::{FeeStatementsRepo, ReposFactory, SearchFeeParams};
+use services::types::spawn_on_pool;
+use services::{Error, ErrorContext, ErrorKind};
+
+pub trait FeeStatementService {
+    /// Generates statements for all stores charged with fees in the given month
+    fn generate_fee_statements(&self, payload: GenerateFeeStatementsRequest) -> ServiceFutureV2<Vec<FeeStatementResponse>>;
+    /// Lists fee statements of a store, newest period first
+    fn get_fee_statements_by_store(&self, store_id: StoreId) -> ServiceFutureV2<Vec<FeeStatementResponse>>;
+    /// Returns a fee statement with all of its lines
+    fn download_fee_statement(&self, fee_statement_id: FeeStatementId) -> ServiceFutureV2<FeeStatementDocumentResponse>;
+}
+
+pub struct FeeStatementServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+    pub config: FeeStatementsConfig,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > FeeStatementService for FeeStatementServiceImpl<T, M, F>
+{
+    fn generate_fee_statements(&self, payload: GenerateFeeStatementsRequest) -> ServiceFutureV2<Vec<FeeStatementResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+        let tax_percent = self.config.tax_percent;
+
+        let now = chrono::offset::Utc::now().naive_utc();
+        let (period_start, period_end) = match statement_period(payload, now) {
+            Ok(period) => period,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let fee_statements_repo = repo_factory.create_fee_statements_repo(&conn, user_id);
+            generate_fee_statements_for_period(&*conn, &repo_factory, fee_statements_repo, period_start, period_end, tax_percent)
+        })
+    }
+
+    fn get_fee_statements_by_store(&self, store_id: StoreId) -> ServiceFutureV2<Vec<FeeStatementResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let fee_statements_repo = repo_factory.create_fee_statements_repo(&conn, user_id);
+
+            let fee_statements = fee_statements_repo
+                .search(FeeStatementSearch::by_store_id(store_id))
+                .map_err(ectx!(try convert => store_id))?;
+
+            Ok(fee_statements.into_iter().map(FeeStatementResponse::from).collect())
+        })
+    }
+
+    fn download_fee_statement(&self, fee_statement_id: FeeStatementId) -> ServiceFutureV2<FeeStatementDocumentResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let fee_statements_repo = repo_factory.create_fee_statements_repo(&conn, user_id);
+
+            let fee_statement = fee_statements_repo
+                .get(FeeStatementSearch::by_id(fee_statement_id))
+                .map_err(ectx!(try convert => fee_statement_id))?
+                .ok_or({
+                    let e = format_err!("Fee statement {} not found", fee_statement_id);
+                    ectx!(try err e, ErrorKind::NotFound)
+                })?;
+
+            FeeStatementDocumentResponse::try_from_fee_statement(fee_statement)
+        })
+    }
+}
+
+/// Generates the statements of every store charged with fees in `[period_start, period_end)`.
+/// Stores that already have a statement for the period in a currency are skipped
+fn generate_fee_statements_for_period<'a, T, F>(
+    conn: &'a T,
+    repo_factory: &F,
+    fee_statements_repo: Box<FeeStatementsRepo + 'a>,
+    period_start: NaiveDateTime,
+    period_end: NaiveDateTime,
+    tax_percent: u64,
+) -> Result<Vec<FeeStatementResponse>, Error>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    F: ReposFactory<T>,
+{
+    let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+    let fee_adjustments_repo = repo_factory.create_fee_adjustments_repo_with_sys_acl(&conn);
+    let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+    let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+    conn.transaction(move || {
+        let fees = fees_repo
+            .search(SearchFeeParams::by_created_at(period_start, period_end))
+            .map_err(ectx!(try convert))?;
+
+        // Only reversals of charged fees are listed, an unpaid fee has been reduced instead
+        let adjustments = fee_adjustments_repo
+            .search(period_start, period_end)
+            .map_err(ectx!(try convert))?
+            .into_iter()
+            .filter(|adjustment| adjustment.fee_charge_id.is_some())
+            .collect::<Vec<_>>();
+
+        let order_ids: Vec<OrderId> = fees
+            .iter()
+            .map(|fee| fee.order_id)
+            .chain(adjustments.iter().map(|adjustment| adjustment.order_id))
+            .collect();
+        let store_ids_by_order: HashMap<OrderId, StoreId> = orders_repo
+            .get_many(&order_ids)
+            .map_err(ectx!(try convert))?
+            .into_iter()
+            .map(|order| (order.id, StoreId(order.store_id.inner())))
+            .collect();
+
+        let mut fees_by_store: HashMap<(StoreId, Currency), (Vec<Fee>, Vec<FeeAdjustment>)> = HashMap::new();
+        for fee in fees {
+            match store_ids_by_order.get(&fee.order_id) {
+                Some(store_id) => fees_by_store
+                    .entry((*store_id, fee.currency))
+                    .or_insert_with(Default::default)
+                    .0
+                    .push(fee),
+                None => warn!("Fee #{} skipped in fee statements: order {} not found", fee.id, fee.order_id),
+            }
+        }
+        for adjustment in adjustments {
+            match store_ids_by_order.get(&adjustment.order_id) {
+                Some(store_id) => fees_by_store
+                    .entry((*store_id, adjustment.currency))
+                    .or_insert_with(Default::default)
+                    .1
+                    .push(adjustment),
+                None => warn!(
+                    "Fee adjustment #{} skipped in fee statements: order {} not found",
+                    adjustment.id, adjustment.order_id
+                ),
+            }
+        }
+
+        let mut fee_statements = Vec::new();
+        for ((store_id, currency), (fees, adjustments)) in fees_by_store {
+            let existing_search = FeeStatementSearch {
+                store_id: Some(store_id),
+                currency: Some(currency),
+                period_start: Some(period_start),
+                ..Default::default()
+            };
+            let existing = fee_statements_repo.get(existing_search).map_err(ectx!(try convert))?;
+            if existing.is_some() {
+                debug!(
+                    "Fee statement for store {} in {} starting {} already exists",
+                    store_id, currency, period_start
+                );
+                continue;
+            }
+
+            let new_fee_statement = create_fee_statement(store_id, currency, period_start, period_end, fees, adjustments, tax_percent)?;
+            let fee_statement = fee_statements_repo.create(new_fee_statement).map_err(ectx!(try convert))?;
+
+            let event = Event::new(EventPayload::FeeStatementGenerated {
+                fee_statement_id: fee_statement.id,
+            });
+            event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+
+            fee_statements.push(FeeStatementResponse::from(fee_statement));
+        }
+
+        Ok(fee_statements)
+    })
+}
+
+/// Generates the statements of the month that has just ended on every tick of `check_interval_sec`.
+/// A failed run is reported and retried on the next tick
+pub fn run_fee_statement_generation<T, M, F>(
+    config: FeeStatementsConfig,
+    db_pool: Pool<M>,
+    cpu_pool: CpuPool,
+    repo_factory: F,
+) -> impl Future<Item = (), Error = FailureError>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    let interval = StdDuration::from_secs(config.check_interval_sec);
+    let tax_percent = config.tax_percent;
+
+    Interval::new(Instant::now(), interval)
+        .map_err(FailureError::from)
+        .for_each(move |_| {
+            let db_pool = db_pool.clone();
+            let cpu_pool = cpu_pool.clone();
+            let repo_factory = repo_factory.clone();
+
+            future::result(statement_period(GenerateFeeStatementsRequest::default(), Utc::now().naive_utc()))
+                .and_then(move |(period_start, period_end)| {
+                    spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                        let fee_statements_repo = repo_factory.create_fee_statements_repo_with_sys_acl(&conn);
+                        generate_fee_statements_for_period(
+                            &*conn,
+                            &repo_factory,
+                            fee_statements_repo,
+                            period_start,
+                            period_end,
+                            tax_percent,
+                        )
+                    })
+                    .map(move |fee_statements| (period_start, fee_statements))
+                })
+                .then(|res| {
+                    match res {
+                        Ok((_, ref fee_statements)) if fee_statements.is_empty() => {}
+                        Ok((period_start, fee_statements)) => {
+                            info!(
+                                "Generated {} fee statements for the month starting {}",
+                                fee_statements.len(),
+                                period_start
+                            );
+                        }
+                        Err(err) => {
+                            let err = FailureError::from(err.context("An error occurred while generating fee statements"));
+                            error!("{:?}", &err);
+                            capture_error(&err);
+                        }
+                    };
+
+                    future::ok::<_, FailureError>(())
+                })
+        })
+}
+
+/// Returns `[start, end)` of the requested month, or of the month preceding `now` if none was requested
+pub fn statement_period(payload: GenerateFeeStatementsRequest, now: NaiveDateTime) -> Result<(NaiveDateTime, NaiveDateTime), Error> {
+    let (year, month) = match (payload.year, payload.month) {
+        (Some(year), Some(month)) => (year, month),
+        (None, None) => {
+            let previous_month = now.date().with_day(1).unwrap_or(now.date()) - Duration::days(1);
+            (previous_month.year(), previous_month.month())
+        }
+        _ => return Err(period_validation_error("Both year and month must be provided")),
+    };
+
+    let start = NaiveDate::from_ymd_opt(year, month, 1).ok_or(period_validation_error("Invalid month"))?;
+    let end = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)
+    }
+    .ok_or(period_validation_error("Invalid month"))?;
+
+    Ok((start.and_hms(0, 0, 0), end.and_hms(0, 0, 0)))
+}
+
+fn period_validation_error(message: &str) -> Error {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new("invalid_period");
+    error.message = Some(message.to_string().into());
+    errors.add("month", error);
+    ectx!(err ErrorContext::FeeStatement, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
+/// Builds a statement out of the fees charged to a store in one currency.
+/// Failed fees are listed and then reversed by an adjustment, as are the fee shares refunded with orders.
+/// Tax is charged on the net amount, which does not go below zero.
+pub fn create_fee_statement(
+    store_id: StoreId,
+    currency: Currency,
+    period_start: NaiveDateTime,
+    period_end: NaiveDateTime,
+    fees: Vec<Fee>,
+    fee_adjustments: Vec<FeeAdjustment>,
+    tax_percent: u64,
+) -> Result<NewFeeStatement, Error> {
+    let mut lines = Vec::new();
+    let mut fees_amount = Amount::zero();
+    let mut adjustments_amount = Amount::zero();
+
+    for fee in fees {
+        let conversion = fee.conversion();
+        fees_amount = fees_amount
+            .checked_add(fee.amount)
+            .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+        lines.push(FeeStatementLine {
+            kind: FeeStatementLineKind::Fee,
+            fee_id: Some(fee.id),
+            order_id: Some(fee.order_id),
+            amount: fee.amount,
+            description: format!("Platform fee for order {}", fee.order_id),
+            created_at: Some(fee.created_at),
+            conversion: conversion.clone(),
+        });
+
+        let reversal = match fee.status {
+            FeeStatus::Fail => Some(format!("Reversal of failed fee #{}", fee.id)),
+            FeeStatus::WrittenOff => Some(format!("Write-off of fee #{} of a closed store", fee.id)),
+            FeeStatus::NotPaid | FeeStatus::Paid => None,
+        };
+
+        if let Some(description) = reversal {
+            adjustments_amount = adjustments_amount
+                .checked_add(fee.amount)
+                .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+            lines.push(FeeStatementLine {
+                kind: FeeStatementLineKind::Adjustment,
+                fee_id: Some(fee.id),
+                order_id: Some(fee.order_id),
+                amount: fee.amount,
+                description,
+                created_at: Some(fee.updated_at),
+                conversion,
+            });
+        }
+    }
+
+    for adjustment in fee_adjustments {
+        adjustments_amount = adjustments_amount
+            .checked_add(adjustment.fee_amount)
+            .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+        lines.push(FeeStatementLine {
+            kind: FeeStatementLineKind::Adjustment,
+            fee_id: Some(adjustment.fee_id),
+            order_id: Some(adjustment.order_id),
+            amount: adjustment.fee_amount,
+            description: format!("Refund of fee #{} for refunded order {}", adjustment.fee_id, adjustment.order_id),
+            created_at: Some(adjustment.created_at),
+            conversion: None,
+        });
+    }
+
+    let hundred_percents = 100u64;
+
+    let net_amount = fees_amount.checked_sub(adjustments_amount).unwrap_or_else(Amount::zero);
+    // multiplied first, so that the remainder of the net amount is taxed too
+    let taxes_amount = net_amount
+        .checked_mul(Amount::from(tax_percent))
+        .and_then(|amount| amount.checked_div(Amount::from(hundred_percents)))
+        .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+    if taxes_amount > Amount::zero() {
+        lines.push(FeeStatementLine {
+            kind: FeeStatementLineKind::Tax,
+            fee_id: None,
+            order_id: None,
+            amount: taxes_amount,
+            description: format!("Tax {}%", tax_percent),
+            created_at: None,
+            conversion: None,
+        });
+    }
+    let total_amount = net_amount
+        .checked_add(taxes_amount)
+        .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+
+    let lines = serde_json::to_value(lines).map_err(|e| ectx!(err e, ErrorKind::Internal))?;
+
+    Ok(NewFeeStatement {
+        store_id,
+        currency,
+        period_start,
+        period_end,
+        fees_amount,
+        adjustments_amount,
+        taxes_amount,
+        total_amount,
+        lines,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use uuid::Uuid;
+
+    use stq_types::StoreId;
+
+    use controller::requests::GenerateFeeStatementsRequest;
+    use models::order_v2::OrderId;
+    use models::*;
+
+    use super::{create_fee_statement, statement_period};
+
+    fn fee(id: i32, amount: u128, status: FeeStatus) -> Fee {
+        let created_at = NaiveDate::from_ymd(2019, 2, 10).and_hms(12, 0, 0);
+        Fee {
+            id: FeeId::new(id),
+            order_id: OrderId::new(Uuid::new_v4()),
+            amount: Amount::new(amount),
+            status,
+            currency: Currency::Eur,
+            charge_id: None,
+            metadata: None,
+            created_at,
+            updated_at: created_at,
+            crypto_currency: None,
+            crypto_amount: None,
+            exchange_rate: None,
+            currency_exchange_id: None,
+            converted_at: None,
+            charged_currency: None,
+            charged_amount: None,
+            charge_exchange_rate: None,
+        }
+    }
+
+    #[test]
+    fn statement_period_defaults_to_previous_month() {
+        let now = NaiveDate::from_ymd(2019, 1, 15).and_hms(10, 0, 0);
+        let (start, end) = statement_period(GenerateFeeStatementsRequest::default(), now).unwrap();
+        assert_eq!(start, NaiveDate::from_ymd(2018, 12, 1).and_hms(0, 0, 0));
+        assert_eq!(end, NaiveDate::from_ymd(2019, 1, 1).and_hms(0, 0, 0));
+
+        let payload = GenerateFeeStatementsRequest {
+            year: Some(2019),
+            month: Some(13),
+        };
+        assert!(statement_period(payload, now).is_err());
+    }
+
+    #[test]
+    fn fee_statement_totals() {
+        let period_start = NaiveDate::from_ymd(2019, 2, 1).and_hms(0, 0, 0);
+        let period_end = NaiveDate::from_ymd(2019, 3, 1).and_hms(0, 0, 0);
+        let fees = vec![
+            fee(1, 1000, FeeStatus::Paid),
+            fee(2, 500, FeeStatus::NotPaid),
+            fee(3, 300, FeeStatus::Fail),
+        ];
+
+        let statement = create_fee_statement(StoreId(1), Currency::Eur, period_start, period_end, fees, vec![], 20).unwrap();
+
+        assert_eq!(statement.fees_amount, Amount::new(1800));
+        assert_eq!(statement.adjustments_amount, Amount::new(300));
+        assert_eq!(statement.taxes_amount, Amount::new(300));
+        assert_eq!(statement.total_amount, Amount::new(1800));
+        assert_eq!(statement.lines.as_array().map(|lines| lines.len()), Some(5));
+    }
+
+    #[test]
+    fn fee_statement_taxes_the_whole_net_amount() {
+        let period_start = NaiveDate::from_ymd(2019, 2, 1).and_hms(0, 0, 0);
+        let period_end = NaiveDate::from_ymd(2019, 3, 1).and_hms(0, 0, 0);
+        let fees = vec![fee(1, 1999, FeeStatus::Paid)];
+
+        let statement = create_fee_statement(StoreId(1), Currency::Eur, period_start, period_end, fees, vec![], 20).unwrap();
+
+        assert_eq!(statement.taxes_amount, Amount::new(399));
+        assert_eq!(statement.total_amount, Amount::new(2398));
+    }
+
+    #[test]
+    fn fee_statement_reverses_written_off_fees() {
+        let period_start = NaiveDate::from_ymd(2019, 2, 1).and_hms(0, 0, 0);
+        let period_end = NaiveDate::from_ymd(2019, 3, 1).and_hms(0, 0, 0);
+        let fees = vec![fee(1, 1000, FeeStatus::Paid), fee(2, 500, FeeStatus::WrittenOff)];
+
+        let statement = create_fee_statement(StoreId(1), Currency::Eur, period_start, period_end, fees, vec![], 20).unwrap();
+
+        assert_eq!(statement.fees_amount, Amount::new(1500));
+        assert_eq!(statement.adjustments_amount, Amount::new(500));
+        assert_eq!(statement.taxes_amount, Amount::new(200));
+        assert_eq!(statement.total_amount, Amount::new(1200));
+        assert_eq!(statement.lines.as_array().map(|lines| lines.len()), Some(4));
+    }
+
+    #[test]
+    fn fee_statement_includes_refunded_fees() {
+        let period_start = NaiveDate::from_ymd(2019, 2, 1).and_hms(0, 0, 0);
+        let period_end = NaiveDate::from_ymd(2019, 3, 1).and_hms(0, 0, 0);
+        let paid_fee = fee(1, 1000, FeeStatus::Paid);
+        let adjustment = FeeAdjustment {
+            id: FeeAdjustmentId::new(1),
+            fee_id: paid_fee.id,
+            order_id: paid_fee.order_id,
+            currency: Currency::Eur,
+            refund_amount: Amount::new(20000),
+            seller_amount: Amount::new(19600),
+            fee_amount: Amount::new(400),
+            fee_charge_id: Some(ChargeId::new("ch_1".to_string())),
+            created_at: period_start,
+            charged_currency: Some(Currency::Eur),
+            charged_fee_amount: Some(Amount::new(400)),
+        };
+
+        let statement = create_fee_statement(
+            StoreId(1),
+            Currency::Eur,
+            period_start,
+            period_end,
+            vec![paid_fee, fee(2, 500, FeeStatus::NotPaid)],
+            vec![adjustment],
+            20,
+        )
+        .unwrap();
+
+        assert_eq!(statement.fees_amount, Amount::new(1500));
+        assert_eq!(statement.adjustments_amount, Amount::new(400));
+        assert_eq!(statement.taxes_amount, Amount::new(220));
+        assert_eq!(statement.total_amount, Amount::new(1320));
+        assert_eq!(statement.lines.as_array().map(|lines| lines.len()), Some(4));
+    }
+}
@@ -0,0 +1,123 @@
+//! HistoricalReportService reconstructs the states of invoices, fees and payouts as of a past time for audits.
+//! Reports as of times that can no longer change are kept, so repeated queries are answered the same way
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use serde_json;
+use validator::{ValidationError, ValidationErrors};
+
+use stq_types::UserId;
+
+use super::error::{Error as ServiceError, ErrorContext, ErrorKind};
+use super::types::ServiceFutureV2;
+use controller::responses::{HistoricalReportResponse, HistoricalStateTotalResponse};
+use models::NewHistoricalReport;
+use repos::ReposFactory;
+use services::types::spawn_on_pool;
+
+/// Events of the last hour may still be processed and stamped with earlier times, reports as of them are not kept
+const SETTLEMENT_PERIOD_MIN: i64 = 60;
+
+pub trait HistoricalReportService {
+    /// Invoices, fees and payouts by their states as of the given time
+    fn get_historical_report(&self, as_of: Option<NaiveDateTime>) -> ServiceFutureV2<HistoricalReportResponse>;
+}
+
+pub struct HistoricalReportServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > HistoricalReportService for HistoricalReportServiceImpl<T, M, F>
+{
+    fn get_historical_report(&self, as_of: Option<NaiveDateTime>) -> ServiceFutureV2<HistoricalReportResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let historical_reports_repo = repo_factory.create_historical_reports_repo(&conn, user_id);
+            let historical_reports_repo_with_sys_acl = repo_factory.create_historical_reports_repo_with_sys_acl(&conn);
+
+            let now = Utc::now().naive_utc();
+            let as_of = as_of.ok_or(historical_report_validation_error("required", "as_of is required"))?;
+            if as_of > now {
+                return Err(historical_report_validation_error("future", "as_of is in the future"));
+            }
+
+            let report = historical_reports_repo.get(as_of).map_err(ectx!(try convert => as_of))?;
+            if let Some(report) = report {
+                return HistoricalReportResponse::try_from_historical_report(report);
+            }
+
+            let invoices = historical_reports_repo
+                .get_invoice_states(as_of)
+                .map_err(ectx!(try convert => as_of))?;
+            let fees = historical_reports_repo.get_fee_states(as_of).map_err(ectx!(try convert => as_of))?;
+            let payouts = historical_reports_repo
+                .get_payout_states(as_of)
+                .map_err(ectx!(try convert => as_of))?;
+
+            if !is_settled(as_of, now) {
+                return Ok(HistoricalReportResponse {
+                    as_of,
+                    invoices: invoices.into_iter().map(HistoricalStateTotalResponse::from).collect(),
+                    fees: fees.into_iter().map(HistoricalStateTotalResponse::from).collect(),
+                    payouts: payouts.into_iter().map(HistoricalStateTotalResponse::from).collect(),
+                    generated_at: now,
+                });
+            }
+
+            let payload = NewHistoricalReport::new(as_of, &invoices, &fees, &payouts).map_err(|e| ectx!(try err e, ErrorKind::Internal))?;
+            // Reading the report has been authorized above, keeping it is up to the service
+            let report = historical_reports_repo_with_sys_acl
+                .create(payload.clone())
+                .map_err(ectx!(try convert => payload))?;
+
+            HistoricalReportResponse::try_from_historical_report(report)
+        })
+    }
+}
+
+/// Whether nothing recorded from `now` on can change the report as of `as_of`
+pub fn is_settled(as_of: NaiveDateTime, now: NaiveDateTime) -> bool {
+    as_of <= now - Duration::minutes(SETTLEMENT_PERIOD_MIN)
+}
+
+fn historical_report_validation_error(code: &'static str, message: &str) -> ServiceError {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.to_string().into());
+    errors.add("as_of", error);
+    ectx!(err ErrorContext::HistoricalReport, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn reports_as_of_the_last_hour_are_not_settled() {
+        let now = NaiveDate::from_ymd(2019, 4, 1).and_hms(12, 0, 0);
+
+        assert!(is_settled(NaiveDate::from_ymd(2019, 3, 31).and_hms(23, 59, 59), now));
+        assert!(is_settled(NaiveDate::from_ymd(2019, 4, 1).and_hms(11, 0, 0), now));
+        assert!(!is_settled(NaiveDate::from_ymd(2019, 4, 1).and_hms(11, 30, 0), now));
+    }
+}
@@ -1,57 +1,93 @@
 //! Invoices Services, presents CRUD operations with invoices
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
 
 use bigdecimal::BigDecimal;
-use chrono::{Duration, Utc};
+use chrono::{Duration, NaiveDateTime, Utc};
 use diesel::connection::AnsiTransactionManager;
 use diesel::pg::Pg;
 use diesel::Connection;
 use failure::{err_msg, Error as FailureError, Fail};
 use futures::{future, stream, Future, IntoFuture, Stream};
+use futures_cpupool::CpuPool;
 use hyper::header::{Authorization, Bearer, ContentType};
 use hyper::Headers;
 use hyper::Post;
 use models::invoice_v2::InvoiceSetAmountPaid;
 use models::invoice_v2::RawInvoice;
-use r2d2::ManageConnection;
-use secp256k1::{Message, PublicKey, Secp256k1, Signature};
+use r2d2::{ManageConnection, Pool};
 use serde_json;
-use sha2::digest::Digest;
-use sha2::Sha256;
 use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
 
 use stq_http::client::HttpClient;
 use stq_http::request_util::Sign as TureSignature;
+use stq_static_resources::OrderState;
 use stq_types::stripe::PaymentIntentId;
-use stq_types::{InvoiceId, OrderId, SagaId};
+use stq_types::{Alpha3, BillingRole, InvoiceId, OrderId, SagaId};
 
+use client::fiat_payments::{CaptureMethod, FiatPaymentProvider, NewFiatPaymentIntent};
 use client::payments::{GetRate, PaymentsClient, Rate, RateRefresh};
 use client::stores::CurrencyExchangeInfo;
-use client::stripe::{NewPaymentIntent as StripeClientNewPaymentIntent, StripeClient};
-use config::ExternalBilling;
+use config::{ExternalBilling, PaymentExpiry};
 use controller::context::DynamicContext;
+use controller::requests::{
+    CreateStoreInvoiceRequest, InvoiceRequoteRequest, SimulateInboundTxRequest, SimulatedPaymentKind, UpdateInvoiceDetailsRequest,
+};
+use controller::responses::{
+    InboundTransactionResponse, InvoiceRequoteResponse, PublicInvoiceStatusResponse, SimulatedInboundTxResponse, StoreInvoiceResponse,
+};
+use controller::routes::{CHECKOUT_SESSIONS_ENDPOINT, PAYMENTS_CALLBACK_ENDPOINT};
 use errors::Error;
-use models::invoice_v2::{calculate_invoice_price, InvoiceDump, InvoiceId as InvoiceV2Id, NewInvoice, RawInvoice as InvoiceV2};
-use models::order_v2::{ExchangeId, NewOrder, OrderId as OrderV2Id, RawOrder};
+use models::invoice_v2::{
+    calculate_invoice_price, generate_public_token, receipt_description, InvoiceDump, InvoiceId as InvoiceV2Id, NewInvoice, PaymentFlow,
+    RawInvoice as InvoiceV2, UpdateInvoiceDetails,
+};
+use models::order_v2::{ExchangeId, NewOrder, OrderId as OrderV2Id, RawOrder, StoreId as StoreV2Id};
 use models::*;
 use repos::error::ErrorKind as RepoErrorKind;
 use repos::repo_factory::ReposFactory;
 use repos::{
-    AccountsRepo, EventStoreRepo, InvoicesV2Repo, OrderExchangeRatesRepo, OrdersRepo, PaymentIntentInvoiceRepo, PaymentIntentRepo,
+    user_is_store_manager, AccountsRepo, EventStoreRepo, ExchangeRateSlippagesRepo, InvoiceSnapshotsRepo, InvoicesV2Repo,
+    OrderExchangeRatesRepo, OrdersRepo, PaymentIntentInvoiceRepo, PaymentIntentRepo, SearchCustomer, SearchPaymentIntent,
     SearchPaymentIntentInvoice,
 };
 use services::accounts::AccountService;
+use services::analytics::{enqueue_analytics_event, invoice_analytics_data};
+use services::cashback::apply_cashback_limits;
+use services::exchange_rate_slippage::record_exchange_rate_slippages;
+use services::fee_crypto_payment::credit_fee_crypto_payment;
+use services::invoice_callback::validate_invoice_callback;
+use services::invoice_risk::{assess_invoice_risk, invoice_risk_denied_error};
+use services::payment_intent::{cancel_payment_intent, record_payment_intent_status};
+use services::payment_method::allowed_currencies;
+use services::payment_recovery::close_payment_recovery;
+use services::saga::enqueue_order_state_updates;
 use services::types::spawn_on_pool;
+use services::user_credit::{
+    apply_invoice_credit, credit_to_apply, invoice_credit_applied, restore_invoice_credit, user_credit_validation_error,
+};
 use services::Service;
+use signature::{sign_ture_body, verify_ture_signature, SignatureError};
 
 use super::error::{Error as ServiceError, ErrorContext, ErrorKind};
 use super::types::{ServiceFuture, ServiceFutureV2};
 
+const INVOICE_MEMO_MAX_LENGTH: usize = 1000;
+const INVOICE_PO_NUMBER_MAX_LENGTH: usize = 64;
+const BUYER_BILLING_DETAILS_MAX_LENGTH: usize = 256;
+
 pub trait InvoiceService {
     /// Creates invoice in billing system
     fn create_invoice(&self, create_invoice: CreateInvoice) -> ServiceFuture<Invoice>;
     fn create_invoice_v2(&self, create_invoice: CreateInvoiceV2) -> ServiceFutureV2<InvoiceDump>;
+    /// Creates invoice a store bills its customer with, returning the link to share with the customer
+    fn create_store_invoice(&self, store_id: StoreV2Id, payload: CreateStoreInvoiceRequest) -> ServiceFutureV2<StoreInvoiceResponse>;
+    /// Replaces an expired invoice with a new one priced at the current rates and with a new payment target
+    fn requote_invoice(&self, id: InvoiceV2Id, payload: InvoiceRequoteRequest) -> ServiceFutureV2<InvoiceRequoteResponse>;
+    /// Cancels an invoice the buyer has not paid yet, releasing its payment intent or account
+    fn cancel_invoice(&self, id: InvoiceV2Id) -> ServiceFutureV2<InvoiceDump>;
     /// Get invoice by order id
     fn get_invoice_by_order_id(&self, order_id: OrderId) -> ServiceFuture<Option<Invoice>>;
     fn get_invoice_by_order_id_v1(&self, order_id: OrderId) -> ServiceFuture<Option<Invoice>>;
@@ -65,6 +101,17 @@ pub trait InvoiceService {
     fn recalc_invoice(&self, id: InvoiceId) -> ServiceFuture<Invoice>;
     fn recalc_invoice_v1(&self, id: InvoiceId) -> ServiceFuture<Invoice>;
     fn recalc_invoice_v2(&self, id: InvoiceV2Id) -> ServiceFutureV2<Option<InvoiceDump>>;
+    /// Updates memo and PO number of an invoice that has not been paid yet
+    fn update_invoice_details_v2(&self, id: InvoiceV2Id, payload: UpdateInvoiceDetailsRequest) -> ServiceFutureV2<Option<InvoiceDump>>;
+    /// Get checkout session by invoice id, reflecting live payment progress of the invoice
+    fn get_checkout_session(&self, id: InvoiceV2Id) -> ServiceFutureV2<Option<CheckoutSession>>;
+    /// Builds checkout session for the invoice without refreshing its price
+    fn checkout_session_for_invoice(&self, invoice: InvoiceDump) -> ServiceFutureV2<CheckoutSession>;
+    /// Get minimal status of the invoice by its public token, without authentication.
+    /// The price is not refreshed, so polling it never reaches Payments gateway
+    fn get_public_invoice_status(&self, public_token: String) -> ServiceFutureV2<Option<PublicInvoiceStatusResponse>>;
+    /// Inbound transactions credited to the account of a crypto invoice, including the ones too small to pay it
+    fn get_invoice_transactions(&self, id: InvoiceV2Id) -> ServiceFutureV2<Option<Vec<InboundTransactionResponse>>>;
     /// Get orders ids by invoice id
     fn get_invoice_orders_ids(&self, id: InvoiceId) -> ServiceFuture<Vec<OrderId>>;
     fn get_invoice_orders_ids_v1(&self, id: InvoiceId) -> ServiceFuture<Vec<OrderId>>;
@@ -78,6 +125,11 @@ pub trait InvoiceService {
     fn update_invoice(&self, invoice: ExternalBillingInvoice) -> ServiceFuture<()>;
     /// Handles the callback from Payments gateway which carries a new inbound transaction
     fn handle_inbound_tx(&self, signature_header: TureSignature, callback: PaymentsCallback, callback_body: String) -> ServiceFutureV2<()>;
+    /// Handles the callback given the hex signature of its body
+    fn handle_signed_inbound_tx(&self, signature: String, callback: PaymentsCallback, callback_body: String) -> ServiceFutureV2<()>;
+    /// Sends a signed callback with a fabricated transaction to the crypto invoice, exercising the whole inbound transaction path.
+    /// Superusers only, available when the payments sandbox is enabled
+    fn simulate_inbound_tx(&self, id: InvoiceV2Id, payload: SimulateInboundTxRequest) -> ServiceFutureV2<SimulatedInboundTxResponse>;
     /// Get missing rates from Payments gateway and refresh existing rates
     fn get_missing_rates_from_payments_gateway_and_refresh_existing_rates(
         &self,
@@ -122,55 +174,48 @@ impl<
     }
 
     fn create_invoice_v2(&self, create_invoice: CreateInvoiceV2) -> ServiceFutureV2<InvoiceDump> {
-        let repo_factory = self.static_context.repo_factory.clone();
-        let DynamicContext {
-            user_id,
-            payments_client,
-            account_service,
-            ..
-        } = self.dynamic_context.clone();
-
-        let (payments_client, account_service) = if let (Some(payments_client), Some(account_service)) = (payments_client, account_service)
-        {
-            (payments_client, account_service)
-        } else {
-            let e = err_msg("payments integration has not been configured");
-            return Box::new(future::err::<_, ServiceError>(ectx!(err e, ErrorKind::Internal)));
-        };
-
         let CreateInvoiceV2 {
             orders,
             customer_id: buyer_user_id,
             currency: buyer_currency,
             saga_id: invoice_id,
+            metadata,
+            memo,
+            po_number,
+            buyer_country,
+            callback,
+            buyer_ip,
+            apply_credit,
+            buyer_billing_details,
         } = create_invoice;
 
-        let db_pool = self.static_context.db_pool.clone();
-        let cpu_pool = self.static_context.cpu_pool.clone();
-
-        let stripe_client = self.static_context.stripe_client.clone();
+        let orders = match apply_cashback_limits(&self.static_context.config.cashback, orders) {
+            Ok(orders) => orders,
+            Err(e) => return Box::new(future::err(e)),
+        };
 
-        let fut = stream::iter_ok::<_, ServiceError>(orders.into_iter().map(move |order| (payments_client.clone(), order)))
-            .and_then(move |(payments_client, create_order)| {
-                // process each order individually
+        let orders = orders
+            .into_iter()
+            .map(|create_order| {
                 let CreateOrderV2 {
                     id,
                     store_id,
                     currency: seller_currency,
                     total_amount: seller_total_amount,
                     product_cashback: seller_cashback_percent,
+                    line_items,
                 } = create_order;
 
-                let total_amount = Amount::from_super_unit(seller_currency, BigDecimal::from(seller_total_amount));
+                let total_amount = order_amount(id, seller_currency, seller_total_amount, "total_amount")?;
                 let cashback_amount = match seller_cashback_percent {
                     None => Amount::new(0),
-                    Some(cashback_fraction) => Amount::from_super_unit(
-                        seller_currency,
-                        BigDecimal::from(seller_total_amount) * BigDecimal::from(cashback_fraction),
-                    ),
+                    Some(cashback_fraction) => {
+                        order_amount(id, seller_currency, seller_total_amount * cashback_fraction, "product_cashback")?
+                    }
                 };
+                let line_items = validate_order_line_items(id, seller_currency, total_amount, line_items)?;
 
-                let new_order = NewOrder {
+                let order = NewOrder {
                     id,
                     seller_currency,
                     total_amount,
@@ -178,108 +223,385 @@ impl<
                     invoice_id: invoice_id.clone(),
                     store_id,
                 };
-
-                match (buyer_currency.is_fiat(), seller_currency.is_fiat()) {
-                    (true, true) => exchage_rate_fiat(new_order, buyer_currency, seller_currency),
-                    (false, false) => exchage_rate_crypto(payments_client, new_order, buyer_currency, seller_currency, total_amount),
-                    _ => {
-                        let e = err_msg("fiat - crypto payments are not supported yet");
-                        Box::new(future::err::<_, ServiceError>(ectx!(err e, ErrorKind::Internal)))
-                    }
-                }
+                Ok((order, line_items))
             })
-            .collect()
-            .and_then(move |orders| {
-                // process collection of orders
-                if buyer_currency.is_fiat() {
-                    future::Either::A(
-                        create_payment_intent(stripe_client, &orders, invoice_id, buyer_currency)
-                            .map(|new_payment_intent| (None, None, Some(new_payment_intent), orders)),
-                    )
-                } else {
-                    future::Either::B(to_ture_currency(buyer_currency).and_then(move |buyer_currency| {
-                        account_service
-                            .get_or_create_free_pooled_account(buyer_currency)
-                            .map_err(ectx!(convert => buyer_currency))
-                            .map(|account| (Some(account.id), Some(account.wallet_address), None, orders))
-                    }))
+            .collect::<Result<Vec<_>, ServiceError>>();
+        let (orders, line_items): (Vec<_>, Vec<_>) = match orders {
+            Ok(orders) => orders.into_iter().unzip(),
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let line_items = line_items.into_iter().flatten().collect();
+
+        let invoice = InvoiceDraft {
+            id: invoice_id,
+            buyer_user_id,
+            buyer_currency,
+            buyer_country,
+            metadata,
+            memo,
+            po_number,
+            callback,
+            requoted_from: None,
+            apply_credit,
+            line_items,
+            buyer_billing_details,
+        };
+
+        let risk_config = self.static_context.config.invoice_risk.clone();
+        if !risk_config.enabled {
+            return self.create_invoice_with_orders(invoice, orders, InvoiceIssuer::Buyer);
+        }
+
+        let db_pool = self.static_context.db_pool.clone();
+        let cpu_pool = self.static_context.cpu_pool.clone();
+        let repo_factory = self.static_context.repo_factory.clone();
+        let risk_evaluator = self.static_context.risk_evaluator.clone();
+        let amounts = orders
+            .iter()
+            .map(|order| (order.seller_currency, order.total_amount))
+            .collect::<Vec<_>>();
+
+        // buyers have no access to the assessments, they are recorded with the system ACL
+        let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let invoice_risk_assessments_repo = repo_factory.create_invoice_risk_assessments_repo_with_sys_acl(&conn);
+
+            assess_invoice_risk(
+                &risk_config,
+                &*risk_evaluator,
+                &*invoice_risk_assessments_repo,
+                invoice_id,
+                buyer_user_id,
+                buyer_ip,
+                amounts,
+            )
+        })
+        .and_then({
+            let self_ = self.clone();
+            move |assessment| -> ServiceFutureV2<InvoiceDump> {
+                match assessment.decision {
+                    RiskDecision::Deny => Box::new(future::err(invoice_risk_denied_error())),
+                    RiskDecision::Allow | RiskDecision::Review => self_.create_invoice_with_orders(invoice, orders, InvoiceIssuer::Buyer),
                 }
-            })
-            .and_then({
-                let payment_expiry = self.static_context.config.payment_expiry.clone();
-                move |(account_id, wallet_address, new_payment_intent, orders)| {
-                    cpu_pool.spawn_fn(move || {
-                        db_pool.get().map_err(ectx!(ErrorKind::Internal)).and_then(move |conn| {
-                            // Add scheduled PaymentExpired event
-                            let payment_expired_event = Event::new(EventPayload::PaymentExpired { invoice_id });
-                            let expiry_timeout = match new_payment_intent {
-                                // use timeout crypto flow
-                                None => Duration::minutes(payment_expiry.crypto_timeout_min as i64),
-                                // use timeout for fiat flow
-                                Some(_) => Duration::minutes(payment_expiry.fiat_timeout_min as i64),
-                            };
-                            let expires_on = Utc::now().naive_utc() + expiry_timeout;
+            }
+        });
 
-                            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
-                            event_store_repo
-                                .add_scheduled_event(payment_expired_event.clone(), expires_on.clone())
-                                .map_err(ectx!(try convert => payment_expired_event, expires_on))?;
+        Box::new(fut)
+    }
 
-                            // Save invoice data to database
-                            let invoices_repo = repo_factory.create_invoices_v2_repo(&conn, user_id);
-                            let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
-                            let order_exchange_rates_repo = repo_factory.create_order_exchange_rates_repo(&conn, user_id);
-                            let payment_intent_repo = repo_factory.create_payment_intent_repo_with_sys_acl(&conn);
-                            let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo_with_sys_acl(&conn);
+    fn create_store_invoice(&self, store_id: StoreV2Id, payload: CreateStoreInvoiceRequest) -> ServiceFutureV2<StoreInvoiceResponse> {
+        let CreateStoreInvoiceRequest {
+            customer_id: buyer_user_id,
+            currency,
+            line_items,
+            memo,
+            po_number,
+            buyer_country,
+            callback,
+        } = payload;
+
+        let total_amount = match validate_store_invoice_line_items(currency, &line_items) {
+            Ok(total_amount) => total_amount,
+            Err(e) => return Box::new(future::err(e)),
+        };
 
-                            conn.transaction::<InvoiceDump, ServiceError, _>(move || {
-                                let invoice = NewInvoice {
-                                    id: invoice_id,
-                                    account_id,
-                                    buyer_currency,
-                                    amount_captured: Amount::new(0u128),
-                                    buyer_user_id,
-                                };
+        let invoice_id = InvoiceV2Id::new(Uuid::new_v4());
+        let order = NewOrder {
+            id: OrderV2Id::new(Uuid::new_v4()),
+            seller_currency: currency,
+            total_amount,
+            cashback_amount: Amount::zero(),
+            invoice_id,
+            store_id,
+        };
 
-                                let invoice = invoices_repo.create(invoice.clone()).map_err(ectx!(try convert => invoice))?;
+        let invoice = InvoiceDraft {
+            id: invoice_id,
+            buyer_user_id,
+            buyer_currency: currency,
+            buyer_country,
+            metadata: Some(serde_json::json!({ "line_items": line_items })),
+            memo,
+            po_number,
+            callback,
+            requoted_from: None,
+            apply_credit: false,
+            line_items: Vec::new(),
+            buyer_billing_details: None,
+        };
 
-                                if let Some((new_payment_intent, new_payment_intent_invoice)) = new_payment_intent {
-                                    payment_intent_repo
-                                        .create(new_payment_intent.clone())
-                                        .map_err(ectx!(try convert => new_payment_intent))?;
+        let db_pool = self.static_context.db_pool.clone();
+        let cpu_pool = self.static_context.cpu_pool.clone();
+        let user_id = self.dynamic_context.user_id;
+        let payment_url = format!(
+            "{}/{}",
+            self.static_context.config.store_invoices.payment_url.trim_end_matches('/'),
+            invoice_id
+        );
 
-                                    payment_intent_invoices_repo
-                                        .create(new_payment_intent_invoice.clone())
-                                        .map_err(ectx!(try convert => new_payment_intent_invoice))?;
-                                }
+        let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            // store invoices are saved with the system ACL, so the manager of the store is checked up front
+            let is_store_manager = user_id
+                .map(|user_id| user_is_store_manager(&*conn, user_id, store_id))
+                .unwrap_or(false);
+            if is_store_manager {
+                Ok(())
+            } else {
+                Err(ectx!(err ErrorContext::Unauthorized, ErrorKind::Forbidden))
+            }
+        })
+        .and_then({
+            let self_ = self.clone();
+            move |_| self_.create_invoice_with_orders(invoice, vec![order], InvoiceIssuer::Store)
+        })
+        .and_then({
+            let self_ = self.clone();
+            move |invoice| {
+                self_
+                    .checkout_session_for_invoice(invoice.clone())
+                    .map(|checkout_session| StoreInvoiceResponse {
+                        invoice,
+                        checkout_session,
+                        payment_url,
+                    })
+            }
+        });
 
-                                let orders_with_rates = orders
-                                    .into_iter()
-                                    .map(|(new_order, exchange_id, exchange_rate)| {
-                                        let order_id = new_order.id;
+        Box::new(fut)
+    }
 
-                                        let order = orders_repo.create(new_order.clone()).map_err(ectx!(try convert => new_order))?;
+    fn requote_invoice(&self, id: InvoiceV2Id, payload: InvoiceRequoteRequest) -> ServiceFutureV2<InvoiceRequoteResponse> {
+        let db_pool = self.static_context.db_pool.clone();
+        let cpu_pool = self.static_context.cpu_pool.clone();
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let InvoiceRequoteRequest { buyer_country } = payload;
 
-                                        let new_rate = NewOrderExchangeRate {
-                                            order_id,
-                                            exchange_id,
-                                            exchange_rate,
-                                        };
+        let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let invoices_repo = repo_factory.create_invoices_v2_repo(&conn, user_id);
+            let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
+            let invoice_callbacks_repo = repo_factory.create_invoice_callbacks_repo_with_sys_acl(&conn);
+            let invoice_requotes_repo = repo_factory.create_invoice_requotes_repo_with_sys_acl(&conn);
+            let order_line_items_repo = repo_factory.create_order_line_items_repo_with_sys_acl(&conn);
+            let invoice_buyer_details_repo = repo_factory.create_invoice_buyer_details_repo_with_sys_acl(&conn);
+
+            let invoice = invoices_repo.get(id).map_err(ectx!(try convert => id))?.ok_or_else(|| {
+                let e = format_err!("Invoice {} not found", id);
+                ectx!(try err e, ErrorKind::NotFound)
+            })?;
 
-                                        let rate = order_exchange_rates_repo
-                                            .add_new_active_rate(new_rate.clone())
-                                            .map_err(ectx!(try convert => new_rate))?;
+            if invoice.paid_at.is_some() || invoice.status != OrderState::AmountExpired {
+                let message = format!("Invoice in status \"{:?}\" has not expired", invoice.status);
+                return Err(invoice_requote_validation_error("invoice_not_expired", message));
+            }
 
-                                        Ok((order, vec![rate.active_rate]))
-                                    })
-                                    .collect::<Result<Vec<_>, ServiceError>>()?;
+            let requote = invoice_requotes_repo
+                .get_by_original_invoice_id(id)
+                .map_err(ectx!(try convert => id))?;
+            if let Some(requote) = requote {
+                let message = format!("Invoice has already been requoted as invoice {}", requote.invoice_id);
+                return Err(invoice_requote_validation_error("invoice_already_requoted", message));
+            }
 
-                                Ok(calculate_invoice_price(invoice, orders_with_rates, wallet_address))
-                            })
-                        })
+            let orders = orders_repo.get_many_by_invoice_id(id).map_err(ectx!(try convert => id))?;
+            let callback = invoice_callbacks_repo.get_by_invoice_id(id).map_err(ectx!(try convert => id))?;
+            let order_ids = orders.iter().map(|order| order.id).collect::<Vec<_>>();
+            let line_items = order_line_items_repo
+                .get_by_order_ids(&order_ids)
+                .map_err(ectx!(try convert => order_ids))?;
+            let buyer_details = invoice_buyer_details_repo.get(id).map_err(ectx!(try convert => id))?;
+
+            Ok((invoice, orders, callback, line_items, buyer_details))
+        })
+        .and_then({
+            let self_ = self.clone();
+            move |(invoice, orders, callback, line_items, buyer_details)| {
+                let invoice_id = InvoiceV2Id::new(Uuid::new_v4());
+                let order_ids = orders
+                    .iter()
+                    .map(|order| (order.id, OrderV2Id::new(Uuid::new_v4())))
+                    .collect::<HashMap<_, _>>();
+                let orders = orders
+                    .into_iter()
+                    .map(|order| NewOrder {
+                        id: order_ids[&order.id],
+                        seller_currency: order.seller_currency,
+                        total_amount: order.total_amount,
+                        cashback_amount: order.cashback_amount,
+                        invoice_id,
+                        store_id: order.store_id,
+                    })
+                    .collect();
+                // the line items are carried over as they were ordered, refunds are made on the orders paid
+                let line_items = line_items
+                    .into_iter()
+                    .map(|line_item| NewOrderLineItem {
+                        id: OrderLineItemId::generate(),
+                        order_id: order_ids[&line_item.order_id],
+                        product_id: line_item.product_id,
+                        name: line_item.name,
+                        quantity: line_item.quantity,
+                        unit_price: line_item.unit_price,
+                    })
+                    .collect();
+
+                let invoice = InvoiceDraft {
+                    id: invoice_id,
+                    buyer_user_id: invoice.buyer_user_id,
+                    buyer_currency: invoice.buyer_currency,
+                    buyer_country,
+                    metadata: invoice.metadata,
+                    memo: invoice.memo,
+                    po_number: invoice.po_number,
+                    callback: callback.map(|InvoiceCallback { url, secret, .. }| InvoiceCallbackRegistration { url, secret }),
+                    requoted_from: Some(id),
+                    apply_credit: false,
+                    line_items,
+                    buyer_billing_details: buyer_details.map(|buyer_details| buyer_details.details),
+                };
+
+                self_.create_invoice_with_orders(invoice, orders, InvoiceIssuer::Buyer)
+            }
+        })
+        .and_then({
+            let self_ = self.clone();
+            move |invoice| {
+                self_
+                    .checkout_session_for_invoice(invoice.clone())
+                    .map(move |checkout_session| InvoiceRequoteResponse {
+                        invoice,
+                        checkout_session,
+                        requoted_from: id,
                     })
+            }
+        });
+
+        Box::new(fut)
+    }
+
+    fn cancel_invoice(&self, id: InvoiceV2Id) -> ServiceFutureV2<InvoiceDump> {
+        let db_pool = self.static_context.db_pool.clone();
+        let cpu_pool = self.static_context.cpu_pool.clone();
+        let repo_factory = self.static_context.repo_factory.clone();
+        let stripe_client = self.static_context.stripe_client.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        let fut = spawn_on_pool(db_pool.clone(), cpu_pool.clone(), {
+            let repo_factory = repo_factory.clone();
+            move |conn| {
+                let invoices_repo = repo_factory.create_invoices_v2_repo(&conn, user_id);
+                let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo_with_sys_acl(&conn);
+                let user_credits_repo = repo_factory.create_user_credits_repo_with_sys_acl(&conn);
+
+                let invoice = invoices_repo.get(id).map_err(ectx!(try convert => id))?.ok_or_else(|| {
+                    let e = format_err!("Invoice {} not found", id);
+                    ectx!(try err e, ErrorKind::NotFound)
+                })?;
+
+                if invoice.paid_at.is_some() || invoice.status != OrderState::PaymentAwaited {
+                    let message = format!("Invoice in status \"{:?}\" can not be cancelled", invoice.status);
+                    return Err(invoice_cancel_validation_error("invoice_not_cancellable", message));
                 }
-            });
+
+                // credit applied at checkout is captured up front and given back on cancellation
+                let credit_applied = invoice_credit_applied(&*user_credits_repo, id)?;
+                let amounts_received = invoices_repo.list_amounts_received(id).map_err(ectx!(try convert => id))?;
+                if invoice.amount_captured > credit_applied || !amounts_received.is_empty() {
+                    let message = "Invoice has already received funds".to_string();
+                    return Err(invoice_cancel_validation_error("funds_captured", message));
+                }
+
+                let payment_intent_id = match invoice.payment_flow() {
+                    PaymentFlow::Crypto => None,
+                    PaymentFlow::Fiat => payment_intent_invoices_repo
+                        .get(SearchPaymentIntentInvoice::InvoiceId(id))
+                        .map_err(ectx!(try convert => id))?
+                        .map(|payment_intent_invoice| payment_intent_invoice.payment_intent_id),
+                };
+
+                Ok((invoice, payment_intent_id))
+            }
+        })
+        // Stripe knows of a captured payment before its webhook reaches the billing
+        .and_then({
+            let stripe_client = stripe_client.clone();
+            move |(invoice, payment_intent_id)| match payment_intent_id {
+                None => future::Either::A(future::ok((invoice, false))),
+                Some(payment_intent_id) => future::Either::B(
+                    stripe_client
+                        .get_payment_intent(payment_intent_id.clone())
+                        .map_err(ectx!(convert => payment_intent_id))
+                        .and_then(move |payment_intent| match PaymentIntentStatus::from(payment_intent.status) {
+                            PaymentIntentStatus::Processing | PaymentIntentStatus::Succeeded => {
+                                let message = "Payment of the invoice has already been captured".to_string();
+                                Err(invoice_cancel_validation_error("funds_captured", message))
+                            }
+                            _ => Ok((invoice, true)),
+                        }),
+                ),
+            }
+        })
+        .and_then({
+            let db_pool = db_pool.clone();
+            let cpu_pool = cpu_pool.clone();
+            let repo_factory = repo_factory.clone();
+            move |(invoice, has_payment_intent)| {
+                if has_payment_intent {
+                    future::Either::A(cancel_payment_intent(db_pool, cpu_pool, stripe_client, repo_factory, id).map(move |_| invoice))
+                } else {
+                    future::Either::B(future::ok(invoice))
+                }
+            }
+        })
+        .and_then(move |invoice| {
+            spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                let invoices_repo = repo_factory.create_invoices_v2_repo(&conn, user_id);
+                let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
+                let rates_repo = repo_factory.create_order_exchange_rates_repo(&conn, user_id);
+                let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
+                let invoice_snapshots_repo = repo_factory.create_invoice_snapshots_repo_with_sys_acl(&conn);
+                let account_assignments_repo = repo_factory.create_account_assignments_repo_with_sys_acl(&conn);
+                let payment_recoveries_repo = repo_factory.create_payment_recoveries_repo_with_sys_acl(&conn);
+                let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+                let user_credits_repo = repo_factory.create_user_credits_repo_with_sys_acl(&conn);
+
+                let orders = orders_repo.get_many_by_invoice_id(id).map_err(ectx!(try convert => id))?;
+                let order_state_updates = orders
+                    .into_iter()
+                    .map(|order| OrderStateUpdate {
+                        order_id: order.id,
+                        store_id: order.store_id,
+                        customer_id: invoice.buyer_user_id.clone(),
+                        status: OrderState::Cancelled,
+                        metadata: invoice.metadata.clone(),
+                    })
+                    .collect::<Vec<_>>();
+
+                conn.transaction(|| {
+                    // Nothing was paid to the account, so it goes to quarantine right away instead of being drained
+                    if let Some(account_id) = invoice.account_id {
+                        invoices_repo.unlink_account(id).map_err(ectx!(try convert => id))?;
+
+                        let released_at = Utc::now().naive_utc();
+                        account_assignments_repo
+                            .release(account_id, released_at)
+                            .map_err(ectx!(try convert => account_id, released_at))?;
+                    }
+
+                    let invoice = invoices_repo
+                        .set_status(id, OrderState::Cancelled)
+                        .map_err(ectx!(try convert => id))?;
+
+                    enqueue_order_state_updates(&*event_store_repo, order_state_updates)?;
+                    restore_invoice_credit(&*user_credits_repo, id)?;
+
+                    if let PaymentFlow::Fiat = invoice.payment_flow() {
+                        close_payment_recovery(&*payment_recoveries_repo, id, PaymentRecoveryStatus::Cancelled)?;
+                    }
+
+                    get_invoice_price(&*orders_repo, &*rates_repo, &*accounts_repo, &*invoice_snapshots_repo, invoice)
+                })
+            })
+        });
 
         Box::new(fut)
     }
@@ -426,11 +748,11 @@ impl<
             ..
         } = self.static_context.config.external_billing.clone();
         let credentials = ExternalBillingCredentials::new(username, password);
-        let saga_url = self.static_context.config.saga_addr.url.clone();
 
         self.spawn_on_pool(move |conn| {
             let invoice_repo = repo_factory.create_invoice_repo(&conn, user_id);
             let order_info_repo = repo_factory.create_order_info_repo(&conn, user_id);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
 
             conn.transaction::<Invoice, FailureError, _>(move || {
                 debug!("Recalculating invoice with id: {}", &id);
@@ -464,16 +786,8 @@ impl<
                         order_info_repo
                             .update_status(invoice.id, invoice.state)
                             .and_then(|orders| {
-                                let body = serde_json::to_string(&orders)?;
-                                let url = format!("{}/orders/update_state", saga_url);
-                                client
-                                    .request_json::<()>(Post, url, Some(body), None)
-                                    .map_err(|e| {
-                                        e.context("Occured an error during setting orders new status in saga.")
-                                            .context(Error::HttpClient)
-                                            .into()
-                                    })
-                                    .wait()
+                                let order_states = orders.iter().map(OrderStateUpdate::from).collect();
+                                enqueue_order_state_updates(&*event_store_repo, order_states).map_err(FailureError::from)
                             })
                             .map(|_| invoice)
                     })
@@ -571,6 +885,8 @@ impl<
                                 let rates_repo = repo_factory.create_order_exchange_rates_repo(&conn, user_id);
                                 let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
                                 let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+                                let exchange_rate_slippages_repo = repo_factory.create_exchange_rate_slippages_repo_with_sys_acl(&conn);
+                                let invoice_snapshots_repo = repo_factory.create_invoice_snapshots_repo_with_sys_acl(&conn);
 
                                 calculate_invoice_price_and_set_final_price_if_paid(
                                     &*conn,
@@ -579,6 +895,8 @@ impl<
                                     &*rates_repo,
                                     &*accounts_repo,
                                     &*event_store_repo,
+                                    &*exchange_rate_slippages_repo,
+                                    &*invoice_snapshots_repo,
                                     invoice.id.clone(),
                                 )
                             })
@@ -593,40 +911,235 @@ impl<
         Box::new(fut)
     }
 
-    /// Get orders ids by invoice id
+    fn update_invoice_details_v2(&self, id: InvoiceV2Id, payload: UpdateInvoiceDetailsRequest) -> ServiceFutureV2<Option<InvoiceDump>> {
+        if let Err(e) = validate_invoice_details(payload.memo.as_ref(), payload.po_number.as_ref()) {
+            return Box::new(future::err(e));
+        }
 
-    fn get_invoice_orders_ids(&self, id: InvoiceId) -> ServiceFuture<Vec<OrderId>> {
-        let v2_handler = if self.payments_v2_enabled() {
-            future::Either::A(self.get_invoice_orders_ids_v2(InvoiceV2Id::new(id.0)).map_err(FailureError::from))
-        } else {
-            future::Either::B(future::ok(vec![]))
-        };
+        let db_pool = self.static_context.db_pool.clone();
+        let cpu_pool = self.static_context.cpu_pool.clone();
+        let repo_factory = self.static_context.repo_factory.clone();
+        let stripe_client = self.static_context.stripe_client.clone();
+        let user_id = self.dynamic_context.user_id;
 
-        let fut = Future::join(self.get_invoice_orders_ids_v1(id), v2_handler).and_then(move |(order_ids_v1, order_ids_v2)| {
-            match (order_ids_v1.is_empty(), order_ids_v2.is_empty()) {
-                (false, false) => Err(format_err!("Invoice with ID: {} is stored both in v1 and v2 tables", id)),
-                (false, true) => Ok(order_ids_v1),
-                (true, false) => Ok(order_ids_v2.into_iter().map(|id| OrderId(id.into_inner())).collect()),
-                (true, true) => Ok(vec![]),
+        let update_invoice_details = UpdateInvoiceDetails::from(payload);
+
+        let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let invoices_repo = repo_factory.create_invoices_v2_repo(&conn, user_id);
+            let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
+            let rates_repo = repo_factory.create_order_exchange_rates_repo(&conn, user_id);
+            let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
+            let invoice_snapshots_repo = repo_factory.create_invoice_snapshots_repo_with_sys_acl(&conn);
+            let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo_with_sys_acl(&conn);
+            let payment_intent_repo = repo_factory.create_payment_intent_repo_with_sys_acl(&conn);
+
+            let invoice = match invoices_repo.get(id).map_err(ectx!(try convert => id))? {
+                None => return Ok(None),
+                Some(invoice) => invoice,
+            };
+
+            if invoice.paid_at.is_some() {
+                return Err(invoice_details_validation_error(
+                    "invoice",
+                    "already_paid",
+                    "Invoice details cannot be changed after the invoice has been paid",
+                ));
             }
+
+            let invoice = invoices_repo
+                .update_details(id, update_invoice_details.clone())
+                .map_err(ectx!(try convert => id, update_invoice_details))?;
+
+            // Receipts are sent by Stripe, so the open payment intent has to carry the new description
+            let payment_intent = match invoice.payment_flow() {
+                PaymentFlow::Crypto => None,
+                PaymentFlow::Fiat => match payment_intent_invoices_repo
+                    .get(SearchPaymentIntentInvoice::InvoiceId(id))
+                    .map_err(ectx!(try convert => id))?
+                {
+                    None => None,
+                    Some(payment_intent_invoice) => payment_intent_repo
+                        .get(SearchPaymentIntent::Id(payment_intent_invoice.payment_intent_id))
+                        .map_err(ectx!(try convert => id))?,
+                },
+            };
+            let receipt_description_update = payment_intent
+                .filter(|payment_intent| payment_intent.status.is_cancellable())
+                .map(|payment_intent| (payment_intent.id, invoice.receipt_description().unwrap_or_default()));
+
+            let invoice = get_invoice_price(&*orders_repo, &*rates_repo, &*accounts_repo, &*invoice_snapshots_repo, invoice)?;
+
+            Ok(Some((invoice, receipt_description_update)))
+        })
+        .and_then(move |result| match result {
+            None => future::Either::A(future::ok(None)),
+            Some((invoice, None)) => future::Either::A(future::ok(Some(invoice))),
+            Some((invoice, Some((payment_intent_id, description)))) => future::Either::B(
+                stripe_client
+                    .update_payment_intent_description(payment_intent_id.clone(), description)
+                    .map_err(ectx!(convert => payment_intent_id))
+                    .map(move |_| Some(invoice)),
+            ),
         });
 
         Box::new(fut)
     }
 
-    fn get_invoice_orders_ids_v1(&self, id: InvoiceId) -> ServiceFuture<Vec<OrderId>> {
-        let user_id = self.dynamic_context.user_id;
+    fn get_checkout_session(&self, id: InvoiceV2Id) -> ServiceFutureV2<Option<CheckoutSession>> {
+        let self_ = self.clone();
+
+        let fut = self.recalc_invoice_v2(id).and_then(move |invoice| match invoice {
+            None => future::Either::A(future::ok(None)),
+            Some(invoice) => future::Either::B(self_.checkout_session_for_invoice(invoice).map(Some)),
+        });
+
+        Box::new(fut)
+    }
+
+    fn checkout_session_for_invoice(&self, invoice: InvoiceDump) -> ServiceFutureV2<CheckoutSession> {
+        let db_pool = self.static_context.db_pool.clone();
+        let cpu_pool = self.static_context.cpu_pool.clone();
         let repo_factory = self.static_context.repo_factory.clone();
-        self.spawn_on_pool(move |conn| {
-            let invoice_repo = repo_factory.create_invoice_repo(&conn, user_id);
-            let order_info_repo = repo_factory.create_order_info_repo(&conn, user_id);
-            debug!("Requesting vec order ids by invoice id: {}", &id);
+        let user_id = self.dynamic_context.user_id;
 
-            invoice_repo
-                .find(id)
-                .and_then(|invoice| {
-                    if let Some(invoice) = invoice {
-                        order_info_repo
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let payment_intent = if invoice.buyer_currency.is_fiat() {
+                let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo(&conn, user_id);
+                let payment_intent_repo = repo_factory.create_payment_intent_repo(&conn, user_id);
+
+                let invoice_id = invoice.id;
+                let payment_intent_invoice = payment_intent_invoices_repo
+                    .get(SearchPaymentIntentInvoice::InvoiceId(invoice_id))
+                    .map_err(ectx!(try convert => invoice_id))?;
+
+                match payment_intent_invoice {
+                    None => None,
+                    Some(payment_intent_invoice) => payment_intent_repo
+                        .get(SearchPaymentIntent::Id(payment_intent_invoice.payment_intent_id))
+                        .map_err(ectx!(try convert => invoice_id))?,
+                }
+            } else {
+                None
+            };
+
+            let expires_at = invoice.price_reserved;
+            let status_url = format!("{}/{}", CHECKOUT_SESSIONS_ENDPOINT, invoice.id);
+
+            Ok(CheckoutSession::new(
+                &invoice,
+                payment_intent.as_ref(),
+                expires_at,
+                Utc::now().naive_utc(),
+                status_url,
+            ))
+        })
+    }
+
+    fn get_public_invoice_status(&self, public_token: String) -> ServiceFutureV2<Option<PublicInvoiceStatusResponse>> {
+        let db_pool = self.static_context.db_pool.clone();
+        let cpu_pool = self.static_context.cpu_pool.clone();
+        let repo_factory = self.static_context.repo_factory.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            // the caller is anonymous, knowing the token is what grants access to the invoice
+            let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
+            let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+            let rates_repo = repo_factory.create_order_exchange_rates_repo_with_sys_acl(&conn);
+            let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
+            let invoice_snapshots_repo = repo_factory.create_invoice_snapshots_repo_with_sys_acl(&conn);
+
+            let invoice = invoices_repo.get_by_public_token(&public_token).map_err(ectx!(try convert))?;
+
+            match invoice {
+                None => Ok(None),
+                Some(invoice) => get_invoice_price(&*orders_repo, &*rates_repo, &*accounts_repo, &*invoice_snapshots_repo, invoice)
+                    .map(PublicInvoiceStatusResponse::from)
+                    .map(Some),
+            }
+        })
+    }
+
+    fn get_invoice_transactions(&self, id: InvoiceV2Id) -> ServiceFutureV2<Option<Vec<InboundTransactionResponse>>> {
+        let db_pool = self.static_context.db_pool.clone();
+        let cpu_pool = self.static_context.cpu_pool.clone();
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let payments_client = self.dynamic_context.payments_client.clone();
+
+        let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let invoices_repo = repo_factory.create_invoices_v2_repo(&conn, user_id);
+
+            let invoice = match invoices_repo.get(id).map_err(ectx!(try convert => id))? {
+                None => return Ok(None),
+                Some(invoice) => invoice,
+            };
+            let amounts_received = invoices_repo.list_amounts_received(id).map_err(ectx!(try convert => id))?;
+
+            Ok(Some((invoice.buyer_currency, amounts_received)))
+        })
+        .and_then(move |amounts_received| match amounts_received {
+            None => future::Either::A(future::ok(None)),
+            Some((currency, amounts_received)) => future::Either::B(
+                stream::iter_ok::<_, ServiceError>(amounts_received)
+                    .and_then(move |amount_received| {
+                        // the status is best known to Payments gateway, the billing only stores the amount
+                        let status = match payments_client.clone() {
+                            None => future::Either::A(future::ok(None)),
+                            Some(payments_client) => {
+                                let tx_id = *amount_received.id.inner();
+                                future::Either::B(
+                                    payments_client
+                                        .get_transaction(tx_id)
+                                        .map(|tx| tx.map(|tx| tx.status))
+                                        .map_err(ectx!(convert => tx_id)),
+                                )
+                            }
+                        };
+
+                        status.map(move |status| InboundTransactionResponse::new(currency, amount_received, status))
+                    })
+                    .collect()
+                    .map(Some),
+            ),
+        });
+
+        Box::new(fut)
+    }
+
+    /// Get orders ids by invoice id
+
+    fn get_invoice_orders_ids(&self, id: InvoiceId) -> ServiceFuture<Vec<OrderId>> {
+        let v2_handler = if self.payments_v2_enabled() {
+            future::Either::A(self.get_invoice_orders_ids_v2(InvoiceV2Id::new(id.0)).map_err(FailureError::from))
+        } else {
+            future::Either::B(future::ok(vec![]))
+        };
+
+        let fut = Future::join(self.get_invoice_orders_ids_v1(id), v2_handler).and_then(move |(order_ids_v1, order_ids_v2)| {
+            match (order_ids_v1.is_empty(), order_ids_v2.is_empty()) {
+                (false, false) => Err(format_err!("Invoice with ID: {} is stored both in v1 and v2 tables", id)),
+                (false, true) => Ok(order_ids_v1),
+                (true, false) => Ok(order_ids_v2.into_iter().map(|id| OrderId(id.into_inner())).collect()),
+                (true, true) => Ok(vec![]),
+            }
+        });
+
+        Box::new(fut)
+    }
+
+    fn get_invoice_orders_ids_v1(&self, id: InvoiceId) -> ServiceFuture<Vec<OrderId>> {
+        let user_id = self.dynamic_context.user_id;
+        let repo_factory = self.static_context.repo_factory.clone();
+        self.spawn_on_pool(move |conn| {
+            let invoice_repo = repo_factory.create_invoice_repo(&conn, user_id);
+            let order_info_repo = repo_factory.create_order_info_repo(&conn, user_id);
+            debug!("Requesting vec order ids by invoice id: {}", &id);
+
+            invoice_repo
+                .find(id)
+                .and_then(|invoice| {
+                    if let Some(invoice) = invoice {
+                        order_info_repo
                             .find_by_saga_id(invoice.id)
                             .map(|order_infos| order_infos.into_iter().map(|order_info| order_info.order_id).collect())
                     } else {
@@ -736,15 +1249,14 @@ impl<
     /// Updates specific invoice and orders
     fn update_invoice(&self, external_invoice: ExternalBillingInvoice) -> ServiceFuture<()> {
         let current_user = self.dynamic_context.user_id;
-        let client = self.dynamic_context.http_client.clone();
         let repo_factory = self.static_context.repo_factory.clone();
-        let saga_url = self.static_context.config.saga_addr.url.clone();
 
         debug!("Updating by external invoice {:?}.", &external_invoice);
 
         self.spawn_on_pool(move |conn| {
             let order_info_repo = repo_factory.create_order_info_repo(&conn, current_user);
             let invoice_repo = repo_factory.create_invoice_repo(&conn, current_user);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
             let invoice_id = external_invoice.id;
             let update_payload = external_invoice.into();
             conn.transaction::<(), FailureError, _>(move || {
@@ -752,16 +1264,8 @@ impl<
                     .update(invoice_id, update_payload)
                     .and_then(|invoice| order_info_repo.update_status(invoice.id, invoice.state))
                     .and_then(|orders| {
-                        let body = serde_json::to_string(&orders)?;
-                        let url = format!("{}/orders/update_state", saga_url);
-                        client
-                            .request_json::<()>(Post, url, Some(body), None)
-                            .map_err(|e| {
-                                e.context("Occured an error during setting orders new status in saga.")
-                                    .context(Error::HttpClient)
-                                    .into()
-                            })
-                            .wait()
+                        let order_states = orders.iter().map(OrderStateUpdate::from).collect();
+                        enqueue_order_state_updates(&*event_store_repo, order_states).map_err(FailureError::from)
                     })
             })
             .map_err(|e: FailureError| e.context("Service invoice, update endpoint error occured.").into())
@@ -770,6 +1274,10 @@ impl<
 
     /// Handles the callback from Payments gateway which carries a new inbound transaction
     fn handle_inbound_tx(&self, signature_header: TureSignature, callback: PaymentsCallback, callback_body: String) -> ServiceFutureV2<()> {
+        self.handle_signed_inbound_tx(format!("{}", signature_header), callback, callback_body)
+    }
+
+    fn handle_signed_inbound_tx(&self, signature: String, callback: PaymentsCallback, callback_body: String) -> ServiceFutureV2<()> {
         let payments_client = if let Some(payments_client) = self.dynamic_context.payments_client.clone() {
             payments_client
         } else {
@@ -786,10 +1294,12 @@ impl<
             account_id,
             amount_captured: amount_received,
             address: wallet_address,
+            created_at,
             ..
         } = callback.clone();
+        // Transactions are attributed to the invoice the account was assigned to when they were made
+        let transaction_at = created_at.unwrap_or_else(|| Utc::now().naive_utc());
 
-        let signature_header = format!("{}", signature_header);
         let sign_public_key = if let Some(payments) = self.static_context.config.payments.clone() {
             payments.sign_public_key
         } else {
@@ -804,9 +1314,10 @@ impl<
                 {
                     let repo_factory = repo_factory.clone();
                     move |conn| {
-                        check_ture_sign(sign_public_key, signature_header, callback_body)?;
+                        check_ture_sign(sign_public_key, signature, callback_body)?;
                         let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
                         let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
+                        let account_assignments_repo = repo_factory.create_account_assignments_repo_with_sys_acl(&conn);
                         let account_id = match account_id {
                             Some(account_id) => account_id,
                             None => accounts_repo.get_by_wallet_address(wallet_address.clone())
@@ -824,7 +1335,45 @@ impl<
 
                         // if callback received to an account that is not connected to any invoice
                         let account_id_clone = account_id.clone();
-                        if invoices_repo.get_by_account_id(account_id_clone.clone()).map_err(ectx!(try convert => account_id_clone))?.is_none() {
+                        let linked_invoice = match invoices_repo.get_by_account_id(account_id_clone.clone()).map_err(ectx!(try convert => account_id_clone))? {
+                            Some(invoice) => invoice,
+                            // the account may collect a fee payment of a store instead
+                            None => {
+                                let fee_crypto_payments_repo = repo_factory.create_fee_crypto_payments_repo_with_sys_acl(&conn);
+                                let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+                                let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                                let store_webhooks_repo = repo_factory.create_store_webhooks_repo_with_sys_acl(&conn);
+                                let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+                                conn.transaction(|| credit_fee_crypto_payment(
+                                    &*fee_crypto_payments_repo,
+                                    &*fees_repo,
+                                    &*orders_repo,
+                                    &*store_webhooks_repo,
+                                    &*event_store_repo,
+                                    account_id.clone(),
+                                    transaction_id.clone(),
+                                    transaction_at,
+                                    amount_received,
+                                ))?;
+                                // there is no invoice price to recalculate
+                                return Err(ErrorKind::NotFound.into());
+                            }
+                        };
+
+                        // if the transaction was made before the account was assigned to the linked invoice,
+                        // e.g. a late payment for the previous invoice of a pooled account
+                        let assignment = account_assignments_repo
+                            .get_active_at(account_id.clone(), transaction_at)
+                            .map_err({ let account_id = account_id.clone(); ectx!(try convert => account_id, transaction_at) })?;
+                        if assignment.as_ref().map(|assignment| assignment.invoice_id) != Some(linked_invoice.id) {
+                            warn!(
+                                "Transaction {} to account {} made at {} is not credited to invoice {}: the account was assigned to {} then",
+                                transaction_id,
+                                account_id,
+                                transaction_at,
+                                linked_invoice.id,
+                                assignment.map(|assignment| format!("invoice {}", assignment.invoice_id)).unwrap_or_else(|| "no invoice".to_string())
+                            );
                             return Err(ErrorKind::NotFound.into());
                         }
 
@@ -867,8 +1416,10 @@ impl<
                             .and_then({
                                 let buyer_currency = invoice.buyer_currency.clone();
                                 move |current_order_rates| {
+                                    let refreshed_from = active_rate_ids(&current_order_rates);
                                     to_ture_currency(buyer_currency.clone())
                                         .and_then(move |buyer_currency| refresh_rates(payments_client, buyer_currency, current_order_rates))
+                                        .map(move |new_active_rates| (refreshed_from, new_active_rates))
                                 }
                             })
                             // Save new and updated rates to database
@@ -876,108 +1427,643 @@ impl<
                                 let db_pool = db_pool.clone();
                                 let cpu_pool = cpu_pool.clone();
                                 let repo_factory = repo_factory.clone();
-                                move |new_active_rates| {
+                                let invoice_id = invoice.id;
+                                move |(refreshed_from, new_active_rates)| {
                                     spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                                        let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
+                                        let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
                                         let rates_repo = repo_factory.create_order_exchange_rates_repo_with_sys_acl(&conn);
 
-                                        new_active_rates
-                                            .into_iter()
-                                            .map(|new_rate| {
-                                                rates_repo
-                                                    .add_new_active_rate(new_rate.clone())
-                                                    .map_err(ectx!(convert => new_rate))
-                                                    .map(|_| ())
-                                            })
-                                            .collect::<Result<Vec<_>, ServiceError>>()
-                                    })
+                                        save_refreshed_rates(
+                                            &*conn,
+                                            &*invoices_repo,
+                                            &*orders_repo,
+                                            &*rates_repo,
+                                            invoice_id,
+                                            refreshed_from,
+                                            new_active_rates,
+                                        )
+                                    })
+                                }
+                            })
+                            .and_then({
+                                let db_pool = db_pool.clone();
+                                let cpu_pool = cpu_pool.clone();
+                                let invoice = invoice.clone();
+                                let repo_factory = repo_factory.clone();
+                                move |_| spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                                    let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
+                                    let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                                    let rates_repo = repo_factory.create_order_exchange_rates_repo_with_sys_acl(&conn);
+                                    let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
+                                    let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+                                    let exchange_rate_slippages_repo = repo_factory.create_exchange_rate_slippages_repo_with_sys_acl(&conn);
+                                    let invoice_snapshots_repo = repo_factory.create_invoice_snapshots_repo_with_sys_acl(&conn);
+
+                                    calculate_invoice_price_and_set_final_price_if_paid(
+                                        &*conn,
+                                        &*invoices_repo,
+                                        &*orders_repo,
+                                        &*rates_repo,
+                                        &*accounts_repo,
+                                        &*event_store_repo,
+                                        &*exchange_rate_slippages_repo,
+                                        &*invoice_snapshots_repo,
+                                        invoice.id.clone(),
+                                    )?;
+
+                                    Ok(())
+                                })
+                            })
+                        )),
+                        // Skip recalc if the invoice is paid
+                        Some(_) => future::Either::B(future::ok(())),
+                    }
+                }
+            })
+            .then(|res| {
+                if let Err(e) = res {
+                    match e.kind() {
+                        ErrorKind::NotFound => Ok(()),
+                        _ => Err(e)
+                    }
+                } else {
+                    res
+                }
+            });
+
+        Box::new(fut)
+    }
+
+    fn simulate_inbound_tx(&self, id: InvoiceV2Id, payload: SimulateInboundTxRequest) -> ServiceFutureV2<SimulatedInboundTxResponse> {
+        let sandbox = self.static_context.config.payments_sandbox.clone();
+        if !sandbox.enabled {
+            let e = err_msg("payments sandbox is not enabled");
+            return Box::new(future::err::<_, ServiceError>(
+                ectx!(err e, ErrorContext::PaymentsSandbox, ErrorKind::NotFound),
+            ));
+        }
+
+        let db_pool = self.static_context.db_pool.clone();
+        let cpu_pool = self.static_context.cpu_pool.clone();
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+        let callback_url = format!("{}{}", self.static_context.config.callback.url, PAYMENTS_CALLBACK_ENDPOINT);
+
+        let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let user_id = user_id.ok_or_else(|| ectx!(err ErrorContext::Unauthorized, ErrorKind::Forbidden))?;
+            let user_roles_repo = repo_factory.create_user_roles_repo_with_sys_acl(&conn);
+            let roles = user_roles_repo
+                .list_for_user(user_id)
+                .map_err(ectx!(try ErrorKind::Internal => user_id))?;
+
+            if roles.contains(&BillingRole::Superuser) {
+                Ok(())
+            } else {
+                Err(ectx!(err ErrorContext::Unauthorized, ErrorKind::Forbidden))
+            }
+        })
+        .and_then({
+            let self_ = self.clone();
+            move |_| self_.recalc_invoice_v2(id)
+        })
+        .and_then(move |invoice| {
+            let invoice = invoice.ok_or_else(|| {
+                let e = format_err!("Invoice {} not found", id);
+                ectx!(err e, ErrorKind::NotFound)
+            })?;
+
+            let (currency, wallet_address) = match (
+                TureCurrency::try_from_currency(invoice.buyer_currency),
+                invoice.wallet_address.clone(),
+            ) {
+                (Ok(currency), Some(wallet_address)) => (currency, wallet_address),
+                _ => {
+                    return Err(payments_sandbox_validation_error(
+                        "not_crypto",
+                        format!("Invoice {} is not paid to a crypto wallet", id),
+                    ));
+                }
+            };
+
+            let amount_remaining = invoice.amount_remaining();
+            if amount_remaining <= BigDecimal::from(0) {
+                return Err(payments_sandbox_validation_error(
+                    "nothing_to_pay",
+                    format!("Invoice {} awaits no payment", id),
+                ));
+            }
+            let amount = Amount::from_super_unit(invoice.buyer_currency, simulated_payment_amount(payload.kind, amount_remaining));
+
+            let callback = PaymentsCallback {
+                url: callback_url,
+                transaction_id: TransactionId::generate(),
+                amount_captured: amount.to_string(),
+                currency,
+                address: wallet_address,
+                account_id: None,
+                created_at: Some(Utc::now().naive_utc()),
+            };
+            let callback_body = serde_json::to_string(&callback).map_err(ectx!(try ErrorKind::Internal))?;
+            let signature = sign_ture_callback(&sandbox.sign_private_key, &callback_body)?;
+
+            warn!(
+                "Payments sandbox: simulated {:?} transaction {} of {} {} to invoice {}",
+                payload.kind, callback.transaction_id, amount, invoice.buyer_currency, id
+            );
+            Ok((invoice.buyer_currency, amount, signature, callback, callback_body))
+        })
+        .and_then({
+            let self_ = self.clone();
+            move |(buyer_currency, amount, signature, callback, callback_body)| {
+                let response = SimulatedInboundTxResponse {
+                    invoice_id: id,
+                    transaction_id: callback.transaction_id,
+                    wallet_address: callback.address.clone(),
+                    currency: buyer_currency,
+                    amount: amount.to_super_unit(buyer_currency),
+                };
+
+                self_
+                    .handle_signed_inbound_tx(signature, callback, callback_body)
+                    .map(move |_| response)
+            }
+        });
+
+        Box::new(fut)
+    }
+
+    fn get_missing_rates_from_payments_gateway_and_refresh_existing_rates(
+        &self,
+        invoice: InvoiceV2,
+        current_order_rates: Vec<(RawOrder, Option<RawOrderExchangeRate>)>,
+        user_id: Option<stq_types::UserId>,
+    ) -> ServiceFutureV2<()> {
+        let db_pool = self.static_context.db_pool.clone();
+        let cpu_pool = self.static_context.cpu_pool.clone();
+        let repo_factory = self.static_context.repo_factory.clone();
+
+        let invoice_id = invoice.id;
+        let refreshed_from = active_rate_ids(&current_order_rates);
+
+        let fut = self
+            .dynamic_context
+            .payments_client
+            .clone()
+            .ok_or_else(|| {
+                let e = err_msg("payments integration has not been configured");
+                ectx!(err e, ErrorKind::Internal)
+            })
+            .into_future()
+            .and_then(move |payments_client| {
+                to_ture_currency(invoice.buyer_currency.clone()).map(move |buyer_currency| (payments_client, buyer_currency))
+            })
+            .and_then(move |(payments_client, buyer_currency)| refresh_rates(payments_client, buyer_currency, current_order_rates))
+            // Save new and updated rates to database
+            .and_then(move |new_active_rates| {
+                spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                    let invoices_repo = repo_factory.create_invoices_v2_repo(&conn, user_id);
+                    let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
+                    let rates_repo = repo_factory.create_order_exchange_rates_repo(&conn, user_id);
+
+                    save_refreshed_rates(
+                        &*conn,
+                        &*invoices_repo,
+                        &*orders_repo,
+                        &*rates_repo,
+                        invoice_id,
+                        refreshed_from,
+                        new_active_rates,
+                    )
+                })
+            });
+        Box::new(fut)
+    }
+}
+
+/// Invoice data shared by the marketplace and the store invoices
+struct InvoiceDraft {
+    id: InvoiceV2Id,
+    buyer_user_id: UserId,
+    buyer_currency: Currency,
+    buyer_country: Option<Alpha3>,
+    metadata: Option<serde_json::Value>,
+    memo: Option<String>,
+    po_number: Option<String>,
+    callback: Option<InvoiceCallbackRegistration>,
+    /// Expired invoice this one replaces at the current rates
+    requoted_from: Option<InvoiceV2Id>,
+    /// Spend the credit of the buyer in the buyer currency on the invoice
+    apply_credit: bool,
+    /// Line items of the orders of the invoice
+    line_items: Vec<NewOrderLineItem>,
+    /// Billing name and address of the buyer for the receipts
+    buyer_billing_details: Option<BuyerBillingDetails>,
+}
+
+/// Credit of the buyer spent on an invoice at checkout
+#[derive(Clone, Copy, Debug)]
+struct AppliedCredit {
+    amount: Amount,
+    /// The invoice is paid with the credit alone, no payment is prepared for it
+    covers_invoice: bool,
+}
+
+/// Who requested the invoice, decides on whose behalf the invoice and its orders are saved
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum InvoiceIssuer {
+    /// Marketplace checkout, saved with the ACL of the requesting user
+    Buyer,
+    /// Store billing its customer, saved with the system ACL after the store manager has been checked
+    Store,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+        C: HttpClient + Clone,
+        PC: PaymentsClient + Clone,
+        AS: AccountService + Clone + 'static,
+    > Service<T, M, F, C, PC, AS>
+{
+    /// Prices the orders in the buyer currency, prepares the payment (payment intent or pooled account) and saves the invoice
+    fn create_invoice_with_orders(
+        &self,
+        invoice: InvoiceDraft,
+        orders: Vec<NewOrder>,
+        issuer: InvoiceIssuer,
+    ) -> ServiceFutureV2<InvoiceDump> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let DynamicContext {
+            user_id,
+            payments_client,
+            account_service,
+            ..
+        } = self.dynamic_context.clone();
+
+        let (payments_client, account_service) = if let (Some(payments_client), Some(account_service)) = (payments_client, account_service)
+        {
+            (payments_client, account_service)
+        } else {
+            let e = err_msg("payments integration has not been configured");
+            return Box::new(future::err::<_, ServiceError>(ectx!(err e, ErrorKind::Internal)));
+        };
+
+        let InvoiceDraft {
+            id: invoice_id,
+            buyer_user_id,
+            buyer_currency,
+            buyer_country,
+            metadata,
+            memo,
+            po_number,
+            callback,
+            requoted_from,
+            apply_credit,
+            line_items,
+            buyer_billing_details,
+        } = invoice;
+
+        if let Err(e) = validate_invoice_details(memo.as_ref(), po_number.as_ref()) {
+            return Box::new(future::err(e));
+        }
+
+        let buyer_billing_details = buyer_billing_details.map(BuyerBillingDetails::trimmed);
+        if let Some(Err(e)) = buyer_billing_details.as_ref().map(validate_buyer_billing_details) {
+            return Box::new(future::err(e));
+        }
+
+        // the billing address decides the availability of the payment methods unless the saga gives the country explicitly
+        let buyer_country = match buyer_country_of(buyer_country, buyer_billing_details.as_ref()) {
+            Ok(buyer_country) => buyer_country,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        if let Some(Err(e)) = callback.as_ref().map(validate_invoice_callback) {
+            return Box::new(future::err(e));
+        }
+
+        // Fiat payment intents are captured for the totals of the orders, so credit can't pay a part of a fiat invoice
+        if apply_credit && buyer_currency.is_fiat() {
+            return Box::new(future::err(user_credit_validation_error(
+                "apply_credit",
+                "unsupported_currency",
+                "Credit can only be applied to invoices in crypto currencies",
+            )));
+        }
+
+        let store_ids = orders.iter().map(|order| order.store_id.inner()).collect::<Vec<_>>();
+        let allowed_currencies = allowed_currencies(
+            &self.static_context.config.payment_methods.rules,
+            buyer_country.as_ref(),
+            &store_ids,
+        );
+        if !allowed_currencies.contains(&buyer_currency) {
+            let e = format_err!("Currency {} is not available for the buyer", buyer_currency);
+            return Box::new(future::err(ectx!(err e, ErrorKind::Validation(serde_json::json!({
+                "currency": buyer_currency,
+                "buyer_country": buyer_country,
+                "allowed_currencies": allowed_currencies,
+            })))));
+        }
+
+        let receipt_description = receipt_description(memo.as_ref().map(String::as_str), po_number.as_ref().map(String::as_str));
+
+        let db_pool = self.static_context.db_pool.clone();
+        let cpu_pool = self.static_context.cpu_pool.clone();
+
+        let fiat_payment_provider = self.static_context.fiat_payment_provider.clone();
+
+        let fut = stream::iter_ok::<_, ServiceError>(orders.into_iter().map(move |order| (payments_client.clone(), order)))
+            .and_then(move |(payments_client, new_order)| {
+                // process each order individually
+                let seller_currency = new_order.seller_currency;
+                let total_amount = new_order.total_amount;
+
+                match (buyer_currency.is_fiat(), seller_currency.is_fiat()) {
+                    (true, true) => exchage_rate_fiat(new_order, buyer_currency, seller_currency),
+                    (false, false) => exchage_rate_crypto(payments_client, new_order, buyer_currency, seller_currency, total_amount),
+                    _ => {
+                        let e = err_msg("fiat - crypto payments are not supported yet");
+                        Box::new(future::err::<_, ServiceError>(ectx!(err e, ErrorKind::Internal)))
+                    }
+                }
+            })
+            .collect()
+            .and_then({
+                let repo_factory = repo_factory.clone();
+                let db_pool = db_pool.clone();
+                let cpu_pool = cpu_pool.clone();
+                move |orders: Vec<(NewOrder, Option<ExchangeId>, BigDecimal)>| -> ServiceFutureV2<_> {
+                    if !apply_credit {
+                        return Box::new(future::ok((orders, None)));
+                    }
+
+                    let amount_due = match invoice_amount_due(&orders, invoice_id) {
+                        Ok(amount_due) => amount_due,
+                        Err(e) => return Box::new(future::err(e)),
+                    };
+                    Box::new(spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                        let user_credits_repo = repo_factory.create_user_credits_repo(&conn, user_id);
+                        let balance = user_credits_repo
+                            .get_balance(buyer_user_id, buyer_currency)
+                            .map_err(ectx!(try convert => buyer_user_id, buyer_currency))?
+                            .map(|balance| balance.balance)
+                            .unwrap_or_default();
+
+                        let amount = credit_to_apply(balance, amount_due);
+                        let credit = if amount > Amount::zero() {
+                            Some(AppliedCredit {
+                                amount,
+                                covers_invoice: amount >= amount_due,
+                            })
+                        } else {
+                            None
+                        };
+                        Ok((orders, credit))
+                    }))
+                }
+            })
+            .and_then({
+                let repo_factory = repo_factory.clone();
+                let db_pool = db_pool.clone();
+                let cpu_pool = cpu_pool.clone();
+                move |(orders, credit)| {
+                    // process collection of orders
+                    let payment = if credit.map_or(false, |credit: AppliedCredit| credit.covers_invoice) {
+                        future::Either::A(future::ok((None, None, None, orders)))
+                    } else if buyer_currency.is_fiat() {
+                        future::Either::B(future::Either::A(
+                            get_receipt_email(db_pool, cpu_pool, repo_factory, buyer_user_id).and_then(move |receipt_email| {
+                                create_payment_intent(
+                                    fiat_payment_provider,
+                                    &orders,
+                                    invoice_id,
+                                    buyer_currency,
+                                    receipt_email,
+                                    receipt_description,
+                                )
+                                .map(|new_payment_intent| (None, None, Some(new_payment_intent), orders))
+                            }),
+                        ))
+                    } else {
+                        future::Either::B(future::Either::B(to_ture_currency(buyer_currency).and_then(
+                            move |buyer_currency| {
+                                account_service
+                                    .get_or_create_free_pooled_account(buyer_currency)
+                                    .map_err(ectx!(convert => buyer_currency))
+                                    .map(|account| (Some(account.id), Some(account.wallet_address), None, orders))
+                            },
+                        )))
+                    };
+
+                    payment.map(move |(account_id, wallet_address, new_payment_intent, orders)| {
+                        (account_id, wallet_address, new_payment_intent, orders, credit)
+                    })
+                }
+            })
+            .and_then({
+                let payment_expiry = self.static_context.config.payment_expiry.clone();
+                let analytics_enabled = self.static_context.config.analytics.is_some();
+                move |(account_id, wallet_address, new_payment_intent, orders, credit)| {
+                    cpu_pool.spawn_fn(move || {
+                        db_pool.get().map_err(ectx!(ErrorKind::Internal)).and_then(move |conn| {
+                            // Add scheduled PaymentExpired event
+                            let payment_expired_event = Event::new(EventPayload::PaymentExpired { invoice_id });
+                            let expires_on = Utc::now().naive_utc() + payment_expiry_timeout(&payment_expiry, buyer_currency);
+
+                            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+                            event_store_repo
+                                .add_scheduled_event(payment_expired_event.clone(), expires_on.clone())
+                                .map_err(ectx!(try convert => payment_expired_event, expires_on))?;
+
+                            // Save invoice data to database
+                            let (invoices_repo, orders_repo, order_exchange_rates_repo) = match issuer {
+                                InvoiceIssuer::Buyer => (
+                                    repo_factory.create_invoices_v2_repo(&conn, user_id),
+                                    repo_factory.create_orders_repo(&conn, user_id),
+                                    repo_factory.create_order_exchange_rates_repo(&conn, user_id),
+                                ),
+                                InvoiceIssuer::Store => (
+                                    repo_factory.create_invoices_v2_repo_with_sys_acl(&conn),
+                                    repo_factory.create_orders_repo_with_sys_acl(&conn),
+                                    repo_factory.create_order_exchange_rates_repo_with_sys_acl(&conn),
+                                ),
+                            };
+                            let payment_intent_repo = repo_factory.create_payment_intent_repo_with_sys_acl(&conn);
+                            let payment_intent_history_repo = repo_factory.create_payment_intent_history_repo_with_sys_acl(&conn);
+                            let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo_with_sys_acl(&conn);
+                            let invoice_callbacks_repo = repo_factory.create_invoice_callbacks_repo_with_sys_acl(&conn);
+                            let invoice_requotes_repo = repo_factory.create_invoice_requotes_repo_with_sys_acl(&conn);
+                            let order_line_items_repo = repo_factory.create_order_line_items_repo_with_sys_acl(&conn);
+                            // buyers give their details at checkout but may not read them back
+                            let invoice_buyer_details_repo = repo_factory.create_invoice_buyer_details_repo_with_sys_acl(&conn);
+                            let account_assignments_repo = repo_factory.create_account_assignments_repo_with_sys_acl(&conn);
+                            // the buyer has consented to spending the credit, spending it is up to the service
+                            let user_credits_repo = repo_factory.create_user_credits_repo_with_sys_acl(&conn);
+                            let paid_invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
+                            let paid_orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                            let paid_rates_repo = repo_factory.create_order_exchange_rates_repo_with_sys_acl(&conn);
+                            let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
+                            let exchange_rate_slippages_repo = repo_factory.create_exchange_rate_slippages_repo_with_sys_acl(&conn);
+                            let invoice_snapshots_repo = repo_factory.create_invoice_snapshots_repo_with_sys_acl(&conn);
+                            let db_conn = &*conn;
+
+                            conn.transaction::<InvoiceDump, ServiceError, _>(move || {
+                                let invoice = NewInvoice {
+                                    id: invoice_id,
+                                    account_id,
+                                    buyer_currency,
+                                    // credit is captured up front, the rest of the price is paid as usual
+                                    amount_captured: credit.map(|credit| credit.amount).unwrap_or(Amount::new(0u128)),
+                                    buyer_user_id,
+                                    metadata,
+                                    memo,
+                                    po_number,
+                                    price_reserved: expires_on,
+                                    public_token: generate_public_token(),
+                                };
+
+                                let invoice = invoices_repo.create(invoice.clone()).map_err(ectx!(try convert => invoice))?;
+
+                                if let Some(account_id) = account_id {
+                                    let new_account_assignment = NewAccountAssignment {
+                                        account_id,
+                                        invoice_id: invoice.id,
+                                    };
+                                    account_assignments_repo
+                                        .create(new_account_assignment.clone())
+                                        .map_err(ectx!(try convert => new_account_assignment))?;
+                                }
+
+                                if let Some(InvoiceCallbackRegistration { url, secret }) = callback {
+                                    let new_invoice_callback = NewInvoiceCallback {
+                                        invoice_id: invoice.id,
+                                        url,
+                                        secret,
+                                    };
+                                    invoice_callbacks_repo
+                                        .create(new_invoice_callback.clone())
+                                        .map_err(ectx!(try convert => new_invoice_callback))?;
+                                }
+
+                                if let Some(original_invoice_id) = requoted_from {
+                                    let new_invoice_requote = NewInvoiceRequote {
+                                        invoice_id: invoice.id,
+                                        original_invoice_id,
+                                    };
+                                    invoice_requotes_repo
+                                        .create(new_invoice_requote.clone())
+                                        .map_err(ectx!(try convert => new_invoice_requote))?;
+                                }
+
+                                if let Some((new_payment_intent, new_payment_intent_invoice)) = new_payment_intent {
+                                    let payment_intent = payment_intent_repo
+                                        .create(new_payment_intent.clone())
+                                        .map_err(ectx!(try convert => new_payment_intent))?;
+                                    record_payment_intent_status(
+                                        &*payment_intent_history_repo,
+                                        &payment_intent,
+                                        PaymentIntentHistorySource::Api,
+                                    )?;
+
+                                    payment_intent_invoices_repo
+                                        .create(new_payment_intent_invoice.clone())
+                                        .map_err(ectx!(try convert => new_payment_intent_invoice))?;
+                                }
+
+                                let orders_with_rates = orders
+                                    .into_iter()
+                                    .map(|(new_order, exchange_id, exchange_rate)| {
+                                        let order_id = new_order.id;
+
+                                        let order = orders_repo.create(new_order.clone()).map_err(ectx!(try convert => new_order))?;
+
+                                        let new_rate = NewOrderExchangeRate {
+                                            order_id,
+                                            exchange_id,
+                                            exchange_rate,
+                                        };
+
+                                        let rate = order_exchange_rates_repo
+                                            .add_new_active_rate(new_rate.clone())
+                                            .map_err(ectx!(try convert => new_rate))?;
+
+                                        Ok((order, vec![rate.active_rate]))
+                                    })
+                                    .collect::<Result<Vec<_>, ServiceError>>()?;
+
+                                for new_line_item in line_items {
+                                    order_line_items_repo
+                                        .create(new_line_item.clone())
+                                        .map_err(ectx!(try convert => new_line_item))?;
                                 }
-                            })
-                            .and_then({
-                                let db_pool = db_pool.clone();
-                                let cpu_pool = cpu_pool.clone();
-                                let invoice = invoice.clone();
-                                let repo_factory = repo_factory.clone();
-                                move |_| spawn_on_pool(db_pool, cpu_pool, move |conn| {
-                                    let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
-                                    let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
-                                    let rates_repo = repo_factory.create_order_exchange_rates_repo_with_sys_acl(&conn);
-                                    let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
-                                    let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
 
-                                    calculate_invoice_price_and_set_final_price_if_paid(
-                                        &*conn,
-                                        &*invoices_repo,
-                                        &*orders_repo,
-                                        &*rates_repo,
-                                        &*accounts_repo,
-                                        &*event_store_repo,
-                                        invoice.id.clone(),
-                                    )?;
+                                if let Some(details) = buyer_billing_details {
+                                    let new_buyer_details = NewInvoiceBuyerDetails {
+                                        invoice_id: invoice.id,
+                                        buyer_user_id,
+                                        details,
+                                    };
+                                    invoice_buyer_details_repo
+                                        .create(new_buyer_details)
+                                        .map_err(ectx!(try convert => invoice_id))?;
+                                }
 
-                                    Ok(())
-                                })
+                                if analytics_enabled {
+                                    let orders = orders_with_rates.iter().map(|(order, _)| order.clone()).collect::<Vec<_>>();
+                                    let mut analytics_data = invoice_analytics_data(invoice.id, &orders);
+                                    if let Some(original_invoice_id) = requoted_from {
+                                        analytics_data["requoted_from_invoice_id"] = serde_json::json!(original_invoice_id);
+                                    }
+                                    enqueue_analytics_event(&*event_store_repo, AnalyticsEventType::InvoiceCreated, analytics_data)?;
+                                }
+
+                                match credit {
+                                    None => Ok(calculate_invoice_price(invoice, orders_with_rates, wallet_address)),
+                                    Some(credit) => {
+                                        apply_invoice_credit(
+                                            &*user_credits_repo,
+                                            buyer_user_id,
+                                            buyer_currency,
+                                            invoice.id,
+                                            credit.amount,
+                                        )?;
+                                        if !credit.covers_invoice {
+                                            return Ok(calculate_invoice_price(invoice, orders_with_rates, wallet_address));
+                                        }
+
+                                        // nothing is left to pay, the invoice becomes paid right away
+                                        calculate_invoice_price_and_set_final_price_if_paid(
+                                            db_conn,
+                                            &*paid_invoices_repo,
+                                            &*paid_orders_repo,
+                                            &*paid_rates_repo,
+                                            &*accounts_repo,
+                                            &*event_store_repo,
+                                            &*exchange_rate_slippages_repo,
+                                            &*invoice_snapshots_repo,
+                                            invoice.id,
+                                        )
+                                    }
+                                }
                             })
-                        )),
-                        // Skip recalc if the invoice is paid
-                        Some(_) => future::Either::B(future::ok(())),
-                    }
-                }
-            })
-            .then(|res| {
-                if let Err(e) = res {
-                    match e.kind() {
-                        ErrorKind::NotFound => Ok(()),
-                        _ => Err(e)
-                    }
-                } else {
-                    res
+                        })
+                    })
                 }
             });
 
         Box::new(fut)
     }
+}
 
-    fn get_missing_rates_from_payments_gateway_and_refresh_existing_rates(
-        &self,
-        invoice: InvoiceV2,
-        current_order_rates: Vec<(RawOrder, Option<RawOrderExchangeRate>)>,
-        user_id: Option<stq_types::UserId>,
-    ) -> ServiceFutureV2<()> {
-        let db_pool = self.static_context.db_pool.clone();
-        let cpu_pool = self.static_context.cpu_pool.clone();
-        let repo_factory = self.static_context.repo_factory.clone();
-
-        let fut = self
-            .dynamic_context
-            .payments_client
-            .clone()
-            .ok_or_else(|| {
-                let e = err_msg("payments integration has not been configured");
-                ectx!(err e, ErrorKind::Internal)
-            })
-            .into_future()
-            .and_then(move |payments_client| {
-                to_ture_currency(invoice.buyer_currency.clone()).map(move |buyer_currency| (payments_client, buyer_currency))
-            })
-            .and_then(move |(payments_client, buyer_currency)| refresh_rates(payments_client, buyer_currency, current_order_rates))
-            // Save new and updated rates to database
-            .and_then(move |new_active_rates| {
-                spawn_on_pool(db_pool, cpu_pool, move |conn| {
-                    let rates_repo = repo_factory.create_order_exchange_rates_repo(&conn, user_id);
-
-                    new_active_rates
-                        .into_iter()
-                        .map(|new_rate| {
-                            rates_repo
-                                .add_new_active_rate(new_rate.clone())
-                                .map_err(ectx!(convert => new_rate))
-                                .map(|_| ())
-                        })
-                        .collect::<Result<Vec<_>, ServiceError>>()
-                })
-            })
-            .map(|_| ());
-        Box::new(fut)
+/// Time the buyer has to pay the invoice, depends on the payment flow
+fn payment_expiry_timeout(payment_expiry: &PaymentExpiry, buyer_currency: Currency) -> Duration {
+    if buyer_currency.is_fiat() {
+        Duration::minutes(payment_expiry.fiat_timeout_min as i64)
+    } else {
+        Duration::minutes(payment_expiry.crypto_timeout_min as i64)
     }
 }
 
@@ -1018,20 +2104,281 @@ where
     Box::new(fut)
 }
 
+/// Returns the email of the buyer's Stripe customer, which is used as the receipt email of payment intents
+fn get_receipt_email<T, M, F>(
+    db_pool: Pool<M>,
+    cpu_pool: CpuPool,
+    repo_factory: F,
+    buyer_user_id: UserId,
+) -> ServiceFutureV2<Option<String>>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    spawn_on_pool(db_pool, cpu_pool, move |conn| {
+        let customers_repo = repo_factory.create_customers_repo_with_sys_acl(&conn);
+        let buyer_user_id = stq_types::UserId(buyer_user_id.inner());
+
+        customers_repo
+            .get(SearchCustomer::UserId(buyer_user_id))
+            .map_err(ectx!(convert => buyer_user_id))
+            .map(|customer| customer.and_then(|customer| customer.email))
+    })
+}
+
+fn validate_invoice_details(memo: Option<&String>, po_number: Option<&String>) -> Result<(), ServiceError> {
+    if memo.map(|memo| memo.chars().count() > INVOICE_MEMO_MAX_LENGTH).unwrap_or(false) {
+        return Err(invoice_details_validation_error(
+            "memo",
+            "length",
+            &format!("Memo must not be longer than {} characters", INVOICE_MEMO_MAX_LENGTH),
+        ));
+    }
+
+    if po_number
+        .map(|po_number| po_number.chars().count() > INVOICE_PO_NUMBER_MAX_LENGTH)
+        .unwrap_or(false)
+    {
+        return Err(invoice_details_validation_error(
+            "po_number",
+            "length",
+            &format!("PO number must not be longer than {} characters", INVOICE_PO_NUMBER_MAX_LENGTH),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Validates the billing details of the buyer, the details are expected to be trimmed
+fn validate_buyer_billing_details(details: &BuyerBillingDetails) -> Result<(), ServiceError> {
+    let required = [
+        ("buyer_billing_details.name", &details.name),
+        ("buyer_billing_details.address_line1", &details.address_line1),
+        ("buyer_billing_details.city", &details.city),
+    ];
+    for &(field, value) in required.iter() {
+        if value.is_empty() {
+            return Err(invoice_details_validation_error(
+                field,
+                "required",
+                "Buyer billing details must not be empty",
+            ));
+        }
+    }
+
+    let optional = [
+        ("buyer_billing_details.address_line2", &details.address_line2),
+        ("buyer_billing_details.postal_code", &details.postal_code),
+        ("buyer_billing_details.region", &details.region),
+        ("buyer_billing_details.tax_id", &details.tax_id),
+    ];
+    let fields = required.iter().cloned().chain(
+        optional
+            .iter()
+            .filter_map(|&(field, value)| value.as_ref().map(|value| (field, value))),
+    );
+    for (field, value) in fields {
+        if value.chars().count() > BUYER_BILLING_DETAILS_MAX_LENGTH {
+            return Err(invoice_details_validation_error(
+                field,
+                "length",
+                &format!(
+                    "Buyer billing details must not be longer than {} characters",
+                    BUYER_BILLING_DETAILS_MAX_LENGTH
+                ),
+            ));
+        }
+    }
+
+    let country = &details.country.0;
+    if country.len() != 3 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(invoice_details_validation_error(
+            "buyer_billing_details.country",
+            "country",
+            "Country of the buyer must be an ISO 3166-1 alpha-3 code",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Country of the buyer the invoice is issued for. The country of the billing details is taken
+/// if the saga gives none, the two must not differ otherwise
+fn buyer_country_of(buyer_country: Option<Alpha3>, details: Option<&BuyerBillingDetails>) -> Result<Option<Alpha3>, ServiceError> {
+    match (buyer_country, details) {
+        (Some(buyer_country), Some(details)) => {
+            if buyer_country.0.eq_ignore_ascii_case(&details.country.0) {
+                Ok(Some(buyer_country))
+            } else {
+                Err(invoice_details_validation_error(
+                    "buyer_billing_details.country",
+                    "country_mismatch",
+                    &format!(
+                        "Country of the billing details {} differs from the buyer country {}",
+                        details.country.0, buyer_country.0
+                    ),
+                ))
+            }
+        }
+        (buyer_country, None) => Ok(buyer_country),
+        (None, Some(details)) => Ok(Some(details.country.clone())),
+    }
+}
+
+/// Validates the line items of a store invoice, returning their total amount
+fn validate_store_invoice_line_items(currency: Currency, line_items: &[StoreInvoiceLineItem]) -> Result<Amount, ServiceError> {
+    if line_items.is_empty() {
+        return Err(invoice_details_validation_error(
+            "line_items",
+            "empty",
+            "Invoice must have at least one line item",
+        ));
+    }
+
+    if line_items.iter().any(|line_item| line_item.description.trim().is_empty()) {
+        return Err(invoice_details_validation_error(
+            "line_items",
+            "description",
+            "Line item description must not be empty",
+        ));
+    }
+
+    store_invoice_total(currency, line_items).ok_or_else(|| {
+        invoice_details_validation_error(
+            "line_items",
+            "amount",
+            "Line items must have a positive quantity and a price of at least one minimal unit of the currency",
+        )
+    })
+}
+
+/// Validates the line items of an order of a new invoice, their amounts must add up to the order total
+fn validate_order_line_items(
+    order_id: OrderV2Id,
+    currency: Currency,
+    total_amount: Amount,
+    line_items: Vec<CreateOrderLineItem>,
+) -> Result<Vec<NewOrderLineItem>, ServiceError> {
+    if line_items.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    if line_items.iter().any(|line_item| line_item.name.trim().is_empty()) {
+        return Err(invoice_details_validation_error(
+            "line_items",
+            "name",
+            &format!("Line item names of order {} must not be empty", order_id),
+        ));
+    }
+
+    let line_items_total = order_line_items_total(currency, &line_items).ok_or_else(|| {
+        invoice_details_validation_error(
+            "line_items",
+            "amount",
+            &format!(
+                "Line items of order {} must have a positive quantity and a price of at least one minimal unit of the currency",
+                order_id
+            ),
+        )
+    })?;
+    if line_items_total != total_amount {
+        return Err(invoice_details_validation_error(
+            "line_items",
+            "total",
+            &format!(
+                "Line items of order {} add up to {} instead of the order total {}",
+                order_id,
+                line_items_total.to_super_unit(currency),
+                total_amount.to_super_unit(currency)
+            ),
+        ));
+    }
+
+    line_items
+        .into_iter()
+        .map(|line_item| {
+            NewOrderLineItem::new(order_id, currency, line_item).ok_or_else(|| {
+                invoice_details_validation_error(
+                    "line_items",
+                    "quantity",
+                    &format!("Line item quantities of order {} are out of range", order_id),
+                )
+            })
+        })
+        .collect()
+}
+
+fn invoice_details_validation_error(field: &'static str, code: &'static str, message: &str) -> ServiceError {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.to_string().into());
+    errors.add(field, error);
+    ectx!(err ErrorContext::InvoiceDetails, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
+fn invoice_requote_validation_error(code: &'static str, message: String) -> ServiceError {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    errors.add("invoice_id", error);
+    ectx!(err ErrorContext::InvoiceRequote, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
+fn invoice_cancel_validation_error(code: &'static str, message: String) -> ServiceError {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    errors.add("invoice_id", error);
+    ectx!(err ErrorContext::InvoiceCancel, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
+fn payments_sandbox_validation_error(code: &'static str, message: String) -> ServiceError {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    errors.add("invoice_id", error);
+    ectx!(err ErrorContext::PaymentsSandbox, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
+/// Amount a simulated transaction sends to an invoice that still awaits `amount_remaining`
+pub fn simulated_payment_amount(kind: SimulatedPaymentKind, amount_remaining: BigDecimal) -> BigDecimal {
+    match kind {
+        SimulatedPaymentKind::Full => amount_remaining,
+        SimulatedPaymentKind::Partial => amount_remaining / BigDecimal::from(2),
+        SimulatedPaymentKind::Overpayment => amount_remaining * BigDecimal::from(3) / BigDecimal::from(2),
+    }
+}
+
 fn create_payment_intent(
-    stripe_client: Arc<dyn StripeClient>,
+    fiat_payment_provider: Arc<dyn FiatPaymentProvider>,
     orders: &[(NewOrder, Option<ExchangeId>, BigDecimal)],
     invoice_id: InvoiceV2Id,
     buyer_currency: Currency,
+    receipt_email: Option<String>,
+    description: Option<String>,
 ) -> ServiceFutureV2<(NewPaymentIntent, NewPaymentIntentInvoice)> {
-    let fut = payment_intent_create_params(orders, invoice_id, buyer_currency)
+    let fut = invoice_payment_amount(orders, invoice_id)
         .into_future()
-        .and_then(move |payment_intent_creation| {
-            stripe_client
-                .create_payment_intent(payment_intent_creation)
+        .and_then(move |amount| {
+            let new_payment_intent = NewFiatPaymentIntent {
+                amount,
+                currency: buyer_currency,
+                capture_method: CaptureMethod::Manual,
+                receipt_email,
+                description,
+            };
+            fiat_payment_provider
+                .create_payment_intent(new_payment_intent)
                 .map_err(ectx!(convert => invoice_id))
         })
-        .and_then(move |stripe_payment_intent| new_payment_intent(invoice_id, stripe_payment_intent));
+        .map(move |payment_intent| {
+            let payment_intent_invoice = NewPaymentIntentInvoice {
+                invoice_id,
+                payment_intent_id: payment_intent.id.clone(),
+            };
+            (payment_intent, payment_intent_invoice)
+        });
 
     Box::new(fut)
 }
@@ -1126,24 +2473,36 @@ pub fn get_invoice_price_by_invoice_id(
     orders_repo: &OrdersRepo,
     rates_repo: &OrderExchangeRatesRepo,
     accounts_repo: &AccountsRepo,
+    invoice_snapshots_repo: &InvoiceSnapshotsRepo,
     invoice_id: InvoiceV2Id,
 ) -> Result<Option<InvoiceDump>, ServiceError> {
     let invoice = invoices_repo.get(invoice_id.clone()).map_err(ectx!(try convert => invoice_id))?;
 
     match invoice {
         None => Ok(None),
-        Some(invoice) => get_invoice_price(orders_repo, rates_repo, accounts_repo, invoice).map(Some),
+        Some(invoice) => get_invoice_price(orders_repo, rates_repo, accounts_repo, invoice_snapshots_repo, invoice).map(Some),
     }
 }
 
-/// Gets all of the invoice data from the DB and calculates the total price
+/// Gets all of the invoice data from the DB and calculates the total price.
+/// Paid invoices are read from the snapshot taken at payment time when there is one
 pub fn get_invoice_price(
     orders_repo: &OrdersRepo,
     rates_repo: &OrderExchangeRatesRepo,
     accounts_repo: &AccountsRepo,
+    invoice_snapshots_repo: &InvoiceSnapshotsRepo,
     invoice: RawInvoice,
 ) -> Result<InvoiceDump, ServiceError> {
     let invoice_id = invoice.id.clone();
+    if invoice.paid_at.is_some() {
+        let snapshot = invoice_snapshots_repo.get(invoice_id).map_err(ectx!(try convert => invoice_id))?;
+        if let Some(snapshot) = snapshot {
+            return snapshot
+                .invoice_dump(&invoice)
+                .map_err(ectx!(ErrorContext::InvoiceSnapshot, ErrorKind::Internal => invoice_id));
+        }
+    }
+
     let orders_with_rates = orders_repo
         .get_many_by_invoice_id(invoice_id.clone())
         .map_err(ectx!(try convert => invoice_id))?
@@ -1178,6 +2537,21 @@ pub fn get_invoice_price(
     Ok(calculate_invoice_price(invoice, orders_with_rates, wallet_address))
 }
 
+/// Stores the dump of an invoice that has just been paid, the invoice is read from it from now on
+pub fn record_invoice_snapshot(
+    invoice_snapshots_repo: &InvoiceSnapshotsRepo,
+    invoice_dump: &InvoiceDump,
+    paid_at: NaiveDateTime,
+) -> Result<(), ServiceError> {
+    let invoice_id = invoice_dump.id;
+    let new_snapshot = NewInvoiceSnapshot::new(invoice_dump, paid_at)
+        .map_err(ectx!(try ErrorContext::InvoiceSnapshot, ErrorKind::Internal => invoice_id))?;
+    invoice_snapshots_repo
+        .create(new_snapshot.clone())
+        .map_err(ectx!(try convert => new_snapshot))?;
+    Ok(())
+}
+
 /// Returns new and updated active rates which then have to be saved in the database. Rates that remained the same get filetered out
 pub fn refresh_rates<PC: PaymentsClient + Send + Clone + 'static>(
     payments_client: PC,
@@ -1250,6 +2624,58 @@ pub fn reserve_or_refresh_rate<PC: PaymentsClient + Send + Clone + 'static>(
     Box::new(fut)
 }
 
+/// Ids of the active rates the rates of the orders are refreshed from
+pub fn active_rate_ids(
+    current_order_rates: &[(RawOrder, Option<RawOrderExchangeRate>)],
+) -> HashMap<OrderV2Id, Option<OrderExchangeRateId>> {
+    current_order_rates
+        .iter()
+        .map(|(order, rate)| (order.id, rate.as_ref().map(|rate| rate.id)))
+        .collect()
+}
+
+/// Saves the refreshed rates of the invoice holding its lock. A rate is dropped if a concurrent recalculation has replaced
+/// the active rate of the order since the rate was refreshed from it, so that every refresh adds a single rate
+pub fn save_refreshed_rates<C>(
+    conn: &C,
+    invoices_repo: &InvoicesV2Repo,
+    orders_repo: &OrdersRepo,
+    rates_repo: &OrderExchangeRatesRepo,
+    invoice_id: InvoiceV2Id,
+    refreshed_from: HashMap<OrderV2Id, Option<OrderExchangeRateId>>,
+    new_rates: Vec<NewOrderExchangeRate>,
+) -> Result<(), ServiceError>
+where
+    C: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+{
+    if new_rates.is_empty() {
+        return Ok(());
+    }
+
+    conn.transaction::<_, ServiceError, _>(move || {
+        invoices_repo
+            .lock_for_recalculation(invoice_id)
+            .map_err(ectx!(try convert => invoice_id))?;
+        let active_rates = active_rate_ids(&get_order_active_rates(orders_repo, rates_repo, invoice_id)?);
+
+        for new_rate in new_rates {
+            if active_rates.get(&new_rate.order_id) != refreshed_from.get(&new_rate.order_id) {
+                debug!(
+                    "Rate of order {} has been refreshed by a concurrent recalculation of invoice {}",
+                    new_rate.order_id, invoice_id
+                );
+                continue;
+            }
+
+            rates_repo
+                .add_new_active_rate(new_rate.clone())
+                .map_err(ectx!(try convert => new_rate))?;
+        }
+
+        Ok(())
+    })
+}
+
 pub fn calculate_invoice_price_and_set_final_price_if_paid<C>(
     conn: &C,
     invoices_repo: &InvoicesV2Repo,
@@ -1257,12 +2683,18 @@ pub fn calculate_invoice_price_and_set_final_price_if_paid<C>(
     rates_repo: &OrderExchangeRatesRepo,
     accounts_repo: &AccountsRepo,
     event_store_repo: &EventStoreRepo,
+    exchange_rate_slippages_repo: &ExchangeRateSlippagesRepo,
+    invoice_snapshots_repo: &InvoiceSnapshotsRepo,
     invoice_id: InvoiceV2Id,
 ) -> Result<InvoiceDump, ServiceError>
 where
     C: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
 {
     conn.transaction::<_, ServiceError, _>(move || {
+        // The invoice is read once the concurrent recalculations are done, so it is marked as paid only once
+        invoices_repo
+            .lock_for_recalculation(invoice_id)
+            .map_err(ectx!(try convert => invoice_id))?;
         let invoice = invoices_repo
             .get(invoice_id.clone())
             .map_err(ectx!(try convert => invoice_id))?
@@ -1271,7 +2703,13 @@ where
                 ectx!(try err e, ErrorKind::Internal => invoice_id)
             })?;
 
-        let invoice_dump = get_invoice_price(&*orders_repo, &*rates_repo, &*accounts_repo, invoice.clone())?;
+        let invoice_dump = get_invoice_price(
+            &*orders_repo,
+            &*rates_repo,
+            &*accounts_repo,
+            &*invoice_snapshots_repo,
+            invoice.clone(),
+        )?;
 
         // Do not update anything in DB if the invoice is already marked as paid
         if invoice.paid_at.is_some() {
@@ -1283,20 +2721,37 @@ where
             if !has_become_paid {
                 Ok(invoice_dump)
             } else {
+                let final_amount_paid = Amount::checked_from_super_unit(invoice_dump.buyer_currency, invoice_dump.total_price.clone())
+                    .ok_or_else(|| {
+                        let e = format_err!(
+                            "Invoice with ID: {} can not convert total_price: {}",
+                            invoice.id,
+                            invoice_dump.total_price
+                        );
+                        ectx!(try err e, ErrorContext::AmountConversion, ErrorKind::Internal)
+                    })?;
+                let total_cashback = invoice_dump.total_cashback.clone().unwrap_or(BigDecimal::from(0));
+                let final_cashback_amount = Amount::checked_from_super_unit(Currency::Stq, total_cashback.clone()).ok_or_else(|| {
+                    let e = format_err!("Invoice with ID: {} can not convert total_cashback: {}", invoice.id, total_cashback);
+                    ectx!(try err e, ErrorContext::AmountConversion, ErrorKind::Internal)
+                })?;
+
                 let input = InvoiceSetAmountPaid {
-                    final_amount_paid: Amount::from_super_unit(invoice_dump.buyer_currency.clone(), invoice_dump.total_price.clone()),
-                    final_cashback_amount: Amount::from_super_unit(
-                        Currency::Stq,
-                        invoice_dump.total_cashback.clone().unwrap_or(BigDecimal::from(0)),
-                    ),
+                    final_amount_paid,
+                    final_cashback_amount,
                     paid_at: chrono::Utc::now().naive_utc(),
                 };
 
                 let invoice_id = invoice.id.clone();
-                let invoice_dump = invoices_repo
+                let paid_at = input.paid_at;
+                let mut invoice_dump = invoices_repo
                     .set_amount_paid(invoice_id.clone(), input.clone())
                     .map_err(ectx!(try convert => invoice_id, input))
                     .map(|_| invoice_dump)?;
+                invoice_dump.paid_at = Some(paid_at);
+
+                record_exchange_rate_slippages(exchange_rate_slippages_repo, &invoice_dump)?;
+                record_invoice_snapshot(invoice_snapshots_repo, &invoice_dump, paid_at)?;
 
                 // Publish "InvoicePaid" event
                 let event = Event::new(EventPayload::InvoicePaid { invoice_id: invoice.id });
@@ -1308,71 +2763,57 @@ where
     })
 }
 
-fn payment_intent_create_params(
-    orders: &[(NewOrder, Option<ExchangeId>, BigDecimal)],
-    invoice_id: InvoiceV2Id,
-    buyer_currency: Currency,
-) -> Result<StripeClientNewPaymentIntent, ServiceError> {
-    use bigdecimal::ToPrimitive;
-
-    let exchanged_amount: BigDecimal = orders
+/// Total price of the orders in base units of the buyer currency, not rounded
+fn exchanged_orders_amount(orders: &[(NewOrder, Option<ExchangeId>, BigDecimal)]) -> BigDecimal {
+    orders
         .iter()
         .map(|(order, _, exchange_rate)| {
             let seller_price: BigDecimal = order.total_amount.into();
             let exchanged_price = seller_price / exchange_rate;
             exchanged_price
         })
-        .fold(BigDecimal::from(0), |acc, next| acc + next);
-    let amount = exchanged_amount.to_u64().ok_or_else(|| {
-        let e = format_err!("Invoice with ID: {} can not convert total_price: {}", invoice_id, exchanged_amount,);
-        ectx!(try err e, ErrorKind::Internal)
-    })?;
+        .fold(BigDecimal::from(0), |acc, next| acc + next)
+}
 
-    Ok(StripeClientNewPaymentIntent {
-        allowed_source_types: vec![stripe::PaymentIntentSourceType::Card],
-        amount,
-        currency: buyer_currency.try_into_stripe_currency().map_err(|_| {
-            let e = format_err!("Invoice with ID: {} can not convert total_price: {}", invoice_id, buyer_currency,);
-            ectx!(try err e, ErrorKind::Internal)
-        })?,
-        capture_method: Some(stripe::CaptureMethod::Automatic),
+/// Total price of the orders in the buyer currency
+fn invoice_payment_amount(orders: &[(NewOrder, Option<ExchangeId>, BigDecimal)], invoice_id: InvoiceV2Id) -> Result<Amount, ServiceError> {
+    let exchanged_amount = exchanged_orders_amount(orders);
+    Amount::checked_from_decimal(exchanged_amount.clone()).ok_or_else(|| {
+        let e = format_err!("Invoice with ID: {} can not convert total_price: {}", invoice_id, exchanged_amount,);
+        ectx!(err e, ErrorContext::AmountConversion, ErrorKind::Internal)
     })
 }
 
-fn new_payment_intent(
-    invoice_id: InvoiceV2Id,
-    stripe_payment_intent: stripe::PaymentIntent,
-) -> Result<(NewPaymentIntent, NewPaymentIntentInvoice), ServiceError> {
-    let payment_intent = NewPaymentIntent {
-        id: PaymentIntentId(stripe_payment_intent.id.clone()),
-        amount: stripe_payment_intent.amount.into(),
-        amount_received: stripe_payment_intent.amount_received.into(),
-        client_secret: stripe_payment_intent.client_secret,
-        currency: Currency::try_from_stripe_currency(stripe_payment_intent.currency).map_err({
-            let e = format_err!(
-                "Payment intent for invoice with ID: {} can not convert currency: {}",
-                invoice_id,
-                stripe_payment_intent.currency,
-            );
-            move |_| ectx!(try err e, ErrorKind::Internal)
-        })?,
-        last_payment_error_message: stripe_payment_intent.last_payment_error.map(|err| format!("{:?}", err)),
-        receipt_email: stripe_payment_intent.receipt_email,
-        charge_id: stripe_payment_intent
-            .charges
-            .data
-            .into_iter()
-            .next()
-            .map(|charge| ChargeId::new(charge.id)),
-        status: stripe_payment_intent.status.into(),
-    };
+/// Total price of the orders in the buyer currency rounded up, so that capturing it pays the invoice
+fn invoice_amount_due(orders: &[(NewOrder, Option<ExchangeId>, BigDecimal)], invoice_id: InvoiceV2Id) -> Result<Amount, ServiceError> {
+    let amount = invoice_payment_amount(orders, invoice_id)?;
+    let exchanged_amount = exchanged_orders_amount(orders);
+    if BigDecimal::from(amount) < exchanged_amount {
+        amount.checked_add(Amount::new(1)).ok_or_else(|| {
+            let e = format_err!("Invoice with ID: {} can not round total_price up: {}", invoice_id, exchanged_amount);
+            ectx!(err e, ErrorContext::AmountConversion, ErrorKind::Internal)
+        })
+    } else {
+        Ok(amount)
+    }
+}
 
-    let payment_intent_invoice = NewPaymentIntentInvoice {
-        invoice_id,
-        payment_intent_id: PaymentIntentId(stripe_payment_intent.id),
+/// Amount of an order given in super units of the seller currency,
+/// amounts that are negative, not finite or too large to be stored are rejected
+fn order_amount(order_id: OrderV2Id, currency: Currency, value: f64, field: &str) -> Result<Amount, ServiceError> {
+    let amount = if value.is_finite() {
+        Amount::checked_from_super_unit(currency, BigDecimal::from(value))
+    } else {
+        None
     };
 
-    Ok((payment_intent, payment_intent_invoice))
+    amount.ok_or_else(|| {
+        let e = format_err!("Order with ID: {} has {} out of range: {}", order_id, field, value);
+        ectx!(err e, ErrorContext::AmountConversion, ErrorKind::Validation(serde_json::json!({
+            "order_id": order_id,
+            field: value.to_string(),
+        })))
+    })
 }
 
 pub fn to_ture_currency(currency: Currency) -> Box<Future<Item = TureCurrency, Error = ServiceError>> {
@@ -1387,35 +2828,19 @@ pub fn to_ture_currency(currency: Currency) -> Box<Future<Item = TureCurrency, E
 }
 
 pub fn check_ture_sign(sign_public_key: String, signature: String, body: String) -> Result<(), ServiceError> {
-    let mut hasher = Sha256::new();
-    hasher.input(&body);
-    let bytes = hasher.result();
-    let message = Message::from_slice(&bytes).map_err(ectx!(try ErrorContext::WrongMessage, ErrorKind::Forbidden))?;
-    let secp = Secp256k1::new();
-    let public_key =
-        PublicKey::from_slice(&parse_hex(&sign_public_key)).map_err(ectx!(try ErrorContext::PublicKey, ErrorKind::Forbidden))?;
-    let sig = Signature::from_compact(&parse_hex(&signature)).map_err(ectx!(try ErrorContext::Sign, ErrorKind::Forbidden))?;
-    secp.verify(&message, &sig, &public_key)
-        .map_err(ectx!(ErrorContext::VerifySign, ErrorKind::Forbidden))
+    verify_ture_signature(&sign_public_key, &signature, &body).map_err(|e| {
+        let context = match e {
+            SignatureError::MalformedKey => ErrorContext::PublicKey,
+            SignatureError::MalformedSignature(_) => ErrorContext::Sign,
+            _ => ErrorContext::VerifySign,
+        };
+        ectx!(err e, context, ErrorKind::Forbidden)
+    })
 }
 
-pub fn parse_hex(hex_asm: &str) -> Vec<u8> {
-    let mut hex_bytes = hex_asm
-        .as_bytes()
-        .iter()
-        .filter_map(|b| match b {
-            b'0'...b'9' => Some(b - b'0'),
-            b'a'...b'f' => Some(b - b'a' + 10),
-            b'A'...b'F' => Some(b - b'A' + 10),
-            _ => None,
-        })
-        .fuse();
-
-    let mut bytes = Vec::new();
-    while let (Some(h), Some(l)) = (hex_bytes.next(), hex_bytes.next()) {
-        bytes.push(h << 4 | l)
-    }
-    bytes
+/// Signs the body of a callback the way Payments gateway does, `check_ture_sign` accepts the signature
+pub fn sign_ture_callback(sign_private_key: &str, body: &str) -> Result<String, ServiceError> {
+    sign_ture_body(sign_private_key, body).map_err(|e| ectx!(err e, ErrorContext::Sign, ErrorKind::Internal))
 }
 
 /// The Commission for the services of the platform from sellers who trade in ' STQ ' is deducted in Fiat currency.
@@ -1429,14 +2854,12 @@ pub fn create_crypto_fee(
 ) -> Result<NewFee, ServiceError> {
     let hundred_percents = 100u64;
 
-    let exchange_rate = currency_exchange_info
-        .data
-        .get(&order.seller_currency)
-        .and_then(|exchanges| exchanges.get(&fee_currency).map(|c| c.0))
+    let exchange_rate = fee_exchange_rate(currency_exchange_info, order.seller_currency, *fee_currency)
         .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
 
     let total_amount_super_unit = order.total_amount.to_super_unit(order.seller_currency);
-    let convert_total_amount = Amount::from_super_unit(fee_currency.clone(), total_amount_super_unit / BigDecimal::from(exchange_rate));
+    let convert_total_amount = Amount::checked_from_super_unit(*fee_currency, total_amount_super_unit / BigDecimal::from(exchange_rate))
+        .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
 
     let amount = convert_total_amount
         .checked_div(Amount::from(hundred_percents))
@@ -1452,9 +2875,20 @@ pub fn create_crypto_fee(
         metadata: None,
         crypto_currency: Some(order.seller_currency.clone()),
         crypto_amount: Some(order.total_amount.clone()),
+        exchange_rate: Some(exchange_rate),
+        currency_exchange_id: Some(currency_exchange_info.id.0),
+        converted_at: Some(Utc::now().naive_utc()),
     })
 }
 
+/// Rate the crypto fee is converted with: the amount in the order currency one unit of the fee currency is worth
+fn fee_exchange_rate(currency_exchange_info: &CurrencyExchangeInfo, order_currency: Currency, fee_currency: Currency) -> Option<f64> {
+    currency_exchange_info
+        .data
+        .get(&order_currency)
+        .and_then(|exchanges| exchanges.get(&fee_currency).map(|c| c.0))
+}
+
 #[cfg(test)]
 pub mod tests {
 
@@ -1466,20 +2900,26 @@ pub mod tests {
     use uuid::Uuid;
 
     use models::currency::Currency as StqCurrency;
-    use stq_static_resources::Currency;
+    use stq_static_resources::{Currency, OrderState};
     use stq_types::*;
 
     use client::stores::*;
+    use controller::requests::SimulatedPaymentKind;
     use models::invoice_v2::InvoiceId as InvoiceIdv2;
-    use models::order_v2::{OrderId as OrderIdv2, RawOrder, StoreId as StoreIdv2};
+    use models::order_v2::{NewOrder, OrderId as OrderIdv2, RawOrder, StoreId as StoreIdv2};
     use models::*;
     use repos::repo_factory::tests::*;
-    use services::invoice::create_crypto_fee;
+    use secp256k1::{PublicKey, Secp256k1, SecretKey};
     use services::invoice::InvoiceService;
+    use services::invoice::{
+        buyer_country_of, check_ture_sign, create_crypto_fee, invoice_payment_amount, order_amount, sign_ture_callback,
+        simulated_payment_amount, validate_buyer_billing_details, validate_order_line_items,
+    };
     use services::merchant::MerchantService;
+    use test_support::{InMemoryReposFactory, InvoiceBuilder, OrderInfoBuilder};
 
     #[test]
-    #[ignore]
+    #[ignore] // needs the merchant gateway and the Payments gateway
     fn test_create_order_info() {
         let id = UserId(1);
         let mut core = Core::new().unwrap();
@@ -1518,23 +2958,64 @@ pub mod tests {
     }
 
     #[test]
-    #[ignore]
     fn test_set_paid() {
         let mut core = Core::new().unwrap();
         let handle = Arc::new(core.handle());
-        let service = create_service(Some(UserId(1)), handle);
-        let invoice = ExternalBillingInvoice {
-            id: InvoiceId::new(),
-            amount: "0.000000000".to_string(),
-            status: ExternalBillingStatus::New,
+        let invoice = InvoiceBuilder::new().build();
+        let order_infos = vec![
+            OrderInfoBuilder::new().saga_id(invoice.id).build(),
+            OrderInfoBuilder::new().saga_id(invoice.id).build(),
+        ];
+        let repo_factory = InMemoryReposFactory::new()
+            .with_legacy_invoices(vec![invoice.clone()])
+            .with_order_infos(order_infos);
+        let service = create_service_with_repo_factory(Some(UserId(1)), handle, repo_factory.clone());
+        let external_invoice = ExternalBillingInvoice {
+            id: invoice.invoice_id,
+            amount: "1.000000000".to_string(),
+            status: ExternalBillingStatus::Done,
             wallet: Some("wallet".to_string()),
-            amount_captured: "0.000000000".to_string(),
+            amount_captured: "1.000000000".to_string(),
             transactions: None,
             currency: Currency::STQ,
             expired: SystemTime::now().into(),
         };
-        let work = service.update_invoice(invoice);
-        let _result = core.run(work).unwrap();
+        let work = service.update_invoice(external_invoice);
+        core.run(work).unwrap();
+
+        let state = repo_factory.state();
+        assert_eq!(state.legacy_invoices[0].state, OrderState::Paid);
+        assert!(state.order_infos.iter().all(|order_info| order_info.status == OrderState::Paid));
+        assert_eq!(state.events.len(), 1);
+        match state.events[0].event.payload {
+            EventPayload::SagaOrderStatesUpdate { ref order_states } => assert_eq!(order_states.len(), 2),
+            _ => panic!("order states update is expected"),
+        }
+    }
+
+    #[test]
+    fn test_set_paid_fails_when_order_infos_are_not_updated() {
+        let mut core = Core::new().unwrap();
+        let handle = Arc::new(core.handle());
+        let invoice = InvoiceBuilder::new().build();
+        let repo_factory = InMemoryReposFactory::new()
+            .with_legacy_invoices(vec![invoice.clone()])
+            .fail_on("order_infos.update_status");
+        let service = create_service_with_repo_factory(Some(UserId(1)), handle, repo_factory.clone());
+        let external_invoice = ExternalBillingInvoice {
+            id: invoice.invoice_id,
+            amount: "1.000000000".to_string(),
+            status: ExternalBillingStatus::Done,
+            wallet: None,
+            amount_captured: "1.000000000".to_string(),
+            transactions: None,
+            currency: Currency::STQ,
+            expired: SystemTime::now().into(),
+        };
+        let work = service.update_invoice(external_invoice);
+
+        assert!(core.run(work).is_err());
+        assert!(repo_factory.state().events.is_empty());
     }
 
     #[test]
@@ -1572,6 +3053,134 @@ pub mod tests {
         let new_fee = create_crypto_fee(order_percent, &fee_currency, &currency_exchange_info, &order).expect("cannot get new fee");
 
         assert_eq!(new_fee.amount, Amount::from_super_unit(fee_currency, BigDecimal::from(1)));
+        assert_eq!(new_fee.exchange_rate, Some(5.0));
+        assert_eq!(new_fee.currency_exchange_id, Some(currency_exchange_info.id.0));
+        assert!(new_fee.converted_at.is_some());
+    }
+
+    #[test]
+    fn order_amounts_out_of_range_are_rejected() {
+        let order_id = OrderIdv2::new(Uuid::new_v4());
+
+        assert_eq!(
+            order_amount(order_id, StqCurrency::Usd, 20.5, "total_amount").unwrap(),
+            Amount::new(2050)
+        );
+        for value in &[-1.0, 1e300, ::std::f64::NAN, ::std::f64::INFINITY] {
+            assert!(
+                order_amount(order_id, StqCurrency::Usd, *value, "total_amount").is_err(),
+                "value: {}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn order_line_items_add_up_to_the_order_total() {
+        use std::str::FromStr;
+
+        let order_id = OrderIdv2::new(Uuid::new_v4());
+        let line_item = |name: &str, quantity: u32, unit_price: &str| CreateOrderLineItem {
+            product_id: ProductId(1),
+            name: name.to_string(),
+            quantity,
+            unit_price: BigDecimal::from_str(unit_price).unwrap(),
+        };
+
+        let line_items = validate_order_line_items(
+            order_id,
+            StqCurrency::Usd,
+            Amount::new(2029),
+            vec![line_item("Mug", 3, "0.1"), line_item("Poster", 1, "19.99")],
+        )
+        .unwrap();
+        assert_eq!(line_items.len(), 2);
+        assert!(line_items.iter().all(|line_item| line_item.order_id == order_id));
+
+        assert!(validate_order_line_items(order_id, StqCurrency::Usd, Amount::new(1000), vec![])
+            .unwrap()
+            .is_empty());
+        assert!(validate_order_line_items(order_id, StqCurrency::Usd, Amount::new(2000), vec![line_item("Mug", 1, "19.99")]).is_err());
+        assert!(validate_order_line_items(order_id, StqCurrency::Usd, Amount::new(1999), vec![line_item(" ", 1, "19.99")]).is_err());
+    }
+
+    #[test]
+    fn buyer_billing_details_decide_the_buyer_country() {
+        let details = BuyerBillingDetails {
+            name: "Jane Doe".to_string(),
+            address_line1: "Main st. 1".to_string(),
+            address_line2: None,
+            city: "Berlin".to_string(),
+            postal_code: Some("10115".to_string()),
+            region: None,
+            country: Alpha3("DEU".to_string()),
+            tax_id: Some("DE123456789".to_string()),
+        };
+        assert!(validate_buyer_billing_details(&details).is_ok());
+
+        let country = buyer_country_of(None, Some(&details)).unwrap().unwrap();
+        assert_eq!(country.0, "DEU");
+        let country = buyer_country_of(Some(Alpha3("deu".to_string())), Some(&details)).unwrap().unwrap();
+        assert_eq!(country.0, "deu");
+        assert!(buyer_country_of(Some(Alpha3("FRA".to_string())), Some(&details)).is_err());
+        assert!(buyer_country_of(None, None).unwrap().is_none());
+
+        let no_name = BuyerBillingDetails {
+            name: "".to_string(),
+            ..details.clone()
+        };
+        assert!(validate_buyer_billing_details(&no_name).is_err());
+        let bad_country = BuyerBillingDetails {
+            country: Alpha3("DE".to_string()),
+            ..details
+        };
+        assert!(validate_buyer_billing_details(&bad_country).is_err());
+    }
+
+    #[test]
+    fn invoice_payment_amount_is_not_limited_to_u64() {
+        // 100 ETH in wei is beyond u64
+        let total_amount = Amount::new(100 * 10u128.pow(18));
+        let invoice_id = InvoiceIdv2::new(Uuid::new_v4());
+        let order = NewOrder {
+            id: OrderIdv2::new(Uuid::new_v4()),
+            seller_currency: StqCurrency::Eth,
+            total_amount,
+            cashback_amount: Amount::zero(),
+            invoice_id,
+            store_id: StoreIdv2::new(1),
+        };
+
+        let amount = invoice_payment_amount(&[(order, None, BigDecimal::from(1))], invoice_id).unwrap();
+        assert_eq!(amount, total_amount);
     }
 
+    #[test]
+    fn simulated_callbacks_pass_the_sign_check() {
+        let sign_private_key = "42".repeat(32);
+        let secret_key = SecretKey::from_slice(&[0x42; 32]).unwrap();
+        let sign_public_key = ::hex::encode(&PublicKey::from_secret_key(&Secp256k1::new(), &secret_key).serialize()[..]);
+        let body = r#"{"transactionId":"6f9e3c2a-0d4b-4a37-9d65-2f0a5c1b7e10","amountCaptured":"1000"}"#.to_string();
+
+        let signature = sign_ture_callback(&sign_private_key, &body).unwrap();
+        assert!(check_ture_sign(sign_public_key.clone(), signature.clone(), body.clone()).is_ok());
+        assert!(check_ture_sign(sign_public_key, signature, body.replace("1000", "2000")).is_err());
+    }
+
+    #[test]
+    fn simulated_payments_are_relative_to_the_amount_remaining() {
+        let amount_remaining = BigDecimal::from(10);
+        assert_eq!(
+            simulated_payment_amount(SimulatedPaymentKind::Full, amount_remaining.clone()),
+            BigDecimal::from(10)
+        );
+        assert_eq!(
+            simulated_payment_amount(SimulatedPaymentKind::Partial, amount_remaining.clone()),
+            BigDecimal::from(5)
+        );
+        assert_eq!(
+            simulated_payment_amount(SimulatedPaymentKind::Overpayment, amount_remaining),
+            BigDecimal::from(15)
+        );
+    }
 }
@@ -0,0 +1,184 @@
+//! InvoiceCallbackService shows external storefronts how the notifications of their invoice callbacks were delivered.
+//! The callbacks are registered at invoice creation and notified of paid, expired and refunded invoices
+//! through the event store, so they don't depend on the saga the marketplace orders go through
+use chrono::Utc;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use serde_json;
+use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
+
+use failure::Fail;
+
+use stq_types::UserId;
+
+use super::types::ServiceFutureV2;
+use controller::responses::InvoiceCallbackResponse;
+use models::invoice_v2::InvoiceId;
+use models::order_v2::RawOrder;
+use models::{Amount, Event, EventPayload, InvoiceCallbackEventType, InvoiceCallbackNotification, InvoiceCallbackRegistration};
+use repos::{EventStoreRepo, InvoiceCallbacksRepo, ReposFactory};
+use services::types::spawn_on_pool;
+use services::{Error, ErrorContext, ErrorKind};
+
+pub trait InvoiceCallbackService {
+    /// Callback of the invoice with its delivery log, none if the invoice has no callback
+    fn get_invoice_callback(&self, invoice_id: InvoiceId) -> ServiceFutureV2<Option<InvoiceCallbackResponse>>;
+}
+
+pub struct InvoiceCallbackServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > InvoiceCallbackService for InvoiceCallbackServiceImpl<T, M, F>
+{
+    fn get_invoice_callback(&self, invoice_id: InvoiceId) -> ServiceFutureV2<Option<InvoiceCallbackResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let invoices_repo = repo_factory.create_invoices_v2_repo(&conn, user_id);
+            let invoice_callbacks_repo = repo_factory.create_invoice_callbacks_repo_with_sys_acl(&conn);
+
+            // The callback is visible to whoever may see the invoice
+            if invoices_repo.get(invoice_id).map_err(ectx!(try convert => invoice_id))?.is_none() {
+                return Ok(None);
+            }
+
+            let invoice_callback = match invoice_callbacks_repo
+                .get_by_invoice_id(invoice_id)
+                .map_err(ectx!(try convert => invoice_id))?
+            {
+                None => return Ok(None),
+                Some(invoice_callback) => invoice_callback,
+            };
+
+            let invoice_callback_id = invoice_callback.id;
+            let deliveries = invoice_callbacks_repo
+                .list_deliveries(invoice_callback_id)
+                .map_err(ectx!(try convert => invoice_callback_id))?;
+
+            Ok(Some(InvoiceCallbackResponse::new(invoice_callback, deliveries)))
+        })
+    }
+}
+
+/// Schedules delivery of the notification if the invoice has a callback.
+/// A failed delivery is retried by the event store with the same notification id
+pub fn enqueue_invoice_callback_delivery(
+    invoice_callbacks_repo: &InvoiceCallbacksRepo,
+    event_store_repo: &EventStoreRepo,
+    invoice_id: InvoiceId,
+    event_type: InvoiceCallbackEventType,
+    data: serde_json::Value,
+) -> Result<(), Error> {
+    let invoice_callback = invoice_callbacks_repo
+        .get_by_invoice_id(invoice_id)
+        .map_err(ectx!(try convert => invoice_id))?;
+
+    if let Some(invoice_callback) = invoice_callback {
+        let event = Event::new(EventPayload::InvoiceCallbackDelivery {
+            invoice_callback_id: invoice_callback.id,
+            notification: InvoiceCallbackNotification {
+                id: Uuid::new_v4(),
+                event_type,
+                invoice_id,
+                data,
+                created_at: Utc::now().naive_utc(),
+            },
+        });
+        event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+    }
+
+    Ok(())
+}
+
+/// Amounts of the orders are given in their seller currencies
+pub fn invoice_paid_callback_data(orders: &[RawOrder]) -> serde_json::Value {
+    let orders = orders
+        .iter()
+        .map(|order| {
+            json!({
+                "order_id": order.id,
+                "store_id": order.store_id,
+                "currency": order.seller_currency,
+                "total_amount": order.total_amount.to_super_unit(order.seller_currency),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({ "orders": orders })
+}
+
+pub fn invoice_refunded_callback_data(order: &RawOrder, refund_amount: Amount) -> serde_json::Value {
+    json!({
+        "order_id": order.id,
+        "store_id": order.store_id,
+        "currency": order.seller_currency,
+        "refund_amount": refund_amount.to_super_unit(order.seller_currency),
+    })
+}
+
+pub fn validate_invoice_callback(callback: &InvoiceCallbackRegistration) -> Result<(), Error> {
+    let mut errors = ValidationErrors::new();
+
+    if !callback.url.starts_with("https://") && !callback.url.starts_with("http://") {
+        let mut error = ValidationError::new("invalid_url");
+        error.message = Some("Callback url must be an absolute http(s) url".into());
+        errors.add("callback.url", error);
+    }
+
+    if callback.secret.trim().is_empty() {
+        let mut error = ValidationError::new("empty");
+        error.message = Some("Callback secret must not be empty".into());
+        errors.add("callback.secret", error);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ectx!(err ErrorContext::InvoiceCallback, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invoice_callback_requires_http_url_and_secret() {
+        let callback = InvoiceCallbackRegistration {
+            url: "https://shop.example.com/billing/callback".to_string(),
+            secret: "s3cr3t".to_string(),
+        };
+        assert!(validate_invoice_callback(&callback).is_ok());
+
+        let callback = InvoiceCallbackRegistration {
+            url: "shop.example.com/billing/callback".to_string(),
+            ..callback
+        };
+        assert!(validate_invoice_callback(&callback).is_err());
+
+        let callback = InvoiceCallbackRegistration {
+            url: "https://shop.example.com/billing/callback".to_string(),
+            secret: " ".to_string(),
+        };
+        assert!(validate_invoice_callback(&callback).is_err());
+    }
+}
@@ -0,0 +1,440 @@
+//! Risk scoring of the invoices buyers create. Every invoice is evaluated before it is created by the `RiskEvaluator`
+//! of the app, allowed invoices are created as usual, denied ones are not created, and the card payments of the ones
+//! held for a review are authorized but not captured until a superuser approves or rejects the invoice
+use std::net::IpAddr;
+
+use bigdecimal::BigDecimal;
+use chrono::{Duration, Utc};
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::Fail;
+use futures::future;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use serde_json;
+use validator::{ValidationError, ValidationErrors};
+
+use stq_types::UserId as StqUserId;
+
+use super::types::{ServiceFutureV2, ServiceResultV2};
+use config::InvoiceRisk as InvoiceRiskConfig;
+use controller::requests::{InvoiceRiskReviewDecision, ReviewInvoiceRiskRequest};
+use models::invoice_v2::InvoiceId;
+use models::{
+    Amount, Currency, Event, EventPayload, InvoiceRiskAssessment, InvoiceRiskReview, NewInvoiceRiskAssessment, RiskDecision,
+    RiskDecisionCount, RiskEvaluation, RiskFeatures, RiskReviewStatus, UserId,
+};
+use repos::{InvoiceRiskAssessmentsRepo, ReposFactory, SearchPaymentIntentInvoice};
+use services::types::spawn_on_pool;
+use services::{Error, ErrorContext, ErrorKind};
+
+/// Scores the risk of an invoice, the hook fraud rules are plugged in with
+pub trait RiskEvaluator: Send + Sync {
+    fn evaluate(&self, features: &RiskFeatures) -> RiskEvaluation;
+}
+
+/// Adds up the scores of the signals configured in the `invoice_risk` section
+#[derive(Clone, Debug)]
+pub struct RuleRiskEvaluator {
+    config: InvoiceRiskConfig,
+}
+
+impl RuleRiskEvaluator {
+    pub fn from_config(config: &InvoiceRiskConfig) -> Self {
+        Self { config: config.clone() }
+    }
+
+    fn is_large_amount(&self, currency: Currency, amount: Amount) -> bool {
+        let large_amount = match self.config.large_amounts.get(&currency) {
+            Some(large_amount) if large_amount.is_finite() => *large_amount,
+            _ => return false,
+        };
+
+        Amount::checked_from_super_unit(currency, BigDecimal::from(large_amount)).map_or(false, |large_amount| amount >= large_amount)
+    }
+}
+
+impl RiskEvaluator for RuleRiskEvaluator {
+    fn evaluate(&self, features: &RiskFeatures) -> RiskEvaluation {
+        let config = &self.config;
+        let history = &features.history;
+        let mut signals = Vec::new();
+
+        if features
+            .amounts
+            .iter()
+            .any(|(currency, amount)| self.is_large_amount(*currency, *amount))
+        {
+            signals.push(("large_amount", config.large_amount_score));
+        }
+
+        if history.paid_invoices_count == 0 {
+            signals.push(("new_buyer", config.new_buyer_score));
+        }
+
+        if history.invoices_count - history.paid_invoices_count >= config.max_unpaid_invoices {
+            signals.push(("unpaid_invoices", config.unpaid_invoices_score));
+        }
+
+        match features.buyer_ip {
+            None => signals.push(("missing_ip", config.missing_ip_score)),
+            Some(_) if history.ip_invoices_count >= config.max_ip_invoices => signals.push(("ip_velocity", config.ip_velocity_score)),
+            Some(_) => {}
+        }
+
+        let score = signals.iter().map(|(_, score)| score).sum::<i32>();
+        let decision = if score >= config.deny_score {
+            RiskDecision::Deny
+        } else if score >= config.review_score {
+            RiskDecision::Review
+        } else {
+            RiskDecision::Allow
+        };
+
+        RiskEvaluation {
+            score,
+            decision,
+            reasons: signals.into_iter().map(|(reason, _)| reason.to_string()).collect(),
+        }
+    }
+}
+
+/// Evaluates and records the risk of an invoice about to be created.
+/// A retried saga gets the decision recorded for the invoice the first time
+pub fn assess_invoice_risk(
+    config: &InvoiceRiskConfig,
+    risk_evaluator: &RiskEvaluator,
+    invoice_risk_assessments_repo: &InvoiceRiskAssessmentsRepo,
+    invoice_id: InvoiceId,
+    buyer_user_id: UserId,
+    buyer_ip: Option<IpAddr>,
+    amounts: Vec<(Currency, Amount)>,
+) -> ServiceResultV2<InvoiceRiskAssessment> {
+    if let Some(assessment) = invoice_risk_assessments_repo
+        .get(invoice_id)
+        .map_err(ectx!(try convert => invoice_id))?
+    {
+        return Ok(assessment);
+    }
+
+    let ip_since = Utc::now().naive_utc() - Duration::minutes(config.ip_window_min as i64);
+    let history = invoice_risk_assessments_repo
+        .get_buyer_history(buyer_user_id, buyer_ip.map(|ip| ip.to_string()), ip_since)
+        .map_err(ectx!(try convert => buyer_user_id, buyer_ip))?;
+
+    let features = RiskFeatures {
+        invoice_id,
+        buyer_user_id,
+        buyer_ip,
+        amounts,
+        history,
+    };
+    let RiskEvaluation { score, decision, reasons } = risk_evaluator.evaluate(&features);
+    if decision != RiskDecision::Allow {
+        info!(
+            "Invoice {} of buyer {} scored {} for {:?}, decision: {}",
+            invoice_id, buyer_user_id, score, reasons, decision
+        );
+    }
+
+    let payload = NewInvoiceRiskAssessment {
+        invoice_id,
+        buyer_user_id,
+        buyer_ip: buyer_ip.map(|ip| ip.to_string()),
+        score,
+        decision,
+        reasons,
+        review_status: match decision {
+            RiskDecision::Review => Some(RiskReviewStatus::Pending),
+            RiskDecision::Allow | RiskDecision::Deny => None,
+        },
+    };
+    invoice_risk_assessments_repo
+        .create(payload.clone())
+        .map_err(ectx!(convert => payload))
+}
+
+/// Error of a denied invoice, the score and the signals are not disclosed to the buyer
+pub fn invoice_risk_denied_error() -> Error {
+    invoice_risk_validation_error("invoice", "risk_denied", "Invoice was declined, contact the support".to_string())
+}
+
+fn invoice_risk_validation_error(field: &'static str, code: &'static str, message: String) -> Error {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    errors.add(field, error);
+    ectx!(err ErrorContext::InvoiceRisk, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
+pub trait InvoiceRiskService {
+    /// Risk evaluation of the invoice, `None` for the invoices created without risk scoring
+    fn get_invoice_risk_assessment(&self, invoice_id: InvoiceId) -> ServiceFutureV2<Option<InvoiceRiskAssessment>>;
+    /// Invoices waiting for a review, the oldest first
+    fn list_pending_invoice_risk_reviews(&self) -> ServiceFutureV2<Vec<InvoiceRiskAssessment>>;
+    /// Approves or rejects an invoice held for a review and re-evaluates the capture of its payment
+    fn review_invoice_risk(&self, invoice_id: InvoiceId, payload: ReviewInvoiceRiskRequest) -> ServiceFutureV2<InvoiceRiskAssessment>;
+    /// Risk decisions and reviews in the Prometheus text format
+    fn get_invoice_risk_gauges(&self) -> ServiceFutureV2<String>;
+}
+
+pub struct InvoiceRiskServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<StqUserId>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > InvoiceRiskService for InvoiceRiskServiceImpl<T, M, F>
+{
+    fn get_invoice_risk_assessment(&self, invoice_id: InvoiceId) -> ServiceFutureV2<Option<InvoiceRiskAssessment>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+
+        spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let invoice_risk_assessments_repo = repo_factory.create_invoice_risk_assessments_repo(&conn, user_id);
+
+            invoice_risk_assessments_repo.get(invoice_id).map_err(ectx!(convert => invoice_id))
+        })
+    }
+
+    fn list_pending_invoice_risk_reviews(&self) -> ServiceFutureV2<Vec<InvoiceRiskAssessment>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+
+        spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let invoice_risk_assessments_repo = repo_factory.create_invoice_risk_assessments_repo(&conn, user_id);
+
+            invoice_risk_assessments_repo.list_pending_reviews().map_err(ectx!(convert))
+        })
+    }
+
+    fn review_invoice_risk(&self, invoice_id: InvoiceId, payload: ReviewInvoiceRiskRequest) -> ServiceFutureV2<InvoiceRiskAssessment> {
+        let repo_factory = self.repo_factory.clone();
+
+        let user_id = match self.user_id {
+            None => return Box::new(future::err(ErrorKind::Forbidden.into())),
+            Some(user_id) => user_id,
+        };
+
+        let review = InvoiceRiskReview {
+            status: match payload.decision {
+                InvoiceRiskReviewDecision::Approve => RiskReviewStatus::Approved,
+                InvoiceRiskReviewDecision::Reject => RiskReviewStatus::Rejected,
+            },
+            reviewed_by: user_id,
+        };
+
+        spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let invoice_risk_assessments_repo = repo_factory.create_invoice_risk_assessments_repo(&conn, Some(user_id));
+            let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo_with_sys_acl(&conn);
+            let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+            conn.transaction(|| {
+                let assessment = match invoice_risk_assessments_repo
+                    .review(invoice_id, review.clone())
+                    .map_err(ectx!(try convert => invoice_id))?
+                {
+                    Some(assessment) => assessment,
+                    None => {
+                        invoice_risk_assessments_repo
+                            .get(invoice_id)
+                            .map_err(ectx!(try convert => invoice_id))?
+                            .ok_or_else(|| {
+                                let e = format_err!("Risk assessment of invoice {} not found", invoice_id);
+                                ectx!(try err e, ErrorKind::NotFound)
+                            })?;
+                        let message = "Invoice is not waiting for a risk review".to_string();
+                        return Err(invoice_risk_validation_error("invoice_id", "risk_review_not_pending", message));
+                    }
+                };
+                info!("Risk review of invoice {} by user {}: {}", invoice_id, user_id, review.status);
+
+                // the capture of a card payment is re-evaluated, an unpaid invoice is captured or released once it is paid
+                let payment_intent_invoice = payment_intent_invoices_repo
+                    .get(SearchPaymentIntentInvoice::InvoiceId(invoice_id))
+                    .map_err(ectx!(try convert => invoice_id))?;
+                if payment_intent_invoice.is_some() {
+                    let orders = orders_repo
+                        .get_many_by_invoice_id(invoice_id)
+                        .map_err(ectx!(try convert => invoice_id))?;
+                    if let Some(order) = orders.first() {
+                        let event = Event::new(EventPayload::PaymentIntentCapture { order_id: order.id });
+                        event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+                    }
+                }
+
+                Ok(assessment)
+            })
+        })
+    }
+
+    fn get_invoice_risk_gauges(&self) -> ServiceFutureV2<String> {
+        let repo_factory = self.repo_factory.clone();
+
+        // Scraped without a user like the other diagnostics of the instance
+        spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let invoice_risk_assessments_repo = repo_factory.create_invoice_risk_assessments_repo_with_sys_acl(&conn);
+
+            let counts = invoice_risk_assessments_repo.count_by_decision().map_err(ectx!(try convert))?;
+
+            Ok(invoice_risk_gauges(&counts))
+        })
+    }
+}
+
+/// Renders the assessments as Prometheus gauges labelled with the decision, and the reviews labelled with their status
+pub fn invoice_risk_gauges(counts: &[RiskDecisionCount]) -> String {
+    let decisions = [RiskDecision::Allow, RiskDecision::Review, RiskDecision::Deny];
+    let review_statuses = [RiskReviewStatus::Pending, RiskReviewStatus::Approved, RiskReviewStatus::Rejected];
+
+    let mut text = String::new();
+    text.push_str("# HELP billing_invoice_risk_decisions Invoices evaluated by the risk scoring by decision\n");
+    text.push_str("# TYPE billing_invoice_risk_decisions gauge\n");
+    for decision in decisions.iter() {
+        let count = counts
+            .iter()
+            .filter(|count| count.decision == *decision)
+            .map(|count| count.count)
+            .sum::<i64>();
+        text.push_str(&format!("billing_invoice_risk_decisions{{decision=\"{}\"}} {}\n", decision, count));
+    }
+
+    text.push_str("# HELP billing_invoice_risk_reviews Invoices held for a risk review by review status\n");
+    text.push_str("# TYPE billing_invoice_risk_reviews gauge\n");
+    for review_status in review_statuses.iter() {
+        let count = counts
+            .iter()
+            .filter(|count| count.review_status == Some(*review_status))
+            .map(|count| count.count)
+            .sum::<i64>();
+        text.push_str(&format!("billing_invoice_risk_reviews{{status=\"{}\"}} {}\n", review_status, count));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    use models::BuyerRiskHistory;
+
+    use super::*;
+
+    fn config() -> InvoiceRiskConfig {
+        let mut large_amounts = HashMap::new();
+        large_amounts.insert(Currency::Eur, 1000.0);
+        InvoiceRiskConfig {
+            enabled: true,
+            large_amounts,
+            ..Default::default()
+        }
+    }
+
+    fn features(amount: u128, buyer_ip: Option<IpAddr>, history: BuyerRiskHistory) -> RiskFeatures {
+        RiskFeatures {
+            invoice_id: InvoiceId::new(Uuid::new_v4()),
+            buyer_user_id: UserId::new(1),
+            buyer_ip,
+            amounts: vec![(Currency::Eur, Amount::new(amount))],
+            history,
+        }
+    }
+
+    fn returning_buyer() -> BuyerRiskHistory {
+        BuyerRiskHistory {
+            invoices_count: 3,
+            paid_invoices_count: 3,
+            ip_invoices_count: 1,
+        }
+    }
+
+    #[test]
+    fn returning_buyer_is_allowed() {
+        let evaluator = RuleRiskEvaluator::from_config(&config());
+        let ip = "10.0.0.1".parse().ok();
+
+        let evaluation = evaluator.evaluate(&features(5_000, ip, returning_buyer()));
+
+        assert_eq!(
+            evaluation,
+            RiskEvaluation {
+                score: 0,
+                decision: RiskDecision::Allow,
+                reasons: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn large_invoice_of_new_buyer_is_reviewed() {
+        let evaluator = RuleRiskEvaluator::from_config(&config());
+        let ip = "10.0.0.1".parse().ok();
+
+        let evaluation = evaluator.evaluate(&features(100_000, ip, BuyerRiskHistory::default()));
+
+        assert_eq!(evaluation.score, 60);
+        assert_eq!(evaluation.decision, RiskDecision::Review);
+        assert_eq!(evaluation.reasons, vec!["large_amount".to_string(), "new_buyer".to_string()]);
+    }
+
+    #[test]
+    fn ip_velocity_with_unpaid_invoices_is_denied() {
+        let evaluator = RuleRiskEvaluator::from_config(&config());
+        let ip = "10.0.0.1".parse().ok();
+        let history = BuyerRiskHistory {
+            invoices_count: 7,
+            paid_invoices_count: 1,
+            ip_invoices_count: 12,
+        };
+
+        let evaluation = evaluator.evaluate(&features(100_000, ip, history));
+
+        assert_eq!(evaluation.score, 120);
+        assert_eq!(evaluation.decision, RiskDecision::Deny);
+        assert_eq!(
+            evaluation.reasons,
+            vec!["large_amount".to_string(), "unpaid_invoices".to_string(), "ip_velocity".to_string()]
+        );
+    }
+
+    #[test]
+    fn gauges_count_decisions_and_reviews() {
+        let counts = vec![
+            RiskDecisionCount {
+                decision: RiskDecision::Allow,
+                review_status: None,
+                count: 10,
+            },
+            RiskDecisionCount {
+                decision: RiskDecision::Review,
+                review_status: Some(RiskReviewStatus::Approved),
+                count: 2,
+            },
+            RiskDecisionCount {
+                decision: RiskDecision::Review,
+                review_status: Some(RiskReviewStatus::Pending),
+                count: 1,
+            },
+        ];
+
+        let text = invoice_risk_gauges(&counts);
+
+        assert!(text.contains("billing_invoice_risk_decisions{decision=\"allow\"} 10\n"));
+        assert!(text.contains("billing_invoice_risk_decisions{decision=\"review\"} 3\n"));
+        assert!(text.contains("billing_invoice_risk_decisions{decision=\"deny\"} 0\n"));
+        assert!(text.contains("billing_invoice_risk_reviews{status=\"pending\"} 1\n"));
+        assert!(text.contains("billing_invoice_risk_reviews{status=\"approved\"} 2\n"));
+    }
+}
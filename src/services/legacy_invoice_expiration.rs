@@ -0,0 +1,245 @@
+//! Expires v1 invoices left awaiting payment after their price reservation ended.
+//! Their orders are expired too and saga is notified of the new order states
+use std::time::{Duration as StdDuration, Instant, SystemTime};
+
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::{Error as FailureError, Fail};
+use futures::{future, Future, Stream};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use sentry::integrations::failure::capture_error;
+use tokio_timer::Interval;
+
+use stq_static_resources::OrderState;
+use stq_types::UserId;
+
+use super::types::ServiceFuture;
+use config::LegacyInvoiceExpiration as LegacyInvoiceExpirationConfig;
+use controller::responses::LegacyInvoiceExpirationSweepResponse;
+use errors::Error;
+use models::OrderStateUpdate;
+use repos::{EventStoreRepo, InvoiceRepo, OrderInfoRepo, ReposFactory};
+use services::saga::enqueue_order_state_updates;
+
+pub trait LegacyInvoiceExpirationService {
+    /// Expires the v1 invoices awaiting payment past their price reservation and the grace period
+    fn sweep_expired_invoices(&self) -> ServiceFuture<LegacyInvoiceExpirationSweepResponse>;
+}
+
+pub struct LegacyInvoiceExpirationServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+    pub config: LegacyInvoiceExpirationConfig,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > LegacyInvoiceExpirationService for LegacyInvoiceExpirationServiceImpl<T, M, F>
+{
+    fn sweep_expired_invoices(&self) -> ServiceFuture<LegacyInvoiceExpirationSweepResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let config = self.config.clone();
+        let reserved_before = reservation_end(&config, SystemTime::now());
+
+        spawn_legacy_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let invoice_repo = repo_factory.create_invoice_repo(&*conn, user_id);
+            let order_info_repo = repo_factory.create_order_info_repo(&*conn, user_id);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&*conn);
+
+            expire_invoices(
+                &*conn,
+                &*invoice_repo,
+                &*order_info_repo,
+                &*event_store_repo,
+                reserved_before,
+                config.batch_size,
+            )
+        })
+    }
+}
+
+/// Sweeps expired invoices on every tick of `sweep_interval_sec`.
+/// A failed sweep is reported and the rest of the invoices are swept on the next tick
+pub fn run_legacy_invoice_expiration_sweep<T, M, F>(
+    config: LegacyInvoiceExpirationConfig,
+    db_pool: Pool<M>,
+    cpu_pool: CpuPool,
+    repo_factory: F,
+) -> impl Future<Item = (), Error = FailureError>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    let interval = StdDuration::from_secs(config.sweep_interval_sec);
+
+    Interval::new(Instant::now(), interval)
+        .map_err(FailureError::from)
+        .for_each(move |_| {
+            let repo_factory = repo_factory.clone();
+            let batch_size = config.batch_size;
+            let reserved_before = reservation_end(&config, SystemTime::now());
+
+            spawn_legacy_on_pool(db_pool.clone(), cpu_pool.clone(), move |conn| {
+                let invoice_repo = repo_factory.create_invoice_repo_with_sys_acl(&*conn);
+                let order_info_repo = repo_factory.create_order_info_repo_with_sys_acl(&*conn);
+                let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&*conn);
+
+                expire_invoices(
+                    &*conn,
+                    &*invoice_repo,
+                    &*order_info_repo,
+                    &*event_store_repo,
+                    reserved_before,
+                    batch_size,
+                )
+            })
+            .then(|res| {
+                match res {
+                    Ok(ref sweep) if sweep.invoices_expired == 0 => {}
+                    Ok(sweep) => {
+                        info!(
+                            "Expired {} v1 invoices with {} orders awaiting payment",
+                            sweep.invoices_expired, sweep.orders_expired
+                        );
+                    }
+                    Err(err) => {
+                        let err = FailureError::from(err.context("An error occurred while expiring v1 invoices"));
+                        error!("{:?}", &err);
+                        capture_error(&err);
+                    }
+                };
+
+                future::ok::<_, FailureError>(())
+            })
+        })
+}
+
+/// Price reservations ended before the returned time are past the grace period
+fn reservation_end(config: &LegacyInvoiceExpirationConfig, now: SystemTime) -> SystemTime {
+    now - StdDuration::from_secs(config.grace_period_sec)
+}
+
+/// Expires invoices awaiting payment whose price reservation ended before `reserved_before` with their orders,
+/// `batch_size` invoices per transaction. Saga is notified of the expired orders of every batch in batches of its own
+fn expire_invoices<T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static>(
+    conn: &T,
+    invoice_repo: &InvoiceRepo,
+    order_info_repo: &OrderInfoRepo,
+    event_store_repo: &EventStoreRepo,
+    reserved_before: SystemTime,
+    batch_size: i64,
+) -> Result<LegacyInvoiceExpirationSweepResponse, FailureError> {
+    let mut sweep = LegacyInvoiceExpirationSweepResponse::default();
+
+    loop {
+        let (invoices_expired, orders_expired) = conn.transaction::<_, FailureError, _>(|| {
+            let invoices = invoice_repo.find_expired(reserved_before, batch_size)?;
+
+            let mut order_states = Vec::new();
+            for invoice in &invoices {
+                invoice_repo.set_state(invoice.id, OrderState::AmountExpired)?;
+                let order_infos = order_info_repo.update_status(invoice.id, OrderState::AmountExpired)?;
+                order_states.extend(order_infos.iter().map(OrderStateUpdate::from));
+            }
+
+            let orders_expired = order_states.len();
+            enqueue_order_state_updates(event_store_repo, order_states).map_err(FailureError::from)?;
+
+            Ok((invoices.len(), orders_expired))
+        })?;
+
+        sweep.invoices_expired += invoices_expired;
+        sweep.orders_expired += orders_expired;
+
+        if invoices_expired == 0 || (invoices_expired as i64) < batch_size {
+            return Ok(sweep);
+        }
+    }
+}
+
+fn spawn_legacy_on_pool<T, M, Func, R>(db_pool: Pool<M>, cpu_pool: CpuPool, f: Func) -> ServiceFuture<R>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    Func: FnOnce(::r2d2::PooledConnection<M>) -> Result<R, FailureError> + Send + 'static,
+    R: Send + 'static,
+{
+    Box::new(cpu_pool.spawn_fn(move || db_pool.get().map_err(|e| e.context(Error::Connection).into()).and_then(f)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use futures_cpupool::CpuPool;
+    use stq_static_resources::OrderState;
+    use tokio_core::reactor::Core;
+
+    use super::*;
+    use models::EventPayload;
+    use test_support::{InMemoryReposFactory, InvoiceBuilder, MockConnectionManager, OrderInfoBuilder};
+
+    #[test]
+    fn expires_invoices_past_their_reservation_with_their_orders() {
+        let mut core = Core::new().unwrap();
+        let now = SystemTime::now();
+        let expired = InvoiceBuilder::new()
+            .state(OrderState::PaymentAwaited)
+            .price_reserved(now - Duration::from_secs(7200))
+            .build();
+        let reserved = InvoiceBuilder::new()
+            .state(OrderState::PaymentAwaited)
+            .price_reserved(now - Duration::from_secs(60))
+            .build();
+        let paid = InvoiceBuilder::new()
+            .state(OrderState::Paid)
+            .price_reserved(now - Duration::from_secs(7200))
+            .build();
+        let awaiting_order = |saga_id| OrderInfoBuilder::new().saga_id(saga_id).status(OrderState::PaymentAwaited).build();
+        let order_infos = vec![awaiting_order(expired.id), awaiting_order(expired.id), awaiting_order(reserved.id)];
+        let repo_factory = InMemoryReposFactory::new()
+            .with_legacy_invoices(vec![expired.clone(), reserved.clone(), paid.clone()])
+            .with_order_infos(order_infos);
+        let service = LegacyInvoiceExpirationServiceImpl {
+            db_pool: ::r2d2::Pool::builder().build(MockConnectionManager::default()).unwrap(),
+            cpu_pool: CpuPool::new(1),
+            repo_factory: repo_factory.clone(),
+            user_id: None,
+            config: LegacyInvoiceExpirationConfig {
+                batch_size: 1,
+                ..Default::default()
+            },
+        };
+
+        let sweep = core.run(service.sweep_expired_invoices()).unwrap();
+
+        assert_eq!(sweep.invoices_expired, 1);
+        assert_eq!(sweep.orders_expired, 2);
+        let state = repo_factory.state();
+        let invoice_state = |id| state.legacy_invoices.iter().find(|invoice| invoice.id == id).unwrap().state;
+        assert_eq!(invoice_state(expired.id), OrderState::AmountExpired);
+        assert_eq!(invoice_state(reserved.id), OrderState::PaymentAwaited);
+        assert_eq!(invoice_state(paid.id), OrderState::Paid);
+        assert!(state
+            .order_infos
+            .iter()
+            .all(|order_info| (order_info.saga_id == expired.id) == (order_info.status == OrderState::AmountExpired)));
+        assert_eq!(state.events.len(), 1);
+        match state.events[0].event.payload {
+            EventPayload::SagaOrderStatesUpdate { ref order_states } => assert_eq!(order_states.len(), 2),
+            _ => panic!("order states update is expected"),
+        }
+    }
+}
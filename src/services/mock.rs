@@ -0,0 +1,289 @@
+//! In-memory `AccountService` backed by `MockPaymentsClient`, for running services without
+//! a database or a Payments gateway
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use failure::Fail;
+use futures::{future, Future};
+use uuid::Uuid;
+
+use super::accounts::AccountService;
+use super::error::ErrorKind;
+use super::types::ServiceFutureV2;
+use client::payments::mock::MockPaymentsClient;
+use client::payments::{Account as PaymentsAccount, CreateAccount, CreateInternalTransaction, PaymentsClient};
+use models::*;
+
+#[derive(Default)]
+struct State {
+    accounts: HashMap<AccountId, Account>,
+    system_accounts: HashMap<(TureCurrency, SystemAccountType), AccountId>,
+    taken_accounts: HashSet<AccountId>,
+}
+
+#[derive(Clone, Default)]
+pub struct MockAccountService {
+    payments_client: MockPaymentsClient,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockAccountService {
+    pub fn new(payments_client: MockPaymentsClient) -> Self {
+        MockAccountService {
+            payments_client,
+            state: Arc::new(Mutex::new(State::default())),
+        }
+    }
+
+    /// Gateway mock holding the balances and transactions of the accounts
+    pub fn payments_client(&self) -> MockPaymentsClient {
+        self.payments_client.clone()
+    }
+
+    /// Returns a pooled account handed out by `get_or_create_free_pooled_account` back to the pool
+    pub fn release_account(&self, account_id: AccountId) {
+        let mut state = self.state.lock().unwrap();
+        state.taken_accounts.remove(&account_id);
+    }
+
+    fn get_system_account(&self, currency: TureCurrency, account_type: SystemAccountType) -> ServiceFutureV2<AccountWithBalance> {
+        let existing_account_id = self.state.lock().unwrap().system_accounts.get(&(currency, account_type)).cloned();
+
+        match existing_account_id {
+            Some(account_id) => self.get_account(account_id.into_inner()),
+            None => {
+                let self_ = self.clone();
+                let id = Uuid::new_v4();
+                let name = SystemAccount {
+                    id: AccountId::new(id),
+                    currency,
+                    account_type,
+                }
+                .to_string();
+
+                let fut = self.create_account(id, name, currency, false).and_then(move |account| {
+                    self_
+                        .state
+                        .lock()
+                        .unwrap()
+                        .system_accounts
+                        .insert((currency, account_type), account.id);
+                    self_.get_account(id)
+                });
+
+                Box::new(fut)
+            }
+        }
+    }
+}
+
+impl AccountService for MockAccountService {
+    fn init_system_accounts(&self) -> ServiceFutureV2<()> {
+        Box::new(future::ok(()))
+    }
+
+    fn init_account_pools(&self) -> ServiceFutureV2<()> {
+        Box::new(future::ok(()))
+    }
+
+    fn get_account_pool_statuses(&self) -> ServiceFutureV2<Vec<AccountPoolStatus>> {
+        Box::new(future::ok(vec![]))
+    }
+
+    fn replenish_account_pools(&self) -> ServiceFutureV2<Vec<AccountPoolStatus>> {
+        Box::new(future::ok(vec![]))
+    }
+
+    fn get_account(&self, account_id: Uuid) -> ServiceFutureV2<AccountWithBalance> {
+        let account = self.state.lock().unwrap().accounts.get(&AccountId::new(account_id)).cloned();
+
+        let account = match account {
+            Some(account) => account,
+            None => {
+                let e = format_err!("Account {} not found", account_id);
+                return Box::new(future::err(ectx!(err e, ErrorKind::Internal)));
+            }
+        };
+
+        let fut = self
+            .payments_client
+            .get_account(account_id)
+            .map(move |PaymentsAccount { balance, .. }| AccountWithBalance { account, balance })
+            .map_err(ectx!(ErrorKind::Internal => account_id.hyphenated().to_string()));
+
+        Box::new(fut)
+    }
+
+    fn get_main_account(&self, currency: TureCurrency) -> ServiceFutureV2<AccountWithBalance> {
+        self.get_system_account(currency, SystemAccountType::Main)
+    }
+
+    fn get_stq_cashback_account(&self) -> ServiceFutureV2<AccountWithBalance> {
+        self.get_system_account(TureCurrency::Stq, SystemAccountType::Cashback)
+    }
+
+    fn create_account(&self, account_id: Uuid, name: String, currency: TureCurrency, is_pooled: bool) -> ServiceFutureV2<Account> {
+        let input = CreateAccount {
+            id: account_id,
+            currency,
+            name,
+            callback_url: String::default(),
+            daily_limit_type: DailyLimitType::Unlimited,
+        };
+
+        let fut = self
+            .payments_client
+            .create_account(input.clone())
+            .map_err(ectx!(convert => input))
+            .map({
+                let state = self.state.clone();
+                move |PaymentsAccount { account_address, .. }| {
+                    let account = Account {
+                        id: AccountId::new(account_id),
+                        currency,
+                        is_pooled,
+                        created_at: Utc::now().naive_utc(),
+                        wallet_address: account_address,
+                        status: AccountStatus::Active,
+                    };
+
+                    state.lock().unwrap().accounts.insert(account.id, account.clone());
+                    account
+                }
+            });
+
+        Box::new(fut)
+    }
+
+    fn get_or_create_free_pooled_account(&self, currency: TureCurrency) -> ServiceFutureV2<Account> {
+        {
+            let mut state = self.state.lock().unwrap();
+
+            let free_account = state
+                .accounts
+                .values()
+                .find(|account| {
+                    account.is_pooled
+                        && account.currency == currency
+                        && account.status == AccountStatus::Active
+                        && !state.taken_accounts.contains(&account.id)
+                })
+                .cloned();
+
+            if let Some(free_account) = free_account {
+                state.taken_accounts.insert(free_account.id);
+                return Box::new(future::ok(free_account));
+            }
+        }
+
+        let state = self.state.clone();
+        let id = Uuid::new_v4();
+        let name = id.hyphenated().to_string();
+
+        let fut = self.create_account(id, name, currency, true).map(move |account| {
+            state.lock().unwrap().taken_accounts.insert(account.id);
+            account
+        });
+
+        Box::new(fut)
+    }
+
+    fn drain_and_archive_account(&self, account_id: AccountId) -> ServiceFutureV2<Account> {
+        let account = {
+            let mut state = self.state.lock().unwrap();
+            let is_taken = state.taken_accounts.contains(&account_id);
+
+            match state.accounts.get_mut(&account_id) {
+                None => {
+                    let e = format_err!("Account {} not found", account_id);
+                    return Box::new(future::err(ectx!(err e, ErrorKind::NotFound)));
+                }
+                Some(account) => {
+                    if account.status == AccountStatus::Archived {
+                        return Box::new(future::ok(account.clone()));
+                    }
+
+                    if !account.is_pooled || is_taken {
+                        let e = format_err!("Account {} is not a free pooled account", account_id);
+                        return Box::new(future::err(ectx!(err e, ErrorKind::Internal)));
+                    }
+
+                    account.status = AccountStatus::Draining;
+                    account.clone()
+                }
+            }
+        };
+
+        let fut = Future::join(self.get_account(account_id.into_inner()), self.get_main_account(account.currency))
+            .and_then({
+                let payments_client = self.payments_client.clone();
+                move |(AccountWithBalance { balance, .. }, AccountWithBalance { account: main_account, .. })| {
+                    if balance == Amount::zero() {
+                        return future::Either::A(future::ok(()));
+                    }
+
+                    let input = CreateInternalTransaction {
+                        id: Uuid::new_v4(),
+                        from: account_id.into_inner(),
+                        to: main_account.id.into_inner(),
+                        amount: balance,
+                    };
+
+                    future::Either::B(
+                        payments_client
+                            .create_internal_transaction(input.clone())
+                            .map_err(ectx!(convert => input)),
+                    )
+                }
+            })
+            .map({
+                let state = self.state.clone();
+                move |_| {
+                    let mut state = state.lock().unwrap();
+                    let account = state.accounts.entry(account_id).or_insert(account);
+                    account.status = AccountStatus::Archived;
+                    account.clone()
+                }
+            });
+
+        Box::new(fut)
+    }
+
+    fn transfer_between_system_accounts(
+        &self,
+        from_type: SystemAccountType,
+        to_type: SystemAccountType,
+        currency: TureCurrency,
+        amount: Amount,
+    ) -> ServiceFutureV2<SystemAccountsTransfer> {
+        let fut = Future::join(
+            self.get_system_account(currency, from_type),
+            self.get_system_account(currency, to_type),
+        )
+        .and_then({
+            let payments_client = self.payments_client.clone();
+            move |(AccountWithBalance { account: from_account, .. }, AccountWithBalance { account: to_account, .. })| {
+                let input = CreateInternalTransaction {
+                    id: Uuid::new_v4(),
+                    from: from_account.id.into_inner(),
+                    to: to_account.id.into_inner(),
+                    amount,
+                };
+
+                payments_client
+                    .create_internal_transaction(input.clone())
+                    .map_err(ectx!(convert => input))
+                    .map(move |_| SystemAccountsTransfer {
+                        transaction_id: input.id,
+                        from_account_id: from_account.id,
+                        to_account_id: to_account.id,
+                        currency,
+                        amount,
+                    })
+            }
+        });
+
+        Box::new(fut)
+    }
+}
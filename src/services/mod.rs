@@ -2,23 +2,59 @@
 //! validation, authorization, etc.
 
 pub mod accounts;
+pub mod analytics;
+pub mod api_key;
+pub mod audit_log;
 pub mod billing_info;
 pub mod billing_type;
+pub mod cashback;
+pub mod cashback_liability;
 pub mod customer;
+pub mod customer_export;
+pub mod data_retention;
 pub mod error;
+pub mod exchange_rate_retention;
+pub mod exchange_rate_slippage;
+pub mod feature_flag;
 pub mod fee;
+pub mod fee_crypto_payment;
+pub mod fee_dunning;
+pub mod fee_preview;
+pub mod fee_statement;
+pub mod historical_report;
 pub mod invoice;
+pub mod invoice_callback;
+pub mod invoice_risk;
+pub mod legacy_invoice_expiration;
 pub mod merchant;
+pub mod mock;
 pub mod order;
 pub mod order_billing;
+pub mod payment_attempt;
 pub mod payment_intent;
+pub mod payment_method;
+pub mod payment_recovery;
 pub mod payout;
+pub mod payout_instruction;
+pub mod payout_restriction;
+pub mod payout_statement;
+pub mod receipt;
+pub mod saga;
+pub mod schema_migration;
+pub mod store_balance;
+pub mod store_billing_status;
+pub mod store_offboarding;
 pub mod store_subscription;
+pub mod store_webhook;
 pub mod stripe;
+pub mod stripe_fee_backfill;
 pub mod subscription;
 pub mod subscription_payment;
 pub mod types;
+pub mod user_credit;
 pub mod user_roles;
+pub mod user_wallet;
+pub mod work_queue;
 
 pub use self::error::*;
 pub use self::types::Service;
@@ -5,7 +5,7 @@ use diesel::pg::Pg;
 use diesel::Connection;
 use failure::Fail;
 use future::Either;
-use futures::Future;
+use futures::{future, Future};
 use futures_cpupool::CpuPool;
 use r2d2::{ManageConnection, Pool};
 use validator::{ValidationError, ValidationErrors};
@@ -15,15 +15,23 @@ use stq_types::UserId;
 
 use super::error::{ErrorContext, ErrorKind};
 use super::types::ServiceFutureV2;
+use client::fiat_payments::FiatPaymentProvider;
 use client::payments::PaymentsClient;
-use client::stripe::StripeClient;
-use controller::responses::{OrderResponse, OrderSearchResultsResponse};
+use controller::requests::{OrderLineItemRefund, RefundOrderLineItemsRequest};
+use controller::responses::{OrderExchangeRateResponse, OrderExchangeRatesResponse, OrderResponse, OrderSearchResultsResponse};
 use models::order_v2::{OrderId, OrdersSearch, RawOrder};
 use models::PaymentState;
-use models::{Event, EventPayload};
-use repos::{ReposFactory, SearchPaymentIntent, SearchPaymentIntentInvoice};
+use models::{
+    Amount, ChargeId, Currency, Event, EventPayload, Fee, FeeStatus, InvoiceCallbackEventType, NewFeeAdjustment, NewOrderCaptureApproval,
+    OrderLineItem, OrderLineItemId, PaymentIntentStatus, RefundSplit, UpdateFee,
+};
+use repos::{
+    FeeAdjustmentsRepo, FeeRepo, OrderLineItemsRepo, OrdersRepo, ReposFactory, SearchFee, SearchPaymentIntent, SearchPaymentIntentInvoice,
+};
 use services::accounts::AccountService;
 use services::error::Error as ServiceError;
+use services::invoice_callback::{enqueue_invoice_callback_delivery, invoice_refunded_callback_data};
+use services::store_balance::{check_store_balance, StoreBalanceRepos};
 use services::types::spawn_on_pool;
 use services::Service;
 
@@ -36,6 +44,10 @@ pub trait OrderService {
     fn update_order_state(&self, order_id: OrderId, state: PaymentState) -> ServiceFutureV2<()>;
     // Search orders
     fn search_orders(&self, skip: i64, count: i64, payload: OrdersSearch) -> ServiceFutureV2<OrderSearchResultsResponse>;
+    /// Get all exchange rates that have ever been attached to the order
+    fn get_order_exchange_rates(&self, order_id: OrderId) -> ServiceFutureV2<OrderExchangeRatesResponse>;
+    /// Refunds units of the line items of a captured fiat order along with their share of the platform fee
+    fn refund_order_line_items(&self, order_id: OrderId, payload: RefundOrderLineItemsRequest) -> ServiceFutureV2<OrderResponse>;
 }
 
 impl<
@@ -92,7 +104,7 @@ impl<
 
     fn order_decline(&self, order_id: OrderId) -> ServiceFutureV2<()> {
         let repo_factory = self.static_context.repo_factory.clone();
-        let stripe_client = self.static_context.stripe_client.clone();
+        let fiat_payment_provider = self.static_context.fiat_payment_provider.clone();
         let user_id = self.dynamic_context.user_id;
 
         let db_pool = self.static_context.db_pool.clone();
@@ -125,7 +137,14 @@ impl<
                 let cpu_pool = self.static_context.cpu_pool.clone();
                 move |order| {
                     if order.seller_currency.is_fiat() {
-                        Either::A(order_decline_fiat(cpu_pool, db_pool, repo_factory, user_id, stripe_client, order))
+                        Either::A(order_decline_fiat(
+                            cpu_pool,
+                            db_pool,
+                            repo_factory,
+                            user_id,
+                            fiat_payment_provider,
+                            order,
+                        ))
                     } else {
                         Either::B(order_decline_crypto(cpu_pool, db_pool, repo_factory, user_id, order))
                     }
@@ -176,13 +195,18 @@ impl<
 
         spawn_on_pool(db_pool, cpu_pool, move |conn| {
             let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
+            let order_line_items_repo = repo_factory.create_order_line_items_repo_with_sys_acl(&conn);
             debug!("Requesting orders  {:?}", payload);
 
             let search_result = orders_repo.search(skip, count, payload).map_err(ectx!(try convert))?;
+            let order_ids: Vec<_> = search_result.orders.iter().map(|order| order.id).collect();
+            let line_items = order_line_items_repo
+                .get_by_order_ids(&order_ids)
+                .map_err(ectx!(try convert => order_ids))?;
             let orders = search_result
                 .orders
                 .into_iter()
-                .map(OrderResponse::try_from_raw_order)
+                .map(|order| OrderResponse::try_from_raw_order(order, &line_items))
                 .collect::<Result<Vec<_>, ServiceError>>()?;
             Ok(OrderSearchResultsResponse {
                 total_count: search_result.total_count,
@@ -190,8 +214,236 @@ impl<
             })
         })
     }
+
+    fn get_order_exchange_rates(&self, order_id: OrderId) -> ServiceFutureV2<OrderExchangeRatesResponse> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        let db_pool = self.static_context.db_pool.clone();
+        let cpu_pool = self.static_context.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            // Access to the order is checked by the orders repo - both the buyer and the store manager may read it
+            let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
+            let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
+            let rates_repo = repo_factory.create_order_exchange_rates_repo_with_sys_acl(&conn);
+            debug!("Requesting exchange rates for order with ID: {}", order_id);
+
+            let order = orders_repo.get(order_id).map_err(ectx!(try convert => order_id))?.ok_or({
+                let e = format_err!("Order {} not found", order_id);
+                ectx!(try err e, ErrorKind::NotFound)
+            })?;
+
+            let invoice_id = order.invoice_id;
+            let invoice = invoices_repo.get(invoice_id).map_err(ectx!(try convert => invoice_id))?.ok_or({
+                let e = format_err!("Invoice {} linked to order {} not found", invoice_id, order_id);
+                ectx!(try err e, ErrorKind::Internal)
+            })?;
+
+            let rates = rates_repo
+                .get_all_rates_for_order(order_id)
+                .map_err(ectx!(try convert => order_id))?
+                .into_iter()
+                .map(|rate| OrderExchangeRateResponse {
+                    id: rate.id,
+                    exchange_rate: rate.exchange_rate.clone(),
+                    status: rate.status,
+                    source: rate.source(),
+                    used_for_payment: invoice.paid_at.map(|paid_at| rate.was_active_at(paid_at)).unwrap_or(false),
+                    created_at: rate.created_at,
+                    updated_at: rate.updated_at,
+                })
+                .collect();
+
+            Ok(OrderExchangeRatesResponse {
+                order_id,
+                invoice_id,
+                paid_at: invoice.paid_at,
+                rates,
+            })
+        })
+    }
+
+    fn refund_order_line_items(&self, order_id: OrderId, payload: RefundOrderLineItemsRequest) -> ServiceFutureV2<OrderResponse> {
+        let repo_factory = self.static_context.repo_factory.clone();
+        let fiat_payment_provider = self.static_context.fiat_payment_provider.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        let db_pool = self.static_context.db_pool.clone();
+        let cpu_pool = self.static_context.cpu_pool.clone();
+
+        let refunds = match line_item_refunds(payload) {
+            Ok(refunds) => refunds,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        let fut = spawn_on_pool(db_pool.clone(), cpu_pool.clone(), {
+            let repo_factory = repo_factory.clone();
+            let refunds = refunds.clone();
+            move |conn| {
+                let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
+                // Refunding line items is checked by the line items repo, the orders repo lets the buyer read the order too
+                let order_line_items_repo = repo_factory.create_order_line_items_repo(&conn, user_id);
+                let payment_intent_repo = repo_factory.create_payment_intent_repo_with_sys_acl(&conn);
+                let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo_with_sys_acl(&conn);
+                debug!("Requesting order by id: {}", order_id);
+                let order = orders_repo.get(order_id).map_err(ectx!(try convert => order_id))?.ok_or({
+                    let e = format_err!("Order {} not found", order_id);
+                    ectx!(try err e, ErrorKind::NotFound)
+                })?;
+
+                if !order.seller_currency.is_fiat() {
+                    return Err(order_line_items_validation_error(
+                        "unsupported_currency",
+                        &format!("Line items of order {} in {} cannot be refunded", order_id, order.seller_currency),
+                    ));
+                }
+                match order.state {
+                    PaymentState::Captured | PaymentState::PaymentToSellerNeeded | PaymentState::PaidToSeller => (),
+                    // the decline has refunded whatever was left of the order
+                    PaymentState::Declined => {
+                        return Err(order_line_items_validation_error(
+                            "declined",
+                            &format!("Line items of declined order {} cannot be refunded", order_id),
+                        ));
+                    }
+                    state => {
+                        return Err(order_line_items_validation_error(
+                            "wrong_state",
+                            &format!("Cannot refund line items of order in state \"{}\"", state),
+                        ));
+                    }
+                }
+
+                let invoice_id = order.invoice_id;
+                let payment_intent_invoice = payment_intent_invoices_repo
+                    .get(SearchPaymentIntentInvoice::InvoiceId(invoice_id))
+                    .map_err(ectx!(try convert => invoice_id))?
+                    .ok_or({
+                        let e = format_err!("Record payment_intent_invoice by invoice id {} not found", invoice_id);
+                        ectx!(try err e, ErrorKind::Internal)
+                    })?;
+
+                let search = SearchPaymentIntent::Id(payment_intent_invoice.payment_intent_id);
+                let search_clone = search.clone();
+                let payment_intent = payment_intent_repo
+                    .get(search.clone())
+                    .map_err(ectx!(try convert => search))?
+                    .ok_or({
+                        let e = format_err!("payment intent {:?} not found", search_clone);
+                        ectx!(try err e, ErrorKind::Internal)
+                    })?;
+                let payment_intent_id = payment_intent.id;
+                let charge_id = payment_intent.charge_id.ok_or({
+                    let e = format_err!("charge is absent in payment intent {:?}", payment_intent_id);
+                    ectx!(try err e, ErrorKind::Internal)
+                })?;
+
+                let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+                check_fee_refundable(&*fees_repo, order_id)?;
+
+                // The units are reserved before the refund is made, so concurrent refunds cannot refund them twice
+                let refund_amount = conn.transaction::<_, ServiceError, _>(|| {
+                    let line_items = order_line_items_repo
+                        .get_by_order_ids(&[order_id])
+                        .map_err(ectx!(try convert => order_id))?;
+                    reserve_line_item_refunds(&*order_line_items_repo, &order, &line_items, &refunds)
+                })?;
+
+                Ok((order, charge_id, refund_amount))
+            }
+        })
+        .and_then({
+            let db_pool = db_pool.clone();
+            let cpu_pool = cpu_pool.clone();
+            let repo_factory = repo_factory.clone();
+            move |(order, charge_id, refund_amount)| {
+                let order_total = order.total_amount;
+                let charge_id_cloned = charge_id.clone();
+                fiat_payment_provider
+                    .refund(charge_id.clone(), refund_amount, order_id)
+                    .map_err(ectx!(convert => charge_id_cloned, refund_amount, order_id))
+                    .or_else({
+                        let db_pool = db_pool.clone();
+                        let cpu_pool = cpu_pool.clone();
+                        let repo_factory = repo_factory.clone();
+                        move |e: ServiceError| {
+                            release_line_item_refunds(cpu_pool, db_pool, repo_factory, order_id, refunds).then(move |_| Err(e))
+                        }
+                    })
+                    .and_then({
+                        let db_pool = db_pool.clone();
+                        let cpu_pool = cpu_pool.clone();
+                        let repo_factory = repo_factory.clone();
+                        move |_| {
+                            refund_order_fee(
+                                cpu_pool,
+                                db_pool,
+                                repo_factory,
+                                fiat_payment_provider,
+                                order_id,
+                                order_total,
+                                refund_amount,
+                            )
+                        }
+                    })
+                    .and_then(move |new_fee_adjustment| {
+                        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                            let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+                            let fee_adjustments_repo = repo_factory.create_fee_adjustments_repo_with_sys_acl(&conn);
+                            let invoice_callbacks_repo = repo_factory.create_invoice_callbacks_repo_with_sys_acl(&conn);
+                            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+                            let sys_orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                            let payouts_repo = repo_factory.create_payouts_repo_with_sys_acl(&conn);
+                            let payout_instructions_repo = repo_factory.create_payout_instructions_repo_with_sys_acl(&conn);
+                            let negative_store_balances_repo = repo_factory.create_negative_store_balances_repo_with_sys_acl(&conn);
+                            conn.transaction(|| {
+                                record_fee_adjustment(&*fees_repo, &*fee_adjustments_repo, order_id, new_fee_adjustment)?;
+
+                                // the seller share of a refund made after the payout is owed by the store
+                                let store_balance_repos = StoreBalanceRepos {
+                                    orders_repo: &*sys_orders_repo,
+                                    payouts_repo: &*payouts_repo,
+                                    payout_instructions_repo: &*payout_instructions_repo,
+                                    fee_adjustments_repo: &*fee_adjustments_repo,
+                                    negative_store_balances_repo: &*negative_store_balances_repo,
+                                    event_store_repo: &*event_store_repo,
+                                };
+                                check_store_balance(&store_balance_repos, order.store_id)?;
+
+                                info!("Line items of order {} have been refunded for {}", order_id, refund_amount);
+                                enqueue_invoice_callback_delivery(
+                                    &*invoice_callbacks_repo,
+                                    &*event_store_repo,
+                                    order.invoice_id,
+                                    InvoiceCallbackEventType::InvoiceRefunded,
+                                    invoice_refunded_callback_data(&order, refund_amount),
+                                )
+                            })
+                        })
+                    })
+            }
+        })
+        .and_then(move |_| {
+            spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
+                let order_line_items_repo = repo_factory.create_order_line_items_repo_with_sys_acl(&conn);
+                let order = orders_repo.get(order_id).map_err(ectx!(try convert => order_id))?.ok_or({
+                    let e = format_err!("Order {} not found", order_id);
+                    ectx!(try err e, ErrorKind::Internal)
+                })?;
+                let line_items = order_line_items_repo
+                    .get_by_order_ids(&[order_id])
+                    .map_err(ectx!(try convert => order_id))?;
+                OrderResponse::try_from_raw_order(order, &line_items)
+            })
+        });
+
+        Box::new(fut)
+    }
 }
 
+/// Records the approval of the order capture, the payment intent is captured once for all orders of the invoice
 fn order_capture_fiat<T, F, M>(cpu_pool: CpuPool, db_pool: Pool<M>, repo_factory: F, order: RawOrder) -> ServiceFutureV2<()>
 where
     T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
@@ -199,10 +451,45 @@ where
     M: ManageConnection<Connection = T>,
 {
     let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
+        let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo_with_sys_acl(&conn);
+        let order_capture_approvals_repo = repo_factory.create_order_capture_approvals_repo_with_sys_acl(&conn);
         let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+        let invoice_id = order.invoice_id;
+        let payment_intent_invoice = payment_intent_invoices_repo
+            .get(SearchPaymentIntentInvoice::InvoiceId(invoice_id))
+            .map_err(ectx!(try convert => invoice_id))?
+            .ok_or({
+                let e = format_err!("Record payment_intent_invoice by invoice id {} not found", invoice_id);
+                ectx!(try err e, ErrorKind::Internal)
+            })?;
+
+        let payment_intent_id = payment_intent_invoice.payment_intent_id;
+        let payment_intent_id_cloned = payment_intent_id.clone();
+        let approvals = order_capture_approvals_repo
+            .get_by_payment_intent_id(payment_intent_id.clone())
+            .map_err(ectx!(try convert => payment_intent_id_cloned))?;
+
+        if approvals.iter().any(|approval| approval.order_id == order.id) {
+            let mut errors = ValidationErrors::new();
+            let mut error = ValidationError::new("already_approved");
+            error.message = Some(format!("Capture of order {} has already been approved", order.id).into());
+            errors.add("order", error);
+            return Err(ectx!(err ErrorContext::OrderState ,ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default())));
+        }
+
+        let new_approval = NewOrderCaptureApproval {
+            order_id: order.id,
+            payment_intent_id,
+        };
         let event = Event::new(EventPayload::PaymentIntentCapture { order_id: order.id });
-        event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
-        Ok(())
+        conn.transaction(|| {
+            order_capture_approvals_repo
+                .create(new_approval.clone())
+                .map_err(ectx!(try convert => new_approval))?;
+            event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+            Ok(())
+        })
     });
     Box::new(fut)
 }
@@ -212,7 +499,7 @@ fn order_decline_fiat<T, F, M>(
     db_pool: Pool<M>,
     repo_factory: F,
     user_id: Option<UserId>,
-    stripe_client: std::sync::Arc<dyn StripeClient>,
+    fiat_payment_provider: std::sync::Arc<dyn FiatPaymentProvider>,
     order: RawOrder,
 ) -> ServiceFutureV2<()>
 where
@@ -224,6 +511,7 @@ where
     let cpu_pool_ = cpu_pool.clone();
     let repo_factory_ = repo_factory.clone();
     let order_id = order.id;
+    let order_total = order.total_amount;
 
     let fut = spawn_on_pool(db_pool_, cpu_pool_, move |conn| {
         let payment_intent_repo = repo_factory_.create_payment_intent_repo(&conn, user_id);
@@ -248,39 +536,623 @@ where
                 ectx!(try err e, ErrorKind::Internal)
             })?;
 
+        // The funds of the order haven't been captured yet, so there is nothing to refund
+        if payment_intent.status == PaymentIntentStatus::RequiresCapture {
+            let orders_repo = repo_factory_.create_orders_repo(&conn, user_id);
+            let fees_repo = repo_factory_.create_fees_repo_with_sys_acl(&conn);
+            let fee_adjustments_repo = repo_factory_.create_fee_adjustments_repo_with_sys_acl(&conn);
+            let event_store_repo = repo_factory_.create_event_store_repo_with_sys_acl(&conn);
+            let event = Event::new(EventPayload::PaymentIntentCapture { order_id });
+            return conn
+                .transaction(|| {
+                    decline_released_order(&*orders_repo, &*fees_repo, &*fee_adjustments_repo, &order)?;
+                    event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+                    Ok(())
+                })
+                .map(|_| None);
+        }
+
         let payment_intent_id = payment_intent.id;
-        payment_intent
-            .charge_id
-            .ok_or({
-                let e = format_err!("charge is absent in payment intent {:?}", payment_intent_id);
-                ectx!(err e, ErrorKind::Internal)
-            })
-            .map(|charge_id| (charge_id, order.total_amount))
-    })
-    .and_then(move |(charge_id, total_amount)| {
-        stripe_client
-            .refund(charge_id.clone(), total_amount, order_id)
-            .map_err(ectx!(convert => charge_id, total_amount, order_id))
-            .map(|_| ())
+        let charge_id = payment_intent.charge_id.ok_or({
+            let e = format_err!("charge is absent in payment intent {:?}", payment_intent_id);
+            ectx!(try err e, ErrorKind::Internal)
+        })?;
+
+        let fees_repo = repo_factory_.create_fees_repo_with_sys_acl(&conn);
+        check_fee_refundable(&*fees_repo, order_id)?;
+
+        // The units left are reserved, so that they are not refunded by line while the order is declined
+        let order_line_items_repo = repo_factory_.create_order_line_items_repo_with_sys_acl(&conn);
+        let (refund_amount, reserved) = conn.transaction::<_, ServiceError, _>(|| {
+            let line_items = order_line_items_repo
+                .get_by_order_ids(&[order_id])
+                .map_err(ectx!(try convert => order_id))?;
+            reserve_line_items_for_decline(&*order_line_items_repo, &order, &line_items)
+        })?;
+
+        Ok(Some((charge_id, refund_amount, reserved)))
     })
     .and_then({
         let db_pool = db_pool.clone();
         let cpu_pool = cpu_pool.clone();
         let repo_factory = repo_factory.clone();
-        move |_| {
+        move |refund| match refund {
+            None => Either::A(future::ok(())),
+            Some((charge_id, refund_amount, reserved)) => Either::B(order_decline_fiat_refund(
+                cpu_pool,
+                db_pool,
+                repo_factory,
+                user_id,
+                fiat_payment_provider,
+                order_id,
+                charge_id,
+                order_total,
+                refund_amount,
+                reserved,
+            )),
+        }
+    });
+    Box::new(fut)
+}
+
+/// Refunds what is left of the captured funds of the declined order along with its share of the platform fee.
+/// Line items refunded before are not refunded again, the units `reserved` for the decline are released if the refund fails
+fn order_decline_fiat_refund<T, F, M>(
+    cpu_pool: CpuPool,
+    db_pool: Pool<M>,
+    repo_factory: F,
+    user_id: Option<UserId>,
+    fiat_payment_provider: std::sync::Arc<dyn FiatPaymentProvider>,
+    order_id: OrderId,
+    charge_id: ChargeId,
+    order_total: Amount,
+    refund_amount: Amount,
+    reserved: Vec<(OrderLineItemId, i32)>,
+) -> ServiceFutureV2<()>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    F: ReposFactory<T>,
+    M: ManageConnection<Connection = T>,
+{
+    let refund = if refund_amount == Amount::zero() {
+        info!("Order {} has been refunded in full by line, nothing is left to refund", order_id);
+        Either::A(future::ok::<_, ServiceError>(None))
+    } else {
+        Either::B(
+            fiat_payment_provider
+                .refund(charge_id.clone(), refund_amount, order_id)
+                .map_err(ectx!(convert => charge_id, refund_amount, order_id))
+                .or_else({
+                    let db_pool = db_pool.clone();
+                    let cpu_pool = cpu_pool.clone();
+                    let repo_factory = repo_factory.clone();
+                    move |e: ServiceError| {
+                        release_line_item_refunds(cpu_pool, db_pool, repo_factory, order_id, reserved).then(move |_| Err(e))
+                    }
+                })
+                .and_then({
+                    let db_pool = db_pool.clone();
+                    let cpu_pool = cpu_pool.clone();
+                    let repo_factory = repo_factory.clone();
+                    move |_| {
+                        refund_order_fee(
+                            cpu_pool,
+                            db_pool,
+                            repo_factory,
+                            fiat_payment_provider,
+                            order_id,
+                            order_total,
+                            refund_amount,
+                        )
+                    }
+                }),
+        )
+    };
+
+    let fut = refund.and_then({
+        let db_pool = db_pool.clone();
+        let cpu_pool = cpu_pool.clone();
+        let repo_factory = repo_factory.clone();
+        move |new_fee_adjustment| {
             spawn_on_pool(db_pool, cpu_pool, move |conn| {
                 let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
-                info!("Setting order {} state \'Declined\'", order_id);
-                orders_repo
-                    .update_state(order_id, PaymentState::Declined)
-                    .map_err(ectx!(convert => order_id))
-                    .map(|_| ())
+                let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+                let fee_adjustments_repo = repo_factory.create_fee_adjustments_repo_with_sys_acl(&conn);
+                let invoice_callbacks_repo = repo_factory.create_invoice_callbacks_repo_with_sys_acl(&conn);
+                let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+                let sys_orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                let payouts_repo = repo_factory.create_payouts_repo_with_sys_acl(&conn);
+                let payout_instructions_repo = repo_factory.create_payout_instructions_repo_with_sys_acl(&conn);
+                let negative_store_balances_repo = repo_factory.create_negative_store_balances_repo_with_sys_acl(&conn);
+                conn.transaction(|| {
+                    record_fee_adjustment(&*fees_repo, &*fee_adjustments_repo, order_id, new_fee_adjustment)?;
+
+                    info!("Setting order {} state \'Declined\'", order_id);
+                    let order = orders_repo
+                        .update_state(order_id, PaymentState::Declined)
+                        .map_err(ectx!(try convert => order_id))?;
+
+                    // the seller share of a refund made after the payout is owed by the store
+                    let store_balance_repos = StoreBalanceRepos {
+                        orders_repo: &*sys_orders_repo,
+                        payouts_repo: &*payouts_repo,
+                        payout_instructions_repo: &*payout_instructions_repo,
+                        fee_adjustments_repo: &*fee_adjustments_repo,
+                        negative_store_balances_repo: &*negative_store_balances_repo,
+                        event_store_repo: &*event_store_repo,
+                    };
+                    check_store_balance(&store_balance_repos, order.store_id)?;
+
+                    if refund_amount == Amount::zero() {
+                        return Ok(());
+                    }
+
+                    enqueue_invoice_callback_delivery(
+                        &*invoice_callbacks_repo,
+                        &*event_store_repo,
+                        order.invoice_id,
+                        InvoiceCallbackEventType::InvoiceRefunded,
+                        invoice_refunded_callback_data(&order, refund_amount),
+                    )
+                })
             })
         }
     });
     Box::new(fut)
 }
 
+/// Declines the order whose authorized funds have been released without being captured.
+/// Nothing is refunded to the buyer, so the whole unpaid platform fee of the order is released as well
+pub fn decline_released_order(
+    orders_repo: &OrdersRepo,
+    fees_repo: &FeeRepo,
+    fee_adjustments_repo: &FeeAdjustmentsRepo,
+    order: &RawOrder,
+) -> Result<(), ServiceError> {
+    let order_id = order.id;
+    let fee = fees_repo
+        .get(SearchFee::OrderId(order_id))
+        .map_err(ectx!(try convert => order_id))?;
+
+    match fee {
+        None => info!("Order {} has no platform fee to release", order_id),
+        Some(ref fee) if fee.status == FeeStatus::Paid => {
+            warn!("Platform fee #{} of uncaptured order {} has already been paid", fee.id, order_id);
+        }
+        Some(fee) => {
+            let split = RefundSplit::new(order.total_amount, order.total_amount, fee.amount)
+                .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+            let update_fee = UpdateFee {
+                amount: Some(Amount::zero()),
+                ..Default::default()
+            };
+            fees_repo
+                .update(fee.id, update_fee.clone())
+                .map_err(ectx!(try convert => update_fee))?;
+
+            let new_fee_adjustment = NewFeeAdjustment {
+                fee_id: fee.id,
+                order_id,
+                currency: fee.currency,
+                refund_amount: order.total_amount,
+                seller_amount: split.seller_amount,
+                fee_amount: split.fee_amount,
+                fee_charge_id: None,
+                charged_currency: None,
+                charged_fee_amount: None,
+            };
+            info!(
+                "Order {} has been released, fee #{} of {} is released too",
+                order_id, fee.id, fee.amount
+            );
+            fee_adjustments_repo
+                .create(new_fee_adjustment.clone())
+                .map_err(ectx!(try convert => new_fee_adjustment))?;
+        }
+    }
+
+    info!("Setting order {} state \'Declined\'", order_id);
+    orders_repo
+        .update_state(order_id, PaymentState::Declined)
+        .map_err(ectx!(convert => order_id))
+        .map(|_| ())
+}
+
+/// Reverses the share of the order platform fee in the refund, taken from the fee before the earlier refunds of the order.
+/// The share of an already charged fee is refunded on the fee charge, the unpaid fee is reduced by the caller
+fn refund_order_fee<T, F, M>(
+    cpu_pool: CpuPool,
+    db_pool: Pool<M>,
+    repo_factory: F,
+    fiat_payment_provider: std::sync::Arc<dyn FiatPaymentProvider>,
+    order_id: OrderId,
+    order_total: Amount,
+    refund_amount: Amount,
+) -> ServiceFutureV2<Option<(Fee, NewFeeAdjustment)>>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    F: ReposFactory<T>,
+    M: ManageConnection<Connection = T>,
+{
+    let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
+        let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+        let fee_adjustments_repo = repo_factory.create_fee_adjustments_repo_with_sys_acl(&conn);
+        let fee = match fees_repo
+            .get(SearchFee::OrderId(order_id))
+            .map_err(ectx!(try convert => order_id))?
+        {
+            None => return Ok(None),
+            Some(fee) => fee,
+        };
+
+        let adjustments = fee_adjustments_repo
+            .get_by_order_ids(&[order_id])
+            .map_err(ectx!(try convert => order_id))?
+            .into_iter()
+            .filter(|adjustment| adjustment.fee_id == fee.id)
+            .collect::<Vec<_>>();
+
+        Ok(Some((fee, adjustments)))
+    })
+    .and_then(move |fee| {
+        let (fee, adjustments) = match fee {
+            None => {
+                info!("Order {} has no platform fee to refund", order_id);
+                return Either::A(future::ok(None));
+            }
+            Some(fee) => fee,
+        };
+        // the fee has been paid by crypto transfer after the refund was checked
+        if is_fee_paid_by_crypto(&fee) {
+            return Either::A(future::err(fee_paid_by_crypto_error(&fee)));
+        }
+
+        let split = match RefundSplit::after_refunds(order_total, refund_amount, fee.amount, &adjustments) {
+            Some(split) => split,
+            None => {
+                let e = format_err!(
+                    "Cannot split refund {} of order {} with total {}",
+                    refund_amount,
+                    order_id,
+                    order_total
+                );
+                return Either::A(future::err(ectx!(err e, ErrorContext::AmountConversion, ErrorKind::Internal)));
+            }
+        };
+
+        let new_fee_adjustment = NewFeeAdjustment {
+            fee_id: fee.id,
+            order_id,
+            currency: fee.currency,
+            refund_amount,
+            seller_amount: split.seller_amount,
+            fee_amount: split.fee_amount,
+            fee_charge_id: None,
+            charged_currency: None,
+            charged_fee_amount: None,
+        };
+
+        let is_fee_charged = fee.status == FeeStatus::Paid && split.fee_amount > Amount::zero();
+        let fee_charge_id = match fee.charge_id.clone() {
+            Some(fee_charge_id) => fee_charge_id,
+            None => return Either::A(future::ok(Some((fee, new_fee_adjustment)))),
+        };
+        if !is_fee_charged {
+            return Either::A(future::ok(Some((fee, new_fee_adjustment))));
+        }
+
+        // the charge is refunded in the currency the fee was charged in
+        let (charged_currency, charged_fee_amount) = match split.charged_fee_amount(&fee) {
+            Some(charged_fee_amount) => charged_fee_amount,
+            None => {
+                let e = format_err!(
+                    "Cannot convert fee share {} of order {} into the charged amount",
+                    split.fee_amount,
+                    order_id
+                );
+                return Either::A(future::err(ectx!(err e, ErrorContext::AmountConversion, ErrorKind::Internal)));
+            }
+        };
+        let fee_charge_id_cloned = fee_charge_id.clone();
+        Either::B(
+            fiat_payment_provider
+                .refund(fee_charge_id.clone(), charged_fee_amount, order_id)
+                .map_err(ectx!(convert => fee_charge_id_cloned, charged_fee_amount, order_id))
+                .map(move |_| {
+                    let new_fee_adjustment = NewFeeAdjustment {
+                        fee_charge_id: Some(fee_charge_id),
+                        charged_currency: Some(charged_currency),
+                        charged_fee_amount: Some(charged_fee_amount),
+                        ..new_fee_adjustment
+                    };
+                    Some((fee, new_fee_adjustment))
+                }),
+        )
+    });
+
+    Box::new(fut)
+}
+
+/// Reduces the unpaid fee by its share in the refund and records the adjustment. The amount of a paid fee is kept,
+/// its share has already been refunded on its charge
+fn record_fee_adjustment(
+    fees_repo: &FeeRepo,
+    fee_adjustments_repo: &FeeAdjustmentsRepo,
+    order_id: OrderId,
+    new_fee_adjustment: Option<(Fee, NewFeeAdjustment)>,
+) -> Result<(), ServiceError> {
+    let (fee, new_fee_adjustment) = match new_fee_adjustment {
+        None => return Ok(()),
+        Some(new_fee_adjustment) => new_fee_adjustment,
+    };
+
+    if fee.status != FeeStatus::Paid {
+        let amount = fee
+            .amount
+            .checked_sub(new_fee_adjustment.fee_amount)
+            .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+        let update_fee = UpdateFee {
+            amount: Some(amount),
+            ..Default::default()
+        };
+        fees_repo
+            .update(fee.id, update_fee.clone())
+            .map_err(ectx!(try convert => update_fee))?;
+    }
+
+    info!(
+        "Order {} refund of {} splits into {} from seller and {} from fee #{}",
+        order_id, new_fee_adjustment.refund_amount, new_fee_adjustment.seller_amount, new_fee_adjustment.fee_amount, fee.id
+    );
+    fee_adjustments_repo
+        .create(new_fee_adjustment.clone())
+        .map_err(ectx!(convert => new_fee_adjustment))
+        .map(|_| ())
+}
+
+/// Units to refund per line item, every line item may be given once with a positive quantity
+fn line_item_refunds(payload: RefundOrderLineItemsRequest) -> Result<Vec<(OrderLineItemId, i32)>, ServiceError> {
+    if payload.line_items.is_empty() {
+        return Err(order_line_items_validation_error(
+            "required",
+            "At least one line item must be refunded",
+        ));
+    }
+
+    let mut refunds: Vec<(OrderLineItemId, i32)> = Vec::new();
+    for OrderLineItemRefund { line_item_id, quantity } in payload.line_items {
+        if quantity == 0 || quantity > i32::max_value() as u32 {
+            return Err(order_line_items_validation_error(
+                "quantity",
+                &format!("Refunded quantity of line item {} is out of range", line_item_id),
+            ));
+        }
+        if refunds.iter().any(|(id, _)| *id == line_item_id) {
+            return Err(order_line_items_validation_error(
+                "duplicate",
+                &format!("Line item {} is refunded more than once", line_item_id),
+            ));
+        }
+        refunds.push((line_item_id, quantity as i32));
+    }
+
+    Ok(refunds)
+}
+
+/// Marks the units as refunded and returns the amount to refund to the buyer.
+/// Fails with a validation error if a line item is not of the order or fewer units are left to refund
+fn reserve_line_item_refunds(
+    order_line_items_repo: &OrderLineItemsRepo,
+    order: &RawOrder,
+    line_items: &[OrderLineItem],
+    refunds: &[(OrderLineItemId, i32)],
+) -> Result<Amount, ServiceError> {
+    let mut refund_amount = Amount::zero();
+    for &(line_item_id, quantity) in refunds {
+        let line_item = line_items.iter().find(|line_item| line_item.id == line_item_id).ok_or_else(|| {
+            order_line_items_validation_error(
+                "not_found",
+                &format!("Line item {} is not a line item of order {}", line_item_id, order.id),
+            )
+        })?;
+
+        let reserved = order_line_items_repo
+            .add_refunded_quantity(line_item_id, quantity)
+            .map_err(ectx!(try convert => line_item_id, quantity))?;
+        if reserved.is_none() {
+            return Err(order_line_items_validation_error(
+                "quantity",
+                &format!(
+                    "Only {} units of line item {} are left to refund",
+                    line_item.refundable_quantity(),
+                    line_item_id
+                ),
+            ));
+        }
+
+        refund_amount = line_item
+            .amount_of(quantity)
+            .and_then(|amount| refund_amount.checked_add(amount))
+            .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+    }
+
+    // the line items have been checked against the order total when the invoice was created
+    if refund_amount > order.total_amount {
+        let e = format_err!(
+            "Refund {} exceeds the total {} of order {}",
+            refund_amount,
+            order.total_amount,
+            order.id
+        );
+        return Err(ectx!(err e, ErrorContext::AmountConversion, ErrorKind::Internal));
+    }
+
+    Ok(refund_amount)
+}
+
+/// Marks the units left of the line items of the declined order as refunded. Returns the amount left to refund,
+/// which leaves out the line items refunded before, and the reserved units
+fn reserve_line_items_for_decline(
+    order_line_items_repo: &OrderLineItemsRepo,
+    order: &RawOrder,
+    line_items: &[OrderLineItem],
+) -> Result<(Amount, Vec<(OrderLineItemId, i32)>), ServiceError> {
+    let mut refunded_amount = Amount::zero();
+    let mut reserved = Vec::new();
+    for line_item in line_items {
+        refunded_amount = line_item
+            .amount_of(line_item.refunded_quantity)
+            .and_then(|amount| refunded_amount.checked_add(amount))
+            .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+
+        let line_item_id = line_item.id;
+        let quantity = line_item.refundable_quantity();
+        if quantity <= 0 {
+            continue;
+        }
+
+        let reserved_line_item = order_line_items_repo
+            .add_refunded_quantity(line_item_id, quantity)
+            .map_err(ectx!(try convert => line_item_id, quantity))?;
+        if reserved_line_item.is_none() {
+            return Err(order_line_items_validation_error(
+                "quantity",
+                &format!("Line item {} of order {} is being refunded", line_item_id, order.id),
+            ));
+        }
+        reserved.push((line_item_id, quantity));
+    }
+
+    let refund_amount = order.total_amount.checked_sub(refunded_amount).ok_or({
+        let e = format_err!(
+            "Refunded line items {} exceed the total {} of order {}",
+            refunded_amount,
+            order.total_amount,
+            order.id
+        );
+        ectx!(try err e, ErrorContext::AmountConversion, ErrorKind::Internal)
+    })?;
+
+    Ok((refund_amount, reserved))
+}
+
+/// Takes back the units reserved for a refund that has failed, so that they can be refunded again
+fn release_line_item_refunds<T, F, M>(
+    cpu_pool: CpuPool,
+    db_pool: Pool<M>,
+    repo_factory: F,
+    order_id: OrderId,
+    refunds: Vec<(OrderLineItemId, i32)>,
+) -> ServiceFutureV2<()>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    F: ReposFactory<T>,
+    M: ManageConnection<Connection = T>,
+{
+    let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
+        let order_line_items_repo = repo_factory.create_order_line_items_repo_with_sys_acl(&conn);
+        conn.transaction::<_, ServiceError, _>(|| {
+            for (line_item_id, quantity) in refunds {
+                order_line_items_repo
+                    .subtract_refunded_quantity(line_item_id, quantity)
+                    .map_err(ectx!(try convert => line_item_id, quantity))?;
+            }
+            Ok(())
+        })
+    })
+    .map_err(move |e| {
+        error!("Failed to release refunded line items of order {}: {}", order_id, e);
+        e
+    });
+
+    Box::new(fut)
+}
+
+/// Renders orders as CSV with a header row, one row per line item. An order without line items takes a single row
+pub fn order_line_items_csv(orders: &[OrderResponse]) -> String {
+    let mut csv = String::from(
+        "order_id,invoice_id,store_id,state,seller_currency,total_amount,line_item_id,product_id,name,quantity,unit_price,refunded_quantity,created_at\n",
+    );
+    for order in orders {
+        let seller_currency = Currency::try_from_stq_currency(order.seller_currency)
+            .map(|currency| currency.to_string())
+            .unwrap_or_default();
+        let order_columns = format!(
+            "{},{},{},{},{},{}",
+            order.id,
+            order.invoice_id,
+            order.store_id.inner(),
+            order.state,
+            seller_currency,
+            order.total_amount
+        );
+
+        if order.line_items.is_empty() {
+            csv.push_str(&format!("{},,,,,,,{}\n", order_columns, order.created_at));
+        }
+        for line_item in &order.line_items {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{}\n",
+                order_columns,
+                line_item.id,
+                line_item.product_id.0,
+                csv_field(&line_item.name),
+                line_item.quantity,
+                line_item.unit_price,
+                line_item.refunded_quantity,
+                order.created_at,
+            ));
+        }
+    }
+    csv
+}
+
+/// Quotes a free text field, product names may contain commas and quotes
+fn csv_field(value: &str) -> String {
+    if value.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Refuses to refund an order whose fee has been paid by crypto transfer, its share cannot be given back to the store
+fn check_fee_refundable(fees_repo: &FeeRepo, order_id: OrderId) -> Result<(), ServiceError> {
+    let fee = fees_repo
+        .get(SearchFee::OrderId(order_id))
+        .map_err(ectx!(try convert => order_id))?;
+    match fee {
+        Some(ref fee) if is_fee_paid_by_crypto(fee) => Err(fee_paid_by_crypto_error(fee)),
+        _ => Ok(()),
+    }
+}
+
+fn is_fee_paid_by_crypto(fee: &Fee) -> bool {
+    fee.status == FeeStatus::Paid && fee.charge_id.is_none()
+}
+
+fn fee_paid_by_crypto_error(fee: &Fee) -> ServiceError {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new("fee_paid_by_crypto");
+    error.message = Some(
+        format!(
+            "Platform fee #{} of order {} has been paid by crypto transfer, its share cannot be refunded",
+            fee.id, fee.order_id
+        )
+        .into(),
+    );
+    errors.add("fee", error);
+    ectx!(err ErrorContext::OrderState, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
+fn order_line_items_validation_error(code: &'static str, message: &str) -> ServiceError {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.to_string().into());
+    errors.add("line_items", error);
+    ectx!(err ErrorContext::OrderState, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
 fn order_capture_crypto<T, F, M>(
     cpu_pool: CpuPool,
     db_pool: Pool<M>,
@@ -347,3 +1219,117 @@ fn check_change_order_payment_state(current_state: PaymentState, new_state: Paym
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+    use chrono::NaiveDate;
+    use uuid::Uuid;
+
+    use stq_types::{Currency as StqCurrency, ProductId};
+
+    use super::*;
+    use controller::responses::OrderLineItemResponse;
+    use models::invoice_v2::InvoiceId;
+    use models::order_v2::StoreId;
+    use models::FeeId;
+    use test_support::{FeeBuilder, InMemoryReposFactory, MockConnection};
+
+    #[test]
+    fn orders_with_a_fee_paid_by_crypto_are_not_refunded() {
+        let conn = MockConnection::default();
+        let by_card = FeeBuilder::new()
+            .id(FeeId::new(1))
+            .status(FeeStatus::Paid)
+            .charge_id(Some(ChargeId::new("ch_fee".to_string())))
+            .build();
+        let by_crypto = FeeBuilder::new().id(FeeId::new(2)).status(FeeStatus::Paid).build();
+        let unpaid = FeeBuilder::new().id(FeeId::new(3)).build();
+        let repo_factory = InMemoryReposFactory::new().with_fees(vec![by_card.clone(), by_crypto.clone(), unpaid.clone()]);
+        let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+
+        assert!(check_fee_refundable(&*fees_repo, by_card.order_id).is_ok());
+        assert!(check_fee_refundable(&*fees_repo, unpaid.order_id).is_ok());
+        assert!(check_fee_refundable(&*fees_repo, OrderId::generate()).is_ok());
+        let e = check_fee_refundable(&*fees_repo, by_crypto.order_id).unwrap_err();
+        match e.kind() {
+            ErrorKind::Validation(errors) => assert_eq!(errors["fee"][0]["code"], "fee_paid_by_crypto"),
+            kind => panic!("validation error is expected, got {:?}", kind),
+        }
+    }
+
+    fn refund(line_item_id: OrderLineItemId, quantity: u32) -> OrderLineItemRefund {
+        OrderLineItemRefund { line_item_id, quantity }
+    }
+
+    #[test]
+    fn line_item_refunds_take_every_line_item_once() {
+        let mug = OrderLineItemId::generate();
+        let cup = OrderLineItemId::generate();
+
+        let refunds = line_item_refunds(RefundOrderLineItemsRequest {
+            line_items: vec![refund(mug, 2), refund(cup, 1)],
+        })
+        .unwrap();
+        assert_eq!(refunds, vec![(mug, 2), (cup, 1)]);
+
+        assert!(line_item_refunds(RefundOrderLineItemsRequest { line_items: vec![] }).is_err());
+        assert!(line_item_refunds(RefundOrderLineItemsRequest {
+            line_items: vec![refund(mug, 0)],
+        })
+        .is_err());
+        assert!(line_item_refunds(RefundOrderLineItemsRequest {
+            line_items: vec![refund(mug, 1), refund(mug, 1)],
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn csv_has_a_row_per_line_item() {
+        let created_at = NaiveDate::from_ymd(2019, 5, 2).and_hms(9, 0, 0);
+        let order = OrderResponse {
+            id: OrderId::new(Uuid::nil()),
+            seller_currency: StqCurrency::USD,
+            total_amount: 25.0,
+            cashback_amount: 0.0,
+            invoice_id: InvoiceId::new(Uuid::nil()),
+            created_at,
+            updated_at: created_at,
+            store_id: StoreId::new(1),
+            state: PaymentState::Captured,
+            stripe_fee: None,
+            line_items: vec![
+                OrderLineItemResponse {
+                    id: OrderLineItemId::new(Uuid::nil()),
+                    product_id: ProductId(7),
+                    name: "Mug, \"large\"".to_string(),
+                    quantity: 2,
+                    unit_price: BigDecimal::from_str("10.00").unwrap(),
+                    refunded_quantity: 1,
+                },
+                OrderLineItemResponse {
+                    id: OrderLineItemId::new(Uuid::nil()),
+                    product_id: ProductId(8),
+                    name: "Spoon".to_string(),
+                    quantity: 1,
+                    unit_price: BigDecimal::from_str("5.00").unwrap(),
+                    refunded_quantity: 0,
+                },
+            ],
+        };
+        let order_without_line_items = OrderResponse {
+            line_items: vec![],
+            ..order.clone()
+        };
+
+        let csv = order_line_items_csv(&[order, order_without_line_items]);
+        let lines = csv.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].contains(",7,\"Mug, \"\"large\"\"\",2,10.00,1,"));
+        assert!(lines[2].contains(",8,Spoon,1,5.00,0,"));
+        assert!(lines[3].ends_with(",,,,,,,2019-05-02 09:00:00"));
+    }
+}
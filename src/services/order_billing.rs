@@ -66,6 +66,9 @@ impl<
             let international_billing_info_repo = repo_factory.create_international_billing_info_repo(&conn, user_id);
             let russia_billing_info_repo = repo_factory.create_russia_billing_info_repo(&conn, user_id);
             let proxy_companies_billing_info_repo = repo_factory.create_proxy_companies_billing_info_repo(&conn, user_id);
+            // access to the orders has already been checked, invoices are only read for their metadata and buyer references
+            let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
+            let order_line_items_repo = repo_factory.create_order_line_items_repo_with_sys_acl(&conn);
             debug!("Requesting order billing {:?}", payload);
             let orders_search_result = orders_repo
                 .search(
@@ -110,6 +113,26 @@ impl<
                 .map(|billing| (billing.store_id, billing))
                 .collect();
 
+            let invoice_ids: Vec<_> = orders_search_result
+                .orders
+                .iter()
+                .map(|order| order.invoice_id)
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect();
+
+            let invoices: HashMap<_, _> = invoices_repo
+                .get_many(&invoice_ids)
+                .map_err(ectx!(try convert))?
+                .into_iter()
+                .map(|invoice| (invoice.id, invoice))
+                .collect();
+
+            let order_ids: Vec<_> = orders_search_result.orders.iter().map(|order| order.id).collect();
+            let line_items = order_line_items_repo
+                .get_by_order_ids(&order_ids)
+                .map_err(ectx!(try convert => order_ids))?;
+
             // todo find correct store country
             let russia = Alpha3("RUS".to_string());
             let proxy_company_billing_info = proxy_companies_billing_info_repo
@@ -126,6 +149,7 @@ impl<
                         .get(&store_id)
                         .map(|store_billing| store_billing.billing_type)
                         .unwrap_or(BillingType::International);
+                    let invoice = invoices.get(&order.invoice_id);
                     Ok(OrderBillingInfo {
                         russia_billing_info: russia_billings.get(&store_id).cloned(),
                         international_billing_info: international_billings.get(&store_id).cloned(),
@@ -133,7 +157,10 @@ impl<
                         proxy_company_billing_info: proxy_company_billing_info
                             .clone()
                             .filter(move |_| billing_type == BillingType::Russia),
-                        order: OrderResponse::try_from_raw_order(order)?,
+                        invoice_metadata: invoice.and_then(|invoice| invoice.metadata.clone()),
+                        invoice_memo: invoice.and_then(|invoice| invoice.memo.clone()),
+                        invoice_po_number: invoice.and_then(|invoice| invoice.po_number.clone()),
+                        order: OrderResponse::try_from_raw_order(order, &line_items)?,
                     })
                 })
                 .collect::<Result<Vec<_>, ServiceError>>()?;
@@ -0,0 +1,142 @@
+//! Log of the attempts to pay invoices in the fiat flow. Every failed and successful payment of an invoice reported
+//! by a Stripe webhook is recorded with its decline code and card fingerprint, so that support can tell a buyer
+//! retrying the same declined card from one trying several cards
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::Fail;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use stripe::{ErrorCode, PaymentIntent as StripePaymentIntent, PaymentIntentStatus as StripePaymentIntentStatus, PaymentSource};
+
+use stq_types::stripe::PaymentIntentId;
+use stq_types::UserId;
+
+use super::types::{ServiceFutureV2, ServiceResultV2};
+use controller::responses::InvoiceInspectionResponse;
+use models::invoice_v2::InvoiceId;
+use models::{NewPaymentAttempt, PaymentAttempt, PaymentAttemptStatus};
+use repos::{PaymentAttemptsRepo, ReposFactory, SearchPaymentIntentInvoice};
+use services::types::spawn_on_pool;
+use services::ErrorKind;
+
+pub trait PaymentAttemptService {
+    /// Returns the invoice with the attempts to pay it
+    fn get_invoice_inspection(&self, invoice_id: InvoiceId) -> ServiceFutureV2<InvoiceInspectionResponse>;
+}
+
+pub struct PaymentAttemptServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > PaymentAttemptService for PaymentAttemptServiceImpl<T, M, F>
+{
+    fn get_invoice_inspection(&self, invoice_id: InvoiceId) -> ServiceFutureV2<InvoiceInspectionResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let invoices_repo = repo_factory.create_invoices_v2_repo(&conn, user_id);
+            let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo(&conn, user_id);
+            let payment_attempts_repo = repo_factory.create_payment_attempts_repo(&conn, user_id);
+
+            let invoice = invoices_repo.get(invoice_id).map_err(ectx!(try convert => invoice_id))?.ok_or({
+                let e = format_err!("Invoice {} not found", invoice_id);
+                ectx!(try err e, ErrorKind::NotFound)
+            })?;
+
+            let payment_intent_id = payment_intent_invoices_repo
+                .get(SearchPaymentIntentInvoice::InvoiceId(invoice_id))
+                .map_err(ectx!(try convert => invoice_id))?
+                .map(|payment_intent_invoice| payment_intent_invoice.payment_intent_id);
+
+            let payment_attempts = payment_attempts_repo
+                .list_by_invoice_id(invoice_id)
+                .map_err(ectx!(try convert => invoice_id))?;
+
+            Ok(InvoiceInspectionResponse::new(invoice, payment_intent_id, payment_attempts))
+        })
+    }
+}
+
+/// Outcome of the latest attempt to pay a payment intent, as reported by a webhook
+#[derive(Debug, Clone)]
+pub struct PaymentAttemptOutcome {
+    pub status: PaymentAttemptStatus,
+    pub decline_code: Option<String>,
+    pub error_code: Option<String>,
+    pub payment_method_fingerprint: Option<String>,
+}
+
+impl PaymentAttemptOutcome {
+    pub fn from_payment_intent(payment_intent: &StripePaymentIntent) -> Self {
+        // Stripe lists the charges of a payment intent newest first
+        let payment_method_fingerprint = payment_intent.charges.data.first().and_then(|charge| match charge.source {
+            PaymentSource::Card(ref card) => Some(card.fingerprint.clone()),
+            _ => None,
+        });
+
+        match payment_intent.last_payment_error {
+            Some(ref error) => {
+                let status = if error.decline_code.is_some() {
+                    PaymentAttemptStatus::Declined
+                } else if error.code == Some(ErrorCode::PaymentIntentAuthenticationFailure) {
+                    PaymentAttemptStatus::AuthenticationFailed
+                } else {
+                    PaymentAttemptStatus::Failed
+                };
+
+                PaymentAttemptOutcome {
+                    status,
+                    decline_code: error.decline_code.clone(),
+                    error_code: error.code.as_ref().map(|code| format!("{:?}", code)),
+                    payment_method_fingerprint,
+                }
+            }
+            None => PaymentAttemptOutcome {
+                status: match payment_intent.status {
+                    StripePaymentIntentStatus::Succeeded => PaymentAttemptStatus::Succeeded,
+                    StripePaymentIntentStatus::RequiresCapture => PaymentAttemptStatus::Authorized,
+                    _ => PaymentAttemptStatus::Failed,
+                },
+                decline_code: None,
+                error_code: None,
+                payment_method_fingerprint,
+            },
+        }
+    }
+}
+
+/// Records an attempt to pay the invoice with the payment intent
+pub fn record_payment_attempt(
+    payment_attempts_repo: &PaymentAttemptsRepo,
+    invoice_id: InvoiceId,
+    payment_intent_id: PaymentIntentId,
+    outcome: PaymentAttemptOutcome,
+) -> ServiceResultV2<PaymentAttempt> {
+    let new_payment_attempt = NewPaymentAttempt {
+        invoice_id,
+        payment_intent_id,
+        status: outcome.status,
+        decline_code: outcome.decline_code,
+        error_code: outcome.error_code,
+        payment_method_fingerprint: outcome.payment_method_fingerprint,
+    };
+
+    payment_attempts_repo
+        .create(new_payment_attempt.clone())
+        .map_err(ectx!(convert => new_payment_attempt))
+}
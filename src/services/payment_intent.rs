@@ -4,7 +4,7 @@ use std::sync::Arc;
 use diesel::connection::AnsiTransactionManager;
 use diesel::pg::Pg;
 use diesel::Connection;
-use futures::{future, Future, IntoFuture};
+use futures::{future, Future};
 use futures_cpupool::CpuPool;
 use r2d2::{ManageConnection, Pool};
 use validator::{ValidationError, ValidationErrors};
@@ -14,17 +14,18 @@ use failure::Fail;
 use stq_http::client::HttpClient;
 use stq_types::stripe::PaymentIntentId;
 
+use client::fiat_payments::{CaptureMethod, FiatPaymentProvider, NewFiatPaymentIntent};
 use client::payments::PaymentsClient;
-use client::stripe::{NewPaymentIntent as StripeClientNewPaymentIntent, StripeClient};
+use client::stripe::StripeClient;
 use controller::context::DynamicContext;
 use models::invoice_v2::InvoiceId;
 use models::*;
 use services::accounts::AccountService;
 
-use repos::{ReposFactory, SearchFee, SearchPaymentIntent, SearchPaymentIntentInvoice};
+use repos::{PaymentIntentHistoryRepo, ReposFactory, SearchCustomer, SearchFee, SearchPaymentIntent, SearchPaymentIntentInvoice};
 use services::{Error as ServiceError, ErrorContext, ErrorKind};
 
-use controller::responses::PaymentIntentResponse;
+use controller::responses::{PaymentIntentHistoryEntryResponse, PaymentIntentHistoryResponse, PaymentIntentResponse};
 
 use super::types::ServiceFutureV2;
 
@@ -35,6 +36,8 @@ pub trait PaymentIntentService {
     fn get_by_invoice(&self, invoice_id: InvoiceId) -> ServiceFutureV2<Option<PaymentIntentResponse>>;
     /// Create payment intent object by fee ID
     fn create_by_fee(&self, fee_id: FeeId) -> ServiceFutureV2<PaymentIntentResponse>;
+    /// Returns status transitions of the payment intent observed by billing
+    fn get_history(&self, payment_intent_id: PaymentIntentId) -> ServiceFutureV2<PaymentIntentHistoryResponse>;
 }
 
 pub struct PaymentIntentServiceImpl<
@@ -49,7 +52,7 @@ pub struct PaymentIntentServiceImpl<
     pub cpu_pool: CpuPool,
     pub repo_factory: F,
     pub dynamic_context: DynamicContext<C, PC, AS>,
-    pub stripe_client: Arc<dyn StripeClient>,
+    pub fiat_payment_provider: Arc<dyn FiatPaymentProvider>,
 }
 
 impl<
@@ -101,7 +104,7 @@ impl<
         let db_pool = self.db_pool.clone();
         let cpu_pool = self.cpu_pool.clone();
 
-        let stripe_client = self.stripe_client.clone();
+        let fiat_payment_provider = self.fiat_payment_provider.clone();
 
         let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
             let fee_repo = repo_factory.create_fees_repo(&conn, user_id);
@@ -111,9 +114,21 @@ impl<
                 ectx!(try err e, ErrorKind::NotFound)
             })?;
             validate_payment_intent_create_fee(&fee)?;
-            Ok(fee)
+
+            let receipt_email = match user_id {
+                Some(user_id) => {
+                    let customers_repo = repo_factory.create_customers_repo(&conn, Some(user_id));
+                    customers_repo
+                        .get(SearchCustomer::UserId(user_id))
+                        .map_err(ectx!(try convert => user_id))?
+                        .and_then(|customer| customer.email)
+                }
+                None => None,
+            };
+
+            Ok((fee, receipt_email))
         })
-        .and_then(move |fee| create_fee_payment_intent(stripe_client, fee))
+        .and_then(move |(fee, receipt_email)| create_fee_payment_intent(fiat_payment_provider, fee, receipt_email))
         .and_then({
             let repo_factory = self.repo_factory.clone();
 
@@ -124,11 +139,13 @@ impl<
                 spawn_on_pool(db_pool, cpu_pool, move |conn| {
                     let payment_intent_fees_repo = repo_factory.create_payment_intent_fees_repo(&conn, user_id);
                     let payment_intent_repo = repo_factory.create_payment_intent_repo(&conn, user_id);
+                    let payment_intent_history_repo = repo_factory.create_payment_intent_history_repo_with_sys_acl(&conn);
                     conn.transaction(move || {
                         payment_intent_fees_repo
                             .create(new_payment_intent_fee)
                             .map_err(ectx!(try convert))?;
                         let payment_intent = payment_intent_repo.create(new_payment_intent).map_err(ectx!(try convert))?;
+                        record_payment_intent_status(&*payment_intent_history_repo, &payment_intent, PaymentIntentHistorySource::Api)?;
                         Ok(payment_intent)
                     })
                 })
@@ -138,6 +155,62 @@ impl<
 
         Box::new(fut)
     }
+
+    fn get_history(&self, payment_intent_id: PaymentIntentId) -> ServiceFutureV2<PaymentIntentHistoryResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let payment_intent_repo = repo_factory.create_payment_intent_repo(&conn, user_id);
+            let payment_intent_history_repo = repo_factory.create_payment_intent_history_repo(&conn, user_id);
+
+            let payment_intent = payment_intent_repo
+                .get(SearchPaymentIntent::Id(payment_intent_id.clone()))
+                .map_err(ectx!(try convert => payment_intent_id))?
+                .ok_or({
+                    let e = format_err!("Payment intent {} not found", payment_intent_id);
+                    ectx!(try err e, ErrorKind::NotFound)
+                })?;
+
+            let history = payment_intent_history_repo
+                .list_by_payment_intent_id(payment_intent_id.clone())
+                .map_err(ectx!(try convert => payment_intent_id))?;
+
+            Ok(PaymentIntentHistoryResponse {
+                payment_intent_id: payment_intent.id,
+                status: payment_intent.status,
+                history: history.into_iter().map(PaymentIntentHistoryEntryResponse::from).collect(),
+            })
+        })
+    }
+}
+
+/// Records the status of the payment intent unless it is already its latest recorded status,
+/// so repeated webhooks and reads of an unchanged payment intent don't clutter the history
+pub fn record_payment_intent_status(
+    payment_intent_history_repo: &PaymentIntentHistoryRepo,
+    payment_intent: &PaymentIntent,
+    source: PaymentIntentHistorySource,
+) -> Result<(), ServiceError> {
+    let payment_intent_id = payment_intent.id.clone();
+    let latest_entry = payment_intent_history_repo
+        .get_latest_by_payment_intent_id(payment_intent_id.clone())
+        .map_err(ectx!(try convert => payment_intent_id))?;
+
+    if latest_entry.map_or(false, |latest_entry| latest_entry.status == payment_intent.status) {
+        return Ok(());
+    }
+
+    let new_entry = NewPaymentIntentHistoryEntry {
+        payment_intent_id,
+        status: payment_intent.status.clone(),
+        source,
+    };
+    payment_intent_history_repo
+        .create(new_entry.clone())
+        .map_err(ectx!(try convert => new_entry))?;
+
+    Ok(())
 }
 
 pub fn cancel_payment_intent<T, M, F, STRC>(
@@ -204,16 +277,20 @@ where
         None => Box::new(future::ok(())),
         Some((id, status)) => spawn_on_pool(db_pool, cpu_pool, move |conn| {
             let payment_intent_repo = repo_factory.create_payment_intent_repo_with_sys_acl(&conn);
+            let payment_intent_history_repo = repo_factory.create_payment_intent_history_repo_with_sys_acl(&conn);
 
             let update_payment_intent = UpdatePaymentIntent {
                 status: Some(status),
                 ..UpdatePaymentIntent::default()
             };
 
-            payment_intent_repo
-                .update(id.clone(), update_payment_intent.clone())
-                .map_err(ectx!(convert => id, update_payment_intent))
-                .map(|_| ())
+            conn.transaction(move || {
+                let payment_intent = payment_intent_repo
+                    .update(id.clone(), update_payment_intent.clone())
+                    .map_err(ectx!(try convert => id, update_payment_intent))?;
+
+                record_payment_intent_status(&*payment_intent_history_repo, &payment_intent, PaymentIntentHistorySource::Api)
+            })
         }),
     });
 
@@ -222,7 +299,7 @@ where
 
 fn validate_payment_intent_create_fee(fee: &Fee) -> Result<(), ServiceError> {
     match &fee.status {
-        illegal_status @ FeeStatus::Paid | illegal_status @ FeeStatus::Fail => {
+        illegal_status @ FeeStatus::Paid | illegal_status @ FeeStatus::Fail | illegal_status @ FeeStatus::WrittenOff => {
             let mut errors = ValidationErrors::new();
             let mut error = ValidationError::new("Can not create payment intent");
             error.message = Some(format!("Can not create payment intent with fee status \"{:?}\"", illegal_status).into());
@@ -237,64 +314,30 @@ fn validate_payment_intent_create_fee(fee: &Fee) -> Result<(), ServiceError> {
     Ok(())
 }
 
-fn create_fee_payment_intent(stripe_client: Arc<dyn StripeClient>, fee: Fee) -> ServiceFutureV2<(NewPaymentIntent, NewPaymentIntentFee)> {
+fn create_fee_payment_intent(
+    fiat_payment_provider: Arc<dyn FiatPaymentProvider>,
+    fee: Fee,
+    receipt_email: Option<String>,
+) -> ServiceFutureV2<(NewPaymentIntent, NewPaymentIntentFee)> {
     let fee_id = fee.id;
-    let fut = payment_intent_create_params(fee)
-        .into_future()
-        .and_then(move |payment_intent_creation| {
-            stripe_client
-                .create_payment_intent(payment_intent_creation)
-                .map_err(ectx!(convert => fee_id))
-        })
-        .and_then(move |stripe_payment_intent| new_payment_intent(fee_id, stripe_payment_intent));
-
-    Box::new(fut)
-}
-
-fn payment_intent_create_params(fee: Fee) -> Result<StripeClientNewPaymentIntent, ServiceError> {
-    Ok(StripeClientNewPaymentIntent {
-        allowed_source_types: vec![stripe::PaymentIntentSourceType::Card],
-        amount: fee.amount.into(),
-        currency: fee.currency.try_into_stripe_currency().map_err(|_| {
-            let e = format_err!("Fee with id {} - could not convet currency: {}", fee.id, fee.currency);
-            ectx!(try err e, ErrorKind::Internal)
-        })?,
-        capture_method: Some(stripe::CaptureMethod::Manual),
-    })
-}
-
-fn new_payment_intent(
-    fee_id: FeeId,
-    stripe_payment_intent: stripe::PaymentIntent,
-) -> Result<(NewPaymentIntent, NewPaymentIntentFee), ServiceError> {
-    let payment_intent = NewPaymentIntent {
-        id: PaymentIntentId(stripe_payment_intent.id.clone()),
-        amount: stripe_payment_intent.amount.into(),
-        amount_received: stripe_payment_intent.amount_received.into(),
-        client_secret: stripe_payment_intent.client_secret,
-        currency: Currency::try_from_stripe_currency(stripe_payment_intent.currency).map_err({
-            let e = format_err!(
-                "Payment intent for invoice with ID: {} can not convert currency: {}",
-                fee_id,
-                stripe_payment_intent.currency,
-            );
-            move |_| ectx!(try err e, ErrorKind::Internal)
-        })?,
-        last_payment_error_message: stripe_payment_intent.last_payment_error.map(|err| format!("{:?}", err)),
-        receipt_email: stripe_payment_intent.receipt_email,
-        charge_id: stripe_payment_intent
-            .charges
-            .data
-            .into_iter()
-            .next()
-            .map(|charge| ChargeId::new(charge.id)),
-        status: stripe_payment_intent.status.into(),
+    let new_payment_intent = NewFiatPaymentIntent {
+        amount: fee.amount,
+        currency: fee.currency,
+        capture_method: CaptureMethod::Manual,
+        receipt_email,
+        description: None,
     };
 
-    let payment_intent_invoice = NewPaymentIntentFee {
-        fee_id,
-        payment_intent_id: PaymentIntentId(stripe_payment_intent.id),
-    };
+    let fut = fiat_payment_provider
+        .create_payment_intent(new_payment_intent)
+        .map_err(ectx!(convert => fee_id))
+        .map(move |payment_intent| {
+            let payment_intent_fee = NewPaymentIntentFee {
+                fee_id,
+                payment_intent_id: payment_intent.id.clone(),
+            };
+            (payment_intent, payment_intent_fee)
+        });
 
-    Ok((payment_intent, payment_intent_invoice))
+    Box::new(fut)
 }
@@ -0,0 +1,100 @@
+//! PaymentMethodService tells which payment methods and currencies are available to a buyer
+use enum_iterator::IntoEnumIterator;
+use futures::future;
+
+use stq_types::{Alpha3, StoreId};
+
+use super::types::ServiceFutureV2;
+use config::{PaymentMethodRule, PaymentMethods as PaymentMethodsConfig};
+use controller::responses::{AvailablePaymentMethod, PaymentMethodsResponse};
+use models::{CheckoutPaymentMethod, Currency};
+
+pub trait PaymentMethodService {
+    /// Payment methods available to a buyer from `country` purchasing in the store
+    fn get_payment_methods(&self, country: Option<Alpha3>, store_id: Option<StoreId>) -> ServiceFutureV2<PaymentMethodsResponse>;
+}
+
+pub struct PaymentMethodServiceImpl {
+    pub config: PaymentMethodsConfig,
+}
+
+impl PaymentMethodService for PaymentMethodServiceImpl {
+    fn get_payment_methods(&self, country: Option<Alpha3>, store_id: Option<StoreId>) -> ServiceFutureV2<PaymentMethodsResponse> {
+        let store_ids = store_id.map(|store_id| vec![store_id.0]).unwrap_or_default();
+        let currencies = allowed_currencies(&self.config.rules, country.as_ref(), &store_ids);
+
+        let payment_methods = vec![CheckoutPaymentMethod::Card, CheckoutPaymentMethod::CryptoWallet]
+            .into_iter()
+            .map(|payment_method| AvailablePaymentMethod {
+                payment_method,
+                currencies: currencies
+                    .iter()
+                    .cloned()
+                    .filter(|currency| CheckoutPaymentMethod::for_currency(*currency) == payment_method)
+                    .collect(),
+            })
+            .filter(|available| !available.currencies.is_empty())
+            .collect();
+
+        Box::new(future::ok(PaymentMethodsResponse { payment_methods }))
+    }
+}
+
+/// Currencies allowed by every rule that applies to a buyer from `country` purchasing in the stores.
+/// Rules limited to countries don't apply when the country is unknown
+pub fn allowed_currencies(rules: &[PaymentMethodRule], country: Option<&Alpha3>, store_ids: &[i32]) -> Vec<Currency> {
+    let applicable_rules = rules
+        .iter()
+        .filter(|rule| rule_applies(rule, country, store_ids))
+        .collect::<Vec<_>>();
+
+    Currency::into_enum_iter()
+        .filter(|currency| applicable_rules.iter().all(|rule| rule.currencies.contains(currency)))
+        .collect()
+}
+
+fn rule_applies(rule: &PaymentMethodRule, country: Option<&Alpha3>, store_ids: &[i32]) -> bool {
+    let country_matches = rule.countries.is_empty()
+        || country.map_or(false, |country| {
+            rule.countries
+                .iter()
+                .any(|rule_country| rule_country.eq_ignore_ascii_case(&country.0))
+        });
+    let store_matches = rule.store_ids.is_empty() || store_ids.iter().any(|store_id| rule.store_ids.contains(store_id));
+
+    country_matches && store_matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(countries: &[&str], store_ids: &[i32], currencies: &[Currency]) -> PaymentMethodRule {
+        PaymentMethodRule {
+            countries: countries.iter().map(|country| country.to_string()).collect(),
+            store_ids: store_ids.to_vec(),
+            currencies: currencies.to_vec(),
+        }
+    }
+
+    #[test]
+    fn matching_rules_narrow_currencies_down() {
+        let rules = vec![
+            rule(&["IRN"], &[], &[Currency::Eur, Currency::Usd, Currency::Rub]),
+            rule(&[], &[7], &[Currency::Eur, Currency::Stq]),
+        ];
+        let iran = Alpha3("irn".to_string());
+        let germany = Alpha3("DEU".to_string());
+
+        assert_eq!(allowed_currencies(&rules, Some(&iran), &[7]), vec![Currency::Eur]);
+        assert_eq!(
+            allowed_currencies(&rules, Some(&iran), &[1]),
+            vec![Currency::Eur, Currency::Usd, Currency::Rub]
+        );
+        assert_eq!(allowed_currencies(&rules, Some(&germany), &[7]), vec![Currency::Stq, Currency::Eur]);
+        assert_eq!(
+            allowed_currencies(&rules, None, &[]),
+            Currency::into_enum_iter().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,401 @@
+//! Recovery of failed fiat payments of invoices. A failed payment starts a recovery and the event handler
+//! emails the buyer a retry link, PaymentRecoveryService serves the retry and reports the outcomes
+use std::sync::Arc;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use futures::{future, Future};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use serde_json;
+use stq_static_resources::OrderState;
+use stq_types::stripe::PaymentIntentId;
+use validator::{ValidationError, ValidationErrors};
+
+use failure::Fail;
+
+use stq_types::UserId;
+
+use super::types::ServiceFutureV2;
+use client::fiat_payments::{CaptureMethod, FiatPaymentProvider, NewFiatPaymentIntent};
+use controller::responses::{PaymentIntentResponse, PaymentRecoveryReportResponse};
+use models::invoice_v2::{InvoiceId, RawInvoice};
+use models::{
+    Event, EventPayload, NewPaymentIntentInvoice, NewPaymentRecovery, PaymentIntentHistorySource, PaymentIntentStatus, PaymentRecovery,
+    PaymentRecoveryId, PaymentRecoveryStatus, UpdatePaymentRecovery,
+};
+use repos::{EventStoreRepo, PaymentRecoveriesRepo, ReposFactory, SearchPaymentIntent, SearchPaymentIntentInvoice};
+use services::payment_intent::record_payment_intent_status;
+use services::types::spawn_on_pool;
+use services::{Error, ErrorContext, ErrorKind};
+
+pub trait PaymentRecoveryService {
+    /// Returns the payment intent the buyer confirms to pay the invoice after a failed payment
+    fn retry_payment(&self, invoice_id: InvoiceId) -> ServiceFutureV2<PaymentIntentResponse>;
+    /// Returns outcomes of the recoveries started in the period
+    fn get_report(
+        &self,
+        created_from: Option<NaiveDateTime>,
+        created_to: Option<NaiveDateTime>,
+    ) -> ServiceFutureV2<PaymentRecoveryReportResponse>;
+}
+
+pub struct PaymentRecoveryServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+    pub fiat_payment_provider: Arc<dyn FiatPaymentProvider>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > PaymentRecoveryService for PaymentRecoveryServiceImpl<T, M, F>
+{
+    fn retry_payment(&self, invoice_id: InvoiceId) -> ServiceFutureV2<PaymentIntentResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+        let fiat_payment_provider = self.fiat_payment_provider.clone();
+
+        let fut = spawn_on_pool(db_pool.clone(), cpu_pool.clone(), {
+            let repo_factory = repo_factory.clone();
+            move |conn| {
+                let invoices_repo = repo_factory.create_invoices_v2_repo(&conn, user_id);
+                let payment_recoveries_repo = repo_factory.create_payment_recoveries_repo(&conn, user_id);
+                let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo(&conn, user_id);
+                let payment_intent_repo = repo_factory.create_payment_intent_repo(&conn, user_id);
+
+                let invoice = invoices_repo
+                    .get(invoice_id)
+                    .map_err(ectx!(try convert => invoice_id))?
+                    .ok_or_else(|| {
+                        let e = format_err!("Invoice {} not found", invoice_id);
+                        ectx!(try err e, ErrorKind::NotFound)
+                    })?;
+
+                let recovery = payment_recoveries_repo
+                    .get_by_invoice_id(invoice_id)
+                    .map_err(ectx!(try convert => invoice_id))?
+                    .ok_or_else(|| {
+                        let e = format_err!("Payment of invoice {} has not failed", invoice_id);
+                        ectx!(try err e, ErrorKind::NotFound)
+                    })?;
+
+                validate_payment_retry(&invoice, &recovery)?;
+
+                let payment_intent_invoice = payment_intent_invoices_repo
+                    .get(SearchPaymentIntentInvoice::InvoiceId(invoice_id))
+                    .map_err(ectx!(try convert => invoice_id))?
+                    .ok_or_else(|| {
+                        let e = format_err!("Payment intent of invoice {} not found", invoice_id);
+                        ectx!(try err e, ErrorKind::Internal)
+                    })?;
+
+                let payment_intent_id = payment_intent_invoice.payment_intent_id;
+                let payment_intent = payment_intent_repo
+                    .get(SearchPaymentIntent::Id(payment_intent_id.clone()))
+                    .map_err(ectx!(try convert => payment_intent_id))?
+                    .ok_or_else(|| {
+                        let e = format_err!("Payment intent {} not found", payment_intent_id);
+                        ectx!(try err e, ErrorKind::Internal)
+                    })?;
+
+                Ok((recovery, payment_intent))
+            }
+        })
+        .and_then(move |(recovery, payment_intent)| match payment_intent.status.clone() {
+            // the failed payment intent is confirmed again on the client side
+            ref status if status.is_retryable() => future::Either::A(future::ok((recovery, payment_intent, None))),
+            PaymentIntentStatus::Canceled | PaymentIntentStatus::Other => {
+                let new_payment_intent = NewFiatPaymentIntent {
+                    amount: payment_intent.amount,
+                    currency: payment_intent.currency,
+                    capture_method: CaptureMethod::Manual,
+                    receipt_email: payment_intent.receipt_email.clone(),
+                    description: None,
+                };
+
+                future::Either::B(future::Either::A(
+                    fiat_payment_provider
+                        .create_payment_intent(new_payment_intent)
+                        .map_err(ectx!(convert => invoice_id))
+                        .map(move |new_payment_intent| (recovery, payment_intent, Some(new_payment_intent))),
+                ))
+            }
+            ref status => {
+                let message = format!("Payment of the invoice can not be retried in status \"{:?}\"", status);
+                future::Either::B(future::Either::B(future::err(payment_retry_validation_error(
+                    "payment_in_progress",
+                    message,
+                ))))
+            }
+        })
+        .and_then(move |(recovery, payment_intent, new_payment_intent)| {
+            spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                let payment_recoveries_repo = repo_factory.create_payment_recoveries_repo(&conn, user_id);
+                let payment_intent_repo = repo_factory.create_payment_intent_repo(&conn, user_id);
+                let payment_intent_invoices_repo = repo_factory.create_payment_intent_invoices_repo_with_sys_acl(&conn);
+                let payment_intent_history_repo = repo_factory.create_payment_intent_history_repo_with_sys_acl(&conn);
+
+                conn.transaction(move || {
+                    let payment_intent = match new_payment_intent {
+                        None => payment_intent,
+                        Some(new_payment_intent) => {
+                            let payment_intent = payment_intent_repo
+                                .create(new_payment_intent)
+                                .map_err(ectx!(try convert => invoice_id))?;
+                            record_payment_intent_status(&*payment_intent_history_repo, &payment_intent, PaymentIntentHistorySource::Api)?;
+
+                            payment_intent_invoices_repo
+                                .delete(SearchPaymentIntentInvoice::InvoiceId(invoice_id))
+                                .map_err(ectx!(try convert => invoice_id))?;
+
+                            let new_payment_intent_invoice = NewPaymentIntentInvoice {
+                                invoice_id,
+                                payment_intent_id: payment_intent.id.clone(),
+                            };
+                            payment_intent_invoices_repo
+                                .create(new_payment_intent_invoice.clone())
+                                .map_err(ectx!(try convert => new_payment_intent_invoice))?;
+
+                            payment_intent
+                        }
+                    };
+
+                    let recovery_id = recovery.id;
+                    let update = UpdatePaymentRecovery {
+                        status: Some(PaymentRecoveryStatus::Retried),
+                        retry_payment_intent_id: Some(Some(payment_intent.id.clone())),
+                        retried_at: Some(Some(Utc::now().naive_utc())),
+                        ..Default::default()
+                    };
+                    payment_recoveries_repo
+                        .update(recovery_id, update.clone())
+                        .map_err(ectx!(try convert => recovery_id, update))?;
+
+                    PaymentIntentResponse::try_from_payment_intent(payment_intent)
+                })
+            })
+        });
+
+        Box::new(fut)
+    }
+
+    fn get_report(
+        &self,
+        created_from: Option<NaiveDateTime>,
+        created_to: Option<NaiveDateTime>,
+    ) -> ServiceFutureV2<PaymentRecoveryReportResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let payment_recoveries_repo = repo_factory.create_payment_recoveries_repo(&conn, user_id);
+
+            payment_recoveries_repo
+                .search(created_from, created_to)
+                .map(|recoveries| payment_recovery_report(&recoveries))
+                .map_err(ectx!(convert => created_from, created_to))
+        })
+    }
+}
+
+/// Starts the recovery of a failed payment of the invoice or restarts it when a retried payment
+/// failed again, the buyer is notified once the transaction commits
+pub fn record_payment_failure(
+    payment_recoveries_repo: &PaymentRecoveriesRepo,
+    event_store_repo: &EventStoreRepo,
+    invoice: &RawInvoice,
+    payment_intent_id: PaymentIntentId,
+    failure_message: Option<String>,
+) -> Result<PaymentRecovery, Error> {
+    let invoice_id = invoice.id;
+    let recovery = payment_recoveries_repo
+        .get_by_invoice_id(invoice_id)
+        .map_err(ectx!(try convert => invoice_id))?;
+
+    let recovery = match recovery {
+        None => {
+            let new_recovery = NewPaymentRecovery {
+                id: PaymentRecoveryId::generate(),
+                invoice_id,
+                buyer_user_id: invoice.buyer_user_id,
+                status: PaymentRecoveryStatus::Pending,
+                failed_payment_intent_id: payment_intent_id,
+                failure_message,
+            };
+            payment_recoveries_repo
+                .create(new_recovery.clone())
+                .map_err(ectx!(try convert => new_recovery))?
+        }
+        Some(recovery) => {
+            let recovery_id = recovery.id;
+            let update = UpdatePaymentRecovery {
+                status: Some(PaymentRecoveryStatus::Pending),
+                failed_payment_intent_id: Some(payment_intent_id),
+                failure_message: Some(failure_message),
+                failures_count: Some(recovery.failures_count + 1),
+                closed_at: Some(None),
+                ..Default::default()
+            };
+            payment_recoveries_repo
+                .update(recovery_id, update.clone())
+                .map_err(ectx!(try convert => recovery_id, update))?
+        }
+    };
+
+    let event = Event::new(EventPayload::PaymentRecoveryNotification {
+        payment_recovery_id: recovery.id,
+    });
+    event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+
+    Ok(recovery)
+}
+
+/// Closes the recovery of the invoice with `status` if the invoice has an open one
+pub fn close_payment_recovery(
+    payment_recoveries_repo: &PaymentRecoveriesRepo,
+    invoice_id: InvoiceId,
+    status: PaymentRecoveryStatus,
+) -> Result<(), Error> {
+    let recovery = payment_recoveries_repo
+        .get_by_invoice_id(invoice_id)
+        .map_err(ectx!(try convert => invoice_id))?;
+
+    match recovery {
+        Some(ref recovery) if recovery.status.is_open() => {
+            let recovery_id = recovery.id;
+            let update = UpdatePaymentRecovery {
+                status: Some(status),
+                closed_at: Some(Some(Utc::now().naive_utc())),
+                ..Default::default()
+            };
+            payment_recoveries_repo
+                .update(recovery_id, update.clone())
+                .map_err(ectx!(try convert => recovery_id, update))?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn validate_payment_retry(invoice: &RawInvoice, recovery: &PaymentRecovery) -> Result<(), Error> {
+    if invoice.paid_at.is_some() || invoice.status != OrderState::PaymentAwaited {
+        let message = format!("Invoice in status \"{:?}\" can not be paid", invoice.status);
+        return Err(payment_retry_validation_error("invoice_not_payable", message));
+    }
+
+    if !recovery.status.is_open() {
+        let message = format!("Payment can not be retried after the recovery is {:?}", recovery.status);
+        return Err(payment_retry_validation_error("payment_recovery_closed", message));
+    }
+
+    Ok(())
+}
+
+fn payment_retry_validation_error(code: &'static str, message: String) -> Error {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    errors.add("invoice_id", error);
+    ectx!(err ErrorContext::PaymentRecovery, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
+fn payment_recovery_report(recoveries: &[PaymentRecovery]) -> PaymentRecoveryReportResponse {
+    let mut report = PaymentRecoveryReportResponse::default();
+
+    for recovery in recoveries {
+        report.total += 1;
+        match recovery.status {
+            PaymentRecoveryStatus::Pending => report.pending += 1,
+            PaymentRecoveryStatus::Notified => report.notified += 1,
+            PaymentRecoveryStatus::Retried => report.retried += 1,
+            PaymentRecoveryStatus::Recovered => report.recovered += 1,
+            PaymentRecoveryStatus::Expired => report.expired += 1,
+            PaymentRecoveryStatus::Cancelled => report.cancelled += 1,
+        }
+    }
+
+    let closed = report.recovered + report.expired + report.cancelled;
+    if closed > 0 {
+        report.recovery_rate = Some(report.recovered as f64 / closed as f64);
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use stq_types::stripe::PaymentIntentId;
+
+    use controller::responses::PaymentRecoveryReportResponse;
+    use models::invoice_v2::InvoiceId;
+    use models::*;
+
+    use super::payment_recovery_report;
+
+    fn recovery(status: PaymentRecoveryStatus) -> PaymentRecovery {
+        let created_at = NaiveDate::from_ymd(2019, 3, 20).and_hms(9, 0, 0);
+
+        PaymentRecovery {
+            id: PaymentRecoveryId::generate(),
+            invoice_id: InvoiceId::generate(),
+            buyer_user_id: UserId::new(1),
+            status,
+            failed_payment_intent_id: PaymentIntentId("pi_failed".to_string()),
+            failure_message: None,
+            failures_count: 1,
+            retry_payment_intent_id: None,
+            notified_at: None,
+            retried_at: None,
+            closed_at: None,
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    #[test]
+    fn report_counts_statuses_and_rate_of_closed_recoveries() {
+        let recoveries = vec![
+            recovery(PaymentRecoveryStatus::Notified),
+            recovery(PaymentRecoveryStatus::Retried),
+            recovery(PaymentRecoveryStatus::Recovered),
+            recovery(PaymentRecoveryStatus::Recovered),
+            recovery(PaymentRecoveryStatus::Recovered),
+            recovery(PaymentRecoveryStatus::Expired),
+        ];
+
+        let report = payment_recovery_report(&recoveries);
+
+        assert_eq!(
+            report,
+            PaymentRecoveryReportResponse {
+                total: 6,
+                pending: 0,
+                notified: 1,
+                retried: 1,
+                recovered: 3,
+                expired: 1,
+                cancelled: 0,
+                recovery_rate: Some(0.75),
+            }
+        );
+        assert_eq!(
+            payment_recovery_report(&[recovery(PaymentRecoveryStatus::Pending)]).recovery_rate,
+            None
+        );
+    }
+}
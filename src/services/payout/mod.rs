@@ -1,7 +1,9 @@
 mod types;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
+use bigdecimal::BigDecimal;
 use chrono::Utc;
 use diesel::connection::AnsiTransactionManager;
 use diesel::pg::Pg;
@@ -9,17 +11,28 @@ use diesel::Connection;
 use failure::{err_msg, Fail};
 use futures::{future, Future};
 use futures_cpupool::CpuPool;
+use itertools::Itertools;
 use r2d2::{ManageConnection, Pool};
-use stq_types::UserId as StqUserId;
+use stq_types::{StoreId as StqStoreId, UserId as StqUserId};
 use validator::{ValidationError, ValidationErrors};
 
 use client::payments::{self, PaymentsClient};
-use controller::responses::BalancesResponse;
+use client::stores::{CurrencyExchangeInfo, StoresClient};
+use config::FeatureFlags as FeatureFlagsConfig;
+use config::PayoutRestrictions as PayoutRestrictionsConfig;
+use config::WalletVerification as WalletVerificationConfig;
+use controller::responses::{BalancesResponse, CurrencyBalanceOverviewResponse, StoreBalanceOverviewResponse, StqFiatEstimateResponse};
 use models::order_v2::{OrderId, OrderPaymentKind, RawOrder, StoreId};
 use models::*;
-use repos::ReposFactory;
+use repos::error::ErrorKind as RepoErrorKind;
+use repos::{OrdersRepo, PayoutsRepo, ReposFactory, UserWalletsRepo};
+use services::feature_flag::is_feature_enabled;
+use services::payout_restriction::{check_store_payout_restriction, PayoutCountryRepos};
+use services::payout_statement::{generate_payout_statement, payout_statement_ids, PayoutStatementRepos};
+use services::store_balance::{check_store_balance, store_balance_deficits, StoreBalanceRepos};
 use services::types::spawn_on_pool;
-use services::ErrorKind;
+use services::user_wallet::validate_payout_wallet_verified;
+use services::{ErrorContext, ErrorKind};
 
 use super::types::{ServiceFutureV2, ServiceResultV2};
 
@@ -27,6 +40,7 @@ pub use self::types::*;
 
 pub trait PayoutService {
     fn get_balance(&self, store_id: StoreId) -> ServiceFutureV2<BalancesResponse>;
+    fn get_balance_overview(&self, store_id: StoreId) -> ServiceFutureV2<StoreBalanceOverviewResponse>;
     fn calculate_payout(&self, payload: CalculatePayoutPayload) -> ServiceFutureV2<CalculatedPayoutOutput>;
     fn get_payout(&self, payout_id: PayoutId) -> ServiceFutureV2<Option<PayoutOutput>>;
     fn get_payouts_by_order_ids(&self, order_ids: GetPayoutsPayload) -> ServiceFutureV2<PayoutsByOrderIdsOutput>;
@@ -45,6 +59,10 @@ pub struct PayoutServiceImpl<
     pub repo_factory: F,
     pub user_id: Option<StqUserId>,
     pub payments_client: Option<PC>,
+    pub stores_client: Arc<dyn StoresClient>,
+    pub wallet_verification: WalletVerificationConfig,
+    pub feature_flags: FeatureFlagsConfig,
+    pub payout_restrictions: PayoutRestrictionsConfig,
 }
 
 impl<
@@ -64,22 +82,11 @@ impl<
             let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
             let payouts_repo = repo_factory.create_payouts_repo(&conn, user_id);
 
-            let orders_for_payout = orders_repo
-                .get_orders_for_payout(store_id.clone(), None)
-                .map_err(ectx!(try convert => store_id))?;
-
-            let order_ids_without_payout = {
-                let order_ids = orders_for_payout.iter().map(|o| o.id).collect::<Vec<_>>();
+            let unpaid_orders = get_unpaid_orders(&*orders_repo, &*payouts_repo, store_id, None)?;
+            let payouts = get_store_payouts(&*orders_repo, &*payouts_repo, &[store_id])?;
 
-                payouts_repo
-                    .get_by_order_ids(&order_ids)
-                    .map(|p| p.order_ids_without_payout)
-                    .map_err(ectx!(try convert => order_ids))
-            }?;
-
-            orders_for_payout
+            let gross_amounts = unpaid_orders
                 .into_iter()
-                .filter(|order| order_ids_without_payout.contains(&order.id))
                 .try_fold(
                     HashMap::new(),
                     |mut hash_map,
@@ -97,20 +104,67 @@ impl<
                 )
                 .ok_or({
                     let e = err_msg("Overflow while calculating the gross amount of a payout");
-                    ectx!(err e, ErrorKind::Internal)
-                })
-                .map(|hash| {
-                    BalancesResponse::new(
-                        hash.into_iter()
-                            .map(|(currency, gross_amount)| (currency.into(), gross_amount.to_super_unit(currency)))
-                            .collect(),
-                    )
-                })
+                    ectx!(try err e, ErrorKind::Internal)
+                })?;
+
+            let currencies = gross_amounts
+                .keys()
+                .cloned()
+                .chain(payouts.iter().map(Payout::currency))
+                .collect::<HashSet<_>>();
+
+            let mut balances = HashMap::new();
+            for currency in currencies {
+                let gross_amount = gross_amounts.get(&currency).cloned().unwrap_or(Amount::zero());
+                let outstanding_fees = outstanding_balance_fees(&payouts, currency)?;
+
+                if gross_amount == Amount::zero() && outstanding_fees == Amount::zero() {
+                    continue;
+                }
+
+                // fees exceeding the balance are deducted from the payout of future orders
+                let balance = gross_amount.checked_sub(outstanding_fees).unwrap_or(Amount::zero());
+                balances.insert(currency.into(), balance.to_super_unit(currency));
+            }
+
+            Ok(BalancesResponse::new(balances))
         });
 
         Box::new(fut)
     }
 
+    fn get_balance_overview(&self, store_id: StoreId) -> ServiceFutureV2<StoreBalanceOverviewResponse> {
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id.clone();
+
+        let bucket_amounts = spawn_on_pool(db_pool.clone(), cpu_pool.clone(), move |conn| {
+            let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
+            orders_repo.get_store_balance_overview(store_id).map_err(ectx!(convert => store_id))
+        });
+
+        // the overview is returned without the fiat estimate when the exchange rates are unavailable
+        let currency_exchange_info =
+            self.stores_client
+                .get_currency_exchange_info()
+                .map_err(ectx!(convert))
+                .then(|res: ServiceResultV2<CurrencyExchangeInfo>| {
+                    if let Err(ref e) = res {
+                        warn!("Failed to get currency exchange info for the balance overview: {}", e);
+                    }
+                    Ok(res.ok())
+                });
+
+        let fut = bucket_amounts
+            .join(currency_exchange_info)
+            .and_then(move |(bucket_amounts, currency_exchange_info)| {
+                store_balance_overview(store_id, bucket_amounts, currency_exchange_info.as_ref())
+            });
+
+        Box::new(fut)
+    }
+
     fn calculate_payout(&self, payload: CalculatePayoutPayload) -> ServiceFutureV2<CalculatedPayoutOutput> {
         let db_pool = self.db_pool.clone();
         let cpu_pool = self.cpu_pool.clone();
@@ -128,31 +182,28 @@ impl<
             wallet_address,
         } = payload;
 
+        let payout_wallet_address = wallet_address.clone();
         let fut = spawn_on_pool(db_pool.clone(), cpu_pool.clone(), move |conn| {
             let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
             let payouts_repo = repo_factory.create_payouts_repo(&conn, user_id);
+            let user_wallets_repo = repo_factory.create_user_wallets_repo(&conn, user_id);
 
-            let orders_for_payout = orders_repo
-                .get_orders_for_payout(store_id.clone(), Some(currency.clone().into()))
-                .map_err(ectx!(try convert => store_id, currency))?;
-
-            let order_ids_without_payout = {
-                let order_ids = orders_for_payout.iter().map(|o| o.id).collect::<Vec<_>>();
+            if let Some(user_id) = user_id {
+                validate_payout_wallet(&*user_wallets_repo, UserId::new(user_id.0), currency, &payout_wallet_address)?;
+            }
 
-                payouts_repo
-                    .get_by_order_ids(&order_ids)
-                    .map(|p| p.order_ids_without_payout)
-                    .map_err(ectx!(try convert => order_ids))
-            }?;
+            let unpaid_orders = get_unpaid_orders(&*orders_repo, &*payouts_repo, store_id, Some(currency.into()))?;
+            let payouts = get_store_payouts(&*orders_repo, &*payouts_repo, &[store_id])?;
+            let carried_fee = outstanding_balance_fees(&payouts, currency.into())?;
 
-            orders_for_payout
+            unpaid_orders
                 .into_iter()
-                .filter(|order| order_ids_without_payout.contains(&order.id))
                 .try_fold(
                     CalculatedPayoutExcludingFees {
                         order_ids: Vec::default(),
                         currency,
                         gross_amount: Amount::zero(),
+                        carried_fee,
                     },
                     |mut payout, RawOrder { id, total_amount, .. }| {
                         payout.order_ids.push(id);
@@ -170,6 +221,7 @@ impl<
                 order_ids,
                 currency,
                 gross_amount,
+                carried_fee,
             } = calculated_payout_excluding_fees;
 
             let input = payments::GetFees {
@@ -183,6 +235,7 @@ impl<
                     order_ids,
                     currency,
                     gross_amount: gross_amount.to_super_unit(currency.into()),
+                    carried_fee: carried_fee.to_super_unit(currency.into()),
                     blockchain_fee_options: fees
                         .into_iter()
                         .map(|fee| BlockchainFeeOption::from_payments_fee(currency, fee))
@@ -206,10 +259,12 @@ impl<
 
         spawn_on_pool(db_pool.clone(), cpu_pool.clone(), move |conn| {
             let payouts_repo = repo_factory.create_payouts_repo(&conn, user_id);
-            payouts_repo
-                .get(payout_id)
-                .map_err(ectx!(convert => payout_id))
-                .map(|payout| payout.map(PayoutOutput::from))
+            let payout_statements_repo = repo_factory.create_payout_statements_repo(&conn, user_id);
+
+            let payout = payouts_repo.get(payout_id).map_err(ectx!(try convert => payout_id))?;
+            let statement_ids = payout_statement_ids(&*payout_statements_repo, &[payout_id])?;
+
+            Ok(payout.map(|payout| PayoutOutput::from(payout).with_statement_ids(&statement_ids)))
         })
     }
 
@@ -221,10 +276,15 @@ impl<
 
         spawn_on_pool(db_pool.clone(), cpu_pool.clone(), move |conn| {
             let payouts_repo = repo_factory.create_payouts_repo(&conn, user_id);
-            payouts_repo
+            let payout_statements_repo = repo_factory.create_payout_statements_repo(&conn, user_id);
+
+            let payouts = payouts_repo
                 .get_by_order_ids(&payload.order_ids)
                 .map(PayoutsByOrderIdsOutput::from)
-                .map_err(ectx!(convert => payload.order_ids.to_vec()))
+                .map_err(ectx!(try convert => payload.order_ids.to_vec()))?;
+            let statement_ids = payout_statement_ids(&*payout_statements_repo, &payouts.payout_ids())?;
+
+            Ok(payouts.with_statement_ids(&statement_ids))
         })
     }
 
@@ -237,21 +297,22 @@ impl<
         spawn_on_pool(db_pool.clone(), cpu_pool.clone(), move |conn| {
             let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
             let payouts_repo = repo_factory.create_payouts_repo(&conn, user_id);
+            let payout_statements_repo = repo_factory.create_payout_statements_repo(&conn, user_id);
 
             let order_ids = orders_repo
                 .get_order_ids_by_store_id(store_id.clone())
                 .map_err(ectx!(try convert => store_id))?;
 
-            payouts_repo
+            let payouts_by_order_ids = payouts_repo
                 .get_by_order_ids(&order_ids)
-                .map_err(ectx!(convert => order_ids.to_vec()))
-                .map(|payouts| {
-                    let payouts_by_order_ids = PayoutsByOrderIdsOutput::from(payouts);
-                    PayoutsByStoreIdOutput {
-                        store_id,
-                        payouts_by_order_ids,
-                    }
-                })
+                .map(PayoutsByOrderIdsOutput::from)
+                .map_err(ectx!(try convert => order_ids.to_vec()))?;
+            let statement_ids = payout_statement_ids(&*payout_statements_repo, &payouts_by_order_ids.payout_ids())?;
+
+            Ok(PayoutsByStoreIdOutput {
+                store_id,
+                payouts_by_order_ids: payouts_by_order_ids.with_statement_ids(&statement_ids),
+            })
         })
     }
 
@@ -260,6 +321,9 @@ impl<
         let cpu_pool = self.cpu_pool.clone();
         let repo_factory = self.repo_factory.clone();
         let user_id = self.user_id.clone();
+        let wallet_verification = self.wallet_verification.clone();
+        let feature_flags = self.feature_flags.clone();
+        let payout_restrictions = self.payout_restrictions.clone();
 
         let user_id = match user_id {
             None => return Box::new(future::err(ErrorKind::Forbidden.into())),
@@ -274,106 +338,588 @@ impl<
                     wallet_address,
                     blockchain_fee,
                 }),
+            fee_payer,
+            request_id,
         } = payload;
 
-        let blockchain_fee = Amount::from_super_unit(wallet_currency.into(), blockchain_fee);
+        let blockchain_fee = match blockchain_fee {
+            None => None,
+            Some(blockchain_fee) => match Amount::checked_from_super_unit(wallet_currency.into(), blockchain_fee.clone()) {
+                Some(blockchain_fee) => Some(blockchain_fee),
+                None => {
+                    let mut errors = ValidationErrors::new();
+                    let mut error = ValidationError::new("out_of_range");
+                    error.message = Some("Blockchain fee must be a non-negative amount".into());
+                    error.add_param("value".into(), &blockchain_fee);
+                    errors.add("blockchain_fee", error);
 
-        spawn_on_pool(db_pool.clone(), cpu_pool.clone(), move |conn| {
-            let orders_repo = repo_factory.create_orders_repo(&conn, Some(user_id));
-            let payouts_repo = repo_factory.create_payouts_repo(&conn, Some(user_id));
-            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+                    return Box::new(future::err(ErrorKind::from(errors).into()));
+                }
+            },
+        };
+
+        let estimated_fee = match self.payments_client.clone() {
+            None => future::Either::A(future::ok(None)),
+            Some(payments_client) => {
+                let input = payments::GetFees {
+                    currency: wallet_currency,
+                    account_address: wallet_address.clone().into_inner(),
+                };
+
+                future::Either::B(payments_client.get_fees(input.clone()).map_err(ectx!(convert => input)).and_then(
+                    move |payments::FeesResponse { currency: _, fees }| {
+                        match fees.into_iter().map(|fee| fee.value).min() {
+                            None => Ok(None),
+                            Some(value) => Amount::checked_from_super_unit(wallet_currency.into(), value.clone())
+                                .map(Some)
+                                .ok_or_else(|| {
+                                    let e = format_err!("Estimated blockchain fee is out of range: {}", value);
+                                    ectx!(err e, ErrorContext::AmountConversion, ErrorKind::Internal)
+                                }),
+                        }
+                    },
+                ))
+            }
+        };
+
+        let fut = estimated_fee.and_then(move |estimated_fee| {
+            spawn_on_pool(db_pool.clone(), cpu_pool.clone(), move |conn| {
+                let orders_repo = repo_factory.create_orders_repo(&conn, Some(user_id));
+                let payouts_repo = repo_factory.create_payouts_repo(&conn, Some(user_id));
+                let user_wallets_repo = repo_factory.create_user_wallets_repo(&conn, Some(user_id));
+                let wallet_verifications_repo = repo_factory.create_wallet_verifications_repo(&conn, Some(user_id));
+                let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+                let feature_flags_repo = repo_factory.create_feature_flags_repo_with_sys_acl(&conn);
+                let sys_orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+                let sys_payouts_repo = repo_factory.create_payouts_repo_with_sys_acl(&conn);
+                let sys_payout_instructions_repo = repo_factory.create_payout_instructions_repo_with_sys_acl(&conn);
+                let fee_adjustments_repo = repo_factory.create_fee_adjustments_repo_with_sys_acl(&conn);
+                let negative_store_balances_repo = repo_factory.create_negative_store_balances_repo_with_sys_acl(&conn);
+                let payout_statements_repo = repo_factory.create_payout_statements_repo_with_sys_acl(&conn);
+                let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+                let invoices_repo = repo_factory.create_invoices_v2_repo_with_sys_acl(&conn);
+                let order_exchange_rates_repo = repo_factory.create_order_exchange_rates_repo_with_sys_acl(&conn);
+                let payout_restriction_overrides_repo = repo_factory.create_payout_restriction_overrides_repo_with_sys_acl(&conn);
+                let store_billing_type_repo = repo_factory.create_store_billing_type_repo_with_sys_acl(&conn);
+                let international_billing_info_repo = repo_factory.create_international_billing_repo_info_with_sys_acl(&conn);
+
+                // a retried request returns the payout of the first one instead of failing the validation below
+                if let Some(request_id) = request_id {
+                    let payout = find_requested_payout(&*payouts_repo, UserId::new(user_id.0), request_id, &order_ids)?;
+                    if let Some(payout) = payout {
+                        let statement_ids = payout_statement_ids(&*payout_statements_repo, &[payout.id])?;
+                        return Ok(PayoutOutput::from(payout).with_statement_ids(&statement_ids));
+                    }
+                }
+
+                validate_payout_wallet(&*user_wallets_repo, UserId::new(user_id.0), wallet_currency, &wallet_address)?;
 
-            let order_ids_clone = order_ids.clone();
-            let orders = orders_repo
-                .get_many(&order_ids_clone)
-                .map_err(ectx!(try convert => order_ids_clone))?;
+                let blockchain_fee = match (blockchain_fee, estimated_fee) {
+                    (Some(blockchain_fee), Some(estimated_fee)) if blockchain_fee < estimated_fee => {
+                        let mut errors = ValidationErrors::new();
+                        let mut error = ValidationError::new("fee_below_estimate");
+                        error.message = Some("Blockchain fee is lower than the cheapest estimated fee".into());
+                        error.add_param("estimated_fee".into(), &estimated_fee.to_super_unit(wallet_currency.into()));
+                        errors.add("blockchain_fee", error);
 
-            if orders.len() != order_ids.len() {
-                let missing_ids = order_ids
+                        return Err(ErrorKind::from(errors).into());
+                    }
+                    (Some(blockchain_fee), _) => blockchain_fee,
+                    (None, Some(estimated_fee)) => estimated_fee,
+                    (None, None) => {
+                        let mut errors = ValidationErrors::new();
+                        let mut error = ValidationError::new("required");
+                        error.message = Some("Blockchain fee could not be estimated and has to be provided".into());
+                        errors.add("blockchain_fee", error);
+
+                        return Err(ErrorKind::from(errors).into());
+                    }
+                };
+
+                let order_ids_clone = order_ids.clone();
+                let orders = orders_repo
+                    .get_many(&order_ids_clone)
+                    .map_err(ectx!(try convert => order_ids_clone))?;
+
+                if orders.len() != order_ids.len() {
+                    let missing_ids = order_ids
+                        .iter()
+                        .filter(|order_id| orders.iter().all(|order| order.id != **order_id))
+                        .map(OrderId::to_string)
+                        .collect::<Vec<_>>();
+
+                    let mut errors = ValidationErrors::new();
+                    let mut error = ValidationError::new("missing_orders");
+                    error.message = Some(format!("Missing orders with IDs: {}", missing_ids.join(", ")).into());
+                    errors.add("order_ids", error);
+
+                    return Err(ErrorKind::from(errors).into());
+                }
+
+                let store_ids = orders.iter().map(|order| order.store_id).unique().collect::<Vec<_>>();
+
+                let country_repos = PayoutCountryRepos {
+                    store_billing_type_repo: &*store_billing_type_repo,
+                    international_billing_info_repo: &*international_billing_info_repo,
+                };
+                for store_id in &store_ids {
+                    check_store_payout_restriction(
+                        &payout_restrictions,
+                        &*payout_restriction_overrides_repo,
+                        &country_repos,
+                        StqStoreId(store_id.inner()),
+                    )?;
+                }
+
+                let raw_orders = orders.clone();
+                let OrdersForPayout { currency, orders } = validate_orders_for_payout(orders)?;
+                if wallet_currency != currency {
+                    let mut errors = ValidationErrors::new();
+                    let mut error = ValidationError::new("currency_mismatch");
+                    error.message = Some(format!("Currency of the orders differs from the wallet currency").into());
+                    error.add_param("orders_currency".into(), &currency);
+                    error.add_param("wallet_currency".into(), &wallet_currency);
+                    errors.add("wallet_currency", error);
+
+                    return Err(ErrorKind::from(errors).into());
+                }
+
+                let PayoutsByOrderIds {
+                    payouts,
+                    order_ids_without_payout: _,
+                } = payouts_repo.get_by_order_ids(&order_ids).map_err(ectx!(try convert))?;
+
+                if !payouts.is_empty() {
+                    let order_ids = payouts.keys().cloned().collect::<Vec<_>>();
+
+                    let mut errors = ValidationErrors::new();
+                    let mut error = ValidationError::new("payouts_exist");
+                    error.message = Some("Payouts already exist for some orders".into());
+                    error.add_param("payouts".into(), &order_ids);
+                    errors.add("order_ids", error);
+
+                    return Err(ErrorKind::from(errors).into());
+                }
+
+                let gross_amount = orders
                     .iter()
-                    .filter(|order_id| orders.iter().all(|order| order.id != **order_id))
-                    .map(OrderId::to_string)
-                    .collect::<Vec<_>>();
+                    .map(|o| o.total_amount)
+                    .try_fold(Amount::new(0), |acc, next| acc.checked_add(next))
+                    .ok_or(ErrorKind::Internal)?;
 
-                let mut errors = ValidationErrors::new();
-                let mut error = ValidationError::new("missing_orders");
-                error.message = Some(format!("Missing orders with IDs: {}", missing_ids.join(", ")).into());
-                errors.add("order_ids", error);
+                let store_payouts = get_store_payouts(&*orders_repo, &*payouts_repo, &store_ids)?;
+                let carried_fee = outstanding_balance_fees(&store_payouts, currency.into())?;
 
-                return Err(ErrorKind::from(errors).into());
-            }
+                let net_amount = match fee_payer {
+                    PayoutFeePayer::Payout => gross_amount
+                        .checked_sub(blockchain_fee)
+                        .and_then(|amount| amount.checked_sub(carried_fee)),
+                    PayoutFeePayer::Balance => {
+                        validate_balance_covers_fee(&*orders_repo, &*payouts_repo, &store_ids, &order_ids, currency, blockchain_fee)?;
+                        gross_amount.checked_sub(carried_fee)
+                    }
+                }
+                .ok_or({
+                    let mut errors = ValidationErrors::new();
+                    let mut error = ValidationError::new("payout_lt_fee");
+                    error.message = Some("Payout is less than the fees deducted from it".into());
+                    error.add_param("payouts".into(), &order_ids);
+                    error.add_param("carried_fee".into(), &carried_fee.to_super_unit(currency.into()));
+                    errors.add("blockchain_fee", error);
 
-            let OrdersForPayout { currency, orders } = validate_orders_for_payout(orders)?;
-            if wallet_currency != currency {
-                let mut errors = ValidationErrors::new();
-                let mut error = ValidationError::new("currency_mismatch");
-                error.message = Some(format!("Currency of the orders differs from the wallet currency").into());
-                error.add_param("orders_currency".into(), &currency);
-                error.add_param("wallet_currency".into(), &wallet_currency);
-                errors.add("wallet_currency", error);
+                    ErrorKind::from(errors)
+                })?;
 
-                return Err(ErrorKind::from(errors).into());
-            }
+                let mut wallet_verification_required = false;
+                for store_id in &store_ids {
+                    let store_id = StqStoreId(store_id.inner());
+                    if is_feature_enabled(&*feature_flags_repo, &feature_flags, Feature::PayoutWalletVerification, store_id)? {
+                        wallet_verification_required = true;
+                        break;
+                    }
+                }
 
-            let PayoutsByOrderIds {
-                payouts,
-                order_ids_without_payout: _,
-            } = payouts_repo.get_by_order_ids(&order_ids).map_err(ectx!(try convert))?;
+                if wallet_verification_required {
+                    validate_payout_wallet_verified(
+                        &wallet_verification,
+                        &*user_wallets_repo,
+                        &*wallet_verifications_repo,
+                        UserId::new(user_id.0),
+                        wallet_currency,
+                        &wallet_address,
+                        net_amount,
+                    )?;
+                }
 
-            if !payouts.is_empty() {
-                let order_ids = payouts.keys().cloned().collect::<Vec<_>>();
+                let payout = Payout {
+                    id: PayoutId::generate(),
+                    gross_amount,
+                    net_amount,
+                    target: PayoutTarget::CryptoWallet(CryptoWalletPayoutTarget {
+                        currency,
+                        wallet_address,
+                        blockchain_fee,
+                    }),
+                    user_id: UserId::new(user_id.clone().0),
+                    status: PayoutStatus::Processing {
+                        initiated_at: Utc::now().naive_utc(),
+                    },
+                    order_ids,
+                    fee_payer,
+                    carried_fee,
+                    request_id,
+                };
 
-                let mut errors = ValidationErrors::new();
-                let mut error = ValidationError::new("payouts_exist");
-                error.message = Some("Payouts already exist for some orders".into());
-                error.add_param("payouts".into(), &order_ids);
-                errors.add("order_ids", error);
+                let store_balance_repos = StoreBalanceRepos {
+                    orders_repo: &*sys_orders_repo,
+                    payouts_repo: &*sys_payouts_repo,
+                    payout_instructions_repo: &*sys_payout_instructions_repo,
+                    fee_adjustments_repo: &*fee_adjustments_repo,
+                    negative_store_balances_repo: &*negative_store_balances_repo,
+                    event_store_repo: &*event_store_repo,
+                };
+                for store_id in &store_ids {
+                    validate_store_balance_for_payout(&store_balance_repos, *store_id, &payout)?;
+                }
 
-                return Err(ErrorKind::from(errors).into());
-            }
+                let payout_statement_repos = PayoutStatementRepos {
+                    payout_statements_repo: &*payout_statements_repo,
+                    fees_repo: &*fees_repo,
+                    invoices_repo: &*invoices_repo,
+                    order_exchange_rates_repo: &*order_exchange_rates_repo,
+                };
 
-            let gross_amount = orders
-                .iter()
-                .map(|o| o.total_amount)
-                .try_fold(Amount::new(0), |acc, next| acc.checked_add(next))
-                .ok_or(ErrorKind::Internal)?;
+                conn.transaction(move || {
+                    let payout_initiated_event = Event::new(EventPayload::PayoutInitiated { payout_id: payout.id });
+                    event_store_repo
+                        .add_event(payout_initiated_event.clone())
+                        .map_err(ectx!(try convert => payout_initiated_event))?;
 
-            let net_amount = gross_amount.checked_sub(blockchain_fee).ok_or({
-                let mut errors = ValidationErrors::new();
-                let mut error = ValidationError::new("payout_lt_fee");
-                error.message = Some("Payout is less than the blockchain fee".into());
-                error.add_param("payouts".into(), &order_ids);
-                errors.add("blockchain_fee", error);
+                    let payout = create_payout(&*payouts_repo, payout)?;
+                    let payout_statement = generate_payout_statement(&payout_statement_repos, &payout, &raw_orders)?;
 
-                ErrorKind::from(errors)
-            })?;
+                    Ok(PayoutOutput {
+                        statement_id: Some(payout_statement.id),
+                        ..PayoutOutput::from(payout)
+                    })
+                })
+            })
+        });
 
-            let payout = Payout {
-                id: PayoutId::generate(),
-                gross_amount,
-                net_amount,
-                target: PayoutTarget::CryptoWallet(CryptoWalletPayoutTarget {
-                    currency,
-                    wallet_address,
-                    blockchain_fee,
-                }),
-                user_id: UserId::new(user_id.clone().0),
-                status: PayoutStatus::Processing {
-                    initiated_at: Utc::now().naive_utc(),
-                },
-                order_ids,
-            };
+        Box::new(fut)
+    }
+}
+
+#[derive(Default)]
+struct CurrencyBucketAmounts {
+    unpaid_invoices: Amount,
+    paid_not_eligible: Amount,
+    eligible: Amount,
+    paid_out: Amount,
+}
+
+impl CurrencyBucketAmounts {
+    fn pending(&self) -> Option<Amount> {
+        self.unpaid_invoices
+            .checked_add(self.paid_not_eligible)
+            .and_then(|amount| amount.checked_add(self.eligible))
+    }
+}
+
+/// Sums the order amounts per currency and estimates the fiat value of the STQ that hasn't been paid out yet
+fn store_balance_overview(
+    store_id: StoreId,
+    bucket_amounts: Vec<StoreBalanceBucketAmount>,
+    currency_exchange_info: Option<&CurrencyExchangeInfo>,
+) -> ServiceResultV2<StoreBalanceOverviewResponse> {
+    let mut amounts_by_currency = HashMap::<Currency, CurrencyBucketAmounts>::new();
+    for bucket_amount in bucket_amounts {
+        let amounts = amounts_by_currency
+            .entry(bucket_amount.currency)
+            .or_insert_with(CurrencyBucketAmounts::default);
+        let total = match bucket_amount.bucket {
+            StoreBalanceBucket::UnpaidInvoices => &mut amounts.unpaid_invoices,
+            StoreBalanceBucket::PaidNotEligible => &mut amounts.paid_not_eligible,
+            StoreBalanceBucket::Eligible => &mut amounts.eligible,
+            StoreBalanceBucket::PaidOut => &mut amounts.paid_out,
+        };
+        *total = total.checked_add(bucket_amount.amount).ok_or({
+            let e = err_msg("Overflow while calculating the balance overview of a store");
+            ectx!(try err e, ErrorKind::Internal)
+        })?;
+    }
+
+    let stq_pending = match amounts_by_currency.get(&Currency::Stq) {
+        None => None,
+        Some(amounts) => Some(amounts.pending().ok_or({
+            let e = err_msg("Overflow while calculating the pending STQ amount of a store");
+            ectx!(try err e, ErrorKind::Internal)
+        })?),
+    };
+
+    let mut stq_fiat_estimates = match (stq_pending, currency_exchange_info.and_then(|info| info.data.get(&Currency::Stq))) {
+        (Some(stq_pending), Some(stq_rates)) => stq_rates
+            .iter()
+            .filter(|(currency, _)| currency.is_fiat())
+            .map(|(currency, exchange_rate)| -> ServiceResultV2<StqFiatEstimateResponse> {
+                let pending_amount = stq_pending.to_super_unit(Currency::Stq) / BigDecimal::from(exchange_rate.0);
+                let pending_amount = Amount::checked_from_super_unit(*currency, pending_amount.clone()).ok_or_else(|| {
+                    let e = format_err!(
+                        "Estimated {} value of the pending STQ is out of range: {}",
+                        currency,
+                        pending_amount
+                    );
+                    ectx!(try err e, ErrorContext::AmountConversion, ErrorKind::Internal)
+                })?;
 
-            let payout_initiated_event = Event::new(EventPayload::PayoutInitiated { payout_id: payout.id });
-            event_store_repo
-                .add_event(payout_initiated_event.clone())
-                .map_err(ectx!(try convert => payout_initiated_event))?;
+                Ok(StqFiatEstimateResponse {
+                    currency: (*currency).into(),
+                    exchange_rate: exchange_rate.0,
+                    pending_amount: pending_amount.to_super_unit(*currency),
+                })
+            })
+            .collect::<ServiceResultV2<Vec<_>>>()?,
+        _ => Vec::new(),
+    };
+    stq_fiat_estimates.sort_by_key(|estimate| estimate.currency.to_string());
 
-            payouts_repo
-                .create(payout.clone())
-                .map(PayoutOutput::from)
-                .map_err(ectx!(convert => payout))
+    let mut currencies = amounts_by_currency
+        .into_iter()
+        .map(|(currency, amounts)| CurrencyBalanceOverviewResponse {
+            currency: currency.into(),
+            unpaid_invoices: amounts.unpaid_invoices.to_super_unit(currency),
+            paid_not_eligible: amounts.paid_not_eligible.to_super_unit(currency),
+            eligible: amounts.eligible.to_super_unit(currency),
+            paid_out: amounts.paid_out.to_super_unit(currency),
         })
+        .collect::<Vec<_>>();
+    currencies.sort_by_key(|overview| overview.currency.to_string());
+
+    Ok(StoreBalanceOverviewResponse {
+        store_id: StqStoreId(store_id.inner()),
+        currencies,
+        stq_fiat_estimates,
+    })
+}
+
+/// Orders of the store that are ready to be paid out and have no payout yet
+pub fn get_unpaid_orders(
+    orders_repo: &OrdersRepo,
+    payouts_repo: &PayoutsRepo,
+    store_id: StoreId,
+    currency: Option<Currency>,
+) -> ServiceResultV2<Vec<RawOrder>> {
+    let orders_for_payout = orders_repo
+        .get_orders_for_payout(store_id, currency)
+        .map_err(ectx!(try convert => store_id, currency))?;
+
+    let order_ids = orders_for_payout.iter().map(|o| o.id).collect::<Vec<_>>();
+    let order_ids_without_payout = payouts_repo
+        .get_by_order_ids(&order_ids)
+        .map(|p| p.order_ids_without_payout)
+        .map_err(ectx!(try convert => order_ids))?;
+
+    Ok(orders_for_payout
+        .into_iter()
+        .filter(|order| order_ids_without_payout.contains(&order.id))
+        .collect())
+}
+
+/// Payouts made for the orders of the stores, each payout listed once
+pub fn get_store_payouts(orders_repo: &OrdersRepo, payouts_repo: &PayoutsRepo, store_ids: &[StoreId]) -> ServiceResultV2<Vec<Payout>> {
+    let mut payouts = HashMap::new();
+
+    for store_id in store_ids {
+        let order_ids = orders_repo
+            .get_order_ids_by_store_id(*store_id)
+            .map_err(ectx!(try convert => store_id))?;
+
+        let PayoutsByOrderIds {
+            payouts: payouts_by_order_id,
+            order_ids_without_payout: _,
+        } = payouts_repo.get_by_order_ids(&order_ids).map_err(ectx!(try convert => order_ids))?;
+
+        for (_, payout) in payouts_by_order_id {
+            payouts.insert(payout.id, payout);
+        }
+    }
+
+    Ok(payouts.into_iter().map(|(_, payout)| payout).collect())
+}
+
+/// Payouts to an address of a wallet the user has deactivated are refused,
+/// the error points to the active wallet of the currency if the user has one
+fn validate_payout_wallet(
+    user_wallets_repo: &UserWalletsRepo,
+    user_id: UserId,
+    currency: TureCurrency,
+    wallet_address: &WalletAddress,
+) -> ServiceResultV2<()> {
+    let wallet_address_clone = wallet_address.clone();
+    let user_wallets = user_wallets_repo
+        .get_by_address(user_id, currency, wallet_address.clone())
+        .map_err(ectx!(try convert => user_id, currency, wallet_address_clone))?;
+
+    // addresses that were never added as wallets are paid out to as before
+    if user_wallets.is_empty() || user_wallets.iter().any(|user_wallet| user_wallet.is_active) {
+        return Ok(());
+    }
+
+    let active_wallet = user_wallets_repo
+        .get_currency_wallets_by_user_id(currency, user_id)
+        .map_err(ectx!(try convert => currency, user_id))?
+        .into_iter()
+        .next();
+
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new("wallet_deactivated");
+    error.message = Some("The wallet has been deactivated, payouts have to be sent to the active wallet of the currency".into());
+    error.add_param("wallet_address".into(), wallet_address);
+    if let Some(active_wallet) = active_wallet {
+        error.add_param("active_wallet_id".into(), &active_wallet.id);
+        error.add_param("active_wallet_address".into(), &active_wallet.address);
+    }
+    errors.add("wallet_address", error);
+
+    Err(ErrorKind::from(errors).into())
+}
+
+/// Fees of the payouts paid from the balance that haven't been deducted from a later payout yet
+pub fn outstanding_balance_fees(payouts: &[Payout], currency: Currency) -> ServiceResultV2<Amount> {
+    let payouts = payouts.iter().filter(|payout| payout.currency() == currency).collect::<Vec<_>>();
+
+    let charged = payouts
+        .iter()
+        .filter(|payout| payout.fee_payer == PayoutFeePayer::Balance)
+        .try_fold(Amount::zero(), |acc, payout| acc.checked_add(payout.fee()));
+    let deducted = payouts
+        .iter()
+        .try_fold(Amount::zero(), |acc, payout| acc.checked_add(payout.carried_fee));
+
+    match (charged, deducted) {
+        (Some(charged), Some(deducted)) => Ok(charged.checked_sub(deducted).unwrap_or(Amount::zero())),
+        _ => {
+            let e = err_msg("Overflow while calculating the outstanding fees of payouts");
+            Err(ectx!(err e, ErrorKind::Internal))
+        }
+    }
+}
+
+/// Payouts of a store with a negative balance are blocked, and a payout may not take the balance below zero
+fn validate_store_balance_for_payout(repos: &StoreBalanceRepos, store_id: StoreId, payout: &Payout) -> ServiceResultV2<()> {
+    let mut errors = ValidationErrors::new();
+
+    let negative_store_balances = check_store_balance(repos, store_id)?;
+    if !negative_store_balances.is_empty() {
+        let negative_store_balance_ids = negative_store_balances.iter().map(|balance| balance.id).collect::<Vec<_>>();
+
+        let mut error = ValidationError::new("negative_balance");
+        error.message = Some("Payouts of the store are blocked until its negative balance is covered".into());
+        error.add_param("store_id".into(), &store_id);
+        error.add_param("negative_store_balance_ids".into(), &negative_store_balance_ids);
+        errors.add("order_ids", error);
+
+        return Err(ErrorKind::from(errors).into());
+    }
+
+    let deficits = store_balance_deficits(repos, store_id, &payout.order_ids, Some(payout))?;
+    if !deficits.is_empty() {
+        let mut error = ValidationError::new("negative_balance");
+        error.message = Some("Payout would leave the balance of the store negative".into());
+        error.add_param("store_id".into(), &store_id);
+        errors.add("order_ids", error);
+
+        return Err(ErrorKind::from(errors).into());
+    }
+
+    Ok(())
+}
+
+/// A fee paid from the balance has to be covered by the orders left unpaid after the payout
+fn validate_balance_covers_fee(
+    orders_repo: &OrdersRepo,
+    payouts_repo: &PayoutsRepo,
+    store_ids: &[StoreId],
+    paid_out_order_ids: &[OrderId],
+    currency: TureCurrency,
+    blockchain_fee: Amount,
+) -> ServiceResultV2<()> {
+    let mut errors = ValidationErrors::new();
+
+    if store_ids.len() != 1 {
+        let mut error = ValidationError::new("multiple_stores");
+        error.message = Some("Fees can be paid from the balance of a single store only".into());
+        errors.add("fee_payer", error);
+
+        return Err(ErrorKind::from(errors).into());
+    }
+
+    let store_id = store_ids[0];
+
+    let remaining_balance = get_unpaid_orders(orders_repo, payouts_repo, store_id, Some(currency.into()))?
+        .into_iter()
+        .filter(|order| !paid_out_order_ids.contains(&order.id))
+        .try_fold(Amount::zero(), |acc, order| acc.checked_add(order.total_amount))
+        .ok_or({
+            let e = err_msg("Overflow while calculating the remaining balance of a store");
+            ectx!(try err e, ErrorKind::Internal)
+        })?;
+
+    if remaining_balance < blockchain_fee {
+        let mut error = ValidationError::new("balance_lt_fee");
+        error.message = Some("Remaining balance is less than the blockchain fee".into());
+        error.add_param("remaining_balance".into(), &remaining_balance.to_super_unit(currency.into()));
+        errors.add("fee_payer", error);
+
+        return Err(ErrorKind::from(errors).into());
+    }
+
+    Ok(())
+}
+
+/// Payout created by an earlier request with the same ID, the request can't be reused for other orders
+fn find_requested_payout(
+    payouts_repo: &PayoutsRepo,
+    user_id: UserId,
+    request_id: PayoutRequestId,
+    order_ids: &[OrderId],
+) -> ServiceResultV2<Option<Payout>> {
+    let payout = payouts_repo
+        .get_by_request_id(user_id, request_id)
+        .map_err(ectx!(try convert => user_id, request_id))?;
+
+    match payout {
+        Some(ref payout) if order_set_key(&payout.order_ids) != order_set_key(order_ids) => {
+            let mut errors = ValidationErrors::new();
+            let mut error = ValidationError::new("request_id_reused");
+            error.message = Some("Request ID has already been used for a payout of other orders".into());
+            error.add_param("payout_id".into(), &payout.id);
+            errors.add("request_id", error);
+
+            Err(ErrorKind::from(errors).into())
+        }
+        payout => Ok(payout),
+    }
+}
+
+/// Creates the payout, a concurrent request for any of the same orders or with the same request ID violates the unique constraints
+fn create_payout(payouts_repo: &PayoutsRepo, payout: Payout) -> ServiceResultV2<Payout> {
+    let e = match payouts_repo.create(payout.clone()) {
+        Ok(payout) => return Ok(payout),
+        Err(e) => e,
+    };
+
+    match e.kind() {
+        RepoErrorKind::Constraints(_) => {
+            let mut errors = ValidationErrors::new();
+            let mut error = ValidationError::new("payouts_exist");
+            error.message = Some("Payout for these orders is already being created".into());
+            error.add_param("payouts".into(), &payout.order_ids);
+            errors.add("order_ids", error);
+
+            Err(ErrorKind::from(errors).into())
+        }
+        _ => Err(ectx!(convert err e => payout)),
     }
 }
 
@@ -432,3 +978,98 @@ fn validate_orders_for_payout(orders: Vec<RawOrder>) -> ServiceResultV2<OrdersFo
             .collect(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::BigDecimal;
+    use chrono::NaiveDate;
+    use stq_static_resources::Currency as StqCurrency;
+    use stq_types::{CurrencyExchangeId, ExchangeRate};
+    use uuid::Uuid;
+
+    use client::stores::{CurrencyExchangeData, CurrencyExchangeInfo, ExchangeRates};
+    use models::order_v2::StoreId;
+    use models::*;
+
+    use super::{outstanding_balance_fees, store_balance_overview};
+
+    fn payout(currency: TureCurrency, fee_payer: PayoutFeePayer, blockchain_fee: u128, carried_fee: u128) -> Payout {
+        Payout {
+            id: PayoutId::generate(),
+            gross_amount: Amount::new(1000),
+            net_amount: Amount::new(1000),
+            target: PayoutTarget::CryptoWallet(CryptoWalletPayoutTarget {
+                currency,
+                wallet_address: WalletAddress::new("0x0".to_string()),
+                blockchain_fee: Amount::new(blockchain_fee),
+            }),
+            user_id: UserId::new(1),
+            status: PayoutStatus::Processing {
+                initiated_at: NaiveDate::from_ymd(2019, 3, 21).and_hms(9, 0, 0),
+            },
+            order_ids: vec![],
+            fee_payer,
+            carried_fee: Amount::new(carried_fee),
+            request_id: None,
+        }
+    }
+
+    #[test]
+    fn outstanding_balance_fees_subtracts_fees_carried_to_later_payouts() {
+        let payouts = vec![
+            payout(TureCurrency::Stq, PayoutFeePayer::Balance, 10, 0),
+            payout(TureCurrency::Stq, PayoutFeePayer::Payout, 5, 10),
+            payout(TureCurrency::Stq, PayoutFeePayer::Balance, 7, 0),
+            payout(TureCurrency::Eth, PayoutFeePayer::Balance, 3, 0),
+        ];
+
+        assert_eq!(outstanding_balance_fees(&payouts, Currency::Stq).unwrap(), Amount::new(7));
+        assert_eq!(outstanding_balance_fees(&payouts, Currency::Eth).unwrap(), Amount::new(3));
+        assert_eq!(outstanding_balance_fees(&payouts, Currency::Btc).unwrap(), Amount::zero());
+    }
+
+    fn bucket_amount(currency: Currency, bucket: StoreBalanceBucket, amount: u64) -> StoreBalanceBucketAmount {
+        StoreBalanceBucketAmount {
+            currency,
+            bucket,
+            amount: Amount::from_super_unit(currency, BigDecimal::from(amount)),
+            orders_count: 1,
+        }
+    }
+
+    #[test]
+    fn store_balance_overview_estimates_pending_stq_in_fiat() {
+        let bucket_amounts = vec![
+            bucket_amount(Currency::Stq, StoreBalanceBucket::UnpaidInvoices, 100),
+            bucket_amount(Currency::Stq, StoreBalanceBucket::Eligible, 50),
+            bucket_amount(Currency::Stq, StoreBalanceBucket::PaidOut, 1000),
+            bucket_amount(Currency::Eth, StoreBalanceBucket::PaidNotEligible, 2),
+        ];
+
+        let mut stq_rates = ExchangeRates::new();
+        stq_rates.insert(Currency::Usd, ExchangeRate(50.0));
+        stq_rates.insert(Currency::Eth, ExchangeRate(10000.0));
+        let mut data = CurrencyExchangeData::new();
+        data.insert(Currency::Stq, stq_rates);
+        let currency_exchange_info = CurrencyExchangeInfo {
+            id: CurrencyExchangeId(Uuid::new_v4()),
+            data,
+        };
+
+        let overview = store_balance_overview(StoreId::new(1), bucket_amounts, Some(&currency_exchange_info)).unwrap();
+
+        assert_eq!(overview.currencies.len(), 2);
+        let stq = overview.currencies.iter().find(|c| c.currency == StqCurrency::Stq).unwrap();
+        assert_eq!(stq.unpaid_invoices, BigDecimal::from(100));
+        assert_eq!(stq.eligible, BigDecimal::from(50));
+        assert_eq!(stq.paid_out, BigDecimal::from(1000));
+
+        assert_eq!(overview.stq_fiat_estimates.len(), 1);
+        assert_eq!(overview.stq_fiat_estimates[0].currency, StqCurrency::Usd);
+        assert_eq!(overview.stq_fiat_estimates[0].pending_amount, BigDecimal::from(3));
+
+        let overview = store_balance_overview(StoreId::new(1), vec![], None).unwrap();
+        assert!(overview.currencies.is_empty());
+        assert!(overview.stq_fiat_estimates.is_empty());
+    }
+}
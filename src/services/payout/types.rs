@@ -1,4 +1,7 @@
+use std::collections::HashMap;
+
 use bigdecimal::BigDecimal;
+use itertools::Itertools;
 
 use client::payments;
 use models::order_v2::{OrderId, StoreId};
@@ -16,6 +19,7 @@ pub struct CalculatedPayoutExcludingFees {
     pub order_ids: Vec<OrderId>,
     pub currency: TureCurrency,
     pub gross_amount: Amount,
+    pub carried_fee: Amount,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -23,6 +27,8 @@ pub struct CalculatedPayoutOutput {
     pub order_ids: Vec<OrderId>,
     pub currency: TureCurrency,
     pub gross_amount: BigDecimal,
+    /// Fees of earlier payouts paid from the balance, deducted from the next payout
+    pub carried_fee: BigDecimal,
     pub blockchain_fee_options: Vec<BlockchainFeeOption>,
 }
 
@@ -52,6 +58,11 @@ pub struct GetPayoutsPayload {
 pub struct PayOutToSellerPayload {
     pub order_ids: Vec<OrderId>,
     pub payment_details: PaymentDetails,
+    #[serde(default)]
+    pub fee_payer: PayoutFeePayer,
+    /// Makes the request idempotent, a retry with the same ID returns the payout of the first request
+    #[serde(default)]
+    pub request_id: Option<PayoutRequestId>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -63,23 +74,40 @@ pub enum PaymentDetails {
 pub struct CryptoPaymentDetails {
     pub wallet_currency: TureCurrency,
     pub wallet_address: WalletAddress,
-    pub blockchain_fee: BigDecimal,
+    /// Defaults to the cheapest fee estimated by the Payments gateway
+    pub blockchain_fee: Option<BigDecimal>,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct PayoutOutput {
     pub id: PayoutId,
     pub gross_amount: BigDecimal,
+    pub blockchain_fee: BigDecimal,
+    pub carried_fee: BigDecimal,
     pub net_amount: BigDecimal,
+    pub fee_payer: PayoutFeePayer,
     pub target: PayoutTarget,
     pub user_id: UserId,
     pub status: PayoutStatus,
     pub order_ids: Vec<OrderId>,
+    /// Statement of the payout with its breakdown by order, none for payouts made before statements were introduced
+    pub statement_id: Option<PayoutStatementId>,
+    pub request_id: Option<PayoutRequestId>,
+}
+
+impl PayoutOutput {
+    pub fn with_statement_ids(self, statement_ids: &HashMap<PayoutId, PayoutStatementId>) -> Self {
+        Self {
+            statement_id: statement_ids.get(&self.id).cloned(),
+            ..self
+        }
+    }
 }
 
 impl From<Payout> for PayoutOutput {
     fn from(payout: Payout) -> Self {
         let currency = payout.currency();
+        let blockchain_fee = payout.fee();
 
         let Payout {
             id,
@@ -89,16 +117,24 @@ impl From<Payout> for PayoutOutput {
             user_id,
             status,
             order_ids,
+            fee_payer,
+            carried_fee,
+            request_id,
         } = payout;
 
         Self {
             id,
             gross_amount: gross_amount.to_super_unit(currency),
+            blockchain_fee: blockchain_fee.to_super_unit(currency),
+            carried_fee: carried_fee.to_super_unit(currency),
             net_amount: net_amount.to_super_unit(currency),
+            fee_payer,
             target,
             user_id,
             status,
             order_ids,
+            statement_id: None,
+            request_id,
         }
     }
 }
@@ -137,6 +173,25 @@ impl From<PayoutsByOrderIds> for PayoutsByOrderIdsOutput {
     }
 }
 
+impl PayoutsByOrderIdsOutput {
+    pub fn payout_ids(&self) -> Vec<PayoutId> {
+        self.payouts.iter().map(|payout| payout.payout.id).unique().collect()
+    }
+
+    pub fn with_statement_ids(self, statement_ids: &HashMap<PayoutId, PayoutStatementId>) -> Self {
+        let payouts = self
+            .payouts
+            .into_iter()
+            .map(|PayoutOutputWithOrderId { order_id, payout }| PayoutOutputWithOrderId {
+                order_id,
+                payout: payout.with_statement_ids(statement_ids),
+            })
+            .collect();
+
+        Self { payouts, ..self }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct PayoutsByStoreIdOutput {
     pub store_id: StoreId,
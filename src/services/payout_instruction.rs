@@ -0,0 +1,401 @@
+//! PayoutInstructionsService prepares bank transfer instructions for paying out fiat orders of international stores
+use std::collections::HashSet;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use serde_json;
+use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
+
+use failure::Fail;
+
+use stq_types::{Alpha3, StoreId, UserId};
+
+use super::types::ServiceFutureV2;
+use config::PayoutRestrictions as PayoutRestrictionsConfig;
+use controller::requests::GeneratePayoutInstructionRequest;
+use controller::responses::PayoutInstructionResponse;
+use models::order_v2::{RawOrder, StoreId as StoreIdV2};
+use models::{
+    Amount, Currency, FeeAdjustment, InternationalBillingInfo, InternationalBillingInfoSearch, NewPayoutInstruction, PayoutBankDetails,
+    PayoutBeneficiary, PayoutInstructionDocument, PayoutInstructionId, PayoutInstructionSearch, PayoutRemitter, ProxyCompanyBillingInfo,
+    ProxyCompanyBillingInfoSearch,
+};
+use repos::ReposFactory;
+use services::payout_restriction::check_payout_restriction;
+use services::types::spawn_on_pool;
+use services::{Error, ErrorContext, ErrorKind};
+
+pub trait PayoutInstructionsService {
+    /// Generates an instruction covering all orders of the store awaiting payout in the currency
+    /// that have neither been paid out nor included into an earlier instruction
+    fn generate_payout_instruction(
+        &self,
+        store_id: StoreId,
+        payload: GeneratePayoutInstructionRequest,
+    ) -> ServiceFutureV2<PayoutInstructionResponse>;
+    /// Lists payout instructions of a store, newest first
+    fn get_payout_instructions_by_store(&self, store_id: StoreId) -> ServiceFutureV2<Vec<PayoutInstructionResponse>>;
+    /// Returns a payout instruction with its transfer document
+    fn get_payout_instruction(&self, id: PayoutInstructionId) -> ServiceFutureV2<Option<PayoutInstructionResponse>>;
+}
+
+pub struct PayoutInstructionsServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+    pub payout_restrictions: PayoutRestrictionsConfig,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > PayoutInstructionsService for PayoutInstructionsServiceImpl<T, M, F>
+{
+    fn generate_payout_instruction(
+        &self,
+        store_id: StoreId,
+        payload: GeneratePayoutInstructionRequest,
+    ) -> ServiceFutureV2<PayoutInstructionResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+        let payout_restrictions = self.payout_restrictions.clone();
+
+        let currency = Currency::from(payload.currency);
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let payout_instructions_repo = repo_factory.create_payout_instructions_repo(&conn, user_id);
+            let international_billing_info_repo = repo_factory.create_international_billing_info_repo(&conn, user_id);
+            let proxy_companies_billing_info_repo = repo_factory.create_proxy_companies_billing_info_repo(&conn, user_id);
+            let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
+            let payouts_repo = repo_factory.create_payouts_repo(&conn, user_id);
+            let fee_adjustments_repo = repo_factory.create_fee_adjustments_repo_with_sys_acl(&conn);
+            let payout_restriction_overrides_repo = repo_factory.create_payout_restriction_overrides_repo_with_sys_acl(&conn);
+
+            conn.transaction(move || {
+                let beneficiary = international_billing_info_repo
+                    .get(InternationalBillingInfoSearch::by_store_id(store_id))
+                    .map_err(ectx!(try convert => store_id))?
+                    .ok_or_else(|| {
+                        payout_instruction_validation_error(
+                            "store_id",
+                            "no_international_billing_info",
+                            "Store has no international billing info",
+                        )
+                    })?;
+                check_payout_restriction(
+                    &payout_restrictions,
+                    &*payout_restriction_overrides_repo,
+                    store_id,
+                    Some(beneficiary.country.as_str()),
+                )?;
+
+                // todo find correct proxy company country
+                let remitter = proxy_companies_billing_info_repo
+                    .get(ProxyCompanyBillingInfoSearch::by_country_alpha3(Alpha3("RUS".to_string())))
+                    .map_err(ectx!(try convert))?;
+
+                let orders_for_payout = orders_repo
+                    .get_orders_for_payout(StoreIdV2::new(store_id.0), Some(currency))
+                    .map_err(ectx!(try convert => store_id, currency))?;
+
+                let order_ids_without_payout = {
+                    let order_ids = orders_for_payout.iter().map(|o| o.id).collect::<Vec<_>>();
+
+                    payouts_repo
+                        .get_by_order_ids(&order_ids)
+                        .map(|p| p.order_ids_without_payout)
+                        .map_err(ectx!(try convert => order_ids))
+                }?;
+
+                let mut instructed_order_ids = HashSet::new();
+                for payout_instruction in payout_instructions_repo
+                    .search(PayoutInstructionSearch::by_store_id(store_id))
+                    .map_err(ectx!(try convert => store_id))?
+                {
+                    let order_ids = payout_instruction.order_ids().map_err(|e| ectx!(err e, ErrorKind::Internal))?;
+                    instructed_order_ids.extend(order_ids);
+                }
+
+                let orders = orders_for_payout
+                    .into_iter()
+                    .filter(|order| order_ids_without_payout.contains(&order.id) && !instructed_order_ids.contains(&order.id))
+                    .collect::<Vec<_>>();
+
+                if orders.is_empty() {
+                    return Err(payout_instruction_validation_error(
+                        "currency",
+                        "no_orders_for_payout",
+                        "Store has no orders awaiting payout in this currency",
+                    ));
+                }
+
+                let order_ids = orders.iter().map(|order| order.id).collect::<Vec<_>>();
+                let fee_adjustments = fee_adjustments_repo
+                    .get_by_order_ids(&order_ids)
+                    .map_err(ectx!(try convert => order_ids))?;
+
+                let reference_code = payout_reference_code(store_id, Utc::now().naive_utc());
+                let new_payout_instruction =
+                    create_payout_instruction(store_id, currency, orders, &fee_adjustments, beneficiary, remitter, reference_code)?;
+
+                let payout_instruction = payout_instructions_repo
+                    .create(new_payout_instruction.clone())
+                    .map_err(ectx!(try convert => new_payout_instruction))?;
+
+                PayoutInstructionResponse::try_from_payout_instruction(payout_instruction)
+            })
+        })
+    }
+
+    fn get_payout_instructions_by_store(&self, store_id: StoreId) -> ServiceFutureV2<Vec<PayoutInstructionResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let payout_instructions_repo = repo_factory.create_payout_instructions_repo(&conn, user_id);
+
+            payout_instructions_repo
+                .search(PayoutInstructionSearch::by_store_id(store_id))
+                .map_err(ectx!(try convert => store_id))?
+                .into_iter()
+                .map(PayoutInstructionResponse::try_from_payout_instruction)
+                .collect()
+        })
+    }
+
+    fn get_payout_instruction(&self, id: PayoutInstructionId) -> ServiceFutureV2<Option<PayoutInstructionResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let payout_instructions_repo = repo_factory.create_payout_instructions_repo(&conn, user_id);
+
+            match payout_instructions_repo
+                .get(PayoutInstructionSearch::by_id(id))
+                .map_err(ectx!(try convert => id))?
+            {
+                Some(payout_instruction) => PayoutInstructionResponse::try_from_payout_instruction(payout_instruction).map(Some),
+                None => Ok(None),
+            }
+        })
+    }
+}
+
+/// Reference code the bank transfer is made with, e.g. `PI-42-20190316-1A2B3C4D`
+pub fn payout_reference_code(store_id: StoreId, now: NaiveDateTime) -> String {
+    let suffix = Uuid::new_v4().simple().to_string()[..8].to_uppercase();
+    format!("PI-{}-{}-{}", store_id, now.format("%Y%m%d"), suffix)
+}
+
+/// Builds a transfer instruction paying out the orders less their Stripe fees and the seller share of their refunds
+/// in `fee_adjustments` to the store's bank account
+pub fn create_payout_instruction(
+    store_id: StoreId,
+    currency: Currency,
+    orders: Vec<RawOrder>,
+    fee_adjustments: &[FeeAdjustment],
+    beneficiary: InternationalBillingInfo,
+    remitter: Option<ProxyCompanyBillingInfo>,
+    reference_code: String,
+) -> Result<NewPayoutInstruction, Error> {
+    let mut gross_amount = Amount::zero();
+    let mut stripe_fee = Amount::zero();
+    let mut order_ids = Vec::new();
+    for order in orders {
+        gross_amount = gross_amount
+            .checked_add(order.total_amount)
+            .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+        // orders captured before the fee was recorded and not backfilled yet are paid out in full
+        stripe_fee = stripe_fee
+            .checked_add(order.stripe_fee.unwrap_or_default())
+            .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+        order_ids.push(order.id);
+    }
+    // the refunded line items are not paid to the seller
+    let mut refunded_amount = Amount::zero();
+    for fee_adjustment in fee_adjustments.iter().filter(|adjustment| order_ids.contains(&adjustment.order_id)) {
+        refunded_amount = refunded_amount
+            .checked_add(fee_adjustment.seller_amount)
+            .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+    }
+    let total_amount = gross_amount
+        .checked_sub(stripe_fee)
+        .and_then(|amount| amount.checked_sub(refunded_amount))
+        .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+
+    let document = PayoutInstructionDocument {
+        beneficiary: PayoutBeneficiary {
+            name: beneficiary.name,
+            address: beneficiary.recipient_address,
+            city: beneficiary.city,
+            country: beneficiary.country,
+            bank: PayoutBankDetails {
+                name: beneficiary.bank,
+                address: beneficiary.bank_address,
+                iban: beneficiary.account,
+                swift: beneficiary.swift,
+            },
+        },
+        remitter: remitter.map(|remitter| PayoutRemitter {
+            name: remitter.name,
+            address: remitter.recipient_address,
+            city: remitter.city,
+            country: remitter.country,
+            bank: PayoutBankDetails {
+                name: remitter.bank,
+                address: remitter.bank_address,
+                iban: remitter.account,
+                swift: remitter.swift,
+            },
+        }),
+        account_currency: beneficiary.currency,
+        reference_code: reference_code.clone(),
+        payment_details: format!(
+            "Payout {} of {} {} for {} orders of store {}",
+            reference_code,
+            total_amount.to_super_unit(currency),
+            currency,
+            order_ids.len(),
+            store_id
+        ),
+    };
+
+    let order_ids = serde_json::to_value(order_ids).map_err(|e| ectx!(err e, ErrorKind::Internal))?;
+    let document = serde_json::to_value(document).map_err(|e| ectx!(err e, ErrorKind::Internal))?;
+
+    Ok(NewPayoutInstruction {
+        store_id,
+        currency,
+        total_amount,
+        order_ids,
+        reference_code,
+        document,
+        stripe_fee,
+    })
+}
+
+fn payout_instruction_validation_error(field: &'static str, code: &'static str, message: &'static str) -> Error {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    errors.add(field, error);
+    ectx!(err ErrorContext::PayoutInstruction, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use serde_json;
+    use uuid::Uuid;
+
+    use stq_static_resources::Currency as StqCurrency;
+    use stq_types::{InternationalBillingId, StoreId, SwiftId};
+
+    use models::invoice_v2::InvoiceId;
+    use models::order_v2::{OrderId, RawOrder, StoreId as StoreIdV2};
+    use models::*;
+
+    use super::{create_payout_instruction, payout_reference_code};
+
+    fn order(amount: u128, stripe_fee: Option<u128>) -> RawOrder {
+        let created_at = NaiveDate::from_ymd(2019, 3, 10).and_hms(12, 0, 0);
+        RawOrder {
+            id: OrderId::new(Uuid::new_v4()),
+            seller_currency: Currency::Eur,
+            total_amount: Amount::new(amount),
+            cashback_amount: Amount::new(0),
+            invoice_id: InvoiceId::new(Uuid::new_v4()),
+            created_at,
+            updated_at: created_at,
+            store_id: StoreIdV2::new(42),
+            state: PaymentState::PaymentToSellerNeeded,
+            stripe_fee: stripe_fee.map(Amount::new),
+        }
+    }
+
+    fn billing_info() -> InternationalBillingInfo {
+        InternationalBillingInfo {
+            id: InternationalBillingId(1),
+            store_id: StoreId(42),
+            account: "DE89370400440532013000".to_string(),
+            currency: StqCurrency::EUR,
+            name: "Store GmbH".to_string(),
+            bank: "Commerzbank".to_string(),
+            swift: SwiftId("COBADEFFXXX".to_string()),
+            bank_address: "Kaiserplatz, Frankfurt".to_string(),
+            country: "Germany".to_string(),
+            city: "Berlin".to_string(),
+            recipient_address: "Unter den Linden 1".to_string(),
+        }
+    }
+
+    #[test]
+    fn payout_reference_code_format() {
+        let now = NaiveDate::from_ymd(2019, 3, 16).and_hms(10, 0, 0);
+        let reference_code = payout_reference_code(StoreId(42), now);
+        assert!(reference_code.starts_with("PI-42-20190316-"));
+        assert_eq!(reference_code.len(), "PI-42-20190316-".len() + 8);
+    }
+
+    #[test]
+    fn payout_instruction_totals() {
+        let orders = vec![order(1000, Some(30)), order(2500, None)];
+        let order_ids: Vec<OrderId> = orders.iter().map(|order| order.id).collect();
+        let line_item_refund = FeeAdjustment {
+            id: FeeAdjustmentId::new(1),
+            fee_id: FeeId::new(1),
+            order_id: order_ids[1],
+            currency: Currency::Eur,
+            refund_amount: Amount::new(500),
+            seller_amount: Amount::new(475),
+            fee_amount: Amount::new(25),
+            fee_charge_id: None,
+            created_at: NaiveDate::from_ymd(2019, 3, 12).and_hms(12, 0, 0),
+            charged_currency: None,
+            charged_fee_amount: None,
+        };
+        // a refund of an order paid out otherwise is left out
+        let other_refund = FeeAdjustment {
+            id: FeeAdjustmentId::new(2),
+            order_id: OrderId::new(Uuid::new_v4()),
+            ..line_item_refund.clone()
+        };
+
+        let new_payout_instruction = create_payout_instruction(
+            StoreId(42),
+            Currency::Eur,
+            orders,
+            &[line_item_refund, other_refund],
+            billing_info(),
+            None,
+            "PI-42-20190316-1A2B3C4D".to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(new_payout_instruction.total_amount, Amount::new(2995));
+        assert_eq!(new_payout_instruction.stripe_fee, Amount::new(30));
+        assert_eq!(new_payout_instruction.order_ids, serde_json::to_value(order_ids).unwrap());
+
+        let document: PayoutInstructionDocument = serde_json::from_value(new_payout_instruction.document).unwrap();
+        assert_eq!(document.beneficiary.bank.iban, "DE89370400440532013000");
+        assert_eq!(document.reference_code, "PI-42-20190316-1A2B3C4D");
+        assert!(document.remitter.is_none());
+    }
+}
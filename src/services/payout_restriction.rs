@@ -0,0 +1,283 @@
+//! PayoutRestrictionService blocks payouts to stores in countries that can't legally receive them.
+//! The country of a store is the one of its billing info, it is checked when payouts and payout instructions
+//! are created and when a change of the billing info is approved. Superusers may allow the payouts of a store
+//! in spite of its country, the override is audited together with its reason
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::Fail;
+use futures::{future, Future};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use validator::{ValidationError, ValidationErrors};
+
+use stq_types::{BillingType, StoreId, UserId};
+
+use super::types::{ServiceFutureV2, ServiceResultV2};
+use config::PayoutRestrictions as PayoutRestrictionsConfig;
+use controller::requests::OverridePayoutRestrictionRequest;
+use controller::responses::PayoutRestrictionResponse;
+use models::{BillingInfoChangePayload, InternationalBillingInfoSearch, NewPayoutRestrictionOverride, StoreBillingTypeSearch};
+use repos::{InternationalBillingInfoRepo, PayoutRestrictionOverridesRepo, ReposFactory, StoreBillingTypeRepo};
+use services::types::spawn_on_pool;
+use services::{Error, ErrorKind};
+
+/// Country of the stores with Russian billing info
+const RUSSIA_COUNTRY: &str = "RUS";
+
+pub trait PayoutRestrictionService {
+    /// Whether the payouts of the store are blocked by the country of its billing info
+    fn get_payout_restriction(&self, store_id: StoreId) -> ServiceFutureV2<PayoutRestrictionResponse>;
+    /// Allows the payouts of the store in spite of its country
+    fn override_payout_restriction(
+        &self,
+        store_id: StoreId,
+        payload: OverridePayoutRestrictionRequest,
+    ) -> ServiceFutureV2<PayoutRestrictionResponse>;
+    /// Removes the override, the payouts of the store are blocked again if its country is restricted
+    fn remove_payout_restriction_override(&self, store_id: StoreId) -> ServiceFutureV2<PayoutRestrictionResponse>;
+}
+
+pub struct PayoutRestrictionServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+    pub config: PayoutRestrictionsConfig,
+}
+
+/// Repos the country of a store is looked up in
+pub struct PayoutCountryRepos<'a> {
+    pub store_billing_type_repo: &'a StoreBillingTypeRepo,
+    pub international_billing_info_repo: &'a InternationalBillingInfoRepo,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > PayoutRestrictionService for PayoutRestrictionServiceImpl<T, M, F>
+{
+    fn get_payout_restriction(&self, store_id: StoreId) -> ServiceFutureV2<PayoutRestrictionResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let config = self.config.clone();
+
+        spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let payout_restriction_overrides_repo = repo_factory.create_payout_restriction_overrides_repo(&conn, user_id);
+            let store_billing_type_repo = repo_factory.create_store_billing_type_repo_with_sys_acl(&conn);
+            let international_billing_info_repo = repo_factory.create_international_billing_repo_info_with_sys_acl(&conn);
+            let country_repos = PayoutCountryRepos {
+                store_billing_type_repo: &*store_billing_type_repo,
+                international_billing_info_repo: &*international_billing_info_repo,
+            };
+
+            let payout_override = payout_restriction_overrides_repo
+                .get(store_id)
+                .map_err(ectx!(try convert => store_id))?;
+            let country = store_payout_country(&country_repos, store_id)?;
+            let restricted_country = country.as_ref().map_or(false, |country| is_restricted_country(&config, country));
+
+            Ok(PayoutRestrictionResponse::new(
+                store_id,
+                country,
+                restricted_country,
+                payout_override,
+            ))
+        })
+    }
+
+    fn override_payout_restriction(
+        &self,
+        store_id: StoreId,
+        payload: OverridePayoutRestrictionRequest,
+    ) -> ServiceFutureV2<PayoutRestrictionResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let service = self.clone();
+
+        let user_id = match self.user_id {
+            None => return Box::new(future::err(ErrorKind::Forbidden.into())),
+            Some(user_id) => user_id,
+        };
+
+        let reason = payload.reason.trim().to_string();
+        if reason.is_empty() {
+            let mut errors = ValidationErrors::new();
+            let mut error = ValidationError::new("required");
+            error.message = Some("Override of a payout restriction requires a reason".into());
+            errors.add("reason", error);
+
+            return Box::new(future::err(ErrorKind::from(errors).into()));
+        }
+
+        let fut = spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let payout_restriction_overrides_repo = repo_factory.create_payout_restriction_overrides_repo(&conn, Some(user_id));
+
+            let payload = NewPayoutRestrictionOverride {
+                store_id,
+                reason,
+                granted_by: user_id,
+            };
+            payout_restriction_overrides_repo
+                .upsert(payload.clone())
+                .map_err(ectx!(try convert => payload))?;
+            info!(
+                "Payouts of store {} are allowed in spite of its country by user {}",
+                store_id, user_id
+            );
+
+            Ok(())
+        })
+        .and_then(move |_| service.get_payout_restriction(store_id));
+
+        Box::new(fut)
+    }
+
+    fn remove_payout_restriction_override(&self, store_id: StoreId) -> ServiceFutureV2<PayoutRestrictionResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let service = self.clone();
+
+        let fut = spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let payout_restriction_overrides_repo = repo_factory.create_payout_restriction_overrides_repo(&conn, user_id);
+
+            payout_restriction_overrides_repo
+                .delete(store_id)
+                .map(|_| ())
+                .map_err(ectx!(convert => store_id))
+        })
+        .and_then(move |_| service.get_payout_restriction(store_id));
+
+        Box::new(fut)
+    }
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > Clone for PayoutRestrictionServiceImpl<T, M, F>
+{
+    fn clone(&self) -> Self {
+        Self {
+            db_pool: self.db_pool.clone(),
+            cpu_pool: self.cpu_pool.clone(),
+            repo_factory: self.repo_factory.clone(),
+            user_id: self.user_id,
+            config: self.config.clone(),
+        }
+    }
+}
+
+/// Country of the billing info of the store, `None` if the store has no billing info yet
+pub fn store_payout_country(repos: &PayoutCountryRepos, store_id: StoreId) -> ServiceResultV2<Option<String>> {
+    let store_billing_type = repos
+        .store_billing_type_repo
+        .get(StoreBillingTypeSearch::by_store_id(store_id))
+        .map_err(ectx!(try convert => store_id))?;
+
+    match store_billing_type.map(|store_billing_type| store_billing_type.billing_type) {
+        Some(BillingType::Russia) => Ok(Some(RUSSIA_COUNTRY.to_string())),
+        Some(BillingType::International) | None => repos
+            .international_billing_info_repo
+            .get(InternationalBillingInfoSearch::by_store_id(store_id))
+            .map(|billing_info| billing_info.map(|billing_info| billing_info.country))
+            .map_err(ectx!(convert => store_id)),
+    }
+}
+
+/// Fails with a `restricted_country` validation error if payouts to `country` are restricted
+/// and the store has no override
+pub fn check_payout_restriction(
+    config: &PayoutRestrictionsConfig,
+    payout_restriction_overrides_repo: &PayoutRestrictionOverridesRepo,
+    store_id: StoreId,
+    country: Option<&str>,
+) -> ServiceResultV2<()> {
+    let country = match country {
+        Some(country) if is_restricted_country(config, country) => country,
+        _ => return Ok(()),
+    };
+
+    let payout_override = payout_restriction_overrides_repo
+        .get(store_id)
+        .map_err(ectx!(try convert => store_id))?;
+    if payout_override.is_some() {
+        return Ok(());
+    }
+
+    warn!("Payouts of store {} are blocked, {} is a restricted country", store_id, country);
+    Err(restricted_country_error(store_id, country))
+}
+
+/// Checks the country of the billing info of the store, see `check_payout_restriction`
+pub fn check_store_payout_restriction(
+    config: &PayoutRestrictionsConfig,
+    payout_restriction_overrides_repo: &PayoutRestrictionOverridesRepo,
+    country_repos: &PayoutCountryRepos,
+    store_id: StoreId,
+) -> ServiceResultV2<()> {
+    if config.restricted_countries.is_empty() {
+        return Ok(());
+    }
+
+    let country = store_payout_country(country_repos, store_id)?;
+    check_payout_restriction(
+        config,
+        payout_restriction_overrides_repo,
+        store_id,
+        country.as_ref().map(String::as_str),
+    )
+}
+
+/// Country of the billing info once the change is applied, `None` if the change keeps the country
+pub fn billing_info_change_country(payload: &BillingInfoChangePayload) -> Option<String> {
+    match payload {
+        BillingInfoChangePayload::CreateInternational { billing_info } => Some(billing_info.country.clone()),
+        BillingInfoChangePayload::UpdateInternational { billing_info, .. } => billing_info.country.clone(),
+        BillingInfoChangePayload::CreateRussia { .. } | BillingInfoChangePayload::UpdateRussia { .. } => Some(RUSSIA_COUNTRY.to_string()),
+    }
+}
+
+pub fn is_restricted_country(config: &PayoutRestrictionsConfig, country: &str) -> bool {
+    let country = country.trim();
+
+    config
+        .restricted_countries
+        .iter()
+        .any(|restricted_country| restricted_country.trim().eq_ignore_ascii_case(country))
+}
+
+fn restricted_country_error(store_id: StoreId, country: &str) -> Error {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new("restricted_country");
+    error.message = Some(format!("Payouts to stores in {} are restricted", country).into());
+    error.add_param("store_id".into(), &store_id);
+    error.add_param("country".into(), &country);
+    errors.add("store_id", error);
+
+    ErrorKind::from(errors).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> PayoutRestrictionsConfig {
+        PayoutRestrictionsConfig {
+            restricted_countries: vec!["PRK".to_string(), " Iran ".to_string()],
+        }
+    }
+
+    #[test]
+    fn countries_are_matched_ignoring_case_and_whitespace() {
+        assert!(is_restricted_country(&config(), "prk"));
+        assert!(is_restricted_country(&config(), "IRAN "));
+        assert!(!is_restricted_country(&config(), "Iraq"));
+        assert!(!is_restricted_country(&PayoutRestrictionsConfig::default(), "PRK"));
+    }
+}
@@ -0,0 +1,269 @@
+//! PayoutStatementService serves statements of payouts to sellers.
+//! A statement is created together with its payout and breaks the payout down into the paid out orders
+use std::collections::HashMap;
+
+use bigdecimal::BigDecimal;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::Fail;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use serde_json;
+
+use stq_types::UserId;
+
+use super::types::{ServiceFutureV2, ServiceResultV2};
+use controller::responses::PayoutStatementDocumentResponse;
+use models::order_v2::RawOrder;
+use models::{Amount, Currency, Fee, NewPayoutStatement, Payout, PayoutId, PayoutStatement, PayoutStatementId, PayoutStatementLine};
+use repos::{FeeRepo, InvoicesV2Repo, OrderExchangeRatesRepo, PayoutStatementsRepo, ReposFactory, SearchFeeParams};
+use services::types::spawn_on_pool;
+use services::{ErrorContext, ErrorKind};
+
+pub trait PayoutStatementService {
+    /// Returns a payout statement with all of its lines
+    fn download_payout_statement(&self, payout_statement_id: PayoutStatementId) -> ServiceFutureV2<PayoutStatementDocumentResponse>;
+}
+
+pub struct PayoutStatementServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > PayoutStatementService for PayoutStatementServiceImpl<T, M, F>
+{
+    fn download_payout_statement(&self, payout_statement_id: PayoutStatementId) -> ServiceFutureV2<PayoutStatementDocumentResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let payout_statements_repo = repo_factory.create_payout_statements_repo(&conn, user_id);
+
+            let payout_statement = payout_statements_repo
+                .get(payout_statement_id)
+                .map_err(ectx!(try convert => payout_statement_id))?
+                .ok_or({
+                    let e = format_err!("Payout statement {} not found", payout_statement_id);
+                    ectx!(try err e, ErrorKind::NotFound)
+                })?;
+
+            PayoutStatementDocumentResponse::try_from_payout_statement(payout_statement)
+        })
+    }
+}
+
+pub struct PayoutStatementRepos<'a> {
+    pub payout_statements_repo: &'a PayoutStatementsRepo,
+    pub fees_repo: &'a FeeRepo,
+    pub invoices_repo: &'a InvoicesV2Repo,
+    pub order_exchange_rates_repo: &'a OrderExchangeRatesRepo,
+}
+
+/// Order of a payout with the details its statement line is made of
+#[derive(Clone, Debug)]
+pub struct PayoutStatementOrder {
+    pub order: RawOrder,
+    pub fee: Option<Fee>,
+    pub buyer_currency: Currency,
+    pub exchange_rate: Option<BigDecimal>,
+}
+
+/// Creates the statement of a payout that is being made out of the orders
+pub fn generate_payout_statement(repos: &PayoutStatementRepos, payout: &Payout, orders: &[RawOrder]) -> ServiceResultV2<PayoutStatement> {
+    let fees = repos
+        .fees_repo
+        .search(SearchFeeParams {
+            order_ids: Some(payout.order_ids.clone()),
+            ..Default::default()
+        })
+        .map_err(ectx!(try convert))?;
+
+    let mut statement_orders = Vec::new();
+    for order_id in &payout.order_ids {
+        let order = orders.iter().find(|order| order.id == *order_id).cloned().ok_or({
+            let e = format_err!("Order {} of payout {} not found", order_id, payout.id);
+            ectx!(try err e, ErrorKind::Internal)
+        })?;
+
+        let invoice = repos
+            .invoices_repo
+            .get(order.invoice_id)
+            .map_err(ectx!(try convert => order.invoice_id))?
+            .ok_or({
+                let e = format_err!("Invoice {} of order {} not found", order.invoice_id, order.id);
+                ectx!(try err e, ErrorKind::Internal)
+            })?;
+
+        let exchange_rate = if invoice.buyer_currency != order.seller_currency {
+            repos
+                .order_exchange_rates_repo
+                .get_active_rate_for_order(order.id)
+                .map_err(ectx!(try convert => order.id))?
+                .map(|rate| rate.exchange_rate)
+        } else {
+            None
+        };
+
+        statement_orders.push(PayoutStatementOrder {
+            fee: fees.iter().find(|fee| fee.order_id == order.id).cloned(),
+            buyer_currency: invoice.buyer_currency,
+            exchange_rate,
+            order,
+        });
+    }
+
+    let new_payout_statement = create_payout_statement(payout, statement_orders)?;
+    repos
+        .payout_statements_repo
+        .create(new_payout_statement.clone())
+        .map_err(ectx!(try convert => new_payout_statement))
+}
+
+/// Builds the statement of a payout with a line per order.
+/// The deductions of the payout, i.e. its blockchain fee if the payout bears it and the fees carried
+/// from earlier payouts, are split between the orders in proportion to their gross amounts.
+/// The share of the last order takes the rounding remainder so that the lines add up to the net amount of the payout
+pub fn create_payout_statement(payout: &Payout, orders: Vec<PayoutStatementOrder>) -> ServiceResultV2<NewPayoutStatement> {
+    let currency = payout.currency();
+    let deductions = payout
+        .gross_amount
+        .checked_sub(payout.net_amount)
+        .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+
+    let orders_count = orders.len();
+    let mut allocated = Amount::zero();
+    let mut lines = Vec::new();
+    for (index, statement_order) in orders.into_iter().enumerate() {
+        let PayoutStatementOrder {
+            order,
+            fee,
+            buyer_currency,
+            exchange_rate,
+        } = statement_order;
+
+        let deduction = if index + 1 == orders_count {
+            deductions.checked_sub(allocated)
+        } else if payout.gross_amount == Amount::zero() {
+            Some(Amount::zero())
+        } else {
+            deductions
+                .checked_mul(order.total_amount)
+                .and_then(|amount| amount.checked_div(payout.gross_amount))
+        }
+        .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+
+        allocated = allocated
+            .checked_add(deduction)
+            .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+        let net_amount = order
+            .total_amount
+            .checked_sub(deduction)
+            .ok_or(ectx!(try err ErrorContext::AmountConversion, ErrorKind::Internal))?;
+
+        lines.push(PayoutStatementLine {
+            order_id: order.id,
+            store_id: order.store_id,
+            gross_amount: order.total_amount,
+            fee_amount: fee.and_then(|fee| fee_in_currency(&fee, currency)),
+            stripe_fee: order.stripe_fee,
+            net_amount,
+            buyer_currency,
+            exchange_rate,
+        });
+    }
+
+    let lines = serde_json::to_value(lines).map_err(|e| ectx!(err e, ErrorKind::Internal))?;
+
+    Ok(NewPayoutStatement {
+        payout_id: payout.id,
+        user_id: payout.user_id,
+        currency,
+        gross_amount: payout.gross_amount,
+        net_amount: payout.net_amount,
+        lines,
+    })
+}
+
+/// Platform fee of an order in the payout currency. Fees of crypto orders are kept in fiat
+/// together with the amount they were converted from, which is the one in the order currency
+fn fee_in_currency(fee: &Fee, currency: Currency) -> Option<Amount> {
+    if fee.crypto_currency == Some(currency) {
+        fee.crypto_amount
+    } else if fee.currency == currency {
+        Some(fee.amount)
+    } else {
+        None
+    }
+}
+
+/// Statement ids of the payouts that have one, payouts made before statements were introduced have none
+pub fn payout_statement_ids(
+    payout_statements_repo: &PayoutStatementsRepo,
+    payout_ids: &[PayoutId],
+) -> ServiceResultV2<HashMap<PayoutId, PayoutStatementId>> {
+    let payout_statements = payout_statements_repo
+        .get_by_payout_ids(payout_ids)
+        .map_err(ectx!(try convert => payout_ids.to_vec()))?;
+
+    Ok(payout_statements
+        .into_iter()
+        .map(|payout_statement| (payout_statement.payout_id, payout_statement.id))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::BigDecimal;
+    use serde_json;
+    use std::str::FromStr;
+
+    use models::*;
+    use test_support::{PayoutBuilder, RawOrderBuilder};
+
+    use super::{create_payout_statement, PayoutStatementOrder};
+
+    #[test]
+    fn payout_deductions_are_split_between_the_orders() {
+        let orders = [100, 200, 700]
+            .iter()
+            .map(|total_amount| RawOrderBuilder::payable(Currency::Btc, Amount::new(*total_amount)).build())
+            .collect::<Vec<_>>();
+        let payout = PayoutBuilder::new()
+            .net_amount(Amount::new(990))
+            .crypto_wallet(TureCurrency::Btc, Amount::new(7))
+            .order_ids(orders.iter().map(|order| order.id).collect())
+            .carried_fee(Amount::new(3))
+            .build();
+
+        let statement_orders = orders
+            .into_iter()
+            .map(|order| PayoutStatementOrder {
+                order,
+                fee: None,
+                buyer_currency: Currency::Eth,
+                exchange_rate: Some(BigDecimal::from_str("0.03").unwrap()),
+            })
+            .collect();
+
+        let statement = create_payout_statement(&payout, statement_orders).unwrap();
+        let lines: Vec<PayoutStatementLine> = serde_json::from_value(statement.lines).unwrap();
+
+        let net_amounts = lines.iter().map(|line| line.net_amount).collect::<Vec<_>>();
+        assert_eq!(net_amounts, vec![Amount::new(99), Amount::new(198), Amount::new(693)]);
+        assert_eq!(statement.net_amount, Amount::new(990));
+        assert_eq!(lines[0].exchange_rate, Some(BigDecimal::from_str("0.03").unwrap()));
+    }
+}
@@ -0,0 +1,279 @@
+//! Receipts of crypto invoices. The billing renders their subject and text, and the notifications microservice
+//! delivers them. Every message can be translated with `receipts.translations`, the English text is the fallback
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+
+use client::notifications::InvoiceReceiptEmail;
+use config::Receipts as ReceiptsConfig;
+use models::{Currency, InvoiceReceipt, ReceiptMessage, UserId};
+
+/// Text of a message in English, the placeholders it uses are listed next to it
+pub fn default_receipt_message(message: ReceiptMessage) -> &'static str {
+    match message {
+        // invoice_id
+        ReceiptMessage::Subject => "Receipt for invoice {invoice_id}",
+        ReceiptMessage::Greeting => "Thank you for your payment.",
+        // amount, currency
+        ReceiptMessage::AmountPaid => "Amount paid: {amount} {currency}",
+        // paid_at
+        ReceiptMessage::PaidAt => "Paid at: {paid_at} UTC",
+        // wallet_address
+        ReceiptMessage::WalletAddress => "Paid to wallet: {wallet_address}",
+        // name
+        ReceiptMessage::BilledTo => "Billed to: {name}",
+        // address
+        ReceiptMessage::BuyerAddress => "{address}",
+        // tax_id
+        ReceiptMessage::BuyerTaxId => "Tax ID: {tax_id}",
+        ReceiptMessage::OrdersHeader => "Orders:",
+        // order_id, seller_price, seller_currency, buyer_price, currency, exchange_rate
+        ReceiptMessage::Order => {
+            "Order {order_id}: {seller_price} {seller_currency} = {buyer_price} {currency} at 1 {currency} = {exchange_rate} {seller_currency}"
+        }
+        // order_id, seller_price, seller_currency
+        ReceiptMessage::OrderWithoutRate => "Order {order_id}: {seller_price} {seller_currency}",
+        // quantity, name, unit_price, seller_currency
+        ReceiptMessage::LineItem => "  {quantity} x {name} at {unit_price} {seller_currency}",
+        ReceiptMessage::TransactionsHeader => "Transactions:",
+        // transaction_id, amount, currency, received_at
+        ReceiptMessage::Transaction => "{transaction_id}: {amount} {currency} received at {received_at} UTC",
+    }
+}
+
+/// Renders the messages of a receipt in a locale
+pub struct ReceiptTemplate<'a> {
+    translations: Option<&'a HashMap<ReceiptMessage, String>>,
+}
+
+impl<'a> ReceiptTemplate<'a> {
+    pub fn new(config: &'a ReceiptsConfig, locale: &str) -> Self {
+        ReceiptTemplate {
+            translations: config.translations.get(locale),
+        }
+    }
+
+    pub fn message(&self, message: ReceiptMessage, params: &[(&str, String)]) -> String {
+        let text = self
+            .translations
+            .and_then(|translations| translations.get(&message))
+            .map(String::as_str)
+            .unwrap_or_else(|| default_receipt_message(message));
+
+        params.iter().fold(text.to_string(), |text, &(name, ref value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        })
+    }
+
+    pub fn subject(&self, receipt: &InvoiceReceipt) -> String {
+        self.message(ReceiptMessage::Subject, &[("invoice_id", receipt.invoice_id.to_string())])
+    }
+
+    pub fn text(&self, receipt: &InvoiceReceipt) -> String {
+        let currency = format_currency(receipt.currency);
+        let mut lines = vec![
+            self.message(ReceiptMessage::Greeting, &[]),
+            self.message(
+                ReceiptMessage::AmountPaid,
+                &[("amount", receipt.amount_paid.to_string()), ("currency", currency.clone())],
+            ),
+        ];
+
+        if let Some(paid_at) = receipt.paid_at {
+            lines.push(self.message(ReceiptMessage::PaidAt, &[("paid_at", format_time(paid_at))]));
+        }
+
+        if let Some(ref wallet_address) = receipt.wallet_address {
+            lines.push(self.message(ReceiptMessage::WalletAddress, &[("wallet_address", wallet_address.to_string())]));
+        }
+
+        if let Some(ref details) = receipt.buyer_billing_details {
+            lines.push(String::new());
+            lines.push(self.message(ReceiptMessage::BilledTo, &[("name", details.name.clone())]));
+            lines.push(self.message(ReceiptMessage::BuyerAddress, &[("address", details.address())]));
+            if let Some(ref tax_id) = details.tax_id {
+                lines.push(self.message(ReceiptMessage::BuyerTaxId, &[("tax_id", tax_id.clone())]));
+            }
+        }
+
+        if !receipt.orders.is_empty() {
+            lines.push(String::new());
+            lines.push(self.message(ReceiptMessage::OrdersHeader, &[]));
+            for order in &receipt.orders {
+                let mut params = vec![
+                    ("order_id", order.order_id.to_string()),
+                    ("seller_price", order.seller_price.to_string()),
+                    ("seller_currency", format_currency(order.seller_currency)),
+                    ("currency", currency.clone()),
+                ];
+                let message = match (&order.buyer_price, &order.exchange_rate) {
+                    (Some(buyer_price), Some(exchange_rate)) => {
+                        params.push(("buyer_price", buyer_price.to_string()));
+                        params.push(("exchange_rate", exchange_rate.to_string()));
+                        ReceiptMessage::Order
+                    }
+                    _ => ReceiptMessage::OrderWithoutRate,
+                };
+                lines.push(self.message(message, &params));
+                for line_item in &order.line_items {
+                    lines.push(self.message(
+                        ReceiptMessage::LineItem,
+                        &[
+                            ("quantity", line_item.quantity.to_string()),
+                            ("name", line_item.name.clone()),
+                            ("unit_price", line_item.unit_price.to_string()),
+                            ("seller_currency", format_currency(order.seller_currency)),
+                        ],
+                    ));
+                }
+            }
+        }
+
+        if !receipt.transactions.is_empty() {
+            lines.push(String::new());
+            lines.push(self.message(ReceiptMessage::TransactionsHeader, &[]));
+            for transaction in &receipt.transactions {
+                lines.push(self.message(
+                    ReceiptMessage::Transaction,
+                    &[
+                        ("transaction_id", transaction.id.to_string()),
+                        ("amount", transaction.amount.to_string()),
+                        ("currency", currency.clone()),
+                        ("received_at", format_time(transaction.received_at)),
+                    ],
+                ));
+            }
+        }
+
+        lines.join("\n")
+    }
+}
+
+/// Email with the receipt rendered in the locale of the config
+pub fn invoice_receipt_email(
+    config: &ReceiptsConfig,
+    user_id: UserId,
+    email: Option<String>,
+    receipt: InvoiceReceipt,
+) -> InvoiceReceiptEmail {
+    let template = ReceiptTemplate::new(config, &config.locale);
+
+    InvoiceReceiptEmail {
+        user_id,
+        email,
+        invoice_id: receipt.invoice_id,
+        locale: config.locale.clone(),
+        subject: template.subject(&receipt),
+        text: template.text(&receipt),
+        receipt,
+    }
+}
+
+fn format_currency(currency: Currency) -> String {
+    currency.to_string().to_uppercase()
+}
+
+fn format_time(time: NaiveDateTime) -> String {
+    time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+    use chrono::NaiveDate;
+    use uuid::Uuid;
+
+    use super::*;
+    use models::invoice_v2::InvoiceId;
+    use models::order_v2::OrderId;
+    use models::{
+        BuyerBillingDetails, InvoiceReceiptLineItem, InvoiceReceiptOrder, InvoiceReceiptTransaction, TransactionId, WalletAddress,
+    };
+    use stq_types::{Alpha3, ProductId};
+
+    fn receipt() -> InvoiceReceipt {
+        let paid_at = NaiveDate::from_ymd(2019, 4, 5).and_hms(9, 0, 0);
+
+        InvoiceReceipt {
+            invoice_id: InvoiceId::new(Uuid::nil()),
+            currency: Currency::Btc,
+            amount_paid: BigDecimal::from_str("0.5").unwrap(),
+            paid_at: Some(paid_at),
+            wallet_address: Some(WalletAddress::new("1BoatSLRHtKNngkdXEeobR76b53LETtpyT".to_string())),
+            buyer_billing_details: Some(BuyerBillingDetails {
+                name: "Jane Doe".to_string(),
+                address_line1: "Main st. 1".to_string(),
+                address_line2: None,
+                city: "Berlin".to_string(),
+                postal_code: Some("10115".to_string()),
+                region: None,
+                country: Alpha3("DEU".to_string()),
+                tax_id: Some("DE123456789".to_string()),
+            }),
+            orders: vec![
+                InvoiceReceiptOrder {
+                    order_id: OrderId::new(Uuid::nil()),
+                    seller_currency: Currency::Eth,
+                    seller_price: BigDecimal::from(10),
+                    buyer_price: Some(BigDecimal::from_str("0.5").unwrap()),
+                    exchange_rate: Some(BigDecimal::from(20)),
+                    line_items: vec![InvoiceReceiptLineItem {
+                        product_id: ProductId(1),
+                        name: "Mug".to_string(),
+                        quantity: 2,
+                        unit_price: BigDecimal::from(5),
+                        refunded_quantity: 0,
+                    }],
+                },
+                InvoiceReceiptOrder {
+                    order_id: OrderId::new(Uuid::nil()),
+                    seller_currency: Currency::Stq,
+                    seller_price: BigDecimal::from(100),
+                    buyer_price: None,
+                    exchange_rate: None,
+                    line_items: Vec::new(),
+                },
+            ],
+            transactions: vec![InvoiceReceiptTransaction {
+                id: TransactionId::new(Uuid::nil()),
+                amount: BigDecimal::from_str("0.5").unwrap(),
+                received_at: paid_at,
+            }],
+        }
+    }
+
+    #[test]
+    fn receipt_lists_amounts_rates_wallet_and_transactions() {
+        let config = ReceiptsConfig::default();
+        let email = invoice_receipt_email(&config, UserId::new(1), None, receipt());
+
+        assert_eq!(email.subject, "Receipt for invoice 00000000-0000-0000-0000-000000000000");
+        assert!(email.text.contains("Amount paid: 0.5 BTC"));
+        assert!(email.text.contains("Paid to wallet: 1BoatSLRHtKNngkdXEeobR76b53LETtpyT"));
+        assert!(email
+            .text
+            .contains("Billed to: Jane Doe\nMain st. 1, Berlin, 10115, DEU\nTax ID: DE123456789"));
+        assert!(email.text.contains("10 ETH = 0.5 BTC at 1 BTC = 20 ETH\n  2 x Mug at 5 ETH"));
+        assert!(email.text.contains("00000000-0000-0000-0000-000000000000: 100 STQ"));
+        assert!(email
+            .text
+            .contains("00000000-0000-0000-0000-000000000000: 0.5 BTC received at 2019-04-05 09:00:00 UTC"));
+    }
+
+    #[test]
+    fn translated_messages_replace_the_english_ones() {
+        let mut translations = HashMap::new();
+        translations.insert(ReceiptMessage::Subject, "Квитанция по счёту {invoice_id}".to_string());
+        let mut config = ReceiptsConfig::default();
+        config.locale = "ru".to_string();
+        config.translations.insert("ru".to_string(), translations);
+
+        let email = invoice_receipt_email(&config, UserId::new(1), None, receipt());
+
+        assert_eq!(email.locale, "ru");
+        assert_eq!(email.subject, "Квитанция по счёту 00000000-0000-0000-0000-000000000000");
+        assert!(email.text.starts_with("Thank you for your payment."));
+    }
+}
@@ -0,0 +1,17 @@
+//! Order state updates are delivered to saga by the event store after the transaction
+//! that changed the orders commits, so saga being unavailable never rolls the change back
+use failure::Fail;
+
+use models::{batch_order_state_updates, Event, EventPayload, OrderStateUpdate};
+use repos::EventStoreRepo;
+use services::Error;
+
+/// Schedules delivery of the updates to saga in batches, one request per batch
+pub fn enqueue_order_state_updates(event_store_repo: &EventStoreRepo, order_states: Vec<OrderStateUpdate>) -> Result<(), Error> {
+    for order_states in batch_order_state_updates(order_states) {
+        let event = Event::new(EventPayload::SagaOrderStatesUpdate { order_states });
+        event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,90 @@
+//! Compares the migrations the binary is built with against the ones applied to the database.
+//! A deploy running against a database that lacks migrations fails on missing tables and columns
+//! only once a query touches them, so the comparison is made on startup and reported to superusers
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::Fail;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+
+use stq_types::UserId;
+
+use super::types::{ServiceFutureV2, ServiceResultV2};
+use controller::responses::SchemaVersionResponse;
+use models::{SchemaVersion, EMBEDDED_MIGRATIONS};
+use repos::{ReposFactory, SchemaMigrationsRepo};
+use services::types::spawn_on_pool;
+
+pub trait SchemaMigrationService {
+    /// Current schema version of the database and the migrations pending for this binary
+    fn get_schema_version(&self) -> ServiceFutureV2<SchemaVersionResponse>;
+}
+
+pub struct SchemaMigrationServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > SchemaMigrationService for SchemaMigrationServiceImpl<T, M, F>
+{
+    fn get_schema_version(&self) -> ServiceFutureV2<SchemaVersionResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let schema_migrations_repo = repo_factory.create_schema_migrations_repo(&conn, user_id);
+
+            schema_version(&*schema_migrations_repo).map(SchemaVersionResponse::from)
+        })
+    }
+}
+
+/// Schema version of the database compared with the migrations embedded into the binary
+pub fn schema_version(schema_migrations_repo: &SchemaMigrationsRepo) -> ServiceResultV2<SchemaVersion> {
+    let applied_versions = schema_migrations_repo.get_applied_versions().map_err(ectx!(try convert))?;
+
+    Ok(SchemaVersion::new(EMBEDDED_MIGRATIONS, &applied_versions))
+}
+
+#[cfg(test)]
+mod tests {
+    use models::SchemaVersion;
+
+    #[test]
+    fn schema_version_lists_pending_and_unknown_migrations() {
+        let embedded = &["00000000000000", "20190410090000", "20190411090000", "20190412090000"];
+        let applied = vec![
+            "20190410090000".to_string(),
+            "00000000000000".to_string(),
+            "20190413090000".to_string(),
+        ];
+
+        let schema_version = SchemaVersion::new(embedded, &applied);
+
+        assert_eq!(schema_version.current_version, Some("20190413090000".to_string()));
+        assert_eq!(schema_version.expected_version, Some("20190412090000".to_string()));
+        assert_eq!(
+            schema_version.pending_migrations,
+            vec!["20190411090000".to_string(), "20190412090000".to_string()]
+        );
+        assert_eq!(schema_version.unknown_migrations, vec!["20190413090000".to_string()]);
+        assert!(schema_version.is_behind());
+
+        let up_to_date = SchemaVersion::new(embedded, &embedded.iter().map(|version| version.to_string()).collect::<Vec<_>>());
+        assert!(!up_to_date.is_behind());
+        assert!(up_to_date.unknown_migrations.is_empty());
+    }
+}
@@ -0,0 +1,337 @@
+//! Detects stores whose balance has gone negative. A store owes the balance fees of its payouts and the seller share
+//! of refunds made after its orders have been paid out, by a payout or by a payout instruction; a balance is negative
+//! when these exceed the orders that haven't been paid out yet. The balance is checked after every refund and payout
+//! of the store
+use std::collections::{HashMap, HashSet};
+
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::{err_msg, Fail};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+
+use stq_types::{StoreId as StqStoreId, UserId};
+
+use super::types::{ServiceFutureV2, ServiceResultV2};
+use controller::responses::NegativeStoreBalanceResponse;
+use models::order_v2::{OrderId, RawOrder, StoreId};
+use models::{
+    Amount, Currency, Event, EventPayload, FeeAdjustment, NegativeStoreBalance, NewNegativeStoreBalance, Payout, PayoutInstruction,
+    PayoutInstructionSearch, PayoutsByOrderIds,
+};
+use repos::{EventStoreRepo, FeeAdjustmentsRepo, NegativeStoreBalancesRepo, OrdersRepo, PayoutInstructionsRepo, PayoutsRepo, ReposFactory};
+use services::payout::{get_store_payouts, get_unpaid_orders, outstanding_balance_fees};
+use services::types::spawn_on_pool;
+use services::ErrorKind;
+
+pub trait StoreBalanceService {
+    /// Negative balances of all stores that haven't recovered yet, the largest deficits first
+    fn get_negative_store_balances(&self) -> ServiceFutureV2<Vec<NegativeStoreBalanceResponse>>;
+}
+
+pub struct StoreBalanceServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > StoreBalanceService for StoreBalanceServiceImpl<T, M, F>
+{
+    fn get_negative_store_balances(&self) -> ServiceFutureV2<Vec<NegativeStoreBalanceResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let negative_store_balances_repo = repo_factory.create_negative_store_balances_repo(&conn, user_id);
+
+            let negative_store_balances = negative_store_balances_repo.list_open().map_err(ectx!(try convert))?;
+
+            Ok(negative_store_balances
+                .into_iter()
+                .map(NegativeStoreBalanceResponse::from)
+                .collect())
+        })
+    }
+}
+
+/// Repos the balance check reads and writes, all of them have to use the system ACL
+pub struct StoreBalanceRepos<'a> {
+    pub orders_repo: &'a OrdersRepo,
+    pub payouts_repo: &'a PayoutsRepo,
+    pub payout_instructions_repo: &'a PayoutInstructionsRepo,
+    pub fee_adjustments_repo: &'a FeeAdjustmentsRepo,
+    pub negative_store_balances_repo: &'a NegativeStoreBalancesRepo,
+    pub event_store_repo: &'a EventStoreRepo,
+}
+
+/// Flags every currency the store owes more in than its unpaid orders cover and resolves the flags
+/// of the currencies that have recovered. A `NegativeStoreBalanceDetected` event is published for every new flag.
+/// Returns the flags left open
+pub fn check_store_balance(repos: &StoreBalanceRepos, store_id: StoreId) -> ServiceResultV2<Vec<NegativeStoreBalance>> {
+    let StoreBalanceRepos {
+        negative_store_balances_repo,
+        event_store_repo,
+        ..
+    } = *repos;
+
+    let deficits = store_balance_deficits(repos, store_id, &[], None)?;
+    let open = negative_store_balances_repo
+        .get_open_by_store_id(store_id)
+        .map_err(ectx!(try convert => store_id))?;
+
+    let mut negative_store_balances = Vec::new();
+    for negative_store_balance in open {
+        let id = negative_store_balance.id;
+        match deficits.get(&negative_store_balance.currency) {
+            None => {
+                info!(
+                    "Balance of store {} in {} has recovered, resolving negative balance {}",
+                    store_id, negative_store_balance.currency, id
+                );
+                negative_store_balances_repo.resolve(id).map_err(ectx!(try convert => id))?;
+            }
+            Some(amount) if *amount == negative_store_balance.amount => negative_store_balances.push(negative_store_balance),
+            Some(amount) => {
+                let amount = *amount;
+                let negative_store_balance = negative_store_balances_repo
+                    .update_amount(id, amount)
+                    .map_err(ectx!(try convert => id, amount))?;
+                negative_store_balances.push(negative_store_balance);
+            }
+        }
+    }
+
+    for (currency, amount) in deficits {
+        if negative_store_balances.iter().any(|balance| balance.currency == currency) {
+            continue;
+        }
+
+        warn!("Balance of store {} in {} is negative by {}", store_id, currency, amount);
+        let new_negative_store_balance = NewNegativeStoreBalance {
+            store_id,
+            currency,
+            amount,
+        };
+        let negative_store_balance = negative_store_balances_repo
+            .create(new_negative_store_balance.clone())
+            .map_err(ectx!(try convert => new_negative_store_balance))?;
+
+        let event = Event::new(EventPayload::NegativeStoreBalanceDetected {
+            negative_store_balance_id: negative_store_balance.id,
+        });
+        event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+
+        negative_store_balances.push(negative_store_balance);
+    }
+
+    Ok(negative_store_balances)
+}
+
+/// Deficits of the store as if the orders in `paid_out_order_ids` had been paid out by `pending_payout`
+pub fn store_balance_deficits(
+    repos: &StoreBalanceRepos,
+    store_id: StoreId,
+    paid_out_order_ids: &[OrderId],
+    pending_payout: Option<&Payout>,
+) -> ServiceResultV2<HashMap<Currency, Amount>> {
+    let StoreBalanceRepos {
+        orders_repo,
+        payouts_repo,
+        payout_instructions_repo,
+        fee_adjustments_repo,
+        ..
+    } = *repos;
+
+    let mut payouts = get_store_payouts(orders_repo, payouts_repo, &[store_id])?;
+    payouts.extend(pending_payout.cloned());
+
+    let order_ids = orders_repo
+        .get_order_ids_by_store_id(store_id)
+        .map_err(ectx!(try convert => store_id))?;
+    let PayoutsByOrderIds {
+        payouts: payouts_by_order_id,
+        order_ids_without_payout: _,
+    } = payouts_repo.get_by_order_ids(&order_ids).map_err(ectx!(try convert => order_ids))?;
+    let payout_instructions = payout_instructions_repo
+        .search(PayoutInstructionSearch::by_store_id(StqStoreId(store_id.inner())))
+        .map_err(ectx!(try convert => store_id))?;
+    let order_ids_with_payout = order_ids_paid_out(payouts_by_order_id.keys().cloned(), &payout_instructions)?;
+
+    let unpaid_orders = get_unpaid_orders(orders_repo, payouts_repo, store_id, None)?
+        .into_iter()
+        .filter(|order| !paid_out_order_ids.contains(&order.id) && !order_ids_with_payout.contains(&order.id))
+        .collect::<Vec<_>>();
+
+    let order_ids_with_payout = order_ids_with_payout.into_iter().collect::<Vec<_>>();
+    let refunds_after_payout = fee_adjustments_repo
+        .get_by_order_ids(&order_ids_with_payout)
+        .map_err(ectx!(try convert => order_ids_with_payout))?;
+
+    balance_deficits(&unpaid_orders, &payouts, &refunds_after_payout)
+}
+
+/// Orders paid out by payouts or covered by payout instructions. Fiat orders are paid out by bank transfers
+/// of the instructions only, crypto orders by payouts
+pub fn order_ids_paid_out<I>(payout_order_ids: I, payout_instructions: &[PayoutInstruction]) -> ServiceResultV2<HashSet<OrderId>>
+where
+    I: IntoIterator<Item = OrderId>,
+{
+    let mut order_ids = payout_order_ids.into_iter().collect::<HashSet<_>>();
+    for payout_instruction in payout_instructions {
+        let instructed_order_ids = payout_instruction.order_ids().map_err(|e| ectx!(err e, ErrorKind::Internal))?;
+        order_ids.extend(instructed_order_ids);
+    }
+
+    Ok(order_ids)
+}
+
+/// Amounts owed above the unpaid orders by currency, currencies that are covered are left out
+pub fn balance_deficits(
+    unpaid_orders: &[RawOrder],
+    payouts: &[Payout],
+    refunds_after_payout: &[FeeAdjustment],
+) -> ServiceResultV2<HashMap<Currency, Amount>> {
+    let overflow = || {
+        let e = err_msg("Overflow while calculating the deficit of a store balance");
+        ectx!(err e, ErrorKind::Internal)
+    };
+
+    let mut credits = HashMap::<Currency, Amount>::new();
+    for order in unpaid_orders {
+        let credit = credits.entry(order.seller_currency).or_insert(Amount::zero());
+        *credit = credit.checked_add(order.total_amount).ok_or_else(overflow)?;
+    }
+
+    let mut debits = HashMap::<Currency, Amount>::new();
+    for refund in refunds_after_payout {
+        let debit = debits.entry(refund.currency).or_insert(Amount::zero());
+        *debit = debit.checked_add(refund.seller_amount).ok_or_else(overflow)?;
+    }
+    for payout in payouts {
+        let currency = payout.currency();
+        if debits.contains_key(&currency) {
+            continue;
+        }
+        debits.insert(currency, Amount::zero());
+    }
+    for (currency, debit) in debits.iter_mut() {
+        *debit = debit
+            .checked_add(outstanding_balance_fees(payouts, *currency)?)
+            .ok_or_else(overflow)?;
+    }
+
+    Ok(debits
+        .into_iter()
+        .filter_map(|(currency, debit)| {
+            let credit = credits.get(&currency).cloned().unwrap_or(Amount::zero());
+            if debit > credit {
+                debit.checked_sub(credit).map(|deficit| (currency, deficit))
+            } else {
+                None
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use serde_json;
+
+    use stq_types::StoreId;
+
+    use models::order_v2::OrderId;
+    use models::*;
+    use test_support::{PayoutBuilder, RawOrderBuilder};
+
+    use super::{balance_deficits, order_ids_paid_out};
+
+    fn refund(currency: Currency, seller_amount: u128) -> FeeAdjustment {
+        FeeAdjustment {
+            id: FeeAdjustmentId::new(1),
+            fee_id: FeeId::new(1),
+            order_id: OrderId::generate(),
+            currency,
+            refund_amount: Amount::new(seller_amount + 5),
+            seller_amount: Amount::new(seller_amount),
+            fee_amount: Amount::new(5),
+            fee_charge_id: None,
+            created_at: NaiveDate::from_ymd(2019, 4, 2).and_hms(12, 0, 0),
+            charged_currency: None,
+            charged_fee_amount: None,
+        }
+    }
+
+    #[test]
+    fn balance_deficits_count_refunds_and_fees_above_unpaid_orders() {
+        let unpaid_orders = vec![
+            RawOrderBuilder::payable(Currency::Eur, Amount::new(300)).build(),
+            RawOrderBuilder::payable(Currency::Stq, Amount::new(50)).build(),
+        ];
+        let payouts = vec![PayoutBuilder::new()
+            .crypto_wallet(TureCurrency::Stq, Amount::new(80))
+            .fee_payer(PayoutFeePayer::Balance)
+            .build()];
+        let refunds = vec![refund(Currency::Eur, 200), refund(Currency::Eur, 150), refund(Currency::Usd, 10)];
+
+        let deficits = balance_deficits(&unpaid_orders, &payouts, &refunds).unwrap();
+
+        assert_eq!(deficits.len(), 3);
+        assert_eq!(deficits[&Currency::Eur], Amount::new(50));
+        assert_eq!(deficits[&Currency::Usd], Amount::new(10));
+        assert_eq!(deficits[&Currency::Stq], Amount::new(30));
+
+        let unpaid_orders = vec![
+            RawOrderBuilder::payable(Currency::Eur, Amount::new(350)).build(),
+            RawOrderBuilder::payable(Currency::Usd, Amount::new(10)).build(),
+            RawOrderBuilder::payable(Currency::Stq, Amount::new(80)).build(),
+        ];
+        assert!(balance_deficits(&unpaid_orders, &payouts, &refunds).unwrap().is_empty());
+    }
+
+    #[test]
+    fn fiat_order_refunded_after_its_payout_instruction_is_owed_by_the_store() {
+        let instructed = RawOrderBuilder::payable(Currency::Eur, Amount::new(300)).build();
+        let awaiting_payout = RawOrderBuilder::payable(Currency::Eur, Amount::new(100)).build();
+        let payout_instruction = PayoutInstruction {
+            id: PayoutInstructionId::new(1),
+            store_id: StoreId(instructed.store_id.inner()),
+            currency: Currency::Eur,
+            total_amount: Amount::new(300),
+            order_ids: serde_json::to_value(vec![instructed.id]).unwrap(),
+            reference_code: "PI-1-20190402-1A2B3C4D".to_string(),
+            document: serde_json::Value::Null,
+            created_at: NaiveDate::from_ymd(2019, 4, 1).and_hms(12, 0, 0),
+            stripe_fee: Amount::zero(),
+        };
+
+        let order_ids_with_payout = order_ids_paid_out(vec![], &[payout_instruction]).unwrap();
+        assert!(order_ids_with_payout.contains(&instructed.id));
+        assert!(!order_ids_with_payout.contains(&awaiting_payout.id));
+
+        let unpaid_orders = vec![instructed.clone(), awaiting_payout]
+            .into_iter()
+            .filter(|order| !order_ids_with_payout.contains(&order.id))
+            .collect::<Vec<_>>();
+        let refunds = vec![FeeAdjustment {
+            order_id: instructed.id,
+            ..refund(Currency::Eur, 250)
+        }];
+
+        let deficits = balance_deficits(&unpaid_orders, &[], &refunds).unwrap();
+        assert_eq!(deficits.len(), 1);
+        assert_eq!(deficits[&Currency::Eur], Amount::new(150));
+    }
+}
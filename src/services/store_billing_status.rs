@@ -0,0 +1,562 @@
+//! StoreBillingStatusService keeps stores that leave their fees unpaid suspended.
+//! The unpaid fees policy is evaluated for every store on a schedule, financial managers may override
+//! its decision for a while or reinstate a suspended store. Stores suspended by their fee dunning stay
+//! suspended until the dunning is resolved. Every change of the state is sent to saga
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration as StdDuration, Instant};
+
+use bigdecimal::BigDecimal;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::{Error as FailureError, Fail};
+use futures::{future, Future, Stream};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use sentry::integrations::failure::capture_error;
+use tokio_timer::Interval;
+use validator::{ValidationError, ValidationErrors};
+
+use stq_types::{StoreId, UserId};
+
+use super::types::{ServiceFutureV2, ServiceResultV2};
+use config::FeatureFlags as FeatureFlagsConfig;
+use config::StoreBillingSuspension as StoreBillingSuspensionConfig;
+use controller::requests::OverrideStoreBillingStatusRequest;
+use controller::responses::StoreBillingStatusResponse;
+use models::order_v2::OrderId;
+use models::{
+    Amount, Currency, Event, EventPayload, Feature, Fee, FeeStatus, NewStoreBillingStatus, StoreBillingState, StoreBillingStatus,
+    StoreSuspensionReason, UpdateStoreBillingStatus,
+};
+use repos::{EventStoreRepo, FeeRepo, OrdersRepo, ReposFactory, SearchFeeParams, StoreBillingStatusesRepo};
+use services::feature_flag::is_feature_enabled;
+use services::fee_dunning::resolve_paid_fee_dunning;
+use services::types::spawn_on_pool;
+use services::{ErrorContext, ErrorKind};
+
+pub trait StoreBillingStatusService {
+    /// Billing status of a store, a store the policy has never evaluated is active
+    fn get_billing_status(&self, store_id: StoreId) -> ServiceFutureV2<StoreBillingStatusResponse>;
+    /// Puts a store in the requested state, the policy leaves it alone until the override ends
+    fn override_billing_status(
+        &self,
+        store_id: StoreId,
+        payload: OverrideStoreBillingStatusRequest,
+    ) -> ServiceFutureV2<StoreBillingStatusResponse>;
+    /// Reinstates a suspended store, the policy leaves it alone for `reinstatement_grace_days`
+    fn reinstate_store(&self, store_id: StoreId) -> ServiceFutureV2<StoreBillingStatusResponse>;
+}
+
+pub struct StoreBillingStatusServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+    pub config: StoreBillingSuspensionConfig,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > StoreBillingStatusService for StoreBillingStatusServiceImpl<T, M, F>
+{
+    fn get_billing_status(&self, store_id: StoreId) -> ServiceFutureV2<StoreBillingStatusResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+
+        spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let store_billing_statuses_repo = repo_factory.create_store_billing_statuses_repo(&conn, user_id);
+
+            store_billing_statuses_repo
+                .get(store_id)
+                .map(|store_billing_status| {
+                    store_billing_status
+                        .map(StoreBillingStatusResponse::from)
+                        .unwrap_or_else(|| StoreBillingStatusResponse::active(store_id))
+                })
+                .map_err(ectx!(convert => store_id))
+        })
+    }
+
+    fn override_billing_status(
+        &self,
+        store_id: StoreId,
+        payload: OverrideStoreBillingStatusRequest,
+    ) -> ServiceFutureV2<StoreBillingStatusResponse> {
+        let repo_factory = self.repo_factory.clone();
+
+        let user_id = match self.user_id {
+            None => return Box::new(future::err(ErrorKind::Forbidden.into())),
+            Some(user_id) => user_id,
+        };
+
+        let now = Utc::now().naive_utc();
+        if let Some(until) = payload.until {
+            if until <= now {
+                let mut errors = ValidationErrors::new();
+                let mut error = ValidationError::new("in_past");
+                error.message = Some("Override has to end in the future".into());
+                error.add_param("value".into(), &until);
+                errors.add("until", error);
+
+                return Box::new(future::err(ErrorKind::from(errors).into()));
+            }
+        }
+
+        spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let store_billing_statuses_repo = repo_factory.create_store_billing_statuses_repo(&conn, Some(user_id));
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+            conn.transaction(move || {
+                let suspension_reason = match payload.state {
+                    StoreBillingState::Active => None,
+                    StoreBillingState::Suspended => Some(StoreSuspensionReason::Manual),
+                };
+
+                let update = UpdateStoreBillingStatus {
+                    state: Some(payload.state),
+                    suspension_reason: Some(suspension_reason),
+                    overridden_by: Some(Some(user_id)),
+                    override_until: Some(payload.until),
+                    ..Default::default()
+                };
+
+                let (store_billing_status, state_changed) = save_store_billing_status(&*store_billing_statuses_repo, store_id, update)?;
+                if state_changed {
+                    let event = Event::new(EventPayload::StoreBillingStatusChanged { store_id });
+                    event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+                }
+
+                Ok(StoreBillingStatusResponse::from(store_billing_status))
+            })
+        })
+    }
+
+    fn reinstate_store(&self, store_id: StoreId) -> ServiceFutureV2<StoreBillingStatusResponse> {
+        let repo_factory = self.repo_factory.clone();
+
+        let user_id = match self.user_id {
+            None => return Box::new(future::err(ErrorKind::Forbidden.into())),
+            Some(user_id) => user_id,
+        };
+
+        let override_until = Utc::now().naive_utc() + Duration::days(self.config.reinstatement_grace_days);
+
+        spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let store_billing_statuses_repo = repo_factory.create_store_billing_statuses_repo(&conn, Some(user_id));
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+            conn.transaction(move || {
+                let store_billing_status = store_billing_statuses_repo.get(store_id).map_err(ectx!(try convert => store_id))?;
+
+                let is_suspended = store_billing_status.map_or(false, |store_billing_status| {
+                    store_billing_status.state == StoreBillingState::Suspended
+                });
+                if !is_suspended {
+                    let mut errors = ValidationErrors::new();
+                    let mut error = ValidationError::new("not_suspended");
+                    error.message = Some("Only suspended stores can be reinstated".into());
+                    errors.add("store_id", error);
+
+                    return Err(ErrorKind::from(errors).into());
+                }
+
+                let update = UpdateStoreBillingStatus {
+                    state: Some(StoreBillingState::Active),
+                    suspension_reason: Some(None),
+                    overridden_by: Some(Some(user_id)),
+                    override_until: Some(Some(override_until)),
+                    ..Default::default()
+                };
+                let store_billing_status = store_billing_statuses_repo
+                    .update(store_id, update)
+                    .map_err(ectx!(try convert => store_id))?;
+
+                let event = Event::new(EventPayload::StoreBillingStatusChanged { store_id });
+                event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+
+                Ok(StoreBillingStatusResponse::from(store_billing_status))
+            })
+        })
+    }
+}
+
+/// Evaluates the unpaid fees policy on every tick of `evaluation_interval_sec`.
+/// A failed evaluation is reported and retried on the next tick
+pub fn run_store_billing_policy<T, M, F>(
+    config: StoreBillingSuspensionConfig,
+    feature_flags: FeatureFlagsConfig,
+    db_pool: Pool<M>,
+    cpu_pool: CpuPool,
+    repo_factory: F,
+) -> impl Future<Item = (), Error = FailureError>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    let interval = StdDuration::from_secs(config.evaluation_interval_sec);
+
+    Interval::new(Instant::now(), interval)
+        .map_err(FailureError::from)
+        .for_each(move |_| {
+            debug!("Started evaluating store billing statuses");
+            evaluate_store_billing_statuses(
+                db_pool.clone(),
+                cpu_pool.clone(),
+                repo_factory.clone(),
+                config.clone(),
+                feature_flags.clone(),
+            )
+            .then(|res| {
+                match res {
+                    Ok(changed) => {
+                        debug!("Finished evaluating store billing statuses, {} stores changed state", changed.len());
+                    }
+                    Err(err) => {
+                        let err = FailureError::from(err.context("An error occurred while evaluating store billing statuses"));
+                        error!("{:?}", &err);
+                        capture_error(&err);
+                    }
+                };
+
+                future::ok::<_, FailureError>(())
+            })
+        })
+}
+
+/// Evaluates the policy for every store with unpaid fees or a billing status and returns the stores whose state has changed.
+/// Overridden stores only get their unpaid fees recorded
+pub fn evaluate_store_billing_statuses<T, M, F>(
+    db_pool: Pool<M>,
+    cpu_pool: CpuPool,
+    repo_factory: F,
+    config: StoreBillingSuspensionConfig,
+    feature_flags: FeatureFlagsConfig,
+) -> ServiceFutureV2<Vec<StoreBillingStatus>>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    spawn_on_pool(db_pool, cpu_pool, move |conn| {
+        let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+        let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+        let store_billing_statuses_repo = repo_factory.create_store_billing_statuses_repo_with_sys_acl(&conn);
+        let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+        let feature_flags_repo = repo_factory.create_feature_flags_repo_with_sys_acl(&conn);
+        let fee_dunnings_repo = repo_factory.create_fee_dunnings_repo_with_sys_acl(&conn);
+
+        conn.transaction(move || {
+            let now = Utc::now().naive_utc();
+
+            // Dunnings of stores that have paid their fees since, e.g. in crypto, are resolved before the stores are evaluated
+            let mut dunning_store_ids = HashSet::new();
+            for dunning in fee_dunnings_repo.list_open().map_err(ectx!(try convert))? {
+                let store_id = dunning.store_id;
+                let is_resolved = resolve_paid_fee_dunning(
+                    &*fee_dunnings_repo,
+                    &*fees_repo,
+                    &*orders_repo,
+                    &*store_billing_statuses_repo,
+                    &*event_store_repo,
+                    store_id,
+                    now,
+                )?;
+                if !is_resolved && dunning.is_suspending() {
+                    dunning_store_ids.insert(store_id);
+                }
+            }
+
+            let unpaid_fees = fees_repo
+                .search(SearchFeeParams::by_status(FeeStatus::NotPaid))
+                .map_err(ectx!(try convert))?;
+
+            let order_ids: Vec<OrderId> = unpaid_fees.iter().map(|fee| fee.order_id).collect();
+            let store_ids_by_order: HashMap<OrderId, StoreId> = orders_repo
+                .get_many(&order_ids)
+                .map_err(ectx!(try convert))?
+                .into_iter()
+                .map(|order| (order.id, StoreId(order.store_id.inner())))
+                .collect();
+
+            let mut unpaid_fees_by_store: HashMap<StoreId, Vec<Fee>> = HashMap::new();
+            for fee in unpaid_fees {
+                match store_ids_by_order.get(&fee.order_id) {
+                    Some(store_id) => unpaid_fees_by_store.entry(*store_id).or_insert_with(Vec::new).push(fee),
+                    None => warn!(
+                        "Fee #{} skipped in store billing statuses: order {} not found",
+                        fee.id, fee.order_id
+                    ),
+                }
+            }
+
+            let store_billing_statuses: HashMap<StoreId, StoreBillingStatus> = store_billing_statuses_repo
+                .list()
+                .map_err(ectx!(try convert))?
+                .into_iter()
+                .map(|store_billing_status| (store_billing_status.store_id, store_billing_status))
+                .collect();
+
+            let store_ids: HashSet<StoreId> = unpaid_fees_by_store
+                .keys()
+                .chain(store_billing_statuses.keys())
+                .chain(dunning_store_ids.iter())
+                .cloned()
+                .collect();
+
+            let mut changed = Vec::new();
+            for store_id in store_ids {
+                let unpaid_fees = UnpaidFees::new(unpaid_fees_by_store.get(&store_id).map_or(&[], Vec::as_slice), &config, now);
+                let current = store_billing_statuses.get(&store_id);
+
+                let mut update = UpdateStoreBillingStatus {
+                    overdue_fees_count: Some(unpaid_fees.overdue_fees_count as i32),
+                    unpaid_since: Some(unpaid_fees.unpaid_since),
+                    evaluated_at: Some(Some(now)),
+                    ..Default::default()
+                };
+
+                let is_overridden = current.map_or(false, |current| current.is_overridden(now));
+                if !is_overridden {
+                    // A store the suspension is not rolled out to is kept active, unless its fee dunning suspended it
+                    let is_suspendable =
+                        is_feature_enabled(&*feature_flags_repo, &feature_flags, Feature::StoreBillingSuspension, store_id)?;
+                    let is_suspended = dunning_store_ids.contains(&store_id) || (is_suspendable && unpaid_fees.exceed_limits(&config));
+                    let (state, suspension_reason) = if is_suspended {
+                        (StoreBillingState::Suspended, Some(StoreSuspensionReason::UnpaidFees))
+                    } else {
+                        (StoreBillingState::Active, None)
+                    };
+
+                    update.state = Some(state);
+                    update.suspension_reason = Some(suspension_reason);
+                    if current.map_or(false, |current| current.overridden_by.is_some()) {
+                        update.overridden_by = Some(None);
+                        update.override_until = Some(None);
+                    }
+                }
+
+                let (store_billing_status, state_changed) = save_store_billing_status(&*store_billing_statuses_repo, store_id, update)?;
+                if state_changed {
+                    info!(
+                        "Store {} is {} by the unpaid fees policy, {} fees are overdue",
+                        store_id, store_billing_status.state, store_billing_status.overdue_fees_count
+                    );
+
+                    let event = Event::new(EventPayload::StoreBillingStatusChanged { store_id });
+                    event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+
+                    changed.push(store_billing_status);
+                }
+            }
+
+            Ok(changed)
+        })
+    })
+}
+
+/// Suspends the store when its fee dunning reaches the suspension step, or reinstates it when the dunning that suspended it
+/// is resolved. A store overridden by a financial manager is left alone. Returns the status if its state has changed
+pub fn apply_fee_dunning_suspension(
+    store_billing_statuses_repo: &StoreBillingStatusesRepo,
+    event_store_repo: &EventStoreRepo,
+    store_id: StoreId,
+    suspended: bool,
+    now: NaiveDateTime,
+) -> ServiceResultV2<Option<StoreBillingStatus>> {
+    let current = store_billing_statuses_repo.get(store_id).map_err(ectx!(try convert => store_id))?;
+    if current.as_ref().map_or(false, |current| current.is_overridden(now)) {
+        return Ok(None);
+    }
+
+    let mut update = if suspended {
+        UpdateStoreBillingStatus {
+            state: Some(StoreBillingState::Suspended),
+            suspension_reason: Some(Some(StoreSuspensionReason::UnpaidFees)),
+            ..Default::default()
+        }
+    } else {
+        let is_suspended_for_fees = current.as_ref().map_or(false, |current| {
+            current.state == StoreBillingState::Suspended && current.suspension_reason == Some(StoreSuspensionReason::UnpaidFees)
+        });
+        if !is_suspended_for_fees {
+            return Ok(None);
+        }
+
+        UpdateStoreBillingStatus {
+            state: Some(StoreBillingState::Active),
+            suspension_reason: Some(None),
+            ..Default::default()
+        }
+    };
+    if current.map_or(false, |current| current.overridden_by.is_some()) {
+        update.overridden_by = Some(None);
+        update.override_until = Some(None);
+    }
+
+    let (store_billing_status, state_changed) = save_store_billing_status(store_billing_statuses_repo, store_id, update)?;
+    if !state_changed {
+        return Ok(None);
+    }
+
+    info!("Store {} is {} by its fee dunning", store_id, store_billing_status.state);
+    let event = Event::new(EventPayload::StoreBillingStatusChanged { store_id });
+    event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+
+    Ok(Some(store_billing_status))
+}
+
+/// Creates the status of a store on its first change, a new store is active unless the change says otherwise.
+/// Returns the saved status and whether its state has changed
+fn save_store_billing_status(
+    store_billing_statuses_repo: &StoreBillingStatusesRepo,
+    store_id: StoreId,
+    update: UpdateStoreBillingStatus,
+) -> ServiceResultV2<(StoreBillingStatus, bool)> {
+    let current = store_billing_statuses_repo.get(store_id).map_err(ectx!(try convert => store_id))?;
+
+    match current {
+        Some(current) => {
+            let store_billing_status = store_billing_statuses_repo
+                .update(store_id, update)
+                .map_err(ectx!(try convert => store_id))?;
+            let state_changed = store_billing_status.state != current.state;
+
+            Ok((store_billing_status, state_changed))
+        }
+        None => {
+            let new_store_billing_status = NewStoreBillingStatus {
+                store_id,
+                state: update.state.unwrap_or(StoreBillingState::Active),
+                suspension_reason: update.suspension_reason.unwrap_or(None),
+                overdue_fees_count: update.overdue_fees_count.unwrap_or(0),
+                unpaid_since: update.unpaid_since.unwrap_or(None),
+                overridden_by: update.overridden_by.unwrap_or(None),
+                override_until: update.override_until.unwrap_or(None),
+                evaluated_at: update.evaluated_at.unwrap_or(None),
+            };
+            let store_billing_status = store_billing_statuses_repo
+                .create(new_store_billing_status.clone())
+                .map_err(ectx!(try convert => new_store_billing_status))?;
+            let state_changed = store_billing_status.state != StoreBillingState::Active;
+
+            Ok((store_billing_status, state_changed))
+        }
+    }
+}
+
+/// Unpaid fees of a store as seen by the policy
+#[derive(Debug, Default)]
+struct UnpaidFees {
+    overdue_fees_count: usize,
+    unpaid_since: Option<NaiveDateTime>,
+    amounts: HashMap<Currency, Amount>,
+}
+
+impl UnpaidFees {
+    fn new(fees: &[Fee], config: &StoreBillingSuspensionConfig, now: NaiveDateTime) -> Self {
+        let overdue_at = now - Duration::days(config.grace_period_days);
+
+        let mut unpaid_fees = UnpaidFees::default();
+        for fee in fees {
+            if fee.created_at <= overdue_at {
+                unpaid_fees.overdue_fees_count += 1;
+            }
+
+            unpaid_fees.unpaid_since = Some(match unpaid_fees.unpaid_since {
+                Some(unpaid_since) if unpaid_since <= fee.created_at => unpaid_since,
+                _ => fee.created_at,
+            });
+
+            let amount = unpaid_fees.amounts.entry(fee.currency).or_insert(Amount::zero());
+            *amount = amount.checked_add(fee.amount).unwrap_or(*amount);
+        }
+
+        unpaid_fees
+    }
+
+    fn exceed_limits(&self, config: &StoreBillingSuspensionConfig) -> bool {
+        let too_many_overdue = self.overdue_fees_count > 0 && self.overdue_fees_count >= config.max_overdue_fees as usize;
+
+        let too_much_unpaid = self.amounts.iter().any(|(currency, amount)| {
+            config
+                .max_unpaid_amounts
+                .get(currency)
+                .filter(|max_unpaid_amount| max_unpaid_amount.is_finite())
+                .and_then(|max_unpaid_amount| Amount::checked_from_super_unit(*currency, BigDecimal::from(*max_unpaid_amount)))
+                .map_or(false, |max_unpaid_amount| *amount >= max_unpaid_amount)
+        });
+
+        too_many_overdue || too_much_unpaid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_support::FeeBuilder;
+
+    fn config() -> StoreBillingSuspensionConfig {
+        let mut max_unpaid_amounts = HashMap::new();
+        max_unpaid_amounts.insert(Currency::Eur, 100.0);
+
+        StoreBillingSuspensionConfig {
+            enabled: true,
+            evaluation_interval_sec: 3600,
+            grace_period_days: 30,
+            max_overdue_fees: 2,
+            max_unpaid_amounts,
+            reinstatement_grace_days: 14,
+        }
+    }
+
+    #[test]
+    fn stores_are_suspended_for_repeatedly_overdue_fees() {
+        let now = Utc::now().naive_utc();
+        let overdue_fee = FeeBuilder::new()
+            .currency(Currency::Eur)
+            .amount(Amount::new(1000))
+            .created_at(now - Duration::days(31))
+            .build();
+        let recent_fee = FeeBuilder::new()
+            .currency(Currency::Eur)
+            .amount(Amount::new(1000))
+            .created_at(now - Duration::days(1))
+            .build();
+
+        let unpaid_fees = UnpaidFees::new(&[overdue_fee.clone(), recent_fee.clone()], &config(), now);
+        assert_eq!(unpaid_fees.overdue_fees_count, 1);
+        assert_eq!(unpaid_fees.unpaid_since, Some(overdue_fee.created_at));
+        assert!(!unpaid_fees.exceed_limits(&config()));
+
+        let unpaid_fees = UnpaidFees::new(&[overdue_fee.clone(), overdue_fee, recent_fee], &config(), now);
+        assert!(unpaid_fees.exceed_limits(&config()));
+    }
+
+    #[test]
+    fn stores_are_suspended_for_large_unpaid_fees_regardless_of_their_age() {
+        let now = Utc::now().naive_utc();
+        let large_fee = FeeBuilder::new()
+            .currency(Currency::Eur)
+            .amount(Amount::new(10000))
+            .created_at(now)
+            .build();
+        let stq_fee = FeeBuilder::new()
+            .currency(Currency::Stq)
+            .amount(Amount::new(1_000_000_000_000_000_000_000))
+            .created_at(now)
+            .build();
+
+        assert!(UnpaidFees::new(&[large_fee], &config(), now).exceed_limits(&config()));
+        assert!(!UnpaidFees::new(&[stq_fee], &config(), now).exceed_limits(&config()));
+        assert!(!UnpaidFees::new(&[], &config(), now).exceed_limits(&config()));
+    }
+}
@@ -0,0 +1,444 @@
+//! StoreOffboardingService closes down the billing of a store deleted upstream. Saga deletes the merchant of the store,
+//! which cancels its subscription, writes off its unpaid fees, schedules the final payout of its balance and archives its billing info.
+//! Every step is taken in a transaction of its own and is skipped once taken, a failed offboarding is resumed by deleting the store again
+use chrono::{NaiveDateTime, Utc};
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use enum_iterator::IntoEnumIterator;
+use failure::{err_msg, Fail};
+use futures::future;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use serde_json;
+
+use stq_types::{StoreId, UserId};
+
+use super::types::{ServiceFutureV2, ServiceResultV2};
+use controller::responses::StoreOffboardingResponse;
+use models::order_v2::{RawOrder, StoreId as StoreIdV2};
+use models::{
+    AccountStatus, Amount, BillingInfoChangeReview, BillingInfoChangeStatus, Currency, Fee, FeeStatus, FinalPayout, NewStoreOffboarding,
+    Payout, StoreOffboarding, StoreOffboardingStatus, StoreSubscriptionSearch, StoreSubscriptionStatus, UpdateFee, UpdateStoreOffboarding,
+    UpdateStoreSubscription, WrittenOffFee,
+};
+use repos::{
+    AccountsRepo, BillingInfoChangesRepo, EventStoreRepo, FeeDunningsRepo, FeeRepo, OrdersRepo, PayoutsRepo, ReposFactory,
+    StoreBillingStatusesRepo, StoreOffboardingsRepo, StoreSubscriptionRepo,
+};
+use services::fee_dunning::{resolve_paid_fee_dunning, store_unpaid_fees};
+use services::payout::{get_store_payouts, get_unpaid_orders, outstanding_balance_fees};
+use services::types::spawn_on_pool;
+use services::{Error, ErrorContext, ErrorKind};
+
+pub trait StoreOffboardingService {
+    /// Offboards a store deleted upstream, an offboarding that has failed is resumed from the step it has failed at
+    fn offboard_store(&self, store_id: StoreId) -> ServiceFutureV2<StoreOffboardingResponse>;
+    /// Progress of the offboarding of a store
+    fn get_store_offboarding(&self, store_id: StoreId) -> ServiceFutureV2<Option<StoreOffboardingResponse>>;
+}
+
+pub struct StoreOffboardingServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > StoreOffboardingService for StoreOffboardingServiceImpl<T, M, F>
+{
+    fn offboard_store(&self, store_id: StoreId) -> ServiceFutureV2<StoreOffboardingResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        let requested_by = match user_id {
+            Some(user_id) => user_id,
+            None => return Box::new(future::err(ectx!(err ErrorContext::Unauthorized, ErrorKind::Forbidden))),
+        };
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let store_offboardings_repo = repo_factory.create_store_offboardings_repo(&conn, user_id);
+            let store_subscription_repo = repo_factory.create_store_subscription_repo(&conn, user_id);
+            let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
+            let fees_repo = repo_factory.create_fees_repo_with_sys_acl(&conn);
+            let orders_repo = repo_factory.create_orders_repo_with_sys_acl(&conn);
+            let payouts_repo = repo_factory.create_payouts_repo_with_sys_acl(&conn);
+            let fee_dunnings_repo = repo_factory.create_fee_dunnings_repo_with_sys_acl(&conn);
+            let store_billing_statuses_repo = repo_factory.create_store_billing_statuses_repo_with_sys_acl(&conn);
+            let billing_info_changes_repo = repo_factory.create_billing_info_changes_repo_with_sys_acl(&conn);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+            let repos = StoreOffboardingRepos {
+                store_offboardings_repo: &*store_offboardings_repo,
+                store_subscription_repo: &*store_subscription_repo,
+                accounts_repo: &*accounts_repo,
+                fees_repo: &*fees_repo,
+                orders_repo: &*orders_repo,
+                payouts_repo: &*payouts_repo,
+                fee_dunnings_repo: &*fee_dunnings_repo,
+                store_billing_statuses_repo: &*store_billing_statuses_repo,
+                billing_info_changes_repo: &*billing_info_changes_repo,
+                event_store_repo: &*event_store_repo,
+            };
+
+            offboard_store(&*conn, &repos, store_id, requested_by, Utc::now().naive_utc())
+                .and_then(StoreOffboardingResponse::try_from_store_offboarding)
+        })
+    }
+
+    fn get_store_offboarding(&self, store_id: StoreId) -> ServiceFutureV2<Option<StoreOffboardingResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let store_offboardings_repo = repo_factory.create_store_offboardings_repo(&conn, user_id);
+
+            match store_offboardings_repo.get(store_id).map_err(ectx!(try convert => store_id))? {
+                Some(offboarding) => StoreOffboardingResponse::try_from_store_offboarding(offboarding).map(Some),
+                None => Ok(None),
+            }
+        })
+    }
+}
+
+/// Repos the offboarding reads and writes. All of them but the offboardings and store subscriptions repos have to use the system ACL
+pub struct StoreOffboardingRepos<'a> {
+    pub store_offboardings_repo: &'a StoreOffboardingsRepo,
+    pub store_subscription_repo: &'a StoreSubscriptionRepo,
+    pub accounts_repo: &'a AccountsRepo,
+    pub fees_repo: &'a FeeRepo,
+    pub orders_repo: &'a OrdersRepo,
+    pub payouts_repo: &'a PayoutsRepo,
+    pub fee_dunnings_repo: &'a FeeDunningsRepo,
+    pub store_billing_statuses_repo: &'a StoreBillingStatusesRepo,
+    pub billing_info_changes_repo: &'a BillingInfoChangesRepo,
+    pub event_store_repo: &'a EventStoreRepo,
+}
+
+/// Takes the steps of the offboarding that haven't been taken yet, in order
+fn offboard_store<T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static>(
+    conn: &T,
+    repos: &StoreOffboardingRepos,
+    store_id: StoreId,
+    requested_by: UserId,
+    now: NaiveDateTime,
+) -> ServiceResultV2<StoreOffboarding> {
+    let store_offboardings_repo = repos.store_offboardings_repo;
+
+    let new_offboarding = NewStoreOffboarding {
+        store_id,
+        status: StoreOffboardingStatus::InProgress,
+        requested_by,
+    };
+    let mut offboarding = store_offboardings_repo
+        .get_or_create(new_offboarding.clone())
+        .map_err(ectx!(try convert => new_offboarding))?;
+
+    if offboarding.status == StoreOffboardingStatus::Completed {
+        return Ok(offboarding);
+    }
+
+    if offboarding.subscription_cancelled_at.is_none() {
+        offboarding = conn.transaction::<_, Error, _>(|| cancel_subscription(repos, store_id, now))?;
+    }
+
+    if offboarding.fees_written_off_at.is_none() {
+        offboarding = conn.transaction::<_, Error, _>(|| write_off_fees(repos, store_id, now))?;
+    }
+
+    if offboarding.final_payout_scheduled_at.is_none() {
+        offboarding = conn.transaction::<_, Error, _>(|| schedule_final_payout(repos, store_id, now))?;
+    }
+
+    if offboarding.billing_info_archived_at.is_none() {
+        conn.transaction::<_, Error, _>(|| archive_billing_info(repos, store_id, requested_by, now))?;
+    }
+
+    info!("Store {} is offboarded", store_id);
+
+    let update = UpdateStoreOffboarding {
+        status: Some(StoreOffboardingStatus::Completed),
+        completed_at: Some(Some(now)),
+        ..Default::default()
+    };
+    store_offboardings_repo.update(store_id, update).map_err(ectx!(convert => store_id))
+}
+
+/// Cancels the subscription of the store and archives the account its crypto subscription was paid from,
+/// so that it is neither charged nor handed out anymore. Funds left on the account stay there
+fn cancel_subscription(repos: &StoreOffboardingRepos, store_id: StoreId, now: NaiveDateTime) -> ServiceResultV2<StoreOffboarding> {
+    let StoreOffboardingRepos {
+        store_offboardings_repo,
+        store_subscription_repo,
+        accounts_repo,
+        ..
+    } = *repos;
+
+    let by_store_id = StoreSubscriptionSearch::by_store_id(store_id);
+    let store_subscription = store_subscription_repo
+        .get(by_store_id.clone())
+        .map_err(ectx!(try convert => store_id))?;
+
+    let mut subscription_account_id = None;
+    if let Some(store_subscription) = store_subscription {
+        if store_subscription.status != StoreSubscriptionStatus::Cancelled {
+            let update = UpdateStoreSubscription {
+                status: Some(StoreSubscriptionStatus::Cancelled),
+                ..Default::default()
+            };
+            store_subscription_repo
+                .update(by_store_id, update)
+                .map_err(ectx!(try convert => store_id))?;
+        }
+
+        if let Some(wallet_address) = store_subscription.wallet_address {
+            let account = accounts_repo
+                .get_by_wallet_address(wallet_address.clone())
+                .map_err(ectx!(try convert => wallet_address))?;
+            if let Some(account) = account {
+                let account_id = account.id;
+                if account.status != AccountStatus::Archived {
+                    accounts_repo
+                        .set_status(account_id, AccountStatus::Archived)
+                        .map_err(ectx!(try convert => account_id))?;
+                }
+                subscription_account_id = Some(account_id);
+            }
+        }
+    }
+
+    let update = UpdateStoreOffboarding {
+        subscription_cancelled_at: Some(Some(now)),
+        subscription_account_id: Some(subscription_account_id),
+        ..Default::default()
+    };
+    store_offboardings_repo.update(store_id, update).map_err(ectx!(convert => store_id))
+}
+
+/// Writes off the fees the store hasn't paid and resolves its fee dunning, the store is not going to pay them anymore
+fn write_off_fees(repos: &StoreOffboardingRepos, store_id: StoreId, now: NaiveDateTime) -> ServiceResultV2<StoreOffboarding> {
+    let StoreOffboardingRepos {
+        store_offboardings_repo,
+        fees_repo,
+        orders_repo,
+        fee_dunnings_repo,
+        store_billing_statuses_repo,
+        event_store_repo,
+        ..
+    } = *repos;
+
+    let unpaid_fees = store_unpaid_fees(fees_repo, orders_repo, store_id)?;
+    for fee in &unpaid_fees {
+        let update = UpdateFee {
+            status: Some(FeeStatus::WrittenOff),
+            ..Default::default()
+        };
+        fees_repo.update(fee.id, update).map_err(ectx!(try convert => fee.id))?;
+    }
+
+    resolve_paid_fee_dunning(
+        fee_dunnings_repo,
+        fees_repo,
+        orders_repo,
+        store_billing_statuses_repo,
+        event_store_repo,
+        store_id,
+        now,
+    )?;
+
+    let written_off_fees = unpaid_fees.iter().map(written_off_fee).collect::<Vec<_>>();
+    if !written_off_fees.is_empty() {
+        info!("Wrote off {} unpaid fees of store {}", written_off_fees.len(), store_id);
+    }
+
+    let written_off_fees = serde_json::to_value(written_off_fees).map_err(|e| ectx!(try err e, ErrorKind::Internal))?;
+    let update = UpdateStoreOffboarding {
+        fees_written_off_at: Some(Some(now)),
+        written_off_fees: Some(written_off_fees),
+        ..Default::default()
+    };
+    store_offboardings_repo.update(store_id, update).map_err(ectx!(convert => store_id))
+}
+
+fn written_off_fee(fee: &Fee) -> WrittenOffFee {
+    WrittenOffFee {
+        fee_id: fee.id,
+        order_id: fee.order_id,
+        currency: fee.currency,
+        amount: fee.amount,
+    }
+}
+
+/// Records the remaining balance of the store by currency as its final payout, saga pays it out like any other payout
+fn schedule_final_payout(repos: &StoreOffboardingRepos, store_id: StoreId, now: NaiveDateTime) -> ServiceResultV2<StoreOffboarding> {
+    let StoreOffboardingRepos {
+        store_offboardings_repo,
+        orders_repo,
+        payouts_repo,
+        ..
+    } = *repos;
+
+    let store_id_v2 = StoreIdV2::new(store_id.0);
+    let unpaid_orders = get_unpaid_orders(orders_repo, payouts_repo, store_id_v2, None)?;
+    let payouts = get_store_payouts(orders_repo, payouts_repo, &[store_id_v2])?;
+
+    let final_payouts = final_payouts(&unpaid_orders, &payouts)?;
+    for final_payout in &final_payouts {
+        info!(
+            "Final payout of store {} in {} is {} for {} orders",
+            store_id,
+            final_payout.currency,
+            final_payout.amount,
+            final_payout.order_ids.len()
+        );
+    }
+
+    let final_payouts = serde_json::to_value(final_payouts).map_err(|e| ectx!(try err e, ErrorKind::Internal))?;
+    let update = UpdateStoreOffboarding {
+        final_payout_scheduled_at: Some(Some(now)),
+        final_payouts: Some(final_payouts),
+        ..Default::default()
+    };
+    store_offboardings_repo.update(store_id, update).map_err(ectx!(convert => store_id))
+}
+
+/// Remaining balance of the store in every currency it has orders awaiting payout or outstanding fees in.
+/// The outstanding fees are settled from the orders as far as they cover them
+pub fn final_payouts(unpaid_orders: &[RawOrder], payouts: &[Payout]) -> ServiceResultV2<Vec<FinalPayout>> {
+    let overflow = || {
+        let e = err_msg("Overflow while calculating the final payout of a store");
+        ectx!(err e, ErrorKind::Internal)
+    };
+
+    let mut final_payouts = Vec::new();
+    for currency in Currency::into_enum_iter() {
+        let orders = unpaid_orders
+            .iter()
+            .filter(|order| order.seller_currency == currency)
+            .collect::<Vec<_>>();
+        let gross_amount = orders
+            .iter()
+            .try_fold(Amount::zero(), |acc, order| acc.checked_add(order.total_amount))
+            .ok_or_else(overflow)?;
+        let outstanding_fees = outstanding_balance_fees(payouts, currency)?;
+
+        if gross_amount == Amount::zero() && outstanding_fees == Amount::zero() {
+            continue;
+        }
+
+        let settled_fees = if outstanding_fees < gross_amount {
+            outstanding_fees
+        } else {
+            gross_amount
+        };
+        final_payouts.push(FinalPayout {
+            currency,
+            gross_amount,
+            settled_fees,
+            amount: gross_amount.checked_sub(settled_fees).ok_or_else(overflow)?,
+            order_ids: orders.iter().map(|order| order.id).collect(),
+        });
+    }
+
+    Ok(final_payouts)
+}
+
+/// Archives the billing info of the store, a change of it waiting for approval is rejected.
+/// The billing info is kept for the final payout until its retention period is over
+fn archive_billing_info(
+    repos: &StoreOffboardingRepos,
+    store_id: StoreId,
+    requested_by: UserId,
+    now: NaiveDateTime,
+) -> ServiceResultV2<StoreOffboarding> {
+    let StoreOffboardingRepos {
+        store_offboardings_repo,
+        billing_info_changes_repo,
+        ..
+    } = *repos;
+
+    let pending_change = billing_info_changes_repo
+        .get_pending_by_store_id(store_id)
+        .map_err(ectx!(try convert => store_id))?;
+    if let Some(pending_change) = pending_change {
+        let id = pending_change.id;
+        let review = BillingInfoChangeReview {
+            status: BillingInfoChangeStatus::Rejected,
+            reviewed_by: requested_by,
+            rejection_reason: Some("The store is closed".to_string()),
+        };
+        billing_info_changes_repo.review(id, review).map_err(ectx!(try convert => id))?;
+    }
+
+    let update = UpdateStoreOffboarding {
+        billing_info_archived_at: Some(Some(now)),
+        ..Default::default()
+    };
+    store_offboardings_repo.update(store_id, update).map_err(ectx!(convert => store_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use models::*;
+    use test_support::{PayoutBuilder, RawOrderBuilder};
+
+    use super::final_payouts;
+
+    #[test]
+    fn final_payouts_settle_outstanding_fees_from_unpaid_orders() {
+        let orders = vec![
+            RawOrderBuilder::payable(Currency::Stq, Amount::new(300)).build(),
+            RawOrderBuilder::payable(Currency::Stq, Amount::new(200)).build(),
+            RawOrderBuilder::payable(Currency::Eur, Amount::new(1000)).build(),
+        ];
+        let payouts = vec![
+            PayoutBuilder::new()
+                .crypto_wallet(TureCurrency::Stq, Amount::new(50))
+                .fee_payer(PayoutFeePayer::Balance)
+                .build(),
+            PayoutBuilder::new()
+                .crypto_wallet(TureCurrency::Eth, Amount::new(20))
+                .fee_payer(PayoutFeePayer::Balance)
+                .build(),
+        ];
+
+        let final_payouts = final_payouts(&orders, &payouts).unwrap();
+        let final_payout = |currency| final_payouts.iter().find(|payout| payout.currency == currency).cloned();
+
+        assert_eq!(final_payouts.len(), 3);
+
+        let stq = final_payout(Currency::Stq).unwrap();
+        assert_eq!(stq.gross_amount, Amount::new(500));
+        assert_eq!(stq.settled_fees, Amount::new(50));
+        assert_eq!(stq.amount, Amount::new(450));
+        assert_eq!(stq.order_ids, vec![orders[0].id, orders[1].id]);
+
+        let eur = final_payout(Currency::Eur).unwrap();
+        assert_eq!(eur.amount, Amount::new(1000));
+        assert_eq!(eur.settled_fees, Amount::zero());
+
+        // fees exceeding the orders are not collected
+        let eth = final_payout(Currency::Eth).unwrap();
+        assert_eq!(eth.gross_amount, Amount::zero());
+        assert_eq!(eth.settled_fees, Amount::zero());
+        assert_eq!(eth.amount, Amount::zero());
+        assert!(eth.order_ids.is_empty());
+    }
+
+    #[test]
+    fn final_payouts_skip_settled_currencies() {
+        assert!(final_payouts(&[], &[]).unwrap().is_empty());
+    }
+}
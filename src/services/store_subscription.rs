@@ -16,22 +16,31 @@ use super::types::ServiceFutureV2;
 use client::payments::PaymentsClient;
 use config::Subscription as SubscriptionConfig;
 use controller::context::DynamicContext;
-use controller::requests::{CreateStoreSubscriptionRequest, UpdateStoreSubscriptionRequest};
+use controller::requests::{CreateStoreSubscriptionRequest, ExtendTrialRequest, UpdateStoreSubscriptionRequest};
 use controller::responses::StoreSubscriptionResponse;
 use models::{
-    Amount, CreateStoreSubscription, Currency, NewStoreSubscription, StoreSubscriptionSearch, TureCurrency, UpdateStoreSubscription,
+    Amount, CreateStoreSubscription, Currency, NewStoreSubscription, StoreSubscription, StoreSubscriptionSearch, StoreSubscriptionStatus,
+    TureCurrency, UpdateStoreSubscription,
 };
 use repos::repo_factory::ReposFactory;
+use repos::StoreSubscriptionRepo;
 use services::accounts::AccountService;
+use services::subscription::find_update_or_create_store_subscription;
 use services::subscription::DEFAULT_EUR_CENTS_AMOUNT;
 use services::subscription::DEFAULT_STQ_WEI_AMOUNT;
 use services::types::spawn_on_pool;
-use services::ErrorKind;
+use services::{Error, ErrorKind};
 
 pub trait StoreSubscriptionService {
     fn create(&self, store_id: StoreId, payload: CreateStoreSubscriptionRequest) -> ServiceFutureV2<StoreSubscriptionResponse>;
     fn get(&self, store_id: StoreId) -> ServiceFutureV2<Option<StoreSubscriptionResponse>>;
     fn update(&self, store_id: StoreId, payload: UpdateStoreSubscriptionRequest) -> ServiceFutureV2<StoreSubscriptionResponse>;
+    /// Starts the trial of the store when it is first published, does nothing if the trial has already started
+    fn start_trial(&self, store_id: StoreId) -> ServiceFutureV2<StoreSubscriptionResponse>;
+    /// Moves the end of an ongoing trial by `days`
+    fn extend_trial(&self, store_id: StoreId, payload: ExtendTrialRequest) -> ServiceFutureV2<StoreSubscriptionResponse>;
+    /// Ends an ongoing trial right away, the store is billed from now on
+    fn end_trial(&self, store_id: StoreId) -> ServiceFutureV2<StoreSubscriptionResponse>;
 }
 
 pub struct StoreSubscriptionServiceImpl<
@@ -99,17 +108,7 @@ impl<
 
                 let result = store_subscription_repo.create(new_store_subscription).map_err(ectx!(try convert))?;
 
-                Ok(StoreSubscriptionResponse {
-                    store_id: result.store_id,
-                    currency: result.currency.into(),
-                    value: result.value.to_super_unit(result.currency),
-                    wallet_address: result.wallet_address,
-                    trial_start_date: result.trial_start_date,
-                    trial_end_date: result.trial_start_date.map(|date| date + max_trial_duration),
-                    created_at: result.created_at,
-                    updated_at: result.updated_at,
-                    status: result.status,
-                })
+                Ok(store_subscription_response(result, max_trial_duration))
             })
         });
 
@@ -132,17 +131,7 @@ impl<
                 .get(StoreSubscriptionSearch::by_store_id(store_id))
                 .map_err(ectx!(try convert))?;
 
-            Ok(result.map(|result| StoreSubscriptionResponse {
-                store_id: result.store_id,
-                currency: result.currency.into(),
-                value: result.value.to_super_unit(result.currency),
-                wallet_address: result.wallet_address,
-                trial_start_date: result.trial_start_date,
-                trial_end_date: result.trial_start_date.map(|date| date + max_trial_duration),
-                created_at: result.created_at,
-                updated_at: result.updated_at,
-                status: result.status,
-            }))
+            Ok(result.map(|result| store_subscription_response(result, max_trial_duration)))
         })
     }
 
@@ -230,23 +219,140 @@ impl<
                     let result = store_subscription_repo
                         .update(by_store_id, store_subscription)
                         .map_err(ectx!(try convert))?;
-                    Ok(StoreSubscriptionResponse {
-                        store_id: result.store_id,
-                        currency: result.currency.into(),
-                        value: result.value.to_super_unit(result.currency),
-                        wallet_address: result.wallet_address,
-                        trial_start_date: result.trial_start_date,
-                        trial_end_date: result.trial_start_date.map(|date| date + max_trial_duration),
-                        created_at: result.created_at,
-                        updated_at: result.updated_at,
-                        status: result.status,
-                    })
+                    Ok(store_subscription_response(result, max_trial_duration))
                 })
             }
         });
 
         Box::new(fut)
     }
+
+    fn start_trial(&self, store_id: StoreId) -> ServiceFutureV2<StoreSubscriptionResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        let now = chrono::offset::Utc::now().naive_utc();
+        let max_trial_duration = Duration::days(self.config.trial_time_duration_days);
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let store_subscription_repo = repo_factory.create_store_subscription_repo(&conn, user_id);
+
+            conn.transaction(|| {
+                find_update_or_create_store_subscription(&*store_subscription_repo, store_id, now)
+                    .map(|result| store_subscription_response(result, max_trial_duration))
+                    .map_err(ectx!(convert => store_id))
+            })
+        })
+    }
+
+    fn extend_trial(&self, store_id: StoreId, payload: ExtendTrialRequest) -> ServiceFutureV2<StoreSubscriptionResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        let max_trial_duration = Duration::days(self.config.trial_time_duration_days);
+
+        if payload.days <= 0 {
+            let e = format_err!("Trial can only be extended by a positive number of days");
+            return Box::new(futures::future::err(ectx!(err e, ErrorKind::Validation(serde_json::json!({
+                "days": payload.days,
+            })))));
+        }
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let store_subscription_repo = repo_factory.create_store_subscription_repo(&conn, user_id);
+            let by_store_id = StoreSubscriptionSearch::by_store_id(store_id);
+
+            conn.transaction(move || {
+                let store_subscription = get_ongoing_trial(&*store_subscription_repo, store_id)?;
+                let trial_end_date = store_subscription.trial_end_date(max_trial_duration).ok_or_else(|| {
+                    let e = format_err!("Store {} has empty trial start time", store_id);
+                    ectx!(try err e, ErrorKind::Internal)
+                })?;
+
+                let update = UpdateStoreSubscription {
+                    trial_end_date: Some(trial_end_date + Duration::days(payload.days)),
+                    ..Default::default()
+                };
+                let result = store_subscription_repo
+                    .update(by_store_id, update)
+                    .map_err(ectx!(try convert => store_id))?;
+
+                Ok(store_subscription_response(result, max_trial_duration))
+            })
+        })
+    }
+
+    fn end_trial(&self, store_id: StoreId) -> ServiceFutureV2<StoreSubscriptionResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        let now = chrono::offset::Utc::now().naive_utc();
+        let max_trial_duration = Duration::days(self.config.trial_time_duration_days);
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let store_subscription_repo = repo_factory.create_store_subscription_repo(&conn, user_id);
+            let by_store_id = StoreSubscriptionSearch::by_store_id(store_id);
+
+            conn.transaction(move || {
+                let store_subscription = get_ongoing_trial(&*store_subscription_repo, store_id)?;
+
+                let update = UpdateStoreSubscription {
+                    trial_start_date: store_subscription.trial_start_date.or(Some(now)),
+                    trial_end_date: Some(now),
+                    status: Some(StoreSubscriptionStatus::Paid),
+                    ..Default::default()
+                };
+                let result = store_subscription_repo
+                    .update(by_store_id, update)
+                    .map_err(ectx!(try convert => store_id))?;
+
+                Ok(store_subscription_response(result, max_trial_duration))
+            })
+        })
+    }
+}
+
+/// Subscription of the store that is still on trial
+fn get_ongoing_trial(store_subscription_repo: &StoreSubscriptionRepo, store_id: StoreId) -> Result<StoreSubscription, Error> {
+    let store_subscription = store_subscription_repo
+        .get(StoreSubscriptionSearch::by_store_id(store_id))
+        .map_err(ectx!(try convert => store_id))?
+        .ok_or({
+            let e = format_err!("Store subscription not found");
+            ectx!(err e, ErrorKind::NotFound)
+        })?;
+
+    if store_subscription.status != StoreSubscriptionStatus::Trial {
+        let e = format_err!("Store {} is not on trial", store_id);
+        return Err(ectx!(err e, ErrorKind::Validation(serde_json::json!({
+            "status": store_subscription.status,
+        }))));
+    }
+
+    Ok(store_subscription)
+}
+
+fn store_subscription_response(store_subscription: StoreSubscription, max_trial_duration: Duration) -> StoreSubscriptionResponse {
+    StoreSubscriptionResponse {
+        trial_end_date: store_subscription.trial_end_date(max_trial_duration),
+        store_id: store_subscription.store_id,
+        currency: store_subscription.currency.into(),
+        value: store_subscription.value.to_super_unit(store_subscription.currency),
+        wallet_address: store_subscription.wallet_address,
+        trial_start_date: store_subscription.trial_start_date,
+        created_at: store_subscription.created_at,
+        updated_at: store_subscription.updated_at,
+        status: store_subscription.status,
+    }
 }
 
 fn create_store_subscription_account<AS: AccountService>(account_service: AS, store_id: StoreId) -> ServiceFutureV2<NewStoreSubscription> {
@@ -0,0 +1,269 @@
+//! StoreWebhookService manages store subscriptions to billing event notifications
+use std::collections::BTreeMap;
+
+use chrono::Utc;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use futures::future;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use serde_json;
+use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
+
+use failure::Fail;
+
+use stq_types::{StoreId, UserId};
+
+use super::types::ServiceFutureV2;
+use controller::requests::{CreateStoreWebhookRequest, UpdateStoreWebhookRequest};
+use controller::responses::{FeeResponse, StoreWebhookResponse};
+use models::order_v2::RawOrder;
+use models::{
+    Event, EventPayload, Fee, NewStoreWebhook, Payout, StoreWebhookEventType, StoreWebhookId, StoreWebhookNotification, UpdateStoreWebhook,
+};
+use repos::{EventStoreRepo, OrdersRepo, ReposFactory, StoreWebhooksRepo};
+use services::types::spawn_on_pool;
+use services::{Error, ErrorContext, ErrorKind};
+
+pub trait StoreWebhookService {
+    /// Subscribes a store to billing event notifications
+    fn create_store_webhook(&self, store_id: StoreId, payload: CreateStoreWebhookRequest) -> ServiceFutureV2<StoreWebhookResponse>;
+    /// Lists webhooks of a store
+    fn get_store_webhooks(&self, store_id: StoreId) -> ServiceFutureV2<Vec<StoreWebhookResponse>>;
+    /// Updates webhook url, secret or event filter
+    fn update_store_webhook(&self, id: StoreWebhookId, payload: UpdateStoreWebhookRequest) -> ServiceFutureV2<StoreWebhookResponse>;
+    /// Removes a webhook, pending deliveries are dropped
+    fn delete_store_webhook(&self, id: StoreWebhookId) -> ServiceFutureV2<StoreWebhookResponse>;
+}
+
+pub struct StoreWebhookServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > StoreWebhookService for StoreWebhookServiceImpl<T, M, F>
+{
+    fn create_store_webhook(&self, store_id: StoreId, payload: CreateStoreWebhookRequest) -> ServiceFutureV2<StoreWebhookResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        let CreateStoreWebhookRequest { url, secret, event_types } = payload;
+
+        if let Err(e) = validate_url(&url) {
+            return Box::new(future::err(e));
+        }
+
+        let new_store_webhook = NewStoreWebhook {
+            store_id,
+            url,
+            secret: secret.unwrap_or_else(|| Uuid::new_v4().simple().to_string()),
+            event_types: serde_json::to_value(event_types).unwrap_or(json!([])),
+        };
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let store_webhooks_repo = repo_factory.create_store_webhooks_repo(&conn, user_id);
+
+            store_webhooks_repo
+                .create(new_store_webhook.clone())
+                .map_err(ectx!(try convert => new_store_webhook))
+                .and_then(StoreWebhookResponse::try_from_store_webhook)
+        })
+    }
+
+    fn get_store_webhooks(&self, store_id: StoreId) -> ServiceFutureV2<Vec<StoreWebhookResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let store_webhooks_repo = repo_factory.create_store_webhooks_repo(&conn, user_id);
+
+            store_webhooks_repo
+                .list_by_store_id(store_id)
+                .map_err(ectx!(try convert => store_id))?
+                .into_iter()
+                .map(StoreWebhookResponse::try_from_store_webhook)
+                .collect()
+        })
+    }
+
+    fn update_store_webhook(&self, id: StoreWebhookId, payload: UpdateStoreWebhookRequest) -> ServiceFutureV2<StoreWebhookResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        let UpdateStoreWebhookRequest { url, secret, event_types } = payload;
+
+        if let Some(Err(e)) = url.as_ref().map(|url| validate_url(url)) {
+            return Box::new(future::err(e));
+        }
+
+        let update_store_webhook = UpdateStoreWebhook {
+            url,
+            secret,
+            event_types: event_types.map(|event_types| serde_json::to_value(event_types).unwrap_or(json!([]))),
+        };
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let store_webhooks_repo = repo_factory.create_store_webhooks_repo(&conn, user_id);
+
+            store_webhooks_repo
+                .update(id, update_store_webhook)
+                .map_err(ectx!(try convert => id))
+                .and_then(StoreWebhookResponse::try_from_store_webhook)
+        })
+    }
+
+    fn delete_store_webhook(&self, id: StoreWebhookId) -> ServiceFutureV2<StoreWebhookResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let store_webhooks_repo = repo_factory.create_store_webhooks_repo(&conn, user_id);
+
+            store_webhooks_repo
+                .delete(id)
+                .map_err(ectx!(try convert => id))
+                .and_then(StoreWebhookResponse::try_from_store_webhook)
+        })
+    }
+}
+
+/// Schedules delivery of the notification to every webhook of the store subscribed to the event type.
+/// Each delivery is a separate event, so a failing endpoint is retried without affecting the others
+pub fn enqueue_store_webhook_deliveries(
+    store_webhooks_repo: &StoreWebhooksRepo,
+    event_store_repo: &EventStoreRepo,
+    store_id: StoreId,
+    event_type: StoreWebhookEventType,
+    data: serde_json::Value,
+) -> Result<(), Error> {
+    let store_webhooks = store_webhooks_repo
+        .list_by_store_id(store_id)
+        .map_err(ectx!(try convert => store_id))?;
+
+    for store_webhook in store_webhooks.into_iter().filter(|webhook| webhook.is_subscribed_to(event_type)) {
+        let event = Event::new(EventPayload::StoreWebhookDelivery {
+            store_webhook_id: store_webhook.id,
+            notification: StoreWebhookNotification {
+                event_type,
+                store_id,
+                data: data.clone(),
+                created_at: Utc::now().naive_utc(),
+            },
+        });
+        event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+    }
+
+    Ok(())
+}
+
+pub fn enqueue_order_paid_webhooks(
+    store_webhooks_repo: &StoreWebhooksRepo,
+    event_store_repo: &EventStoreRepo,
+    orders: &[RawOrder],
+) -> Result<(), Error> {
+    for order in orders {
+        let data = json!({
+            "order_id": order.id,
+            "invoice_id": order.invoice_id,
+            "currency": order.seller_currency,
+            "total_amount": order.total_amount.to_super_unit(order.seller_currency),
+        });
+        enqueue_store_webhook_deliveries(
+            store_webhooks_repo,
+            event_store_repo,
+            StoreId(order.store_id.inner()),
+            StoreWebhookEventType::OrderPaid,
+            data,
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn enqueue_fee_charged_webhooks(
+    store_webhooks_repo: &StoreWebhooksRepo,
+    event_store_repo: &EventStoreRepo,
+    store_id: StoreId,
+    fees: Vec<Fee>,
+) -> Result<(), Error> {
+    let fees = fees.into_iter().map(FeeResponse::try_from_fee).collect::<Result<Vec<_>, _>>()?;
+
+    enqueue_store_webhook_deliveries(
+        store_webhooks_repo,
+        event_store_repo,
+        store_id,
+        StoreWebhookEventType::FeeCharged,
+        json!({ "fees": fees }),
+    )
+}
+
+/// A payout may cover orders of several stores, every store is notified about its own orders
+pub fn enqueue_payout_completed_webhooks(
+    store_webhooks_repo: &StoreWebhooksRepo,
+    event_store_repo: &EventStoreRepo,
+    orders_repo: &OrdersRepo,
+    payout: &Payout,
+) -> Result<(), Error> {
+    let payout_id = payout.id.clone();
+    let orders = orders_repo.get_many(&payout.order_ids).map_err(ectx!(try convert => payout_id))?;
+
+    let mut order_ids_by_store = BTreeMap::new();
+    for order in orders {
+        order_ids_by_store
+            .entry(order.store_id.inner())
+            .or_insert_with(Vec::new)
+            .push(order.id);
+    }
+
+    let currency = payout.currency();
+    for (store_id, order_ids) in order_ids_by_store {
+        let data = json!({
+            "payout_id": payout.id,
+            "order_ids": order_ids,
+            "currency": currency,
+            "gross_amount": payout.gross_amount.to_super_unit(currency),
+            "net_amount": payout.net_amount.to_super_unit(currency),
+        });
+        enqueue_store_webhook_deliveries(
+            store_webhooks_repo,
+            event_store_repo,
+            StoreId(store_id),
+            StoreWebhookEventType::PayoutCompleted,
+            data,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn validate_url(url: &str) -> Result<(), Error> {
+    if url.starts_with("https://") || url.starts_with("http://") {
+        return Ok(());
+    }
+
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new("invalid_url");
+    error.message = Some("Webhook url must be an absolute http(s) url".into());
+    errors.add("url", error);
+    Err(ectx!(err ErrorContext::StoreWebhook, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default())))
+}
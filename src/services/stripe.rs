@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use chrono::NaiveDateTime;
 use diesel::connection::AnsiTransactionManager;
 use diesel::pg::Pg;
 use diesel::Connection;
@@ -12,17 +13,16 @@ use failure::Fail;
 use stq_http::client::HttpClient;
 use stq_http::request_util::StripeSignature;
 
+use client::fiat_payments::FiatPaymentProvider;
 use client::payments::PaymentsClient;
-use client::stripe::StripeClient;
 use models::*;
 use services::accounts::AccountService;
 use stq_types::stripe::PaymentIntentId;
-use stripe::Webhook;
 
 use repos::ReposFactory;
 use repos::{
-    FeeRepo, InvoicesV2Repo, OrdersRepo, PaymentIntentFeeRepo, PaymentIntentInvoiceRepo, PaymentIntentRepo, SearchPaymentIntent,
-    SearchPaymentIntentFee, SearchPaymentIntentInvoice,
+    FeeRepo, InvoicesV2Repo, OrdersRepo, PaymentAttemptsRepo, PaymentIntentFeeRepo, PaymentIntentHistoryRepo, PaymentIntentInvoiceRepo,
+    PaymentIntentRepo, SearchPaymentIntent, SearchPaymentIntentFee, SearchPaymentIntentInvoice,
 };
 
 use models::invoice_v2::RawInvoice as InvoiceV2;
@@ -34,6 +34,8 @@ use config;
 use controller::context::DynamicContext;
 use controller::context::StaticContext;
 
+use services::payment_attempt::{record_payment_attempt, PaymentAttemptOutcome};
+use services::payment_intent::record_payment_intent_status;
 use services::types::spawn_on_pool;
 
 pub trait StripeService {
@@ -52,7 +54,7 @@ pub struct StripeServiceImpl<
     pub db_pool: Pool<M>,
     pub cpu_pool: CpuPool,
     pub repo_factory: F,
-    pub stripe_client: Arc<dyn StripeClient>,
+    pub fiat_payment_provider: Arc<dyn FiatPaymentProvider>,
     pub dynamic_context: DynamicContext<C, PC, AS>,
     pub static_context: StaticContext<T, M, F>,
 }
@@ -72,52 +74,23 @@ impl<
             signature_header,
             event_payload.len()
         );
-        use stripe::EventObject::*;
-        use stripe::EventType::*;
-
         let db_pool = self.static_context.db_pool.clone();
         let cpu_pool = self.static_context.cpu_pool.clone();
         let repo_factory = self.static_context.repo_factory.clone();
+        let fiat_payment_provider = self.fiat_payment_provider.clone();
 
         let signature_header = format!("{}", signature_header);
-        let signing_secret = self.static_context.config.stripe.signing_secret.clone();
 
         let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
             let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
             conn.transaction(move || {
-                let event = Webhook::new()
-                    .construct_event(event_payload, signature_header, signing_secret)
-                    .map_err(|e| {
-                        warn!("stripe Webhook::construct_event error: {:?}", e);
-                        ectx!(try err e, ErrorKind::Internal)
-                    })?;
-                info!("stripe handle_stripe_event event: {:?}", event);
-                match (event.event_type, event.data.object) {
-                    (PaymentIntentAmountCapturableUpdated, PaymentIntent(payment_intent)) => {
-                        let payment_intent_id = payment_intent.id.clone();
-                        event_store_repo
-                            .add_event(Event::new(EventPayload::PaymentIntentAmountCapturableUpdated { payment_intent }))
-                            .map_err(ectx!(try convert => payment_intent_id))?;
-                    }
-                    (PaymentIntentSucceeded, PaymentIntent(payment_intent)) => {
-                        let payment_intent_id = payment_intent.id.clone();
-                        event_store_repo
-                            .add_event(Event::new(EventPayload::PaymentIntentSucceeded { payment_intent }))
-                            .map_err(ectx!(try convert => payment_intent_id))?;
-                    }
-                    (PaymentIntentPaymentFailed, PaymentIntent(payment_intent)) => {
-                        let payment_intent_id = payment_intent.id.clone();
-                        event_store_repo
-                            .add_event(Event::new(EventPayload::PaymentIntentPaymentFailed { payment_intent }))
-                            .map_err(ectx!(try convert => payment_intent_id))?;
-                    }
-                    (event_type, event_object) => {
-                        warn!(
-                            "stripe handle_stripe_event unprocessable event - type: {:?}, object: {:?}",
-                            event_type, event_object
-                        );
-                    }
-                };
+                let payload = fiat_payment_provider
+                    .parse_webhook(signature_header, event_payload)
+                    .map_err(ectx!(try convert))?;
+                if let Some(payload) = payload {
+                    let event = Event::new(payload);
+                    event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+                }
                 Ok(())
             })
         });
@@ -132,7 +105,9 @@ pub enum PaymentType {
         invoice: InvoiceV2,
         orders: Vec<RawOrder>,
     },
-    Fee,
+    Fee {
+        fee: Fee,
+    },
 }
 
 pub fn payment_intent_succeeded_or_amount_capturable_updated<C>(
@@ -140,11 +115,14 @@ pub fn payment_intent_succeeded_or_amount_capturable_updated<C>(
     orders_repo: &OrdersRepo,
     invoices_repo: &InvoicesV2Repo,
     payment_intent_repo: &PaymentIntentRepo,
+    payment_intent_history_repo: &PaymentIntentHistoryRepo,
     payment_intent_invoices_repo: &PaymentIntentInvoiceRepo,
     payment_intent_fees_repo: &PaymentIntentFeeRepo,
+    payment_attempts_repo: &PaymentAttemptsRepo,
     fees_repo: &FeeRepo,
     fee_config: config::FeeValues,
     payment_intent: StripePaymentIntent,
+    event_created_at: Option<NaiveDateTime>,
 ) -> Result<PaymentType, ServiceError>
 where
     C: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
@@ -152,7 +130,8 @@ where
     let payment_intent_id = PaymentIntentId(payment_intent.id.clone());
     let payment_intent_id_cloned1 = payment_intent_id.clone();
 
-    let payment_intent_update = update_payment_intent(payment_intent);
+    let payment_attempt_outcome = PaymentAttemptOutcome::from_payment_intent(&payment_intent);
+    let mut payment_intent_update = update_payment_intent(payment_intent);
     let payment_intent = payment_intent_repo
         .get(SearchPaymentIntent::Id(payment_intent_id.clone()))
         .map_err(ectx!(try convert => payment_intent_id_cloned1))?
@@ -161,6 +140,21 @@ where
             ectx!(try err e, ErrorKind::Internal)
         })?;
 
+    // the payment is processed either way, only a stale status is kept out of the payment intent
+    let is_stale = match payment_intent_update.status {
+        Some(ref status) => !payment_intent.accepts_webhook_status(status, event_created_at),
+        None => false,
+    };
+    if is_stale {
+        info!(
+            "Payment intent {} is in status {:?}, ignoring stale status {:?}",
+            payment_intent_id, payment_intent.status, payment_intent_update.status
+        );
+        payment_intent_update.status = None;
+    } else {
+        payment_intent_update.status_event_created_at = event_created_at;
+    }
+
     let payment_intent_id_cloned2 = payment_intent_id.clone();
     let payment_intent_invoice = payment_intent_invoices_repo
         .get(SearchPaymentIntentInvoice::PaymentIntentId(payment_intent_id.clone()))
@@ -173,9 +167,14 @@ where
     let payment_intent_id_cloned4 = payment_intent_id.clone();
 
     conn.transaction::<_, ServiceError, _>(move || {
-        payment_intent_repo
+        let updated_payment_intent = payment_intent_repo
             .update(payment_intent_id.clone(), payment_intent_update)
             .map_err(ectx!(try convert => payment_intent_id_cloned4))?;
+        record_payment_intent_status(
+            payment_intent_history_repo,
+            &updated_payment_intent,
+            PaymentIntentHistorySource::Webhook,
+        )?;
         match (payment_intent_invoice, payment_intent_fee) {
             (Some(_), Some(_)) => {
                 let e = format_err!(
@@ -184,20 +183,29 @@ where
                 );
                 Err(ectx!(err e, ErrorKind::Internal))
             }
-            (Some(payment_intent_invoice), None) => payment_intent_succeeded_or_amount_capturable_updated_invoice(
-                orders_repo,
-                invoices_repo,
-                fees_repo,
-                fee_config,
-                payment_intent_invoice,
-            )
-            .map(|res| PaymentType::Invoice {
-                payment_intent,
-                invoice: res.0,
-                orders: res.1,
-            }),
+            (Some(payment_intent_invoice), None) => {
+                record_payment_attempt(
+                    payment_attempts_repo,
+                    payment_intent_invoice.invoice_id,
+                    payment_intent_id.clone(),
+                    payment_attempt_outcome,
+                )?;
+
+                payment_intent_succeeded_or_amount_capturable_updated_invoice(
+                    orders_repo,
+                    invoices_repo,
+                    fees_repo,
+                    fee_config,
+                    payment_intent_invoice,
+                )
+                .map(|res| PaymentType::Invoice {
+                    payment_intent,
+                    invoice: res.0,
+                    orders: res.1,
+                })
+            }
             (None, Some(payment_intent_fee)) => {
-                payment_intent_succeeded_or_amount_capturable_updated_fee(fees_repo, payment_intent_fee).map(|_| PaymentType::Fee)
+                payment_intent_succeeded_or_amount_capturable_updated_fee(fees_repo, payment_intent_fee).map(|fee| PaymentType::Fee { fee })
             }
             _ => {
                 let e = format_err!("Payment intent relationship by id {} not found.", payment_intent_id);
@@ -207,7 +215,7 @@ where
     })
 }
 
-fn update_payment_intent(payment_intent: StripePaymentIntent) -> UpdatePaymentIntent {
+pub fn update_payment_intent(payment_intent: StripePaymentIntent) -> UpdatePaymentIntent {
     UpdatePaymentIntent {
         charge_id: payment_intent
             .charges
@@ -249,7 +257,7 @@ pub fn payment_intent_succeeded_or_amount_capturable_updated_invoice(
     Ok((invoice, orders))
 }
 
-fn create_fee(order_percent: u64, order: &RawOrder) -> Result<NewFee, ServiceError> {
+pub fn create_fee(order_percent: u64, order: &RawOrder) -> Result<NewFee, ServiceError> {
     let hundred_percents = 100u64;
 
     let amount = order
@@ -267,13 +275,16 @@ fn create_fee(order_percent: u64, order: &RawOrder) -> Result<NewFee, ServiceErr
         metadata: None,
         crypto_currency: None,
         crypto_amount: None,
+        exchange_rate: None,
+        currency_exchange_id: None,
+        converted_at: None,
     })
 }
 
 pub fn payment_intent_succeeded_or_amount_capturable_updated_fee(
     fees_repo: &FeeRepo,
     payment_intent_fee: PaymentIntentFee,
-) -> Result<(), ServiceError> {
+) -> Result<Fee, ServiceError> {
     let update_fee = UpdateFee {
         status: Some(FeeStatus::Paid),
         ..Default::default()
@@ -282,5 +293,4 @@ pub fn payment_intent_succeeded_or_amount_capturable_updated_fee(
     fees_repo
         .update(payment_intent_fee.fee_id.clone(), update_fee)
         .map_err(ectx!(convert => payment_intent_fee.fee_id.clone()))
-        .map(|_| ())
 }
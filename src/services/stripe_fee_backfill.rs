@@ -0,0 +1,282 @@
+//! StripeFeeBackfillService fills in `stripe_fee` of historical fiat orders that were captured
+//! before the fee started being recorded. The work itself is done in batches by the event handler
+use chrono::Utc;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use serde_json;
+use stripe::BalanceTransaction;
+
+use failure::Fail;
+
+use stq_types::UserId;
+
+use super::types::ServiceFutureV2;
+use controller::responses::StripeFeeBackfillResponse;
+use models::order_v2::{OrderId, OrderPaymentKind};
+use models::{
+    Amount, Event, EventPayload, NewStripeFeeBackfill, PaymentState, StripeFeeBackfill, StripeFeeBackfillId, StripeFeeBackfillProgress,
+    StripeFeeBackfillStatus,
+};
+use repos::ReposFactory;
+use services::types::spawn_on_pool;
+use services::{Error, ErrorKind};
+
+/// Number of orders whose fees are requested from Stripe by a single event
+pub const STRIPE_FEE_BACKFILL_BATCH_SIZE: usize = 50;
+
+pub trait StripeFeeBackfillService {
+    /// Starts a backfill of all captured fiat orders that have no stripe fee
+    fn start_stripe_fee_backfill(&self) -> ServiceFutureV2<StripeFeeBackfillResponse>;
+    /// Returns progress of a backfill
+    fn get_stripe_fee_backfill(&self, id: StripeFeeBackfillId) -> ServiceFutureV2<Option<StripeFeeBackfillResponse>>;
+}
+
+pub struct StripeFeeBackfillServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > StripeFeeBackfillService for StripeFeeBackfillServiceImpl<T, M, F>
+{
+    fn start_stripe_fee_backfill(&self) -> ServiceFutureV2<StripeFeeBackfillResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let stripe_fee_backfills_repo = repo_factory.create_stripe_fee_backfills_repo(&conn, user_id);
+            let orders_repo = repo_factory.create_orders_repo(&conn, user_id);
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+            conn.transaction(move || {
+                let order_ids = orders_repo
+                    .get_orders_without_stripe_fee(vec![
+                        PaymentState::Captured,
+                        PaymentState::PaymentToSellerNeeded,
+                        PaymentState::PaidToSeller,
+                    ])
+                    .map_err(ectx!(try convert))?
+                    .into_iter()
+                    .filter(|order| match order.payment_kind() {
+                        OrderPaymentKind::Fiat { .. } => true,
+                        OrderPaymentKind::Crypto { .. } => false,
+                    })
+                    .map(|order| order.id)
+                    .collect::<Vec<_>>();
+
+                let new_backfill = NewStripeFeeBackfill {
+                    status: StripeFeeBackfillStatus::InProgress,
+                    total_orders: order_ids.len() as i32,
+                    order_ids: serde_json::to_value(order_ids).map_err(|e| ectx!(err e, ErrorKind::Internal))?,
+                };
+
+                let backfill = stripe_fee_backfills_repo
+                    .create(new_backfill.clone())
+                    .map_err(ectx!(try convert => new_backfill))?;
+
+                let event = Event::new(EventPayload::StripeFeeBackfillBatch {
+                    stripe_fee_backfill_id: backfill.id,
+                });
+                event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+
+                Ok(StripeFeeBackfillResponse::from(backfill))
+            })
+        })
+    }
+
+    fn get_stripe_fee_backfill(&self, id: StripeFeeBackfillId) -> ServiceFutureV2<Option<StripeFeeBackfillResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let stripe_fee_backfills_repo = repo_factory.create_stripe_fee_backfills_repo(&conn, user_id);
+
+            stripe_fee_backfills_repo
+                .get(id)
+                .map(|backfill| backfill.map(StripeFeeBackfillResponse::from))
+                .map_err(ectx!(convert => id))
+        })
+    }
+}
+
+/// Orders of the backfill that the next batch has to process
+pub fn next_stripe_fee_backfill_batch(backfill: &StripeFeeBackfill) -> Result<Vec<OrderId>, Error> {
+    let order_ids = backfill.order_ids().map_err(|e| ectx!(err e, ErrorKind::Internal))?;
+
+    Ok(order_ids
+        .into_iter()
+        .skip(backfill.processed_orders as usize)
+        .take(STRIPE_FEE_BACKFILL_BATCH_SIZE)
+        .collect())
+}
+
+/// Counters of the backfill after a batch of `batch_size` orders was processed,
+/// the backfill is finished once all of its orders have been processed
+pub fn stripe_fee_backfill_progress(
+    backfill: &StripeFeeBackfill,
+    batch_size: usize,
+    updated_orders: usize,
+    failed_orders: usize,
+) -> StripeFeeBackfillProgress {
+    let processed_orders = (backfill.processed_orders + batch_size as i32).min(backfill.total_orders);
+    let (status, finished_at) = if processed_orders >= backfill.total_orders {
+        (StripeFeeBackfillStatus::Finished, Some(Utc::now().naive_utc()))
+    } else {
+        (StripeFeeBackfillStatus::InProgress, None)
+    };
+
+    StripeFeeBackfillProgress {
+        status,
+        processed_orders,
+        updated_orders: backfill.updated_orders + updated_orders as i32,
+        failed_orders: backfill.failed_orders + failed_orders as i32,
+        finished_at,
+    }
+}
+
+/// Share of the charge fee attributable to an order, a charge may pay for several orders of an invoice.
+/// Computed in base units of the order currency, none if the balance transaction has no positive amount or the share overflows
+pub fn stripe_fee_for_order(total_amount: Amount, balance_transaction: &BalanceTransaction) -> Option<Amount> {
+    if balance_transaction.amount <= 0 || balance_transaction.fee < 0 {
+        return None;
+    }
+
+    total_amount
+        .checked_mul(Amount::new(balance_transaction.fee as u128))
+        .and_then(|amount| amount.checked_div(Amount::new(balance_transaction.amount as u128)))
+}
+
+/// Splits the fee of a charge paying for several orders between them in proportion to their amounts.
+/// The last order gets what is left after the others, so the shares add up to the fee. None if a share overflows
+pub fn allocate_stripe_fee(stripe_fee: Amount, order_amounts: &[Amount]) -> Option<Vec<Amount>> {
+    let total_amount = order_amounts
+        .iter()
+        .try_fold(Amount::zero(), |acc, amount| acc.checked_add(*amount))?;
+
+    let mut allocated = Amount::zero();
+    let mut shares = Vec::with_capacity(order_amounts.len());
+    for (index, amount) in order_amounts.iter().enumerate() {
+        let share = if index + 1 == order_amounts.len() {
+            stripe_fee.checked_sub(allocated)
+        } else if total_amount == Amount::zero() {
+            Some(Amount::zero())
+        } else {
+            stripe_fee.checked_mul(*amount).and_then(|fee| fee.checked_div(total_amount))
+        }?;
+
+        allocated = allocated.checked_add(share)?;
+        shares.push(share);
+    }
+
+    Some(shares)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use serde_json;
+    use uuid::Uuid;
+
+    use models::order_v2::OrderId;
+    use models::*;
+
+    use super::{allocate_stripe_fee, next_stripe_fee_backfill_batch, stripe_fee_backfill_progress, STRIPE_FEE_BACKFILL_BATCH_SIZE};
+
+    fn backfill(total_orders: usize, processed_orders: i32) -> StripeFeeBackfill {
+        let created_at = NaiveDate::from_ymd(2019, 3, 18).and_hms(9, 0, 0);
+        let order_ids = (0..total_orders).map(|_| OrderId::new(Uuid::new_v4())).collect::<Vec<_>>();
+
+        StripeFeeBackfill {
+            id: StripeFeeBackfillId::new(1),
+            status: StripeFeeBackfillStatus::InProgress,
+            order_ids: serde_json::to_value(order_ids).unwrap(),
+            total_orders: total_orders as i32,
+            processed_orders,
+            updated_orders: processed_orders,
+            failed_orders: 0,
+            created_at,
+            updated_at: created_at,
+            finished_at: None,
+        }
+    }
+
+    #[test]
+    fn next_batch_starts_after_processed_orders() {
+        let backfill = backfill(STRIPE_FEE_BACKFILL_BATCH_SIZE + 10, STRIPE_FEE_BACKFILL_BATCH_SIZE as i32);
+        let order_ids = backfill.order_ids().unwrap();
+
+        let batch = next_stripe_fee_backfill_batch(&backfill).unwrap();
+
+        assert_eq!(batch, order_ids[STRIPE_FEE_BACKFILL_BATCH_SIZE..].to_vec());
+    }
+
+    #[test]
+    fn progress_keeps_backfill_running_until_all_orders_are_processed() {
+        let backfill = backfill(STRIPE_FEE_BACKFILL_BATCH_SIZE * 2, 0);
+
+        let progress = stripe_fee_backfill_progress(&backfill, STRIPE_FEE_BACKFILL_BATCH_SIZE, 45, 5);
+
+        assert_eq!(progress.status, StripeFeeBackfillStatus::InProgress);
+        assert_eq!(progress.processed_orders, STRIPE_FEE_BACKFILL_BATCH_SIZE as i32);
+        assert_eq!(progress.updated_orders, 45);
+        assert_eq!(progress.failed_orders, 5);
+        assert!(progress.finished_at.is_none());
+    }
+
+    #[test]
+    fn progress_finishes_backfill_after_last_batch() {
+        let backfill = backfill(STRIPE_FEE_BACKFILL_BATCH_SIZE + 10, STRIPE_FEE_BACKFILL_BATCH_SIZE as i32);
+
+        let progress = stripe_fee_backfill_progress(&backfill, 10, 10, 0);
+
+        assert_eq!(progress.status, StripeFeeBackfillStatus::Finished);
+        assert_eq!(progress.processed_orders, backfill.total_orders);
+        assert_eq!(progress.updated_orders, STRIPE_FEE_BACKFILL_BATCH_SIZE as i32 + 10);
+        assert!(progress.finished_at.is_some());
+    }
+
+    #[test]
+    fn progress_finishes_empty_backfill() {
+        let backfill = backfill(0, 0);
+
+        let progress = stripe_fee_backfill_progress(&backfill, 0, 0, 0);
+
+        assert_eq!(progress.status, StripeFeeBackfillStatus::Finished);
+        assert_eq!(progress.processed_orders, 0);
+    }
+
+    #[test]
+    fn stripe_fee_is_allocated_in_proportion_to_order_amounts() {
+        let order_amounts = vec![Amount::new(1000), Amount::new(2000), Amount::new(333)];
+
+        let shares = allocate_stripe_fee(Amount::new(100), &order_amounts).unwrap();
+
+        assert_eq!(shares, vec![Amount::new(30), Amount::new(60), Amount::new(10)]);
+    }
+
+    #[test]
+    fn stripe_fee_shares_add_up_to_fee() {
+        let order_amounts = vec![Amount::new(1), Amount::new(1), Amount::new(1)];
+
+        let shares = allocate_stripe_fee(Amount::new(100), &order_amounts).unwrap();
+
+        assert_eq!(shares, vec![Amount::new(33), Amount::new(33), Amount::new(34)]);
+        assert!(allocate_stripe_fee(Amount::new(100), &[]).unwrap().is_empty());
+    }
+}
@@ -83,20 +83,19 @@ impl<
 
                     match store_subscription.status {
                         StoreSubscriptionStatus::Trial => {
-                            let trial_duration = now
-                                - store_subscription.trial_start_date.ok_or_else(|| {
-                                    let e = format_err!("Store {} has empty trial start time", store_id);
-                                    ectx!(try err e, ErrorKind::Internal)
-                                })?;
+                            let trial_end_date = store_subscription.trial_end_date(max_trial_duration).ok_or_else(|| {
+                                let e = format_err!("Store {} has empty trial start time", store_id);
+                                ectx!(try err e, ErrorKind::Internal)
+                            })?;
 
-                            if trial_duration < max_trial_duration {
+                            if now < trial_end_date {
                                 continue 'subscriptions;
                             }
                         }
                         StoreSubscriptionStatus::Paid => {
                             //do nothing - just pay
                         }
-                        StoreSubscriptionStatus::Free => {
+                        StoreSubscriptionStatus::Free | StoreSubscriptionStatus::Cancelled => {
                             continue 'subscriptions;
                         }
                     }
@@ -141,7 +140,8 @@ impl<
     }
 }
 
-fn find_update_or_create_store_subscription(
+/// Returns the subscription of the store starting its trial if it hasn't started yet
+pub fn find_update_or_create_store_subscription(
     store_subscription_repo: &StoreSubscriptionRepo,
     store_id: StoreId,
     now: NaiveDateTime,
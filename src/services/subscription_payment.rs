@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use bigdecimal::BigDecimal;
 use chrono::{Duration, NaiveDateTime};
 use diesel::connection::AnsiTransactionManager;
 use diesel::pg::Pg;
@@ -12,28 +13,36 @@ use r2d2::{ManageConnection, Pool};
 use failure::Fail;
 
 use stq_http::client::HttpClient;
-use stq_types::{StoreId, UserId};
+use stq_types::{Quantity, StoreId, SubscriptionPaymentId, UserId};
 
 use super::types::ServiceFutureV2;
 use client::payments::{CreateInternalTransaction, PaymentsClient};
+use client::stores::StoresClient;
 use client::stripe::{NewCharge, StripeClient};
-use config::Subscription as SubscriptionConfig;
+use config::{Subscription as SubscriptionConfig, SubscriptionPricingTier};
 use controller::context::DynamicContext;
-use controller::responses::SubscriptionPaymentSearchResponse;
+use controller::responses::{SubscriptionPaymentReceiptResponse, SubscriptionPaymentReceiptsResponse, SubscriptionPaymentSearchResponse};
 use models::{
-    Account, Amount, ChargeId, CurrencyChoice, DbCustomer, FiatCurrency, NewSubscriptionPayment, StoreSubscription,
-    StoreSubscriptionSearch, Subscription, SubscriptionPaymentSearch, SubscriptionPaymentStatus, SubscriptionSearch, TransactionId,
-    TureCurrency, UpdateSubscription,
+    Account, Amount, ChargeId, Currency, CurrencyChoice, DbCustomer, FiatCurrency, NewSubscriptionPayment, StoreSubscription,
+    StoreSubscriptionSearch, StoreSubscriptionStatus, Subscription, SubscriptionPaymentSearch, SubscriptionPaymentSearchResults,
+    SubscriptionPaymentStatus, SubscriptionSearch, TransactionId, TureCurrency, UpdateSubscription,
 };
 use repos::repo_factory::ReposFactory;
 use repos::{AccountsRepo, CustomersRepo, SearchCustomer, StoreSubscriptionRepo, SubscriptionRepo, UserRolesRepo};
 use services::accounts::AccountService;
 use services::types::{spawn_on_pool, ServiceResultV2};
-use services::ErrorKind;
+use services::{Error, ErrorKind};
 
 pub trait SubscriptionPaymentService {
     fn pay_subscriptions(&self) -> ServiceFutureV2<()>;
     fn search(&self, skip: i64, count: i64, payload: SubscriptionPaymentSearch) -> ServiceFutureV2<SubscriptionPaymentSearchResponse>;
+    /// Returns subscription payments of the store, newest first, with the periods they cover
+    fn get_store_subscription_payments(
+        &self,
+        store_id: StoreId,
+        skip: i64,
+        count: i64,
+    ) -> ServiceFutureV2<SubscriptionPaymentReceiptsResponse>;
 }
 
 pub struct SubscriptionPaymentServiceImpl<
@@ -49,16 +58,25 @@ pub struct SubscriptionPaymentServiceImpl<
     pub repo_factory: F,
     pub dynamic_context: DynamicContext<C, PC, AS>,
     pub stripe_client: Arc<dyn StripeClient>,
+    pub stores_client: Arc<dyn StoresClient>,
     pub config: SubscriptionConfig,
 }
 
+/// Published products a store is charged for in a billing run and the price of one
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SubscriptionCharge {
+    quantity: Quantity,
+    unit_price: Amount,
+    total_amount: Amount,
+}
+
 #[derive(Debug)]
 struct FiatPaymentPreparation {
     fiat_currency: FiatCurrency,
     customer: DbCustomer,
     store_subscription: StoreSubscription,
     subscriptions: Vec<Subscription>,
-    total_amount: Amount,
+    charge: SubscriptionCharge,
 }
 
 #[derive(Debug)]
@@ -67,13 +85,13 @@ struct CryptoPaymentPreparation {
     ture_currency: TureCurrency,
     store_subscription: StoreSubscription,
     subscriptions: Vec<Subscription>,
-    total_amount: Amount,
+    charge: SubscriptionCharge,
 }
 
 struct FailedPaymentPreparation {
     store_subscription: StoreSubscription,
     subscriptions: Vec<Subscription>,
-    total_amount: Amount,
+    charge: SubscriptionCharge,
 }
 
 enum PaymentPreparation {
@@ -125,23 +143,47 @@ impl<
             }
         };
 
-        let fut = spawn_on_pool(db_pool, cpu_pool, move |conn| {
-            let store_subscription_repo = repo_factory.create_store_subscription_repo(&conn, user_id);
-            let subscription_repo = repo_factory.create_subscription_repo(&conn, user_id);
-            let user_role_repo = repo_factory.create_user_roles_repo(&conn, user_id);
-            let customer_repo = repo_factory.create_customers_repo(&conn, user_id);
-            let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
-
-            conn.transaction(move || {
-                let subscriptions_by_stores = subscriptions_to_pay(&*subscription_repo, now, payment_periodicity_duration)?;
-
-                create_payment_preparations(
-                    &*store_subscription_repo,
-                    &*accounts_repo,
-                    &*customer_repo,
-                    &*user_role_repo,
-                    subscriptions_by_stores,
-                )
+        let pricing_tiers = self.config.pricing_tiers.clone();
+        let stores_client = self.stores_client.clone();
+
+        let fut = spawn_on_pool(db_pool.clone(), cpu_pool.clone(), {
+            let repo_factory = repo_factory.clone();
+            move |conn| {
+                let subscription_repo = repo_factory.create_subscription_repo(&conn, user_id);
+                subscriptions_to_pay(&*subscription_repo, now, payment_periodicity_duration)
+            }
+        })
+        .and_then({
+            let pricing_tiers = pricing_tiers.clone();
+            move |subscriptions_by_stores| {
+                // published products are only counted for usage-based pricing
+                let store_ids = if pricing_tiers.is_empty() {
+                    Vec::new()
+                } else {
+                    subscriptions_by_stores.keys().cloned().collect()
+                };
+                published_products_quantities(stores_client, store_ids)
+                    .map(move |published_quantities| (subscriptions_by_stores, published_quantities))
+            }
+        })
+        .and_then(move |(subscriptions_by_stores, published_quantities)| {
+            spawn_on_pool(db_pool, cpu_pool, move |conn| {
+                let store_subscription_repo = repo_factory.create_store_subscription_repo(&conn, user_id);
+                let user_role_repo = repo_factory.create_user_roles_repo(&conn, user_id);
+                let customer_repo = repo_factory.create_customers_repo(&conn, user_id);
+                let accounts_repo = repo_factory.create_accounts_repo_with_sys_acl(&conn);
+
+                conn.transaction(move || {
+                    create_payment_preparations(
+                        &*store_subscription_repo,
+                        &*accounts_repo,
+                        &*customer_repo,
+                        &*user_role_repo,
+                        &pricing_tiers,
+                        subscriptions_by_stores,
+                        &published_quantities,
+                    )
+                })
             })
         })
         .map(futures::stream::iter_ok)
@@ -198,6 +240,98 @@ impl<
             Ok(resposne.into())
         })
     }
+
+    fn get_store_subscription_payments(
+        &self,
+        store_id: StoreId,
+        skip: i64,
+        count: i64,
+    ) -> ServiceFutureV2<SubscriptionPaymentReceiptsResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.dynamic_context.user_id;
+
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let subscription_payment_repo = repo_factory.create_subscription_payment_repo(&conn, user_id);
+            let subscription_repo = repo_factory.create_subscription_repo(&conn, user_id);
+
+            let search = SubscriptionPaymentSearch {
+                id: None,
+                store_id: Some(store_id),
+                status: None,
+            };
+            let SubscriptionPaymentSearchResults {
+                total_count,
+                subscription_payments,
+            } = subscription_payment_repo
+                .search(skip, count, search.clone())
+                .map_err(ectx!(try convert => skip, count, search))?;
+
+            let subscription_payment_ids = subscription_payments.iter().map(|payment| payment.id).collect::<Vec<_>>();
+            let mut subscriptions_by_payment: HashMap<SubscriptionPaymentId, Vec<Subscription>> = HashMap::new();
+            for subscription in subscription_repo
+                .get_by_subscription_payment_ids(subscription_payment_ids.clone())
+                .map_err(ectx!(try convert => subscription_payment_ids))?
+            {
+                if let Some(subscription_payment_id) = subscription.subscription_payment_id {
+                    subscriptions_by_payment
+                        .entry(subscription_payment_id)
+                        .or_insert_with(Vec::new)
+                        .push(subscription);
+                }
+            }
+
+            let subscription_payments = subscription_payments
+                .into_iter()
+                .map(|payment| {
+                    let subscriptions = subscriptions_by_payment.remove(&payment.id).unwrap_or_default();
+                    SubscriptionPaymentReceiptResponse::new(payment, &subscriptions)
+                })
+                .collect();
+
+            Ok(SubscriptionPaymentReceiptsResponse {
+                total_count,
+                subscription_payments,
+            })
+        })
+    }
+}
+
+/// Renders subscription payments as CSV with a header row
+pub fn subscription_payment_receipts_csv(receipts: &[SubscriptionPaymentReceiptResponse]) -> String {
+    let format_date = |date: Option<NaiveDateTime>| date.map(|date| date.to_string()).unwrap_or_default();
+
+    let mut csv = String::from(
+        "id,period_start,period_end,published_base_products_quantity,amount,currency,status,charge_id,transaction_id,created_at\n",
+    );
+    for receipt in receipts {
+        let payment = &receipt.payment;
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{}\n",
+            payment.id,
+            format_date(receipt.period_start),
+            format_date(receipt.period_end),
+            receipt.published_base_products_quantity,
+            payment.amount,
+            Currency::try_from_stq_currency(payment.currency)
+                .map(|currency| currency.to_string())
+                .unwrap_or_default(),
+            payment.status,
+            payment
+                .charge_id
+                .as_ref()
+                .map(|charge_id| charge_id.to_string())
+                .unwrap_or_default(),
+            payment
+                .transaction_id
+                .map(|transaction_id| transaction_id.to_string())
+                .unwrap_or_default(),
+            payment.created_at,
+        ));
+    }
+    csv
 }
 
 fn create_payment_preparations(
@@ -205,7 +339,9 @@ fn create_payment_preparations(
     accounts_repo: &AccountsRepo,
     customer_repo: &CustomersRepo,
     user_role_repo: &UserRolesRepo,
+    pricing_tiers: &[SubscriptionPricingTier],
     subscriptions_by_stores: HashMap<StoreId, Vec<Subscription>>,
+    published_quantities: &HashMap<StoreId, Quantity>,
 ) -> ServiceResultV2<Vec<PaymentPreparation>> {
     let mut payment_preparations = Vec::new();
     for (store_id, subscriptions) in subscriptions_by_stores {
@@ -222,7 +358,20 @@ fn create_payment_preparations(
                 ectx!(try err e, ErrorKind::Internal)
             })?;
 
-        let total_amount = calculate_total_amount(&store_subscription, &subscriptions)?;
+        if store_subscription.status == StoreSubscriptionStatus::Cancelled {
+            info!(
+                "subscription_payment: Store {} is offboarded, its subscriptions are not collected",
+                store_id
+            );
+            continue;
+        }
+
+        let charge = calculate_charge(
+            &store_subscription,
+            &subscriptions,
+            pricing_tiers,
+            published_quantities.get(&store_id).cloned(),
+        )?;
 
         let store_owner = user_role_repo
             .get_by_store_id(store_id)
@@ -233,14 +382,8 @@ fn create_payment_preparations(
             })?
             .user_id;
 
-        let payment_preparation = payment_preparation(
-            accounts_repo,
-            customer_repo,
-            store_subscription,
-            subscriptions,
-            store_owner,
-            total_amount,
-        )?;
+        let payment_preparation =
+            payment_preparation(accounts_repo, customer_repo, store_subscription, subscriptions, store_owner, charge)?;
 
         payment_preparations.push(payment_preparation)
     }
@@ -254,7 +397,7 @@ fn payment_preparation(
     store_subscription: StoreSubscription,
     subscriptions: Vec<Subscription>,
     store_owner: UserId,
-    total_amount: Amount,
+    charge: SubscriptionCharge,
 ) -> ServiceResultV2<PaymentPreparation> {
     match store_subscription.currency.classify() {
         CurrencyChoice::Crypto(ture_currency) => {
@@ -265,7 +408,7 @@ fn payment_preparation(
                         "subscription_payment: User {} has no wallet addess in store subscription",
                         store_owner
                     );
-                    return Ok(failed_payment_preparation(store_subscription, subscriptions, total_amount));
+                    return Ok(failed_payment_preparation(store_subscription, subscriptions, charge));
                 }
             };
 
@@ -273,7 +416,7 @@ fn payment_preparation(
                 Some(store_owner_account) => store_owner_account,
                 None => {
                     warn!("subscription_payment: Account with wallet address {} not found", store_owner);
-                    return Ok(failed_payment_preparation(store_subscription, subscriptions, total_amount));
+                    return Ok(failed_payment_preparation(store_subscription, subscriptions, charge));
                 }
             };
 
@@ -282,7 +425,7 @@ fn payment_preparation(
                 ture_currency,
                 store_subscription,
                 subscriptions,
-                total_amount,
+                charge,
             }))
         }
         CurrencyChoice::Fiat(fiat_currency) => {
@@ -290,7 +433,7 @@ fn payment_preparation(
                 Some(customer) => customer,
                 None => {
                     warn!("subscription_payment: User {} has no stripe customer", store_owner);
-                    return Ok(failed_payment_preparation(store_subscription, subscriptions, total_amount));
+                    return Ok(failed_payment_preparation(store_subscription, subscriptions, charge));
                 }
             };
             Ok(PaymentPreparation::Fiat(FiatPaymentPreparation {
@@ -298,7 +441,7 @@ fn payment_preparation(
                 customer,
                 store_subscription,
                 subscriptions,
-                total_amount,
+                charge,
             }))
         }
     }
@@ -307,12 +450,12 @@ fn payment_preparation(
 fn failed_payment_preparation(
     store_subscription: StoreSubscription,
     subscriptions: Vec<Subscription>,
-    total_amount: Amount,
+    charge: SubscriptionCharge,
 ) -> PaymentPreparation {
     PaymentPreparation::Failed(FailedPaymentPreparation {
         store_subscription,
         subscriptions,
-        total_amount,
+        charge,
     })
 }
 
@@ -345,7 +488,7 @@ fn collect_fiat_subscription(
 ) -> ServiceFutureV2<FinishedPayment> {
     let new_charge = NewCharge {
         customer_id: payment_preparation.customer.id.clone(),
-        amount: payment_preparation.total_amount,
+        amount: payment_preparation.charge.total_amount,
         currency: payment_preparation.store_subscription.currency,
         capture: true,
     };
@@ -367,11 +510,13 @@ fn collect_fiat_subscription(
         .map(|(charge_id, status)| FinishedPayment {
             subscription_payment: NewSubscriptionPayment {
                 store_id: payment_preparation.store_subscription.store_id,
-                amount: payment_preparation.total_amount,
+                amount: payment_preparation.charge.total_amount,
                 currency: payment_preparation.store_subscription.currency,
                 charge_id,
                 transaction_id: None,
                 status,
+                quantity: Some(payment_preparation.charge.quantity),
+                unit_price: Some(payment_preparation.charge.unit_price),
             },
             subscriptions: payment_preparation.subscriptions,
         });
@@ -384,11 +529,13 @@ fn into_finished_payment(failed_payment_preparation: FailedPaymentPreparation) -
         subscriptions: failed_payment_preparation.subscriptions,
         subscription_payment: NewSubscriptionPayment {
             store_id: failed_payment_preparation.store_subscription.store_id,
-            amount: failed_payment_preparation.total_amount,
+            amount: failed_payment_preparation.charge.total_amount,
             currency: failed_payment_preparation.store_subscription.currency,
             charge_id: None,
             transaction_id: None,
             status: SubscriptionPaymentStatus::Failed,
+            quantity: Some(failed_payment_preparation.charge.quantity),
+            unit_price: Some(failed_payment_preparation.charge.unit_price),
         },
     }))
 }
@@ -405,7 +552,7 @@ fn collect_ture_subscription<PC: PaymentsClient, AS: AccountService>(
         .map(|account_with_balance| account_with_balance.account.id)
         .map({
             let from = payment_preparation.store_owner_account.id.inner().clone();
-            let amount = payment_preparation.total_amount.clone();
+            let amount = payment_preparation.charge.total_amount.clone();
             move |main_account_id| CreateInternalTransaction {
                 id: transaction_id.inner().clone(),
                 from,
@@ -427,11 +574,13 @@ fn collect_ture_subscription<PC: PaymentsClient, AS: AccountService>(
         .map(move |(transaction_id, status)| FinishedPayment {
             subscription_payment: NewSubscriptionPayment {
                 store_id,
-                amount: payment_preparation.total_amount,
+                amount: payment_preparation.charge.total_amount,
                 currency: payment_preparation.store_subscription.currency,
                 charge_id: None,
                 transaction_id: Some(transaction_id),
                 status,
+                quantity: Some(payment_preparation.charge.quantity),
+                unit_price: Some(payment_preparation.charge.unit_price),
             },
             subscriptions: payment_preparation.subscriptions,
         });
@@ -439,11 +588,113 @@ fn collect_ture_subscription<PC: PaymentsClient, AS: AccountService>(
     Box::new(fut)
 }
 
+/// Published products of the stores counted by the stores microservice.
+/// Stores the count is not available for are left out
+fn published_products_quantities(
+    stores_client: Arc<dyn StoresClient>,
+    store_ids: Vec<StoreId>,
+) -> ServiceFutureV2<HashMap<StoreId, Quantity>> {
+    let fut = futures::stream::iter_ok::<_, Error>(store_ids)
+        .and_then(move |store_id| {
+            stores_client.get_published_products_count(store_id).then(move |res| match res {
+                Ok(quantity) => Ok(Some((store_id, quantity))),
+                Err(err) => {
+                    warn!(
+                        "subscription_payment: Failed to count published products of store {}: {}",
+                        store_id, err
+                    );
+                    Ok(None)
+                }
+            })
+        })
+        .filter_map(|quantity| quantity)
+        .collect()
+        .map(|quantities| quantities.into_iter().collect());
+
+    Box::new(fut)
+}
+
+/// Charge of the store for the billing run. With pricing tiers configured for the currency of the subscription
+/// the products published now are charged at the price of their tier, falling back to the latest daily count
+/// when the stores microservice didn't count them. Otherwise every product published on every day is charged
+/// the value of the subscription
+fn calculate_charge(
+    store_subscription: &StoreSubscription,
+    subscriptions: &[Subscription],
+    pricing_tiers: &[SubscriptionPricingTier],
+    published_quantity: Option<Quantity>,
+) -> ServiceResultV2<SubscriptionCharge> {
+    if let Some((quantity, unit_price)) = tier_unit_price(pricing_tiers, store_subscription, published_quantity, subscriptions) {
+        let total_amount = Amount::from_quantity(quantity)
+            .and_then(|quantity| quantity.checked_mul(unit_price))
+            .ok_or({
+                let e = format_err!(
+                    "Could not calculate total amount: checked multiplication error for store {}",
+                    store_subscription.store_id
+                );
+                ectx!(try err e, ErrorKind::Internal)
+            })?;
+
+        return Ok(SubscriptionCharge {
+            quantity,
+            unit_price,
+            total_amount,
+        });
+    }
+
+    let quantity = subscriptions
+        .iter()
+        .try_fold(0i32, |sum, subscription| {
+            sum.checked_add(subscription.published_base_products_quantity.0)
+        })
+        .ok_or({
+            let e = format_err!(
+                "Could not calculate quantity: checked addition error for store {}",
+                store_subscription.store_id
+            );
+            ectx!(try err e, ErrorKind::Internal)
+        })?;
+    let total_amount = calculate_total_amount(store_subscription, subscriptions)?;
+
+    Ok(SubscriptionCharge {
+        quantity: Quantity(quantity),
+        unit_price: store_subscription.value,
+        total_amount,
+    })
+}
+
+/// Quantity charged with usage-based pricing and the price of a product in the tier it falls into,
+/// none if there are no tiers or the tier has no price in the currency of the subscription
+fn tier_unit_price(
+    pricing_tiers: &[SubscriptionPricingTier],
+    store_subscription: &StoreSubscription,
+    published_quantity: Option<Quantity>,
+    subscriptions: &[Subscription],
+) -> Option<(Quantity, Amount)> {
+    let quantity = published_quantity.or_else(|| {
+        subscriptions
+            .iter()
+            .max_by_key(|subscription| subscription.created_at)
+            .map(|subscription| subscription.published_base_products_quantity)
+    })?;
+
+    let tier = pricing_tiers
+        .iter()
+        .find(|tier| tier.up_to.map_or(true, |up_to| i64::from(quantity.0) <= i64::from(up_to)))
+        .or_else(|| pricing_tiers.last())?;
+
+    let currency = store_subscription.currency;
+    let unit_price = tier.unit_prices.get(&currency).filter(|unit_price| unit_price.is_finite())?;
+    let unit_price = Amount::checked_from_super_unit(currency, BigDecimal::from(*unit_price))?;
+
+    Some((quantity, unit_price))
+}
+
 fn calculate_total_amount(store_subscription: &StoreSubscription, subscriptions: &[Subscription]) -> ServiceResultV2<Amount> {
     let mut total_amount = Amount::zero();
     for subscription in subscriptions {
-        let subscription_amount = Amount::from(subscription.published_base_products_quantity)
-            .checked_mul(store_subscription.value)
+        let subscription_amount = Amount::from_quantity(subscription.published_base_products_quantity)
+            .and_then(|quantity| quantity.checked_mul(store_subscription.value))
             .ok_or({
                 let e = format_err!(
                     "Could not calculate total amount: checked multiplication error for store {}",
@@ -471,7 +722,7 @@ mod tests {
 
     use stq_types::{Quantity, SubscriptionId};
 
-    use models::NewSubscription;
+    use models::{NewSubscription, StoreSubscriptionStatus, SubscriptionPayment};
     use repos::types::RepoResultV2;
 
     struct SubscriptionRepoStub;
@@ -518,6 +769,12 @@ mod tests {
         fn search(&self, _search: SubscriptionSearch) -> RepoResultV2<Vec<Subscription>> {
             unimplemented!()
         }
+        fn get_by_subscription_payment_ids(
+            &self,
+            _subscription_payment_ids: Vec<SubscriptionPaymentId>,
+        ) -> RepoResultV2<Vec<Subscription>> {
+            unimplemented!()
+        }
         fn update(&self, _search: SubscriptionSearch, _payload: UpdateSubscription) -> RepoResultV2<Subscription> {
             unimplemented!()
         }
@@ -542,4 +799,109 @@ mod tests {
             vec![SubscriptionId(1), SubscriptionId(2)]
         );
     }
+
+    #[test]
+    fn receipts_csv_contains_period_and_quantity() {
+        let subscription_payment_id = SubscriptionPaymentId(7);
+        let subscriptions = (0..3)
+            .map(|day| Subscription {
+                id: SubscriptionId(day + 1),
+                store_id: StoreId(1),
+                published_base_products_quantity: Quantity(10 + day),
+                subscription_payment_id: Some(subscription_payment_id),
+                created_at: NaiveDate::from_ymd(2019, 2, 9 + day as u32).and_hms(12, 0, 0),
+            })
+            .collect::<Vec<_>>();
+        let subscription_payment = SubscriptionPayment {
+            id: subscription_payment_id,
+            store_id: StoreId(1),
+            amount: Amount::new(3300),
+            currency: Currency::Eur,
+            charge_id: Some(ChargeId::new("ch_1".to_string())),
+            transaction_id: None,
+            status: SubscriptionPaymentStatus::Paid,
+            created_at: NaiveDate::from_ymd(2019, 2, 12).and_hms(0, 0, 0),
+            quantity: Some(Quantity(33)),
+            unit_price: Some(Amount::new(100)),
+        };
+
+        let receipt = SubscriptionPaymentReceiptResponse::new(subscription_payment, &subscriptions);
+        let csv = subscription_payment_receipts_csv(&[receipt]);
+
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        let columns = lines[1].split(',').collect::<Vec<_>>();
+        assert_eq!(columns[0], "7");
+        assert_eq!(columns[1], "2019-02-09 12:00:00");
+        assert_eq!(columns[2], "2019-02-11 12:00:00");
+        assert_eq!(columns[3], "33");
+        assert_eq!(columns[5], "eur");
+        assert_eq!(columns[6], "paid");
+        assert_eq!(columns[7], "ch_1");
+        assert_eq!(columns[8], "");
+    }
+
+    fn pricing_tier(up_to: Option<u32>, eur_unit_price: f64) -> SubscriptionPricingTier {
+        let mut unit_prices = HashMap::new();
+        unit_prices.insert(Currency::Eur, eur_unit_price);
+        SubscriptionPricingTier { up_to, unit_prices }
+    }
+
+    fn store_subscription(currency: Currency) -> StoreSubscription {
+        let created_at = NaiveDate::from_ymd(2019, 1, 1).and_hms(0, 0, 0);
+        StoreSubscription {
+            store_id: StoreId(1),
+            currency,
+            value: Amount::new(3),
+            wallet_address: None,
+            trial_start_date: Some(created_at),
+            created_at,
+            updated_at: created_at,
+            status: StoreSubscriptionStatus::Paid,
+            trial_end_date: None,
+        }
+    }
+
+    #[test]
+    fn charges_published_products_at_price_of_their_tier() {
+        let pricing_tiers = vec![pricing_tier(Some(10), 0.5), pricing_tier(None, 0.25)];
+        let subscriptions = [8, 12]
+            .iter()
+            .enumerate()
+            .map(|(day, quantity)| Subscription {
+                id: SubscriptionId(day as i32 + 1),
+                store_id: StoreId(1),
+                published_base_products_quantity: Quantity(*quantity),
+                subscription_payment_id: None,
+                created_at: NaiveDate::from_ymd(2019, 2, 9 + day as u32).and_hms(12, 0, 0),
+            })
+            .collect::<Vec<_>>();
+        let eur_subscription = store_subscription(Currency::Eur);
+
+        let charge = calculate_charge(&eur_subscription, &subscriptions, &pricing_tiers, Some(Quantity(10))).unwrap();
+        assert_eq!(
+            (charge.quantity, charge.unit_price, charge.total_amount),
+            (Quantity(10), Amount::new(50), Amount::new(500))
+        );
+
+        // the latest daily count is charged when the stores microservice didn't count the products
+        let charge = calculate_charge(&eur_subscription, &subscriptions, &pricing_tiers, None).unwrap();
+        assert_eq!(
+            (charge.quantity, charge.unit_price, charge.total_amount),
+            (Quantity(12), Amount::new(25), Amount::new(300))
+        );
+
+        // STQ has no tier prices, so every product of every day is charged the subscription value
+        let charge = calculate_charge(
+            &store_subscription(Currency::Stq),
+            &subscriptions,
+            &pricing_tiers,
+            Some(Quantity(10)),
+        )
+        .unwrap();
+        assert_eq!(
+            (charge.quantity, charge.unit_price, charge.total_amount),
+            (Quantity(20), Amount::new(3), Amount::new(60))
+        );
+    }
 }
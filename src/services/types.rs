@@ -12,6 +12,7 @@ use controller::context::{DynamicContext, StaticContext};
 use errors::Error;
 use repos::repo_factory::*;
 use services::accounts::AccountService;
+use services::work_queue::CONTROLLER_WORK_QUEUE;
 
 use super::{Error as ServiceError, ErrorKind};
 
@@ -78,12 +79,18 @@ impl<
         Func: FnOnce(PooledConnection<M>) -> Result<R, FailureError> + Send + 'static,
         R: Send + 'static,
     {
+        let slot = CONTROLLER_WORK_QUEUE.enqueue();
         let db_pool = self.static_context.db_pool.clone();
         let cpu_pool = self.static_context.cpu_pool.clone();
-        Box::new(cpu_pool.spawn_fn(move || db_pool.get().map_err(|e| e.context(Error::Connection).into()).and_then(f)))
+        Box::new(cpu_pool.spawn_fn(move || {
+            let _slot = slot;
+            db_pool.get().map_err(|e| e.context(Error::Connection).into()).and_then(f)
+        }))
     }
 }
 
+/// Runs the closure on the CPU pool with a database connection. The closure is counted in the work queue of the controller,
+/// which is never refused: requests are refused on entry while the queue is full, and the ones admitted finish their work
 pub fn spawn_on_pool<T, M, Func, R>(db_pool: r2d2::Pool<M>, cpu_pool: futures_cpupool::CpuPool, f: Func) -> ServiceFutureV2<R>
 where
     T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
@@ -91,5 +98,10 @@ where
     Func: FnOnce(PooledConnection<M>) -> Result<R, ServiceError> + Send + 'static,
     R: Send + 'static,
 {
-    Box::new(cpu_pool.spawn_fn(move || db_pool.get().map_err(ectx!(ErrorKind::Internal)).and_then(f)))
+    let slot = CONTROLLER_WORK_QUEUE.enqueue();
+    Box::new(cpu_pool.spawn_fn(move || {
+        // the place in the queue is given back once the closure has run or has been dropped unrun
+        let _slot = slot;
+        db_pool.get().map_err(ectx!(ErrorKind::Internal)).and_then(f)
+    }))
 }
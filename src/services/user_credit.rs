@@ -0,0 +1,300 @@
+//! UserCreditService keeps the platform credit of buyers, e.g. from refunds or promos. Credit is held per currency
+//! and spent on invoices in that currency at checkout. It is given back when the invoice expires or is cancelled unpaid
+use bigdecimal::BigDecimal;
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::Fail;
+use futures::{future, Future};
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use serde_json;
+use validator::{ValidationError, ValidationErrors};
+
+use stq_types::UserId as StqUserId;
+
+use super::error::{Error as ServiceError, ErrorContext, ErrorKind};
+use super::types::{ServiceFutureV2, ServiceResultV2};
+use controller::requests::GrantUserCreditRequest;
+use controller::responses::UserCreditsResponse;
+use models::invoice_v2::InvoiceId;
+use models::{Amount, Currency, NewUserCreditEntry, UserCreditEntry, UserCreditEntryId, UserCreditEntryKind, UserId};
+use repos::{ReposFactory, UserCreditsRepo};
+use services::types::spawn_on_pool;
+
+pub trait UserCreditService {
+    /// Credit of the current user
+    fn get_my_credits(&self) -> ServiceFutureV2<UserCreditsResponse>;
+    fn get_user_credits(&self, user_id: StqUserId) -> ServiceFutureV2<UserCreditsResponse>;
+    /// Adds credit to the balance of the user in the currency
+    fn grant_user_credit(&self, user_id: StqUserId, payload: GrantUserCreditRequest) -> ServiceFutureV2<UserCreditsResponse>;
+}
+
+pub struct UserCreditServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<StqUserId>,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > UserCreditService for UserCreditServiceImpl<T, M, F>
+{
+    fn get_my_credits(&self) -> ServiceFutureV2<UserCreditsResponse> {
+        match self.user_id {
+            None => Box::new(future::err(ErrorKind::Forbidden.into())),
+            Some(user_id) => self.get_user_credits(user_id),
+        }
+    }
+
+    fn get_user_credits(&self, credit_user_id: StqUserId) -> ServiceFutureV2<UserCreditsResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let user_id = self.user_id;
+
+        spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let user_credits_repo = repo_factory.create_user_credits_repo(&conn, user_id);
+            let credit_user_id = UserId::new(credit_user_id.0);
+
+            let balances = user_credits_repo
+                .get_balances(credit_user_id)
+                .map_err(ectx!(try convert => credit_user_id))?;
+            let entries = user_credits_repo
+                .list_entries(credit_user_id)
+                .map_err(ectx!(try convert => credit_user_id))?;
+
+            Ok(UserCreditsResponse::new(StqUserId(credit_user_id.inner()), balances, entries))
+        })
+    }
+
+    fn grant_user_credit(&self, credit_user_id: StqUserId, payload: GrantUserCreditRequest) -> ServiceFutureV2<UserCreditsResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let service = self.clone();
+
+        let user_id = match self.user_id {
+            None => return Box::new(future::err(ErrorKind::Forbidden.into())),
+            Some(user_id) => user_id,
+        };
+
+        let GrantUserCreditRequest { currency, amount, reason } = payload;
+        let amount = match granted_amount(currency, amount) {
+            Ok(amount) => amount,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let reason = reason.trim().to_string();
+        if reason.is_empty() {
+            return Box::new(future::err(user_credit_validation_error(
+                "reason",
+                "required",
+                "Credit granted to a user requires a reason",
+            )));
+        }
+
+        let fut = spawn_on_pool(self.db_pool.clone(), self.cpu_pool.clone(), move |conn| {
+            let user_credits_repo = repo_factory.create_user_credits_repo(&conn, Some(user_id));
+            let credit_user_id = UserId::new(credit_user_id.0);
+
+            let new_entry = NewUserCreditEntry {
+                id: UserCreditEntryId::generate(),
+                user_id: credit_user_id,
+                currency,
+                kind: UserCreditEntryKind::Granted,
+                amount,
+                invoice_id: None,
+                reason: Some(reason),
+                created_by: Some(user_id),
+            };
+
+            conn.transaction::<_, ServiceError, _>(|| {
+                user_credits_repo
+                    .credit(credit_user_id, currency, amount)
+                    .map_err(ectx!(try convert => credit_user_id, currency, amount))?;
+                user_credits_repo
+                    .create_entry(new_entry.clone())
+                    .map_err(ectx!(try convert => new_entry))?;
+                Ok(())
+            })?;
+            info!(
+                "User {} has been granted {} {} of credit by user {}",
+                credit_user_id, amount, currency, user_id
+            );
+
+            Ok(credit_user_id)
+        })
+        .and_then(move |credit_user_id| service.get_user_credits(StqUserId(credit_user_id.inner())));
+
+        Box::new(fut)
+    }
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > Clone for UserCreditServiceImpl<T, M, F>
+{
+    fn clone(&self) -> Self {
+        Self {
+            db_pool: self.db_pool.clone(),
+            cpu_pool: self.cpu_pool.clone(),
+            repo_factory: self.repo_factory.clone(),
+            user_id: self.user_id,
+        }
+    }
+}
+
+/// Credit spent on an invoice of `amount_due`, all of it if the balance covers the invoice
+pub fn credit_to_apply(balance: Amount, amount_due: Amount) -> Amount {
+    if balance < amount_due {
+        balance
+    } else {
+        amount_due
+    }
+}
+
+/// Takes the credit spent on the invoice from the balance of the buyer and records it.
+/// Fails with an `insufficient_credit` validation error if the balance has been spent meanwhile
+pub fn apply_invoice_credit(
+    user_credits_repo: &UserCreditsRepo,
+    buyer_user_id: UserId,
+    currency: Currency,
+    invoice_id: InvoiceId,
+    amount: Amount,
+) -> ServiceResultV2<UserCreditEntry> {
+    let balance = user_credits_repo
+        .debit(buyer_user_id, currency, amount)
+        .map_err(ectx!(try convert => buyer_user_id, currency, amount))?;
+    if balance.is_none() {
+        return Err(user_credit_validation_error(
+            "apply_credit",
+            "insufficient_credit",
+            "Credit of the buyer is not enough for the invoice anymore",
+        ));
+    }
+
+    let new_entry = NewUserCreditEntry {
+        id: UserCreditEntryId::generate(),
+        user_id: buyer_user_id,
+        currency,
+        kind: UserCreditEntryKind::Applied,
+        amount,
+        invoice_id: Some(invoice_id),
+        reason: None,
+        created_by: None,
+    };
+    user_credits_repo
+        .create_entry(new_entry.clone())
+        .map_err(ectx!(convert => new_entry))
+}
+
+/// Credit spent on the invoice, zero if none was applied
+pub fn invoice_credit_applied(user_credits_repo: &UserCreditsRepo, invoice_id: InvoiceId) -> ServiceResultV2<Amount> {
+    let entries = user_credits_repo
+        .get_entries_by_invoice_id(invoice_id)
+        .map_err(ectx!(try convert => invoice_id))?;
+
+    Ok(applied_credit(&entries).map(|entry| entry.amount).unwrap_or_default())
+}
+
+/// Gives the credit spent on an unpaid invoice back to the buyer. Credit is restored once,
+/// returns `None` if none was applied or it has already been restored
+pub fn restore_invoice_credit(user_credits_repo: &UserCreditsRepo, invoice_id: InvoiceId) -> ServiceResultV2<Option<UserCreditEntry>> {
+    let entries = user_credits_repo
+        .get_entries_by_invoice_id(invoice_id)
+        .map_err(ectx!(try convert => invoice_id))?;
+
+    let applied = match applied_credit(&entries) {
+        None => return Ok(None),
+        Some(applied) => applied,
+    };
+    if entries.iter().any(|entry| entry.kind == UserCreditEntryKind::Restored) {
+        return Ok(None);
+    }
+
+    let UserCreditEntry {
+        user_id, currency, amount, ..
+    } = *applied;
+    user_credits_repo
+        .credit(user_id, currency, amount)
+        .map_err(ectx!(try convert => user_id, currency, amount))?;
+
+    let new_entry = NewUserCreditEntry {
+        id: UserCreditEntryId::generate(),
+        user_id,
+        currency,
+        kind: UserCreditEntryKind::Restored,
+        amount,
+        invoice_id: Some(invoice_id),
+        reason: None,
+        created_by: None,
+    };
+    user_credits_repo
+        .create_entry(new_entry.clone())
+        .map(Some)
+        .map_err(ectx!(convert => new_entry))
+}
+
+fn applied_credit(entries: &[UserCreditEntry]) -> Option<&UserCreditEntry> {
+    entries.iter().find(|entry| entry.kind == UserCreditEntryKind::Applied)
+}
+
+/// Granted credit in base units, it must be positive and fit into an amount
+fn granted_amount(currency: Currency, amount: BigDecimal) -> ServiceResultV2<Amount> {
+    if amount <= BigDecimal::from(0) {
+        return Err(user_credit_validation_error(
+            "amount",
+            "not_positive",
+            "Granted credit must be positive",
+        ));
+    }
+
+    match Amount::checked_from_super_unit(currency, amount) {
+        Some(amount) if amount > Amount::zero() => Ok(amount),
+        _ => Err(user_credit_validation_error(
+            "amount",
+            "out_of_range",
+            "Granted credit is out of range of the currency",
+        )),
+    }
+}
+
+pub fn user_credit_validation_error(field: &'static str, code: &'static str, message: &str) -> ServiceError {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.to_string().into());
+    errors.add(field, error);
+    ectx!(err ErrorContext::UserCredit, ErrorKind::Validation(serde_json::to_value(errors).unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn credit_covers_the_invoice_up_to_the_balance() {
+        assert_eq!(credit_to_apply(Amount::new(300), Amount::new(1000)), Amount::new(300));
+        assert_eq!(credit_to_apply(Amount::new(1000), Amount::new(1000)), Amount::new(1000));
+        assert_eq!(credit_to_apply(Amount::new(5000), Amount::new(1000)), Amount::new(1000));
+        assert_eq!(credit_to_apply(Amount::zero(), Amount::new(1000)), Amount::zero());
+    }
+
+    #[test]
+    fn granted_credit_is_positive() {
+        assert_eq!(
+            granted_amount(Currency::Usd, BigDecimal::from_str("12.5").unwrap()).unwrap(),
+            Amount::new(1250)
+        );
+        assert!(granted_amount(Currency::Usd, BigDecimal::from(0)).is_err());
+        assert!(granted_amount(Currency::Usd, BigDecimal::from(-5)).is_err());
+        // less than a cent
+        assert!(granted_amount(Currency::Usd, BigDecimal::from_str("0.001").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,420 @@
+//! UserWalletService lets users retire the crypto wallets their payouts are sent to
+//! and prove the control of a wallet before large payouts are sent to it.
+//! A user has at most one active wallet per currency, payouts to deactivated wallets are refused
+use bigdecimal::BigDecimal;
+use chrono::{Duration, Utc};
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use futures::future;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+use ring::rand::{SecureRandom, SystemRandom};
+use validator::{ValidationError, ValidationErrors};
+
+use failure::{err_msg, Fail};
+
+use stq_types::UserId;
+
+use super::types::{ServiceFutureV2, ServiceResultV2};
+use config::WalletVerification as WalletVerificationConfig;
+use controller::requests::ConfirmWalletVerificationRequest;
+use controller::responses::{UserWalletResponse, WalletVerificationResponse};
+use models::{
+    Amount, Currency, Event, EventPayload, NewWalletVerification, TureCurrency, UpdateWalletVerification, UserId as WalletUserId,
+    UserWallet, UserWalletId, WalletAddress, WalletVerificationId, WalletVerificationStatus,
+};
+use repos::{ReposFactory, UserWalletsRepo, WalletVerificationsRepo};
+use services::types::spawn_on_pool;
+use services::ErrorKind;
+
+pub trait UserWalletService {
+    /// Deactivates a wallet of the current user, deactivating an inactive wallet changes nothing
+    fn deactivate_wallet(&self, id: UserWalletId) -> ServiceFutureV2<UserWalletResponse>;
+    /// Sends a transfer of a random small amount to a wallet of the current user, a pending verification is replaced.
+    /// Starting the verification of a verified wallet changes nothing
+    fn start_wallet_verification(&self, id: UserWalletId) -> ServiceFutureV2<WalletVerificationResponse>;
+    /// The most recent verification of a wallet of the current user
+    fn get_wallet_verification(&self, id: UserWalletId) -> ServiceFutureV2<Option<WalletVerificationResponse>>;
+    /// Confirms the amount of the verification transfer, a wrong amount expires the verification
+    fn confirm_wallet_verification(
+        &self,
+        id: UserWalletId,
+        payload: ConfirmWalletVerificationRequest,
+    ) -> ServiceFutureV2<WalletVerificationResponse>;
+}
+
+pub struct UserWalletServiceImpl<
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+> {
+    pub db_pool: Pool<M>,
+    pub cpu_pool: CpuPool,
+    pub repo_factory: F,
+    pub user_id: Option<UserId>,
+    pub wallet_verification: WalletVerificationConfig,
+}
+
+impl<
+        T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+        M: ManageConnection<Connection = T>,
+        F: ReposFactory<T>,
+    > UserWalletService for UserWalletServiceImpl<T, M, F>
+{
+    fn deactivate_wallet(&self, id: UserWalletId) -> ServiceFutureV2<UserWalletResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        let user_id = match self.user_id {
+            None => return Box::new(future::err(ErrorKind::Forbidden.into())),
+            Some(user_id) => user_id,
+        };
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let user_wallets_repo = repo_factory.create_user_wallets_repo(&conn, Some(user_id));
+
+            let user_wallet = get_own_wallet(&*user_wallets_repo, user_id, id)?;
+
+            if !user_wallet.is_active {
+                return Ok(UserWalletResponse::from(user_wallet));
+            }
+
+            user_wallets_repo
+                .deactivate(id)
+                .map(UserWalletResponse::from)
+                .map_err(ectx!(convert => id))
+        })
+    }
+
+    fn start_wallet_verification(&self, id: UserWalletId) -> ServiceFutureV2<WalletVerificationResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+        let config = self.wallet_verification.clone();
+
+        let user_id = match self.user_id {
+            None => return Box::new(future::err(ErrorKind::Forbidden.into())),
+            Some(user_id) => user_id,
+        };
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let user_wallets_repo = repo_factory.create_user_wallets_repo(&conn, Some(user_id));
+            let wallet_verifications_repo = repo_factory.create_wallet_verifications_repo(&conn, Some(user_id));
+            let event_store_repo = repo_factory.create_event_store_repo_with_sys_acl(&conn);
+
+            let user_wallet = get_own_wallet(&*user_wallets_repo, user_id, id)?;
+            if !user_wallet.is_active {
+                let mut errors = ValidationErrors::new();
+                let mut error = ValidationError::new("wallet_deactivated");
+                error.message = Some("Deactivated wallets can't be verified".into());
+                errors.add("user_wallet_id", error);
+
+                return Err(ErrorKind::from(errors).into());
+            }
+
+            let now = Utc::now().naive_utc();
+
+            if let Some(wallet_verification) = wallet_verifications_repo
+                .get_confirmed_by_user_wallet_id(id)
+                .map_err(ectx!(try convert => id))?
+            {
+                return Ok(WalletVerificationResponse::new(wallet_verification, now));
+            }
+
+            let amount = verification_transfer_amount(&config, user_wallet.currency)?;
+
+            conn.transaction(move || {
+                let latest_verification = wallet_verifications_repo
+                    .get_latest_by_user_wallet_id(id)
+                    .map_err(ectx!(try convert => id))?;
+
+                if let Some(latest_verification) = latest_verification {
+                    if latest_verification.status == WalletVerificationStatus::Pending {
+                        let latest_verification_id = latest_verification.id;
+                        wallet_verifications_repo
+                            .update(
+                                latest_verification_id,
+                                UpdateWalletVerification {
+                                    status: Some(WalletVerificationStatus::Expired),
+                                    ..Default::default()
+                                },
+                            )
+                            .map_err(ectx!(try convert => latest_verification_id))?;
+                    }
+                }
+
+                let new_wallet_verification = NewWalletVerification {
+                    id: WalletVerificationId::generate(),
+                    user_wallet_id: id,
+                    user_id: WalletUserId::new(user_id.0),
+                    amount,
+                    status: WalletVerificationStatus::Pending,
+                    expires_at: now + Duration::minutes(config.timeout_min as i64),
+                };
+
+                let wallet_verification = wallet_verifications_repo
+                    .create(new_wallet_verification.clone())
+                    .map_err(ectx!(try convert => new_wallet_verification))?;
+
+                let event = Event::new(EventPayload::WalletVerificationInitiated {
+                    wallet_verification_id: wallet_verification.id,
+                });
+                event_store_repo.add_event(event.clone()).map_err(ectx!(try convert => event))?;
+
+                Ok(WalletVerificationResponse::new(wallet_verification, now))
+            })
+        })
+    }
+
+    fn get_wallet_verification(&self, id: UserWalletId) -> ServiceFutureV2<Option<WalletVerificationResponse>> {
+        let repo_factory = self.repo_factory.clone();
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        let user_id = match self.user_id {
+            None => return Box::new(future::err(ErrorKind::Forbidden.into())),
+            Some(user_id) => user_id,
+        };
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let user_wallets_repo = repo_factory.create_user_wallets_repo(&conn, Some(user_id));
+            let wallet_verifications_repo = repo_factory.create_wallet_verifications_repo(&conn, Some(user_id));
+
+            get_own_wallet(&*user_wallets_repo, user_id, id)?;
+
+            let confirmed_verification = wallet_verifications_repo
+                .get_confirmed_by_user_wallet_id(id)
+                .map_err(ectx!(try convert => id))?;
+
+            // a verified wallet stays verified even if a later verification has expired
+            let wallet_verification = match confirmed_verification {
+                Some(wallet_verification) => Some(wallet_verification),
+                None => wallet_verifications_repo
+                    .get_latest_by_user_wallet_id(id)
+                    .map_err(ectx!(try convert => id))?,
+            };
+
+            Ok(wallet_verification.map(|wallet_verification| WalletVerificationResponse::new(wallet_verification, Utc::now().naive_utc())))
+        })
+    }
+
+    fn confirm_wallet_verification(
+        &self,
+        id: UserWalletId,
+        payload: ConfirmWalletVerificationRequest,
+    ) -> ServiceFutureV2<WalletVerificationResponse> {
+        let repo_factory = self.repo_factory.clone();
+        let db_pool = self.db_pool.clone();
+        let cpu_pool = self.cpu_pool.clone();
+
+        let user_id = match self.user_id {
+            None => return Box::new(future::err(ErrorKind::Forbidden.into())),
+            Some(user_id) => user_id,
+        };
+
+        spawn_on_pool(db_pool, cpu_pool, move |conn| {
+            let user_wallets_repo = repo_factory.create_user_wallets_repo(&conn, Some(user_id));
+            let wallet_verifications_repo = repo_factory.create_wallet_verifications_repo(&conn, Some(user_id));
+
+            let user_wallet = get_own_wallet(&*user_wallets_repo, user_id, id)?;
+
+            let wallet_verification = wallet_verifications_repo
+                .get_latest_by_user_wallet_id(id)
+                .map_err(ectx!(try convert => id))?
+                .ok_or(ErrorKind::NotFound)?;
+
+            let now = Utc::now().naive_utc();
+            if wallet_verification.status == WalletVerificationStatus::Confirmed {
+                return Ok(WalletVerificationResponse::new(wallet_verification, now));
+            }
+
+            let wallet_verification_id = wallet_verification.id;
+            if !wallet_verification.is_pending(now) {
+                if wallet_verification.status == WalletVerificationStatus::Pending {
+                    wallet_verifications_repo
+                        .update(
+                            wallet_verification_id,
+                            UpdateWalletVerification {
+                                status: Some(WalletVerificationStatus::Expired),
+                                ..Default::default()
+                            },
+                        )
+                        .map_err(ectx!(try convert => wallet_verification_id))?;
+                }
+
+                return Err(verification_error(
+                    "verification_expired",
+                    "The verification has expired, a new one has to be started",
+                ));
+            }
+
+            let currency = Currency::from(user_wallet.currency);
+            if Amount::checked_from_super_unit(currency, payload.amount.clone()) != Some(wallet_verification.amount) {
+                // one guess per transfer, otherwise the amount could be found by trying them all
+                wallet_verifications_repo
+                    .update(
+                        wallet_verification_id,
+                        UpdateWalletVerification {
+                            status: Some(WalletVerificationStatus::Expired),
+                            ..Default::default()
+                        },
+                    )
+                    .map_err(ectx!(try convert => wallet_verification_id))?;
+
+                return Err(verification_error(
+                    "amount_mismatch",
+                    "The amount differs from the transfer, a new verification has to be started",
+                ));
+            }
+
+            wallet_verifications_repo
+                .update(
+                    wallet_verification_id,
+                    UpdateWalletVerification {
+                        status: Some(WalletVerificationStatus::Confirmed),
+                        confirmed_at: Some(Some(now)),
+                    },
+                )
+                .map(|wallet_verification| WalletVerificationResponse::new(wallet_verification, now))
+                .map_err(ectx!(convert => wallet_verification_id))
+        })
+    }
+}
+
+/// Payouts of at least the configured amount are only sent to a wallet of the user that has been verified
+pub fn validate_payout_wallet_verified(
+    config: &WalletVerificationConfig,
+    user_wallets_repo: &UserWalletsRepo,
+    wallet_verifications_repo: &WalletVerificationsRepo,
+    user_id: WalletUserId,
+    currency: TureCurrency,
+    wallet_address: &WalletAddress,
+    amount: Amount,
+) -> ServiceResultV2<()> {
+    let large_payout_amount = match config.large_payout_amounts.get(&Currency::from(currency)) {
+        None => return Ok(()),
+        Some(large_payout_amount) => large_payout_amount,
+    };
+
+    let large_payout_amount = super_unit_amount(currency, *large_payout_amount).unwrap_or(Amount::zero());
+    if amount < large_payout_amount {
+        return Ok(());
+    }
+
+    let wallet_address_clone = wallet_address.clone();
+    let user_wallets = user_wallets_repo
+        .get_by_address(user_id, currency, wallet_address.clone())
+        .map_err(ectx!(try convert => user_id, currency, wallet_address_clone))?;
+
+    for user_wallet in user_wallets.iter().filter(|user_wallet| user_wallet.is_active) {
+        let user_wallet_id = user_wallet.id;
+        if wallet_verifications_repo
+            .get_confirmed_by_user_wallet_id(user_wallet_id)
+            .map_err(ectx!(try convert => user_wallet_id))?
+            .is_some()
+        {
+            return Ok(());
+        }
+    }
+
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new("wallet_unverified");
+    error.message = Some("Large payouts are only sent to verified wallets".into());
+    error.add_param("wallet_address".into(), wallet_address);
+    error.add_param(
+        "large_payout_amount".into(),
+        &large_payout_amount.to_super_unit(Currency::from(currency)),
+    );
+    if let Some(user_wallet) = user_wallets.iter().find(|user_wallet| user_wallet.is_active) {
+        error.add_param("user_wallet_id".into(), &user_wallet.id);
+    }
+    errors.add("wallet_address", error);
+
+    Err(ErrorKind::from(errors).into())
+}
+
+/// Wallets of other users are not found even by superusers, the routes are about the current user
+fn get_own_wallet(user_wallets_repo: &UserWalletsRepo, user_id: UserId, id: UserWalletId) -> ServiceResultV2<UserWallet> {
+    user_wallets_repo
+        .get(id)
+        .map_err(ectx!(try convert => id))?
+        .filter(|user_wallet| user_wallet.user_id.inner() == user_id.0)
+        .ok_or(ErrorKind::NotFound.into())
+}
+
+/// Random amount from the smallest unit of the currency up to the configured maximum
+fn verification_transfer_amount(config: &WalletVerificationConfig, currency: TureCurrency) -> ServiceResultV2<Amount> {
+    let max_units = config
+        .max_transfer_amounts
+        .get(&Currency::from(currency))
+        .and_then(|max_transfer_amount| super_unit_amount(currency, *max_transfer_amount))
+        .and_then(|max_transfer_amount| max_transfer_amount.to_u64())
+        .filter(|max_units| *max_units > 0);
+
+    let max_units = match max_units {
+        Some(max_units) => max_units,
+        None => {
+            let mut errors = ValidationErrors::new();
+            let mut error = ValidationError::new("verification_unavailable");
+            error.message = Some("Wallets of the currency can't be verified".into());
+            error.add_param("currency".into(), &currency);
+            errors.add("user_wallet_id", error);
+
+            return Err(ErrorKind::from(errors).into());
+        }
+    };
+
+    let mut bytes = [0u8; 8];
+    SystemRandom::new().fill(&mut bytes).map_err(|_| {
+        let e = err_msg("Failed to generate the amount of a verification transfer");
+        ectx!(try err e, ErrorKind::Internal)
+    })?;
+    let random = bytes.iter().fold(0u64, |acc, byte| (acc << 8) | u64::from(*byte));
+
+    Ok(Amount::new(u128::from(1 + random % max_units)))
+}
+
+fn super_unit_amount(currency: TureCurrency, value: f64) -> Option<Amount> {
+    if value.is_finite() {
+        Amount::checked_from_super_unit(Currency::from(currency), BigDecimal::from(value))
+    } else {
+        None
+    }
+}
+
+fn verification_error(code: &'static str, message: &'static str) -> ::services::Error {
+    let mut errors = ValidationErrors::new();
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    errors.add("amount", error);
+
+    ErrorKind::from(errors).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn verification_transfer_amounts_stay_within_limits() {
+        let mut max_transfer_amounts = HashMap::new();
+        max_transfer_amounts.insert(Currency::Btc, 0.00000005);
+
+        let config = WalletVerificationConfig {
+            large_payout_amounts: HashMap::new(),
+            max_transfer_amounts,
+            timeout_min: 60,
+        };
+
+        for _ in 0..100 {
+            let amount = verification_transfer_amount(&config, TureCurrency::Btc).unwrap();
+            assert!(amount >= Amount::new(1) && amount <= Amount::new(5), "amount: {:?}", amount);
+        }
+
+        assert!(verification_transfer_amount(&config, TureCurrency::Eth).is_err());
+    }
+}
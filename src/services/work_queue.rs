@@ -0,0 +1,145 @@
+//! Depth of the work queued on the CPU pool. The pool takes any number of closures, so under load latencies grow long
+//! before anything fails. A closure is counted from the time it is queued until it is done or dropped unrun. No closure
+//! is refused, the controller refuses new requests while its queue is full. The event handler shares the pool and has
+//! a queue of its own
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+lazy_static! {
+    /// Work of the HTTP requests, queued by `spawn_on_pool` of the services
+    pub static ref CONTROLLER_WORK_QUEUE: WorkQueue = WorkQueue::new("controller");
+    /// Work of the events, queued by `spawn_on_pool` of the event handling
+    pub static ref EVENT_HANDLER_WORK_QUEUE: WorkQueue = WorkQueue::new("event_handler");
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkQueue {
+    name: &'static str,
+    state: Arc<WorkQueueState>,
+}
+
+#[derive(Debug, Default)]
+struct WorkQueueState {
+    depth: AtomicUsize,
+    /// Zero leaves the queue unbounded
+    max_depth: AtomicUsize,
+    rejected: AtomicUsize,
+}
+
+impl WorkQueue {
+    pub fn new(name: &'static str) -> Self {
+        WorkQueue {
+            name,
+            state: Arc::new(WorkQueueState::default()),
+        }
+    }
+
+    /// Sets the number of closures the queue takes before it is full, zero leaves it unbounded
+    pub fn set_max_depth(&self, max_depth: usize) {
+        self.state.max_depth.store(max_depth, Ordering::SeqCst);
+    }
+
+    pub fn depth(&self) -> usize {
+        self.state.depth.load(Ordering::SeqCst)
+    }
+
+    pub fn is_full(&self) -> bool {
+        let max_depth = self.state.max_depth.load(Ordering::SeqCst);
+        max_depth > 0 && self.depth() >= max_depth
+    }
+
+    /// Takes a place in the queue whether it is full or not
+    pub fn enqueue(&self) -> WorkQueueSlot {
+        self.state.depth.fetch_add(1, Ordering::SeqCst);
+        WorkQueueSlot { state: self.state.clone() }
+    }
+
+    /// Counts the request refused before it has queued any work
+    pub fn record_rejected(&self) {
+        self.state.rejected.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Depth, limit and rejected work of the queues in the Prometheus text format
+    pub fn metrics(queues: &[&WorkQueue]) -> String {
+        let mut text = String::new();
+
+        text.push_str("# HELP billing_work_queue_depth Closures queued or running on the CPU pool\n");
+        text.push_str("# TYPE billing_work_queue_depth gauge\n");
+        for queue in queues {
+            text.push_str(&format!("billing_work_queue_depth{{queue=\"{}\"}} {}\n", queue.name, queue.depth()));
+        }
+
+        text.push_str("# HELP billing_work_queue_max_depth Closures the queue takes before it is full, 0 if unbounded\n");
+        text.push_str("# TYPE billing_work_queue_max_depth gauge\n");
+        for queue in queues {
+            text.push_str(&format!(
+                "billing_work_queue_max_depth{{queue=\"{}\"}} {}\n",
+                queue.name,
+                queue.state.max_depth.load(Ordering::SeqCst)
+            ));
+        }
+
+        text.push_str("# HELP billing_work_queue_rejected Work refused because the queue was full\n");
+        text.push_str("# TYPE billing_work_queue_rejected counter\n");
+        for queue in queues {
+            text.push_str(&format!(
+                "billing_work_queue_rejected{{queue=\"{}\"}} {}\n",
+                queue.name,
+                queue.state.rejected.load(Ordering::SeqCst)
+            ));
+        }
+
+        text
+    }
+}
+
+/// Place of a closure in the queue, given back when the slot is dropped
+#[derive(Debug)]
+pub struct WorkQueueSlot {
+    state: Arc<WorkQueueState>,
+}
+
+impl Drop for WorkQueueSlot {
+    fn drop(&mut self) {
+        self.state.depth.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queue_is_full_until_slots_are_given_back() {
+        let queue = WorkQueue::new("test");
+        queue.set_max_depth(2);
+
+        let first = queue.enqueue();
+        let _second = queue.enqueue();
+        assert!(queue.is_full());
+        queue.record_rejected();
+
+        // closures of admitted requests are queued past the limit
+        let third = queue.enqueue();
+        assert_eq!(queue.depth(), 3);
+
+        drop(first);
+        drop(third);
+        assert!(!queue.is_full());
+        assert_eq!(queue.depth(), 1);
+
+        let metrics = WorkQueue::metrics(&[&queue]);
+        assert!(metrics.contains("billing_work_queue_depth{queue=\"test\"} 1\n"));
+        assert!(metrics.contains("billing_work_queue_max_depth{queue=\"test\"} 2\n"));
+        assert!(metrics.contains("billing_work_queue_rejected{queue=\"test\"} 1\n"));
+    }
+
+    #[test]
+    fn unbounded_queue_takes_any_work() {
+        let queue = WorkQueue::new("test");
+
+        let _slots = (0..100).map(|_| queue.enqueue()).collect::<Vec<_>>();
+        assert!(!queue.is_full());
+        assert_eq!(queue.depth(), 100);
+    }
+}
@@ -0,0 +1,398 @@
+//! Verification of the signatures of requests sent to the billing by Stripe webhooks and Payments gateway (Ture) callbacks.
+//! Keys and the clock are passed in, so that a tampered request is rejected the same way in tests as in production
+use chrono::{NaiveDateTime, Utc};
+use hex;
+use ring::constant_time::verify_slices_are_equal;
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey, Signature};
+use sha2::digest::Digest;
+use sha2::Sha256;
+
+use models::store_webhook::hmac_sha256;
+
+/// Largest allowed difference in seconds between the Stripe webhook signature timestamp and now
+pub const STRIPE_TOLERANCE_SECS: i64 = 300;
+
+/// Source of the current time for the signature timestamp checks
+pub trait Clock {
+    fn now(&self) -> NaiveDateTime;
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Utc::now().naive_utc()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Fail)]
+pub enum SignatureError {
+    #[fail(display = "signature error - malformed signature: {}", _0)]
+    MalformedSignature(String),
+    #[fail(display = "signature error - malformed key")]
+    MalformedKey,
+    #[fail(display = "signature error - timestamp {} is outside of the tolerance", _0)]
+    StaleTimestamp(i64),
+    #[fail(display = "signature error - signature does not match")]
+    Mismatch,
+    #[fail(display = "signature error - no signing key is configured")]
+    NoKeys,
+}
+
+/// Checks the `Stripe-Signature` header - `t=<timestamp>,v1=<hex HMAC-SHA256 of "<timestamp>.<payload>">` against
+/// the signing secrets in order. While a secret is rolled Stripe signs webhooks with both, and a webhook sent before
+/// the rotation is only signed with the old one, so a match with any of them is accepted. Returns the key of the match
+pub fn verify_stripe_signature<K, C: Clock>(
+    signing_secrets: Vec<(K, String)>,
+    header: &str,
+    payload: &str,
+    clock: &C,
+) -> Result<K, SignatureError> {
+    let (timestamp, signatures) = parse_stripe_header(header)?;
+    if (clock.now().timestamp() - timestamp).abs() > STRIPE_TOLERANCE_SECS {
+        return Err(SignatureError::StaleTimestamp(timestamp));
+    }
+
+    if signing_secrets.is_empty() {
+        return Err(SignatureError::NoKeys);
+    }
+
+    let signed_payload = format!("{}.{}", timestamp, payload);
+    signing_secrets
+        .into_iter()
+        .find(|(_, secret)| {
+            let expected = hmac_sha256(secret.as_bytes(), signed_payload.as_bytes());
+            signatures
+                .iter()
+                .any(|signature| verify_slices_are_equal(signature, &expected).is_ok())
+        })
+        .map(|(key, _)| key)
+        .ok_or(SignatureError::Mismatch)
+}
+
+/// Timestamp and v1 signatures of the `Stripe-Signature` header, signatures of other schemes are skipped
+fn parse_stripe_header(header: &str) -> Result<(i64, Vec<Vec<u8>>), SignatureError> {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for pair in header.split(',') {
+        let mut parts = pair.trim().splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some("t"), Some(value)) => {
+                let value = value
+                    .parse::<i64>()
+                    .map_err(|_| SignatureError::MalformedSignature(format!("timestamp {} is not a number", value)))?;
+                timestamp = Some(value);
+            }
+            (Some("v1"), Some(value)) => {
+                let signature = decode_hex(value).ok_or_else(|| SignatureError::MalformedSignature(format!("v1 {} is not hex", value)))?;
+                signatures.push(signature);
+            }
+            _ => {}
+        }
+    }
+
+    let timestamp = timestamp.ok_or_else(|| SignatureError::MalformedSignature("header has no timestamp".to_string()))?;
+    if signatures.is_empty() {
+        return Err(SignatureError::MalformedSignature("header has no v1 signature".to_string()));
+    }
+
+    Ok((timestamp, signatures))
+}
+
+/// Checks the compact secp256k1 signature of the SHA-256 of the callback body, Payments gateway signs callbacks
+/// with its private key. The callbacks carry no timestamp, so there is no clock to check it against
+pub fn verify_ture_signature(public_key: &str, signature: &str, body: &str) -> Result<(), SignatureError> {
+    let message = ture_message(body)?;
+    let public_key = decode_hex(public_key)
+        .and_then(|bytes| PublicKey::from_slice(&bytes).ok())
+        .ok_or(SignatureError::MalformedKey)?;
+    let signature = decode_hex(signature)
+        .and_then(|bytes| Signature::from_compact(&bytes).ok())
+        .ok_or_else(|| SignatureError::MalformedSignature(format!("{} is not a compact signature", signature)))?;
+
+    Secp256k1::new()
+        .verify(&message, &signature, &public_key)
+        .map_err(|_| SignatureError::Mismatch)
+}
+
+/// Signs the callback body the way Payments gateway does, `verify_ture_signature` accepts the signature
+pub fn sign_ture_body(private_key: &str, body: &str) -> Result<String, SignatureError> {
+    let message = ture_message(body)?;
+    let secret_key = decode_hex(private_key)
+        .and_then(|bytes| SecretKey::from_slice(&bytes).ok())
+        .ok_or(SignatureError::MalformedKey)?;
+    let signature = Secp256k1::new().sign(&message, &secret_key);
+
+    Ok(hex::encode(&signature.serialize_compact()[..]))
+}
+
+fn ture_message(body: &str) -> Result<Message, SignatureError> {
+    let mut hasher = Sha256::new();
+    hasher.input(body);
+    Message::from_slice(&hasher.result()).map_err(|_| SignatureError::MalformedSignature("body digest is not a message".to_string()))
+}
+
+/// Strict hex decoding, a signature or a key with anything but hex digits is malformed
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+
+    hex::decode(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    const PAYLOAD: &str = r#"{"type":"setup_intent.succeeded"}"#;
+    const SIGNATURE: &str = "f0f2975429454eaf3de0ba12ddd4dcdc7ca07bcb6100a4b59ae8d455c55efbce";
+    const TIMESTAMP: i64 = 1554000000;
+
+    const TURE_BODY: &str = r#"{"transactionId":"6f9e3c2a-0d4b-4a37-9d65-2f0a5c1b7e10","amountCaptured":"1000"}"#;
+
+    struct FixedClock(NaiveDateTime);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> NaiveDateTime {
+            self.0
+        }
+    }
+
+    fn clock_at(timestamp: i64) -> FixedClock {
+        FixedClock(NaiveDateTime::from_timestamp(timestamp, 0))
+    }
+
+    fn secrets(secrets: &[&str]) -> Vec<(usize, String)> {
+        secrets.iter().enumerate().map(|(i, secret)| (i, secret.to_string())).collect()
+    }
+
+    fn stripe_header(timestamp: i64, payload: &str, secret: &str) -> String {
+        let signature = hmac_sha256(secret.as_bytes(), format!("{}.{}", timestamp, payload).as_bytes());
+        format!("t={},v1={}", timestamp, hex::encode(signature))
+    }
+
+    fn ture_public_key(private_key: &str) -> String {
+        let secret_key = SecretKey::from_slice(&hex::decode(private_key).unwrap()).unwrap();
+        hex::encode(&PublicKey::from_secret_key(&Secp256k1::new(), &secret_key).serialize()[..])
+    }
+
+    #[test]
+    fn stripe_signature_matches_known_vector() {
+        let header = format!("t={},v1=deadbeef,v1={}", TIMESTAMP, SIGNATURE);
+        assert_eq!(
+            stripe_header(TIMESTAMP, PAYLOAD, "whsec_test"),
+            format!("t={},v1={}", TIMESTAMP, SIGNATURE)
+        );
+        assert_eq!(
+            verify_stripe_signature(secrets(&["whsec_test"]), &header, PAYLOAD, &clock_at(TIMESTAMP + 100)),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn stripe_signature_with_wrong_key_is_rejected() {
+        let header = stripe_header(TIMESTAMP, PAYLOAD, "whsec_test");
+        let clock = clock_at(TIMESTAMP);
+
+        assert_eq!(
+            verify_stripe_signature(secrets(&["whsec_other"]), &header, PAYLOAD, &clock),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            verify_stripe_signature(secrets(&["whsec_tes"]), &header, PAYLOAD, &clock),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            verify_stripe_signature(secrets(&[]), &header, PAYLOAD, &clock),
+            Err(SignatureError::NoKeys)
+        );
+    }
+
+    #[test]
+    fn stripe_signature_with_secondary_key_is_accepted() {
+        let header = stripe_header(TIMESTAMP, PAYLOAD, "whsec_test");
+        let clock = clock_at(TIMESTAMP);
+
+        assert_eq!(
+            verify_stripe_signature(secrets(&["whsec_other", "whsec_test"]), &header, PAYLOAD, &clock),
+            Ok(1)
+        );
+        assert_eq!(
+            verify_stripe_signature(secrets(&["whsec_other"]), &header, PAYLOAD, &clock),
+            Err(SignatureError::Mismatch)
+        );
+    }
+
+    #[test]
+    fn stripe_signature_of_altered_body_is_rejected() {
+        let header = stripe_header(TIMESTAMP, PAYLOAD, "whsec_test");
+        let clock = clock_at(TIMESTAMP);
+        let altered_bodies = vec![
+            PAYLOAD.replace("succeeded", "canceled"),
+            format!("{} ", PAYLOAD),
+            format!(" {}", PAYLOAD),
+            PAYLOAD.to_uppercase(),
+            String::new(),
+        ];
+
+        for body in altered_bodies {
+            assert_eq!(
+                verify_stripe_signature(secrets(&["whsec_test"]), &header, &body, &clock),
+                Err(SignatureError::Mismatch),
+                "body: {}",
+                body
+            );
+        }
+    }
+
+    #[test]
+    fn stripe_signature_with_altered_timestamp_is_rejected() {
+        let signature = hmac_sha256(b"whsec_test", format!("{}.{}", TIMESTAMP, PAYLOAD).as_bytes());
+        let header = format!("t={},v1={}", TIMESTAMP + 1, hex::encode(signature));
+
+        assert_eq!(
+            verify_stripe_signature(secrets(&["whsec_test"]), &header, PAYLOAD, &clock_at(TIMESTAMP)),
+            Err(SignatureError::Mismatch)
+        );
+    }
+
+    #[test]
+    fn stripe_signature_with_stale_timestamp_is_rejected() {
+        let header = stripe_header(TIMESTAMP, PAYLOAD, "whsec_test");
+        let verify = |now| verify_stripe_signature(secrets(&["whsec_test"]), &header, PAYLOAD, &clock_at(now));
+
+        assert_eq!(verify(TIMESTAMP + STRIPE_TOLERANCE_SECS), Ok(0));
+        assert_eq!(verify(TIMESTAMP - STRIPE_TOLERANCE_SECS), Ok(0));
+        assert_eq!(
+            verify(TIMESTAMP + STRIPE_TOLERANCE_SECS + 1),
+            Err(SignatureError::StaleTimestamp(TIMESTAMP))
+        );
+        assert_eq!(
+            verify(TIMESTAMP - STRIPE_TOLERANCE_SECS - 1),
+            Err(SignatureError::StaleTimestamp(TIMESTAMP))
+        );
+        assert_eq!(
+            verify(NaiveDate::from_ymd(2030, 1, 1).and_hms(0, 0, 0).timestamp()),
+            Err(SignatureError::StaleTimestamp(TIMESTAMP))
+        );
+    }
+
+    #[test]
+    fn malformed_stripe_signature_is_rejected() {
+        let signature = hex::encode(hmac_sha256(b"whsec_test", format!("{}.{}", TIMESTAMP, PAYLOAD).as_bytes()));
+        let malformed_headers = vec![
+            String::new(),
+            ",,,".to_string(),
+            format!("v1={}", signature),
+            format!("t=,v1={}", signature),
+            format!("t=yesterday,v1={}", signature),
+            format!("t={}", TIMESTAMP),
+            format!("t={},v0={}", TIMESTAMP, signature),
+            format!("t={},v1=", TIMESTAMP),
+            format!("t={},v1=g{}", TIMESTAMP, &signature[1..]),
+            format!("t={},v1={}", TIMESTAMP, &signature[1..]),
+        ];
+
+        for header in malformed_headers {
+            match verify_stripe_signature(secrets(&["whsec_test"]), &header, PAYLOAD, &clock_at(TIMESTAMP)) {
+                Err(SignatureError::MalformedSignature(_)) => {}
+                result => panic!("header: {}, result: {:?}", header, result),
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_stripe_signature_is_rejected() {
+        let signature = hex::encode(hmac_sha256(b"whsec_test", format!("{}.{}", TIMESTAMP, PAYLOAD).as_bytes()));
+        let header = format!("t={},v1={}", TIMESTAMP, &signature[..signature.len() - 2]);
+
+        assert_eq!(
+            verify_stripe_signature(secrets(&["whsec_test"]), &header, PAYLOAD, &clock_at(TIMESTAMP)),
+            Err(SignatureError::Mismatch)
+        );
+    }
+
+    #[test]
+    fn ture_signature_is_accepted() {
+        let private_key = "42".repeat(32);
+        let signature = sign_ture_body(&private_key, TURE_BODY).unwrap();
+
+        assert_eq!(verify_ture_signature(&ture_public_key(&private_key), &signature, TURE_BODY), Ok(()));
+    }
+
+    #[test]
+    fn ture_signature_with_wrong_key_is_rejected() {
+        let signature = sign_ture_body(&"42".repeat(32), TURE_BODY).unwrap();
+
+        assert_eq!(
+            verify_ture_signature(&ture_public_key(&"43".repeat(32)), &signature, TURE_BODY),
+            Err(SignatureError::Mismatch)
+        );
+    }
+
+    #[test]
+    fn ture_signature_of_altered_body_is_rejected() {
+        let private_key = "42".repeat(32);
+        let public_key = ture_public_key(&private_key);
+        let signature = sign_ture_body(&private_key, TURE_BODY).unwrap();
+        let altered_bodies = vec![
+            TURE_BODY.replace("1000", "2000"),
+            TURE_BODY.replace("6f9e3c2a", "6f9e3c2b"),
+            format!("{}\n", TURE_BODY),
+            String::new(),
+        ];
+
+        for body in altered_bodies {
+            assert_eq!(
+                verify_ture_signature(&public_key, &signature, &body),
+                Err(SignatureError::Mismatch),
+                "body: {}",
+                body
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_ture_signature_is_rejected() {
+        let private_key = "42".repeat(32);
+        let public_key = ture_public_key(&private_key);
+        let signature = sign_ture_body(&private_key, TURE_BODY).unwrap();
+        let malformed_signatures = vec![
+            String::new(),
+            "deadbeef".to_string(),
+            format!("g{}", &signature[1..]),
+            format!("0x{}", signature),
+            signature[2..].to_string(),
+            format!("{}00", signature),
+        ];
+
+        for signature in malformed_signatures {
+            match verify_ture_signature(&public_key, &signature, TURE_BODY) {
+                Err(SignatureError::MalformedSignature(_)) => {}
+                result => panic!("signature: {}, result: {:?}", signature, result),
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_ture_key_is_rejected() {
+        let private_key = "42".repeat(32);
+        let public_key = ture_public_key(&private_key);
+        let signature = sign_ture_body(&private_key, TURE_BODY).unwrap();
+
+        for public_key in vec![String::new(), "zz".repeat(33), public_key[2..].to_string(), "00".repeat(33)] {
+            assert_eq!(
+                verify_ture_signature(&public_key, &signature, TURE_BODY),
+                Err(SignatureError::MalformedKey),
+                "public key: {}",
+                public_key
+            );
+        }
+        assert_eq!(sign_ture_body("not a key", TURE_BODY), Err(SignatureError::MalformedKey));
+    }
+}
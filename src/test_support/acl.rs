@@ -0,0 +1,262 @@
+//! ACL fixtures: the permission matrix of every role, kept in `repos/acl/permission_matrix.txt`,
+//! and `RecordingAcl` to assert that a repo checks the ACL in every method.
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use failure::Error as FailureError;
+
+use stq_types::{BillingRole, UserId};
+
+use models::authorization::*;
+use repos::legacy_acl::{Acl, CheckScope};
+use repos::ApplicationAcl;
+
+/// Roles in the order of the permission matrix
+pub const ROLES: &[BillingRole] = &[
+    BillingRole::Superuser,
+    BillingRole::User,
+    BillingRole::StoreManager,
+    BillingRole::FinancialManager,
+    BillingRole::Support,
+];
+
+/// Resources in the order of the permission matrix
+pub const RESOURCES: &[Resource] = &[
+    Resource::Account,
+    Resource::BillingInfo,
+    Resource::BillingInfoSecrets,
+    Resource::OrderInfo,
+    Resource::UserRoles,
+    Resource::Invoice,
+    Resource::InvoiceCallback,
+    Resource::InvoiceRequote,
+    Resource::OrderExchangeRate,
+    Resource::PaymentIntent,
+    Resource::PaymentIntentHistory,
+    Resource::ProxyCompanyBillingInfo,
+    Resource::StoreBillingType,
+    Resource::StoreBillingStatus,
+    Resource::Subscription,
+    Resource::StoreSubscription,
+    Resource::StoreSubscriptionStatus,
+    Resource::StoreWebhook,
+    Resource::SubscriptionPayment,
+    Resource::Customer,
+    Resource::FeatureFlag,
+    Resource::Fee,
+    Resource::FeeAdjustment,
+    Resource::FeeCryptoPayment,
+    Resource::FeeStatement,
+    Resource::PaymentIntentInvoice,
+    Resource::PaymentIntentFee,
+    Resource::PaymentRecovery,
+    Resource::UserWallet,
+    Resource::Payout,
+    Resource::PayoutInstruction,
+    Resource::StripeFeeBackfill,
+    Resource::OrderCaptureApproval,
+    Resource::AuditLog,
+    Resource::CashbackLiability,
+    Resource::NegativeStoreBalance,
+    Resource::ExchangeRateSlippage,
+    Resource::PayoutStatement,
+    Resource::SchemaMigration,
+    Resource::BillingInfoChange,
+    Resource::PaymentAttempt,
+    Resource::ApiKey,
+    Resource::DataRetention,
+    Resource::InvoiceSnapshot,
+    Resource::FeeDunning,
+    Resource::PayoutRestriction,
+    Resource::CustomerExport,
+    Resource::InvoiceRiskAssessment,
+    Resource::StoreOffboarding,
+    Resource::HistoricalReport,
+    Resource::UserCredit,
+    Resource::OrderLineItem,
+    Resource::InvoiceBuyerDetails,
+];
+
+/// Actions in the order of the columns of the permission matrix
+pub const ACTIONS: &[Action] = &[Action::Read, Action::Write, Action::All];
+
+// Stops compiling when a resource is added, so that it is added to `RESOURCES` and the matrix too
+#[allow(dead_code)]
+fn listed_in_matrix(resource: Resource) {
+    match resource {
+        Resource::Account
+        | Resource::BillingInfo
+        | Resource::BillingInfoSecrets
+        | Resource::OrderInfo
+        | Resource::UserRoles
+        | Resource::Invoice
+        | Resource::InvoiceCallback
+        | Resource::InvoiceRequote
+        | Resource::OrderExchangeRate
+        | Resource::PaymentIntent
+        | Resource::PaymentIntentHistory
+        | Resource::ProxyCompanyBillingInfo
+        | Resource::StoreBillingType
+        | Resource::StoreBillingStatus
+        | Resource::Subscription
+        | Resource::StoreSubscription
+        | Resource::StoreSubscriptionStatus
+        | Resource::StoreWebhook
+        | Resource::SubscriptionPayment
+        | Resource::Customer
+        | Resource::FeatureFlag
+        | Resource::Fee
+        | Resource::FeeAdjustment
+        | Resource::FeeCryptoPayment
+        | Resource::FeeStatement
+        | Resource::PaymentIntentInvoice
+        | Resource::PaymentIntentFee
+        | Resource::PaymentRecovery
+        | Resource::UserWallet
+        | Resource::Payout
+        | Resource::PayoutInstruction
+        | Resource::StripeFeeBackfill
+        | Resource::OrderCaptureApproval
+        | Resource::AuditLog
+        | Resource::CashbackLiability
+        | Resource::NegativeStoreBalance
+        | Resource::ExchangeRateSlippage
+        | Resource::PayoutStatement
+        | Resource::SchemaMigration
+        | Resource::BillingInfoChange
+        | Resource::PaymentAttempt
+        | Resource::ApiKey
+        | Resource::DataRetention
+        | Resource::InvoiceSnapshot
+        | Resource::FeeDunning
+        | Resource::PayoutRestriction
+        | Resource::CustomerExport
+        | Resource::InvoiceRiskAssessment
+        | Resource::StoreOffboarding
+        | Resource::HistoricalReport
+        | Resource::UserCredit
+        | Resource::OrderLineItem
+        | Resource::InvoiceBuyerDetails => (),
+    }
+}
+
+/// Scope checker telling whether the object belongs to the user, regardless of the object
+struct Ownership {
+    owned: bool,
+}
+
+impl CheckScope<Scope, ()> for Ownership {
+    fn is_in_scope(&self, _user_id: UserId, scope: &Scope, _obj: Option<&()>) -> bool {
+        match *scope {
+            Scope::All => true,
+            Scope::Owned => self.owned,
+        }
+    }
+}
+
+/// Cell of the permission matrix: `all` - allowed on any object, `owned` - allowed on the objects of the user only,
+/// `-` - denied
+pub fn matrix_cell(role: BillingRole, resource: Resource, action: Action) -> &'static str {
+    let acl = ApplicationAcl::new(vec![role], UserId(1));
+    let allows = |owned| {
+        <ApplicationAcl as Acl<Resource, Action, Scope, FailureError, ()>>::allows(&acl, resource, action, &Ownership { owned }, Some(&()))
+            .expect("ApplicationAcl never fails")
+    };
+
+    if allows(false) {
+        "all"
+    } else if allows(true) {
+        "owned"
+    } else {
+        "-"
+    }
+}
+
+/// Renders the allow/deny matrix of every role, resource, action and scope, one line per role and resource
+pub fn render_permission_matrix() -> String {
+    let mut matrix = format!("{:<17} {:<24} {:<6} {:<6} {}\n", "# role", "resource", "read", "write", "all");
+    for role in ROLES {
+        for resource in RESOURCES {
+            let cells = ACTIONS
+                .iter()
+                .map(|action| matrix_cell(*role, *resource, *action))
+                .collect::<Vec<_>>();
+            matrix.push_str(&format!(
+                "{:<17} {:<24} {:<6} {:<6} {}\n",
+                format!("{:?}", role),
+                format!("{:?}", resource),
+                cells[0],
+                cells[1],
+                cells[2]
+            ));
+        }
+    }
+    matrix
+}
+
+/// ACL answering every check the same way and remembering the checks,
+/// clones share the checks so the test keeps one while the repo owns the other
+#[derive(Clone, Default)]
+pub struct RecordingAcl {
+    allow: bool,
+    checks: Arc<Mutex<Vec<(Resource, Action)>>>,
+}
+
+impl RecordingAcl {
+    pub fn allowing() -> Self {
+        RecordingAcl {
+            allow: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn denying() -> Self {
+        RecordingAcl::default()
+    }
+
+    /// Checks made since the last call
+    pub fn take_checks(&self) -> Vec<(Resource, Action)> {
+        let mut checks = self.checks.lock().unwrap();
+        checks.drain(..).collect()
+    }
+}
+
+impl<T> Acl<Resource, Action, Scope, FailureError, T> for RecordingAcl {
+    fn allows(
+        &self,
+        resource: Resource,
+        action: Action,
+        _scope_checker: &CheckScope<Scope, T>,
+        _obj: Option<&T>,
+    ) -> Result<bool, FailureError> {
+        self.checks.lock().unwrap().push((resource, action));
+        Ok(self.allow)
+    }
+}
+
+/// Asserts that the repo method just called with the denying `acl` checked `action` on `resource` and failed
+pub fn assert_forbidden<T: Debug, E>(acl: &RecordingAcl, resource: Resource, action: Action, result: Result<T, E>) {
+    let checks = acl.take_checks();
+    assert!(
+        checks.contains(&(resource, action)),
+        "expected a check of {:?} on {:?}, got {:?}",
+        action,
+        resource,
+        checks
+    );
+    if let Ok(value) = result {
+        panic!("expected {:?} on {:?} to be forbidden, got {:?}", action, resource, value);
+    }
+}
+
+/// Asserts that the repo method just called with the allowing `acl` checked `action` on `resource`
+pub fn assert_checked(acl: &RecordingAcl, resource: Resource, action: Action) {
+    let checks = acl.take_checks();
+    assert!(
+        checks.contains(&(resource, action)),
+        "expected a check of {:?} on {:?}, got {:?}",
+        action,
+        resource,
+        checks
+    );
+}
@@ -0,0 +1,463 @@
+//! Builders of model fixtures. Every builder starts from a valid model and only the fields
+//! that matter to a test are set, e.g. `RawOrderBuilder::new().state(PaymentState::Paid).build()`
+use std::time::SystemTime;
+
+use chrono::{NaiveDateTime, Utc};
+use serde_json;
+use stq_static_resources::{Currency as StqCurrency, OrderState};
+use stq_types::stripe::PaymentIntentId;
+use stq_types::{InvoiceId as SagaInvoiceId, OrderId as StqOrderId, OrderInfoId, ProductPrice, SagaId, StoreId as StqStoreId, UserId};
+use uuid::Uuid;
+
+use models::invoice_v2::{generate_public_token, InvoiceId, RawInvoice};
+use models::order_v2::{OrderId, RawOrder, StoreId};
+use models::UserId as BuyerUserId;
+use models::{
+    Account, AccountId, AccountStatus, Amount, ChargeId, CryptoWalletPayoutTarget, Currency, Fee, FeeId, FeeStatus, Invoice, OrderInfo,
+    PaymentIntent, PaymentIntentStatus, PaymentState, Payout, PayoutFeePayer, PayoutId, PayoutRequestId, PayoutStatus, PayoutTarget,
+    TureCurrency, WalletAddress,
+};
+
+macro_rules! setters {
+    ($model:ident { $($field:ident: $ty:ty),* $(,)* }) => {
+        $(
+            pub fn $field(mut self, $field: $ty) -> Self {
+                self.$model.$field = $field;
+                self
+            }
+        )*
+    };
+}
+
+fn now() -> NaiveDateTime {
+    Utc::now().naive_utc()
+}
+
+pub struct RawOrderBuilder {
+    order: RawOrder,
+}
+
+impl Default for RawOrderBuilder {
+    fn default() -> Self {
+        let now = now();
+        Self {
+            order: RawOrder {
+                id: OrderId::generate(),
+                seller_currency: Currency::Eur,
+                total_amount: Amount::new(10000),
+                cashback_amount: Amount::new(0),
+                invoice_id: InvoiceId::generate(),
+                created_at: now,
+                updated_at: now,
+                store_id: StoreId::new(1),
+                state: PaymentState::Initial,
+                stripe_fee: None,
+            },
+        }
+    }
+}
+
+impl RawOrderBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Order awaiting payout to the seller
+    pub fn payable(seller_currency: Currency, total_amount: Amount) -> Self {
+        Self::new()
+            .seller_currency(seller_currency)
+            .total_amount(total_amount)
+            .state(PaymentState::PaymentToSellerNeeded)
+    }
+
+    setters!(order {
+        id: OrderId,
+        seller_currency: Currency,
+        total_amount: Amount,
+        cashback_amount: Amount,
+        invoice_id: InvoiceId,
+        created_at: NaiveDateTime,
+        updated_at: NaiveDateTime,
+        store_id: StoreId,
+        state: PaymentState,
+        stripe_fee: Option<Amount>,
+    });
+
+    pub fn build(self) -> RawOrder {
+        self.order
+    }
+}
+
+pub struct RawInvoiceBuilder {
+    invoice: RawInvoice,
+}
+
+impl Default for RawInvoiceBuilder {
+    fn default() -> Self {
+        let now = now();
+        Self {
+            invoice: RawInvoice {
+                id: InvoiceId::generate(),
+                account_id: None,
+                buyer_currency: Currency::Eur,
+                amount_captured: Amount::new(0),
+                final_amount_paid: None,
+                final_cashback_amount: None,
+                paid_at: None,
+                created_at: now,
+                updated_at: now,
+                buyer_user_id: BuyerUserId::new(1),
+                status: OrderState::PaymentAwaited,
+                metadata: None,
+                memo: None,
+                po_number: None,
+                price_reserved: now,
+                public_token: generate_public_token(),
+            },
+        }
+    }
+}
+
+impl RawInvoiceBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    setters!(invoice {
+        id: InvoiceId,
+        account_id: Option<AccountId>,
+        buyer_currency: Currency,
+        amount_captured: Amount,
+        final_amount_paid: Option<Amount>,
+        final_cashback_amount: Option<Amount>,
+        paid_at: Option<NaiveDateTime>,
+        created_at: NaiveDateTime,
+        updated_at: NaiveDateTime,
+        buyer_user_id: BuyerUserId,
+        status: OrderState,
+        metadata: Option<serde_json::Value>,
+        memo: Option<String>,
+        po_number: Option<String>,
+        price_reserved: NaiveDateTime,
+        public_token: String,
+    });
+
+    /// Marks the invoice paid in full, as `InvoicesV2Repo::set_amount_paid` does
+    pub fn paid(mut self, final_amount_paid: Amount) -> Self {
+        self.invoice.final_amount_paid = Some(final_amount_paid);
+        self.invoice.final_cashback_amount = Some(Amount::new(0));
+        self.invoice.paid_at = Some(now());
+        self.invoice.status = OrderState::Paid;
+        self
+    }
+
+    pub fn build(self) -> RawInvoice {
+        self.invoice
+    }
+}
+
+pub struct PaymentIntentBuilder {
+    payment_intent: PaymentIntent,
+}
+
+impl Default for PaymentIntentBuilder {
+    fn default() -> Self {
+        let now = now();
+        Self {
+            payment_intent: PaymentIntent {
+                id: PaymentIntentId("pi_test".to_string()),
+                amount: Amount::new(10000),
+                amount_received: Amount::new(0),
+                client_secret: None,
+                currency: Currency::Eur,
+                last_payment_error_message: None,
+                receipt_email: None,
+                charge_id: None,
+                status: PaymentIntentStatus::RequiresSource,
+                created_at: now,
+                updated_at: now,
+                status_event_created_at: None,
+            },
+        }
+    }
+}
+
+impl PaymentIntentBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    setters!(payment_intent {
+        id: PaymentIntentId,
+        amount: Amount,
+        amount_received: Amount,
+        client_secret: Option<String>,
+        currency: Currency,
+        last_payment_error_message: Option<String>,
+        receipt_email: Option<String>,
+        charge_id: Option<ChargeId>,
+        status: PaymentIntentStatus,
+        created_at: NaiveDateTime,
+        updated_at: NaiveDateTime,
+        status_event_created_at: Option<NaiveDateTime>,
+    });
+
+    pub fn build(self) -> PaymentIntent {
+        self.payment_intent
+    }
+}
+
+pub struct FeeBuilder {
+    fee: Fee,
+}
+
+impl Default for FeeBuilder {
+    fn default() -> Self {
+        let now = now();
+        Self {
+            fee: Fee {
+                id: FeeId::new(1),
+                order_id: OrderId::generate(),
+                amount: Amount::new(500),
+                status: FeeStatus::NotPaid,
+                currency: Currency::Eur,
+                charge_id: None,
+                metadata: None,
+                created_at: now,
+                updated_at: now,
+                crypto_currency: None,
+                crypto_amount: None,
+                exchange_rate: None,
+                currency_exchange_id: None,
+                converted_at: None,
+                charged_currency: None,
+                charged_amount: None,
+                charge_exchange_rate: None,
+            },
+        }
+    }
+}
+
+impl FeeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    setters!(fee {
+        id: FeeId,
+        order_id: OrderId,
+        amount: Amount,
+        status: FeeStatus,
+        currency: Currency,
+        charge_id: Option<ChargeId>,
+        metadata: Option<serde_json::Value>,
+        created_at: NaiveDateTime,
+        updated_at: NaiveDateTime,
+        crypto_currency: Option<Currency>,
+        crypto_amount: Option<Amount>,
+        exchange_rate: Option<f64>,
+        currency_exchange_id: Option<Uuid>,
+        converted_at: Option<NaiveDateTime>,
+        charged_currency: Option<Currency>,
+        charged_amount: Option<Amount>,
+        charge_exchange_rate: Option<f64>,
+    });
+
+    pub fn build(self) -> Fee {
+        self.fee
+    }
+}
+
+pub struct AccountBuilder {
+    account: Account,
+}
+
+impl Default for AccountBuilder {
+    fn default() -> Self {
+        Self {
+            account: Account {
+                id: AccountId::generate(),
+                currency: TureCurrency::Stq,
+                is_pooled: false,
+                created_at: now(),
+                wallet_address: WalletAddress::new("0x0000000000000000000000000000000000000000".to_string()),
+                status: AccountStatus::Active,
+            },
+        }
+    }
+}
+
+impl AccountBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    setters!(account {
+        id: AccountId,
+        currency: TureCurrency,
+        is_pooled: bool,
+        created_at: NaiveDateTime,
+        wallet_address: WalletAddress,
+        status: AccountStatus,
+    });
+
+    pub fn build(self) -> Account {
+        self.account
+    }
+}
+
+/// Order of the legacy saga flow
+pub struct OrderInfoBuilder {
+    order_info: OrderInfo,
+}
+
+impl Default for OrderInfoBuilder {
+    fn default() -> Self {
+        let now = SystemTime::now();
+        Self {
+            order_info: OrderInfo {
+                id: OrderInfoId::new(),
+                order_id: StqOrderId::new(),
+                status: OrderState::New,
+                created_at: now,
+                updated_at: now,
+                customer_id: UserId(1),
+                store_id: StqStoreId(1),
+                saga_id: SagaId::new(),
+                total_amount: ProductPrice(100.0),
+            },
+        }
+    }
+}
+
+impl OrderInfoBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    setters!(order_info {
+        id: OrderInfoId,
+        order_id: StqOrderId,
+        status: OrderState,
+        created_at: SystemTime,
+        updated_at: SystemTime,
+        customer_id: UserId,
+        store_id: StqStoreId,
+        saga_id: SagaId,
+        total_amount: ProductPrice,
+    });
+
+    pub fn build(self) -> OrderInfo {
+        self.order_info
+    }
+}
+
+/// Invoice of the legacy external billing flow
+pub struct InvoiceBuilder {
+    invoice: Invoice,
+}
+
+impl Default for InvoiceBuilder {
+    fn default() -> Self {
+        let now = SystemTime::now();
+        Self {
+            invoice: Invoice {
+                id: SagaId::new(),
+                invoice_id: SagaInvoiceId::new(),
+                amount: ProductPrice(1.0),
+                price_reserved: now,
+                state: OrderState::New,
+                wallet: None,
+                created_at: now,
+                updated_at: now,
+                transactions: serde_json::Value::Array(vec![]),
+                amount_captured: ProductPrice(0.0),
+                currency: StqCurrency::STQ,
+            },
+        }
+    }
+}
+
+impl InvoiceBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    setters!(invoice {
+        id: SagaId,
+        invoice_id: SagaInvoiceId,
+        amount: ProductPrice,
+        price_reserved: SystemTime,
+        state: OrderState,
+        wallet: Option<String>,
+        created_at: SystemTime,
+        updated_at: SystemTime,
+        transactions: serde_json::Value,
+        amount_captured: ProductPrice,
+        currency: StqCurrency,
+    });
+
+    pub fn build(self) -> Invoice {
+        self.invoice
+    }
+}
+
+pub struct PayoutBuilder {
+    payout: Payout,
+}
+
+impl Default for PayoutBuilder {
+    fn default() -> Self {
+        Self {
+            payout: Payout {
+                id: PayoutId::generate(),
+                gross_amount: Amount::new(1000),
+                net_amount: Amount::new(1000),
+                target: PayoutTarget::CryptoWallet(CryptoWalletPayoutTarget {
+                    currency: TureCurrency::Stq,
+                    wallet_address: WalletAddress::new("0x0".to_string()),
+                    blockchain_fee: Amount::new(0),
+                }),
+                user_id: BuyerUserId::new(1),
+                status: PayoutStatus::Processing { initiated_at: now() },
+                order_ids: vec![],
+                fee_payer: PayoutFeePayer::Payout,
+                carried_fee: Amount::new(0),
+                request_id: None,
+            },
+        }
+    }
+}
+
+impl PayoutBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    setters!(payout {
+        id: PayoutId,
+        gross_amount: Amount,
+        net_amount: Amount,
+        target: PayoutTarget,
+        user_id: BuyerUserId,
+        status: PayoutStatus,
+        order_ids: Vec<OrderId>,
+        fee_payer: PayoutFeePayer,
+        carried_fee: Amount,
+        request_id: Option<PayoutRequestId>,
+    });
+
+    /// Pays out to a crypto wallet in `currency` with the network fee `blockchain_fee`
+    pub fn crypto_wallet(mut self, currency: TureCurrency, blockchain_fee: Amount) -> Self {
+        self.payout.target = PayoutTarget::CryptoWallet(CryptoWalletPayoutTarget {
+            currency,
+            wallet_address: WalletAddress::new("0x0".to_string()),
+            blockchain_fee,
+        });
+        self
+    }
+
+    pub fn build(self) -> Payout {
+        self.payout
+    }
+}
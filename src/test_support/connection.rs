@@ -0,0 +1,104 @@
+//! Database connection that never reaches a database, repos are expected to be replaced
+//! by mocks or by `InMemoryReposFactory`. Transactions always succeed.
+use std::error::Error;
+use std::fmt;
+
+use diesel::connection::{AnsiTransactionManager, SimpleConnection};
+use diesel::deserialize::QueryableByName;
+use diesel::pg::Pg;
+use diesel::query_builder::{AsQuery, QueryFragment, QueryId};
+use diesel::sql_types::HasSqlType;
+use diesel::{Connection, ConnectionResult, QueryResult, Queryable};
+use r2d2::ManageConnection;
+
+#[derive(Default)]
+pub struct MockConnection {
+    tr: AnsiTransactionManager,
+}
+
+impl Connection for MockConnection {
+    type Backend = Pg;
+    type TransactionManager = AnsiTransactionManager;
+
+    fn establish(_database_url: &str) -> ConnectionResult<MockConnection> {
+        Ok(MockConnection::default())
+    }
+
+    fn execute(&self, _query: &str) -> QueryResult<usize> {
+        unimplemented!()
+    }
+
+    fn query_by_index<T, U>(&self, _source: T) -> QueryResult<Vec<U>>
+    where
+        T: AsQuery,
+        T::Query: QueryFragment<Pg> + QueryId,
+        Pg: HasSqlType<T::SqlType>,
+        U: Queryable<T::SqlType, Pg>,
+    {
+        unimplemented!()
+    }
+
+    fn query_by_name<T, U>(&self, _source: &T) -> QueryResult<Vec<U>>
+    where
+        T: QueryFragment<Pg> + QueryId,
+        U: QueryableByName<Pg>,
+    {
+        unimplemented!()
+    }
+
+    fn execute_returning_count<T>(&self, _source: &T) -> QueryResult<usize>
+    where
+        T: QueryFragment<Pg> + QueryId,
+    {
+        unimplemented!()
+    }
+
+    fn transaction_manager(&self) -> &Self::TransactionManager {
+        &self.tr
+    }
+}
+
+impl SimpleConnection for MockConnection {
+    fn batch_execute(&self, _query: &str) -> QueryResult<()> {
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MockConnectionManager;
+
+impl ManageConnection for MockConnectionManager {
+    type Connection = MockConnection;
+    type Error = MockError;
+
+    fn connect(&self) -> Result<MockConnection, MockError> {
+        Ok(MockConnection::default())
+    }
+
+    fn is_valid(&self, _conn: &mut MockConnection) -> Result<(), MockError> {
+        Ok(())
+    }
+
+    fn has_broken(&self, _conn: &mut MockConnection) -> bool {
+        false
+    }
+}
+
+#[derive(Debug)]
+pub struct MockError {}
+
+impl fmt::Display for MockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SuperError is here!")
+    }
+}
+
+impl Error for MockError {
+    fn description(&self) -> &str {
+        "I'm the superhero of errors"
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        None
+    }
+}
@@ -0,0 +1,15 @@
+//! Fixtures for tests of the billing and of the services depending on it, enabled
+//! with the `test-support` feature.
+//!
+//! Model builders live in `builders`, `InMemoryReposFactory` replaces the database
+//! behind the services and `MockConnection` satisfies the connection pool.
+//! `acl` renders the permission matrix and records the ACL checks of repos.
+pub mod acl;
+pub mod builders;
+pub mod connection;
+pub mod repos;
+
+pub use self::acl::*;
+pub use self::builders::*;
+pub use self::connection::*;
+pub use self::repos::*;
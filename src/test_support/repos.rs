@@ -0,0 +1,1590 @@
+//! `ReposFactory` keeping orders, invoices, payment intents, fees, events, legacy
+//! order infos and invoices and data retention subjects in memory.
+//!
+//! Every repo created by the factory shares the same state, so a test can seed it,
+//! run a service and inspect what the service has written. ACLs are not checked and
+//! nothing is rolled back when a transaction fails. Repos of other models are not
+//! implemented and panic when created.
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::connection::AnsiTransactionManager;
+use diesel::pg::Pg;
+use diesel::Connection;
+use failure::Error as FailureError;
+use stq_static_resources::OrderState;
+use stq_types::stripe::PaymentIntentId;
+use stq_types::{BillingRole, InvoiceId as SagaInvoiceId, OrderId as StqOrderId, OrderInfoId, SagaId, UserId};
+
+use models::invoice_v2::{InvoiceId, InvoiceSetAmountPaid, NewInvoice, RawAmountReceived, RawInvoice, UpdateInvoiceDetails};
+use models::order_v2::{InvoicePaymentStatus, NewOrder, OrderId, OrderSearchResults, OrdersSearch, RawOrder, StoreId};
+use models::UserId as BuyerUserId;
+use models::{
+    AccountId, Amount, ApiKeyScope, Currency, DataRetentionSubject, DataSubject, Event, EventEntry, EventEntryId, EventPayloadTypes,
+    EventStatus, EventTimings, Fee, FeeId, Invoice, InvoiceSnapshot, NewFee, NewInvoiceSnapshot, NewOrderInfo, NewPaymentIntent, OrderInfo,
+    PaymentIntent, PaymentState, RetentionTable, StoreBalanceBucketAmount, TransactionId, UpdateFee, UpdateInvoice, UpdatePaymentIntent,
+};
+use repos::Error as RepoError;
+use repos::*;
+
+const DEFAULT_MAX_PROCESSING_ATTEMPTS: u32 = 3;
+const LEASE_DURATION_SEC: i64 = 300;
+const INSTANCE_ID: &'static str = "in-memory";
+
+#[derive(Clone)]
+pub struct InMemoryReposFactory {
+    state: Arc<Mutex<InMemoryState>>,
+}
+
+#[derive(Clone)]
+pub struct InMemoryState {
+    pub orders: Vec<RawOrder>,
+    pub invoices: Vec<RawInvoice>,
+    pub amounts_received: Vec<RawAmountReceived>,
+    pub payment_intents: Vec<PaymentIntent>,
+    pub fees: Vec<Fee>,
+    pub events: Vec<EventEntry>,
+    pub order_infos: Vec<OrderInfo>,
+    pub legacy_invoices: Vec<Invoice>,
+    pub data_retention_subjects: Vec<DataRetentionSubject>,
+    /// Rows holding personal data, one entry per row with the table and the id of the subject owning it
+    pub retained_data: Vec<(RetentionTable, i32)>,
+    pub invoice_snapshots: Vec<InvoiceSnapshot>,
+    max_processing_attempts: u32,
+    failing_operations: HashSet<&'static str>,
+}
+
+impl Default for InMemoryReposFactory {
+    fn default() -> Self {
+        let state = InMemoryState {
+            orders: vec![],
+            invoices: vec![],
+            amounts_received: vec![],
+            payment_intents: vec![],
+            fees: vec![],
+            events: vec![],
+            order_infos: vec![],
+            legacy_invoices: vec![],
+            data_retention_subjects: vec![],
+            retained_data: vec![],
+            invoice_snapshots: vec![],
+            max_processing_attempts: DEFAULT_MAX_PROCESSING_ATTEMPTS,
+            failing_operations: HashSet::new(),
+        };
+
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+}
+
+impl InMemoryReposFactory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_orders(self, orders: Vec<RawOrder>) -> Self {
+        self.lock().orders.extend(orders);
+        self
+    }
+
+    pub fn with_invoices(self, invoices: Vec<RawInvoice>) -> Self {
+        self.lock().invoices.extend(invoices);
+        self
+    }
+
+    pub fn with_payment_intents(self, payment_intents: Vec<PaymentIntent>) -> Self {
+        self.lock().payment_intents.extend(payment_intents);
+        self
+    }
+
+    pub fn with_fees(self, fees: Vec<Fee>) -> Self {
+        self.lock().fees.extend(fees);
+        self
+    }
+
+    pub fn with_order_infos(self, order_infos: Vec<OrderInfo>) -> Self {
+        self.lock().order_infos.extend(order_infos);
+        self
+    }
+
+    pub fn with_legacy_invoices(self, invoices: Vec<Invoice>) -> Self {
+        self.lock().legacy_invoices.extend(invoices);
+        self
+    }
+
+    pub fn with_data_retention_subjects(self, subjects: Vec<DataRetentionSubject>) -> Self {
+        self.lock().data_retention_subjects.extend(subjects);
+        self
+    }
+
+    pub fn with_retained_data(self, rows: Vec<(RetentionTable, i32)>) -> Self {
+        self.lock().retained_data.extend(rows);
+        self
+    }
+
+    pub fn with_max_processing_attempts(self, max_processing_attempts: u32) -> Self {
+        self.lock().max_processing_attempts = max_processing_attempts;
+        self
+    }
+
+    /// Makes the repo operation fail with `ErrorKind::Internal`, e.g. `fail_on("orders.update_state")`.
+    /// Operations are named after the repo and its method
+    pub fn fail_on(self, operation: &'static str) -> Self {
+        self.lock().failing_operations.insert(operation);
+        self
+    }
+
+    /// Snapshot of the stored models
+    pub fn state(&self) -> InMemoryState {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> MutexGuard<InMemoryState> {
+        self.state.lock().expect("in-memory repos state is poisoned")
+    }
+
+    fn repos(&self) -> InMemoryRepos {
+        InMemoryRepos { state: self.state.clone() }
+    }
+}
+
+/// Implements every supported repo trait on top of the shared state
+struct InMemoryRepos {
+    state: Arc<Mutex<InMemoryState>>,
+}
+
+impl InMemoryRepos {
+    fn lock(&self, operation: &'static str) -> RepoResultV2<MutexGuard<InMemoryState>> {
+        let state = self.state.lock().expect("in-memory repos state is poisoned");
+        if state.failing_operations.contains(operation) {
+            let e = format_err!("Operation \"{}\" is set up to fail", operation);
+            return Err(ectx!(err e, ErrorKind::Internal));
+        }
+        Ok(state)
+    }
+
+    fn lock_legacy(&self, operation: &'static str) -> RepoResult<MutexGuard<InMemoryState>> {
+        self.lock(operation).map_err(FailureError::from)
+    }
+}
+
+fn not_found<T: ::std::fmt::Display>(entity: &str, id: T) -> RepoError {
+    let e = format_err!("{} {} not found", entity, id);
+    ectx!(err e, ErrorKind::NotFound)
+}
+
+impl OrdersRepo for InMemoryRepos {
+    fn get(&self, order_id: OrderId) -> RepoResultV2<Option<RawOrder>> {
+        let state = self.lock("orders.get")?;
+        Ok(state.orders.iter().find(|order| order.id == order_id).cloned())
+    }
+
+    fn get_many(&self, order_ids: &[OrderId]) -> RepoResultV2<Vec<RawOrder>> {
+        let state = self.lock("orders.get_many")?;
+        Ok(state.orders.iter().filter(|order| order_ids.contains(&order.id)).cloned().collect())
+    }
+
+    fn get_many_by_invoice_id(&self, invoice_id: InvoiceId) -> RepoResultV2<Vec<RawOrder>> {
+        let state = self.lock("orders.get_many_by_invoice_id")?;
+        Ok(state
+            .orders
+            .iter()
+            .filter(|order| order.invoice_id == invoice_id)
+            .cloned()
+            .collect())
+    }
+
+    fn get_order_ids_by_store_id(&self, store_id: StoreId) -> RepoResultV2<Vec<OrderId>> {
+        let state = self.lock("orders.get_order_ids_by_store_id")?;
+        Ok(state
+            .orders
+            .iter()
+            .filter(|order| order.store_id == store_id)
+            .map(|order| order.id)
+            .collect())
+    }
+
+    fn get_orders_for_payout(&self, store_id: StoreId, currency: Option<Currency>) -> RepoResultV2<Vec<RawOrder>> {
+        let state = self.lock("orders.get_orders_for_payout")?;
+        Ok(state
+            .orders
+            .iter()
+            .filter(|order| order.state == PaymentState::PaymentToSellerNeeded && order.store_id == store_id)
+            .filter(|order| currency.map_or(true, |currency| order.seller_currency == currency))
+            .cloned()
+            .collect())
+    }
+
+    fn get_orders_without_stripe_fee(&self, states: Vec<PaymentState>) -> RepoResultV2<Vec<RawOrder>> {
+        let state = self.lock("orders.get_orders_without_stripe_fee")?;
+        let mut orders = state
+            .orders
+            .iter()
+            .filter(|order| order.stripe_fee.is_none() && states.contains(&order.state))
+            .cloned()
+            .collect::<Vec<_>>();
+        orders.sort_by_key(|order| order.created_at);
+        Ok(orders)
+    }
+
+    fn get_store_balance_overview(&self, _store_id: StoreId) -> RepoResultV2<Vec<StoreBalanceBucketAmount>> {
+        unimplemented!()
+    }
+
+    fn search(&self, skip: i64, count: i64, search: OrdersSearch) -> RepoResultV2<OrderSearchResults> {
+        let state = self.lock("orders.search")?;
+        let OrdersSearch {
+            store_id,
+            state: payment_state,
+            order_id,
+            order_ids,
+            payment_status,
+        } = search;
+
+        let orders = state
+            .orders
+            .iter()
+            .filter(|order| store_id.map_or(true, |store_id| order.store_id == store_id))
+            .filter(|order| payment_state.map_or(true, |payment_state| order.state == payment_state))
+            .filter(|order| order_id.map_or(true, |order_id| order.id == order_id))
+            .filter(|order| order_ids.as_ref().map_or(true, |order_ids| order_ids.contains(&order.id)))
+            .filter(|order| {
+                payment_status.map_or(true, |payment_status| {
+                    // Fee adjustments are not kept in memory, so refunds are told by the order state only
+                    let is_refunded = InvoicePaymentStatus::refunded_states().contains(&order.state);
+                    let invoice = state.invoices.iter().find(|invoice| invoice.id == order.invoice_id);
+                    match payment_status {
+                        InvoicePaymentStatus::AwaitingPayment => invoice.map_or(false, |invoice| {
+                            invoice.paid_at.is_none()
+                                && (invoice.status == OrderState::PaymentAwaited || invoice.status == OrderState::TransactionPending)
+                        }),
+                        InvoicePaymentStatus::Paid => !is_refunded && invoice.map_or(false, |invoice| invoice.paid_at.is_some()),
+                        InvoicePaymentStatus::Refunded => is_refunded,
+                    }
+                })
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        Ok(OrderSearchResults {
+            total_count: orders.len() as i64,
+            orders: orders.into_iter().skip(skip as usize).take(count as usize).collect(),
+        })
+    }
+
+    fn create(&self, payload: NewOrder) -> RepoResultV2<RawOrder> {
+        let mut state = self.lock("orders.create")?;
+        let now = Utc::now().naive_utc();
+        let order = RawOrder {
+            id: payload.id,
+            seller_currency: payload.seller_currency,
+            total_amount: payload.total_amount,
+            cashback_amount: payload.cashback_amount,
+            invoice_id: payload.invoice_id,
+            created_at: now,
+            updated_at: now,
+            store_id: payload.store_id,
+            state: PaymentState::Initial,
+            stripe_fee: None,
+        };
+        state.orders.push(order.clone());
+        Ok(order)
+    }
+
+    fn delete(&self, order_id: OrderId) -> RepoResultV2<Option<RawOrder>> {
+        let mut state = self.lock("orders.delete")?;
+        let index = state.orders.iter().position(|order| order.id == order_id);
+        Ok(index.map(|index| state.orders.remove(index)))
+    }
+
+    fn delete_by_invoice_id(&self, invoice_id: InvoiceId) -> RepoResultV2<Vec<RawOrder>> {
+        let mut state = self.lock("orders.delete_by_invoice_id")?;
+        let (deleted, kept): (Vec<_>, Vec<_>) = state.orders.drain(..).partition(|order| order.invoice_id == invoice_id);
+        state.orders = kept;
+        Ok(deleted)
+    }
+
+    fn update_state(&self, order_id: OrderId, payment_state: PaymentState) -> RepoResultV2<RawOrder> {
+        let mut state = self.lock("orders.update_state")?;
+        let order = state
+            .orders
+            .iter_mut()
+            .find(|order| order.id == order_id)
+            .ok_or_else(|| not_found("Order", order_id))?;
+        order.state = payment_state;
+        order.updated_at = Utc::now().naive_utc();
+        Ok(order.clone())
+    }
+
+    fn update_stripe_fee(&self, order_id: OrderId, stripe_fee: Amount) -> RepoResultV2<RawOrder> {
+        let mut state = self.lock("orders.update_stripe_fee")?;
+        let order = state
+            .orders
+            .iter_mut()
+            .find(|order| order.id == order_id)
+            .ok_or_else(|| not_found("Order", order_id))?;
+        order.stripe_fee = Some(stripe_fee);
+        order.updated_at = Utc::now().naive_utc();
+        Ok(order.clone())
+    }
+}
+
+impl InvoicesV2Repo for InMemoryRepos {
+    fn get(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<RawInvoice>> {
+        let state = self.lock("invoices.get")?;
+        Ok(state.invoices.iter().find(|invoice| invoice.id == invoice_id).cloned())
+    }
+
+    fn get_many(&self, invoice_ids: &[InvoiceId]) -> RepoResultV2<Vec<RawInvoice>> {
+        let state = self.lock("invoices.get_many")?;
+        Ok(state
+            .invoices
+            .iter()
+            .filter(|invoice| invoice_ids.contains(&invoice.id))
+            .cloned()
+            .collect())
+    }
+
+    fn get_by_account_id(&self, account_id: AccountId) -> RepoResultV2<Option<RawInvoice>> {
+        let state = self.lock("invoices.get_by_account_id")?;
+        Ok(state
+            .invoices
+            .iter()
+            .find(|invoice| invoice.account_id == Some(account_id))
+            .cloned())
+    }
+
+    fn get_by_public_token(&self, public_token: &str) -> RepoResultV2<Option<RawInvoice>> {
+        let state = self.lock("invoices.get_by_public_token")?;
+        Ok(state.invoices.iter().find(|invoice| invoice.public_token == public_token).cloned())
+    }
+
+    fn get_unpaid_by_buyer_user_id(&self, buyer_user_id: BuyerUserId) -> RepoResultV2<Vec<RawInvoice>> {
+        let state = self.lock("invoices.get_unpaid_by_buyer_user_id")?;
+        let mut invoices = state
+            .invoices
+            .iter()
+            .filter(|invoice| invoice.buyer_user_id == buyer_user_id && invoice.paid_at.is_none())
+            .cloned()
+            .collect::<Vec<_>>();
+        invoices.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        Ok(invoices)
+    }
+
+    fn create(&self, input: NewInvoice) -> RepoResultV2<RawInvoice> {
+        let mut state = self.lock("invoices.create")?;
+        let now = Utc::now().naive_utc();
+        let invoice = RawInvoice {
+            id: input.id,
+            account_id: input.account_id,
+            buyer_currency: input.buyer_currency,
+            amount_captured: input.amount_captured,
+            final_amount_paid: None,
+            final_cashback_amount: None,
+            paid_at: None,
+            created_at: now,
+            updated_at: now,
+            buyer_user_id: input.buyer_user_id,
+            status: OrderState::PaymentAwaited,
+            metadata: input.metadata,
+            memo: input.memo,
+            po_number: input.po_number,
+            price_reserved: input.price_reserved,
+            public_token: input.public_token,
+        };
+        state.invoices.push(invoice.clone());
+        Ok(invoice)
+    }
+
+    fn increase_amount_captured(
+        &self,
+        account_id: AccountId,
+        transaction_id: TransactionId,
+        amount_received: Amount,
+    ) -> RepoResultV2<RawInvoice> {
+        let mut state = self.lock("invoices.increase_amount_captured")?;
+        let invoice = {
+            let invoice = state
+                .invoices
+                .iter_mut()
+                .find(|invoice| invoice.account_id == Some(account_id))
+                .ok_or_else(|| not_found("Invoice with account", account_id))?;
+            invoice.amount_captured = invoice.amount_captured.checked_add(amount_received).ok_or_else(|| {
+                let e = format_err!("Overflow occurred when adding amounts");
+                ectx!(try err e, ErrorKind::Internal)
+            })?;
+            invoice.updated_at = Utc::now().naive_utc();
+            invoice.clone()
+        };
+        state.amounts_received.push(RawAmountReceived {
+            id: transaction_id,
+            invoice_id: invoice.id,
+            amount_received,
+            created_at: invoice.updated_at,
+        });
+        Ok(invoice)
+    }
+
+    fn list_amounts_received(&self, invoice_id: InvoiceId) -> RepoResultV2<Vec<RawAmountReceived>> {
+        let state = self.lock("invoices.list_amounts_received")?;
+        Ok(state
+            .amounts_received
+            .iter()
+            .filter(|amount_received| amount_received.invoice_id == invoice_id)
+            .cloned()
+            .collect())
+    }
+
+    fn set_amount_paid(&self, invoice_id: InvoiceId, input: InvoiceSetAmountPaid) -> RepoResultV2<RawInvoice> {
+        let mut state = self.lock("invoices.set_amount_paid")?;
+        let invoice = state
+            .invoices
+            .iter_mut()
+            .find(|invoice| invoice.id == invoice_id)
+            .ok_or_else(|| not_found("Invoice", invoice_id))?;
+        set_paid(invoice, input);
+        Ok(invoice.clone())
+    }
+
+    fn set_amount_paid_fiat(&self, invoice_id: InvoiceId, input: InvoiceSetAmountPaid) -> RepoResultV2<RawInvoice> {
+        let mut state = self.lock("invoices.set_amount_paid_fiat")?;
+        let invoice = state
+            .invoices
+            .iter_mut()
+            .find(|invoice| invoice.id == invoice_id)
+            .ok_or_else(|| not_found("Invoice", invoice_id))?;
+        invoice.amount_captured = input.final_amount_paid;
+        set_paid(invoice, input);
+        Ok(invoice.clone())
+    }
+
+    fn update_details(&self, invoice_id: InvoiceId, input: UpdateInvoiceDetails) -> RepoResultV2<RawInvoice> {
+        let mut state = self.lock("invoices.update_details")?;
+        let invoice = state
+            .invoices
+            .iter_mut()
+            .find(|invoice| invoice.id == invoice_id)
+            .ok_or_else(|| not_found("Invoice", invoice_id))?;
+        if let Some(memo) = input.memo {
+            invoice.memo = memo;
+        }
+        if let Some(po_number) = input.po_number {
+            invoice.po_number = po_number;
+        }
+        invoice.updated_at = Utc::now().naive_utc();
+        Ok(invoice.clone())
+    }
+
+    fn unlink_account(&self, invoice_id: InvoiceId) -> RepoResultV2<RawInvoice> {
+        let mut state = self.lock("invoices.unlink_account")?;
+        let invoice = state
+            .invoices
+            .iter_mut()
+            .find(|invoice| invoice.id == invoice_id)
+            .ok_or_else(|| not_found("Invoice", invoice_id))?;
+        invoice.account_id = None;
+        invoice.updated_at = Utc::now().naive_utc();
+        Ok(invoice.clone())
+    }
+
+    fn set_status(&self, invoice_id: InvoiceId, status: OrderState) -> RepoResultV2<RawInvoice> {
+        let mut state = self.lock("invoices.set_status")?;
+        let invoice = state
+            .invoices
+            .iter_mut()
+            .find(|invoice| invoice.id == invoice_id)
+            .ok_or_else(|| not_found("Invoice", invoice_id))?;
+        invoice.status = status;
+        invoice.updated_at = Utc::now().naive_utc();
+        Ok(invoice.clone())
+    }
+
+    fn delete(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<RawInvoice>> {
+        let mut state = self.lock("invoices.delete")?;
+        let index = state.invoices.iter().position(|invoice| invoice.id == invoice_id);
+        Ok(index.map(|index| state.invoices.remove(index)))
+    }
+
+    // the state is behind a mutex already
+    fn lock_for_recalculation(&self, _invoice_id: InvoiceId) -> RepoResultV2<()> {
+        Ok(())
+    }
+}
+
+fn set_paid(invoice: &mut RawInvoice, input: InvoiceSetAmountPaid) {
+    invoice.final_amount_paid = Some(input.final_amount_paid);
+    invoice.final_cashback_amount = Some(input.final_cashback_amount);
+    invoice.paid_at = Some(input.paid_at);
+    invoice.status = OrderState::Paid;
+    invoice.updated_at = Utc::now().naive_utc();
+}
+
+impl PaymentIntentRepo for InMemoryRepos {
+    fn get(&self, search: SearchPaymentIntent) -> RepoResultV2<Option<PaymentIntent>> {
+        let state = self.lock("payment_intents.get")?;
+        let SearchPaymentIntent::Id(payment_intent_id) = search;
+        Ok(state
+            .payment_intents
+            .iter()
+            .find(|payment_intent| payment_intent.id == payment_intent_id)
+            .cloned())
+    }
+
+    fn create(&self, new_payment_intent: NewPaymentIntent) -> RepoResultV2<PaymentIntent> {
+        let mut state = self.lock("payment_intents.create")?;
+        let now = Utc::now().naive_utc();
+        let payment_intent = PaymentIntent {
+            id: new_payment_intent.id,
+            amount: new_payment_intent.amount,
+            amount_received: new_payment_intent.amount_received,
+            client_secret: new_payment_intent.client_secret,
+            currency: new_payment_intent.currency,
+            last_payment_error_message: new_payment_intent.last_payment_error_message,
+            receipt_email: new_payment_intent.receipt_email,
+            charge_id: new_payment_intent.charge_id,
+            status: new_payment_intent.status,
+            created_at: now,
+            updated_at: now,
+            status_event_created_at: None,
+        };
+        state.payment_intents.push(payment_intent.clone());
+        Ok(payment_intent)
+    }
+
+    fn update(&self, payment_intent_id: PaymentIntentId, update: UpdatePaymentIntent) -> RepoResultV2<PaymentIntent> {
+        let mut state = self.lock("payment_intents.update")?;
+        let payment_intent = state
+            .payment_intents
+            .iter_mut()
+            .find(|payment_intent| payment_intent.id == payment_intent_id)
+            .ok_or_else(|| not_found("Payment intent", &payment_intent_id.0))?;
+        if let Some(status) = update.status {
+            payment_intent.status = status;
+        }
+        if let Some(amount) = update.amount {
+            payment_intent.amount = amount;
+        }
+        if let Some(amount_received) = update.amount_received {
+            payment_intent.amount_received = amount_received;
+        }
+        if let Some(client_secret) = update.client_secret {
+            payment_intent.client_secret = Some(client_secret);
+        }
+        if let Some(currency) = update.currency {
+            payment_intent.currency = currency;
+        }
+        if let Some(last_payment_error_message) = update.last_payment_error_message {
+            payment_intent.last_payment_error_message = Some(last_payment_error_message);
+        }
+        if let Some(receipt_email) = update.receipt_email {
+            payment_intent.receipt_email = Some(receipt_email);
+        }
+        if let Some(charge_id) = update.charge_id {
+            payment_intent.charge_id = Some(charge_id);
+        }
+        if let Some(status_event_created_at) = update.status_event_created_at {
+            payment_intent.status_event_created_at = Some(status_event_created_at);
+        }
+        payment_intent.updated_at = Utc::now().naive_utc();
+        Ok(payment_intent.clone())
+    }
+
+    fn delete(&self, payment_intent_id: PaymentIntentId) -> RepoResultV2<Option<PaymentIntent>> {
+        let mut state = self.lock("payment_intents.delete")?;
+        let index = state
+            .payment_intents
+            .iter()
+            .position(|payment_intent| payment_intent.id == payment_intent_id);
+        Ok(index.map(|index| state.payment_intents.remove(index)))
+    }
+}
+
+impl FeeRepo for InMemoryRepos {
+    fn get(&self, search: SearchFee) -> RepoResultV2<Option<Fee>> {
+        let state = self.lock("fees.get")?;
+        Ok(state
+            .fees
+            .iter()
+            .find(|fee| match search {
+                SearchFee::Id(ref fee_id) => fee.id == *fee_id,
+                SearchFee::OrderId(ref order_id) => fee.order_id == *order_id,
+            })
+            .cloned())
+    }
+
+    fn search(&self, search_params: SearchFeeParams) -> RepoResultV2<Vec<Fee>> {
+        let state = self.lock("fees.search")?;
+        let SearchFeeParams {
+            id,
+            order_ids,
+            created_from,
+            created_to,
+            status,
+        } = search_params;
+
+        if id.is_none() && order_ids.is_none() && created_from.is_none() && created_to.is_none() && status.is_none() {
+            let e = format_err!("fee search_params is empty");
+            return Err(ectx!(err e, ErrorKind::Internal));
+        }
+
+        Ok(state
+            .fees
+            .iter()
+            .filter(|fee| id.map_or(true, |id| fee.id == id))
+            .filter(|fee| order_ids.as_ref().map_or(true, |order_ids| order_ids.contains(&fee.order_id)))
+            .filter(|fee| created_from.map_or(true, |created_from| fee.created_at >= created_from))
+            .filter(|fee| created_to.map_or(true, |created_to| fee.created_at <= created_to))
+            .filter(|fee| status.as_ref().map_or(true, |status| fee.status == *status))
+            .cloned()
+            .collect())
+    }
+
+    fn create(&self, payload: NewFee) -> RepoResultV2<Fee> {
+        let mut state = self.lock("fees.create")?;
+        let now = Utc::now().naive_utc();
+        let id = state.fees.iter().map(|fee| *fee.id.inner()).max().unwrap_or(0) + 1;
+        let fee = Fee {
+            id: FeeId::new(id),
+            order_id: payload.order_id,
+            amount: payload.amount,
+            status: payload.status,
+            currency: payload.currency,
+            charge_id: payload.charge_id,
+            metadata: payload.metadata,
+            created_at: now,
+            updated_at: now,
+            crypto_currency: payload.crypto_currency,
+            crypto_amount: payload.crypto_amount,
+            exchange_rate: payload.exchange_rate,
+            currency_exchange_id: payload.currency_exchange_id,
+            converted_at: payload.converted_at,
+            charged_currency: None,
+            charged_amount: None,
+            charge_exchange_rate: None,
+        };
+        state.fees.push(fee.clone());
+        Ok(fee)
+    }
+
+    fn update(&self, fee_id: FeeId, payload: UpdateFee) -> RepoResultV2<Fee> {
+        let mut state = self.lock("fees.update")?;
+        let fee = state
+            .fees
+            .iter_mut()
+            .find(|fee| fee.id == fee_id)
+            .ok_or_else(|| not_found("Fee", fee_id.inner()))?;
+        if let Some(order_id) = payload.order_id {
+            fee.order_id = order_id;
+        }
+        if let Some(amount) = payload.amount {
+            fee.amount = amount;
+        }
+        if let Some(status) = payload.status {
+            fee.status = status;
+        }
+        if let Some(currency) = payload.currency {
+            fee.currency = currency;
+        }
+        if let Some(charge_id) = payload.charge_id {
+            fee.charge_id = Some(charge_id);
+        }
+        if let Some(metadata) = payload.metadata {
+            fee.metadata = Some(metadata);
+        }
+        if let Some(crypto_currency) = payload.crypto_currency {
+            fee.crypto_currency = Some(crypto_currency);
+        }
+        if let Some(crypto_amount) = payload.crypto_amount {
+            fee.crypto_amount = Some(crypto_amount);
+        }
+        if let Some(charged_currency) = payload.charged_currency {
+            fee.charged_currency = Some(charged_currency);
+        }
+        if let Some(charged_amount) = payload.charged_amount {
+            fee.charged_amount = Some(charged_amount);
+        }
+        if let Some(charge_exchange_rate) = payload.charge_exchange_rate {
+            fee.charge_exchange_rate = Some(charge_exchange_rate);
+        }
+        fee.updated_at = Utc::now().naive_utc();
+        Ok(fee.clone())
+    }
+
+    fn delete(&self, fee_id: FeeId) -> RepoResultV2<()> {
+        let mut state = self.lock("fees.delete")?;
+        state.fees.retain(|fee| fee.id != fee_id);
+        Ok(())
+    }
+}
+
+impl InMemoryRepos {
+    fn push_event(&self, operation: &'static str, event: Event, scheduled_on: Option<::chrono::NaiveDateTime>) -> RepoResultV2<EventEntry> {
+        let mut state = self.lock(operation)?;
+        let now = Utc::now().naive_utc();
+        let event_entry = EventEntry {
+            id: EventEntryId::new(state.events.len() as i64 + 1),
+            event,
+            status: EventStatus::Pending,
+            attempt_count: 0,
+            created_at: now,
+            status_updated_at: now,
+            scheduled_on,
+            locked_by: None,
+            lease_expires_at: None,
+            timings: None,
+        };
+        state.events.push(event_entry.clone());
+        Ok(event_entry)
+    }
+
+    fn release_event(&self, operation: &'static str, event_entry_id: EventEntryId, completed: bool) -> RepoResultV2<EventEntry> {
+        let mut state = self.lock(operation)?;
+        let max_processing_attempts = state.max_processing_attempts;
+        let event_entry = state
+            .events
+            .iter_mut()
+            .find(|event_entry| event_entry.id == event_entry_id)
+            .ok_or_else(|| not_found("Event entry", event_entry_id))?;
+
+        if event_entry.status != EventStatus::InProgress {
+            let e = format_err!(
+                "Cannot release event entry with ID: {} in \"{}\" status",
+                event_entry_id,
+                event_entry.status
+            );
+            return Err(ectx!(err e, ErrorKind::Internal));
+        }
+
+        event_entry.status = if completed {
+            EventStatus::Completed
+        } else if event_entry.attempt_count >= max_processing_attempts {
+            EventStatus::Failed
+        } else {
+            EventStatus::Pending
+        };
+        event_entry.status_updated_at = Utc::now().naive_utc();
+        event_entry.locked_by = None;
+        event_entry.lease_expires_at = None;
+        Ok(event_entry.clone())
+    }
+}
+
+impl EventStoreRepo for InMemoryRepos {
+    fn add_event(&self, event: Event) -> RepoResultV2<EventEntry> {
+        self.push_event("event_store.add_event", event, None)
+    }
+
+    fn add_scheduled_event(&self, event: Event, scheduled_on: ::chrono::NaiveDateTime) -> RepoResultV2<EventEntry> {
+        self.push_event("event_store.add_scheduled_event", event, Some(scheduled_on))
+    }
+
+    fn get_events_for_processing(&self, limit: u32, payload_types: &EventPayloadTypes) -> RepoResultV2<Vec<EventEntry>> {
+        let mut state = self.lock("event_store.get_events_for_processing")?;
+        let now = Utc::now().naive_utc();
+        Ok(state
+            .events
+            .iter_mut()
+            .filter(|event_entry| {
+                event_entry.status == EventStatus::Pending
+                    && event_entry.scheduled_on.map_or(true, |scheduled_on| scheduled_on <= now)
+                    && payload_types.matches(&event_entry.event.payload.to_string())
+            })
+            .take(limit as usize)
+            .map(|event_entry| {
+                event_entry.attempt_count += 1;
+                event_entry.status = EventStatus::InProgress;
+                event_entry.status_updated_at = now;
+                event_entry.locked_by = Some(INSTANCE_ID.to_string());
+                event_entry.lease_expires_at = Some(now + Duration::seconds(LEASE_DURATION_SEC));
+                event_entry.clone()
+            })
+            .collect())
+    }
+
+    fn reset_stuck_events(&self) -> RepoResultV2<Vec<EventEntry>> {
+        let mut state = self.lock("event_store.reset_stuck_events")?;
+        let max_processing_attempts = state.max_processing_attempts;
+        let now = Utc::now().naive_utc();
+        Ok(state
+            .events
+            .iter_mut()
+            .filter(|event_entry| {
+                event_entry.status == EventStatus::InProgress
+                    && event_entry.lease_expires_at.map_or(true, |lease_expires_at| lease_expires_at < now)
+            })
+            .map(|event_entry| {
+                event_entry.status = if event_entry.attempt_count >= max_processing_attempts {
+                    EventStatus::Failed
+                } else {
+                    EventStatus::Pending
+                };
+                event_entry.status_updated_at = now;
+                event_entry.locked_by = None;
+                event_entry.lease_expires_at = None;
+                event_entry.clone()
+            })
+            .collect())
+    }
+
+    fn complete_event(&self, event_entry_id: EventEntryId) -> RepoResultV2<EventEntry> {
+        self.release_event("event_store.complete_event", event_entry_id, true)
+    }
+
+    fn fail_event(&self, event_entry_id: EventEntryId) -> RepoResultV2<EventEntry> {
+        self.release_event("event_store.fail_event", event_entry_id, false)
+    }
+
+    fn record_event_timings(&self, event_entry_id: EventEntryId, timings: EventTimings) -> RepoResultV2<()> {
+        let mut state = self.lock("event_store.record_event_timings")?;
+        let event_entry = state
+            .events
+            .iter_mut()
+            .find(|event_entry| event_entry.id == event_entry_id)
+            .ok_or_else(|| not_found("Event entry", event_entry_id))?;
+        event_entry.timings = Some(timings);
+        Ok(())
+    }
+}
+
+impl OrderInfoRepo for InMemoryRepos {
+    fn find(&self, order_info_id: OrderInfoId) -> RepoResult<Option<OrderInfo>> {
+        let state = self.lock_legacy("order_infos.find")?;
+        Ok(state.order_infos.iter().find(|order_info| order_info.id == order_info_id).cloned())
+    }
+
+    fn find_by_order_id(&self, order_id: StqOrderId) -> RepoResult<Option<OrderInfo>> {
+        let state = self.lock_legacy("order_infos.find_by_order_id")?;
+        Ok(state.order_infos.iter().find(|order_info| order_info.order_id == order_id).cloned())
+    }
+
+    fn find_by_saga_id(&self, saga_id: SagaId) -> RepoResult<Vec<OrderInfo>> {
+        let state = self.lock_legacy("order_infos.find_by_saga_id")?;
+        Ok(state
+            .order_infos
+            .iter()
+            .filter(|order_info| order_info.saga_id == saga_id)
+            .cloned()
+            .collect())
+    }
+
+    fn create(&self, payload: NewOrderInfo) -> RepoResult<OrderInfo> {
+        let mut state = self.lock_legacy("order_infos.create")?;
+        let now = SystemTime::now();
+        let order_info = OrderInfo {
+            id: OrderInfoId::new(),
+            order_id: payload.order_id,
+            status: OrderState::New,
+            created_at: now,
+            updated_at: now,
+            customer_id: payload.customer_id,
+            store_id: payload.store_id,
+            saga_id: payload.saga_id,
+            total_amount: payload.total_amount,
+        };
+        state.order_infos.push(order_info.clone());
+        Ok(order_info)
+    }
+
+    fn update_status(&self, saga_id: SagaId, new_status: OrderState) -> RepoResult<Vec<OrderInfo>> {
+        let mut state = self.lock_legacy("order_infos.update_status")?;
+        let now = SystemTime::now();
+        Ok(state
+            .order_infos
+            .iter_mut()
+            .filter(|order_info| order_info.saga_id == saga_id)
+            .map(|order_info| {
+                order_info.status = new_status;
+                order_info.updated_at = now;
+                order_info.clone()
+            })
+            .collect())
+    }
+
+    fn delete_by_saga_id(&self, saga_id: SagaId) -> RepoResult<Vec<OrderInfo>> {
+        let mut state = self.lock_legacy("order_infos.delete_by_saga_id")?;
+        let (deleted, kept): (Vec<_>, Vec<_>) = state.order_infos.drain(..).partition(|order_info| order_info.saga_id == saga_id);
+        state.order_infos = kept;
+        Ok(deleted)
+    }
+}
+
+impl InvoiceRepo for InMemoryRepos {
+    fn find(&self, invoice_id: SagaInvoiceId) -> RepoResult<Option<Invoice>> {
+        let state = self.lock_legacy("legacy_invoices.find")?;
+        Ok(state
+            .legacy_invoices
+            .iter()
+            .find(|invoice| invoice.invoice_id == invoice_id)
+            .cloned())
+    }
+
+    fn find_by_saga_id(&self, saga_id: SagaId) -> RepoResult<Option<Invoice>> {
+        let state = self.lock_legacy("legacy_invoices.find_by_saga_id")?;
+        Ok(state.legacy_invoices.iter().find(|invoice| invoice.id == saga_id).cloned())
+    }
+
+    fn create(&self, payload: Invoice) -> RepoResult<Invoice> {
+        let mut state = self.lock_legacy("legacy_invoices.create")?;
+        state.legacy_invoices.push(payload.clone());
+        Ok(payload)
+    }
+
+    fn update(&self, invoice_id: SagaInvoiceId, payload: UpdateInvoice) -> RepoResult<Invoice> {
+        let mut state = self.lock_legacy("legacy_invoices.update")?;
+        let invoice = state
+            .legacy_invoices
+            .iter_mut()
+            .find(|invoice| invoice.invoice_id == invoice_id)
+            .ok_or_else(|| not_found("Invoice", invoice_id))?;
+        invoice.transactions = payload.transactions;
+        invoice.amount = payload.amount;
+        invoice.currency = payload.currency;
+        invoice.price_reserved = payload.price_reserved;
+        invoice.state = payload.state;
+        invoice.wallet = payload.wallet;
+        invoice.amount_captured = payload.amount_captured;
+        invoice.updated_at = SystemTime::now();
+        Ok(invoice.clone())
+    }
+
+    fn delete(&self, saga_id: SagaId) -> RepoResult<Invoice> {
+        let mut state = self.lock_legacy("legacy_invoices.delete")?;
+        let index = state
+            .legacy_invoices
+            .iter()
+            .position(|invoice| invoice.id == saga_id)
+            .ok_or_else(|| not_found("Invoice with saga", saga_id))?;
+        Ok(state.legacy_invoices.remove(index))
+    }
+
+    fn find_expired(&self, reserved_before: SystemTime, limit: i64) -> RepoResult<Vec<Invoice>> {
+        let state = self.lock_legacy("legacy_invoices.find_expired")?;
+        let mut expired = state
+            .legacy_invoices
+            .iter()
+            .filter(|invoice| invoice.state == OrderState::PaymentAwaited && invoice.price_reserved < reserved_before)
+            .cloned()
+            .collect::<Vec<_>>();
+        expired.sort_by_key(|invoice| invoice.price_reserved);
+        expired.truncate(limit as usize);
+        Ok(expired)
+    }
+
+    fn set_state(&self, saga_id: SagaId, new_state: OrderState) -> RepoResult<Invoice> {
+        let mut state = self.lock_legacy("legacy_invoices.set_state")?;
+        let invoice = state
+            .legacy_invoices
+            .iter_mut()
+            .find(|invoice| invoice.id == saga_id)
+            .ok_or_else(|| not_found("Invoice with saga", saga_id))?;
+        invoice.state = new_state;
+        invoice.updated_at = SystemTime::now();
+        Ok(invoice.clone())
+    }
+}
+
+impl DataRetentionRepo for InMemoryRepos {
+    fn get(&self, subject: DataSubject) -> RepoResultV2<Option<DataRetentionSubject>> {
+        let state = self.lock("data_retention.get")?;
+        Ok(find_retention_subject(&state, subject).cloned())
+    }
+
+    fn mark_closed(&self, subject: DataSubject, closed_at: NaiveDateTime) -> RepoResultV2<DataRetentionSubject> {
+        let mut state = self.lock("data_retention.mark_closed")?;
+        let retention_subject = get_or_create_retention_subject(&mut state, subject);
+        if retention_subject.closed_at.is_none() {
+            retention_subject.closed_at = Some(closed_at);
+            retention_subject.updated_at = Utc::now().naive_utc();
+        }
+        Ok(retention_subject.clone())
+    }
+
+    fn set_legal_hold(&self, subject: DataSubject, legal_hold: bool, reason: Option<String>) -> RepoResultV2<DataRetentionSubject> {
+        let mut state = self.lock("data_retention.set_legal_hold")?;
+        let retention_subject = get_or_create_retention_subject(&mut state, subject);
+        retention_subject.legal_hold = legal_hold;
+        retention_subject.legal_hold_reason = reason;
+        retention_subject.updated_at = Utc::now().naive_utc();
+        Ok(retention_subject.clone())
+    }
+
+    fn find_purgeable(
+        &self,
+        table: RetentionTable,
+        closed_before: NaiveDateTime,
+        after_subject_id: i32,
+        limit: i64,
+    ) -> RepoResultV2<Vec<i32>> {
+        let state = self.lock("data_retention.find_purgeable")?;
+        let mut subject_ids = state
+            .data_retention_subjects
+            .iter()
+            .filter(|subject| subject.subject_type == table.subject_type() && subject.subject_id > after_subject_id)
+            .filter(|subject| subject.closed_at.map(|closed_at| closed_at < closed_before).unwrap_or(false) && !subject.legal_hold)
+            .filter(|subject| state.retained_data.contains(&(table, subject.subject_id)))
+            .map(|subject| subject.subject_id)
+            .collect::<Vec<_>>();
+        subject_ids.sort();
+        subject_ids.truncate(limit as usize);
+        Ok(subject_ids)
+    }
+
+    fn count_rows(&self, table: RetentionTable, subject_ids: Vec<i32>) -> RepoResultV2<i64> {
+        let state = self.lock("data_retention.count_rows")?;
+        Ok(state
+            .retained_data
+            .iter()
+            .filter(|&&(row_table, subject_id)| row_table == table && subject_ids.contains(&subject_id))
+            .count() as i64)
+    }
+
+    fn purge(&self, table: RetentionTable, subject_ids: Vec<i32>) -> RepoResultV2<usize> {
+        let mut state = self.lock("data_retention.purge")?;
+        let rows = state.retained_data.len();
+        state
+            .retained_data
+            .retain(|&(row_table, subject_id)| row_table != table || !subject_ids.contains(&subject_id));
+        Ok(rows - state.retained_data.len())
+    }
+}
+
+impl InvoiceSnapshotsRepo for InMemoryRepos {
+    fn create(&self, payload: NewInvoiceSnapshot) -> RepoResultV2<InvoiceSnapshot> {
+        let mut state = self.lock("invoice_snapshots.create")?;
+        if let Some(snapshot) = state
+            .invoice_snapshots
+            .iter()
+            .find(|snapshot| snapshot.invoice_id == payload.invoice_id)
+        {
+            return Ok(snapshot.clone());
+        }
+        let snapshot = InvoiceSnapshot {
+            id: state.invoice_snapshots.len() as i32 + 1,
+            invoice_id: payload.invoice_id,
+            dump: payload.dump,
+            paid_at: payload.paid_at,
+            created_at: Utc::now().naive_utc(),
+        };
+        state.invoice_snapshots.push(snapshot.clone());
+        Ok(snapshot)
+    }
+
+    fn get(&self, invoice_id: InvoiceId) -> RepoResultV2<Option<InvoiceSnapshot>> {
+        let state = self.lock("invoice_snapshots.get")?;
+        Ok(state
+            .invoice_snapshots
+            .iter()
+            .find(|snapshot| snapshot.invoice_id == invoice_id)
+            .cloned())
+    }
+}
+
+fn find_retention_subject(state: &InMemoryState, subject: DataSubject) -> Option<&DataRetentionSubject> {
+    state.data_retention_subjects.iter().find(|retention_subject| {
+        retention_subject.subject_type == subject.subject_type() && retention_subject.subject_id == subject.subject_id()
+    })
+}
+
+fn get_or_create_retention_subject(state: &mut InMemoryState, subject: DataSubject) -> &mut DataRetentionSubject {
+    let index = match state.data_retention_subjects.iter().position(|retention_subject| {
+        retention_subject.subject_type == subject.subject_type() && retention_subject.subject_id == subject.subject_id()
+    }) {
+        Some(index) => index,
+        None => {
+            let now = Utc::now().naive_utc();
+            state.data_retention_subjects.push(DataRetentionSubject {
+                id: state.data_retention_subjects.len() as i32 + 1,
+                subject_type: subject.subject_type(),
+                subject_id: subject.subject_id(),
+                closed_at: None,
+                legal_hold: false,
+                legal_hold_reason: None,
+                created_at: now,
+                updated_at: now,
+            });
+            state.data_retention_subjects.len() - 1
+        }
+    };
+    &mut state.data_retention_subjects[index]
+}
+
+impl<C: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static> ReposFactory<C> for InMemoryReposFactory {
+    fn create_order_info_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<OrderInfoRepo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_order_info_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<OrderInfoRepo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_invoice_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<InvoiceRepo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_invoice_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InvoiceRepo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_user_roles_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<UserRolesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_user_roles_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<UserRolesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_accounts_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<AccountsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_accounts_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<AccountsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_invoices_v2_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InvoicesV2Repo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_invoices_v2_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<InvoicesV2Repo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_orders_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<OrdersRepo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_orders_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<OrdersRepo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_order_exchange_rates_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<OrderExchangeRatesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_order_exchange_rates_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<OrderExchangeRatesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_event_store_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<EventStoreRepo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_payment_intent_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PaymentIntentRepo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_payment_intent_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PaymentIntentRepo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_payment_intent_history_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PaymentIntentHistoryRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payment_intent_history_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PaymentIntentHistoryRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_customers_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<CustomersRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_customers_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<CustomersRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_fees_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<FeeRepo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_fees_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<FeeRepo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_payment_intent_invoices_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PaymentIntentInvoiceRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payment_intent_invoices_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PaymentIntentInvoiceRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payment_intent_fees_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PaymentIntentFeeRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payment_intent_fees_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PaymentIntentFeeRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_store_billing_type_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<StoreBillingTypeRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_store_billing_type_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<StoreBillingTypeRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_international_billing_info_repo<'a>(
+        &self,
+        _db_conn: &'a C,
+        _user_id: Option<UserId>,
+    ) -> Box<InternationalBillingInfoRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_international_billing_repo_info_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InternationalBillingInfoRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_russia_billing_info_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<RussiaBillingInfoRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_russia_billing_info_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<RussiaBillingInfoRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_proxy_companies_billing_info_repo<'a>(
+        &self,
+        _db_conn: &'a C,
+        _user_id: Option<UserId>,
+    ) -> Box<ProxyCompanyBillingInfoRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_proxy_companies_billing_info_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<ProxyCompanyBillingInfoRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_user_wallets_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<UserWalletsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_user_wallets_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<UserWalletsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_wallet_verifications_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<WalletVerificationsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_wallet_verifications_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<WalletVerificationsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payouts_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PayoutsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payouts_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PayoutsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_subscription_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<SubscriptionRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_subscription_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<SubscriptionRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_store_subscription_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<StoreSubscriptionRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_store_subscription_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<StoreSubscriptionRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_subscription_payment_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<SubscriptionPaymentRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_subscription_payment_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<SubscriptionPaymentRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_fee_statements_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<FeeStatementsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_fee_statements_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<FeeStatementsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_fee_adjustments_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<FeeAdjustmentsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_fee_adjustments_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<FeeAdjustmentsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_fee_crypto_payments_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<FeeCryptoPaymentsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_fee_crypto_payments_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<FeeCryptoPaymentsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_audit_log_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<AuditLogRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_audit_log_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<AuditLogRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_cashback_liabilities_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<CashbackLiabilitiesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_cashback_liabilities_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<CashbackLiabilitiesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_negative_store_balances_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<NegativeStoreBalancesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_negative_store_balances_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<NegativeStoreBalancesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_exchange_rate_slippages_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<ExchangeRateSlippagesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_exchange_rate_slippages_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<ExchangeRateSlippagesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payout_statements_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PayoutStatementsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payout_statements_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PayoutStatementsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_schema_migrations_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<SchemaMigrationsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_schema_migrations_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<SchemaMigrationsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_billing_info_changes_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<BillingInfoChangesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_billing_info_changes_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<BillingInfoChangesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payment_attempts_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PaymentAttemptsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payment_attempts_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PaymentAttemptsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_account_pool_claims_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<AccountPoolClaimsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_store_webhooks_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<StoreWebhooksRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_store_webhooks_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<StoreWebhooksRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_api_keys_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<ApiKeysRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_api_keys_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<ApiKeysRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_data_retention_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<DataRetentionRepo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_data_retention_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<DataRetentionRepo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_invoice_snapshots_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<InvoiceSnapshotsRepo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_invoice_snapshots_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InvoiceSnapshotsRepo + 'a> {
+        Box::new(self.repos())
+    }
+
+    fn create_fee_dunnings_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<FeeDunningsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_fee_dunnings_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<FeeDunningsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payout_restriction_overrides_repo<'a>(
+        &self,
+        _db_conn: &'a C,
+        _user_id: Option<UserId>,
+    ) -> Box<PayoutRestrictionOverridesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payout_restriction_overrides_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PayoutRestrictionOverridesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_customer_exports_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<CustomerExportsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_customer_exports_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<CustomerExportsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_invoice_risk_assessments_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<InvoiceRiskAssessmentsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_invoice_risk_assessments_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InvoiceRiskAssessmentsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_store_offboardings_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<StoreOffboardingsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_store_offboardings_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<StoreOffboardingsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_historical_reports_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<HistoricalReportsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_historical_reports_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<HistoricalReportsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_user_credits_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<UserCreditsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_user_credits_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<UserCreditsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_store_billing_statuses_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<StoreBillingStatusesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_store_billing_statuses_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<StoreBillingStatusesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payout_instructions_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PayoutInstructionsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payout_instructions_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PayoutInstructionsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_stripe_fee_backfills_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<StripeFeeBackfillsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_stripe_fee_backfills_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<StripeFeeBackfillsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payment_recoveries_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<PaymentRecoveriesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_payment_recoveries_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<PaymentRecoveriesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_order_capture_approvals_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<OrderCaptureApprovalsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_order_capture_approvals_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<OrderCaptureApprovalsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_order_line_items_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<OrderLineItemsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_order_line_items_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<OrderLineItemsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_invoice_buyer_details_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<InvoiceBuyerDetailsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_invoice_buyer_details_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InvoiceBuyerDetailsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_invoice_callbacks_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<InvoiceCallbacksRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_invoice_callbacks_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InvoiceCallbacksRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_invoice_requotes_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<InvoiceRequotesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_invoice_requotes_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<InvoiceRequotesRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_account_assignments_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<AccountAssignmentsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_account_assignments_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<AccountAssignmentsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_feature_flags_repo<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Box<FeatureFlagsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn create_feature_flags_repo_with_sys_acl<'a>(&self, _db_conn: &'a C) -> Box<FeatureFlagsRepo + 'a> {
+        unimplemented!()
+    }
+
+    fn with_api_key_scopes(&self, _scopes: Vec<ApiKeyScope>) -> Self {
+        self.clone()
+    }
+
+    fn get_caller_roles<'a>(&self, _db_conn: &'a C, _user_id: Option<UserId>) -> Vec<BillingRole> {
+        unimplemented!()
+    }
+}
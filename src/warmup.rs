@@ -0,0 +1,123 @@
+//! Startup warmup. Establishes database connections, pings Stripe and Payments gateway
+//! and caches roles of the system user in parallel, so that the first requests served
+//! after a deploy don't pay for connection setup and cold caches.
+//!
+//! Warmup is best effort - failures are logged and never prevent the service from starting.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use diesel::connection::{AnsiTransactionManager, SimpleConnection};
+use diesel::pg::Pg;
+use diesel::Connection;
+use futures::future::{self, Either};
+use futures::Future;
+use futures_cpupool::CpuPool;
+use r2d2::{ManageConnection, Pool};
+
+use stq_types::UserId;
+
+use client::payments::PaymentsClient;
+use client::stripe::StripeClient;
+use config;
+use repos::ReposFactory;
+
+pub fn run<T, M, F, S>(
+    config: &config::Warmup,
+    db_pool: Pool<M>,
+    cpu_pool: CpuPool,
+    repo_factory: F,
+    stripe_client: S,
+    payments_client: Option<Arc<dyn PaymentsClient>>,
+) -> Box<Future<Item = (), Error = ()>>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+    S: StripeClient,
+{
+    info!("Warming up");
+    let started_at = Instant::now();
+
+    let db_connections = config.db_connections.min(db_pool.max_size());
+    let system_user_id = UserId(config.system_user_id);
+
+    let db_fut = warm_up_db_pool(db_pool.clone(), cpu_pool.clone(), db_connections).then(move |res| {
+        match res {
+            Ok(()) => info!("Warmup: established {} database connections", db_connections),
+            Err(e) => warn!("Warmup: failed to establish database connections: {}", e),
+        };
+        Ok::<(), ()>(())
+    });
+
+    let roles_fut = cache_roles(db_pool, cpu_pool, repo_factory, system_user_id).then(move |res| {
+        match res {
+            Ok(roles_count) => info!("Warmup: cached {} roles of system user {}", roles_count, system_user_id),
+            Err(e) => warn!("Warmup: failed to cache roles of system user {}: {}", system_user_id, e),
+        };
+        Ok::<(), ()>(())
+    });
+
+    let stripe_fut = stripe_client.ping().then(|res| {
+        match res {
+            Ok(()) => info!("Warmup: Stripe is reachable"),
+            Err(e) => warn!("Warmup: failed to reach Stripe: {}", e),
+        };
+        Ok::<(), ()>(())
+    });
+
+    let payments_fut = match payments_client {
+        None => Either::A(future::ok(())),
+        Some(payments_client) => Either::B(payments_client.ping().then(|res| {
+            match res {
+                Ok(()) => info!("Warmup: Payments gateway is reachable"),
+                Err(e) => warn!("Warmup: failed to reach Payments gateway: {}", e),
+            };
+            Ok::<(), ()>(())
+        })),
+    };
+
+    Box::new(db_fut.join4(roles_fut, stripe_fut, payments_fut).map(move |_| {
+        let elapsed = started_at.elapsed();
+        info!(
+            "Warmup finished in {}.{:03}s",
+            elapsed.as_secs(),
+            elapsed.subsec_nanos() / 1_000_000
+        );
+    }))
+}
+
+/// Checks out `count` connections at once, so that the pool has to open them in parallel,
+/// and returns them to the pool when all of them are ready
+fn warm_up_db_pool<T, M>(db_pool: Pool<M>, cpu_pool: CpuPool, count: u32) -> Box<Future<Item = (), Error = String>>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+{
+    let connections = (0..count).map(move |_| {
+        let db_pool = db_pool.clone();
+        cpu_pool.spawn_fn(move || {
+            let conn = db_pool.get().map_err(|e| e.to_string())?;
+            conn.batch_execute("SELECT 1").map_err(|e| e.to_string())?;
+            Ok::<_, String>(conn)
+        })
+    });
+
+    Box::new(future::join_all(connections).map(|_| ()))
+}
+
+fn cache_roles<T, M, F>(db_pool: Pool<M>, cpu_pool: CpuPool, repo_factory: F, user_id: UserId) -> Box<Future<Item = usize, Error = String>>
+where
+    T: Connection<Backend = Pg, TransactionManager = AnsiTransactionManager> + 'static,
+    M: ManageConnection<Connection = T>,
+    F: ReposFactory<T>,
+{
+    Box::new(cpu_pool.spawn_fn(move || {
+        let conn = db_pool.get().map_err(|e| e.to_string())?;
+        repo_factory
+            .create_user_roles_repo_with_sys_acl(&*conn)
+            .list_for_user(user_id)
+            .map(|roles| roles.len())
+            .map_err(|e| e.to_string())
+    }))
+}
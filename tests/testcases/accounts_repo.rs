@@ -1,8 +1,9 @@
+use chrono::Utc;
 use diesel::pg::PgConnection;
 use diesel::Connection;
 use uuid::Uuid;
 
-use billing_lib::models::{AccountId, NewAccount, TureCurrency};
+use billing_lib::models::{AccountId, AccountStatus, NewAccount, TureCurrency};
 use billing_lib::repos::{legacy_acl::SystemACL, AccountsRepo, AccountsRepoImpl};
 
 fn with_test_db_conn<F, T>(f: F) -> T
@@ -58,3 +59,33 @@ fn accounts_repo_crud_happy() {
     };
     assert_eq!(Some(new_account.id), deleted_account.map(|a| a.id));
 }
+
+#[test]
+fn accounts_repo_status_lifecycle() {
+    let system_acl = Box::new(SystemACL::default());
+
+    let new_account = NewAccount {
+        id: AccountId::new(Uuid::new_v4()),
+        currency: TureCurrency::Stq,
+        is_pooled: true,
+        wallet_address: "0x0".to_string().into(),
+    };
+
+    with_test_db_conn(move |conn| {
+        let repo = AccountsRepoImpl::new(conn, system_acl);
+
+        let created_account = repo.create(new_account.clone()).unwrap();
+        assert_eq!(AccountStatus::Active, created_account.status);
+
+        let draining_account = repo.set_status(new_account.id, AccountStatus::Draining).unwrap();
+        assert_eq!(AccountStatus::Draining, draining_account.status);
+
+        let free_account = repo.get_free_account(TureCurrency::Stq, Utc::now().naive_utc()).unwrap();
+        assert_ne!(Some(new_account.id), free_account.map(|a| a.id));
+
+        let archived_account = repo.set_status(new_account.id, AccountStatus::Archived).unwrap();
+        assert_eq!(AccountStatus::Archived, archived_account.status);
+
+        repo.delete(new_account.id).unwrap();
+    });
+}
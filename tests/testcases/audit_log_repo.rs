@@ -0,0 +1,78 @@
+use diesel::pg::PgConnection;
+use diesel::Connection;
+use uuid::Uuid;
+
+use billing_lib::models::{AuditAction, AuditLogSearch, AuditResourceType, NewAuditLogEntry};
+use billing_lib::repos::{legacy_acl::SystemACL, AuditLogRepo, AuditLogRepoImpl};
+
+fn with_test_db_conn<F, T>(f: F) -> T
+where
+    F: FnOnce(&PgConnection) -> T,
+{
+    let config = billing_lib::config::Config::new().unwrap();
+    let database_url = config.server.database.parse::<String>().unwrap();
+    let db_conn = PgConnection::establish(&database_url).unwrap();
+
+    f(&db_conn)
+}
+
+#[test]
+fn audit_log_repo_create_and_search() {
+    let resource_id = Uuid::new_v4().to_string();
+    let new_entry = NewAuditLogEntry {
+        actor_user_id: None,
+        action: AuditAction::OrderPaymentStateChanged,
+        resource_type: AuditResourceType::Order,
+        resource_id: resource_id.clone(),
+        before: Some(json!({ "state": "initial" })),
+        after: Some(json!({ "state": "paid_to_seller" })),
+    };
+
+    with_test_db_conn(move |conn| {
+        let repo = AuditLogRepoImpl::new(conn, Box::new(SystemACL::default()));
+
+        let created_entry = repo.create(new_entry.clone()).unwrap();
+        assert_eq!(resource_id, created_entry.resource_id);
+        assert_eq!(AuditAction::OrderPaymentStateChanged, created_entry.action);
+
+        let by_resource = repo
+            .search(
+                0,
+                10,
+                AuditLogSearch {
+                    resource_type: Some(AuditResourceType::Order),
+                    resource_id: Some(resource_id.clone()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(1, by_resource.total_count);
+        assert_eq!(created_entry.id, by_resource.entries[0].id);
+
+        let by_text = repo
+            .search(
+                0,
+                10,
+                AuditLogSearch {
+                    resource_id: Some(resource_id.clone()),
+                    text: Some("paid_to_seller".to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(1, by_text.total_count);
+
+        let by_missing_text = repo
+            .search(
+                0,
+                10,
+                AuditLogSearch {
+                    resource_id: Some(resource_id),
+                    text: Some("declined".to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(0, by_missing_text.total_count);
+    });
+}
@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::Duration;
+
+use diesel::pg::PgConnection;
+use diesel::Connection;
+
+use billing_lib::models::{Event, EventEntry, EventEntryId, EventPayload};
+use billing_lib::repos::{EventStoreRepo, EventStoreRepoImpl};
+
+const MAX_PROCESSING_ATTEMPTS: u32 = 3;
+const STUCK_THRESHOLD_SEC: u32 = 300;
+const POLLERS_COUNT: usize = 4;
+
+fn establish_db_conn() -> PgConnection {
+    let config = billing_lib::config::Config::new().unwrap();
+    let database_url = config.server.database.parse::<String>().unwrap();
+    PgConnection::establish(&database_url).unwrap()
+}
+
+fn event_store_repo<'a>(conn: &'a PgConnection, instance_id: &str, stuck_threshold_sec: u32) -> EventStoreRepoImpl<'a, PgConnection> {
+    EventStoreRepoImpl::new(conn, MAX_PROCESSING_ATTEMPTS, stuck_threshold_sec, instance_id.to_string())
+}
+
+/// Takes events with `repo` until the one with `event_entry_id` comes up, completing all the others
+fn take_event(repo: &EventStoreRepo, event_entry_id: EventEntryId) -> EventEntry {
+    loop {
+        let entries = repo.get_events_for_processing(100).unwrap();
+        assert!(
+            !entries.is_empty(),
+            "event entry {} was not available for processing",
+            event_entry_id
+        );
+
+        let mut found = None;
+        for entry in entries {
+            if entry.id == event_entry_id {
+                found = Some(entry);
+            } else {
+                repo.complete_event(entry.id).unwrap();
+            }
+        }
+
+        if let Some(entry) = found {
+            return entry;
+        }
+    }
+}
+
+fn concurrent_pollers_take_distinct_events() {
+    let created_ids: HashSet<i64> = {
+        let conn = establish_db_conn();
+        let repo = event_store_repo(&conn, "test-producer", STUCK_THRESHOLD_SEC);
+        (0..20)
+            .map(|_| repo.add_event(Event::new(EventPayload::NoOp)).unwrap().id.inner())
+            .collect()
+    };
+
+    let barrier = Arc::new(Barrier::new(POLLERS_COUNT));
+    let pollers = (0..POLLERS_COUNT)
+        .map(|i| {
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                let instance_id = format!("test-poller-{}", i);
+                let conn = establish_db_conn();
+                let repo = event_store_repo(&conn, &instance_id, STUCK_THRESHOLD_SEC);
+
+                barrier.wait();
+
+                let mut taken_ids = Vec::new();
+                loop {
+                    let entries = repo.get_events_for_processing(2).unwrap();
+                    if entries.is_empty() {
+                        break;
+                    }
+
+                    for entry in entries {
+                        assert_eq!(Some(instance_id.clone()), entry.locked_by);
+                        repo.complete_event(entry.id).unwrap();
+                        taken_ids.push(entry.id.inner());
+                    }
+                }
+                taken_ids
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let taken_ids = pollers.into_iter().flat_map(|poller| poller.join().unwrap()).collect::<Vec<_>>();
+    let unique_taken_ids = taken_ids.iter().cloned().collect::<HashSet<_>>();
+
+    assert_eq!(
+        unique_taken_ids.len(),
+        taken_ids.len(),
+        "an event was taken by more than one poller"
+    );
+    assert!(created_ids.is_subset(&unique_taken_ids));
+}
+
+fn only_lease_holder_finishes_event() {
+    let conn = establish_db_conn();
+    let holder = event_store_repo(&conn, "test-holder", STUCK_THRESHOLD_SEC);
+    let other = event_store_repo(&conn, "test-other", STUCK_THRESHOLD_SEC);
+
+    let created = holder.add_event(Event::new(EventPayload::NoOp)).unwrap();
+    let taken = take_event(&holder, created.id);
+    assert_eq!(Some("test-holder".to_string()), taken.locked_by);
+    assert!(taken.lease_expires_at.is_some());
+
+    assert!(other.complete_event(created.id).is_err());
+    assert!(other.fail_event(created.id).is_err());
+
+    let completed = holder.complete_event(created.id).unwrap();
+    assert_eq!(None, completed.locked_by);
+    assert_eq!(None, completed.lease_expires_at);
+}
+
+fn expired_lease_is_taken_over() {
+    let conn = establish_db_conn();
+    let crashed = event_store_repo(&conn, "test-crashed", 0);
+    let survivor = event_store_repo(&conn, "test-survivor", STUCK_THRESHOLD_SEC);
+
+    let created = crashed.add_event(Event::new(EventPayload::NoOp)).unwrap();
+    take_event(&crashed, created.id);
+
+    thread::sleep(Duration::from_millis(100));
+
+    let reset_ids = survivor
+        .reset_stuck_events()
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.id)
+        .collect::<Vec<_>>();
+    assert!(reset_ids.contains(&created.id));
+
+    let taken = take_event(&survivor, created.id);
+    assert_eq!(Some("test-survivor".to_string()), taken.locked_by);
+
+    assert!(crashed.complete_event(created.id).is_err());
+    survivor.complete_event(created.id).unwrap();
+}
+
+// Scenarios share the event queue, so they run sequentially within a single test
+#[test]
+fn event_store_repo_leases() {
+    concurrent_pollers_take_distinct_events();
+    only_lease_holder_finishes_event();
+    expired_lease_is_taken_over();
+}
@@ -0,0 +1,156 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bigdecimal::BigDecimal;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use futures::Stream;
+use futures_cpupool::CpuPool;
+use r2d2_diesel::ConnectionManager;
+use stq_cache::cache::NullCache;
+use stq_http::client::{Client, ClientHandle};
+use stq_types::{BillingRole, RoleId, UserId as StqUserId};
+use tokio_core::reactor::Core;
+
+use billing_lib::client::payments::mock::{MockOperation, MockPaymentsClient};
+use billing_lib::config::Config;
+use billing_lib::controller::context::{DynamicContext, StaticContext};
+use billing_lib::models::invoice_v2::InvoiceId;
+use billing_lib::models::order_v2::{OrderId, StoreId};
+use billing_lib::models::{CreateInvoiceV2, CreateOrderV2, Currency, NewUserRole, TureCurrency, UserId};
+use billing_lib::repos::{
+    legacy_acl::SystemACL, FeatureFlagsCache, FieldCipher, InvoicesV2Repo, InvoicesV2RepoImpl, ReposFactoryImpl, RolesCacheImpl,
+};
+use billing_lib::schema::roles;
+use billing_lib::services::accounts::{AccountPoolSizing, AccountService, AccountServiceImpl};
+use billing_lib::services::invoice::InvoiceService;
+use billing_lib::services::Service;
+
+const SUPERUSER_ID: i32 = 1_000_001;
+
+type TestService = Service<
+    PgConnection,
+    ConnectionManager<PgConnection>,
+    ReposFactoryImpl<NullCache>,
+    ClientHandle,
+    MockPaymentsClient,
+    Arc<dyn AccountService + Send + Sync>,
+>;
+
+/// Creates a service acting as a superuser, with the Payments gateway replaced by `payments_client`
+fn create_service(core: &Core, payments_client: MockPaymentsClient) -> TestService {
+    let config = Config::new().unwrap();
+
+    let database_url = config.server.database.parse::<String>().unwrap();
+    let db_pool = r2d2::Pool::builder()
+        .build(ConnectionManager::<PgConnection>::new(database_url))
+        .unwrap();
+    let cpu_pool = CpuPool::new(1);
+
+    let conn = db_pool.get().unwrap();
+    diesel::insert_into(roles::table)
+        .values(&NewUserRole {
+            id: RoleId::new(),
+            user_id: StqUserId(SUPERUSER_ID),
+            name: BillingRole::Superuser,
+            data: None,
+        })
+        .on_conflict_do_nothing()
+        .execute(&*conn)
+        .unwrap();
+
+    let handle = core.handle();
+    let client = Client::new(&config.to_http_config(), &handle);
+    let client_handle = client.handle();
+    handle.spawn(client.stream().for_each(|_| Ok(())));
+
+    let repo_factory = ReposFactoryImpl::new(
+        RolesCacheImpl::new(NullCache::new()),
+        FeatureFlagsCache::new(Duration::from_secs(0)),
+        3,
+        60,
+        "test".to_string(),
+        FieldCipher::disabled(),
+    );
+
+    let account_service = AccountServiceImpl::new(
+        db_pool.clone(),
+        cpu_pool.clone(),
+        repo_factory.clone(),
+        AccountPoolSizing::new(1, &config.account_pool),
+        config.payment_expiry.account_quarantine(),
+        payments_client.clone(),
+        String::default(),
+        config.payments_mock.accounts.clone().into(),
+    );
+    let account_service = Arc::new(account_service) as Arc<dyn AccountService + Send + Sync>;
+
+    let static_context = StaticContext::new(db_pool, cpu_pool, client_handle.clone(), Arc::new(config), repo_factory);
+    let dynamic_context = DynamicContext::new(
+        Some(StqUserId(SUPERUSER_ID)),
+        String::default(),
+        client_handle,
+        Some(payments_client),
+        Some(account_service),
+    );
+
+    Service::new(static_context, dynamic_context)
+}
+
+fn create_invoice_payload(buyer_currency: Currency, seller_currency: Currency, total_amount: f64) -> CreateInvoiceV2 {
+    CreateInvoiceV2 {
+        orders: vec![CreateOrderV2 {
+            id: OrderId::generate(),
+            store_id: StoreId::new(1),
+            currency: seller_currency,
+            total_amount,
+            product_cashback: None,
+            line_items: Vec::new(),
+        }],
+        customer_id: UserId::new(SUPERUSER_ID),
+        currency: buyer_currency,
+        saga_id: InvoiceId::generate(),
+        metadata: Some(json!({ "order_source": "integration_test" })),
+        memo: None,
+        po_number: None,
+        callback: None,
+    }
+}
+
+#[test]
+fn create_invoice_v2_crypto_happy() {
+    let mut core = Core::new().unwrap();
+    let payments_client = MockPaymentsClient::default();
+    payments_client.set_rate(TureCurrency::Btc, TureCurrency::Stq, BigDecimal::from(100_000));
+    let service = create_service(&core, payments_client);
+
+    let payload = create_invoice_payload(Currency::Btc, Currency::Stq, 1000.0);
+    let invoice = core.run(service.create_invoice_v2(payload.clone())).unwrap();
+
+    assert_eq!(payload.saga_id, invoice.id);
+    assert_eq!(Currency::Btc, invoice.buyer_currency);
+    assert!(invoice.wallet_address.is_some());
+    assert!(!invoice.has_missing_rates);
+    assert_eq!(BigDecimal::from_str("0.01").unwrap(), invoice.total_price);
+    assert_eq!(payload.metadata, invoice.metadata);
+}
+
+#[test]
+fn create_invoice_v2_crypto_gateway_failure() {
+    let mut core = Core::new().unwrap();
+    let payments_client = MockPaymentsClient::default();
+    payments_client.fail(MockOperation::GetRate);
+    let service = create_service(&core, payments_client);
+
+    let payload = create_invoice_payload(Currency::Eth, Currency::Stq, 1000.0);
+    assert!(core.run(service.create_invoice_v2(payload.clone())).is_err());
+
+    let config = Config::new().unwrap();
+    let database_url = config.server.database.parse::<String>().unwrap();
+    let conn = PgConnection::establish(&database_url).unwrap();
+    let invoice = InvoicesV2RepoImpl::new(&conn, Box::new(SystemACL::default()))
+        .get(payload.saga_id)
+        .unwrap();
+    assert!(invoice.is_none());
+}
@@ -1,3 +1,4 @@
+use chrono::NaiveDate;
 use diesel::pg::PgConnection;
 use diesel::Connection;
 
@@ -26,6 +27,11 @@ fn invoices_v2_repo_crud_happy() {
         buyer_currency: Currency::Stq,
         amount_captured: Amount::new(0),
         buyer_user_id: UserId::new(1),
+        metadata: Some(json!({ "campaign_id": "spring-sale", "order_source": "mobile" })),
+        memo: Some("Q1 office supplies".to_string()),
+        po_number: Some("4500012345".to_string()),
+        price_reserved: NaiveDate::from_ymd(2019, 3, 17).and_hms(10, 0, 0),
+        public_token: generate_public_token(),
     };
 
     let created_invoice = {
@@ -34,6 +40,8 @@ fn invoices_v2_repo_crud_happy() {
         with_test_db_conn(move |conn| InvoicesV2RepoImpl::new(conn, system_acl).create(new_invoice)).unwrap()
     };
     assert_eq!(new_invoice.id, created_invoice.id);
+    assert_eq!(new_invoice.metadata, created_invoice.metadata);
+    assert_eq!(new_invoice.price_reserved, created_invoice.price_reserved);
 
     let existing_invoice = {
         let new_invoice = new_invoice.clone();
@@ -42,6 +50,20 @@ fn invoices_v2_repo_crud_happy() {
     };
     assert_eq!(Some(new_invoice.id), existing_invoice.map(|a| a.id));
 
+    let invoice_by_public_token = {
+        let new_invoice = new_invoice.clone();
+        let system_acl = system_acl.clone();
+        with_test_db_conn(move |conn| InvoicesV2RepoImpl::new(conn, system_acl).get_by_public_token(&new_invoice.public_token)).unwrap()
+    };
+    assert_eq!(Some(new_invoice.id), invoice_by_public_token.map(|a| a.id));
+
+    let many_invoices = {
+        let new_invoice = new_invoice.clone();
+        let system_acl = system_acl.clone();
+        with_test_db_conn(move |conn| InvoicesV2RepoImpl::new(conn, system_acl).get_many(&[new_invoice.id])).unwrap()
+    };
+    assert_eq!(vec![new_invoice.id], many_invoices.iter().map(|a| a.id).collect::<Vec<_>>());
+
     let deleted_invoice = {
         let new_invoice = new_invoice.clone();
         let system_acl = system_acl.clone();
@@ -0,0 +1,101 @@
+use chrono::NaiveDate;
+use diesel::pg::PgConnection;
+use diesel::Connection;
+
+use billing_lib::models::authorization::{Action, Resource};
+use billing_lib::models::{invoice_v2::*, Amount, Currency, UserId};
+use billing_lib::repos::{legacy_acl::SystemACL, InvoicesV2Repo, InvoicesV2RepoImpl};
+use billing_lib::test_support::{assert_forbidden, RecordingAcl};
+use stq_static_resources::OrderState;
+
+fn with_test_db_conn<F, T>(f: F) -> T
+where
+    F: FnOnce(&PgConnection) -> T,
+{
+    let config = billing_lib::config::Config::new().unwrap();
+    let database_url = config.server.database.parse::<String>().unwrap();
+    let db_conn = PgConnection::establish(&database_url).unwrap();
+
+    f(&db_conn)
+}
+
+#[test]
+fn invoices_v2_repo_checks_acl_in_every_method() {
+    let buyer_user_id = UserId::new(1);
+    let new_invoice = NewInvoice {
+        id: InvoiceId::generate(),
+        account_id: None,
+        buyer_currency: Currency::Stq,
+        amount_captured: Amount::new(0),
+        buyer_user_id,
+        metadata: None,
+        memo: None,
+        po_number: None,
+        price_reserved: NaiveDate::from_ymd(2019, 4, 3).and_hms(10, 0, 0),
+        public_token: generate_public_token(),
+    };
+    let public_token = new_invoice.public_token.clone();
+    let invoice_id = new_invoice.id;
+    let amount_paid = InvoiceSetAmountPaid {
+        final_amount_paid: Amount::new(0),
+        final_cashback_amount: Amount::new(0),
+        paid_at: NaiveDate::from_ymd(2019, 4, 3).and_hms(11, 0, 0),
+    };
+
+    with_test_db_conn(move |conn| {
+        InvoicesV2RepoImpl::new(conn, Box::new(SystemACL::default()))
+            .create(new_invoice.clone())
+            .unwrap();
+
+        let acl = RecordingAcl::denying();
+        let repo = InvoicesV2RepoImpl::new(conn, Box::new(acl.clone()));
+
+        assert_forbidden(&acl, Resource::Invoice, Action::Read, repo.get(invoice_id));
+        assert_forbidden(&acl, Resource::Invoice, Action::Read, repo.get_many(&[invoice_id]));
+        assert_forbidden(&acl, Resource::Invoice, Action::Read, repo.get_by_public_token(&public_token));
+        assert_forbidden(
+            &acl,
+            Resource::Invoice,
+            Action::Read,
+            repo.get_unpaid_by_buyer_user_id(buyer_user_id),
+        );
+        assert_forbidden(&acl, Resource::Invoice, Action::Read, repo.list_amounts_received(invoice_id));
+        assert_forbidden(&acl, Resource::Invoice, Action::Write, repo.create(new_invoice));
+        assert_forbidden(
+            &acl,
+            Resource::Invoice,
+            Action::Write,
+            repo.set_amount_paid(invoice_id, amount_paid.clone()),
+        );
+        assert_forbidden(
+            &acl,
+            Resource::Invoice,
+            Action::Write,
+            repo.set_amount_paid_fiat(invoice_id, amount_paid),
+        );
+        assert_forbidden(
+            &acl,
+            Resource::Invoice,
+            Action::Write,
+            repo.update_details(
+                invoice_id,
+                UpdateInvoiceDetails {
+                    memo: Some(Some("Q2 office supplies".to_string())),
+                    po_number: None,
+                },
+            ),
+        );
+        assert_forbidden(&acl, Resource::Invoice, Action::Write, repo.unlink_account(invoice_id));
+        assert_forbidden(
+            &acl,
+            Resource::Invoice,
+            Action::Write,
+            repo.set_status(invoice_id, OrderState::AmountExpired),
+        );
+        assert_forbidden(&acl, Resource::Invoice, Action::Write, repo.delete(invoice_id));
+
+        InvoicesV2RepoImpl::new(conn, Box::new(SystemACL::default()))
+            .delete(invoice_id)
+            .unwrap();
+    });
+}
@@ -1,13 +1,29 @@
+extern crate bigdecimal;
 extern crate billing_lib;
+extern crate chrono;
 extern crate diesel;
 extern crate failure;
 extern crate futures;
+extern crate futures_cpupool;
 extern crate hyper;
+extern crate r2d2;
+extern crate r2d2_diesel;
+#[macro_use]
 extern crate serde_json;
+extern crate stq_cache;
 extern crate stq_http;
+extern crate stq_static_resources;
+extern crate stq_types;
 extern crate tokio_core;
 extern crate uuid;
 
 mod accounts_repo;
+mod audit_log_repo;
+mod event_store_repo;
+mod invoices_crypto;
 mod invoices_v2_repo;
+#[cfg(feature = "test-support")]
+mod invoices_v2_repo_acl;
 mod payments_client;
+#[cfg(feature = "payments-stub")]
+mod payments_stub;
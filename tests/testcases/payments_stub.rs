@@ -0,0 +1,68 @@
+use futures::{Future, Stream};
+use hyper::Method;
+use serde_json::Value;
+use stq_http::client::{Client, HttpClient};
+use tokio_core::reactor::Core;
+use uuid::Uuid;
+
+use billing_lib::client::payments::mock::MockPaymentsClient;
+use billing_lib::client::payments::stub::PaymentsStub;
+use billing_lib::config::Config;
+
+#[test]
+fn payments_stub_account_and_rate() {
+    let mut core = Core::new().unwrap();
+    let handle = core.handle();
+
+    let address = PaymentsStub::new(MockPaymentsClient::default(), 1)
+        .serve(&"127.0.0.1:0".parse().unwrap(), &handle)
+        .unwrap();
+    let url = format!("http://{}", address);
+
+    let config = Config::new().unwrap();
+    let client = Client::new(&config.to_http_config(), &handle);
+    let client_handle = client.handle();
+    handle.spawn(client.stream().for_each(|_| Ok(())));
+
+    let account_id = Uuid::new_v4();
+    let create_account = json!({
+        "id": account_id,
+        "currency": "stq",
+        "name": "Stub account",
+        "callbackUrl": "",
+        "dailyLimitType": "unlimited",
+    });
+
+    let account = core
+        .run(
+            client_handle
+                .request_json::<Value>(
+                    Method::Post,
+                    format!("{}/v1/users/1/accounts", url),
+                    Some(create_account.to_string()),
+                    None,
+                )
+                .and_then({
+                    let client_handle = client_handle.clone();
+                    let url = url.clone();
+                    move |_| client_handle.request_json::<Value>(Method::Get, format!("{}/v1/accounts/{}", url, account_id), None, None)
+                }),
+        )
+        .unwrap();
+    assert_eq!(json!(account_id), account["id"]);
+    assert_eq!(json!("stq"), account["currency"]);
+
+    let get_rate = json!({
+        "id": Uuid::new_v4(),
+        "from": "btc",
+        "to": "stq",
+        "amountCurrency": "stq",
+        "amount": "1000",
+    });
+
+    let rate = core
+        .run(client_handle.request_json::<Value>(Method::Post, format!("{}/v1/rate", url), Some(get_rate.to_string()), None))
+        .unwrap();
+    assert_eq!(json!("btc"), rate["from"]);
+    assert!(rate["rate"].as_f64().unwrap() > 0.0);
+}